@@ -0,0 +1,62 @@
+//! Keyed sequential execution
+//!
+//! Every account hashes to one of a fixed number of workers. A task runs on
+//! the workers of all the accounts it touches, and a worker runs one task at a
+//! time in the order tasks arrived, so all balance mutations of an account
+//! execute one after another. Tasks spanning several workers take them in index
+//! order, so two of them can never wait on each other.
+
+use std::collections::hash_map::DefaultHasher;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Workers an executor starts with unless configured otherwise
+pub const DEFAULT_WORKERS: usize = 64;
+
+/// Runs tasks sequentially per account, concurrently across workers
+pub struct KeyedExecutor {
+    workers: Vec<Mutex<()>>,
+}
+
+impl Default for KeyedExecutor {
+    fn default() -> Self {
+        Self::new(DEFAULT_WORKERS)
+    }
+}
+
+impl KeyedExecutor {
+    /// Create an executor with the given number of workers, at least one
+    pub fn new(workers: usize) -> Self {
+        Self {
+            workers: (0..workers.max(1)).map(|_| Mutex::new(())).collect(),
+        }
+    }
+
+    /// Number of workers accounts are spread over
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Worker an account's tasks run on
+    pub fn worker_of(&self, account_id: Uuid) -> usize {
+        let mut hasher = DefaultHasher::new();
+        account_id.hash(&mut hasher);
+        (hasher.finish() % self.workers.len() as u64) as usize
+    }
+
+    /// Run a task once every earlier task of its accounts has finished
+    pub async fn run<T>(&self, account_ids: &[Uuid], task: impl Future<Output = T>) -> T {
+        let mut workers: Vec<usize> = account_ids.iter().map(|id| self.worker_of(*id)).collect();
+        workers.sort_unstable();
+        workers.dedup();
+
+        let mut guards = Vec::with_capacity(workers.len());
+        for worker in workers {
+            guards.push(self.workers[worker].lock().await);
+        }
+        task.await
+    }
+}
@@ -1,11 +1,23 @@
 //! Account service for managing user balances and positions
 
+pub mod executor;
+pub mod position;
 pub mod service;
 pub mod repository;
 pub mod config;
+pub mod settlement;
+pub mod withdrawal;
 
 pub use service::AccountService;
+pub use position::PositionTracker;
 pub use service::RepositoryType;
-pub use repository::{AccountRepository, InMemoryAccountRepository, PostgresAccountRepository};
+pub use repository::{
+    sort_for_locking, AccountFilter, AccountPage, AccountRepository, InMemoryAccountRepository, PostgresAccountRepository,
+};
 pub use config::AccountServiceConfig;
+pub use settlement::{
+    BankFileSettlementAdapter, CryptoNodeSettlementAdapter, DepositConfirmation, MockSettlementAdapter, Payout,
+    SettlementAdapter, SettlementConfig,
+};
+pub use withdrawal::{NoSecondFactor, SecondFactor, TotpSecondFactor};
 
@@ -0,0 +1,86 @@
+//! Position tracking
+//!
+//! An account's position in a market is the base quantity it has bought there
+//! minus what it has sold, built up from settled trades. Reduce-only orders are
+//! checked against it so they can only bring a position back towards flat.
+
+use common::decimal::Quantity;
+use common::model::account::Position;
+use common::model::order::Side;
+use common::model::trade::Trade;
+use dashmap::DashMap;
+use uuid::Uuid;
+
+/// Net positions of accounts, by account ID and market
+#[derive(Default)]
+pub struct PositionTracker {
+    positions: DashMap<(Uuid, String), Quantity>,
+}
+
+impl PositionTracker {
+    /// Create an empty tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the buyer's position up and the seller's down by a settled trade
+    pub fn apply_trade(&self, trade: &Trade) {
+        if trade.buyer_id == trade.seller_id {
+            return;
+        }
+        *self.positions.entry((trade.buyer_id, trade.market.clone())).or_default() += trade.quantity;
+        *self.positions.entry((trade.seller_id, trade.market.clone())).or_default() -= trade.quantity;
+    }
+
+    /// Undo a trade applied by [`PositionTracker::apply_trade`], when it is busted
+    pub fn reverse_trade(&self, trade: &Trade) {
+        if trade.buyer_id == trade.seller_id {
+            return;
+        }
+        *self.positions.entry((trade.buyer_id, trade.market.clone())).or_default() -= trade.quantity;
+        *self.positions.entry((trade.seller_id, trade.market.clone())).or_default() += trade.quantity;
+    }
+
+    /// An account's position in a market, zero if it never traded there
+    pub fn get(&self, account_id: Uuid, market: &str) -> Quantity {
+        self.positions
+            .get(&(account_id, market.to_string()))
+            .map(|quantity| *quantity)
+            .unwrap_or_default()
+    }
+
+    /// An account's open positions, by market
+    pub fn get_all(&self, account_id: Uuid) -> Vec<Position> {
+        let mut positions: Vec<Position> = self.positions
+            .iter()
+            .filter(|entry| entry.key().0 == account_id && !entry.value().is_zero())
+            .map(|entry| Position {
+                account_id,
+                market: entry.key().1.clone(),
+                quantity: *entry.value(),
+            })
+            .collect();
+        positions.sort_by(|a, b| a.market.cmp(&b.market));
+        positions
+    }
+
+    /// Accounts holding an open position in a market, with their positions
+    pub fn holders(&self, market: &str) -> Vec<(Uuid, Quantity)> {
+        self.positions
+            .iter()
+            .filter(|entry| entry.key().1 == market && !entry.value().is_zero())
+            .map(|entry| (entry.key().0, *entry.value()))
+            .collect()
+    }
+
+    /// Quantity an order on `side` can trade before it would grow the position
+    /// or flip it to the other side
+    pub fn reducible(&self, account_id: Uuid, market: &str, side: Side) -> Quantity {
+        let position = self.get(account_id, market);
+        match side {
+            Side::Buy => -position,
+            Side::Sell => position,
+        }
+        .max(Quantity::ZERO)
+    }
+}
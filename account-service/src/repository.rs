@@ -1,53 +1,255 @@
 //! Repository for account data
 
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
 use async_trait::async_trait;
-use chrono::Utc;
-use common::decimal::Quantity;
+use chrono::{DateTime, Utc};
+use common::decimal::{format_decimal, Quantity};
 use common::error::{Error, Result};
-use common::model::account::{Account, Balance};
+use common::model::account::{
+    Account, AccountPermissions, AccountTotals, AssetTotal, Balance, BalanceAdjustment, BalanceSnapshot, Reservation,
+};
+use common::model::asset::Asset;
+use common::model::order::OrderTransition;
+use common::model::trade::{PendingSettlement, Trade, TradeBust};
 use common::{DBTransaction, TransactionManager};
-use common::db::{PgTransactionManager, InMemoryTransactionManager};
+use common::db::{PgTransactionManager, InMemoryTransaction, InMemoryTransactionManager};
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
-use sqlx::{PgPool, postgres::PgPoolOptions, Row};
+use serde::Serialize;
+use sqlx::postgres::{PgArguments, PgPoolOptions, PgRow};
+use sqlx::query::Query;
+use sqlx::{PgPool, Postgres, types::Json, Row};
 use tracing::{debug, info};
 use uuid::Uuid;
 
+/// Accounts to list, every account when no field is set
+#[derive(Debug, Clone, Default)]
+pub struct AccountFilter {
+    /// Only closed accounts when true, only open ones when false
+    pub closed: Option<bool>,
+    /// Only accounts whose external reference starts with this
+    pub external_id_prefix: Option<String>,
+    /// Only accounts created at or after this time
+    pub created_from: Option<DateTime<Utc>>,
+    /// Only accounts created before this time
+    pub created_to: Option<DateTime<Utc>>,
+}
+
+impl AccountFilter {
+    /// Whether an account passes the filter
+    pub fn matches(&self, account: &Account) -> bool {
+        self.closed.is_none_or(|closed| account.is_closed() == closed)
+            && self.external_id_prefix.as_deref().is_none_or(|prefix| {
+                account.external_id.as_deref().is_some_and(|external_id| external_id.starts_with(prefix))
+            })
+            && self.created_from.is_none_or(|from| account.created_at >= from)
+            && self.created_to.is_none_or(|to| account.created_at < to)
+    }
+}
+
+/// A page of accounts in ID order
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountPage {
+    /// Accounts of the page
+    pub accounts: Vec<Account>,
+    /// Cursor of the next page, unset once the last page is reached
+    pub next_cursor: Option<Uuid>,
+}
+
+impl AccountPage {
+    /// Page of up to `limit` accounts, the cursor pointing past the last one when full
+    fn new(accounts: Vec<Account>, limit: usize) -> Self {
+        let next_cursor = if accounts.len() == limit { accounts.last().map(|account| account.id) } else { None };
+        Self { accounts, next_cursor }
+    }
+}
+
 /// Account repository trait defining the interface for account data storage
 #[async_trait]
 pub trait AccountRepository: Send + Sync {
+    /// Storage backend name, e.g. `memory` or `postgres`
+    fn name(&self) -> &str;
+
     /// Get the transaction manager
     fn transaction_manager(&self) -> &dyn TransactionManager;
 
     /// Create a new account
     async fn create_account(&self) -> Result<Account>;
     
-    /// Get an account by ID
+    /// Get an account by ID, including closed accounts
     async fn get_account(&self, id: Uuid) -> Result<Option<Account>>;
     
+    /// Mark an account closed at `closed_at`, keeping its records
+    async fn close_account(&self, id: Uuid, closed_at: DateTime<Utc>) -> Result<Option<Account>>;
+    
+    /// Set or clear an account's external reference, failing with a conflict
+    /// when another account has it
+    async fn set_external_id(&self, id: Uuid, external_id: Option<&str>) -> Result<Option<Account>>;
+    
+    /// Find the account with an external reference
+    async fn find_by_external_id(&self, external_id: &str) -> Result<Option<Account>>;
+    
+    /// List up to `limit` accounts matching `filter` in ID order, starting
+    /// after the account `cursor` when set
+    async fn list_accounts(&self, filter: &AccountFilter, cursor: Option<Uuid>, limit: usize) -> Result<AccountPage>;
+    
     /// Get a balance
     async fn get_balance(&self, account_id: Uuid, asset: &str) -> Result<Option<Balance>>;
     
     /// Get all balances for an account
     async fn get_balances(&self, account_id: Uuid) -> Result<Vec<Balance>>;
     
+    /// Count the accounts and sum every account's balances by asset, in asset order
+    async fn account_totals(&self) -> Result<AccountTotals>;
+    
     /// Create or update a balance
     async fn update_balance(&self, balance: Balance) -> Result<Balance>;
     
+    /// Create or update a balance as part of `transaction`, when it commits
+    async fn update_balance_in(&self, transaction: &mut DBTransaction, balance: Balance) -> Result<Balance>;
+    
+    /// Create or update several balances as part of `transaction`, in lock order
+    ///
+    /// Transactions writing more than one balance must use this rather than
+    /// writing them one by one, see [`sort_for_locking`]. Each balance may be
+    /// given once: callers net their changes to it first, as a later write of
+    /// the same row would silently replace an earlier one.
+    async fn update_balances_in(&self, transaction: &mut DBTransaction, mut balances: Vec<Balance>) -> Result<Vec<Balance>> {
+        sort_for_locking(&mut balances);
+        if let Some(pair) = balances.windows(2).find(|pair| (pair[0].account_id, &pair[0].asset) == (pair[1].account_id, &pair[1].asset)) {
+            return Err(Error::Internal(format!(
+                "Balance {} of account {} written twice in one transaction", pair[0].asset, pair[0].account_id
+            )));
+        }
+        let mut updated = Vec::with_capacity(balances.len());
+        for balance in balances {
+            updated.push(self.update_balance_in(transaction, balance).await?);
+        }
+        Ok(updated)
+    }
+    
     /// Ensure a balance exists, creating it if necessary
     async fn ensure_balance(&self, account_id: Uuid, asset: &str) -> Result<Balance>;
     
+    /// Record every balance, or only those of `account_id`, as of `taken_at`,
+    /// returning how many were recorded
+    async fn snapshot_balances(&self, account_id: Option<Uuid>, taken_at: DateTime<Utc>) -> Result<usize>;
+    
+    /// Get an account's balance snapshots taken in `[from, to)`, of one asset
+    /// or all, oldest first
+    async fn get_balance_snapshots(
+        &self,
+        account_id: Uuid,
+        asset: Option<&str>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<BalanceSnapshot>>;
+    
+    /// Register an asset, replacing its earlier entry
+    async fn save_asset(&self, asset: &Asset) -> Result<Asset>;
+    
+    /// Get every registered asset, by code
+    async fn list_assets(&self) -> Result<Vec<Asset>>;
+    
+    /// Save a settled trade under both of its orders
+    async fn save_trade(&self, trade: &Trade) -> Result<()>;
+    
+    /// Save a settled trade as part of `transaction`, when it commits
+    async fn save_trade_in(&self, transaction: &mut DBTransaction, trade: &Trade) -> Result<()>;
+    
+    /// Get the settled trades that filled an order, oldest first
+    async fn get_order_trades(&self, order_id: Uuid) -> Result<Vec<Trade>>;
+    
+    /// Get a settled trade
+    async fn get_trade(&self, trade_id: Uuid) -> Result<Option<Trade>>;
+    
+    /// Save a trade's bust as part of `transaction`, when it commits; a trade can only be busted once
+    async fn save_trade_bust_in(&self, transaction: &mut DBTransaction, bust: &TradeBust) -> Result<()>;
+    
+    /// Get the bust of a trade, if it was busted
+    async fn get_trade_bust(&self, trade_id: Uuid) -> Result<Option<TradeBust>>;
+    
+    /// Append status changes to their orders' histories, in order
+    async fn save_order_transitions(&self, transitions: &[OrderTransition]) -> Result<()>;
+    
+    /// Get an order's status changes, oldest first
+    async fn get_order_history(&self, order_id: Uuid) -> Result<Vec<OrderTransition>>;
+    
+    /// Save an operator's balance adjustment as part of `transaction`, when it commits
+    async fn save_adjustment_in(&self, transaction: &mut DBTransaction, adjustment: &BalanceAdjustment) -> Result<()>;
+    
+    /// Get an account's balance adjustments posted in `[from, to)`, oldest first
+    async fn get_adjustments(&self, account_id: Uuid, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<BalanceAdjustment>>;
+    
+    /// Save an order's reservation within a transaction, replacing an earlier one
+    async fn save_reservation_in(&self, transaction: &mut DBTransaction, reservation: &Reservation) -> Result<()>;
+    
+    /// Delete an order's reservation within a transaction
+    async fn delete_reservation_in(&self, transaction: &mut DBTransaction, order_id: Uuid) -> Result<()>;
+    
+    /// Get every stored reservation, oldest first
+    async fn list_reservations(&self) -> Result<Vec<Reservation>>;
+    
+    /// Save the trades of a placement until they are settled, replacing an earlier save
+    async fn save_pending_settlement(&self, pending: &PendingSettlement) -> Result<()>;
+    
+    /// Delete a settlement once it is done
+    async fn delete_pending_settlement(&self, id: Uuid) -> Result<()>;
+    
+    /// Get every settlement not done yet, oldest first
+    async fn list_pending_settlements(&self) -> Result<Vec<PendingSettlement>>;
+    
+    /// Save an account's permissions, replacing earlier ones
+    async fn save_permissions(&self, account_id: Uuid, permissions: &AccountPermissions) -> Result<()>;
+    
+    /// Get an account's permissions, if an operator set any
+    async fn get_permissions(&self, account_id: Uuid) -> Result<Option<AccountPermissions>>;
+    
     /// Begin a database transaction
     async fn begin_transaction(&self) -> Result<DBTransaction> {
         self.transaction_manager().begin_transaction().await
     }
 }
 
+/// Sort balances into the order their rows are locked in: by account ID, then asset
+///
+/// Writing a balance locks its row until the transaction ends. When every
+/// transaction locks the rows it writes in this one order, two transactions
+/// sharing rows always queue for them in the same direction and neither can
+/// hold a row the other is waiting for, so they never deadlock. The sort is
+/// stable, so writes of the same balance keep their order.
+pub fn sort_for_locking(balances: &mut [Balance]) {
+    balances.sort_by(|a, b| (a.account_id, &a.asset).cmp(&(b.account_id, &b.asset)));
+}
+
 /// In-memory repository for account data
 pub struct InMemoryAccountRepository {
     /// Accounts by ID
     pub accounts: DashMap<Uuid, Account>,
     /// Balances by account ID and asset
-    pub balances: DashMap<(Uuid, String), Balance>,
+    pub balances: Arc<DashMap<(Uuid, String), Balance>>,
+    /// Settled trades by order ID, oldest first
+    pub order_trades: Arc<DashMap<Uuid, Vec<Trade>>>,
+    /// Account IDs by external reference
+    pub external_ids: DashMap<String, Uuid>,
+    /// Balance snapshots by account ID and asset, oldest first
+    pub balance_snapshots: DashMap<(Uuid, String), Vec<BalanceSnapshot>>,
+    /// Registered assets by code
+    pub assets: DashMap<String, Asset>,
+    /// Trade busts by trade ID
+    pub trade_busts: Arc<DashMap<Uuid, TradeBust>>,
+    /// Balance adjustments by account ID, oldest first
+    pub adjustments: Arc<DashMap<Uuid, Vec<BalanceAdjustment>>>,
+    /// Permissions set by operators, by account ID
+    pub permissions: DashMap<Uuid, AccountPermissions>,
+    /// Status changes by order ID, oldest first
+    pub order_history: DashMap<Uuid, Vec<OrderTransition>>,
+    /// Funds locked for open orders, by order ID
+    pub reservations: Arc<DashMap<Uuid, Reservation>>,
+    /// Settlements not done yet, by ID
+    pub pending_settlements: DashMap<Uuid, PendingSettlement>,
     /// Transaction manager
     transaction_manager: InMemoryTransactionManager,
 }
@@ -57,7 +259,17 @@ impl InMemoryAccountRepository {
     pub fn new() -> Self {
         Self {
             accounts: DashMap::new(),
-            balances: DashMap::new(),
+            balances: Arc::new(DashMap::new()),
+            order_trades: Arc::new(DashMap::new()),
+            external_ids: DashMap::new(),
+            balance_snapshots: DashMap::new(),
+            assets: DashMap::new(),
+            trade_busts: Arc::new(DashMap::new()),
+            adjustments: Arc::new(DashMap::new()),
+            permissions: DashMap::new(),
+            order_history: DashMap::new(),
+            reservations: Arc::new(DashMap::new()),
+            pending_settlements: DashMap::new(),
             transaction_manager: InMemoryTransactionManager::new(),
         }
     }
@@ -65,6 +277,10 @@ impl InMemoryAccountRepository {
 
 #[async_trait]
 impl AccountRepository for InMemoryAccountRepository {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
     fn transaction_manager(&self) -> &dyn TransactionManager {
         &self.transaction_manager
     }
@@ -74,8 +290,10 @@ impl AccountRepository for InMemoryAccountRepository {
         let now = Utc::now();
         let account = Account {
             id: Uuid::new_v4(),
+            external_id: None,
             created_at: now,
             updated_at: now,
+            closed_at: None,
         };
         
         self.accounts.insert(account.id, account.clone());
@@ -87,6 +305,59 @@ impl AccountRepository for InMemoryAccountRepository {
         Ok(self.accounts.get(&id).map(|a| a.clone()))
     }
     
+    /// Mark an account closed
+    async fn close_account(&self, id: Uuid, closed_at: DateTime<Utc>) -> Result<Option<Account>> {
+        Ok(self.accounts.get_mut(&id).map(|mut account| {
+            account.closed_at = Some(closed_at);
+            account.updated_at = closed_at;
+            account.clone()
+        }))
+    }
+    
+    /// Set or clear an account's external reference
+    async fn set_external_id(&self, id: Uuid, external_id: Option<&str>) -> Result<Option<Account>> {
+        let Some(mut account) = self.accounts.get_mut(&id) else {
+            return Ok(None);
+        };
+        if let Some(external_id) = external_id {
+            match self.external_ids.entry(external_id.to_string()) {
+                Entry::Occupied(entry) if *entry.get() != id => {
+                    return Err(Error::Conflict(format!("External ID {} belongs to another account", external_id)));
+                }
+                Entry::Occupied(_) => {}
+                Entry::Vacant(entry) => {
+                    entry.insert(id);
+                }
+            }
+        }
+        if let Some(previous) = account.external_id.take().filter(|previous| Some(previous.as_str()) != external_id) {
+            self.external_ids.remove(&previous);
+        }
+        account.external_id = external_id.map(str::to_string);
+        account.updated_at = Utc::now();
+        Ok(Some(account.clone()))
+    }
+    
+    /// Find the account with an external reference
+    async fn find_by_external_id(&self, external_id: &str) -> Result<Option<Account>> {
+        let Some(id) = self.external_ids.get(external_id).map(|id| *id) else {
+            return Ok(None);
+        };
+        Ok(self.accounts.get(&id).map(|account| account.clone()))
+    }
+    
+    /// List accounts matching a filter in ID order
+    async fn list_accounts(&self, filter: &AccountFilter, cursor: Option<Uuid>, limit: usize) -> Result<AccountPage> {
+        let mut accounts: Vec<Account> = self.accounts
+            .iter()
+            .filter(|account| cursor.is_none_or(|cursor| account.id > cursor) && filter.matches(account))
+            .map(|account| account.clone())
+            .collect();
+        accounts.sort_by_key(|account| account.id);
+        accounts.truncate(limit);
+        Ok(AccountPage::new(accounts, limit))
+    }
+    
     /// Get a balance
     async fn get_balance(&self, account_id: Uuid, asset: &str) -> Result<Option<Balance>> {
         Ok(self.balances.get(&(account_id, asset.to_string())).map(|b| b.clone()))
@@ -109,6 +380,27 @@ impl AccountRepository for InMemoryAccountRepository {
         Ok(balances)
     }
     
+    /// Count the accounts and sum every account's balances by asset
+    async fn account_totals(&self) -> Result<AccountTotals> {
+        let mut balances: BTreeMap<String, AssetTotal> = BTreeMap::new();
+        for entry in self.balances.iter() {
+            let balance = entry.value();
+            let asset = balances.entry(balance.asset.clone())
+                .or_insert_with(|| AssetTotal { asset: balance.asset.clone(), ..AssetTotal::default() });
+            if !balance.total.is_zero() {
+                asset.holders += 1;
+            }
+            asset.total += balance.total;
+            asset.available += balance.available;
+            asset.locked += balance.locked;
+        }
+        Ok(AccountTotals {
+            accounts: self.accounts.len(),
+            closed: self.accounts.iter().filter(|account| account.is_closed()).count(),
+            balances: balances.into_values().collect(),
+        })
+    }
+    
     /// Create or update a balance
     async fn update_balance(&self, balance: Balance) -> Result<Balance> {
         let key = (balance.account_id, balance.asset.clone());
@@ -116,6 +408,16 @@ impl AccountRepository for InMemoryAccountRepository {
         Ok(balance)
     }
     
+    /// Stage a balance update in a transaction
+    async fn update_balance_in(&self, transaction: &mut DBTransaction, balance: Balance) -> Result<Balance> {
+        let balances = self.balances.clone();
+        let staged = balance.clone();
+        in_memory(transaction)?.stage(move || {
+            balances.insert((staged.account_id, staged.asset.clone()), staged);
+        });
+        Ok(balance)
+    }
+    
     /// Ensure a balance exists, creating it if necessary
     async fn ensure_balance(&self, account_id: Uuid, asset: &str) -> Result<Balance> {
         let key = (account_id, asset.to_string());
@@ -133,8 +435,220 @@ impl AccountRepository for InMemoryAccountRepository {
             Ok(balance)
         }
     }
+    
+    /// Record balances as of a time
+    async fn snapshot_balances(&self, account_id: Option<Uuid>, taken_at: DateTime<Utc>) -> Result<usize> {
+        let balances: Vec<Balance> = self.balances
+            .iter()
+            .filter(|entry| account_id.is_none_or(|account_id| entry.key().0 == account_id))
+            .map(|entry| entry.value().clone())
+            .collect();
+        
+        let mut recorded = 0;
+        for balance in &balances {
+            let mut snapshots = self.balance_snapshots.entry((balance.account_id, balance.asset.clone())).or_default();
+            if !snapshots.iter().any(|snapshot| snapshot.taken_at == taken_at) {
+                snapshots.push(BalanceSnapshot::of(balance, taken_at));
+                snapshots.sort_by_key(|snapshot| snapshot.taken_at);
+                recorded += 1;
+            }
+        }
+        Ok(recorded)
+    }
+    
+    /// Get an account's balance snapshots in a time range
+    async fn get_balance_snapshots(
+        &self,
+        account_id: Uuid,
+        asset: Option<&str>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<BalanceSnapshot>> {
+        let mut snapshots: Vec<BalanceSnapshot> = self.balance_snapshots
+            .iter()
+            .filter(|entry| entry.key().0 == account_id && asset.is_none_or(|asset| entry.key().1 == asset))
+            .flat_map(|entry| {
+                entry.value().iter()
+                    .filter(|snapshot| snapshot.taken_at >= from && snapshot.taken_at < to)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        snapshots.sort_by(|a, b| a.taken_at.cmp(&b.taken_at).then_with(|| a.asset.cmp(&b.asset)));
+        Ok(snapshots)
+    }
+    
+    /// Register an asset, replacing its earlier entry
+    async fn save_asset(&self, asset: &Asset) -> Result<Asset> {
+        self.assets.insert(asset.code.clone(), asset.clone());
+        Ok(asset.clone())
+    }
+    
+    /// Get every registered asset, by code
+    async fn list_assets(&self) -> Result<Vec<Asset>> {
+        let mut assets: Vec<Asset> = self.assets.iter().map(|entry| entry.value().clone()).collect();
+        assets.sort_by(|a, b| a.code.cmp(&b.code));
+        Ok(assets)
+    }
+    
+    /// Save a settled trade under both of its orders
+    async fn save_trade(&self, trade: &Trade) -> Result<()> {
+        save_order_trades(&self.order_trades, trade);
+        Ok(())
+    }
+    
+    /// Stage saving a settled trade in a transaction
+    async fn save_trade_in(&self, transaction: &mut DBTransaction, trade: &Trade) -> Result<()> {
+        let order_trades = self.order_trades.clone();
+        let trade = trade.clone();
+        in_memory(transaction)?.stage(move || save_order_trades(&order_trades, &trade));
+        Ok(())
+    }
+    
+    /// Get the settled trades that filled an order, oldest first
+    async fn get_order_trades(&self, order_id: Uuid) -> Result<Vec<Trade>> {
+        Ok(self.order_trades.get(&order_id).map(|trades| trades.clone()).unwrap_or_default())
+    }
+    
+    /// Get a settled trade
+    async fn get_trade(&self, trade_id: Uuid) -> Result<Option<Trade>> {
+        Ok(self.order_trades.iter().find_map(|entry| entry.value().iter().find(|trade| trade.id == trade_id).cloned()))
+    }
+    
+    /// Stage saving a trade's bust in a transaction
+    async fn save_trade_bust_in(&self, transaction: &mut DBTransaction, bust: &TradeBust) -> Result<()> {
+        if self.trade_busts.contains_key(&bust.trade_id) {
+            return Err(Error::Conflict(format!("Trade {} is already busted", bust.trade_id)));
+        }
+        let trade_busts = self.trade_busts.clone();
+        let bust = bust.clone();
+        in_memory(transaction)?.stage(move || {
+            trade_busts.insert(bust.trade_id, bust);
+        });
+        Ok(())
+    }
+    
+    /// Get the bust of a trade, if it was busted
+    async fn get_trade_bust(&self, trade_id: Uuid) -> Result<Option<TradeBust>> {
+        Ok(self.trade_busts.get(&trade_id).map(|bust| bust.clone()))
+    }
+    
+    /// Append status changes to their orders' histories, in order
+    async fn save_order_transitions(&self, transitions: &[OrderTransition]) -> Result<()> {
+        for transition in transitions {
+            self.order_history.entry(transition.order_id).or_default().push(transition.clone());
+        }
+        Ok(())
+    }
+    
+    /// Get an order's status changes, oldest first
+    async fn get_order_history(&self, order_id: Uuid) -> Result<Vec<OrderTransition>> {
+        Ok(self.order_history.get(&order_id).map(|history| history.clone()).unwrap_or_default())
+    }
+    
+    /// Stage saving a balance adjustment in a transaction
+    async fn save_adjustment_in(&self, transaction: &mut DBTransaction, adjustment: &BalanceAdjustment) -> Result<()> {
+        let adjustments = self.adjustments.clone();
+        let adjustment = adjustment.clone();
+        in_memory(transaction)?.stage(move || {
+            adjustments.entry(adjustment.account_id).or_default().push(adjustment);
+        });
+        Ok(())
+    }
+    
+    /// Get an account's balance adjustments posted in `[from, to)`, oldest first
+    async fn get_adjustments(&self, account_id: Uuid, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<BalanceAdjustment>> {
+        Ok(self.adjustments
+            .get(&account_id)
+            .map(|adjustments| {
+                adjustments.iter()
+                    .filter(|adjustment| adjustment.created_at >= from && adjustment.created_at < to)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+    
+    /// Stage saving an order's reservation in a transaction
+    async fn save_reservation_in(&self, transaction: &mut DBTransaction, reservation: &Reservation) -> Result<()> {
+        let reservations = self.reservations.clone();
+        let reservation = reservation.clone();
+        in_memory(transaction)?.stage(move || {
+            reservations.insert(reservation.order_id, reservation);
+        });
+        Ok(())
+    }
+    
+    /// Stage deleting an order's reservation in a transaction
+    async fn delete_reservation_in(&self, transaction: &mut DBTransaction, order_id: Uuid) -> Result<()> {
+        let reservations = self.reservations.clone();
+        in_memory(transaction)?.stage(move || {
+            reservations.remove(&order_id);
+        });
+        Ok(())
+    }
+    
+    /// Get every stored reservation, oldest first
+    async fn list_reservations(&self) -> Result<Vec<Reservation>> {
+        let mut reservations: Vec<Reservation> = self.reservations.iter().map(|entry| entry.value().clone()).collect();
+        reservations.sort_by_key(|reservation| reservation.created_at);
+        Ok(reservations)
+    }
+    
+    /// Save the trades of a placement until they are settled, replacing an earlier save
+    async fn save_pending_settlement(&self, pending: &PendingSettlement) -> Result<()> {
+        self.pending_settlements.insert(pending.id, pending.clone());
+        Ok(())
+    }
+    
+    /// Delete a settlement once it is done
+    async fn delete_pending_settlement(&self, id: Uuid) -> Result<()> {
+        self.pending_settlements.remove(&id);
+        Ok(())
+    }
+    
+    /// Get every settlement not done yet, oldest first
+    async fn list_pending_settlements(&self) -> Result<Vec<PendingSettlement>> {
+        let mut pending: Vec<PendingSettlement> = self.pending_settlements.iter().map(|entry| entry.value().clone()).collect();
+        pending.sort_by_key(|pending| pending.created_at);
+        Ok(pending)
+    }
+    
+    /// Save an account's permissions, replacing earlier ones
+    async fn save_permissions(&self, account_id: Uuid, permissions: &AccountPermissions) -> Result<()> {
+        self.permissions.insert(account_id, permissions.clone());
+        Ok(())
+    }
+    
+    /// Get an account's permissions, if an operator set any
+    async fn get_permissions(&self, account_id: Uuid) -> Result<Option<AccountPermissions>> {
+        Ok(self.permissions.get(&account_id).map(|permissions| permissions.clone()))
+    }
+}
+
+/// The in-memory transaction behind a repository transaction
+fn in_memory(transaction: &mut DBTransaction) -> Result<&mut InMemoryTransaction> {
+    match transaction {
+        DBTransaction::InMemory(transaction) => Ok(transaction),
+        DBTransaction::Postgres(_) => Err(Error::Internal(
+            "In-memory repository given a PostgreSQL transaction".to_string()
+        )),
+    }
+}
+
+/// Add a trade to both of its orders' fills, once
+fn save_order_trades(order_trades: &DashMap<Uuid, Vec<Trade>>, trade: &Trade) {
+    for order_id in [trade.buyer_order_id, trade.seller_order_id] {
+        let mut trades = order_trades.entry(order_id).or_default();
+        if !trades.iter().any(|saved| saved.id == trade.id) {
+            trades.push(trade.clone());
+        }
+    }
 }
 
+/// SQLSTATE of a unique constraint violation
+const UNIQUE_VIOLATION: &str = "23505";
+
 /// PostgreSQL repository for account data
 pub struct PostgresAccountRepository {
     /// Database connection pool
@@ -201,6 +715,10 @@ impl PostgresAccountRepository {
 
 #[async_trait]
 impl AccountRepository for PostgresAccountRepository {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
     fn transaction_manager(&self) -> &dyn TransactionManager {
         &self.transaction_manager
     }
@@ -224,8 +742,10 @@ impl AccountRepository for PostgresAccountRepository {
         // Return the new account
         let account = Account {
             id,
+            external_id: None,
             created_at: now,
             updated_at: now,
+            closed_at: None,
         };
         
         Ok(account)
@@ -237,7 +757,7 @@ impl AccountRepository for PostgresAccountRepository {
         
         // Query the account using manual query rather than sqlx::query_as macro
         let row = sqlx::query(
-            "SELECT id, created_at, updated_at FROM accounts WHERE id = $1"
+            "SELECT id, external_id, created_at, updated_at, closed_at FROM accounts WHERE id = $1"
         )
         .bind(id)
         .fetch_optional(&self.pool)
@@ -246,17 +766,87 @@ impl AccountRepository for PostgresAccountRepository {
         // Convert the row to Account if found
         match row {
             Some(row) => {
-                let account = Account {
-                    id: row.get("id"),
-                    created_at: row.get("created_at"),
-                    updated_at: row.get("updated_at"),
-                };
+                let account = account_from_row(&row);
                 Ok(Some(account))
             },
             None => Ok(None),
         }
     }
     
+    /// Mark an account closed, keeping its row and balances
+    async fn close_account(&self, id: Uuid, closed_at: DateTime<Utc>) -> Result<Option<Account>> {
+        debug!("Closing account in database: {}", id);
+        
+        let row = sqlx::query(
+            "UPDATE accounts SET closed_at = $2, updated_at = $2 WHERE id = $1
+             RETURNING id, external_id, created_at, updated_at, closed_at"
+        )
+        .bind(id)
+        .bind(closed_at)
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        Ok(row.as_ref().map(account_from_row))
+    }
+    
+    /// Set or clear an account's external reference, unique by index
+    async fn set_external_id(&self, id: Uuid, external_id: Option<&str>) -> Result<Option<Account>> {
+        debug!("Setting external ID of account {}", id);
+        
+        let row = sqlx::query(
+            "UPDATE accounts SET external_id = $2, updated_at = NOW() WHERE id = $1
+             RETURNING id, external_id, created_at, updated_at, closed_at"
+        )
+        .bind(id)
+        .bind(external_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error().and_then(|db| db.code()) {
+            Some(code) if code == UNIQUE_VIOLATION => Error::Conflict(format!(
+                "External ID {} belongs to another account", external_id.unwrap_or_default()
+            )),
+            _ => Error::Database(e),
+        })?;
+        
+        Ok(row.as_ref().map(account_from_row))
+    }
+    
+    /// Find the account with an external reference
+    async fn find_by_external_id(&self, external_id: &str) -> Result<Option<Account>> {
+        let row = sqlx::query(
+            "SELECT id, external_id, created_at, updated_at, closed_at FROM accounts WHERE external_id = $1"
+        )
+        .bind(external_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        
+        Ok(row.as_ref().map(account_from_row))
+    }
+    
+    /// List accounts matching a filter in ID order
+    async fn list_accounts(&self, filter: &AccountFilter, cursor: Option<Uuid>, limit: usize) -> Result<AccountPage> {
+        let rows = sqlx::query(
+            "SELECT id, external_id, created_at, updated_at, closed_at FROM accounts
+             WHERE ($1::uuid IS NULL OR id > $1)
+               AND ($2::boolean IS NULL OR (closed_at IS NOT NULL) = $2)
+               AND ($3::text IS NULL OR starts_with(external_id, $3))
+               AND ($4::timestamptz IS NULL OR created_at >= $4)
+               AND ($5::timestamptz IS NULL OR created_at < $5)
+             ORDER BY id
+             LIMIT $6"
+        )
+        .bind(cursor)
+        .bind(filter.closed)
+        .bind(filter.external_id_prefix.as_deref())
+        .bind(filter.created_from)
+        .bind(filter.created_to)
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(AccountPage::new(rows.iter().map(account_from_row).collect(), limit))
+    }
+    
     /// Get a balance for an account and asset
     async fn get_balance(&self, account_id: Uuid, asset: &str) -> Result<Option<Balance>> {
         debug!("Getting balance from database: {} for {}", asset, account_id);
@@ -347,27 +937,52 @@ impl AccountRepository for PostgresAccountRepository {
         Ok(balances)
     }
     
+    /// Count the accounts and sum every account's balances by asset in the database
+    async fn account_totals(&self) -> Result<AccountTotals> {
+        let counts = sqlx::query("SELECT COUNT(*) AS accounts, COUNT(closed_at) AS closed FROM accounts")
+            .fetch_one(&self.pool)
+            .await?;
+        let rows = sqlx::query(
+            "SELECT asset,
+                    COUNT(*) FILTER (WHERE total::numeric <> 0) AS holders,
+                    SUM(total::numeric)::text AS total,
+                    SUM(available::numeric)::text AS available,
+                    SUM(locked::numeric)::text AS locked
+             FROM balances
+             GROUP BY asset
+             ORDER BY asset"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        let mut balances = Vec::with_capacity(rows.len());
+        for row in rows {
+            let sum = |column: &str| row.get::<String, _>(column).parse::<Quantity>()
+                .map_err(|e| Error::Internal(format!("Invalid {} balance sum: {}", column, e)));
+            balances.push(AssetTotal {
+                asset: row.get("asset"),
+                holders: row.get::<i64, _>("holders") as usize,
+                total: sum("total")?,
+                available: sum("available")?,
+                locked: sum("locked")?,
+            });
+        }
+        
+        Ok(AccountTotals {
+            accounts: counts.get::<i64, _>("accounts") as usize,
+            closed: counts.get::<i64, _>("closed") as usize,
+            balances,
+        })
+    }
+    
     /// Update a balance
     async fn update_balance(&self, balance: Balance) -> Result<Balance> {
         debug!("Updating balance in database: {} {}", balance.asset, balance.account_id);
         
         // Try to update an existing balance
-        let result = sqlx::query(
-            "INSERT INTO balances (account_id, asset, total, available, locked) 
-             VALUES ($1, $2, $3, $4, $5)
-             ON CONFLICT (account_id, asset) 
-             DO UPDATE SET 
-                total = $3, 
-                available = $4, 
-                locked = $5"
-        )
-        .bind(balance.account_id)
-        .bind(&balance.asset)
-        .bind(balance.total.to_string())
-        .bind(balance.available.to_string())
-        .bind(balance.locked.to_string())
-        .execute(&self.pool)
-        .await?;
+        let result = upsert_balance(&balance)
+            .execute(&self.pool)
+            .await?;
         
         if result.rows_affected() == 0 {
             return Err(Error::Internal(format!("Failed to update balance for account: {}, asset: {}", 
@@ -377,6 +992,18 @@ impl AccountRepository for PostgresAccountRepository {
         Ok(balance)
     }
     
+    /// Update a balance within a transaction
+    async fn update_balance_in(&self, transaction: &mut DBTransaction, balance: Balance) -> Result<Balance> {
+        debug!("Updating balance in transaction: {} {}", balance.asset, balance.account_id);
+        
+        if transaction.execute(upsert_balance(&balance)).await? == 0 {
+            return Err(Error::Internal(format!("Failed to update balance for account: {}, asset: {}", 
+                                               balance.account_id, balance.asset)));
+        }
+        
+        Ok(balance)
+    }
+    
     /// Ensure a balance exists, creating it if necessary
     async fn ensure_balance(&self, account_id: Uuid, asset: &str) -> Result<Balance> {
         debug!("Ensuring balance exists: {} for {}", asset, account_id);
@@ -407,12 +1034,397 @@ impl AccountRepository for PostgresAccountRepository {
         )
         .bind(account_id)
         .bind(asset)
-        .bind(balance.total.to_string())
-        .bind(balance.available.to_string())
-        .bind(balance.locked.to_string())
+        .bind(format_decimal(balance.total))
+        .bind(format_decimal(balance.available))
+        .bind(format_decimal(balance.locked))
         .execute(&self.pool)
         .await?;
         
         Ok(balance)
     }
-}
\ No newline at end of file
+    
+    /// Copy balances into the snapshot table as of a time
+    async fn snapshot_balances(&self, account_id: Option<Uuid>, taken_at: DateTime<Utc>) -> Result<usize> {
+        debug!("Snapshotting balances at {}", taken_at);
+        
+        let result = sqlx::query(
+            "INSERT INTO balance_snapshots (account_id, asset, total, available, locked, taken_at)
+             SELECT account_id, asset, total, available, locked, $1 FROM balances
+             WHERE $2::uuid IS NULL OR account_id = $2
+             ON CONFLICT (account_id, asset, taken_at) DO NOTHING"
+        )
+        .bind(taken_at)
+        .bind(account_id)
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(result.rows_affected() as usize)
+    }
+    
+    /// Get an account's balance snapshots in a time range
+    async fn get_balance_snapshots(
+        &self,
+        account_id: Uuid,
+        asset: Option<&str>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<BalanceSnapshot>> {
+        let rows = sqlx::query(
+            "SELECT asset, total, available, locked, taken_at FROM balance_snapshots
+             WHERE account_id = $1 AND ($2::text IS NULL OR asset = $2) AND taken_at >= $3 AND taken_at < $4
+             ORDER BY taken_at, asset"
+        )
+        .bind(account_id)
+        .bind(asset)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        rows.iter()
+            .map(|row| {
+                let quantity = |column: &str| {
+                    row.get::<String, _>(column).parse::<Quantity>()
+                        .map_err(|e| Error::Internal(format!("Invalid {} balance format: {}", column, e)))
+                };
+                Ok(BalanceSnapshot {
+                    account_id,
+                    asset: row.get("asset"),
+                    total: quantity("total")?,
+                    available: quantity("available")?,
+                    locked: quantity("locked")?,
+                    taken_at: row.get("taken_at"),
+                })
+            })
+            .collect()
+    }
+    
+    /// Register an asset, replacing its earlier entry
+    async fn save_asset(&self, asset: &Asset) -> Result<Asset> {
+        debug!("Registering asset {}", asset.code);
+        
+        sqlx::query(
+            "INSERT INTO assets (code, name, decimals, withdrawal_min, withdrawal_fee, deposit_enabled)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT (code) DO UPDATE SET
+                name = EXCLUDED.name,
+                decimals = EXCLUDED.decimals,
+                withdrawal_min = EXCLUDED.withdrawal_min,
+                withdrawal_fee = EXCLUDED.withdrawal_fee,
+                deposit_enabled = EXCLUDED.deposit_enabled"
+        )
+        .bind(&asset.code)
+        .bind(&asset.name)
+        .bind(asset.precision as i32)
+        .bind(format_decimal(asset.withdrawal_min))
+        .bind(format_decimal(asset.withdrawal_fee))
+        .bind(asset.deposit_enabled)
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(asset.clone())
+    }
+    
+    /// Get every registered asset, by code
+    async fn list_assets(&self) -> Result<Vec<Asset>> {
+        let rows = sqlx::query(
+            "SELECT code, name, decimals, withdrawal_min, withdrawal_fee, deposit_enabled FROM assets ORDER BY code"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        
+        rows.iter()
+            .map(|row| {
+                let quantity = |column: &str| {
+                    row.get::<String, _>(column).parse::<Quantity>()
+                        .map_err(|e| Error::Internal(format!("Invalid {} format: {}", column, e)))
+                };
+                Ok(Asset {
+                    code: row.get("code"),
+                    name: row.get("name"),
+                    precision: row.get::<i32, _>("decimals") as u32,
+                    withdrawal_min: quantity("withdrawal_min")?,
+                    withdrawal_fee: quantity("withdrawal_fee")?,
+                    deposit_enabled: row.get("deposit_enabled"),
+                })
+            })
+            .collect()
+    }
+    
+    /// Save a settled trade under both of its orders
+    async fn save_trade(&self, trade: &Trade) -> Result<()> {
+        debug!("Saving trade in database: {}", trade.id);
+        
+        for order_id in [trade.buyer_order_id, trade.seller_order_id] {
+            insert_order_fill(order_id, trade)
+                .execute(&self.pool)
+                .await?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Save a settled trade within a transaction
+    async fn save_trade_in(&self, transaction: &mut DBTransaction, trade: &Trade) -> Result<()> {
+        debug!("Saving trade in transaction: {}", trade.id);
+        
+        for order_id in [trade.buyer_order_id, trade.seller_order_id] {
+            transaction.execute(insert_order_fill(order_id, trade)).await?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Get the settled trades that filled an order, oldest first
+    async fn get_order_trades(&self, order_id: Uuid) -> Result<Vec<Trade>> {
+        let rows = sqlx::query(
+            "SELECT data FROM order_fills WHERE order_id = $1 ORDER BY executed_at, trade_id"
+        )
+        .bind(order_id)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(rows.into_iter().map(|row| row.get::<Json<Trade>, _>("data").0).collect())
+    }
+    
+    /// Get a settled trade from the fills it made
+    async fn get_trade(&self, trade_id: Uuid) -> Result<Option<Trade>> {
+        let row = sqlx::query("SELECT data FROM order_fills WHERE trade_id = $1 LIMIT 1")
+            .bind(trade_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        Ok(row.map(|row| row.get::<Json<Trade>, _>("data").0))
+    }
+    
+    /// Save a trade's bust within a transaction
+    async fn save_trade_bust_in(&self, transaction: &mut DBTransaction, bust: &TradeBust) -> Result<()> {
+        debug!("Saving bust of trade {} in transaction", bust.trade_id);
+        
+        let inserted = transaction.execute(
+            sqlx::query(
+                "INSERT INTO trade_busts (trade_id, busted_at, data) VALUES ($1, $2, $3)
+                 ON CONFLICT (trade_id) DO NOTHING"
+            )
+            .bind(bust.trade_id)
+            .bind(bust.busted_at)
+            .bind(Json(bust.clone()))
+        ).await?;
+        if inserted == 0 {
+            return Err(Error::Conflict(format!("Trade {} is already busted", bust.trade_id)));
+        }
+        
+        Ok(())
+    }
+    
+    /// Get the bust of a trade, if it was busted
+    async fn get_trade_bust(&self, trade_id: Uuid) -> Result<Option<TradeBust>> {
+        let row = sqlx::query("SELECT data FROM trade_busts WHERE trade_id = $1")
+            .bind(trade_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        Ok(row.map(|row| row.get::<Json<TradeBust>, _>("data").0))
+    }
+    
+    /// Append status changes to their orders' histories, in order
+    async fn save_order_transitions(&self, transitions: &[OrderTransition]) -> Result<()> {
+        debug!("Saving {} order status changes in database", transitions.len());
+        
+        let mut transaction = self.begin_transaction().await?;
+        for transition in transitions {
+            transaction.execute(
+                sqlx::query("INSERT INTO order_history (order_id, recorded_at, data) VALUES ($1, $2, $3)")
+                    .bind(transition.order_id)
+                    .bind(transition.at)
+                    .bind(Json(transition.clone()))
+            ).await?;
+        }
+        transaction.commit().await?;
+        
+        Ok(())
+    }
+    
+    /// Get an order's status changes, oldest first
+    async fn get_order_history(&self, order_id: Uuid) -> Result<Vec<OrderTransition>> {
+        let rows = sqlx::query("SELECT data FROM order_history WHERE order_id = $1 ORDER BY id")
+            .bind(order_id)
+            .fetch_all(&self.pool)
+            .await?;
+        
+        Ok(rows.into_iter().map(|row| row.get::<Json<OrderTransition>, _>("data").0).collect())
+    }
+    
+    /// Save a balance adjustment within a transaction
+    async fn save_adjustment_in(&self, transaction: &mut DBTransaction, adjustment: &BalanceAdjustment) -> Result<()> {
+        debug!("Saving adjustment {} of account {} in transaction", adjustment.id, adjustment.account_id);
+        
+        transaction.execute(
+            sqlx::query("INSERT INTO balance_adjustments (id, account_id, created_at, data) VALUES ($1, $2, $3, $4)")
+                .bind(adjustment.id)
+                .bind(adjustment.account_id)
+                .bind(adjustment.created_at)
+                .bind(Json(adjustment.clone()))
+        ).await?;
+        
+        Ok(())
+    }
+    
+    /// Get an account's balance adjustments posted in `[from, to)`, oldest first
+    async fn get_adjustments(&self, account_id: Uuid, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<BalanceAdjustment>> {
+        let rows = sqlx::query(
+            "SELECT data FROM balance_adjustments
+             WHERE account_id = $1 AND created_at >= $2 AND created_at < $3
+             ORDER BY created_at, id"
+        )
+        .bind(account_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+        
+        Ok(rows.into_iter().map(|row| row.get::<Json<BalanceAdjustment>, _>("data").0).collect())
+    }
+    
+    /// Save an order's reservation within a transaction
+    async fn save_reservation_in(&self, transaction: &mut DBTransaction, reservation: &Reservation) -> Result<()> {
+        debug!("Saving reservation of order {} in transaction", reservation.order_id);
+        
+        transaction.execute(
+            sqlx::query(
+                "INSERT INTO reservations (order_id, account_id, created_at, data) VALUES ($1, $2, $3, $4)
+                 ON CONFLICT (order_id) DO UPDATE SET data = EXCLUDED.data"
+            )
+            .bind(reservation.order_id)
+            .bind(reservation.account_id)
+            .bind(reservation.created_at)
+            .bind(Json(reservation.clone()))
+        ).await?;
+        
+        Ok(())
+    }
+    
+    /// Delete an order's reservation within a transaction
+    async fn delete_reservation_in(&self, transaction: &mut DBTransaction, order_id: Uuid) -> Result<()> {
+        debug!("Deleting reservation of order {} in transaction", order_id);
+        
+        transaction.execute(sqlx::query("DELETE FROM reservations WHERE order_id = $1").bind(order_id)).await?;
+        
+        Ok(())
+    }
+    
+    /// Get every stored reservation, oldest first
+    async fn list_reservations(&self) -> Result<Vec<Reservation>> {
+        let rows = sqlx::query("SELECT data FROM reservations ORDER BY created_at, order_id")
+            .fetch_all(&self.pool)
+            .await?;
+        
+        Ok(rows.into_iter().map(|row| row.get::<Json<Reservation>, _>("data").0).collect())
+    }
+    
+    /// Save the trades of a placement until they are settled, replacing an earlier save
+    async fn save_pending_settlement(&self, pending: &PendingSettlement) -> Result<()> {
+        debug!("Saving pending settlement {} of order {}", pending.id, pending.order_id);
+        
+        sqlx::query(
+            "INSERT INTO pending_settlements (id, order_id, created_at, data) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (id) DO UPDATE SET data = EXCLUDED.data"
+        )
+        .bind(pending.id)
+        .bind(pending.order_id)
+        .bind(pending.created_at)
+        .bind(Json(pending.clone()))
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Delete a settlement once it is done
+    async fn delete_pending_settlement(&self, id: Uuid) -> Result<()> {
+        debug!("Deleting pending settlement {}", id);
+        
+        sqlx::query("DELETE FROM pending_settlements WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        
+        Ok(())
+    }
+    
+    /// Get every settlement not done yet, oldest first
+    async fn list_pending_settlements(&self) -> Result<Vec<PendingSettlement>> {
+        let rows = sqlx::query("SELECT data FROM pending_settlements ORDER BY created_at, id")
+            .fetch_all(&self.pool)
+            .await?;
+        
+        Ok(rows.into_iter().map(|row| row.get::<Json<PendingSettlement>, _>("data").0).collect())
+    }
+    
+    /// Save an account's permissions, replacing earlier ones
+    async fn save_permissions(&self, account_id: Uuid, permissions: &AccountPermissions) -> Result<()> {
+        debug!("Saving permissions of account {}", account_id);
+        
+        sqlx::query(
+            "INSERT INTO account_permissions (account_id, updated_at, data) VALUES ($1, NOW(), $2)
+             ON CONFLICT (account_id) DO UPDATE SET updated_at = NOW(), data = EXCLUDED.data"
+        )
+        .bind(account_id)
+        .bind(Json(permissions.clone()))
+        .execute(&self.pool)
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Get an account's permissions, if an operator set any
+    async fn get_permissions(&self, account_id: Uuid) -> Result<Option<AccountPermissions>> {
+        let row = sqlx::query("SELECT data FROM account_permissions WHERE account_id = $1")
+            .bind(account_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        
+        Ok(row.map(|row| row.get::<Json<AccountPermissions>, _>("data").0))
+    }
+}
+
+/// Query writing a balance, inserting it if new
+fn upsert_balance(balance: &Balance) -> Query<'static, Postgres, PgArguments> {
+    sqlx::query(
+        "INSERT INTO balances (account_id, asset, total, available, locked) 
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (account_id, asset) 
+         DO UPDATE SET 
+            total = $3, 
+            available = $4, 
+            locked = $5"
+    )
+    .bind(balance.account_id)
+    .bind(balance.asset.clone())
+    .bind(format_decimal(balance.total))
+    .bind(format_decimal(balance.available))
+    .bind(format_decimal(balance.locked))
+}
+
+/// Query recording a trade among an order's fills, once
+fn insert_order_fill(order_id: Uuid, trade: &Trade) -> Query<'static, Postgres, PgArguments> {
+    sqlx::query(
+        "INSERT INTO order_fills (order_id, trade_id, executed_at, data) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (order_id, trade_id) DO NOTHING"
+    )
+    .bind(order_id)
+    .bind(trade.id)
+    .bind(trade.created_at)
+    .bind(Json(trade.clone()))
+}
+
+/// Account of an `accounts` row
+fn account_from_row(row: &PgRow) -> Account {
+    Account {
+        id: row.get("id"),
+        external_id: row.get("external_id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        closed_at: row.get("closed_at"),
+    }
+}
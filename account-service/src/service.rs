@@ -1,16 +1,30 @@
 //! Account service implementation
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
 
-use common::decimal::Quantity;
+use chrono::{DateTime, Utc};
+use common::chaos::{Chaos, ChaosPoint, SharedChaos};
+use common::decimal::{format_amount, Amount, DisplayFormat, Price, Quantity};
 use common::error::{Error, Result, ErrorExt};
-use common::model::account::{Account, Balance};
-use common::model::order::{Order, Side};
-use common::model::trade::Trade;
-use tracing::{debug, info, error};
+use common::model::account::{
+    Account, AccountPermissions, AccountTotals, AdjustmentKind, Balance, BalanceAdjustment, BalanceSnapshot, FundingPayment,
+    Position, ReasonCode, Reservation, StatementEntry, StatementEntryKind, WithdrawalAddress,
+};
+use common::model::asset::Asset;
+use common::model::order::{Order, OrderTransition, Side};
+use common::model::trade::{OrderFill, PendingSettlement, Trade, TradeBust};
+use dashmap::{DashMap, DashSet};
+use rust_decimal::{Decimal, RoundingStrategy};
+use tracing::{debug, info, error, warn};
 use uuid::Uuid;
 
-use crate::repository::{AccountRepository, InMemoryAccountRepository, PostgresAccountRepository};
+use crate::executor::KeyedExecutor;
+use crate::position::PositionTracker;
+use crate::repository::{AccountFilter, AccountPage, AccountRepository, InMemoryAccountRepository, PostgresAccountRepository};
+use crate::settlement::{DepositConfirmation, Payout, SettlementAdapter};
+use crate::withdrawal::{NoSecondFactor, SecondFactor};
 
 // Not used currently but might be useful in the future
 #[allow(dead_code)]
@@ -20,6 +34,59 @@ type TransactionResult = std::result::Result<(), Error>;
 pub struct AccountService {
     /// Repository for account data
     repo: Arc<dyn AccountRepository>,
+    /// Runs each account's balance mutations one after another
+    executor: KeyedExecutor,
+    /// Recently settled trades by account, oldest first
+    account_trades: DashMap<Uuid, Vec<Trade>>,
+    /// Accounts whose withdrawals are frozen
+    frozen_withdrawals: DashSet<Uuid>,
+    /// Funds locked for each open order, by order ID
+    reservations: DashMap<Uuid, Reservation>,
+    /// Net positions built up from settled trades
+    positions: PositionTracker,
+    /// Recent funding payments by account, oldest first
+    funding_payments: DashMap<Uuid, Vec<FundingPayment>>,
+    /// Whitelisted withdrawal addresses by account
+    withdrawal_addresses: DashMap<Uuid, Vec<WithdrawalAddress>>,
+    /// Second factor confirming withdrawals and whitelist changes
+    second_factor: Arc<dyn SecondFactor>,
+    /// External custodians paying out withdrawals and confirming deposits
+    settlement_adapters: Vec<Arc<dyn SettlementAdapter>>,
+    /// Deposit references already credited, as `adapter:reference`
+    credited_deposits: DashSet<String>,
+    /// Registered assets by code, as last loaded from or saved to the repository
+    assets: DashMap<String, Asset>,
+    /// Account permissions, as last loaded from or saved to the repository
+    permissions: DashMap<Uuid, AccountPermissions>,
+    /// Faults injected into trade settlement, for failure testing
+    chaos: SharedChaos,
+}
+
+/// Number of settled trades kept per account
+const TRADE_HISTORY_LIMIT: usize = 1000;
+
+/// Number of funding payments kept per account
+const FUNDING_HISTORY_LIMIT: usize = 1000;
+
+/// Decimal places funding payments are rounded down to
+const FUNDING_SCALE: u32 = 8;
+
+/// Withdrawal addresses an account can whitelist
+const MAX_WITHDRAWAL_ADDRESSES: usize = 20;
+
+/// Longest external reference an account can carry
+const MAX_EXTERNAL_ID_LENGTH: usize = 128;
+
+/// Most accounts listed per page
+pub const MAX_ACCOUNT_PAGE: usize = 1000;
+
+/// Leverage of an order whose funds are locked in full
+const UNLEVERED: Decimal = Decimal::ONE;
+
+impl Default for AccountService {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Repository Type
@@ -33,9 +100,7 @@ pub enum RepositoryType {
 impl AccountService {
     /// Create a new account service
     pub fn new() -> Self {
-        Self {
-            repo: Arc::new(InMemoryAccountRepository::new()),
-        }
+        Self::from_repository(Arc::new(InMemoryAccountRepository::new()))
     }
     
     /// Create a new account service with a specific repository type
@@ -49,7 +114,7 @@ impl AccountService {
             }
         };
         
-        Ok(Self { repo })
+        Self::from_repository(repo).restore_reservations().await
     }
     
     /// Create a new account service with a configuration
@@ -58,7 +123,77 @@ impl AccountService {
             PostgresAccountRepository::with_config(config).await?
         );
         
-        Ok(Self { repo })
+        Self::from_repository(repo).restore_reservations().await
+    }
+    
+    fn from_repository(repo: Arc<dyn AccountRepository>) -> Self {
+        Self {
+            repo,
+            executor: KeyedExecutor::default(),
+            account_trades: DashMap::new(),
+            frozen_withdrawals: DashSet::new(),
+            reservations: DashMap::new(),
+            positions: PositionTracker::new(),
+            funding_payments: DashMap::new(),
+            withdrawal_addresses: DashMap::new(),
+            second_factor: Arc::new(NoSecondFactor),
+            settlement_adapters: Vec::new(),
+            credited_deposits: DashSet::new(),
+            assets: DashMap::new(),
+            permissions: DashMap::new(),
+            chaos: Chaos::shared(),
+        }
+    }
+    
+    /// Reload the reservations stored before a restart, so the funds they
+    /// lock can still be drawn on by fills or released on cancel
+    async fn restore_reservations(self) -> Result<Self> {
+        let reservations = self.repo.list_reservations().await?;
+        if !reservations.is_empty() {
+            info!("Restored {} reservations for open orders", reservations.len());
+        }
+        for reservation in reservations {
+            self.reservations.insert(reservation.order_id, reservation);
+        }
+        Ok(self)
+    }
+    
+    /// Confirm withdrawals and whitelist changes with `second_factor`
+    pub fn with_second_factor(mut self, second_factor: Arc<dyn SecondFactor>) -> Self {
+        self.second_factor = second_factor;
+        self
+    }
+    
+    /// Settle withdrawals and deposits of the assets `adapter` handles externally
+    ///
+    /// The first adapter handling an asset is used.
+    pub fn with_settlement_adapter(mut self, adapter: Arc<dyn SettlementAdapter>) -> Self {
+        self.settlement_adapters.push(adapter);
+        self
+    }
+    
+    /// Inject the faults of `chaos` into trade settlement and deposit credits
+    pub fn with_chaos(mut self, chaos: SharedChaos) -> Self {
+        self.chaos = chaos;
+        self
+    }
+    
+    /// Name of the storage backend for accounts and balances
+    pub fn repository_name(&self) -> &str {
+        self.repo.name()
+    }
+    
+    /// Names of the external custodians withdrawals and deposits settle through
+    pub fn settlement_adapter_names(&self) -> Vec<String> {
+        self.settlement_adapters.iter().map(|adapter| adapter.name().to_string()).collect()
+    }
+    
+    /// Spread accounts over `workers` sequential workers instead of the default 64
+    ///
+    /// More workers let more unrelated accounts settle at once.
+    pub fn with_account_workers(mut self, workers: usize) -> Self {
+        self.executor = KeyedExecutor::new(workers);
+        self
     }
     
     /// Create a new account
@@ -67,11 +202,205 @@ impl AccountService {
         self.repo.create_account().await
     }
     
+    /// Create a new account carrying a reference in an external system,
+    /// failing with a conflict when another account has it
+    pub async fn create_account_with_external_id(&self, external_id: &str) -> Result<Account> {
+        check_external_id(external_id)?;
+        if self.repo.find_by_external_id(external_id).await?.is_some() {
+            return Err(Error::Conflict(format!("External ID {} belongs to another account", external_id)));
+        }
+        let account = self.create_account().await?;
+        self.set_external_id(account.id, Some(external_id)).await
+    }
+    
     /// Get an account by ID
     pub async fn get_account(&self, id: Uuid) -> Result<Option<Account>> {
         self.repo.get_account(id).await
     }
     
+    /// Set or clear the reference of an account in an external system
+    ///
+    /// References are unique: setting one held by another account fails with
+    /// a conflict.
+    pub async fn set_external_id(&self, account_id: Uuid, external_id: Option<&str>) -> Result<Account> {
+        if let Some(external_id) = external_id {
+            check_external_id(external_id)?;
+        }
+        info!("Setting external ID of account {}", account_id);
+        self.repo.set_external_id(account_id, external_id).await?
+            .ok_or_else(|| Error::AccountNotFound(format!("Account not found: {}", account_id)))
+    }
+    
+    /// Record every account's balances, or one account's, as of `taken_at`,
+    /// returning how many balances were recorded
+    ///
+    /// Snapshotting the same balances twice at one time records them once.
+    pub async fn snapshot_balances(&self, account_id: Option<Uuid>, taken_at: DateTime<Utc>) -> Result<usize> {
+        let recorded = self.repo.snapshot_balances(account_id, taken_at).await?;
+        debug!("Recorded {} balance snapshots at {}", recorded, taken_at);
+        Ok(recorded)
+    }
+    
+    /// An account's balance snapshots in `[from, to)`, one per asset per
+    /// `interval` (the last taken in it), oldest first
+    ///
+    /// Intervals are aligned to the Unix epoch, so daily intervals are UTC days.
+    pub async fn balance_history(
+        &self,
+        account_id: Uuid,
+        asset: Option<&str>,
+        interval: chrono::Duration,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<BalanceSnapshot>> {
+        let interval = interval.num_milliseconds().max(1);
+        let mut history: Vec<BalanceSnapshot> = Vec::new();
+        for snapshot in self.repo.get_balance_snapshots(account_id, asset, from, to).await? {
+            let bucket = snapshot.taken_at.timestamp_millis().div_euclid(interval);
+            // Snapshots arrive oldest first, so a later one of the same bucket replaces the earlier
+            let previous = history.iter().rposition(|kept| {
+                kept.asset == snapshot.asset && kept.taken_at.timestamp_millis().div_euclid(interval) == bucket
+            });
+            match previous {
+                Some(i) => {
+                    history.remove(i);
+                    history.push(snapshot);
+                }
+                None => history.push(snapshot),
+            }
+        }
+        Ok(history)
+    }
+    
+    /// Load the registered assets from the repository, returning how many there are
+    pub async fn load_assets(&self) -> Result<usize> {
+        let assets = self.repo.list_assets().await?;
+        self.assets.clear();
+        for asset in &assets {
+            self.cache_asset(asset);
+        }
+        Ok(assets.len())
+    }
+    
+    /// Register an asset, replacing its earlier entry
+    pub async fn register_asset(&self, mut asset: Asset) -> Result<Asset> {
+        asset.code = asset.code.trim().to_uppercase();
+        asset.validate()?;
+        info!("Registering asset {} with {} decimal places", asset.code, asset.precision);
+        let asset = self.repo.save_asset(&asset).await?;
+        self.cache_asset(&asset);
+        Ok(asset)
+    }
+    
+    /// Keep an asset's entry, and show its amounts with its precision
+    fn cache_asset(&self, asset: &Asset) {
+        DisplayFormat::global().register_asset(&asset.code, asset.precision);
+        self.assets.insert(asset.code.clone(), asset.clone());
+    }
+    
+    /// Register the given assets unless they already are, returning how many were added
+    ///
+    /// Loads the registry first, so entries changed since are kept.
+    pub async fn seed_assets(&self, assets: Vec<Asset>) -> Result<usize> {
+        self.load_assets().await?;
+        let mut added = 0;
+        for asset in assets {
+            if !self.assets.contains_key(&asset.code.trim().to_uppercase()) {
+                self.register_asset(asset).await?;
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+    
+    /// Every registered asset, by code
+    pub fn assets(&self) -> Vec<Asset> {
+        let mut assets: Vec<Asset> = self.assets.iter().map(|entry| entry.value().clone()).collect();
+        assets.sort_by(|a, b| a.code.cmp(&b.code));
+        assets
+    }
+    
+    /// The registered entry of an asset, or the default rules if it is not registered
+    pub fn asset(&self, code: &str) -> Asset {
+        self.assets.get(&code.to_uppercase())
+            .map(|asset| asset.clone())
+            .unwrap_or_else(|| Asset::unlisted(code))
+    }
+    
+    /// Check a deposit is positive, within the asset's precision and accepted
+    pub fn check_deposit(&self, asset: &str, amount: Quantity) -> Result<()> {
+        let asset = self.asset(asset);
+        if !asset.deposit_enabled {
+            return Err(Error::ValidationError(format!("Deposits of {} are disabled", asset.code)));
+        }
+        asset.check_amount(amount)
+    }
+    
+    /// Find the account with an external reference
+    pub async fn find_by_external_id(&self, external_id: &str) -> Result<Option<Account>> {
+        self.repo.find_by_external_id(external_id).await
+    }
+    
+    /// List accounts matching `filter` in ID order, a page of at most
+    /// [`MAX_ACCOUNT_PAGE`] starting after the account `cursor` when set
+    pub async fn list_accounts(&self, filter: &AccountFilter, cursor: Option<Uuid>, limit: usize) -> Result<AccountPage> {
+        self.repo.list_accounts(filter, cursor, limit.clamp(1, MAX_ACCOUNT_PAGE)).await
+    }
+    
+    /// Count the accounts and sum their balances by asset
+    pub async fn totals(&self) -> Result<AccountTotals> {
+        self.repo.account_totals().await
+    }
+    
+    /// Get an account that exists and has not been closed
+    async fn open_account(&self, account_id: Uuid) -> Result<Account> {
+        let account = self.repo.get_account(account_id).await
+            .with_context(|| format!("Failed to retrieve account {}", account_id))?
+            .ok_or_else(|| Error::AccountNotFound(format!("Account not found: {}", account_id)))?;
+        if account.is_closed() {
+            return Err(Error::AuthorizationError(format!("Account {} is closed", account_id)));
+        }
+        Ok(account)
+    }
+    
+    /// Close an account, keeping its records
+    ///
+    /// The account must hold no funds and have none reserved for open orders,
+    /// unless `force` is set. A closed account can no longer deposit, withdraw
+    /// or reserve funds for orders.
+    pub async fn close_account(&self, account_id: Uuid, force: bool) -> Result<Account> {
+        info!("Closing account {}{}", account_id, if force { " by override" } else { "" });
+        self.executor.run(&[account_id], async {
+            let account = self.repo.get_account(account_id).await?
+                .ok_or_else(|| Error::AccountNotFound(format!("Account not found: {}", account_id)))?;
+            if account.is_closed() {
+                return Err(Error::Conflict(format!("Account {} is already closed", account_id)));
+            }
+            
+            if !force {
+                if self.reservations.iter().any(|reservation| reservation.account_id == account_id) {
+                    return Err(Error::ValidationError(format!(
+                        "Account {} has funds reserved for open orders", account_id
+                    )));
+                }
+                
+                let funded: Vec<String> = self.repo.get_balances(account_id).await?
+                    .into_iter()
+                    .filter(|balance| !balance.total.is_zero())
+                    .map(|balance| balance.asset)
+                    .collect();
+                if !funded.is_empty() {
+                    return Err(Error::ValidationError(format!(
+                        "Account {} still holds {}", account_id, funded.join(", ")
+                    )));
+                }
+            }
+            
+            self.repo.close_account(account_id, Utc::now()).await?
+                .ok_or_else(|| Error::AccountNotFound(format!("Account not found: {}", account_id)))
+        }).await
+    }
+    
     /// Get a balance
     pub async fn get_balance(&self, account_id: Uuid, asset: &str) -> Result<Option<Balance>> {
         self.repo.get_balance(account_id, asset).await
@@ -84,261 +413,1136 @@ impl AccountService {
     
     /// Deposit funds into an account
     pub async fn deposit(&self, account_id: Uuid, asset: &str, amount: Quantity) -> Result<Balance> {
-        info!("Depositing {} {} to account {}", amount, asset, account_id);
-        
-        // Ensure the account exists
-        let _account = self.repo.get_account(account_id).await
-            .with_context(|| format!("Failed to retrieve account {}", account_id))?
-            .ok_or_else(|| Error::AccountNotFound(format!("Account not found: {}", account_id)))?;
+        info!("Depositing {} {} to account {}", format_amount(asset, amount), asset, account_id);
+        self.executor.run(&[account_id], async {
+            // Ensure the account exists and is open
+            self.open_account(account_id).await?;
         
-        // Get or create balance
-        let mut balance = self.repo.ensure_balance(account_id, asset).await
-            .with_context(|| format!("Failed to ensure balance for account {}, asset {}", account_id, asset))?;
+            // Get or create balance
+            let mut balance = self.repo.ensure_balance(account_id, asset).await
+                .with_context(|| format!("Failed to ensure balance for account {}, asset {}", account_id, asset))?;
         
-        // Update balance
-        balance.deposit(amount);
+            // Update balance
+            balance.deposit(amount);
         
-        // Save and return
-        self.repo.update_balance(balance).await
-            .with_context(|| format!("Failed to update balance after deposit for account {}, asset {}", account_id, asset))
+            // Save and return
+            self.repo.update_balance(balance).await
+                .with_context(|| format!("Failed to update balance after deposit for account {}, asset {}", account_id, asset))
+        }).await
     }
     
     /// Withdraw funds from an account
     pub async fn withdraw(&self, account_id: Uuid, asset: &str, amount: Quantity) -> Result<Balance> {
-        info!("Withdrawing {} {} from account {}", amount, asset, account_id);
+        info!("Withdrawing {} {} from account {}", format_amount(asset, amount), asset, account_id);
+        if self.withdrawals_frozen(account_id) {
+            return Err(Error::AuthorizationError(format!(
+                "Withdrawals are frozen for account {}", account_id
+            )));
+        }
         
-        // Ensure the account exists
-        let _account = self.repo.get_account(account_id).await
-            .with_context(|| format!("Failed to retrieve account {}", account_id))?
-            .ok_or_else(|| Error::AccountNotFound(format!("Account not found: {}", account_id)))?;
+        self.executor.run(&[account_id], async {
+            // Ensure the account exists, is open and may withdraw
+            self.open_account(account_id).await?;
+            if !self.get_permissions(account_id).await?.can_withdraw {
+                return Err(Error::AuthorizationError(format!(
+                    "Withdrawals are not permitted for account {}", account_id
+                )));
+            }
         
-        // Get balance
-        let mut balance = self.repo.get_balance(account_id, asset).await
-            .with_context(|| format!("Failed to retrieve balance for account {}, asset {}", account_id, asset))?
-            .ok_or_else(|| Error::InsufficientBalance(format!("No balance found for {} in account {}", asset, account_id)))?;
+            // Get balance
+            let mut balance = self.repo.get_balance(account_id, asset).await
+                .with_context(|| format!("Failed to retrieve balance for account {}, asset {}", account_id, asset))?
+                .ok_or_else(|| Error::InsufficientBalance(format!("No balance found for {} in account {}", asset, account_id)))?;
         
-        // Update balance
-        balance.withdraw(amount).map_err(|e| {
-            Error::InsufficientBalance(format!("Cannot withdraw {} {}: {}", amount, asset, e))
-        })?;
+            // Update balance
+            balance.withdraw(amount).map_err(|e| {
+                Error::InsufficientBalance(format!("Cannot withdraw {} {}: {}", amount, asset, e))
+            })?;
         
-        // Save and return
-        self.repo.update_balance(balance).await
-            .with_context(|| format!("Failed to update balance after withdrawal for account {}, asset {}", account_id, asset))
+            // Save and return
+            self.repo.update_balance(balance).await
+                .with_context(|| format!("Failed to update balance after withdrawal for account {}, asset {}", account_id, asset))
+        }).await
     }
     
-    /// Reserve funds for an order
-    pub async fn reserve_for_order(&self, order: &Order) -> Result<()> {
-        // For buy orders, we need to lock quote currency
-        // For sell orders, we need to lock base currency
-        let (asset, amount) = match order.side {
-            Side::Buy => {
-                let market_parts: Vec<&str> = order.market.split('/').collect();
-                if market_parts.len() != 2 {
-                    return Err(Error::Internal(format!("Invalid market format: {}", order.market)));
+    /// Withdraw to an external address, enforcing the account's whitelist and second factor
+    ///
+    /// Once an account whitelists an address for an asset, withdrawals of that
+    /// asset must go to one of its whitelisted addresses.
+    pub async fn withdraw_to(
+        &self,
+        account_id: Uuid,
+        asset: &str,
+        amount: Quantity,
+        address: Option<&str>,
+        code: Option<&str>,
+    ) -> Result<Balance> {
+        self.authorize_withdrawal(account_id, asset, address, code)?;
+        self.settle_withdrawal(account_id, asset, amount, address).await
+    }
+    
+    /// Withdraw funds and pay them out through the asset's settlement adapter, if any
+    ///
+    /// The amount must be within the asset's precision and at least its
+    /// withdrawal minimum. The asset's withdrawal fee is kept from the amount
+    /// paid out. If the payout fails the funds are credited back and the
+    /// error returned.
+    pub async fn settle_withdrawal(&self, account_id: Uuid, asset: &str, amount: Quantity, address: Option<&str>) -> Result<Balance> {
+        let paid_out = self.asset(asset).withdrawal_payout(amount)?;
+        let balance = self.withdraw(account_id, asset, amount).await?;
+        let Some(adapter) = self.settlement_adapter(asset) else {
+            return Ok(balance);
+        };
+        
+        let payout = Payout {
+            id: Uuid::new_v4(),
+            account_id,
+            asset: asset.to_string(),
+            amount: paid_out,
+            address: address.map(str::to_string),
+            requested_at: Utc::now(),
+        };
+        match adapter.send_payout(&payout).await {
+            Ok(reference) => {
+                info!("Paid out {} {} for account {} via {} ({})", format_amount(asset, paid_out), asset, account_id, adapter.name(), reference);
+                Ok(balance)
+            }
+            Err(e) => {
+                error!("Payout {} via {} failed, returning funds to account {}: {}", payout.id, adapter.name(), account_id, e);
+                self.deposit(account_id, asset, amount).await?;
+                Err(e)
+            }
+        }
+    }
+    
+    /// Credit a deposit confirmed by a settlement adapter, once per reference
+    ///
+    /// Returns `None` if the deposit was already credited.
+    pub async fn credit_deposit(&self, adapter: &str, confirmation: &DepositConfirmation) -> Result<Option<Balance>> {
+        let key = format!("{}:{}", adapter, confirmation.reference);
+        if !self.credited_deposits.insert(key.clone()) {
+            return Ok(None);
+        }
+        
+        let credit = async {
+            self.chaos.inject(ChaosPoint::Deposit).await?;
+            self.deposit(confirmation.account_id, &confirmation.asset, confirmation.amount).await
+        };
+        match credit.await {
+            Ok(balance) => Ok(Some(balance)),
+            Err(e) => {
+                self.credited_deposits.remove(&key);
+                Err(e)
+            }
+        }
+    }
+    
+    /// Poll every settlement adapter and credit newly confirmed deposits, returning how many were credited
+    pub async fn sync_deposits(&self) -> Result<usize> {
+        let mut credited = 0;
+        for adapter in &self.settlement_adapters {
+            for confirmation in adapter.poll_deposits().await? {
+                match self.credit_deposit(adapter.name(), &confirmation).await {
+                    Ok(Some(_)) => credited += 1,
+                    Ok(None) => {}
+                    Err(e) => warn!("Failed to credit deposit {} from {}: {}", confirmation.reference, adapter.name(), e),
                 }
-                
-                let quote_asset = market_parts[1];
-                let price = order.price.ok_or_else(|| {
-                    Error::InvalidOrder("Buy limit order must have a price".to_string())
-                })?;
-                
-                (quote_asset, price * order.quantity)
-            },
-            Side::Sell => {
-                let market_parts: Vec<&str> = order.market.split('/').collect();
-                if market_parts.len() != 2 {
-                    return Err(Error::Internal(format!("Invalid market format: {}", order.market)));
+            }
+        }
+        Ok(credited)
+    }
+    
+    /// Credit confirmed deposits from the settlement adapters every `interval`
+    pub fn spawn_deposit_sync(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                if let Err(e) = self.sync_deposits().await {
+                    warn!("Failed to sync deposits: {}", e);
                 }
-                
-                let base_asset = market_parts[0];
-                (base_asset, order.quantity)
             }
-        };
+        })
+    }
+    
+    /// Whether any settlement adapter is configured
+    pub fn has_settlement_adapters(&self) -> bool {
+        !self.settlement_adapters.is_empty()
+    }
+    
+    fn settlement_adapter(&self, asset: &str) -> Option<&Arc<dyn SettlementAdapter>> {
+        self.settlement_adapters.iter().find(|adapter| adapter.handles(asset))
+    }
+    
+    /// Whether withdrawals of an asset are paid out by an external custodian
+    pub fn pays_out(&self, asset: &str) -> bool {
+        self.settlement_adapter(asset).is_some()
+    }
+    
+    /// Check a withdrawal against the account's whitelist and second factor
+    pub fn authorize_withdrawal(&self, account_id: Uuid, asset: &str, address: Option<&str>, code: Option<&str>) -> Result<()> {
+        let whitelist: Vec<String> = self.get_withdrawal_addresses(account_id)
+            .into_iter()
+            .filter(|entry| entry.asset == asset)
+            .map(|entry| entry.address)
+            .collect();
+        if !whitelist.is_empty() && !address.is_some_and(|address| whitelist.iter().any(|entry| entry == address)) {
+            return Err(Error::AuthorizationError(format!(
+                "{} withdrawals from account {} must go to a whitelisted address", asset, account_id
+            )));
+        }
         
-        debug!("Reserving {} {} for order {}", amount, asset, order.id);
+        self.confirm_second_factor(account_id, code)
+    }
+    
+    /// Check a second-factor code if the account has a second factor enrolled
+    pub fn confirm_second_factor(&self, account_id: Uuid, code: Option<&str>) -> Result<()> {
+        if !self.second_factor.is_enrolled(account_id) {
+            return Ok(());
+        }
         
-        // Get balance
-        let mut balance = self.repo.get_balance(order.user_id, asset).await?
-            .ok_or_else(|| Error::InsufficientBalance(format!("No balance found for {} in account {}", asset, order.user_id)))?;
+        match code {
+            Some(code) if self.second_factor.verify(account_id, code) => Ok(()),
+            Some(_) => Err(Error::AuthorizationError("Invalid second-factor code".to_string())),
+            None => Err(Error::AuthorizationError("Second-factor code required".to_string())),
+        }
+    }
+    
+    /// Whitelist a withdrawal address for an account
+    pub async fn add_withdrawal_address(
+        &self,
+        account_id: Uuid,
+        asset: &str,
+        address: &str,
+        label: Option<String>,
+        code: Option<&str>,
+    ) -> Result<WithdrawalAddress> {
+        let address = address.trim();
+        if asset.is_empty() || address.is_empty() || address.chars().any(char::is_whitespace) {
+            return Err(Error::ValidationError(format!("Invalid {} withdrawal address: {:?}", asset, address)));
+        }
         
-        // Lock funds
-        balance.lock(amount).map_err(|e| {
-            Error::InsufficientBalance(e)
-        })?;
+        self.repo.get_account(account_id).await?
+            .ok_or_else(|| Error::AccountNotFound(format!("Account not found: {}", account_id)))?;
+        self.confirm_second_factor(account_id, code)?;
         
-        // Save balance
-        self.repo.update_balance(balance).await?;
+        let mut addresses = self.withdrawal_addresses.entry(account_id).or_default();
+        if addresses.iter().any(|entry| entry.asset == asset && entry.address == address) {
+            return Err(Error::Conflict(format!("{} address {} is already whitelisted", asset, address)));
+        }
+        if addresses.len() >= MAX_WITHDRAWAL_ADDRESSES {
+            return Err(Error::ValidationError(format!(
+                "An account can whitelist at most {} withdrawal addresses", MAX_WITHDRAWAL_ADDRESSES
+            )));
+        }
         
-        Ok(())
+        let entry = WithdrawalAddress {
+            id: Uuid::new_v4(),
+            account_id,
+            asset: asset.to_string(),
+            address: address.to_string(),
+            label,
+            created_at: Utc::now(),
+        };
+        info!("Whitelisted {} withdrawal address {} for account {}", asset, address, account_id);
+        addresses.push(entry.clone());
+        Ok(entry)
     }
     
-    /// Release funds when an order is canceled
-    pub async fn release_reserved_funds(&self, order: &Order) -> Result<()> {
-        // Calculate remaining locked amount
-        let (asset, amount) = match order.side {
+    /// Remove a whitelisted withdrawal address, returning it if it existed
+    pub fn remove_withdrawal_address(&self, account_id: Uuid, address_id: Uuid, code: Option<&str>) -> Result<Option<WithdrawalAddress>> {
+        self.confirm_second_factor(account_id, code)?;
+        
+        let Some(mut addresses) = self.withdrawal_addresses.get_mut(&account_id) else {
+            return Ok(None);
+        };
+        let removed = addresses.iter()
+            .position(|entry| entry.id == address_id)
+            .map(|index| addresses.remove(index));
+        if removed.is_some() {
+            info!("Removed withdrawal address {} of account {}", address_id, account_id);
+        }
+        Ok(removed)
+    }
+    
+    /// Get an account's whitelisted withdrawal addresses
+    pub fn get_withdrawal_addresses(&self, account_id: Uuid) -> Vec<WithdrawalAddress> {
+        self.withdrawal_addresses
+            .get(&account_id)
+            .map(|addresses| addresses.clone())
+            .unwrap_or_default()
+    }
+    
+    /// Freeze withdrawals for an account, returning false if already frozen
+    pub fn freeze_withdrawals(&self, account_id: Uuid) -> bool {
+        info!("Freezing withdrawals for account {}", account_id);
+        self.frozen_withdrawals.insert(account_id)
+    }
+    
+    /// Unfreeze withdrawals for an account, returning false if they were not frozen
+    pub fn unfreeze_withdrawals(&self, account_id: Uuid) -> bool {
+        info!("Unfreezing withdrawals for account {}", account_id);
+        self.frozen_withdrawals.remove(&account_id).is_some()
+    }
+    
+    /// Check whether withdrawals are frozen for an account
+    pub fn withdrawals_frozen(&self, account_id: Uuid) -> bool {
+        self.frozen_withdrawals.contains(&account_id)
+    }
+    
+    /// Get an account's permissions; accounts without any set may do everything
+    pub async fn get_permissions(&self, account_id: Uuid) -> Result<AccountPermissions> {
+        if let Some(permissions) = self.permissions.get(&account_id) {
+            return Ok(permissions.clone());
+        }
+        let permissions = self.repo.get_permissions(account_id).await
+            .with_context(|| format!("Failed to retrieve permissions of account {}", account_id))?
+            .unwrap_or_default();
+        self.permissions.insert(account_id, permissions.clone());
+        Ok(permissions)
+    }
+    
+    /// Set an account's permissions, replacing earlier ones
+    ///
+    /// Allowed markets are upper-cased and deduplicated. A leverage cap must
+    /// be at least 1, since orders always use their own funds in full.
+    pub async fn set_permissions(&self, account_id: Uuid, mut permissions: AccountPermissions) -> Result<AccountPermissions> {
+        if let Some(markets) = permissions.allowed_markets.as_mut() {
+            for market in markets.iter_mut() {
+                *market = market.trim().to_uppercase();
+            }
+            if markets.iter().any(|market| market.is_empty()) {
+                return Err(Error::ValidationError("Allowed markets cannot be blank".to_string()));
+            }
+            markets.sort();
+            markets.dedup();
+        }
+        if permissions.max_leverage.is_some_and(|max_leverage| max_leverage < UNLEVERED) {
+            return Err(Error::ValidationError("Maximum leverage must be at least 1".to_string()));
+        }
+        
+        info!("Setting permissions of account {}: {:?}", account_id, permissions);
+        self.executor.run(&[account_id], async {
+            self.open_account(account_id).await?;
+            self.repo.save_permissions(account_id, &permissions).await
+                .with_context(|| format!("Failed to save permissions of account {}", account_id))?;
+            self.permissions.insert(account_id, permissions.clone());
+            Ok(permissions)
+        }).await
+    }
+    
+    /// Asset and amount placing an order would lock
+    pub fn funds_required(&self, order: &Order) -> Result<(String, Quantity)> {
+        Self::required_funds(order, order.remaining_quantity)
+    }
+    
+    /// Asset and amount an order needs locked for `quantity` of it
+    fn required_funds(order: &Order, quantity: Quantity) -> Result<(String, Quantity)> {
+        // For buy orders, we need to lock quote currency
+        // For sell orders, we need to lock base currency
+        let symbol = order.symbol()?;
+        match order.side {
             Side::Buy => {
-                let market_parts: Vec<&str> = order.market.split('/').collect();
-                if market_parts.len() != 2 {
-                    return Err(Error::Internal(format!("Invalid market format: {}", order.market)));
-                }
-                
-                let quote_asset = market_parts[1];
                 let price = order.price.ok_or_else(|| {
                     Error::InvalidOrder("Buy limit order must have a price".to_string())
                 })?;
                 
-                (quote_asset, price * order.remaining_quantity)
+                Ok((symbol.quote().as_str().to_string(), price * quantity))
             },
-            Side::Sell => {
-                let market_parts: Vec<&str> = order.market.split('/').collect();
-                if market_parts.len() != 2 {
-                    return Err(Error::Internal(format!("Invalid market format: {}", order.market)));
-                }
-                
-                let base_asset = market_parts[0];
-                (base_asset, order.remaining_quantity)
+            Side::Sell => Ok((symbol.base().as_str().to_string(), quantity)),
+        }
+    }
+    
+    /// Quantity a reduce-only order may still trade: what is left of the
+    /// position once the account's other open reduce-only orders on that side
+    /// have closed their share of it
+    fn reduce_only_allowance(&self, order: &Order) -> Quantity {
+        let reducible = self.positions.reducible(order.user_id, &order.market, order.side);
+        let pending: Quantity = self.reservations
+            .iter()
+            .filter(|reservation| {
+                reservation.reduce_only
+                    && reservation.account_id == order.user_id
+                    && reservation.market == order.market
+                    && reservation.side == order.side
+                    && reservation.order_id != order.id
+            })
+            .map(|reservation| reservation.quantity)
+            .sum();
+        (reducible - pending).max(Quantity::ZERO)
+    }
+    
+    /// Clip a reduce-only order to the quantity that closes the account's position
+    ///
+    /// Orders that are not reduce-only are left alone. A reduce-only order is
+    /// rejected when it could only grow the position.
+    pub fn clip_reduce_only(&self, order: &mut Order) -> Result<()> {
+        if !order.reduce_only {
+            return Ok(());
+        }
+        let allowance = self.reduce_only_allowance(order);
+        if allowance.is_zero() {
+            return Err(Error::InvalidOrder(format!(
+                "Reduce-only order would increase the position in {}", order.market
+            )));
+        }
+        if order.remaining_quantity > allowance {
+            debug!("Clipping reduce-only order {} from {} to {}", order.id, order.remaining_quantity, allowance);
+            order.quantity = order.filled_quantity + allowance;
+            order.remaining_quantity = allowance;
+        }
+        Ok(())
+    }
+    
+    /// Get an account's open positions, by market
+    pub fn get_positions(&self, account_id: Uuid) -> Vec<Position> {
+        self.positions.get_all(account_id)
+    }
+    
+    /// Reserve funds for an order
+    ///
+    /// Reduce-only orders are rejected if they exceed what is left of the
+    /// position, so they should be clipped with
+    /// [`clip_reduce_only`](Self::clip_reduce_only) first. Orders the
+    /// account's permissions do not allow are rejected too.
+    pub async fn reserve_for_order(&self, order: &Order) -> Result<()> {
+        let (asset, amount) = Self::required_funds(order, order.remaining_quantity)?;
+        
+        debug!("Reserving {} {} for order {}", format_amount(&asset, amount), asset, order.id);
+        self.executor.run(&[order.user_id], async {
+            self.open_account(order.user_id).await?;
+            self.get_permissions(order.user_id).await?
+                .check_order(&order.market, UNLEVERED)
+                .map_err(|reason| Error::AuthorizationError(format!("{} for account {}", reason, order.user_id)))?;
+            if self.reservations.contains_key(&order.id) {
+                return Err(Error::InvalidOrder(format!("Funds already reserved for order {}", order.id)));
+            }
+            if order.reduce_only && order.remaining_quantity > self.reduce_only_allowance(order) {
+                return Err(Error::InvalidOrder(format!(
+                    "Reduce-only order {} would increase the position in {}", order.id, order.market
+                )));
             }
-        };
         
-        debug!("Releasing {} {} for canceled order {}", amount, asset, order.id);
+            // Get balance
+            let mut balance = self.repo.get_balance(order.user_id, &asset).await?
+                .ok_or_else(|| Error::InsufficientBalance(format!("No balance found for {} in account {}", asset, order.user_id)))?;
+        
+            // Lock funds
+            balance.lock(amount).map_err(|e| {
+                Error::InsufficientBalance(e)
+            })?;
+        
+            // Save balance and reservation together
+            let reservation = Reservation {
+                order_id: order.id,
+                account_id: order.user_id,
+                market: order.market.clone(),
+                side: order.side,
+                reduce_only: order.reduce_only,
+                asset,
+                amount,
+                quantity: order.remaining_quantity,
+                created_at: Utc::now(),
+            };
+            self.save_reserved_balance(balance, &reservation, false).await?;
+            self.reservations.insert(order.id, reservation);
+        
+            Ok(())
+        }).await
+    }
+    
+    /// Resize an order's reservation to its amended price and remaining quantity
+    ///
+    /// Only the difference is locked or unlocked, so a failed amend leaves the
+    /// original reservation in place.
+    pub async fn amend_reservation(&self, order: &Order) -> Result<()> {
+        let (asset, amount) = Self::required_funds(order, order.remaining_quantity)?;
+        self.executor.run(&[order.user_id], async {
+            let mut reservation = self.reservations.get(&order.id)
+                .map(|reservation| reservation.clone())
+                .ok_or_else(|| Error::OrderNotFound(format!("No funds reserved for order {}", order.id)))?;
+            if reservation.asset != asset {
+                return Err(Error::InvalidOrder(format!("Order {} cannot change its reserved asset", order.id)));
+            }
         
-        // Get balance
-        let mut balance = self.repo.get_balance(order.user_id, asset).await?
-            .ok_or_else(|| Error::Internal(format!("No balance found for {} in account {}", asset, order.user_id)))?;
+            debug!(
+                "Amending reservation of order {} from {} to {} {}",
+                order.id, format_amount(&asset, reservation.amount), format_amount(&asset, amount), asset
+            );
+            let mut balance = self.repo.get_balance(order.user_id, &asset).await?
+                .ok_or_else(|| Error::Internal(format!("No balance found for {} in account {}", asset, order.user_id)))?;
         
-        // Unlock funds
-        balance.unlock(amount);
+            if amount > reservation.amount {
+                balance.lock(amount - reservation.amount).map_err(Error::InsufficientBalance)?;
+            } else {
+                balance.unlock(reservation.amount - amount);
+            }
         
-        // Save balance
-        self.repo.update_balance(balance).await?;
+            reservation.amount = amount;
+            reservation.quantity = order.remaining_quantity;
+            self.save_reserved_balance(balance, &reservation, false).await?;
+            self.reservations.insert(order.id, reservation);
         
+            Ok(())
+        }).await
+    }
+    
+    /// Release whatever is still reserved for an order when it is canceled
+    pub async fn release_reserved_funds(&self, order: &Order) -> Result<()> {
+        self.release_reservation(order.user_id, order.id).await?;
         Ok(())
     }
     
+    /// Release an account's reservation for an order, returning what was released
+    pub async fn release_reservation(&self, account_id: Uuid, order_id: Uuid) -> Result<Option<Reservation>> {
+        self.executor.run(&[account_id], async {
+            let Some((_, reservation)) = self.reservations.remove_if(&order_id, |_, reservation| reservation.account_id == account_id) else {
+                debug!("No funds reserved for order {} of account {}", order_id, account_id);
+                return Ok(None);
+            };
+        
+            debug!("Releasing {} {} for order {}", format_amount(&reservation.asset, reservation.amount), reservation.asset, order_id);
+        
+            // Get balance
+            let mut balance = self.repo.get_balance(account_id, &reservation.asset).await?
+                .ok_or_else(|| Error::Internal(format!("No balance found for {} in account {}", reservation.asset, account_id)))?;
+        
+            // Unlock funds
+            balance.unlock(reservation.amount);
+        
+            // Save balance and drop the reservation together
+            self.save_reserved_balance(balance, &reservation, true).await?;
+        
+            Ok(Some(reservation))
+        }).await
+    }
+    
+    /// Get an account's reservations, oldest first
+    pub fn get_reservations(&self, account_id: Uuid) -> Vec<Reservation> {
+        let mut reservations: Vec<Reservation> = self.reservations
+            .iter()
+            .filter(|reservation| reservation.account_id == account_id)
+            .map(|reservation| reservation.clone())
+            .collect();
+        reservations.sort_by_key(|reservation| reservation.created_at);
+        reservations
+    }
+    
+    /// An order's reservation after `quantity` of it fills for `amount`, and the
+    /// leftover it no longer needs once fully filled at a better price
+    fn consume_reservation(&self, order_id: Uuid, amount: Quantity, quantity: Quantity) -> Option<(Reservation, Quantity)> {
+        let mut reservation = self.reservations.get(&order_id)?.clone();
+        reservation.amount = (reservation.amount - amount).max(Quantity::ZERO);
+        reservation.quantity = (reservation.quantity - quantity).max(Quantity::ZERO);
+        
+        let leftover = if reservation.quantity.is_zero() {
+            std::mem::replace(&mut reservation.amount, Quantity::ZERO)
+        } else {
+            Quantity::ZERO
+        };
+        Some((reservation, leftover))
+    }
+    
+    /// Save a balance with the reservation it locks funds for, or with the
+    /// reservation's removal once `released`, in one transaction
+    async fn save_reserved_balance(&self, balance: Balance, reservation: &Reservation, released: bool) -> Result<()> {
+        let mut transaction = self.repo.begin_transaction().await
+            .with_context(|| format!("Failed to start transaction for reservation of order {}", reservation.order_id))?;
+        
+        let transaction_result = async {
+            self.repo.update_balance_in(&mut transaction, balance).await?;
+            if released {
+                self.repo.delete_reservation_in(&mut transaction, reservation.order_id).await
+            } else {
+                self.repo.save_reservation_in(&mut transaction, reservation).await
+            }
+        }.await;
+        
+        match transaction_result {
+            Ok(()) => transaction.commit().await
+                .with_context(|| format!("Failed to commit reservation of order {}", reservation.order_id)),
+            Err(e) => {
+                error!("Error saving reservation of order {}: {}", reservation.order_id, e);
+                if let Err(rollback_err) = transaction.rollback().await {
+                    error!("Failed to roll back transaction: {}", rollback_err);
+                }
+                Err(e)
+            }
+        }
+    }
+    
+    /// Store a consumed reservation, dropping it once its order is filled
+    fn store_reservation(&self, reservation: Reservation) {
+        if reservation.quantity.is_zero() {
+            self.reservations.remove(&reservation.order_id);
+        } else {
+            self.reservations.insert(reservation.order_id, reservation);
+        }
+    }
+    
     /// Process a trade, updating balances for both parties with database transaction
     pub async fn process_trade(&self, trade: &Trade) -> Result<()> {
         debug!("Processing trade: {}", trade.id);
+        self.chaos.inject(ChaosPoint::Settlement).await?;
         
         // Market components
-        let market_parts: Vec<&str> = trade.market.split('/').collect();
-        if market_parts.len() != 2 {
-            return Err(Error::ValidationError(format!("Invalid market format: {}", trade.market)));
-        }
-        
-        let base_asset = market_parts[0];
-        let quote_asset = market_parts[1];
+        let symbol = trade.symbol()?;
+        let base_asset = symbol.base().as_str();
+        let quote_asset = symbol.quote().as_str();
         
         // Trade amount
         let base_amount = trade.quantity;
         let quote_amount = trade.price * trade.quantity;
         
-        // Start a database transaction
-        let transaction = self.repo.begin_transaction().await
-            .with_context(|| format!("Failed to start transaction for trade {}", trade.id))?;
+        // Fees are deducted from what each side receives
+        let buyer_fee = trade.buyer_fee();
+        let seller_fee = trade.seller_fee();
+        if buyer_fee > base_amount || seller_fee > quote_amount {
+            return Err(Error::ValidationError(format!(
+                "Fees exceed proceeds for trade {}", trade.id
+            )));
+        }
         
-        // Use a closure for the transaction work to handle errors consistently
-        let transaction_result = async {
-            // Get all balances first to avoid deadlocks
-            let buyer_quote_balance_result = self.repo.get_balance(trade.buyer_id, quote_asset).await
-                .with_context(|| format!("Failed to get buyer's quote balance ({}) for trade {}", quote_asset, trade.id))?;
+        // Settle on both parties' workers
+        self.executor.run(&[trade.buyer_id, trade.seller_id], async {
+            // Draw the fill from each order's reservation, if it has one
+            let buyer_reservation = self.consume_reservation(trade.buyer_order_id, quote_amount, base_amount);
+            let seller_reservation = self.consume_reservation(trade.seller_order_id, base_amount, base_amount);
+        
+            // Start a database transaction
+            let mut transaction = self.repo.begin_transaction().await
+                .with_context(|| format!("Failed to start transaction for trade {}", trade.id))?;
+        
+            // Use a closure for the transaction work to handle errors consistently
+            let transaction_result = async {
+                // Read all balances before writing any, each once, so a
+                // self-trade nets its legs on the same balances
+                let mut balances: BTreeMap<(Uuid, &str), Balance> = BTreeMap::new();
+                for (account_id, asset, party, paying) in [
+                    (trade.buyer_id, quote_asset, "buyer", true),
+                    (trade.buyer_id, base_asset, "buyer", false),
+                    (trade.seller_id, base_asset, "seller", true),
+                    (trade.seller_id, quote_asset, "seller", false),
+                ] {
+                    if balances.contains_key(&(account_id, asset)) {
+                        continue;
+                    }
+                    let balance = self.repo.get_balance(account_id, asset).await
+                        .with_context(|| format!("Failed to get {}'s {} balance for trade {}", party, asset, trade.id))?;
+                    
+                    // The side paying must hold the asset, the side receiving may be new to it
+                    let balance = match balance {
+                        Some(balance) => balance,
+                        None if paying => return Err(Error::InsufficientBalance(
+                            format!("No {} balance found for {} {}", asset, party, account_id)
+                        )),
+                        None => self.repo.ensure_balance(account_id, asset).await
+                            .with_context(|| format!("Failed to create {} balance for {}", asset, party))?,
+                    };
+                    balances.insert((account_id, asset), balance);
+                }
                 
-            let buyer_base_balance_result = self.repo.get_balance(trade.buyer_id, base_asset).await
-                .with_context(|| format!("Failed to get buyer's base balance ({}) for trade {}", base_asset, trade.id))?;
+                // Validate locked funds
+                let buyer_locked = balances[&(trade.buyer_id, quote_asset)].locked;
+                if buyer_locked < quote_amount {
+                    return Err(Error::InsufficientBalance(format!(
+                        "Buyer has insufficient locked funds: {} < {}", buyer_locked, quote_amount
+                    )));
+                }
                 
-            let seller_base_balance_result = self.repo.get_balance(trade.seller_id, base_asset).await
-                .with_context(|| format!("Failed to get seller's base balance ({}) for trade {}", base_asset, trade.id))?;
+                let seller_locked = balances[&(trade.seller_id, base_asset)].locked;
+                if seller_locked < base_amount {
+                    return Err(Error::InsufficientBalance(format!(
+                        "Seller has insufficient locked funds: {} < {}", seller_locked, base_amount
+                    )));
+                }
                 
-            let seller_quote_balance_result = self.repo.get_balance(trade.seller_id, quote_asset).await
-                .with_context(|| format!("Failed to get seller's quote balance ({}) for trade {}", quote_asset, trade.id))?;
-            
-            // Validate and prepare balances
-            let mut buyer_quote_balance = buyer_quote_balance_result
-                .ok_or_else(|| Error::InsufficientBalance(
-                    format!("No {} balance found for buyer {}", quote_asset, trade.buyer_id)
-                ))?;
-            
-            let mut buyer_base_balance = match buyer_base_balance_result {
-                Some(balance) => balance,
-                None => self.repo.ensure_balance(trade.buyer_id, base_asset).await
-                    .with_context(|| format!("Failed to create base balance for buyer"))?,
-            };
-            
-            let mut seller_base_balance = seller_base_balance_result
-                .ok_or_else(|| Error::InsufficientBalance(
-                    format!("No {} balance found for seller {}", base_asset, trade.seller_id)
-                ))?;
+                // Update buyer balances
+                let buyer_quote_balance = balances.get_mut(&(trade.buyer_id, quote_asset)).unwrap();
+                buyer_quote_balance.locked -= quote_amount;
+                buyer_quote_balance.total -= quote_amount;
+                let buyer_base_balance = balances.get_mut(&(trade.buyer_id, base_asset)).unwrap();
+                buyer_base_balance.total += base_amount - buyer_fee;
+                buyer_base_balance.available += base_amount - buyer_fee;
+                
+                // Update seller balances
+                let seller_base_balance = balances.get_mut(&(trade.seller_id, base_asset)).unwrap();
+                seller_base_balance.locked -= base_amount;
+                seller_base_balance.total -= base_amount;
+                let seller_quote_balance = balances.get_mut(&(trade.seller_id, quote_asset)).unwrap();
+                seller_quote_balance.total += quote_amount - seller_fee;
+                seller_quote_balance.available += quote_amount - seller_fee;
+                
+                // An order filled at a better price than reserved for leaves funds it no longer needs
+                if let Some((_, leftover)) = &buyer_reservation {
+                    balances.get_mut(&(trade.buyer_id, quote_asset)).unwrap().unlock(*leftover);
+                }
+                if let Some((_, leftover)) = &seller_reservation {
+                    balances.get_mut(&(trade.seller_id, base_asset)).unwrap().unlock(*leftover);
+                }
             
-            let mut seller_quote_balance = match seller_quote_balance_result {
-                Some(balance) => balance,
-                None => self.repo.ensure_balance(trade.seller_id, quote_asset).await
-                    .with_context(|| format!("Failed to create quote balance for seller"))?,
-            };
+                // Update all balances, in lock order
+                let balances = balances.into_values().collect();
+                self.repo.update_balances_in(&mut transaction, balances).await
+                    .with_context(|| format!("Failed to update balances for trade {}", trade.id))?;
+                
+                self.repo.save_trade_in(&mut transaction, trade).await
+                    .with_context(|| format!("Failed to save trade {}", trade.id))?;
+                
+                // Store what is left of each order's reservation, dropping it once filled
+                for (reservation, _) in [&buyer_reservation, &seller_reservation].into_iter().flatten() {
+                    if reservation.quantity.is_zero() {
+                        self.repo.delete_reservation_in(&mut transaction, reservation.order_id).await
+                    } else {
+                        self.repo.save_reservation_in(&mut transaction, reservation).await
+                    }
+                    .with_context(|| format!("Failed to save reservation of order {}", reservation.order_id))?;
+                }
             
-            // Validate locked funds
-            if buyer_quote_balance.locked < quote_amount {
-                return Err(Error::InsufficientBalance(format!(
-                    "Buyer has insufficient locked funds: {} < {}", buyer_quote_balance.locked, quote_amount
-                )));
+                Ok(())
+            }.await;
+        
+            // Handle transaction result
+            match transaction_result {
+                Ok(_) => {
+                    // Commit the transaction
+                    transaction.commit().await
+                        .with_context(|| format!("Failed to commit transaction for trade {}", trade.id))?;
+                    
+                    info!("Successfully processed trade: {}", trade.id);
+                    for (reservation, _) in [buyer_reservation, seller_reservation].into_iter().flatten() {
+                        self.store_reservation(reservation);
+                    }
+                    self.positions.apply_trade(trade);
+                    self.record_trade(trade);
+                    Ok(())
+                },
+                Err(e) => {
+                    // Log the error and roll back
+                    error!("Error processing trade {}: {}", trade.id, e);
+                
+                    // Roll back the transaction
+                    if let Err(rollback_err) = transaction.rollback().await {
+                        // Log rollback failure but return the original error
+                        error!("Failed to roll back transaction: {}", rollback_err);
+                    }
+                
+                    // Return the original error
+                    Err(e)
+                }
             }
+        }).await
+    }
+    
+    /// Bust a settled trade, reversing it with a compensating entry
+    ///
+    /// The buyer gives back the base asset it received and gets the quote
+    /// asset back, the seller the other way round, and both get their fees
+    /// refunded. The trade itself is kept; the bust is saved beside it and a
+    /// trade can only be busted once. Each party must still have the asset it
+    /// gives back available.
+    pub async fn bust_trade(&self, trade_id: Uuid, reason: &str, actor: &str) -> Result<TradeBust> {
+        if reason.trim().is_empty() {
+            return Err(Error::ValidationError("A trade bust needs a reason".to_string()));
+        }
+        let trade = self.repo.get_trade(trade_id).await?
+            .ok_or_else(|| Error::OrderNotFound(format!("No settled trade {}", trade_id)))?;
+        if self.repo.get_trade_bust(trade_id).await?.is_some() {
+            return Err(Error::Conflict(format!("Trade {} is already busted", trade_id)));
+        }
+        
+        let symbol = trade.symbol()?;
+        let base_asset = symbol.base().as_str();
+        let quote_asset = symbol.quote().as_str();
+        let bust = TradeBust::new(&trade, reason.trim(), actor, Utc::now());
+        
+        self.executor.run(&[trade.buyer_id, trade.seller_id], async {
+            let mut transaction = self.repo.begin_transaction().await
+                .with_context(|| format!("Failed to start transaction for bust of trade {}", trade.id))?;
             
-            if seller_base_balance.locked < base_amount {
-                return Err(Error::InsufficientBalance(format!(
-                    "Seller has insufficient locked funds: {} < {}", seller_base_balance.locked, base_amount
-                )));
-            }
+            let transaction_result = async {
+                // Net change per balance, so a self-trade only gets its fees back
+                let quote_amount = trade.price * trade.quantity;
+                let mut changes: BTreeMap<(Uuid, &str), Amount> = BTreeMap::new();
+                *changes.entry((trade.buyer_id, base_asset)).or_default() -= trade.quantity - bust.buyer_fee_refund;
+                *changes.entry((trade.buyer_id, quote_asset)).or_default() += quote_amount;
+                *changes.entry((trade.seller_id, quote_asset)).or_default() -= quote_amount - bust.seller_fee_refund;
+                *changes.entry((trade.seller_id, base_asset)).or_default() += trade.quantity;
+                
+                let mut balances = Vec::with_capacity(changes.len());
+                for ((account_id, asset), change) in changes {
+                    let mut balance = self.repo.ensure_balance(account_id, asset).await?;
+                    if change < Amount::ZERO {
+                        balance.withdraw(-change).map_err(|e| Error::InsufficientBalance(format!(
+                            "Account {} cannot give back {} {}: {}", account_id, format_amount(asset, -change), asset, e
+                        )))?;
+                    } else {
+                        balance.deposit(change);
+                    }
+                    balances.push(balance);
+                }
+                
+                self.repo.update_balances_in(&mut transaction, balances).await
+                    .with_context(|| format!("Failed to update balances for bust of trade {}", trade.id))?;
+                self.repo.save_trade_bust_in(&mut transaction, &bust).await?;
+                Ok(())
+            }.await;
             
-            // Update buyer balances
-            buyer_quote_balance.locked -= quote_amount;
-            buyer_quote_balance.total -= quote_amount;
-            buyer_base_balance.total += base_amount;
-            buyer_base_balance.available += base_amount;
+            match transaction_result {
+                Ok(()) => {
+                    transaction.commit().await
+                        .with_context(|| format!("Failed to commit bust of trade {}", trade.id))?;
+                    
+                    info!("Busted trade {} on {} by {}: {}", trade.id, trade.market, actor, bust.reason);
+                    self.positions.reverse_trade(&trade);
+                    Ok(bust.clone())
+                },
+                Err(e) => {
+                    error!("Error busting trade {}: {}", trade.id, e);
+                    if let Err(rollback_err) = transaction.rollback().await {
+                        error!("Failed to roll back transaction: {}", rollback_err);
+                    }
+                    Err(e)
+                }
+            }
+        }).await
+    }
+    
+    /// Get a settled trade
+    pub async fn get_trade(&self, trade_id: Uuid) -> Result<Option<Trade>> {
+        self.repo.get_trade(trade_id).await
+    }
+    
+    /// Get the bust of a trade, if it was busted
+    pub async fn get_trade_bust(&self, trade_id: Uuid) -> Result<Option<TradeBust>> {
+        self.repo.get_trade_bust(trade_id).await
+    }
+    
+    /// Store a placement's trades until they are settled, or record a failed attempt
+    pub async fn save_pending_settlement(&self, pending: &PendingSettlement) -> Result<()> {
+        self.repo.save_pending_settlement(pending).await
+            .with_context(|| format!("Failed to save pending settlement of order {}", pending.order_id))
+    }
+    
+    /// Forget a settlement once its trades are settled and published
+    pub async fn complete_pending_settlement(&self, id: Uuid) -> Result<()> {
+        self.repo.delete_pending_settlement(id).await
+            .with_context(|| format!("Failed to delete pending settlement {}", id))
+    }
+    
+    /// Get every settlement not done yet, oldest first
+    pub async fn pending_settlements(&self) -> Result<Vec<PendingSettlement>> {
+        self.repo.list_pending_settlements().await
+    }
+    
+    /// Get an account's settled trades, newest first
+    pub fn get_trades(&self, account_id: Uuid, limit: usize) -> Vec<Trade> {
+        self.account_trades
+            .get(&account_id)
+            .map(|trades| trades.iter().rev().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+    
+    /// Get the fills of an order, oldest first, with the running average price
+    pub async fn get_order_fills(&self, order_id: Uuid) -> Result<Vec<OrderFill>> {
+        let trades = self.repo.get_order_trades(order_id).await?;
+        Ok(OrderFill::from_trades(order_id, &trades))
+    }
+    
+    /// Append status changes to their orders' audit trails, in order
+    pub async fn record_order_transitions(&self, transitions: &[OrderTransition]) -> Result<()> {
+        self.repo.save_order_transitions(transitions).await
+    }
+    
+    /// Get an order's status changes, oldest first
+    pub async fn get_order_history(&self, order_id: Uuid) -> Result<Vec<OrderTransition>> {
+        self.repo.get_order_history(order_id).await
+    }
+    
+    /// Settle one funding period of a perpetual market between its position holders
+    ///
+    /// Each holder owes its position valued at `mark_price` times `rate`, so
+    /// with a positive rate longs pay and shorts receive. Payers pay out of
+    /// their available `asset` balance, as far as it goes, and receivers
+    /// share what was collected in proportion to what they are owed, so the
+    /// ledger stays balanced when a payer falls short. Pending trades should be
+    /// settled first so the positions are current.
+    pub async fn settle_funding(
+        &self,
+        market: &str,
+        asset: &str,
+        rate: Decimal,
+        mark_price: Price,
+        settled_at: DateTime<Utc>,
+    ) -> Result<Vec<FundingPayment>> {
+        let holders = self.positions.holders(market);
+        let accounts: Vec<Uuid> = holders.iter().map(|(account_id, _)| *account_id).collect();
+        
+        self.executor.run(&accounts, async {
+            let owed: Vec<(Uuid, Quantity, Amount)> = holders.iter()
+                .map(|(account_id, position)| {
+                    let amount = (*position * mark_price * rate)
+                        .round_dp_with_strategy(FUNDING_SCALE, RoundingStrategy::ToZero);
+                    (*account_id, *position, amount)
+                })
+                .filter(|(_, _, amount)| !amount.is_zero())
+                .collect();
+            let payment = |account_id: Uuid, position: Quantity, amount: Amount| FundingPayment {
+                account_id,
+                market: market.to_string(),
+                asset: asset.to_string(),
+                position,
+                rate,
+                mark_price,
+                amount: amount.normalize(),
+                settled_at,
+            };
             
-            // Update seller balances
-            seller_base_balance.locked -= base_amount;
-            seller_base_balance.total -= base_amount;
-            seller_quote_balance.total += quote_amount;
-            seller_quote_balance.available += quote_amount;
+            let mut transaction = self.repo.begin_transaction().await
+                .with_context(|| format!("Failed to start funding transaction for {}", market))?;
             
-            // Update all balances
-            self.repo.update_balance(buyer_quote_balance).await
-                .with_context(|| "Failed to update buyer quote balance")?;
+            let transaction_result = async {
+                let mut payments = Vec::with_capacity(owed.len());
+                let mut balances = Vec::with_capacity(owed.len());
                 
-            self.repo.update_balance(buyer_base_balance).await
-                .with_context(|| "Failed to update buyer base balance")?;
+                // Payers first, each paying what its available balance covers
+                let mut total_owed = Amount::ZERO;
+                let mut collected = Amount::ZERO;
+                for (account_id, position, amount) in owed.iter().filter(|(_, _, amount)| *amount > Amount::ZERO) {
+                    let mut balance = self.repo.ensure_balance(*account_id, asset).await?;
+                    let paid = (*amount).min(balance.available.max(Quantity::ZERO));
+                    if paid < *amount {
+                        warn!("Account {} is short {} {} of funding on {}", account_id, format_amount(asset, *amount - paid), asset, market);
+                    }
+                    balance.withdraw(paid).map_err(Error::InsufficientBalance)?;
+                    balances.push(balance);
+                    
+                    total_owed += *amount;
+                    collected += paid;
+                    payments.push(payment(*account_id, *position, paid));
+                }
                 
-            self.repo.update_balance(seller_base_balance).await
-                .with_context(|| "Failed to update seller base balance")?;
+                // Receivers share the collected funding, the last one taking the rounding dust
+                let receivers: Vec<&(Uuid, Quantity, Amount)> = owed.iter()
+                    .filter(|(_, _, amount)| *amount < Amount::ZERO)
+                    .collect();
+                let mut credited = Amount::ZERO;
+                for (index, (account_id, position, amount)) in receivers.iter().enumerate() {
+                    let share = if collected.is_zero() {
+                        Amount::ZERO
+                    } else if index + 1 == receivers.len() {
+                        collected - credited
+                    } else {
+                        (-*amount * collected / total_owed)
+                            .round_dp_with_strategy(FUNDING_SCALE, RoundingStrategy::ToZero)
+                            .min(collected - credited)
+                    };
+                    let mut balance = self.repo.ensure_balance(*account_id, asset).await?;
+                    balance.deposit(share);
+                    balances.push(balance);
+                    
+                    credited += share;
+                    payments.push(payment(*account_id, *position, -share));
+                }
                 
-            self.repo.update_balance(seller_quote_balance).await
-                .with_context(|| "Failed to update seller quote balance")?;
+                // Write every payer's and receiver's balance together, in lock order
+                self.repo.update_balances_in(&mut transaction, balances).await?;
+                Ok(payments)
+            }.await;
             
-            Ok(())
-        }.await;
-        
-        // Handle transaction result
-        match transaction_result {
-            Ok(_) => {
-                // Commit the transaction
-                transaction.commit().await
-                    .with_context(|| format!("Failed to commit transaction for trade {}", trade.id))?;
+            match transaction_result {
+                Ok(payments) => {
+                    transaction.commit().await
+                        .with_context(|| format!("Failed to commit funding transaction for {}", market))?;
                     
-                info!("Successfully processed trade: {}", trade.id);
-                Ok(())
-            },
-            Err(e) => {
-                // Log the error and roll back
-                error!("Error processing trade {}: {}", trade.id, e);
-                
-                // Roll back the transaction
-                if let Err(rollback_err) = transaction.rollback().await {
-                    // Log rollback failure but return the original error
-                    error!("Failed to roll back transaction: {}", rollback_err);
+                    info!("Settled funding on {} at rate {} between {} accounts", market, rate, payments.len());
+                    for payment in &payments {
+                        let mut history = self.funding_payments.entry(payment.account_id).or_default();
+                        history.push(payment.clone());
+                        if history.len() > FUNDING_HISTORY_LIMIT {
+                            history.remove(0);
+                        }
+                    }
+                    Ok(payments)
+                },
+                Err(e) => {
+                    error!("Error settling funding on {}: {}", market, e);
+                    if let Err(rollback_err) = transaction.rollback().await {
+                        error!("Failed to roll back transaction: {}", rollback_err);
+                    }
+                    Err(e)
+                }
+            }
+        }).await
+    }
+    
+    /// Post an operator's fee rebate or manual adjustment to an account
+    ///
+    /// Fee rebates must be credits. The amount must be within the asset's
+    /// precision and a debit must be covered by the available balance.
+    /// [`ReasonCode::Other`] needs a note saying why.
+    pub async fn adjust_balance(&self, adjustment: &BalanceAdjustment) -> Result<Balance> {
+        let account_id = adjustment.account_id;
+        let asset = adjustment.asset.as_str();
+        if adjustment.amount.is_zero() {
+            return Err(Error::ValidationError("Adjustment amount must not be zero".to_string()));
+        }
+        if adjustment.kind == AdjustmentKind::FeeRebate && adjustment.amount < Amount::ZERO {
+            return Err(Error::ValidationError("A fee rebate must be a credit".to_string()));
+        }
+        self.asset(asset).check_precision(adjustment.amount)?;
+        let has_note = adjustment.note.as_deref().is_some_and(|note| !note.trim().is_empty());
+        if adjustment.reason_code == ReasonCode::Other && !has_note {
+            return Err(Error::ValidationError("Adjustments for other reasons need a note".to_string()));
+        }
+        
+        info!(
+            "Adjusting account {} by {} {} ({:?}, {:?})",
+            account_id, format_amount(asset, adjustment.amount), asset, adjustment.kind, adjustment.reason_code
+        );
+        self.executor.run(&[account_id], async {
+            self.open_account(account_id).await?;
+            
+            let mut transaction = self.repo.begin_transaction().await
+                .with_context(|| format!("Failed to start transaction for adjustment {}", adjustment.id))?;
+            
+            let transaction_result = async {
+                let mut balance = self.repo.ensure_balance(account_id, asset).await?;
+                if adjustment.amount > Amount::ZERO {
+                    balance.deposit(adjustment.amount);
+                } else {
+                    balance.withdraw(-adjustment.amount).map_err(Error::InsufficientBalance)?;
                 }
                 
-                // Return the original error
-                Err(e)
+                let balance = self.repo.update_balance_in(&mut transaction, balance).await?;
+                self.repo.save_adjustment_in(&mut transaction, adjustment).await?;
+                Ok(balance)
+            }.await;
+            
+            match transaction_result {
+                Ok(balance) => {
+                    transaction.commit().await
+                        .with_context(|| format!("Failed to commit adjustment {}", adjustment.id))?;
+                    Ok(balance)
+                },
+                Err(e) => {
+                    error!("Error posting adjustment {}: {}", adjustment.id, e);
+                    if let Err(rollback_err) = transaction.rollback().await {
+                        error!("Failed to roll back transaction: {}", rollback_err);
+                    }
+                    Err(e)
+                }
+            }
+        }).await
+    }
+    
+    /// Get every change to an account's balances in `[from, to)`, oldest first
+    ///
+    /// Lists each asset leg of its trades net of fees, their busts, funding
+    /// payments and operator adjustments. Trades and funding payments come
+    /// from the recent history kept by the service.
+    pub async fn get_statement(&self, account_id: Uuid, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<StatementEntry>> {
+        let in_range = |timestamp: DateTime<Utc>| timestamp >= from && timestamp < to;
+        let mut entries = Vec::new();
+        
+        let trades = self.account_trades.get(&account_id).map(|trades| trades.clone()).unwrap_or_default();
+        for trade in &trades {
+            let symbol = trade.symbol()?;
+            let quote_amount = trade.price * trade.quantity;
+            let mut legs = Vec::with_capacity(4);
+            if trade.buyer_id == account_id {
+                legs.push((symbol.base().as_str(), trade.quantity - trade.buyer_fee(), trade.buyer_fee()));
+                legs.push((symbol.quote().as_str(), -quote_amount, Amount::ZERO));
+            }
+            if trade.seller_id == account_id {
+                legs.push((symbol.base().as_str(), -trade.quantity, Amount::ZERO));
+                legs.push((symbol.quote().as_str(), quote_amount - trade.seller_fee(), trade.seller_fee()));
+            }
+            
+            let leg = |timestamp, kind, asset: &str, amount, fee, note: Option<String>| StatementEntry {
+                timestamp,
+                kind,
+                asset: asset.to_string(),
+                amount,
+                fee,
+                reference: Some(trade.id),
+                market: Some(trade.market.clone()),
+                reason_code: None,
+                note,
+            };
+            if in_range(trade.created_at) {
+                for (asset, amount, fee) in &legs {
+                    entries.push(leg(trade.created_at, StatementEntryKind::Trade, asset, *amount, *fee, None));
+                }
+            }
+            if let Some(bust) = self.repo.get_trade_bust(trade.id).await?.filter(|bust| in_range(bust.busted_at)) {
+                for (asset, amount, fee) in &legs {
+                    entries.push(leg(bust.busted_at, StatementEntryKind::TradeBust, asset, -*amount, -*fee, Some(bust.reason.clone())));
+                }
+            }
+        }
+        
+        let payments = self.funding_payments.get(&account_id).map(|payments| payments.clone()).unwrap_or_default();
+        entries.extend(payments.into_iter().filter(|payment| in_range(payment.settled_at)).map(|payment| StatementEntry {
+            timestamp: payment.settled_at,
+            kind: StatementEntryKind::Funding,
+            asset: payment.asset,
+            amount: -payment.amount,
+            fee: Amount::ZERO,
+            reference: None,
+            market: Some(payment.market),
+            reason_code: None,
+            note: None,
+        }));
+        
+        entries.extend(self.repo.get_adjustments(account_id, from, to).await?.into_iter().map(|adjustment| StatementEntry {
+            timestamp: adjustment.created_at,
+            kind: match adjustment.kind {
+                AdjustmentKind::FeeRebate => StatementEntryKind::FeeRebate,
+                AdjustmentKind::Manual => StatementEntryKind::Adjustment,
+            },
+            asset: adjustment.asset,
+            amount: adjustment.amount,
+            fee: Amount::ZERO,
+            reference: Some(adjustment.id),
+            market: None,
+            reason_code: Some(adjustment.reason_code),
+            note: adjustment.note,
+        }));
+        
+        entries.sort_by_key(|entry| entry.timestamp);
+        Ok(entries)
+    }
+    
+    /// Get an account's funding payments, newest first
+    pub fn get_funding_payments(&self, account_id: Uuid, limit: usize) -> Vec<FundingPayment> {
+        self.funding_payments
+            .get(&account_id)
+            .map(|payments| payments.iter().rev().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+    
+    /// Remember a settled trade in both parties' history
+    fn record_trade(&self, trade: &Trade) {
+        let mut parties = vec![trade.buyer_id, trade.seller_id];
+        parties.dedup();
+        
+        for account_id in parties {
+            let mut trades = self.account_trades.entry(account_id).or_default();
+            trades.push(trade.clone());
+            if trades.len() > TRADE_HISTORY_LIMIT {
+                trades.remove(0);
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Check an external reference is not blank and not too long
+fn check_external_id(external_id: &str) -> Result<()> {
+    if external_id.trim().is_empty() || external_id.len() > MAX_EXTERNAL_ID_LENGTH {
+        return Err(Error::ValidationError(format!(
+            "External ID must be 1 to {} characters", MAX_EXTERNAL_ID_LENGTH
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,150 @@
+//! Settlement through files exchanged with a bank
+//!
+//! Payout instructions are appended to a daily CSV file in the outbox
+//! directory (`payouts-YYYY-MM-DD.csv`). The bank drops statements of received
+//! deposits as CSV files in the inbox directory, one
+//! `reference,account_id,asset,amount` line per deposit; each file is renamed
+//! with a `.processed` suffix once read.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use common::decimal::Quantity;
+use common::error::{Error, Result};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{DepositConfirmation, Payout, SettlementAdapter};
+
+/// Header of payout instruction files
+const PAYOUT_HEADER: &str = "payout_id,account_id,asset,amount,address,requested_at\n";
+
+/// Bank file exchange settings
+#[derive(Debug, Clone)]
+pub struct BankFileConfig {
+    /// Directory payout instructions are written to
+    pub outbox: PathBuf,
+    /// Directory deposit statements are read from
+    pub inbox: PathBuf,
+    /// Assets settled through the bank, e.g. `USD`
+    pub assets: Vec<String>,
+}
+
+/// Adapter exchanging CSV files with a bank
+pub struct BankFileSettlementAdapter {
+    /// Directories and assets
+    config: BankFileConfig,
+    /// Serializes appends to payout files
+    write_lock: Mutex<()>,
+}
+
+impl BankFileSettlementAdapter {
+    /// Create an adapter for the configured directories
+    pub fn new(config: BankFileConfig) -> Self {
+        Self {
+            config,
+            write_lock: Mutex::new(()),
+        }
+    }
+}
+
+#[async_trait]
+impl SettlementAdapter for BankFileSettlementAdapter {
+    fn name(&self) -> &str {
+        "bank-file"
+    }
+
+    fn handles(&self, asset: &str) -> bool {
+        self.config.assets.iter().any(|handled| handled == asset)
+    }
+
+    async fn send_payout(&self, payout: &Payout) -> Result<String> {
+        let address = payout.address.as_deref().unwrap_or_default();
+        if address.contains([',', '\n', '\r']) {
+            return Err(Error::ValidationError(format!("Bank account {:?} cannot be written to a payout file", address)));
+        }
+
+        let path = self.config.outbox.join(format!("payouts-{}.csv", payout.requested_at.format("%Y-%m-%d")));
+        let line = format!(
+            "{},{},{},{},{},{}\n",
+            payout.id, payout.account_id, payout.asset, payout.amount, address, payout.requested_at.to_rfc3339()
+        );
+
+        let _guard = self.write_lock.lock().await;
+        tokio::fs::create_dir_all(&self.config.outbox).await
+            .map_err(|e| Error::Internal(format!("Failed to create {}: {}", self.config.outbox.display(), e)))?;
+        let is_new = !tokio::fs::try_exists(&path).await.unwrap_or(false);
+        let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(&path).await
+            .map_err(|e| Error::Internal(format!("Failed to open {}: {}", path.display(), e)))?;
+        let content = if is_new { format!("{}{}", PAYOUT_HEADER, line) } else { line };
+        file.write_all(content.as_bytes()).await
+            .map_err(|e| Error::Internal(format!("Failed to write {}: {}", path.display(), e)))?;
+        // Tokio hands writes to a background thread; wait until the line is written
+        file.flush().await
+            .map_err(|e| Error::Internal(format!("Failed to write {}: {}", path.display(), e)))?;
+
+        Ok(payout.id.to_string())
+    }
+
+    async fn poll_deposits(&self) -> Result<Vec<DepositConfirmation>> {
+        let mut entries = match tokio::fs::read_dir(&self.config.inbox).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::Internal(format!("Failed to read {}: {}", self.config.inbox.display(), e))),
+        };
+
+        let mut statements = Vec::new();
+        while let Some(entry) = entries.next_entry().await
+            .map_err(|e| Error::Internal(format!("Failed to read {}: {}", self.config.inbox.display(), e)))?
+        {
+            let path = entry.path();
+            if path.extension().is_some_and(|extension| extension == "csv") {
+                statements.push(path);
+            }
+        }
+        statements.sort();
+
+        let mut confirmations = Vec::new();
+        for path in statements {
+            let content = tokio::fs::read_to_string(&path).await
+                .map_err(|e| Error::Internal(format!("Failed to read {}: {}", path.display(), e)))?;
+            confirmations.extend(content.lines().enumerate().filter_map(|(index, line)| {
+                parse_deposit(line)
+                    .map_err(|e| warn!("Skipping line {} of {}: {}", index + 1, path.display(), e))
+                    .ok()
+                    .flatten()
+            }));
+
+            let processed = path.with_extension("csv.processed");
+            tokio::fs::rename(&path, &processed).await
+                .map_err(|e| Error::Internal(format!("Failed to rename {}: {}", path.display(), e)))?;
+        }
+
+        Ok(confirmations)
+    }
+}
+
+/// Parse a statement line, skipping blank lines and the header
+fn parse_deposit(line: &str) -> std::result::Result<Option<DepositConfirmation>, String> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    match fields.as_slice() {
+        [""] | ["reference", ..] => Ok(None),
+        [reference, account_id, asset, amount] if !reference.is_empty() && !asset.is_empty() => {
+            let account_id = Uuid::from_str(account_id).map_err(|e| format!("invalid account ID: {}", e))?;
+            let amount = Quantity::from_str(amount).map_err(|e| format!("invalid amount: {}", e))?;
+            if amount <= Quantity::ZERO {
+                return Err(format!("amount must be positive, got {}", amount));
+            }
+            Ok(Some(DepositConfirmation {
+                reference: reference.to_string(),
+                account_id,
+                asset: asset.to_string(),
+                amount,
+            }))
+        }
+        _ => Err("expected reference,account_id,asset,amount".to_string()),
+    }
+}
@@ -0,0 +1,129 @@
+//! Settlement through a Bitcoin Core compatible node's JSON-RPC wallet
+//!
+//! Payouts are sent with `sendtoaddress`. Deposit addresses are created with
+//! `getnewaddress`, labelled with the account ID, so received transactions
+//! listed by `listtransactions` can be credited to the right account once they
+//! have enough confirmations.
+
+use async_trait::async_trait;
+use common::decimal::{format_amount, Quantity};
+use common::error::{Error, Result};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use super::{DepositConfirmation, Payout, SettlementAdapter};
+
+/// Transactions fetched per deposit poll
+const DEPOSIT_POLL_COUNT: usize = 1000;
+
+/// Crypto node connection settings
+#[derive(Debug, Clone)]
+pub struct CryptoNodeConfig {
+    /// Wallet RPC URL, e.g. `http://bitcoind:8332/wallet/exchange`
+    pub url: String,
+    /// RPC user
+    pub user: String,
+    /// RPC password
+    pub password: String,
+    /// Asset the node settles, e.g. `BTC`
+    pub asset: String,
+    /// Confirmations a deposit needs before it is credited
+    pub min_confirmations: u32,
+}
+
+/// Adapter for a node wallet speaking JSON-RPC
+pub struct CryptoNodeSettlementAdapter {
+    /// Connection settings
+    config: CryptoNodeConfig,
+    /// HTTP client
+    client: reqwest::Client,
+}
+
+impl CryptoNodeSettlementAdapter {
+    /// Create an adapter for the configured node
+    pub fn new(config: CryptoNodeConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Create a new deposit address for an account
+    pub async fn deposit_address(&self, account_id: Uuid) -> Result<String> {
+        let address = self.call("getnewaddress", json!([account_id.to_string()])).await?;
+        address.as_str()
+            .map(str::to_string)
+            .ok_or_else(|| Error::Internal(format!("Unexpected getnewaddress result: {}", address)))
+    }
+
+    /// Call an RPC method, returning its result
+    async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let request = json!({ "jsonrpc": "1.0", "id": method, "method": method, "params": params });
+        let response = self.client
+            .post(&self.config.url)
+            .basic_auth(&self.config.user, Some(&self.config.password))
+            .header("content-type", "application/json")
+            .body(request.to_string())
+            .send()
+            .await
+            .map_err(|e| Error::Internal(format!("Node RPC {} failed: {}", method, e)))?;
+
+        // The node answers RPC errors with a JSON body and a non-2xx status
+        let body = response.text().await
+            .map_err(|e| Error::Internal(format!("Node RPC {} failed: {}", method, e)))?;
+        let mut body: Value = serde_json::from_str(&body)
+            .map_err(|e| Error::Internal(format!("Node RPC {} returned invalid JSON: {}", method, e)))?;
+        if !body["error"].is_null() {
+            return Err(Error::Internal(format!("Node RPC {} failed: {}", method, body["error"])));
+        }
+        Ok(body["result"].take())
+    }
+}
+
+#[async_trait]
+impl SettlementAdapter for CryptoNodeSettlementAdapter {
+    fn name(&self) -> &str {
+        "crypto-node"
+    }
+
+    fn handles(&self, asset: &str) -> bool {
+        self.config.asset == asset
+    }
+
+    async fn send_payout(&self, payout: &Payout) -> Result<String> {
+        let address = payout.address.as_deref()
+            .ok_or_else(|| Error::ValidationError(format!("{} withdrawals need a destination address", payout.asset)))?;
+
+        let txid = self.call(
+            "sendtoaddress",
+            json!([address, format_amount(&payout.asset, payout.amount), payout.id.to_string()]),
+        ).await?;
+        txid.as_str()
+            .map(str::to_string)
+            .ok_or_else(|| Error::Internal(format!("Unexpected sendtoaddress result: {}", txid)))
+    }
+
+    async fn poll_deposits(&self) -> Result<Vec<DepositConfirmation>> {
+        let transactions = self.call("listtransactions", json!(["*", DEPOSIT_POLL_COUNT])).await?;
+        let transactions = transactions.as_array()
+            .ok_or_else(|| Error::Internal(format!("Unexpected listtransactions result: {}", transactions)))?;
+
+        Ok(transactions.iter().filter_map(|transaction| {
+            if transaction["category"] != "receive"
+                || transaction["confirmations"].as_u64().unwrap_or(0) < u64::from(self.config.min_confirmations)
+            {
+                return None;
+            }
+
+            // Addresses not created for an account are not deposits
+            let account_id = transaction["label"].as_str().and_then(|label| Uuid::parse_str(label).ok())?;
+            let amount = Quantity::try_from(transaction["amount"].as_f64()?).ok()?.round_dp(8);
+            Some(DepositConfirmation {
+                reference: format!("{}:{}", transaction["txid"].as_str()?, transaction["vout"].as_u64()?),
+                account_id,
+                asset: self.config.asset.clone(),
+                amount,
+            })
+        }).collect())
+    }
+}
@@ -0,0 +1,163 @@
+//! External settlement of deposits and withdrawals
+//!
+//! A [`SettlementAdapter`] connects the account service to an external
+//! custodian. Completed withdrawals are sent to the adapter handling the asset
+//! as payouts, and deposits the adapter reports as confirmed are credited to
+//! accounts once each.
+
+mod bank_file;
+mod crypto_node;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::decimal::Quantity;
+use common::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+pub use bank_file::{BankFileConfig, BankFileSettlementAdapter};
+pub use crypto_node::{CryptoNodeConfig, CryptoNodeSettlementAdapter};
+
+/// Funds leaving the exchange for an external destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Payout {
+    /// Unique payout ID
+    pub id: Uuid,
+    /// Account the funds were withdrawn from
+    pub account_id: Uuid,
+    /// Asset symbol
+    pub asset: String,
+    /// Amount withdrawn
+    pub amount: Quantity,
+    /// Destination address or account number
+    pub address: Option<String>,
+    /// When the withdrawal was completed
+    pub requested_at: DateTime<Utc>,
+}
+
+/// Deposit confirmed by an external custodian
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepositConfirmation {
+    /// Custodian reference, unique per adapter, e.g. a bank reference or transaction ID
+    pub reference: String,
+    /// Account to credit
+    pub account_id: Uuid,
+    /// Asset symbol
+    pub asset: String,
+    /// Amount received
+    pub amount: Quantity,
+}
+
+/// Connection to an external custodian for some assets
+#[async_trait]
+pub trait SettlementAdapter: Send + Sync {
+    /// Adapter name, used to keep deposit references apart
+    fn name(&self) -> &str;
+
+    /// Whether the adapter settles an asset
+    fn handles(&self, asset: &str) -> bool;
+
+    /// Send a payout, returning the custodian's reference for it
+    async fn send_payout(&self, payout: &Payout) -> Result<String>;
+
+    /// Deposits confirmed since the last poll; may repeat earlier confirmations
+    async fn poll_deposits(&self) -> Result<Vec<DepositConfirmation>>;
+}
+
+/// Settlement adapters to enable and how often to poll them for deposits
+#[derive(Debug, Clone, Default)]
+pub struct SettlementConfig {
+    /// Bank file exchange, disabled when unset
+    pub bank_file: Option<BankFileConfig>,
+    /// Crypto node JSON-RPC, disabled when unset
+    pub crypto_node: Option<CryptoNodeConfig>,
+    /// How often adapters are polled for confirmed deposits
+    pub deposit_poll_interval: Duration,
+}
+
+impl SettlementConfig {
+    /// Build the configured adapters
+    pub fn adapters(&self) -> Vec<Arc<dyn SettlementAdapter>> {
+        let mut adapters: Vec<Arc<dyn SettlementAdapter>> = Vec::new();
+        if let Some(config) = &self.bank_file {
+            adapters.push(Arc::new(BankFileSettlementAdapter::new(config.clone())));
+        }
+        if let Some(config) = &self.crypto_node {
+            adapters.push(Arc::new(CryptoNodeSettlementAdapter::new(config.clone())));
+        }
+        adapters
+    }
+}
+
+/// In-memory adapter for tests and demos that records payouts and replays queued deposits
+#[derive(Debug, Default)]
+pub struct MockSettlementAdapter {
+    /// Assets handled, or all when empty
+    assets: Vec<String>,
+    /// Payouts sent with their references
+    payouts: Mutex<Vec<(Payout, String)>>,
+    /// Deposits waiting for the next poll
+    deposits: Mutex<Vec<DepositConfirmation>>,
+    /// Whether payouts are rejected
+    failing: AtomicBool,
+}
+
+impl MockSettlementAdapter {
+    /// Create an adapter that handles every asset
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an adapter that only handles the given assets
+    pub fn for_assets(assets: &[&str]) -> Self {
+        Self {
+            assets: assets.iter().map(|asset| asset.to_string()).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Queue a deposit confirmation for the next poll
+    pub async fn confirm_deposit(&self, confirmation: DepositConfirmation) {
+        self.deposits.lock().await.push(confirmation);
+    }
+
+    /// Payouts sent so far with their references
+    pub async fn payouts(&self) -> Vec<(Payout, String)> {
+        self.payouts.lock().await.clone()
+    }
+
+    /// Reject payouts from now on, or accept them again
+    pub fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl SettlementAdapter for MockSettlementAdapter {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn handles(&self, asset: &str) -> bool {
+        self.assets.is_empty() || self.assets.iter().any(|handled| handled == asset)
+    }
+
+    async fn send_payout(&self, payout: &Payout) -> Result<String> {
+        if self.failing.load(Ordering::Relaxed) {
+            return Err(Error::Internal(format!("Mock payout {} rejected", payout.id)));
+        }
+
+        let reference = format!("mock-{}", payout.id);
+        self.payouts.lock().await.push((payout.clone(), reference.clone()));
+        Ok(reference)
+    }
+
+    async fn poll_deposits(&self) -> Result<Vec<DepositConfirmation>> {
+        Ok(std::mem::take(&mut *self.deposits.lock().await))
+    }
+}
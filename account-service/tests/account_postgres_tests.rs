@@ -1,31 +1,28 @@
 use account_service::{AccountService, RepositoryType};
-use common::decimal::{Quantity, dec};
+use common::decimal::Quantity;
 use common::model::order::{Order, OrderType, Side, Status, TimeInForce};
 use common::model::trade::Trade;
 use uuid::Uuid;
+use common::db::fixture::PostgresFixture;
 use tokio::test;
 
-use dotenv::dotenv;
-
 // PostgreSQL integration tests for account service
-// These tests require a running PostgreSQL database
-// Run with: cargo test --test account_postgres_tests -- --ignored
-
-async fn create_test_service() -> AccountService {
-    dotenv().ok(); // Load .env.test if it exists
+// Each test gets a disposable database from a testcontainers fixture,
+// or uses TEST_DATABASE_URL when set. Tests are skipped only with SKIP_POSTGRES_TESTS=1.
 
-    let database_url = std::env::var("TEST_DATABASE_URL")
-        .expect("TEST_DATABASE_URL must be set to run PostgreSQL tests");
+async fn create_test_service() -> Option<(PostgresFixture, AccountService)> {
+    let fixture = PostgresFixture::start().await?;
 
-    AccountService::with_repository(RepositoryType::Postgres(Some(database_url)))
+    let service = AccountService::with_repository(RepositoryType::Postgres(Some(fixture.database_url.clone())))
         .await
-        .expect("Failed to create account service with PostgreSQL repository")
+        .expect("Failed to create account service with PostgreSQL repository");
+
+    Some((fixture, service))
 }
 
 #[test]
-#[ignore = "Requires test database"]
 async fn test_postgres_account_creation() {
-    let service = create_test_service().await;
+    let Some((_db, service)) = create_test_service().await else { return };
     
     // Create account
     let account = service.create_account().await.unwrap();
@@ -37,9 +34,8 @@ async fn test_postgres_account_creation() {
 }
 
 #[test]
-#[ignore = "Requires test database"]
 async fn test_postgres_balance_operations() {
-    let service = create_test_service().await;
+    let Some((_db, service)) = create_test_service().await else { return };
     
     // Create account
     let account = service.create_account().await.unwrap();
@@ -71,9 +67,8 @@ async fn test_postgres_balance_operations() {
 }
 
 #[test]
-#[ignore = "Requires test database"]
 async fn test_postgres_order_reserves() {
-    let service = create_test_service().await;
+    let Some((_db, service)) = create_test_service().await else { return };
     
     // Create account
     let account = service.create_account().await.unwrap();
@@ -94,6 +89,14 @@ async fn test_postgres_order_reserves() {
         filled_quantity: Quantity::ZERO,
         remaining_quantity: Quantity::from(2),
         average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
         time_in_force: TimeInForce::GTC,
         status: Status::New,
         created_at: chrono::Utc::now(),
@@ -120,6 +123,14 @@ async fn test_postgres_order_reserves() {
         filled_quantity: Quantity::ZERO,
         remaining_quantity: Quantity::from(1),
         average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
         time_in_force: TimeInForce::GTC,
         status: Status::New,
         created_at: chrono::Utc::now(),
@@ -146,6 +157,14 @@ async fn test_postgres_order_reserves() {
         filled_quantity: Quantity::from(1),
         remaining_quantity: Quantity::from(1), // 1 BTC unfilled
         average_fill_price: Some(Quantity::from(100)),
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
         time_in_force: TimeInForce::GTC,
         status: Status::Cancelled,
         created_at: chrono::Utc::now(),
@@ -155,16 +174,15 @@ async fn test_postgres_order_reserves() {
     // Release funds
     service.release_reserved_funds(&canceled_buy).await.unwrap();
     
-    // Verify released funds
+    // No fill was settled, so the whole reservation is released whatever the order reports
     let updated_usd = service.get_balance(account.id, "USD").await.unwrap().unwrap();
-    assert_eq!(updated_usd.locked, Quantity::from(100)); // $100 still locked
-    assert_eq!(updated_usd.available, Quantity::from(900)); // $900 available
+    assert_eq!(updated_usd.locked, Quantity::ZERO);
+    assert_eq!(updated_usd.available, Quantity::from(1000));
 }
 
 #[test]
-#[ignore = "Requires test database"]
 async fn test_postgres_trade_processing() {
-    let service = create_test_service().await;
+    let Some((_db, service)) = create_test_service().await else { return };
     
     // Create buyer and seller accounts
     let buyer = service.create_account().await.unwrap();
@@ -186,6 +204,14 @@ async fn test_postgres_trade_processing() {
         filled_quantity: Quantity::ZERO,
         remaining_quantity: Quantity::from(3),
         average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
         time_in_force: TimeInForce::GTC,
         status: Status::New,
         created_at: chrono::Utc::now(),
@@ -203,6 +229,14 @@ async fn test_postgres_trade_processing() {
         filled_quantity: Quantity::ZERO,
         remaining_quantity: Quantity::from(3),
         average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
         time_in_force: TimeInForce::GTC,
         status: Status::New,
         created_at: chrono::Utc::now(),
@@ -225,7 +259,13 @@ async fn test_postgres_trade_processing() {
         quantity: Quantity::from(3),
         amount: Quantity::from(300), // 3 * 100
         taker_side: Side::Buy,
+        is_buyer_maker: false,
+        maker_fee: Quantity::ZERO,
+        maker_fee_asset: "USD".to_string(),
+        taker_fee: Quantity::ZERO,
+        taker_fee_asset: "BTC".to_string(),
         created_at: chrono::Utc::now(),
+        sequence: 0,
     };
     
     service.process_trade(&trade).await.unwrap();
@@ -249,4 +289,247 @@ async fn test_postgres_trade_processing() {
     assert_eq!(seller_btc.total, Quantity::from(7)); // 10 - 3
     assert_eq!(seller_btc.available, Quantity::from(7));
     assert_eq!(seller_btc.locked, Quantity::ZERO);
-}
\ No newline at end of file
+    
+    // Busting the trade posts the reverse and is kept beside it
+    service.bust_trade(trade.id, "test bust", "admin").await.unwrap();
+    let buyer_usd = service.get_balance(buyer.id, "USD").await.unwrap().unwrap();
+    let seller_btc = service.get_balance(seller.id, "BTC").await.unwrap().unwrap();
+    assert_eq!(buyer_usd.available, Quantity::from(1000));
+    assert_eq!(seller_btc.available, Quantity::from(10));
+    assert_eq!(service.get_trade_bust(trade.id).await.unwrap().unwrap().reason, "test bust");
+    assert!(matches!(
+        service.bust_trade(trade.id, "test bust", "admin").await,
+        Err(common::error::Error::Conflict(_))
+    ));
+}
+#[test]
+async fn test_postgres_accounts_by_external_id_and_pages() {
+    let Some((_db, service)) = create_test_service().await else { return };
+
+    let first = service.create_account_with_external_id("crm-1").await.unwrap();
+    let second = service.create_account().await.unwrap();
+    service.set_external_id(second.id, Some("crm-2")).await.unwrap();
+    assert!(matches!(
+        service.set_external_id(second.id, Some("crm-1")).await,
+        Err(common::error::Error::Conflict(_))
+    ));
+
+    let found = service.find_by_external_id("crm-1").await.unwrap().unwrap();
+    assert_eq!(found.id, first.id);
+    assert_eq!(found.external_id.as_deref(), Some("crm-1"));
+
+    let filter = account_service::AccountFilter {
+        external_id_prefix: Some("crm-".to_string()),
+        ..Default::default()
+    };
+    let page = service.list_accounts(&filter, None, 1).await.unwrap();
+    assert_eq!(page.accounts.len(), 1);
+    let rest = service.list_accounts(&filter, page.next_cursor, 1).await.unwrap();
+    assert_eq!(rest.accounts.len(), 1);
+    assert!(rest.accounts[0].id > page.accounts[0].id);
+}
+
+#[test]
+async fn test_postgres_balance_snapshots() {
+    let Some((_db, service)) = create_test_service().await else { return };
+
+    let account = service.create_account().await.unwrap();
+    service.deposit(account.id, "BTC", Quantity::from(2)).await.unwrap();
+    let taken_at = chrono::Utc::now();
+    assert_eq!(service.snapshot_balances(Some(account.id), taken_at).await.unwrap(), 1);
+    assert_eq!(service.snapshot_balances(Some(account.id), taken_at).await.unwrap(), 0);
+
+    let history = service
+        .balance_history(account.id, Some("BTC"), chrono::Duration::days(1), taken_at - chrono::Duration::days(1), taken_at + chrono::Duration::days(1))
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].total, Quantity::from(2));
+    assert_eq!(history[0].available, Quantity::from(2));
+}
+
+#[test]
+async fn test_postgres_asset_registry() {
+    use common::model::asset::Asset;
+
+    let Some((db, service)) = create_test_service().await else { return };
+
+    let btc = Asset {
+        withdrawal_min: "0.001".parse().unwrap(),
+        withdrawal_fee: "0.0005".parse().unwrap(),
+        ..Asset::new("btc", "Bitcoin", 8)
+    };
+    assert_eq!(service.register_asset(btc.clone()).await.unwrap().code, "BTC");
+    service.register_asset(Asset::new("USD", "US Dollar", 2)).await.unwrap();
+
+    // A restarted service keeps registered rules over its seeds
+    let restarted = AccountService::with_repository(RepositoryType::Postgres(Some(db.database_url.clone())))
+        .await
+        .unwrap();
+    restarted
+        .seed_assets(vec![Asset::new("BTC", "Bitcoin", 6), Asset::new("ETH", "Ether", 8)])
+        .await
+        .unwrap();
+    let codes: Vec<String> = restarted.assets().into_iter().map(|asset| asset.code).collect();
+    assert!(["BTC", "ETH", "USD"].iter().all(|code| codes.iter().any(|listed| listed == code)));
+    assert_eq!(restarted.asset("btc"), btc);
+    assert_eq!(restarted.seed_assets(vec![Asset::new("ETH", "Ether", 6)]).await.unwrap(), 0);
+    assert_eq!(restarted.asset("ETH").precision, 8);
+}
+
+#[test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_postgres_concurrent_multi_balance_writes_do_not_deadlock() {
+    use account_service::{AccountRepository, PostgresAccountRepository};
+    use std::sync::Arc;
+
+    let Some(db) = PostgresFixture::start().await else { return };
+    let repo = Arc::new(PostgresAccountRepository::new(Some(db.database_url.clone())).await.unwrap());
+
+    let mut balances = Vec::new();
+    for _ in 0..2 {
+        let account = repo.create_account().await.unwrap();
+        for asset in ["BTC", "USD"] {
+            balances.push(repo.ensure_balance(account.id, asset).await.unwrap());
+        }
+    }
+
+    // Half the writers name the balances in reverse, which deadlocks unless writes are ordered
+    let writers = (0..8).map(|writer| {
+        let repo = repo.clone();
+        let mut balances = balances.clone();
+        if writer % 2 == 1 {
+            balances.reverse();
+        }
+        tokio::spawn(async move {
+            for round in 0..25 {
+                let mut transaction = repo.begin_transaction().await.unwrap();
+                let mut written = balances.clone();
+                for balance in &mut written {
+                    balance.deposit(Quantity::from(round));
+                }
+                repo.update_balances_in(&mut transaction, written).await?;
+                transaction.commit().await?;
+            }
+            Ok::<_, common::error::Error>(())
+        })
+    });
+
+    for writer in futures::future::join_all(writers).await {
+        writer.unwrap().expect("multi-balance write failed");
+    }
+}
+
+#[test]
+async fn test_postgres_balance_adjustments() {
+    use common::model::account::{AdjustmentKind, BalanceAdjustment, ReasonCode, StatementEntryKind};
+
+    let Some((_db, service)) = create_test_service().await else { return };
+    let account = service.create_account().await.unwrap();
+    let from = chrono::Utc::now();
+
+    let rebate = BalanceAdjustment {
+        id: Uuid::new_v4(),
+        account_id: account.id,
+        kind: AdjustmentKind::FeeRebate,
+        asset: "USD".to_string(),
+        amount: Quantity::from(5),
+        reason_code: ReasonCode::FeeOvercharge,
+        note: None,
+        actor: "admin".to_string(),
+        created_at: chrono::Utc::now(),
+    };
+    let balance = service.adjust_balance(&rebate).await.unwrap();
+    assert_eq!(balance.available, Quantity::from(5));
+
+    let statement = service.get_statement(account.id, from, chrono::Utc::now()).await.unwrap();
+    assert_eq!(statement.len(), 1);
+    assert_eq!(statement[0].kind, StatementEntryKind::FeeRebate);
+    assert_eq!(statement[0].reference, Some(rebate.id));
+    assert_eq!(statement[0].reason_code, Some(ReasonCode::FeeOvercharge));
+}
+
+#[test]
+async fn test_postgres_account_permissions() {
+    use common::model::account::AccountPermissions;
+
+    let Some((db, service)) = create_test_service().await else { return };
+    let account = service.create_account().await.unwrap();
+
+    let permissions = AccountPermissions {
+        can_trade: true,
+        can_withdraw: false,
+        allowed_markets: Some(vec!["BTC/USD".to_string()]),
+        max_leverage: Some(Quantity::from(3)),
+    };
+    service.set_permissions(account.id, permissions.clone()).await.unwrap();
+
+    // A fresh service reads them back from the database
+    let reloaded = AccountService::with_repository(RepositoryType::Postgres(Some(db.database_url.clone())))
+        .await
+        .unwrap();
+    assert_eq!(reloaded.get_permissions(account.id).await.unwrap(), permissions);
+    let other = service.create_account().await.unwrap();
+    assert_eq!(reloaded.get_permissions(other.id).await.unwrap(), AccountPermissions::default());
+}
+
+#[test]
+async fn test_postgres_order_history() {
+    use common::model::order::OrderTransition;
+
+    let Some((_db, service)) = create_test_service().await else { return };
+    let account = service.create_account().await.unwrap();
+
+    let mut order = Order::new_limit(account.id, "BTC/USD".to_string(), Side::Buy, Quantity::from(100), Quantity::from(2), TimeInForce::GTC);
+    let accepted = OrderTransition::accepted(&order);
+    order.filled_quantity = Quantity::from(1);
+    order.remaining_quantity = Quantity::from(1);
+    order.status = Status::PartiallyFilled;
+    let filled = OrderTransition::new(&order, Some((Status::New, Quantity::ZERO)));
+    order.status = Status::Cancelled;
+    let cancelled = OrderTransition::new(&order, Some((Status::PartiallyFilled, Quantity::from(1))));
+    service.record_order_transitions(&[accepted.clone(), filled.clone()]).await.unwrap();
+    service.record_order_transitions(std::slice::from_ref(&cancelled)).await.unwrap();
+
+    let history = service.get_order_history(order.id).await.unwrap();
+    assert_eq!(history, [accepted, filled, cancelled]);
+    assert_eq!(history[1].fill_quantity, Quantity::from(1));
+    assert_eq!(history[2].fill_quantity, Quantity::ZERO);
+    assert!(service.get_order_history(Uuid::new_v4()).await.unwrap().is_empty());
+}
+
+#[test]
+async fn test_postgres_reservations_survive_a_restart() {
+    let Some((db, service)) = create_test_service().await else { return };
+    let buyer = service.create_account().await.unwrap();
+    let seller = service.create_account().await.unwrap();
+    service.deposit(buyer.id, "USD", Quantity::from(1000)).await.unwrap();
+    service.deposit(seller.id, "BTC", Quantity::from(5)).await.unwrap();
+
+    let buy = Order::new_limit(buyer.id, "BTC/USD".to_string(), Side::Buy, Quantity::from(100), Quantity::from(3), TimeInForce::GTC);
+    let sell = Order::new_limit(seller.id, "BTC/USD".to_string(), Side::Sell, Quantity::from(100), Quantity::from(1), TimeInForce::GTC);
+    service.reserve_for_order(&buy).await.unwrap();
+    service.reserve_for_order(&sell).await.unwrap();
+    let trade = Trade::new("BTC/USD".to_string(), Quantity::from(100), Quantity::from(1), buy.id, sell.id, buyer.id, seller.id, Side::Sell);
+    service.process_trade(&trade).await.unwrap();
+
+    // What is left of the buy is still reserved after a restart, the filled sell is not
+    let restarted = AccountService::with_repository(RepositoryType::Postgres(Some(db.database_url.clone())))
+        .await
+        .unwrap();
+    let reservations = restarted.get_reservations(buyer.id);
+    assert_eq!(reservations.len(), 1);
+    assert_eq!(reservations[0].order_id, buy.id);
+    assert_eq!(reservations[0].amount, Quantity::from(200));
+    assert_eq!(reservations[0].quantity, Quantity::from(2));
+    assert!(restarted.get_reservations(seller.id).is_empty());
+
+    // So cancelling the order unlocks its funds
+    restarted.release_reserved_funds(&buy).await.unwrap();
+    let usd = restarted.get_balance(buyer.id, "USD").await.unwrap().unwrap();
+    assert_eq!(usd.locked, Quantity::ZERO);
+    assert_eq!(usd.available, Quantity::from(900));
+    let again = AccountService::with_repository(RepositoryType::Postgres(Some(db.database_url.clone())))
+        .await
+        .unwrap();
+    assert!(again.get_reservations(buyer.id).is_empty());
+}
@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use account_service::{AccountService, RepositoryType};
+use common::db::fixture::PostgresFixture;
+use common::decimal::{dec, Quantity};
+use common::model::order::{Order, Side, TimeInForce};
+use common::model::trade::Trade;
+use uuid::Uuid;
+
+// Concurrency stress tests for settlement correctness
+// Many tasks reserve, trade and cancel against a small set of shared accounts,
+// then the final balances are checked for conservation and consistency.
+
+const ACCOUNTS: usize = 4;
+const WORKERS: usize = 8;
+const ROUNDS: usize = 50;
+const INITIAL_USD: Quantity = dec!(1000000);
+const INITIAL_BTC: Quantity = dec!(1000);
+const TOP_UP_USD: Quantity = dec!(10);
+
+// Run one worker: each round places a buy and a sell between two shared accounts,
+// settles part of it and cancels the rest. Every fourth round the account trades
+// with itself, so both legs of the trade settle against the same balances.
+async fn run_worker(service: Arc<AccountService>, accounts: Arc<Vec<Uuid>>, worker: usize) {
+    for round in 0..ROUNDS {
+        let buyer = accounts[(worker + round) % ACCOUNTS];
+        let seller = if round % 4 == 3 { buyer } else { accounts[(worker + round + 1) % ACCOUNTS] };
+
+        let price = dec!(100) + Quantity::from(round as u64);
+        let quantity = dec!(2);
+        let fill = if round % 3 == 0 { quantity } else { dec!(1) };
+
+        let mut buy = Order::new_limit(buyer, "BTC/USD".to_string(), Side::Buy, price, quantity, TimeInForce::GTC);
+        let mut sell = Order::new_limit(seller, "BTC/USD".to_string(), Side::Sell, price, quantity, TimeInForce::GTC);
+
+        service.reserve_for_order(&buy).await.unwrap();
+        service.reserve_for_order(&sell).await.unwrap();
+
+        // Every other round an unrelated deposit races with settlement
+        if round % 2 == 0 {
+            service.deposit(buyer, "USD", TOP_UP_USD).await.unwrap();
+        }
+
+        if round % 5 != 4 {
+            let trade = Trade::new(
+                "BTC/USD".to_string(),
+                price,
+                fill,
+                buy.id,
+                sell.id,
+                buyer,
+                seller,
+                Side::Buy,
+            );
+            service.process_trade(&trade).await.unwrap();
+
+            buy.remaining_quantity -= fill;
+            sell.remaining_quantity -= fill;
+        }
+
+        // Cancel whatever is left of both orders
+        if buy.remaining_quantity > Quantity::ZERO {
+            service.release_reserved_funds(&buy).await.unwrap();
+        }
+        if sell.remaining_quantity > Quantity::ZERO {
+            service.release_reserved_funds(&sell).await.unwrap();
+        }
+    }
+}
+
+async fn run_stress(service: AccountService) {
+    let service = Arc::new(service);
+
+    // Fund a small set of shared accounts
+    let mut accounts = Vec::with_capacity(ACCOUNTS);
+    for _ in 0..ACCOUNTS {
+        let account = service.create_account().await.unwrap();
+        service.deposit(account.id, "USD", INITIAL_USD).await.unwrap();
+        service.deposit(account.id, "BTC", INITIAL_BTC).await.unwrap();
+        accounts.push(account.id);
+    }
+    let accounts = Arc::new(accounts);
+
+    // Hammer the accounts from many tasks at once
+    let handles: Vec<_> = (0..WORKERS)
+        .map(|worker| tokio::spawn(run_worker(service.clone(), accounts.clone(), worker)))
+        .collect();
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    // Every balance must be non-negative, fully unlocked and internally consistent
+    let mut total_usd = Quantity::ZERO;
+    let mut total_btc = Quantity::ZERO;
+    for account_id in accounts.iter() {
+        for balance in service.get_balances(*account_id).await.unwrap() {
+            assert!(balance.available >= Quantity::ZERO, "negative available: {:?}", balance);
+            assert_eq!(balance.locked, Quantity::ZERO, "funds left locked: {:?}", balance);
+            assert_eq!(balance.total, balance.available + balance.locked, "inconsistent balance: {:?}", balance);
+
+            match balance.asset.as_str() {
+                "USD" => total_usd += balance.total,
+                "BTC" => total_btc += balance.total,
+                other => panic!("unexpected asset {}", other),
+            }
+        }
+    }
+
+    // Trades only move funds between accounts, self-trades included, so per asset
+    // nothing may be lost or created
+    let top_ups = Quantity::from((WORKERS * ROUNDS.div_ceil(2)) as u64) * TOP_UP_USD;
+    assert_eq!(total_usd, INITIAL_USD * Quantity::from(ACCOUNTS as u64) + top_ups);
+    assert_eq!(total_btc, INITIAL_BTC * Quantity::from(ACCOUNTS as u64));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_settlement_in_memory() {
+    run_stress(AccountService::new()).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_settlement_on_one_worker() {
+    // Every account shares the worker, so trades between any two still settle one at a time
+    run_stress(AccountService::new().with_account_workers(1)).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_settlement_postgres() {
+    let Some(fixture) = PostgresFixture::start().await else { return };
+
+    let service = AccountService::with_repository(RepositoryType::Postgres(Some(fixture.database_url.clone())))
+        .await
+        .expect("Failed to create account service with PostgreSQL repository");
+
+    run_stress(service).await;
+}
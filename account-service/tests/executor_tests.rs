@@ -0,0 +1,105 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use account_service::executor::KeyedExecutor;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+// Two accounts that hash to different workers
+fn apart(executor: &KeyedExecutor) -> (Uuid, Uuid) {
+    let first = Uuid::new_v4();
+    loop {
+        let second = Uuid::new_v4();
+        if executor.worker_of(second) != executor.worker_of(first) {
+            return (first, second);
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_tasks_of_an_account_never_overlap() {
+    let executor = Arc::new(KeyedExecutor::new(8));
+    let account = Uuid::new_v4();
+    let running = Arc::new(AtomicUsize::new(0));
+    let finished = Arc::new(AtomicUsize::new(0));
+
+    let handles: Vec<_> = (0..32)
+        .map(|_| {
+            let (executor, running, finished) = (executor.clone(), running.clone(), finished.clone());
+            tokio::spawn(async move {
+                executor.run(&[account], async {
+                    assert_eq!(running.fetch_add(1, Ordering::SeqCst), 0, "tasks of one account overlapped");
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    finished.fetch_add(1, Ordering::SeqCst);
+                }).await;
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    assert_eq!(finished.load(Ordering::SeqCst), 32);
+}
+
+#[tokio::test]
+async fn test_accounts_on_other_workers_run_concurrently() {
+    let executor = Arc::new(KeyedExecutor::new(8));
+    let (blocked, free) = apart(&executor);
+    let (release, wait) = oneshot::channel::<()>();
+
+    // The first task only finishes once the second one has run
+    let waiting = {
+        let executor = executor.clone();
+        tokio::spawn(async move { executor.run(&[blocked], async { wait.await.unwrap() }).await })
+    };
+    tokio::task::yield_now().await;
+
+    tokio::time::timeout(Duration::from_secs(1), executor.run(&[free], async { release.send(()).unwrap() }))
+        .await
+        .expect("task of another worker was held back");
+    waiting.await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_tasks_spanning_workers_do_not_deadlock() {
+    let executor = Arc::new(KeyedExecutor::new(4));
+    let (first, second) = apart(&executor);
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    // Half the tasks name the accounts in the opposite order
+    let handles: Vec<_> = (0..64)
+        .map(|task| {
+            let (executor, runs) = (executor.clone(), runs.clone());
+            let accounts = if task % 2 == 0 { [first, second] } else { [second, first] };
+            tokio::spawn(async move {
+                executor.run(&accounts, async {
+                    tokio::task::yield_now().await;
+                    runs.fetch_add(1, Ordering::SeqCst);
+                }).await;
+            })
+        })
+        .collect();
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    })
+    .await
+    .expect("tasks spanning workers deadlocked");
+    assert_eq!(runs.load(Ordering::SeqCst), 64);
+}
+
+#[test]
+fn test_accounts_keep_their_worker() {
+    let executor = KeyedExecutor::new(16);
+    let account = Uuid::new_v4();
+
+    assert_eq!(executor.workers(), 16);
+    assert!(executor.worker_of(account) < 16);
+    assert_eq!(executor.worker_of(account), executor.worker_of(account));
+    assert_eq!(KeyedExecutor::new(0).workers(), 1);
+}
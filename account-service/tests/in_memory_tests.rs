@@ -1,8 +1,14 @@
+use std::sync::Arc;
+
 use common::decimal::{Quantity, dec};
-use common::model::account::{Account, Balance};
+use common::error::Error;
+use common::model::account::{Account, AccountPermissions, AdjustmentKind, Balance, BalanceAdjustment, ReasonCode, StatementEntryKind};
 use common::model::order::{Order, OrderType, Side, Status, TimeInForce};
 use common::model::trade::Trade;
-use account_service::{AccountService, InMemoryAccountRepository, RepositoryType};
+use account_service::{
+    sort_for_locking, AccountFilter, AccountRepository, AccountService, InMemoryAccountRepository, RepositoryType,
+    TotpSecondFactor,
+};
 use uuid::Uuid;
 
 // No longer needed as all tests are now using #[tokio::test]
@@ -18,8 +24,10 @@ async fn test_create_account() {
     // Add an account
     let account = Account {
         id: account_id,
+        external_id: None,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        closed_at: None,
     };
     _repo.accounts.insert(account_id, account);
     
@@ -100,6 +108,14 @@ async fn test_account_service_operations() {
         filled_quantity: Quantity::ZERO,
         remaining_quantity: dec!(2),
         average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
         time_in_force: TimeInForce::GTC,
         status: Status::New,
         created_at: chrono::Utc::now(),
@@ -141,6 +157,14 @@ async fn test_trade_execution() {
         filled_quantity: Quantity::ZERO,
         remaining_quantity: dec!(3),
         average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
         time_in_force: TimeInForce::GTC,
         status: Status::New,
         created_at: chrono::Utc::now(),
@@ -158,6 +182,14 @@ async fn test_trade_execution() {
         filled_quantity: Quantity::ZERO,
         remaining_quantity: dec!(3),
         average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
         time_in_force: TimeInForce::GTC,
         status: Status::New,
         created_at: chrono::Utc::now(),
@@ -180,7 +212,13 @@ async fn test_trade_execution() {
         quantity: dec!(3),
         amount: dec!(300), // 3 * 100
         taker_side: Side::Buy,
+        is_buyer_maker: false,
+        maker_fee: Quantity::ZERO,
+        maker_fee_asset: "USD".to_string(),
+        taker_fee: Quantity::ZERO,
+        taker_fee_asset: "BTC".to_string(),
         created_at: chrono::Utc::now(),
+        sequence: 0,
     };
     
     service.process_trade(&trade).await.unwrap();
@@ -204,4 +242,555 @@ async fn test_trade_execution() {
     assert_eq!(seller_btc.total, dec!(7)); // 10 - 3
     assert_eq!(seller_btc.available, dec!(7));
     assert_eq!(seller_btc.locked, Quantity::ZERO);
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_trade_settlement_deducts_fees_and_records_history() {
+    let service = AccountService::new();
+    
+    let buyer = service.create_account().await.unwrap();
+    let seller = service.create_account().await.unwrap();
+    service.deposit(buyer.id, "USD", dec!(1000)).await.unwrap();
+    service.deposit(seller.id, "BTC", dec!(10)).await.unwrap();
+    
+    let buy_order = Order::new_limit(buyer.id, "BTC/USD".to_string(), Side::Buy, dec!(100), dec!(2), TimeInForce::GTC);
+    let sell_order = Order::new_limit(seller.id, "BTC/USD".to_string(), Side::Sell, dec!(100), dec!(2), TimeInForce::GTC);
+    service.reserve_for_order(&buy_order).await.unwrap();
+    service.reserve_for_order(&sell_order).await.unwrap();
+    
+    // Seller rests, buyer takes
+    let mut trade = Trade::new(
+        "BTC/USD".to_string(),
+        dec!(100),
+        dec!(2),
+        buy_order.id,
+        sell_order.id,
+        buyer.id,
+        seller.id,
+        Side::Buy,
+    );
+    trade.maker_fee = dec!(0.2);
+    trade.taker_fee = dec!(0.004);
+    
+    service.process_trade(&trade).await.unwrap();
+    
+    // Each side receives its proceeds net of its fee
+    let buyer_btc = service.get_balance(buyer.id, "BTC").await.unwrap().unwrap();
+    let seller_usd = service.get_balance(seller.id, "USD").await.unwrap().unwrap();
+    assert_eq!(buyer_btc.total, dec!(1.996));
+    assert_eq!(seller_usd.total, dec!(199.8));
+    
+    // Both parties see the trade with its fee breakdown
+    for account_id in [buyer.id, seller.id] {
+        let trades = service.get_trades(account_id, 10);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].id, trade.id);
+        assert!(!trades[0].is_buyer_maker);
+        assert_eq!(trades[0].maker_fee_asset, "USD");
+        assert_eq!(trades[0].taker_fee_asset, "BTC");
+    }
+}
+
+#[tokio::test]
+async fn test_busting_a_trade_reverses_balances_fees_and_positions() {
+    let service = AccountService::new();
+    
+    let buyer = service.create_account().await.unwrap();
+    let seller = service.create_account().await.unwrap();
+    service.deposit(buyer.id, "USD", dec!(1000)).await.unwrap();
+    service.deposit(seller.id, "BTC", dec!(10)).await.unwrap();
+    
+    let buy_order = Order::new_limit(buyer.id, "BTC/USD".to_string(), Side::Buy, dec!(100), dec!(2), TimeInForce::GTC);
+    let sell_order = Order::new_limit(seller.id, "BTC/USD".to_string(), Side::Sell, dec!(100), dec!(2), TimeInForce::GTC);
+    service.reserve_for_order(&buy_order).await.unwrap();
+    service.reserve_for_order(&sell_order).await.unwrap();
+    let mut trade = Trade::new("BTC/USD".to_string(), dec!(100), dec!(2), buy_order.id, sell_order.id, buyer.id, seller.id, Side::Buy);
+    trade.maker_fee = dec!(0.2);
+    trade.taker_fee = dec!(0.004);
+    service.process_trade(&trade).await.unwrap();
+    
+    assert!(matches!(service.bust_trade(trade.id, " ", "admin").await, Err(Error::ValidationError(_))));
+    let bust = service.bust_trade(trade.id, "fat finger", "admin").await.unwrap();
+    assert_eq!(bust.buyer_fee_refund, dec!(0.004));
+    assert_eq!(bust.seller_fee_refund, dec!(0.2));
+    
+    // Both parties are back where they started, fees included
+    let balance = |account_id, asset| {
+        let service = &service;
+        async move { service.get_balance(account_id, asset).await.unwrap().unwrap() }
+    };
+    assert_eq!(balance(buyer.id, "USD").await.total, dec!(1000));
+    assert_eq!(balance(buyer.id, "USD").await.available, dec!(1000));
+    assert_eq!(balance(buyer.id, "BTC").await.total, dec!(0));
+    assert_eq!(balance(seller.id, "BTC").await.available, dec!(10));
+    assert_eq!(balance(seller.id, "USD").await.total, dec!(0));
+    assert!(service.get_positions(buyer.id).is_empty());
+    assert!(service.get_positions(seller.id).is_empty());
+    
+    // The trade is kept and can only be busted once
+    assert!(service.get_trade(trade.id).await.unwrap().is_some());
+    assert_eq!(service.get_trade_bust(trade.id).await.unwrap().unwrap().reason, "fat finger");
+    assert!(matches!(service.bust_trade(trade.id, "again", "admin").await, Err(Error::Conflict(_))));
+    assert!(matches!(service.bust_trade(Uuid::new_v4(), "unknown", "admin").await, Err(Error::OrderNotFound(_))));
+}
+
+#[tokio::test]
+async fn test_statement_lists_trades_busts_and_adjustments() {
+    let service = AccountService::new();
+    
+    let buyer = service.create_account().await.unwrap();
+    let seller = service.create_account().await.unwrap();
+    service.deposit(buyer.id, "USD", dec!(1000)).await.unwrap();
+    service.deposit(seller.id, "BTC", dec!(10)).await.unwrap();
+    let from = chrono::Utc::now();
+    
+    let buy_order = Order::new_limit(buyer.id, "BTC/USD".to_string(), Side::Buy, dec!(100), dec!(2), TimeInForce::GTC);
+    let sell_order = Order::new_limit(seller.id, "BTC/USD".to_string(), Side::Sell, dec!(100), dec!(2), TimeInForce::GTC);
+    service.reserve_for_order(&buy_order).await.unwrap();
+    service.reserve_for_order(&sell_order).await.unwrap();
+    let mut trade = Trade::new("BTC/USD".to_string(), dec!(100), dec!(2), buy_order.id, sell_order.id, buyer.id, seller.id, Side::Buy);
+    trade.taker_fee = dec!(0.004);
+    service.process_trade(&trade).await.unwrap();
+    service.bust_trade(trade.id, "fat finger", "admin").await.unwrap();
+    
+    let adjustment = |kind, amount, reason_code, note: Option<&str>| BalanceAdjustment {
+        id: Uuid::new_v4(),
+        account_id: buyer.id,
+        kind,
+        asset: "USD".to_string(),
+        amount,
+        reason_code,
+        note: note.map(str::to_string),
+        actor: "admin".to_string(),
+        created_at: chrono::Utc::now(),
+    };
+    let rebate = adjustment(AdjustmentKind::FeeRebate, dec!(2.5), ReasonCode::VolumeTier, None);
+    assert_eq!(service.adjust_balance(&rebate).await.unwrap().total, dec!(1002.5));
+    let debit = adjustment(AdjustmentKind::Manual, dec!(-2.5), ReasonCode::Other, Some("rebate posted twice"));
+    assert_eq!(service.adjust_balance(&debit).await.unwrap().total, dec!(1000));
+    
+    // Rebates only credit, and other reasons need a note
+    let bad_rebate = adjustment(AdjustmentKind::FeeRebate, dec!(-1), ReasonCode::VolumeTier, None);
+    assert!(matches!(service.adjust_balance(&bad_rebate).await, Err(Error::ValidationError(_))));
+    let unexplained = adjustment(AdjustmentKind::Manual, dec!(1), ReasonCode::Other, Some(" "));
+    assert!(matches!(service.adjust_balance(&unexplained).await, Err(Error::ValidationError(_))));
+    let overdraft = adjustment(AdjustmentKind::Manual, dec!(-1001), ReasonCode::ErrorCorrection, None);
+    assert!(matches!(service.adjust_balance(&overdraft).await, Err(Error::InsufficientBalance(_))));
+    
+    let statement = service.get_statement(buyer.id, from, chrono::Utc::now()).await.unwrap();
+    let kinds: Vec<StatementEntryKind> = statement.iter().map(|entry| entry.kind).collect();
+    assert_eq!(kinds, [
+        StatementEntryKind::Trade,
+        StatementEntryKind::Trade,
+        StatementEntryKind::TradeBust,
+        StatementEntryKind::TradeBust,
+        StatementEntryKind::FeeRebate,
+        StatementEntryKind::Adjustment,
+    ]);
+    assert_eq!((statement[0].asset.as_str(), statement[0].amount, statement[0].fee), ("BTC", dec!(1.996), dec!(0.004)));
+    assert_eq!((statement[1].asset.as_str(), statement[1].amount), ("USD", dec!(-200)));
+    assert_eq!((statement[2].amount, statement[2].fee), (dec!(-1.996), dec!(-0.004)));
+    assert_eq!(statement[2].note.as_deref(), Some("fat finger"));
+    assert_eq!(statement[4].reason_code, Some(ReasonCode::VolumeTier));
+    assert_eq!(statement[5].reference, Some(debit.id));
+}
+
+#[tokio::test]
+async fn test_permissions_gate_orders_and_withdrawals() {
+    let service = AccountService::new();
+    let account = service.create_account().await.unwrap();
+    service.deposit(account.id, "USD", dec!(1000)).await.unwrap();
+    assert_eq!(service.get_permissions(account.id).await.unwrap(), AccountPermissions::default());
+    
+    let permissions = AccountPermissions {
+        can_trade: true,
+        can_withdraw: false,
+        allowed_markets: Some(vec![" eth/usd ".to_string(), "ETH/USD".to_string()]),
+        max_leverage: Some(dec!(2)),
+    };
+    let permissions = service.set_permissions(account.id, permissions).await.unwrap();
+    assert_eq!(permissions.allowed_markets, Some(vec!["ETH/USD".to_string()]));
+    assert_eq!(permissions.scopes(), ["read", "trade", "market:ETH/USD", "leverage:2"]);
+    
+    // Only the allowed market can be traded, and nothing withdrawn
+    let order = |market: &str| Order::new_limit(account.id, market.to_string(), Side::Buy, dec!(100), dec!(1), TimeInForce::GTC);
+    assert!(matches!(service.reserve_for_order(&order("BTC/USD")).await, Err(Error::AuthorizationError(_))));
+    service.reserve_for_order(&order("ETH/USD")).await.unwrap();
+    assert!(matches!(service.withdraw(account.id, "USD", dec!(1)).await, Err(Error::AuthorizationError(_))));
+    
+    let suspended = AccountPermissions { can_trade: false, ..AccountPermissions::default() };
+    service.set_permissions(account.id, suspended).await.unwrap();
+    assert!(matches!(service.reserve_for_order(&order("ETH/USD")).await, Err(Error::AuthorizationError(_))));
+    assert_eq!(service.withdraw(account.id, "USD", dec!(1)).await.unwrap().available, dec!(899));
+    
+    // Leverage caps below 1 would block every order
+    let capped = AccountPermissions { max_leverage: Some(dec!(0.5)), ..AccountPermissions::default() };
+    assert!(matches!(service.set_permissions(account.id, capped).await, Err(Error::ValidationError(_))));
+    let unknown = service.set_permissions(Uuid::new_v4(), AccountPermissions::default()).await;
+    assert!(matches!(unknown, Err(Error::AccountNotFound(_))));
+}
+
+#[tokio::test]
+async fn test_reservations_track_each_order() {
+    let service = AccountService::new();
+    
+    let buyer = service.create_account().await.unwrap();
+    let seller = service.create_account().await.unwrap();
+    service.deposit(buyer.id, "USD", dec!(1000)).await.unwrap();
+    service.deposit(seller.id, "BTC", dec!(10)).await.unwrap();
+    
+    // Two open buys at different prices from the same account
+    let mut cheap = Order::new_limit(buyer.id, "BTC/USD".to_string(), Side::Buy, dec!(100), dec!(2), TimeInForce::GTC);
+    let mut dear = Order::new_limit(buyer.id, "BTC/USD".to_string(), Side::Buy, dec!(110), dec!(1), TimeInForce::GTC);
+    let sell = Order::new_limit(seller.id, "BTC/USD".to_string(), Side::Sell, dec!(105), dec!(3), TimeInForce::GTC);
+    service.reserve_for_order(&cheap).await.unwrap();
+    service.reserve_for_order(&dear).await.unwrap();
+    service.reserve_for_order(&sell).await.unwrap();
+    assert!(service.reserve_for_order(&cheap).await.is_err());
+    
+    let reservations = service.get_reservations(buyer.id);
+    assert_eq!(reservations.len(), 2);
+    assert_eq!(reservations[0].order_id, cheap.id);
+    assert_eq!(reservations[0].amount, dec!(200));
+    assert_eq!(reservations[1].amount, dec!(110));
+    
+    // Filling the dearer buy below its limit frees the unused 5 USD with it
+    let trade = Trade::new("BTC/USD".to_string(), dec!(105), dec!(1), dear.id, sell.id, buyer.id, seller.id, Side::Sell);
+    service.process_trade(&trade).await.unwrap();
+    dear.remaining_quantity = dec!(0);
+    let usd = service.get_balance(buyer.id, "USD").await.unwrap().unwrap();
+    assert_eq!(usd.locked, dec!(200));
+    assert_eq!(usd.total, dec!(895));
+    assert_eq!(service.get_reservations(buyer.id).len(), 1);
+    assert_eq!(service.get_reservations(seller.id)[0].amount, dec!(2));
+    
+    // Amending locks or unlocks only the difference
+    cheap.price = Some(dec!(102));
+    service.amend_reservation(&cheap).await.unwrap();
+    assert_eq!(service.get_balance(buyer.id, "USD").await.unwrap().unwrap().locked, dec!(204));
+    cheap.remaining_quantity = dec!(1);
+    service.amend_reservation(&cheap).await.unwrap();
+    assert_eq!(service.get_balance(buyer.id, "USD").await.unwrap().unwrap().locked, dec!(102));
+    
+    // An amend the account cannot fund leaves the reservation as it was
+    cheap.remaining_quantity = dec!(100);
+    assert!(service.amend_reservation(&cheap).await.is_err());
+    assert_eq!(service.get_reservations(buyer.id)[0].amount, dec!(102));
+    assert!(service.amend_reservation(&dear).await.is_err());
+    
+    // Canceling releases exactly what is left, once
+    service.release_reserved_funds(&cheap).await.unwrap();
+    service.release_reserved_funds(&cheap).await.unwrap();
+    let usd = service.get_balance(buyer.id, "USD").await.unwrap().unwrap();
+    assert_eq!(usd.locked, dec!(0));
+    assert_eq!(usd.available, dec!(895));
+    assert!(service.get_reservations(buyer.id).is_empty());
+}
+
+#[tokio::test]
+async fn test_reduce_only_orders_never_grow_the_position() {
+    let service = AccountService::new();
+
+    let buyer = service.create_account().await.unwrap();
+    let seller = service.create_account().await.unwrap();
+    service.deposit(buyer.id, "USD", dec!(1000)).await.unwrap();
+    service.deposit(buyer.id, "BTC", dec!(10)).await.unwrap();
+    service.deposit(seller.id, "BTC", dec!(10)).await.unwrap();
+
+    // Without a position there is nothing to reduce
+    let mut sell = Order::new_limit(buyer.id, "BTC/USD".to_string(), Side::Sell, dec!(100), dec!(1), TimeInForce::GTC);
+    sell.reduce_only = true;
+    assert!(matches!(service.clip_reduce_only(&mut sell), Err(Error::InvalidOrder(_))));
+    assert!(service.reserve_for_order(&sell).await.is_err());
+
+    // Buying 2 opens a long position
+    let buy = Order::new_limit(buyer.id, "BTC/USD".to_string(), Side::Buy, dec!(100), dec!(2), TimeInForce::GTC);
+    let ask = Order::new_limit(seller.id, "BTC/USD".to_string(), Side::Sell, dec!(100), dec!(2), TimeInForce::GTC);
+    service.reserve_for_order(&buy).await.unwrap();
+    service.reserve_for_order(&ask).await.unwrap();
+    let trade = Trade::new("BTC/USD".to_string(), dec!(100), dec!(2), buy.id, ask.id, buyer.id, seller.id, Side::Buy);
+    service.process_trade(&trade).await.unwrap();
+    let positions = service.get_positions(buyer.id);
+    assert_eq!(positions.len(), 1);
+    assert_eq!(positions[0].quantity, dec!(2));
+    assert_eq!(service.get_positions(seller.id)[0].quantity, dec!(-2));
+
+    // A reduce-only sell is clipped to the long, and a second one to what the first leaves
+    let mut sell = Order::new_limit(buyer.id, "BTC/USD".to_string(), Side::Sell, dec!(100), dec!(1.5), TimeInForce::GTC);
+    sell.reduce_only = true;
+    service.clip_reduce_only(&mut sell).unwrap();
+    assert_eq!(sell.quantity, dec!(1.5));
+    service.reserve_for_order(&sell).await.unwrap();
+
+    let mut second = Order::new_limit(buyer.id, "BTC/USD".to_string(), Side::Sell, dec!(100), dec!(3), TimeInForce::GTC);
+    second.reduce_only = true;
+    service.clip_reduce_only(&mut second).unwrap();
+    assert_eq!(second.quantity, dec!(0.5));
+    assert_eq!(second.remaining_quantity, dec!(0.5));
+
+    // An unclipped order over the position is refused at reservation
+    let mut unclipped = Order::new_limit(buyer.id, "BTC/USD".to_string(), Side::Sell, dec!(100), dec!(1), TimeInForce::GTC);
+    unclipped.reduce_only = true;
+    assert!(matches!(service.reserve_for_order(&unclipped).await, Err(Error::InvalidOrder(_))));
+
+    // Buying would only grow the long
+    let mut buy = Order::new_limit(buyer.id, "BTC/USD".to_string(), Side::Buy, dec!(100), dec!(1), TimeInForce::GTC);
+    buy.reduce_only = true;
+    assert!(service.clip_reduce_only(&mut buy).is_err());
+}
+
+#[test]
+fn test_totp_matches_rfc_6238_vectors() {
+    let secret = b"12345678901234567890";
+    assert_eq!(TotpSecondFactor::code_at(secret, 59), "287082");
+    assert_eq!(TotpSecondFactor::code_at(secret, 1111111109), "081804");
+    assert_eq!(TotpSecondFactor::code_at(secret, 1234567890), "005924");
+    
+    // A code is accepted within one step of drift, and only once
+    let totp = TotpSecondFactor::new();
+    let account_id = Uuid::new_v4();
+    assert!(!totp.verify_at(account_id, "287082", 59));
+    totp.enroll(account_id, secret.to_vec());
+    assert!(totp.verify_at(account_id, "287082", 80));
+    assert!(!totp.verify_at(account_id, "287082", 80));
+    assert!(!totp.verify_at(account_id, &TotpSecondFactor::code_at(secret, 0), 120));
+}
+
+#[tokio::test]
+async fn test_withdrawals_enforce_whitelist_and_second_factor() {
+    let totp = Arc::new(TotpSecondFactor::new());
+    let service = AccountService::new().with_second_factor(totp.clone());
+    
+    let account = service.create_account().await.unwrap();
+    service.deposit(account.id, "BTC", dec!(5)).await.unwrap();
+    service.deposit(account.id, "USD", dec!(100)).await.unwrap();
+    
+    // Without a whitelist or second factor withdrawals go anywhere
+    service.withdraw_to(account.id, "BTC", dec!(1), Some("bc1qanywhere"), None).await.unwrap();
+    
+    let entry = service.add_withdrawal_address(account.id, "BTC", "bc1qcold", Some("cold".to_string()), None).await.unwrap();
+    assert!(service.add_withdrawal_address(account.id, "BTC", "bc1qcold", None, None).await.is_err());
+    assert!(service.add_withdrawal_address(account.id, "BTC", "bc1 q", None, None).await.is_err());
+    
+    // Only whitelisted addresses receive the whitelisted asset
+    assert!(matches!(
+        service.withdraw_to(account.id, "BTC", dec!(1), Some("bc1qanywhere"), None).await,
+        Err(Error::AuthorizationError(_))
+    ));
+    assert!(service.withdraw_to(account.id, "BTC", dec!(1), None, None).await.is_err());
+    service.withdraw_to(account.id, "BTC", dec!(1), Some("bc1qcold"), None).await.unwrap();
+    service.withdraw_to(account.id, "USD", dec!(10), None, None).await.unwrap();
+    
+    // Once enrolled, withdrawals and whitelist changes need a valid code
+    let secret = b"12345678901234567890".to_vec();
+    totp.enroll(account.id, secret.clone());
+    assert!(matches!(
+        service.withdraw_to(account.id, "BTC", dec!(1), Some("bc1qcold"), None).await,
+        Err(Error::AuthorizationError(_))
+    ));
+    assert!(service.withdraw_to(account.id, "BTC", dec!(1), Some("bc1qcold"), Some("000000")).await.is_err());
+    assert!(service.remove_withdrawal_address(account.id, entry.id, None).is_err());
+    
+    let code = TotpSecondFactor::code_at(&secret, chrono::Utc::now().timestamp());
+    service.withdraw_to(account.id, "BTC", dec!(1), Some("bc1qcold"), Some(&code)).await.unwrap();
+    assert_eq!(service.get_balance(account.id, "BTC").await.unwrap().unwrap().total, dec!(2));
+    
+    totp.unenroll(account.id);
+    assert_eq!(service.remove_withdrawal_address(account.id, entry.id, None).unwrap().unwrap().address, "bc1qcold");
+    assert!(service.get_withdrawal_addresses(account.id).is_empty());
+}
+
+#[tokio::test]
+async fn test_accounts_listed_by_filter_and_external_id() {
+    let service = AccountService::new();
+    let tagged = service.create_account_with_external_id("desk-1").await.unwrap();
+    let untagged = service.create_account().await.unwrap();
+    let closed = service.create_account_with_external_id("desk-2").await.unwrap();
+    service.close_account(closed.id, false).await.unwrap();
+
+    assert!(matches!(service.create_account_with_external_id("desk-1").await, Err(Error::Conflict(_))));
+    assert!(matches!(service.set_external_id(untagged.id, Some("")).await, Err(Error::ValidationError(_))));
+    assert_eq!(service.find_by_external_id("desk-1").await.unwrap().unwrap().id, tagged.id);
+
+    // Clearing a reference frees it for another account
+    service.set_external_id(tagged.id, None).await.unwrap();
+    assert!(service.find_by_external_id("desk-1").await.unwrap().is_none());
+    service.set_external_id(untagged.id, Some("desk-1")).await.unwrap();
+
+    let open = AccountFilter { closed: Some(false), ..Default::default() };
+    let page = service.list_accounts(&open, None, 10).await.unwrap();
+    assert_eq!(page.accounts.len(), 2);
+    assert!(page.next_cursor.is_none());
+
+    let desks = AccountFilter { external_id_prefix: Some("desk-".to_string()), ..Default::default() };
+    let first = service.list_accounts(&desks, None, 1).await.unwrap();
+    let second = service.list_accounts(&desks, first.next_cursor, 1).await.unwrap();
+    let mut ids = vec![first.accounts[0].id, second.accounts[0].id];
+    ids.sort();
+    let mut expected = vec![untagged.id, closed.id];
+    expected.sort();
+    assert_eq!(ids, expected);
+}
+
+#[tokio::test]
+async fn test_balance_history_keeps_the_last_snapshot_of_each_interval() {
+    let service = AccountService::new();
+    let account = service.create_account().await.unwrap();
+    let day = chrono::DateTime::parse_from_rfc3339("2025-03-01T00:00:00Z").unwrap().with_timezone(&chrono::Utc);
+    let hours = chrono::Duration::hours;
+
+    service.deposit(account.id, "BTC", dec!(1)).await.unwrap();
+    service.deposit(account.id, "USD", dec!(100)).await.unwrap();
+    assert_eq!(service.snapshot_balances(None, day + hours(1)).await.unwrap(), 2);
+    service.deposit(account.id, "BTC", dec!(1)).await.unwrap();
+    assert_eq!(service.snapshot_balances(Some(account.id), day + hours(23)).await.unwrap(), 2);
+    service.deposit(account.id, "BTC", dec!(1)).await.unwrap();
+    service.snapshot_balances(None, day + hours(25)).await.unwrap();
+
+    // Snapshotting the same time again records nothing new
+    assert_eq!(service.snapshot_balances(None, day + hours(25)).await.unwrap(), 0);
+
+    let history = service
+        .balance_history(account.id, Some("BTC"), hours(24), day, day + hours(48))
+        .await
+        .unwrap();
+    let points: Vec<_> = history.iter().map(|snapshot| (snapshot.taken_at, snapshot.total)).collect();
+    assert_eq!(points, vec![(day + hours(23), dec!(2)), (day + hours(25), dec!(3))]);
+
+    let hourly = service.balance_history(account.id, None, hours(1), day, day + hours(24)).await.unwrap();
+    assert_eq!(hourly.len(), 4);
+    assert!(hourly.iter().all(|snapshot| snapshot.account_id == account.id));
+}
+
+#[tokio::test]
+async fn test_in_memory_transactions_apply_staged_changes_only_on_commit() {
+    let repo = InMemoryAccountRepository::new();
+    let account = repo.create_account().await.unwrap();
+    let mut balance = repo.ensure_balance(account.id, "USD").await.unwrap();
+    balance.deposit(dec!(100));
+    let trade = Trade {
+        id: Uuid::new_v4(),
+        market: "BTC/USD".to_string(),
+        buyer_id: account.id,
+        seller_id: Uuid::new_v4(),
+        buyer_order_id: Uuid::new_v4(),
+        seller_order_id: Uuid::new_v4(),
+        price: dec!(100),
+        quantity: dec!(1),
+        amount: dec!(100),
+        taker_side: Side::Buy,
+        is_buyer_maker: false,
+        maker_fee: Quantity::ZERO,
+        maker_fee_asset: "USD".to_string(),
+        taker_fee: Quantity::ZERO,
+        taker_fee_asset: "BTC".to_string(),
+        created_at: chrono::Utc::now(),
+        sequence: 0,
+    };
+    let usd = || async { repo.get_balance(account.id, "USD").await.unwrap().unwrap().total };
+
+    // Rolled back legs are never seen
+    let mut transaction = repo.begin_transaction().await.unwrap();
+    repo.update_balance_in(&mut transaction, balance.clone()).await.unwrap();
+    repo.save_trade_in(&mut transaction, &trade).await.unwrap();
+    assert_eq!(usd().await, Quantity::ZERO);
+    transaction.rollback().await.unwrap();
+    assert_eq!(usd().await, Quantity::ZERO);
+    assert!(repo.get_order_trades(trade.buyer_order_id).await.unwrap().is_empty());
+
+    // Dropping a transaction discards its legs too
+    let mut transaction = repo.begin_transaction().await.unwrap();
+    repo.update_balance_in(&mut transaction, balance.clone()).await.unwrap();
+    drop(transaction);
+    assert_eq!(usd().await, Quantity::ZERO);
+
+    // Committed legs are applied together
+    let mut transaction = repo.begin_transaction().await.unwrap();
+    repo.update_balance_in(&mut transaction, balance).await.unwrap();
+    repo.save_trade_in(&mut transaction, &trade).await.unwrap();
+    assert!(repo.get_order_trades(trade.seller_order_id).await.unwrap().is_empty());
+    transaction.commit().await.unwrap();
+    assert_eq!(usd().await, dec!(100));
+    assert_eq!(repo.get_order_trades(trade.seller_order_id).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_self_trade_settlement_conserves_balances() {
+    let service = AccountService::new();
+    let account = service.create_account().await.unwrap();
+    service.deposit(account.id, "USD", dec!(1000)).await.unwrap();
+    service.deposit(account.id, "BTC", dec!(1)).await.unwrap();
+    
+    let buy_order = Order::new_limit(account.id, "BTC/USD".to_string(), Side::Buy, dec!(100), dec!(1), TimeInForce::GTC);
+    let sell_order = Order::new_limit(account.id, "BTC/USD".to_string(), Side::Sell, dec!(100), dec!(1), TimeInForce::GTC);
+    service.reserve_for_order(&buy_order).await.unwrap();
+    service.reserve_for_order(&sell_order).await.unwrap();
+    
+    // The account buys its own BTC, paying a USD fee as seller
+    let trade = Trade {
+        id: Uuid::new_v4(),
+        market: "BTC/USD".to_string(),
+        buyer_id: account.id,
+        seller_id: account.id,
+        buyer_order_id: buy_order.id,
+        seller_order_id: sell_order.id,
+        price: dec!(100),
+        quantity: dec!(1),
+        amount: dec!(100),
+        taker_side: Side::Sell,
+        is_buyer_maker: true,
+        maker_fee: Quantity::ZERO,
+        maker_fee_asset: "BTC".to_string(),
+        taker_fee: dec!(1),
+        taker_fee_asset: "USD".to_string(),
+        created_at: chrono::Utc::now(),
+        sequence: 1,
+    };
+    service.process_trade(&trade).await.unwrap();
+    
+    // Only the fee leaves the account and nothing stays locked
+    let usd = service.get_balance(account.id, "USD").await.unwrap().unwrap();
+    let btc = service.get_balance(account.id, "BTC").await.unwrap().unwrap();
+    assert_eq!((usd.total, usd.available, usd.locked), (dec!(999), dec!(999), Quantity::ZERO));
+    assert_eq!((btc.total, btc.available, btc.locked), (dec!(1), dec!(1), Quantity::ZERO));
+}
+
+#[tokio::test]
+async fn test_balances_written_twice_in_one_transaction_are_refused() {
+    let repo = InMemoryAccountRepository::new();
+    let account_id = Uuid::new_v4();
+    let mut transaction = repo.begin_transaction().await.unwrap();
+    
+    let result = repo.update_balances_in(&mut transaction, vec![
+        Balance::new(account_id, "USD".to_string()),
+        Balance::new(account_id, "BTC".to_string()),
+        Balance::new(account_id, "USD".to_string()),
+    ]).await;
+    assert!(matches!(result, Err(Error::Internal(_))), "{:?}", result);
+}
+
+#[test]
+fn test_balances_sort_into_lock_order() {
+    let (first, second) = {
+        let mut ids = [Uuid::new_v4(), Uuid::new_v4()];
+        ids.sort();
+        (ids[0], ids[1])
+    };
+    let mut funded = Balance::new(second, "BTC".to_string());
+    funded.deposit(dec!(1));
+    let mut balances = vec![
+        Balance::new(second, "BTC".to_string()),
+        Balance::new(first, "USD".to_string()),
+        funded,
+        Balance::new(first, "BTC".to_string()),
+    ];
+
+    sort_for_locking(&mut balances);
+    let keys: Vec<_> = balances.iter().map(|balance| (balance.account_id, balance.asset.as_str(), balance.total)).collect();
+    // Writes of the same balance keep their order
+    assert_eq!(keys, vec![
+        (first, "BTC", Quantity::ZERO),
+        (first, "USD", Quantity::ZERO),
+        (second, "BTC", Quantity::ZERO),
+        (second, "BTC", dec!(1)),
+    ]);
+}
@@ -56,8 +56,10 @@ mod tests {
         let account_id = Uuid::new_v4();
         let account = Account {
             id: account_id,
+            external_id: None,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
+            closed_at: None,
         };
         repo.accounts.insert(account_id, account);
         
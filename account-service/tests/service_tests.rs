@@ -5,11 +5,7 @@ use common::model::order::{Order, OrderType, Side, Status, TimeInForce};
 use common::model::trade::Trade;
 use account_service::{AccountService, RepositoryType};
 use tokio::runtime::Runtime;
-#[cfg(not(feature = "db_tests"))]
-use std::env;
-
-// Focus on in-memory tests for now
-const SKIP_POSTGRES_TESTS: bool = true;
+use common::db::fixture::PostgresFixture;
 
 // Helper function to run async tests 
 fn run_async<F>(test: F)
@@ -25,6 +21,34 @@ where
     });
 }
 
+// Two accounts, one closed, with USD partly locked for an order
+async fn assert_account_totals(service: &AccountService) {
+    let open = service.create_account().await.unwrap();
+    service.deposit(open.id, "USD", dec!(100)).await.unwrap();
+    service.deposit(open.id, "BTC", dec!(2)).await.unwrap();
+    let order = Order::new_limit(open.id, "BTC/USD".to_string(), Side::Buy, dec!(10), dec!(1), TimeInForce::GTC);
+    service.reserve_for_order(&order).await.unwrap();
+
+    let closed = service.create_account().await.unwrap();
+    service.deposit(closed.id, "USD", dec!(50)).await.unwrap();
+    service.withdraw(closed.id, "USD", dec!(50)).await.unwrap();
+    service.close_account(closed.id, false).await.unwrap();
+
+    let totals = service.totals().await.unwrap();
+    assert_eq!(totals.accounts, 2);
+    assert_eq!(totals.closed, 1);
+
+    let assets: Vec<&str> = totals.balances.iter().map(|total| total.asset.as_str()).collect();
+    assert_eq!(assets, ["BTC", "USD"]);
+    let usd = &totals.balances[1];
+    // The closed account's empty balance is not a holding
+    assert_eq!(usd.holders, 1);
+    assert_eq!(usd.total, dec!(100));
+    assert_eq!(usd.available, dec!(90));
+    assert_eq!(usd.locked, dec!(10));
+    assert_eq!(totals.balances[0].total, dec!(2));
+}
+
 // In-memory repository tests
 mod in_memory_tests {
     use super::*;
@@ -143,6 +167,14 @@ mod in_memory_tests {
                     created_at: chrono::Utc::now(),
                     updated_at: chrono::Utc::now(),
                     average_fill_price: None,
+                    reject_reason: None,
+                    reject_message: None,
+                    client_order_id: None,
+                    sequence: 0,
+                    max_slippage_bps: None,
+                    peg: None,
+                    min_fill_quantity: None,
+                    reduce_only: false,
                 };
                 
                 // Reserve funds
@@ -188,6 +220,14 @@ mod in_memory_tests {
                     created_at: chrono::Utc::now(),
                     updated_at: chrono::Utc::now(),
                     average_fill_price: None,
+                    reject_reason: None,
+                    reject_message: None,
+                    client_order_id: None,
+                    sequence: 0,
+                    max_slippage_bps: None,
+                    peg: None,
+                    min_fill_quantity: None,
+                    reduce_only: false,
                 };
                 
                 let sell_order = Order {
@@ -205,6 +245,14 @@ mod in_memory_tests {
                     created_at: chrono::Utc::now(),
                     updated_at: chrono::Utc::now(),
                     average_fill_price: None,
+                    reject_reason: None,
+                    reject_message: None,
+                    client_order_id: None,
+                    sequence: 0,
+                    max_slippage_bps: None,
+                    peg: None,
+                    min_fill_quantity: None,
+                    reduce_only: false,
                 };
                 
                 // Lock funds
@@ -223,7 +271,13 @@ mod in_memory_tests {
                     quantity: dec!(0.1),
                     amount: dec!(1000), // 0.1 BTC * 10000 USD
                     taker_side: Side::Buy,
+                    is_buyer_maker: false,
+                    maker_fee: Quantity::ZERO,
+                    maker_fee_asset: "USD".to_string(),
+                    taker_fee: Quantity::ZERO,
+                    taker_fee_asset: "BTC".to_string(),
                     created_at: chrono::Utc::now(),
+                    sequence: 0,
                 };
                 
                 // Process trade
@@ -254,38 +308,83 @@ mod in_memory_tests {
             })
         });
     }
+    
+    #[test]
+    fn test_close_account() {
+        run_async(|| {
+            Box::pin(async move {
+                let service = AccountService::new();
+                let account = service.create_account().await.unwrap();
+                service.deposit(account.id, "USD", dec!(100)).await.unwrap();
+                
+                // Funds must be withdrawn first
+                let result = service.close_account(account.id, false).await;
+                assert!(matches!(result, Err(Error::ValidationError(_))));
+                
+                service.withdraw(account.id, "USD", dec!(100)).await.unwrap();
+                let closed = service.close_account(account.id, false).await.unwrap();
+                assert!(closed.is_closed());
+                
+                // The record stays, but funds can no longer move
+                assert!(service.get_account(account.id).await.unwrap().unwrap().is_closed());
+                assert_eq!(service.get_balances(account.id).await.unwrap().len(), 1);
+                let result = service.deposit(account.id, "USD", dec!(1)).await;
+                assert!(matches!(result, Err(Error::AuthorizationError(_))));
+                let result = service.close_account(account.id, false).await;
+                assert!(matches!(result, Err(Error::Conflict(_))));
+            })
+        });
+    }
+    
+    #[test]
+    fn test_account_totals() {
+        run_async(|| {
+            Box::pin(async move {
+                assert_account_totals(&AccountService::new()).await;
+            })
+        });
+    }
+    
+    #[test]
+    fn test_force_close_account_with_funds() {
+        run_async(|| {
+            Box::pin(async move {
+                let service = AccountService::new();
+                let account = service.create_account().await.unwrap();
+                service.deposit(account.id, "USD", dec!(100)).await.unwrap();
+                
+                let closed = service.close_account(account.id, true).await.unwrap();
+                assert!(closed.is_closed());
+                
+                let order = Order::new_limit(account.id, "BTC/USD".to_string(), Side::Buy, dec!(10), dec!(1), TimeInForce::GTC);
+                let result = service.reserve_for_order(&order).await;
+                assert!(matches!(result, Err(Error::AuthorizationError(_))));
+                let balance = service.get_balance(account.id, "USD").await.unwrap().unwrap();
+                assert_eq!(balance.total, dec!(100));
+            })
+        });
+    }
 }
 
 // PostgreSQL repository tests
 mod postgres_tests {
     use super::*;
     
-    // Helper to create a PostgreSQL service
-    async fn create_postgres_service() -> Result<AccountService, Error> {
-        // Skip if postgres tests are disabled
-        if SKIP_POSTGRES_TESTS {
-            return Err(Error::Internal("PostgreSQL tests are disabled".to_string()));
-        }
-        
-        // Check for TEST_DATABASE_URL
-        let _db_url = match env::var("TEST_DATABASE_URL") {
-            Ok(url) => url,
-            Err(_) => {
-                return Err(Error::Internal("TEST_DATABASE_URL not set".to_string()));
-            }
-        };
-        
-        // Create service with PostgreSQL repository
-        AccountService::with_repository(RepositoryType::Postgres(None)).await
+    // Helper to create a PostgreSQL service backed by a disposable database
+    async fn create_postgres_service() -> Option<(PostgresFixture, AccountService)> {
+        let fixture = PostgresFixture::start().await?;
+        let service = AccountService::with_repository(RepositoryType::Postgres(Some(fixture.database_url.clone())))
+            .await
+            .expect("Failed to create account service with PostgreSQL repository");
+        Some((fixture, service))
     }
     
     #[test]
     fn test_postgres_create_account() {
         run_async(|| {
             Box::pin(async move {
-                let service = match create_postgres_service().await {
-                    Ok(svc) => svc,
-                    Err(_) => return, // Skip test
+                let Some((_db, service)) = create_postgres_service().await else {
+                    return; // No database available
                 };
                 
                 let account = service.create_account().await.unwrap();
@@ -300,10 +399,9 @@ mod postgres_tests {
     fn test_postgres_deposit() {
         run_async(|| {
             Box::pin(async move {
-                // Skipped if PostgreSQL tests are disabled
-                let service = match create_postgres_service().await {
-                    Ok(svc) => svc,
-                    Err(_) => return,
+                // Skipped only with SKIP_POSTGRES_TESTS=1
+                let Some((_db, service)) = create_postgres_service().await else {
+                    return;
                 };
                 
                 let account = service.create_account().await.unwrap();
@@ -321,10 +419,9 @@ mod postgres_tests {
     fn test_postgres_order_and_trade() {
         run_async(|| {
             Box::pin(async move {
-                // Skipped if PostgreSQL tests are disabled
-                let service = match create_postgres_service().await {
-                    Ok(svc) => svc,
-                    Err(_) => return,
+                // Skipped only with SKIP_POSTGRES_TESTS=1
+                let Some((_db, service)) = create_postgres_service().await else {
+                    return;
                 };
                 
                 // Create test accounts
@@ -351,6 +448,14 @@ mod postgres_tests {
                     created_at: chrono::Utc::now(),
                     updated_at: chrono::Utc::now(),
                     average_fill_price: None,
+                    reject_reason: None,
+                    reject_message: None,
+                    client_order_id: None,
+                    sequence: 0,
+                    max_slippage_bps: None,
+                    peg: None,
+                    min_fill_quantity: None,
+                    reduce_only: false,
                 };
                 
                 // Reserve funds
@@ -363,4 +468,37 @@ mod postgres_tests {
             })
         });
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_postgres_close_account() {
+        run_async(|| {
+            Box::pin(async move {
+                // Skipped only with SKIP_POSTGRES_TESTS=1
+                let Some((_db, service)) = create_postgres_service().await else {
+                    return;
+                };
+                
+                let account = service.create_account().await.unwrap();
+                let closed = service.close_account(account.id, false).await.unwrap();
+                assert!(closed.is_closed());
+                
+                let stored = service.get_account(account.id).await.unwrap().unwrap();
+                assert_eq!(stored.closed_at, closed.closed_at);
+            })
+        });
+    }
+    
+    #[test]
+    fn test_postgres_account_totals() {
+        run_async(|| {
+            Box::pin(async move {
+                // Skipped only with SKIP_POSTGRES_TESTS=1
+                let Some((_db, service)) = create_postgres_service().await else {
+                    return;
+                };
+                
+                assert_account_totals(&service).await;
+            })
+        });
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use account_service::settlement::{BankFileConfig, BankFileSettlementAdapter};
+use account_service::{AccountService, DepositConfirmation, MockSettlementAdapter, SettlementAdapter};
+use common::decimal::dec;
+
+#[tokio::test]
+async fn test_withdrawals_pay_out_and_refund_failed_payouts() {
+    let adapter = Arc::new(MockSettlementAdapter::for_assets(&["BTC"]));
+    let service = AccountService::new().with_settlement_adapter(adapter.clone());
+
+    let account = service.create_account().await.unwrap();
+    service.deposit(account.id, "BTC", dec!(2)).await.unwrap();
+    service.deposit(account.id, "USD", dec!(100)).await.unwrap();
+
+    let balance = service.withdraw_to(account.id, "BTC", dec!(0.5), Some("bc1qdest"), None).await.unwrap();
+    assert_eq!(balance.total, dec!(1.5));
+    let payouts = adapter.payouts().await;
+    assert_eq!(payouts.len(), 1);
+    assert_eq!(payouts[0].0.account_id, account.id);
+    assert_eq!(payouts[0].0.amount, dec!(0.5));
+    assert_eq!(payouts[0].0.address.as_deref(), Some("bc1qdest"));
+    assert_eq!(payouts[0].1, format!("mock-{}", payouts[0].0.id));
+
+    // Assets without an adapter settle internally only
+    service.withdraw_to(account.id, "USD", dec!(10), None, None).await.unwrap();
+    assert_eq!(adapter.payouts().await.len(), 1);
+
+    // A rejected payout returns the funds
+    adapter.set_failing(true);
+    assert!(service.withdraw_to(account.id, "BTC", dec!(1), Some("bc1qdest"), None).await.is_err());
+    let btc = service.get_balance(account.id, "BTC").await.unwrap().unwrap();
+    assert_eq!(btc.total, dec!(1.5));
+    assert_eq!(btc.available, dec!(1.5));
+}
+
+#[tokio::test]
+async fn test_confirmed_deposits_are_credited_once() {
+    let adapter = Arc::new(MockSettlementAdapter::new());
+    let service = AccountService::new().with_settlement_adapter(adapter.clone());
+    let account = service.create_account().await.unwrap();
+
+    let confirmation = DepositConfirmation {
+        reference: "tx-1".to_string(),
+        account_id: account.id,
+        asset: "BTC".to_string(),
+        amount: dec!(0.25),
+    };
+    adapter.confirm_deposit(confirmation.clone()).await;
+    adapter.confirm_deposit(confirmation.clone()).await;
+    adapter.confirm_deposit(DepositConfirmation { account_id: uuid::Uuid::new_v4(), reference: "tx-2".to_string(), ..confirmation.clone() }).await;
+
+    assert_eq!(service.sync_deposits().await.unwrap(), 1);
+    assert_eq!(service.sync_deposits().await.unwrap(), 0);
+    assert!(service.credit_deposit("mock", &confirmation).await.unwrap().is_none());
+    assert_eq!(service.get_balance(account.id, "BTC").await.unwrap().unwrap().total, dec!(0.25));
+}
+
+#[tokio::test]
+async fn test_bank_file_adapter_exchanges_csv_files() {
+    let dir = std::env::temp_dir().join(format!("bank-file-{}", uuid::Uuid::new_v4()));
+    let adapter = Arc::new(BankFileSettlementAdapter::new(BankFileConfig {
+        outbox: dir.join("outbox"),
+        inbox: dir.join("inbox"),
+        assets: vec!["USD".to_string()],
+    }));
+    let service = AccountService::new().with_settlement_adapter(adapter.clone());
+    let account = service.create_account().await.unwrap();
+    service.deposit(account.id, "USD", dec!(500)).await.unwrap();
+
+    // Payout instructions are appended to the day's file
+    service.withdraw_to(account.id, "USD", dec!(100), Some("DE89370400440532013000"), None).await.unwrap();
+    service.withdraw_to(account.id, "USD", dec!(50), Some("DE89370400440532013000"), None).await.unwrap();
+    let files: Vec<_> = std::fs::read_dir(dir.join("outbox")).unwrap().map(|entry| entry.unwrap().path()).collect();
+    assert_eq!(files.len(), 1);
+    let payouts = std::fs::read_to_string(&files[0]).unwrap();
+    let lines: Vec<&str> = payouts.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("payout_id,account_id"));
+    assert!(lines[1].contains(&format!("{},USD,100,DE89370400440532013000", account.id)));
+    assert!(service.withdraw_to(account.id, "USD", dec!(1), Some("DE89,evil"), None).await.is_err());
+    assert_eq!(service.get_balance(account.id, "USD").await.unwrap().unwrap().total, dec!(350));
+
+    // Statements are credited, skipping bad lines, and not read twice
+    std::fs::create_dir_all(dir.join("inbox")).unwrap();
+    std::fs::write(
+        dir.join("inbox/statement-1.csv"),
+        format!("reference,account_id,asset,amount\nREF1,{id},USD,250\nREF2,{id},USD,-5\nREF3,not-an-id,USD,5\n", id = account.id),
+    ).unwrap();
+    assert_eq!(service.sync_deposits().await.unwrap(), 1);
+    assert!(dir.join("inbox/statement-1.csv.processed").exists());
+    assert!(adapter.poll_deposits().await.unwrap().is_empty());
+    assert_eq!(service.get_balance(account.id, "USD").await.unwrap().unwrap().total, dec!(600));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,84 @@
+//! Example WebSocket client
+//!
+//! Subscribes to the order book, trade and ticker channels of a market and
+//! prints every notification. Depth updates carry a per-market sequence number;
+//! the client reports any gap it sees.
+//!
+//! Usage: `cargo run -p api-gateway --example ws_client -- [ws://localhost:8081/ws] [BTC/USD]`
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let url = args.next().unwrap_or_else(|| "ws://localhost:8081/ws".to_string());
+    let market = args.next().unwrap_or_else(|| "BTC/USD".to_string());
+
+    let (socket, _) = connect_async(url.as_str()).await?;
+    println!("Connected to {}", url);
+    let (mut sender, mut receiver) = socket.split();
+
+    // Subscribe to every per-market channel
+    for (id, channel) in ["orderbook", "trades", "ticker"].iter().enumerate() {
+        let request = json!({
+            "id": (id + 1).to_string(),
+            "method": "subscribe",
+            "params": { "channel": channel, "market": market },
+        });
+        sender.send(Message::Text(request.to_string())).await?;
+    }
+
+    let mut last_sequence: Option<u64> = None;
+    let mut ping = tokio::time::interval(std::time::Duration::from_secs(30));
+
+    loop {
+        tokio::select! {
+            message = receiver.next() => {
+                let Some(message) = message else { break };
+                let Message::Text(text) = message? else { continue };
+                let message: Value = serde_json::from_str(&text)?;
+
+                match message.get("method").and_then(Value::as_str) {
+                    Some("orderbook") => {
+                        let data = &message["params"]["data"];
+                        let sequence = data["sequence"].as_u64().unwrap_or_default();
+                        if let Some(last) = last_sequence {
+                            if sequence != last + 1 {
+                                println!("Gap in depth updates: {} -> {}", last, sequence);
+                            }
+                        }
+                        last_sequence = Some(sequence);
+                        println!(
+                            "[orderbook #{}] best bid {} / best ask {}",
+                            sequence, data["bids"][0]["price"], data["asks"][0]["price"]
+                        );
+                    }
+                    Some("trades") => {
+                        let data = &message["params"]["data"];
+                        println!(
+                            "[trade] {} {} @ {} ({} taker)",
+                            data["market"], data["quantity"], data["price"], data["taker_side"]
+                        );
+                    }
+                    Some("ticker") => {
+                        let data = &message["params"]["data"];
+                        println!("[ticker] bid {} ask {} last {}", data["bid"], data["ask"], data["last"]);
+                    }
+                    _ if message.get("error").is_some() => println!("Error: {}", message["error"]),
+                    _ => println!("Response: {}", message),
+                }
+            }
+            _ = ping.tick() => {
+                sender.send(Message::Text(json!({ "id": "ping", "method": "ping", "params": {} }).to_string())).await?;
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    let _ = sender.close().await;
+    println!("Disconnected");
+    Ok(())
+}
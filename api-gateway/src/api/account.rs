@@ -4,27 +4,58 @@
 //! - Create account
 //! - Get account details
 //! - Get account balances
+//! - Get funds reserved for open orders
+//! - Get net positions from settled trades
 //! - Deposit and withdraw funds
+//! - Get settled trades
+//! - Value balances in a quote currency
+//! - Get balance history from daily snapshots
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use axum::{
-    extract::{Path, State},
-    Json,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
 };
-use common::decimal::Quantity;
-use common::model::account::{Account, Balance};
-use serde::Deserialize;
+use common::decimal::{format_amount, Quantity};
+use common::error::Error;
+use chrono::{DateTime, Utc};
+use common::model::account::{Account, Balance, BalanceSnapshot, Position, Reservation};
+use common::model::trade::Trade;
+use market_data::CandleInterval;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use uuid::Uuid;
 use utoipa::ToSchema;
 
+use crate::auth::{second_factor_code, AuthContext, DEFAULT_SCOPES};
 use crate::error::ApiError;
+use crate::notification::NotificationKind;
+use crate::security::SecurityEventKind;
+use crate::valuation::{value_balances, Portfolio};
+use crate::webhook::WebhookEventType;
 use crate::AppState;
-use crate::api::response::{ApiResponse, ApiListResponse};
+use crate::api::response::{ApiResponse, ApiListResponse, Created};
 
 /// Create account request
 #[derive(Debug, Deserialize, ToSchema)]
-pub struct CreateAccountRequest {}
+pub struct CreateAccountRequest {
+    /// Reference of the account in an external system, unique
+    #[serde(default)]
+    pub external_id: Option<String>,
+}
+
+/// Newly created account with its API key
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AccountCreated {
+    /// The account
+    #[serde(flatten)]
+    pub account: Account,
+    /// API key for trading and account endpoints, sent as `X-API-Key`
+    pub api_key: String,
+}
 
 /// Create a new account
 #[utoipa::path(
@@ -32,33 +63,49 @@ pub struct CreateAccountRequest {}
     path = "/api/v1/accounts",
     request_body = CreateAccountRequest,
     responses(
-        (status = 200, description = "Account successfully created"),
+        (status = 201, description = "Account successfully created, with its path in Location"),
         (status = 400, description = "Bad request"),
+        (status = 409, description = "Another account has the external ID"),
         (status = 500, description = "Internal server error")
     ),
     tag = "account"
 )]
 pub async fn create_account(
     State(state): State<Arc<AppState>>,
-    Json(_request): Json<CreateAccountRequest>,
-) -> Result<ApiResponse<Account>, ApiError> {
-    let account = state.account_service.create_account().await
-        .map_err(ApiError::Common)?;
-    
+    connect: Option<ConnectInfo<SocketAddr>>,
+    Json(request): Json<CreateAccountRequest>,
+) -> Result<Created<AccountCreated>, ApiError> {
+    let account = match &request.external_id {
+        Some(external_id) => state.account_service.create_account_with_external_id(external_id).await,
+        None => state.account_service.create_account().await,
+    }
+    .map_err(ApiError::Common)?;
+    let (api_key, key) = state.api_keys.issue_scoped(account.id, DEFAULT_SCOPES.into_iter().collect(), Vec::new());
+    state.security.record(
+        account.id,
+        SecurityEventKind::ApiKeyCreated,
+        Some(&key),
+        connect.map(|ConnectInfo(addr)| addr.ip()),
+        json!({ "issued_by": "signup", "scopes": key.scopes }),
+    );
+
     // Create a standardized response
-    let response = ApiResponse::new(account);
-    Ok(response)
+    let location = format!("/api/v1/accounts/{}", account.id);
+    Ok(Created::new(location, AccountCreated { account, api_key }))
 }
 
 /// Get an account by ID
 #[utoipa::path(
     get,
     path = "/api/v1/accounts/{id}",
+    security(("api_key" = [])),
     params(
         ("id" = Uuid, Path, description = "Account ID")
     ),
     responses(
         (status = 200, description = "Account details retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
         (status = 404, description = "Account not found"),
         (status = 500, description = "Internal server error")
     ),
@@ -66,8 +113,12 @@ pub async fn create_account(
 )]
 pub async fn get_account(
     State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
     Path(id): Path<Uuid>,
 ) -> Result<ApiResponse<Account>, ApiError> {
+    auth.ensure_account(id)?;
+    state.settlement.flush(id).await;
+
     // Request the account from the service
     let account = state.account_service.get_account(id).await
         .map_err(ApiError::Common)?
@@ -81,11 +132,14 @@ pub async fn get_account(
 #[utoipa::path(
     get,
     path = "/api/v1/accounts/{id}/balances",
+    security(("api_key" = [])),
     params(
         ("id" = Uuid, Path, description = "Account ID")
     ),
     responses(
         (status = 200, description = "Account balances retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
         (status = 404, description = "Account not found"),
         (status = 500, description = "Internal server error")
     ),
@@ -93,8 +147,12 @@ pub async fn get_account(
 )]
 pub async fn get_balances(
     State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
     Path(id): Path<Uuid>,
 ) -> Result<ApiListResponse<Balance>, ApiError> {
+    auth.ensure_account(id)?;
+    state.settlement.flush(id).await;
+
     // Verify the account exists before fetching balances
     let _ = state.account_service.get_account(id).await
         .map_err(ApiError::Common)?
@@ -108,6 +166,58 @@ pub async fn get_balances(
     Ok(ApiListResponse::new(balances))
 }
 
+/// Get the funds reserved for an account's open orders, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/reservations",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Reservations retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account")
+    ),
+    tag = "account"
+)]
+pub async fn get_reservations(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiListResponse<Reservation>, ApiError> {
+    auth.ensure_account(id)?;
+    state.settlement.flush(id).await;
+
+    Ok(ApiListResponse::new(state.account_service.get_reservations(id)))
+}
+
+/// Get an account's open positions from its settled trades, by market
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/positions",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Positions retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account")
+    ),
+    tag = "account"
+)]
+pub async fn get_positions(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiListResponse<Position>, ApiError> {
+    auth.ensure_account(id)?;
+    state.settlement.flush(id).await;
+
+    Ok(ApiListResponse::new(state.account_service.get_positions(id)))
+}
+
 /// Deposit request
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct DepositRequest {
@@ -121,12 +231,15 @@ pub struct DepositRequest {
 #[utoipa::path(
     post,
     path = "/api/v1/accounts/{id}/deposit",
+    security(("api_key" = [])),
     params(
         ("id" = Uuid, Path, description = "Account ID")
     ),
     request_body = DepositRequest,
     responses(
         (status = 200, description = "Funds deposited successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
         (status = 404, description = "Account not found"),
         (status = 400, description = "Invalid deposit request"),
         (status = 500, description = "Internal server error")
@@ -135,12 +248,22 @@ pub struct DepositRequest {
 )]
 pub async fn deposit(
     State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
     Path(id): Path<Uuid>,
     Json(request): Json<DepositRequest>,
 ) -> Result<ApiResponse<Balance>, ApiError> {
+    auth.ensure_account(id)?;
+    state.account_service.check_deposit(&request.asset, request.amount)
+        .map_err(ApiError::Common)?;
+
     // Call the service to deposit funds
     let balance = state.account_service.deposit(id, &request.asset, request.amount).await
         .map_err(ApiError::Common)?;
+    state.webhooks.notify(id, WebhookEventType::Deposit, json!({
+        "asset": request.asset,
+        "amount": request.amount,
+        "balance": balance,
+    }));
     
     // Return a standardized response with the updated balance
     Ok(ApiResponse::new(balance))
@@ -153,18 +276,24 @@ pub struct WithdrawRequest {
     pub asset: String,
     /// Amount
     pub amount: Quantity,
+    /// Destination address, required once the account whitelists addresses for the asset
+    pub address: Option<String>,
 }
 
 /// Withdraw funds from an account
 #[utoipa::path(
     post,
     path = "/api/v1/accounts/{id}/withdraw",
+    security(("api_key" = [])),
     params(
         ("id" = Uuid, Path, description = "Account ID")
     ),
     request_body = WithdrawRequest,
     responses(
         (status = 200, description = "Funds withdrawn successfully"),
+        (status = 202, description = "Funds withdrawn and handed to the asset's custodian to pay out"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account or lacks the withdraw scope, address not whitelisted or second-factor code missing or invalid"),
         (status = 404, description = "Account not found"),
         (status = 400, description = "Invalid withdrawal request or insufficient funds"),
         (status = 500, description = "Internal server error")
@@ -173,13 +302,250 @@ pub struct WithdrawRequest {
 )]
 pub async fn withdraw(
     State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
     Path(id): Path<Uuid>,
+    headers: HeaderMap,
     Json(request): Json<WithdrawRequest>,
-) -> Result<ApiResponse<Balance>, ApiError> {
-    // Call the service to withdraw funds
-    let balance = state.account_service.withdraw(id, &request.asset, request.amount).await
+) -> Result<(StatusCode, ApiResponse<Balance>), ApiError> {
+    auth.ensure_account(id)?;
+    state.settlement.flush(id).await;
+
+    // Enforce the key's scopes, the account's address whitelist and second factor
+    let authorized = if auth.permissions.can_withdraw {
+        state.account_service.authorize_withdrawal(
+            id,
+            &request.asset,
+            request.address.as_deref(),
+            second_factor_code(&headers),
+        )
+    } else {
+        Err(Error::AuthorizationError("API key does not grant the withdraw scope".to_string()))
+    };
+    match authorized {
+        Ok(()) => {}
+        Err(Error::AuthorizationError(reason)) => {
+            state.audit_log.record(
+                format!("account:{}", id),
+                "withdrawal.rejected",
+                Some(id),
+                json!({
+                    "asset": request.asset,
+                    "amount": request.amount,
+                    "address": request.address,
+                    "reason": reason,
+                }),
+            );
+            return Err(ApiError::Common(Error::AuthorizationError(reason)));
+        }
+        Err(e) => return Err(ApiError::Common(e)),
+    }
+
+    // Call the service to withdraw funds and pay them out
+    let balance = state.account_service
+        .settle_withdrawal(id, &request.asset, request.amount, request.address.as_deref())
+        .await
         .map_err(ApiError::Common)?;
+    state.webhooks.notify(id, WebhookEventType::Withdrawal, json!({
+        "asset": request.asset,
+        "amount": request.amount,
+        "balance": balance,
+    }));
+    state.notifications.notify(
+        id,
+        NotificationKind::Withdrawal,
+        format!("Withdrew {} {}", format_amount(&request.asset, request.amount), request.asset),
+        json!({
+            "asset": request.asset,
+            "amount": request.amount,
+            "address": request.address,
+        }),
+    );
+    
+    // Payouts complete with the custodian after the response
+    let status = if state.account_service.pays_out(&request.asset) { StatusCode::ACCEPTED } else { StatusCode::OK };
     
     // Return a standardized response with the updated balance
-    Ok(ApiResponse::new(balance))
-}
\ No newline at end of file
+    Ok((status, ApiResponse::new(balance)))
+}
+/// Account trades query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AccountTradesQuery {
+    /// Maximum number of trades to return
+    #[serde(default = "default_account_trades_limit")]
+    pub limit: usize,
+}
+
+fn default_account_trades_limit() -> usize {
+    100
+}
+
+/// Get an account's settled trades, including liquidity flag and fees
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/trades",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of trades to return")
+    ),
+    responses(
+        (status = 200, description = "Account trades retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn get_account_trades(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<AccountTradesQuery>,
+) -> Result<ApiListResponse<Trade>, ApiError> {
+    auth.ensure_account(id)?;
+    state.settlement.flush(id).await;
+
+    // Verify the account exists before fetching its trades
+    let _ = state.account_service.get_account(id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", id)))?;
+
+    let trades = state.account_service.get_trades(id, query.limit);
+
+    Ok(ApiListResponse::new(trades))
+}
+
+/// Portfolio query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PortfolioQuery {
+    /// Currency to value the balances in
+    #[serde(default = "default_portfolio_quote")]
+    pub quote: String,
+}
+
+fn default_portfolio_quote() -> String {
+    "USD".to_string()
+}
+
+/// Value an account's balances in a quote currency at current mark prices
+///
+/// Assets without a market against the quote are converted through other
+/// markets. Assets that cannot be converted are listed without a value and
+/// left out of the totals.
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/portfolio",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("quote" = Option<String>, Query, description = "Currency to value balances in, USD by default")
+    ),
+    responses(
+        (status = 200, description = "Portfolio valued successfully", body = Portfolio),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn get_portfolio(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<PortfolioQuery>,
+) -> Result<ApiResponse<Portfolio>, ApiError> {
+    auth.ensure_account(id)?;
+    state.settlement.flush(id).await;
+
+    let _ = state.account_service.get_account(id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", id)))?;
+    let balances = state.account_service.get_balances(id).await
+        .map_err(ApiError::Common)?;
+
+    let portfolio = value_balances(
+        &state.matching_engine,
+        &state.markets,
+        state.conversion.as_ref(),
+        balances,
+        &query.quote.to_uppercase(),
+    );
+    Ok(ApiResponse::new(portfolio))
+}
+
+/// Balance history query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BalanceHistoryQuery {
+    /// Only snapshots of this asset
+    pub asset: Option<String>,
+    /// Interval between points, as a candle interval code such as `1h` or `1d`
+    #[serde(default = "default_history_interval")]
+    pub interval: String,
+    /// Start of the history, 100 intervals before `to` by default
+    pub from: Option<DateTime<Utc>>,
+    /// End of the history, exclusive, the end of the current interval by default
+    pub to: Option<DateTime<Utc>>,
+}
+
+fn default_history_interval() -> String {
+    "1d".to_string()
+}
+
+/// Get an account's balance history from its balance snapshots
+///
+/// Each point is the last snapshot of an asset taken in an interval, oldest
+/// first. Snapshots are taken after every UTC midnight and on demand by
+/// operators, so intervals without one have no point.
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/balance-history",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("asset" = Option<String>, Query, description = "Only this asset, every asset by default"),
+        ("interval" = Option<String>, Query, description = "Interval between points, e.g. 1h or 1d (default)"),
+        ("from" = Option<String>, Query, description = "RFC 3339 start, 100 intervals before `to` by default"),
+        ("to" = Option<String>, Query, description = "RFC 3339 exclusive end, the end of the current interval by default")
+    ),
+    responses(
+        (status = 200, description = "Balance history retrieved successfully"),
+        (status = 400, description = "Invalid interval or range"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn get_balance_history(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<BalanceHistoryQuery>,
+) -> Result<ApiListResponse<BalanceSnapshot>, ApiError> {
+    auth.ensure_account(id)?;
+
+    let interval = CandleInterval::from_code(&query.interval)
+        .ok_or_else(|| ApiError::BadRequest(format!("Invalid interval: {}", query.interval)))?;
+    let interval = chrono::Duration::seconds(interval.duration_secs());
+    let to = query.to.unwrap_or_else(|| {
+        let now = state.matching_engine.clock().now().timestamp_millis();
+        let interval = interval.num_milliseconds();
+        DateTime::from_timestamp_millis((now.div_euclid(interval) + 1) * interval).unwrap_or(DateTime::<Utc>::MAX_UTC)
+    });
+    let from = query.from.unwrap_or(to - interval * 100);
+    if from >= to {
+        return Err(ApiError::BadRequest("from must be before to".to_string()));
+    }
+
+    let _ = state.account_service.get_account(id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", id)))?;
+
+    let asset = query.asset.map(|asset| asset.to_uppercase());
+    let history = state.account_service.balance_history(id, asset.as_deref(), interval, from, to).await
+        .map_err(ApiError::Common)?;
+    Ok(ApiListResponse::new(history))
+}
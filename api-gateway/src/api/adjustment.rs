@@ -0,0 +1,208 @@
+//! Balance adjustment and statement handlers
+//!
+//! Admins can credit fee rebates to an account or post manual credits and
+//! debits, each with a reason code and posted through the ledger like any
+//! other balance change. Account holders see them on their statement next to
+//! their trades and funding.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use common::decimal::Amount;
+use common::model::account::{AdjustmentKind, Balance, BalanceAdjustment, ReasonCode, StatementEntry};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::auth::AuthContext;
+use crate::error::ApiError;
+use crate::AppState;
+use crate::api::response::{ApiListResponse, ApiResponse};
+
+/// Actor name recorded for admin requests
+const ADMIN_ACTOR: &str = "admin";
+
+/// Days a statement covers unless `from` is given
+const DEFAULT_STATEMENT_DAYS: i64 = 30;
+
+/// Fee rebate or manual adjustment request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AdjustmentRequest {
+    /// Asset symbol
+    pub asset: String,
+    /// Amount to credit, negative to debit (manual adjustments only)
+    pub amount: Amount,
+    /// Why the adjustment is made
+    pub reason_code: ReasonCode,
+    /// Explanation, required with the `other` reason code
+    pub note: Option<String>,
+}
+
+/// Posted adjustment and the balance it left
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdjustmentResult {
+    /// The adjustment
+    pub adjustment: BalanceAdjustment,
+    /// Balance of the asset afterwards
+    pub balance: Balance,
+}
+
+/// Statement query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct StatementQuery {
+    /// Start of the statement, 30 days before `to` by default
+    pub from: Option<DateTime<Utc>>,
+    /// End of the statement, exclusive, now by default
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Credit a fee rebate to an account
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/accounts/{id}/fee-rebates",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    request_body = AdjustmentRequest,
+    responses(
+        (status = 200, description = "Fee rebate credited", body = AdjustmentResult),
+        (status = 400, description = "Amount not positive or beyond the asset's precision"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn credit_fee_rebate(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<AdjustmentRequest>,
+) -> Result<ApiResponse<AdjustmentResult>, ApiError> {
+    let result = post(&state, id, AdjustmentKind::FeeRebate, request).await?;
+    Ok(ApiResponse::new(result))
+}
+
+/// Credit or debit an account
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/accounts/{id}/adjustments",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    request_body = AdjustmentRequest,
+    responses(
+        (status = 200, description = "Adjustment posted", body = AdjustmentResult),
+        (status = 400, description = "Zero amount, beyond the asset's precision, missing note or insufficient balance"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn adjust_balance(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<AdjustmentRequest>,
+) -> Result<ApiResponse<AdjustmentResult>, ApiError> {
+    let result = post(&state, id, AdjustmentKind::Manual, request).await?;
+    Ok(ApiResponse::new(result))
+}
+
+/// Get an account's statement: every change to its balances, oldest first
+///
+/// Trades appear as one entry per asset, net of fees. Busts, funding
+/// payments, fee rebates and manual adjustments have their own entries.
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/statement",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("from" = Option<String>, Query, description = "RFC 3339 start, 30 days before `to` by default"),
+        ("to" = Option<String>, Query, description = "RFC 3339 exclusive end, now by default")
+    ),
+    responses(
+        (status = 200, description = "Statement retrieved successfully"),
+        (status = 400, description = "Invalid range"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn get_statement(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<StatementQuery>,
+) -> Result<ApiListResponse<StatementEntry>, ApiError> {
+    auth.ensure_account(id)?;
+
+    let to = query.to.unwrap_or_else(|| state.matching_engine.clock().now());
+    let from = query.from.unwrap_or(to - Duration::days(DEFAULT_STATEMENT_DAYS));
+    if from >= to {
+        return Err(ApiError::BadRequest("from must be before to".to_string()));
+    }
+    ensure_account_exists(&state, id).await?;
+
+    let statement = state.account_service.get_statement(id, from, to).await
+        .map_err(ApiError::Common)?;
+    Ok(ApiListResponse::new(statement))
+}
+
+/// Post an adjustment through the ledger and audit it
+async fn post(
+    state: &AppState,
+    account_id: Uuid,
+    kind: AdjustmentKind,
+    request: AdjustmentRequest,
+) -> Result<AdjustmentResult, ApiError> {
+    ensure_account_exists(state, account_id).await?;
+
+    let adjustment = BalanceAdjustment {
+        id: Uuid::new_v4(),
+        account_id,
+        kind,
+        asset: request.asset.trim().to_uppercase(),
+        amount: request.amount.normalize(),
+        reason_code: request.reason_code,
+        note: request.note.map(|note| note.trim().to_string()).filter(|note| !note.is_empty()),
+        actor: ADMIN_ACTOR.to_string(),
+        created_at: state.matching_engine.clock().now(),
+    };
+    let balance = state.account_service.adjust_balance(&adjustment).await
+        .map_err(ApiError::Common)?;
+
+    state.audit_log.record(
+        ADMIN_ACTOR,
+        match kind {
+            AdjustmentKind::FeeRebate => "fee.rebated",
+            AdjustmentKind::Manual => "balance.adjusted",
+        },
+        Some(account_id),
+        json!({
+            "adjustment_id": adjustment.id,
+            "asset": adjustment.asset,
+            "amount": adjustment.amount,
+            "reason_code": adjustment.reason_code,
+            "note": adjustment.note,
+        }),
+    );
+
+    Ok(AdjustmentResult { adjustment, balance })
+}
+
+async fn ensure_account_exists(state: &AppState, account_id: Uuid) -> Result<(), ApiError> {
+    state.account_service.get_account(account_id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", account_id)))?;
+    Ok(())
+}
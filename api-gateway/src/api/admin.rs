@@ -0,0 +1,956 @@
+//! Admin API handlers
+//!
+//! Operator endpoints behind the admin key:
+//! - Summarize markets, accounts, connections and queues for a dashboard
+//! - List accounts and find them by external reference
+//! - Take balance snapshots on demand
+//! - List and toggle feature flags
+//! - Query the audit log
+//! - Query trade surveillance alerts
+//! - Regenerate end-of-day reports
+//! - Inspect and force-release an account's fund reservations
+//! - Set and clear market trading calendars
+//! - Set and clear limits on a market's resting orders
+//! - Bulk import orders from CSV
+//! - Report market maker activity and settle maker rebates
+//! - Report order path latency
+//! - Report candle retention compactions
+//! - Report gaps in market data trades
+//! - Report market data memory use and subscriber lag
+//! - Inspect and close live WebSocket connections
+
+use std::sync::Arc;
+
+use account_service::{AccountFilter, AccountPage};
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use chrono::{DateTime, NaiveDate, Utc};
+use common::flags::{self, FlagState};
+use common::model::account::{Account, Reservation};
+use common::model::market::{BookLimits, MarketSession, TradingSchedule};
+use common::model::surveillance::{Alert, AlertKind};
+use market_data::channel::ChannelMetrics;
+use market_data::memory::MemoryUsage;
+use market_data::retention::CompactionMetrics;
+use market_data::sync::MarketGaps;
+use matching_engine::BookChecks;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::audit::AuditEntry;
+use crate::balance_history::{take_snapshots, BalanceSnapshotTaken};
+use crate::error::ApiError;
+use crate::incentives::{IncentiveReport, RebatePeriod};
+use crate::latency::StageLatency;
+use crate::order_import::{import_orders as run_import, ImportSummary};
+use crate::overview::{overview, Overview};
+use crate::report::ReportSummary;
+use crate::ws::connections::ConnectionInfo;
+use crate::AppState;
+use crate::api::response::{ApiListResponse, ApiResponse};
+
+/// Account listing query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AccountsQuery {
+    /// Only closed accounts when true, only open ones when false
+    pub closed: Option<bool>,
+    /// Only accounts whose external reference starts with this
+    pub external_id_prefix: Option<String>,
+    /// Only accounts created at or after this time
+    pub created_from: Option<DateTime<Utc>>,
+    /// Only accounts created before this time
+    pub created_to: Option<DateTime<Utc>>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<Uuid>,
+    /// Maximum number of accounts
+    #[serde(default = "default_audit_limit")]
+    pub limit: usize,
+}
+
+/// External reference request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ExternalIdRequest {
+    /// Reference of the account in an external system, cleared when null
+    pub external_id: Option<String>,
+}
+
+/// Balance snapshot request
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct BalanceSnapshotRequest {
+    /// Only this account's balances, every account's by default
+    #[serde(default)]
+    pub account_id: Option<Uuid>,
+}
+
+/// Feature flag update
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FeatureFlagUpdate {
+    /// Whether the flag is on
+    pub enabled: bool,
+}
+
+/// Audit log query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AuditQuery {
+    /// Only entries for this account
+    pub account_id: Option<Uuid>,
+    /// Maximum number of entries
+    #[serde(default = "default_audit_limit")]
+    pub limit: usize,
+}
+
+/// Force-release request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ForceReleaseRequest {
+    /// Why the reservation is being released, e.g. a support ticket
+    pub reason: String,
+}
+
+/// Surveillance alert query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AlertsQuery {
+    /// Only alerts involving this account
+    pub account_id: Option<Uuid>,
+    /// Only alerts of this kind
+    pub kind: Option<AlertKind>,
+    /// Maximum number of alerts
+    #[serde(default = "default_audit_limit")]
+    pub limit: usize,
+}
+
+/// Rebate period query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RebatePeriodsQuery {
+    /// Maximum number of periods
+    #[serde(default = "default_period_limit")]
+    pub limit: usize,
+}
+
+fn default_period_limit() -> usize {
+    30
+}
+
+fn default_audit_limit() -> usize {
+    100
+}
+
+/// Order import query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ImportQuery {
+    /// Only validate the rows, placing no orders
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Get the operator dashboard: markets, accounts, open orders, connections and queue depths
+///
+/// Balances are summed over every account on each request, so the dashboard
+/// should poll it every few seconds rather than continuously. Trades still
+/// in the settlement queue are not yet in the balances.
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/overview",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Current figures of every market and service", body = Overview),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn get_overview(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiResponse<Overview>, ApiError> {
+    Ok(ApiResponse::new(overview(&state).await.map_err(ApiError::Common)?))
+}
+
+/// List accounts in ID order, a page at a time
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/accounts",
+    security(("admin_key" = [])),
+    params(
+        ("closed" = Option<bool>, Query, description = "Only closed accounts when true, only open ones when false"),
+        ("external_id_prefix" = Option<String>, Query, description = "Only accounts whose external reference starts with this"),
+        ("created_from" = Option<String>, Query, description = "Only accounts created at or after this RFC 3339 time"),
+        ("created_to" = Option<String>, Query, description = "Only accounts created before this RFC 3339 time"),
+        ("cursor" = Option<Uuid>, Query, description = "`next_cursor` of the previous page"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of accounts to return, at most 1000")
+    ),
+    responses(
+        (status = 200, description = "Accounts and the cursor of the next page, null on the last"),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn list_accounts(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AccountsQuery>,
+) -> Result<ApiResponse<AccountPage>, ApiError> {
+    let filter = AccountFilter {
+        closed: query.closed,
+        external_id_prefix: query.external_id_prefix,
+        created_from: query.created_from,
+        created_to: query.created_to,
+    };
+    let page = state.account_service.list_accounts(&filter, query.cursor, query.limit).await
+        .map_err(ApiError::Common)?;
+    Ok(ApiResponse::new(page))
+}
+
+/// Find the account with an external reference
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/accounts/external/{external_id}",
+    security(("admin_key" = [])),
+    params(
+        ("external_id" = String, Path, description = "Reference of the account in an external system")
+    ),
+    responses(
+        (status = 200, description = "Account found"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "No account has the reference")
+    ),
+    tag = "admin"
+)]
+pub async fn find_account_by_external_id(
+    State(state): State<Arc<AppState>>,
+    Path(external_id): Path<String>,
+) -> Result<ApiResponse<Account>, ApiError> {
+    let account = state.account_service.find_by_external_id(&external_id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("No account with external ID {}", external_id)))?;
+    Ok(ApiResponse::new(account))
+}
+
+/// Set or clear an account's external reference, recorded in the audit log
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/accounts/{id}/external-id",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    request_body = ExternalIdRequest,
+    responses(
+        (status = 200, description = "Reference set"),
+        (status = 400, description = "Empty or overlong reference"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Account not found"),
+        (status = 409, description = "Another account has the reference")
+    ),
+    tag = "admin"
+)]
+pub async fn set_account_external_id(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<ExternalIdRequest>,
+) -> Result<ApiResponse<Account>, ApiError> {
+    let account = state.account_service.set_external_id(id, request.external_id.as_deref()).await
+        .map_err(ApiError::Common)?;
+    state.audit_log.record(
+        "admin",
+        "account.external_id_set",
+        Some(id),
+        json!({ "external_id": account.external_id }),
+    );
+    Ok(ApiResponse::new(account))
+}
+
+/// Snapshot balances now for balance history, recorded in the audit log
+///
+/// Snapshots are otherwise taken after every UTC midnight.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/balance-snapshots",
+    security(("admin_key" = [])),
+    request_body = BalanceSnapshotRequest,
+    responses(
+        (status = 200, description = "Balances recorded", body = BalanceSnapshotTaken),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn take_balance_snapshots(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<BalanceSnapshotRequest>,
+) -> Result<ApiResponse<BalanceSnapshotTaken>, ApiError> {
+    let taken = take_snapshots(&state, request.account_id).await
+        .map_err(ApiError::Common)?;
+    state.audit_log.record(
+        "admin",
+        "balances.snapshot_taken",
+        request.account_id,
+        json!({ "taken_at": taken.taken_at, "recorded": taken.recorded }),
+    );
+    Ok(ApiResponse::new(taken))
+}
+
+/// List every feature flag and whether it is on
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/feature-flags",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Feature flags retrieved successfully", body = ApiListResponse<FlagState>),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn get_feature_flags(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiListResponse<FlagState>, ApiError> {
+    Ok(ApiListResponse::new(state.matching_engine.feature_flags().list()))
+}
+
+/// Turn a feature flag on or off
+///
+/// Takes effect on the next order or request that consults the flag.
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/feature-flags/{name}",
+    security(("admin_key" = [])),
+    params(
+        ("name" = String, Path, description = "Flag name, e.g. new_settlement_pipeline")
+    ),
+    request_body = FeatureFlagUpdate,
+    responses(
+        (status = 200, description = "Flag set", body = FlagState),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Unknown flag")
+    ),
+    tag = "admin"
+)]
+pub async fn set_feature_flag(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(update): Json<FeatureFlagUpdate>,
+) -> Result<ApiResponse<FlagState>, ApiError> {
+    if flags::spec(&name).is_none() {
+        return Err(ApiError::NotFound(format!("Feature flag {} not found", name)));
+    }
+    let feature_flags = state.matching_engine.feature_flags();
+    let previous = feature_flags.set(&name, update.enabled)
+        .map_err(ApiError::Common)?;
+
+    state.audit_log.record("admin", "feature_flag.set", None, json!({
+        "name": name,
+        "enabled": update.enabled,
+        "previous": previous,
+    }));
+
+    let flag = feature_flags.list()
+        .into_iter()
+        .find(|flag| flag.name == name)
+        .ok_or_else(|| ApiError::NotFound(format!("Feature flag {} not found", name)))?;
+    Ok(ApiResponse::new(flag))
+}
+
+/// Get recent audit log entries, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/audit",
+    security(("admin_key" = [])),
+    params(
+        ("account_id" = Option<Uuid>, Query, description = "Only entries for this account"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of entries to return")
+    ),
+    responses(
+        (status = 200, description = "Audit entries retrieved successfully"),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn get_audit_log(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<ApiListResponse<AuditEntry>, ApiError> {
+    Ok(ApiListResponse::new(state.audit_log.recent(query.account_id, query.limit)))
+}
+
+/// Get recent trade surveillance alerts, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/surveillance/alerts",
+    security(("admin_key" = [])),
+    params(
+        ("account_id" = Option<Uuid>, Query, description = "Only alerts involving this account"),
+        ("kind" = Option<AlertKind>, Query, description = "Only alerts of this kind"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of alerts to return")
+    ),
+    responses(
+        (status = 200, description = "Alerts retrieved successfully"),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn get_surveillance_alerts(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AlertsQuery>,
+) -> Result<ApiListResponse<Alert>, ApiError> {
+    Ok(ApiListResponse::new(state.surveillance.alerts(query.account_id, query.kind, query.limit)))
+}
+
+/// Regenerate the end-of-day reports for a UTC day still in the journal
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/reports/{date}",
+    security(("admin_key" = [])),
+    params(
+        ("date" = String, Path, description = "UTC day, e.g. 2025-02-27")
+    ),
+    responses(
+        (status = 200, description = "Reports written"),
+        (status = 400, description = "Day is outside the journal retention"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 403, description = "No report destination configured"),
+        (status = 500, description = "Writing a report failed")
+    ),
+    tag = "admin"
+)]
+pub async fn regenerate_report(
+    State(state): State<Arc<AppState>>,
+    Path(date): Path<NaiveDate>,
+) -> Result<ApiResponse<ReportSummary>, ApiError> {
+    if !state.reports.is_enabled() {
+        return Err(ApiError::Forbidden("Reports are not configured".to_string()));
+    }
+
+    let markets: Vec<String> = state.markets.iter().map(|market| market.symbol.clone()).collect();
+    let summary = state.reports.generate(date, &markets).await
+        .map_err(ApiError::Common)?;
+
+    state.audit_log.record(
+        "admin",
+        "report.generated",
+        None,
+        json!({ "date": date, "files": summary.files.len() }),
+    );
+
+    Ok(ApiResponse::new(summary))
+}
+
+/// Get any account's fund reservations, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/accounts/{id}/reservations",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Reservations retrieved successfully"),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn get_account_reservations(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiListResponse<Reservation>, ApiError> {
+    state.settlement.flush(id).await;
+    Ok(ApiListResponse::new(state.account_service.get_reservations(id)))
+}
+
+/// Release a reservation whose order is no longer open, unlocking its funds
+///
+/// Meant for balances left locked without a matching open order. The release
+/// is recorded in the audit log with its reason.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/accounts/{id}/reservations/{order_id}/release",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("order_id" = Uuid, Path, description = "Order the funds are reserved for")
+    ),
+    request_body = ForceReleaseRequest,
+    responses(
+        (status = 200, description = "Reservation released"),
+        (status = 400, description = "Missing reason, or the order is still open"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Reservation not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn force_release_reservation(
+    State(state): State<Arc<AppState>>,
+    Path((id, order_id)): Path<(Uuid, Uuid)>,
+    Json(request): Json<ForceReleaseRequest>,
+) -> Result<ApiResponse<Reservation>, ApiError> {
+    if request.reason.trim().is_empty() {
+        return Err(ApiError::BadRequest("A reason is required".to_string()));
+    }
+
+    // Funds of a resting order are still needed to settle its fills
+    if state.matching_engine.get_order(order_id).is_some_and(|order| order.user_id == id && order.is_active()) {
+        return Err(ApiError::BadRequest(format!("Order {} is still open, cancel it instead", order_id)));
+    }
+
+    state.settlement.flush(id).await;
+    let reservation = state.account_service.release_reservation(id, order_id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("No reservation for order {} of account {}", order_id, id)))?;
+
+    state.audit_log.record(
+        "admin",
+        "reservation.force_released",
+        Some(id),
+        json!({
+            "order_id": order_id,
+            "asset": reservation.asset,
+            "amount": reservation.amount,
+            "reason": request.reason,
+        }),
+    );
+
+    Ok(ApiResponse::new(reservation))
+}
+
+/// Set a market's trading calendar
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/markets/{market}/schedule",
+    security(("admin_key" = [])),
+    params(
+        ("market" = String, Path, description = "Market symbol")
+    ),
+    request_body = TradingSchedule,
+    responses(
+        (status = 200, description = "Schedule set, taking effect within a second"),
+        (status = 400, description = "Invalid schedule"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Market not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn set_market_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(market): Path<String>,
+    Json(schedule): Json<TradingSchedule>,
+) -> Result<ApiResponse<MarketSession>, ApiError> {
+    state.matching_engine.set_trading_schedule(&market, Some(schedule.clone()))
+        .map_err(ApiError::Common)?;
+
+    state.audit_log.record("admin", "market.schedule_set", None, json!({
+        "market": market,
+        "schedule": schedule,
+    }));
+
+    let session = state.matching_engine.market_session(&market, Utc::now())
+        .map_err(ApiError::Common)?;
+    Ok(ApiResponse::new(session))
+}
+
+/// Clear a market's trading calendar so it trades around the clock
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/markets/{market}/schedule",
+    security(("admin_key" = [])),
+    params(
+        ("market" = String, Path, description = "Market symbol")
+    ),
+    responses(
+        (status = 200, description = "Schedule cleared, the market opens within a second"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Market not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn clear_market_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(market): Path<String>,
+) -> Result<ApiResponse<MarketSession>, ApiError> {
+    state.matching_engine.set_trading_schedule(&market, None)
+        .map_err(ApiError::Common)?;
+
+    state.audit_log.record("admin", "market.schedule_cleared", None, json!({ "market": market }));
+
+    let session = state.matching_engine.market_session(&market, Utc::now())
+        .map_err(ApiError::Common)?;
+    Ok(ApiResponse::new(session))
+}
+
+/// Get the limits on a market's resting orders
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/markets/{market}/book-limits",
+    security(("admin_key" = [])),
+    params(
+        ("market" = String, Path, description = "Market symbol")
+    ),
+    responses(
+        (status = 200, description = "Book limits, unset limits are off", body = BookLimits),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Market not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn get_book_limits(
+    State(state): State<Arc<AppState>>,
+    Path(market): Path<String>,
+) -> Result<ApiResponse<BookLimits>, ApiError> {
+    let limits = state.matching_engine.book_limits(&market)
+        .map_err(ApiError::Common)?;
+    Ok(ApiResponse::new(limits))
+}
+
+/// Set the limits on a market's resting orders
+///
+/// Applies to orders that rest from now on; orders already on the book stay.
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/markets/{market}/book-limits",
+    security(("admin_key" = [])),
+    params(
+        ("market" = String, Path, description = "Market symbol")
+    ),
+    request_body = BookLimits,
+    responses(
+        (status = 200, description = "Book limits set", body = BookLimits),
+        (status = 400, description = "Invalid limits"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Market not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn set_book_limits(
+    State(state): State<Arc<AppState>>,
+    Path(market): Path<String>,
+    Json(limits): Json<BookLimits>,
+) -> Result<ApiResponse<BookLimits>, ApiError> {
+    state.matching_engine.set_book_limits(&market, Some(limits.clone()))
+        .map_err(ApiError::Common)?;
+
+    state.audit_log.record("admin", "market.book_limits_set", None, json!({
+        "market": market,
+        "limits": limits,
+    }));
+
+    Ok(ApiResponse::new(limits))
+}
+
+/// Clear the limits on a market's resting orders
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/markets/{market}/book-limits",
+    security(("admin_key" = [])),
+    params(
+        ("market" = String, Path, description = "Market symbol")
+    ),
+    responses(
+        (status = 200, description = "Book limits cleared", body = BookLimits),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Market not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn clear_book_limits(
+    State(state): State<Arc<AppState>>,
+    Path(market): Path<String>,
+) -> Result<ApiResponse<BookLimits>, ApiError> {
+    state.matching_engine.set_book_limits(&market, None)
+        .map_err(ApiError::Common)?;
+
+    state.audit_log.record("admin", "market.book_limits_cleared", None, json!({ "market": market }));
+
+    Ok(ApiResponse::new(BookLimits::default()))
+}
+
+/// Place orders for any accounts from a CSV file, one order per row
+///
+/// Each row is checked against its market's filters and placed like
+/// `POST /orders`. Rejected rows are reported and skipped. The import is
+/// recorded in the audit log.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/orders/import",
+    security(("admin_key" = [])),
+    params(
+        ("dry_run" = Option<bool>, Query, description = "Only validate the rows, placing no orders")
+    ),
+    request_body(content = String, content_type = "text/csv", description = "Header row, then one order per row"),
+    responses(
+        (status = 200, description = "Per-row results", body = ImportSummary),
+        (status = 400, description = "Missing or unknown columns, or too many rows"),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn import_orders(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ImportQuery>,
+    csv: String,
+) -> Result<ApiResponse<ImportSummary>, ApiError> {
+    let summary = run_import(&state, &csv, query.dry_run).await?;
+
+    if !summary.dry_run {
+        state.audit_log.record("admin", "orders.imported", None, json!({
+            "rows": summary.rows,
+            "placed": summary.placed,
+            "rejected": summary.rejected,
+        }));
+    }
+
+    Ok(ApiResponse::new(summary))
+}
+
+/// Get maker volume, time at the top of the book and spread of every quoting
+/// account in the current rebate period
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/incentives",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Current period figures", body = IncentiveReport),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn get_incentives(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiResponse<IncentiveReport>, ApiError> {
+    Ok(ApiResponse::new(state.incentives.current(Utc::now())))
+}
+
+/// Get settled rebate periods, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/incentives/periods",
+    security(("admin_key" = [])),
+    params(
+        ("limit" = Option<usize>, Query, description = "Maximum number of periods to return")
+    ),
+    responses(
+        (status = 200, description = "Rebate periods retrieved successfully"),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn get_rebate_periods(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RebatePeriodsQuery>,
+) -> Result<ApiListResponse<RebatePeriod>, ApiError> {
+    Ok(ApiListResponse::new(state.incentives.periods(query.limit)))
+}
+
+/// End the current rebate period now and credit its rebates
+///
+/// The next period starts immediately. The settlement is recorded in the
+/// audit log.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/incentives/periods",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Period settled", body = RebatePeriod),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn settle_rebates(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiResponse<RebatePeriod>, ApiError> {
+    let period = state.incentives.settle(&state.account_service, &state.markets, Utc::now()).await;
+
+    state.audit_log.record("admin", "incentives.settled", None, json!({
+        "start": period.start,
+        "end": period.end,
+        "credited": period.rebates.iter().filter(|rebate| rebate.credited).count(),
+    }));
+
+    Ok(ApiResponse::new(period))
+}
+
+/// Get the latency distribution of each stage of the order path
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/metrics/latency",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Per-stage latency histograms since startup"),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn get_order_latency(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiListResponse<StageLatency>, ApiError> {
+    Ok(ApiListResponse::new(state.latency.snapshot()))
+}
+
+/// Get the candles purged and downsampled by retention compactions
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/metrics/candles",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Candle compaction totals since startup"),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn get_candle_compaction(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiResponse<CompactionMetrics>, ApiError> {
+    Ok(ApiResponse::new(state.market_data_service.compaction_metrics()))
+}
+
+/// Get the memory held by market data, by structure, with eviction totals
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/metrics/market-data",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Estimated memory use and evictions since startup", body = MemoryUsage),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn get_market_data_memory(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiResponse<MemoryUsage>, ApiError> {
+    Ok(ApiResponse::new(state.market_data_service.memory_usage()))
+}
+
+/// Get the queue depth, lag and dropped messages of every market data subscriber
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/metrics/subscribers",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Subscribers, most lagging first, with disconnects since startup", body = ChannelMetrics),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn get_subscriber_metrics(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiResponse<ChannelMetrics>, ApiError> {
+    Ok(ApiResponse::new(state.market_data_service.channel().metrics().await))
+}
+
+/// List live WebSocket connections, most lagging first
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/ws/connections",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Live connections with their subscriptions, message rates and lag", body = [ConnectionInfo]),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn list_ws_connections(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiListResponse<ConnectionInfo>, ApiError> {
+    let channel = state.market_data_service.channel().metrics().await;
+    Ok(ApiListResponse::new(state.ws_connections.list(&channel)))
+}
+
+/// Get a live WebSocket connection
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/ws/connections/{id}",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Connection ID")
+    ),
+    responses(
+        (status = 200, description = "The connection", body = ConnectionInfo),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "No live connection with the ID")
+    ),
+    tag = "admin"
+)]
+pub async fn get_ws_connection(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiResponse<ConnectionInfo>, ApiError> {
+    let channel = state.market_data_service.channel().metrics().await;
+    state.ws_connections.get(id, &channel)
+        .map(ApiResponse::new)
+        .ok_or_else(|| ApiError::NotFound(format!("No live WebSocket connection {}", id)))
+}
+
+/// Close a live WebSocket connection, ending its subscriptions
+///
+/// The disconnect is recorded in the audit log.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/ws/connections/{id}",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Connection ID")
+    ),
+    responses(
+        (status = 200, description = "The connection as it was when closed", body = ConnectionInfo),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "No live connection with the ID")
+    ),
+    tag = "admin"
+)]
+pub async fn disconnect_ws_connection(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiResponse<ConnectionInfo>, ApiError> {
+    let channel = state.market_data_service.channel().metrics().await;
+    let connection = state.ws_connections.disconnect(id, &channel)
+        .ok_or_else(|| ApiError::NotFound(format!("No live WebSocket connection {}", id)))?;
+
+    state.audit_log.record(
+        "admin",
+        "ws_connection.disconnected",
+        connection.account_id,
+        json!({
+            "connection_id": id,
+            "client_address": connection.client_address,
+            "subscriptions": connection.subscriptions.len(),
+        }),
+    );
+
+    Ok(ApiResponse::new(connection))
+}
+
+/// Get the trades market data missed from the engine and repaired, by market
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/metrics/market-data-gaps",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Gaps detected in each market's trades since startup"),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn get_market_data_gaps(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiListResponse<MarketGaps>, ApiError> {
+    Ok(ApiListResponse::new(state.market_data_service.gap_report().await))
+}
+
+/// Get the crossed book checks run after each engine command, by market
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/metrics/books",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Checks, crossed books found and orders refused for crossing since startup"),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn get_book_checks(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiListResponse<BookChecks>, ApiError> {
+    Ok(ApiListResponse::new(state.matching_engine.book_checks()))
+}
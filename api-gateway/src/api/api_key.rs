@@ -0,0 +1,283 @@
+//! API key handlers
+//!
+//! Account holders can issue further keys for their account with a subset of
+//! the calling key's scopes, limit them to client addresses, rotate their
+//! secrets and revoke them. Admins can issue keys with any scopes, including
+//! `admin`. Each change is also a security event of the account.
+
+use std::collections::BTreeSet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    Extension, Json,
+};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::auth::{ApiKey, AuthContext, Scope};
+use crate::error::ApiError;
+use crate::security::SecurityEventKind;
+use crate::AppState;
+use crate::api::response::{ApiListResponse, ApiResponse, Created};
+
+/// Actor name recorded for admin requests
+const ADMIN_ACTOR: &str = "admin";
+
+/// Most addresses or networks a key's allowlist can hold
+const MAX_ALLOWED_IPS: usize = 50;
+
+/// API key request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ApiKeyRequest {
+    /// What the key may be used for
+    pub scopes: BTreeSet<Scope>,
+    /// Client addresses (`203.0.113.7`) or networks (`203.0.113.0/24`) the key may be used from, any when empty
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
+}
+
+/// Newly issued API key
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IssuedApiKey {
+    /// The key, sent as `X-API-Key`; it is not shown again
+    pub api_key: String,
+    /// The key's scopes and allowlist
+    pub key: ApiKey,
+}
+
+/// List the API keys issued to an account
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/api-keys",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "API keys retrieved successfully", body = Vec<ApiKey>),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account or lacks the read scope"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn list_api_keys(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiListResponse<ApiKey>, ApiError> {
+    auth.ensure_account(id)?;
+    Ok(ApiListResponse::new(state.api_keys.list(id)))
+}
+
+/// Issue another API key for an account
+///
+/// The new key can only have scopes the calling key has.
+#[utoipa::path(
+    post,
+    path = "/api/v1/accounts/{id}/api-keys",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    request_body = ApiKeyRequest,
+    responses(
+        (status = 201, description = "API key issued", body = IssuedApiKey),
+        (status = 400, description = "No scopes or an invalid address"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account or lacks a requested scope"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn create_api_key(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    connect: Option<ConnectInfo<SocketAddr>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<ApiKeyRequest>,
+) -> Result<Created<IssuedApiKey>, ApiError> {
+    auth.ensure_account(id)?;
+    for scope in &request.scopes {
+        auth.ensure_scope(*scope)?;
+    }
+
+    let client = connect.map(|ConnectInfo(addr)| addr.ip());
+    let issued = issue(&state, format!("account:{}", id), id, request, client)?;
+    Ok(Created::new(format!("/api/v1/accounts/{}/api-keys", id), issued))
+}
+
+/// Revoke one of an account's API keys
+#[utoipa::path(
+    delete,
+    path = "/api/v1/accounts/{id}/api-keys/{key_id}",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("key_id" = Uuid, Path, description = "API key ID")
+    ),
+    responses(
+        (status = 200, description = "API key revoked", body = ApiKey),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account or lacks the trade scope"),
+        (status = 404, description = "API key not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn revoke_api_key(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    connect: Option<ConnectInfo<SocketAddr>>,
+    Path((id, key_id)): Path<(Uuid, Uuid)>,
+) -> Result<ApiResponse<ApiKey>, ApiError> {
+    auth.ensure_account(id)?;
+    let revoked = state.api_keys.revoke_by_id(id, key_id)
+        .ok_or_else(|| ApiError::NotFound(format!("API key not found: {}", key_id)))?;
+    state.security.end_session(key_id);
+
+    let client = connect.map(|ConnectInfo(addr)| addr.ip());
+    state.security.record(id, SecurityEventKind::ApiKeyRevoked, Some(&revoked), client, json!({
+        "revoked_by": auth.key.id,
+    }));
+
+    state.audit_log.record(
+        format!("account:{}", id),
+        "api_key.revoked",
+        Some(id),
+        json!({ "key_id": revoked.id, "prefix": revoked.prefix }),
+    );
+    Ok(ApiResponse::new(revoked))
+}
+
+/// Give one of an account's API keys a new secret
+///
+/// The key keeps its ID, scopes and allowlist; the old secret stops working
+/// at once.
+#[utoipa::path(
+    post,
+    path = "/api/v1/accounts/{id}/api-keys/{key_id}/rotate",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("key_id" = Uuid, Path, description = "API key ID")
+    ),
+    responses(
+        (status = 200, description = "API key rotated", body = IssuedApiKey),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account or lacks the trade scope"),
+        (status = 404, description = "API key not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn rotate_api_key(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    connect: Option<ConnectInfo<SocketAddr>>,
+    Path((id, key_id)): Path<(Uuid, Uuid)>,
+) -> Result<ApiResponse<IssuedApiKey>, ApiError> {
+    auth.ensure_account(id)?;
+    let previous_prefix = state.api_keys.list(id)
+        .into_iter()
+        .find(|key| key.id == key_id)
+        .map(|key| key.prefix)
+        .ok_or_else(|| ApiError::NotFound(format!("API key not found: {}", key_id)))?;
+    let (api_key, key) = state.api_keys.rotate(id, key_id)
+        .ok_or_else(|| ApiError::NotFound(format!("API key not found: {}", key_id)))?;
+
+    let client = connect.map(|ConnectInfo(addr)| addr.ip());
+    state.security.record(id, SecurityEventKind::ApiKeyRotated, Some(&key), client, json!({
+        "previous_prefix": previous_prefix,
+    }));
+    state.audit_log.record(
+        format!("account:{}", id),
+        "api_key.rotated",
+        Some(id),
+        json!({ "key_id": key.id, "prefix": key.prefix, "previous_prefix": previous_prefix }),
+    );
+    Ok(ApiResponse::new(IssuedApiKey { api_key, key }))
+}
+
+/// Issue an API key with any scopes for an account
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/accounts/{id}/api-keys",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    request_body = ApiKeyRequest,
+    responses(
+        (status = 201, description = "API key issued", body = IssuedApiKey),
+        (status = 400, description = "No scopes or an invalid address"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn issue_api_key(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<ApiKeyRequest>,
+) -> Result<Created<IssuedApiKey>, ApiError> {
+    state.account_service.get_account(id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", id)))?;
+
+    let issued = issue(&state, ADMIN_ACTOR.to_string(), id, request, None)?;
+    Ok(Created::new(format!("/api/v1/accounts/{}/api-keys", id), issued))
+}
+
+/// Issue a key as `actor` from `client`, auditing it and recording it as a security event
+fn issue(
+    state: &AppState,
+    actor: String,
+    account_id: Uuid,
+    request: ApiKeyRequest,
+    client: Option<IpAddr>,
+) -> Result<IssuedApiKey, ApiError> {
+    if request.scopes.is_empty() {
+        return Err(ApiError::BadRequest("An API key needs at least one scope".to_string()));
+    }
+    if request.allowed_ips.len() > MAX_ALLOWED_IPS {
+        return Err(ApiError::BadRequest(format!("At most {} allowed IPs", MAX_ALLOWED_IPS)));
+    }
+    let allowed_ips = request.allowed_ips
+        .iter()
+        .map(|ip| parse_network(ip.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (api_key, key) = state.api_keys.issue_scoped(account_id, request.scopes, allowed_ips);
+    state.security.record(account_id, SecurityEventKind::ApiKeyCreated, Some(&key), client, json!({
+        "issued_by": actor,
+        "scopes": key.scopes,
+        "allowed_ips": key.allowed_ips,
+    }));
+    state.audit_log.record(
+        actor,
+        "api_key.issued",
+        Some(account_id),
+        json!({
+            "key_id": key.id,
+            "prefix": key.prefix,
+            "scopes": key.scopes,
+            "allowed_ips": key.allowed_ips,
+        }),
+    );
+    Ok(IssuedApiKey { api_key, key })
+}
+
+/// Parse an address or network; a bare address allows only itself
+fn parse_network(value: &str) -> Result<IpNet, ApiError> {
+    value.parse::<IpNet>()
+        .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+        .map(|network| network.trunc())
+        .map_err(|_| ApiError::BadRequest(format!("Invalid IP address or network: {}", value)))
+}
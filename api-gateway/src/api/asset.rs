@@ -0,0 +1,92 @@
+//! Asset registry handlers
+//!
+//! Anyone can list the registered assets with the decimal places their
+//! amounts are quoted in and their deposit and withdrawal rules. Operators
+//! register assets and change their rules through the admin API.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::Json;
+use common::decimal::Quantity;
+use common::model::asset::Asset;
+use serde::Deserialize;
+use serde_json::json;
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+use crate::AppState;
+use crate::api::response::{ApiResponse, ApiListResponse};
+
+/// Asset registration request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RegisterAssetRequest {
+    /// Display name
+    pub name: String,
+    /// Decimal places deposits, withdrawals and order quantities may have
+    pub precision: u32,
+    /// Smallest amount that can be withdrawn
+    #[serde(default)]
+    pub withdrawal_min: Quantity,
+    /// Fee kept from every withdrawal
+    #[serde(default)]
+    pub withdrawal_fee: Quantity,
+    /// Whether deposits are accepted
+    #[serde(default = "default_deposit_enabled")]
+    pub deposit_enabled: bool,
+}
+
+fn default_deposit_enabled() -> bool {
+    true
+}
+
+/// Get every registered asset
+#[utoipa::path(
+    get,
+    path = "/api/v1/assets",
+    responses(
+        (status = 200, description = "Assets retrieved successfully", body = ApiListResponse<Asset>)
+    ),
+    tag = "market"
+)]
+pub async fn get_assets(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiListResponse<Asset>, ApiError> {
+    Ok(ApiListResponse::new(state.account_service.assets()))
+}
+
+/// Register an asset or change its rules
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/assets/{code}",
+    security(("admin_key" = [])),
+    params(
+        ("code" = String, Path, description = "Asset code, e.g. BTC")
+    ),
+    request_body = RegisterAssetRequest,
+    responses(
+        (status = 200, description = "Asset registered", body = Asset),
+        (status = 400, description = "Invalid asset"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn register_asset(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+    Json(request): Json<RegisterAssetRequest>,
+) -> Result<ApiResponse<Asset>, ApiError> {
+    let asset = Asset {
+        withdrawal_min: request.withdrawal_min,
+        withdrawal_fee: request.withdrawal_fee,
+        deposit_enabled: request.deposit_enabled,
+        ..Asset::new(&code, &request.name, request.precision)
+    };
+    let asset = state.account_service.register_asset(asset).await
+        .map_err(ApiError::Common)?;
+
+    state.audit_log.record("admin", "asset.registered", None, json!({ "asset": asset }));
+
+    Ok(ApiResponse::new(asset))
+}
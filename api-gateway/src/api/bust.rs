@@ -0,0 +1,100 @@
+//! Trade bust handlers
+//!
+//! Admins can bust a settled trade made in error. The trade is kept and
+//! reversed with a compensating ledger entry, both parties are told on their
+//! `account` channel and the market's `corrections` channel takes it off the
+//! public tape.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use common::model::order::Side;
+use common::model::trade::TradeBust;
+use market_data::channel::Topic;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+use crate::ws::message::AccountEvent;
+use crate::AppState;
+use crate::api::response::ApiResponse;
+
+/// Actor name recorded for admin requests
+const ADMIN_ACTOR: &str = "admin";
+
+/// Trade bust request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TradeBustRequest {
+    /// Why the trade is being busted, kept in the audit log
+    pub reason: String,
+}
+
+/// Bust a settled trade, reversing its balances, fees and positions
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/trades/{id}/bust",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Trade ID")
+    ),
+    request_body = TradeBustRequest,
+    responses(
+        (status = 200, description = "Trade busted", body = TradeBust),
+        (status = 400, description = "Missing reason, or a party no longer holds what it must give back"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Trade not found"),
+        (status = 409, description = "Trade already busted"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn bust_trade(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<TradeBustRequest>,
+) -> Result<ApiResponse<TradeBust>, ApiError> {
+    state.account_service.get_trade(id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Trade not found: {}", id)))?;
+
+    let bust = state.account_service.bust_trade(id, &request.reason, ADMIN_ACTOR).await
+        .map_err(ApiError::Common)?;
+
+    // Each party's audit trail and private channel hear of it
+    let mut parties = vec![(bust.buyer_id, Side::Buy), (bust.seller_id, Side::Sell)];
+    parties.dedup_by_key(|(account_id, _)| *account_id);
+    for (account_id, side) in parties {
+        state.audit_log.record(
+            ADMIN_ACTOR,
+            "trade.busted",
+            Some(account_id),
+            json!({
+                "trade_id": bust.trade_id,
+                "market": bust.market,
+                "side": side,
+                "reason": bust.reason,
+            }),
+        );
+        let event = AccountEvent::TradeBusted {
+            account_id,
+            trade_id: bust.trade_id,
+            market: bust.market.clone(),
+            side,
+            price: bust.price,
+            quantity: bust.quantity,
+            reason: bust.reason.clone(),
+            timestamp: bust.busted_at,
+        };
+        state.market_data_service.channel()
+            .publish(Topic::Account(account_id), event)
+            .await;
+    }
+    state.market_data_service.bust_trade(&bust).await;
+
+    Ok(ApiResponse::new(bust))
+}
@@ -0,0 +1,243 @@
+//! Account closure and personal data export
+//!
+//! Account holders can close an account that holds no funds and has no open
+//! orders, and export everything the exchange keeps about it. Closed accounts
+//! keep their records and can still be read and exported, but can no longer
+//! trade or move funds. Admins can close any account, cancelling its orders
+//! first, and export any account's data.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use common::error::Error;
+use common::model::account::{Account, Balance, Reservation, WithdrawalAddress};
+use common::model::order::Order;
+use common::model::trade::Trade;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::audit::AuditEntry;
+use crate::auth::AuthContext;
+use crate::error::ApiError;
+use crate::AppState;
+use crate::api::response::ApiResponse;
+
+/// Actor name recorded for admin requests
+const ADMIN_ACTOR: &str = "admin";
+
+/// Admin account closure request
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct CloseAccountRequest {
+    /// Why the account is being closed
+    pub reason: Option<String>,
+}
+
+/// Everything kept about an account
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AccountExport {
+    /// The account
+    pub account: Account,
+    /// Current balances
+    pub balances: Vec<Balance>,
+    /// Funds reserved for open orders
+    pub reservations: Vec<Reservation>,
+    /// Whitelisted withdrawal addresses
+    pub withdrawal_addresses: Vec<WithdrawalAddress>,
+    /// Orders resting on the book
+    pub open_orders: Vec<Order>,
+    /// Settled trades, newest first
+    pub trades: Vec<Trade>,
+    /// Audit log entries about the account, newest first
+    pub activity: Vec<AuditEntry>,
+    /// When the export was taken
+    pub exported_at: DateTime<Utc>,
+}
+
+/// Close the caller's own account
+#[utoipa::path(
+    post,
+    path = "/api/v1/accounts/{id}/close",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Account closed", body = Account),
+        (status = 400, description = "Account holds funds or has open orders"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Account not found"),
+        (status = 409, description = "Account is already closed"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn close_account(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiResponse<Account>, ApiError> {
+    auth.ensure_account(id)?;
+    state.settlement.flush(id).await;
+
+    let open_orders = state.matching_engine.get_open_orders(id);
+    if !open_orders.is_empty() {
+        return Err(ApiError::Common(Error::ValidationError(format!(
+            "Account {} has {} open orders, cancel them first", id, open_orders.len()
+        ))));
+    }
+
+    let account = state.account_service.close_account(id, false).await
+        .map_err(ApiError::Common)?;
+    state.matching_engine.block_account(id);
+
+    state.audit_log.record(format!("account:{}", id), "account.closed", Some(id), json!({ "forced": false }));
+
+    Ok(ApiResponse::new(account))
+}
+
+/// Export everything kept about the caller's own account
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/export",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Account data", body = AccountExport),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn export_account(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiResponse<AccountExport>, ApiError> {
+    auth.ensure_account(id)?;
+
+    let export = export(&state, id, format!("account:{}", id)).await?;
+    Ok(ApiResponse::new(export))
+}
+
+/// Close any account, cancelling its open orders and closing it even if it holds funds
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/accounts/{id}/close",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    request_body = CloseAccountRequest,
+    responses(
+        (status = 200, description = "Account closed", body = Account),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Account not found"),
+        (status = 409, description = "Account is already closed"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn force_close_account(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<CloseAccountRequest>,
+) -> Result<ApiResponse<Account>, ApiError> {
+    state.account_service.get_account(id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", id)))?;
+
+    // Block first so no new order can slip in behind the cancellations
+    state.matching_engine.block_account(id);
+    let cancelled = state.matching_engine.cancel_account_orders(id);
+    state.settlement.flush(id).await;
+
+    let mut markets = HashSet::new();
+    for order in &cancelled {
+        state.account_service.release_reserved_funds(order).await
+            .map_err(ApiError::Common)?;
+        markets.insert(order.market.clone());
+    }
+    for market in markets {
+        if let Ok((bids, asks)) = state.matching_engine.get_market_depth(&market, 10) {
+            state.market_data_service.update_order_book(&market, bids, asks)
+                .await
+                .map_err(ApiError::Common)?;
+        }
+    }
+
+    let account = state.account_service.close_account(id, true).await
+        .map_err(ApiError::Common)?;
+
+    let cancelled_ids: Vec<Uuid> = cancelled.iter().map(|order| order.id).collect();
+    state.audit_log.record(ADMIN_ACTOR, "account.closed", Some(id), json!({
+        "forced": true,
+        "cancelled_orders": cancelled_ids,
+        "reason": request.reason,
+    }));
+
+    Ok(ApiResponse::new(account))
+}
+
+/// Export everything kept about any account
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/accounts/{id}/export",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Account data", body = AccountExport),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn admin_export_account(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiResponse<AccountExport>, ApiError> {
+    let export = export(&state, id, ADMIN_ACTOR.to_string()).await?;
+    Ok(ApiResponse::new(export))
+}
+
+/// Gather an account's data, recording the export in the audit log
+async fn export(state: &AppState, account_id: Uuid, actor: String) -> Result<AccountExport, ApiError> {
+    state.settlement.flush(account_id).await;
+
+    let account = state.account_service.get_account(account_id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", account_id)))?;
+    let balances = state.account_service.get_balances(account_id).await
+        .map_err(ApiError::Common)?;
+
+    // Record the export before gathering the activity so it lists itself
+    state.audit_log.record(actor, "account.exported", Some(account_id), json!({}));
+
+    Ok(AccountExport {
+        account,
+        balances,
+        reservations: state.account_service.get_reservations(account_id),
+        withdrawal_addresses: state.account_service.get_withdrawal_addresses(account_id),
+        open_orders: state.matching_engine.get_open_orders(account_id)
+            .iter()
+            .map(|order| order.as_ref().clone())
+            .collect(),
+        trades: state.account_service.get_trades(account_id, usize::MAX),
+        activity: state.audit_log.recent(Some(account_id), usize::MAX),
+        exported_at: Utc::now(),
+    })
+}
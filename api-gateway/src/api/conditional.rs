@@ -0,0 +1,118 @@
+//! Conditional GET support for market data
+//!
+//! Handlers describe the current representation with [`Validators`] (an
+//! `ETag` and optionally `Last-Modified`). When the request's `If-None-Match`
+//! or `If-Modified-Since` shows the client already has it, the handler answers
+//! `304 Not Modified` without a body.
+
+use std::fmt::Display;
+
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+
+/// Format of HTTP dates (RFC 7231 IMF-fixdate)
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Cache validators of a response
+#[derive(Debug, Clone)]
+pub struct Validators {
+    /// Quoted entity tag
+    etag: String,
+    /// Time the resource last changed
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl Validators {
+    /// Create validators with a strong entity tag
+    pub fn new(tag: impl Display) -> Self {
+        Self {
+            etag: format!("\"{}\"", tag),
+            last_modified: None,
+        }
+    }
+
+    /// Set the time the resource last changed
+    pub fn last_modified(mut self, at: DateTime<Utc>) -> Self {
+        self.last_modified = Some(at);
+        self
+    }
+
+    /// Whether the client's cached copy is still current
+    ///
+    /// `If-None-Match` takes precedence; `If-Modified-Since` is only used when
+    /// it is absent.
+    pub fn is_fresh(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+            let Ok(if_none_match) = if_none_match.to_str() else {
+                return false;
+            };
+            return if_none_match.split(',').map(str::trim).any(|tag| {
+                tag == "*" || tag.trim_start_matches("W/") == self.etag
+            });
+        }
+
+        let (Some(last_modified), Some(since)) = (self.last_modified, headers.get(header::IF_MODIFIED_SINCE)) else {
+            return false;
+        };
+        since
+            .to_str()
+            .ok()
+            .and_then(|since| DateTime::parse_from_rfc2822(since).ok())
+            .is_some_and(|since| last_modified.timestamp() <= since.timestamp())
+    }
+
+    fn apply(&self, response: &mut Response) {
+        let headers = response.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(header::ETAG, etag);
+        }
+        if let Some(last_modified) = self.last_modified {
+            if let Ok(value) = HeaderValue::from_str(&last_modified.format(HTTP_DATE_FORMAT).to_string()) {
+                headers.insert(header::LAST_MODIFIED, value);
+            }
+        }
+    }
+}
+
+/// A response that is either the full body or `304 Not Modified`
+pub enum Conditional<T> {
+    /// The client's copy is current
+    NotModified(Validators),
+    /// The client needs the full representation
+    Modified(Validators, T),
+}
+
+impl<T> Conditional<T> {
+    /// Answer `304` if the client is fresh, otherwise build the body with `body`
+    pub fn respond<E>(
+        validators: Validators,
+        headers: &HeaderMap,
+        body: impl FnOnce() -> Result<T, E>,
+    ) -> Result<Self, E> {
+        if validators.is_fresh(headers) {
+            Ok(Conditional::NotModified(validators))
+        } else {
+            Ok(Conditional::Modified(validators, body()?))
+        }
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for Conditional<T> {
+    fn into_response(self) -> Response {
+        match self {
+            Conditional::NotModified(validators) => {
+                let mut response = StatusCode::NOT_MODIFIED.into_response();
+                validators.apply(&mut response);
+                response
+            }
+            Conditional::Modified(validators, body) => {
+                let mut response = body.into_response();
+                validators.apply(&mut response);
+                response
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Bulk historical data download handlers
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use chrono::NaiveDate;
+use market_data::CandleInterval;
+
+use crate::api::conditional::{Conditional, Validators};
+use crate::api::response::ApiListResponse;
+use crate::archive::{Archive, ArchiveEntry};
+use crate::error::ApiError;
+use crate::AppState;
+
+/// Archives are never regenerated with different content, so clients can cache them for a day
+const ARCHIVE_CACHE_CONTROL: &str = "public, max-age=86400, immutable";
+
+/// Gzipped CSV archive download
+pub struct ArchiveDownload(Archive);
+
+impl IntoResponse for ArchiveDownload {
+    fn into_response(self) -> Response {
+        let entry = &self.0.entry;
+        let name = entry.path.trim_start_matches("/data/").replace('/', "_");
+        let headers = [
+            (header::CONTENT_TYPE, "application/gzip".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", name)),
+            (header::CACHE_CONTROL, ARCHIVE_CACHE_CONTROL.to_string()),
+        ];
+        (headers, self.0.body.as_ref().clone()).into_response()
+    }
+}
+
+/// List the trade and candle archives generated so far
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/manifest",
+    responses(
+        (status = 200, description = "Generated archives, oldest day first", body = [ArchiveEntry])
+    ),
+    tag = "market"
+)]
+pub async fn get_data_manifest(State(state): State<Arc<AppState>>) -> ApiListResponse<ArchiveEntry> {
+    ApiListResponse::new(state.archive.manifest())
+}
+
+/// Download a market's trades for a UTC day as gzipped CSV
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/trades/{market}/{file}",
+    params(
+        ("market" = String, Path, description = "Market symbol, e.g. BTC-USD"),
+        ("file" = String, Path, description = "UTC day and extension, e.g. 2025-02-27.csv.gz")
+    ),
+    responses(
+        (status = 200, description = "Gzipped CSV of the day's trades", content_type = "application/gzip"),
+        (status = 304, description = "Archive unchanged since the client's copy"),
+        (status = 404, description = "Unknown market, or day not ended or outside retention")
+    ),
+    tag = "market"
+)]
+pub async fn get_trade_archive(
+    State(state): State<Arc<AppState>>,
+    Path((market, file)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Conditional<ArchiveDownload>, ApiError> {
+    let market = resolve_market(&state, &market)?;
+    let date = archive_date(&file)?;
+    let archive = state.archive.trades(&market, date).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| not_available(&market, date))?;
+    respond(archive, &headers)
+}
+
+/// Download a market's candles of one interval for a UTC day as gzipped CSV
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/candles/{market}/{interval}/{file}",
+    params(
+        ("market" = String, Path, description = "Market symbol, e.g. BTC-USD"),
+        ("interval" = String, Path, description = "Candle interval code, e.g. 1m"),
+        ("file" = String, Path, description = "UTC day and extension, e.g. 2025-02-27.csv.gz")
+    ),
+    responses(
+        (status = 200, description = "Gzipped CSV of the day's candles", content_type = "application/gzip"),
+        (status = 304, description = "Archive unchanged since the client's copy"),
+        (status = 404, description = "Unknown market or interval, or day not ended or outside retention")
+    ),
+    tag = "market"
+)]
+pub async fn get_candle_archive(
+    State(state): State<Arc<AppState>>,
+    Path((market, interval, file)): Path<(String, String, String)>,
+    headers: HeaderMap,
+) -> Result<Conditional<ArchiveDownload>, ApiError> {
+    let market = resolve_market(&state, &market)?;
+    let date = archive_date(&file)?;
+    let interval = CandleInterval::from_code(&interval)
+        .filter(|interval| state.archive.config().candle_intervals.contains(interval))
+        .ok_or_else(|| ApiError::NotFound(format!("No candle archives at interval {}", interval)))?;
+    let archive = state.archive.candles(&market, interval, date).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| not_available(&market, date))?;
+    respond(archive, &headers)
+}
+
+fn resolve_market(state: &AppState, name: &str) -> Result<String, ApiError> {
+    state.archive.resolve_market(name)
+        .map(str::to_string)
+        .ok_or_else(|| ApiError::NotFound(format!("Market not found: {}", name)))
+}
+
+/// Day named by a file such as `2025-02-27.csv.gz`
+fn archive_date(file: &str) -> Result<NaiveDate, ApiError> {
+    file.strip_suffix(".csv.gz")
+        .and_then(|date| date.parse().ok())
+        .ok_or_else(|| ApiError::NotFound(format!("No archive named {}, expected e.g. 2025-02-27.csv.gz", file)))
+}
+
+fn not_available(market: &str, date: NaiveDate) -> ApiError {
+    ApiError::NotFound(format!("No archive of {} for {}, the day has not ended or is outside retention", market, date))
+}
+
+fn respond(archive: Archive, headers: &HeaderMap) -> Result<Conditional<ArchiveDownload>, ApiError> {
+    let validators = Validators::new(&archive.entry.sha256).last_modified(archive.entry.generated_at);
+    Conditional::respond(validators, headers, || Ok(ArchiveDownload(archive)))
+}
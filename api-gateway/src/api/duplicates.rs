@@ -0,0 +1,82 @@
+//! Duplicate order detection handlers
+//!
+//! Account holders choose how long an identical order counts as a duplicate
+//! and whether duplicates are rejected or answered with the earlier order.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use uuid::Uuid;
+
+use crate::auth::AuthContext;
+use crate::duplicates::DuplicateOrderSettings;
+use crate::error::ApiError;
+use crate::AppState;
+use crate::api::response::ApiResponse;
+
+/// Get an account's duplicate order detection
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/duplicate-orders",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Settings retrieved successfully", body = DuplicateOrderSettings),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account")
+    ),
+    tag = "account"
+)]
+pub async fn get_duplicate_order_settings(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiResponse<DuplicateOrderSettings>, ApiError> {
+    auth.ensure_account(id)?;
+
+    Ok(ApiResponse::new(state.duplicate_orders.settings(id)))
+}
+
+/// Replace an account's duplicate order detection
+///
+/// A window of 0 turns detection off.
+#[utoipa::path(
+    put,
+    path = "/api/v1/accounts/{id}/duplicate-orders",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    request_body = DuplicateOrderSettings,
+    responses(
+        (status = 200, description = "Settings saved", body = DuplicateOrderSettings),
+        (status = 400, description = "Window over a minute"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Account not found")
+    ),
+    tag = "account"
+)]
+pub async fn set_duplicate_order_settings(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Json(settings): Json<DuplicateOrderSettings>,
+) -> Result<ApiResponse<DuplicateOrderSettings>, ApiError> {
+    auth.ensure_account(id)?;
+
+    // Verify the account exists before saving its settings
+    let _ = state.account_service.get_account(id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", id)))?;
+
+    let settings = state.duplicate_orders.set_settings(id, settings)
+        .map_err(ApiError::Common)?;
+
+    Ok(ApiResponse::new(settings))
+}
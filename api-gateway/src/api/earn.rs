@@ -0,0 +1,190 @@
+//! Earn handlers
+//!
+//! Account holders opt assets in and out of earning interest on their idle
+//! balances and review the interest accrued. Admins can accrue interest ahead
+//! of the schedule.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use chrono::Utc;
+use common::error::Error;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::auth::AuthContext;
+use crate::earn::{Accrual, EarnSubscription};
+use crate::error::ApiError;
+use crate::AppState;
+use crate::api::response::{ApiResponse, ApiListResponse};
+
+/// Opt in to earn request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SubscribeEarnRequest {
+    /// Asset to earn interest on
+    pub asset: String,
+}
+
+/// Accrual history query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AccrualsQuery {
+    /// Maximum number of accruals to return
+    #[serde(default = "default_accruals_limit")]
+    pub limit: usize,
+}
+
+fn default_accruals_limit() -> usize {
+    100
+}
+
+/// Opt an asset in to earning interest on its available balance
+#[utoipa::path(
+    post,
+    path = "/api/v1/accounts/{id}/earn",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    request_body = SubscribeEarnRequest,
+    responses(
+        (status = 200, description = "Asset opted in", body = EarnSubscription),
+        (status = 400, description = "No interest is offered on the asset"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account or the account is closed"),
+        (status = 404, description = "Account not found")
+    ),
+    tag = "account"
+)]
+pub async fn subscribe_earn(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<SubscribeEarnRequest>,
+) -> Result<ApiResponse<EarnSubscription>, ApiError> {
+    auth.ensure_account(id)?;
+
+    let account = state.account_service.get_account(id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", id)))?;
+    if account.is_closed() {
+        return Err(ApiError::Common(Error::AuthorizationError(format!("Account {} is closed", id))));
+    }
+
+    let subscription = state.earn.subscribe(id, &request.asset.to_uppercase(), Utc::now())
+        .map_err(ApiError::Common)?;
+
+    Ok(ApiResponse::new(subscription))
+}
+
+/// List an account's opted-in assets
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/earn",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Opted-in assets retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account")
+    ),
+    tag = "account"
+)]
+pub async fn get_earn_subscriptions(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiListResponse<EarnSubscription>, ApiError> {
+    auth.ensure_account(id)?;
+
+    Ok(ApiListResponse::new(state.earn.subscriptions(id)))
+}
+
+/// Opt an asset out of earning interest
+///
+/// Interest since the last accrual is forgone.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/accounts/{id}/earn/{asset}",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("asset" = String, Path, description = "Asset symbol")
+    ),
+    responses(
+        (status = 200, description = "Asset opted out", body = EarnSubscription),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Asset is not opted in")
+    ),
+    tag = "account"
+)]
+pub async fn unsubscribe_earn(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path((id, asset)): Path<(Uuid, String)>,
+) -> Result<ApiResponse<EarnSubscription>, ApiError> {
+    auth.ensure_account(id)?;
+
+    let subscription = state.earn.unsubscribe(id, &asset.to_uppercase())
+        .ok_or_else(|| ApiError::NotFound(format!("{} is not opted in to earn", asset)))?;
+
+    Ok(ApiResponse::new(subscription))
+}
+
+/// Get an account's interest accruals, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/earn/accruals",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of accruals to return")
+    ),
+    responses(
+        (status = 200, description = "Accruals retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account")
+    ),
+    tag = "account"
+)]
+pub async fn get_earn_accruals(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<AccrualsQuery>,
+) -> Result<ApiListResponse<Accrual>, ApiError> {
+    auth.ensure_account(id)?;
+
+    Ok(ApiListResponse::new(state.earn.accruals(id, query.limit)))
+}
+
+/// Accrue and credit earn interest now instead of at the end of the period
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/earn/accruals",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Interest accrued"),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn accrue_earn(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiListResponse<Accrual>, ApiError> {
+    let accruals = state.earn.accrue(&state.account_service, &state.settlement, Utc::now()).await;
+
+    state.audit_log.record("admin", "earn.accrued", None, json!({
+        "accruals": accruals.len(),
+        "credited": accruals.iter().filter(|accrual| accrual.credited).count(),
+    }));
+
+    Ok(ApiListResponse::new(accruals))
+}
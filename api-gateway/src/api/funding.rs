@@ -0,0 +1,151 @@
+//! Funding handlers
+//!
+//! Anyone can review a perpetual market's funding rates, account holders
+//! review the funding their positions paid or received, and admins can settle
+//! funding ahead of the schedule.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use chrono::Utc;
+use common::decimal::Price;
+use common::error::Error;
+use common::model::account::FundingPayment;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::auth::AuthContext;
+use crate::error::ApiError;
+use crate::funding::{mark_price, FundingRate};
+use crate::AppState;
+use crate::api::response::{ApiResponse, ApiListResponse};
+
+/// Funding history query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FundingQuery {
+    /// Maximum number of entries to return
+    #[serde(default = "default_funding_limit")]
+    pub limit: usize,
+}
+
+fn default_funding_limit() -> usize {
+    100
+}
+
+/// Settle funding request
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct SettleFundingRequest {
+    /// Index price to fund towards, instead of the configured index source
+    #[serde(default)]
+    pub index_price: Option<Price>,
+}
+
+/// Get a perpetual market's funding settlements, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/markets/{market}/funding",
+    params(
+        ("market" = String, Path, description = "Market symbol"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of settlements to return")
+    ),
+    responses(
+        (status = 200, description = "Funding rates retrieved successfully"),
+        (status = 404, description = "Market not found")
+    ),
+    tag = "market"
+)]
+pub async fn get_funding_rates(
+    State(state): State<Arc<AppState>>,
+    Path(market): Path<String>,
+    Query(query): Query<FundingQuery>,
+) -> Result<ApiListResponse<FundingRate>, ApiError> {
+    if !state.markets.iter().any(|candidate| candidate.symbol == market) {
+        return Err(ApiError::Common(Error::MarketNotFound(format!("Market not found: {}", market))));
+    }
+
+    Ok(ApiListResponse::new(state.funding.history(&market, query.limit)))
+}
+
+/// Get the funding an account's positions paid or received, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/funding",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of payments to return")
+    ),
+    responses(
+        (status = 200, description = "Funding payments retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account")
+    ),
+    tag = "account"
+)]
+pub async fn get_funding_payments(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<FundingQuery>,
+) -> Result<ApiListResponse<FundingPayment>, ApiError> {
+    auth.ensure_account(id)?;
+
+    Ok(ApiListResponse::new(state.account_service.get_funding_payments(id, query.limit)))
+}
+
+/// Settle a perpetual market's funding now instead of at the end of the interval
+///
+/// Uses the given index price, or the configured index source's when none is
+/// given, and the mark price against it.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/markets/{market}/funding",
+    security(("admin_key" = [])),
+    params(
+        ("market" = String, Path, description = "Market symbol")
+    ),
+    request_body = SettleFundingRequest,
+    responses(
+        (status = 200, description = "Funding settled", body = FundingRate),
+        (status = 400, description = "Not a perpetual market, or no mark or index price"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Market not found")
+    ),
+    tag = "admin"
+)]
+pub async fn settle_funding(
+    State(state): State<Arc<AppState>>,
+    Path(market): Path<String>,
+    request: Option<Json<SettleFundingRequest>>,
+) -> Result<ApiResponse<FundingRate>, ApiError> {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let market = state.markets.iter()
+        .find(|candidate| candidate.symbol == market)
+        .ok_or_else(|| ApiError::Common(Error::MarketNotFound(format!("Market not found: {}", market))))?;
+
+    let index_price = request.index_price
+        .or_else(|| state.funding.index_price(&market.symbol))
+        .ok_or_else(|| ApiError::BadRequest(format!("No index price for {}", market.symbol)))?;
+    let mark_price = mark_price(&state.matching_engine, &market.symbol, Some(index_price))
+        .ok_or_else(|| ApiError::BadRequest(format!("No mark price for {}", market.symbol)))?;
+
+    let (funding, payments) = state.funding
+        .settle(&state.account_service, &state.settlement, market, mark_price, index_price, Utc::now())
+        .await
+        .map_err(ApiError::Common)?;
+
+    state.audit_log.record("admin", "funding.settled", None, json!({
+        "market": funding.market,
+        "rate": funding.rate,
+        "mark_price": funding.mark_price,
+        "index_price": funding.index_price,
+        "payments": payments.len(),
+    }));
+
+    Ok(ApiResponse::new(funding))
+}
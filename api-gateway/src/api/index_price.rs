@@ -0,0 +1,51 @@
+//! Index price handlers
+//!
+//! Anyone can review the index prices perpetual markets are marked and funded
+//! against, with the source quotes each is composed of.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use chrono::Utc;
+
+use crate::error::ApiError;
+use crate::index_price::AssetIndex;
+use crate::AppState;
+use crate::api::response::{ApiResponse, ApiListResponse};
+
+/// Get the index of every quoted asset
+#[utoipa::path(
+    get,
+    path = "/api/v1/index-prices",
+    responses(
+        (status = 200, description = "Index prices retrieved successfully")
+    ),
+    tag = "market"
+)]
+pub async fn get_index_prices(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiListResponse<AssetIndex>, ApiError> {
+    Ok(ApiListResponse::new(state.index_prices.indices(Utc::now())))
+}
+
+/// Get an asset's index with its source quotes
+#[utoipa::path(
+    get,
+    path = "/api/v1/index-prices/{asset}",
+    params(
+        ("asset" = String, Path, description = "Asset symbol")
+    ),
+    responses(
+        (status = 200, description = "Index price retrieved successfully", body = AssetIndex),
+        (status = 404, description = "No source quotes the asset")
+    ),
+    tag = "market"
+)]
+pub async fn get_index_price(
+    State(state): State<Arc<AppState>>,
+    Path(asset): Path<String>,
+) -> Result<ApiResponse<AssetIndex>, ApiError> {
+    state.index_prices.index(&asset, Utc::now())
+        .map(ApiResponse::new)
+        .ok_or_else(|| ApiError::NotFound(format!("No index price for {}", asset)))
+}
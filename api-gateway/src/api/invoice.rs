@@ -0,0 +1,89 @@
+//! Invoice handlers
+//!
+//! Account holders list the monthly fee invoices issued to them. Admins mark
+//! invoices paid once payment arrives.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::auth::AuthContext;
+use crate::error::ApiError;
+use crate::invoice::Invoice;
+use crate::AppState;
+use crate::api::response::{ApiResponse, ApiListResponse};
+
+/// Mark invoice paid request
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct MarkInvoicePaidRequest {
+    /// Payment reference, e.g. a bank transfer ID
+    #[serde(default)]
+    pub reference: Option<String>,
+}
+
+/// Get an account's fee invoices, newest month first
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/invoices",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Invoices retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account")
+    ),
+    tag = "account"
+)]
+pub async fn get_invoices(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiListResponse<Invoice>, ApiError> {
+    auth.ensure_account(id)?;
+
+    Ok(ApiListResponse::new(state.invoices.invoices(id)))
+}
+
+/// Mark an invoice paid
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/invoices/{id}/paid",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Invoice ID")
+    ),
+    request_body = MarkInvoicePaidRequest,
+    responses(
+        (status = 200, description = "Invoice marked paid", body = Invoice),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Invoice not found"),
+        (status = 409, description = "Invoice is already paid")
+    ),
+    tag = "admin"
+)]
+pub async fn mark_invoice_paid(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    request: Option<Json<MarkInvoicePaidRequest>>,
+) -> Result<ApiResponse<Invoice>, ApiError> {
+    let Json(request) = request.unwrap_or_default();
+    let invoice = state.invoices.mark_paid(id, request.reference, state.matching_engine.clock().now())
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Invoice not found: {}", id)))?;
+
+    state.audit_log.record("admin", "invoice.paid", Some(invoice.account_id), json!({
+        "invoice": invoice.number,
+        "reference": invoice.payment_reference,
+    }));
+
+    Ok(ApiResponse::new(invoice))
+}
@@ -0,0 +1,231 @@
+//! Kill switch handlers
+//!
+//! Engaging the kill switch blocks new orders for an account in the matching
+//! engine, cancels its resting orders and optionally freezes withdrawals.
+//! Admins can engage and release it for any account. Account holders can
+//! engage it for their own account, but only an admin can release it.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use chrono::Utc;
+use common::model::order::Order;
+use market_data::channel::Topic;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::auth::AuthContext;
+use crate::error::ApiError;
+use crate::ws::message::AccountEvent;
+use crate::AppState;
+use crate::api::response::ApiResponse;
+
+/// Actor name recorded for admin requests
+const ADMIN_ACTOR: &str = "admin";
+
+/// Kill switch request
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct KillSwitchRequest {
+    /// Also freeze withdrawals
+    #[serde(default)]
+    pub freeze_withdrawals: bool,
+    /// Why the switch is being engaged
+    pub reason: Option<String>,
+}
+
+/// Kill switch state of an account
+#[derive(Debug, Serialize, ToSchema)]
+pub struct KillSwitchStatus {
+    /// Account ID
+    pub account_id: Uuid,
+    /// Whether new orders are blocked
+    pub orders_blocked: bool,
+    /// Whether withdrawals are frozen
+    pub withdrawals_frozen: bool,
+    /// Orders cancelled by this request
+    pub cancelled_orders: Vec<Order>,
+}
+
+/// Engage the kill switch for any account
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/accounts/{id}/kill-switch",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    request_body = KillSwitchRequest,
+    responses(
+        (status = 200, description = "Kill switch engaged"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn engage_kill_switch(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<KillSwitchRequest>,
+) -> Result<ApiResponse<KillSwitchStatus>, ApiError> {
+    let status = engage(&state, id, request, ADMIN_ACTOR.to_string()).await?;
+    Ok(ApiResponse::new(status))
+}
+
+/// Release the kill switch, re-enabling orders and withdrawals
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/accounts/{id}/kill-switch",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Kill switch released"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn release_kill_switch(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiResponse<KillSwitchStatus>, ApiError> {
+    ensure_account_exists(&state, id).await?;
+
+    let was_blocked = state.matching_engine.unblock_account(id);
+    let was_frozen = state.account_service.unfreeze_withdrawals(id);
+
+    state.audit_log.record(
+        ADMIN_ACTOR,
+        "kill_switch.released",
+        Some(id),
+        json!({ "orders_were_blocked": was_blocked, "withdrawals_were_frozen": was_frozen }),
+    );
+
+    publish(&state, id, AccountEvent::KillSwitchReleased {
+        account_id: id,
+        actor: ADMIN_ACTOR.to_string(),
+        timestamp: Utc::now(),
+    }).await;
+
+    Ok(ApiResponse::new(KillSwitchStatus {
+        account_id: id,
+        orders_blocked: false,
+        withdrawals_frozen: false,
+        cancelled_orders: Vec::new(),
+    }))
+}
+
+/// Engage the kill switch for the caller's own account
+#[utoipa::path(
+    post,
+    path = "/api/v1/accounts/{id}/kill-switch",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    request_body = KillSwitchRequest,
+    responses(
+        (status = 200, description = "Kill switch engaged"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn engage_own_kill_switch(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<KillSwitchRequest>,
+) -> Result<ApiResponse<KillSwitchStatus>, ApiError> {
+    auth.ensure_account(id)?;
+
+    let status = engage(&state, id, request, format!("account:{}", id)).await?;
+    Ok(ApiResponse::new(status))
+}
+
+/// Block, cancel and optionally freeze, then audit and notify the account
+async fn engage(
+    state: &AppState,
+    account_id: Uuid,
+    request: KillSwitchRequest,
+    actor: String,
+) -> Result<KillSwitchStatus, ApiError> {
+    ensure_account_exists(state, account_id).await?;
+
+    // Block first so no new order can slip in behind the cancellations
+    state.matching_engine.block_account(account_id);
+    let cancelled = state.matching_engine.cancel_account_orders(account_id);
+    state.settlement.flush(account_id).await;
+
+    let mut markets = HashSet::new();
+    for order in &cancelled {
+        state.account_service.release_reserved_funds(order).await
+            .map_err(ApiError::Common)?;
+        markets.insert(order.market.clone());
+    }
+
+    for market in markets {
+        if let Ok((bids, asks)) = state.matching_engine.get_market_depth(&market, 10) {
+            state.market_data_service.update_order_book(&market, bids, asks)
+                .await
+                .map_err(ApiError::Common)?;
+        }
+    }
+
+    if request.freeze_withdrawals {
+        state.account_service.freeze_withdrawals(account_id);
+    }
+    let withdrawals_frozen = state.account_service.withdrawals_frozen(account_id);
+
+    let cancelled_ids: Vec<Uuid> = cancelled.iter().map(|order| order.id).collect();
+    state.audit_log.record(
+        actor.clone(),
+        "kill_switch.engaged",
+        Some(account_id),
+        json!({
+            "cancelled_orders": cancelled_ids,
+            "withdrawals_frozen": withdrawals_frozen,
+            "reason": request.reason,
+        }),
+    );
+
+    publish(state, account_id, AccountEvent::KillSwitchEngaged {
+        account_id,
+        actor,
+        cancelled_orders: cancelled_ids,
+        withdrawals_frozen,
+        reason: request.reason,
+        timestamp: Utc::now(),
+    }).await;
+
+    Ok(KillSwitchStatus {
+        account_id,
+        orders_blocked: true,
+        withdrawals_frozen,
+        cancelled_orders: cancelled.iter().map(|order| order.as_ref().clone()).collect(),
+    })
+}
+
+async fn ensure_account_exists(state: &AppState, account_id: Uuid) -> Result<(), ApiError> {
+    state.account_service.get_account(account_id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", account_id)))?;
+    Ok(())
+}
+
+async fn publish(state: &AppState, account_id: Uuid, event: AccountEvent) {
+    state.market_data_service.channel()
+        .publish(Topic::Account(account_id), event)
+        .await;
+}
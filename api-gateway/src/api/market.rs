@@ -3,21 +3,42 @@
 //! Handlers for market data endpoints including:
 //! - List all markets
 //! - Get order book data
-//! - Get market ticker information
-//! - Retrieve market trades
+//! - Replay historical order book snapshots
+//! - Get market ticker information, optionally converted into another currency
+//! - Retrieve market trades, or the full tape including dust
 //! - Get OHLCV candles
+//! - Get spread, depth and trade flow analytics
+//! - Estimate the price impact of an order of a given size
+//! - Get the trading session state and calendar
+//! - List the read-only shadow markets mirrored from an external exchange
+//!
+//! Markets, order book and candle responses carry an `ETag` (and where known
+//! `Last-Modified`) so polling clients can revalidate with a `304`.
 
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 use axum::{
     extract::{Path, Query, State},
+    http::HeaderMap,
 };
-use market_data::{CandleInterval, Ticker, TradeMessage, Candle};
+use chrono::{DateTime, Utc};
+use common::decimal::{Price, Quantity};
+use common::model::market::MarketSession;
+use common::model::order::Side;
+use market_data::{CandleFill, CandleInterval, Ticker, TradeMessage, Candle, MarketAnalytics, MarketDepth};
+use market_data::heatmap::Heatmap;
+use market_data::shadow::ShadowMarketStatus;
+use market_data::sync::Levels;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 use crate::error::ApiError;
+use crate::valuation::{convert_ticker, TickerConversion};
 use crate::AppState;
+use crate::api::conditional::{Conditional, Validators};
+use crate::api::order::slippage_bps;
 use crate::api::response::{ApiResponse, ApiListResponse};
 
 /// Get all markets
@@ -26,15 +47,24 @@ use crate::api::response::{ApiResponse, ApiListResponse};
     path = "/api/v1/markets",
     responses(
         (status = 200, description = "List of available markets retrieved successfully"),
+        (status = 304, description = "Markets unchanged since the client's copy"),
         (status = 500, description = "Internal server error")
     ),
     tag = "market"
 )]
 pub async fn get_markets(
     State(state): State<Arc<AppState>>,
-) -> Result<ApiListResponse<common::model::market::Market>, ApiError> {
+    headers: HeaderMap,
+) -> Result<Conditional<ApiListResponse<common::model::market::Market>>, ApiError> {
+    // Markets only change with configuration, so tag them by content
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(&state.markets)
+        .map_err(|e| ApiError::Internal(e.to_string()))?
+        .hash(&mut hasher);
+    let validators = Validators::new(format!("{:016x}", hasher.finish()));
+
     // Return a standardized list response with all markets
-    Ok(ApiListResponse::new(state.markets.clone()))
+    Conditional::respond(validators, &headers, || Ok(ApiListResponse::new(state.markets.clone())))
 }
 
 /// Order book query parameters
@@ -60,6 +90,14 @@ pub struct OrderBookData {
     pub asks: Vec<(common::decimal::Price, common::decimal::Quantity)>,
 }
 
+/// A market's book from the matching engine, or from market data for a shadow market
+pub(crate) fn book_depth(state: &AppState, market: &str, depth: usize) -> common::error::Result<(Levels, Levels)> {
+    match state.market_data_service.shadow_depth(market, depth) {
+        Some(levels) => Ok(levels),
+        None => state.matching_engine.get_market_depth(market, depth),
+    }
+}
+
 /// Get order book
 #[utoipa::path(
     get,
@@ -70,6 +108,7 @@ pub struct OrderBookData {
     ),
     responses(
         (status = 200, description = "Order book retrieved successfully"),
+        (status = 304, description = "Order book unchanged since the client's copy"),
         (status = 404, description = "Market not found"),
         (status = 500, description = "Internal server error")
     ),
@@ -79,20 +118,130 @@ pub async fn get_order_book(
     State(state): State<Arc<AppState>>,
     Path(market): Path<String>,
     Query(query): Query<OrderBookQuery>,
-) -> Result<ApiResponse<OrderBookData>, ApiError> {
-    // Get market depth from matching engine
-    let (bids, asks) = state.matching_engine.get_market_depth(&market, query.depth)
-        .map_err(ApiError::Common)?;
-    
-    // Create order book data
-    let order_book = OrderBookData {
-        market,
-        bids,
-        asks,
+    headers: HeaderMap,
+) -> Result<Conditional<ApiResponse<OrderBookData>>, ApiError> {
+    // Tag by the last published depth sequence. It is read before the book so
+    // a concurrent update can only make the tag older than the body, never newer
+    let validators = match state.market_data_service.get_market_depth(&market) {
+        Some(depth) => Validators::new(format!("{}-{}", depth.sequence, depth.timestamp.timestamp_millis()))
+            .last_modified(depth.timestamp),
+        None => Validators::new("0"),
     };
-    
-    // Return standardized response
-    Ok(ApiResponse::new(order_book))
+
+    Conditional::respond(validators, &headers, || {
+        let (bids, asks) = book_depth(&state, &market, query.depth)
+            .map_err(ApiError::Common)?;
+
+        // Create order book data
+        let order_book = OrderBookData {
+            market,
+            bids,
+            asks,
+        };
+
+        // Return standardized response
+        Ok(ApiResponse::new(order_book))
+    })
+}
+
+/// Historical order book query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct OrderBookHistoryQuery {
+    /// Time to replay the order book at, RFC 3339
+    pub at: DateTime<Utc>,
+}
+
+/// Get the order book as of a past time
+///
+/// Returns the newest snapshot taken at or before `at`. Snapshots are taken
+/// periodically, so the book may have changed between the snapshot and `at`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/markets/{market}/order-book/history",
+    params(
+        ("market" = String, Path, description = "Market symbol"),
+        ("at" = String, Query, description = "Time to replay the order book at, RFC 3339")
+    ),
+    responses(
+        (status = 200, description = "Order book snapshot retrieved successfully"),
+        (status = 400, description = "Missing or invalid time"),
+        (status = 404, description = "No snapshot of the market at or before the time"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "market"
+)]
+pub async fn get_order_book_history(
+    State(state): State<Arc<AppState>>,
+    Path(market): Path<String>,
+    Query(query): Query<OrderBookHistoryQuery>,
+) -> Result<ApiResponse<MarketDepth>, ApiError> {
+    let snapshot = state.market_data_service.get_order_book_at(&market, query.at).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("No order book snapshot of {} at or before {}", market, query.at)))?;
+
+    Ok(ApiResponse::new(snapshot))
+}
+
+/// Liquidity heatmap query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct HeatmapQuery {
+    /// Length of the window, as a candle interval code such as `1h` or `1d`
+    #[serde(default = "default_heatmap_window")]
+    pub window: String,
+    /// Number of price buckets
+    #[serde(default = "default_heatmap_buckets")]
+    pub buckets: usize,
+    /// End of the window, exclusive, now by default
+    pub to: Option<DateTime<Utc>>,
+}
+
+fn default_heatmap_window() -> String {
+    "1h".to_string()
+}
+
+fn default_heatmap_buckets() -> usize {
+    100
+}
+
+/// Get resting liquidity by price over time for a market
+///
+/// Spreads the depth samples taken in the window over equal price buckets
+/// between the lowest and highest sampled price, one column per sample.
+/// Samples are taken every few seconds, so the book may have changed
+/// between them.
+#[utoipa::path(
+    get,
+    path = "/api/v1/markets/{market}/heatmap",
+    params(
+        ("market" = String, Path, description = "Market symbol"),
+        ("window" = Option<String>, Query, description = "Length of the window, e.g. 15m or 1h (default)"),
+        ("buckets" = Option<usize>, Query, description = "Number of price buckets, at most 500 (default 100)"),
+        ("to" = Option<String>, Query, description = "RFC 3339 exclusive end, now by default")
+    ),
+    responses(
+        (status = 200, description = "Heatmap retrieved successfully", body = Heatmap),
+        (status = 400, description = "Invalid window or bucket count"),
+        (status = 404, description = "No order book for the market"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "market"
+)]
+pub async fn get_heatmap(
+    State(state): State<Arc<AppState>>,
+    Path(market): Path<String>,
+    Query(query): Query<HeatmapQuery>,
+) -> Result<ApiResponse<Heatmap>, ApiError> {
+    let window = CandleInterval::from_code(&query.window)
+        .ok_or_else(|| ApiError::BadRequest(format!("Invalid window: {}", query.window)))?;
+    if state.market_data_service.get_market_depth(&market).is_none() {
+        return Err(ApiError::NotFound(format!("Order book not found for market: {}", market)));
+    }
+
+    let to = query.to.unwrap_or_else(|| state.matching_engine.clock().now());
+    let from = to - chrono::Duration::seconds(window.duration_secs());
+    let heatmap = state.market_data_service.get_heatmap(&market, from, to, query.buckets).await
+        .map_err(ApiError::Common)?;
+    Ok(ApiResponse::new(heatmap))
 }
 
 /// Get ticker for a market
@@ -121,26 +270,85 @@ pub async fn get_ticker(
     Ok(ApiResponse::new(ticker))
 }
 
+/// Tickers query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TickersQuery {
+    /// Currency to convert prices and quote volumes into, e.g. `EUR`
+    pub convert: Option<String>,
+}
+
+/// Ticker of a market, with its values in another currency if asked for
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MarketTicker {
+    /// The ticker, in the market's quote asset
+    #[serde(flatten)]
+    pub ticker: Ticker,
+    /// Last, high, low and quote volume in the requested currency
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub converted: Option<TickerConversion>,
+}
+
 /// Get all tickers
+///
+/// With `convert`, each ticker's last, high and low prices and its quote
+/// volume are also given in that currency, converted at mark prices through
+/// the fewest markets. Values that cannot be converted are `null`.
 #[utoipa::path(
     get,
     path = "/api/v1/markets/tickers",
+    params(
+        ("convert" = Option<String>, Query, description = "Currency to also give prices and quote volumes in, e.g. EUR")
+    ),
     responses(
-        (status = 200, description = "All tickers retrieved successfully"),
+        (status = 200, description = "All tickers retrieved successfully", body = Vec<MarketTicker>),
         (status = 500, description = "Internal server error")
     ),
     tag = "market"
 )]
 pub async fn get_tickers(
     State(state): State<Arc<AppState>>,
-) -> Result<ApiListResponse<Ticker>, ApiError> {
+    Query(query): Query<TickersQuery>,
+) -> Result<ApiListResponse<MarketTicker>, ApiError> {
     // Get all tickers from market data service
     let tickers = state.market_data_service.get_all_tickers();
-    
+    let currency = query.convert.map(|currency| currency.to_uppercase());
+
+    let tickers = tickers
+        .into_iter()
+        .map(|ticker| MarketTicker {
+            converted: currency.as_deref().map(|currency| convert_ticker(
+                &state.matching_engine,
+                &state.markets,
+                state.conversion.as_ref(),
+                &ticker,
+                currency,
+            )),
+            ticker,
+        })
+        .collect();
+
     // Return standardized list response
     Ok(ApiListResponse::new(tickers))
 }
 
+/// List the shadow markets with their ingestion state
+///
+/// Shadow markets mirror an external exchange's book and trades, served by
+/// the usual market data endpoints. They cannot be traded.
+#[utoipa::path(
+    get,
+    path = "/api/v1/markets/shadow",
+    responses(
+        (status = 200, description = "Shadow markets retrieved successfully")
+    ),
+    tag = "market"
+)]
+pub async fn get_shadow_markets(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiListResponse<ShadowMarketStatus>, ApiError> {
+    Ok(ApiListResponse::new(state.market_data_service.shadow_markets()))
+}
+
 /// Trades query parameters
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct TradesQuery {
@@ -162,7 +370,7 @@ pub struct MarketTradesData {
     pub trades: Vec<TradeMessage>,
 }
 
-/// Get recent trades
+/// Get recent trades, without ones below the market's minimum displayed size
 #[utoipa::path(
     get,
     path = "/api/v1/markets/{market}/trades",
@@ -195,6 +403,32 @@ pub async fn get_trades(
     Ok(ApiResponse::new(trade_data))
 }
 
+/// Get recent trades, including ones below the market's minimum displayed size
+#[utoipa::path(
+    get,
+    path = "/api/v1/markets/{market}/trades/raw",
+    security(("api_key" = [])),
+    params(
+        ("market" = String, Path, description = "Market symbol"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of trades to return")
+    ),
+    responses(
+        (status = 200, description = "Trades retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "market"
+)]
+pub async fn get_raw_trades(
+    State(state): State<Arc<AppState>>,
+    Path(market): Path<String>,
+    Query(query): Query<TradesQuery>,
+) -> Result<ApiResponse<MarketTradesData>, ApiError> {
+    let trades = state.market_data_service.get_raw_trades(&market, query.limit);
+
+    Ok(ApiResponse::new(MarketTradesData { market, trades }))
+}
+
 /// Candles query parameters
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct CandlesQuery {
@@ -204,6 +438,8 @@ pub struct CandlesQuery {
     /// Limit
     #[serde(default = "default_candles_limit")]
     pub limit: usize,
+    /// Fill periods without trades up to the current one, `zero` or `previous`
+    pub fill: Option<CandleFill>,
 }
 
 fn default_interval() -> String {
@@ -232,10 +468,12 @@ pub struct MarketCandleData {
     params(
         ("market" = String, Path, description = "Market symbol"),
         ("interval" = Option<String>, Query, description = "Candle interval (1m, 5m, 15m, 30m, 1h, 4h, 12h, 1d, 1w)"),
-        ("limit" = Option<usize>, Query, description = "Maximum number of candles to return")
+        ("limit" = Option<usize>, Query, description = "Maximum number of candles to return"),
+        ("fill" = Option<CandleFill>, Query, description = "Fill periods without trades with empty candles priced at zero (`zero`) or the previous close (`previous`)")
     ),
     responses(
         (status = 200, description = "Candles retrieved successfully"),
+        (status = 304, description = "Candles unchanged since the client's copy"),
         (status = 404, description = "Market not found"),
         (status = 400, description = "Invalid interval"),
         (status = 500, description = "Internal server error")
@@ -246,31 +484,197 @@ pub async fn get_candles(
     State(state): State<Arc<AppState>>,
     Path(market): Path<String>,
     Query(query): Query<CandlesQuery>,
-) -> Result<ApiResponse<MarketCandleData>, ApiError> {
+    headers: HeaderMap,
+) -> Result<Conditional<ApiResponse<MarketCandleData>>, ApiError> {
     // Parse the interval string
-    let interval = match query.interval.as_str() {
-        "1m" => CandleInterval::Minute1,
-        "5m" => CandleInterval::Minute5,
-        "15m" => CandleInterval::Minute15,
-        "30m" => CandleInterval::Minute30,
-        "1h" => CandleInterval::Hour1,
-        "4h" => CandleInterval::Hour4,
-        "12h" => CandleInterval::Hour12,
-        "1d" => CandleInterval::Day1,
-        "1w" => CandleInterval::Week1,
-        _ => return Err(ApiError::BadRequest(format!("Invalid interval: {}", query.interval))),
-    };
+    let interval = CandleInterval::from_code(&query.interval)
+        .ok_or_else(|| ApiError::BadRequest(format!("Invalid interval: {}", query.interval)))?;
     
     // Get candles from market data service
-    let candles = state.market_data_service.get_candles(&market, interval, query.limit);
-    
-    // Create candle data
-    let candle_data = MarketCandleData {
-        market,
-        interval: query.interval,
-        candles,
+    let candles = match query.fill {
+        Some(fill) => state.market_data_service.get_filled_candles(&market, interval, query.limit, fill),
+        None => state.market_data_service.get_candle_history(&market, interval, query.limit).await
+            .map_err(ApiError::Common)?,
     };
-    
-    // Return standardized response
-    Ok(ApiResponse::new(candle_data))
-}
\ No newline at end of file
+
+    // Only the newest candle changes, so tag by its open time and trade count
+    let validators = match candles.first() {
+        Some(latest) => Validators::new(format!("{}-{}", latest.open_time.timestamp(), latest.trades)),
+        None => Validators::new("empty"),
+    };
+
+    Conditional::respond(validators, &headers, || {
+        // Create candle data
+        let candle_data = MarketCandleData {
+            market,
+            interval: query.interval,
+            candles,
+        };
+
+        // Return standardized response
+        Ok(ApiResponse::new(candle_data))
+    })
+}
+
+/// Analytics query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AnalyticsQuery {
+    /// Distance from the mid, in basis points, to measure depth within
+    #[serde(default = "default_depth_bps")]
+    pub depth_bps: u32,
+    /// Number of recent trades to measure trade flow over
+    #[serde(default = "default_trades_limit")]
+    pub trades: usize,
+}
+
+fn default_depth_bps() -> u32 {
+    10
+}
+
+/// Get spread, depth and trade flow analytics for a market
+#[utoipa::path(
+    get,
+    path = "/api/v1/markets/{market}/analytics",
+    params(
+        ("market" = String, Path, description = "Market symbol"),
+        ("depth_bps" = Option<u32>, Query, description = "Distance from the mid, in basis points, to measure depth within (default 10)"),
+        ("trades" = Option<usize>, Query, description = "Number of recent trades to measure trade flow over (default 100)")
+    ),
+    responses(
+        (status = 200, description = "Analytics computed successfully"),
+        (status = 400, description = "Invalid depth band"),
+        (status = 404, description = "No order book or trades for the market"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "market"
+)]
+pub async fn get_analytics(
+    State(state): State<Arc<AppState>>,
+    Path(market): Path<String>,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<ApiResponse<MarketAnalytics>, ApiError> {
+    if query.depth_bps > 10_000 {
+        return Err(ApiError::BadRequest(format!("depth_bps must be at most 10000, got {}", query.depth_bps)));
+    }
+
+    let analytics = state.market_data_service.get_analytics(&market, query.depth_bps, query.trades)
+        .ok_or_else(|| ApiError::NotFound(format!("No market data for market: {}", market)))?;
+
+    Ok(ApiResponse::new(analytics))
+}
+
+/// Price impact query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PriceImpactQuery {
+    /// Side of the hypothetical order, `buy` or `sell`
+    pub side: String,
+    /// Quantity of the hypothetical order
+    pub quantity: Quantity,
+}
+
+/// Estimated cost of taking a quantity from the current book
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PriceImpact {
+    /// Market symbol
+    pub market: String,
+    /// Side of the hypothetical order
+    pub side: Side,
+    /// Quantity asked for
+    pub quantity: Quantity,
+    /// Quantity the book could fill
+    pub filled_quantity: Quantity,
+    /// Quantity left once the book runs out
+    pub unfilled_quantity: Quantity,
+    /// Volume weighted price of the fills
+    pub average_price: Option<Price>,
+    /// Best opposite price the fills start from
+    pub best_price: Option<Price>,
+    /// Price of the last level the fills reach
+    pub worst_price: Option<Price>,
+    /// How much worse than `best_price` the average price is, in basis points
+    pub slippage_bps: Option<f64>,
+    /// Quote value of the fills
+    pub notional: Quantity,
+}
+
+/// Estimate the price impact of an order of a given size
+///
+/// Walks the opposite side of the current book like a market order would,
+/// without an account, funds or session checks. Nothing is placed.
+#[utoipa::path(
+    get,
+    path = "/api/v1/markets/{market}/impact",
+    params(
+        ("market" = String, Path, description = "Market symbol"),
+        ("side" = String, Query, description = "Side of the hypothetical order, buy or sell"),
+        ("quantity" = String, Query, description = "Quantity of the hypothetical order, e.g. 2.5")
+    ),
+    responses(
+        (status = 200, description = "Price impact estimated successfully", body = PriceImpact),
+        (status = 400, description = "Invalid side or quantity"),
+        (status = 404, description = "Market not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "market"
+)]
+pub async fn get_price_impact(
+    State(state): State<Arc<AppState>>,
+    Path(market): Path<String>,
+    Query(query): Query<PriceImpactQuery>,
+) -> Result<ApiResponse<PriceImpact>, ApiError> {
+    let side = match query.side.to_ascii_lowercase().as_str() {
+        "buy" => Side::Buy,
+        "sell" => Side::Sell,
+        _ => return Err(ApiError::BadRequest(format!("Invalid side: {}", query.side))),
+    };
+    if query.quantity <= Quantity::ZERO {
+        return Err(ApiError::BadRequest(format!("quantity must be positive, got {}", query.quantity)));
+    }
+
+    let fills = state.matching_engine.estimate_impact(&market, side, query.quantity)
+        .map_err(ApiError::Common)?;
+
+    let filled_quantity: Quantity = fills.iter().map(|(_, quantity)| *quantity).sum();
+    let notional: Quantity = fills.iter().map(|(price, quantity)| price * quantity).sum();
+    let average_price = (!filled_quantity.is_zero()).then(|| notional / filled_quantity);
+    let best_price = fills.first().map(|(price, _)| *price);
+    let slippage_bps = average_price.zip(best_price)
+        .and_then(|(average, best)| slippage_bps(side, average, best));
+
+    Ok(ApiResponse::new(PriceImpact {
+        market,
+        side,
+        quantity: query.quantity,
+        filled_quantity,
+        unfilled_quantity: query.quantity - filled_quantity,
+        average_price,
+        best_price,
+        worst_price: fills.last().map(|(price, _)| *price),
+        slippage_bps,
+        notional,
+    }))
+}
+
+/// Get a market's trading session state, calendar and next scheduled change
+#[utoipa::path(
+    get,
+    path = "/api/v1/markets/{market}/session",
+    params(
+        ("market" = String, Path, description = "Market symbol")
+    ),
+    responses(
+        (status = 200, description = "Market session retrieved successfully"),
+        (status = 404, description = "Market not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "market"
+)]
+pub async fn get_market_session(
+    State(state): State<Arc<AppState>>,
+    Path(market): Path<String>,
+) -> Result<ApiResponse<MarketSession>, ApiError> {
+    let session = state.matching_engine.market_session(&market, Utc::now())
+        .map_err(ApiError::Common)?;
+
+    Ok(ApiResponse::new(session))
+}
@@ -8,9 +8,30 @@
 //! - Map the result to a standardized response format
 
 pub mod account;
+pub mod adjustment;
+pub mod admin;
+pub mod api_key;
+pub mod asset;
+pub mod bust;
+pub mod closure;
+pub mod conditional;
+pub mod data;
+pub mod duplicates;
+pub mod earn;
+pub mod funding;
+pub mod index_price;
+pub mod invoice;
+pub mod kill_switch;
 pub mod market;
+pub mod notification;
 pub mod order;
+pub mod permissions;
+pub mod report_job;
 pub mod response;
+pub mod security;
+pub mod system;
+pub mod webhook;
+pub mod withdrawal;
 
 // Re-export the response module for easy access
 pub use response::{ApiResponse, PaginatedResponse, ApiListResponse};
\ No newline at end of file
@@ -0,0 +1,80 @@
+//! Notification preference handlers
+//!
+//! Account holders choose which events they are notified about and the email
+//! address and URL notifications are sent to.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use uuid::Uuid;
+
+use crate::auth::AuthContext;
+use crate::error::ApiError;
+use crate::notification::NotificationPreferences;
+use crate::AppState;
+use crate::api::response::ApiResponse;
+
+/// Get an account's notification preferences
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/notifications",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Preferences retrieved successfully", body = NotificationPreferences),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account")
+    ),
+    tag = "account"
+)]
+pub async fn get_notification_preferences(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiResponse<NotificationPreferences>, ApiError> {
+    auth.ensure_account(id)?;
+
+    Ok(ApiResponse::new(state.notifications.preferences(id)))
+}
+
+/// Replace an account's notification preferences
+#[utoipa::path(
+    put,
+    path = "/api/v1/accounts/{id}/notifications",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    request_body = NotificationPreferences,
+    responses(
+        (status = 200, description = "Preferences saved", body = NotificationPreferences),
+        (status = 400, description = "Invalid email address or URL"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Account not found")
+    ),
+    tag = "account"
+)]
+pub async fn set_notification_preferences(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Json(preferences): Json<NotificationPreferences>,
+) -> Result<ApiResponse<NotificationPreferences>, ApiError> {
+    auth.ensure_account(id)?;
+
+    // Verify the account exists before saving its preferences
+    let _ = state.account_service.get_account(id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", id)))?;
+
+    let preferences = state.notifications.set_preferences(id, preferences)
+        .map_err(ApiError::Common)?;
+
+    Ok(ApiResponse::new(preferences))
+}
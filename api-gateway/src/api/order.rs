@@ -2,25 +2,41 @@
 //!
 //! Handlers for order management endpoints including:
 //! - Place new orders
+//! - Preview the fills and funds of an order without placing it
 //! - Cancel existing orders
 //! - Get order details
+//! - Get the trades that filled an order
 //! - List orders by user
 
 use std::sync::Arc;
 
 use axum::{
-    extract::{Path, Query, State},
-    Json,
+    extract::{FromRequest, Path, Query, Request, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
-use common::model::order::{Order, OrderType, Side, TimeInForce};
-use common::model::trade::Trade;
+use account_service::AccountService;
+use common::decimal::{Price, Quantity};
+use common::error::Error;
+use common::flags::NEW_SETTLEMENT_PIPELINE;
+use common::id::IdGenerator;
+use common::model::market::Market;
+use common::model::order::{Order, OrderTransition, OrderType, Peg, Side, TimeInForce};
+use common::model::trade::{OrderFill, PendingSettlement, Trade};
+use market_data::MarketDataService;
+use matching_engine::MatchingEngine;
+use rust_decimal::prelude::ToPrimitive;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use utoipa::ToSchema;
 
+use crate::auth::AuthContext;
+use crate::duplicates::DuplicateAction;
 use crate::error::ApiError;
+use crate::latency::{LatencyBreakdown, Stage, StageTimer};
+use crate::pipeline::{settlement_accounts, SettlementPipeline};
 use crate::AppState;
-use crate::api::response::{ApiResponse, ApiListResponse};
+use crate::api::response::{ApiResponse, ApiListResponse, Created};
 
 /// Place order request
 #[derive(Debug, Deserialize, ToSchema)]
@@ -33,19 +49,116 @@ pub struct PlaceOrderRequest {
     pub side: Side,
     /// Order type
     pub order_type: OrderType,
-    /// Price (for limit orders)
+    /// Price of limit orders, or the worst price a market order may fill at
     pub price: Option<common::decimal::Price>,
     /// Quantity
     pub quantity: common::decimal::Quantity,
     /// Time in force
     #[serde(default = "default_time_in_force")]
     pub time_in_force: TimeInForce,
+    /// Furthest a market order may fill from the best price on arrival, in basis points
+    #[serde(default)]
+    pub max_slippage_bps: Option<u32>,
+    /// Only trade what reduces the account's position, clipping the quantity to it
+    #[serde(default)]
+    pub reduce_only: bool,
+    /// Reference for the order, telling it apart from identical orders
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+    /// Peg a good-til-cancelled limit order to the mid or its side's best
+    /// price plus an offset; `price` is then the furthest it may move to
+    #[serde(default)]
+    pub peg: Option<Peg>,
+    /// Least quantity the order's first execution may be; IOC, FOK and market
+    /// orders short of it on arrival are rejected, resting orders wait for it
+    #[serde(default)]
+    pub min_fill_quantity: Option<common::decimal::Quantity>,
 }
 
 fn default_time_in_force() -> TimeInForce {
     TimeInForce::GTC
 }
 
+impl PlaceOrderRequest {
+    /// Create the order the request describes, with an id from `ids`
+    pub fn into_order(self, ids: &dyn IdGenerator) -> Result<Order, ApiError> {
+        if self.min_fill_quantity.is_some_and(|min| min <= common::decimal::Quantity::ZERO || min > self.quantity) {
+            return Err(ApiError::BadRequest("Minimum fill quantity must be positive and at most the quantity".to_string()));
+        }
+        let mut order = match self.order_type {
+            OrderType::Limit => {
+                let price = self.price.ok_or_else(|| {
+                    ApiError::BadRequest("Limit orders must have a price".to_string())
+                })?;
+                if self.max_slippage_bps.is_some() {
+                    return Err(ApiError::BadRequest("Only market orders can have a max slippage".to_string()));
+                }
+                if self.peg.is_some() && self.time_in_force != TimeInForce::GTC {
+                    return Err(ApiError::BadRequest("Only good-til-cancelled orders can be pegged".to_string()));
+                }
+                
+                let mut order = Order::new_limit(
+                    self.user_id,
+                    self.market,
+                    self.side,
+                    price,
+                    self.quantity,
+                    self.time_in_force,
+                );
+                // Funds are reserved at the limit, which the peg never moves past
+                order.peg = self.peg.map(|peg| Peg { limit: Some(price), ..peg });
+                order
+            },
+            OrderType::Market => {
+                if self.peg.is_some() {
+                    return Err(ApiError::BadRequest("Only limit orders can be pegged".to_string()));
+                }
+                let mut order = Order::new_market(
+                    self.user_id,
+                    self.market,
+                    self.side,
+                    self.quantity,
+                );
+                order.price = self.price;
+                order.max_slippage_bps = self.max_slippage_bps;
+                order
+            },
+        };
+        order.id = ids.next_id();
+        order.reduce_only = self.reduce_only;
+        order.client_order_id = self.client_order_id;
+        order.min_fill_quantity = self.min_fill_quantity;
+        Ok(order)
+    }
+
+    /// Check the request against its market's price, quantity and size filters
+    pub fn check_filters(&self, markets: &[Market]) -> Result<(), ApiError> {
+        let market = markets.iter()
+            .find(|market| market.symbol == self.market)
+            .ok_or_else(|| ApiError::Common(Error::MarketNotFound(format!("Market not found: {}", self.market))))?;
+
+        if self.order_type == OrderType::Limit && self.price.is_none() {
+            return Err(ApiError::BadRequest("Limit orders must have a price".to_string()));
+        }
+        if let Some(peg) = &self.peg {
+            if !market.price_tick.is_zero() && !(peg.offset % market.price_tick).is_zero() {
+                return Err(ApiError::BadRequest(format!(
+                    "Peg offset {} is not a multiple of the tick {}", peg.offset, market.price_tick
+                )));
+            }
+        }
+        market.check_order(self.price, self.quantity).map_err(ApiError::Common)
+    }
+}
+
+/// Order placement query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PlaceOrderQuery {
+    /// Return the time spent in each stage of the order path
+    #[serde(default)]
+    pub latency_breakdown: bool,
+}
+
 /// Order placement result
 #[derive(Debug, Serialize, ToSchema)]
 pub struct OrderPlacementResult {
@@ -53,95 +166,431 @@ pub struct OrderPlacementResult {
     pub order: Order,
     /// Trades that were generated
     pub trades: Vec<Trade>,
+    /// Worst price a market order was allowed to fill at, from its price or slippage limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price_cap: Option<common::decimal::Price>,
+    /// Time spent in each stage of the order path, when asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_breakdown: Option<LatencyBreakdown>,
 }
 
 /// Place a new order
 #[utoipa::path(
     post,
     path = "/api/v1/orders",
+    security(("api_key" = [])),
+    params(
+        ("latency_breakdown" = Option<bool>, Query, description = "Return the time spent in each stage of the order path")
+    ),
     request_body = PlaceOrderRequest,
     responses(
-        (status = 200, description = "Order placed successfully"),
+        (status = 201, description = "Order placed successfully, with its path in Location"),
+        (status = 200, description = "Duplicate of a recent order, which is returned instead"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account or does not grant trading the market"),
         (status = 400, description = "Invalid order request"),
+        (status = 409, description = "Duplicate of a recent order"),
         (status = 500, description = "Internal server error")
     ),
     tag = "order"
 )]
 pub async fn place_order(
     State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Query(query): Query<PlaceOrderQuery>,
+    request: Request,
+) -> Result<Response, ApiError> {
+    let mut timer = StageTimer::start();
+
+    // Decode the body here rather than in an extractor so it can be timed
+    let Json(request) = Json::<PlaceOrderRequest>::from_request(request, &()).await
+        .map_err(|rejection| ApiError::BadRequest(rejection.body_text()))?;
+    timer.lap(Stage::Parse);
+
+    auth.ensure_account(request.user_id)?;
+    auth.ensure_can_trade(&request.market)?;
+    let order = request.into_order(state.matching_engine.id_generator().as_ref())?;
+    if let Some(duplicate) = state.duplicate_orders.check(&order) {
+        return match duplicate.action {
+            DuplicateAction::Reject => Err(ApiError::Common(Error::Conflict(format!(
+                "Duplicate of order {} placed {} ms ago", duplicate.order.id, duplicate.age.as_millis()
+            )))),
+            DuplicateAction::Deduplicate => {
+                // The earlier order as it stands, if it still rests
+                let order = state.matching_engine.get_order(duplicate.order.id)
+                    .map_or(duplicate.order, |order| order.as_ref().clone());
+                let result = OrderPlacementResult { order, trades: Vec::new(), price_cap: None, latency_breakdown: None };
+                Ok(ApiResponse::new(result).into_response())
+            }
+        };
+    }
+    timer.lap(Stage::RiskChecks);
+
+    let mut placement_result = match submit_order(&state, order.clone(), &mut timer).await {
+        Ok(result) => result,
+        Err(e) => {
+            // An order the engine never took may be placed again
+            if state.matching_engine.get_order(order.id).is_none() {
+                state.duplicate_orders.forget(&order);
+            }
+            return Err(e);
+        }
+    };
+    state.duplicate_orders.placed(&placement_result.order);
+
+    let breakdown = timer.finish();
+    state.latency.record(&breakdown);
+    if query.latency_breakdown {
+        placement_result.latency_breakdown = Some(breakdown);
+    }
+
+    // Return standardized response
+    let location = format!("/api/v1/orders/{}", placement_result.order.id);
+    Ok(Created::new(location, placement_result).into_response())
+}
+
+/// Fill an order would get at one price level
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PreviewFill {
+    /// Level price
+    pub price: Price,
+    /// Quantity filled at the level
+    pub quantity: Quantity,
+}
+
+/// Estimated outcome of placing an order against the current book
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrderPreview {
+    /// Fills against the current book, best price first
+    pub fills: Vec<PreviewFill>,
+    /// Quantity the fills add up to
+    pub filled_quantity: Quantity,
+    /// Quantity left once the fills are done
+    pub unfilled_quantity: Quantity,
+    /// Whether the unfilled quantity would rest on the book rather than expire
+    pub rests: bool,
+    /// Volume weighted price of the fills
+    pub average_price: Option<Price>,
+    /// Best opposite price the fills start from
+    pub best_price: Option<Price>,
+    /// How much worse than `best_price` the average price is, in basis points
+    pub slippage_bps: Option<f64>,
+    /// Quote value of the fills
+    pub notional: Quantity,
+    /// Taker fee on the fills
+    pub estimated_fee: Quantity,
+    /// Net amount the fills pay out, after the fee
+    pub estimated_receive: Quantity,
+    /// Asset paid out and charged the fee: base for buys, quote for sells
+    pub receive_asset: String,
+    /// Asset locked when the order is placed
+    pub funds_asset: String,
+    /// Amount locked when the order is placed
+    pub funds_required: Quantity,
+    /// Available balance of `funds_asset`
+    pub funds_available: Quantity,
+    /// Whether the available balance covers the funds required
+    pub sufficient_funds: bool,
+}
+
+/// How much worse than `best` an average fill price on `side` is, in basis
+/// points
+pub(crate) fn slippage_bps(side: Side, average: Price, best: Price) -> Option<f64> {
+    if best.is_zero() {
+        return None;
+    }
+    let worse = match side {
+        Side::Buy => average - best,
+        Side::Sell => best - average,
+    };
+    (worse / best * Price::from(10_000)).to_f64()
+}
+
+/// Preview an order without placing it
+///
+/// Checks the order against its market's filters, the account's kill switch
+/// and the market session like a placement would, then estimates its fills
+/// and slippage from the current book and compares the funds it needs with
+/// the available balance. Nothing is reserved or matched.
+#[utoipa::path(
+    post,
+    path = "/api/v1/orders/preview",
+    security(("api_key" = [])),
+    request_body = PlaceOrderRequest,
+    responses(
+        (status = 200, description = "Estimated fills, slippage, fee and funds", body = OrderPreview),
+        (status = 400, description = "Order fails its market's filters or cannot be placed now"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account, or the account is blocked"),
+        (status = 404, description = "Market not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "order"
+)]
+pub async fn preview_order(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
     Json(request): Json<PlaceOrderRequest>,
-) -> Result<ApiResponse<OrderPlacementResult>, ApiError> {
-    // Create order from request
-    let order = match request.order_type {
-        OrderType::Limit => {
-            let price = request.price.ok_or_else(|| {
-                ApiError::BadRequest("Limit orders must have a price".to_string())
-            })?;
-            
-            Order::new_limit(
-                request.user_id,
-                request.market,
-                request.side,
-                price,
-                request.quantity,
-                request.time_in_force,
-            )
-        },
-        OrderType::Market => {
-            Order::new_market(
-                request.user_id,
-                request.market,
-                request.side,
-                request.quantity,
-            )
-        },
+) -> Result<ApiResponse<OrderPreview>, ApiError> {
+    auth.ensure_account(request.user_id)?;
+    request.check_filters(&state.markets)?;
+    let mut order = request.into_order(state.matching_engine.id_generator().as_ref())?;
+    check_quantity_precision(&state, &order)?;
+    state.account_service.clip_reduce_only(&mut order)
+        .map_err(ApiError::Common)?;
+
+    let (funds_asset, funds_required) = state.account_service.funds_required(&order)
+        .map_err(ApiError::Common)?;
+    let levels = state.matching_engine.preview_order(&order)
+        .map_err(ApiError::Common)?;
+    let symbol = order.symbol().map_err(ApiError::Common)?;
+
+    // Settle pending fills so the balance reflects them
+    state.settlement.flush(order.user_id).await;
+    let funds_available = state.account_service.get_balance(order.user_id, &funds_asset).await
+        .map_err(ApiError::Common)?
+        .map(|balance| balance.available)
+        .unwrap_or_default();
+
+    let filled_quantity: Quantity = levels.iter().map(|(_, quantity)| *quantity).sum();
+    let notional: Quantity = levels.iter().map(|(price, quantity)| price * quantity).sum();
+    let average_price = (!filled_quantity.is_zero()).then(|| notional / filled_quantity);
+    let best_price = state.matching_engine.get_market_depth(&order.market, 1).ok()
+        .and_then(|(bids, asks)| match order.side {
+            Side::Buy => asks.first().map(|(price, _)| *price),
+            Side::Sell => bids.first().map(|(price, _)| *price),
+        });
+    let slippage_bps = average_price.zip(best_price)
+        .and_then(|(average, best)| slippage_bps(order.side, average, best));
+
+    // Every fill takes liquidity, so it pays the taker rate on what it receives
+    let (receive_asset, gross) = match order.side {
+        Side::Buy => (symbol.base().as_str().to_string(), filled_quantity),
+        Side::Sell => (symbol.quote().as_str().to_string(), notional),
     };
+    let estimated_fee = gross * state.matching_engine.fee_schedule().taker_rate;
+
+    let unfilled_quantity = order.quantity - filled_quantity;
+    let rests = !unfilled_quantity.is_zero()
+        && order.order_type == OrderType::Limit
+        && order.time_in_force == TimeInForce::GTC;
+
+    Ok(ApiResponse::new(OrderPreview {
+        fills: levels.into_iter().map(|(price, quantity)| PreviewFill { price, quantity }).collect(),
+        filled_quantity,
+        unfilled_quantity,
+        rests,
+        average_price,
+        best_price,
+        slippage_bps,
+        notional,
+        estimated_fee,
+        estimated_receive: gross - estimated_fee,
+        receive_asset,
+        sufficient_funds: funds_available >= funds_required,
+        funds_asset,
+        funds_required,
+        funds_available,
+    }))
+}
+
+/// Check an order's quantity has no more decimal places than its base asset allows
+fn check_quantity_precision(state: &AppState, order: &Order) -> Result<(), ApiError> {
+    let symbol = order.symbol().map_err(ApiError::Common)?;
+    state.account_service.asset(symbol.base().as_str())
+        .check_precision(order.quantity)
+        .map_err(ApiError::Common)
+}
+
+/// Reserve funds for an order, match it, and settle and publish the result
+///
+/// Shared by order placement and the admin order import. Each stage is
+/// lapped on `timer`.
+pub async fn submit_order(state: &AppState, mut order: Order, timer: &mut StageTimer) -> Result<OrderPlacementResult, ApiError> {
+    // Shadow markets only mirror an external exchange
+    if state.market_data_service.is_shadow_market(&order.market) {
+        return Err(ApiError::BadRequest(format!("{} is a read-only shadow market", order.market)));
+    }
+    
+    check_quantity_precision(state, &order)?;
     
-    // Reserve funds for the order
+    // Reserve funds for the order, clipping reduce-only orders to the position first
+    state.account_service.clip_reduce_only(&mut order)
+        .map_err(ApiError::Common)?;
     state.account_service.reserve_for_order(&order).await
         .map_err(ApiError::Common)?;
+    timer.lap(Stage::Reserve);
     
-    // Place the order
-    let result = state.matching_engine.place_order(order.clone())
-        .map_err(ApiError::Common)?;
+    // Place the order, releasing the reservation if the engine refuses it
+    let result = match state.matching_engine.place_order(order.clone()) {
+        Ok(result) => result,
+        Err(e) => {
+            state.account_service.release_reserved_funds(&order).await
+                .map_err(ApiError::Common)?;
+            return Err(ApiError::Common(e));
+        }
+    };
+    timer.lap(Stage::Match);
     
-    // Process trades
-    for trade in &result.trades {
-        state.account_service.process_trade(trade).await
-            .map_err(ApiError::Common)?;
-        
-        state.market_data_service.process_trade(trade)
-            .await
-            .map_err(ApiError::Common)?;
-    }
+    // Settle and publish, in the background when the pipeline has workers
+    let trades = result.trades.into_vec();
+    let terminated: Vec<Arc<Order>> = result.taker_order.iter()
+        .filter(|o| o.is_engine_terminated())
+        .chain(&result.expired_orders)
+        .cloned()
+        .collect();
+    let pending = (state.settlement.is_async() && state.matching_engine.feature_flags().is_enabled(NEW_SETTLEMENT_PIPELINE))
+        .then(|| PendingSettlement::new(
+            order.id,
+            order.market.clone(),
+            trades.clone(),
+            terminated.iter().map(|o| o.as_ref().clone()).collect(),
+        ));
     
-    // Update order book
-    let market = order.market.clone();
-    if let Ok((bids, asks)) = state.matching_engine.get_market_depth(&market, 10) {
-        state.market_data_service.update_order_book(&market, bids, asks)
-            .await
+    // Store the trades before answering so a failed settlement is retried,
+    // settling inline if they cannot be stored
+    let pending = match pending {
+        // Claimed before it is stored, so a retry never runs it as well
+        Some(pending) if state.settlement.claim(pending.id) => {
+            match state.account_service.save_pending_settlement(&pending).await {
+                Ok(()) => Some(pending),
+                Err(e) => {
+                    tracing::warn!("Settling order {} before answering: {}", order.id, e);
+                    state.settlement.release(pending.id);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    if let Some(pending) = pending {
+        let accounts: Vec<Uuid> = settlement_accounts(&pending).into_iter().chain([order.user_id]).collect();
+        let job = settle_pending(
+            state.account_service.clone(),
+            state.market_data_service.clone(),
+            state.matching_engine.clone(),
+            state.settlement.clone(),
+            pending,
+        );
+        state.settlement.submit(accounts, job).await;
+        timer.lap(Stage::Settle);
+    } else {
+        settle_trades(state.account_service.clone(), trades.clone(), terminated).await
             .map_err(ApiError::Common)?;
+        timer.lap(Stage::Settle);
+        publish_trades(
+            state.market_data_service.clone(),
+            state.matching_engine.clone(),
+            trades.clone(),
+            order.market.clone(),
+        ).await.map_err(ApiError::Common)?;
+        timer.lap(Stage::Publish);
     }
     
     // Create placement result
     let placement_result = OrderPlacementResult {
         order: result.taker_order.map(|o| o.as_ref().clone()).unwrap_or(order),
-        trades: result.trades,
+        trades,
+        price_cap: result.price_cap,
+        latency_breakdown: None,
     };
     
-    // Return standardized response
-    Ok(ApiResponse::new(placement_result))
+    Ok(placement_result)
+}
+
+/// Settle and publish stored trades, deleting them once done
+///
+/// A failed attempt is recorded on the stored settlement and left for
+/// [`crate::pipeline::retry_pending_settlements`]. A retry skips the trades
+/// an earlier attempt already settled.
+pub(crate) async fn settle_pending(
+    account_service: Arc<AccountService>,
+    market_data_service: Arc<MarketDataService>,
+    matching_engine: Arc<MatchingEngine>,
+    settlement: Arc<SettlementPipeline>,
+    mut pending: PendingSettlement,
+) {
+    let settled = async {
+        let mut trades = Vec::with_capacity(pending.trades.len());
+        for trade in &pending.trades {
+            if pending.attempts == 0 || account_service.get_trade(trade.id).await?.is_none() {
+                trades.push(trade.clone());
+            }
+        }
+        let terminated = pending.terminated.iter().cloned().map(Arc::new).collect();
+        settle_trades(account_service.clone(), trades, terminated).await?;
+        publish_trades(market_data_service, matching_engine, pending.trades.clone(), pending.market.clone()).await?;
+        account_service.complete_pending_settlement(pending.id).await
+    }.await;
+
+    match settled {
+        Ok(()) => settlement.record_settled(pending.id),
+        Err(e) => {
+            tracing::error!("Settlement of order {} failed, retrying later: {}", pending.order_id, e);
+            pending.attempts += 1;
+            pending.last_error = Some(e.to_string());
+            if let Err(e) = account_service.save_pending_settlement(&pending).await {
+                tracing::error!("Failed to record failed settlement of order {}: {}", pending.order_id, e);
+            }
+            settlement.record_failure(pending.id, e.to_string());
+        }
+    }
+}
+
+/// Settle trades, then release funds held for orders the engine expired or rejected
+async fn settle_trades(
+    account_service: Arc<AccountService>,
+    trades: Vec<Trade>,
+    terminated: Vec<Arc<Order>>,
+) -> Result<(), Error> {
+    for trade in &trades {
+        account_service.process_trade(trade).await?;
+    }
+
+    for order in terminated {
+        tracing::info!(
+            "Order {} {:?} by engine: {}",
+            order.id,
+            order.status,
+            order.reject_message.as_deref().unwrap_or_default()
+        );
+
+        account_service.release_reserved_funds(&order).await?;
+    }
+    Ok(())
+}
+
+/// Publish trades and the market's order book
+async fn publish_trades(
+    market_data_service: Arc<MarketDataService>,
+    matching_engine: Arc<MatchingEngine>,
+    trades: Vec<Trade>,
+    market: String,
+) -> Result<(), Error> {
+    for trade in &trades {
+        market_data_service.process_trade(trade).await?;
+    }
+    if let Ok((bids, asks)) = matching_engine.get_market_depth(&market, 10) {
+        market_data_service.update_order_book(&market, bids, asks).await?;
+    }
+    Ok(())
 }
 
 /// Cancel an order
+///
+/// Also served on `POST /orders/{id}` for older clients, with a
+/// `Deprecation` header.
 #[utoipa::path(
-    post,
+    delete,
     path = "/api/v1/orders/{id}",
+    security(("api_key" = [])),
     params(
         ("id" = Uuid, Path, description = "Order ID to cancel")
     ),
     responses(
         (status = 200, description = "Order canceled successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
         (status = 404, description = "Order not found"),
         (status = 500, description = "Internal server error")
     ),
@@ -149,16 +598,23 @@ pub async fn place_order(
 )]
 pub async fn cancel_order(
     State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
     Path(id): Path<Uuid>,
 ) -> Result<ApiResponse<Order>, ApiError> {
     // Add logging for debugging
     tracing::info!("Attempting to cancel order: {}", id);
     
+    // Only the owner may cancel an order
+    let existing = state.matching_engine.get_order(id)
+        .ok_or_else(|| ApiError::NotFound(format!("Order not found: {}", id)))?;
+    auth.ensure_account(existing.user_id)?;
+    
     // Cancel the order
     let order = state.matching_engine.cancel_order(id)
         .map_err(ApiError::Common)?;
     
-    // Release reserved funds
+    // Release reserved funds once fills already matched are settled
+    state.settlement.flush(order.user_id).await;
     state.account_service.release_reserved_funds(&order).await
         .map_err(ApiError::Common)?;
     
@@ -180,11 +636,14 @@ pub async fn cancel_order(
 #[utoipa::path(
     get,
     path = "/api/v1/orders/{id}",
+    security(("api_key" = [])),
     params(
         ("id" = Uuid, Path, description = "Order ID")
     ),
     responses(
         (status = 200, description = "Order retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
         (status = 404, description = "Order not found"),
         (status = 500, description = "Internal server error")
     ),
@@ -192,16 +651,102 @@ pub async fn cancel_order(
 )]
 pub async fn get_order(
     State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
     Path(id): Path<Uuid>,
 ) -> Result<ApiResponse<Order>, ApiError> {
     // Get order from matching engine
     let order = state.matching_engine.get_order(id)
         .ok_or_else(|| ApiError::NotFound(format!("Order not found: {}", id)))?;
+    auth.ensure_account(order.user_id)?;
     
     // Return standardized response with the order
     Ok(ApiResponse::new(order.as_ref().clone()))
 }
 
+/// Get the trades that filled an order, with fees, liquidity flag and running average price
+///
+/// Fills are kept after the order leaves the book, so filled and canceled
+/// orders still report theirs.
+#[utoipa::path(
+    get,
+    path = "/api/v1/orders/{id}/fills",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Order fills retrieved successfully, oldest first"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Order not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "order"
+)]
+pub async fn get_order_fills(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiListResponse<OrderFill>, ApiError> {
+    state.settlement.flush(auth.account_id).await;
+    
+    let fills = state.account_service.get_order_fills(id).await
+        .map_err(ApiError::Common)?;
+    
+    // Orders without fills are only known while they rest in the book
+    let owner = match fills.first() {
+        Some(fill) => fill.account_id,
+        None => state.matching_engine.get_order(id)
+            .map(|order| order.user_id)
+            .ok_or_else(|| ApiError::NotFound(format!("Order not found: {}", id)))?,
+    };
+    auth.ensure_account(owner)?;
+    
+    Ok(ApiListResponse::new(fills))
+}
+
+/// Get an order's status changes, with the quantities filled and reasons, for its audit trail
+///
+/// Every change the engine made is kept, from the order's acceptance as
+/// `New` through its fills to its final status.
+#[utoipa::path(
+    get,
+    path = "/api/v1/orders/{id}/history",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Order history retrieved successfully, oldest first"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Order not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "order"
+)]
+pub async fn get_order_history(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiListResponse<OrderTransition>, ApiError> {
+    state.order_history.flush().await;
+    
+    let history = state.account_service.get_order_history(id).await
+        .map_err(ApiError::Common)?;
+    
+    // Orders the engine has not emitted yet are only known to it
+    let owner = match history.first() {
+        Some(transition) => transition.account_id,
+        None => state.matching_engine.get_order(id)
+            .map(|order| order.user_id)
+            .ok_or_else(|| ApiError::NotFound(format!("Order not found: {}", id)))?,
+    };
+    auth.ensure_account(owner)?;
+    
+    Ok(ApiListResponse::new(history))
+}
+
 /// Orders query parameters
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct OrdersQuery {
@@ -222,6 +767,7 @@ fn default_orders_limit() -> usize {
 #[utoipa::path(
     get,
     path = "/api/v1/accounts/{id}/orders",
+    security(("api_key" = [])),
     params(
         ("id" = Uuid, Path, description = "User ID"),
         ("market" = Option<String>, Query, description = "Filter by market"),
@@ -229,6 +775,8 @@ fn default_orders_limit() -> usize {
     ),
     responses(
         (status = 200, description = "Orders retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
         (status = 404, description = "User not found"),
         (status = 500, description = "Internal server error")
     ),
@@ -236,9 +784,12 @@ fn default_orders_limit() -> usize {
 )]
 pub async fn get_orders(
     State(_state): State<Arc<AppState>>,
-    Path(_user_id): Path<Uuid>,
+    Extension(auth): Extension<AuthContext>,
+    Path(user_id): Path<Uuid>,
     Query(_query): Query<OrdersQuery>,
 ) -> Result<ApiListResponse<Order>, ApiError> {
+    auth.ensure_account(user_id)?;
+    
     // TODO: Implement get orders by user ID and market
     // This is just a placeholder for MVP
     
@@ -0,0 +1,148 @@
+//! Account permission handlers
+//!
+//! Admins decide per account whether it may trade, which markets, with how
+//! much leverage, and whether it may withdraw. The permissions are checked
+//! when funds are reserved for an order and when a withdrawal is made, and
+//! become the scopes of the account's API keys, which holders can look up.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use common::model::account::AccountPermissions;
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::auth::AuthContext;
+use crate::error::ApiError;
+use crate::AppState;
+use crate::api::response::ApiResponse;
+
+/// Actor name recorded for admin requests
+const ADMIN_ACTOR: &str = "admin";
+
+/// Scopes of the calling API key
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeyScopes {
+    /// Account the key belongs to
+    pub account_id: Uuid,
+    /// The account's permissions
+    pub permissions: AccountPermissions,
+    /// Scopes they grant, e.g. `read`, `trade`, `market:BTC/USD`, `leverage:3`, `withdraw`
+    pub scopes: Vec<String>,
+}
+
+/// Get an account's permissions
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/accounts/{id}/permissions",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Permissions retrieved successfully", body = AccountPermissions),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn get_account_permissions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiResponse<AccountPermissions>, ApiError> {
+    ensure_account_exists(&state, id).await?;
+    let permissions = state.account_service.get_permissions(id).await
+        .map_err(ApiError::Common)?;
+    Ok(ApiResponse::new(permissions))
+}
+
+/// Replace an account's permissions
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/accounts/{id}/permissions",
+    security(("admin_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    request_body = AccountPermissions,
+    responses(
+        (status = 200, description = "Permissions set", body = AccountPermissions),
+        (status = 400, description = "Unknown market or leverage cap below 1"),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 403, description = "Account is closed"),
+        (status = 404, description = "Account not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "admin"
+)]
+pub async fn set_account_permissions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<AccountPermissions>,
+) -> Result<ApiResponse<AccountPermissions>, ApiError> {
+    ensure_account_exists(&state, id).await?;
+    for market in request.allowed_markets.iter().flatten() {
+        let market = market.trim().to_uppercase();
+        if !state.markets.iter().any(|candidate| candidate.symbol == market) {
+            return Err(ApiError::BadRequest(format!("Unknown market: {}", market)));
+        }
+    }
+
+    let previous = state.account_service.get_permissions(id).await
+        .map_err(ApiError::Common)?;
+    let permissions = state.account_service.set_permissions(id, request).await
+        .map_err(ApiError::Common)?;
+
+    state.audit_log.record(
+        ADMIN_ACTOR,
+        "account.permissions_set",
+        Some(id),
+        json!({
+            "previous": previous,
+            "permissions": permissions,
+        }),
+    );
+
+    Ok(ApiResponse::new(permissions))
+}
+
+/// Get the permissions and scopes of the calling API key
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/permissions",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Scopes retrieved successfully", body = ApiKeyScopes),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn get_api_key_scopes(
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiResponse<ApiKeyScopes>, ApiError> {
+    auth.ensure_account(id)?;
+    Ok(ApiResponse::new(ApiKeyScopes {
+        account_id: auth.account_id,
+        scopes: auth.scopes(),
+        permissions: auth.permissions.as_ref().clone(),
+    }))
+}
+
+async fn ensure_account_exists(state: &AppState, account_id: Uuid) -> Result<(), ApiError> {
+    state.account_service.get_account(account_id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", account_id)))?;
+    Ok(())
+}
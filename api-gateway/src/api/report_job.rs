@@ -0,0 +1,167 @@
+//! Report job handlers
+//!
+//! Admins can list the registered report jobs with their schedules, run a
+//! job now for any reporting date and look through each job's runs.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{Days, NaiveDate};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+use crate::scheduler::{JobInfo, JobRun, JobTrigger};
+use crate::AppState;
+use crate::api::response::{ApiListResponse, ApiResponse, Created};
+
+/// Actor name recorded for admin requests
+const ADMIN_ACTOR: &str = "admin";
+
+/// Report job run request
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct RunReportJobRequest {
+    /// Reporting date to cover, the previous UTC day when unset
+    pub date: Option<NaiveDate>,
+}
+
+/// Report job run query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReportJobRunsQuery {
+    /// Maximum number of runs
+    #[serde(default = "default_run_limit")]
+    pub limit: usize,
+}
+
+fn default_run_limit() -> usize {
+    50
+}
+
+/// List the registered report jobs with their schedules and latest runs
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/report-jobs",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Report jobs retrieved successfully", body = Vec<JobInfo>),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn list_report_jobs(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiListResponse<JobInfo>, ApiError> {
+    Ok(ApiListResponse::new(state.scheduler.jobs(state.matching_engine.clock().now())))
+}
+
+/// Run a report job now and wait for it to finish
+///
+/// A job that fails still records its run, returned with the error.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/report-jobs/{name}/runs",
+    security(("admin_key" = [])),
+    params(
+        ("name" = String, Path, description = "Job name, e.g. daily_statements")
+    ),
+    request_body = RunReportJobRequest,
+    responses(
+        (status = 201, description = "Job run, successfully or not", body = JobRun),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Report job not found"),
+        (status = 409, description = "Job is already running")
+    ),
+    tag = "admin"
+)]
+pub async fn run_report_job(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    request: Option<Json<RunReportJobRequest>>,
+) -> Result<Created<JobRun>, ApiError> {
+    ensure_job(&state, &name)?;
+    let Json(request) = request.unwrap_or_default();
+    let date = request.date
+        .unwrap_or_else(|| state.matching_engine.clock().now().date_naive() - Days::new(1));
+
+    let run = state.scheduler.run(&state, &name, date, JobTrigger::Manual).await
+        .map_err(ApiError::Common)?;
+
+    state.audit_log.record(
+        ADMIN_ACTOR,
+        "report_job.run",
+        None,
+        json!({
+            "job": run.job,
+            "run_id": run.id,
+            "date": run.date,
+            "status": run.status,
+        }),
+    );
+
+    Ok(Created::new(format!("/api/v1/admin/report-jobs/{}/runs/{}", name, run.id), run))
+}
+
+/// Get a report job's recent runs, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/report-jobs/{name}/runs",
+    security(("admin_key" = [])),
+    params(
+        ("name" = String, Path, description = "Job name, e.g. daily_statements"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of runs to return")
+    ),
+    responses(
+        (status = 200, description = "Runs retrieved successfully", body = Vec<JobRun>),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Report job not found")
+    ),
+    tag = "admin"
+)]
+pub async fn get_report_job_runs(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(query): Query<ReportJobRunsQuery>,
+) -> Result<ApiListResponse<JobRun>, ApiError> {
+    ensure_job(&state, &name)?;
+    Ok(ApiListResponse::new(state.scheduler.runs(Some(&name), query.limit)))
+}
+
+/// Get one run of a report job
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/report-jobs/{name}/runs/{run_id}",
+    security(("admin_key" = [])),
+    params(
+        ("name" = String, Path, description = "Job name, e.g. daily_statements"),
+        ("run_id" = Uuid, Path, description = "Run ID")
+    ),
+    responses(
+        (status = 200, description = "Run retrieved successfully", body = JobRun),
+        (status = 401, description = "Missing or invalid admin key"),
+        (status = 404, description = "Report job or run not found")
+    ),
+    tag = "admin"
+)]
+pub async fn get_report_job_run(
+    State(state): State<Arc<AppState>>,
+    Path((name, run_id)): Path<(String, Uuid)>,
+) -> Result<ApiResponse<JobRun>, ApiError> {
+    ensure_job(&state, &name)?;
+    state.scheduler.run_by_id(run_id)
+        .filter(|run| run.job == name)
+        .map(ApiResponse::new)
+        .ok_or_else(|| ApiError::NotFound(format!("Report job run not found: {}", run_id)))
+}
+
+fn ensure_job(state: &AppState, name: &str) -> Result<(), ApiError> {
+    if state.scheduler.has_job(name) {
+        Ok(())
+    } else {
+        Err(ApiError::NotFound(format!("Report job not found: {}", name)))
+    }
+}
@@ -3,6 +3,7 @@
 //! This module provides a set of consistent response types to be used by all API endpoints.
 //! Using these standardized formats ensures a consistent API experience for clients.
 
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde::{Deserialize, Serialize};
@@ -30,6 +31,16 @@ pub struct ResponseMetadata {
     pub extra: Option<serde_json::Value>,
 }
 
+/// A single resource response for a newly created resource, sent as
+/// `201 Created` with the resource's path in `Location`
+#[derive(Debug)]
+pub struct Created<T> {
+    /// Path of the new resource
+    pub location: String,
+    /// The response body
+    pub response: ApiResponse<T>,
+}
+
 /// A standardized API response wrapper for list/collection responses
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ApiListResponse<T> {
@@ -75,6 +86,16 @@ where
     }
 }
 
+// Implementation to convert Created to axum Response
+impl<T> IntoResponse for Created<T>
+where
+    T: Serialize + Debug,
+{
+    fn into_response(self) -> Response {
+        (StatusCode::CREATED, [(header::LOCATION, self.location)], Json(self.response)).into_response()
+    }
+}
+
 // Implementation to convert ApiListResponse to axum Response
 impl<T> IntoResponse for ApiListResponse<T>
 where
@@ -126,6 +147,16 @@ impl<T> ApiResponse<T> {
     }
 }
 
+impl<T> Created<T> {
+    /// Create a response for a resource created at `location`
+    pub fn new(location: impl Into<String>, data: T) -> Self {
+        Self {
+            location: location.into(),
+            response: ApiResponse::new(data),
+        }
+    }
+}
+
 impl<T> ApiListResponse<T> {
     /// Create a new list response with just data
     pub fn new(data: Vec<T>) -> Self {
@@ -0,0 +1,137 @@
+//! Security event and session handlers
+//!
+//! Account holders can review the security events of their account and the
+//! sessions of its API keys, and end a session, which revokes its key.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    Extension,
+};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::auth::AuthContext;
+use crate::error::ApiError;
+use crate::security::{SecurityEvent, SecurityEventKind, Session};
+use crate::AppState;
+use crate::api::response::{ApiListResponse, ApiResponse};
+
+/// Security event query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SecurityEventsQuery {
+    /// Maximum number of events
+    #[serde(default = "default_event_limit")]
+    pub limit: usize,
+}
+
+fn default_event_limit() -> usize {
+    100
+}
+
+/// Get recent security events of an account, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/security-events",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of events to return")
+    ),
+    responses(
+        (status = 200, description = "Security events retrieved successfully", body = Vec<SecurityEvent>),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account or lacks the read scope"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn get_security_events(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<SecurityEventsQuery>,
+) -> Result<ApiListResponse<SecurityEvent>, ApiError> {
+    auth.ensure_account(id)?;
+    Ok(ApiListResponse::new(state.security.events(id, query.limit)))
+}
+
+/// List the sessions of an account's API keys, most recently used first
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/sessions",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Sessions retrieved successfully", body = Vec<Session>),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account or lacks the read scope"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiListResponse<Session>, ApiError> {
+    auth.ensure_account(id)?;
+    let sessions = state.security.sessions(id)
+        .into_iter()
+        .map(|session| Session { current: session.id == auth.key.id, ..session })
+        .collect();
+    Ok(ApiListResponse::new(sessions))
+}
+
+/// End a session, revoking its API key
+#[utoipa::path(
+    delete,
+    path = "/api/v1/accounts/{id}/sessions/{session_id}",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("session_id" = Uuid, Path, description = "Session ID")
+    ),
+    responses(
+        (status = 200, description = "Session ended and its API key revoked", body = Session),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account or lacks the trade scope"),
+        (status = 404, description = "Session not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn revoke_session(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    connect: Option<ConnectInfo<SocketAddr>>,
+    Path((id, session_id)): Path<(Uuid, Uuid)>,
+) -> Result<ApiResponse<Session>, ApiError> {
+    auth.ensure_account(id)?;
+    let session = state.security.sessions(id)
+        .into_iter()
+        .find(|session| session.id == session_id)
+        .ok_or_else(|| ApiError::NotFound(format!("Session not found: {}", session_id)))?;
+    let revoked = state.api_keys.revoke_by_id(id, session_id)
+        .ok_or_else(|| ApiError::NotFound(format!("Session not found: {}", session_id)))?;
+    state.security.end_session(session_id);
+
+    let client = connect.map(|ConnectInfo(addr)| addr.ip());
+    state.security.record(id, SecurityEventKind::SessionRevoked, Some(&revoked), client, json!({
+        "revoked_by": auth.key.id,
+        "last_address": session.ip_address,
+    }));
+    state.audit_log.record(
+        format!("account:{}", id),
+        "session.revoked",
+        Some(id),
+        json!({ "key_id": revoked.id, "prefix": revoked.prefix }),
+    );
+    Ok(ApiResponse::new(Session { current: session.id == auth.key.id, ..session }))
+}
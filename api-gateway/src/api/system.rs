@@ -0,0 +1,129 @@
+//! System handlers
+//!
+//! Operators publish announcements to the `system` WebSocket channel, and
+//! anyone can list the recent ones or discover what the deployment supports.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::capabilities::Capabilities;
+use crate::error::ApiError;
+use crate::system::{Announcement, AnnouncementKind, Severity};
+use crate::AppState;
+use crate::api::response::{ApiResponse, ApiListResponse};
+
+/// Publish announcement request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PublishAnnouncementRequest {
+    /// What the announcement is about
+    #[serde(default)]
+    pub kind: AnnouncementKind,
+    /// How urgent it is
+    #[serde(default)]
+    pub severity: Severity,
+    /// One-line summary
+    pub title: String,
+    /// Details
+    #[serde(default)]
+    pub message: String,
+    /// Start of the window the announcement covers
+    #[serde(default)]
+    pub starts_at: Option<DateTime<Utc>>,
+    /// End of that window
+    #[serde(default)]
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+/// Announcement list query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AnnouncementsQuery {
+    /// Maximum number of announcements to return
+    #[serde(default = "default_announcements_limit")]
+    pub limit: usize,
+}
+
+fn default_announcements_limit() -> usize {
+    20
+}
+
+/// List recent announcements, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/system/announcements",
+    params(
+        ("limit" = Option<usize>, Query, description = "Maximum number of announcements (default 20)")
+    ),
+    responses(
+        (status = 200, description = "Announcements retrieved successfully", body = ApiListResponse<Announcement>)
+    ),
+    tag = "system"
+)]
+pub async fn get_announcements(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AnnouncementsQuery>,
+) -> ApiListResponse<Announcement> {
+    ApiListResponse::new(state.system.announcements(query.limit))
+}
+
+/// Publish an announcement to clients on the `system` channel
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/announcements",
+    security(("admin_key" = [])),
+    request_body = PublishAnnouncementRequest,
+    responses(
+        (status = 200, description = "Announcement published", body = ApiResponse<Announcement>),
+        (status = 400, description = "Empty title or window ending before it starts"),
+        (status = 401, description = "Missing or invalid admin key")
+    ),
+    tag = "admin"
+)]
+pub async fn publish_announcement(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<PublishAnnouncementRequest>,
+) -> Result<ApiResponse<Announcement>, ApiError> {
+    let announcement = state.system.announce(Announcement {
+        id: Uuid::new_v4(),
+        kind: request.kind,
+        severity: request.severity,
+        title: request.title,
+        message: request.message,
+        starts_at: request.starts_at,
+        ends_at: request.ends_at,
+        published_at: Utc::now(),
+    }).await.map_err(ApiError::Common)?;
+
+    state.audit_log.record("admin", "announcement.published", None, json!({
+        "id": announcement.id,
+        "kind": announcement.kind,
+        "severity": announcement.severity,
+        "title": announcement.title,
+    }));
+
+    Ok(ApiResponse::new(announcement))
+}
+
+/// Features, limits and protocols this deployment supports
+#[utoipa::path(
+    get,
+    path = "/api/v1/capabilities",
+    responses(
+        (status = 200, description = "Capabilities retrieved successfully", body = ApiResponse<Capabilities>)
+    ),
+    tag = "system"
+)]
+pub async fn get_capabilities(
+    State(state): State<Arc<AppState>>,
+    Extension(capabilities): Extension<Arc<Capabilities>>,
+) -> ApiResponse<Capabilities> {
+    ApiResponse::new(capabilities.as_ref().clone().with_flags(state.matching_engine.feature_flags()))
+}
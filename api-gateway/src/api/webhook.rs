@@ -0,0 +1,165 @@
+//! Webhook handlers
+//!
+//! Account holders register URLs that receive signed notifications for their
+//! fills, order status changes, deposits and withdrawals, and can inspect the
+//! outcome of recent deliveries.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::auth::AuthContext;
+use crate::error::ApiError;
+use crate::webhook::{Delivery, Webhook, WebhookEventType};
+use crate::AppState;
+use crate::api::response::{ApiResponse, ApiListResponse, Created};
+
+/// Register webhook request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateWebhookRequest {
+    /// HTTPS URL notifications are posted to
+    pub url: String,
+    /// Event types to deliver, all when empty
+    #[serde(default)]
+    pub events: Vec<WebhookEventType>,
+}
+
+/// Webhook delivery log query parameters
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DeliveriesQuery {
+    /// Maximum number of deliveries to return
+    #[serde(default = "default_deliveries_limit")]
+    pub limit: usize,
+}
+
+fn default_deliveries_limit() -> usize {
+    100
+}
+
+/// Register a webhook for an account
+///
+/// The signing secret is only returned by this request.
+#[utoipa::path(
+    post,
+    path = "/api/v1/accounts/{id}/webhooks",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 201, description = "Webhook registered, with its path in Location"),
+        (status = 400, description = "Invalid URL or too many webhooks"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Account not found")
+    ),
+    tag = "account"
+)]
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<Created<Webhook>, ApiError> {
+    auth.ensure_account(id)?;
+
+    // Verify the account exists before registering the webhook
+    let _ = state.account_service.get_account(id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", id)))?;
+
+    let webhook = state.webhooks.register(id, &request.url, request.events)
+        .map_err(ApiError::Common)?;
+
+    let location = format!("/api/v1/accounts/{}/webhooks/{}", id, webhook.id);
+    Ok(Created::new(location, webhook))
+}
+
+/// List an account's webhooks
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/webhooks",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Webhooks retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account")
+    ),
+    tag = "account"
+)]
+pub async fn get_webhooks(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiListResponse<Webhook>, ApiError> {
+    auth.ensure_account(id)?;
+
+    Ok(ApiListResponse::new(state.webhooks.list(id)))
+}
+
+/// Remove one of an account's webhooks
+#[utoipa::path(
+    delete,
+    path = "/api/v1/accounts/{id}/webhooks/{webhook_id}",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("webhook_id" = Uuid, Path, description = "Webhook ID")
+    ),
+    responses(
+        (status = 200, description = "Webhook removed"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 404, description = "Webhook not found")
+    ),
+    tag = "account"
+)]
+pub async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path((id, webhook_id)): Path<(Uuid, Uuid)>,
+) -> Result<ApiResponse<Webhook>, ApiError> {
+    auth.ensure_account(id)?;
+
+    let webhook = state.webhooks.remove(id, webhook_id)
+        .ok_or_else(|| ApiError::NotFound(format!("Webhook not found: {}", webhook_id)))?;
+
+    Ok(ApiResponse::new(webhook))
+}
+
+/// Get an account's recent webhook deliveries, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/webhooks/deliveries",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of deliveries to return")
+    ),
+    responses(
+        (status = 200, description = "Deliveries retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account")
+    ),
+    tag = "account"
+)]
+pub async fn get_webhook_deliveries(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<DeliveriesQuery>,
+) -> Result<ApiListResponse<Delivery>, ApiError> {
+    auth.ensure_account(id)?;
+
+    Ok(ApiListResponse::new(state.webhooks.deliveries(id, query.limit)))
+}
@@ -0,0 +1,160 @@
+//! Withdrawal address handlers
+//!
+//! Account holders manage the addresses their withdrawals may go to. Once an
+//! address is whitelisted for an asset, withdrawals of that asset must go to a
+//! whitelisted address. Changes require a second-factor code in the
+//! `x-2fa-code` header when the account has a second factor enrolled.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    Extension, Json,
+};
+use common::model::account::WithdrawalAddress;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::auth::{second_factor_code, AuthContext};
+use crate::error::ApiError;
+use crate::AppState;
+use crate::api::response::{ApiResponse, ApiListResponse, Created};
+
+/// Add withdrawal address request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddWithdrawalAddressRequest {
+    /// Asset symbol (e.g., "BTC")
+    pub asset: String,
+    /// Destination address
+    pub address: String,
+    /// Optional label
+    pub label: Option<String>,
+}
+
+/// Get an account's whitelisted withdrawal addresses
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts/{id}/withdrawal-addresses",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID")
+    ),
+    responses(
+        (status = 200, description = "Withdrawal addresses retrieved successfully"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn get_withdrawal_addresses(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+) -> Result<ApiListResponse<WithdrawalAddress>, ApiError> {
+    auth.ensure_account(id)?;
+
+    let addresses = state.account_service.get_withdrawal_addresses(id);
+    Ok(ApiListResponse::new(addresses))
+}
+
+/// Whitelist a withdrawal address
+#[utoipa::path(
+    post,
+    path = "/api/v1/accounts/{id}/withdrawal-addresses",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("x-2fa-code" = Option<String>, Header, description = "Second-factor code, required when enrolled")
+    ),
+    request_body = AddWithdrawalAddressRequest,
+    responses(
+        (status = 201, description = "Withdrawal address whitelisted, with its path in Location"),
+        (status = 400, description = "Invalid address or too many addresses"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account or second-factor code missing or invalid"),
+        (status = 404, description = "Account not found"),
+        (status = 409, description = "Address already whitelisted"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn add_withdrawal_address(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<AddWithdrawalAddressRequest>,
+) -> Result<Created<WithdrawalAddress>, ApiError> {
+    auth.ensure_account(id)?;
+
+    let address = state.account_service.add_withdrawal_address(
+        id,
+        &request.asset,
+        &request.address,
+        request.label,
+        second_factor_code(&headers),
+    ).await.map_err(ApiError::Common)?;
+
+    state.audit_log.record(
+        format!("account:{}", id),
+        "withdrawal_address.added",
+        Some(id),
+        json!({
+            "address_id": address.id,
+            "asset": address.asset,
+            "address": address.address,
+        }),
+    );
+
+    let location = format!("/api/v1/accounts/{}/withdrawal-addresses/{}", id, address.id);
+    Ok(Created::new(location, address))
+}
+
+/// Remove a whitelisted withdrawal address
+#[utoipa::path(
+    delete,
+    path = "/api/v1/accounts/{id}/withdrawal-addresses/{address_id}",
+    security(("api_key" = [])),
+    params(
+        ("id" = Uuid, Path, description = "Account ID"),
+        ("address_id" = Uuid, Path, description = "Withdrawal address ID"),
+        ("x-2fa-code" = Option<String>, Header, description = "Second-factor code, required when enrolled")
+    ),
+    responses(
+        (status = 200, description = "Withdrawal address removed"),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key belongs to another account or second-factor code missing or invalid"),
+        (status = 404, description = "Withdrawal address not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "account"
+)]
+pub async fn remove_withdrawal_address(
+    State(state): State<Arc<AppState>>,
+    Extension(auth): Extension<AuthContext>,
+    Path((id, address_id)): Path<(Uuid, Uuid)>,
+    headers: HeaderMap,
+) -> Result<ApiResponse<WithdrawalAddress>, ApiError> {
+    auth.ensure_account(id)?;
+
+    let address = state.account_service.remove_withdrawal_address(id, address_id, second_factor_code(&headers))
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Withdrawal address not found: {}", address_id)))?;
+
+    state.audit_log.record(
+        format!("account:{}", id),
+        "withdrawal_address.removed",
+        Some(id),
+        json!({
+            "address_id": address.id,
+            "asset": address.asset,
+            "address": address.address,
+        }),
+    );
+
+    Ok(ApiResponse::new(address))
+}
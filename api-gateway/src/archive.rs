@@ -0,0 +1,323 @@
+//! Bulk historical data downloads
+//!
+//! Shortly after midnight UTC, the previous day's trades of every market are
+//! read from the persisted trade history and written as gzipped CSV
+//! archives, together with candles built from them, so quants can backfill
+//! datasets without paging through the REST API. Days still in retention
+//! that were not generated, e.g. because the gateway was down at midnight,
+//! are generated on startup or on their first download. The manifest lists
+//! every archive generated so far.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Days, NaiveDate, SecondsFormat, Utc};
+use common::error::{Error, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use market_data::{Candle, CandleInterval, MarketDataService, TradeMessage};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+/// Archive generation settings
+#[derive(Debug, Clone)]
+pub struct ArchiveConfig {
+    /// Past days archives are kept and can be generated for
+    pub retention_days: u32,
+    /// Candle intervals archived for every day
+    pub candle_intervals: Vec<CandleInterval>,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: 30,
+            candle_intervals: vec![CandleInterval::Minute1, CandleInterval::Hour1, CandleInterval::Day1],
+        }
+    }
+}
+
+/// Data in an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveKind {
+    /// One row per trade
+    Trades,
+    /// One row per candle of an interval
+    Candles,
+}
+
+/// Description of a generated archive
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ArchiveEntry {
+    /// Data in the archive
+    pub kind: ArchiveKind,
+    /// Market symbol
+    pub market: String,
+    /// Candle interval code, for candle archives
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+    /// UTC day covered
+    pub date: NaiveDate,
+    /// Download path under the API version prefix
+    pub path: String,
+    /// Number of rows
+    pub records: usize,
+    /// Size of the gzipped file
+    pub bytes: usize,
+    /// SHA-256 of the gzipped file, hex encoded
+    pub sha256: String,
+    /// When the archive was generated
+    pub generated_at: DateTime<Utc>,
+}
+
+/// A generated archive and its gzipped CSV
+#[derive(Debug, Clone)]
+pub struct Archive {
+    /// Description
+    pub entry: ArchiveEntry,
+    /// Gzipped CSV
+    pub body: Arc<Vec<u8>>,
+}
+
+/// Generates and keeps the daily archives of every market
+pub struct DataArchive {
+    market_data: Arc<MarketDataService>,
+    markets: Vec<String>,
+    config: ArchiveConfig,
+    /// Archives by day and path
+    archives: RwLock<BTreeMap<(NaiveDate, String), Archive>>,
+}
+
+impl DataArchive {
+    /// Archive the given markets' history from a market data service
+    pub fn new(market_data: Arc<MarketDataService>, markets: Vec<String>, config: ArchiveConfig) -> Self {
+        Self {
+            market_data,
+            markets,
+            config,
+            archives: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Generation settings
+    pub fn config(&self) -> &ArchiveConfig {
+        &self.config
+    }
+
+    /// Market symbol named in a path, as `BTC-USD` or `BTC/USD`
+    pub fn resolve_market(&self, name: &str) -> Option<&str> {
+        self.markets
+            .iter()
+            .find(|market| *market == name || path_name(market) == name)
+            .map(String::as_str)
+    }
+
+    /// Whether a day has ended and is still in retention
+    pub fn covers(&self, date: NaiveDate) -> bool {
+        let today = Utc::now().date_naive();
+        let oldest = today.checked_sub_days(Days::new(self.config.retention_days.into())).unwrap_or(today);
+        date < today && date >= oldest
+    }
+
+    /// Generated archives, oldest day first
+    pub fn manifest(&self) -> Vec<ArchiveEntry> {
+        self.archives.read().unwrap().values().map(|archive| archive.entry.clone()).collect()
+    }
+
+    /// Trades archive of a market for a day, generating the day if needed
+    pub async fn trades(&self, market: &str, date: NaiveDate) -> Result<Option<Archive>> {
+        self.find(date, trades_path(market, date)).await
+    }
+
+    /// Candle archive of a market for a day, generating the day if needed
+    pub async fn candles(&self, market: &str, interval: CandleInterval, date: NaiveDate) -> Result<Option<Archive>> {
+        self.find(date, candles_path(market, interval, date)).await
+    }
+
+    async fn find(&self, date: NaiveDate, path: String) -> Result<Option<Archive>> {
+        if !self.covers(date) {
+            return Ok(None);
+        }
+        let key = (date, path);
+        if let Some(archive) = self.archives.read().unwrap().get(&key) {
+            return Ok(Some(archive.clone()));
+        }
+        self.generate(date).await?;
+        Ok(self.archives.read().unwrap().get(&key).cloned())
+    }
+
+    /// Write the archives of every market for a day
+    ///
+    /// Markets always get a trades archive, even without trades, so a missing
+    /// archive never has to be told apart from a quiet day.
+    pub async fn generate(&self, date: NaiveDate) -> Result<Vec<ArchiveEntry>> {
+        if !self.covers(date) {
+            return Err(Error::ValidationError(format!("{} has not ended or is outside archive retention", date)));
+        }
+        let from = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time").and_utc();
+        let to = from + chrono::Duration::days(1);
+
+        let mut archives = Vec::new();
+        for market in &self.markets {
+            let trades = self.market_data.get_trade_history(market, from, to).await?;
+            archives.push(build(
+                ArchiveKind::Trades,
+                market,
+                None,
+                date,
+                trades_path(market, date),
+                trades_csv(&trades),
+                trades.len(),
+            )?);
+
+            for interval in &self.config.candle_intervals {
+                let candles = Candle::from_trades(market, *interval, &trades);
+                archives.push(build(
+                    ArchiveKind::Candles,
+                    market,
+                    Some(*interval),
+                    date,
+                    candles_path(market, *interval, date),
+                    candles_csv(&candles),
+                    candles.len(),
+                )?);
+            }
+        }
+
+        let entries: Vec<ArchiveEntry> = archives.iter().map(|archive| archive.entry.clone()).collect();
+        let mut stored = self.archives.write().unwrap();
+        for archive in archives {
+            stored.insert((date, archive.entry.path.clone()), archive);
+        }
+        stored.retain(|(day, _), _| self.covers(*day));
+
+        info!("Generated {} data archives for {}", entries.len(), date);
+        Ok(entries)
+    }
+
+    /// Generate missing days in retention, then the previous day shortly
+    /// after every UTC midnight
+    pub fn spawn_daily(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let today = Utc::now().date_naive();
+            for days_ago in (1..=self.config.retention_days).rev() {
+                let Some(date) = today.checked_sub_days(Days::new(days_ago.into())) else {
+                    continue;
+                };
+                let generated = self.archives.read().unwrap().keys().any(|(day, _)| *day == date);
+                if !generated {
+                    if let Err(e) = self.generate(date).await {
+                        error!("Failed to generate data archives for {}: {}", date, e);
+                    }
+                }
+            }
+
+            loop {
+                let now = Utc::now();
+                let next_midnight = (now.date_naive() + Days::new(1))
+                    .and_hms_opt(0, 0, 0)
+                    .expect("midnight is a valid time")
+                    .and_utc();
+                // Give trades settling at midnight a moment to be saved
+                let wait = (next_midnight - now).to_std().unwrap_or_default() + Duration::from_secs(5);
+                tokio::time::sleep(wait).await;
+
+                let yesterday = Utc::now().date_naive() - Days::new(1);
+                if let Err(e) = self.generate(yesterday).await {
+                    error!("Failed to generate data archives for {}: {}", yesterday, e);
+                }
+            }
+        })
+    }
+}
+
+/// Market symbol as used in paths, e.g. `BTC-USD`
+fn path_name(market: &str) -> String {
+    market.replace('/', "-")
+}
+
+fn trades_path(market: &str, date: NaiveDate) -> String {
+    format!("/data/trades/{}/{}.csv.gz", path_name(market), date)
+}
+
+fn candles_path(market: &str, interval: CandleInterval, date: NaiveDate) -> String {
+    format!("/data/candles/{}/{}/{}.csv.gz", path_name(market), interval.code(), date)
+}
+
+fn build(
+    kind: ArchiveKind,
+    market: &str,
+    interval: Option<CandleInterval>,
+    date: NaiveDate,
+    path: String,
+    csv: String,
+    records: usize,
+) -> Result<Archive> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let body = encoder.write_all(csv.as_bytes())
+        .and_then(|_| encoder.finish())
+        .map_err(|e| Error::Internal(format!("Failed to compress {}: {}", path, e)))?;
+
+    Ok(Archive {
+        entry: ArchiveEntry {
+            kind,
+            market: market.to_string(),
+            interval: interval.map(|interval| interval.code().to_string()),
+            date,
+            path,
+            records,
+            bytes: body.len(),
+            sha256: hex::encode(Sha256::digest(&body)),
+            generated_at: Utc::now(),
+        },
+        body: Arc::new(body),
+    })
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn trades_csv(trades: &[TradeMessage]) -> String {
+    let mut csv = String::from("id,timestamp,price,quantity,taker_side,is_buyer_maker\n");
+    for trade in trades {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            trade.id,
+            timestamp(trade.timestamp),
+            trade.price,
+            trade.quantity,
+            trade.taker_side,
+            trade.is_buyer_maker
+        ));
+    }
+    csv
+}
+
+fn candles_csv(candles: &[Candle]) -> String {
+    let mut csv = String::from(
+        "open_time,close_time,open,high,low,close,volume,quote_volume,trades,taker_buy_volume,taker_buy_quote_volume\n",
+    );
+    for candle in candles {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{}\n",
+            timestamp(candle.open_time),
+            timestamp(candle.close_time),
+            candle.open,
+            candle.high,
+            candle.low,
+            candle.close,
+            candle.volume,
+            candle.quote_volume,
+            candle.trades,
+            candle.taker_buy_volume,
+            candle.taker_buy_quote_volume
+        ));
+    }
+    csv
+}
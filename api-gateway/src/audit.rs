@@ -0,0 +1,93 @@
+//! Audit log of administrative and risk actions
+//!
+//! Entries are kept in memory (most recent [`AUDIT_LOG_CAPACITY`]) for the
+//! admin API and also written to the `audit` tracing target for log shipping.
+
+use std::collections::VecDeque;
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Number of entries kept in memory
+pub const AUDIT_LOG_CAPACITY: usize = 10_000;
+
+/// A recorded action
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AuditEntry {
+    /// Entry ID
+    pub id: Uuid,
+    /// When the action happened
+    pub timestamp: DateTime<Utc>,
+    /// Who performed it, e.g. `admin` or `account:<id>`
+    pub actor: String,
+    /// What was done, e.g. `kill_switch.engaged`
+    pub action: String,
+    /// Account the action applied to
+    pub account_id: Option<Uuid>,
+    /// Action-specific details
+    #[schema(value_type = Object)]
+    pub details: serde_json::Value,
+}
+
+/// In-memory audit log, newest entries last
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    entries: RwLock<VecDeque<AuditEntry>>,
+}
+
+impl AuditLog {
+    /// Create an empty audit log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an action
+    pub fn record(
+        &self,
+        actor: impl Into<String>,
+        action: impl Into<String>,
+        account_id: Option<Uuid>,
+        details: serde_json::Value,
+    ) -> AuditEntry {
+        let entry = AuditEntry {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            actor: actor.into(),
+            action: action.into(),
+            account_id,
+            details,
+        };
+
+        tracing::info!(
+            target: "audit",
+            actor = %entry.actor,
+            action = %entry.action,
+            account_id = ?entry.account_id,
+            details = %entry.details,
+            "audit"
+        );
+
+        let mut entries = self.entries.write().unwrap();
+        if entries.len() == AUDIT_LOG_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(entry.clone());
+        entry
+    }
+
+    /// Most recent entries, newest first, optionally for one account
+    pub fn recent(&self, account_id: Option<Uuid>, limit: usize) -> Vec<AuditEntry> {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|entry| account_id.is_none() || entry.account_id == account_id)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,430 @@
+//! API key authentication for trading and account endpoints
+//!
+//! Keys are issued when an account is created and sent by clients in the
+//! `X-API-Key` header. Authenticated requests carry an [`AuthContext`] that
+//! handlers use to check the caller owns the resource.
+//!
+//! Each key carries [`Scope`]s and may be limited to an allowlist of client
+//! addresses. Private routes are split into classes that each need a scope:
+//! reads need `read`, orders and account changes `trade`, withdrawals
+//! `withdraw`. What a key may do is further narrowed by its account's
+//! permissions, looked up on every request so operator changes apply at once.
+//!
+//! Each successful request is noted in the key's session, and keys rejected
+//! for their allowlist or scopes are recorded as security events of their
+//! account.
+//!
+//! Admin endpoints take the operator key configured with `ADMIN_API_KEY`, or
+//! an issued key with the `admin` scope, sent in the same header.
+
+use std::collections::BTreeSet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use account_service::AccountService;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use common::model::account::AccountPermissions;
+use dashmap::DashMap;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+use crate::notification::NotificationService;
+use crate::rate_limit::RateLimiter;
+use crate::security::{SecurityEventKind, SecurityLog};
+
+/// Header carrying the API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Header carrying a second-factor code for withdrawals and whitelist changes
+pub const SECOND_FACTOR_HEADER: &str = "x-2fa-code";
+
+/// Second-factor code sent with a request, if any
+pub fn second_factor_code(headers: &HeaderMap) -> Option<&str> {
+    headers.get(SECOND_FACTOR_HEADER).and_then(|value| value.to_str().ok())
+}
+
+/// Address of the client a request came from, if known
+pub fn client_ip(request: &Request) -> Option<IpAddr> {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// What an API key may be used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Read the account's balances, orders and history
+    Read,
+    /// Place and cancel orders and change the account's settings
+    Trade,
+    /// Withdraw funds and manage withdrawal addresses
+    Withdraw,
+    /// Use the admin API
+    Admin,
+}
+
+impl Scope {
+    /// Name of the scope, as serialized
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Trade => "trade",
+            Scope::Withdraw => "withdraw",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+/// Scopes of keys issued with a new account
+pub const DEFAULT_SCOPES: [Scope; 3] = [Scope::Read, Scope::Trade, Scope::Withdraw];
+
+/// An issued API key, without its secret
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ApiKey {
+    /// Key ID, used to revoke it
+    pub id: Uuid,
+    /// Account the key belongs to
+    pub account_id: Uuid,
+    /// First characters of the key, to tell keys apart
+    pub prefix: String,
+    /// What the key may be used for
+    pub scopes: BTreeSet<Scope>,
+    /// Client addresses or networks the key may be used from, any when empty
+    #[schema(value_type = Vec<String>)]
+    pub allowed_ips: Vec<IpNet>,
+    /// When the key was issued
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiKey {
+    /// Check whether the key has a scope
+    pub fn grants(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    /// Check whether the key may be used from a client address
+    ///
+    /// Keys with an allowlist reject clients whose address is unknown.
+    pub fn allows_ip(&self, client: Option<IpAddr>) -> bool {
+        self.allowed_ips.is_empty()
+            || client.is_some_and(|client| self.allowed_ips.iter().any(|network| network.contains(&client)))
+    }
+}
+
+/// Characters of a key shown in its prefix
+const KEY_PREFIX_LENGTH: usize = 8;
+
+/// API keys issued to accounts
+#[derive(Debug, Default)]
+pub struct ApiKeyStore {
+    /// API key -> issued key
+    keys: DashMap<String, ApiKey>,
+}
+
+impl ApiKeyStore {
+    /// Create an empty key store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Issue a new API key for an account, with the default scopes and no allowlist
+    pub fn issue(&self, account_id: Uuid) -> String {
+        self.issue_scoped(account_id, DEFAULT_SCOPES.into_iter().collect(), Vec::new()).0
+    }
+
+    /// Issue a new API key for an account with the given scopes and address allowlist
+    pub fn issue_scoped(&self, account_id: Uuid, scopes: BTreeSet<Scope>, allowed_ips: Vec<IpNet>) -> (String, ApiKey) {
+        let key = format!("zk_{}", Uuid::new_v4().simple());
+        let api_key = ApiKey {
+            id: Uuid::new_v4(),
+            account_id,
+            prefix: key[..KEY_PREFIX_LENGTH].to_string(),
+            scopes,
+            allowed_ips,
+            created_at: Utc::now(),
+        };
+        self.keys.insert(key.clone(), api_key.clone());
+        (key, api_key)
+    }
+
+    /// Get the issued key behind an API key
+    pub fn resolve(&self, key: &str) -> Option<ApiKey> {
+        self.keys.get(key).map(|entry| entry.value().clone())
+    }
+
+    /// Check an API key is valid and may be used from `client`, returning it if so
+    pub fn authenticate(&self, key: &str, client: Option<IpAddr>) -> Result<ApiKey, ApiError> {
+        let api_key = self.resolve(key).ok_or_else(|| ApiError::InvalidApiKey("Invalid API key".to_string()))?;
+        if !api_key.allows_ip(client) {
+            return Err(ApiError::IpNotAllowed(format!(
+                "API key may not be used from {}",
+                client.map(|client| client.to_string()).unwrap_or_else(|| "an unknown address".to_string())
+            )));
+        }
+        Ok(api_key)
+    }
+
+    /// Check an API key may be used for `scope` from `client`, returning it if so
+    ///
+    /// Unknown keys, keys used from outside their allowlist and keys without
+    /// the scope are rejected with distinct errors.
+    pub fn authorize(&self, key: &str, scope: Scope, client: Option<IpAddr>) -> Result<ApiKey, ApiError> {
+        let api_key = self.authenticate(key, client)?;
+        if !api_key.grants(scope) {
+            return Err(ApiError::InsufficientScope(format!("API key lacks the {} scope", scope.as_str())));
+        }
+        Ok(api_key)
+    }
+
+    /// List the keys issued to an account, oldest first
+    pub fn list(&self, account_id: Uuid) -> Vec<ApiKey> {
+        let mut keys: Vec<ApiKey> = self.keys
+            .iter()
+            .filter(|entry| entry.value().account_id == account_id)
+            .map(|entry| entry.value().clone())
+            .collect();
+        keys.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        keys
+    }
+
+    /// Revoke an API key
+    pub fn revoke(&self, key: &str) -> bool {
+        self.keys.remove(key).is_some()
+    }
+
+    /// Give one of an account's keys a new secret, keeping its ID, scopes and
+    /// allowlist, returning the new key if it existed
+    ///
+    /// The old secret stops working at once.
+    pub fn rotate(&self, account_id: Uuid, id: Uuid) -> Option<(String, ApiKey)> {
+        let mut api_key = self.revoke_by_id(account_id, id)?;
+        let key = format!("zk_{}", Uuid::new_v4().simple());
+        api_key.prefix = key[..KEY_PREFIX_LENGTH].to_string();
+        self.keys.insert(key.clone(), api_key.clone());
+        Some((key, api_key))
+    }
+
+    /// Revoke one of an account's keys by ID, returning it if it existed
+    pub fn revoke_by_id(&self, account_id: Uuid, id: Uuid) -> Option<ApiKey> {
+        let key = self.keys
+            .iter()
+            .find(|entry| entry.value().account_id == account_id && entry.value().id == id)
+            .map(|entry| entry.key().clone())?;
+        self.keys.remove(&key).map(|(_, api_key)| api_key)
+    }
+}
+
+/// Identity of an authenticated caller
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+    /// Account the API key belongs to
+    pub account_id: Uuid,
+    /// The API key used
+    pub key: Arc<ApiKey>,
+    /// Permissions of the account, which narrow what the key may do
+    pub permissions: Arc<AccountPermissions>,
+}
+
+impl AuthContext {
+    /// Authenticate with an issued key, loading its account's current permissions
+    pub async fn for_key(accounts: &AccountService, key: ApiKey) -> Result<Self, ApiError> {
+        let permissions = accounts.get_permissions(key.account_id).await.map_err(ApiError::Common)?;
+        Ok(Self {
+            account_id: key.account_id,
+            key: Arc::new(key),
+            permissions: Arc::new(permissions),
+        })
+    }
+
+    /// Scopes the API key grants once narrowed by the account's permissions,
+    /// e.g. `["read", "trade", "market:BTC/USD", "withdraw"]`
+    pub fn scopes(&self) -> Vec<String> {
+        let mut scopes: Vec<String> = self.permissions
+            .scopes()
+            .into_iter()
+            .filter(|scope| {
+                let required = match scope.split(':').next().unwrap_or_default() {
+                    "read" => Scope::Read,
+                    "withdraw" => Scope::Withdraw,
+                    _ => Scope::Trade,
+                };
+                self.key.grants(required)
+            })
+            .collect();
+        if self.key.grants(Scope::Admin) {
+            scopes.push(Scope::Admin.as_str().to_string());
+        }
+        scopes
+    }
+
+    /// Reject use of a key without `scope`
+    pub fn ensure_scope(&self, scope: Scope) -> Result<(), ApiError> {
+        if self.key.grants(scope) {
+            Ok(())
+        } else {
+            Err(ApiError::InsufficientScope(format!("API key lacks the {} scope", scope.as_str())))
+        }
+    }
+
+    /// Reject orders on a market the account's permissions do not allow
+    pub fn ensure_can_trade(&self, market: &str) -> Result<(), ApiError> {
+        if !self.permissions.can_trade {
+            return Err(ApiError::Forbidden(format!("Account {} may not trade", self.account_id)));
+        }
+        if !self.permissions.allows_market(market) {
+            return Err(ApiError::Forbidden(format!("Account {} may not trade {}", self.account_id, market)));
+        }
+        Ok(())
+    }
+
+    /// Reject access to another account's resources
+    pub fn ensure_account(&self, account_id: Uuid) -> Result<(), ApiError> {
+        if self.account_id == account_id {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden(format!(
+                "API key does not grant access to account {}", account_id
+            )))
+        }
+    }
+}
+
+/// State for the authentication middleware
+#[derive(Clone)]
+pub struct AuthLayerState {
+    /// Issued API keys
+    pub api_keys: Arc<ApiKeyStore>,
+    /// Per-key request limits
+    pub limiter: Arc<RateLimiter>,
+    /// Login notifications for requests from new client addresses
+    pub notifications: Arc<NotificationService>,
+    /// Accounts, for the permissions narrowing each key
+    pub accounts: Arc<AccountService>,
+    /// Sessions of the keys used and rejected uses of them
+    pub security: Arc<SecurityLog>,
+}
+
+/// Require a valid API key and apply the per-key rate limit
+///
+/// Each class of routes adds [`require_scope`] on top for the scope it needs.
+pub async fn require_api_key(
+    State(state): State<AuthLayerState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+    else {
+        return ApiError::Unauthorized(format!("Missing {} header", API_KEY_HEADER)).into_response();
+    };
+
+    let client = client_ip(&request);
+    let api_key = match state.api_keys.authenticate(&key, client) {
+        Ok(api_key) => api_key,
+        Err(e) => {
+            if let (ApiError::IpNotAllowed(reason), Some(api_key)) = (&e, state.api_keys.resolve(&key)) {
+                state.security.record(api_key.account_id, SecurityEventKind::AuthFailed, Some(&api_key), client, serde_json::json!({
+                    "code": "ip_not_allowed",
+                    "reason": reason,
+                }));
+            }
+            return e.into_response();
+        }
+    };
+
+    state.security.touch(&api_key, client);
+    let client = client.map(|client| client.to_string()).unwrap_or_else(|| "unknown".to_string());
+    state.notifications.record_login(api_key.account_id, &client);
+
+    let auth = match AuthContext::for_key(&state.accounts, api_key).await {
+        Ok(auth) => auth,
+        Err(e) => return e.into_response(),
+    };
+    request.extensions_mut().insert(auth);
+    state.limiter.enforce(&key, request, next).await
+}
+
+/// State for the scope middleware of a class of routes
+#[derive(Clone)]
+pub struct ScopeLayerState {
+    /// Scope the routes need
+    pub scope: Scope,
+    /// Where uses of keys without the scope are recorded
+    pub security: Arc<SecurityLog>,
+}
+
+/// Require the authenticated API key to have the routes' scope
+pub async fn require_scope(
+    State(state): State<ScopeLayerState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(auth) = request.extensions().get::<AuthContext>() else {
+        return ApiError::Unauthorized(format!("Missing {} header", API_KEY_HEADER)).into_response();
+    };
+    if let Err(e) = auth.ensure_scope(state.scope) {
+        state.security.record(auth.account_id, SecurityEventKind::AuthFailed, Some(&auth.key), client_ip(&request), serde_json::json!({
+            "code": "insufficient_scope",
+            "scope": state.scope,
+            "method": request.method().as_str(),
+            "path": request.uri().path(),
+        }));
+        return e.into_response();
+    }
+    next.run(request).await
+}
+
+/// State for the admin authentication middleware
+#[derive(Clone)]
+pub struct AdminAuthState {
+    /// Configured operator key; the admin API is disabled without one
+    pub admin_key: Option<Arc<str>>,
+    /// Issued API keys, some of which may have the `admin` scope
+    pub api_keys: Arc<ApiKeyStore>,
+}
+
+/// Require the configured admin key or an API key with the `admin` scope
+pub async fn require_admin_key(
+    State(state): State<AdminAuthState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(admin_key) = state.admin_key else {
+        return ApiError::Forbidden("Admin API is disabled".to_string()).into_response();
+    };
+
+    let Some(key) = request.headers().get(API_KEY_HEADER).and_then(|value| value.to_str().ok()) else {
+        return ApiError::Unauthorized(format!("Missing {} header", API_KEY_HEADER)).into_response();
+    };
+
+    // Keys without the admin scope are not admin keys at all
+    if !constant_time_eq(key.as_bytes(), admin_key.as_bytes()) {
+        if !state.api_keys.resolve(key).is_some_and(|api_key| api_key.grants(Scope::Admin)) {
+            return ApiError::InvalidApiKey("Invalid admin key".to_string()).into_response();
+        }
+        if let Err(e) = state.api_keys.authenticate(key, client_ip(&request)) {
+            return e.into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+/// Compare secrets without leaking the position of the first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,60 @@
+//! Balance history snapshots
+//!
+//! Shortly after midnight UTC, every account's balances are copied into the
+//! account repository as of that moment, so balance charts can be drawn
+//! without replaying the ledger. Operators can take the same snapshot at any
+//! other time through the admin API. Pending settlements are applied first,
+//! so snapshots include every trade executed before them.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Days, Utc};
+use common::error::Result;
+use serde::Serialize;
+use tracing::{error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::AppState;
+
+/// Balances recorded by a snapshot
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BalanceSnapshotTaken {
+    /// Time the snapshot was taken at
+    pub taken_at: DateTime<Utc>,
+    /// Number of balances recorded
+    pub recorded: usize,
+}
+
+/// Snapshot every account's balances, or one account's, at the engine's current time
+pub async fn take_snapshots(state: &AppState, account_id: Option<Uuid>) -> Result<BalanceSnapshotTaken> {
+    match account_id {
+        Some(account_id) => state.settlement.flush(account_id).await,
+        None => state.settlement.flush_all().await,
+    }
+    let taken_at = state.matching_engine.clock().now();
+    let recorded = state.account_service.snapshot_balances(account_id, taken_at).await?;
+    Ok(BalanceSnapshotTaken { taken_at, recorded })
+}
+
+/// Snapshot every account's balances shortly after each UTC midnight
+pub fn spawn_daily_snapshots(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let now = state.matching_engine.clock().now();
+            let next_midnight = (now.date_naive() + Days::new(1))
+                .and_hms_opt(0, 0, 0)
+                .expect("midnight is a valid time")
+                .and_utc();
+            // Give trades settling at midnight a moment to be saved
+            let wait = (next_midnight - now).to_std().unwrap_or_default() + Duration::from_secs(5);
+            tokio::time::sleep(wait).await;
+
+            match take_snapshots(&state, None).await {
+                Ok(taken) => info!("Recorded {} end-of-day balance snapshots", taken.recorded),
+                Err(e) => error!("Failed to record end-of-day balance snapshots: {}", e),
+            }
+        }
+    })
+}
@@ -0,0 +1,203 @@
+//! Capability discovery
+//!
+//! Describes what this deployment supports, built once from the configuration
+//! and the services it runs, so client SDKs and the UI can turn features on
+//! and off without hardcoding them. The same description is logged as a
+//! banner at startup. Feature flags change at runtime, so they are read
+//! again on every request.
+
+use common::flags::{FeatureFlags, FlagState, ENABLE_MARGIN};
+use common::model::fee::FeeSchedule;
+use common::model::order::{OrderType, TimeInForce};
+use serde::Serialize;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::auth::{API_KEY_HEADER, SECOND_FACTOR_HEADER};
+use crate::config::AppConfig;
+use crate::number_format::NumberFormat;
+use crate::versioning::ApiVersion;
+use crate::ws::message::{ProtocolVersion, CHANNELS};
+use crate::AppState;
+
+/// What this deployment supports
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Capabilities {
+    /// Gateway version
+    pub version: String,
+    /// REST API versions served, oldest first
+    pub api_versions: Vec<u64>,
+    /// Whether margin trading is offered
+    pub margin: bool,
+    /// Fee rates charged on every trade
+    pub fees: FeeSchedule,
+    /// Order types accepted
+    pub order_types: Vec<OrderType>,
+    /// Time in force options for limit orders
+    pub time_in_force: Vec<TimeInForce>,
+    /// How requests authenticate
+    pub auth: AuthCapabilities,
+    /// Storage backends in use
+    pub repositories: RepositoryCapabilities,
+    /// WebSocket protocol
+    pub websocket: WebSocketCapabilities,
+    /// Optional features and whether they are enabled
+    pub features: FeatureCapabilities,
+}
+
+/// How requests authenticate
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AuthCapabilities {
+    /// Header carrying an account's API key
+    pub api_key_header: String,
+    /// Header carrying a second factor code, for withdrawals and whitelist changes
+    pub second_factor_header: String,
+    /// Whether the admin API is enabled
+    pub admin_api: bool,
+}
+
+/// Storage backends in use
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RepositoryCapabilities {
+    /// Accounts and balances
+    pub accounts: String,
+    /// Order book history
+    pub market_data: String,
+}
+
+/// WebSocket protocol
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WebSocketCapabilities {
+    /// Notification schema versions, oldest first
+    pub protocol_versions: Vec<u64>,
+    /// Version used until a client negotiates another
+    pub default_version: u64,
+    /// Channels clients can subscribe to
+    pub channels: Vec<String>,
+}
+
+/// Optional features
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FeatureCapabilities {
+    /// End-of-day reports are written
+    pub reports: bool,
+    /// Notifications can be emailed
+    pub email_notifications: bool,
+    /// Makers earn rebates
+    pub maker_rebates: bool,
+    /// Assets that earn interest when opted in
+    pub earn_assets: Vec<String>,
+    /// External sources index prices are composed from
+    pub index_price_sources: Vec<String>,
+    /// Read-only markets mirrored from an external exchange
+    pub shadow_markets: Vec<String>,
+    /// External custodians withdrawals and deposits settle through
+    pub settlement: Vec<String>,
+    /// Past order books can be replayed
+    pub order_book_history: bool,
+    /// REST responses are compressed for clients that accept it
+    pub compression: bool,
+    /// Depth deltas and trades are published on the binary feed
+    pub binary_feed: bool,
+    /// JSON number formats clients can ask for
+    pub number_formats: Vec<String>,
+    /// Number format of clients that do not ask for one
+    pub default_number_format: String,
+    /// Experimental behaviors and whether they are turned on
+    pub flags: Vec<FlagState>,
+}
+
+impl Capabilities {
+    /// Describe a deployment from its configuration and state
+    pub fn new(config: &AppConfig, state: &AppState) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            api_versions: ApiVersion::SUPPORTED.iter().map(ApiVersion::number).collect(),
+            margin: false,
+            fees: state.matching_engine.fee_schedule(),
+            order_types: vec![OrderType::Market, OrderType::Limit],
+            time_in_force: vec![TimeInForce::GTC, TimeInForce::IOC, TimeInForce::FOK],
+            auth: AuthCapabilities {
+                api_key_header: API_KEY_HEADER.to_string(),
+                second_factor_header: SECOND_FACTOR_HEADER.to_string(),
+                admin_api: config.admin_api_key.is_some(),
+            },
+            repositories: RepositoryCapabilities {
+                accounts: state.account_service.repository_name().to_string(),
+                market_data: state.market_data_service.repository_name().to_string(),
+            },
+            websocket: WebSocketCapabilities {
+                protocol_versions: ProtocolVersion::SUPPORTED.iter().map(ProtocolVersion::number).collect(),
+                default_version: ProtocolVersion::default().number(),
+                channels: CHANNELS.iter().map(|channel| channel.to_string()).collect(),
+            },
+            features: FeatureCapabilities {
+                reports: state.reports.is_enabled(),
+                email_notifications: config.notifications.smtp.is_some(),
+                maker_rebates: !config.incentives.rebate_rate.is_zero(),
+                earn_assets: config.earn.rates.keys().cloned().collect(),
+                index_price_sources: config.index_prices.sources.iter().map(|(name, _)| name.clone()).collect(),
+                shadow_markets: state.market_data_service.shadow_markets().into_iter().map(|market| market.symbol).collect(),
+                settlement: state.account_service.settlement_adapter_names(),
+                order_book_history: config.order_book_snapshot_interval.is_some(),
+                compression: config.compression_enabled,
+                binary_feed: config.binary_feed.is_enabled(),
+                number_formats: [NumberFormat::Native, NumberFormat::DecimalStrings]
+                    .iter()
+                    .map(|format| format.name().to_string())
+                    .collect(),
+                default_number_format: state.number_format.name().to_string(),
+                flags: Vec::new(),
+            },
+        }
+        .with_flags(state.matching_engine.feature_flags())
+    }
+
+    /// Report the current value of the feature flags
+    pub fn with_flags(mut self, flags: &FeatureFlags) -> Self {
+        self.margin = flags.is_enabled(ENABLE_MARGIN);
+        self.features.flags = flags.list();
+        self
+    }
+
+    /// Log what this deployment supports
+    pub fn log_banner(&self) {
+        let features = &self.features;
+        let enabled: Vec<&str> = [
+            ("reports", features.reports),
+            ("email-notifications", features.email_notifications),
+            ("maker-rebates", features.maker_rebates),
+            ("earn", !features.earn_assets.is_empty()),
+            ("order-book-history", features.order_book_history),
+            ("compression", features.compression),
+            ("binary-feed", features.binary_feed),
+            ("admin-api", self.auth.admin_api),
+        ]
+        .into_iter()
+        .filter_map(|(name, on)| on.then_some(name))
+        .collect();
+
+        info!("Zavora gateway {}", self.version);
+        info!("  fees: maker {} / taker {}", self.fees.maker_rate, self.fees.taker_rate);
+        info!("  REST API versions: {:?}", self.api_versions);
+        info!("  repositories: accounts {}, market data {}", self.repositories.accounts, self.repositories.market_data);
+        info!(
+            "  websocket: protocol versions {:?}, channels {}",
+            self.websocket.protocol_versions,
+            self.websocket.channels.join(", ")
+        );
+        if !features.index_price_sources.is_empty() {
+            info!("  index price sources: {}", features.index_price_sources.join(", "));
+        }
+        if !features.shadow_markets.is_empty() {
+            info!("  shadow markets: {}", features.shadow_markets.join(", "));
+        }
+        info!("  settlement: {}", if features.settlement.is_empty() { "none".to_string() } else { features.settlement.join(", ") });
+        info!("  features: {}", if enabled.is_empty() { "none".to_string() } else { enabled.join(", ") });
+        let flags: Vec<String> = features.flags
+            .iter()
+            .map(|flag| format!("{}={}", flag.name, flag.enabled))
+            .collect();
+        info!("  feature flags: {}", flags.join(", "));
+    }
+}
@@ -1,6 +1,46 @@
 //! Application configuration
 
+use std::collections::BTreeMap;
 use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use account_service::settlement::{BankFileConfig, CryptoNodeConfig, SettlementConfig};
+use common::chaos::{Chaos, ChaosConfig};
+use common::flags;
+use common::decimal::RoundingMode;
+use common::id::{IdScheme, MAX_NODE};
+use common::model::asset::Asset;
+use common::model::market::Allocation;
+use market_data::channel::Backpressure;
+use market_data::feed::FeedConfig;
+use market_data::heatmap::HeatmapConfig;
+use market_data::memory::MemoryBudget;
+use market_data::retention::CandleRetention;
+use market_data::shadow::ShadowMarket;
+use market_data::tape::TapeFilter;
+use market_data::warmup::WarmupConfig;
+use market_data::CandleInterval;
+use matching_engine::{BookVerification, DEFAULT_PEG_REPRICE_INTERVAL};
+use tracing::warn;
+
+use crate::archive::ArchiveConfig;
+use crate::duplicates::{DuplicateOrderSettings, MAX_WINDOW_MS};
+use crate::earn::EarnConfig;
+use crate::funding::FundingConfig;
+use crate::health::HealthConfig;
+use crate::incentives::IncentiveConfig;
+use crate::index_price::IndexPriceConfig;
+use crate::limits::RequestLimits;
+use crate::notification::{NotificationConfig, SmtpConfig};
+use crate::number_format::NumberFormat;
+use crate::pipeline::PipelineConfig;
+use crate::report::accounting::AccountMapping;
+use crate::report::{AccountingConfig, ChartOfAccounts, ReportConfig, ReportSink, S3Config};
+use crate::scheduler::{JobSchedule, SchedulerConfig};
+use crate::shadow::ShadowConfig;
+use crate::webhook::WebhookConfig;
 
 /// Application configuration
 #[allow(dead_code)]
@@ -11,6 +51,93 @@ pub struct AppConfig {
     pub database_url: Option<String>,
     /// JWT secret
     pub jwt_secret: Option<String>,
+    /// Anonymous requests per minute per client address on public endpoints
+    pub public_rate_limit: u32,
+    /// Requests per minute per API key on trading and account endpoints
+    pub private_rate_limit: u32,
+    /// Origins allowed to call trading and account endpoints from a browser
+    pub cors_allowed_origins: Vec<String>,
+    /// `max-age` in seconds for cacheable market data responses
+    pub market_data_cache_seconds: u32,
+    /// Compress REST responses for clients that accept gzip or brotli
+    pub compression_enabled: bool,
+    /// Smallest response body, in bytes, worth compressing
+    pub compression_min_bytes: u16,
+    /// Content types eligible for compression
+    pub compression_content_types: Vec<String>,
+    /// Operator key for admin endpoints, which are disabled when unset
+    pub admin_api_key: Option<String>,
+    /// End-of-day report destination, encodings and columns
+    pub reports: ReportConfig,
+    /// Schedules of report jobs and how many runs are kept
+    pub scheduler: SchedulerConfig,
+    /// Webhook retry and URL settings
+    pub webhooks: WebhookConfig,
+    /// Notification queue, mail relay and URL settings
+    pub notifications: NotificationConfig,
+    /// How often order books are snapshotted for replay, disabled when unset
+    pub order_book_snapshot_interval: Option<Duration>,
+    /// How often order books are sampled for liquidity heatmaps, disabled when unset
+    pub heatmap: Option<HeatmapConfig>,
+    /// External custody adapters for withdrawals and deposits
+    pub settlement: SettlementConfig,
+    /// JSON number format of clients that do not ask for one
+    pub number_format: NumberFormat,
+    /// How prices, quantities and amounts are rounded for display
+    pub display_rounding: RoundingMode,
+    /// Maker rebate period, rate and quoting requirements
+    pub incentives: IncentiveConfig,
+    /// Earn accrual period and interest rates
+    pub earn: EarnConfig,
+    /// Perpetual funding interval and rate cap
+    pub funding: FundingConfig,
+    /// External reference price sources and staleness
+    pub index_prices: IndexPriceConfig,
+    /// Background workers settling trades after placement
+    pub settlement_pipeline: PipelineConfig,
+    /// Health probe caching and timeout
+    pub health: HealthConfig,
+    /// Request size and time limits
+    pub limits: RequestLimits,
+    /// Binary market data feed, disabled unless an address is set
+    pub binary_feed: FeedConfig,
+    /// Daily trade and candle archives
+    pub archive: ArchiveConfig,
+    /// How long candles of each interval are kept
+    pub candle_retention: CandleRetention,
+    /// Bounds on the trades, candles and books market data keeps in memory
+    pub memory_budget: MemoryBudget,
+    /// Queue depths at which lagging market data subscribers are conflated or disconnected
+    pub backpressure: Backpressure,
+    /// Whether market data keeps trade, book and candle history in the database
+    /// at `database_url` instead of in memory
+    pub market_data_persist: bool,
+    /// History restored into market data at startup, `None` to start empty
+    pub market_data_warmup: Option<WarmupConfig>,
+    /// Time between checks of market data for trades missed from the engine
+    pub market_data_sync_interval: Option<Duration>,
+    /// Minimum trade sizes shown on public trade feeds and tickers
+    pub tape_filter: TapeFilter,
+    /// External exchange and markets mirrored as read-only shadow markets
+    pub shadow: ShadowConfig,
+    /// How markets that do not use FIFO share takers within a price level
+    pub allocations: BTreeMap<String, Allocation>,
+    /// Least time between re-pricings of a book's pegged orders
+    pub peg_reprice_interval: Duration,
+    /// What the engine does with a book found crossed after a command
+    pub book_verification: BookVerification,
+    /// How new order and trade ids are generated
+    pub id_scheme: IdScheme,
+    /// Node number written into monotonic ids, distinct per engine sharing a store
+    pub id_node: u16,
+    /// Feature flags started on or off instead of at their defaults
+    pub feature_flags: BTreeMap<String, bool>,
+    /// Assets registered at startup unless they already are
+    pub assets: Vec<Asset>,
+    /// Faults injected for failure testing, in builds with the `chaos` feature
+    pub chaos: ChaosConfig,
+    /// Duplicate order detection of accounts that set none
+    pub duplicate_orders: DuplicateOrderSettings,
 }
 
 impl AppConfig {
@@ -23,6 +150,555 @@ impl AppConfig {
                 .unwrap_or(8080),
             database_url: env::var("DATABASE_URL").ok(),
             jwt_secret: env::var("JWT_SECRET").ok(),
+            public_rate_limit: env_number("PUBLIC_RATE_LIMIT", 1200),
+            private_rate_limit: env_number("PRIVATE_RATE_LIMIT", 300),
+            cors_allowed_origins: env_list("CORS_ALLOWED_ORIGINS").unwrap_or_default(),
+            market_data_cache_seconds: env_number("MARKET_DATA_CACHE_SECONDS", 1),
+            compression_enabled: env_number("COMPRESSION_ENABLED", true),
+            compression_min_bytes: env_number("COMPRESSION_MIN_BYTES", 1024),
+            compression_content_types: env_list("COMPRESSION_CONTENT_TYPES")
+                .unwrap_or_else(|| vec!["application/json".to_string()]),
+            admin_api_key: env::var("ADMIN_API_KEY").ok().filter(|key| !key.is_empty()),
+            reports: report_config(),
+            scheduler: scheduler_config(),
+            webhooks: webhook_config(),
+            notifications: notification_config(),
+            order_book_snapshot_interval: Some(env_number("ORDER_BOOK_SNAPSHOT_SECONDS", 60))
+                .filter(|seconds| *seconds > 0)
+                .map(Duration::from_secs),
+            heatmap: Some(env_number("ORDER_BOOK_HEATMAP_SECONDS", 10))
+                .filter(|seconds| *seconds > 0)
+                .map(|seconds| HeatmapConfig {
+                    interval: Duration::from_secs(seconds),
+                    levels: env_number("ORDER_BOOK_HEATMAP_LEVELS", HeatmapConfig::default().levels).max(1),
+                }),
+            settlement: settlement_config(),
+            number_format: env::var("JSON_NUMBER_FORMAT").ok()
+                .and_then(|format| format.parse().map_err(|e| warn!("Ignoring JSON_NUMBER_FORMAT: {}", e)).ok())
+                .unwrap_or_default(),
+            display_rounding: env::var("DISPLAY_ROUNDING").ok()
+                .and_then(|mode| mode.parse().map_err(|e| warn!("Ignoring DISPLAY_ROUNDING: {}", e)).ok())
+                .unwrap_or_default(),
+            incentives: incentive_config(),
+            earn: earn_config(),
+            funding: funding_config(),
+            index_prices: index_price_config(),
+            settlement_pipeline: PipelineConfig {
+                workers: env_number("TRADE_SETTLEMENT_WORKERS", 4),
+                capacity: env_number("TRADE_SETTLEMENT_QUEUE", PipelineConfig::default().capacity).max(1),
+                retry_interval: Duration::from_secs(
+                    env_number("TRADE_SETTLEMENT_RETRY_SECONDS", PipelineConfig::default().retry_interval.as_secs()).max(1),
+                ),
+            },
+            health: HealthConfig {
+                ttl: Duration::from_secs(env_number("HEALTH_CACHE_SECONDS", HealthConfig::default().ttl.as_secs()).max(1)),
+                timeout: Duration::from_millis(env_number("HEALTH_PROBE_TIMEOUT_MS", 2000)),
+            },
+            limits: limits_config(),
+            binary_feed: binary_feed_config(),
+            archive: archive_config(),
+            candle_retention: candle_retention_config(),
+            memory_budget: memory_budget_config(),
+            backpressure: backpressure_config(),
+            market_data_persist: env_number("MARKET_DATA_PERSIST", false),
+            market_data_warmup: Some(env_number("MARKET_DATA_WARMUP_HOURS", 24i64))
+                .filter(|hours| *hours > 0)
+                .map(|hours| WarmupConfig { window: chrono::Duration::hours(hours) }),
+            market_data_sync_interval: Some(env_number("MARKET_DATA_SYNC_SECONDS", 5))
+                .filter(|seconds| *seconds > 0)
+                .map(Duration::from_secs),
+            tape_filter: tape_filter_config(),
+            shadow: shadow_config(),
+            allocations: allocation_config(),
+            peg_reprice_interval: Duration::from_millis(
+                env_number("PEG_REPRICE_INTERVAL_MS", DEFAULT_PEG_REPRICE_INTERVAL.as_millis() as u64).max(1)
+            ),
+            book_verification: env::var("MATCHING_BOOK_VERIFICATION").ok()
+                .and_then(|mode| mode.parse().map_err(|e| warn!("Ignoring MATCHING_BOOK_VERIFICATION: {}", e)).ok())
+                .unwrap_or_default(),
+            id_scheme: env::var("ID_SCHEME").ok()
+                .and_then(|scheme| scheme.parse().map_err(|e| warn!("Ignoring ID_SCHEME: {}", e)).ok())
+                .unwrap_or_default(),
+            id_node: id_node(),
+            feature_flags: feature_flags_config(),
+            assets: default_assets(),
+            chaos: chaos_config(),
+            duplicate_orders: DuplicateOrderSettings {
+                window_ms: env_number("DUPLICATE_ORDER_WINDOW_MS", 0u64).min(MAX_WINDOW_MS),
+                action: env::var("DUPLICATE_ORDER_ACTION").ok()
+                    .and_then(|action| action.parse().map_err(|e| warn!("Ignoring DUPLICATE_ORDER_ACTION: {}", e)).ok())
+                    .unwrap_or_default(),
+            },
+        }
+    }
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read the monotonic id node number, ignoring ones that do not fit
+fn id_node() -> u16 {
+    let node = env_number("ID_NODE", 0);
+    if node > MAX_NODE {
+        warn!("Ignoring ID_NODE {}: above {}", node, MAX_NODE);
+        return 0;
+    }
+    node
+}
+
+/// Assets listed until operators register their own
+pub fn default_assets() -> Vec<Asset> {
+    vec![
+        Asset::new("BTC", "Bitcoin", 8),
+        Asset::new("ETH", "Ether", 8),
+        Asset::new("USD", "US Dollar", 2),
+        Asset::new("USDT", "Tether USD", 6),
+    ]
+}
+
+/// Read faults to inject, ignoring them in builds without the `chaos` feature
+fn chaos_config() -> ChaosConfig {
+    let Ok(faults) = env::var("CHAOS_FAULTS") else {
+        return ChaosConfig::default();
+    };
+    if !Chaos::is_supported() {
+        warn!("Ignoring CHAOS_FAULTS: built without the chaos feature");
+        return ChaosConfig::default();
+    }
+    match faults.parse::<ChaosConfig>() {
+        Ok(config) => ChaosConfig { seed: env_number("CHAOS_SEED", 0), ..config },
+        Err(e) => {
+            warn!("Ignoring CHAOS_FAULTS: {}", e);
+            ChaosConfig::default()
         }
     }
-}
\ No newline at end of file
+}
+
+/// Read feature flag overrides; `FEATURE_FLAGS` lists them as `NAME=true|false`,
+/// e.g. `enable_margin=true,book_level_pruning=false`
+fn feature_flags_config() -> BTreeMap<String, bool> {
+    env_list("FEATURE_FLAGS")
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| {
+            let (name, enabled) = entry.split_once('=')?;
+            let name = name.trim();
+            if flags::spec(name).is_none() {
+                warn!("Ignoring FEATURE_FLAGS entry {}: unknown flag", entry);
+                return None;
+            }
+            match enabled.trim().parse() {
+                Ok(enabled) => Some((name.to_string(), enabled)),
+                Err(e) => {
+                    warn!("Ignoring FEATURE_FLAGS entry {}: {}", entry, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Read request limits, keeping the defaults for unset values
+fn limits_config() -> RequestLimits {
+    let defaults = RequestLimits::default();
+    RequestLimits {
+        max_body_bytes: env_number("MAX_REQUEST_BODY_BYTES", defaults.max_body_bytes),
+        max_headers: env_number("MAX_REQUEST_HEADERS", defaults.max_headers),
+        max_header_bytes: env_number("MAX_REQUEST_HEADER_BYTES", defaults.max_header_bytes),
+        timeout: Duration::from_secs(env_number("REQUEST_TIMEOUT_SECONDS", defaults.timeout.as_secs()).max(1)),
+        ws_max_message_bytes: env_number("WS_MAX_MESSAGE_BYTES", defaults.ws_max_message_bytes),
+        ws_max_frame_bytes: env_number("WS_MAX_FRAME_BYTES", defaults.ws_max_frame_bytes),
+        ws_messages_per_second: env_number("WS_MESSAGES_PER_SECOND", defaults.ws_messages_per_second),
+        ws_method_quotas: ws_method_quotas_config().unwrap_or(defaults.ws_method_quotas),
+        ws_max_refused_per_minute: env_number("WS_MAX_REFUSED_PER_MINUTE", defaults.ws_max_refused_per_minute),
+    }
+}
+
+/// Read WebSocket method quotas; `WS_METHOD_QUOTAS` lists them as
+/// `METHOD=CALLS_PER_MINUTE`, e.g. `getOrderBook=60,getTrades=120`
+fn ws_method_quotas_config() -> Option<BTreeMap<String, u32>> {
+    let quotas = env_list("WS_METHOD_QUOTAS")?
+        .iter()
+        .filter_map(|entry| {
+            let (method, per_minute) = entry.split_once('=')?;
+            match per_minute.trim().parse() {
+                Ok(per_minute) => Some((method.trim().to_string(), per_minute)),
+                Err(e) => {
+                    warn!("Ignoring WS_METHOD_QUOTAS entry {}: {}", entry, e);
+                    None
+                }
+            }
+        })
+        .collect();
+    Some(quotas)
+}
+
+/// Read binary feed addresses, ignoring ones that do not parse
+fn binary_feed_config() -> FeedConfig {
+    let address = |name: &str| {
+        env::var(name).ok()
+            .filter(|addr| !addr.is_empty())
+            .and_then(|addr| addr.parse().map_err(|e| warn!("Ignoring {}: {}", name, e)).ok())
+    };
+    FeedConfig {
+        tcp_addr: address("BINARY_FEED_TCP_ADDR"),
+        udp_target: address("BINARY_FEED_UDP_ADDR"),
+        retained: env_number("BINARY_FEED_RETAINED", FeedConfig::default().retained),
+    }
+}
+
+/// Read candle retention, where a retention of 0 keeps candles forever
+fn candle_retention_config() -> CandleRetention {
+    let defaults = CandleRetention::default();
+    let minute_days = env_number("CANDLE_MINUTE_RETENTION_DAYS", defaults.minute.map_or(0, |kept| kept.num_days()));
+    // Months of 30 days
+    let hour_months = env_number("CANDLE_HOUR_RETENTION_MONTHS", defaults.hour.map_or(0, |kept| kept.num_days() / 30));
+    CandleRetention {
+        minute: (minute_days > 0).then(|| chrono::Duration::days(minute_days)),
+        hour: (hour_months > 0).then(|| chrono::Duration::days(hour_months * 30)),
+        compaction_interval: Duration::from_secs(
+            env_number("CANDLE_COMPACTION_SECONDS", defaults.compaction_interval.as_secs()).max(1),
+        ),
+    }
+}
+
+/// Read the market data memory budget, where a cap of 0 means no limit
+fn memory_budget_config() -> MemoryBudget {
+    let defaults = MemoryBudget::default();
+    let idle_minutes = env_number("MARKET_DATA_IDLE_EVICTION_MINUTES", 0i64);
+    MemoryBudget {
+        recent_trades: env_number("MARKET_DATA_RECENT_TRADES", defaults.recent_trades),
+        candles_per_series: Some(env_number("MARKET_DATA_CANDLES_PER_SERIES", 0usize)).filter(|cap| *cap > 0),
+        markets: Some(env_number("MARKET_DATA_MAX_MARKETS", 0usize)).filter(|cap| *cap > 0),
+        idle_after: (idle_minutes > 0).then(|| chrono::Duration::minutes(idle_minutes)),
+        eviction_interval: Duration::from_secs(
+            env_number("MARKET_DATA_EVICTION_SECONDS", defaults.eviction_interval.as_secs()).max(1),
+        ),
+    }
+}
+
+/// Read the queue depths at which lagging market data subscribers are conflated or disconnected
+fn backpressure_config() -> Backpressure {
+    let defaults = Backpressure::default();
+    Backpressure {
+        conflate_at: env_number("MARKET_DATA_CONFLATE_DEPTH", defaults.conflate_at).max(1),
+        disconnect_at: env_number("MARKET_DATA_DISCONNECT_DEPTH", defaults.disconnect_at).max(1),
+    }
+}
+
+/// Read minimum displayed trade sizes; `TRADE_TAPE_MIN_SIZES` lists them as
+/// `MARKET:SIZE`, e.g. `BTC/USD:0.001,ETH/USD:0.01`
+fn tape_filter_config() -> TapeFilter {
+    let min_trade_sizes = env_list("TRADE_TAPE_MIN_SIZES")
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| {
+            let (market, size) = entry.split_once(':')?;
+            match size.trim().parse() {
+                Ok(size) => Some((market.trim().to_uppercase(), size)),
+                Err(e) => {
+                    warn!("Ignoring TRADE_TAPE_MIN_SIZES entry {}: {}", entry, e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    TapeFilter { min_trade_sizes }
+}
+
+/// Read per-market allocation; `MATCHING_ALLOCATION` lists markets as
+/// `SYMBOL=ALLOCATION`, e.g. `ES/USD=pro_rata:2:1` for pro-rata with a
+/// minimum allocation of 2 in lots of 1. A missing lot size is the market's
+/// quantity step.
+fn allocation_config() -> BTreeMap<String, Allocation> {
+    env_list("MATCHING_ALLOCATION")
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| {
+            let Some((symbol, allocation)) = entry.split_once('=') else {
+                warn!("Ignoring MATCHING_ALLOCATION entry {}: expected SYMBOL=ALLOCATION", entry);
+                return None;
+            };
+            match allocation.parse() {
+                Ok(allocation) => Some((symbol.trim().to_uppercase(), allocation)),
+                Err(e) => {
+                    warn!("Ignoring MATCHING_ALLOCATION entry {}: {}", entry, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Read shadow market settings; `SHADOW_MARKETS` lists markets as
+/// `SYMBOL=EXTERNAL_SYMBOL`, e.g. `BTC/USDT=BTCUSDT`
+fn shadow_config() -> ShadowConfig {
+    let defaults = ShadowConfig::default();
+
+    let markets = env_list("SHADOW_MARKETS")
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| match entry.split_once('=') {
+            Some((symbol, external)) if !symbol.trim().is_empty() && !external.trim().is_empty() => Some(ShadowMarket {
+                symbol: symbol.trim().to_uppercase(),
+                external_symbol: external.trim().to_string(),
+            }),
+            _ => {
+                warn!("Ignoring SHADOW_MARKETS entry {}: expected SYMBOL=EXTERNAL_SYMBOL", entry);
+                None
+            }
+        })
+        .collect();
+
+    ShadowConfig {
+        url: env::var("SHADOW_FEED_URL").ok().filter(|url| !url.trim().is_empty()),
+        markets,
+        poll_interval: Duration::from_secs(env_number("SHADOW_POLL_SECONDS", defaults.poll_interval.as_secs()).max(1)),
+        depth: env_number("SHADOW_DEPTH", defaults.depth).max(1),
+        trades: env_number("SHADOW_TRADES", defaults.trades).max(1),
+    }
+}
+
+/// Read data archive settings, keeping the defaults for unset values
+fn archive_config() -> ArchiveConfig {
+    let defaults = ArchiveConfig::default();
+    ArchiveConfig {
+        retention_days: env_number("ARCHIVE_RETENTION_DAYS", defaults.retention_days),
+        candle_intervals: env_list("ARCHIVE_CANDLE_INTERVALS")
+            .map(|codes| {
+                codes.iter()
+                    .filter_map(|code| {
+                        let interval = CandleInterval::from_code(code);
+                        if interval.is_none() {
+                            warn!("Ignoring ARCHIVE_CANDLE_INTERVALS entry: unknown interval {}", code);
+                        }
+                        interval
+                    })
+                    .collect()
+            })
+            .unwrap_or(defaults.candle_intervals),
+    }
+}
+
+/// Read report settings, preferring an S3 bucket over a local directory
+fn report_config() -> ReportConfig {
+    let defaults = ReportConfig::default();
+
+    let sink = match (env::var("REPORT_S3_ENDPOINT").ok(), env::var("REPORT_S3_BUCKET").ok()) {
+        (Some(endpoint), Some(bucket)) => Some(ReportSink::S3(S3Config {
+            endpoint,
+            bucket,
+            prefix: env::var("REPORT_S3_PREFIX").unwrap_or_default(),
+            region: env::var("REPORT_S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            access_key: env::var("REPORT_S3_ACCESS_KEY").unwrap_or_default(),
+            secret_key: env::var("REPORT_S3_SECRET_KEY").unwrap_or_default(),
+        })),
+        _ => env::var("REPORT_DIR").ok()
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| ReportSink::Directory(PathBuf::from(dir))),
+    };
+
+    ReportConfig {
+        sink,
+        formats: env_parsed_list("REPORT_FORMATS").unwrap_or(defaults.formats),
+        fields: env_parsed_list("REPORT_FIELDS").unwrap_or(defaults.fields),
+        retention_days: env_number("REPORT_RETENTION_DAYS", defaults.retention_days),
+        accounting: AccountingConfig {
+            formats: env_parsed_list("ACCOUNTING_FORMATS").unwrap_or(defaults.accounting.formats),
+            chart: env_parsed_list::<AccountMapping>("ACCOUNTING_ACCOUNTS")
+                .map(|mappings| ChartOfAccounts::with_mappings(&mappings))
+                .unwrap_or(defaults.accounting.chart),
+        },
+    }
+}
+
+/// Read report job schedules, `;` separated as schedules contain commas
+fn scheduler_config() -> SchedulerConfig {
+    let defaults = SchedulerConfig::default();
+
+    let schedules = env::var("REPORT_SCHEDULES").ok()
+        .map(|schedules| {
+            schedules.split(';')
+                .map(str::trim)
+                .filter(|schedule| !schedule.is_empty())
+                .filter_map(|schedule| {
+                    schedule.parse::<JobSchedule>()
+                        .map_err(|e| warn!("Ignoring REPORT_SCHEDULES entry: {}", e))
+                        .ok()
+                })
+                .collect()
+        })
+        .unwrap_or(defaults.schedules);
+
+    SchedulerConfig {
+        schedules,
+        history: env_number("REPORT_JOB_HISTORY", defaults.history).max(1),
+    }
+}
+
+/// Read webhook delivery settings
+fn webhook_config() -> WebhookConfig {
+    let defaults = WebhookConfig::default();
+
+    WebhookConfig {
+        max_attempts: env_number("WEBHOOK_MAX_ATTEMPTS", defaults.max_attempts),
+        allow_http: env_number("WEBHOOK_ALLOW_HTTP", defaults.allow_http),
+        ..defaults
+    }
+}
+
+/// Read notification settings; email is enabled by `SMTP_HOST`
+fn notification_config() -> NotificationConfig {
+    let defaults = NotificationConfig::default();
+
+    let smtp = env::var("SMTP_HOST").ok()
+        .filter(|host| !host.is_empty())
+        .map(|host| SmtpConfig {
+            host,
+            port: env_number("SMTP_PORT", 25),
+            from: env::var("SMTP_FROM").unwrap_or_else(|_| "notifications@localhost".to_string()),
+            username: env::var("SMTP_USERNAME").ok().filter(|user| !user.is_empty()),
+            password: env::var("SMTP_PASSWORD").ok(),
+        });
+
+    NotificationConfig {
+        queue_capacity: env_number("NOTIFICATION_QUEUE", defaults.queue_capacity).max(1),
+        smtp,
+        allow_http: env_number("NOTIFICATION_ALLOW_HTTP", defaults.allow_http),
+        ..defaults
+    }
+}
+
+/// Read settlement adapter settings; each adapter is enabled by its directory or URL
+fn settlement_config() -> SettlementConfig {
+    let bank_file = match (env::var("SETTLEMENT_BANK_OUTBOX").ok(), env::var("SETTLEMENT_BANK_INBOX").ok()) {
+        (Some(outbox), Some(inbox)) if !outbox.is_empty() && !inbox.is_empty() => Some(BankFileConfig {
+            outbox: PathBuf::from(outbox),
+            inbox: PathBuf::from(inbox),
+            assets: env_list("SETTLEMENT_BANK_ASSETS").unwrap_or_else(|| vec!["USD".to_string()]),
+        }),
+        _ => None,
+    };
+
+    let crypto_node = env::var("SETTLEMENT_NODE_URL").ok()
+        .filter(|url| !url.is_empty())
+        .map(|url| CryptoNodeConfig {
+            url,
+            user: env::var("SETTLEMENT_NODE_USER").unwrap_or_default(),
+            password: env::var("SETTLEMENT_NODE_PASSWORD").unwrap_or_default(),
+            asset: env::var("SETTLEMENT_NODE_ASSET").unwrap_or_else(|_| "BTC".to_string()),
+            min_confirmations: env_number("SETTLEMENT_NODE_CONFIRMATIONS", 3),
+        });
+
+    SettlementConfig {
+        bank_file,
+        crypto_node,
+        deposit_poll_interval: Duration::from_secs(env_number("SETTLEMENT_POLL_SECONDS", 30).max(1)),
+    }
+}
+
+/// Read maker rebate settings
+fn incentive_config() -> IncentiveConfig {
+    let defaults = IncentiveConfig::default();
+
+    IncentiveConfig {
+        period: Duration::from_secs(env_number("INCENTIVE_PERIOD_SECONDS", defaults.period.as_secs()).max(60)),
+        rebate_rate: env_number("INCENTIVE_REBATE_RATE", defaults.rebate_rate),
+        min_presence: env_number("INCENTIVE_MIN_PRESENCE", defaults.min_presence),
+        max_spread_bps: env::var("INCENTIVE_MAX_SPREAD_BPS").ok().and_then(|bps| bps.parse().ok()),
+    }
+}
+
+/// Read earn settings; `EARN_RATES` lists annual rates as `ASSET:RATE`, e.g. `USD:0.05,BTC:0.01`
+fn earn_config() -> EarnConfig {
+    let defaults = EarnConfig::default();
+
+    let rates: BTreeMap<String, _> = env_list("EARN_RATES")
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| {
+            let (asset, rate) = entry.split_once(':')?;
+            match rate.trim().parse() {
+                Ok(rate) => Some((asset.trim().to_uppercase(), rate)),
+                Err(e) => {
+                    warn!("Ignoring EARN_RATES entry {}: {}", entry, e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    EarnConfig {
+        period: Duration::from_secs(env_number("EARN_PERIOD_SECONDS", defaults.period.as_secs()).max(60)),
+        rates,
+    }
+}
+
+/// Read perpetual funding settings
+fn funding_config() -> FundingConfig {
+    let defaults = FundingConfig::default();
+    FundingConfig {
+        interval: Duration::from_secs(env_number("FUNDING_INTERVAL_SECONDS", defaults.interval.as_secs()).max(60)),
+        max_rate: env_number("FUNDING_MAX_RATE", defaults.max_rate).abs(),
+    }
+}
+
+/// Read index price settings; `INDEX_PRICE_SOURCES` lists sources as `NAME=URL_OR_PATH`
+fn index_price_config() -> IndexPriceConfig {
+    let defaults = IndexPriceConfig::default();
+
+    let sources = env_list("INDEX_PRICE_SOURCES")
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| match entry.split_once('=') {
+            Some((name, location)) if !name.trim().is_empty() && !location.trim().is_empty() => {
+                Some((name.trim().to_string(), location.trim().to_string()))
+            }
+            _ => {
+                warn!("Ignoring INDEX_PRICE_SOURCES entry {}: expected NAME=URL_OR_PATH", entry);
+                None
+            }
+        })
+        .collect();
+
+    IndexPriceConfig {
+        poll_interval: Duration::from_secs(env_number("INDEX_PRICE_POLL_SECONDS", defaults.poll_interval.as_secs()).max(1)),
+        max_age: Duration::from_secs(env_number("INDEX_PRICE_MAX_AGE_SECONDS", defaults.max_age.as_secs()).max(1)),
+        min_sources: env_number("INDEX_PRICE_MIN_SOURCES", defaults.min_sources).max(1),
+        quote_asset: env::var("INDEX_PRICE_QUOTE_ASSET").ok()
+            .map(|asset| asset.trim().to_uppercase())
+            .filter(|asset| !asset.is_empty())
+            .unwrap_or(defaults.quote_asset),
+        sources,
+    }
+}
+
+fn env_number<T: FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Read a comma separated list, skipping empty entries
+fn env_list(name: &str) -> Option<Vec<String>> {
+    env::var(name).ok().map(|list| {
+        list.split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    })
+}
+
+/// Read a comma separated list of values, skipping and logging ones that do not parse
+fn env_parsed_list<T: FromStr<Err = String>>(name: &str) -> Option<Vec<T>> {
+    let values: Vec<T> = env_list(name)?
+        .iter()
+        .filter_map(|item| item.parse().map_err(|e| warn!("Ignoring {} entry: {}", name, e)).ok())
+        .collect();
+    (!values.is_empty()).then_some(values)
+}
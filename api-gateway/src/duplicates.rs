@@ -0,0 +1,200 @@
+//! Duplicate order detection
+//!
+//! A double-clicked order button or a client retrying a placement it never
+//! got an answer for sends the same order twice. With detection on for an
+//! account, an order placed within the account's window of an identical one
+//! (same market, side, price, quantity and client order ID) is a duplicate:
+//! it is either refused with `409` or answered with the earlier order instead
+//! of being placed. Orders with a distinct client order ID are never
+//! duplicates, and an order the engine refused does not count.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use common::decimal::{Price, Quantity};
+use common::error::{Error, Result};
+use common::model::order::{Order, Side};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Longest duplicate window an account may set, a minute
+pub const MAX_WINDOW_MS: u64 = 60_000;
+
+/// What happens to a duplicate order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateAction {
+    /// Refuse it with `409 Conflict`
+    #[default]
+    Reject,
+    /// Answer with the earlier order, placing nothing
+    Deduplicate,
+}
+
+impl fmt::Display for DuplicateAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DuplicateAction::Reject => "reject",
+            DuplicateAction::Deduplicate => "deduplicate",
+        })
+    }
+}
+
+impl FromStr for DuplicateAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "reject" => Ok(DuplicateAction::Reject),
+            "deduplicate" => Ok(DuplicateAction::Deduplicate),
+            other => Err(Error::ValidationError(format!("Unknown duplicate order action: {}", other))),
+        }
+    }
+}
+
+/// An account's duplicate order detection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct DuplicateOrderSettings {
+    /// Milliseconds within which an identical order is a duplicate, 0 for off
+    pub window_ms: u64,
+    /// What happens to a duplicate
+    #[serde(default)]
+    pub action: DuplicateAction,
+}
+
+impl DuplicateOrderSettings {
+    /// Whether duplicates are detected
+    pub fn is_enabled(&self) -> bool {
+        self.window_ms > 0
+    }
+}
+
+/// What makes two orders identical
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OrderKey {
+    market: String,
+    side: Side,
+    price: Option<Price>,
+    quantity: Quantity,
+    client_order_id: Option<String>,
+}
+
+impl OrderKey {
+    fn of(order: &Order) -> Self {
+        Self {
+            market: order.market.clone(),
+            side: order.side,
+            price: order.price,
+            quantity: order.quantity,
+            client_order_id: order.client_order_id.clone(),
+        }
+    }
+}
+
+/// An order placed recently
+struct Placement {
+    key: OrderKey,
+    order: Order,
+    at: Instant,
+}
+
+/// An order found to duplicate an earlier one
+#[derive(Debug, Clone)]
+pub struct Duplicate {
+    /// The earlier order, as last placed
+    pub order: Order,
+    /// Time since the earlier order was placed
+    pub age: Duration,
+    /// What to do with the new one
+    pub action: DuplicateAction,
+}
+
+/// Recent orders of each account with detection on
+pub struct DuplicateOrderGuard {
+    defaults: DuplicateOrderSettings,
+    settings: DashMap<Uuid, DuplicateOrderSettings>,
+    recent: DashMap<Uuid, VecDeque<Placement>>,
+}
+
+impl DuplicateOrderGuard {
+    /// Detect duplicates with `defaults` for accounts that set nothing else
+    pub fn new(defaults: DuplicateOrderSettings) -> Self {
+        Self {
+            defaults,
+            settings: DashMap::new(),
+            recent: DashMap::new(),
+        }
+    }
+
+    /// An account's settings
+    pub fn settings(&self, account_id: Uuid) -> DuplicateOrderSettings {
+        self.settings.get(&account_id).map_or(self.defaults, |settings| *settings)
+    }
+
+    /// Replace an account's settings
+    pub fn set_settings(&self, account_id: Uuid, settings: DuplicateOrderSettings) -> Result<DuplicateOrderSettings> {
+        if settings.window_ms > MAX_WINDOW_MS {
+            return Err(Error::ValidationError(format!("Duplicate window is over {} ms", MAX_WINDOW_MS)));
+        }
+        self.settings.insert(account_id, settings);
+        if !settings.is_enabled() {
+            self.recent.remove(&account_id);
+        }
+        Ok(settings)
+    }
+
+    /// Check `order` against its account's recent orders
+    ///
+    /// Returns the earlier order it duplicates, or records it as placed.
+    /// Identical orders checked at the same time see each other, so only one
+    /// of them is placed.
+    pub fn check(&self, order: &Order) -> Option<Duplicate> {
+        let settings = self.settings(order.user_id);
+        if !settings.is_enabled() {
+            return None;
+        }
+        let window = Duration::from_millis(settings.window_ms);
+        let now = Instant::now();
+        let key = OrderKey::of(order);
+
+        let mut recent = self.recent.entry(order.user_id).or_default();
+        while recent.front().is_some_and(|placement| now.duration_since(placement.at) >= window) {
+            recent.pop_front();
+        }
+        if let Some(earlier) = recent.iter().find(|placement| placement.key == key) {
+            return Some(Duplicate {
+                order: earlier.order.clone(),
+                age: now.duration_since(earlier.at),
+                action: settings.action,
+            });
+        }
+        recent.push_back(Placement { key, order: order.clone(), at: now });
+        None
+    }
+
+    /// Record the order the engine placed, to answer its duplicates with
+    pub fn placed(&self, order: &Order) {
+        if let Some(mut recent) = self.recent.get_mut(&order.user_id) {
+            if let Some(placement) = recent.iter_mut().find(|placement| placement.order.id == order.id) {
+                placement.order = order.clone();
+            }
+        }
+    }
+
+    /// Forget an order the engine refused, so placing it again is no duplicate
+    pub fn forget(&self, order: &Order) {
+        if let Some(mut recent) = self.recent.get_mut(&order.user_id) {
+            recent.retain(|placement| placement.order.id != order.id);
+        }
+    }
+}
+
+impl Default for DuplicateOrderGuard {
+    fn default() -> Self {
+        Self::new(DuplicateOrderSettings::default())
+    }
+}
@@ -0,0 +1,276 @@
+//! Interest on idle balances
+//!
+//! Accounts opt assets into earn and accrue interest on their available
+//! balance at the asset's configured annual rate. Funds locked in open orders
+//! earn nothing. Each period, which defaults to a day, interest since the last
+//! accrual is worked out per account and asset, from the later of the previous
+//! accrual and the opt-in, and credited in the same asset.
+//!
+//! Credits go through the account service's deposit path under the
+//! `earn-interest` source with a reference per account, asset and accrual
+//! start, so an accrual is never paid twice. No asset earns anything unless a
+//! rate is configured for it.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use account_service::settlement::DepositConfirmation;
+use account_service::AccountService;
+use chrono::{DateTime, Utc};
+use common::decimal::{Amount, Quantity};
+use common::error::{Error, Result};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
+use tracing::{info, warn};
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::pipeline::SettlementPipeline;
+use crate::AppState;
+
+/// Source interest is credited under
+pub const EARN_SOURCE: &str = "earn-interest";
+
+/// Most accruals kept in memory per account
+const HISTORY_CAPACITY: usize = 1000;
+
+/// Decimal places interest is rounded down to
+const INTEREST_SCALE: u32 = 8;
+
+const SECONDS_PER_YEAR: i64 = 365 * 24 * 60 * 60;
+
+/// Earn program settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct EarnConfig {
+    /// Time between accruals
+    pub period: Duration,
+    /// Annual interest rate by asset, e.g. 0.05 for 5%; assets without one cannot be opted in
+    pub rates: BTreeMap<String, Decimal>,
+}
+
+impl Default for EarnConfig {
+    fn default() -> Self {
+        Self {
+            period: Duration::from_secs(24 * 60 * 60),
+            rates: BTreeMap::new(),
+        }
+    }
+}
+
+/// An asset an account opted into earn
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EarnSubscription {
+    /// Account ID
+    pub account_id: Uuid,
+    /// Asset symbol
+    pub asset: String,
+    /// Annual interest rate
+    pub annual_rate: Decimal,
+    /// When the account opted in
+    pub subscribed_at: DateTime<Utc>,
+    /// Time interest has been accrued up to
+    pub accrued_until: DateTime<Utc>,
+}
+
+/// Interest accrued on one asset of one account
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Accrual {
+    /// Account ID
+    pub account_id: Uuid,
+    /// Asset the interest is paid in
+    pub asset: String,
+    /// Start of the accrual
+    pub start: DateTime<Utc>,
+    /// End of the accrual
+    pub end: DateTime<Utc>,
+    /// Available balance interest was paid on, excluding locked funds
+    pub principal: Quantity,
+    /// Annual interest rate
+    pub annual_rate: Decimal,
+    /// Interest earned
+    pub amount: Amount,
+    /// Whether the interest was credited to the account
+    pub credited: bool,
+}
+
+/// Opt-ins and accrual history of the earn program
+pub struct EarnProgram {
+    config: EarnConfig,
+    /// Opted-in assets by account
+    subscriptions: RwLock<HashMap<Uuid, BTreeMap<String, EarnSubscription>>>,
+    /// Accruals by account, newest first
+    history: RwLock<HashMap<Uuid, VecDeque<Accrual>>>,
+}
+
+impl EarnProgram {
+    /// Program with the given rates and no opt-ins
+    pub fn new(config: EarnConfig) -> Self {
+        Self {
+            config,
+            subscriptions: RwLock::new(HashMap::new()),
+            history: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Program settings
+    pub fn config(&self) -> &EarnConfig {
+        &self.config
+    }
+
+    /// Opt an account's asset in from `now`, or return its existing opt-in
+    pub fn subscribe(&self, account_id: Uuid, asset: &str, now: DateTime<Utc>) -> Result<EarnSubscription> {
+        let annual_rate = *self.config.rates.get(asset).ok_or_else(|| {
+            Error::ValidationError(format!("No interest is offered on {}", asset))
+        })?;
+
+        let mut subscriptions = self.subscriptions.write().unwrap();
+        let subscription = subscriptions
+            .entry(account_id)
+            .or_default()
+            .entry(asset.to_string())
+            .or_insert_with(|| EarnSubscription {
+                account_id,
+                asset: asset.to_string(),
+                annual_rate,
+                subscribed_at: now,
+                accrued_until: now,
+            });
+        Ok(subscription.clone())
+    }
+
+    /// Opt an account's asset out, forgoing interest since the last accrual
+    pub fn unsubscribe(&self, account_id: Uuid, asset: &str) -> Option<EarnSubscription> {
+        let mut subscriptions = self.subscriptions.write().unwrap();
+        let assets = subscriptions.get_mut(&account_id)?;
+        let removed = assets.remove(asset);
+        if assets.is_empty() {
+            subscriptions.remove(&account_id);
+        }
+        removed
+    }
+
+    /// An account's opted-in assets
+    pub fn subscriptions(&self, account_id: Uuid) -> Vec<EarnSubscription> {
+        self.subscriptions.read().unwrap()
+            .get(&account_id)
+            .map(|assets| assets.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// An account's accruals, newest first
+    pub fn accruals(&self, account_id: Uuid, limit: usize) -> Vec<Accrual> {
+        self.history.read().unwrap()
+            .get(&account_id)
+            .map(|accruals| accruals.iter().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Accrue interest on every opt-in up to `now` and credit it
+    ///
+    /// Pending trade settlement of each account is waited for first, so the
+    /// principal reflects its trades. Interest that fails to credit is logged
+    /// and reported as not credited, and the opt-in stays where it was so the
+    /// next accrual credits the period again.
+    pub async fn accrue(
+        &self,
+        account_service: &AccountService,
+        settlement: &SettlementPipeline,
+        now: DateTime<Utc>,
+    ) -> Vec<Accrual> {
+        let due: Vec<EarnSubscription> = self.subscriptions.read().unwrap()
+            .values()
+            .flat_map(|assets| assets.values())
+            .filter(|subscription| subscription.accrued_until < now)
+            .cloned()
+            .collect();
+
+        let mut accruals = Vec::with_capacity(due.len());
+        for subscription in due {
+            settlement.flush(subscription.account_id).await;
+            let principal = match account_service.get_balance(subscription.account_id, &subscription.asset).await {
+                Ok(balance) => balance.map_or(Quantity::ZERO, |balance| balance.available),
+                Err(e) => {
+                    warn!("Failed to read {} balance of {}: {}", subscription.asset, subscription.account_id, e);
+                    continue;
+                }
+            };
+
+            let mut accrual = Accrual {
+                account_id: subscription.account_id,
+                asset: subscription.asset.clone(),
+                start: subscription.accrued_until,
+                end: now,
+                principal,
+                annual_rate: subscription.annual_rate,
+                amount: interest(principal, subscription.annual_rate, subscription.accrued_until, now),
+                credited: false,
+            };
+
+            if accrual.amount > Amount::ZERO {
+                let confirmation = DepositConfirmation {
+                    reference: format!("{}:{}:{}", accrual.account_id, accrual.asset, accrual.start.timestamp_millis()),
+                    account_id: accrual.account_id,
+                    asset: accrual.asset.clone(),
+                    amount: accrual.amount,
+                };
+                match account_service.credit_deposit(EARN_SOURCE, &confirmation).await {
+                    Ok(credited) => accrual.credited = credited.is_some(),
+                    Err(e) => {
+                        warn!("Failed to credit interest {} to {}: {}", confirmation.reference, accrual.account_id, e);
+                        accruals.push(accrual);
+                        continue;
+                    }
+                }
+            }
+
+            self.advance(&accrual);
+            accruals.push(accrual);
+        }
+
+        info!(
+            "Accrued earn interest up to {}: {} credited",
+            now, accruals.iter().filter(|accrual| accrual.credited).count()
+        );
+        accruals
+    }
+
+    /// Move an opt-in past an accrual and record the accrual
+    fn advance(&self, accrual: &Accrual) {
+        // The account may have opted out while the interest was credited
+        if let Some(subscription) = self.subscriptions.write().unwrap()
+            .get_mut(&accrual.account_id)
+            .and_then(|assets| assets.get_mut(&accrual.asset))
+        {
+            subscription.accrued_until = accrual.end;
+        }
+
+        let mut history = self.history.write().unwrap();
+        let accruals = history.entry(accrual.account_id).or_default();
+        if accruals.len() == HISTORY_CAPACITY {
+            accruals.pop_back();
+        }
+        accruals.push_front(accrual.clone());
+    }
+}
+
+/// Simple interest on a principal between two times, rounded down
+fn interest(principal: Quantity, annual_rate: Decimal, start: DateTime<Utc>, end: DateTime<Utc>) -> Amount {
+    let seconds = Decimal::from((end - start).num_seconds().max(0));
+    (principal * annual_rate * seconds / Decimal::from(SECONDS_PER_YEAR))
+        .round_dp_with_strategy(INTEREST_SCALE, RoundingStrategy::ToZero)
+        .normalize()
+}
+
+/// Accrue earn interest at the end of every period
+pub fn spawn_earn_clock(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(state.earn.config().period);
+        // The first tick completes immediately
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+            state.earn.accrue(&state.account_service, &state.settlement, state.matching_engine.clock().now()).await;
+        }
+    })
+}
@@ -46,22 +46,40 @@ pub enum ApiError {
     #[error("Forbidden: {0}")]
     Forbidden(String),
     
+    #[error("Invalid API key: {0}")]
+    InvalidApiKey(String),
+    
+    #[error("Insufficient scope: {0}")]
+    InsufficientScope(String),
+    
+    #[error("IP address not allowed: {0}")]
+    IpNotAllowed(String),
+    
     #[error("Internal server error: {0}")]
     Internal(String),
     
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+    
+    #[error("Headers too large: {0}")]
+    HeadersTooLarge(String),
+    
+    #[error("Request timeout: {0}")]
+    Timeout(String),
+    
     #[error("Common error: {0}")]
     Common(#[from] common::error::Error),
 }
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
-        // Generate a request ID for tracking errors
-        let request_id = Uuid::new_v4().to_string();
-        
-        // Log the error with request ID for backend tracing
-        tracing::error!("API Error [{}]: {:?}", request_id, &self);
-        
-        let (status, code, details) = match &self {
+impl ApiError {
+    /// Stable error code, shared by REST error bodies and GraphQL error extensions
+    pub fn code(&self) -> &'static str {
+        self.parts().1
+    }
+
+    /// HTTP status, error code and details
+    fn parts(&self) -> (StatusCode, &'static str, Option<serde_json::Value>) {
+        match self {
             ApiError::NotFound(_) => (
                 StatusCode::NOT_FOUND, 
                 "not_found", 
@@ -82,11 +100,41 @@ impl IntoResponse for ApiError {
                 "forbidden", 
                 None
             ),
+            ApiError::InvalidApiKey(_) => (
+                StatusCode::UNAUTHORIZED, 
+                "invalid_api_key", 
+                None
+            ),
+            ApiError::InsufficientScope(_) => (
+                StatusCode::FORBIDDEN, 
+                "insufficient_scope", 
+                None
+            ),
+            ApiError::IpNotAllowed(_) => (
+                StatusCode::FORBIDDEN, 
+                "ip_not_allowed", 
+                None
+            ),
             ApiError::Internal(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR, 
                 "internal_error", 
                 None
             ),
+            ApiError::PayloadTooLarge(_) => (
+                StatusCode::PAYLOAD_TOO_LARGE, 
+                "payload_too_large", 
+                None
+            ),
+            ApiError::HeadersTooLarge(_) => (
+                StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE, 
+                "headers_too_large", 
+                None
+            ),
+            ApiError::Timeout(_) => (
+                StatusCode::REQUEST_TIMEOUT, 
+                "request_timeout", 
+                None
+            ),
             ApiError::Common(e) => match e {
                 // Client errors (4xx)
                 common::error::Error::InvalidOrder(_) => (
@@ -119,6 +167,11 @@ impl IntoResponse for ApiError {
                     "validation_error", 
                     None
                 ),
+                common::error::Error::Conflict(_) => (
+                    StatusCode::CONFLICT, 
+                    "conflict", 
+                    None
+                ),
                 common::error::Error::AuthorizationError(_) => (
                     StatusCode::FORBIDDEN, 
                     "authorization_error", 
@@ -165,7 +218,19 @@ impl IntoResponse for ApiError {
                     None
                 ),
             },
-        };
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        // Generate a request ID for tracking errors
+        let request_id = Uuid::new_v4().to_string();
+        
+        // Log the error with request ID for backend tracing
+        tracing::error!("API Error [{}]: {:?}", request_id, &self);
+        
+        let (status, code, details) = self.parts();
         
         // Create the error response with the new structure
         let error_response = ErrorResponse {
@@ -0,0 +1,51 @@
+//! Open order age sweep
+//!
+//! Expires orders that have rested on the book longer than their market's
+//! `max_order_age_secs`, releasing their reserved funds. Owners are notified
+//! through the engine's `OrderExpired` events.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use common::model::order::Order;
+use tracing::warn;
+
+use crate::AppState;
+
+/// How often resting orders are checked against their market's maximum age
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Expire stale orders every second
+pub fn spawn_expiry_sweep(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(EXPIRY_SWEEP_INTERVAL);
+        loop {
+            ticks.tick().await;
+            expire_stale_orders(&state, state.matching_engine.clock().now()).await;
+        }
+    })
+}
+
+/// Expire the orders that are too old at `now` and release their funds
+pub async fn expire_stale_orders(state: &AppState, now: DateTime<Utc>) -> Vec<Arc<Order>> {
+    let expired = state.matching_engine.expire_stale_orders(now);
+    for order in &expired {
+        // Fills of the order still settling must land before its remainder is released
+        state.settlement.flush(order.user_id).await;
+        if let Err(e) = state.account_service.release_reserved_funds(order).await {
+            warn!("Failed to release funds of expired order {}: {}", order.id, e);
+        }
+    }
+
+    let markets: BTreeSet<&str> = expired.iter().map(|order| order.market.as_str()).collect();
+    for market in markets {
+        if let Ok((bids, asks)) = state.matching_engine.get_market_depth(market, 10) {
+            if let Err(e) = state.market_data_service.update_order_book(market, bids, asks).await {
+                warn!("Failed to update order book of {}: {}", market, e);
+            }
+        }
+    }
+    expired
+}
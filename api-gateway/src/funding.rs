@@ -0,0 +1,237 @@
+//! Perpetual funding
+//!
+//! Perpetual markets never deliver, so their price is tied to the underlying
+//! by funding: at the end of every interval each position holder pays or
+//! receives its position valued at the mark price times the funding rate.
+//! The rate is the premium of the mark price over the index price, clamped to
+//! the configured maximum, so when the perpetual trades above the index longs
+//! pay shorts and when it trades below shorts pay longs.
+//!
+//! Index prices come from an [`IndexPriceSource`]; markets without one are
+//! skipped until it has a price. The mark price is the median of the best bid,
+//! best ask and index price, so a thin book cannot pull it away from the index.
+//! Payments are settled through the account service in the market's quote
+//! asset.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use account_service::AccountService;
+use chrono::{DateTime, Utc};
+use common::decimal::{dec, format_price, Price};
+use common::error::{Error, Result};
+use common::model::account::FundingPayment;
+use common::model::market::{Market, MarketKind};
+use matching_engine::MatchingEngine;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::pipeline::SettlementPipeline;
+use crate::AppState;
+
+/// Most funding rates kept in memory per market
+const HISTORY_CAPACITY: usize = 1000;
+
+/// Decimal places funding rates are rounded to
+const RATE_SCALE: u32 = 8;
+
+/// Funding settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundingConfig {
+    /// Time between funding settlements
+    pub interval: Duration,
+    /// Largest rate charged in either direction per interval, e.g. 0.0075 for 0.75%
+    pub max_rate: Decimal,
+}
+
+impl Default for FundingConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(8 * 60 * 60),
+            max_rate: dec!(0.0075),
+        }
+    }
+}
+
+/// Reference prices perpetual markets are funded towards
+pub trait IndexPriceSource: Send + Sync {
+    /// Current index price of a market, if known
+    fn index_price(&self, market: &str) -> Option<Price>;
+}
+
+/// Source without any index prices, leaving funding to be settled by hand
+pub struct NoIndexPrices;
+
+impl IndexPriceSource for NoIndexPrices {
+    fn index_price(&self, _market: &str) -> Option<Price> {
+        None
+    }
+}
+
+/// Mark price of a perpetual market
+///
+/// The median of the best bid, best ask and index price, or the index price
+/// while the book is one-sided. Without an index price it is the engine's mark
+/// price.
+pub fn mark_price(engine: &MatchingEngine, market: &str, index_price: Option<Price>) -> Option<Price> {
+    let Some(index_price) = index_price else {
+        return engine.mark_price(market).ok().flatten();
+    };
+    let (bids, asks) = engine.get_market_depth(market, 1).ok()?;
+    match (bids.first(), asks.first()) {
+        (Some(&(bid, _)), Some(&(ask, _))) => Some(index_price.clamp(bid, ask)),
+        _ => Some(index_price),
+    }
+}
+
+/// One funding settlement of a perpetual market
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FundingRate {
+    /// Market symbol
+    pub market: String,
+    /// Rate charged: positive when longs pay shorts
+    pub rate: Decimal,
+    /// Mark price positions were valued at
+    pub mark_price: Price,
+    /// Index price the rate was measured against
+    pub index_price: Price,
+    /// Funding time
+    pub settled_at: DateTime<Utc>,
+    /// Position holders who paid or received funding
+    pub accounts: usize,
+}
+
+/// Funding rate engine and history of the perpetual markets
+pub struct FundingEngine {
+    config: FundingConfig,
+    index: Arc<dyn IndexPriceSource>,
+    /// Settlements by market, newest first
+    history: RwLock<HashMap<String, VecDeque<FundingRate>>>,
+}
+
+impl FundingEngine {
+    /// Engine with the given settings and no index prices
+    pub fn new(config: FundingConfig) -> Self {
+        Self {
+            config,
+            index: Arc::new(NoIndexPrices),
+            history: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Fund markets towards the index prices of `index`
+    pub fn with_index_source(mut self, index: Arc<dyn IndexPriceSource>) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Engine settings
+    pub fn config(&self) -> &FundingConfig {
+        &self.config
+    }
+
+    /// Current index price of a market
+    pub fn index_price(&self, market: &str) -> Option<Price> {
+        self.index.index_price(market)
+    }
+
+    /// Funding rate of a mark price against an index price
+    pub fn rate(&self, mark_price: Price, index_price: Price) -> Decimal {
+        ((mark_price - index_price) / index_price)
+            .clamp(-self.config.max_rate, self.config.max_rate)
+            .round_dp_with_strategy(RATE_SCALE, RoundingStrategy::MidpointNearestEven)
+            .normalize()
+    }
+
+    /// A market's funding settlements, newest first
+    pub fn history(&self, market: &str, limit: usize) -> Vec<FundingRate> {
+        self.history.read().unwrap()
+            .get(market)
+            .map(|rates| rates.iter().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Settle a perpetual market's funding at the given prices
+    ///
+    /// Pending trade settlement is waited for first, so payments are charged
+    /// on current positions.
+    pub async fn settle(
+        &self,
+        account_service: &AccountService,
+        settlement: &SettlementPipeline,
+        market: &Market,
+        mark_price: Price,
+        index_price: Price,
+        now: DateTime<Utc>,
+    ) -> Result<(FundingRate, Vec<FundingPayment>)> {
+        if market.kind != MarketKind::Perpetual {
+            return Err(Error::ValidationError(format!("{} is not a perpetual market", market.symbol)));
+        }
+        if mark_price <= Price::ZERO || index_price <= Price::ZERO {
+            return Err(Error::ValidationError("Mark and index prices must be positive".to_string()));
+        }
+
+        settlement.flush_all().await;
+        let rate = self.rate(mark_price, index_price);
+        let payments = account_service
+            .settle_funding(&market.symbol, &market.quote_asset, rate, mark_price, now)
+            .await?;
+
+        let funding = FundingRate {
+            market: market.symbol.clone(),
+            rate,
+            mark_price,
+            index_price,
+            settled_at: now,
+            accounts: payments.len(),
+        };
+        let mut history = self.history.write().unwrap();
+        let rates = history.entry(market.symbol.clone()).or_default();
+        if rates.len() == HISTORY_CAPACITY {
+            rates.pop_back();
+        }
+        rates.push_front(funding.clone());
+
+        info!(
+            "Settled funding on {} at rate {} (mark {}, index {})",
+            market.symbol,
+            rate,
+            format_price(&market.symbol, mark_price),
+            format_price(&market.symbol, index_price),
+        );
+        Ok((funding, payments))
+    }
+
+    /// Settle funding on every perpetual market with a mark and index price
+    pub async fn settle_all(&self, state: &AppState, now: DateTime<Utc>) -> Vec<FundingRate> {
+        let mut settled = Vec::new();
+        for market in state.markets.iter().filter(|market| market.kind == MarketKind::Perpetual) {
+            let index_price = self.index_price(&market.symbol);
+            let (Some(mark_price), Some(index_price)) = (mark_price(&state.matching_engine, &market.symbol, index_price), index_price) else {
+                warn!("Skipping funding on {}: no mark or index price", market.symbol);
+                continue;
+            };
+            match self.settle(&state.account_service, &state.settlement, market, mark_price, index_price, now).await {
+                Ok((funding, _)) => settled.push(funding),
+                Err(e) => warn!("Failed to settle funding on {}: {}", market.symbol, e),
+            }
+        }
+        settled
+    }
+}
+
+/// Settle perpetual funding at the end of every interval
+pub fn spawn_funding_clock(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(state.funding.config().interval);
+        // The first tick completes immediately
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+            state.funding.settle_all(&state, state.matching_engine.clock().now()).await;
+        }
+    })
+}
@@ -0,0 +1,448 @@
+//! GraphQL API
+//!
+//! A single query surface over the same services as the REST API: markets,
+//! order books, tickers, trades and candles for anyone, and accounts and
+//! orders for the caller that owns them. Queries are sent to `/api/v1/graphql`
+//! (which serves GraphiQL on `GET`) and subscriptions to `/graphql/ws` over
+//! either the `graphql-transport-ws` or the legacy `graphql-ws` protocol.
+//!
+//! Callers identify themselves with the `X-API-Key` header, or on WebSocket
+//! connections with an `apiKey` field in the `connection_init` payload.
+//! Subscriptions are bridged from the market data channel, so they carry the
+//! same updates as the JSON-RPC WebSocket API.
+
+use std::any::Any;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_graphql::http::{GraphiQLSource, WebSocket, WebSocketProtocols, WsMessage, ALL_WEBSOCKET_PROTOCOLS};
+use async_graphql::{Context, Data, EmptyMutation, ErrorExtensions, Object, Schema, SimpleObject, Subscription};
+use axum::{
+    extract::{
+        ws::{CloseFrame, Message, WebSocket as AxumWebSocket},
+        ConnectInfo, State, WebSocketUpgrade,
+    },
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse, Response},
+    Extension, Json,
+};
+use common::model::account::{Account, Balance, Reservation};
+use common::model::market::Market;
+use common::model::order::Order;
+use common::model::trade::Trade;
+use futures::{future, SinkExt, Stream, StreamExt};
+use market_data::channel::{MarketDataChannel, Topic};
+use market_data::{BestBidOffer, Candle, CandleFill, CandleInterval, CandleUpdate, OrderBookUpdate, PriceLevel, Ticker, TradeMessage};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::auth::{AuthContext, Scope, API_KEY_HEADER};
+use crate::error::ApiError;
+use crate::AppState;
+
+/// Deepest query nesting accepted
+const MAX_DEPTH: usize = 8;
+
+/// Highest query complexity accepted, counting one per selected field
+const MAX_COMPLEXITY: usize = 500;
+
+/// Updates buffered per subscription before the bridge waits for the client
+const SUBSCRIPTION_BUFFER: usize = 100;
+
+/// Schema served by the GraphQL endpoints
+pub type TradingSchema = Schema<Query, EmptyMutation, Subscription>;
+
+/// Build the schema over the gateway's services
+pub fn schema(state: Arc<AppState>) -> TradingSchema {
+    Schema::build(Query, EmptyMutation, Subscription)
+        .data(state)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// GraphQL error carrying the REST error code in its `code` extension
+fn graphql_error(error: ApiError) -> async_graphql::Error {
+    let code = error.code();
+    async_graphql::Error::new(error.to_string()).extend_with(|_, extensions| extensions.set("code", code))
+}
+
+fn app_state<'a>(ctx: &Context<'a>) -> &'a Arc<AppState> {
+    ctx.data_unchecked::<Arc<AppState>>()
+}
+
+/// Identity of the caller, required by account and order fields
+fn caller(ctx: &Context<'_>) -> async_graphql::Result<AuthContext> {
+    ctx.data_opt::<AuthContext>()
+        .cloned()
+        .ok_or_else(|| graphql_error(ApiError::Unauthorized(format!("Missing {} header", API_KEY_HEADER))))
+}
+
+/// Resolve an API key, if one was sent; GraphQL only reads, so the key needs the `read` scope
+async fn authenticate(state: &AppState, key: Option<&str>, client: Option<IpAddr>) -> Result<Option<AuthContext>, ApiError> {
+    let Some(key) = key else {
+        return Ok(None);
+    };
+    let api_key = state.api_keys.authorize(key, Scope::Read, client)?;
+    state.security.touch(&api_key, client);
+    AuthContext::for_key(&state.account_service, api_key).await.map(Some)
+}
+
+/// Order book of a market at one point in time
+#[derive(SimpleObject)]
+pub struct OrderBook {
+    /// Market symbol
+    pub market: String,
+    /// Bids, best first
+    pub bids: Vec<PriceLevel>,
+    /// Asks, best first
+    pub asks: Vec<PriceLevel>,
+}
+
+/// An account and the resources it owns
+pub struct AccountNode(Account);
+
+#[Object(name = "Account")]
+impl AccountNode {
+    /// Unique account ID
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    /// Reference of the account in an external system
+    async fn external_id(&self) -> Option<&str> {
+        self.0.external_id.as_deref()
+    }
+
+    /// Account creation timestamp
+    async fn created_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.0.created_at
+    }
+
+    /// Last update timestamp
+    async fn updated_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.0.updated_at
+    }
+
+    /// Balances in every asset the account holds
+    async fn balances(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Balance>> {
+        app_state(ctx).settlement.flush(self.0.id).await;
+        app_state(ctx)
+            .account_service
+            .get_balances(self.0.id)
+            .await
+            .map_err(|e| graphql_error(ApiError::Common(e)))
+    }
+
+    /// Funds reserved for open orders, oldest first
+    async fn reservations(&self, ctx: &Context<'_>) -> Vec<Reservation> {
+        app_state(ctx).settlement.flush(self.0.id).await;
+        app_state(ctx).account_service.get_reservations(self.0.id)
+    }
+
+    /// Settled trades, newest first
+    async fn trades(&self, ctx: &Context<'_>, #[graphql(default = 100)] limit: usize) -> Vec<Trade> {
+        app_state(ctx).settlement.flush(self.0.id).await;
+        app_state(ctx).account_service.get_trades(self.0.id, limit)
+    }
+
+    /// Orders resting on any book
+    async fn open_orders(&self, ctx: &Context<'_>) -> Vec<Order> {
+        app_state(ctx)
+            .matching_engine
+            .get_open_orders(self.0.id)
+            .iter()
+            .map(|order| order.as_ref().clone())
+            .collect()
+    }
+}
+
+/// Root query
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Available markets
+    async fn markets(&self, ctx: &Context<'_>) -> Vec<Market> {
+        app_state(ctx).markets.clone()
+    }
+
+    /// Market with the given symbol
+    async fn market(&self, ctx: &Context<'_>, symbol: String) -> Option<Market> {
+        app_state(ctx).markets.iter().find(|market| market.symbol == symbol).cloned()
+    }
+
+    /// Aggregated price levels of a market's book
+    async fn order_book(
+        &self,
+        ctx: &Context<'_>,
+        market: String,
+        #[graphql(default = 10)] depth: usize,
+    ) -> async_graphql::Result<OrderBook> {
+        let (bids, asks) = app_state(ctx)
+            .matching_engine
+            .get_market_depth(&market, depth)
+            .map_err(|e| graphql_error(ApiError::Common(e)))?;
+
+        let levels = |levels: Vec<(_, _)>| {
+            levels.into_iter().map(|(price, quantity)| PriceLevel { price, quantity }).collect()
+        };
+        Ok(OrderBook { market, bids: levels(bids), asks: levels(asks) })
+    }
+
+    /// 24 hour ticker of a market
+    async fn ticker(&self, ctx: &Context<'_>, market: String) -> Option<Ticker> {
+        app_state(ctx).market_data_service.get_ticker(&market)
+    }
+
+    /// Tickers of every market
+    async fn tickers(&self, ctx: &Context<'_>) -> Vec<Ticker> {
+        app_state(ctx).market_data_service.get_all_tickers()
+    }
+
+    /// Recent public trades of a market, newest first
+    async fn trades(&self, ctx: &Context<'_>, market: String, #[graphql(default = 100)] limit: usize) -> Vec<TradeMessage> {
+        app_state(ctx).market_data_service.get_recent_trades(&market, limit)
+    }
+
+    /// Candles of a market, newest first, with periods without trades filled in if `fill` is set
+    async fn candles(
+        &self,
+        ctx: &Context<'_>,
+        market: String,
+        #[graphql(default_with = "CandleInterval::Minute1")] interval: CandleInterval,
+        #[graphql(default = 100)] limit: usize,
+        fill: Option<CandleFill>,
+    ) -> async_graphql::Result<Vec<Candle>> {
+        let market_data = &app_state(ctx).market_data_service;
+        match fill {
+            Some(fill) => Ok(market_data.get_filled_candles(&market, interval, limit, fill)),
+            None => market_data.get_candle_history(&market, interval, limit).await.map_err(|e| graphql_error(ApiError::Common(e))),
+        }
+    }
+
+    /// The caller's account
+    async fn account(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<AccountNode> {
+        caller(ctx)?.ensure_account(id).map_err(graphql_error)?;
+
+        let account = app_state(ctx)
+            .account_service
+            .get_account(id)
+            .await
+            .map_err(|e| graphql_error(ApiError::Common(e)))?
+            .ok_or_else(|| graphql_error(ApiError::NotFound(format!("Account not found: {}", id))))?;
+
+        Ok(AccountNode(account))
+    }
+
+    /// One of the caller's resting orders
+    async fn order(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Option<Order>> {
+        let auth = caller(ctx)?;
+
+        let Some(order) = app_state(ctx).matching_engine.get_order(id) else {
+            return Ok(None);
+        };
+        auth.ensure_account(order.user_id).map_err(graphql_error)?;
+
+        Ok(Some(order.as_ref().clone()))
+    }
+}
+
+/// Root subscription
+pub struct Subscription;
+
+#[Subscription]
+impl Subscription {
+    /// Changed price levels of a market's book
+    async fn order_book(&self, ctx: &Context<'_>, market: String) -> impl Stream<Item = OrderBookUpdate> {
+        bridge(ctx, Topic::OrderBook(market)).await
+    }
+
+    /// Best bid and offer of a market, whenever it changes
+    async fn bbo(&self, ctx: &Context<'_>, market: String) -> impl Stream<Item = BestBidOffer> {
+        bridge(ctx, Topic::Bbo(market)).await
+    }
+
+    /// Public trades of a market
+    async fn trades(&self, ctx: &Context<'_>, market: String) -> impl Stream<Item = TradeMessage> {
+        bridge(ctx, Topic::Trades(market)).await
+    }
+
+    /// 24 hour ticker of a market
+    async fn ticker(&self, ctx: &Context<'_>, market: String) -> impl Stream<Item = Ticker> {
+        bridge(ctx, Topic::Ticker(market)).await
+    }
+
+    /// Working and closed candles of a market at one interval
+    async fn candles(
+        &self,
+        ctx: &Context<'_>,
+        market: String,
+        #[graphql(default_with = "CandleInterval::Minute1")] interval: CandleInterval,
+    ) -> impl Stream<Item = CandleUpdate> {
+        bridge(ctx, Topic::Candles(market, interval)).await
+    }
+}
+
+/// Removes a bridged subscription from the channel when its stream is dropped
+struct Unsubscribe {
+    channel: Arc<MarketDataChannel>,
+    id: Uuid,
+}
+
+impl Drop for Unsubscribe {
+    fn drop(&mut self) {
+        let channel = self.channel.clone();
+        let id = self.id;
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                channel.unsubscribe_by_id(id).await;
+            });
+        }
+    }
+}
+
+/// Stream the messages of type `T` published on a market data topic
+async fn bridge<T: Clone + Send + Sync + 'static>(ctx: &Context<'_>, topic: Topic) -> impl Stream<Item = T> {
+    let channel = app_state(ctx).market_data_service.channel();
+    let id = Uuid::new_v4();
+    let receiver = channel.subscribe_with_id::<T>(topic, id).await;
+    let (tx, rx) = mpsc::channel(SUBSCRIPTION_BUFFER);
+
+    // The channel receiver blocks until a message arrives, so forward from a
+    // blocking thread. It ends once the subscription is removed
+    tokio::task::spawn_blocking(move || {
+        while let Ok(message) = receiver.recv() {
+            let message: &(dyn Any + Send + Sync) = message.as_ref();
+            if let Some(message) = message.downcast_ref::<T>() {
+                if tx.blocking_send(message.clone()).is_err() {
+                    break;
+                }
+            }
+        }
+        debug!("GraphQL subscription {} exited", id);
+    });
+
+    let unsubscribe = Unsubscribe { channel, id };
+    ReceiverStream::new(rx).map(move |message| {
+        // Owned by the stream, so dropping the stream unsubscribes
+        let _ = &unsubscribe;
+        message
+    })
+}
+
+/// Execute a GraphQL query or introspection request
+pub async fn graphql_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(schema): Extension<TradingSchema>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(request): Json<async_graphql::Request>,
+) -> Response {
+    let key = headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok());
+    let client = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    let request = match authenticate(&state, key, client).await {
+        Ok(Some(auth)) => request.data(auth),
+        Ok(None) => request,
+        Err(e) => return e.into_response(),
+    };
+
+    Json(schema.execute(request).await).into_response()
+}
+
+/// Serve GraphiQL, pointed at the query and subscription endpoints
+pub async fn graphiql() -> Html<String> {
+    Html(
+        GraphiQLSource::build()
+            .endpoint("/api/v1/graphql")
+            .subscription_endpoint("/graphql/ws")
+            .finish(),
+    )
+}
+
+/// Upgrade to a GraphQL subscription connection
+pub async fn graphql_ws_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(schema): Extension<TradingSchema>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let Some(protocol) = headers
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').find_map(|protocol| WebSocketProtocols::from_str(protocol.trim()).ok()))
+    else {
+        return ApiError::BadRequest(format!(
+            "Expected one of the WebSocket subprotocols: {}",
+            ALL_WEBSOCKET_PROTOCOLS.join(", ")
+        ))
+        .into_response();
+    };
+
+    let key = headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok());
+    let client = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    let auth = match authenticate(&state, key, client).await {
+        Ok(auth) => auth,
+        Err(e) => return e.into_response(),
+    };
+
+    ws.protocols(ALL_WEBSOCKET_PROTOCOLS)
+        .max_message_size(state.limits.ws_max_message_bytes)
+        .max_frame_size(state.limits.ws_max_frame_bytes)
+        .on_upgrade(move |socket| serve_graphql_ws(socket, state, schema, protocol, auth, client))
+}
+
+/// Run the GraphQL WebSocket protocol until either side closes
+async fn serve_graphql_ws(
+    socket: AxumWebSocket,
+    state: Arc<AppState>,
+    schema: TradingSchema,
+    protocol: WebSocketProtocols,
+    auth: Option<AuthContext>,
+    client: Option<IpAddr>,
+) {
+    let (mut sink, stream) = socket.split();
+    let input = stream
+        .take_while(|message| future::ready(message.is_ok()))
+        .filter_map(|message| {
+            future::ready(match message {
+                Ok(Message::Text(text)) => Some(text.into_bytes()),
+                Ok(Message::Binary(bytes)) => Some(bytes),
+                _ => None,
+            })
+        });
+
+    let mut connection_data = Data::default();
+    if let Some(auth) = auth {
+        connection_data.insert(auth);
+    }
+
+    // Browsers cannot set headers on WebSocket requests, so also accept the
+    // key in the `connection_init` payload
+    let init_state = state.clone();
+    let output = WebSocket::new(schema, input, protocol)
+        .connection_data(connection_data)
+        .on_connection_init(move |payload| async move {
+            let mut data = Data::default();
+            let key = payload.get("apiKey").and_then(|key| key.as_str());
+            if let Some(auth) = authenticate(&init_state, key, client).await.map_err(|e| async_graphql::Error::new(e.to_string()))? {
+                data.insert(auth);
+            }
+            Ok(data)
+        });
+    let mut output = std::pin::pin!(output);
+
+    while let Some(message) = output.next().await {
+        let message = match message {
+            WsMessage::Text(text) => Message::Text(state.number_format.apply_to_text(text)),
+            WsMessage::Close(code, reason) => Message::Close(Some(CloseFrame { code, reason: reason.into() })),
+        };
+        if sink.send(message).await.is_err() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,463 @@
+//! Health checks
+//!
+//! `/health` reports the result of every registered [`Probe`]. The defaults
+//! check the matching engine, account repository, market data, and the
+//! channel carrying updates to clients; the report journal and a database
+//! pool are probed when configured. Probes run concurrently, each within a
+//! timeout, and results are cached so frequent polling does not load the
+//! services. A background task refreshes them as the cache expires, so
+//! components going down or recovering reach the `system` channel without
+//! anyone polling.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use account_service::AccountService;
+use async_trait::async_trait;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use common::error::{Error, Result};
+use futures::future::join_all;
+use market_data::channel::MarketDataChannel;
+use market_data::MarketDataService;
+use matching_engine::MatchingEngine;
+use serde::Serialize;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::pipeline::SettlementPipeline;
+use crate::report::ReportGenerator;
+use crate::system::{ComponentStatus, SystemStatus};
+use crate::AppState;
+
+/// Probe caching and timeout settings
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// How long results are reused before probes run again
+    pub ttl: Duration,
+    /// Time allowed for each probe before its component counts as down
+    pub timeout: Duration,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(5),
+            timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+/// A check of one dependency
+#[async_trait]
+pub trait Probe: Send + Sync {
+    /// Component name reported by `/health`
+    fn name(&self) -> &str;
+
+    /// Check the component, failing with the reason it is down
+    async fn check(&self) -> Result<()>;
+}
+
+/// Result of one probe
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeResult {
+    /// Whether the component responded
+    pub status: ComponentStatus,
+    /// Time the probe took
+    pub latency_ms: u64,
+    /// Why the component is down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Results of all probes from one run
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    /// Result per component
+    pub services: BTreeMap<String, ProbeResult>,
+    /// When the probes ran
+    pub checked_at: DateTime<Utc>,
+}
+
+impl HealthReport {
+    /// Whether every component is up
+    pub fn is_healthy(&self) -> bool {
+        self.services.values().all(|result| result.status == ComponentStatus::Up)
+    }
+}
+
+/// Registered probes and their cached results
+pub struct HealthChecker {
+    config: HealthConfig,
+    system: Arc<SystemStatus>,
+    probes: RwLock<Vec<Arc<dyn Probe>>>,
+    /// Last report and when it was taken, locked while probes run so
+    /// concurrent requests share one run
+    cache: Mutex<Option<(Instant, HealthReport)>>,
+    started_at: Instant,
+}
+
+impl HealthChecker {
+    /// Create a checker with the given probes, reporting status changes to `system`
+    pub fn new(config: HealthConfig, system: Arc<SystemStatus>, probes: Vec<Arc<dyn Probe>>) -> Self {
+        Self {
+            config,
+            system,
+            probes: RwLock::new(probes),
+            cache: Mutex::new(None),
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Caching and timeout settings
+    pub fn config(&self) -> &HealthConfig {
+        &self.config
+    }
+
+    /// Add a probe, replacing any registered under the same name
+    pub fn register(&self, probe: Arc<dyn Probe>) {
+        let mut probes = self.probes.write().unwrap();
+        probes.retain(|registered| registered.name() != probe.name());
+        probes.push(probe);
+    }
+
+    /// Registered probes
+    pub fn probes(&self) -> Vec<Arc<dyn Probe>> {
+        self.probes.read().unwrap().clone()
+    }
+
+    /// Probe a PostgreSQL database, connecting on first use
+    pub fn register_database(&self, database_url: &str) -> Result<()> {
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .acquire_timeout(self.config.timeout)
+            .connect_lazy(database_url)?;
+        self.register(Arc::new(DatabaseProbe::new(pool)));
+        Ok(())
+    }
+
+    /// Time since the checker was created
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// Latest results, probing again if the cached ones are older than the TTL
+    pub async fn report(&self) -> HealthReport {
+        let mut cache = self.cache.lock().await;
+        if let Some((taken, report)) = cache.as_ref() {
+            if taken.elapsed() < self.config.ttl {
+                return report.clone();
+            }
+        }
+
+        let report = self.run().await;
+        *cache = Some((Instant::now(), report.clone()));
+        report
+    }
+
+    /// Probe every component now, replacing the cached results
+    pub async fn refresh(&self) -> HealthReport {
+        let mut cache = self.cache.lock().await;
+        let report = self.run().await;
+        *cache = Some((Instant::now(), report.clone()));
+        report
+    }
+
+    async fn run(&self) -> HealthReport {
+        let probes = self.probes();
+        let results = join_all(probes.iter().map(|probe| async move {
+            let start = Instant::now();
+            let outcome = match tokio::time::timeout(self.config.timeout, probe.check()).await {
+                Ok(outcome) => outcome,
+                Err(_) => Err(Error::Internal(format!("No response within {:?}", self.config.timeout))),
+            };
+            let result = ProbeResult {
+                status: if outcome.is_ok() { ComponentStatus::Up } else { ComponentStatus::Down },
+                latency_ms: start.elapsed().as_millis() as u64,
+                error: outcome.err().map(|e| e.to_string()),
+            };
+            (probe.name().to_string(), result)
+        })).await;
+
+        for (component, result) in &results {
+            self.system.report_component(component, result.status == ComponentStatus::Up).await;
+        }
+
+        HealthReport {
+            services: results.into_iter().collect(),
+            checked_at: Utc::now(),
+        }
+    }
+}
+
+/// Probes of the services every gateway runs
+pub fn default_probes(
+    engine: Arc<MatchingEngine>,
+    accounts: Arc<AccountService>,
+    market_data: Arc<MarketDataService>,
+    markets: Vec<String>,
+) -> Vec<Arc<dyn Probe>> {
+    vec![
+        Arc::new(EngineProbe::new(engine, markets.clone())),
+        Arc::new(AccountRepositoryProbe::new(accounts)),
+        Arc::new(MessageBusProbe::new(market_data.channel())),
+        Arc::new(MarketDataProbe::new(market_data, markets)),
+    ]
+}
+
+/// Refresh health results every TTL
+pub fn spawn_health_checks(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(state.health.config().ttl);
+        loop {
+            ticks.tick().await;
+            state.health.refresh().await;
+        }
+    })
+}
+
+/// Reads the depth of every market from the matching engine
+pub struct EngineProbe {
+    engine: Arc<MatchingEngine>,
+    markets: Vec<String>,
+}
+
+impl EngineProbe {
+    /// Probe the engine's order books of the given markets
+    pub fn new(engine: Arc<MatchingEngine>, markets: Vec<String>) -> Self {
+        Self { engine, markets }
+    }
+}
+
+#[async_trait]
+impl Probe for EngineProbe {
+    fn name(&self) -> &str {
+        "matching_engine"
+    }
+
+    async fn check(&self) -> Result<()> {
+        for market in &self.markets {
+            self.engine.get_market_depth(market, 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Looks up an account in the account repository
+pub struct AccountRepositoryProbe {
+    accounts: Arc<AccountService>,
+}
+
+impl AccountRepositoryProbe {
+    /// Probe the service's repository
+    pub fn new(accounts: Arc<AccountService>) -> Self {
+        Self { accounts }
+    }
+}
+
+#[async_trait]
+impl Probe for AccountRepositoryProbe {
+    fn name(&self) -> &str {
+        "account_service"
+    }
+
+    async fn check(&self) -> Result<()> {
+        // Any answer means the repository works, even not found for the nil ID
+        match self.accounts.get_account(Uuid::nil()).await {
+            Ok(_) | Err(Error::AccountNotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Reads order book history from the market data repository
+pub struct MarketDataProbe {
+    market_data: Arc<MarketDataService>,
+    markets: Vec<String>,
+}
+
+impl MarketDataProbe {
+    /// Probe market data through the first of the given markets
+    pub fn new(market_data: Arc<MarketDataService>, markets: Vec<String>) -> Self {
+        Self { market_data, markets }
+    }
+}
+
+#[async_trait]
+impl Probe for MarketDataProbe {
+    fn name(&self) -> &str {
+        "market_data_service"
+    }
+
+    async fn check(&self) -> Result<()> {
+        if let Some(market) = self.markets.first() {
+            self.market_data.get_order_book_at(market, Utc::now()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Takes the lock of the channel that carries updates to clients
+pub struct MessageBusProbe {
+    channel: Arc<MarketDataChannel>,
+}
+
+impl MessageBusProbe {
+    /// Probe the given channel
+    pub fn new(channel: Arc<MarketDataChannel>) -> Self {
+        Self { channel }
+    }
+}
+
+#[async_trait]
+impl Probe for MessageBusProbe {
+    fn name(&self) -> &str {
+        "message_bus"
+    }
+
+    async fn check(&self) -> Result<()> {
+        // Blocks past the timeout if publishing is stuck
+        self.channel.subscription_count().await;
+        Ok(())
+    }
+}
+
+/// Checks the report journal still records the engine's events
+pub struct EventStoreProbe {
+    reports: Arc<ReportGenerator>,
+}
+
+impl EventStoreProbe {
+    /// Probe the journal of the given report generator
+    pub fn new(reports: Arc<ReportGenerator>) -> Self {
+        Self { reports }
+    }
+}
+
+#[async_trait]
+impl Probe for EventStoreProbe {
+    fn name(&self) -> &str {
+        "event_store"
+    }
+
+    async fn check(&self) -> Result<()> {
+        if self.reports.is_journaling() {
+            Ok(())
+        } else {
+            Err(Error::Internal("Report journal stopped recording events".to_string()))
+        }
+    }
+}
+
+/// Checks no trade settlement is failing
+pub struct SettlementProbe {
+    settlement: Arc<SettlementPipeline>,
+}
+
+impl SettlementProbe {
+    /// Probe the settlements of the given pipeline
+    pub fn new(settlement: Arc<SettlementPipeline>) -> Self {
+        Self { settlement }
+    }
+}
+
+#[async_trait]
+impl Probe for SettlementProbe {
+    fn name(&self) -> &str {
+        "settlement"
+    }
+
+    async fn check(&self) -> Result<()> {
+        let failing = self.settlement.failing();
+        match failing.first() {
+            None => Ok(()),
+            Some((id, error)) => Err(Error::Internal(format!(
+                "{} settlements failing, {} with: {}",
+                failing.len(),
+                id,
+                error
+            ))),
+        }
+    }
+}
+
+/// Runs a trivial query on a database pool
+pub struct DatabaseProbe {
+    pool: PgPool,
+}
+
+impl DatabaseProbe {
+    /// Probe the given pool
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Probe for DatabaseProbe {
+    fn name(&self) -> &str {
+        "database"
+    }
+
+    async fn check(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+}
+
+/// Service health, answering 503 while any component is down
+#[utoipa::path(
+    get,
+    path = "/api/v1/health",
+    responses(
+        (status = 200, description = "All components are up"),
+        (status = 503, description = "At least one component is down")
+    ),
+    tag = "system"
+)]
+pub async fn health_check(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let start = Instant::now();
+    let report = state.health.report().await;
+
+    let active_markets = state.markets.iter().filter(|market| market.trading_enabled).count();
+    let health_info = serde_json::json!({
+        "status": if report.is_healthy() { "healthy" } else { "degraded" },
+        "version": env!("CARGO_PKG_VERSION"),
+        "timestamp": Utc::now().to_rfc3339(),
+        "uptime_seconds": state.health.uptime().as_secs(),
+        "services": report.services,
+        "checked_at": report.checked_at,
+        "markets": {
+            "total": state.markets.len(),
+            "active": active_markets
+        },
+        "system": {
+            "memory_usage_mb": memory_usage_mb(),
+        },
+        "health_check_latency_ms": start.elapsed().as_millis() as u64
+    });
+
+    let status = if report.is_healthy() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(health_info))
+}
+
+/// Resident memory of the process, or 0 where it cannot be read
+fn memory_usage_mb() -> u64 {
+    #[cfg(target_os = "linux")]
+    {
+        if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
+            let resident_kb = status.lines()
+                .find(|line| line.starts_with("VmRSS:"))
+                .and_then(|line| line.split_whitespace().nth(1))
+                .and_then(|kb| kb.parse::<u64>().ok());
+            if let Some(kb) = resident_kb {
+                return kb / 1024;
+            }
+        }
+    }
+
+    0
+}
@@ -0,0 +1,241 @@
+//! Market maker rebates
+//!
+//! Pays accounts a share of the quote value they filled as makers, provided
+//! they kept quoting at the top of the book and, optionally, kept their own
+//! spread tight. Each period, which defaults to a day, the quote tracker's
+//! figures are closed off, rebates are worked out per account and market and
+//! credited in the market's quote asset.
+//!
+//! Credits go through the account service's deposit path under the
+//! `maker-rebates` source with a reference per period, account and market, so
+//! a period is never paid twice.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use account_service::settlement::DepositConfirmation;
+use account_service::AccountService;
+use chrono::{DateTime, Utc};
+use common::decimal::Amount;
+use common::model::incentive::MakerActivity;
+use common::model::market::Market;
+use matching_engine::incentives::QuoteTracker;
+use matching_engine::MatchingEngine;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tracing::{info, warn};
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::AppState;
+
+/// Source rebates are credited under
+pub const REBATE_SOURCE: &str = "maker-rebates";
+
+/// Most settled periods kept in memory
+const PERIOD_CAPACITY: usize = 365;
+
+/// Rebate program settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IncentiveConfig {
+    /// Length of a rebate period
+    pub period: Duration,
+    /// Share of maker quote volume paid back, e.g. 0.0001 for 1 bp; nothing is paid when zero
+    pub rebate_rate: Decimal,
+    /// Share of the period an account must spend at the top of the book, averaged over both sides
+    pub min_presence: f64,
+    /// Widest average spread of an account's own quotes that still qualifies, in basis points
+    pub max_spread_bps: Option<f64>,
+}
+
+impl Default for IncentiveConfig {
+    fn default() -> Self {
+        Self {
+            period: Duration::from_secs(24 * 60 * 60),
+            rebate_rate: Decimal::ZERO,
+            min_presence: 0.0,
+            max_spread_bps: None,
+        }
+    }
+}
+
+/// Figures of the current, still open period
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct IncentiveReport {
+    /// Start of the period
+    pub period_start: DateTime<Utc>,
+    /// Time the figures run up to
+    pub as_of: DateTime<Utc>,
+    /// Share of maker quote volume paid back
+    pub rebate_rate: Decimal,
+    /// Per-account figures by market
+    pub activity: Vec<MakerActivity>,
+}
+
+/// Rebate of one account on one market for a period
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Rebate {
+    /// Market symbol
+    pub market: String,
+    /// Account ID
+    pub account_id: Uuid,
+    /// Asset the rebate is paid in
+    pub asset: String,
+    /// Quote value filled as maker
+    pub maker_quote_volume: Amount,
+    /// Share of the period spent at the top of the book
+    pub presence: f64,
+    /// Average spread of the account's own quotes, in basis points
+    pub average_spread_bps: Option<f64>,
+    /// Whether the account met the quoting requirements
+    pub eligible: bool,
+    /// Rebate earned
+    pub amount: Amount,
+    /// Whether the rebate was credited to the account
+    pub credited: bool,
+}
+
+/// Rebates of a settled period
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RebatePeriod {
+    /// Start of the period
+    pub start: DateTime<Utc>,
+    /// End of the period
+    pub end: DateTime<Utc>,
+    /// Share of maker quote volume paid back
+    pub rebate_rate: Decimal,
+    /// Rebates by market, then by descending maker volume
+    pub rebates: Vec<Rebate>,
+}
+
+/// Maker rebate program over the engine's quote tracker
+pub struct IncentiveProgram {
+    tracker: Arc<QuoteTracker>,
+    config: IncentiveConfig,
+    /// Settled periods, newest first
+    periods: RwLock<VecDeque<RebatePeriod>>,
+}
+
+impl IncentiveProgram {
+    /// Start tracking a matching engine's quotes
+    pub fn start(engine: &MatchingEngine, config: IncentiveConfig) -> Self {
+        Self::new(QuoteTracker::start(engine), config)
+    }
+
+    /// Program over an existing tracker
+    pub fn new(tracker: Arc<QuoteTracker>, config: IncentiveConfig) -> Self {
+        Self {
+            tracker,
+            config,
+            periods: RwLock::new(VecDeque::new()),
+        }
+    }
+
+    /// Program settings
+    pub fn config(&self) -> &IncentiveConfig {
+        &self.config
+    }
+
+    /// Figures of the current period up to `now`
+    pub fn current(&self, now: DateTime<Utc>) -> IncentiveReport {
+        IncentiveReport {
+            period_start: self.tracker.period_start(),
+            as_of: now,
+            rebate_rate: self.config.rebate_rate,
+            activity: self.tracker.activity(now),
+        }
+    }
+
+    /// Settled periods, newest first
+    pub fn periods(&self, limit: usize) -> Vec<RebatePeriod> {
+        self.periods.read().unwrap().iter().take(limit).cloned().collect()
+    }
+
+    /// Close the current period at `now` and credit its rebates
+    ///
+    /// A rebate that fails to credit is logged and reported as not credited.
+    pub async fn settle(&self, account_service: &AccountService, markets: &[Market], now: DateTime<Utc>) -> RebatePeriod {
+        let (start, activity) = self.tracker.close_period(now);
+        let seconds = (now - start).num_milliseconds().max(0) as f64 / 1000.0;
+
+        let mut rebates = Vec::with_capacity(activity.len());
+        for activity in activity {
+            let Some(market) = markets.iter().find(|market| market.symbol == activity.market) else {
+                continue;
+            };
+            let mut rebate = self.rebate(&activity, market, seconds);
+
+            if rebate.amount > Amount::ZERO {
+                let confirmation = DepositConfirmation {
+                    reference: format!("{}:{}:{}", start.timestamp_millis(), rebate.market, rebate.account_id),
+                    account_id: rebate.account_id,
+                    asset: rebate.asset.clone(),
+                    amount: rebate.amount,
+                };
+                match account_service.credit_deposit(REBATE_SOURCE, &confirmation).await {
+                    Ok(credited) => rebate.credited = credited.is_some(),
+                    Err(e) => warn!("Failed to credit rebate {} to {}: {}", confirmation.reference, rebate.account_id, e),
+                }
+            }
+            rebates.push(rebate);
+        }
+
+        let period = RebatePeriod {
+            start,
+            end: now,
+            rebate_rate: self.config.rebate_rate,
+            rebates,
+        };
+        info!(
+            "Settled maker rebates from {} to {}: {} credited",
+            start, now, period.rebates.iter().filter(|rebate| rebate.credited).count()
+        );
+
+        let mut periods = self.periods.write().unwrap();
+        if periods.len() == PERIOD_CAPACITY {
+            periods.pop_back();
+        }
+        periods.push_front(period.clone());
+        period
+    }
+
+    /// Work out one account's rebate on one market
+    fn rebate(&self, activity: &MakerActivity, market: &Market, seconds: f64) -> Rebate {
+        let presence = activity.presence(seconds);
+        let tight = match self.config.max_spread_bps {
+            Some(max) => activity.average_spread_bps.is_some_and(|spread| spread <= max),
+            None => true,
+        };
+        let eligible = presence >= self.config.min_presence && tight;
+
+        Rebate {
+            market: activity.market.clone(),
+            account_id: activity.account_id,
+            asset: market.quote_asset.clone(),
+            maker_quote_volume: activity.maker_quote_volume,
+            presence,
+            average_spread_bps: activity.average_spread_bps,
+            eligible,
+            amount: if eligible {
+                (activity.maker_quote_volume * self.config.rebate_rate).normalize()
+            } else {
+                Amount::ZERO
+            },
+            credited: false,
+        }
+    }
+}
+
+/// Settle maker rebates at the end of every period
+pub fn spawn_rebate_clock(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(state.incentives.config().period);
+        // The first tick completes immediately
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+            state.incentives.settle(&state.account_service, &state.markets, state.matching_engine.clock().now()).await;
+        }
+    })
+}
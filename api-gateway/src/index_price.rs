@@ -0,0 +1,330 @@
+//! Index prices
+//!
+//! External reference prices are polled from a set of [`PriceFetcher`]s, each
+//! reporting the assets it knows priced in the index quote asset. The index of
+//! an asset is the median of its fresh quotes: quotes older than the configured
+//! maximum age are stale and left out, and an asset with fewer fresh quotes
+//! than required has no index until its sources recover.
+//!
+//! Market indices are derived from the asset indices, so `BTC/USD` with a
+//! `USD` index quote is the index of `BTC`, and `ETH/BTC` is the index of
+//! `ETH` divided by that of `BTC`. They feed perpetual mark prices and
+//! funding through [`IndexPriceSource`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::decimal::Price;
+use common::error::{Error, Result};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::funding::IndexPriceSource;
+use crate::AppState;
+
+/// Index price settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexPriceConfig {
+    /// Time between polls of the sources
+    pub poll_interval: Duration,
+    /// Age after which a source's quote is stale
+    pub max_age: Duration,
+    /// Fresh quotes an asset needs to have an index
+    pub min_sources: usize,
+    /// Asset the sources quote prices in
+    pub quote_asset: String,
+    /// Sources as `(name, location)`, where the location is an `http(s)://` URL or a file path
+    pub sources: Vec<(String, String)>,
+}
+
+impl Default for IndexPriceConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(10),
+            max_age: Duration::from_secs(60),
+            min_sources: 1,
+            quote_asset: "USD".to_string(),
+            sources: Vec::new(),
+        }
+    }
+}
+
+impl IndexPriceConfig {
+    /// Fetchers for the configured sources
+    pub fn fetchers(&self) -> Vec<Arc<dyn PriceFetcher>> {
+        self.sources
+            .iter()
+            .map(|(name, location)| -> Arc<dyn PriceFetcher> {
+                if location.starts_with("http://") || location.starts_with("https://") {
+                    Arc::new(HttpPriceFetcher::new(name, location, self.poll_interval))
+                } else {
+                    Arc::new(FilePriceFetcher::new(name, location))
+                }
+            })
+            .collect()
+    }
+}
+
+/// External source of reference prices
+#[async_trait]
+pub trait PriceFetcher: Send + Sync {
+    /// Source name shown with its quotes
+    fn name(&self) -> &str;
+
+    /// Current prices by asset, in the index quote asset
+    async fn fetch(&self) -> Result<HashMap<String, Price>>;
+}
+
+/// Parse a JSON object of prices by asset, e.g. `{"BTC": "65000.5", "ETH": 3100}`
+fn parse_prices(source: &str, body: &[u8]) -> Result<HashMap<String, Price>> {
+    let prices: HashMap<String, Decimal> = serde_json::from_slice(body)
+        .map_err(|e| Error::Internal(format!("Invalid prices from {}: {}", source, e)))?;
+    Ok(prices
+        .into_iter()
+        .filter(|(_, price)| *price > Decimal::ZERO)
+        .map(|(asset, price)| (asset.to_uppercase(), price))
+        .collect())
+}
+
+/// Polls a URL answering with a JSON object of prices by asset
+pub struct HttpPriceFetcher {
+    name: String,
+    url: String,
+    client: reqwest::Client,
+    timeout: Duration,
+}
+
+impl HttpPriceFetcher {
+    /// Fetch from `url`, allowing `timeout` per request
+    pub fn new(name: &str, url: &str, timeout: Duration) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            client: reqwest::Client::new(),
+            timeout,
+        }
+    }
+}
+
+#[async_trait]
+impl PriceFetcher for HttpPriceFetcher {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, Price>> {
+        let response = self.client
+            .get(&self.url)
+            .timeout(self.timeout)
+            .send()
+            .await
+            .map_err(|e| Error::Internal(format!("Price request to {} failed: {}", self.name, e)))?;
+
+        if !response.status().is_success() {
+            return Err(Error::Internal(format!("{} responded with {}", self.name, response.status())));
+        }
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to read prices from {}: {}", self.name, e)))?;
+        parse_prices(&self.name, &body)
+    }
+}
+
+/// Reads a JSON object of prices by asset from a file on every poll
+pub struct FilePriceFetcher {
+    name: String,
+    path: PathBuf,
+}
+
+impl FilePriceFetcher {
+    /// Read prices from `path`
+    pub fn new(name: &str, path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceFetcher for FilePriceFetcher {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, Price>> {
+        let body = tokio::fs::read(&self.path)
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to read {}: {}", self.path.display(), e)))?;
+        parse_prices(&self.name, &body)
+    }
+}
+
+/// One source's latest quote of an asset
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SourceQuote {
+    /// Source name
+    pub source: String,
+    /// Quoted price
+    pub price: Price,
+    /// When the quote was received
+    pub received_at: DateTime<Utc>,
+    /// Whether the quote is too old to count towards the index
+    pub stale: bool,
+}
+
+/// Index of one asset with the quotes it is composed of
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AssetIndex {
+    /// Asset symbol
+    pub asset: String,
+    /// Asset the index is priced in
+    pub quote_asset: String,
+    /// Median of the fresh quotes, absent while the index is stale
+    pub price: Option<Price>,
+    /// Whether too few fresh quotes are left for an index
+    pub stale: bool,
+    /// Latest quote of every source, by source name
+    pub sources: Vec<SourceQuote>,
+}
+
+/// A source's latest quote of an asset
+struct Quote {
+    price: Price,
+    received_at: DateTime<Utc>,
+}
+
+/// Composes index prices from the configured sources
+pub struct IndexPriceService {
+    config: IndexPriceConfig,
+    fetchers: Vec<Arc<dyn PriceFetcher>>,
+    /// Latest quotes by asset, then source
+    quotes: RwLock<BTreeMap<String, BTreeMap<String, Quote>>>,
+}
+
+impl IndexPriceService {
+    /// Service polling the given fetchers
+    pub fn new(config: IndexPriceConfig, fetchers: Vec<Arc<dyn PriceFetcher>>) -> Self {
+        Self {
+            config,
+            fetchers,
+            quotes: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Service settings
+    pub fn config(&self) -> &IndexPriceConfig {
+        &self.config
+    }
+
+    /// Record a source's quote of an asset
+    pub fn record(&self, source: &str, asset: &str, price: Price, received_at: DateTime<Utc>) {
+        self.quotes.write().unwrap()
+            .entry(asset.to_uppercase())
+            .or_default()
+            .insert(source.to_string(), Quote { price, received_at });
+    }
+
+    /// Fetch every source once, keeping the previous quotes of sources that fail
+    pub async fn poll(&self, now: DateTime<Utc>) {
+        for fetcher in &self.fetchers {
+            match fetcher.fetch().await {
+                Ok(prices) => {
+                    for (asset, price) in prices {
+                        self.record(fetcher.name(), &asset, price, now);
+                    }
+                }
+                Err(e) => warn!("Failed to fetch index prices from {}: {}", fetcher.name(), e),
+            }
+        }
+    }
+
+    /// Index of an asset with its quotes, if any source has quoted it
+    pub fn index(&self, asset: &str, now: DateTime<Utc>) -> Option<AssetIndex> {
+        let quotes = self.quotes.read().unwrap();
+        let asset = asset.to_uppercase();
+        quotes.get(&asset).map(|sources| self.compose(&asset, sources, now))
+    }
+
+    /// Indices of every quoted asset
+    pub fn indices(&self, now: DateTime<Utc>) -> Vec<AssetIndex> {
+        self.quotes.read().unwrap()
+            .iter()
+            .map(|(asset, sources)| self.compose(asset, sources, now))
+            .collect()
+    }
+
+    /// Current index price of an asset, absent while it is stale
+    pub fn asset_price(&self, asset: &str, now: DateTime<Utc>) -> Option<Price> {
+        if asset.eq_ignore_ascii_case(&self.config.quote_asset) {
+            return Some(Decimal::ONE);
+        }
+        self.index(asset, now)?.price
+    }
+
+    /// Current index price of a market, its base asset priced in its quote asset
+    pub fn market_price(&self, base_asset: &str, quote_asset: &str, now: DateTime<Utc>) -> Option<Price> {
+        let base = self.asset_price(base_asset, now)?;
+        let quote = self.asset_price(quote_asset, now)?;
+        base.checked_div(quote).map(|price| price.normalize())
+    }
+
+    fn compose(&self, asset: &str, sources: &BTreeMap<String, Quote>, now: DateTime<Utc>) -> AssetIndex {
+        let max_age = chrono::Duration::from_std(self.config.max_age).unwrap_or(chrono::Duration::MAX);
+        let sources: Vec<SourceQuote> = sources
+            .iter()
+            .map(|(source, quote)| SourceQuote {
+                source: source.clone(),
+                price: quote.price,
+                received_at: quote.received_at,
+                stale: now - quote.received_at > max_age,
+            })
+            .collect();
+
+        let mut fresh: Vec<Price> = sources.iter().filter(|quote| !quote.stale).map(|quote| quote.price).collect();
+        let price = (!fresh.is_empty() && fresh.len() >= self.config.min_sources).then(|| median(&mut fresh));
+
+        AssetIndex {
+            asset: asset.to_string(),
+            quote_asset: self.config.quote_asset.clone(),
+            stale: price.is_none(),
+            price,
+            sources,
+        }
+    }
+}
+
+impl IndexPriceSource for IndexPriceService {
+    fn index_price(&self, market: &str) -> Option<Price> {
+        let (base_asset, quote_asset) = market.split_once('/')?;
+        self.market_price(base_asset, quote_asset, Utc::now())
+    }
+}
+
+/// Median of a non-empty list, the mean of the middle two for an even count
+fn median(prices: &mut [Price]) -> Price {
+    prices.sort();
+    let middle = prices.len() / 2;
+    match prices.len() % 2 {
+        1 => prices[middle],
+        _ => ((prices[middle - 1] + prices[middle]) / Decimal::TWO).normalize(),
+    }
+}
+
+/// Poll the index price sources at the configured interval
+pub fn spawn_index_price_poller(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(state.index_prices.config().poll_interval);
+        loop {
+            ticks.tick().await;
+            state.index_prices.poll(Utc::now()).await;
+        }
+    })
+}
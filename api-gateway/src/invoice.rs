@@ -0,0 +1,166 @@
+//! Monthly fee invoices
+//!
+//! The `fee_invoices` report job issues one invoice per account and month
+//! with the account's trading fees, rebates and net amount per asset. Invoices
+//! are numbered in order of issue within their month, e.g. `INV-2025-02-000001`.
+//! Running the job again for a month brings the account's invoice up to date
+//! under the same number, unless it has already been marked paid, which only
+//! admins can do.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, Utc};
+use common::decimal::Amount;
+use common::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Whether an invoice has been paid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceStatus {
+    /// Issued and awaiting payment
+    Issued,
+    /// Marked paid by an admin
+    Paid,
+}
+
+/// Fees of one asset on an invoice
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct InvoiceLine {
+    /// Asset the fees were charged in
+    pub asset: String,
+    /// Trade legs settled in the asset
+    pub trades: usize,
+    /// Fees charged, net of busted trades
+    pub fees: Amount,
+    /// Fees handed back
+    pub rebates: Amount,
+    /// Fees less rebates
+    pub net: Amount,
+}
+
+/// An account's trading fees for one month
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Invoice {
+    /// Invoice ID
+    pub id: Uuid,
+    /// Invoice number, e.g. `INV-2025-02-000001`
+    pub number: String,
+    /// Account billed
+    pub account_id: Uuid,
+    /// Month billed, e.g. `2025-02`
+    pub period: String,
+    /// First day of the month
+    pub period_start: NaiveDate,
+    /// Last day of the month
+    pub period_end: NaiveDate,
+    /// Totals per asset, by asset
+    pub lines: Vec<InvoiceLine>,
+    /// Payment status
+    pub status: InvoiceStatus,
+    /// When the invoice was first issued
+    pub issued_at: DateTime<Utc>,
+    /// When the invoice was last brought up to date
+    pub updated_at: DateTime<Utc>,
+    /// When it was marked paid
+    pub paid_at: Option<DateTime<Utc>>,
+    /// Payment reference given when it was marked paid
+    pub payment_reference: Option<String>,
+}
+
+#[derive(Default)]
+struct Invoices {
+    /// Invoice by ID
+    by_id: HashMap<Uuid, Invoice>,
+    /// Invoice IDs by account and month
+    by_account: HashMap<Uuid, BTreeMap<String, Uuid>>,
+    /// Invoices issued per month, for numbering
+    issued: HashMap<String, u32>,
+}
+
+/// Invoices issued to accounts
+#[derive(Default)]
+pub struct InvoiceBook {
+    invoices: RwLock<Invoices>,
+}
+
+impl InvoiceBook {
+    /// Book with no invoices
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Issue an account's invoice for the month `date` falls in, or bring an
+    /// unpaid one up to date
+    ///
+    /// A paid invoice is returned unchanged.
+    pub fn issue(&self, account_id: Uuid, date: NaiveDate, lines: Vec<InvoiceLine>, now: DateTime<Utc>) -> Invoice {
+        let period_start = date.with_day(1).expect("every month has a first day");
+        let period = period_start.format("%Y-%m").to_string();
+
+        let mut invoices = self.invoices.write().unwrap();
+        let existing = invoices.by_account.get(&account_id).and_then(|periods| periods.get(&period)).copied();
+        if let Some(invoice) = existing.and_then(|id| invoices.by_id.get_mut(&id)) {
+            if invoice.status == InvoiceStatus::Issued {
+                invoice.lines = lines;
+                invoice.updated_at = now;
+            }
+            return invoice.clone();
+        }
+
+        let sequence = invoices.issued.entry(period.clone()).or_default();
+        *sequence += 1;
+        let invoice = Invoice {
+            id: Uuid::new_v4(),
+            number: format!("INV-{}-{:06}", period, sequence),
+            account_id,
+            period_end: period_start + Months::new(1) - Days::new(1),
+            period: period.clone(),
+            period_start,
+            lines,
+            status: InvoiceStatus::Issued,
+            issued_at: now,
+            updated_at: now,
+            paid_at: None,
+            payment_reference: None,
+        };
+        invoices.by_account.entry(account_id).or_default().insert(period, invoice.id);
+        invoices.by_id.insert(invoice.id, invoice.clone());
+        invoice
+    }
+
+    /// An account's invoices, newest month first
+    pub fn invoices(&self, account_id: Uuid) -> Vec<Invoice> {
+        let invoices = self.invoices.read().unwrap();
+        invoices.by_account
+            .get(&account_id)
+            .map(|periods| periods.values().rev().filter_map(|id| invoices.by_id.get(id).cloned()).collect())
+            .unwrap_or_default()
+    }
+
+    /// An invoice by ID
+    pub fn invoice(&self, id: Uuid) -> Option<Invoice> {
+        self.invoices.read().unwrap().by_id.get(&id).cloned()
+    }
+
+    /// Mark an invoice paid, or `None` if there is no such invoice
+    ///
+    /// Fails if it is already paid.
+    pub fn mark_paid(&self, id: Uuid, reference: Option<String>, now: DateTime<Utc>) -> Result<Option<Invoice>> {
+        let mut invoices = self.invoices.write().unwrap();
+        let Some(invoice) = invoices.by_id.get_mut(&id) else {
+            return Ok(None);
+        };
+        if invoice.status == InvoiceStatus::Paid {
+            return Err(Error::Conflict(format!("Invoice {} is already paid", invoice.number)));
+        }
+
+        invoice.status = InvoiceStatus::Paid;
+        invoice.paid_at = Some(now);
+        invoice.payment_reference = reference;
+        Ok(Some(invoice.clone()))
+    }
+}
@@ -0,0 +1,250 @@
+//! Order path latency
+//!
+//! Every order placed through the REST API is timed stage by stage:
+//! - `parse`: reading and decoding the request body
+//! - `risk_checks`: account ownership and order validation
+//! - `reserve`: reserving the order's funds
+//! - `match`: matching, including the engine's own throttle and kill switch checks
+//! - `settle`: settling trades and releasing funds of an unfilled remainder
+//! - `publish`: publishing trades and the order book to market data
+//!
+//! When trades settle in the background, `settle` is the time taken to queue
+//! them, including any wait for room, and `publish` is not part of the order path.
+//!
+//! Each stage, and the total, is recorded in a histogram with fixed bucket
+//! bounds in microseconds. Percentiles are estimated as the upper bound of the
+//! bucket they fall in.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Upper bounds of the histogram buckets in microseconds; one more bucket counts slower samples
+const BUCKET_BOUNDS_US: [u64; 15] = [
+    10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 1_000_000,
+];
+
+/// Stage of the order path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Reading and decoding the request body
+    Parse,
+    /// Account ownership and order validation
+    RiskChecks,
+    /// Reserving funds
+    Reserve,
+    /// Matching in the engine
+    Match,
+    /// Settling trades and releasing unused funds
+    Settle,
+    /// Publishing trades and the order book
+    Publish,
+    /// The whole order path
+    Total,
+}
+
+impl Stage {
+    /// Every stage, in order, followed by the total
+    pub const ALL: [Stage; 7] = [
+        Stage::Parse,
+        Stage::RiskChecks,
+        Stage::Reserve,
+        Stage::Match,
+        Stage::Settle,
+        Stage::Publish,
+        Stage::Total,
+    ];
+}
+
+/// Time an order spent in each stage, in microseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct LatencyBreakdown {
+    /// Reading and decoding the request body
+    pub parse_us: u64,
+    /// Account ownership and order validation
+    pub risk_checks_us: u64,
+    /// Reserving funds
+    pub reserve_us: u64,
+    /// Matching in the engine
+    pub match_us: u64,
+    /// Settling trades and releasing unused funds
+    pub settle_us: u64,
+    /// Publishing trades and the order book
+    pub publish_us: u64,
+    /// The whole order path
+    pub total_us: u64,
+}
+
+impl LatencyBreakdown {
+    /// Time spent in a stage
+    pub fn get(&self, stage: Stage) -> u64 {
+        match stage {
+            Stage::Parse => self.parse_us,
+            Stage::RiskChecks => self.risk_checks_us,
+            Stage::Reserve => self.reserve_us,
+            Stage::Match => self.match_us,
+            Stage::Settle => self.settle_us,
+            Stage::Publish => self.publish_us,
+            Stage::Total => self.total_us,
+        }
+    }
+
+    fn get_mut(&mut self, stage: Stage) -> &mut u64 {
+        match stage {
+            Stage::Parse => &mut self.parse_us,
+            Stage::RiskChecks => &mut self.risk_checks_us,
+            Stage::Reserve => &mut self.reserve_us,
+            Stage::Match => &mut self.match_us,
+            Stage::Settle => &mut self.settle_us,
+            Stage::Publish => &mut self.publish_us,
+            Stage::Total => &mut self.total_us,
+        }
+    }
+}
+
+/// Times consecutive stages of one order
+pub struct StageTimer {
+    started: Instant,
+    last: Instant,
+    breakdown: LatencyBreakdown,
+}
+
+impl StageTimer {
+    /// Start timing at the current instant
+    pub fn start() -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            last: now,
+            breakdown: LatencyBreakdown::default(),
+        }
+    }
+
+    /// Add the time since the previous lap to a stage
+    pub fn lap(&mut self, stage: Stage) {
+        let now = Instant::now();
+        *self.breakdown.get_mut(stage) += micros(now - self.last);
+        self.last = now;
+    }
+
+    /// Stop timing, returning the stages and the total so far
+    pub fn finish(mut self) -> LatencyBreakdown {
+        self.breakdown.total_us = micros(self.started.elapsed());
+        self.breakdown
+    }
+}
+
+fn micros(duration: std::time::Duration) -> u64 {
+    duration.as_micros().try_into().unwrap_or(u64::MAX)
+}
+
+/// Samples of one histogram bucket
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LatencyBucket {
+    /// Upper bound in microseconds, or none for the bucket of slower samples
+    pub le_us: Option<u64>,
+    /// Samples in this bucket
+    pub count: u64,
+}
+
+/// Latency distribution of one stage
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StageLatency {
+    /// Stage
+    pub stage: Stage,
+    /// Orders timed
+    pub count: u64,
+    /// Mean in microseconds
+    pub mean_us: u64,
+    /// Median estimate in microseconds
+    pub p50_us: u64,
+    /// 90th percentile estimate in microseconds
+    pub p90_us: u64,
+    /// 99th percentile estimate in microseconds
+    pub p99_us: u64,
+    /// Slowest sample in microseconds
+    pub max_us: u64,
+    /// Samples per bucket
+    pub buckets: Vec<LatencyBucket>,
+}
+
+/// Histogram with fixed bucket bounds
+#[derive(Default)]
+struct Histogram {
+    buckets: [AtomicU64; BUCKET_BOUNDS_US.len() + 1],
+    sum_us: AtomicU64,
+    max_us: AtomicU64,
+}
+
+impl Histogram {
+    fn record(&self, us: u64) {
+        let bucket = BUCKET_BOUNDS_US.iter().position(|bound| us <= *bound).unwrap_or(BUCKET_BOUNDS_US.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self, stage: Stage) -> StageLatency {
+        let counts: Vec<u64> = self.buckets.iter().map(|bucket| bucket.load(Ordering::Relaxed)).collect();
+        let count: u64 = counts.iter().sum();
+        let max_us = self.max_us.load(Ordering::Relaxed);
+
+        let percentile = |quantile: f64| {
+            if count == 0 {
+                return 0;
+            }
+            let rank = ((count as f64) * quantile).ceil().max(1.0) as u64;
+            let mut seen = 0;
+            for (index, bucket) in counts.iter().enumerate() {
+                seen += bucket;
+                if seen >= rank {
+                    return BUCKET_BOUNDS_US.get(index).map_or(max_us, |bound| (*bound).min(max_us));
+                }
+            }
+            max_us
+        };
+
+        StageLatency {
+            stage,
+            count,
+            mean_us: self.sum_us.load(Ordering::Relaxed).checked_div(count).unwrap_or(0),
+            p50_us: percentile(0.5),
+            p90_us: percentile(0.9),
+            p99_us: percentile(0.99),
+            max_us,
+            buckets: counts.iter().enumerate()
+                .map(|(index, count)| LatencyBucket { le_us: BUCKET_BOUNDS_US.get(index).copied(), count: *count })
+                .collect(),
+        }
+    }
+}
+
+/// Order path latency histograms, one per stage and one for the total
+#[derive(Default)]
+pub struct LatencyMetrics {
+    histograms: [Histogram; Stage::ALL.len()],
+}
+
+impl LatencyMetrics {
+    /// Create empty histograms
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the stages of one order
+    pub fn record(&self, breakdown: &LatencyBreakdown) {
+        for (stage, histogram) in Stage::ALL.iter().zip(&self.histograms) {
+            histogram.record(breakdown.get(*stage));
+        }
+    }
+
+    /// Distribution of every stage, in order, followed by the total
+    pub fn snapshot(&self) -> Vec<StageLatency> {
+        Stage::ALL.iter().zip(&self.histograms)
+            .map(|(stage, histogram)| histogram.snapshot(*stage))
+            .collect()
+    }
+}
@@ -1,13 +1,52 @@
 // api-gateway/src/lib.rs
 pub mod api;
+pub mod archive;
+pub mod audit;
+pub mod auth;
+pub mod balance_history;
+pub mod capabilities;
+pub mod earn;
 pub mod error;
+pub mod expiry;
+pub mod funding;
+pub mod graphql;
+pub mod health;
+pub mod incentives;
+pub mod index_price;
+pub mod invoice;
+pub mod latency;
+pub mod limits;
+pub mod market_sync;
+pub mod notification;
 pub mod config;
+pub mod duplicates;
+pub mod number_format;
+pub mod order_history;
+pub mod order_import;
+pub mod overview;
+pub mod peg;
+pub mod pipeline;
+pub mod rate_limit;
+pub mod report;
+pub mod routes;
+pub mod runtime;
+pub mod scheduler;
+pub mod security;
+pub mod session;
+pub mod shadow;
+pub mod system;
+#[cfg(feature = "ui")]
+pub mod ui;
+pub mod valuation;
+pub mod versioning;
+pub mod webhook;
 pub mod ws;
 
 use std::sync::Arc;
 use account_service::AccountService;
 use market_data::MarketDataService;
 use matching_engine::MatchingEngine;
+use matching_engine::surveillance::{Surveillance, SurveillanceConfig};
 use common::model::market::Market;
 
 /// App state shared across handlers
@@ -20,4 +59,226 @@ pub struct AppState {
     pub market_data_service: Arc<MarketDataService>,
     /// Available markets
     pub markets: Vec<Market>,
-}
\ No newline at end of file
+    /// Issued API keys
+    pub api_keys: Arc<auth::ApiKeyStore>,
+    /// Record of admin and risk actions
+    pub audit_log: Arc<audit::AuditLog>,
+    /// Security events and sessions of accounts
+    pub security: Arc<security::SecurityLog>,
+    /// Trade surveillance alerts
+    pub surveillance: Arc<Surveillance>,
+    /// End-of-day regulatory reports
+    pub reports: Arc<report::ReportGenerator>,
+    /// Report jobs, their schedules and run history
+    pub scheduler: Arc<scheduler::ReportScheduler>,
+    /// Account webhooks and their delivery log
+    pub webhooks: Arc<webhook::WebhookService>,
+    /// Account notification preferences and the send queue
+    pub notifications: Arc<notification::NotificationService>,
+    /// Market maker quote tracking and rebates
+    pub incentives: Arc<incentives::IncentiveProgram>,
+    /// Interest on opted-in idle balances
+    pub earn: Arc<earn::EarnProgram>,
+    /// Monthly fee invoices of accounts
+    pub invoices: Arc<invoice::InvoiceBook>,
+    /// Funding rates and settlement of perpetual markets
+    pub funding: Arc<funding::FundingEngine>,
+    /// Index prices composed from external sources
+    pub index_prices: Arc<index_price::IndexPriceService>,
+    /// Order path latency histograms
+    pub latency: Arc<latency::LatencyMetrics>,
+    /// Trade settlement behind order placement
+    pub settlement: Arc<pipeline::SettlementPipeline>,
+    /// JSON number format of clients that do not ask for one
+    pub number_format: number_format::NumberFormat,
+    /// Finds the markets to value assets through
+    pub conversion: Arc<dyn valuation::ConversionResolver>,
+    /// Component statuses and announcements for the `system` channel
+    pub system: Arc<system::SystemStatus>,
+    /// Dependency probes behind `/health`
+    pub health: Arc<health::HealthChecker>,
+    /// Request size and time limits
+    pub limits: limits::RequestLimits,
+    /// Daily trade and candle archives for bulk download
+    pub archive: Arc<archive::DataArchive>,
+    /// Live WebSocket connections
+    pub ws_connections: Arc<ws::connections::ConnectionRegistry>,
+    /// Recent orders of accounts detecting duplicates
+    pub duplicate_orders: Arc<duplicates::DuplicateOrderGuard>,
+    /// Status changes of orders recorded for their audit trails
+    pub order_history: Arc<order_history::OrderHistory>,
+}
+
+impl AppState {
+    /// Create state over the given services, with no issued keys or audit entries
+    ///
+    /// Starts surveillance and quote tracking of the matching engine with the
+    /// default settings, and records its orders' status changes.
+    pub fn new(
+        matching_engine: Arc<MatchingEngine>,
+        account_service: Arc<AccountService>,
+        market_data_service: Arc<MarketDataService>,
+        markets: Vec<Market>,
+    ) -> Self {
+        let system = Arc::new(system::SystemStatus::new(market_data_service.channel()));
+        let probes = health::default_probes(
+            matching_engine.clone(),
+            account_service.clone(),
+            market_data_service.clone(),
+            markets.iter().map(|market| market.symbol.clone()).collect(),
+        );
+
+        let archive = archive::DataArchive::new(
+            market_data_service.clone(),
+            markets.iter().map(|market| market.symbol.clone()).collect(),
+            archive::ArchiveConfig::default(),
+        );
+
+        let index_prices = Arc::new(index_price::IndexPriceService::new(index_price::IndexPriceConfig::default(), Vec::new()));
+        let order_history = order_history::OrderHistory::start(&matching_engine, account_service.clone());
+
+        Self {
+            health: Arc::new(health::HealthChecker::new(health::HealthConfig::default(), system.clone(), probes)),
+            archive: Arc::new(archive),
+            system,
+            account_service,
+            market_data_service,
+            markets,
+            api_keys: Arc::new(auth::ApiKeyStore::new()),
+            audit_log: Arc::new(audit::AuditLog::new()),
+            security: Arc::new(security::SecurityLog::new()),
+            surveillance: Surveillance::start(&matching_engine, SurveillanceConfig::default()),
+            reports: Arc::new(report::ReportGenerator::disabled()),
+            scheduler: Arc::new(scheduler::ReportScheduler::new(
+                scheduler::SchedulerConfig::default(),
+                scheduler::builtin_jobs(),
+            )),
+            webhooks: webhook::WebhookService::new(matching_engine.clone(), webhook::WebhookConfig::default()),
+            notifications: notification::NotificationService::new(
+                matching_engine.clone(),
+                notification::NotificationConfig::default(),
+                notification::NotificationConfig::default().notifiers(),
+            ),
+            incentives: Arc::new(incentives::IncentiveProgram::start(&matching_engine, incentives::IncentiveConfig::default())),
+            earn: Arc::new(earn::EarnProgram::new(earn::EarnConfig::default())),
+            invoices: Arc::new(invoice::InvoiceBook::new()),
+            funding: Arc::new(funding::FundingEngine::new(funding::FundingConfig::default()).with_index_source(index_prices.clone())),
+            index_prices,
+            latency: Arc::new(latency::LatencyMetrics::new()),
+            settlement: pipeline::SettlementPipeline::new(pipeline::PipelineConfig::default()),
+            number_format: number_format::NumberFormat::default(),
+            conversion: Arc::new(valuation::ShortestPathResolver),
+            limits: limits::RequestLimits::default(),
+            ws_connections: Arc::new(ws::connections::ConnectionRegistry::new()),
+            duplicate_orders: Arc::new(duplicates::DuplicateOrderGuard::default()),
+            order_history,
+            matching_engine,
+        }
+    }
+
+    /// Journal the engine's events and write daily reports with the given settings
+    pub fn with_reports(mut self, config: report::ReportConfig) -> Self {
+        self.reports = Arc::new(report::ReportGenerator::start(&self.matching_engine, config));
+        if self.reports.is_enabled() {
+            self.health.register(Arc::new(health::EventStoreProbe::new(self.reports.clone())));
+        }
+        self
+    }
+
+    /// Run the given report jobs on the configured schedules
+    pub fn with_scheduler(mut self, config: scheduler::SchedulerConfig, jobs: Vec<Arc<dyn scheduler::ReportJob>>) -> Self {
+        self.scheduler = Arc::new(scheduler::ReportScheduler::new(config, jobs));
+        self
+    }
+
+    /// Deliver webhooks with the given retry and URL settings
+    pub fn with_webhooks(mut self, config: webhook::WebhookConfig) -> Self {
+        self.webhooks = webhook::WebhookService::new(self.matching_engine.clone(), config);
+        self
+    }
+
+    /// Send account notifications with the given settings through the given channels
+    pub fn with_notifications(
+        mut self,
+        config: notification::NotificationConfig,
+        notifiers: Vec<Arc<dyn notification::Notifier>>,
+    ) -> Self {
+        self.notifications = notification::NotificationService::new(self.matching_engine.clone(), config, notifiers);
+        self
+    }
+
+    /// Cache and time out health probes with the given settings
+    pub fn with_health(mut self, config: health::HealthConfig) -> Self {
+        self.health = Arc::new(health::HealthChecker::new(config, self.system.clone(), self.health.probes()));
+        self
+    }
+
+    /// Refuse requests and WebSocket messages over the given limits
+    pub fn with_limits(mut self, limits: limits::RequestLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Keep data archives for the given days and candle intervals
+    pub fn with_archive(mut self, config: archive::ArchiveConfig) -> Self {
+        let markets = self.markets.iter().map(|market| market.symbol.clone()).collect();
+        self.archive = Arc::new(archive::DataArchive::new(self.market_data_service.clone(), markets, config));
+        self
+    }
+
+    /// Pay maker rebates with the given rate and quoting requirements
+    pub fn with_incentives(mut self, config: incentives::IncentiveConfig) -> Self {
+        self.incentives = Arc::new(incentives::IncentiveProgram::start(&self.matching_engine, config));
+        self
+    }
+
+    /// Pay interest on opted-in balances at the given rates
+    pub fn with_earn(mut self, config: earn::EarnConfig) -> Self {
+        self.earn = Arc::new(earn::EarnProgram::new(config));
+        self
+    }
+
+    /// Settle perpetual funding with the given settings, against the state's index prices
+    pub fn with_funding(mut self, funding: funding::FundingEngine) -> Self {
+        self.funding = Arc::new(funding.with_index_source(self.index_prices.clone()));
+        self
+    }
+
+    /// Compose index prices from the given sources, and fund perpetual markets against them
+    pub fn with_index_prices(
+        mut self,
+        config: index_price::IndexPriceConfig,
+        fetchers: Vec<Arc<dyn index_price::PriceFetcher>>,
+    ) -> Self {
+        self.index_prices = Arc::new(index_price::IndexPriceService::new(config, fetchers));
+        let funding = funding::FundingEngine::new(self.funding.config().clone());
+        self.with_funding(funding)
+    }
+
+    /// Settle trades on background workers with the given queue settings
+    pub fn with_settlement_pipeline(mut self, config: pipeline::PipelineConfig) -> Self {
+        self.settlement = pipeline::SettlementPipeline::new(config);
+        if self.settlement.is_async() {
+            self.health.register(Arc::new(health::SettlementProbe::new(self.settlement.clone())));
+        }
+        self
+    }
+
+    /// Write JSON numbers in the given format unless a client asks for another
+    pub fn with_number_format(mut self, format: number_format::NumberFormat) -> Self {
+        self.number_format = format;
+        self
+    }
+
+    /// Detect duplicate orders with the given settings for accounts that set none
+    pub fn with_duplicate_orders(mut self, defaults: duplicates::DuplicateOrderSettings) -> Self {
+        self.duplicate_orders = Arc::new(duplicates::DuplicateOrderGuard::new(defaults));
+        self
+    }
+
+    /// Value assets without a direct market through the given resolver
+    pub fn with_conversion_resolver(mut self, resolver: impl valuation::ConversionResolver + 'static) -> Self {
+        self.conversion = Arc::new(resolver);
+        self
+    }
+}
@@ -0,0 +1,109 @@
+//! Request size and time limits
+//!
+//! REST requests are refused before reaching a handler when they carry too
+//! many or too large headers (`431`) or a body over the limit (`413`), and
+//! answered with `408` when reading and handling them takes longer than the
+//! timeout, so slow or oversized clients cannot tie up the gateway. WebSocket
+//! connections close when a client sends a frame or message over the limits,
+//! and their requests are rate limited by [`crate::ws::quota`].
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http_body_util::LengthLimitError;
+
+use crate::error::ApiError;
+use crate::AppState;
+
+/// Request size and time limits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestLimits {
+    /// Largest REST request body
+    pub max_body_bytes: usize,
+    /// Most headers on a REST request
+    pub max_headers: usize,
+    /// Largest total size of a REST request's header names and values
+    pub max_header_bytes: usize,
+    /// Time allowed to read and handle a REST request
+    pub timeout: Duration,
+    /// Largest WebSocket message from a client
+    pub ws_max_message_bytes: usize,
+    /// Largest WebSocket frame from a client
+    pub ws_max_frame_bytes: usize,
+    /// WebSocket messages a connection may send per second, also the burst size
+    pub ws_messages_per_second: u32,
+    /// Calls per minute a WebSocket connection may make, by method
+    pub ws_method_quotas: BTreeMap<String, u32>,
+    /// WebSocket messages refused within a minute after which the connection is closed
+    pub ws_max_refused_per_minute: u32,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 1024 * 1024,
+            max_headers: 64,
+            max_header_bytes: 16 * 1024,
+            timeout: Duration::from_secs(30),
+            ws_max_message_bytes: 64 * 1024,
+            ws_max_frame_bytes: 16 * 1024,
+            ws_messages_per_second: 50,
+            ws_method_quotas: BTreeMap::from([("getOrderBook".to_string(), 60)]),
+            ws_max_refused_per_minute: 30,
+        }
+    }
+}
+
+/// Refuse oversized requests and time out slow ones
+pub async fn limit_requests(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let limits = &state.limits;
+
+    let headers = request.headers();
+    let header_bytes: usize = headers.iter().map(|(name, value)| name.as_str().len() + value.len()).sum();
+    if headers.len() > limits.max_headers || header_bytes > limits.max_header_bytes {
+        return ApiError::HeadersTooLarge(format!(
+            "At most {} headers and {} bytes of headers are accepted",
+            limits.max_headers, limits.max_header_bytes
+        ))
+        .into_response();
+    }
+
+    let too_large = || ApiError::PayloadTooLarge(format!("Request bodies are limited to {} bytes", limits.max_body_bytes));
+    let declared = headers
+        .get(axum::http::header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if declared.is_some_and(|length| length > limits.max_body_bytes) {
+        return too_large().into_response();
+    }
+
+    let handled = tokio::time::timeout(limits.timeout, async {
+        // Read the whole body here, so a client sending it slowly is timed out
+        let (parts, body) = request.into_parts();
+        let bytes = match axum::body::to_bytes(body, limits.max_body_bytes).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                let error = if e.into_inner().is::<LengthLimitError>() {
+                    too_large()
+                } else {
+                    ApiError::BadRequest("Failed to read request body".to_string())
+                };
+                return error.into_response();
+            }
+        };
+        next.run(Request::from_parts(parts, Body::from(bytes))).await
+    })
+    .await;
+
+    match handled {
+        Ok(response) => response,
+        Err(_) => ApiError::Timeout(format!("Request not completed within {:?}", limits.timeout)).into_response(),
+    }
+}
@@ -1,45 +1,21 @@
 //! API Gateway for the trading engine
 
-mod api;
-mod error;
-mod ws;
-mod config;
-
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::{SystemTime, UNIX_EPOCH, Instant};
-
-use axum::{
-    routing::{get, post},
-    Router,
-    extract::State,
-    response::IntoResponse,
-    Json,
+use api_gateway::{
+    api, archive, audit, auth, balance_history, capabilities, duplicates, earn, funding, health, incentives,
+    index_price, invoice, latency, notification, order_import, overview, report, scheduler, security, system, valuation,
+    versioning, webhook, ws,
 };
+use axum::Router;
 use clap::Parser;
-use common::model::market::Market;
+use common::model::fee::FeeSchedule;
 use dotenv::dotenv;
-use tokio::net::TcpListener;
-use tokio::signal;
-use tower_http::cors::{Any, CorsLayer};
-use tower_http::trace::{TraceLayer, DefaultMakeSpan, DefaultOnRequest, DefaultOnResponse};
-use tracing::{info, Level, debug};
-use tracing_subscriber::{EnvFilter, FmtSubscriber, fmt::format::FmtSpan};
-use uuid::Uuid;
-use utoipa::OpenApi;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 use utoipa_swagger_ui::SwaggerUi;
 
-use account_service::AccountService;
-use market_data::MarketDataService;
-use matching_engine::MatchingEngine;
-
-use crate::api::{
-    account::{create_account, get_account, get_balances, deposit, withdraw},
-    market::{get_markets, get_order_book, get_ticker, get_tickers, get_trades, get_candles},
-    order::{place_order, cancel_order, get_order, get_orders},
-};
-use crate::config::AppConfig;
-use crate::ws::handler::ws_handler;
+use api_gateway::config::AppConfig;
+use api_gateway::runtime::{init_tracing, Runtime};
+use matching_engine::ThrottleConfig;
 
 /// API documentation
 #[derive(OpenApi)]
@@ -49,20 +25,124 @@ use crate::ws::handler::ws_handler;
         api::account::create_account,
         api::account::get_account,
         api::account::get_balances,
+        api::account::get_reservations,
+        api::account::get_positions,
         api::account::deposit,
         api::account::withdraw,
+        api::withdrawal::get_withdrawal_addresses,
+        api::withdrawal::add_withdrawal_address,
+        api::withdrawal::remove_withdrawal_address,
+        api::account::get_account_trades,
+        api::account::get_portfolio,
+        api::account::get_balance_history,
+        api::adjustment::get_statement,
+        api::permissions::get_api_key_scopes,
+        api::api_key::list_api_keys,
+        api::api_key::create_api_key,
+        api::api_key::revoke_api_key,
+        api::api_key::rotate_api_key,
+        api::security::get_security_events,
+        api::security::list_sessions,
+        api::security::revoke_session,
+        api::earn::subscribe_earn,
+        api::earn::get_earn_subscriptions,
+        api::earn::unsubscribe_earn,
+        api::earn::get_earn_accruals,
+        api::invoice::get_invoices,
+        api::funding::get_funding_payments,
+        api::notification::get_notification_preferences,
+        api::notification::set_notification_preferences,
+        api::duplicates::get_duplicate_order_settings,
+        api::duplicates::set_duplicate_order_settings,
+        api::kill_switch::engage_own_kill_switch,
+        api::closure::close_account,
+        api::closure::export_account,
+        api::webhook::create_webhook,
+        api::webhook::get_webhooks,
+        api::webhook::delete_webhook,
+        api::webhook::get_webhook_deliveries,
         // Market routes
         api::market::get_markets,
         api::market::get_order_book,
+        api::market::get_order_book_history,
+        api::market::get_heatmap,
         api::market::get_ticker,
         api::market::get_tickers,
         api::market::get_trades,
+        api::market::get_raw_trades,
         api::market::get_candles,
+        api::market::get_analytics,
+        api::market::get_price_impact,
+        api::market::get_market_session,
+        api::market::get_shadow_markets,
+        api::funding::get_funding_rates,
+        api::index_price::get_index_prices,
+        api::index_price::get_index_price,
+        api::data::get_data_manifest,
+        api::data::get_trade_archive,
+        api::data::get_candle_archive,
+        api::system::get_announcements,
+        api::system::get_capabilities,
+        health::health_check,
         // Order routes
         api::order::place_order,
+        api::order::preview_order,
         api::order::cancel_order,
         api::order::get_order,
+        api::order::get_order_fills,
+        api::order::get_order_history,
         api::order::get_orders,
+        // Admin routes
+        api::kill_switch::engage_kill_switch,
+        api::kill_switch::release_kill_switch,
+        api::bust::bust_trade,
+        api::adjustment::credit_fee_rebate,
+        api::adjustment::adjust_balance,
+        api::permissions::get_account_permissions,
+        api::permissions::set_account_permissions,
+        api::api_key::issue_api_key,
+        api::closure::force_close_account,
+        api::closure::admin_export_account,
+        api::admin::list_accounts,
+        api::admin::take_balance_snapshots,
+        api::admin::get_feature_flags,
+        api::asset::get_assets,
+        api::asset::register_asset,
+        api::admin::set_feature_flag,
+        api::admin::find_account_by_external_id,
+        api::admin::set_account_external_id,
+        api::admin::get_audit_log,
+        api::admin::get_surveillance_alerts,
+        api::admin::get_account_reservations,
+        api::admin::force_release_reservation,
+        api::admin::regenerate_report,
+        api::report_job::list_report_jobs,
+        api::report_job::run_report_job,
+        api::report_job::get_report_job_runs,
+        api::report_job::get_report_job_run,
+        api::admin::set_market_schedule,
+        api::admin::clear_market_schedule,
+        api::admin::get_book_limits,
+        api::admin::set_book_limits,
+        api::admin::clear_book_limits,
+        api::admin::import_orders,
+        api::admin::get_incentives,
+        api::admin::get_rebate_periods,
+        api::admin::settle_rebates,
+        api::earn::accrue_earn,
+        api::invoice::mark_invoice_paid,
+        api::funding::settle_funding,
+        api::system::publish_announcement,
+        api::admin::get_order_latency,
+        api::admin::get_candle_compaction,
+        api::admin::get_market_data_memory,
+        api::admin::get_market_data_gaps,
+        api::admin::get_book_checks,
+        api::admin::get_subscriber_metrics,
+        api::admin::list_ws_connections,
+        api::admin::get_ws_connection,
+        api::admin::disconnect_ws_connection,
+        api::admin::get_overview,
     ),
     components(
         schemas(
@@ -70,47 +150,271 @@ use crate::ws::handler::ws_handler;
             api::account::CreateAccountRequest,
             api::account::DepositRequest,
             api::account::WithdrawRequest,
+            api::account::AccountTradesQuery,
+            api::account::PortfolioQuery,
+            api::account::BalanceHistoryQuery,
+            api::adjustment::StatementQuery,
+            common::model::account::StatementEntry,
+            common::model::account::StatementEntryKind,
+            common::model::account::BalanceSnapshot,
+            valuation::ConversionLeg,
+            valuation::AssetValuation,
+            valuation::Portfolio,
+            valuation::TickerConversion,
+            api::account::AccountCreated,
+            api::order::PlaceOrderQuery,
+            latency::LatencyBreakdown,
             common::model::account::Account,
             common::model::account::Balance,
+            common::model::account::Reservation,
+            common::model::account::Position,
+            common::model::market::SessionState,
+            common::model::market::TradingSchedule,
+            common::model::market::BookLimits,
+            common::model::market::LevelPolicy,
+            common::model::market::MarketSession,
+            common::model::account::WithdrawalAddress,
+            api::withdrawal::AddWithdrawalAddressRequest,
+            api::webhook::CreateWebhookRequest,
+            api::webhook::DeliveriesQuery,
+            webhook::Webhook,
+            webhook::WebhookEventType,
+            webhook::Delivery,
+            webhook::DeliveryStatus,
             
             // Order API
             api::order::PlaceOrderRequest,
             api::order::OrderPlacementResult,
+            api::order::OrderPreview,
+            api::order::PreviewFill,
             api::order::OrdersQuery,
             common::model::order::Order,
             common::model::order::TimeInForce,
             common::model::order::Side,
             common::model::order::OrderType,
+            common::model::order::RejectReason,
+            common::model::order::Peg,
+            common::model::order::PegReference,
+            common::model::order::OrderTransition,
             common::model::trade::Trade,
+            common::model::trade::OrderFill,
+            common::model::trade::Liquidity,
             
             // Market API
             api::market::OrderBookQuery,
             api::market::OrderBookData,
+            api::market::OrderBookHistoryQuery,
+            market_data::MarketDepth,
+            market_data::PriceLevel,
+            market_data::heatmap::Heatmap,
+            market_data::heatmap::HeatmapColumn,
+            api::market::HeatmapQuery,
             api::market::TradesQuery,
             api::market::MarketTradesData,
             api::market::CandlesQuery,
+            api::market::TickersQuery,
+            api::market::MarketTicker,
             api::market::MarketCandleData,
+            api::market::AnalyticsQuery,
+            market_data::MarketAnalytics,
+            api::market::PriceImpact,
             market_data::Ticker,
             market_data::Candle,
             market_data::CandleInterval,
+            market_data::CandleFill,
             common::model::market::Market,
             
+            // Admin API
+            api::kill_switch::KillSwitchRequest,
+            api::kill_switch::KillSwitchStatus,
+            api::bust::TradeBustRequest,
+            api::adjustment::AdjustmentRequest,
+            api::adjustment::AdjustmentResult,
+            common::model::account::BalanceAdjustment,
+            common::model::account::AdjustmentKind,
+            common::model::account::ReasonCode,
+            common::model::account::AccountPermissions,
+            api::permissions::ApiKeyScopes,
+            api::api_key::ApiKeyRequest,
+            api::api_key::IssuedApiKey,
+            auth::ApiKey,
+            api::security::SecurityEventsQuery,
+            security::SecurityEvent,
+            security::SecurityEventKind,
+            security::Session,
+            auth::Scope,
+            common::model::trade::TradeBust,
+            market_data::TradeCorrection,
+            market_data::CorrectionKind,
+            api::closure::CloseAccountRequest,
+            api::closure::AccountExport,
+            api::admin::AccountsQuery,
+            api::admin::BalanceSnapshotRequest,
+            api::admin::FeatureFlagUpdate,
+            api::asset::RegisterAssetRequest,
+            common::model::asset::Asset,
+            common::flags::FlagState,
+            balance_history::BalanceSnapshotTaken,
+            api::admin::ExternalIdRequest,
+            api::admin::AuditQuery,
+            audit::AuditEntry,
+            api::admin::AlertsQuery,
+            api::admin::ForceReleaseRequest,
+            common::model::surveillance::Alert,
+            common::model::surveillance::AlertKind,
+            report::ReportSummary,
+            api::report_job::RunReportJobRequest,
+            api::report_job::ReportJobRunsQuery,
+            scheduler::JobInfo,
+            scheduler::JobRun,
+            scheduler::JobStatus,
+            scheduler::JobTrigger,
+            report::ReportFile,
+            archive::ArchiveEntry,
+            archive::ArchiveKind,
+            report::ReportFormat,
+            api::admin::ImportQuery,
+            order_import::ImportSummary,
+            order_import::ImportRowResult,
+            order_import::ImportRowStatus,
+            api::admin::RebatePeriodsQuery,
+            common::model::incentive::MakerActivity,
+            incentives::IncentiveReport,
+            incentives::RebatePeriod,
+            incentives::Rebate,
+            api::earn::SubscribeEarnRequest,
+            api::earn::AccrualsQuery,
+            earn::EarnSubscription,
+            earn::Accrual,
+            api::invoice::MarkInvoicePaidRequest,
+            invoice::Invoice,
+            invoice::InvoiceLine,
+            invoice::InvoiceStatus,
+            api::funding::FundingQuery,
+            api::funding::SettleFundingRequest,
+            funding::FundingRate,
+            index_price::AssetIndex,
+            index_price::SourceQuote,
+            market_data::shadow::ShadowMarketStatus,
+            common::model::account::FundingPayment,
+            common::model::market::MarketKind,
+            api::system::PublishAnnouncementRequest,
+            api::system::AnnouncementsQuery,
+            system::Announcement,
+            system::AnnouncementKind,
+            system::Severity,
+            capabilities::Capabilities,
+            overview::Overview,
+            overview::MarketOverview,
+            overview::WebSocketOverview,
+            overview::QueueDepths,
+            common::model::account::AccountTotals,
+            common::model::account::AssetTotal,
+            capabilities::AuthCapabilities,
+            capabilities::RepositoryCapabilities,
+            capabilities::WebSocketCapabilities,
+            capabilities::FeatureCapabilities,
+            notification::NotificationKind,
+            notification::NotificationPreferences,
+            duplicates::DuplicateAction,
+            duplicates::DuplicateOrderSettings,
+            latency::Stage,
+            latency::StageLatency,
+            market_data::retention::CandleCompaction,
+            market_data::retention::CompactionMetrics,
+            market_data::memory::MemoryUsage,
+            market_data::memory::StructureUsage,
+            market_data::sync::MarketGaps,
+            market_data::channel::ChannelMetrics,
+            market_data::channel::SubscriberMetrics,
+            ws::connections::ConnectionInfo,
+            ws::connections::ConnectionSubscription,
+            latency::LatencyBucket,
+            
             // Response models
             api::response::ApiResponse<common::model::account::Account>,
+            api::response::ApiResponse<api::account::AccountCreated>,
             api::response::ApiResponse<common::model::order::Order>, 
             api::response::ApiResponse<api::order::OrderPlacementResult>,
             api::response::ApiListResponse<common::model::market::Market>,
             api::response::ApiListResponse<common::model::order::Order>,
             api::response::ApiListResponse<common::model::account::Balance>,
+            api::response::ApiListResponse<common::model::asset::Asset>,
+            api::response::ApiListResponse<api::market::MarketTicker>,
+            api::response::ApiListResponse<common::model::account::Reservation>,
+            api::response::ApiListResponse<common::model::account::Position>,
+            api::response::ApiResponse<common::model::account::Reservation>,
+            api::response::ApiResponse<common::model::market::MarketSession>,
+            api::response::ApiListResponse<common::model::account::WithdrawalAddress>,
+            api::response::ApiResponse<common::model::account::WithdrawalAddress>,
+            api::response::ApiListResponse<common::model::trade::Trade>,
+            api::response::ApiListResponse<common::model::trade::OrderFill>,
+            api::response::ApiListResponse<common::model::order::OrderTransition>,
             api::response::ApiListResponse<market_data::Ticker>,
+            api::response::ApiResponse<market_data::MarketDepth>,
+            api::response::ApiResponse<market_data::MarketAnalytics>,
+            api::response::ApiResponse<api::market::PriceImpact>,
+            api::response::ApiListResponse<archive::ArchiveEntry>,
+            api::response::ApiResponse<api::kill_switch::KillSwitchStatus>,
+            api::response::ApiResponse<common::model::trade::TradeBust>,
+            api::response::ApiResponse<api::adjustment::AdjustmentResult>,
+            api::response::ApiResponse<common::model::account::AccountPermissions>,
+            api::response::ApiResponse<api::permissions::ApiKeyScopes>,
+            api::response::ApiResponse<api::api_key::IssuedApiKey>,
+            api::response::ApiResponse<auth::ApiKey>,
+            api::response::ApiListResponse<auth::ApiKey>,
+            api::response::ApiResponse<security::Session>,
+            api::response::ApiListResponse<security::Session>,
+            api::response::ApiListResponse<security::SecurityEvent>,
+            api::response::ApiListResponse<common::model::account::StatementEntry>,
+            api::response::ApiResponse<api::closure::AccountExport>,
+            api::response::ApiResponse<valuation::Portfolio>,
+            api::response::ApiListResponse<audit::AuditEntry>,
+            api::response::ApiListResponse<common::model::surveillance::Alert>,
+            api::response::ApiResponse<report::ReportSummary>,
+            api::response::ApiResponse<scheduler::JobRun>,
+            api::response::ApiListResponse<scheduler::JobRun>,
+            api::response::ApiListResponse<scheduler::JobInfo>,
+            api::response::ApiResponse<incentives::IncentiveReport>,
+            api::response::ApiResponse<incentives::RebatePeriod>,
+            api::response::ApiListResponse<incentives::RebatePeriod>,
+            api::response::ApiResponse<earn::EarnSubscription>,
+            api::response::ApiListResponse<earn::EarnSubscription>,
+            api::response::ApiListResponse<earn::Accrual>,
+            api::response::ApiResponse<invoice::Invoice>,
+            api::response::ApiListResponse<invoice::Invoice>,
+            api::response::ApiResponse<funding::FundingRate>,
+            api::response::ApiListResponse<funding::FundingRate>,
+            api::response::ApiResponse<index_price::AssetIndex>,
+            api::response::ApiListResponse<index_price::AssetIndex>,
+            api::response::ApiListResponse<market_data::shadow::ShadowMarketStatus>,
+            api::response::ApiListResponse<common::model::account::FundingPayment>,
+            api::response::ApiResponse<system::Announcement>,
+            api::response::ApiListResponse<system::Announcement>,
+            api::response::ApiResponse<capabilities::Capabilities>,
+            api::response::ApiResponse<overview::Overview>,
+            api::response::ApiResponse<notification::NotificationPreferences>,
+            api::response::ApiResponse<duplicates::DuplicateOrderSettings>,
+            api::response::ApiListResponse<latency::StageLatency>,
+            api::response::ApiResponse<market_data::retention::CompactionMetrics>,
+            api::response::ApiResponse<market_data::memory::MemoryUsage>,
+            api::response::ApiListResponse<market_data::sync::MarketGaps>,
+            api::response::ApiResponse<market_data::channel::ChannelMetrics>,
+            api::response::ApiResponse<ws::connections::ConnectionInfo>,
+            api::response::ApiListResponse<ws::connections::ConnectionInfo>,
+            api::response::ApiResponse<webhook::Webhook>,
+            api::response::ApiListResponse<webhook::Webhook>,
+            api::response::ApiListResponse<webhook::Delivery>,
             api::response::ResponseMetadata,
             api::response::PaginationMetadata
         )
     ),
+    modifiers(&SecurityAddon),
     tags(
         (name = "account", description = "Account management endpoints"),
         (name = "market", description = "Market data endpoints"),
         (name = "order", description = "Order management endpoints"),
+        (name = "admin", description = "Operator endpoints, require the admin key"),
         (name = "system", description = "System endpoints")
     ),
     info(
@@ -121,6 +425,24 @@ use crate::ws::handler::ws_handler;
 )]
 struct ApiDoc;
 
+/// Registers the `X-API-Key` security schemes used by private and admin endpoints
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+            );
+            components.add_security_scheme(
+                "admin_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+            );
+        }
+    }
+}
+
 /// Trading engine API server
 #[derive(Parser, Debug)]
 #[clap(author, version, about)]
@@ -128,6 +450,18 @@ struct Args {
     /// Listening address
     #[clap(short, long, default_value = "127.0.0.1:8080")]
     addr: String,
+    /// Fee rate charged to the maker side of each trade (e.g. 0.001)
+    #[clap(long, default_value = "0")]
+    maker_fee: rust_decimal::Decimal,
+    /// Fee rate charged to the taker side of each trade (e.g. 0.002)
+    #[clap(long, default_value = "0")]
+    taker_fee: rust_decimal::Decimal,
+    /// New orders per second allowed per account per market (0 = unlimited)
+    #[clap(long, default_value_t = 0)]
+    max_orders_per_sec: u32,
+    /// Cancels per second allowed per account per market (0 = unlimited)
+    #[clap(long, default_value_t = 0)]
+    max_cancels_per_sec: u32,
 }
 
 #[tokio::main]
@@ -139,313 +473,27 @@ async fn main() -> std::io::Result<()> {
     let args = Args::parse();
     
     // Initialize logging with debug level when DEBUG=1 env var is set
-    let env = std::env::var("DEBUG").unwrap_or_else(|_| "0".to_string());
-    let log_level = if env == "1" { Level::DEBUG } else { Level::INFO };
-    
-    let env_filter = EnvFilter::builder()
-        .with_default_directive(log_level.into())
-        .parse("tower_http=debug,api_gateway=debug")
-        .unwrap();
-    
-    let subscriber = FmtSubscriber::builder()
-        .with_env_filter(env_filter)
-        .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
-        .finish();
-        
-    tracing::subscriber::set_global_default(subscriber)
-        .expect("Failed to set tracing subscriber");
-        
-    debug!("Debug logging enabled");
-    
-    // Initialize services
-    let _config = AppConfig::new();
-    let mut matching_engine = MatchingEngine::new();
-    let account_service = Arc::new(AccountService::new());
-    let market_data_service = Arc::new(MarketDataService::new());
-    
-    // Register markets
-    let btc_usd = Market {
-        symbol: "BTC/USD".to_string(),
-        base_asset: "BTC".to_string(),
-        quote_asset: "USD".to_string(),
-        price_tick: rust_decimal_macros::dec!(0.01),
-        quantity_step: rust_decimal_macros::dec!(0.0001),
-        min_order_size: rust_decimal_macros::dec!(10.0),
-        max_price_deviation: 10.0,
-        trading_enabled: true,
-    };
-    
-    matching_engine.register_market(btc_usd.symbol.clone());
+    let log_level = init_tracing("tower_http=debug,api_gateway=debug");
     
-    // Initialize service start time for uptime tracking
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    START_TIME.store(now, Ordering::Relaxed);
-    
-    // Create app state
-    let state = Arc::new(AppState {
-        matching_engine: Arc::new(matching_engine),
-        account_service,
-        market_data_service,
-        markets: vec![btc_usd],
-    });
-    
-    // Set up CORS
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
-    
-    // Set up API routes
-    let api_routes = Router::new()
-        // Health check endpoint
-        .route("/health", get(health_check))
-        
-        // Account routes
-        .route("/accounts", post(create_account))
-        .route("/accounts/:id", get(get_account))
-        .route("/accounts/:id/balances", get(get_balances))
-        .route("/accounts/:id/deposit", post(deposit))
-        .route("/accounts/:id/withdraw", post(withdraw))
-        
-        // Market routes
-        .route("/markets", get(get_markets))
-        .route("/markets/:market/order-book", get(get_order_book))
-        .route("/markets/:market/ticker", get(get_ticker))
-        .route("/markets/:market/trades", get(get_trades))
-        .route("/markets/:market/candles", get(get_candles))
-        .route("/markets/tickers", get(get_tickers))        
-        
-        // Order routes
-        .route("/orders", post(place_order))
-        .route("/orders/:id", get(get_order))
-        .route("/orders/:id", post(cancel_order))
-        .route("/accounts/:id/orders", get(get_orders));
-    
-    
-    // Set up websocket route
-    let ws_routes = Router::new()
-        .route("/ws", get(ws_handler));
+    let fee_schedule = FeeSchedule::new(args.maker_fee, args.taker_fee)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()))?;
     
     // Set up Swagger UI
     let swagger_ui = SwaggerUi::new("/swagger-ui")
-        .url("/api-docs/openapi.json", ApiDoc::openapi());
+        .url("/api-docs/openapi.json", ApiDoc::openapi())
+        .url("/api-docs/v2/openapi.json", versioning::openapi_for(versioning::ApiVersion::V2, ApiDoc::openapi()));
     
-    // Combine all routes
-    let app = Router::new()
-        .nest("/api/v1", api_routes)
-        .merge(ws_routes)
-        .merge(swagger_ui)
-        .layer(cors)
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(
-                    DefaultMakeSpan::new()
-                        .level(log_level)
-                )
-                .on_request(DefaultOnRequest::new().level(log_level))
-                .on_response(DefaultOnResponse::new().level(log_level))
-        )
-        .with_state(state);
-    
-    // Start the server
-    let addr: std::net::SocketAddr = args.addr.parse().expect("Invalid address");
-    let listener = TcpListener::bind(addr).await?;
-    info!("Listening on {}", addr);
+    // Build the services, background tasks and routes
+    let server = Runtime::from_config(AppConfig::new())
+        .with_fee_schedule(fee_schedule)
+        .with_throttle(ThrottleConfig::new(args.max_orders_per_sec, args.max_cancels_per_sec))
+        .with_routes(Router::from(swagger_ui))
+        .with_log_level(log_level)
+        .build()
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
     
     // Run until interrupt signal
-    axum::serve(listener, app).with_graceful_shutdown(shutdown_signal()).await?;
-    
-    Ok(())
-}
-
-/// App state shared across handlers
-pub struct AppState {
-    /// Matching engine
-    pub matching_engine: Arc<MatchingEngine>,
-    /// Account service
-    pub account_service: Arc<AccountService>,
-    /// Market data service
-    pub market_data_service: Arc<MarketDataService>,
-    /// Available markets
-    pub markets: Vec<Market>,
-}
-
-// Static variable to track service start time
-static START_TIME: AtomicU64 = AtomicU64::new(0);
-
-// Health check endpoint
-async fn health_check(
-    State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
-    let start_time = Instant::now();
-    
-    // Initialize status for each service
-    let mut matching_engine_status = "unknown";
-    let mut account_service_status = "unknown";
-    let mut market_data_status = "unknown";
-    let mut matching_engine_latency = 0;
-    let mut account_service_latency = 0;
-    let mut market_data_latency = 0;
-    
-    // Check if matching engine is responsive
-    let me_start = Instant::now();
-    matching_engine_status = match state.matching_engine.get_market_depth("BTC/USD", 1) {
-        Ok(_) => "up",
-        Err(_) => "down",
-    };
-    matching_engine_latency = me_start.elapsed().as_millis() as u64;
-    
-    // Check if account service is responsive
-    let as_start = Instant::now();
-    account_service_status = match state.account_service.get_account(Uuid::nil()).await {
-        // Any response means the service is working, even NotFound for a nil UUID
-        Ok(_) => "up",
-        Err(common::error::Error::AccountNotFound(_)) => "up",
-        Err(_) => "down",
-    };
-    account_service_latency = as_start.elapsed().as_millis() as u64;
-    
-    // Check if market data service is responsive
-    let md_start = Instant::now();
-    market_data_status = if state.market_data_service.get_ticker("BTC/USD").is_some() ||
-                           state.market_data_service.get_all_tickers().len() > 0 {
-        "up"
-    } else {
-        "down"
-    };
-    market_data_latency = md_start.elapsed().as_millis() as u64;
-    
-    // Overall status depends on all services
-    let overall_status = if matching_engine_status == "up" && 
-                           account_service_status == "up" && 
-                           market_data_status == "up" {
-        "healthy"
-    } else {
-        "degraded"
-    };
-    
-    // Count available markets
-    let available_markets = state.markets.len();
-    let active_markets = state.markets.iter()
-        .filter(|m| m.trading_enabled)
-        .count();
-    
-    // Get system metrics
-    let memory_usage = get_memory_usage_mb();
-    let uptime = get_uptime_seconds();
-    
-    // Total response time for this health check
-    let total_latency = start_time.elapsed().as_millis() as u64;
-    
-    // Build the health information JSON
-    let health_info = serde_json::json!({
-        "status": overall_status,
-        "version": env!("CARGO_PKG_VERSION"),
-        "timestamp": chrono::Utc::now().to_rfc3339(),
-        "uptime_seconds": uptime,
-        "services": {
-            "matching_engine": {
-                "status": matching_engine_status,
-                "latency_ms": matching_engine_latency
-            },
-            "account_service": {
-                "status": account_service_status,
-                "latency_ms": account_service_latency
-            },
-            "market_data_service": {
-                "status": market_data_status,
-                "latency_ms": market_data_latency
-            }
-        },
-        "markets": {
-            "total": available_markets,
-            "active": active_markets
-        },
-        "system": {
-            "memory_usage_mb": memory_usage,
-        },
-        "health_check_latency_ms": total_latency
-    });
-    
-    if overall_status == "healthy" {
-        (axum::http::StatusCode::OK, Json(health_info))
-    } else {
-        (axum::http::StatusCode::SERVICE_UNAVAILABLE, Json(health_info))
-    }
-}
-
-// Helper function to get uptime in seconds
-fn get_uptime_seconds() -> u64 {
-    let current_start = START_TIME.load(Ordering::Relaxed);
-    if current_start == 0 {
-        // First call, initialize start time
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        START_TIME.store(now, Ordering::Relaxed);
-        return 0;
-    }
-    
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    
-    now.saturating_sub(current_start)
-}
-
-// Helper function to get memory usage in MB
-fn get_memory_usage_mb() -> u64 {
-    #[cfg(target_os = "linux")]
-    {
-        use std::fs::File;
-        use std::io::Read;
-        
-        if let Ok(mut file) = File::open("/proc/self/status") {
-            let mut contents = String::new();
-            if let Ok(_) = file.read_to_string(&mut contents) {
-                if let Some(line) = contents.lines().find(|l| l.starts_with("VmRSS:")) {
-                    if let Some(kb_str) = line.split_whitespace().nth(1) {
-                        if let Ok(kb) = kb_str.parse::<u64>() {
-                            return kb / 1024; // Convert KB to MB
-                        }
-                    }
-                }
-            }
-        }
-    }
-    
-    // Default if we can't get the actual usage or not on Linux
-    0
+    let addr: std::net::SocketAddr = args.addr.parse().expect("Invalid address");
+    server.serve(addr).await
 }
-
-/// Graceful shutdown signal handler
-async fn shutdown_signal() {
-    let ctrl_c = async {
-        signal::ctrl_c()
-            .await
-            .expect("Failed to install Ctrl+C handler");
-    };
-
-    #[cfg(unix)]
-    let terminate = async {
-        signal::unix::signal(signal::unix::SignalKind::terminate())
-            .expect("Failed to install signal handler")
-            .recv()
-            .await;
-    };
-
-    #[cfg(not(unix))]
-    let terminate = std::future::pending::<()>();
-
-    tokio::select! {
-        _ = ctrl_c => {},
-        _ = terminate => {},
-    }
-
-    info!("Shutdown signal received, starting graceful shutdown");
-}
\ No newline at end of file
@@ -0,0 +1,40 @@
+//! Matching engine as the source market data repairs gaps from
+
+use std::sync::Arc;
+
+use common::model::trade::Trade;
+use market_data::sync::{Levels, SyncSource};
+use matching_engine::MatchingEngine;
+
+/// Order book levels re-synced after a gap
+const RESYNC_DEPTH: usize = 10;
+
+/// Serves the engine's numbered trades and order books to market data
+pub struct EngineSyncSource {
+    matching_engine: Arc<MatchingEngine>,
+}
+
+impl EngineSyncSource {
+    /// Repair gaps from `matching_engine`
+    pub fn new(matching_engine: Arc<MatchingEngine>) -> Self {
+        Self { matching_engine }
+    }
+}
+
+impl SyncSource for EngineSyncSource {
+    fn markets(&self) -> Vec<String> {
+        self.matching_engine.markets()
+    }
+
+    fn last_trade_sequence(&self, market: &str) -> Option<u64> {
+        self.matching_engine.last_trade_sequence(market).ok()
+    }
+
+    fn trades_after(&self, market: &str, sequence: u64) -> Vec<Trade> {
+        self.matching_engine.trades_after(market, sequence).unwrap_or_default()
+    }
+
+    fn depth(&self, market: &str) -> Option<(Levels, Levels)> {
+        self.matching_engine.get_market_depth(market, RESYNC_DEPTH).ok()
+    }
+}
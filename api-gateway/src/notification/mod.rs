@@ -0,0 +1,352 @@
+//! Account notifications
+//!
+//! Accounts choose which events they are told about (fills, withdrawals and
+//! logins from a new client address) and where: an email address, a URL, or
+//! both. Notifications are queued and sent by a background worker through
+//! every [`Notifier`] the account has an address for, so raising one never
+//! waits on a mail server or receiver. When the queue is full, notifications
+//! are dropped rather than hold up trading.
+
+mod smtp;
+mod webhook;
+
+pub use smtp::{SmtpConfig, SmtpNotifier};
+pub use webhook::WebhookNotifier;
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::thread;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::decimal::{format_price, format_quantity};
+use common::error::{Error, Result};
+use common::model::order::Side;
+use dashmap::DashMap;
+use matching_engine::{EngineEvent, MatchingEngine};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Most client addresses remembered per account for login notifications
+const MAX_KNOWN_ADDRESSES: usize = 100;
+
+/// Kind of event an account can be notified about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// One of the account's orders traded
+    Fill,
+    /// Funds were withdrawn
+    Withdrawal,
+    /// The account's API key was used from a new client address
+    Login,
+}
+
+impl NotificationKind {
+    /// Name sent in payloads and email subjects
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotificationKind::Fill => "fill",
+            NotificationKind::Withdrawal => "withdrawal",
+            NotificationKind::Login => "login",
+        }
+    }
+}
+
+/// Where and about what an account is notified
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NotificationPreferences {
+    /// Email address notifications are sent to
+    #[serde(default)]
+    pub email: Option<String>,
+    /// URL notifications are posted to
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    /// Events the account is notified about
+    #[serde(default)]
+    pub events: Vec<NotificationKind>,
+}
+
+/// A notification to one account
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Notification {
+    /// Notification ID
+    pub id: Uuid,
+    /// Account notified
+    pub account_id: Uuid,
+    /// Kind of event
+    pub kind: NotificationKind,
+    /// One-line summary, used as the email subject
+    pub subject: String,
+    /// Event details
+    pub data: Value,
+    /// When the event happened
+    pub created_at: DateTime<Utc>,
+}
+
+/// A channel notifications are sent over
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Channel name, used in logs
+    fn name(&self) -> &str;
+
+    /// Where an account is reached on this channel, if it gave an address for it
+    fn address<'a>(&self, preferences: &'a NotificationPreferences) -> Option<&'a str>;
+
+    /// Send a notification to an address
+    async fn send(&self, address: &str, notification: &Notification) -> Result<()>;
+}
+
+/// Notification queue and channel settings
+#[derive(Debug, Clone)]
+pub struct NotificationConfig {
+    /// Notifications waiting to be sent before new ones are dropped
+    pub queue_capacity: usize,
+    /// Time allowed for each send
+    pub timeout: Duration,
+    /// Mail server for email notifications, which are disabled when unset
+    pub smtp: Option<SmtpConfig>,
+    /// Accept plain `http://` notification URLs, for local development
+    pub allow_http: bool,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            queue_capacity: 1024,
+            timeout: Duration::from_secs(10),
+            smtp: None,
+            allow_http: false,
+        }
+    }
+}
+
+impl NotificationConfig {
+    /// Build the configured channels
+    pub fn notifiers(&self) -> Vec<Arc<dyn Notifier>> {
+        let mut notifiers: Vec<Arc<dyn Notifier>> = vec![Arc::new(WebhookNotifier::new(self.timeout))];
+        if let Some(smtp) = &self.smtp {
+            notifiers.push(Arc::new(SmtpNotifier::new(smtp.clone(), self.timeout)));
+        }
+        notifiers
+    }
+}
+
+/// Notification preferences, the send queue and its worker
+pub struct NotificationService {
+    config: NotificationConfig,
+    engine: Arc<MatchingEngine>,
+    notifiers: Vec<Arc<dyn Notifier>>,
+    preferences: DashMap<Uuid, NotificationPreferences>,
+    /// Client addresses each account has used its API key from
+    known_addresses: DashMap<Uuid, HashSet<String>>,
+    queue: mpsc::Sender<Notification>,
+    /// Queue end handed to the worker when it starts
+    receiver: Mutex<Option<mpsc::Receiver<Notification>>>,
+    /// Set once the worker and engine watcher are running
+    started: OnceLock<()>,
+    dropped: AtomicU64,
+}
+
+impl NotificationService {
+    /// Create the service for an engine, sending through the given channels
+    ///
+    /// Nothing is sent or watched until the first account sets its preferences.
+    pub fn new(engine: Arc<MatchingEngine>, config: NotificationConfig, notifiers: Vec<Arc<dyn Notifier>>) -> Arc<Self> {
+        let (queue, receiver) = mpsc::channel(config.queue_capacity.max(1));
+        Arc::new(Self {
+            config,
+            engine,
+            notifiers,
+            preferences: DashMap::new(),
+            known_addresses: DashMap::new(),
+            queue,
+            receiver: Mutex::new(Some(receiver)),
+            started: OnceLock::new(),
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// An account's preferences, notifying it of nothing by default
+    pub fn preferences(&self, account_id: Uuid) -> NotificationPreferences {
+        self.preferences.get(&account_id).map(|preferences| preferences.clone()).unwrap_or_default()
+    }
+
+    /// Replace an account's preferences
+    ///
+    /// Must be called from within a Tokio runtime, which the worker then runs on.
+    pub fn set_preferences(self: &Arc<Self>, account_id: Uuid, mut preferences: NotificationPreferences) -> Result<NotificationPreferences> {
+        if let Some(email) = &preferences.email {
+            validate_email(email)?;
+        }
+        if let Some(url) = &preferences.webhook_url {
+            let parsed = reqwest::Url::parse(url)
+                .map_err(|e| Error::ValidationError(format!("Invalid notification URL {}: {}", url, e)))?;
+            match parsed.scheme() {
+                "https" => {}
+                "http" if self.config.allow_http => {}
+                scheme => {
+                    return Err(Error::ValidationError(format!("Notification URLs must use https, not {}", scheme)));
+                }
+            }
+            preferences.webhook_url = Some(parsed.to_string());
+        }
+        preferences.events.sort_by_key(|kind| kind.name());
+        preferences.events.dedup();
+
+        if !preferences.events.contains(&NotificationKind::Login) {
+            self.known_addresses.remove(&account_id);
+        }
+        self.preferences.insert(account_id, preferences.clone());
+        self.start();
+        Ok(preferences)
+    }
+
+    /// Notifications waiting to be sent
+    pub fn queued(&self) -> usize {
+        self.queue.max_capacity() - self.queue.capacity()
+    }
+
+    /// Notifications dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Queue a notification if the account wants this kind and can be reached
+    pub fn notify(&self, account_id: Uuid, kind: NotificationKind, subject: String, data: Value) {
+        let Some(preferences) = self.preferences.get(&account_id) else {
+            return;
+        };
+        if !preferences.events.contains(&kind)
+            || !self.notifiers.iter().any(|notifier| notifier.address(&preferences).is_some())
+        {
+            return;
+        }
+        drop(preferences);
+
+        let notification = Notification {
+            id: Uuid::new_v4(),
+            account_id,
+            kind,
+            subject,
+            data,
+            created_at: Utc::now(),
+        };
+        if let Err(e) = self.queue.try_send(notification) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            warn!("Dropped {} notification for {}: {}", kind.name(), account_id, e);
+        }
+    }
+
+    /// Note an authenticated request, notifying the account the first time a
+    /// client address is seen
+    pub fn record_login(&self, account_id: Uuid, client: &str) {
+        let wants_logins = self.preferences.get(&account_id)
+            .is_some_and(|preferences| preferences.events.contains(&NotificationKind::Login));
+        if !wants_logins {
+            return;
+        }
+
+        let mut addresses = self.known_addresses.entry(account_id).or_default();
+        if addresses.contains(client) {
+            return;
+        }
+        if addresses.len() == MAX_KNOWN_ADDRESSES {
+            addresses.clear();
+        }
+        addresses.insert(client.to_string());
+        drop(addresses);
+
+        self.notify(
+            account_id,
+            NotificationKind::Login,
+            format!("New sign-in from {}", client),
+            json!({ "address": client }),
+        );
+    }
+
+    /// Start the send worker and turn engine fills into notifications
+    fn start(self: &Arc<Self>) {
+        if self.started.set(()).is_err() {
+            return;
+        }
+        let Some(mut receiver) = self.receiver.lock().unwrap().take() else {
+            return;
+        };
+
+        let service: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            while let Some(notification) = receiver.recv().await {
+                let Some(service) = service.upgrade() else {
+                    break;
+                };
+                service.send(&notification).await;
+            }
+        });
+
+        let events = self.engine.subscribe_events();
+        let service: Weak<Self> = Arc::downgrade(self);
+        thread::Builder::new()
+            .name("notifications".to_string())
+            .spawn(move || {
+                for event in events {
+                    let Some(service) = service.upgrade() else {
+                        break;
+                    };
+                    if let EngineEvent::Trade(trade) = &event {
+                        for (account_id, side) in [(trade.buyer_id, Side::Buy), (trade.seller_id, Side::Sell)] {
+                            service.notify(
+                                account_id,
+                                NotificationKind::Fill,
+                                format!(
+                                    "{:?} {} {} at {}",
+                                    side,
+                                    format_quantity(&trade.market, trade.quantity),
+                                    trade.market,
+                                    format_price(&trade.market, trade.price),
+                                ),
+                                json!({ "trade": trade, "side": side }),
+                            );
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn notification thread");
+    }
+
+    /// Send a notification through every channel the account has an address for
+    async fn send(&self, notification: &Notification) {
+        let preferences = self.preferences(notification.account_id);
+        for notifier in &self.notifiers {
+            let Some(address) = notifier.address(&preferences) else {
+                continue;
+            };
+            match notifier.send(address, notification).await {
+                Ok(()) => debug!("Sent {} notification {} by {}", notification.kind.name(), notification.id, notifier.name()),
+                Err(e) => warn!(
+                    "Failed to send {} notification {} by {}: {}",
+                    notification.kind.name(), notification.id, notifier.name(), e
+                ),
+            }
+        }
+    }
+}
+
+/// Accept a plain `user@domain` address that cannot smuggle mail headers
+fn validate_email(email: &str) -> Result<()> {
+    let valid = email.split_once('@').is_some_and(|(user, domain)| {
+        !user.is_empty() && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.')
+    }) && email.chars().all(|c| c.is_ascii_graphic() && !matches!(c, '<' | '>' | ',' | ';'));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::ValidationError(format!("Invalid email address: {}", email)))
+    }
+}
@@ -0,0 +1,147 @@
+//! Email notifications over SMTP
+//!
+//! Speaks plain SMTP to a relay, one connection per message, authenticating
+//! with `AUTH PLAIN` when credentials are configured. The connection is not
+//! encrypted, so the relay should run next to the gateway and take care of
+//! delivery onwards.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use common::error::{Error, Result};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use super::{Notification, NotificationPreferences, Notifier};
+
+/// Mail relay settings
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    /// Relay host name or address
+    pub host: String,
+    /// Relay port
+    pub port: u16,
+    /// Sender address
+    pub from: String,
+    /// User name for `AUTH PLAIN`, if the relay requires it
+    pub username: Option<String>,
+    /// Password for `AUTH PLAIN`
+    pub password: Option<String>,
+}
+
+/// Emails notifications to the account's address
+pub struct SmtpNotifier {
+    config: SmtpConfig,
+    timeout: Duration,
+}
+
+impl SmtpNotifier {
+    /// Create a notifier allowing `timeout` per message
+    pub fn new(config: SmtpConfig, timeout: Duration) -> Self {
+        Self { config, timeout }
+    }
+
+    /// Hold one SMTP conversation delivering a message
+    async fn deliver(&self, to: &str, message: &str) -> Result<()> {
+        let stream = TcpStream::connect((self.config.host.as_str(), self.config.port)).await
+            .map_err(|e| Error::Internal(format!("SMTP connect to {} failed: {}", self.config.host, e)))?;
+        let mut session = Session { stream: BufReader::new(stream) };
+
+        session.expect(220).await?;
+        session.command("EHLO zavora-gateway", 250).await?;
+        if let Some(username) = &self.config.username {
+            let password = self.config.password.as_deref().unwrap_or_default();
+            let credentials = BASE64.encode(format!("\0{}\0{}", username, password));
+            session.command(&format!("AUTH PLAIN {}", credentials), 235).await?;
+        }
+        session.command(&format!("MAIL FROM:<{}>", self.config.from), 250).await?;
+        session.command(&format!("RCPT TO:<{}>", to), 250).await?;
+        session.command("DATA", 354).await?;
+        session.command(&format!("{}\r\n.", message), 250).await?;
+        session.command("QUIT", 221).await
+    }
+
+    /// Headers and text body of a notification email, dot-stuffed for `DATA`
+    fn message(&self, to: &str, notification: &Notification) -> String {
+        let details = serde_json::to_string_pretty(&notification.data).unwrap_or_default();
+        let body = format!("{}\n\n{}\n", notification.subject, details);
+
+        let mut message = format!(
+            "From: <{}>\r\nTo: <{}>\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}@zavora>\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\r\n",
+            self.config.from,
+            to,
+            header_safe(&notification.subject),
+            notification.created_at.to_rfc2822(),
+            notification.id,
+        );
+        for line in body.lines() {
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message.truncate(message.len() - 2);
+        message
+    }
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    fn address<'a>(&self, preferences: &'a NotificationPreferences) -> Option<&'a str> {
+        preferences.email.as_deref()
+    }
+
+    async fn send(&self, address: &str, notification: &Notification) -> Result<()> {
+        let message = self.message(address, notification);
+        tokio::time::timeout(self.timeout, self.deliver(address, &message)).await
+            .map_err(|_| Error::Internal(format!("SMTP delivery to {} timed out", self.config.host)))?
+    }
+}
+
+/// An open SMTP connection
+struct Session {
+    stream: BufReader<TcpStream>,
+}
+
+impl Session {
+    /// Send a command and require the given reply code
+    async fn command(&mut self, command: &str, code: u16) -> Result<()> {
+        self.stream.get_mut().write_all(format!("{}\r\n", command).as_bytes()).await
+            .map_err(|e| Error::Internal(format!("SMTP write failed: {}", e)))?;
+        self.expect(code).await
+    }
+
+    /// Read a possibly multi-line reply and require the given code
+    async fn expect(&mut self, code: u16) -> Result<()> {
+        loop {
+            let mut line = String::new();
+            let read = self.stream.read_line(&mut line).await
+                .map_err(|e| Error::Internal(format!("SMTP read failed: {}", e)))?;
+            if read == 0 {
+                return Err(Error::Internal("SMTP server closed the connection".to_string()));
+            }
+
+            let reply: Option<u16> = line.get(..3).and_then(|digits| digits.parse().ok());
+            if reply != Some(code) {
+                return Err(Error::Internal(format!("SMTP server replied {}", line.trim_end())));
+            }
+            // A dash after the code marks a line other than the last
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Drop line breaks so a value cannot start a new header
+fn header_safe(value: &str) -> String {
+    value.chars().filter(|c| !matches!(c, '\r' | '\n')).collect()
+}
@@ -0,0 +1,60 @@
+//! Notifications posted to a URL
+//!
+//! Sends each notification once as a JSON `POST` to the account's
+//! notification URL. Unlike account webhooks, deliveries are not signed or
+//! retried; they are meant for chat and paging integrations.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::error::{Error, Result};
+
+use super::{Notification, NotificationPreferences, Notifier};
+
+/// Header carrying the notification ID
+pub const NOTIFICATION_HEADER: &str = "x-notification-id";
+
+/// Posts notifications to the account's URL
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    timeout: Duration,
+}
+
+impl WebhookNotifier {
+    /// Create a notifier allowing `timeout` per request
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            timeout,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn address<'a>(&self, preferences: &'a NotificationPreferences) -> Option<&'a str> {
+        preferences.webhook_url.as_deref()
+    }
+
+    async fn send(&self, address: &str, notification: &Notification) -> Result<()> {
+        let response = self.client
+            .post(address)
+            .timeout(self.timeout)
+            .header("content-type", "application/json")
+            .header(NOTIFICATION_HEADER, notification.id.to_string())
+            .body(serde_json::to_string(notification)?)
+            .send()
+            .await
+            .map_err(|e| Error::Internal(format!("Notification request failed: {}", e)))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(Error::Internal(format!("Receiver responded with {}", response.status())))
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! JSON number formats
+//!
+//! Prices, quantities and amounts are always serialized as decimal strings,
+//! but ratios and percentages such as `spread_bps` or `change_24h_percent` are
+//! JSON numbers. Clients that parse every fractional value as a decimal can
+//! ask for the `decimal-strings` format, which writes those as strings too, so
+//! no fractional JSON number is ever sent. Integers such as sequences and
+//! counts stay numbers in both formats.
+//!
+//! REST clients pick a format with the `profile` parameter of `Accept`
+//! (`Accept: application/json; profile="decimal-strings"`) and WebSocket
+//! clients with the `numbers` parameter of `hello`. `JSON_NUMBER_FORMAT` sets
+//! the format of clients that do neither.
+
+use std::fmt;
+use std::str::FromStr;
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rust_decimal::Decimal;
+use serde_json::Value;
+use tracing::warn;
+
+/// How fractional JSON numbers are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// Decimals as strings, ratios and percentages as numbers
+    #[default]
+    Native,
+    /// Every fractional number as a decimal string
+    DecimalStrings,
+}
+
+impl NumberFormat {
+    /// Name used in `Accept` profiles, `hello` and configuration
+    pub fn name(&self) -> &'static str {
+        match self {
+            NumberFormat::Native => "native",
+            NumberFormat::DecimalStrings => "decimal-strings",
+        }
+    }
+
+    /// Format asked for by the `profile` parameter of a request's `Accept` header
+    pub fn from_accept(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .flat_map(|media_range| media_range.split(';').skip(1))
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("profile"))
+            .and_then(|(_, value)| value.trim().trim_matches('"').parse().ok())
+    }
+
+    /// Rewrite a JSON value in this format
+    pub fn apply(&self, value: &mut Value) {
+        if *self == NumberFormat::DecimalStrings {
+            stringify_fractions(value);
+        }
+    }
+
+    /// Rewrite serialized JSON in this format, leaving anything else untouched
+    pub fn apply_to_text(&self, text: String) -> String {
+        if *self == NumberFormat::Native {
+            return text;
+        }
+        match serde_json::from_str::<Value>(&text) {
+            Ok(mut value) => {
+                self.apply(&mut value);
+                value.to_string()
+            }
+            Err(_) => text,
+        }
+    }
+}
+
+impl fmt::Display for NumberFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for NumberFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "native" => Ok(NumberFormat::Native),
+            "decimal-strings" => Ok(NumberFormat::DecimalStrings),
+            other => Err(format!("Unknown number format: {}", other)),
+        }
+    }
+}
+
+/// Replace every fractional number with its shortest decimal string
+fn stringify_fractions(value: &mut Value) {
+    match value {
+        Value::Number(number) if number.is_f64() => {
+            // The shortest text that reads back as the same float, without an exponent
+            let text = number.to_string();
+            let decimal = if text.contains(['e', 'E']) {
+                Decimal::from_scientific(&text)
+            } else {
+                Decimal::from_str(&text)
+            };
+            *value = Value::String(decimal.map(|decimal| decimal.normalize().to_string()).unwrap_or(text));
+        }
+        Value::Array(items) => items.iter_mut().for_each(stringify_fractions),
+        Value::Object(fields) => fields.values_mut().for_each(stringify_fractions),
+        _ => {}
+    }
+}
+
+/// Rewrite JSON responses in the format the request asks for, or `default`
+pub async fn format_numbers(State(default): State<NumberFormat>, request: Request, next: Next) -> Response {
+    let format = NumberFormat::from_accept(request.headers()).unwrap_or(default);
+    let mut response = next.run(request).await;
+    response.headers_mut().append(header::VARY, HeaderValue::from_static("accept"));
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if format == NumberFormat::Native || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Failed to read response body: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let Ok(mut value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    format.apply(&mut value);
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    if let Ok(content_type) = HeaderValue::from_str(&format!("application/json; profile=\"{}\"", format)) {
+        parts.headers.insert(header::CONTENT_TYPE, content_type);
+    }
+    // Another representation of the same data: a weak tag still revalidates
+    if let Some(etag) = parts.headers.get(header::ETAG).and_then(|etag| etag.to_str().ok()) {
+        if !etag.starts_with("W/") {
+            if let Ok(weak) = HeaderValue::from_str(&format!("W/{}", etag)) {
+                parts.headers.insert(header::ETAG, weak);
+            }
+        }
+    }
+
+    Response::from_parts(parts, Body::from(value.to_string()))
+}
@@ -0,0 +1,139 @@
+//! Order audit trail
+//!
+//! Turns the engine's order events into status changes (`New`,
+//! `PartiallyFilled`, `Filled`, `Cancelled`, `Expired`, `Rejected`) and saves
+//! them, in the order they happened, to the account service's order history.
+//! Events that only move a pegged order's price are not status changes and
+//! are skipped. Changes are saved in the background; readers wait for what
+//! the engine has already emitted with [`OrderHistory::flush`].
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use account_service::AccountService;
+use common::decimal::Quantity;
+use common::model::order::{Order, OrderTransition, Status};
+use dashmap::DashMap;
+use matching_engine::{EngineEvent, MatchingEngine};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Longest [`OrderHistory::flush`] waits for the changes to be saved
+pub const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Records the status changes of every order the engine processes
+pub struct OrderHistory {
+    /// Status and filled quantity last recorded of orders still open
+    open: DashMap<Uuid, (Status, Quantity)>,
+    /// Engine events not yet taken up by the recording thread
+    backlog: Box<dyn Fn() -> usize + Send + Sync>,
+    /// Events taken up whose changes are not saved yet
+    pending: AtomicUsize,
+}
+
+impl OrderHistory {
+    /// Record an engine's order events into `account_service` in the background
+    ///
+    /// Saving needs a Tokio runtime; without one nothing is recorded. The
+    /// background thread exits once the engine is dropped.
+    pub fn start(engine: &MatchingEngine, account_service: Arc<AccountService>) -> Arc<Self> {
+        let Ok(runtime) = Handle::try_current() else {
+            warn!("Order history not recorded: no Tokio runtime");
+            return Arc::new(Self::new(Box::new(|| 0)));
+        };
+
+        let events = engine.subscribe_events();
+        let backlog = events.clone();
+        let history = Arc::new(Self::new(Box::new(move || backlog.len())));
+
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Vec<OrderTransition>>();
+        let saver = history.clone();
+        runtime.spawn(async move {
+            while let Some(transitions) = receiver.recv().await {
+                if let Err(e) = account_service.record_order_transitions(&transitions).await {
+                    warn!("Failed to record {} order status changes: {}", transitions.len(), e);
+                }
+                saver.pending.fetch_sub(1, Ordering::AcqRel);
+            }
+        });
+
+        let worker = history.clone();
+        thread::Builder::new()
+            .name("order-history".to_string())
+            .spawn(move || {
+                for event in events {
+                    worker.pending.fetch_add(1, Ordering::AcqRel);
+                    let transitions = worker.transitions(&event);
+                    if transitions.is_empty() {
+                        worker.pending.fetch_sub(1, Ordering::AcqRel);
+                    } else if sender.send(transitions).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to spawn order history thread");
+
+        history
+    }
+
+    fn new(backlog: Box<dyn Fn() -> usize + Send + Sync>) -> Self {
+        Self {
+            open: DashMap::new(),
+            backlog,
+            pending: AtomicUsize::new(0),
+        }
+    }
+
+    /// Wait until the changes of the events the engine emitted so far are
+    /// saved, or [`FLUSH_TIMEOUT`] passes
+    pub async fn flush(&self) {
+        let deadline = Instant::now() + FLUSH_TIMEOUT;
+        while ((self.backlog)() > 0 || self.pending.load(Ordering::Acquire) > 0) && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    /// Status changes an engine event makes, oldest first
+    pub fn transitions(&self, event: &EngineEvent) -> Vec<OrderTransition> {
+        match event {
+            EngineEvent::OrderPlaced(order) => {
+                // Rejected orders were never accepted
+                let mut transitions = Vec::new();
+                let mut previous = None;
+                if order.status != Status::Rejected {
+                    transitions.push(OrderTransition::accepted(order));
+                    previous = Some((Status::New, Quantity::ZERO));
+                }
+                if order.status != Status::New {
+                    transitions.push(OrderTransition::new(order, previous));
+                }
+                self.track(order);
+                transitions
+            }
+            EngineEvent::OrderUpdated(order)
+            | EngineEvent::OrderCancelled(order)
+            | EngineEvent::OrderExpired(order) => {
+                let previous = self.open.get(&order.id).map(|entry| *entry);
+                if previous == Some((order.status, order.filled_quantity)) {
+                    return Vec::new();
+                }
+                self.track(order);
+                vec![OrderTransition::new(order, previous)]
+            }
+            EngineEvent::Trade(_) | EngineEvent::SessionChanged(_) => Vec::new(),
+        }
+    }
+
+    /// Remember an open order's recorded state, forgetting closed orders
+    fn track(&self, order: &Order) {
+        if order.is_active() {
+            self.open.insert(order.id, (order.status, order.filled_quantity));
+        } else {
+            self.open.remove(&order.id);
+        }
+    }
+}
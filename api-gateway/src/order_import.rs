@@ -0,0 +1,311 @@
+//! Bulk order import from CSV
+//!
+//! Operators seed books for demos, or move resting orders over from another
+//! venue, by uploading one order per row. The first row names the columns:
+//!
+//! - `account_id`, `market`, `side` and `quantity` are required
+//! - `price` is required for limit orders
+//! - `order_type` defaults to `limit` with a price and `market` without one
+//! - `time_in_force` defaults to `gtc`
+//! - `reference`, e.g. the order's ID at the other venue, is echoed back
+//!
+//! Every row is checked against its market's filters and its account before
+//! any order is placed. Rows are then placed in file order through the same
+//! path as `POST /orders`, so they reserve funds and may match. A row that
+//! fails is reported and skipped; it does not stop the rest of the import.
+
+use std::collections::HashMap;
+
+use common::decimal::{Price, Quantity};
+use common::model::order::{OrderType, Side, Status, TimeInForce};
+use serde::Serialize;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::api::order::{submit_order, PlaceOrderRequest};
+use crate::error::ApiError;
+use crate::latency::StageTimer;
+use crate::AppState;
+
+/// Most rows accepted in one import
+pub const MAX_IMPORT_ROWS: usize = 10_000;
+
+const REQUIRED_COLUMNS: [&str; 4] = ["account_id", "market", "side", "quantity"];
+const OPTIONAL_COLUMNS: [&str; 4] = ["order_type", "price", "time_in_force", "reference"];
+
+/// Outcome of one row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportRowStatus {
+    /// The order was placed
+    Placed,
+    /// The row passed validation in a dry run
+    Valid,
+    /// The row was invalid or the order was refused
+    Rejected,
+}
+
+/// Result of one CSV row
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportRowResult {
+    /// Line of the row in the file, the header being line 1
+    pub line: usize,
+    /// The row's `reference` column
+    pub reference: Option<String>,
+    /// Outcome
+    pub status: ImportRowStatus,
+    /// ID of the placed order
+    pub order_id: Option<Uuid>,
+    /// Status of the placed order after matching
+    pub order_status: Option<Status>,
+    /// Quantity of the placed order filled on entry
+    pub filled_quantity: Option<Quantity>,
+    /// Error code, as in REST error responses
+    pub error_code: Option<String>,
+    /// Why the row was rejected
+    pub error: Option<String>,
+}
+
+impl ImportRowResult {
+    fn new(line: usize, reference: Option<String>, status: ImportRowStatus) -> Self {
+        Self {
+            line,
+            reference,
+            status,
+            order_id: None,
+            order_status: None,
+            filled_quantity: None,
+            error_code: None,
+            error: None,
+        }
+    }
+
+    fn rejected(line: usize, reference: Option<String>, error: ApiError) -> Self {
+        let mut result = Self::new(line, reference, ImportRowStatus::Rejected);
+        result.error_code = Some(error.code().to_string());
+        result.error = Some(match error {
+            ApiError::Common(e) => e.to_string(),
+            other => other.to_string(),
+        });
+        result
+    }
+}
+
+/// Result of an import
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportSummary {
+    /// Whether rows were only validated
+    pub dry_run: bool,
+    /// Number of order rows in the file
+    pub rows: usize,
+    /// Orders placed
+    pub placed: usize,
+    /// Rows rejected
+    pub rejected: usize,
+    /// Per-row results in file order
+    pub results: Vec<ImportRowResult>,
+}
+
+/// A parsed order row
+struct ImportRow {
+    line: usize,
+    reference: Option<String>,
+    request: Result<PlaceOrderRequest, ApiError>,
+}
+
+/// Validate and, unless `dry_run`, place the orders of a CSV file
+pub async fn import_orders(state: &AppState, csv: &str, dry_run: bool) -> Result<ImportSummary, ApiError> {
+    let mut rows = parse_rows(csv)?;
+
+    // Check every row before placing any, so a dry run reports what a real
+    // import would refuse up front
+    for row in &mut rows {
+        if let Ok(request) = &row.request {
+            if let Err(e) = validate(state, request).await {
+                row.request = Err(e);
+            }
+        }
+    }
+
+    let mut results = Vec::with_capacity(rows.len());
+    for row in rows {
+        let request = match row.request {
+            Ok(request) => request,
+            Err(e) => {
+                results.push(ImportRowResult::rejected(row.line, row.reference, e));
+                continue;
+            }
+        };
+        if dry_run {
+            results.push(ImportRowResult::new(row.line, row.reference, ImportRowStatus::Valid));
+            continue;
+        }
+
+        let placed = match request.into_order(state.matching_engine.id_generator().as_ref()) {
+            Ok(order) => submit_order(state, order, &mut StageTimer::start()).await,
+            Err(e) => Err(e),
+        };
+        results.push(match placed {
+            Ok(placed) => {
+                let mut result = ImportRowResult::new(row.line, row.reference, ImportRowStatus::Placed);
+                result.order_id = Some(placed.order.id);
+                result.order_status = Some(placed.order.status);
+                result.filled_quantity = Some(placed.order.filled_quantity);
+                result
+            }
+            Err(e) => ImportRowResult::rejected(row.line, row.reference, e),
+        });
+    }
+
+    let count = |status| results.iter().filter(|result| result.status == status).count();
+    Ok(ImportSummary {
+        dry_run,
+        rows: results.len(),
+        placed: count(ImportRowStatus::Placed),
+        rejected: count(ImportRowStatus::Rejected),
+        results,
+    })
+}
+
+/// Check a row against its market's filters and its account
+async fn validate(state: &AppState, request: &PlaceOrderRequest) -> Result<(), ApiError> {
+    request.check_filters(&state.markets)?;
+
+    state.account_service.get_account(request.user_id).await
+        .map_err(ApiError::Common)?
+        .ok_or_else(|| ApiError::NotFound(format!("Account not found: {}", request.user_id)))?;
+
+    Ok(())
+}
+
+/// Parse the header and order rows, keeping row errors with their line
+fn parse_rows(csv: &str) -> Result<Vec<ImportRow>, ApiError> {
+    let mut lines = csv.lines().enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let (_, header) = lines.next()
+        .ok_or_else(|| ApiError::BadRequest("The file has no header row".to_string()))?;
+    let header = split_line(header).map_err(|e| ApiError::BadRequest(format!("Header: {}", e)))?;
+
+    let mut columns = HashMap::new();
+    for (index, name) in header.iter().enumerate() {
+        let name = name.trim().to_ascii_lowercase();
+        if !REQUIRED_COLUMNS.contains(&name.as_str()) && !OPTIONAL_COLUMNS.contains(&name.as_str()) {
+            return Err(ApiError::BadRequest(format!("Unknown column: {}", name)));
+        }
+        if columns.insert(name.clone(), index).is_some() {
+            return Err(ApiError::BadRequest(format!("Duplicate column: {}", name)));
+        }
+    }
+    if let Some(missing) = REQUIRED_COLUMNS.iter().find(|column| !columns.contains_key(**column)) {
+        return Err(ApiError::BadRequest(format!("Missing column: {}", missing)));
+    }
+
+    let rows: Vec<ImportRow> = lines
+        .map(|(line, text)| {
+            let fields = split_line(text);
+            let field = |name: &str| {
+                let fields = fields.as_ref().ok()?;
+                columns.get(name)
+                    .and_then(|index| fields.get(*index))
+                    .map(|value| value.trim())
+                    .filter(|value| !value.is_empty())
+            };
+            let reference = field("reference").map(str::to_string);
+            let request = match &fields {
+                Ok(fields) if fields.len() != header.len() => Err(format!(
+                    "Expected {} fields, found {}", header.len(), fields.len()
+                )),
+                Ok(_) => parse_request(field),
+                Err(e) => Err(e.clone()),
+            };
+            ImportRow { line, reference, request: request.map_err(ApiError::BadRequest) }
+        })
+        .collect();
+
+    if rows.len() > MAX_IMPORT_ROWS {
+        return Err(ApiError::BadRequest(format!(
+            "{} rows exceed the limit of {} per import", rows.len(), MAX_IMPORT_ROWS
+        )));
+    }
+    Ok(rows)
+}
+
+/// Build an order request from a row's fields
+fn parse_request<'a>(field: impl Fn(&str) -> Option<&'a str>) -> Result<PlaceOrderRequest, String> {
+    let required = |name: &str| field(name).ok_or_else(|| format!("Missing {}", name));
+
+    let user_id = required("account_id")?.parse::<Uuid>()
+        .map_err(|_| "Invalid account_id".to_string())?;
+    let side = match required("side")?.to_ascii_lowercase().as_str() {
+        "buy" => Side::Buy,
+        "sell" => Side::Sell,
+        other => return Err(format!("Invalid side: {}", other)),
+    };
+    let quantity = required("quantity")?.parse::<Quantity>()
+        .map_err(|_| "Invalid quantity".to_string())?;
+    let price = field("price")
+        .map(|price| price.parse::<Price>().map_err(|_| "Invalid price".to_string()))
+        .transpose()?;
+    let order_type = match field("order_type").map(str::to_ascii_lowercase).as_deref() {
+        None if price.is_some() => OrderType::Limit,
+        None => OrderType::Market,
+        Some("limit") => OrderType::Limit,
+        Some("market") => OrderType::Market,
+        Some(other) => return Err(format!("Invalid order_type: {}", other)),
+    };
+    let time_in_force = match field("time_in_force").map(str::to_ascii_lowercase).as_deref() {
+        None | Some("gtc") => TimeInForce::GTC,
+        Some("ioc") => TimeInForce::IOC,
+        Some("fok") => TimeInForce::FOK,
+        Some(other) => return Err(format!("Invalid time_in_force: {}", other)),
+    };
+
+    Ok(PlaceOrderRequest {
+        user_id,
+        market: required("market")?.to_string(),
+        side,
+        order_type,
+        price,
+        quantity,
+        time_in_force,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
+        client_order_id: None,
+    })
+}
+
+/// Split a CSV line into fields, unquoting `"..."` fields
+///
+/// Quoted fields may contain commas and doubled quotes but not line breaks.
+fn split_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            (false, ',') => fields.push(std::mem::take(&mut field)),
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("Unterminated quoted field".to_string());
+    }
+    fields.push(field);
+    Ok(fields)
+}
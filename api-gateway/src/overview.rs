@@ -0,0 +1,168 @@
+//! Operator overview
+//!
+//! Gathers the figures an ops dashboard shows into one payload: each
+//! market's book and today's trading, account and balance totals, live
+//! WebSocket connections and the depths of the queues behind order
+//! placement, so the dashboard makes one request instead of scraping the
+//! separate admin and market data endpoints.
+
+use chrono::{DateTime, Utc};
+use common::decimal::{Price, Quantity};
+use common::error::Result;
+use common::model::account::AccountTotals;
+use common::model::market::SessionState;
+use market_data::CandleInterval;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::AppState;
+
+/// Everything on the operator dashboard
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Overview {
+    /// When the figures were gathered
+    pub generated_at: DateTime<Utc>,
+    /// Markets with a book, in symbol order
+    pub markets: Vec<MarketOverview>,
+    /// Resting orders in every market
+    pub open_orders: usize,
+    /// Accounts and their balances by asset
+    pub accounts: AccountTotals,
+    /// Live WebSocket connections
+    pub websocket: WebSocketOverview,
+    /// Work waiting behind order placement
+    pub queues: QueueDepths,
+}
+
+/// One market's book and trading today
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MarketOverview {
+    /// Market symbol
+    pub market: String,
+    /// Whether the market accepts orders
+    pub trading_enabled: bool,
+    /// Trading session state
+    pub session: SessionState,
+    /// Resting buy orders
+    pub bid_orders: usize,
+    /// Resting sell orders
+    pub ask_orders: usize,
+    /// Price levels with buy orders
+    pub bid_levels: usize,
+    /// Price levels with sell orders
+    pub ask_levels: usize,
+    /// Best bid price
+    pub best_bid: Option<Price>,
+    /// Best ask price
+    pub best_ask: Option<Price>,
+    /// Last trade price
+    pub last_price: Option<Price>,
+    /// Sequence number of the engine's last order
+    pub last_order_sequence: u64,
+    /// Sequence number of the engine's last trade
+    pub last_trade_sequence: u64,
+    /// Trades since midnight UTC
+    pub trades_today: u64,
+    /// Base asset traded since midnight UTC
+    pub volume_today: Quantity,
+    /// Quote asset traded since midnight UTC
+    pub quote_volume_today: Quantity,
+}
+
+/// Live WebSocket connections
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WebSocketOverview {
+    /// Open connections
+    pub connections: usize,
+    /// Connections authorized for an account's private channels
+    pub authenticated: usize,
+    /// Open subscriptions across connections
+    pub subscriptions: usize,
+    /// Largest number of messages waiting for one client
+    pub max_lag: usize,
+}
+
+/// Work waiting behind order placement
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct QueueDepths {
+    /// Trade settlement jobs queued or running
+    pub settlement: usize,
+    /// Settlements whose last attempt failed, waiting for a retry
+    pub settlement_failing: usize,
+    /// Failed settlement attempts, since startup
+    pub settlement_failures: u64,
+    /// Notifications waiting to be sent
+    pub notifications: usize,
+    /// Notifications dropped because the queue was full, since startup
+    pub notifications_dropped: u64,
+    /// Webhook deliveries being attempted or waiting for a retry
+    pub webhook_deliveries: usize,
+    /// Market data messages waiting to be read by subscribers
+    pub market_data: usize,
+    /// Market data subscribers disconnected for lagging, since startup
+    pub market_data_disconnected: u64,
+}
+
+/// Gather the overview from the state's services
+pub async fn overview(state: &AppState) -> Result<Overview> {
+    // Candles are bucketed by the market data clock, so "today" is its day
+    let now = state.market_data_service.clock().now();
+    let today = now.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+
+    let mut markets = Vec::with_capacity(state.markets.len());
+    for market in &state.markets {
+        // A configured market without a book has nothing to show
+        let Ok(book) = state.matching_engine.book_stats(&market.symbol) else {
+            continue;
+        };
+        let day = state.market_data_service.get_candles(&market.symbol, CandleInterval::Day1, 1)
+            .into_iter()
+            .find(|candle| candle.open_time == today);
+        markets.push(MarketOverview {
+            market: market.symbol.clone(),
+            trading_enabled: market.trading_enabled,
+            session: state.matching_engine.session_state(&market.symbol),
+            bid_orders: book.bid_orders,
+            ask_orders: book.ask_orders,
+            bid_levels: book.bid_levels,
+            ask_levels: book.ask_levels,
+            best_bid: book.best_bid,
+            best_ask: book.best_ask,
+            last_price: state.market_data_service.get_ticker(&market.symbol).and_then(|ticker| ticker.last),
+            last_order_sequence: book.last_order_sequence,
+            last_trade_sequence: book.last_trade_sequence,
+            trades_today: day.as_ref().map_or(0, |candle| candle.trades),
+            volume_today: day.as_ref().map_or(Quantity::ZERO, |candle| candle.volume),
+            quote_volume_today: day.as_ref().map_or(Quantity::ZERO, |candle| candle.quote_volume),
+        });
+    }
+    markets.sort_by(|a, b| a.market.cmp(&b.market));
+
+    let channel = state.market_data_service.channel().metrics().await;
+    let connections = state.ws_connections.list(&channel);
+    let websocket = WebSocketOverview {
+        connections: connections.len(),
+        authenticated: connections.iter().filter(|connection| connection.account_id.is_some()).count(),
+        subscriptions: connections.iter().map(|connection| connection.subscriptions.len()).sum(),
+        max_lag: connections.iter().map(|connection| connection.lag).max().unwrap_or(0),
+    };
+    let queues = QueueDepths {
+        settlement: state.settlement.queued(),
+        settlement_failing: state.settlement.failing().len(),
+        settlement_failures: state.settlement.failures(),
+        notifications: state.notifications.queued(),
+        notifications_dropped: state.notifications.dropped(),
+        webhook_deliveries: state.webhooks.pending(),
+        market_data: channel.subscribers.iter().map(|subscriber| subscriber.queue_depth).sum(),
+        market_data_disconnected: channel.disconnected,
+    };
+
+    Ok(Overview {
+        generated_at: now,
+        open_orders: markets.iter().map(|market| market.bid_orders + market.ask_orders).sum(),
+        markets,
+        accounts: state.account_service.totals().await?,
+        websocket,
+        queues,
+    })
+}
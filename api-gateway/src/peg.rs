@@ -0,0 +1,42 @@
+//! Pegged order sweep
+//!
+//! The engine re-prices pegged orders as their books change, but no more
+//! often than its re-pricing interval. Changes that land inside the interval
+//! are caught up here, and the published depth is refreshed to show the
+//! orders' new prices.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use common::model::order::Order;
+use tracing::warn;
+
+use crate::AppState;
+
+/// Re-price pegged orders once per re-pricing interval
+pub fn spawn_peg_sweep(state: Arc<AppState>, interval: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            reprice_pegged_orders(&state, state.matching_engine.clock().now()).await;
+        }
+    })
+}
+
+/// Move the pegged orders whose reference price changed and publish the new depth
+pub async fn reprice_pegged_orders(state: &AppState, now: DateTime<Utc>) -> Vec<Arc<Order>> {
+    let repriced = state.matching_engine.reprice_pegged_orders(now);
+
+    let markets: BTreeSet<&str> = repriced.iter().map(|order| order.market.as_str()).collect();
+    for market in markets {
+        if let Ok((bids, asks)) = state.matching_engine.get_market_depth(market, 10) {
+            if let Err(e) = state.market_data_service.update_order_book(market, bids, asks).await {
+                warn!("Failed to update order book of {}: {}", market, e);
+            }
+        }
+    }
+    repriced
+}
@@ -0,0 +1,253 @@
+//! Asynchronous trade settlement
+//!
+//! Once the matching engine has accepted an order, its trades are recorded in
+//! the engine and on its event stream. Settling them against account balances
+//! and publishing them to market data can then run after the placement has been
+//! answered.
+//!
+//! Jobs run on a bounded pool of workers:
+//! - jobs touching the same account run in the order they were submitted, while
+//!   jobs for unrelated accounts run concurrently
+//! - at most `capacity` jobs are queued or running; submitting more waits for
+//!   room, slowing order placement down instead of letting the backlog grow
+//! - requests that read or release an account's funds first wait for its
+//!   pending jobs with [`SettlementPipeline::flush`]
+//!
+//! With no workers configured, jobs run inline before the placement is answered.
+//!
+//! Before a placement is answered, its trades are stored with the account
+//! service as a [`PendingSettlement`], and deleted once settled and published.
+//! A settlement that fails stays stored and is counted as failing until
+//! [`retry_pending_settlements`] settles it, which a background task does
+//! every `retry_interval`, starting with what a restart left unsettled.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::error::Result;
+use common::model::trade::PendingSettlement;
+use futures::future::{FutureExt, Shared};
+use tokio::sync::{oneshot, Semaphore};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::api::order::settle_pending;
+use crate::AppState;
+
+/// Settlement worker settings
+#[derive(Debug, Clone)]
+pub struct PipelineConfig {
+    /// Jobs settled concurrently; 0 settles inline before answering the placement
+    pub workers: usize,
+    /// Jobs queued or running before submitting waits
+    pub capacity: usize,
+    /// Time between retries of settlements that failed
+    pub retry_interval: Duration,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            workers: 0,
+            capacity: 1024,
+            retry_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Completion of a submitted job
+type Done = Shared<oneshot::Receiver<()>>;
+
+/// Last job submitted for an account
+struct Pending {
+    job: u64,
+    done: Done,
+}
+
+/// Runs settlement jobs in the background, in order per account
+pub struct SettlementPipeline {
+    config: PipelineConfig,
+    queue: Arc<Semaphore>,
+    workers: Arc<Semaphore>,
+    pending: Mutex<HashMap<Uuid, Pending>>,
+    next_job: AtomicU64,
+    /// Stored settlements queued or running, so a retry never runs one twice
+    claimed: Mutex<HashSet<Uuid>>,
+    /// Why each failing settlement's last attempt failed
+    failing: Mutex<HashMap<Uuid, String>>,
+    /// Failed attempts since startup
+    failures: AtomicU64,
+}
+
+impl SettlementPipeline {
+    /// Create a pipeline with the given settings
+    pub fn new(config: PipelineConfig) -> Arc<Self> {
+        Arc::new(Self {
+            queue: Arc::new(Semaphore::new(config.capacity.max(1))),
+            workers: Arc::new(Semaphore::new(config.workers.max(1))),
+            pending: Mutex::new(HashMap::new()),
+            next_job: AtomicU64::new(0),
+            claimed: Mutex::new(HashSet::new()),
+            failing: Mutex::new(HashMap::new()),
+            failures: AtomicU64::new(0),
+            config,
+        })
+    }
+
+    /// Settings the pipeline runs with
+    pub fn config(&self) -> &PipelineConfig {
+        &self.config
+    }
+
+    /// Whether jobs run in the background rather than inline
+    pub fn is_async(&self) -> bool {
+        self.config.workers > 0
+    }
+
+    /// Jobs queued or running
+    pub fn queued(&self) -> usize {
+        self.config.capacity.max(1) - self.queue.available_permits()
+    }
+
+    /// Run a job after every job submitted earlier for any of its accounts
+    ///
+    /// Returns once the job is queued, waiting first if the queue is full.
+    pub async fn submit<F>(self: &Arc<Self>, accounts: impl IntoIterator<Item = Uuid>, job: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut accounts: Vec<Uuid> = accounts.into_iter().collect();
+        accounts.sort_unstable();
+        accounts.dedup();
+
+        let slot = self.queue.clone().acquire_owned().await.expect("settlement queue closed");
+        let (finished, done) = oneshot::channel();
+        let done = done.shared();
+        let id = self.next_job.fetch_add(1, Ordering::Relaxed);
+
+        let earlier: Vec<Done> = {
+            let mut pending = self.pending.lock().unwrap();
+            accounts.iter()
+                .filter_map(|account| pending.insert(*account, Pending { job: id, done: done.clone() }))
+                .map(|previous| previous.done)
+                .collect()
+        };
+
+        let pipeline = self.clone();
+        tokio::spawn(async move {
+            // Wait before taking a worker so a waiting job never holds one
+            for previous in earlier {
+                let _ = previous.await;
+            }
+            let worker = pipeline.workers.clone().acquire_owned().await.expect("settlement workers closed");
+            job.await;
+            drop(worker);
+
+            let _ = finished.send(());
+            let mut pending = pipeline.pending.lock().unwrap();
+            for account in &accounts {
+                if pending.get(account).is_some_and(|last| last.job == id) {
+                    pending.remove(account);
+                }
+            }
+            drop(pending);
+            drop(slot);
+        });
+    }
+
+    /// Wait until every job submitted so far for an account has finished
+    pub async fn flush(&self, account_id: Uuid) {
+        let done = self.pending.lock().unwrap().get(&account_id).map(|pending| pending.done.clone());
+        if let Some(done) = done {
+            let _ = done.await;
+        }
+    }
+
+    /// Wait until every job submitted so far has finished
+    pub async fn flush_all(&self) {
+        let pending: Vec<Done> = self.pending.lock().unwrap().values().map(|pending| pending.done.clone()).collect();
+        for done in pending {
+            let _ = done.await;
+        }
+    }
+
+    /// Settlements whose last attempt failed, with why
+    pub fn failing(&self) -> Vec<(Uuid, String)> {
+        self.failing.lock().unwrap().iter().map(|(id, error)| (*id, error.clone())).collect()
+    }
+
+    /// Failed settlement attempts since startup
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Take a stored settlement to run, unless it is already queued or running
+    pub fn claim(&self, id: Uuid) -> bool {
+        self.claimed.lock().unwrap().insert(id)
+    }
+
+    /// Give up a claimed settlement without running it
+    pub fn release(&self, id: Uuid) {
+        self.claimed.lock().unwrap().remove(&id);
+    }
+
+    /// Record that a claimed settlement is done
+    pub fn record_settled(&self, id: Uuid) {
+        self.claimed.lock().unwrap().remove(&id);
+        self.failing.lock().unwrap().remove(&id);
+    }
+
+    /// Record that an attempt of a claimed settlement failed, leaving it to a retry
+    pub fn record_failure(&self, id: Uuid, error: String) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        self.failing.lock().unwrap().insert(id, error);
+        self.claimed.lock().unwrap().remove(&id);
+    }
+}
+
+/// Retry stored settlements every `retry_interval`, starting at once with any
+/// a restart left unsettled
+pub fn spawn_settlement_retries(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(state.settlement.config().retry_interval);
+        loop {
+            ticks.tick().await;
+            if let Err(e) = retry_pending_settlements(&state).await {
+                warn!("Failed to list pending settlements: {}", e);
+            }
+        }
+    })
+}
+
+/// Submit every stored settlement not already queued or running, returning how many
+pub async fn retry_pending_settlements(state: &AppState) -> Result<usize> {
+    let pending = state.account_service.pending_settlements().await?;
+    let mut submitted = 0;
+    for pending in pending {
+        if !state.settlement.claim(pending.id) {
+            continue;
+        }
+        submitted += 1;
+        let accounts = settlement_accounts(&pending);
+        let job = settle_pending(
+            state.account_service.clone(),
+            state.market_data_service.clone(),
+            state.matching_engine.clone(),
+            state.settlement.clone(),
+            pending,
+        );
+        state.settlement.submit(accounts, job).await;
+    }
+    Ok(submitted)
+}
+
+/// Accounts whose balances a settlement changes
+pub fn settlement_accounts(pending: &PendingSettlement) -> Vec<Uuid> {
+    pending.trades.iter()
+        .flat_map(|trade| [trade.buyer_id, trade.seller_id])
+        .chain(pending.terminated.iter().map(|order| order.user_id))
+        .collect()
+}
@@ -0,0 +1,123 @@
+//! Token-bucket request rate limiting
+//!
+//! Anonymous callers are limited per client address, authenticated callers per
+//! API key. Responses carry `X-RateLimit-Limit` and `X-RateLimit-Remaining`;
+//! rejected requests get `429` with `Retry-After`.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+
+use crate::error::ApiError;
+
+/// Number of tracked clients above which idle buckets are dropped
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Per-client token buckets refilled at a fixed rate
+pub struct RateLimiter {
+    /// Requests allowed per minute, also the burst size
+    requests_per_minute: u32,
+    buckets: DashMap<String, Bucket>,
+}
+
+impl RateLimiter {
+    /// Allow `requests_per_minute` requests per client, with bursts of the same size
+    pub fn per_minute(requests_per_minute: u32) -> Self {
+        Self {
+            requests_per_minute: requests_per_minute.max(1),
+            buckets: DashMap::new(),
+        }
+    }
+
+    /// Take a token for `client`, returning the remaining tokens or how long to wait
+    pub fn check(&self, client: &str) -> Result<u32, Duration> {
+        let capacity = self.requests_per_minute as f64;
+        let refill_per_sec = capacity / 60.0;
+        let now = Instant::now();
+
+        if self.buckets.len() > MAX_TRACKED_CLIENTS {
+            self.buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * refill_per_sec < capacity
+            });
+        }
+
+        let mut bucket = self.buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_sec).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(bucket.tokens as u32)
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / refill_per_sec))
+        }
+    }
+
+    /// Run the request if `client` is within its limit, otherwise answer `429`
+    pub async fn enforce(&self, client: &str, request: Request, next: Next) -> Response {
+        match self.check(client) {
+            Ok(remaining) => {
+                let mut response = next.run(request).await;
+                self.set_headers(&mut response, remaining);
+                response
+            }
+            Err(retry_after) => {
+                let mut response = ApiError::Common(common::error::Error::RateLimitExceeded(format!(
+                    "Limit of {} requests per minute exceeded", self.requests_per_minute
+                )))
+                .into_response();
+                self.set_headers(&mut response, 0);
+                response.headers_mut().insert(
+                    HeaderName::from_static("retry-after"),
+                    HeaderValue::from(retry_after.as_secs().max(1)),
+                );
+                response
+            }
+        }
+    }
+
+    fn set_headers(&self, response: &mut Response, remaining: u32) {
+        let headers = response.headers_mut();
+        headers.insert(
+            HeaderName::from_static("x-ratelimit-limit"),
+            HeaderValue::from(self.requests_per_minute),
+        );
+        headers.insert(
+            HeaderName::from_static("x-ratelimit-remaining"),
+            HeaderValue::from(remaining),
+        );
+    }
+}
+
+/// Limit anonymous requests by client address
+pub async fn limit_by_client(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_else(|| "anonymous".to_string());
+
+    limiter.enforce(&client, request, next).await
+}
@@ -0,0 +1,345 @@
+//! Double-entry accounting exports
+//!
+//! Every statement entry becomes a balanced journal transaction: the change to
+//! the client's balance is posted against the exchange account it came from,
+//! e.g. a trade leg against trade clearing and its fee against fee income.
+//! Client balances are liabilities of the exchange, so a credit to a client is
+//! a negative posting. Ledger accounts come from a configurable chart of
+//! accounts, and transactions are written as a
+//! [ledger](https://ledger-cli.org) journal or a CSV journal with debit and
+//! credit columns.
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use common::decimal::Amount;
+use common::model::account::{StatementEntry, StatementEntryKind};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::format::{csv_escape, label};
+
+/// Accounting export settings
+#[derive(Debug, Clone)]
+pub struct AccountingConfig {
+    /// Journal encodings written for each period
+    pub formats: Vec<AccountingFormat>,
+    /// Ledger accounts postings are made to
+    pub chart: ChartOfAccounts,
+}
+
+impl Default for AccountingConfig {
+    fn default() -> Self {
+        Self {
+            formats: vec![AccountingFormat::Ledger, AccountingFormat::Csv],
+            chart: ChartOfAccounts::default(),
+        }
+    }
+}
+
+/// Journal file encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountingFormat {
+    /// Plain text journal read by ledger-cli, hledger and beancount's importers
+    Ledger,
+    /// One row per posting with debit and credit columns
+    Csv,
+}
+
+impl AccountingFormat {
+    /// File extension
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AccountingFormat::Ledger => "ledger",
+            AccountingFormat::Csv => "csv",
+        }
+    }
+
+    /// MIME type of the encoded file
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            AccountingFormat::Ledger => "text/plain",
+            AccountingFormat::Csv => "text/csv",
+        }
+    }
+
+    /// Encode transactions as a journal
+    pub fn encode(&self, transactions: &[JournalTransaction]) -> Vec<u8> {
+        match self {
+            AccountingFormat::Ledger => encode_ledger(transactions),
+            AccountingFormat::Csv => encode_csv(transactions),
+        }
+    }
+}
+
+impl FromStr for AccountingFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ledger" => Ok(AccountingFormat::Ledger),
+            "csv" => Ok(AccountingFormat::Csv),
+            _ => Err(format!("Unknown accounting format: {}", s)),
+        }
+    }
+}
+
+/// Exchange-side account a balance change is posted against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerRole {
+    /// Client balances, one account per client when the name contains `{account}`
+    Clients,
+    /// Trade legs, which net to zero across buyer and seller
+    TradeClearing,
+    /// Funding payments, which net to zero across position holders
+    FundingClearing,
+    /// Trading fees charged
+    FeeIncome,
+    /// Fees handed back
+    FeeRebates,
+    /// Other operator adjustments
+    Adjustments,
+}
+
+impl LedgerRole {
+    /// Name used in configuration
+    pub fn name(&self) -> &'static str {
+        match self {
+            LedgerRole::Clients => "clients",
+            LedgerRole::TradeClearing => "trade_clearing",
+            LedgerRole::FundingClearing => "funding_clearing",
+            LedgerRole::FeeIncome => "fee_income",
+            LedgerRole::FeeRebates => "fee_rebates",
+            LedgerRole::Adjustments => "adjustments",
+        }
+    }
+}
+
+impl FromStr for LedgerRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "clients" => Ok(LedgerRole::Clients),
+            "trade_clearing" => Ok(LedgerRole::TradeClearing),
+            "funding_clearing" => Ok(LedgerRole::FundingClearing),
+            "fee_income" => Ok(LedgerRole::FeeIncome),
+            "fee_rebates" => Ok(LedgerRole::FeeRebates),
+            "adjustments" => Ok(LedgerRole::Adjustments),
+            _ => Err(format!("Unknown ledger account: {}", s)),
+        }
+    }
+}
+
+/// One entry of the chart of accounts, configured as `role=Ledger:Account`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountMapping {
+    /// What is posted to the account
+    pub role: LedgerRole,
+    /// Ledger account name
+    pub account: String,
+}
+
+impl FromStr for AccountMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (role, account) = s.split_once('=')
+            .ok_or_else(|| format!("Expected role=account: {}", s))?;
+        let account = account.trim();
+        if account.is_empty() {
+            return Err(format!("Missing ledger account for {}", role.trim()));
+        }
+        Ok(Self {
+            role: role.trim().parse()?,
+            account: account.to_string(),
+        })
+    }
+}
+
+/// Ledger account names postings are made to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChartOfAccounts {
+    /// Client balances; `{account}` is replaced by the account ID
+    pub clients: String,
+    /// Trade legs
+    pub trade_clearing: String,
+    /// Funding payments
+    pub funding_clearing: String,
+    /// Trading fees charged
+    pub fee_income: String,
+    /// Fees handed back
+    pub fee_rebates: String,
+    /// Other operator adjustments
+    pub adjustments: String,
+}
+
+impl Default for ChartOfAccounts {
+    fn default() -> Self {
+        Self {
+            clients: "Liabilities:Clients:{account}".to_string(),
+            trade_clearing: "Assets:Clearing:Trades".to_string(),
+            funding_clearing: "Assets:Clearing:Funding".to_string(),
+            fee_income: "Income:Trading Fees".to_string(),
+            fee_rebates: "Expenses:Fee Rebates".to_string(),
+            adjustments: "Expenses:Adjustments".to_string(),
+        }
+    }
+}
+
+impl ChartOfAccounts {
+    /// Default chart with the given accounts replaced
+    pub fn with_mappings(mappings: &[AccountMapping]) -> Self {
+        let mut chart = Self::default();
+        for mapping in mappings {
+            *chart.account_mut(mapping.role) = mapping.account.clone();
+        }
+        chart
+    }
+
+    /// Ledger account of a client's balances
+    pub fn client(&self, account_id: Uuid) -> String {
+        self.clients.replace("{account}", &account_id.to_string())
+    }
+
+    fn account(&self, role: LedgerRole) -> &str {
+        match role {
+            LedgerRole::Clients => &self.clients,
+            LedgerRole::TradeClearing => &self.trade_clearing,
+            LedgerRole::FundingClearing => &self.funding_clearing,
+            LedgerRole::FeeIncome => &self.fee_income,
+            LedgerRole::FeeRebates => &self.fee_rebates,
+            LedgerRole::Adjustments => &self.adjustments,
+        }
+    }
+
+    fn account_mut(&mut self, role: LedgerRole) -> &mut String {
+        match role {
+            LedgerRole::Clients => &mut self.clients,
+            LedgerRole::TradeClearing => &mut self.trade_clearing,
+            LedgerRole::FundingClearing => &mut self.funding_clearing,
+            LedgerRole::FeeIncome => &mut self.fee_income,
+            LedgerRole::FeeRebates => &mut self.fee_rebates,
+            LedgerRole::Adjustments => &mut self.adjustments,
+        }
+    }
+
+    /// Balanced transaction for one of a client's statement entries, or `None`
+    /// if it moved nothing
+    ///
+    /// The client is credited the entry's amount, which is net of its fee, so
+    /// a trade leg is posted gross against trade clearing with the fee going
+    /// to fee income. A bust reverses both.
+    pub fn transaction(&self, account_id: Uuid, entry: &StatementEntry) -> Option<JournalTransaction> {
+        let contra = match entry.kind {
+            StatementEntryKind::Trade | StatementEntryKind::TradeBust => LedgerRole::TradeClearing,
+            StatementEntryKind::Funding => LedgerRole::FundingClearing,
+            StatementEntryKind::FeeRebate => LedgerRole::FeeRebates,
+            StatementEntryKind::Adjustment => LedgerRole::Adjustments,
+        };
+        let fee = match entry.kind {
+            StatementEntryKind::Trade | StatementEntryKind::TradeBust => entry.fee,
+            _ => Amount::ZERO,
+        };
+
+        let postings: Vec<Posting> = [
+            (self.client(account_id), -entry.amount),
+            (self.account(contra).to_string(), entry.amount + fee),
+            (self.account(LedgerRole::FeeIncome).to_string(), -fee),
+        ]
+            .into_iter()
+            .filter(|(_, amount)| !amount.is_zero())
+            .map(|(account, amount)| Posting { account, asset: entry.asset.clone(), amount: amount.normalize() })
+            .collect();
+        if postings.is_empty() {
+            return None;
+        }
+
+        let detail = entry.market.clone()
+            .or_else(|| entry.reason_code.as_ref().map(label))
+            .map(|detail| format!(" {}", detail))
+            .unwrap_or_default();
+        Some(JournalTransaction {
+            timestamp: entry.timestamp,
+            description: format!("{}{} for {}", label(&entry.kind), detail, account_id),
+            reference: entry.reference,
+            postings,
+        })
+    }
+}
+
+/// One leg of a journal transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Posting {
+    /// Ledger account name
+    pub account: String,
+    /// Asset symbol, the commodity of the posting
+    pub asset: String,
+    /// Debit when positive, credit when negative
+    pub amount: Amount,
+}
+
+/// Postings of one balance change, summing to zero
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalTransaction {
+    /// When the change was made
+    pub timestamp: DateTime<Utc>,
+    /// What made it, e.g. `trade BTC/USD for <account>`
+    pub description: String,
+    /// Trade or adjustment the change belongs to, if it has an ID
+    pub reference: Option<Uuid>,
+    /// Legs of the transaction
+    pub postings: Vec<Posting>,
+}
+
+/// Commodity as written in a ledger journal, quoted unless it is only letters
+fn commodity(asset: &str) -> String {
+    if asset.chars().all(|c| c.is_ascii_alphabetic()) {
+        asset.to_string()
+    } else {
+        format!("\"{}\"", asset.replace('"', ""))
+    }
+}
+
+fn encode_ledger(transactions: &[JournalTransaction]) -> Vec<u8> {
+    let mut out = String::new();
+    for transaction in transactions {
+        let code = transaction.reference.map(|reference| format!("({}) ", reference)).unwrap_or_default();
+        out.push_str(&format!("{} * {}{}\n", transaction.timestamp.format("%Y-%m-%d"), code, transaction.description));
+        out.push_str(&format!("    ; timestamp: {}\n", transaction.timestamp.to_rfc3339()));
+        for posting in &transaction.postings {
+            out.push_str(&format!("    {}  {} {}\n", posting.account, posting.amount, commodity(&posting.asset)));
+        }
+        out.push('\n');
+    }
+    out.into_bytes()
+}
+
+fn encode_csv(transactions: &[JournalTransaction]) -> Vec<u8> {
+    let mut out = String::from("transaction,date,timestamp,account,asset,debit,credit,description,reference\n");
+    for (number, transaction) in transactions.iter().enumerate() {
+        for posting in &transaction.postings {
+            let (debit, credit) = if posting.amount.is_sign_negative() {
+                (String::new(), (-posting.amount).to_string())
+            } else {
+                (posting.amount.to_string(), String::new())
+            };
+            let row = [
+                (number + 1).to_string(),
+                transaction.timestamp.format("%Y-%m-%d").to_string(),
+                transaction.timestamp.to_rfc3339(),
+                posting.account.clone(),
+                posting.asset.clone(),
+                debit,
+                credit,
+                transaction.description.clone(),
+                transaction.reference.map(|reference| reference.to_string()).unwrap_or_default(),
+            ];
+            let row: Vec<String> = row.iter().map(|value| csv_escape(value)).collect();
+            out.push_str(&row.join(","));
+            out.push('\n');
+        }
+    }
+    out.into_bytes()
+}
@@ -0,0 +1,253 @@
+//! Report schema and encodings
+//!
+//! A report is one row per engine event with a configurable list of columns,
+//! encoded as CSV (with a header row) or as a JSON array of objects. Columns
+//! that do not apply to an event are empty in CSV and `null` in JSON.
+
+use std::fmt;
+use std::str::FromStr;
+
+use common::decimal::{format_price, format_quantity};
+use matching_engine::EngineEvent;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+use super::journal::{event_market, event_time};
+
+/// Report file encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Csv,
+    Json,
+}
+
+impl ReportFormat {
+    /// File extension
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "csv",
+            ReportFormat::Json => "json",
+        }
+    }
+
+    /// MIME type of the encoded file
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "text/csv",
+            ReportFormat::Json => "application/json",
+        }
+    }
+
+    /// Encode events as a report with the given columns
+    pub fn encode(&self, fields: &[ReportField], events: &[EngineEvent]) -> Vec<u8> {
+        match self {
+            ReportFormat::Csv => encode_csv(fields, events),
+            ReportFormat::Json => encode_json(fields, events),
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ReportFormat::Csv),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("Unknown report format: {}", s)),
+        }
+    }
+}
+
+/// Report column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportField {
+    Event,
+    Timestamp,
+    Market,
+    OrderId,
+    AccountId,
+    Side,
+    OrderType,
+    TimeInForce,
+    Status,
+    RejectReason,
+    Price,
+    Quantity,
+    FilledQuantity,
+    TradeId,
+    BuyerOrderId,
+    SellerOrderId,
+    BuyerId,
+    SellerId,
+    TakerSide,
+}
+
+impl ReportField {
+    /// Every column, in the default order
+    pub const ALL: &'static [ReportField] = &[
+        ReportField::Event,
+        ReportField::Timestamp,
+        ReportField::Market,
+        ReportField::OrderId,
+        ReportField::AccountId,
+        ReportField::Side,
+        ReportField::OrderType,
+        ReportField::TimeInForce,
+        ReportField::Status,
+        ReportField::RejectReason,
+        ReportField::Price,
+        ReportField::Quantity,
+        ReportField::FilledQuantity,
+        ReportField::TradeId,
+        ReportField::BuyerOrderId,
+        ReportField::SellerOrderId,
+        ReportField::BuyerId,
+        ReportField::SellerId,
+        ReportField::TakerSide,
+    ];
+
+    /// Column name used in headers and configuration
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReportField::Event => "event",
+            ReportField::Timestamp => "timestamp",
+            ReportField::Market => "market",
+            ReportField::OrderId => "order_id",
+            ReportField::AccountId => "account_id",
+            ReportField::Side => "side",
+            ReportField::OrderType => "order_type",
+            ReportField::TimeInForce => "time_in_force",
+            ReportField::Status => "status",
+            ReportField::RejectReason => "reject_reason",
+            ReportField::Price => "price",
+            ReportField::Quantity => "quantity",
+            ReportField::FilledQuantity => "filled_quantity",
+            ReportField::TradeId => "trade_id",
+            ReportField::BuyerOrderId => "buyer_order_id",
+            ReportField::SellerOrderId => "seller_order_id",
+            ReportField::BuyerId => "buyer_id",
+            ReportField::SellerId => "seller_id",
+            ReportField::TakerSide => "taker_side",
+        }
+    }
+}
+
+impl FromStr for ReportField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ReportField::ALL.iter()
+            .find(|field| field.name() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown report field: {}", s))
+    }
+}
+
+impl fmt::Display for ReportField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl ReportField {
+    /// Value of this column for an event, if it applies
+    fn value(&self, event: &EngineEvent) -> Option<String> {
+        let order = match event {
+            EngineEvent::OrderPlaced(order)
+            | EngineEvent::OrderUpdated(order)
+            | EngineEvent::OrderCancelled(order)
+            | EngineEvent::OrderExpired(order) => Some(order),
+            EngineEvent::Trade(_) | EngineEvent::SessionChanged(_) => None,
+        };
+        let trade = match event {
+            EngineEvent::Trade(trade) => Some(trade),
+            _ => None,
+        };
+
+        match self {
+            ReportField::Event => Some(match event {
+                EngineEvent::OrderPlaced(_) => "order_placed",
+                EngineEvent::OrderUpdated(_) => "order_updated",
+                EngineEvent::OrderCancelled(_) => "order_cancelled",
+                EngineEvent::OrderExpired(_) => "order_expired",
+                EngineEvent::Trade(_) => "trade",
+                EngineEvent::SessionChanged(_) => "session_changed",
+            }.to_string()),
+            ReportField::Timestamp => Some(event_time(event).to_rfc3339()),
+            ReportField::Market => Some(event_market(event).to_string()),
+            ReportField::OrderId => order.map(|order| order.id.to_string()),
+            ReportField::AccountId => order.map(|order| order.user_id.to_string()),
+            ReportField::Side => order.map(|order| label(&order.side)),
+            ReportField::OrderType => order.map(|order| label(&order.order_type)),
+            ReportField::TimeInForce => order.map(|order| label(&order.time_in_force)),
+            ReportField::Status => match event {
+                EngineEvent::SessionChanged(change) => Some(label(&change.state)),
+                _ => order.map(|order| label(&order.status)),
+            },
+            ReportField::RejectReason => order.and_then(|order| order.reject_reason.as_ref()).map(label),
+            ReportField::Price => order.and_then(|order| order.price)
+                .or(trade.map(|trade| trade.price))
+                .map(|price| format_price(event_market(event), price)),
+            ReportField::Quantity => order.map(|order| order.quantity)
+                .or(trade.map(|trade| trade.quantity))
+                .map(|quantity| format_quantity(event_market(event), quantity)),
+            ReportField::FilledQuantity => order.map(|order| format_quantity(event_market(event), order.filled_quantity)),
+            ReportField::TradeId => trade.map(|trade| trade.id.to_string()),
+            ReportField::BuyerOrderId => trade.map(|trade| trade.buyer_order_id.to_string()),
+            ReportField::SellerOrderId => trade.map(|trade| trade.seller_order_id.to_string()),
+            ReportField::BuyerId => trade.map(|trade| trade.buyer_id.to_string()),
+            ReportField::SellerId => trade.map(|trade| trade.seller_id.to_string()),
+            ReportField::TakerSide => trade.map(|trade| label(&trade.taker_side)),
+        }
+    }
+}
+
+/// Serialized name of a unit enum, e.g. `Buy` or `GTC`
+pub(crate) fn label<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(label)) => label,
+        Ok(other) => other.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+fn encode_csv(fields: &[ReportField], events: &[EngineEvent]) -> Vec<u8> {
+    let mut out = String::new();
+    let header: Vec<&str> = fields.iter().map(|field| field.name()).collect();
+    out.push_str(&header.join(","));
+    out.push('\n');
+
+    for event in events {
+        let row: Vec<String> = fields.iter()
+            .map(|field| csv_escape(&field.value(event).unwrap_or_default()))
+            .collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+
+    out.into_bytes()
+}
+
+pub(crate) fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn encode_json(fields: &[ReportField], events: &[EngineEvent]) -> Vec<u8> {
+    let rows: Vec<Value> = events.iter()
+        .map(|event| {
+            let row: Map<String, Value> = fields.iter()
+                .map(|field| (field.name().to_string(), field.value(event).map_or(Value::Null, Value::String)))
+                .collect();
+            Value::Object(row)
+        })
+        .collect();
+
+    serde_json::to_vec_pretty(&rows).unwrap_or_default()
+}
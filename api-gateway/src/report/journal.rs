@@ -0,0 +1,108 @@
+//! Per-day journal of engine events
+//!
+//! Keeps every order event and trade in memory, bucketed by UTC day, so the
+//! reports for a recent day can be regenerated at any time.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use matching_engine::{EngineEvent, MatchingEngine};
+
+/// Engine events grouped by the UTC day they happened on
+pub struct EventJournal {
+    days: RwLock<BTreeMap<NaiveDate, Vec<EngineEvent>>>,
+    retention_days: u32,
+    /// Set while a background thread records the engine's events
+    running: AtomicBool,
+}
+
+impl EventJournal {
+    /// Create an empty journal that keeps today plus the given number of past days
+    pub fn new(retention_days: u32) -> Self {
+        Self {
+            days: RwLock::new(BTreeMap::new()),
+            retention_days,
+            running: AtomicBool::new(false),
+        }
+    }
+
+    /// Journal an engine's events on a background thread
+    ///
+    /// The thread exits once the engine is dropped.
+    pub fn start(engine: &MatchingEngine, retention_days: u32) -> Arc<Self> {
+        let journal = Arc::new(Self::new(retention_days));
+        let events = engine.subscribe_events();
+
+        let worker = journal.clone();
+        journal.running.store(true, Ordering::Relaxed);
+        thread::Builder::new()
+            .name("report-journal".to_string())
+            .spawn(move || {
+                for event in events {
+                    worker.record(event);
+                }
+                worker.running.store(false, Ordering::Relaxed);
+            })
+            .expect("failed to spawn report journal thread");
+
+        journal
+    }
+
+    /// Whether the engine's events are still being recorded
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    /// Add an event under the day it happened on, dropping days past retention
+    pub fn record(&self, event: EngineEvent) {
+        let day = event_time(&event).date_naive();
+        let oldest = self.oldest_day();
+
+        let mut days = self.days.write().unwrap();
+        if day >= oldest {
+            days.entry(day).or_default().push(event);
+        }
+        days.retain(|day, _| *day >= oldest);
+    }
+
+    /// Events of a day in the order they were recorded, or `None` if the day is outside retention
+    pub fn events(&self, day: NaiveDate) -> Option<Vec<EngineEvent>> {
+        if day < self.oldest_day() || day > Utc::now().date_naive() {
+            return None;
+        }
+
+        Some(self.days.read().unwrap().get(&day).cloned().unwrap_or_default())
+    }
+
+    fn oldest_day(&self) -> NaiveDate {
+        let today = Utc::now().date_naive();
+        today.checked_sub_days(Days::new(self.retention_days.into())).unwrap_or(today)
+    }
+}
+
+/// When an event happened
+pub fn event_time(event: &EngineEvent) -> DateTime<Utc> {
+    match event {
+        EngineEvent::OrderPlaced(order)
+        | EngineEvent::OrderUpdated(order)
+        | EngineEvent::OrderCancelled(order)
+        | EngineEvent::OrderExpired(order) => order.updated_at,
+        EngineEvent::Trade(trade) => trade.created_at,
+        EngineEvent::SessionChanged(change) => change.at,
+    }
+}
+
+/// Market an event belongs to
+pub fn event_market(event: &EngineEvent) -> &str {
+    match event {
+        EngineEvent::OrderPlaced(order)
+        | EngineEvent::OrderUpdated(order)
+        | EngineEvent::OrderCancelled(order)
+        | EngineEvent::OrderExpired(order) => &order.market,
+        EngineEvent::Trade(trade) => &trade.market,
+        EngineEvent::SessionChanged(change) => &change.market,
+    }
+}
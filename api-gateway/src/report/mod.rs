@@ -0,0 +1,183 @@
+//! End-of-day regulatory reports
+//!
+//! Every order placement, fill, cancellation and trade is journaled by UTC day.
+//! Shortly after midnight UTC the previous day's events are written out as one
+//! file per market and format, e.g. `2025-02-27/BTC-USD.csv`, to a local
+//! directory or an S3-compatible bucket. Admins can regenerate any day still in
+//! the journal.
+
+pub mod accounting;
+pub mod format;
+pub mod journal;
+pub mod sink;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{Days, NaiveDate, Utc};
+use common::error::{Error, Result};
+use matching_engine::MatchingEngine;
+use serde::Serialize;
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+pub use accounting::{AccountingConfig, AccountingFormat, ChartOfAccounts};
+pub use format::{ReportField, ReportFormat};
+pub use journal::EventJournal;
+pub use sink::{ReportSink, S3Config};
+
+use journal::event_market;
+
+/// Report generation settings
+#[derive(Debug, Clone)]
+pub struct ReportConfig {
+    /// Where reports are written, reports are disabled when unset
+    pub sink: Option<ReportSink>,
+    /// Encodings written for each market
+    pub formats: Vec<ReportFormat>,
+    /// Columns, in order
+    pub fields: Vec<ReportField>,
+    /// Past days kept in the journal for regeneration
+    pub retention_days: u32,
+    /// Journal encodings and chart of accounts of the accounting export
+    pub accounting: AccountingConfig,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            sink: None,
+            formats: vec![ReportFormat::Csv],
+            fields: ReportField::ALL.to_vec(),
+            retention_days: 7,
+            accounting: AccountingConfig::default(),
+        }
+    }
+}
+
+/// File written for a report
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReportFile {
+    /// Market symbol
+    pub market: String,
+    /// Encoding
+    pub format: ReportFormat,
+    /// Number of events in the file
+    pub records: usize,
+    /// Path or object URL the file was written to
+    pub location: String,
+}
+
+/// Result of generating the reports for one day
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReportSummary {
+    /// UTC day covered
+    pub date: NaiveDate,
+    /// Files written
+    pub files: Vec<ReportFile>,
+}
+
+/// Writes daily reports from the event journal
+pub struct ReportGenerator {
+    /// Journal of the engine's events, only kept when reports are enabled
+    journal: Option<Arc<EventJournal>>,
+    config: ReportConfig,
+}
+
+impl ReportGenerator {
+    /// Start journaling an engine's events if a report destination is configured
+    pub fn start(engine: &MatchingEngine, config: ReportConfig) -> Self {
+        Self {
+            journal: config.sink.as_ref().map(|_| EventJournal::start(engine, config.retention_days)),
+            config,
+        }
+    }
+
+    /// Generator that writes nothing
+    pub fn disabled() -> Self {
+        Self {
+            journal: None,
+            config: ReportConfig::default(),
+        }
+    }
+
+    /// Whether a destination is configured
+    pub fn is_enabled(&self) -> bool {
+        self.journal.is_some()
+    }
+
+    /// Where reports are written, if configured
+    pub fn sink(&self) -> Option<&ReportSink> {
+        self.config.sink.as_ref()
+    }
+
+    /// Journal encodings and chart of accounts of the accounting export
+    pub fn accounting(&self) -> &AccountingConfig {
+        &self.config.accounting
+    }
+
+    /// Whether the engine's events are still being journaled for reports
+    pub fn is_journaling(&self) -> bool {
+        self.journal.as_ref().is_some_and(|journal| journal.is_running())
+    }
+
+    /// Write the reports for a UTC day, one file per market and format
+    ///
+    /// `markets` always get a file, even without activity, so a missing file
+    /// never has to be told apart from a quiet day.
+    pub async fn generate(&self, date: NaiveDate, markets: &[String]) -> Result<ReportSummary> {
+        let (Some(sink), Some(journal)) = (&self.config.sink, &self.journal) else {
+            return Err(Error::ConfigurationError("No report destination configured".to_string()));
+        };
+        let events = journal.events(date)
+            .ok_or_else(|| Error::ValidationError(format!("No journal kept for {}", date)))?;
+
+        let mut by_market: BTreeMap<String, Vec<_>> = markets.iter()
+            .map(|market| (market.clone(), Vec::new()))
+            .collect();
+        for event in events {
+            by_market.entry(event_market(&event).to_string()).or_default().push(event);
+        }
+
+        let mut files = Vec::new();
+        for (market, events) in &by_market {
+            for format in &self.config.formats {
+                let key = format!("{}/{}.{}", date, market.replace('/', "-"), format.extension());
+                let body = format.encode(&self.config.fields, events);
+                let location = sink.write(&key, format.content_type(), body).await?;
+
+                files.push(ReportFile {
+                    market: market.clone(),
+                    format: *format,
+                    records: events.len(),
+                    location,
+                });
+            }
+        }
+
+        info!("Generated {} report files for {}", files.len(), date);
+        Ok(ReportSummary { date, files })
+    }
+
+    /// Generate the previous day's reports shortly after every UTC midnight
+    pub fn spawn_daily(self: Arc<Self>, markets: Vec<String>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                let now = Utc::now();
+                let next_midnight = (now.date_naive() + Days::new(1))
+                    .and_hms_opt(0, 0, 0)
+                    .expect("midnight is a valid time")
+                    .and_utc();
+                // Give orders in flight at midnight a moment to be journaled
+                let wait = (next_midnight - now).to_std().unwrap_or_default() + Duration::from_secs(5);
+                tokio::time::sleep(wait).await;
+
+                let yesterday = Utc::now().date_naive() - Days::new(1);
+                if let Err(e) = self.generate(yesterday, &markets).await {
+                    error!("Failed to generate reports for {}: {}", yesterday, e);
+                }
+            }
+        })
+    }
+}
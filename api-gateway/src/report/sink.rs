@@ -0,0 +1,132 @@
+//! Report destinations
+//!
+//! Reports are written either under a local directory or to an S3-compatible
+//! object store with path-style `PUT` requests signed with AWS Signature V4.
+
+use std::path::PathBuf;
+
+use chrono::Utc;
+use common::error::{Error, Result};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+/// S3-compatible bucket settings
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    /// Endpoint URL, e.g. `https://s3.eu-west-1.amazonaws.com` or `http://minio:9000`
+    pub endpoint: String,
+    /// Bucket name
+    pub bucket: String,
+    /// Key prefix, e.g. `reports/`
+    pub prefix: String,
+    /// Signing region
+    pub region: String,
+    /// Access key ID
+    pub access_key: String,
+    /// Secret access key
+    pub secret_key: String,
+}
+
+/// Where report files are written
+#[derive(Debug, Clone)]
+pub enum ReportSink {
+    /// Local directory
+    Directory(PathBuf),
+    /// S3-compatible object store
+    S3(S3Config),
+}
+
+impl ReportSink {
+    /// Store a file under a relative key such as `2025-02-27/BTC-USD.csv`, returning where it went
+    pub async fn write(&self, key: &str, content_type: &str, body: Vec<u8>) -> Result<String> {
+        match self {
+            ReportSink::Directory(dir) => {
+                let path = dir.join(key);
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent).await
+                        .map_err(|e| Error::Internal(format!("Failed to create {}: {}", parent.display(), e)))?;
+                }
+                tokio::fs::write(&path, body).await
+                    .map_err(|e| Error::Internal(format!("Failed to write {}: {}", path.display(), e)))?;
+                Ok(path.display().to_string())
+            }
+            ReportSink::S3(config) => put_object(config, key, content_type, body).await,
+        }
+    }
+}
+
+async fn put_object(config: &S3Config, key: &str, content_type: &str, body: Vec<u8>) -> Result<String> {
+    let path = format!("/{}/{}{}", config.bucket, config.prefix, key);
+    let url = format!("{}{}", config.endpoint.trim_end_matches('/'), uri_encode_path(&path));
+    let parsed = reqwest::Url::parse(&url)
+        .map_err(|e| Error::ConfigurationError(format!("Invalid report S3 endpoint {}: {}", config.endpoint, e)))?;
+    let host = match (parsed.host_str(), parsed.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => return Err(Error::ConfigurationError(format!("Report S3 endpoint has no host: {}", config.endpoint))),
+    };
+
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let payload_hash = hex::encode(Sha256::digest(&body));
+    let authorization = sign_v4(config, "PUT", &host, &uri_encode_path(&path), &payload_hash, &amz_date);
+
+    let response = reqwest::Client::new()
+        .put(parsed)
+        .header("content-type", content_type)
+        .header("x-amz-content-sha256", &payload_hash)
+        .header("x-amz-date", &amz_date)
+        .header("authorization", authorization)
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| Error::Internal(format!("Failed to upload {}: {}", url, e)))?;
+
+    if !response.status().is_success() {
+        return Err(Error::Internal(format!("Upload of {} failed with {}", url, response.status())));
+    }
+
+    Ok(format!("s3://{}/{}{}", config.bucket, config.prefix, key))
+}
+
+/// Build the `Authorization` header for a request signed over host, payload hash and date
+fn sign_v4(config: &S3Config, method: &str, host: &str, canonical_uri: &str, payload_hash: &str, amz_date: &str) -> String {
+    const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+    let date = &amz_date[..8];
+    let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+    let canonical_request = format!(
+        "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        method, canonical_uri, host, payload_hash, amz_date, SIGNED_HEADERS, payload_hash
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date, scope, hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = [date, config.region.as_str(), "s3", "aws4_request"]
+        .iter()
+        .fold(format!("AWS4{}", config.secret_key).into_bytes(), |key, part| hmac(&key, part.as_bytes()));
+    let signature = hex::encode(hmac(&key, string_to_sign.as_bytes()));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        config.access_key, scope, SIGNED_HEADERS, signature
+    )
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encode everything but unreserved characters and `/`
+fn uri_encode_path(path: &str) -> String {
+    path.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
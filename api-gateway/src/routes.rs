@@ -0,0 +1,343 @@
+//! REST route classes
+//!
+//! - Public market data: open CORS, per-address limits, cacheable responses
+//! - Sign-up: account creation, per-address limits
+//! - Private trading and account endpoints: API key required, per-key limits,
+//!   CORS restricted to configured origins, never cached
+//! - Admin endpoints: admin key required, never cached
+//! - GraphQL: per-address limits, optional API key checked per field, CORS
+//!   restricted to configured origins, never cached
+//!
+//! All REST responses may be gzip or brotli compressed, and JSON responses are
+//! written in the number format the client's `Accept` profile asks for.
+//! WebSocket routes are mounted outside this router and are never compressed.
+//! Each API version mounts its own copy of these routes (see `versioning`).
+
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    handler::Handler,
+    http::{header, HeaderValue, Method},
+    middleware,
+    response::Response,
+    routing::{delete, get, post, put},
+    Extension, Router,
+};
+use tower_http::compression::predicate::{Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::set_header::SetResponseHeaderLayer;
+
+use crate::api::account::{
+    create_account, deposit, get_account, get_account_trades, get_balance_history, get_balances, get_portfolio, get_positions, get_reservations, withdraw,
+};
+use crate::api::admin::{
+    clear_book_limits, clear_market_schedule, disconnect_ws_connection, find_account_by_external_id,
+    force_release_reservation, get_account_reservations, get_audit_log, get_book_checks, get_book_limits, get_candle_compaction,
+    get_feature_flags, get_incentives, get_market_data_gaps, get_market_data_memory, get_order_latency, get_overview,
+    get_rebate_periods, get_subscriber_metrics, get_surveillance_alerts, get_ws_connection, import_orders,
+    list_accounts, list_ws_connections, regenerate_report, set_account_external_id, set_book_limits,
+    set_feature_flag, set_market_schedule, settle_rebates, take_balance_snapshots,
+};
+use crate::api::adjustment::{adjust_balance, credit_fee_rebate, get_statement};
+use crate::api::api_key::{create_api_key, issue_api_key, list_api_keys, revoke_api_key, rotate_api_key};
+use crate::api::asset::{get_assets, register_asset};
+use crate::api::bust::bust_trade;
+use crate::api::closure::{admin_export_account, close_account, export_account, force_close_account};
+use crate::api::data::{get_candle_archive, get_data_manifest, get_trade_archive};
+use crate::api::earn::{accrue_earn, get_earn_accruals, get_earn_subscriptions, subscribe_earn, unsubscribe_earn};
+use crate::api::invoice::{get_invoices, mark_invoice_paid};
+use crate::api::funding::{get_funding_payments, get_funding_rates, settle_funding};
+use crate::api::index_price::{get_index_price, get_index_prices};
+use crate::api::kill_switch::{engage_kill_switch, engage_own_kill_switch, release_kill_switch};
+use crate::api::market::{
+    get_analytics, get_candles, get_heatmap, get_market_session, get_markets, get_order_book, get_order_book_history,
+    get_price_impact, get_raw_trades, get_shadow_markets, get_ticker, get_tickers, get_trades,
+};
+use crate::api::duplicates::{get_duplicate_order_settings, set_duplicate_order_settings};
+use crate::api::notification::{get_notification_preferences, set_notification_preferences};
+use crate::api::system::{get_announcements, get_capabilities, publish_announcement};
+use crate::api::permissions::{get_account_permissions, get_api_key_scopes, set_account_permissions};
+use crate::api::report_job::{get_report_job_run, get_report_job_runs, list_report_jobs, run_report_job};
+use crate::api::security::{get_security_events, list_sessions, revoke_session};
+use crate::api::order::{cancel_order, get_order, get_order_fills, get_order_history, get_orders, place_order, preview_order};
+use crate::api::webhook::{create_webhook, delete_webhook, get_webhook_deliveries, get_webhooks};
+use crate::api::withdrawal::{add_withdrawal_address, get_withdrawal_addresses, remove_withdrawal_address};
+use crate::auth::{
+    require_admin_key, require_api_key, require_scope, AdminAuthState, AuthLayerState, Scope, ScopeLayerState, API_KEY_HEADER,
+    SECOND_FACTOR_HEADER,
+};
+use crate::capabilities::Capabilities;
+use crate::config::AppConfig;
+use crate::graphql::{graphiql, graphql_handler};
+use crate::limits::limit_requests;
+use crate::number_format::format_numbers;
+use crate::rate_limit::{limit_by_client, RateLimiter};
+use crate::versioning::{version_layers, ApiVersion, VERSION_HEADER};
+use crate::AppState;
+
+/// Build the `/api/v1` router, adding `public` (e.g. health) to the public class
+#[allow(dead_code)] // The binaries mount every version with `versioned_api`
+pub fn api_router(state: Arc<AppState>, config: &AppConfig, public: Router<Arc<AppState>>) -> Router {
+    api_router_for(ApiVersion::V1, state, config, public)
+}
+
+/// Build the router of an API version, adding `public` to the public class
+pub fn api_router_for(version: ApiVersion, state: Arc<AppState>, config: &AppConfig, public: Router<Arc<AppState>>) -> Router {
+    let public_limiter = Arc::new(RateLimiter::per_minute(config.public_rate_limit));
+    let private_limiter = Arc::new(RateLimiter::per_minute(config.private_rate_limit));
+
+    let cache_control = HeaderValue::from_str(&format!("public, max-age={}", config.market_data_cache_seconds))
+        .expect("valid cache-control header");
+
+    let public_routes = public
+        .route("/markets", get(get_markets))
+        .route("/markets/:market/order-book", get(get_order_book))
+        .route("/markets/:market/order-book/history", get(get_order_book_history))
+        .route("/markets/:market/heatmap", get(get_heatmap))
+        .route("/markets/:market/ticker", get(get_ticker))
+        .route("/markets/:market/trades", get(get_trades))
+        .route("/markets/:market/candles", get(get_candles))
+        .route("/markets/:market/analytics", get(get_analytics))
+        .route("/markets/:market/impact", get(get_price_impact))
+        .route("/markets/:market/session", get(get_market_session))
+        .route("/markets/:market/funding", get(get_funding_rates))
+        .route("/markets/tickers", get(get_tickers))
+        .route("/markets/shadow", get(get_shadow_markets))
+        .route("/assets", get(get_assets))
+        .route("/index-prices", get(get_index_prices))
+        .route("/index-prices/:asset", get(get_index_price))
+        .route("/data/manifest", get(get_data_manifest))
+        .route("/data/trades/:market/:file", get(get_trade_archive))
+        .route("/data/candles/:market/:interval/:file", get(get_candle_archive))
+        .route("/system/announcements", get(get_announcements))
+        .route("/capabilities", get(get_capabilities))
+        .layer(Extension(Arc::new(Capabilities::new(config, &state))))
+        .layer(SetResponseHeaderLayer::if_not_present(header::CACHE_CONTROL, cache_control))
+        .layer(middleware::from_fn_with_state(public_limiter, limit_by_client))
+        .layer(public_cors());
+
+    let signup_routes = Router::new()
+        .route("/accounts", post(create_account))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(RateLimiter::per_minute(config.private_rate_limit)),
+            limit_by_client,
+        ))
+        .layer(private_cors(config));
+
+    let auth_state = AuthLayerState {
+        api_keys: state.api_keys.clone(),
+        limiter: private_limiter,
+        notifications: state.notifications.clone(),
+        accounts: state.account_service.clone(),
+        security: state.security.clone(),
+    };
+    let scope = |scope| ScopeLayerState { scope, security: state.security.clone() };
+
+    // Each class of private routes needs its own API key scope
+    let read_routes = Router::new()
+        .route("/accounts/:id", get(get_account))
+        .route("/accounts/:id/balances", get(get_balances))
+        .route("/accounts/:id/reservations", get(get_reservations))
+        .route("/accounts/:id/positions", get(get_positions))
+        .route("/accounts/:id/funding", get(get_funding_payments))
+        .route("/accounts/:id/withdrawal-addresses", get(get_withdrawal_addresses))
+        .route("/accounts/:id/trades", get(get_account_trades))
+        .route("/accounts/:id/portfolio", get(get_portfolio))
+        .route("/accounts/:id/balance-history", get(get_balance_history))
+        .route("/accounts/:id/statement", get(get_statement))
+        .route("/accounts/:id/permissions", get(get_api_key_scopes))
+        .route("/accounts/:id/api-keys", get(list_api_keys))
+        .route("/accounts/:id/security-events", get(get_security_events))
+        .route("/accounts/:id/sessions", get(list_sessions))
+        .route("/accounts/:id/earn", get(get_earn_subscriptions))
+        .route("/accounts/:id/earn/accruals", get(get_earn_accruals))
+        .route("/accounts/:id/invoices", get(get_invoices))
+        .route("/accounts/:id/export", get(export_account))
+        .route("/accounts/:id/orders", get(get_orders))
+        .route("/accounts/:id/notifications", get(get_notification_preferences))
+        .route("/accounts/:id/duplicate-orders", get(get_duplicate_order_settings))
+        .route("/accounts/:id/webhooks", get(get_webhooks))
+        .route("/accounts/:id/webhooks/deliveries", get(get_webhook_deliveries))
+        .route("/markets/:market/trades/raw", get(get_raw_trades))
+        .route("/orders/preview", post(preview_order))
+        .route("/orders/:id", get(get_order))
+        .route("/orders/:id/fills", get(get_order_fills))
+        .route("/orders/:id/history", get(get_order_history))
+        .route_layer(middleware::from_fn_with_state(scope(Scope::Read), require_scope));
+
+    let trade_routes = Router::new()
+        .route("/accounts/:id/deposit", post(deposit))
+        .route("/accounts/:id/api-keys", post(create_api_key))
+        .route("/accounts/:id/api-keys/:key_id", delete(revoke_api_key))
+        .route("/accounts/:id/api-keys/:key_id/rotate", post(rotate_api_key))
+        .route("/accounts/:id/sessions/:session_id", delete(revoke_session))
+        .route("/accounts/:id/earn", post(subscribe_earn))
+        .route("/accounts/:id/earn/:asset", delete(unsubscribe_earn))
+        .route("/accounts/:id/close", post(close_account))
+        .route("/accounts/:id/kill-switch", post(engage_own_kill_switch))
+        .route("/accounts/:id/notifications", put(set_notification_preferences))
+        .route("/accounts/:id/duplicate-orders", put(set_duplicate_order_settings))
+        .route("/accounts/:id/webhooks", post(create_webhook))
+        .route("/accounts/:id/webhooks/:webhook_id", delete(delete_webhook))
+        .route("/orders", post(place_order))
+        .route(
+            "/orders/:id",
+            delete(cancel_order).post(cancel_order.layer(middleware::map_response_with_state(
+                "299 - \"POST /api/v1/orders/:id is deprecated, cancel with DELETE\"",
+                deprecated,
+            ))),
+        )
+        .route_layer(middleware::from_fn_with_state(scope(Scope::Trade), require_scope));
+
+    let withdraw_routes = Router::new()
+        .route("/accounts/:id/withdraw", post(withdraw))
+        .route("/accounts/:id/withdrawal-addresses", post(add_withdrawal_address))
+        .route("/accounts/:id/withdrawal-addresses/:address_id", delete(remove_withdrawal_address))
+        .route_layer(middleware::from_fn_with_state(scope(Scope::Withdraw), require_scope));
+
+    let private_routes = read_routes
+        .merge(trade_routes)
+        .merge(withdraw_routes)
+        .layer(SetResponseHeaderLayer::overriding(header::CACHE_CONTROL, HeaderValue::from_static("no-store")))
+        .layer(middleware::from_fn_with_state(auth_state, require_api_key))
+        .layer(private_cors(config));
+
+    let admin_routes = Router::new()
+        .route("/admin/accounts", get(list_accounts))
+        .route("/admin/accounts/external/:external_id", get(find_account_by_external_id))
+        .route("/admin/accounts/:id/external-id", put(set_account_external_id))
+        .route("/admin/balance-snapshots", post(take_balance_snapshots))
+        .route("/admin/assets/:code", put(register_asset))
+        .route("/admin/feature-flags", get(get_feature_flags))
+        .route("/admin/feature-flags/:name", put(set_feature_flag))
+        .route("/admin/accounts/:id/kill-switch", post(engage_kill_switch).delete(release_kill_switch))
+        .route("/admin/accounts/:id/close", post(force_close_account))
+        .route("/admin/accounts/:id/fee-rebates", post(credit_fee_rebate))
+        .route("/admin/accounts/:id/adjustments", post(adjust_balance))
+        .route("/admin/accounts/:id/permissions", get(get_account_permissions).put(set_account_permissions))
+        .route("/admin/accounts/:id/api-keys", post(issue_api_key))
+        .route("/admin/accounts/:id/export", get(admin_export_account))
+        .route("/admin/overview", get(get_overview))
+        .route("/admin/audit", get(get_audit_log))
+        .route("/admin/surveillance/alerts", get(get_surveillance_alerts))
+        .route("/admin/accounts/:id/reservations", get(get_account_reservations))
+        .route("/admin/accounts/:id/reservations/:order_id/release", post(force_release_reservation))
+        .route("/admin/reports/:date", post(regenerate_report))
+        .route("/admin/report-jobs", get(list_report_jobs))
+        .route("/admin/report-jobs/:name/runs", get(get_report_job_runs).post(run_report_job))
+        .route("/admin/report-jobs/:name/runs/:run_id", get(get_report_job_run))
+        .route("/admin/markets/:market/schedule", put(set_market_schedule).delete(clear_market_schedule))
+        .route(
+            "/admin/markets/:market/book-limits",
+            get(get_book_limits).put(set_book_limits).delete(clear_book_limits),
+        )
+        .route("/admin/markets/:market/funding", post(settle_funding))
+        .route("/admin/orders/import", post(import_orders))
+        .route("/admin/trades/:id/bust", post(bust_trade))
+        .route("/admin/incentives", get(get_incentives))
+        .route("/admin/incentives/periods", get(get_rebate_periods).post(settle_rebates))
+        .route("/admin/earn/accruals", post(accrue_earn))
+        .route("/admin/invoices/:id/paid", post(mark_invoice_paid))
+        .route("/admin/announcements", post(publish_announcement))
+        .route("/admin/metrics/latency", get(get_order_latency))
+        .route("/admin/metrics/candles", get(get_candle_compaction))
+        .route("/admin/metrics/market-data", get(get_market_data_memory))
+        .route("/admin/metrics/market-data-gaps", get(get_market_data_gaps))
+        .route("/admin/metrics/subscribers", get(get_subscriber_metrics))
+        .route("/admin/metrics/books", get(get_book_checks))
+        .route("/admin/ws/connections", get(list_ws_connections))
+        .route("/admin/ws/connections/:id", get(get_ws_connection).delete(disconnect_ws_connection))
+        .layer(SetResponseHeaderLayer::overriding(header::CACHE_CONTROL, HeaderValue::from_static("no-store")))
+        .layer(middleware::from_fn_with_state(
+            AdminAuthState {
+                admin_key: config.admin_api_key.as_deref().map(Arc::<str>::from),
+                api_keys: state.api_keys.clone(),
+            },
+            require_admin_key,
+        ))
+        .layer(private_cors(config));
+
+    let graphql_routes = Router::new()
+        .route("/graphql", get(graphiql).post(graphql_handler))
+        .layer(Extension(crate::graphql::schema(state.clone())))
+        .layer(SetResponseHeaderLayer::overriding(header::CACHE_CONTROL, HeaderValue::from_static("no-store")))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(RateLimiter::per_minute(config.public_rate_limit)),
+            limit_by_client,
+        ))
+        .layer(private_cors(config));
+
+    let router = Router::new()
+        .merge(public_routes)
+        .merge(signup_routes)
+        .merge(private_routes)
+        .merge(admin_routes)
+        .merge(graphql_routes)
+        .layer(middleware::from_fn_with_state(version.number_format(state.number_format), format_numbers));
+    let router = version_layers(version, router);
+    let router = router.layer(middleware::from_fn_with_state(state.clone(), limit_requests));
+
+    let router = if config.compression_enabled {
+        router.layer(compression(config))
+    } else {
+        router
+    };
+
+    router.with_state(state)
+}
+
+/// Compress large responses whose content type is allowlisted
+fn compression(config: &AppConfig) -> CompressionLayer<impl Predicate> {
+    let content_types: Arc<[String]> = config.compression_content_types.clone().into();
+
+    let allowlisted = move |_, _, headers: &header::HeaderMap, _: &_| {
+        headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .is_some_and(|mime| content_types.iter().any(|allowed| allowed.eq_ignore_ascii_case(mime.trim())))
+    };
+
+    CompressionLayer::new()
+        .gzip(true)
+        .br(true)
+        .compress_when(SizeAbove::new(config.compression_min_bytes).and(allowlisted))
+}
+
+/// Mark a response to a verb kept for older clients as deprecated, with a
+/// `Warning` naming its replacement
+async fn deprecated(State(warning): State<&'static str>, mut response: Response) -> Response {
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    headers.insert(header::WARNING, HeaderValue::from_static(warning));
+    response
+}
+
+/// Any origin may read public market data
+fn public_cors() -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods([Method::GET, Method::HEAD, Method::OPTIONS])
+        .allow_headers(Any)
+}
+
+/// Only configured origins may call trading, account and admin endpoints
+fn private_cors(config: &AppConfig) -> CorsLayer {
+    let origins: Vec<HeaderValue> = config
+        .cors_allowed_origins
+        .iter()
+        .filter_map(|origin| HeaderValue::from_str(origin).ok())
+        .collect();
+
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::OPTIONS])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::HeaderName::from_static(API_KEY_HEADER),
+            header::HeaderName::from_static(SECOND_FACTOR_HEADER),
+            header::HeaderName::from_static(VERSION_HEADER),
+        ])
+}
@@ -0,0 +1,475 @@
+//! Service wiring shared by the binaries
+//!
+//! A [`Runtime`] builds everything the gateway serves from an [`AppConfig`]:
+//! the matching engine, account and market data services, the [`AppState`]
+//! over them, the background tasks and the router of the REST, WebSocket and
+//! GraphQL APIs. Both the api-gateway and trading-engine binaries start from
+//! it, so a new service or periodic task is wired here once:
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! use api_gateway::config::AppConfig;
+//! use api_gateway::runtime::Runtime;
+//!
+//! let server = Runtime::from_config(AppConfig::new()).build().await?;
+//! server.serve(([127, 0, 0, 1], 8080).into()).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Hosts add their own startup work, such as in-process strategies, with
+//! [`Runtime::on_start`]; it runs once the state exists, before serving.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::routing::get;
+use axum::{Extension, Router};
+use common::chaos::{Chaos, ChaosPoint, SharedChaos};
+use common::clock::SystemClock;
+use common::decimal::format::{DisplayFormat, MarketPrecision};
+use common::error::Result;
+use common::flags::FeatureFlags;
+use common::model::fee::FeeSchedule;
+use common::model::market::{Allocation, Market, MarketKind};
+use common::model::symbol::Symbol;
+use futures::future::BoxFuture;
+use market_data::repository::{ChaosRepository, InMemoryMarketRepository, MarketRepository, PostgresMarketRepository};
+use market_data::MarketDataService;
+use matching_engine::{MatchingEngine, ThrottleConfig};
+use account_service::AccountService;
+use rust_decimal_macros::dec;
+use sqlx::postgres::PgPoolOptions;
+use tokio::net::TcpListener;
+use tokio::signal;
+use tower_http::trace::{DefaultMakeSpan, DefaultOnRequest, DefaultOnResponse, TraceLayer};
+use tracing::{debug, info, warn, Level};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::{EnvFilter, FmtSubscriber};
+
+use crate::capabilities::Capabilities;
+use crate::config::AppConfig;
+use crate::graphql::{graphql_ws_handler, schema};
+use crate::ws::handler::ws_handler;
+use crate::{balance_history, earn, expiry, funding, health, incentives, index_price, market_sync, peg, pipeline, scheduler, session, versioning};
+use crate::AppState;
+
+/// Work run once the state is built, before serving
+type StartupTask = Box<dyn FnOnce(Arc<AppState>) -> BoxFuture<'static, Result<()>> + Send>;
+
+/// Builder of the services, background tasks and router of a gateway
+pub struct Runtime {
+    config: AppConfig,
+    fee_schedule: FeeSchedule,
+    throttle: ThrottleConfig,
+    markets: Vec<Market>,
+    routes: Router,
+    log_level: Level,
+    startup: Vec<StartupTask>,
+}
+
+impl Runtime {
+    /// Runtime with the given settings, no fees, no throttling and the BTC/USD market
+    pub fn from_config(config: AppConfig) -> Self {
+        Self {
+            config,
+            fee_schedule: FeeSchedule::default(),
+            throttle: ThrottleConfig::default(),
+            markets: Vec::new(),
+            routes: Router::new(),
+            log_level: Level::INFO,
+            startup: Vec::new(),
+        }
+    }
+
+    /// Charge trades the given maker and taker fees
+    pub fn with_fee_schedule(mut self, fee_schedule: FeeSchedule) -> Self {
+        self.fee_schedule = fee_schedule;
+        self
+    }
+
+    /// Throttle each account's orders and cancels per market
+    pub fn with_throttle(mut self, throttle: ThrottleConfig) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Trade a market, in place of the default BTC/USD once any is added
+    pub fn with_market(mut self, market: Market) -> Self {
+        self.markets.push(market);
+        self
+    }
+
+    /// Serve extra routes, such as API docs, next to the APIs
+    pub fn with_routes(mut self, routes: Router) -> Self {
+        self.routes = self.routes.merge(routes);
+        self
+    }
+
+    /// Trace HTTP requests at the given level
+    pub fn with_log_level(mut self, level: Level) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /// Run `task` once the state is built, in the order added; a failing task fails the build
+    pub fn on_start<F, Fut>(mut self, task: F) -> Self
+    where
+        F: FnOnce(Arc<AppState>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.startup.push(Box::new(move |state| Box::pin(task(state))));
+        self
+    }
+
+    /// Markets traded, BTC/USD unless others were added
+    pub fn markets(&self) -> Result<Vec<Market>> {
+        if self.markets.is_empty() {
+            return Ok(vec![spot_market("BTC/USD")?]);
+        }
+        Ok(self.markets.clone())
+    }
+
+    /// Build the services and state, start the background tasks and the startup work
+    pub async fn build(self) -> Result<Server> {
+        let markets = self.markets()?;
+        let symbols: Vec<String> = markets.iter().map(|market| market.symbol.clone()).collect();
+        let config = self.config;
+
+        // Show prices, quantities and amounts at the markets' precision
+        let display = DisplayFormat::global();
+        display.set_rounding_mode(config.display_rounding);
+        for market in &markets {
+            display.register_market(&market.symbol, MarketPrecision::from_steps(market.price_tick, market.quantity_step));
+        }
+
+        let feature_flags = Arc::new(FeatureFlags::new().with_overrides(&config.feature_flags)?);
+        let matching_engine = Arc::new(MatchingEngine::with_fee_schedule(self.fee_schedule)
+            .with_throttle(self.throttle)
+            .with_peg_reprice_interval(config.peg_reprice_interval)
+            .with_book_verification(config.book_verification)
+            .with_id_generator(config.id_scheme.generator(config.id_node, SystemClock::shared()))
+            .with_feature_flags(feature_flags));
+        // Inject the configured faults at settlement, deposit, publication and repository calls
+        let chaos = Arc::new(Chaos::new(config.chaos.clone()));
+        if config.chaos.is_enabled() {
+            warn!("Injecting faults for failure testing: {:?}", config.chaos.faults);
+        }
+        let account_service = Arc::new(config.settlement.adapters().into_iter()
+            .fold(AccountService::new(), AccountService::with_settlement_adapter)
+            .with_chaos(chaos.clone()));
+        let market_data_service = market_data_repository(&config, &chaos)?
+            .into_iter()
+            .fold(MarketDataService::new(), MarketDataService::with_repository)
+            .with_chaos(chaos)
+            .with_candle_retention(config.candle_retention.clone())
+            .with_memory_budget(config.memory_budget.clone())
+            .with_backpressure(config.backpressure.clone())
+            .with_tape_filter(config.tape_filter.clone())
+            .with_sync_source(Arc::new(market_sync::EngineSyncSource::new(matching_engine.clone())));
+        // Mirror the configured external markets as read-only shadow markets
+        let market_data_service = Arc::new(match config.shadow.fetcher() {
+            Some(fetcher) => market_data_service.with_shadow_markets(Arc::new(fetcher), config.shadow.markets.clone()),
+            None => market_data_service,
+        });
+
+        // List the default assets, keeping any registered since
+        account_service.seed_assets(config.assets.clone()).await?;
+
+        // Credit deposits confirmed by external custodians
+        if account_service.has_settlement_adapters() {
+            account_service.clone().spawn_deposit_sync(config.settlement.deposit_poll_interval);
+        }
+
+        // Serve tickers, recent trades and candles from before the restart
+        if let Some(warmup) = &config.market_data_warmup {
+            match market_data_service.warm_up(&symbols, warmup).await {
+                Ok(warmed) => {
+                    for market in warmed {
+                        info!("Warmed up {} from {} trades, {} candles", market.market, market.trades, market.candles);
+                    }
+                }
+                Err(e) => warn!("Market data not warmed up: {}", e),
+            }
+        }
+
+        // Announce closed candles to WebSocket subscribers even when no trade follows
+        market_data_service.clone().spawn_candle_closer();
+
+        // Drop candles past their interval's retention
+        market_data_service.clone().spawn_candle_compaction();
+
+        // Evict idle markets and spill old candles beyond the memory budget
+        market_data_service.clone().spawn_memory_eviction();
+
+        // Repair trades market data missed from the engine
+        if let Some(interval) = config.market_data_sync_interval {
+            market_data_service.clone().spawn_sync_check(interval);
+        }
+
+        // Poll the external exchange for the shadow markets' books and trades
+        if config.shadow.is_enabled() {
+            market_data_service.clone().spawn_shadow_ingestion(config.shadow.poll_interval);
+        }
+
+        // Keep order book history for replay
+        if let Some(interval) = config.order_book_snapshot_interval {
+            market_data_service.clone().spawn_order_book_snapshots(interval);
+        }
+        if let Some(heatmap) = config.heatmap.clone() {
+            market_data_service.clone().spawn_heatmap_sampling(heatmap);
+        }
+
+        // Publish depth deltas and trades to binary feed consumers
+        if config.binary_feed.is_enabled() {
+            if let Err(e) = market_data_service.start_binary_feed(config.binary_feed.clone()).await {
+                warn!("Binary feed not started: {}", e);
+            }
+        }
+
+        for symbol in &symbols {
+            matching_engine.register_market(symbol.clone());
+        }
+        resume_trade_sequences(&matching_engine, &market_data_service, &symbols).await?;
+
+        // Share takers pro-rata in the markets configured to, and round pegged prices to the tick
+        for market in &markets {
+            if let Err(e) = matching_engine.set_price_tick(&market.symbol, market.price_tick) {
+                warn!("Ignoring price tick of {}: {}", market.symbol, e);
+            }
+            let Some(mut allocation) = config.allocations.get(&market.symbol).copied() else {
+                continue;
+            };
+            if let Allocation::ProRata { lot_size, .. } = &mut allocation {
+                if lot_size.is_zero() {
+                    *lot_size = market.quantity_step;
+                }
+            }
+            if let Err(e) = matching_engine.set_allocation(&market.symbol, allocation) {
+                warn!("Ignoring allocation of {}: {}", market.symbol, e);
+            }
+        }
+
+        let state = Arc::new(AppState::new(matching_engine, account_service, market_data_service, markets)
+            .with_reports(config.reports.clone())
+            .with_scheduler(config.scheduler.clone(), scheduler::builtin_jobs())
+            .with_webhooks(config.webhooks)
+            .with_notifications(config.notifications.clone(), config.notifications.notifiers())
+            .with_incentives(config.incentives)
+            .with_earn(config.earn.clone())
+            .with_index_prices(config.index_prices.clone(), config.index_prices.fetchers())
+            .with_funding(funding::FundingEngine::new(config.funding.clone()))
+            .with_settlement_pipeline(config.settlement_pipeline.clone())
+            .with_number_format(config.number_format)
+            .with_health(config.health.clone())
+            .with_limits(config.limits.clone())
+            .with_duplicate_orders(config.duplicate_orders)
+            .with_archive(config.archive.clone()));
+
+        // Probe the database behind /health when configured
+        if let Some(database_url) = &config.database_url {
+            if let Err(e) = state.health.register_database(database_url) {
+                warn!("Not probing the database: {}", e);
+            }
+        }
+
+        // Journal orders and trades for end-of-day reports
+        if state.reports.is_enabled() {
+            state.reports.clone().spawn_daily(symbols);
+        }
+
+        // Retry settlements that failed, or that a restart left unsettled
+        pipeline::spawn_settlement_retries(state.clone());
+
+        // Run report jobs on their configured schedules
+        scheduler::spawn_scheduler(state.clone());
+
+        // Archive each day's trades and candles for bulk download
+        state.archive.clone().spawn_daily();
+
+        // Open, close and auction markets on their trading calendars
+        session::spawn_session_clock(state.clone());
+
+        // Expire orders that rested past their market's maximum age
+        expiry::spawn_expiry_sweep(state.clone());
+
+        // Catch pegged orders up with book changes the re-pricing interval skipped
+        peg::spawn_peg_sweep(state.clone(), config.peg_reprice_interval);
+
+        // Credit maker rebates at the end of every period
+        incentives::spawn_rebate_clock(state.clone());
+
+        // Credit earn interest at the end of every period
+        earn::spawn_earn_clock(state.clone());
+
+        // Poll external reference prices for the perpetual indices
+        index_price::spawn_index_price_poller(state.clone());
+
+        // Settle perpetual funding at the end of every interval
+        funding::spawn_funding_clock(state.clone());
+
+        // Snapshot balances for balance history at the end of every day
+        balance_history::spawn_daily_snapshots(state.clone());
+
+        // Keep health results fresh so outages reach the system channel
+        health::spawn_health_checks(state.clone());
+
+        Capabilities::new(&config, &state).log_banner();
+
+        for task in self.startup {
+            task(state.clone()).await?;
+        }
+
+        // Set up API routes of every version by class: public market data, sign-up and authenticated trading
+        let api_routes = versioning::versioned_api(
+            state.clone(),
+            &config,
+            Router::new().route("/health", get(health::health_check)),
+        );
+
+        // Set up websocket routes
+        let ws_routes = Router::new()
+            .route("/ws", get(ws_handler))
+            .route("/graphql/ws", get(graphql_ws_handler))
+            .layer(Extension(schema(state.clone())))
+            .with_state(state.clone());
+
+        let router = Router::new()
+            .merge(api_routes)
+            .merge(ws_routes)
+            .merge(self.routes);
+        // Serve the web UI when built with the `ui` feature
+        #[cfg(feature = "ui")]
+        let router = router.merge(crate::ui::router());
+        let router = router.layer(
+            TraceLayer::new_for_http()
+                .make_span_with(DefaultMakeSpan::new().level(self.log_level))
+                .on_request(DefaultOnRequest::new().level(self.log_level))
+                .on_response(DefaultOnResponse::new().level(self.log_level)),
+        );
+
+        Ok(Server { state, router })
+    }
+}
+
+/// Built gateway, ready to serve
+pub struct Server {
+    /// State shared by the handlers
+    pub state: Arc<AppState>,
+    /// Every route of the gateway
+    pub router: Router,
+}
+
+impl Server {
+    /// Serve on `addr` until interrupted
+    pub async fn serve(self, addr: SocketAddr) -> std::io::Result<()> {
+        self.serve_on(TcpListener::bind(addr).await?).await
+    }
+
+    /// Serve on a bound `listener` until interrupted
+    pub async fn serve_on(self, listener: TcpListener) -> std::io::Result<()> {
+        let addr = listener.local_addr()?;
+        info!("Listening on {}", addr);
+        #[cfg(feature = "ui")]
+        info!("Web UI at http://localhost:{}/app", addr.port());
+
+        axum::serve(listener, self.router.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(shutdown_signal())
+            .await
+    }
+}
+
+/// Number each market's trades on from the last one market data stored
+/// before a restart, so replay from a sequence number never mixes trades
+/// from before and after it
+pub async fn resume_trade_sequences(matching_engine: &MatchingEngine, market_data_service: &MarketDataService, symbols: &[String]) -> Result<()> {
+    for symbol in symbols {
+        let sequence = market_data_service.resume_trade_sequence(symbol).await?;
+        matching_engine.resume_trade_sequence(symbol, sequence)?;
+        if sequence > 0 {
+            info!("Numbering trades of {} from {}", symbol, sequence + 1);
+        }
+    }
+    Ok(())
+}
+
+/// Database-backed market data history when persistence is on and a database
+/// is configured, behind the injected faults when any are
+fn market_data_repository(config: &AppConfig, chaos: &SharedChaos) -> Result<Option<Arc<dyn MarketRepository>>> {
+    let repository: Option<Arc<dyn MarketRepository>> = match config.database_url.as_deref().filter(|_| config.market_data_persist) {
+        Some(database_url) => Some(Arc::new(PostgresMarketRepository::new(PgPoolOptions::new().connect_lazy(database_url)?))),
+        None => None,
+    };
+    if !config.chaos.faults.contains_key(&ChaosPoint::Repository) {
+        return Ok(repository);
+    }
+    let inner = repository.unwrap_or_else(|| Arc::new(InMemoryMarketRepository::new()));
+    Ok(Some(Arc::new(ChaosRepository::new(inner, chaos.clone()))))
+}
+
+/// Spot market with the engine's default tick and step sizes
+pub fn spot_market(symbol: &str) -> Result<Market> {
+    let parsed = Symbol::parse(symbol)?;
+    Ok(Market {
+        symbol: symbol.to_string(),
+        base_asset: parsed.base().to_string(),
+        quote_asset: parsed.quote().to_string(),
+        price_tick: dec!(0.01),
+        quantity_step: dec!(0.0001),
+        min_order_size: dec!(10.0),
+        max_price_deviation: 10.0,
+        trading_enabled: true,
+        kind: MarketKind::Spot,
+    })
+}
+
+/// Log at debug level when `DEBUG=1`, otherwise info, with `directives` on top
+///
+/// Returns the level, for tracing requests at it. Leaves a subscriber that is
+/// already set in place.
+pub fn init_tracing(directives: &str) -> Level {
+    let debug = std::env::var("DEBUG").is_ok_and(|value| value == "1");
+    let level = if debug { Level::DEBUG } else { Level::INFO };
+
+    let env_filter = EnvFilter::builder()
+        .with_default_directive(level.into())
+        .parse(directives)
+        .unwrap_or_else(|_| EnvFilter::new(level.to_string()));
+    let subscriber = FmtSubscriber::builder()
+        .with_env_filter(env_filter)
+        .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
+        .finish();
+
+    if tracing::subscriber::set_global_default(subscriber).is_ok() {
+        debug!("Debug logging enabled");
+    }
+    level
+}
+
+/// Resolve on Ctrl+C or, on Unix, SIGTERM
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!("Shutdown signal received, starting graceful shutdown");
+}
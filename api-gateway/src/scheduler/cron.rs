@@ -0,0 +1,142 @@
+//! Cron-like schedules
+//!
+//! Schedules use the five fields of cron, `minute hour day-of-month month
+//! day-of-week`, in UTC. Each field is `*`, a value, a range `a-b`, a step
+//! `*/n` or `a-b/n`, or a comma separated list of those. Days of the week run
+//! from `0` (Sunday) to `6`, with `7` also Sunday. As in cron, when both day
+//! fields are restricted a day matching either is enough. `@hourly`,
+//! `@daily`, `@weekly` and `@monthly` stand for the usual expressions.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, Utc};
+
+/// Days searched for the next occurrence, enough for schedules only on 29 February
+const SEARCH_DAYS: u64 = 366 * 8;
+
+/// A parsed schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    /// Expression as configured
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Whether the day-of-month field is `*`
+    any_day_of_month: bool,
+    /// Whether the day-of-week field is `*`
+    any_day_of_week: bool,
+}
+
+impl CronSchedule {
+    /// First time after `after`, to the minute, the schedule fires at
+    ///
+    /// `None` when it never fires, e.g. on 31 February.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.date_naive();
+        (0..SEARCH_DAYS)
+            .filter_map(|offset| start.checked_add_days(Days::new(offset)))
+            .filter(|day| self.matches_day(*day))
+            .find_map(|day| {
+                (0..24u32)
+                    .filter(|hour| self.hours & (1 << hour) != 0)
+                    .flat_map(|hour| (0..60u32).filter(|minute| self.minutes & (1 << minute) != 0).map(move |minute| (hour, minute)))
+                    .filter_map(|(hour, minute)| day.and_hms_opt(hour, minute, 0))
+                    .map(|time| time.and_utc())
+                    .find(|time| *time > after)
+            })
+    }
+
+    fn matches_day(&self, day: NaiveDate) -> bool {
+        if self.months & (1 << day.month()) == 0 {
+            return false;
+        }
+        let by_month = self.days_of_month & (1 << day.day()) != 0;
+        let by_week = self.days_of_week & (1 << day.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => by_month || by_week,
+            _ => by_month && by_week,
+        }
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = s.trim();
+        let expanded = match expression {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(format!("Schedule must have 5 fields: {}", expression));
+        };
+
+        let mut days_of_week = parse_field(day_of_week, 0, 7, "day of week")?;
+        // 7 is another name for Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            expression: expression.to_string(),
+            minutes: parse_field(minute, 0, 59, "minute")?,
+            hours: parse_field(hour, 0, 23, "hour")?,
+            days_of_month: parse_field(day_of_month, 1, 31, "day of month")?,
+            months: parse_field(month, 1, 12, "month")?,
+            days_of_week,
+            any_day_of_month: day_of_month == "*",
+            any_day_of_week: day_of_week == "*",
+        })
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+/// Parse one field into a bit set of the values it allows
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid {} field: {}", name, field);
+    let mut set = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0).ok_or_else(invalid)?),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((from, to)) => (
+                    from.parse().map_err(|_| invalid())?,
+                    to.parse().map_err(|_| invalid())?,
+                ),
+                // `a/n` runs from `a` to the end of the field
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    (value, if part.contains('/') { max } else { value })
+                }
+            },
+        };
+        if from < min || to > max || from > to {
+            return Err(invalid());
+        }
+        for value in (from..=to).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+
+    Ok(set)
+}
@@ -0,0 +1,264 @@
+//! Report jobs
+//!
+//! A job produces the files covering one reporting date and writes them to
+//! the report destination. The built-in jobs are:
+//!
+//! - `daily_statements`: every account's statement entries for the day,
+//!   `statements/2025-02-27.csv`
+//! - `compliance_export`: the end-of-day regulatory reports of the day, one
+//!   file per market and format as in [`crate::report`]
+//! - `fee_invoices`: trading fees per account and asset for the month the
+//!   date falls in, net of rebates; issues each account that traded an
+//!   invoice in [`crate::invoice`], even without fees, and lists them in
+//!   `invoices/2025-02.csv` when a report destination is configured
+//! - `accounting_export`: every account's statement entries for the day as
+//!   balanced double-entry transactions, one journal per format in
+//!   [`crate::report::accounting`], `accounting/2025-02-27.ledger`
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use account_service::AccountFilter;
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, Utc};
+use common::decimal::Amount;
+use common::error::{Error, Result};
+use common::model::account::StatementEntryKind;
+use uuid::Uuid;
+
+use crate::invoice::InvoiceLine;
+use crate::report::format::{csv_escape, label};
+use crate::report::ReportSink;
+use crate::AppState;
+
+/// Accounts read per page when going through every account
+const ACCOUNT_PAGE: usize = 1_000;
+
+/// Files written by a job run
+#[derive(Debug, Clone, Default)]
+pub struct JobOutput {
+    /// Rows or events written
+    pub records: usize,
+    /// Paths or object URLs of the files
+    pub files: Vec<String>,
+}
+
+/// A report that can be produced on a schedule or on demand
+#[async_trait]
+pub trait ReportJob: Send + Sync {
+    /// Name the job is registered and scheduled under, e.g. `daily_statements`
+    fn name(&self) -> &'static str;
+
+    /// What the job produces
+    fn description(&self) -> &'static str;
+
+    /// Produce the reports covering `date`
+    async fn run(&self, state: &AppState, date: NaiveDate) -> Result<JobOutput>;
+}
+
+/// The jobs every scheduler starts with
+pub fn builtin_jobs() -> Vec<Arc<dyn ReportJob>> {
+    vec![Arc::new(DailyStatements), Arc::new(ComplianceExport), Arc::new(FeeInvoices), Arc::new(AccountingExport)]
+}
+
+/// Every account's statement entries for one day
+pub struct DailyStatements;
+
+#[async_trait]
+impl ReportJob for DailyStatements {
+    fn name(&self) -> &'static str {
+        "daily_statements"
+    }
+
+    fn description(&self) -> &'static str {
+        "Statement entries of every account for the day"
+    }
+
+    async fn run(&self, state: &AppState, date: NaiveDate) -> Result<JobOutput> {
+        let sink = sink(state)?;
+        state.settlement.flush_all().await;
+        let (from, to) = (start_of(date), start_of(date + Days::new(1)));
+
+        let mut rows = Vec::new();
+        for account_id in all_accounts(state).await? {
+            for entry in state.account_service.get_statement(account_id, from, to).await? {
+                rows.push(vec![
+                    account_id.to_string(),
+                    entry.timestamp.to_rfc3339(),
+                    label(&entry.kind),
+                    entry.asset,
+                    entry.amount.to_string(),
+                    entry.fee.to_string(),
+                    entry.reference.map(|reference| reference.to_string()).unwrap_or_default(),
+                    entry.market.unwrap_or_default(),
+                    entry.reason_code.as_ref().map(label).unwrap_or_default(),
+                    entry.note.unwrap_or_default(),
+                ]);
+            }
+        }
+
+        let header = ["account_id", "timestamp", "kind", "asset", "amount", "fee", "reference", "market", "reason_code", "note"];
+        let location = sink.write(&format!("statements/{}.csv", date), "text/csv", encode_csv(&header, &rows)).await?;
+        Ok(JobOutput { records: rows.len(), files: vec![location] })
+    }
+}
+
+/// End-of-day regulatory reports for one day
+pub struct ComplianceExport;
+
+#[async_trait]
+impl ReportJob for ComplianceExport {
+    fn name(&self) -> &'static str {
+        "compliance_export"
+    }
+
+    fn description(&self) -> &'static str {
+        "Regulatory reports of the day's orders and trades, per market and format"
+    }
+
+    async fn run(&self, state: &AppState, date: NaiveDate) -> Result<JobOutput> {
+        let markets: Vec<String> = state.markets.iter().map(|market| market.symbol.clone()).collect();
+        let summary = state.reports.generate(date, &markets).await?;
+        Ok(JobOutput {
+            records: summary.files.iter().map(|file| file.records).sum(),
+            files: summary.files.into_iter().map(|file| file.location).collect(),
+        })
+    }
+}
+
+/// Trading fees per account and asset for one month
+pub struct FeeInvoices;
+
+#[async_trait]
+impl ReportJob for FeeInvoices {
+    fn name(&self) -> &'static str {
+        "fee_invoices"
+    }
+
+    fn description(&self) -> &'static str {
+        "Invoices of every account's trading fees for the month, per asset and net of rebates"
+    }
+
+    async fn run(&self, state: &AppState, date: NaiveDate) -> Result<JobOutput> {
+        state.settlement.flush_all().await;
+        let first = date.with_day(1).expect("every month has a first day");
+        let (from, to) = (start_of(first), start_of(first + Months::new(1)));
+
+        let mut rows = Vec::new();
+        for account_id in all_accounts(state).await? {
+            // Asset -> (trade legs, fees, rebates)
+            let mut lines: BTreeMap<String, (usize, Amount, Amount)> = BTreeMap::new();
+            for entry in state.account_service.get_statement(account_id, from, to).await? {
+                match entry.kind {
+                    StatementEntryKind::Trade | StatementEntryKind::TradeBust => {
+                        let line = lines.entry(entry.asset).or_default();
+                        line.0 += usize::from(entry.kind == StatementEntryKind::Trade);
+                        line.1 += entry.fee;
+                    }
+                    StatementEntryKind::FeeRebate => lines.entry(entry.asset).or_default().2 += entry.amount,
+                    _ => {}
+                }
+            }
+
+            if lines.is_empty() {
+                continue;
+            }
+
+            let lines = lines.into_iter()
+                .map(|(asset, (trades, fees, rebates))| InvoiceLine { asset, trades, fees, rebates, net: fees - rebates })
+                .collect();
+            let invoice = state.invoices.issue(account_id, first, lines, state.matching_engine.clock().now());
+            for line in &invoice.lines {
+                rows.push(vec![
+                    account_id.to_string(),
+                    line.asset.clone(),
+                    line.trades.to_string(),
+                    line.fees.to_string(),
+                    line.rebates.to_string(),
+                    line.net.to_string(),
+                    invoice.number.clone(),
+                ]);
+            }
+        }
+
+        // Invoices are kept even without a destination for the listing
+        let Some(sink) = state.reports.sink() else {
+            return Ok(JobOutput { records: rows.len(), files: Vec::new() });
+        };
+        let header = ["account_id", "asset", "trades", "fees", "rebates", "net", "invoice"];
+        let key = format!("invoices/{}.csv", first.format("%Y-%m"));
+        let location = sink.write(&key, "text/csv", encode_csv(&header, &rows)).await?;
+        Ok(JobOutput { records: rows.len(), files: vec![location] })
+    }
+}
+
+/// Every account's statement entries for one day as double-entry transactions
+pub struct AccountingExport;
+
+#[async_trait]
+impl ReportJob for AccountingExport {
+    fn name(&self) -> &'static str {
+        "accounting_export"
+    }
+
+    fn description(&self) -> &'static str {
+        "Double-entry journal of every account's balance changes for the day, per accounting format"
+    }
+
+    async fn run(&self, state: &AppState, date: NaiveDate) -> Result<JobOutput> {
+        let sink = sink(state)?;
+        state.settlement.flush_all().await;
+        let (from, to) = (start_of(date), start_of(date + Days::new(1)));
+        let config = state.reports.accounting();
+
+        let mut transactions = Vec::new();
+        for account_id in all_accounts(state).await? {
+            for entry in state.account_service.get_statement(account_id, from, to).await? {
+                transactions.extend(config.chart.transaction(account_id, &entry));
+            }
+        }
+        transactions.sort_by_key(|transaction| transaction.timestamp);
+
+        let mut files = Vec::new();
+        for format in &config.formats {
+            let key = format!("accounting/{}.{}", date, format.extension());
+            files.push(sink.write(&key, format.content_type(), format.encode(&transactions)).await?);
+        }
+        Ok(JobOutput { records: transactions.len(), files })
+    }
+}
+
+fn sink(state: &AppState) -> Result<&ReportSink> {
+    state.reports.sink()
+        .ok_or_else(|| Error::ConfigurationError("No report destination configured".to_string()))
+}
+
+/// Start of a UTC day
+fn start_of(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).expect("midnight is a valid time").and_utc()
+}
+
+/// IDs of every account, open or closed
+async fn all_accounts(state: &AppState) -> Result<Vec<Uuid>> {
+    let mut ids = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = state.account_service.list_accounts(&AccountFilter::default(), cursor, ACCOUNT_PAGE).await?;
+        ids.extend(page.accounts.iter().map(|account| account.id));
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => return Ok(ids),
+        }
+    }
+}
+
+fn encode_csv(header: &[&str], rows: &[Vec<String>]) -> Vec<u8> {
+    let mut out = header.join(",");
+    out.push('\n');
+    for row in rows {
+        let row: Vec<String> = row.iter().map(|value| csv_escape(value)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out.into_bytes()
+}
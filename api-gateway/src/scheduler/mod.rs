@@ -0,0 +1,305 @@
+//! Scheduled report jobs
+//!
+//! Report jobs are registered with the scheduler by name and run on the
+//! cron-like schedules configured with `REPORT_SCHEDULES`, e.g.
+//! `daily_statements=5 0 * * *;fee_invoices=0 1 1 * *`. A scheduled run covers
+//! the UTC day before it starts; admins can run any job for any date. Each
+//! run is kept in a history (most recent [`SchedulerConfig::history`]) with
+//! its outcome and the files written, and a job never runs twice at once.
+
+pub mod cron;
+pub mod jobs;
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use common::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub use cron::CronSchedule;
+pub use jobs::{builtin_jobs, JobOutput, ReportJob};
+
+use crate::AppState;
+
+/// Scheduler settings
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// When each job runs; jobs without a schedule only run on demand
+    pub schedules: Vec<JobSchedule>,
+    /// Runs kept in the history
+    pub history: usize,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            schedules: Vec::new(),
+            history: 500,
+        }
+    }
+}
+
+/// A job and when it runs, configured as `name=schedule`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobSchedule {
+    /// Name of the job
+    pub job: String,
+    /// When it runs
+    pub schedule: CronSchedule,
+}
+
+impl FromStr for JobSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (job, schedule) = s.split_once('=')
+            .ok_or_else(|| format!("Expected job=schedule: {}", s))?;
+        Ok(Self {
+            job: job.trim().to_string(),
+            schedule: schedule.parse()?,
+        })
+    }
+}
+
+/// What started a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobTrigger {
+    /// The job's schedule
+    Schedule,
+    /// An admin request
+    Manual,
+}
+
+/// Outcome of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Still running
+    Running,
+    /// Wrote its files
+    Succeeded,
+    /// Stopped with an error
+    Failed,
+}
+
+/// One run of a job
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct JobRun {
+    /// Run ID
+    pub id: Uuid,
+    /// Name of the job
+    pub job: String,
+    /// What started it
+    pub trigger: JobTrigger,
+    /// Reporting date covered
+    pub date: NaiveDate,
+    /// When it started
+    pub started_at: DateTime<Utc>,
+    /// When it finished
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Outcome
+    pub status: JobStatus,
+    /// Rows or events written
+    pub records: usize,
+    /// Paths or object URLs of the files written
+    pub files: Vec<String>,
+    /// Why it failed
+    pub error: Option<String>,
+}
+
+/// A registered job with its schedules
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct JobInfo {
+    /// Name of the job
+    pub name: String,
+    /// What it produces
+    pub description: String,
+    /// Schedules it runs on, empty when it only runs on demand
+    pub schedules: Vec<String>,
+    /// Next scheduled run
+    pub next_run_at: Option<DateTime<Utc>>,
+    /// Most recent run
+    pub last_run: Option<JobRun>,
+}
+
+/// Runs report jobs on their schedules and on demand, keeping their history
+pub struct ReportScheduler {
+    /// Name -> job
+    jobs: BTreeMap<&'static str, Arc<dyn ReportJob>>,
+    /// Schedules of registered jobs
+    schedules: Vec<JobSchedule>,
+    /// Runs, newest last
+    runs: RwLock<VecDeque<JobRun>>,
+    /// Runs kept
+    history: usize,
+    /// Jobs currently running
+    running: Mutex<HashSet<&'static str>>,
+}
+
+impl ReportScheduler {
+    /// Create a scheduler of the given jobs
+    ///
+    /// Schedules of jobs that are not registered are ignored.
+    pub fn new(config: SchedulerConfig, jobs: Vec<Arc<dyn ReportJob>>) -> Self {
+        let jobs: BTreeMap<&'static str, Arc<dyn ReportJob>> = jobs.into_iter().map(|job| (job.name(), job)).collect();
+        let schedules = config.schedules
+            .into_iter()
+            .filter(|schedule| {
+                let known = jobs.contains_key(schedule.job.as_str());
+                if !known {
+                    warn!("Ignoring schedule of unknown report job {}", schedule.job);
+                }
+                known
+            })
+            .collect();
+
+        Self {
+            jobs,
+            schedules,
+            runs: RwLock::new(VecDeque::new()),
+            history: config.history.max(1),
+            running: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Whether a job is registered
+    pub fn has_job(&self, name: &str) -> bool {
+        self.jobs.contains_key(name)
+    }
+
+    /// Registered jobs with their schedules and latest runs, by name
+    pub fn jobs(&self, now: DateTime<Utc>) -> Vec<JobInfo> {
+        self.jobs
+            .values()
+            .map(|job| {
+                let schedules: Vec<&CronSchedule> = self.schedules
+                    .iter()
+                    .filter(|schedule| schedule.job == job.name())
+                    .map(|schedule| &schedule.schedule)
+                    .collect();
+                JobInfo {
+                    name: job.name().to_string(),
+                    description: job.description().to_string(),
+                    next_run_at: schedules.iter().filter_map(|schedule| schedule.next_after(now)).min(),
+                    schedules: schedules.iter().map(|schedule| schedule.to_string()).collect(),
+                    last_run: self.runs(Some(job.name()), 1).pop(),
+                }
+            })
+            .collect()
+    }
+
+    /// Most recent runs, newest first, optionally of one job
+    pub fn runs(&self, job: Option<&str>, limit: usize) -> Vec<JobRun> {
+        self.runs
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|run| job.is_none_or(|job| run.job == job))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// A run still in the history
+    pub fn run_by_id(&self, id: Uuid) -> Option<JobRun> {
+        self.runs.read().unwrap().iter().find(|run| run.id == id).cloned()
+    }
+
+    /// Run a job for a reporting date and wait for it to finish
+    ///
+    /// A failing job still returns its run, with the error. Fails if the job
+    /// is not registered or is already running.
+    pub async fn run(&self, state: &AppState, name: &str, date: NaiveDate, trigger: JobTrigger) -> Result<JobRun> {
+        let job = self.jobs.get(name)
+            .ok_or_else(|| Error::ConfigurationError(format!("Unknown report job: {}", name)))?
+            .clone();
+        if !self.running.lock().unwrap().insert(job.name()) {
+            return Err(Error::Conflict(format!("Report job {} is already running", name)));
+        }
+
+        let mut run = JobRun {
+            id: Uuid::new_v4(),
+            job: job.name().to_string(),
+            trigger,
+            date,
+            started_at: state.matching_engine.clock().now(),
+            finished_at: None,
+            status: JobStatus::Running,
+            records: 0,
+            files: Vec::new(),
+            error: None,
+        };
+        self.record(run.clone());
+
+        let outcome = job.run(state, date).await;
+        self.running.lock().unwrap().remove(job.name());
+
+        run.finished_at = Some(state.matching_engine.clock().now());
+        match outcome {
+            Ok(output) => {
+                info!("Report job {} for {} wrote {} records to {} files", name, date, output.records, output.files.len());
+                run.status = JobStatus::Succeeded;
+                run.records = output.records;
+                run.files = output.files;
+            }
+            Err(e) => {
+                error!("Report job {} for {} failed: {}", name, date, e);
+                run.status = JobStatus::Failed;
+                run.error = Some(e.to_string());
+            }
+        }
+        self.record(run.clone());
+        Ok(run)
+    }
+
+    /// Add a run to the history, or update it if it is there
+    fn record(&self, run: JobRun) {
+        let mut runs = self.runs.write().unwrap();
+        if let Some(existing) = runs.iter_mut().rev().find(|existing| existing.id == run.id) {
+            *existing = run;
+            return;
+        }
+        if runs.len() == self.history {
+            runs.pop_front();
+        }
+        runs.push_back(run);
+    }
+}
+
+/// Run report jobs on their schedules, each covering the UTC day before it starts
+pub fn spawn_scheduler(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut fired: Option<DateTime<Utc>> = None;
+        loop {
+            // Never fire the same minute twice, even if the clock is behind
+            let now = state.matching_engine.clock().now().max(fired.unwrap_or(DateTime::<Utc>::MIN_UTC));
+            let due: Vec<(DateTime<Utc>, &JobSchedule)> = state.scheduler.schedules
+                .iter()
+                .filter_map(|schedule| schedule.schedule.next_after(now).map(|at| (at, schedule)))
+                .collect();
+            let Some(next) = due.iter().map(|(at, _)| *at).min() else {
+                return;
+            };
+
+            tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+
+            fired = Some(next);
+            let date = next.date_naive() - Days::new(1);
+            for job in due.iter().filter(|(at, _)| *at == next).map(|(_, schedule)| schedule.job.clone()) {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = state.scheduler.run(&state, &job, date, JobTrigger::Schedule).await {
+                        warn!("Scheduled report job {} not run: {}", job, e);
+                    }
+                });
+            }
+        }
+    })
+}
@@ -0,0 +1,227 @@
+//! Security events and sessions of accounts
+//!
+//! Every API key in use is a session: the first request with a key starts it,
+//! and it tracks when and from which client addresses the key was last used.
+//! Revoking a session revokes its key. Alongside, each account keeps a log of
+//! security events (most recent [`SECURITY_EVENT_CAPACITY`]) for its holder to
+//! review: keys issued, rotated and revoked, sign-ins, requests from a new
+//! address and rejected uses of its keys.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::auth::{ApiKey, Scope};
+
+/// Number of events kept per account
+pub const SECURITY_EVENT_CAPACITY: usize = 1_000;
+
+/// Number of distinct client addresses remembered per session
+const MAX_SESSION_ADDRESSES: usize = 20;
+
+/// Address recorded when the client's is not known
+const UNKNOWN_ADDRESS: &str = "unknown";
+
+/// Kind of security event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityEventKind {
+    /// An API key was issued
+    ApiKeyCreated,
+    /// An API key was given a new secret
+    ApiKeyRotated,
+    /// An API key was revoked
+    ApiKeyRevoked,
+    /// A key was used for the first time, starting its session
+    Login,
+    /// A key was used from an address its session had not seen
+    IpChanged,
+    /// A key was rejected for its address allowlist or scopes
+    AuthFailed,
+    /// A session was ended, revoking its key
+    SessionRevoked,
+}
+
+/// A recorded security event
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SecurityEvent {
+    /// Event ID
+    pub id: Uuid,
+    /// Account the event concerns
+    pub account_id: Uuid,
+    /// What happened
+    pub kind: SecurityEventKind,
+    /// ID of the API key involved
+    pub key_id: Option<Uuid>,
+    /// Prefix of the API key involved
+    pub key_prefix: Option<String>,
+    /// Client address of the request, if known
+    pub ip_address: Option<String>,
+    /// Event-specific details
+    #[schema(value_type = Object)]
+    pub details: serde_json::Value,
+    /// When it happened
+    pub timestamp: DateTime<Utc>,
+}
+
+/// An API key in use
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Session {
+    /// Session ID, the ID of its API key
+    pub id: Uuid,
+    /// Account the key belongs to
+    pub account_id: Uuid,
+    /// Prefix of the key
+    pub key_prefix: String,
+    /// What the key may be used for
+    pub scopes: BTreeSet<Scope>,
+    /// First request with the key
+    pub started_at: DateTime<Utc>,
+    /// Latest request with the key
+    pub last_seen_at: DateTime<Utc>,
+    /// Client address of the latest request
+    pub ip_address: String,
+    /// Distinct client addresses the key was used from, oldest first
+    pub addresses: Vec<String>,
+    /// Number of requests made with the key
+    pub requests: u64,
+    /// Whether this is the session of the calling key
+    pub current: bool,
+}
+
+/// Security events and sessions of all accounts
+#[derive(Debug, Default)]
+pub struct SecurityLog {
+    /// Account ID -> events, newest last
+    events: DashMap<Uuid, VecDeque<SecurityEvent>>,
+    /// API key ID -> session
+    sessions: DashMap<Uuid, Session>,
+}
+
+impl SecurityLog {
+    /// Create an empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an event about an account, optionally involving one of its keys
+    pub fn record(
+        &self,
+        account_id: Uuid,
+        kind: SecurityEventKind,
+        key: Option<&ApiKey>,
+        client: Option<IpAddr>,
+        details: serde_json::Value,
+    ) -> SecurityEvent {
+        let event = SecurityEvent {
+            id: Uuid::new_v4(),
+            account_id,
+            kind,
+            key_id: key.map(|key| key.id),
+            key_prefix: key.map(|key| key.prefix.clone()),
+            ip_address: client.map(|client| client.to_string()),
+            details,
+            timestamp: Utc::now(),
+        };
+
+        tracing::info!(
+            target: "security",
+            account_id = %account_id,
+            kind = ?kind,
+            key_prefix = ?event.key_prefix,
+            ip_address = ?event.ip_address,
+            "security event"
+        );
+
+        let mut events = self.events.entry(account_id).or_default();
+        if events.len() == SECURITY_EVENT_CAPACITY {
+            events.pop_front();
+        }
+        events.push_back(event.clone());
+        event
+    }
+
+    /// Most recent events of an account, newest first
+    pub fn events(&self, account_id: Uuid, limit: usize) -> Vec<SecurityEvent> {
+        self.events
+            .get(&account_id)
+            .map(|events| events.iter().rev().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Note an authenticated request with `key` from `client`
+    ///
+    /// The first request starts the key's session and is recorded as a
+    /// login; later requests from an address the session has not seen are
+    /// recorded as an address change.
+    pub fn touch(&self, key: &ApiKey, client: Option<IpAddr>) {
+        let address = client.map(|client| client.to_string()).unwrap_or_else(|| UNKNOWN_ADDRESS.to_string());
+        let now = Utc::now();
+
+        let previous = {
+            let mut started = false;
+            let mut session = self.sessions.entry(key.id).or_insert_with(|| {
+                started = true;
+                Session {
+                    id: key.id,
+                    account_id: key.account_id,
+                    key_prefix: key.prefix.clone(),
+                    scopes: key.scopes.clone(),
+                    started_at: now,
+                    last_seen_at: now,
+                    ip_address: address.clone(),
+                    addresses: vec![address.clone()],
+                    requests: 0,
+                    current: false,
+                }
+            });
+            session.requests += 1;
+            session.last_seen_at = now;
+            let previous = std::mem::replace(&mut session.ip_address, address.clone());
+
+            if started {
+                None
+            } else if session.addresses.contains(&address) {
+                return;
+            } else {
+                if session.addresses.len() == MAX_SESSION_ADDRESSES {
+                    session.addresses.remove(0);
+                }
+                session.addresses.push(address.clone());
+                Some(previous)
+            }
+        };
+
+        match previous {
+            None => self.record(key.account_id, SecurityEventKind::Login, Some(key), client, json!({
+                "scopes": key.scopes,
+            })),
+            Some(previous) => self.record(key.account_id, SecurityEventKind::IpChanged, Some(key), client, json!({
+                "previous_address": previous,
+                "address": address,
+            })),
+        };
+    }
+
+    /// Sessions of an account, most recently used first
+    pub fn sessions(&self, account_id: Uuid) -> Vec<Session> {
+        let mut sessions: Vec<Session> = self.sessions
+            .iter()
+            .filter(|entry| entry.value().account_id == account_id)
+            .map(|entry| entry.value().clone())
+            .collect();
+        sessions.sort_by(|a, b| b.last_seen_at.cmp(&a.last_seen_at).then_with(|| a.id.cmp(&b.id)));
+        sessions
+    }
+
+    /// Forget the session of a revoked key, returning it if there was one
+    pub fn end_session(&self, key_id: Uuid) -> Option<Session> {
+        self.sessions.remove(&key_id).map(|(_, session)| session)
+    }
+}
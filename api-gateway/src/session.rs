@@ -0,0 +1,54 @@
+//! Trading session clock
+//!
+//! Moves markets between open, closed and auction states as their trading
+//! calendars dictate, and settles the trades of auctions as they end. Every
+//! change is published on the `system` channel.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use matching_engine::SessionChange;
+use tracing::warn;
+
+use crate::AppState;
+
+/// How often market sessions are brought up to date
+const SESSION_CLOCK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Update market sessions every second
+pub fn spawn_session_clock(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(SESSION_CLOCK_INTERVAL);
+        loop {
+            ticks.tick().await;
+            update_sessions(&state, state.matching_engine.clock().now()).await;
+        }
+    })
+}
+
+/// Apply the session changes due at `now`, settling auction trades
+pub async fn update_sessions(state: &AppState, now: DateTime<Utc>) -> Vec<SessionChange> {
+    let mut changes = Vec::new();
+    for (change, auction) in state.matching_engine.update_sessions(now) {
+        for trade in &auction.trades {
+            if let Err(e) = state.account_service.process_trade(trade).await {
+                warn!("Failed to settle auction trade {} in {}: {}", trade.id, change.market, e);
+            }
+            if let Err(e) = state.market_data_service.process_trade(trade).await {
+                warn!("Failed to publish auction trade {} in {}: {}", trade.id, change.market, e);
+            }
+        }
+
+        if !auction.trades.is_empty() {
+            if let Ok((bids, asks)) = state.matching_engine.get_market_depth(&change.market, 10) {
+                if let Err(e) = state.market_data_service.update_order_book(&change.market, bids, asks).await {
+                    warn!("Failed to update order book of {}: {}", change.market, e);
+                }
+            }
+        }
+        state.system.market_status(&change).await;
+        changes.push(change);
+    }
+    changes
+}
@@ -0,0 +1,153 @@
+//! External exchange feed for shadow markets
+//!
+//! Reads books and recent trades from an exchange's public REST API in the
+//! widely copied Binance layout: `GET /api/v3/depth?symbol=BTCUSDT&limit=20`
+//! and `GET /api/v3/trades?symbol=BTCUSDT&limit=100`. Market data mirrors them
+//! into read-only shadow markets.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::decimal::{Price, Quantity};
+use common::error::{Error, Result};
+use common::model::order::Side;
+use market_data::shadow::{ExternalMarketFetcher, ExternalSnapshot, ExternalTrade, ShadowMarket};
+use market_data::sync::Levels;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+/// Shadow market settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowConfig {
+    /// Base URL of the exchange's public REST API, shadow markets are off without one
+    pub url: Option<String>,
+    /// Markets to mirror
+    pub markets: Vec<ShadowMarket>,
+    /// Time between polls of each market
+    pub poll_interval: Duration,
+    /// Book levels mirrored per side
+    pub depth: usize,
+    /// Recent trades requested per poll
+    pub trades: usize,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            markets: Vec::new(),
+            poll_interval: Duration::from_secs(2),
+            depth: 20,
+            trades: 100,
+        }
+    }
+}
+
+impl ShadowConfig {
+    /// Whether an exchange and markets to mirror are configured
+    pub fn is_enabled(&self) -> bool {
+        self.url.is_some() && !self.markets.is_empty()
+    }
+
+    /// Fetcher for the configured exchange
+    pub fn fetcher(&self) -> Option<RestMarketFetcher> {
+        let url = self.url.as_ref()?;
+        Some(RestMarketFetcher::new(url, self.depth, self.trades, self.poll_interval))
+    }
+}
+
+/// Book as the exchange sends it, levels as `[price, quantity]` strings
+#[derive(Deserialize)]
+struct DepthResponse {
+    bids: Vec<(Price, Quantity)>,
+    asks: Vec<(Price, Quantity)>,
+}
+
+/// Trade as the exchange sends it
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TradeResponse {
+    id: u64,
+    price: Price,
+    qty: Quantity,
+    /// Milliseconds since the epoch
+    time: i64,
+    is_buyer_maker: bool,
+}
+
+/// Reads books and trades from an exchange's public REST API
+pub struct RestMarketFetcher {
+    url: String,
+    depth: usize,
+    trades: usize,
+    client: reqwest::Client,
+    timeout: Duration,
+}
+
+impl RestMarketFetcher {
+    /// Fetch `depth` levels and `trades` recent trades per market from the API at `url`
+    pub fn new(url: &str, depth: usize, trades: usize, timeout: Duration) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            depth,
+            trades,
+            client: reqwest::Client::new(),
+            timeout,
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, symbol: &str, limit: usize) -> Result<T> {
+        let url = format!("{}{}", self.url, path);
+        let response = self.client
+            .get(&url)
+            .query(&[("symbol", symbol), ("limit", &limit.to_string())])
+            .timeout(self.timeout)
+            .send()
+            .await
+            .map_err(|e| Error::Internal(format!("Request to {} failed: {}", url, e)))?;
+
+        if !response.status().is_success() {
+            return Err(Error::Internal(format!("{} responded with {}", url, response.status())));
+        }
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to read {}: {}", url, e)))?;
+        serde_json::from_slice(&body).map_err(|e| Error::Internal(format!("Invalid response from {}: {}", url, e)))
+    }
+}
+
+#[async_trait]
+impl ExternalMarketFetcher for RestMarketFetcher {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    async fn fetch(&self, external_symbol: &str) -> Result<ExternalSnapshot> {
+        let depth: DepthResponse = self.get("/api/v3/depth", external_symbol, self.depth).await?;
+        let trades: Vec<TradeResponse> = self.get("/api/v3/trades", external_symbol, self.trades).await?;
+
+        let trades = trades
+            .into_iter()
+            .map(|trade| ExternalTrade {
+                id: trade.id,
+                price: trade.price,
+                quantity: trade.qty,
+                taker_side: if trade.is_buyer_maker { Side::Sell } else { Side::Buy },
+                executed_at: DateTime::from_timestamp_millis(trade.time).unwrap_or_else(Utc::now),
+            })
+            .collect();
+
+        Ok(ExternalSnapshot {
+            bids: positive(depth.bids),
+            asks: positive(depth.asks),
+            trades,
+        })
+    }
+}
+
+/// Drop levels the exchange sends with nothing left on them
+fn positive(levels: Levels) -> Levels {
+    levels.into_iter().filter(|(_, quantity)| *quantity > Quantity::ZERO).collect()
+}
@@ -0,0 +1,169 @@
+//! System status and announcements
+//!
+//! Publishes on the `system` WebSocket channel when a market's trading session
+//! changes, when a component checked by `/health` goes down or recovers, and
+//! when an operator posts an announcement such as a maintenance window, so
+//! clients learn about incidents as they happen.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Utc};
+use common::error::{Error, Result};
+use dashmap::DashMap;
+use market_data::channel::{MarketDataChannel, Topic};
+use matching_engine::SessionChange;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::ws::message::SystemEvent;
+
+/// Most announcements kept for clients that connect after they were posted
+const MAX_ANNOUNCEMENTS: usize = 100;
+
+/// Status of a component checked by `/health`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentStatus {
+    /// Responding
+    Up,
+    /// Not responding
+    Down,
+}
+
+/// What an announcement is about
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnouncementKind {
+    /// General notice
+    #[default]
+    General,
+    /// Planned maintenance window
+    Maintenance,
+    /// Ongoing incident
+    Incident,
+}
+
+/// How urgent an announcement is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// For information only
+    #[default]
+    Info,
+    /// May affect trading
+    Warning,
+    /// Trading is affected
+    Critical,
+}
+
+/// An operator announcement
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Announcement {
+    /// Announcement ID
+    pub id: Uuid,
+    /// What the announcement is about
+    pub kind: AnnouncementKind,
+    /// How urgent it is
+    pub severity: Severity,
+    /// One-line summary
+    pub title: String,
+    /// Details
+    pub message: String,
+    /// Start of the window the announcement covers, such as a maintenance window
+    pub starts_at: Option<DateTime<Utc>>,
+    /// End of that window
+    pub ends_at: Option<DateTime<Utc>>,
+    /// When it was published
+    pub published_at: DateTime<Utc>,
+}
+
+/// Last known component statuses and recent announcements
+pub struct SystemStatus {
+    channel: Arc<MarketDataChannel>,
+    components: DashMap<String, ComponentStatus>,
+    /// Newest first
+    announcements: RwLock<VecDeque<Announcement>>,
+}
+
+impl SystemStatus {
+    /// Create a tracker publishing on the given channel
+    pub fn new(channel: Arc<MarketDataChannel>) -> Self {
+        Self {
+            channel,
+            components: DashMap::new(),
+            announcements: RwLock::new(VecDeque::new()),
+        }
+    }
+
+    /// Publish a market's session change
+    pub async fn market_status(&self, change: &SessionChange) {
+        self.publish(SystemEvent::MarketStatus {
+            market: change.market.clone(),
+            previous: change.previous,
+            state: change.state,
+            timestamp: change.at,
+        }).await;
+    }
+
+    /// Record the result of a component check, publishing it if the status changed
+    ///
+    /// A component first seen up is not published, since nothing changed for
+    /// clients. Returns whether the status changed.
+    pub async fn report_component(&self, component: &str, healthy: bool) -> bool {
+        let status = if healthy { ComponentStatus::Up } else { ComponentStatus::Down };
+        let previous = self.components.insert(component.to_string(), status);
+        if previous == Some(status) || (previous.is_none() && status == ComponentStatus::Up) {
+            return false;
+        }
+
+        match status {
+            ComponentStatus::Up => info!("Component {} recovered", component),
+            ComponentStatus::Down => warn!("Component {} is down", component),
+        }
+        self.publish(SystemEvent::ComponentStatus {
+            component: component.to_string(),
+            status,
+            timestamp: Utc::now(),
+        }).await;
+        true
+    }
+
+    /// Last known status of a component
+    pub fn component(&self, component: &str) -> Option<ComponentStatus> {
+        self.components.get(component).map(|status| *status)
+    }
+
+    /// Validate, keep and publish an announcement
+    pub async fn announce(&self, announcement: Announcement) -> Result<Announcement> {
+        if announcement.title.trim().is_empty() {
+            return Err(Error::ValidationError("Announcement title must not be empty".to_string()));
+        }
+        if let (Some(starts_at), Some(ends_at)) = (announcement.starts_at, announcement.ends_at) {
+            if ends_at <= starts_at {
+                return Err(Error::ValidationError("Announcement must end after it starts".to_string()));
+            }
+        }
+
+        {
+            let mut announcements = self.announcements.write().unwrap();
+            announcements.push_front(announcement.clone());
+            announcements.truncate(MAX_ANNOUNCEMENTS);
+        }
+        info!("Published {:?} announcement {}: {}", announcement.kind, announcement.id, announcement.title);
+
+        self.publish(SystemEvent::Announcement(announcement.clone())).await;
+        Ok(announcement)
+    }
+
+    /// Most recent announcements, newest first
+    pub fn announcements(&self, limit: usize) -> Vec<Announcement> {
+        self.announcements.read().unwrap().iter().take(limit).cloned().collect()
+    }
+
+    async fn publish(&self, event: SystemEvent) {
+        self.channel.publish(Topic::System, event).await;
+    }
+}
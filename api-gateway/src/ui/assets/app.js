@@ -0,0 +1,203 @@
+// Exchange demo UI: market data over the WebSocket API, orders over REST.
+'use strict';
+
+const API = '/api/v1';
+const WS_URL = `${location.protocol === 'https:' ? 'wss' : 'ws'}://${location.host}/ws`;
+const MAX_TRADES = 30;
+const DEMO_DEPOSITS = { quote: '100000', base: '10' };
+
+const $ = (id) => document.getElementById(id);
+const state = { markets: [], market: null, socket: null, nextId: 1, account: loadAccount() };
+
+function loadAccount() {
+  try {
+    return JSON.parse(localStorage.getItem('zavora.account'));
+  } catch {
+    return null;
+  }
+}
+
+async function api(method, path, body) {
+  const headers = { 'content-type': 'application/json' };
+  if (state.account) headers['x-api-key'] = state.account.apiKey;
+  const response = await fetch(API + path, {
+    method,
+    headers,
+    body: body === undefined ? undefined : JSON.stringify(body),
+  });
+  const json = await response.json().catch(() => ({}));
+  if (!response.ok) throw new Error(json.error ? json.error.message : response.statusText);
+  return json.data;
+}
+
+function cell(text, className) {
+  const td = document.createElement('td');
+  td.textContent = text;
+  if (className) td.className = className;
+  return td;
+}
+
+function row(...cells) {
+  const tr = document.createElement('tr');
+  tr.append(...cells);
+  return tr;
+}
+
+// Market data
+
+function renderBook(book) {
+  const asks = book.asks.slice(0, 10).reverse();
+  $('asks').replaceChildren(...asks.map((level) => row(cell(level.price), cell(level.quantity))));
+  $('bids').replaceChildren(...book.bids.slice(0, 10).map((level) => row(cell(level.price), cell(level.quantity))));
+
+  const bestBid = book.bids[0];
+  const bestAsk = book.asks[0];
+  const spread = bestBid && bestAsk ? `spread ${(bestAsk.price - bestBid.price).toFixed(2)}` : '-';
+  $('spread').replaceChildren(row(Object.assign(cell(spread), { colSpan: 2 })));
+}
+
+function renderTicker(ticker) {
+  for (const span of document.querySelectorAll('#ticker [data-field]')) {
+    const value = ticker[span.dataset.field];
+    span.textContent = value === null || value === undefined ? '-' : value;
+  }
+}
+
+function addTrade(trade) {
+  const time = new Date(trade.timestamp).toLocaleTimeString();
+  const side = trade.taker_side === 'buy' ? 'buy' : 'sell';
+  $('trades').prepend(row(cell(trade.price, side), cell(trade.quantity), cell(time)));
+  while ($('trades').children.length > MAX_TRADES) $('trades').lastChild.remove();
+}
+
+function send(method, params) {
+  if (state.socket && state.socket.readyState === WebSocket.OPEN) {
+    state.socket.send(JSON.stringify({ id: String(state.nextId++), method, params }));
+  }
+}
+
+function subscribe() {
+  $('trades').replaceChildren();
+  for (const channel of ['orderbook', 'ticker', 'trades']) {
+    send('subscribe', { channel, market: state.market });
+  }
+}
+
+function connect() {
+  const socket = new WebSocket(WS_URL);
+  state.socket = socket;
+
+  socket.onopen = () => {
+    $('connection').textContent = 'live';
+    $('connection').classList.add('connected');
+    subscribe();
+  };
+  socket.onclose = () => {
+    $('connection').textContent = 'reconnecting';
+    $('connection').classList.remove('connected');
+    setTimeout(connect, 2000);
+  };
+  socket.onmessage = (event) => {
+    const message = JSON.parse(event.data);
+    if (!message.params || message.params.market !== state.market) return;
+    const data = message.params.data;
+    if (message.method === 'orderbook') renderBook(data);
+    if (message.method === 'ticker') renderTicker(data);
+    if (message.method === 'trades') addTrade(data);
+  };
+}
+
+async function selectMarket(symbol) {
+  // Drop the previous market's subscriptions by reconnecting
+  state.market = symbol;
+  renderBook({ bids: [], asks: [] });
+  renderTicker({});
+  if (state.socket) {
+    state.socket.onclose = null;
+    state.socket.close();
+  }
+  connect();
+  await refreshBalances();
+}
+
+// Account and orders
+
+function currentMarket() {
+  return state.markets.find((market) => market.symbol === state.market);
+}
+
+async function refreshBalances() {
+  $('ticket').querySelector('button').disabled = !state.account;
+  $('create-account').hidden = Boolean(state.account);
+  if (!state.account) return;
+
+  $('account-id').textContent = `Account ${state.account.id}`;
+  try {
+    const balances = await api('GET', `/accounts/${state.account.id}/balances`);
+    $('balances').replaceChildren(...balances.map((b) => row(cell(b.asset), cell(b.available), cell(`${b.locked} locked`))));
+  } catch (error) {
+    // Accounts live in memory and vanish when the server restarts
+    localStorage.removeItem('zavora.account');
+    state.account = null;
+    $('account-id').textContent = 'No account';
+    $('balances').replaceChildren();
+    refreshBalances();
+  }
+}
+
+async function createAccount() {
+  const created = await api('POST', '/accounts', {});
+  state.account = { id: created.id, apiKey: created.api_key };
+  localStorage.setItem('zavora.account', JSON.stringify(state.account));
+
+  const market = currentMarket();
+  await api('POST', `/accounts/${created.id}/deposit`, { asset: market.quote_asset, amount: DEMO_DEPOSITS.quote });
+  await api('POST', `/accounts/${created.id}/deposit`, { asset: market.base_asset, amount: DEMO_DEPOSITS.base });
+  await refreshBalances();
+}
+
+async function placeOrder(event) {
+  event.preventDefault();
+  const form = new FormData(event.target);
+  const orderType = form.get('order_type');
+  const order = {
+    user_id: state.account.id,
+    market: state.market,
+    side: form.get('side'),
+    order_type: orderType,
+    quantity: form.get('quantity'),
+  };
+  if (orderType === 'Limit') order.price = form.get('price');
+
+  const result = $('result');
+  try {
+    const placed = await api('POST', '/orders', order);
+    result.className = '';
+    result.textContent = `${placed.order.side} ${placed.order.quantity} ${placed.order.status}, ${placed.trades.length} trade(s)`;
+  } catch (error) {
+    result.className = 'error';
+    result.textContent = error.message;
+  }
+  await refreshBalances();
+}
+
+async function start() {
+  $('create-account').addEventListener('click', () => createAccount().catch((error) => {
+    $('result').className = 'error';
+    $('result').textContent = error.message;
+  }));
+  $('ticket').addEventListener('submit', placeOrder);
+  $('ticket').elements.order_type.addEventListener('change', (event) => {
+    $('ticket').elements.price.disabled = event.target.value === 'Market';
+  });
+  $('market').addEventListener('change', (event) => selectMarket(event.target.value));
+
+  state.markets = await api('GET', '/markets');
+  $('market').replaceChildren(...state.markets.map((market) => new Option(market.symbol, market.symbol)));
+  if (state.markets.length > 0) await selectMarket(state.markets[0].symbol);
+}
+
+start().catch((error) => {
+  $('result').className = 'error';
+  $('result').textContent = `Failed to load markets: ${error.message}`;
+});
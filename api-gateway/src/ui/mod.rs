@@ -0,0 +1,31 @@
+//! Embedded web UI
+//!
+//! A small single-page app compiled into the binary with the `ui` feature and
+//! served at `/app`. It shows a market's order book, ticker and trades from
+//! the WebSocket API, and has an order ticket that creates a funded demo
+//! account and places orders through the REST API.
+
+use axum::{
+    http::header,
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+
+const INDEX_HTML: &str = include_str!("assets/index.html");
+const APP_JS: &str = include_str!("assets/app.js");
+const APP_CSS: &str = include_str!("assets/app.css");
+
+/// Routes serving the UI under `/app`, to merge beside `/api/v1` and `/ws`
+pub fn router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new()
+        .route("/app", get(|| async { asset("text/html; charset=utf-8", INDEX_HTML) }))
+        .route("/app/", get(|| async { asset("text/html; charset=utf-8", INDEX_HTML) }))
+        .route("/app/app.js", get(|| async { asset("text/javascript; charset=utf-8", APP_JS) }))
+        .route("/app/app.css", get(|| async { asset("text/css; charset=utf-8", APP_CSS) }))
+}
+
+/// A bundled file; revalidated on every load so a new build is picked up
+fn asset(content_type: &'static str, body: &'static str) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, content_type), (header::CACHE_CONTROL, "no-cache")], body)
+}
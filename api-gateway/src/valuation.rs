@@ -0,0 +1,230 @@
+//! Portfolio valuation
+//!
+//! Values an account's balances in a chosen quote currency at mark prices.
+//! An asset with no market against the quote is converted through a chain of
+//! markets found by a [`ConversionResolver`]; the default one takes the chain
+//! with the fewest markets. Assets that cannot be converted, or whose chain
+//! crosses a market without a price, are reported without a value. Ticker
+//! prices and quote volumes are converted the same way.
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+use common::decimal::{Price, Quantity};
+use common::model::account::Balance;
+use common::model::market::Market;
+use market_data::Ticker;
+use matching_engine::MatchingEngine;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// One market crossed when converting an asset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ConversionLeg {
+    /// Market symbol
+    pub market: String,
+    /// Whether the asset is sold for the market's base asset, dividing by the
+    /// mark price instead of multiplying
+    pub inverse: bool,
+}
+
+/// Finds the markets to convert one asset into another through
+pub trait ConversionResolver: Send + Sync {
+    /// Markets to cross in order, or `None` if the assets are not connected
+    fn resolve(&self, markets: &[Market], from: &str, to: &str) -> Option<Vec<ConversionLeg>>;
+}
+
+/// Converts through the fewest markets, preferring them in listing order
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShortestPathResolver;
+
+impl ConversionResolver for ShortestPathResolver {
+    fn resolve(&self, markets: &[Market], from: &str, to: &str) -> Option<Vec<ConversionLeg>> {
+        // Breadth-first search over assets, remembering the leg into each one
+        let mut reached: HashMap<&str, Option<(&str, ConversionLeg)>> = HashMap::from([(from, None)]);
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(asset) = queue.pop_front() {
+            if asset == to {
+                let mut legs = Vec::new();
+                let mut current = to;
+                while let Some(Some((previous, leg))) = reached.get(current) {
+                    legs.push(leg.clone());
+                    current = previous;
+                }
+                legs.reverse();
+                return Some(legs);
+            }
+
+            for market in markets {
+                let (next, inverse) = if market.base_asset == asset {
+                    (market.quote_asset.as_str(), false)
+                } else if market.quote_asset == asset {
+                    (market.base_asset.as_str(), true)
+                } else {
+                    continue;
+                };
+                if !reached.contains_key(next) {
+                    reached.insert(next, Some((asset, ConversionLeg { market: market.symbol.clone(), inverse })));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// One asset of a portfolio
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AssetValuation {
+    /// Asset symbol
+    pub asset: String,
+    /// Amount not locked in orders
+    pub available: Quantity,
+    /// Amount locked in open orders
+    pub locked: Quantity,
+    /// Available and locked amount
+    pub total: Quantity,
+    /// Price of one unit in the quote currency, if it could be converted
+    pub price: Option<Price>,
+    /// Value of the total amount in the quote currency
+    pub value: Option<Decimal>,
+    /// Markets the price was converted through
+    pub path: Vec<ConversionLeg>,
+}
+
+/// Balances valued in one quote currency
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Portfolio {
+    /// Currency values are given in
+    pub quote: String,
+    /// Value of all assets that could be converted
+    pub total_value: Decimal,
+    /// Value of the available amounts
+    pub available_value: Decimal,
+    /// Value of the amounts locked in open orders
+    pub locked_value: Decimal,
+    /// Per-asset breakdown
+    pub assets: Vec<AssetValuation>,
+    /// Assets left out of the totals for lack of a conversion or a price
+    pub unpriced: Vec<String>,
+    /// When the portfolio was valued
+    pub valued_at: DateTime<Utc>,
+}
+
+/// Price of one unit of an asset along a conversion path at current mark prices
+pub fn conversion_price(engine: &MatchingEngine, path: &[ConversionLeg]) -> Option<Price> {
+    path.iter().try_fold(Decimal::ONE, |price, leg| {
+        let mark = engine.mark_price(&leg.market).ok().flatten()?;
+        if leg.inverse {
+            price.checked_div(mark)
+        } else {
+            price.checked_mul(mark)
+        }
+    })
+}
+
+/// Value balances in the quote currency
+pub fn value_balances(
+    engine: &MatchingEngine,
+    markets: &[Market],
+    resolver: &dyn ConversionResolver,
+    balances: Vec<Balance>,
+    quote: &str,
+) -> Portfolio {
+    let mut portfolio = Portfolio {
+        quote: quote.to_string(),
+        total_value: Decimal::ZERO,
+        available_value: Decimal::ZERO,
+        locked_value: Decimal::ZERO,
+        assets: Vec::with_capacity(balances.len()),
+        unpriced: Vec::new(),
+        valued_at: Utc::now(),
+    };
+
+    for balance in balances {
+        let path = resolver.resolve(markets, &balance.asset, quote).unwrap_or_default();
+        let price = if balance.asset == quote {
+            Some(Decimal::ONE)
+        } else if path.is_empty() {
+            None
+        } else {
+            conversion_price(engine, &path)
+        };
+
+        let value = price.map(|price| balance.total * price);
+        match price {
+            Some(price) => {
+                portfolio.total_value += balance.total * price;
+                portfolio.available_value += balance.available * price;
+                portfolio.locked_value += balance.locked * price;
+            }
+            None => portfolio.unpriced.push(balance.asset.clone()),
+        }
+
+        portfolio.assets.push(AssetValuation {
+            asset: balance.asset,
+            available: balance.available,
+            locked: balance.locked,
+            total: balance.total,
+            price,
+            value,
+            path,
+        });
+    }
+    portfolio
+}
+
+/// Ticker prices and quote volume converted into another currency
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TickerConversion {
+    /// Currency the values are given in
+    pub currency: String,
+    /// Price of one unit of the market's quote asset in the currency, if it
+    /// could be converted
+    pub rate: Option<Price>,
+    /// Last trade price
+    pub last: Option<Price>,
+    /// 24h high price
+    pub high_24h: Option<Price>,
+    /// 24h low price
+    pub low_24h: Option<Price>,
+    /// 24h volume in the market's quote asset, valued in the currency
+    pub quote_volume_24h: Option<Quantity>,
+    /// Markets the quote asset was converted through
+    pub path: Vec<ConversionLeg>,
+}
+
+/// Convert a ticker's prices and quote volume into `currency`
+///
+/// All values are `None` when the market is unknown or its quote asset cannot
+/// be converted at current mark prices.
+pub fn convert_ticker(
+    engine: &MatchingEngine,
+    markets: &[Market],
+    resolver: &dyn ConversionResolver,
+    ticker: &Ticker,
+    currency: &str,
+) -> TickerConversion {
+    let quote = markets.iter().find(|market| market.symbol == ticker.market).map(|market| market.quote_asset.as_str());
+    let (rate, path) = match quote {
+        Some(quote) if quote == currency => (Some(Decimal::ONE), Vec::new()),
+        Some(quote) => match resolver.resolve(markets, quote, currency) {
+            Some(path) => (conversion_price(engine, &path), path),
+            None => (None, Vec::new()),
+        },
+        None => (None, Vec::new()),
+    };
+    let convert = |value: Option<Decimal>| rate.zip(value).map(|(rate, value)| value * rate);
+
+    TickerConversion {
+        currency: currency.to_string(),
+        rate,
+        last: convert(ticker.last),
+        high_24h: convert(ticker.high_24h),
+        low_24h: convert(ticker.low_24h),
+        quote_volume_24h: convert(ticker.quote_volume_24h),
+        path,
+    }
+}
@@ -0,0 +1,209 @@
+//! REST API versions
+//!
+//! Every version is the same set of handlers mounted under its own prefix,
+//! `/api/v1` and `/api/v2`, with the differences between versions applied as
+//! layers, so a breaking change to a response shape does not fork the
+//! handler. Version 2 differs from version 1 in that:
+//!
+//! - every fractional number is a decimal string, unless the client asks for
+//!   the `native` number format
+//! - error codes are upper snake case (`ORDER_NOT_FOUND`) and the request ID
+//!   is under `meta`, like successful responses
+//!
+//! Requests to `/api/...` without a version are served by the version named
+//! in `X-API-Version`, or version 1. Every response names the version that
+//! served it in `X-API-Version`.
+
+use std::fmt;
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use tracing::warn;
+use utoipa::openapi::OpenApi;
+
+use crate::config::AppConfig;
+use crate::error::ApiError;
+use crate::number_format::NumberFormat;
+use crate::routes::api_router_for;
+use crate::AppState;
+
+/// Header naming the API version of a request or response
+pub const VERSION_HEADER: &str = "x-api-version";
+
+/// REST API version
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApiVersion {
+    /// Original API
+    #[default]
+    V1,
+    /// Decimal strings throughout and upper snake case error codes
+    V2,
+}
+
+impl ApiVersion {
+    /// Versions served, oldest first
+    pub const SUPPORTED: [ApiVersion; 2] = [ApiVersion::V1, ApiVersion::V2];
+
+    /// Version number
+    pub fn number(&self) -> u64 {
+        match self {
+            ApiVersion::V1 => 1,
+            ApiVersion::V2 => 2,
+        }
+    }
+
+    /// Path the version is mounted under
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/api/v1",
+            ApiVersion::V2 => "/api/v2",
+        }
+    }
+
+    /// Version with the given number, as `2` or `v2`
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let number = value.strip_prefix(['v', 'V']).unwrap_or(value).parse::<u64>().ok()?;
+        Self::SUPPORTED.into_iter().find(|version| version.number() == number)
+    }
+
+    /// JSON number format of clients that do not ask for one
+    pub fn number_format(&self, configured: NumberFormat) -> NumberFormat {
+        match self {
+            ApiVersion::V1 => configured,
+            ApiVersion::V2 => NumberFormat::DecimalStrings,
+        }
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.number())
+    }
+}
+
+/// Routes of every API version, and of `/api` for clients that negotiate one
+pub fn versioned_api(state: Arc<AppState>, config: &AppConfig, public: Router<Arc<AppState>>) -> Router {
+    let versions: Vec<(ApiVersion, Router)> = ApiVersion::SUPPORTED
+        .into_iter()
+        .map(|version| (version, api_router_for(version, state.clone(), config, public.clone())))
+        .collect();
+
+    let router = versions
+        .iter()
+        .fold(Router::new(), |router, (version, api)| router.nest(version.prefix(), api.clone()));
+    router.nest("/api", Router::new().fallback(negotiate).with_state(Arc::new(versions)))
+}
+
+/// Serve an unversioned request with the version it asks for in `X-API-Version`
+async fn negotiate(State(versions): State<Arc<Vec<(ApiVersion, Router)>>>, request: Request) -> Response {
+    let requested = request.headers().get(VERSION_HEADER).map(|value| value.to_str().unwrap_or_default());
+    let version = match requested {
+        None => ApiVersion::default(),
+        Some(value) => match ApiVersion::parse(value) {
+            Some(version) => version,
+            None => {
+                let supported: Vec<String> = ApiVersion::SUPPORTED.iter().map(ApiVersion::to_string).collect();
+                return ApiError::BadRequest(format!(
+                    "Unsupported API version {:?}, expected one of {}",
+                    value,
+                    supported.join(", ")
+                ))
+                .into_response();
+            }
+        },
+    };
+
+    let router = versions.iter().find(|(served, _)| *served == version).map(|(_, router)| router.clone());
+    match router {
+        Some(router) => router.oneshot(request).await.into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Name the version that served a response, and point `Location` at the
+/// same version
+pub async fn tag_version(State(version): State<ApiVersion>, request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(VERSION_HEADER, HeaderValue::from(version.number()));
+
+    let location = headers.get(header::LOCATION).and_then(|value| value.to_str().ok());
+    if let Some(rest) = location.and_then(|location| location.strip_prefix(ApiVersion::V1.prefix())) {
+        if let Ok(location) = HeaderValue::from_str(&format!("{}{}", version.prefix(), rest)) {
+            headers.insert(header::LOCATION, location);
+        }
+    }
+    response
+}
+
+/// Apply the response shapes of a version
+pub fn version_layers(version: ApiVersion, router: Router<Arc<AppState>>) -> Router<Arc<AppState>> {
+    let router = match version {
+        ApiVersion::V1 => router,
+        ApiVersion::V2 => router.layer(middleware::from_fn(v2_errors)),
+    };
+    router.layer(middleware::from_fn_with_state(version, tag_version))
+}
+
+/// Rewrite error bodies in the version 2 shape
+async fn v2_errors(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    if !(response.status().is_client_error() || response.status().is_server_error()) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Failed to read error body: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let Ok(Value::Object(mut error)) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+
+    if let Some(Value::String(code)) = error.get_mut("error").and_then(|info| info.get_mut("code")) {
+        *code = code.to_ascii_uppercase();
+    }
+    if let Some(request_id) = error.remove("request_id") {
+        error.insert("meta".to_string(), json!({ "request_id": request_id }));
+    }
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(Value::Object(error).to_string()))
+}
+
+/// OpenAPI document of a version, from the document of version 1
+pub fn openapi_for(version: ApiVersion, mut doc: OpenApi) -> OpenApi {
+    if version == ApiVersion::V1 {
+        return doc;
+    }
+
+    let v1 = ApiVersion::V1.prefix();
+    doc.paths.paths = std::mem::take(&mut doc.paths.paths)
+        .into_iter()
+        .map(|(path, item)| match path.strip_prefix(v1) {
+            Some(rest) => (format!("{}{}", version.prefix(), rest), item),
+            None => (path, item),
+        })
+        .collect();
+    doc.info.title = format!("{} ({})", doc.info.title, version);
+    doc.info.description = Some(format!(
+        "Fractional numbers are decimal strings unless `Accept` asks for the `native` profile. \
+         Error codes are upper snake case and the request ID is under `meta`.{}",
+        doc.info.description.map(|description| format!("\n\n{}", description)).unwrap_or_default()
+    ));
+    doc
+}
@@ -0,0 +1,415 @@
+//! Account webhooks
+//!
+//! Accounts register HTTPS URLs to receive fills, order status changes,
+//! deposits and withdrawals as JSON `POST`s. Each request is signed in the
+//! `X-Webhook-Signature` header as `t=<unix seconds>,v1=<hex HMAC-SHA256 of
+//! "t.body">`, keyed with the secret returned when the webhook was registered.
+//! Failed deliveries are retried with exponential backoff, and every delivery
+//! is kept in a per-account log. Deliveries are independent, so receivers must
+//! not rely on their order.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, OnceLock, Weak};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use common::error::{Error, Result};
+use common::model::order::Side;
+use dashmap::DashMap;
+use hmac::{Hmac, Mac};
+use matching_engine::{EngineEvent, MatchingEngine};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::runtime::Handle;
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Header carrying the delivery signature
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+/// Header carrying the event type
+pub const EVENT_HEADER: &str = "x-webhook-event";
+/// Header carrying the delivery ID, stable across retries
+pub const DELIVERY_HEADER: &str = "x-webhook-delivery";
+
+/// Maximum number of webhooks per account
+const MAX_WEBHOOKS_PER_ACCOUNT: usize = 10;
+/// Maximum number of deliveries kept per account
+const DELIVERY_LOG_CAPACITY: usize = 1_000;
+
+/// Kind of account event delivered to webhooks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventType {
+    /// One of the account's orders traded
+    Fill,
+    /// One of the account's orders was placed, filled, cancelled, rejected or expired
+    OrderStatus,
+    /// Funds were deposited
+    Deposit,
+    /// Funds were withdrawn
+    Withdrawal,
+}
+
+impl WebhookEventType {
+    /// Every event type
+    pub const ALL: [WebhookEventType; 4] = [
+        WebhookEventType::Fill,
+        WebhookEventType::OrderStatus,
+        WebhookEventType::Deposit,
+        WebhookEventType::Withdrawal,
+    ];
+
+    /// Name sent in the event header and payload
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookEventType::Fill => "fill",
+            WebhookEventType::OrderStatus => "order_status",
+            WebhookEventType::Deposit => "deposit",
+            WebhookEventType::Withdrawal => "withdrawal",
+        }
+    }
+}
+
+/// Webhook delivery settings
+#[derive(Debug, Clone, Copy)]
+pub struct WebhookConfig {
+    /// Attempts per delivery, including the first
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for every further retry
+    pub initial_backoff: Duration,
+    /// Time allowed for each attempt
+    pub timeout: Duration,
+    /// Accept plain `http://` URLs, for local development
+    pub allow_http: bool,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+            allow_http: false,
+        }
+    }
+}
+
+/// Registered webhook
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Webhook {
+    /// Webhook ID
+    pub id: Uuid,
+    /// Account the webhook belongs to
+    pub account_id: Uuid,
+    /// URL notifications are posted to
+    pub url: String,
+    /// Event types delivered
+    pub events: Vec<WebhookEventType>,
+    /// Signing secret, only returned when the webhook is registered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// When the webhook was registered
+    pub created_at: DateTime<Utc>,
+}
+
+/// Outcome of a delivery
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Being attempted or waiting for a retry
+    Pending,
+    /// Acknowledged with a 2xx response
+    Delivered,
+    /// Gave up after the last attempt
+    Failed,
+}
+
+/// Delivery of one event to one webhook
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Delivery {
+    /// Delivery ID, sent in the `X-Webhook-Delivery` header
+    pub id: Uuid,
+    /// Webhook delivered to
+    pub webhook_id: Uuid,
+    /// Event type
+    pub event: WebhookEventType,
+    /// Delivery state
+    pub status: DeliveryStatus,
+    /// Attempts made so far
+    pub attempts: u32,
+    /// HTTP status of the last attempt, if a response was received
+    pub response_status: Option<u16>,
+    /// Error of the last failed attempt
+    pub error: Option<String>,
+    /// When the event happened
+    pub created_at: DateTime<Utc>,
+    /// When the delivery was last attempted
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A registered webhook with its signing secret
+struct Registration {
+    webhook: Webhook,
+    secret: String,
+}
+
+/// Webhook registrations, deliveries and the delivery log
+pub struct WebhookService {
+    config: WebhookConfig,
+    engine: Arc<MatchingEngine>,
+    client: reqwest::Client,
+    hooks: DashMap<Uuid, Vec<Registration>>,
+    deliveries: DashMap<Uuid, VecDeque<Delivery>>,
+    /// Runtime deliveries run on, set when the engine is first watched
+    runtime: OnceLock<Handle>,
+}
+
+impl WebhookService {
+    /// Create the service for an engine
+    ///
+    /// The engine's events are only watched once the first webhook is registered.
+    pub fn new(engine: Arc<MatchingEngine>, config: WebhookConfig) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            engine,
+            client: reqwest::Client::new(),
+            hooks: DashMap::new(),
+            deliveries: DashMap::new(),
+            runtime: OnceLock::new(),
+        })
+    }
+
+    /// Register a webhook, returning it with its signing secret
+    ///
+    /// Must be called from within a Tokio runtime, which deliveries then run on.
+    pub fn register(self: &Arc<Self>, account_id: Uuid, url: &str, events: Vec<WebhookEventType>) -> Result<Webhook> {
+        let parsed = reqwest::Url::parse(url)
+            .map_err(|e| Error::ValidationError(format!("Invalid webhook URL {}: {}", url, e)))?;
+        match parsed.scheme() {
+            "https" => {}
+            "http" if self.config.allow_http => {}
+            scheme => {
+                return Err(Error::ValidationError(format!("Webhook URLs must use https, not {}", scheme)));
+            }
+        }
+
+        let mut registrations = self.hooks.entry(account_id).or_default();
+        if registrations.len() >= MAX_WEBHOOKS_PER_ACCOUNT {
+            return Err(Error::ValidationError(format!(
+                "An account can register at most {} webhooks", MAX_WEBHOOKS_PER_ACCOUNT
+            )));
+        }
+
+        let secret = format!("whsec_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let webhook = Webhook {
+            id: Uuid::new_v4(),
+            account_id,
+            url: parsed.to_string(),
+            events: if events.is_empty() { WebhookEventType::ALL.to_vec() } else { events },
+            secret: None,
+            created_at: Utc::now(),
+        };
+        registrations.push(Registration {
+            webhook: webhook.clone(),
+            secret: secret.clone(),
+        });
+        drop(registrations);
+
+        self.watch_engine();
+        Ok(Webhook {
+            secret: Some(secret),
+            ..webhook
+        })
+    }
+
+    /// Webhooks of an account, without their secrets
+    pub fn list(&self, account_id: Uuid) -> Vec<Webhook> {
+        self.hooks.get(&account_id)
+            .map(|registrations| registrations.iter().map(|registration| registration.webhook.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Remove a webhook, returning `None` if the account has no such webhook
+    pub fn remove(&self, account_id: Uuid, webhook_id: Uuid) -> Option<Webhook> {
+        let mut registrations = self.hooks.get_mut(&account_id)?;
+        let index = registrations.iter().position(|registration| registration.webhook.id == webhook_id)?;
+        Some(registrations.remove(index).webhook)
+    }
+
+    /// Recent deliveries of an account, newest first
+    pub fn deliveries(&self, account_id: Uuid, limit: usize) -> Vec<Delivery> {
+        self.deliveries.get(&account_id)
+            .map(|log| log.iter().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Deliveries of every account being attempted or waiting for a retry
+    pub fn pending(&self) -> usize {
+        self.deliveries.iter()
+            .map(|log| log.iter().filter(|delivery| delivery.status == DeliveryStatus::Pending).count())
+            .sum()
+    }
+
+    /// Deliver an event to every webhook of the account subscribed to its type
+    pub fn notify(self: &Arc<Self>, account_id: Uuid, event: WebhookEventType, data: Value) {
+        let targets: Vec<(Uuid, String, String)> = match self.hooks.get(&account_id) {
+            Some(registrations) => registrations.iter()
+                .filter(|registration| registration.webhook.events.contains(&event))
+                .map(|registration| (registration.webhook.id, registration.webhook.url.clone(), registration.secret.clone()))
+                .collect(),
+            None => return,
+        };
+        let Some(runtime) = self.runtime.get() else {
+            return;
+        };
+
+        let now = Utc::now();
+        for (webhook_id, url, secret) in targets {
+            let delivery_id = Uuid::new_v4();
+            let body = json!({
+                "id": delivery_id,
+                "type": event.name(),
+                "account_id": account_id,
+                "timestamp": now,
+                "data": data,
+            })
+            .to_string();
+
+            let mut log = self.deliveries.entry(account_id).or_default();
+            if log.len() == DELIVERY_LOG_CAPACITY {
+                log.pop_back();
+            }
+            log.push_front(Delivery {
+                id: delivery_id,
+                webhook_id,
+                event,
+                status: DeliveryStatus::Pending,
+                attempts: 0,
+                response_status: None,
+                error: None,
+                created_at: now,
+                updated_at: now,
+            });
+            drop(log);
+
+            runtime.spawn(self.clone().deliver(account_id, delivery_id, event, url, secret, body));
+        }
+    }
+
+    /// Post a payload until it is acknowledged or the attempts run out
+    async fn deliver(self: Arc<Self>, account_id: Uuid, delivery_id: Uuid, event: WebhookEventType, url: String, secret: String, body: String) {
+        let mut backoff = self.config.initial_backoff;
+
+        for attempt in 1..=self.config.max_attempts.max(1) {
+            let timestamp = Utc::now().timestamp();
+            let result = self.client
+                .post(&url)
+                .timeout(self.config.timeout)
+                .header("content-type", "application/json")
+                .header(SIGNATURE_HEADER, format!("t={},v1={}", timestamp, sign(&secret, timestamp, &body)))
+                .header(EVENT_HEADER, event.name())
+                .header(DELIVERY_HEADER, delivery_id.to_string())
+                .body(body.clone())
+                .send()
+                .await;
+
+            let (response_status, error) = match result {
+                Ok(response) if response.status().is_success() => {
+                    debug!("Delivered webhook {} to {}", delivery_id, url);
+                    self.update(account_id, delivery_id, attempt, DeliveryStatus::Delivered, Some(response.status().as_u16()), None);
+                    return;
+                }
+                Ok(response) => (Some(response.status().as_u16()), format!("Receiver responded with {}", response.status())),
+                Err(e) => (None, e.to_string()),
+            };
+
+            let last = attempt == self.config.max_attempts.max(1);
+            let status = if last { DeliveryStatus::Failed } else { DeliveryStatus::Pending };
+            warn!("Webhook delivery {} to {} failed on attempt {}: {}", delivery_id, url, attempt, error);
+            self.update(account_id, delivery_id, attempt, status, response_status, Some(error));
+
+            if !last {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+    }
+
+    fn update(
+        &self,
+        account_id: Uuid,
+        delivery_id: Uuid,
+        attempts: u32,
+        status: DeliveryStatus,
+        response_status: Option<u16>,
+        error: Option<String>,
+    ) {
+        if let Some(mut log) = self.deliveries.get_mut(&account_id) {
+            if let Some(delivery) = log.iter_mut().find(|delivery| delivery.id == delivery_id) {
+                delivery.status = status;
+                delivery.attempts = attempts;
+                delivery.response_status = response_status;
+                delivery.error = error;
+                delivery.updated_at = Utc::now();
+            }
+        }
+    }
+
+    /// Start turning engine events into fill and order status notifications
+    fn watch_engine(self: &Arc<Self>) {
+        if self.runtime.set(Handle::current()).is_err() {
+            return;
+        }
+
+        let events = self.engine.subscribe_events();
+        let service: Weak<Self> = Arc::downgrade(self);
+        thread::Builder::new()
+            .name("webhooks".to_string())
+            .spawn(move || {
+                for event in events {
+                    let Some(service) = service.upgrade() else {
+                        break;
+                    };
+                    service.on_engine_event(&event);
+                }
+            })
+            .expect("failed to spawn webhook thread");
+    }
+
+    fn on_engine_event(self: &Arc<Self>, event: &EngineEvent) {
+        match event {
+            EngineEvent::OrderPlaced(order)
+            | EngineEvent::OrderUpdated(order)
+            | EngineEvent::OrderCancelled(order)
+            | EngineEvent::OrderExpired(order) => {
+                self.notify(order.user_id, WebhookEventType::OrderStatus, json!(order));
+            }
+            EngineEvent::Trade(trade) => {
+                for (account_id, side, is_maker) in [
+                    (trade.buyer_id, Side::Buy, trade.is_buyer_maker),
+                    (trade.seller_id, Side::Sell, !trade.is_buyer_maker),
+                ] {
+                    self.notify(account_id, WebhookEventType::Fill, json!({
+                        "trade": trade,
+                        "side": side,
+                        "is_maker": is_maker,
+                    }));
+                }
+            }
+            // Session changes are not tied to an account
+            EngineEvent::SessionChanged(_) => {}
+        }
+    }
+}
+
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"`
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
@@ -0,0 +1,249 @@
+//! Live WebSocket connections
+//!
+//! Every connection registers itself for as long as it is open, counting the
+//! messages it receives, refuses over its quotas and sends and recording its
+//! subscriptions and, once it authorizes a private channel, its account.
+//! Operators list connections with their message rates and lag, where lag is
+//! the messages waiting in the connection's outbound queue plus those waiting
+//! in its subscriptions' market data queues, and may close any connection.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use market_data::channel::ChannelMetrics;
+use serde::Serialize;
+use tokio::sync::{mpsc, Notify};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// A subscription of a live connection
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ConnectionSubscription {
+    /// Subscription ID
+    pub id: Uuid,
+    /// Channel subscribed to
+    pub channel: String,
+    /// Market subscribed to, if any
+    pub market: Option<String>,
+    /// Market data messages waiting to be forwarded to the connection
+    pub queue_depth: usize,
+    /// Market data messages skipped while the subscription lagged
+    pub dropped: u64,
+}
+
+/// A live connection
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ConnectionInfo {
+    /// Connection ID
+    pub id: Uuid,
+    /// Client address, if known
+    pub client_address: Option<String>,
+    /// Account whose API key authorized a private channel, if any
+    pub account_id: Option<Uuid>,
+    /// When the connection was opened
+    pub connected_at: DateTime<Utc>,
+    /// Open subscriptions
+    pub subscriptions: Vec<ConnectionSubscription>,
+    /// Requests received
+    pub messages_received: u64,
+    /// Requests refused for exceeding the connection's quotas
+    pub messages_refused: u64,
+    /// Responses and notifications sent
+    pub messages_sent: u64,
+    /// Bytes of text sent
+    pub bytes_sent: u64,
+    /// Requests received per second since the connection opened
+    pub received_per_second: f64,
+    /// Messages sent per second since the connection opened
+    pub sent_per_second: f64,
+    /// Messages waiting in the connection's outbound queue
+    pub pending: usize,
+    /// Messages waiting for the client, outbound and in subscription queues
+    pub lag: usize,
+}
+
+/// Counters and controls of one connection
+struct ConnectionEntry {
+    id: Uuid,
+    client: Option<IpAddr>,
+    connected_at: DateTime<Utc>,
+    account_id: Mutex<Option<Uuid>>,
+    /// Subscription ID -> (channel, market)
+    subscriptions: Mutex<HashMap<Uuid, (String, Option<String>)>>,
+    received: AtomicU64,
+    refused: AtomicU64,
+    sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    /// Outbound queue, held weakly so it closes with the connection
+    outbound: mpsc::WeakSender<String>,
+    /// Woken to close the connection
+    close: Notify,
+}
+
+/// Live WebSocket connections
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    connections: DashMap<Uuid, Arc<ConnectionEntry>>,
+}
+
+impl ConnectionRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a connection from `client` sending through `outbound`
+    ///
+    /// The connection stays listed until the returned handle is dropped.
+    pub fn open(self: &Arc<Self>, client: Option<IpAddr>, outbound: &mpsc::Sender<String>) -> ConnectionHandle {
+        let entry = Arc::new(ConnectionEntry {
+            id: Uuid::new_v4(),
+            client,
+            connected_at: Utc::now(),
+            account_id: Mutex::new(None),
+            subscriptions: Mutex::new(HashMap::new()),
+            received: AtomicU64::new(0),
+            refused: AtomicU64::new(0),
+            sent: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            outbound: outbound.downgrade(),
+            close: Notify::new(),
+        });
+        self.connections.insert(entry.id, entry.clone());
+        ConnectionHandle { registry: self.clone(), entry }
+    }
+
+    /// Number of live connections
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Whether no connection is live
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    /// Live connections, most lagging first, with subscription queue depths
+    /// taken from `channel`
+    pub fn list(&self, channel: &ChannelMetrics) -> Vec<ConnectionInfo> {
+        let mut connections: Vec<ConnectionInfo> = self.connections
+            .iter()
+            .map(|entry| Self::info(entry.value(), channel))
+            .collect();
+        connections.sort_by(|a, b| b.lag.cmp(&a.lag).then(a.connected_at.cmp(&b.connected_at)));
+        connections
+    }
+
+    /// A live connection, with subscription queue depths taken from `channel`
+    pub fn get(&self, id: Uuid, channel: &ChannelMetrics) -> Option<ConnectionInfo> {
+        self.connections.get(&id).map(|entry| Self::info(entry.value(), channel))
+    }
+
+    /// Close a live connection, returning it as it was if it existed
+    pub fn disconnect(&self, id: Uuid, channel: &ChannelMetrics) -> Option<ConnectionInfo> {
+        let entry = self.connections.get(&id)?.value().clone();
+        entry.close.notify_one();
+        Some(Self::info(&entry, channel))
+    }
+
+    fn info(entry: &ConnectionEntry, channel: &ChannelMetrics) -> ConnectionInfo {
+        let subscriptions: Vec<ConnectionSubscription> = entry.subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, (channel_name, market))| {
+                let metrics = channel.subscribers.iter().filter(|subscriber| subscriber.id == *id);
+                let (queue_depth, dropped) = metrics.fold((0, 0), |(depth, dropped), subscriber| {
+                    (depth + subscriber.queue_depth, dropped + subscriber.dropped)
+                });
+                ConnectionSubscription {
+                    id: *id,
+                    channel: channel_name.clone(),
+                    market: market.clone(),
+                    queue_depth,
+                    dropped,
+                }
+            })
+            .collect();
+
+        let pending = entry.outbound.upgrade().map_or(0, |outbound| outbound.max_capacity() - outbound.capacity());
+        let received = entry.received.load(Ordering::Relaxed);
+        let sent = entry.sent.load(Ordering::Relaxed);
+        let seconds = ((Utc::now() - entry.connected_at).num_milliseconds() as f64 / 1000.0).max(1.0);
+
+        ConnectionInfo {
+            id: entry.id,
+            client_address: entry.client.map(|client| client.to_string()),
+            account_id: *entry.account_id.lock().unwrap(),
+            connected_at: entry.connected_at,
+            lag: pending + subscriptions.iter().map(|subscription| subscription.queue_depth).sum::<usize>(),
+            subscriptions,
+            messages_received: received,
+            messages_refused: entry.refused.load(Ordering::Relaxed),
+            messages_sent: sent,
+            bytes_sent: entry.bytes_sent.load(Ordering::Relaxed),
+            received_per_second: received as f64 / seconds,
+            sent_per_second: sent as f64 / seconds,
+            pending,
+        }
+    }
+}
+
+/// A connection's registration, removed from the registry when dropped
+pub struct ConnectionHandle {
+    registry: Arc<ConnectionRegistry>,
+    entry: Arc<ConnectionEntry>,
+}
+
+impl ConnectionHandle {
+    /// Connection ID
+    pub fn id(&self) -> Uuid {
+        self.entry.id
+    }
+
+    /// Count a request received
+    pub fn received(&self) {
+        self.entry.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a request refused for exceeding a quota
+    pub fn refused(&self) {
+        self.entry.refused.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a message of `bytes` sent to the client
+    pub fn sent(&self, bytes: usize) {
+        self.entry.sent.fetch_add(1, Ordering::Relaxed);
+        self.entry.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Record the account whose API key authorized a private channel
+    pub fn authorized(&self, account_id: Uuid) {
+        *self.entry.account_id.lock().unwrap() = Some(account_id);
+    }
+
+    /// Record a subscription
+    pub fn subscribed(&self, id: Uuid, channel: &str, market: Option<&str>) {
+        self.entry.subscriptions.lock().unwrap().insert(id, (channel.to_string(), market.map(str::to_string)));
+    }
+
+    /// Forget a subscription
+    pub fn unsubscribed(&self, id: Uuid) {
+        self.entry.subscriptions.lock().unwrap().remove(&id);
+    }
+
+    /// Wait until an operator closes the connection
+    pub async fn closed(&self) {
+        self.entry.close.notified().await;
+    }
+}
+
+impl Drop for ConnectionHandle {
+    fn drop(&mut self) {
+        self.registry.connections.remove(&self.entry.id);
+    }
+}
@@ -1,57 +1,94 @@
 //! WebSocket handler implementation
 
+use std::any::Any;
 use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{
-    extract::{State, WebSocketUpgrade},
+    extract::{ConnectInfo, State, WebSocketUpgrade},
     response::IntoResponse,
 };
+use common::decimal::{format_price, format_quantity};
 use futures::{SinkExt, StreamExt};
 use market_data::channel::Topic;
+use market_data::{BestBidOffer, CandleInterval, CandleUpdate, OrderBookUpdate, Ticker, TradeCorrection, TradeMessage};
 use serde_json::json;
 use tokio::sync::{mpsc, Mutex};
 use tracing::{debug, error, info};
 use uuid::Uuid;
 
+use crate::auth::Scope;
+use crate::number_format::NumberFormat;
 use crate::AppState;
-use crate::ws::message::{Subscription, WsError, WsNotification, WsRequest, WsResponse};
+use crate::ws::message::{
+    AccountEvent, Notification, NotificationPayload, ProtocolVersion, Subscription, SystemEvent, WsError, WsRequest,
+    WsResponse,
+};
+use crate::ws::quota::ConnectionQuota;
+
+/// Longest a closing connection waits to send the messages already queued for it
+const CLOSE_FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Most missed trades a `trades` subscription replays from `from_sequence`
+const MAX_REPLAYED_TRADES: usize = 10_000;
 
 /// Handle WebSocket connection
 pub async fn ws_handler(
     State(state): State<Arc<AppState>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
     ws: WebSocketUpgrade,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    let client = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    ws.max_message_size(state.limits.ws_max_message_bytes)
+        .max_frame_size(state.limits.ws_max_frame_bytes)
+        .on_upgrade(move |socket| handle_socket(socket, state, client))
 }
 
 /// Handle WebSocket connection
+///
+/// Private channels take an API key with the `read` scope, usable from
+/// `client`.
 async fn handle_socket(
     socket: axum::extract::ws::WebSocket,
     state: Arc<AppState>,
+    client: Option<IpAddr>,
 ) {
-    // Client state
-    let client_id = Uuid::new_v4();
+    // Create a channel for sending messages to the client
+    let (tx, mut rx) = mpsc::channel(100);
+    
+    // Client state, listed for operators while the connection is open
+    let connection = Arc::new(state.ws_connections.open(client, &tx));
+    let client_id = connection.id();
     let subscriptions: Arc<Mutex<HashSet<Subscription>>> = Arc::new(Mutex::new(HashSet::new()));
+    // Notification schema for subscriptions that do not ask for one
+    let mut version = ProtocolVersion::default();
+    // JSON number format of everything sent, which `hello` can change
+    let number_format = Arc::new(Mutex::new(state.number_format));
+    // Message rate and method call quotas
+    let mut quota = ConnectionQuota::new(&state.limits);
     
     info!("New WebSocket connection: {}", client_id);
 
     // Get the market data channel
     let market_data_channel = state.market_data_service.channel();
     
-    // Create a channel for sending messages to the client
-    let (tx, mut rx) = mpsc::channel(100);
-    
     // Split the WebSocket
     let (mut ws_sender, mut ws_receiver) = socket.split();
     
     // Spawn a task that forwards messages from the channel to the WebSocket
-    let send_task = tokio::spawn(async move {
+    let send_format = number_format.clone();
+    let send_connection = connection.clone();
+    let mut send_task = tokio::spawn(async move {
         while let Some(message) = rx.recv().await {
+            let message = send_format.lock().await.apply_to_text(message);
+            let bytes = message.len();
             if let Err(e) = ws_sender.send(axum::extract::ws::Message::Text(message)).await {
                 error!("Error sending message: {}", e);
                 break;
             }
+            send_connection.sent(bytes);
         }
         
         // If the channel is closed or an error occurs, close the WebSocket
@@ -62,13 +99,53 @@ async fn handle_socket(
     let tx_clone = tx.clone();
     
     // Handle incoming messages
-    while let Some(result) = ws_receiver.next().await {
+    loop {
+        // An operator may close the connection from the admin API
+        let result = tokio::select! {
+            result = ws_receiver.next() => match result {
+                Some(result) => result,
+                None => break,
+            },
+            _ = connection.closed() => {
+                info!("WebSocket connection {} closed by an operator", client_id);
+                break;
+            }
+        };
+        
         match result {
             Ok(axum::extract::ws::Message::Text(text)) => {
                 debug!("Received text message: {}", text);
+                connection.received();
                 
                 // Parse the message
-                let request: WsRequest = match serde_json::from_str(&text) {
+                let parsed = serde_json::from_str::<WsRequest>(&text);
+                
+                // Refuse messages over the connection's quotas, closing it on sustained abuse
+                if let Err(refusal) = quota.check(parsed.as_ref().ok().map(|request| request.method.as_str())) {
+                    connection.refused();
+                    let response = WsResponse {
+                        id: parsed.as_ref().map_or_else(|_| "0".to_string(), |request| request.id.clone()),
+                        result: None,
+                        error: Some(WsError {
+                            code: 429,
+                            message: refusal.message,
+                        }),
+                    };
+                    
+                    if let Err(e) = tx.send(serde_json::to_string(&response).unwrap()).await {
+                        error!("Error sending error response: {}", e);
+                        break;
+                    }
+                    
+                    if refusal.close {
+                        info!("WebSocket connection {} closed for exceeding its request quotas", client_id);
+                        break;
+                    }
+                    
+                    continue;
+                }
+                
+                let request = match parsed {
                     Ok(req) => req,
                     Err(e) => {
                         // Send error response
@@ -92,6 +169,55 @@ async fn handle_socket(
                 
                 // Handle the request
                 match request.method.as_str() {
+                    "hello" => {
+                        // Pick the newest version not newer than the client's
+                        let negotiated = request.params.get("version")
+                            .and_then(|v| v.as_u64())
+                            .and_then(ProtocolVersion::negotiate);
+                        // Optionally switch the number format, naming it only to clients that ask
+                        let numbers = request.params.get("numbers")
+                            .map(|numbers| numbers.as_str().and_then(|numbers| numbers.parse::<NumberFormat>().ok()));
+                        
+                        let response = match (negotiated, numbers) {
+                            (Some(_), Some(None)) => WsResponse {
+                                id: request.id,
+                                result: None,
+                                error: Some(WsError {
+                                    code: 400,
+                                    message: "Unsupported numbers parameter".to_string(),
+                                }),
+                            },
+                            (Some(negotiated), numbers) => {
+                                version = negotiated;
+                                let mut result = json!({
+                                    "version": negotiated.number(),
+                                    "supportedVersions": ProtocolVersion::SUPPORTED.map(|v| v.number()),
+                                });
+                                if let Some(Some(numbers)) = numbers {
+                                    *number_format.lock().await = numbers;
+                                    result["numbers"] = json!(numbers.name());
+                                }
+                                WsResponse {
+                                    id: request.id,
+                                    result: Some(result),
+                                    error: None,
+                                }
+                            },
+                            (None, _) => WsResponse {
+                                id: request.id,
+                                result: None,
+                                error: Some(WsError {
+                                    code: 400,
+                                    message: "Missing or unsupported version parameter".to_string(),
+                                }),
+                            },
+                        };
+                        
+                        if let Err(e) = tx.send(serde_json::to_string(&response).unwrap()).await {
+                            error!("Error sending hello response: {}", e);
+                            break;
+                        }
+                    },
                     "subscribe" => {
                         // Extract channel and market
                         let channel = match request.params.get("channel") {
@@ -116,6 +242,32 @@ async fn handle_socket(
                             }
                         };
                         
+                        // A subscription may ask for a version other than the connection's
+                        let subscription_version = match request.params.get("version") {
+                            None => version,
+                            Some(requested) => match requested.as_u64().and_then(ProtocolVersion::from_number) {
+                                Some(requested) => requested,
+                                None => {
+                                    // Send error response
+                                    let response = WsResponse {
+                                        id: request.id,
+                                        result: None,
+                                        error: Some(WsError {
+                                            code: 400,
+                                            message: format!("Unsupported version: {}", requested),
+                                        }),
+                                    };
+                                    
+                                    if let Err(e) = tx.send(serde_json::to_string(&response).unwrap()).await {
+                                        error!("Error sending error response: {}", e);
+                                        break;
+                                    }
+                                    
+                                    continue;
+                                }
+                            },
+                        };
+                        
                         let market = request.params.get("market").and_then(|m| {
                             if let serde_json::Value::String(market) = m {
                                 Some(market.clone())
@@ -137,9 +289,100 @@ async fn handle_socket(
                             ("orderbook", Some(market)) => Topic::OrderBook(market),
                             ("trades", Some(market)) => Topic::Trades(market),
                             ("ticker", Some(market)) => Topic::Ticker(market),
+                            ("bbo", Some(market)) => Topic::Bbo(market),
+                            ("corrections", Some(market)) => Topic::TradeCorrections(market),
                             ("orderbook", None) => Topic::AllOrderBooks,
                             ("trades", None) => Topic::AllTrades,
                             ("ticker", None) => Topic::AllTickers,
+                            ("candles", Some(market)) => {
+                                let interval = request.params.get("interval")
+                                    .and_then(|interval| interval.as_str())
+                                    .unwrap_or("1m");
+                                
+                                match CandleInterval::from_code(interval) {
+                                    Some(interval) => Topic::Candles(market, interval),
+                                    None => {
+                                        // Send error response
+                                        let response = WsResponse {
+                                            id: request.id,
+                                            result: None,
+                                            error: Some(WsError {
+                                                code: 400,
+                                                message: format!("Invalid interval: {}", interval),
+                                            }),
+                                        };
+                                        
+                                        if let Err(e) = tx.send(serde_json::to_string(&response).unwrap()).await {
+                                            error!("Error sending error response: {}", e);
+                                            break;
+                                        }
+                                        
+                                        continue;
+                                    }
+                                }
+                            },
+                            ("system", None) => Topic::System,
+                            ("rawtrades", Some(market)) => {
+                                // The full tape, dust included, is for authenticated clients
+                                let account_id = request.params.get("apiKey")
+                                    .and_then(|key| key.as_str())
+                                    .and_then(|key| state.api_keys.authorize(key, Scope::Read, client).ok())
+                                    .map(|api_key| api_key.account_id);
+                                
+                                if let Some(account_id) = account_id {
+                                    connection.authorized(account_id);
+                                    Topic::RawTrades(market)
+                                } else {
+                                    // Send error response
+                                    let response = WsResponse {
+                                        id: request.id,
+                                        result: None,
+                                        error: Some(WsError {
+                                            code: 401,
+                                            message: "Missing or invalid apiKey parameter".to_string(),
+                                        }),
+                                    };
+                                    
+                                    if let Err(e) = tx.send(serde_json::to_string(&response).unwrap()).await {
+                                        error!("Error sending error response: {}", e);
+                                        break;
+                                    }
+                                    
+                                    continue;
+                                }
+                            },
+                            ("account", None) => {
+                                // Private events need the account's API key
+                                let account_id = request.params.get("apiKey")
+                                    .and_then(|key| key.as_str())
+                                    .and_then(|key| state.api_keys.authorize(key, Scope::Read, client).ok())
+                                    .map(|api_key| api_key.account_id);
+                                
+                                match account_id {
+                                    Some(account_id) => {
+                                        connection.authorized(account_id);
+                                        Topic::Account(account_id)
+                                    },
+                                    None => {
+                                        // Send error response
+                                        let response = WsResponse {
+                                            id: request.id,
+                                            result: None,
+                                            error: Some(WsError {
+                                                code: 401,
+                                                message: "Missing or invalid apiKey parameter".to_string(),
+                                            }),
+                                        };
+                                        
+                                        if let Err(e) = tx.send(serde_json::to_string(&response).unwrap()).await {
+                                            error!("Error sending error response: {}", e);
+                                            break;
+                                        }
+                                        
+                                        continue;
+                                    }
+                                }
+                            },
                             _ => {
                                 // Send error response
                                 let response = WsResponse {
@@ -160,79 +403,106 @@ async fn handle_socket(
                             }
                         };
                         
-                        // Subscribe to the topic
-                        let receiver = market_data_channel.subscribe::<serde_json::Value>(topic.clone()).await;
+                        // A market's trades may resume after the last one the client saw
+                        let from_sequence = match (request.params.get("from_sequence"), &topic) {
+                            (None, _) => None,
+                            (Some(from), Topic::Trades(_)) if from.as_u64().is_some() => from.as_u64(),
+                            (Some(_), _) => {
+                                // Send error response
+                                let response = WsResponse {
+                                    id: request.id,
+                                    result: None,
+                                    error: Some(WsError {
+                                        code: 400,
+                                        message: "from_sequence needs the trades channel of a market and a sequence number".to_string(),
+                                    }),
+                                };
+                                
+                                if let Err(e) = tx.send(serde_json::to_string(&response).unwrap()).await {
+                                    error!("Error sending error response: {}", e);
+                                    break;
+                                }
+                                
+                                continue;
+                            }
+                        };
                         
-                        // Set up subscription handler in a separate task
-                        let sub_tx = tx_clone.clone();
-                        let topic_clone = topic.clone();
-                        let market_clone = market.clone();
+                        // Subscribe to the topic under the client-visible subscription ID
+                        let receiver = market_data_channel
+                            .subscribe_with_id::<serde_json::Value>(topic.clone(), subscription_id)
+                            .await;
                         
-                        tokio::spawn(async move {
-                            while let Ok(message) = receiver.recv() {
-                                if let Some(any_ref) = message.downcast_ref::<serde_json::Value>() {
-                                    // Create notification based on topic
-                                    let notification = match topic_clone {
-                                        Topic::OrderBook(_) => WsNotification {
-                                            method: "orderbook".to_string(),
-                                            params: json!({
-                                                "market": market_clone,
-                                                "data": any_ref,
-                                                "subscription_id": subscription_id.to_string(),
-                                            }),
-                                        },
-                                        Topic::Trades(_) => WsNotification {
-                                            method: "trades".to_string(),
-                                            params: json!({
-                                                "market": market_clone,
-                                                "data": any_ref,
-                                                "subscription_id": subscription_id.to_string(),
-                                            }),
-                                        },
-                                        Topic::Ticker(_) => WsNotification {
-                                            method: "ticker".to_string(),
-                                            params: json!({
-                                                "market": market_clone,
-                                                "data": any_ref,
-                                                "subscription_id": subscription_id.to_string(),
-                                            }),
-                                        },
-                                        _ => WsNotification {
-                                            method: "update".to_string(),
-                                            params: json!({
-                                                "data": any_ref,
-                                                "subscription_id": subscription_id.to_string(),
-                                            }),
-                                        },
-                                    };
-                                    
-                                    // Send notification
-                                    if let Err(e) = sub_tx.send(
-                                        serde_json::to_string(&notification).unwrap()
-                                    ).await {
-                                        error!("Error sending notification: {}", e);
-                                        break;
+                        // Read the missed trades after subscribing, so none published
+                        // in between is lost; live ones replayed already are skipped
+                        let replay = match (from_sequence, &topic) {
+                            (Some(from), Topic::Trades(market)) => {
+                                // No trade is numbered past the engine's last, so live ones are never skipped
+                                let from = state.matching_engine.last_trade_sequence(market).map_or(from, |last| from.min(last));
+                                let replay = state.market_data_service
+                                    .get_trades_after(market, from, MAX_REPLAYED_TRADES + 1)
+                                    .await
+                                    .map_err(|e| (500, format!("Error replaying trades: {}", e)))
+                                    .and_then(|trades| if trades.len() > MAX_REPLAYED_TRADES {
+                                        Err((400, format!("More than {} trades after from_sequence {}", MAX_REPLAYED_TRADES, from)))
+                                    } else {
+                                        Ok(trades)
+                                    });
+                                
+                                match replay {
+                                    Ok(trades) => {
+                                        let through = trades.last().map_or(from, |trade| trade.sequence.max(from));
+                                        let filter = state.market_data_service.tape_filter();
+                                        let displayed: Vec<TradeMessage> = trades.into_iter().filter(|trade| filter.displays(trade)).collect();
+                                        Some((displayed, through))
+                                    },
+                                    Err((code, message)) => {
+                                        market_data_channel.unsubscribe_by_id(subscription_id).await;
+                                        
+                                        // Send error response
+                                        let response = WsResponse {
+                                            id: request.id,
+                                            result: None,
+                                            error: Some(WsError { code, message }),
+                                        };
+                                        
+                                        if let Err(e) = tx.send(serde_json::to_string(&response).unwrap()).await {
+                                            error!("Error sending error response: {}", e);
+                                            break;
+                                        }
+                                        
+                                        continue;
                                     }
                                 }
-                            }
-                            
-                            debug!("Subscription handler for {} exited", subscription_id);
-                        });
+                            },
+                            _ => None,
+                        };
                         
                         // Store subscription
                         {
                             let mut subs = subscriptions.lock().await;
                             subs.insert(subscription.clone());
                         }
+                        connection.subscribed(subscription_id, &channel, market.as_deref());
                         
-                        // Send success response
+                        // Send success response, naming the version only to clients that use one
+                        let mut result = json!({
+                            "subscriptionId": subscription_id,
+                            "channel": channel,
+                            "market": market,
+                        });
+                        if let Topic::Candles(_, interval) = &topic {
+                            result["interval"] = json!(interval.code());
+                        }
+                        if subscription_version != ProtocolVersion::V1 {
+                            result["version"] = json!(subscription_version.number());
+                        }
+                        if let Some((trades, through)) = &replay {
+                            result["replayed"] = json!(trades.len());
+                            result["replayedThrough"] = json!(through);
+                        }
                         let response = WsResponse {
                             id: request.id,
-                            result: Some(json!({
-                                "subscriptionId": subscription_id,
-                                "channel": channel,
-                                "market": market,
-                            })),
+                            result: Some(result),
                             error: None,
                         };
                         
@@ -240,6 +510,57 @@ async fn handle_socket(
                             error!("Error sending success response: {}", e);
                             break;
                         }
+                        
+                        // Set up subscription handler on a blocking thread, since the
+                        // channel receiver blocks until a message arrives
+                        let sub_tx = tx_clone.clone();
+                        let topic_clone = topic.clone();
+                        
+                        tokio::task::spawn_blocking(move || {
+                            // Missed trades go first, then live ones not among them
+                            let mut replayed_through = 0;
+                            if let Some((trades, through)) = replay {
+                                replayed_through = through;
+                                for trade in trades {
+                                    let notification = Notification::new(subscription_id, NotificationPayload::Trades(trade))
+                                        .encode(&topic_clone, subscription_version)
+                                        .unwrap();
+                                    
+                                    if let Err(e) = sub_tx.blocking_send(notification) {
+                                        error!("Error sending replayed trade: {}", e);
+                                        return;
+                                    }
+                                }
+                            }
+                            
+                            // Ends once the subscription is removed from the channel
+                            while let Ok(mut message) = receiver.recv() {
+                                // A slow BBO consumer only needs the newest top of book
+                                if matches!(topic_clone, Topic::Bbo(_)) {
+                                    if let Some(latest) = receiver.try_iter().last() {
+                                        message = latest;
+                                    }
+                                }
+                                
+                                if let Some(payload) = message_to_payload(&topic_clone, message.as_ref()) {
+                                    if matches!(&payload, NotificationPayload::Trades(trade) if trade.sequence <= replayed_through) {
+                                        continue;
+                                    }
+                                    
+                                    let notification = Notification::new(subscription_id, payload)
+                                        .encode(&topic_clone, subscription_version)
+                                        .unwrap();
+                                    
+                                    // Send notification
+                                    if let Err(e) = sub_tx.blocking_send(notification) {
+                                        error!("Error sending notification: {}", e);
+                                        break;
+                                    }
+                                }
+                            }
+                            
+                            debug!("Subscription handler for {} exited", subscription_id);
+                        });
                     },
                     "unsubscribe" => {
                         // Extract subscription ID
@@ -302,9 +623,10 @@ async fn handle_socket(
                                     let mut subs = subscriptions.lock().await;
                                     subs.remove(&subscription);
                                 }
+                                connection.unsubscribed(subscription.id);
                                 
-                                // The unsubscribe operation doesn't need to tell the market_data_channel
-                                // because the receiver will be dropped when the subscription handler task completes
+                                // Removing the channel subscription stops its handler task
+                                market_data_channel.unsubscribe_by_id(subscription.id).await;
                                 
                                 // Send success response
                                 let response = WsResponse {
@@ -368,20 +690,20 @@ async fn handle_socket(
                             .unwrap_or(10) as usize;
                         
                         // Get order book data
-                        match state.matching_engine.get_market_depth(&market, depth) {
+                        match crate::api::market::book_depth(&state, &market, depth) {
                             Ok((bids, asks)) => {
                                 // Convert to JSON-friendly format
                                 let bids_json: Vec<Vec<String>> = bids.iter()
                                     .map(|(price, quantity)| vec![
-                                        price.to_string(),
-                                        quantity.to_string(),
+                                        format_price(&market, *price),
+                                        format_quantity(&market, *quantity),
                                     ])
                                     .collect();
                                 
                                 let asks_json: Vec<Vec<String>> = asks.iter()
                                     .map(|(price, quantity)| vec![
-                                        price.to_string(),
-                                        quantity.to_string(),
+                                        format_price(&market, *price),
+                                        format_quantity(&market, *quantity),
                                     ])
                                     .collect();
                                 
@@ -564,12 +886,52 @@ async fn handle_socket(
     // Connection closed, clean up
     info!("WebSocket connection closed: {}", client_id);
     
-    // Cancel send task
-    send_task.abort();
-    
-    // Clean up subscriptions
+    // Clean up subscriptions, whose handlers then drop their senders
     {
         let mut subs = subscriptions.lock().await;
-        subs.clear();
+        for subscription in subs.drain() {
+            market_data_channel.unsubscribe_by_id(subscription.id).await;
+        }
+    }
+    
+    // Let the send task deliver what is queued, such as a final error, then
+    // cancel it if the client does not take it in time
+    drop(tx);
+    drop(tx_clone);
+    if tokio::time::timeout(CLOSE_FLUSH_TIMEOUT, &mut send_task).await.is_err() {
+        send_task.abort();
     }
-}
\ No newline at end of file
+}
+
+/// Typed payload of a published market data message for the topic it was published on
+fn message_to_payload(topic: &Topic, message: &(dyn Any + Send + Sync)) -> Option<NotificationPayload> {
+    match topic {
+        Topic::OrderBook(_) | Topic::AllOrderBooks => message
+            .downcast_ref::<OrderBookUpdate>()
+            .map(|update| NotificationPayload::Orderbook(update.clone())),
+        Topic::Bbo(_) => message
+            .downcast_ref::<BestBidOffer>()
+            .map(|bbo| NotificationPayload::Bbo(bbo.clone())),
+        Topic::Trades(_) | Topic::AllTrades => message
+            .downcast_ref::<TradeMessage>()
+            .map(|trade| NotificationPayload::Trades(trade.clone())),
+        Topic::RawTrades(_) => message
+            .downcast_ref::<TradeMessage>()
+            .map(|trade| NotificationPayload::Rawtrades(trade.clone())),
+        Topic::TradeCorrections(_) => message
+            .downcast_ref::<TradeCorrection>()
+            .map(|correction| NotificationPayload::Corrections(correction.clone())),
+        Topic::Ticker(_) | Topic::AllTickers => message
+            .downcast_ref::<Ticker>()
+            .map(|ticker| NotificationPayload::Ticker(ticker.clone())),
+        Topic::Candles(..) => message
+            .downcast_ref::<CandleUpdate>()
+            .map(|update| NotificationPayload::Candles(update.clone())),
+        Topic::Account(_) => message
+            .downcast_ref::<AccountEvent>()
+            .map(|event| NotificationPayload::Account(event.clone())),
+        Topic::System => message
+            .downcast_ref::<SystemEvent>()
+            .map(|event| NotificationPayload::System(event.clone())),
+    }
+}
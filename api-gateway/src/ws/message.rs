@@ -1,8 +1,24 @@
 //! WebSocket messages
+//!
+//! Notifications come in two schema versions, both built from typed structs.
+//! Version 1 ([`WsNotification`]) is the original shape and stays the default.
+//! Version 2 ([`Notification`]), negotiated with `hello` or requested per
+//! `subscribe`, names the channel and market of every payload.
 
+use chrono::{DateTime, Utc};
+use market_data::channel::Topic;
+use common::model::market::SessionState;
+use common::decimal::{Price, Quantity};
+use common::model::order::Side;
+use market_data::{BestBidOffer, CandleUpdate, OrderBookUpdate, Ticker, TradeCorrection, TradeMessage};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::system::{Announcement, ComponentStatus};
+
+/// Channels clients can subscribe to
+pub const CHANNELS: [&str; 9] = ["orderbook", "bbo", "trades", "rawtrades", "corrections", "ticker", "candles", "account", "system"];
+
 /// WebSocket request message
 #[derive(Debug, Deserialize)]
 pub struct WsRequest {
@@ -36,13 +52,235 @@ pub struct WsError {
     pub message: String,
 }
 
-/// WebSocket notification message
+/// WebSocket notification message, version 1
 #[derive(Debug, Serialize)]
-pub struct WsNotification {
-    /// Method
-    pub method: String,
+pub struct WsNotification<'a, T> {
+    /// Channel name, or `update` for all-market subscriptions
+    pub method: &'static str,
     /// Params
-    pub params: serde_json::Value,
+    pub params: WsNotificationParams<'a, T>,
+}
+
+/// Params of a version 1 notification
+#[derive(Debug, Serialize)]
+pub struct WsNotificationParams<'a, T> {
+    /// Subscribed market, absent for all-market and account subscriptions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<&'a str>,
+    /// Subscription the notification belongs to
+    pub subscription_id: Uuid,
+    /// Payload
+    pub data: &'a T,
+}
+
+/// Depth update on the `orderbook` channel
+pub type OrderBookNotification<'a> = WsNotification<'a, OrderBookUpdate>;
+/// Top of book on the `bbo` channel
+pub type BboNotification<'a> = WsNotification<'a, BestBidOffer>;
+/// Public trade on the `trades` channel, or any trade on `rawtrades`
+pub type TradeNotification<'a> = WsNotification<'a, TradeMessage>;
+/// Bust of a published trade on the `corrections` channel
+pub type CorrectionNotification<'a> = WsNotification<'a, TradeCorrection>;
+/// Market statistics on the `ticker` channel
+pub type TickerNotification<'a> = WsNotification<'a, Ticker>;
+/// Working or closed candle on the `candles` channel
+pub type CandleNotification<'a> = WsNotification<'a, CandleUpdate>;
+/// Private event on the `account` channel
+pub type AccountNotification<'a> = WsNotification<'a, AccountEvent>;
+/// Status change or announcement on the `system` channel
+pub type SystemNotification<'a> = WsNotification<'a, SystemEvent>;
+
+impl<'a, T> WsNotification<'a, T> {
+    /// Notification for a subscription to `topic`
+    ///
+    /// Per-market subscriptions use the channel name as method and repeat the
+    /// market. All-market subscriptions use `update` and omit it.
+    pub fn new(topic: &'a Topic, subscription_id: Uuid, data: &'a T) -> Self {
+        let (method, market) = match topic {
+            Topic::OrderBook(market) => ("orderbook", Some(market.as_str())),
+            Topic::Trades(market) => ("trades", Some(market.as_str())),
+            Topic::RawTrades(market) => ("rawtrades", Some(market.as_str())),
+            Topic::TradeCorrections(market) => ("corrections", Some(market.as_str())),
+            Topic::Ticker(market) => ("ticker", Some(market.as_str())),
+            Topic::Bbo(market) => ("bbo", Some(market.as_str())),
+            Topic::Candles(market, _) => ("candles", Some(market.as_str())),
+            Topic::Account(_) => ("account", None),
+            Topic::System => ("system", None),
+            Topic::AllOrderBooks | Topic::AllTrades | Topic::AllTickers => ("update", None),
+        };
+
+        Self {
+            method,
+            params: WsNotificationParams {
+                market,
+                subscription_id,
+                data,
+            },
+        }
+    }
+}
+
+/// Notification schema version
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProtocolVersion {
+    /// Loosely-typed notifications, `update` for all-market subscriptions
+    #[default]
+    V1,
+    /// Typed notifications tagged with their channel
+    V2,
+}
+
+impl ProtocolVersion {
+    /// Every version the server speaks, oldest first
+    pub const SUPPORTED: [ProtocolVersion; 2] = [ProtocolVersion::V1, ProtocolVersion::V2];
+
+    /// Version number used on the wire
+    pub fn number(&self) -> u64 {
+        match self {
+            ProtocolVersion::V1 => 1,
+            ProtocolVersion::V2 => 2,
+        }
+    }
+
+    /// Version with the given number, if supported
+    pub fn from_number(number: u64) -> Option<Self> {
+        Self::SUPPORTED.into_iter().find(|version| version.number() == number)
+    }
+
+    /// Newest supported version not newer than the one a client asks for
+    pub fn negotiate(requested: u64) -> Option<Self> {
+        Self::SUPPORTED.into_iter().rev().find(|version| version.number() <= requested)
+    }
+}
+
+/// Version 2 notification
+///
+/// ```json
+/// { "version": 2, "method": "trades", "params": { "subscriptionId": "...", "market": "BTC/USD", "channel": "trades", "data": { ... } } }
+/// ```
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    /// Always 2
+    pub version: u64,
+    /// Channel the notification was published on
+    pub method: &'static str,
+    /// Subscription and payload
+    pub params: NotificationParams,
+}
+
+impl Notification {
+    /// Version 2 notification for a subscription
+    pub fn new(subscription_id: Uuid, payload: NotificationPayload) -> Self {
+        Self {
+            version: ProtocolVersion::V2.number(),
+            method: payload.channel(),
+            params: NotificationParams {
+                subscription_id,
+                market: payload.market().map(str::to_string),
+                payload,
+            },
+        }
+    }
+
+    /// Serialize for a subscription to `topic` in the given version
+    pub fn encode(&self, topic: &Topic, version: ProtocolVersion) -> serde_json::Result<String> {
+        let subscription_id = self.params.subscription_id;
+        match (version, &self.params.payload) {
+            (ProtocolVersion::V2, _) => serde_json::to_string(self),
+            (ProtocolVersion::V1, NotificationPayload::Orderbook(update)) => {
+                serde_json::to_string(&OrderBookNotification::new(topic, subscription_id, update))
+            }
+            (ProtocolVersion::V1, NotificationPayload::Bbo(bbo)) => {
+                serde_json::to_string(&BboNotification::new(topic, subscription_id, bbo))
+            }
+            (ProtocolVersion::V1, NotificationPayload::Trades(trade) | NotificationPayload::Rawtrades(trade)) => {
+                serde_json::to_string(&TradeNotification::new(topic, subscription_id, trade))
+            }
+            (ProtocolVersion::V1, NotificationPayload::Corrections(correction)) => {
+                serde_json::to_string(&CorrectionNotification::new(topic, subscription_id, correction))
+            }
+            (ProtocolVersion::V1, NotificationPayload::Ticker(ticker)) => {
+                serde_json::to_string(&TickerNotification::new(topic, subscription_id, ticker))
+            }
+            (ProtocolVersion::V1, NotificationPayload::Candles(update)) => {
+                serde_json::to_string(&CandleNotification::new(topic, subscription_id, update))
+            }
+            (ProtocolVersion::V1, NotificationPayload::Account(event)) => {
+                serde_json::to_string(&AccountNotification::new(topic, subscription_id, event))
+            }
+            (ProtocolVersion::V1, NotificationPayload::System(event)) => {
+                serde_json::to_string(&SystemNotification::new(topic, subscription_id, event))
+            }
+        }
+    }
+}
+
+/// Parameters of a version 2 notification
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationParams {
+    /// Subscription the notification belongs to
+    pub subscription_id: Uuid,
+    /// Market the payload belongs to, absent on the `account` and `system` channels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<String>,
+    /// Channel and data
+    #[serde(flatten)]
+    pub payload: NotificationPayload,
+}
+
+/// Notification payload, serialized as `channel` and `data`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "channel", content = "data", rename_all = "snake_case")]
+pub enum NotificationPayload {
+    /// Depth changes of a market, `sequence` increases by one per update
+    Orderbook(OrderBookUpdate),
+    /// Best bid and offer of a market, sent only when it changes
+    Bbo(BestBidOffer),
+    /// A public trade, at least the market's minimum displayed size
+    Trades(TradeMessage),
+    /// Any trade, including ones below the market's minimum displayed size
+    Rawtrades(TradeMessage),
+    /// A published trade was busted and no longer stands
+    Corrections(TradeCorrection),
+    /// 24h statistics of a market
+    Ticker(Ticker),
+    /// Working candle of a market, or its final state once `closed`
+    Candles(CandleUpdate),
+    /// Private event of the subscribed account
+    Account(AccountEvent),
+    /// Exchange-wide status change or announcement
+    System(SystemEvent),
+}
+
+impl NotificationPayload {
+    /// Channel the payload is published on
+    pub fn channel(&self) -> &'static str {
+        match self {
+            NotificationPayload::Orderbook(_) => "orderbook",
+            NotificationPayload::Bbo(_) => "bbo",
+            NotificationPayload::Trades(_) => "trades",
+            NotificationPayload::Rawtrades(_) => "rawtrades",
+            NotificationPayload::Corrections(_) => "corrections",
+            NotificationPayload::Ticker(_) => "ticker",
+            NotificationPayload::Candles(_) => "candles",
+            NotificationPayload::Account(_) => "account",
+            NotificationPayload::System(_) => "system",
+        }
+    }
+
+    /// Market the payload belongs to
+    pub fn market(&self) -> Option<&str> {
+        match self {
+            NotificationPayload::Orderbook(update) => Some(&update.market),
+            NotificationPayload::Bbo(bbo) => Some(&bbo.market),
+            NotificationPayload::Trades(trade) | NotificationPayload::Rawtrades(trade) => Some(&trade.market),
+            NotificationPayload::Corrections(correction) => Some(&correction.market),
+            NotificationPayload::Ticker(ticker) => Some(&ticker.market),
+            NotificationPayload::Candles(update) => Some(&update.candle.market),
+            NotificationPayload::Account(_) | NotificationPayload::System(_) => None,
+        }
+    }
 }
 
 /// WebSocket subscription
@@ -54,4 +292,81 @@ pub struct Subscription {
     pub market: Option<String>,
     /// Subscription ID
     pub id: Uuid,
-}
\ No newline at end of file
+}
+
+/// Private event published on the `account` channel
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AccountEvent {
+    /// New orders were blocked and resting orders cancelled
+    KillSwitchEngaged {
+        /// Account the switch applies to
+        account_id: Uuid,
+        /// Who engaged it
+        actor: String,
+        /// Orders cancelled by the switch
+        cancelled_orders: Vec<Uuid>,
+        /// Whether withdrawals were frozen too
+        withdrawals_frozen: bool,
+        /// Reason given
+        reason: Option<String>,
+        /// When it was engaged
+        timestamp: DateTime<Utc>,
+    },
+    /// Trading and withdrawals were re-enabled
+    KillSwitchReleased {
+        /// Account the switch applies to
+        account_id: Uuid,
+        /// Who released it
+        actor: String,
+        /// When it was released
+        timestamp: DateTime<Utc>,
+    },
+    /// A trade the account was party to was busted and its balances reversed
+    TradeBusted {
+        /// Account the event is for
+        account_id: Uuid,
+        /// Busted trade
+        trade_id: Uuid,
+        /// Market symbol
+        market: String,
+        /// Side the account was on
+        side: Side,
+        /// Price of the busted trade
+        price: Price,
+        /// Quantity of the busted trade
+        quantity: Quantity,
+        /// Reason given by the operator
+        reason: String,
+        /// When it was busted
+        timestamp: DateTime<Utc>,
+    },
+}
+
+/// Event published on the `system` channel
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SystemEvent {
+    /// A market's trading session changed, moving to `Closed` halts it
+    MarketStatus {
+        /// Market symbol
+        market: String,
+        /// State before the change
+        previous: SessionState,
+        /// State after the change
+        state: SessionState,
+        /// When the change was applied
+        timestamp: DateTime<Utc>,
+    },
+    /// A component checked by `/health` went down or recovered
+    ComponentStatus {
+        /// Component name, as reported by `/health`
+        component: String,
+        /// New status
+        status: ComponentStatus,
+        /// When the change was seen
+        timestamp: DateTime<Utc>,
+    },
+    /// An operator announcement, such as a maintenance window
+    Announcement(Announcement),
+}
@@ -1,4 +1,6 @@
 //! WebSocket handlers
+pub mod connections;
 pub mod handler;
 pub mod message;
+pub mod quota;
 
@@ -0,0 +1,128 @@
+//! WebSocket request quotas
+//!
+//! Each connection may send a limited number of messages per second, and
+//! calls of methods with a quota, such as `getOrderBook`, are further limited
+//! per minute. Messages over either limit are answered with a `429` error
+//! instead of being handled. A connection with too many messages refused
+//! within a minute is closed.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::limits::RequestLimits;
+
+/// Window over which refused messages are counted
+const ABUSE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Tokens refilled at a fixed rate up to a burst size
+struct Bucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(capacity: u32, per: Duration, now: Instant) -> Self {
+        let capacity = capacity.max(1) as f64;
+        Self {
+            capacity,
+            refill_per_sec: capacity / per.as_secs_f64(),
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    /// Take a token, or return how long until one is available
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.refill_per_sec))
+        }
+    }
+}
+
+/// A message refused for exceeding a quota
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Refusal {
+    /// Why the message was refused
+    pub message: String,
+    /// Whether the connection is to be closed for sustained abuse
+    pub close: bool,
+}
+
+/// Request quotas of one connection
+pub struct ConnectionQuota {
+    messages_per_second: u32,
+    messages: Bucket,
+    /// Method -> (calls per minute, bucket)
+    methods: HashMap<String, (u32, Bucket)>,
+    max_refused: u32,
+    refused: u32,
+    window_start: Instant,
+}
+
+impl ConnectionQuota {
+    /// Quotas of a new connection under `limits`
+    pub fn new(limits: &RequestLimits) -> Self {
+        let now = Instant::now();
+        Self {
+            messages_per_second: limits.ws_messages_per_second.max(1),
+            messages: Bucket::new(limits.ws_messages_per_second, Duration::from_secs(1), now),
+            methods: limits.ws_method_quotas
+                .iter()
+                .map(|(method, per_minute)| {
+                    (method.clone(), ((*per_minute).max(1), Bucket::new(*per_minute, Duration::from_secs(60), now)))
+                })
+                .collect(),
+            max_refused: limits.ws_max_refused_per_minute.max(1),
+            refused: 0,
+            window_start: now,
+        }
+    }
+
+    /// Count a message calling `method`, if it names one, refusing it when
+    /// over the connection's or the method's quota
+    pub fn check(&mut self, method: Option<&str>) -> Result<(), Refusal> {
+        let now = Instant::now();
+
+        let exceeded = match self.messages.take(now) {
+            Err(retry_after) => Some(format!(
+                "Limit of {} messages per second exceeded, retry in {}ms",
+                self.messages_per_second,
+                retry_after.as_millis().max(1)
+            )),
+            Ok(()) => method
+                .and_then(|method| self.methods.get_mut(method).map(|quota| (method, quota)))
+                .and_then(|(method, (per_minute, bucket))| {
+                    bucket.take(now).err().map(|retry_after| format!(
+                        "Limit of {} {} calls per minute exceeded, retry in {}s",
+                        per_minute,
+                        method,
+                        retry_after.as_secs().max(1)
+                    ))
+                }),
+        };
+
+        let Some(message) = exceeded else {
+            return Ok(());
+        };
+
+        if now.duration_since(self.window_start) >= ABUSE_WINDOW {
+            self.window_start = now;
+            self.refused = 0;
+        }
+        self.refused += 1;
+
+        Err(Refusal {
+            message,
+            close: self.refused >= self.max_refused,
+        })
+    }
+}
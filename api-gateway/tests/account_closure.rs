@@ -0,0 +1,115 @@
+//! Account closure and data export tests
+//!
+//! Closes accounts through the REST and admin APIs and checks what their
+//! exports contain.
+
+mod common;
+
+use ::common::decimal::dec;
+use axum::http::StatusCode;
+use common::{ADMIN_KEY, Gateway};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    async fn account(&self) -> (Uuid, String) {
+        self.account_with("USD", "1000").await
+    }
+
+    async fn bid(&self, account_id: Uuid, key: &str) -> (StatusCode, Value) {
+        self.limit(account_id, key, "Buy", "100", "1").await
+    }
+}
+
+#[tokio::test]
+async fn test_account_closes_once_emptied() {
+    let gateway = Gateway::start_admin();
+    let (id, key) = gateway.account().await;
+    let close = format!("/accounts/{}/close", id);
+
+    let (status, body) = gateway.bid(id, &key).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let order_id = body["data"]["order"]["id"].as_str().unwrap().to_string();
+
+    // Open orders and funds keep the account open
+    let (status, body) = gateway.send("POST", &close, Some(&key), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]["message"].as_str().unwrap().contains("open orders"), "{}", body);
+
+    let (status, _) = gateway.send("DELETE", &format!("/orders/{}", order_id), Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = gateway.send("POST", &close, Some(&key), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]["message"].as_str().unwrap().contains("USD"), "{}", body);
+
+    let withdraw = json!({ "asset": "USD", "amount": "1000" });
+    let (status, _) = gateway.send("POST", &format!("/accounts/{}/withdraw", id), Some(&key), Some(withdraw)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = gateway.send("POST", &close, Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body["data"]["closed_at"].is_string());
+
+    // A closed account can be read but not funded or traded
+    let (status, body) = gateway.send("GET", &format!("/accounts/{}", id), Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["data"]["closed_at"].is_string());
+    let deposit = json!({ "asset": "USD", "amount": "1" });
+    let (status, _) = gateway.send("POST", &format!("/accounts/{}/deposit", id), Some(&key), Some(deposit)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = gateway.bid(id, &key).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = gateway.send("POST", &close, Some(&key), None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_export_bundles_account_data() {
+    let gateway = Gateway::start_admin();
+    let (buyer, buyer_key) = gateway.account().await;
+    let (other, other_key) = gateway.account().await;
+    let (status, _) = gateway.bid(buyer, &buyer_key).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = gateway.send("GET", &format!("/accounts/{}/export", buyer), Some(&other_key), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = gateway.send("GET", &format!("/accounts/{}/export", buyer), Some(&buyer_key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let export = &body["data"];
+    assert_eq!(export["account"]["id"], json!(buyer));
+    assert_eq!(export["balances"][0]["locked"], "100");
+    assert_eq!(export["reservations"].as_array().unwrap().len(), 1);
+    assert_eq!(export["open_orders"].as_array().unwrap().len(), 1);
+    assert_eq!(export["trades"], json!([]));
+    assert_eq!(export["activity"][0]["action"], "account.exported");
+
+    let (status, body) = gateway.send("GET", &format!("/admin/accounts/{}/export", other), Some(ADMIN_KEY), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["account"]["id"], json!(other));
+    assert_eq!(body["data"]["activity"][0]["actor"], "admin");
+}
+
+#[tokio::test]
+async fn test_admin_override_closes_funded_account() {
+    let gateway = Gateway::start_admin();
+    let (id, key) = gateway.account().await;
+    let (status, _) = gateway.bid(id, &key).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let close = format!("/admin/accounts/{}/close", id);
+    let (status, _) = gateway.send("POST", &close, Some(&key), Some(json!({}))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = gateway.send("POST", &close, Some(ADMIN_KEY), Some(json!({ "reason": "customer request" }))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(gateway.state.matching_engine.get_open_orders(id).is_empty());
+
+    // The cancelled order's funds are released and kept on the closed account
+    let balance = gateway.state.account_service.get_balance(id, "USD").await.unwrap().unwrap();
+    assert_eq!((balance.total, balance.locked), (dec!(1000), dec!(0)));
+
+    let entry = &gateway.state.audit_log.recent(Some(id), 1)[0];
+    assert_eq!(entry.action, "account.closed");
+    assert_eq!(entry.details["forced"], true);
+    assert_eq!(entry.details["reason"], "customer request");
+}
@@ -0,0 +1,83 @@
+//! Account listing and external reference tests
+//!
+//! Creates accounts with external references through the REST API and pages
+//! through them with the admin API.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{ADMIN_KEY, Gateway};
+use serde_json::{json, Value};
+
+impl Gateway {
+    async fn create(&self, external_id: Option<&str>) -> (StatusCode, Value) {
+        self.send("POST", "/accounts", None, Some(json!({ "external_id": external_id }))).await
+    }
+}
+
+#[tokio::test]
+async fn test_accounts_are_found_by_external_reference() {
+    let gateway = Gateway::start_admin();
+
+    let (status, body) = gateway.create(Some("crm-42")).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["external_id"], "crm-42");
+    let id = body["data"]["id"].as_str().unwrap().to_string();
+
+    let (status, body) = gateway.send("GET", "/admin/accounts/external/crm-42", Some(ADMIN_KEY), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["id"], id);
+
+    // References are unique
+    let (status, _) = gateway.create(Some("crm-42")).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // Moving the reference frees the old one
+    let uri = format!("/admin/accounts/{}/external-id", id);
+    let (status, body) = gateway.send("PUT", &uri, Some(ADMIN_KEY), Some(json!({ "external_id": "crm-43" }))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, _) = gateway.send("GET", "/admin/accounts/external/crm-42", Some(ADMIN_KEY), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = gateway.create(Some("crm-42")).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = gateway.send("PUT", &uri, Some(ADMIN_KEY), Some(json!({ "external_id": " " }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Only the admin key may search
+    let (status, _) = gateway.send("GET", "/admin/accounts/external/crm-43", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_accounts_are_listed_a_page_at_a_time() {
+    let gateway = Gateway::start_admin();
+    for i in 0..5 {
+        gateway.create(Some(&format!("desk-{}", i))).await;
+    }
+    gateway.create(None).await;
+
+    let mut seen = Vec::new();
+    let mut uri = "/admin/accounts?external_id_prefix=desk-&limit=2".to_string();
+    loop {
+        let (status, body) = gateway.send("GET", &uri, Some(ADMIN_KEY), None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let accounts = body["data"]["accounts"].as_array().unwrap();
+        assert!(accounts.len() <= 2);
+        seen.extend(accounts.iter().map(|account| account["id"].as_str().unwrap().to_string()));
+
+        match body["data"]["next_cursor"].as_str() {
+            Some(cursor) => uri = format!("/admin/accounts?external_id_prefix=desk-&limit=2&cursor={}", cursor),
+            None => break,
+        }
+    }
+
+    // Pages follow ID order without repeating an account
+    assert_eq!(seen.len(), 5);
+    assert!(seen.windows(2).all(|pair| pair[0] < pair[1]));
+
+    let (_, body) = gateway.send("GET", "/admin/accounts?closed=false", Some(ADMIN_KEY), None).await;
+    assert_eq!(body["data"]["accounts"].as_array().unwrap().len(), 6);
+    let (_, body) = gateway.send("GET", "/admin/accounts?closed=true", Some(ADMIN_KEY), None).await;
+    assert!(body["data"]["accounts"].as_array().unwrap().is_empty());
+}
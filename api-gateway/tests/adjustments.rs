@@ -0,0 +1,100 @@
+//! Fee rebate and adjustment tests
+//!
+//! Posts rebates and manual adjustments as an admin and checks the balances,
+//! audit trail and the account's statement.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{ADMIN_KEY, Gateway, MARKET};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    async fn adjust(&self, account_id: Uuid, kind: &str, body: Value) -> (StatusCode, Value) {
+        self.send("POST", &format!("/admin/accounts/{}/{}", account_id, kind), Some(ADMIN_KEY), Some(body)).await
+    }
+}
+
+#[tokio::test]
+async fn test_rebates_and_adjustments_post_to_the_ledger_and_statement() {
+    let gateway = Gateway::start_admin();
+    let (seller, seller_key) = gateway.trader().await;
+    let (buyer, buyer_key) = gateway.trader().await;
+
+    let order = |account_id: Uuid, side: &str| json!({
+        "user_id": account_id,
+        "market": MARKET,
+        "side": side,
+        "order_type": "Limit",
+        "price": "100",
+        "quantity": "0.5",
+    });
+    assert_eq!(gateway.send("POST", "/orders", Some(&seller_key), Some(order(seller, "Sell"))).await.0, StatusCode::CREATED);
+    assert_eq!(gateway.send("POST", "/orders", Some(&buyer_key), Some(order(buyer, "Buy"))).await.0, StatusCode::CREATED);
+
+    let (status, body) = gateway
+        .adjust(buyer, "fee-rebates", json!({ "asset": "usd", "amount": "1.25", "reason_code": "fee_overcharge" }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["adjustment"]["kind"], "fee_rebate");
+    assert_eq!(body["data"]["adjustment"]["asset"], "USD");
+    let rebate_id = body["data"]["adjustment"]["id"].clone();
+
+    let debit = json!({ "asset": "BTC", "amount": "-0.1", "reason_code": "other", "note": "Duplicate deposit" });
+    let (status, body) = gateway.adjust(buyer, "adjustments", debit).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["balance"]["asset"], "BTC");
+
+    // The statement lists the trade legs, then the rebate and the debit
+    let (status, body) = gateway.send("GET", &format!("/accounts/{}/statement", buyer), Some(&buyer_key), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let entries = body["data"].as_array().unwrap();
+    let kinds: Vec<&str> = entries.iter().map(|entry| entry["kind"].as_str().unwrap()).collect();
+    assert_eq!(kinds, ["trade", "trade", "fee_rebate", "adjustment"]);
+    assert_eq!(entries[2]["reference"], rebate_id);
+    assert_eq!(entries[2]["reason_code"], "fee_overcharge");
+    assert_eq!(entries[3]["amount"], "-0.1");
+    assert_eq!(entries[3]["note"], "Duplicate deposit");
+
+    let (_, body) = gateway.send("GET", &format!("/admin/audit?account_id={}", buyer), Some(ADMIN_KEY), None).await;
+    let actions: Vec<&str> = body["data"].as_array().unwrap().iter().map(|e| e["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, ["balance.adjusted", "fee.rebated"]);
+    assert_eq!(body["data"][1]["details"]["reason_code"], "fee_overcharge");
+
+    // Other accounts' statements are off limits
+    let (status, _) = gateway.send("GET", &format!("/accounts/{}/statement", buyer), Some(&seller_key), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_adjustments_need_a_reason_and_valid_amount() {
+    let gateway = Gateway::start_admin();
+    let (id, _) = gateway.trader().await;
+
+    for (kind, body) in [
+        ("fee-rebates", json!({ "asset": "USD", "amount": "-1", "reason_code": "fee_overcharge" })),
+        ("adjustments", json!({ "asset": "USD", "amount": "0", "reason_code": "goodwill" })),
+        ("adjustments", json!({ "asset": "USD", "amount": "1", "reason_code": "other" })),
+        ("adjustments", json!({ "asset": "USD", "amount": "-1001", "reason_code": "error_correction" })),
+    ] {
+        let (status, response) = gateway.adjust(id, kind, body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", response);
+    }
+
+    // Without a known reason code the request is refused before anything is posted
+    let (status, _) = gateway.adjust(id, "adjustments", json!({ "asset": "USD", "amount": "1" })).await;
+    assert!(status.is_client_error());
+    let (status, _) = gateway.adjust(id, "adjustments", json!({ "asset": "USD", "amount": "1", "reason_code": "whim" })).await;
+    assert!(status.is_client_error());
+
+    let (status, _) = gateway.adjust(Uuid::new_v4(), "adjustments", json!({ "asset": "USD", "amount": "1", "reason_code": "goodwill" })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = gateway
+        .send("POST", &format!("/admin/accounts/{}/adjustments", id), None, Some(json!({ "asset": "USD", "amount": "1", "reason_code": "goodwill" })))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (_, body) = gateway.send("GET", &format!("/admin/audit?account_id={}", id), Some(ADMIN_KEY), None).await;
+    assert!(body["data"].as_array().unwrap().is_empty());
+}
@@ -0,0 +1,142 @@
+//! API key scope tests
+//!
+//! Issues keys with narrower scopes and address allowlists and checks each
+//! route class, the admin API and the distinct rejection codes.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{ADMIN_KEY, Gateway};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    /// Create an account holding USD, returning its ID and API key
+    async fn account(&self) -> (Uuid, String) {
+        self.account_with("USD", "1000").await
+    }
+
+    /// Issue another key for an account with `key`, returning the new key
+    async fn issue(&self, id: Uuid, key: &str, request: Value) -> String {
+        let (status, body) = self.send("POST", &format!("/accounts/{}/api-keys", id), Some(key), Some(request)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        body["data"]["api_key"].as_str().unwrap().to_string()
+    }
+
+    async fn order(&self, account_id: Uuid, key: &str) -> (StatusCode, Value) {
+        self.limit(account_id, key, "Buy", "100", "0.5").await
+    }
+}
+
+#[tokio::test]
+async fn test_each_route_class_needs_its_scope() {
+    let gateway = Gateway::start_admin();
+    let (id, key) = gateway.account().await;
+    let read_only = gateway.issue(id, &key, json!({ "scopes": ["read"] })).await;
+    let trade_only = gateway.issue(id, &key, json!({ "scopes": ["trade"] })).await;
+
+    let balances = format!("/accounts/{}/balances", id);
+    assert_eq!(gateway.send("GET", &balances, Some(&read_only), None).await.0, StatusCode::OK);
+    let (status, body) = gateway.send("GET", &balances, Some(&trade_only), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "insufficient_scope");
+
+    let (status, body) = gateway.order(id, &read_only).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "insufficient_scope");
+    assert_eq!(gateway.order(id, &trade_only).await.0, StatusCode::CREATED);
+
+    let withdrawal = json!({ "asset": "USD", "amount": "10" });
+    let (status, body) = gateway.send("POST", &format!("/accounts/{}/withdraw", id), Some(&trade_only), Some(withdrawal)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "insufficient_scope");
+
+    // A key cannot hand out scopes it lacks
+    let (status, body) = gateway.send("POST", &format!("/accounts/{}/api-keys", id), Some(&trade_only), Some(json!({ "scopes": ["withdraw"] }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "insufficient_scope");
+    let (status, _) = gateway.send("POST", &format!("/accounts/{}/api-keys", id), Some(&key), Some(json!({ "scopes": [] }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // The key's scopes are narrowed by the account's permissions
+    let (_, body) = gateway.send("GET", &format!("/accounts/{}/permissions", id), Some(&read_only), None).await;
+    assert_eq!(body["data"]["scopes"], json!(["read"]));
+}
+
+#[tokio::test]
+async fn test_keys_are_limited_to_their_allowlist() {
+    let gateway = Gateway::start_admin();
+    let (id, key) = gateway.account().await;
+    let office = gateway.issue(id, &key, json!({ "scopes": ["read"], "allowed_ips": ["10.0.0.0/8", "203.0.113.7"] })).await;
+
+    let balances = format!("/accounts/{}/balances", id);
+    for client in ["10.1.2.3", "203.0.113.7"] {
+        assert_eq!(gateway.send_from(Some(client), "GET", &balances, &office, None).await.0, StatusCode::OK);
+    }
+    for client in [Some("192.0.2.1"), None] {
+        let (status, body) = gateway.send_from(client, "GET", &balances, &office, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"]["code"], "ip_not_allowed");
+    }
+
+    let (status, _) = gateway.send("POST", &format!("/accounts/{}/api-keys", id), Some(&key), Some(json!({ "scopes": ["read"], "allowed_ips": ["nowhere"] }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_rejections_have_distinct_codes_and_revoked_keys_stop_working() {
+    let gateway = Gateway::start_admin();
+    let (id, key) = gateway.account().await;
+    let balances = format!("/accounts/{}/balances", id);
+
+    let (status, body) = gateway.send("GET", &balances, Some("zk_unknown"), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["code"], "invalid_api_key");
+
+    let spare = gateway.issue(id, &key, json!({ "scopes": ["read"] })).await;
+    let (_, body) = gateway.send("GET", &format!("/accounts/{}/api-keys", id), Some(&key), None).await;
+    let keys = body["data"].as_array().unwrap();
+    assert_eq!(keys.len(), 2);
+    assert!(keys.iter().all(|listed| listed.get("api_key").is_none()));
+    let spare_id = keys[1]["id"].as_str().unwrap();
+    assert_eq!(keys[1]["scopes"], json!(["read"]));
+
+    let (status, _) = gateway.send("DELETE", &format!("/accounts/{}/api-keys/{}", id, spare_id), Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = gateway.send("GET", &balances, Some(&spare), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["code"], "invalid_api_key");
+    let (status, _) = gateway.send("DELETE", &format!("/accounts/{}/api-keys/{}", id, spare_id), Some(&key), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_admin_scoped_keys_use_the_admin_api() {
+    let gateway = Gateway::start_admin();
+    let (id, key) = gateway.account().await;
+
+    // Only admins can hand out the admin scope
+    let (status, _) = gateway.send("POST", &format!("/accounts/{}/api-keys", id), Some(&key), Some(json!({ "scopes": ["admin"] }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let request = json!({ "scopes": ["admin"], "allowed_ips": ["198.51.100.0/24"] });
+    let (status, body) = gateway.send("POST", &format!("/admin/accounts/{}/api-keys", id), Some(ADMIN_KEY), Some(request)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let operator = body["data"]["api_key"].as_str().unwrap().to_string();
+
+    let (status, _) = gateway.send_from(Some("198.51.100.9"), "GET", "/admin/accounts", &operator, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = gateway.send_from(Some("192.0.2.1"), "GET", "/admin/accounts", &operator, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "ip_not_allowed");
+
+    // Other keys are not admin keys, and the admin key has no account
+    let (status, body) = gateway.send("GET", "/admin/accounts", Some(&key), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["code"], "invalid_api_key");
+    let (status, _) = gateway.send_from(Some("198.51.100.9"), "GET", &format!("/accounts/{}/balances", id), &operator, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (_, body) = gateway.send("GET", &format!("/admin/audit?account_id={}", id), Some(ADMIN_KEY), None).await;
+    assert_eq!(body["data"][0]["action"], "api_key.issued");
+    assert_eq!(body["data"][0]["details"]["scopes"], json!(["admin"]));
+}
@@ -0,0 +1,140 @@
+//! Asset registry tests
+//!
+//! Registers assets through the admin API and checks deposits, withdrawals
+//! and orders are held to each asset's precision and rules.
+
+mod common;
+
+use ::common::decimal::dec;
+use ::common::model::market::Market;
+use axum::http::StatusCode;
+use common::{admin_config, spot, state_for, ADMIN_KEY, Gateway, MARKET};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    /// BTC/USD traded in satoshis
+    fn setup() -> Self {
+        let market = Market {
+            quantity_step: dec!(0.00000001),
+            min_order_size: dec!(0.00000001),
+            ..spot(MARKET)
+        };
+        Self::new(state_for(vec![market]), &admin_config())
+    }
+
+    async fn register(&self, code: &str, asset: Value) {
+        let (status, body) = self.send("PUT", &format!("/admin/assets/{}", code), Some(ADMIN_KEY), Some(asset)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    async fn deposit(&self, id: Uuid, key: &str, asset: &str, amount: &str) -> StatusCode {
+        let deposit = json!({ "asset": asset, "amount": amount });
+        self.send("POST", &format!("/accounts/{}/deposit", id), Some(key), Some(deposit)).await.0
+    }
+}
+
+#[tokio::test]
+async fn test_assets_are_registered_and_listed() {
+    let gateway = Gateway::setup();
+    gateway.register("usd", json!({ "name": "US Dollar", "precision": 2 })).await;
+    gateway.register("BTC", json!({
+        "name": "Bitcoin",
+        "precision": 8,
+        "withdrawal_min": "0.001",
+        "withdrawal_fee": "0.0005",
+    })).await;
+
+    let (status, body) = gateway.send("GET", "/assets", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let assets = body["data"].as_array().unwrap();
+    let codes: Vec<&str> = assets.iter().map(|asset| asset["code"].as_str().unwrap()).collect();
+    assert_eq!(codes, ["BTC", "USD"]);
+    assert_eq!(assets[0]["withdrawal_fee"], "0.0005");
+    assert_eq!(assets[1]["precision"], 2);
+    assert_eq!(assets[1]["deposit_enabled"], true);
+
+    let (_, body) = gateway.send("GET", "/admin/audit", Some(ADMIN_KEY), None).await;
+    assert_eq!(body["data"][0]["action"], "asset.registered");
+}
+
+#[tokio::test]
+async fn test_only_operators_register_valid_assets() {
+    let gateway = Gateway::setup();
+
+    let (status, _) = gateway.send("PUT", "/admin/assets/USD", None, Some(json!({ "name": "US Dollar", "precision": 2 }))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    for (code, asset) in [
+        ("USD", json!({ "name": "US Dollar", "precision": 19 })),
+        ("USD", json!({ "name": "US Dollar", "precision": 2, "withdrawal_fee": "0.001" })),
+        ("US-D", json!({ "name": "US Dollar", "precision": 2 })),
+    ] {
+        let (status, _) = gateway.send("PUT", &format!("/admin/assets/{}", code), Some(ADMIN_KEY), Some(asset)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    let (_, body) = gateway.send("GET", "/assets", None, None).await;
+    assert!(body["data"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_deposits_follow_the_asset_rules() {
+    let gateway = Gateway::setup();
+    gateway.register("USD", json!({ "name": "US Dollar", "precision": 2 })).await;
+    gateway.register("ETH", json!({ "name": "Ether", "precision": 8, "deposit_enabled": false })).await;
+    let (id, key) = gateway.create_account().await;
+
+    assert_eq!(gateway.deposit(id, &key, "USD", "10.25").await, StatusCode::OK);
+    assert_eq!(gateway.deposit(id, &key, "USD", "10.255").await, StatusCode::BAD_REQUEST);
+    assert_eq!(gateway.deposit(id, &key, "ETH", "1").await, StatusCode::BAD_REQUEST);
+
+    // Unregistered assets keep the default eight places
+    assert_eq!(gateway.deposit(id, &key, "SOL", "0.00000001").await, StatusCode::OK);
+    assert_eq!(gateway.deposit(id, &key, "SOL", "0.000000001").await, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_withdrawals_pay_out_net_of_the_fee() {
+    let gateway = Gateway::setup();
+    gateway.register("BTC", json!({
+        "name": "Bitcoin",
+        "precision": 8,
+        "withdrawal_min": "0.001",
+        "withdrawal_fee": "0.0005",
+    })).await;
+    let (id, key) = gateway.create_account().await;
+    assert_eq!(gateway.deposit(id, &key, "BTC", "1").await, StatusCode::OK);
+
+    let uri = format!("/accounts/{}/withdraw", id);
+    let (status, _) = gateway.send("POST", &uri, Some(&key), Some(json!({ "asset": "BTC", "amount": "0.0009" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // The whole amount leaves the balance
+    let (status, body) = gateway.send("POST", &uri, Some(&key), Some(json!({ "asset": "BTC", "amount": "0.1" }))).await;
+    assert!(status.is_success(), "{}", body);
+    assert_eq!(body["data"]["total"], "0.9");
+}
+
+#[tokio::test]
+async fn test_order_quantities_follow_the_base_asset_precision() {
+    let gateway = Gateway::setup();
+    gateway.register("BTC", json!({ "name": "Bitcoin", "precision": 4 })).await;
+    let (id, key) = gateway.create_account().await;
+    assert_eq!(gateway.deposit(id, &key, "USD", "1000").await, StatusCode::OK);
+
+    let order = |quantity: &str| json!({
+        "user_id": id,
+        "market": MARKET,
+        "side": "Buy",
+        "order_type": "Limit",
+        "price": "100",
+        "quantity": quantity,
+    });
+    let (status, _) = gateway.send("POST", "/orders/preview", Some(&key), Some(order("0.00001"))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = gateway.send("POST", "/orders", Some(&key), Some(order("0.00001"))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = gateway.send("POST", "/orders", Some(&key), Some(order("0.0001"))).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+}
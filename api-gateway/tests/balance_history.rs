@@ -0,0 +1,98 @@
+//! Balance history tests
+//!
+//! Takes balance snapshots through the admin API on a manual clock and reads
+//! them back as balance history through the REST API.
+
+mod common;
+
+use std::sync::Arc;
+
+use ::common::clock::{Clock, ManualClock};
+use account_service::AccountService;
+use api_gateway::AppState;
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration};
+use common::{admin_config, ADMIN_KEY, Gateway};
+use market_data::MarketDataService;
+use matching_engine::MatchingEngine;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    /// Gateway whose engine runs on a manual clock, and the clock
+    fn setup() -> (Self, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(DateTime::from_timestamp(1_740_787_200, 0).unwrap()));
+        let state = AppState::new(
+            Arc::new(MatchingEngine::new().with_clock(clock.clone())),
+            Arc::new(AccountService::new()),
+            Arc::new(MarketDataService::new()),
+            Vec::new(),
+        );
+        (Self::new(state, &admin_config()), clock)
+    }
+
+    async fn snapshot(&self, account_id: Option<Uuid>) -> Value {
+        let request = json!({ "account_id": account_id });
+        let (status, body) = self.send("POST", "/admin/balance-snapshots", Some(ADMIN_KEY), Some(request)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["data"].clone()
+    }
+}
+
+#[tokio::test]
+async fn test_balance_history_has_a_point_per_interval() {
+    let (gateway, clock) = Gateway::setup();
+    let (id, key) = gateway.create_account().await;
+    let start = clock.now();
+
+    gateway.fund(id, &key, "BTC", "1").await;
+    gateway.fund(id, &key, "USD", "500").await;
+    clock.advance(Duration::hours(6));
+    assert_eq!(gateway.snapshot(None).await["recorded"], 2);
+
+    // Only the day's last snapshot is kept in a daily history
+    gateway.fund(id, &key, "BTC", "1").await;
+    clock.advance(Duration::hours(12));
+    assert_eq!(gateway.snapshot(Some(id)).await["recorded"], 2);
+    gateway.fund(id, &key, "BTC", "1").await;
+    clock.advance(Duration::days(1));
+    gateway.snapshot(None).await;
+
+    let uri = format!("/accounts/{}/balance-history?asset=btc&interval=1d", id);
+    let (status, body) = gateway.send("GET", &uri, Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let totals: Vec<String> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|point| point["total"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(totals, vec!["2", "3"]);
+
+    let from = (start + Duration::hours(1)).to_rfc3339().replace('+', "%2B");
+    let uri = format!("/accounts/{}/balance-history?interval=1h&from={}", id, from);
+    let (_, body) = gateway.send("GET", &uri, Some(&key), None).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 6);
+}
+
+#[tokio::test]
+async fn test_balance_history_is_private_and_validated() {
+    let gateway = Gateway::start_admin();
+    let (id, key) = gateway.create_account().await;
+    let (_, other_key) = gateway.create_account().await;
+
+    let uri = format!("/accounts/{}/balance-history", id);
+    let (status, _) = gateway.send("GET", &uri, Some(&other_key), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = gateway.send("GET", &uri, Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["data"].as_array().unwrap().is_empty());
+
+    let (status, _) = gateway.send("GET", &format!("{}?interval=2d", uri), Some(&key), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Snapshots are taken by operators only
+    let (status, _) = gateway.send("POST", "/admin/balance-snapshots", Some(&key), Some(json!({}))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
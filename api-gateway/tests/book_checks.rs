@@ -0,0 +1,32 @@
+//! Crossed book check tests
+//!
+//! Trades through the REST API and reads the engine's crossed book checks
+//! through the admin API.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{Gateway, MARKET};
+
+#[tokio::test]
+async fn test_book_checks_are_reported_by_market() {
+    let gateway = Gateway::start_admin();
+    let (status, body) = gateway.admin("GET", "/admin/metrics/books", None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"], serde_json::json!([]));
+
+    let (maker, maker_key) = gateway.trader().await;
+    let (taker, taker_key) = gateway.trader().await;
+    assert_eq!(gateway.limit(maker, &maker_key, "Sell", "100", "0.5").await.0, StatusCode::CREATED);
+    assert_eq!(gateway.limit(taker, &taker_key, "Buy", "101", "0.25").await.0, StatusCode::CREATED);
+
+    let (_, body) = gateway.admin("GET", "/admin/metrics/books", None).await;
+    let checks = &body["data"][0];
+    assert_eq!(checks["market"], MARKET);
+    assert_eq!(checks["checks"], 2);
+    assert_eq!(checks["crossings"], 0);
+    assert_eq!(checks["refused_orders"], 0);
+    assert!(checks["last_crossed_at"].is_null());
+
+    assert_eq!(gateway.send("GET", "/admin/metrics/books", None, None).await.0, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,114 @@
+//! Book limit tests
+//!
+//! Sets a market's book limits through the admin API and checks that orders
+//! refused, pruned or expired by them give their reserved funds back.
+
+mod common;
+
+use ::common::decimal::dec;
+use api_gateway::expiry::expire_stale_orders;
+use axum::http::StatusCode;
+use common::{ADMIN_KEY, Gateway, MARKET};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+const LIMITS_URI: &str = "/admin/markets/BTC%2FUSD/book-limits";
+
+impl Gateway {
+    async fn account(&self) -> (Uuid, String) {
+        self.account_with("USD", "1000").await
+    }
+
+    async fn bid(&self, account_id: Uuid, key: &str, price: &str) -> Value {
+        let (status, body) = self.limit(account_id, key, "Buy", price, "1").await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        body["data"]["order"].clone()
+    }
+
+    async fn locked(&self, account_id: Uuid, key: &str) -> Decimal {
+        let (_, body) = self.send("GET", &format!("/accounts/{}/balances", account_id), Some(key), None).await;
+        let balance = body["data"].as_array().unwrap().iter().find(|b| b["asset"] == "USD").unwrap().clone();
+        balance["locked"].as_str().unwrap().parse().unwrap()
+    }
+}
+
+#[tokio::test]
+async fn test_limits_are_set_and_cleared_through_admin_api() {
+    let gateway = Gateway::start_admin();
+
+    let (status, body) = gateway.send("GET", LIMITS_URI, Some(ADMIN_KEY), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"], json!({
+        "max_orders_per_account": null,
+        "max_distance_bps": null,
+        "max_levels": null,
+        "level_policy": "reject",
+        "max_order_age_secs": null,
+    }));
+
+    let (status, _) = gateway.send("PUT", LIMITS_URI, None, Some(json!({ "max_levels": 2 }))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = gateway.send("PUT", LIMITS_URI, Some(ADMIN_KEY), Some(json!({ "max_levels": 0 }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = gateway.send("PUT", "/admin/markets/ETH-USD/book-limits", Some(ADMIN_KEY), Some(json!({}))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let limits = json!({ "max_levels": 2, "level_policy": "prune_farthest" });
+    let (status, body) = gateway.send("PUT", LIMITS_URI, Some(ADMIN_KEY), Some(limits)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["max_levels"], 2);
+    assert_eq!(gateway.state.matching_engine.book_limits(MARKET).unwrap().max_levels, Some(2));
+
+    let (status, _) = gateway.send("DELETE", LIMITS_URI, Some(ADMIN_KEY), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(gateway.state.matching_engine.book_limits(MARKET).unwrap().max_levels, None);
+
+    let actions: Vec<String> = gateway.state.audit_log.recent(None, 10).into_iter().map(|entry| entry.action).collect();
+    assert_eq!(actions, ["market.book_limits_cleared", "market.book_limits_set"]);
+}
+
+#[tokio::test]
+async fn test_refused_and_pruned_orders_release_their_funds() {
+    let gateway = Gateway::start_admin();
+    let limits = json!({ "max_levels": 2, "level_policy": "prune_farthest" });
+    let (status, _) = gateway.send("PUT", LIMITS_URI, Some(ADMIN_KEY), Some(limits)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (maker, maker_key) = gateway.account().await;
+    let (other, other_key) = gateway.account().await;
+    gateway.bid(maker, &maker_key, "100").await;
+    gateway.bid(maker, &maker_key, "99").await;
+    assert_eq!(gateway.locked(maker, &maker_key).await, dec!(199));
+
+    let refused = gateway.bid(other, &other_key, "98").await;
+    assert_eq!(refused["status"], "Expired");
+    assert_eq!(refused["reject_reason"], "BookLimit");
+    assert_eq!(gateway.locked(other, &other_key).await, Decimal::ZERO);
+
+    // The better bid pushes the bid at 99 off the book
+    gateway.bid(other, &other_key, "101").await;
+    assert_eq!(gateway.locked(other, &other_key).await, dec!(101));
+    assert_eq!(gateway.locked(maker, &maker_key).await, dec!(100));
+}
+
+#[tokio::test]
+async fn test_orders_past_their_maximum_age_release_their_funds() {
+    let gateway = Gateway::start_admin();
+    let (status, _) = gateway.send("PUT", LIMITS_URI, Some(ADMIN_KEY), Some(json!({ "max_order_age_secs": 60 }))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (account, key) = gateway.account().await;
+    let bid = gateway.bid(account, &key, "100").await;
+    assert_eq!(gateway.locked(account, &key).await, dec!(100));
+
+    let created_at: chrono::DateTime<chrono::Utc> = bid["created_at"].as_str().unwrap().parse().unwrap();
+    assert!(expire_stale_orders(&gateway.state, created_at + chrono::Duration::seconds(59)).await.is_empty());
+
+    let expired = expire_stale_orders(&gateway.state, created_at + chrono::Duration::seconds(61)).await;
+    assert_eq!(expired.len(), 1);
+    assert_eq!(gateway.locked(account, &key).await, Decimal::ZERO);
+
+    let (status, body) = gateway.send("GET", &format!("/orders/{}", bid["id"].as_str().unwrap()), Some(&key), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{}", body);
+}
@@ -0,0 +1,86 @@
+//! Capability discovery tests
+//!
+//! Builds gateways from different configurations and checks
+//! `/capabilities` reports what each one enables.
+
+mod common;
+
+use std::sync::Arc;
+
+use ::common::decimal::dec;
+use account_service::settlement::MockSettlementAdapter;
+use account_service::AccountService;
+use api_gateway::config::AppConfig;
+use api_gateway::AppState;
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use common::{engine, spot, Gateway, MARKET};
+use market_data::MarketDataService;
+use serde_json::{json, Value};
+
+impl Gateway {
+    fn setup(config: &AppConfig, account_service: AccountService) -> Self {
+        let state = AppState::new(
+            engine(&[spot(MARKET)]),
+            Arc::new(account_service),
+            Arc::new(MarketDataService::new()),
+            vec![spot(MARKET)],
+        );
+        Self::new(state, config)
+    }
+
+    async fn capabilities(&self) -> Value {
+        let request = Request::builder().uri("/capabilities").body(Body::empty()).unwrap();
+        let (status, headers, body) = self.call(request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(headers[header::CACHE_CONTROL].to_str().unwrap().starts_with("public"));
+        body["data"].clone()
+    }
+}
+
+#[tokio::test]
+async fn test_default_deployment_capabilities() {
+    let gateway = Gateway::setup(&AppConfig::default(), AccountService::new());
+    let capabilities = gateway.capabilities().await;
+
+    assert_eq!(capabilities["margin"], false);
+    assert_eq!(capabilities["order_types"], json!(["Market", "Limit"]));
+    assert_eq!(capabilities["time_in_force"], json!(["GTC", "IOC", "FOK"]));
+    assert_eq!(capabilities["auth"]["api_key_header"], "x-api-key");
+    assert_eq!(capabilities["auth"]["admin_api"], false);
+    assert_eq!(capabilities["repositories"], json!({ "accounts": "memory", "market_data": "memory" }));
+    assert_eq!(capabilities["websocket"]["protocol_versions"], json!([1, 2]));
+    assert_eq!(capabilities["websocket"]["default_version"], 1);
+    assert!(capabilities["websocket"]["channels"].as_array().unwrap().contains(&json!("system")));
+
+    let features = &capabilities["features"];
+    assert_eq!(features["reports"], false);
+    assert_eq!(features["maker_rebates"], false);
+    assert_eq!(features["settlement"], json!([]));
+    assert_eq!(features["order_book_history"], true);
+    assert_eq!(features["binary_feed"], false);
+    assert_eq!(features["number_formats"], json!(["native", "decimal-strings"]));
+    assert_eq!(features["default_number_format"], "native");
+}
+
+#[tokio::test]
+async fn test_capabilities_follow_configuration() {
+    let mut config = AppConfig {
+        admin_api_key: Some("test-admin-key".to_string()),
+        order_book_snapshot_interval: None,
+        ..AppConfig::default()
+    };
+    config.incentives.rebate_rate = dec!(0.0001);
+    config.earn.rates.insert("USD".to_string(), dec!(0.05));
+    let accounts = AccountService::new().with_settlement_adapter(Arc::new(MockSettlementAdapter::new()));
+
+    let gateway = Gateway::setup(&config, accounts);
+    let capabilities = gateway.capabilities().await;
+
+    assert_eq!(capabilities["auth"]["admin_api"], true);
+    let features = &capabilities["features"];
+    assert_eq!(features["maker_rebates"], true);
+    assert_eq!(features["earn_assets"], json!(["USD"]));
+    assert_eq!(features["settlement"], json!(["mock"]));
+    assert_eq!(features["order_book_history"], false);
+}
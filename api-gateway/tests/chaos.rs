@@ -0,0 +1,164 @@
+//! Failure injection tests
+//!
+//! Injects faults at settlement, trade publication and market data
+//! repository calls, then checks that orders still settle, failed
+//! settlements are retried, market data repairs the trades it missed and
+//! live data outlasts failed history writes.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ::common::chaos::{Chaos, ChaosConfig, ChaosPoint, Fault, SharedChaos};
+use ::common::decimal::dec;
+use account_service::AccountService;
+use api_gateway::config::AppConfig;
+use api_gateway::market_sync::EngineSyncSource;
+use api_gateway::pipeline::{retry_pending_settlements, PipelineConfig};
+use api_gateway::system::ComponentStatus;
+use api_gateway::AppState;
+use axum::http::StatusCode;
+use common::{engine, spot, Gateway, MARKET};
+use market_data::repository::{ChaosRepository, InMemoryMarketRepository};
+use market_data::MarketDataService;
+use uuid::Uuid;
+
+impl Gateway {
+    /// Gateway whose services inject the faults of `chaos`, settling on `workers` background workers
+    fn setup(chaos: SharedChaos, workers: usize) -> Self {
+        let matching_engine = engine(&[spot(MARKET)]);
+        let repository = Arc::new(ChaosRepository::new(Arc::new(InMemoryMarketRepository::new()), chaos.clone()));
+
+        let state = AppState::new(
+            matching_engine.clone(),
+            Arc::new(AccountService::new().with_chaos(chaos.clone())),
+            Arc::new(MarketDataService::new()
+                .with_repository(repository)
+                .with_sync_source(Arc::new(EngineSyncSource::new(matching_engine)))
+                .with_chaos(chaos)),
+            vec![spot(MARKET)],
+        ).with_settlement_pipeline(PipelineConfig { workers, ..PipelineConfig::default() });
+
+        Self::new(state, &AppConfig::default())
+    }
+
+    /// Place a limit order, returning the response status
+    async fn order(&self, account_id: Uuid, key: &str, side: &str, price: &str) -> StatusCode {
+        self.limit(account_id, key, side, price, "0.5").await.0
+    }
+
+    async fn balance(&self, account_id: Uuid, key: &str, asset: &str) -> String {
+        let (status, body) = self.send("GET", &format!("/accounts/{}/balances", account_id), Some(key), None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let balances = body["data"].as_array().unwrap();
+        let balance = balances.iter().find(|balance| balance["asset"] == asset).unwrap();
+        balance["total"].as_str().unwrap().to_string()
+    }
+}
+
+#[tokio::test]
+async fn test_trade_lost_on_publication_is_repaired_by_the_sync_check() {
+    let chaos = Arc::new(Chaos::new(ChaosConfig::default().with_fault(ChaosPoint::Publication, Fault::fail_first(1))));
+    let gateway = Gateway::setup(chaos.clone(), 0);
+    let (maker, maker_key) = gateway.trader().await;
+    let (taker, taker_key) = gateway.trader().await;
+
+    // The trade executes and settles, but never reaches market data
+    assert_eq!(gateway.order(maker, &maker_key, "Sell", "100").await, StatusCode::CREATED);
+    assert!(gateway.order(taker, &taker_key, "Buy", "100").await.is_server_error());
+    assert_eq!(chaos.counts(ChaosPoint::Publication).failures, 1);
+    assert_eq!(gateway.balance(taker, &taker_key, "BTC").await, "1.5");
+    let market_data = &gateway.state.market_data_service;
+    assert!(market_data.get_recent_trades(MARKET, 10).is_empty());
+
+    // The first check sees the engine's latest trade, the next repairs it
+    market_data.check_sync().await.unwrap();
+    market_data.check_sync().await.unwrap();
+    let trades = market_data.get_recent_trades(MARKET, 10);
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].price, dec!(100));
+    assert_eq!(market_data.get_ticker(MARKET).unwrap().last, Some(dec!(100)));
+}
+
+#[tokio::test]
+async fn test_slow_settlement_is_flushed_before_balances_are_read() {
+    let chaos = Arc::new(Chaos::new(ChaosConfig::default()));
+    let gateway = Gateway::setup(chaos.clone(), 2);
+    let (maker, maker_key) = gateway.trader().await;
+    let (taker, taker_key) = gateway.trader().await;
+    chaos.set_fault(ChaosPoint::Settlement, Fault::latency(Duration::from_millis(300)));
+
+    // The placement is answered before the trade settles
+    assert_eq!(gateway.order(maker, &maker_key, "Sell", "100").await, StatusCode::CREATED);
+    let placed = Instant::now();
+    assert_eq!(gateway.order(taker, &taker_key, "Buy", "100").await, StatusCode::CREATED);
+    assert!(placed.elapsed() < Duration::from_millis(300));
+
+    // Reading either side's balances waits for it
+    assert_eq!(gateway.balance(taker, &taker_key, "BTC").await, "1.5");
+    assert_eq!(gateway.balance(maker, &maker_key, "USD").await, "1050.0");
+    assert!(placed.elapsed() >= Duration::from_millis(300));
+    assert_eq!(chaos.counts(ChaosPoint::Settlement).calls, 1);
+}
+
+#[tokio::test]
+async fn test_failed_settlement_is_retried_once_settlement_recovers() {
+    let chaos = Arc::new(Chaos::new(ChaosConfig::default()));
+    let gateway = Gateway::setup(chaos.clone(), 2);
+    let (maker, maker_key) = gateway.trader().await;
+    let (taker, taker_key) = gateway.trader().await;
+    chaos.set_fault(ChaosPoint::Settlement, Fault::error_rate(1.0));
+
+    // The placement is answered, but its trade stays stored unsettled
+    assert_eq!(gateway.order(maker, &maker_key, "Sell", "100").await, StatusCode::CREATED);
+    assert_eq!(gateway.order(taker, &taker_key, "Buy", "100").await, StatusCode::CREATED);
+    gateway.state.settlement.flush_all().await;
+    let account_service = &gateway.state.account_service;
+    let pending = account_service.pending_settlements().await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].attempts, 1);
+    assert_eq!(gateway.balance(taker, &taker_key, "BTC").await, "1");
+    assert!(gateway.state.market_data_service.get_recent_trades(MARKET, 10).is_empty());
+
+    // The failure shows in /health and the pipeline's counts
+    let report = gateway.state.health.refresh().await;
+    assert_eq!(report.services["settlement"].status, ComponentStatus::Down);
+    assert_eq!(gateway.state.settlement.failing().len(), 1);
+    assert_eq!(gateway.state.settlement.failures(), 1);
+
+    // Once settlement recovers, the retry settles and publishes the trade
+    chaos.clear(ChaosPoint::Settlement);
+    assert_eq!(retry_pending_settlements(&gateway.state).await.unwrap(), 1);
+    assert_eq!(gateway.balance(taker, &taker_key, "BTC").await, "1.5");
+    assert_eq!(gateway.balance(maker, &maker_key, "USD").await, "1050.0");
+    assert_eq!(gateway.state.market_data_service.get_recent_trades(MARKET, 10).len(), 1);
+    assert!(account_service.pending_settlements().await.unwrap().is_empty());
+    assert!(gateway.state.settlement.failing().is_empty());
+    let report = gateway.state.health.refresh().await;
+    assert_eq!(report.services["settlement"].status, ComponentStatus::Up);
+
+    // Nothing is left to retry
+    assert_eq!(retry_pending_settlements(&gateway.state).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_live_market_data_outlasts_failing_history_writes() {
+    let chaos = Arc::new(Chaos::new(ChaosConfig { seed: 7, ..ChaosConfig::default() }.with_fault(ChaosPoint::Repository, Fault::error_rate(1.0))));
+    let gateway = Gateway::setup(chaos.clone(), 0);
+    let (maker, maker_key) = gateway.trader().await;
+    let (taker, taker_key) = gateway.trader().await;
+
+    assert_eq!(gateway.order(maker, &maker_key, "Sell", "100").await, StatusCode::CREATED);
+    assert_eq!(gateway.order(taker, &taker_key, "Buy", "100").await, StatusCode::CREATED);
+
+    // The trade is served live though it never reached history
+    let market_data = &gateway.state.market_data_service;
+    assert_eq!(market_data.get_recent_trades(MARKET, 10).len(), 1);
+    assert!(market_data.snapshot_order_books().await.is_err());
+    assert!(chaos.counts(ChaosPoint::Repository).failures >= 2);
+
+    // Once the repository recovers, history is written again
+    chaos.clear(ChaosPoint::Repository);
+    market_data.snapshot_order_books().await.unwrap();
+}
@@ -0,0 +1,210 @@
+//! Gateway fixture shared by the integration tests
+//!
+//! Drives the gateway router in-process over a fresh in-memory stack trading
+//! BTC/USD. Each test file adds the helpers it needs in its own
+//! `impl Gateway` block.
+
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use ::common::decimal::dec;
+use ::common::model::market::{Market, MarketKind};
+use account_service::AccountService;
+use api_gateway::config::AppConfig;
+use api_gateway::routes::api_router;
+use api_gateway::AppState;
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::Router;
+use market_data::MarketDataService;
+use matching_engine::MatchingEngine;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+/// Market every fixture trades
+pub const MARKET: &str = "BTC/USD";
+
+/// Admin API key of gateways started with [`admin_config`]
+pub const ADMIN_KEY: &str = "test-admin-key";
+
+/// Spot market with a cent tick and 0.0001 steps, its assets named by the symbol
+pub fn spot(symbol: &str) -> Market {
+    let (base, quote) = symbol.split_once('/').expect("symbol is BASE/QUOTE");
+    Market {
+        symbol: symbol.to_string(),
+        base_asset: base.to_string(),
+        quote_asset: quote.to_string(),
+        price_tick: dec!(0.01),
+        quantity_step: dec!(0.0001),
+        min_order_size: dec!(0.0001),
+        max_price_deviation: 10.0,
+        trading_enabled: true,
+        kind: MarketKind::Spot,
+    }
+}
+
+/// Matching engine with a book for each market
+pub fn engine(markets: &[Market]) -> Arc<MatchingEngine> {
+    let matching_engine = Arc::new(MatchingEngine::new());
+    for market in markets {
+        matching_engine.register_market(market.symbol.clone());
+    }
+    matching_engine
+}
+
+/// In-memory services trading the given markets
+pub fn state_for(markets: Vec<Market>) -> AppState {
+    AppState::new(
+        engine(&markets),
+        Arc::new(AccountService::new()),
+        Arc::new(MarketDataService::new()),
+        markets,
+    )
+}
+
+/// In-memory services trading BTC/USD
+pub fn state() -> AppState {
+    state_for(vec![spot(MARKET)])
+}
+
+/// Default configuration with [`ADMIN_KEY`] as the admin API key
+pub fn admin_config() -> AppConfig {
+    AppConfig {
+        admin_api_key: Some(ADMIN_KEY.to_string()),
+        ..AppConfig::default()
+    }
+}
+
+/// Serve `router` on a local port, with client addresses, returning its address
+pub async fn serve(router: Router) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+    });
+    addr
+}
+
+/// Gateway router over shared state
+pub struct Gateway {
+    pub app: Router,
+    pub state: Arc<AppState>,
+}
+
+impl Gateway {
+    /// Route requests to `state` as configured by `config`
+    pub fn new(state: AppState, config: &AppConfig) -> Self {
+        let state = Arc::new(state);
+        Self {
+            app: api_router(state.clone(), config, Router::new()),
+            state,
+        }
+    }
+
+    /// Gateway over BTC/USD with the default configuration
+    pub fn start() -> Self {
+        Self::new(state(), &AppConfig::default())
+    }
+
+    /// Gateway over BTC/USD that answers [`ADMIN_KEY`] on the admin routes
+    pub fn start_admin() -> Self {
+        Self::new(state(), &admin_config())
+    }
+
+    /// Send a request, returning the status, headers and JSON body, `null` if not JSON
+    pub async fn call(&self, request: Request<Body>) -> (StatusCode, HeaderMap, Value) {
+        let response = self.app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, headers, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    /// Build a request with an API key and a JSON body, either optional
+    pub fn request(method: &str, uri: &str, key: Option<&str>, body: Option<Value>) -> Request<Body> {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(key) = key {
+            request = request.header("x-api-key", key);
+        }
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        request.body(body).unwrap()
+    }
+
+    /// Send a request with an API key and a JSON body, either optional
+    pub async fn send(&self, method: &str, uri: &str, key: Option<&str>, body: Option<Value>) -> (StatusCode, Value) {
+        let (status, _, body) = self.call(Self::request(method, uri, key, body)).await;
+        (status, body)
+    }
+
+    /// Send a request as if from the client at `client`, or with no client address
+    pub async fn send_from(&self, client: Option<&str>, method: &str, uri: &str, key: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let mut request = Self::request(method, uri, Some(key), body);
+        if let Some(client) = client {
+            let addr: SocketAddr = format!("{}:443", client).parse().unwrap();
+            request.extensions_mut().insert(ConnectInfo(addr));
+        }
+
+        let (status, _, body) = self.call(request).await;
+        (status, body)
+    }
+
+    /// Send a request with the admin API key
+    pub async fn admin(&self, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        self.send(method, uri, Some(ADMIN_KEY), body).await
+    }
+
+    /// Create an account, returning its ID and API key
+    pub async fn create_account(&self) -> (Uuid, String) {
+        let (status, body) = self.send("POST", "/accounts", None, Some(json!({}))).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        (
+            body["data"]["id"].as_str().unwrap().parse().unwrap(),
+            body["data"]["api_key"].as_str().unwrap().to_string(),
+        )
+    }
+
+    /// Deposit into an account
+    pub async fn fund(&self, account_id: Uuid, key: &str, asset: &str, amount: &str) {
+        let deposit = json!({ "asset": asset, "amount": amount });
+        let (status, body) = self.send("POST", &format!("/accounts/{}/deposit", account_id), Some(key), Some(deposit)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    /// Create an account holding `amount` of `asset`, returning its ID and API key
+    pub async fn account_with(&self, asset: &str, amount: &str) -> (Uuid, String) {
+        let (id, key) = self.create_account().await;
+        self.fund(id, &key, asset, amount).await;
+        (id, key)
+    }
+
+    /// Create an account holding 1000 USD and 1 BTC, returning its ID and API key
+    pub async fn trader(&self) -> (Uuid, String) {
+        let (id, key) = self.account_with("USD", "1000").await;
+        self.fund(id, &key, "BTC", "1").await;
+        (id, key)
+    }
+
+    /// Place a limit order on BTC/USD
+    pub async fn limit(&self, account_id: Uuid, key: &str, side: &str, price: &str, quantity: &str) -> (StatusCode, Value) {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": side,
+            "order_type": "Limit",
+            "price": price,
+            "quantity": quantity,
+        });
+        self.send("POST", "/orders", Some(key), Some(order)).await
+    }
+}
@@ -0,0 +1,72 @@
+//! REST response compression tests
+//!
+//! Checks that allowlisted responses above the size threshold are compressed
+//! for clients that accept it, and that compression can be turned off.
+
+mod common;
+
+use api_gateway::config::AppConfig;
+use axum::body::Body;
+use axum::http::{header, Response, StatusCode};
+use common::{spot, state_for, Gateway};
+use tower::ServiceExt;
+
+impl Gateway {
+    /// Gateway listing enough markets for `/markets` to pass the size threshold
+    fn with_markets(config: AppConfig) -> Self {
+        let markets = (0..50).map(|i| spot(&format!("COIN{}/USD", i))).collect();
+        Self::new(state_for(markets), &config)
+    }
+
+    async fn markets(&self, accept_encoding: &str) -> Response<Body> {
+        let mut request = Gateway::request("GET", "/markets", None, None);
+        request.headers_mut().insert(header::ACCEPT_ENCODING, accept_encoding.parse().unwrap());
+        let response = self.app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        response
+    }
+}
+
+fn content_encoding(response: &Response<Body>) -> Option<&str> {
+    response.headers().get(header::CONTENT_ENCODING).map(|value| value.to_str().unwrap())
+}
+
+#[tokio::test]
+async fn test_large_json_responses_are_compressed() {
+    let gateway = Gateway::with_markets(AppConfig::default());
+
+    let response = gateway.markets("gzip").await;
+    assert_eq!(content_encoding(&response), Some("gzip"));
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..2], &[0x1f, 0x8b]);
+
+    let response = gateway.markets("br").await;
+    assert_eq!(content_encoding(&response), Some("br"));
+
+    let response = gateway.markets("identity").await;
+    assert_eq!(content_encoding(&response), None);
+}
+
+#[tokio::test]
+async fn test_small_or_unlisted_responses_are_not_compressed() {
+    let gateway = Gateway::with_markets(AppConfig {
+        compression_min_bytes: u16::MAX,
+        ..AppConfig::default()
+    });
+    assert_eq!(content_encoding(&gateway.markets("gzip").await), None);
+
+    let gateway = Gateway::with_markets(AppConfig {
+        compression_content_types: vec!["text/csv".to_string()],
+        ..AppConfig::default()
+    });
+    assert_eq!(content_encoding(&gateway.markets("gzip").await), None);
+}
+
+#[tokio::test]
+async fn test_compression_can_be_disabled() {
+    let gateway = Gateway::with_markets(AppConfig {
+        compression_enabled: false,
+        ..AppConfig::default()
+    });
+    assert_eq!(content_encoding(&gateway.markets("gzip, br").await), None);
+}
@@ -0,0 +1,102 @@
+//! Conditional GET tests for market data endpoints
+//!
+//! Checks that markets, order book and candle responses carry validators and
+//! answer `304 Not Modified` until the underlying data changes.
+
+mod common;
+
+use ::common::decimal::dec;
+use ::common::model::order::Side;
+use ::common::model::trade::Trade;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use common::{Gateway, MARKET};
+use uuid::Uuid;
+
+impl Gateway {
+    /// Send a GET with conditional headers, returning the status and headers
+    async fn get(&self, uri: &str, conditional: &[(header::HeaderName, &str)]) -> (StatusCode, HeaderMap) {
+        let mut request = Gateway::request("GET", uri, None, None);
+        for (name, value) in conditional {
+            request.headers_mut().insert(name, HeaderValue::from_str(value).unwrap());
+        }
+        let (status, headers, _) = self.call(request).await;
+        (status, headers)
+    }
+}
+
+fn etag(headers: &HeaderMap) -> String {
+    headers[header::ETAG].to_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_markets_revalidate_with_etag() {
+    let gateway = Gateway::start();
+
+    let (status, headers) = gateway.get("/markets", &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let tag = etag(&headers);
+
+    let (status, headers) = gateway.get("/markets", &[(header::IF_NONE_MATCH, &tag)]).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert_eq!(etag(&headers), tag);
+    assert!(headers.contains_key(header::CACHE_CONTROL));
+
+    let (status, _) = gateway.get("/markets", &[(header::IF_NONE_MATCH, "\"stale\"")]).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_order_book_etag_follows_sequence() {
+    let gateway = Gateway::start();
+    let market_data = &gateway.state.market_data_service;
+    let uri = "/markets/BTC%2FUSD/order-book";
+
+    market_data.update_order_book(MARKET, vec![(dec!(100), dec!(1))], vec![]).await.unwrap();
+    let (status, headers) = gateway.get(uri, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let tag = etag(&headers);
+    let last_modified = headers[header::LAST_MODIFIED].to_str().unwrap().to_string();
+
+    let (status, _) = gateway.get(uri, &[(header::IF_NONE_MATCH, &tag)]).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+    let (status, _) = gateway.get(uri, &[(header::IF_MODIFIED_SINCE, &last_modified)]).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+    market_data.update_order_book(MARKET, vec![(dec!(101), dec!(1))], vec![]).await.unwrap();
+    let (status, headers) = gateway.get(uri, &[(header::IF_NONE_MATCH, &tag)]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(etag(&headers), tag);
+}
+
+#[tokio::test]
+async fn test_candles_etag_changes_with_new_trades() {
+    let gateway = Gateway::start();
+    let market_data = &gateway.state.market_data_service;
+    let uri = "/markets/BTC%2FUSD/candles?interval=1m";
+
+    let trade = || {
+        Trade::new(
+            MARKET.to_string(),
+            dec!(100),
+            dec!(1),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Side::Buy,
+        )
+    };
+
+    market_data.process_trade(&trade()).await.unwrap();
+    let (status, headers) = gateway.get(uri, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let tag = etag(&headers);
+
+    let (status, _) = gateway.get(uri, &[(header::IF_NONE_MATCH, &tag)]).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+    market_data.process_trade(&trade()).await.unwrap();
+    let (status, _) = gateway.get(uri, &[(header::IF_NONE_MATCH, &tag)]).await;
+    assert_eq!(status, StatusCode::OK);
+}
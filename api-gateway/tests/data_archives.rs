@@ -0,0 +1,129 @@
+//! Bulk data download tests
+//!
+//! Saves trades from yesterday and today to a gateway's market data history
+//! and downloads yesterday's trade and candle archives.
+
+mod common;
+
+use std::io::Read;
+
+use ::common::decimal::dec;
+use ::common::model::order::Side;
+use ::common::model::trade::Trade;
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::response::Response;
+use chrono::{Days, NaiveDate, Utc};
+use common::{Gateway, MARKET};
+use flate2::read::GzDecoder;
+use serde_json::Value;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+impl Gateway {
+    /// Save a trade executed at the given UTC day, hour and minute
+    async fn trade(&self, date: NaiveDate, hour: u32, minute: u32, price: rust_decimal::Decimal) -> Trade {
+        let mut trade = Trade::new(
+            MARKET.to_string(),
+            price,
+            dec!(0.5),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Side::Buy,
+        );
+        trade.created_at = date.and_hms_opt(hour, minute, 0).unwrap().and_utc();
+        self.state.market_data_service.process_trade(&trade).await.unwrap();
+        trade
+    }
+
+    async fn get(&self, uri: &str, etag: Option<&str>) -> Response {
+        let mut request = Request::builder().uri(uri);
+        if let Some(etag) = etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        self.app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    async fn csv(&self, uri: &str) -> Vec<String> {
+        let response = self.get(uri, None).await;
+        assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/gzip");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut csv = String::new();
+        GzDecoder::new(bytes.as_ref()).read_to_string(&mut csv).unwrap();
+        csv.lines().map(str::to_string).collect()
+    }
+}
+
+#[tokio::test]
+async fn test_daily_archives_are_downloadable() {
+    let gateway = Gateway::start();
+    let today = Utc::now().date_naive();
+    let yesterday = today - Days::new(1);
+
+    let first = gateway.trade(yesterday, 10, 0, dec!(100)).await;
+    gateway.trade(yesterday, 10, 0, dec!(104)).await;
+    gateway.trade(yesterday, 11, 30, dec!(98)).await;
+    gateway.trade(today, 0, 0, dec!(120)).await;
+
+    // Only yesterday's trades, oldest first
+    let rows = gateway.csv(&format!("/data/trades/BTC-USD/{}.csv.gz", yesterday)).await;
+    assert_eq!(rows[0], "id,timestamp,price,quantity,taker_side,is_buyer_maker");
+    assert_eq!(rows.len(), 4);
+    assert!(rows[1].starts_with(&format!("{},{}T10:00:00.000Z,100,0.5,buy,false", first.id, yesterday)));
+
+    let rows = gateway.csv(&format!("/data/candles/BTC-USD/1h/{}.csv.gz", yesterday)).await;
+    assert_eq!(rows[0], "open_time,close_time,open,high,low,close,volume,quote_volume,trades,taker_buy_volume,taker_buy_quote_volume");
+    assert_eq!(rows.len(), 3);
+    assert_eq!(
+        rows[1],
+        format!("{d}T10:00:00.000Z,{d}T11:00:00.000Z,100,104,100,104,1.0,102.0,2,1.0,102.0", d = yesterday)
+    );
+    let rows = gateway.csv(&format!("/data/candles/BTC-USD/1d/{}.csv.gz", yesterday)).await;
+    assert_eq!(rows.len(), 2);
+
+    // The manifest lists every archive of the generated day
+    let response = gateway.get("/data/manifest", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let manifest: Value = serde_json::from_slice(&bytes).unwrap();
+    let entries = manifest["data"].as_array().unwrap();
+    assert_eq!(entries.len(), 4);
+    let trades = entries.iter().find(|entry| entry["kind"] == "trades").unwrap();
+    assert_eq!(trades["market"], MARKET);
+    assert_eq!(trades["records"], 3);
+    assert_eq!(trades["path"], format!("/data/trades/BTC-USD/{}.csv.gz", yesterday));
+    assert!(trades.get("interval").is_none());
+
+    // Archives do not change, so clients revalidate with their hash
+    let response = gateway.get(trades["path"].as_str().unwrap(), None).await;
+    assert!(response.headers()[header::CACHE_CONTROL].to_str().unwrap().contains("immutable"));
+    let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+    assert_eq!(etag, format!("\"{}\"", trades["sha256"].as_str().unwrap()));
+    let response = gateway.get(trades["path"].as_str().unwrap(), Some(&etag)).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+}
+
+#[tokio::test]
+async fn test_unavailable_archives_are_not_found() {
+    let gateway = Gateway::start();
+    let today = Utc::now().date_naive();
+    let yesterday = today - Days::new(1);
+
+    for uri in [
+        format!("/data/trades/BTC-USD/{}.csv.gz", today),
+        format!("/data/trades/BTC-USD/{}.csv.gz", today - Days::new(400)),
+        format!("/data/trades/ETH-USD/{}.csv.gz", yesterday),
+        format!("/data/trades/BTC-USD/{}.csv", yesterday),
+        format!("/data/candles/BTC-USD/1w/{}.csv.gz", yesterday),
+    ] {
+        let response = gateway.get(&uri, None).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+
+    // A quiet day still has an archive, with only the header
+    let rows = gateway.csv(&format!("/data/trades/BTC-USD/{}.csv.gz", yesterday)).await;
+    assert_eq!(rows.len(), 1);
+}
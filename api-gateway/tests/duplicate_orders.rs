@@ -0,0 +1,151 @@
+//! Duplicate order detection tests
+//!
+//! Places the same order twice within an account's window and checks the
+//! second is rejected or answered with the first, that a distinct client
+//! order ID or an expired window lets it through, and that concurrent
+//! identical orders place only one.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use ::common::decimal::{dec, Quantity};
+use axum::http::StatusCode;
+use common::{Gateway, MARKET};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    /// Create an account holding USD, returning its ID and API key
+    async fn account(&self) -> (Uuid, String) {
+        self.account_with("USD", "10000").await
+    }
+
+    async fn detect(&self, account_id: Uuid, key: &str, window_ms: u64, action: &str) {
+        let settings = json!({ "window_ms": window_ms, "action": action });
+        let (status, body) = self.send("PUT", &format!("/accounts/{}/duplicate-orders", account_id), Some(key), Some(settings)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    /// Place a resting buy, returning the response
+    async fn buy(&self, account_id: Uuid, key: &str, client_order_id: Option<&str>) -> (StatusCode, Value) {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": "Buy",
+            "order_type": "Limit",
+            "price": "100",
+            "quantity": "1",
+            "client_order_id": client_order_id,
+        });
+        self.send("POST", "/orders", Some(key), Some(order)).await
+    }
+
+    /// Quantity bid on the book, 1 for each buy placed
+    fn resting_quantity(&self) -> Quantity {
+        let (bids, _) = self.state.matching_engine.get_market_depth(MARKET, 10).unwrap();
+        bids.iter().map(|(_, quantity)| *quantity).sum()
+    }
+}
+
+#[tokio::test]
+async fn test_detection_is_off_by_default() {
+    let gateway = Gateway::start();
+    let (account, key) = gateway.account().await;
+
+    let (_, settings) = gateway.send("GET", &format!("/accounts/{}/duplicate-orders", account), Some(&key), None).await;
+    assert_eq!(settings["data"], json!({ "window_ms": 0, "action": "reject" }));
+    assert_eq!(gateway.buy(account, &key, None).await.0, StatusCode::CREATED);
+    assert_eq!(gateway.buy(account, &key, None).await.0, StatusCode::CREATED);
+    assert_eq!(gateway.resting_quantity(), dec!(2));
+}
+
+#[tokio::test]
+async fn test_duplicate_is_rejected_within_the_window() {
+    let gateway = Gateway::start();
+    let (account, key) = gateway.account().await;
+    gateway.detect(account, &key, 1000, "reject").await;
+
+    let (status, first) = gateway.buy(account, &key, None).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, body) = gateway.buy(account, &key, None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(body.to_string().contains(first["data"]["order"]["id"].as_str().unwrap()), "{}", body);
+
+    // A distinct client order ID is a different order
+    assert_eq!(gateway.buy(account, &key, Some("second")).await.0, StatusCode::CREATED);
+    assert_eq!(gateway.buy(account, &key, Some("second")).await.0, StatusCode::CONFLICT);
+    assert_eq!(gateway.resting_quantity(), dec!(2));
+}
+
+#[tokio::test]
+async fn test_duplicate_is_answered_with_the_earlier_order() {
+    let gateway = Gateway::start();
+    let (account, key) = gateway.account().await;
+    gateway.detect(account, &key, 1000, "deduplicate").await;
+
+    let (status, first) = gateway.buy(account, &key, Some("a")).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, second) = gateway.buy(account, &key, Some("a")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(second["data"]["order"]["id"], first["data"]["order"]["id"]);
+    assert_eq!(second["data"]["order"]["client_order_id"], "a");
+    assert_eq!(gateway.resting_quantity(), dec!(1));
+}
+
+#[tokio::test]
+async fn test_identical_order_after_the_window_is_placed() {
+    let gateway = Gateway::start();
+    let (account, key) = gateway.account().await;
+    gateway.detect(account, &key, 50, "reject").await;
+
+    assert_eq!(gateway.buy(account, &key, None).await.0, StatusCode::CREATED);
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(gateway.buy(account, &key, None).await.0, StatusCode::CREATED);
+    assert_eq!(gateway.resting_quantity(), dec!(2));
+}
+
+#[tokio::test]
+async fn test_concurrent_identical_orders_place_one() {
+    let gateway = Arc::new(Gateway::start());
+    let (account, key) = gateway.account().await;
+    gateway.detect(account, &key, 1000, "reject").await;
+
+    let placements = (0..8).map(|_| {
+        let gateway = gateway.clone();
+        let key = key.clone();
+        tokio::spawn(async move { gateway.buy(account, &key, None).await.0 })
+    }).collect::<Vec<_>>();
+    let mut statuses = Vec::new();
+    for placement in placements {
+        statuses.push(placement.await.unwrap());
+    }
+
+    assert_eq!(statuses.iter().filter(|status| **status == StatusCode::CREATED).count(), 1);
+    assert_eq!(statuses.iter().filter(|status| **status == StatusCode::CONFLICT).count(), 7);
+    assert_eq!(gateway.resting_quantity(), dec!(1));
+}
+
+#[tokio::test]
+async fn test_refused_order_is_no_duplicate_and_windows_are_capped() {
+    let gateway = Gateway::start();
+    let (account, key) = gateway.account().await;
+    gateway.detect(account, &key, 1000, "reject").await;
+
+    // Too large to fund, so never placed
+    let order = json!({
+        "user_id": account,
+        "market": MARKET,
+        "side": "Buy",
+        "order_type": "Limit",
+        "price": "100",
+        "quantity": "1000",
+    });
+    assert!(!gateway.send("POST", "/orders", Some(&key), Some(order.clone())).await.0.is_success());
+    assert_ne!(gateway.send("POST", "/orders", Some(&key), Some(order)).await.0, StatusCode::CONFLICT);
+
+    let settings = json!({ "window_ms": 3_600_000, "action": "reject" });
+    let (status, _) = gateway.send("PUT", &format!("/accounts/{}/duplicate-orders", account), Some(&key), Some(settings)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
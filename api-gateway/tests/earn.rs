@@ -0,0 +1,169 @@
+//! Earn tests
+//!
+//! Opts assets in and out through the REST API and accrues interest at chosen
+//! times, checking that only available funds earn and that each accrual is
+//! credited once.
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use ::common::chaos::{Chaos, ChaosConfig, ChaosPoint, Fault};
+use ::common::decimal::dec;
+use account_service::AccountService;
+use api_gateway::earn::{Accrual, EarnConfig};
+use api_gateway::AppState;
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use common::{admin_config, engine, spot, state, ADMIN_KEY, Gateway, MARKET};
+use market_data::MarketDataService;
+use rust_decimal::Decimal;
+use serde_json::json;
+use uuid::Uuid;
+
+impl Gateway {
+    fn setup() -> Self {
+        Self::with_state(state())
+    }
+
+    /// Gateway whose account service fails the first `failures` deposit credits
+    fn failing_credits(failures: u64) -> Self {
+        let chaos = Arc::new(Chaos::new(ChaosConfig::default().with_fault(ChaosPoint::Deposit, Fault::fail_first(failures))));
+        Self::with_state(AppState::new(
+            engine(&[spot(MARKET)]),
+            Arc::new(AccountService::new().with_chaos(chaos)),
+            Arc::new(MarketDataService::new()),
+            vec![spot(MARKET)],
+        ))
+    }
+
+    fn with_state(state: AppState) -> Self {
+        // 36.5% a year is 0.1% a day
+        let state = state.with_earn(EarnConfig {
+            rates: BTreeMap::from([("USD".to_string(), dec!(0.365))]),
+            ..EarnConfig::default()
+        });
+        Self::new(state, &admin_config())
+    }
+
+    async fn account(&self) -> (Uuid, String) {
+        self.account_with("USD", "1000").await
+    }
+
+    /// Opt USD in, returning when the opt-in started
+    async fn subscribe(&self, account_id: Uuid, key: &str) -> DateTime<Utc> {
+        let (status, body) = self.send("POST", &format!("/accounts/{}/earn", account_id), Some(key), Some(json!({ "asset": "usd" }))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["data"]["subscribed_at"].as_str().unwrap().parse().unwrap()
+    }
+
+    async fn accrue(&self, now: DateTime<Utc>) -> Vec<Accrual> {
+        self.state.earn.accrue(&self.state.account_service, &self.state.settlement, now).await
+    }
+
+    async fn available(&self, account_id: Uuid, key: &str) -> Decimal {
+        let (_, body) = self.send("GET", &format!("/accounts/{}/balances", account_id), Some(key), None).await;
+        let balance = body["data"].as_array().unwrap().iter().find(|b| b["asset"] == "USD").unwrap().clone();
+        balance["available"].as_str().unwrap().parse::<Decimal>().unwrap().normalize()
+    }
+}
+
+#[tokio::test]
+async fn test_opted_in_available_balance_accrues_interest() {
+    let gateway = Gateway::setup();
+    let (account, key) = gateway.account().await;
+    let (idle, _) = gateway.account().await;
+
+    let (status, _) = gateway.send("POST", &format!("/accounts/{}/earn", account), Some(&key), Some(json!({ "asset": "BTC" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let start = gateway.subscribe(account, &key).await;
+
+    // Funds locked in the bid earn nothing
+    let bid = json!({
+        "user_id": account,
+        "market": MARKET,
+        "side": "Buy",
+        "order_type": "Limit",
+        "price": "100",
+        "quantity": "1",
+    });
+    let (status, _) = gateway.send("POST", "/orders", Some(&key), Some(bid)).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let accruals = gateway.accrue(start + Duration::days(1)).await;
+    assert_eq!(accruals.len(), 1);
+    assert_eq!(accruals[0].account_id, account);
+    assert_eq!(accruals[0].principal, dec!(900));
+    assert_eq!(accruals[0].amount, dec!(0.9));
+    assert!(accruals[0].credited);
+    assert_eq!(gateway.available(account, &key).await, dec!(900.9));
+    assert!(gateway.state.earn.accruals(idle, 10).is_empty());
+
+    // Interest is not paid twice for the same time
+    assert!(gateway.accrue(start + Duration::days(1)).await.is_empty());
+
+    // Credited interest compounds
+    let accruals = gateway.accrue(start + Duration::days(2)).await;
+    assert_eq!(accruals[0].amount, dec!(0.9009));
+
+    let (status, body) = gateway.send("GET", &format!("/accounts/{}/earn/accruals", account), Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let amounts: Vec<&str> = body["data"].as_array().unwrap().iter().map(|accrual| accrual["amount"].as_str().unwrap()).collect();
+    assert_eq!(amounts, ["0.9009", "0.9"]);
+
+    let (status, body) = gateway.send("DELETE", &format!("/accounts/{}/earn/USD", account), Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["accrued_until"].as_str().unwrap().parse::<DateTime<Utc>>().unwrap(), start + Duration::days(2));
+    assert!(gateway.accrue(start + Duration::days(3)).await.is_empty());
+    assert_eq!(gateway.available(account, &key).await, dec!(901.8009));
+}
+
+#[tokio::test]
+async fn test_interest_that_fails_to_credit_is_accrued_again() {
+    let gateway = Gateway::failing_credits(1);
+    let (account, key) = gateway.account().await;
+    let start = gateway.subscribe(account, &key).await;
+
+    let accruals = gateway.accrue(start + Duration::days(1)).await;
+    assert_eq!(accruals.len(), 1);
+    assert!(!accruals[0].credited);
+    assert_eq!(gateway.available(account, &key).await, dec!(1000));
+    assert!(gateway.state.earn.accruals(account, 10).is_empty());
+
+    // The next accrual covers the failed period as well
+    let accruals = gateway.accrue(start + Duration::days(2)).await;
+    assert_eq!(accruals[0].start, start);
+    assert_eq!(accruals[0].amount, dec!(2));
+    assert!(accruals[0].credited);
+    assert_eq!(gateway.available(account, &key).await, dec!(1002));
+    assert_eq!(gateway.state.earn.accruals(account, 10).len(), 1);
+}
+
+#[tokio::test]
+async fn test_opt_ins_are_listed_and_accrued_by_admins() {
+    let gateway = Gateway::setup();
+    let (account, key) = gateway.account().await;
+    let (other, other_key) = gateway.account().await;
+
+    gateway.subscribe(account, &key).await;
+    // Opting in twice keeps the original opt-in
+    let (_, body) = gateway.send("POST", &format!("/accounts/{}/earn", account), Some(&key), Some(json!({ "asset": "USD" }))).await;
+    let (status, list) = gateway.send("GET", &format!("/accounts/{}/earn", account), Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(list["data"], json!([body["data"]]));
+
+    let (status, _) = gateway.send("GET", &format!("/accounts/{}/earn", account), Some(&other_key), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = gateway.send("DELETE", &format!("/accounts/{}/earn/USD", other), Some(&other_key), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = gateway.send("POST", "/admin/earn/accruals", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = gateway.send("POST", "/admin/earn/accruals", Some(ADMIN_KEY), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"][0]["account_id"], account.to_string());
+
+    let actions: Vec<String> = gateway.state.audit_log.recent(None, 10).into_iter().map(|entry| entry.action).collect();
+    assert_eq!(actions, ["earn.accrued"]);
+}
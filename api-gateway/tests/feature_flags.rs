@@ -0,0 +1,81 @@
+//! Feature flag tests
+//!
+//! Toggles flags through the admin API and checks the engine and
+//! `/capabilities` see the new values without a restart.
+
+mod common;
+
+use std::sync::Arc;
+
+use ::common::flags::{FeatureFlags, BOOK_LEVEL_PRUNING, ENABLE_MARGIN};
+use account_service::AccountService;
+use api_gateway::AppState;
+use axum::http::StatusCode;
+use common::{admin_config, ADMIN_KEY, Gateway};
+use market_data::MarketDataService;
+use matching_engine::MatchingEngine;
+use serde_json::{json, Value};
+
+impl Gateway {
+    fn setup(flags: FeatureFlags) -> Self {
+        let state = AppState::new(
+            Arc::new(MatchingEngine::new().with_feature_flags(Arc::new(flags))),
+            Arc::new(AccountService::new()),
+            Arc::new(MarketDataService::new()),
+            Vec::new(),
+        );
+        Self::new(state, &admin_config())
+    }
+
+    async fn flag(&self, name: &str) -> Value {
+        let (status, body) = self.send("GET", "/admin/feature-flags", Some(ADMIN_KEY), None).await;
+        assert_eq!(status, StatusCode::OK);
+        body["data"].as_array().unwrap().iter().find(|flag| flag["name"] == name).unwrap().clone()
+    }
+}
+
+#[tokio::test]
+async fn test_flags_are_toggled_at_runtime() {
+    let gateway = Gateway::setup(FeatureFlags::new());
+    assert_eq!(gateway.flag(ENABLE_MARGIN).await["enabled"], false);
+    assert_eq!(gateway.flag(BOOK_LEVEL_PRUNING).await["default"], true);
+
+    let uri = format!("/admin/feature-flags/{}", ENABLE_MARGIN);
+    let (status, body) = gateway.send("PUT", &uri, Some(ADMIN_KEY), Some(json!({ "enabled": true }))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["enabled"], true);
+    assert!(gateway.state.matching_engine.feature_flags().is_enabled(ENABLE_MARGIN));
+
+    // Capabilities follow the toggle
+    let (_, body) = gateway.send("GET", "/capabilities", None, None).await;
+    assert_eq!(body["data"]["margin"], true);
+    let flags = body["data"]["features"]["flags"].as_array().unwrap();
+    assert!(flags.iter().any(|flag| flag["name"] == ENABLE_MARGIN && flag["enabled"] == true));
+
+    let (_, body) = gateway.send("GET", "/admin/audit", Some(ADMIN_KEY), None).await;
+    assert_eq!(body["data"][0]["action"], "feature_flag.set");
+    assert_eq!(body["data"][0]["details"]["previous"], false);
+}
+
+#[tokio::test]
+async fn test_flags_start_from_configured_overrides() {
+    let overrides = [(BOOK_LEVEL_PRUNING.to_string(), false)].into_iter().collect();
+    let gateway = Gateway::setup(FeatureFlags::new().with_overrides(&overrides).unwrap());
+    assert_eq!(gateway.flag(BOOK_LEVEL_PRUNING).await["enabled"], false);
+
+    let unknown = [("enable_teleport".to_string(), true)].into_iter().collect();
+    assert!(FeatureFlags::new().with_overrides(&unknown).is_err());
+}
+
+#[tokio::test]
+async fn test_only_operators_toggle_known_flags() {
+    let gateway = Gateway::setup(FeatureFlags::new());
+
+    let (status, _) = gateway.send("PUT", "/admin/feature-flags/enable_teleport", Some(ADMIN_KEY), Some(json!({ "enabled": true }))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let uri = format!("/admin/feature-flags/{}", ENABLE_MARGIN);
+    let (status, _) = gateway.send("PUT", &uri, None, Some(json!({ "enabled": true }))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(!gateway.state.matching_engine.feature_flags().is_enabled(ENABLE_MARGIN));
+}
@@ -0,0 +1,120 @@
+//! Perpetual funding tests
+//!
+//! Opens a long and a short position on a perpetual market, then settles
+//! funding through the admin endpoint and checks who paid whom.
+
+mod common;
+
+use ::common::decimal::dec;
+use ::common::model::market::{Market, MarketKind};
+use axum::http::StatusCode;
+use common::{admin_config, spot, state_for, ADMIN_KEY, Gateway};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+const PERPETUAL: &str = "BTC/USD";
+const SPOT: &str = "ETH/USD";
+
+impl Gateway {
+    fn setup() -> Self {
+        let markets = vec![Market { kind: MarketKind::Perpetual, ..spot(PERPETUAL) }, spot(SPOT)];
+        Self::new(state_for(markets), &admin_config())
+    }
+
+    /// Create an account holding USD and BTC, returning its ID and API key
+    async fn account(&self) -> (Uuid, String) {
+        let (id, key) = self.account_with("USD", "1000").await;
+        self.fund(id, &key, "BTC", "10").await;
+        (id, key)
+    }
+
+    async fn order(&self, account_id: Uuid, key: &str, side: &str, price: &str, quantity: &str) {
+        let order = json!({
+            "user_id": account_id,
+            "market": PERPETUAL,
+            "side": side,
+            "order_type": "Limit",
+            "price": price,
+            "quantity": quantity,
+        });
+        let (status, body) = self.send("POST", "/orders", Some(key), Some(order)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+    }
+
+    async fn usd(&self, account_id: Uuid, key: &str) -> Decimal {
+        let (_, body) = self.send("GET", &format!("/accounts/{}/balances", account_id), Some(key), None).await;
+        let balance = body["data"].as_array().unwrap().iter().find(|b| b["asset"] == "USD").unwrap().clone();
+        decimal(&balance["total"])
+    }
+}
+
+fn decimal(value: &Value) -> Decimal {
+    value.as_str().unwrap().parse::<Decimal>().unwrap().normalize()
+}
+
+#[tokio::test]
+async fn test_longs_pay_shorts_when_mark_is_above_index() {
+    let gateway = Gateway::setup();
+    let (long, long_key) = gateway.account().await;
+    let (short, short_key) = gateway.account().await;
+    let (maker, maker_key) = gateway.account().await;
+
+    // The long buys 2 from the short at 100
+    gateway.order(short, &short_key, "Sell", "100", "2").await;
+    gateway.order(long, &long_key, "Buy", "100", "2").await;
+    let (_, body) = gateway.send("GET", &format!("/accounts/{}/positions", long), Some(&long_key), None).await;
+    assert_eq!(decimal(&body["data"][0]["quantity"]), dec!(2));
+
+    // Against an index of 100 the best bid of 100.5 is the mark
+    gateway.order(maker, &maker_key, "Buy", "100.5", "1").await;
+    gateway.order(maker, &maker_key, "Sell", "101", "1").await;
+    let long_before = gateway.usd(long, &long_key).await;
+    let short_before = gateway.usd(short, &short_key).await;
+
+    // Without an index source there is nothing to fund towards
+    let uri = "/admin/markets/BTC%2FUSD/funding";
+    let (status, _) = gateway.send("POST", uri, Some(ADMIN_KEY), Some(json!({}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // A 0.5% premium over the index charges longs 0.5% of 2 x 100.5
+    let (status, body) = gateway.send("POST", uri, Some(ADMIN_KEY), Some(json!({ "index_price": "100" }))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(decimal(&body["data"]["rate"]), dec!(0.005));
+    assert_eq!(decimal(&body["data"]["mark_price"]), dec!(100.5));
+    assert_eq!(body["data"]["accounts"], 2);
+
+    assert_eq!(gateway.usd(long, &long_key).await, long_before - dec!(1.005));
+    assert_eq!(gateway.usd(short, &short_key).await, short_before + dec!(1.005));
+
+    let (_, body) = gateway.send("GET", &format!("/accounts/{}/funding", short), Some(&short_key), None).await;
+    let payments = body["data"].as_array().unwrap();
+    assert_eq!(payments.len(), 1);
+    assert_eq!(decimal(&payments[0]["position"]), dec!(-2));
+    assert_eq!(decimal(&payments[0]["amount"]), dec!(-1.005));
+
+    let (status, body) = gateway.send("GET", "/markets/BTC%2FUSD/funding", None, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_rate_is_capped_and_only_perpetuals_are_funded() {
+    let gateway = Gateway::setup();
+    let (long, long_key) = gateway.account().await;
+    let (short, short_key) = gateway.account().await;
+    gateway.order(short, &short_key, "Sell", "100", "1").await;
+    gateway.order(long, &long_key, "Buy", "100", "1").await;
+    gateway.order(short, &short_key, "Buy", "90", "1").await;
+    gateway.order(short, &short_key, "Sell", "92", "1").await;
+
+    // A best ask 8% below the index is capped at the default 0.75%, paid by shorts
+    let (status, body) = gateway.send("POST", "/admin/markets/BTC%2FUSD/funding", Some(ADMIN_KEY), Some(json!({ "index_price": "100" }))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(decimal(&body["data"]["rate"]), dec!(-0.0075));
+    let (_, body) = gateway.send("GET", &format!("/accounts/{}/funding", long), Some(&long_key), None).await;
+    assert_eq!(decimal(&body["data"][0]["amount"]), dec!(-0.69));
+
+    let (status, _) = gateway.send("POST", "/admin/markets/ETH%2FUSD/funding", Some(ADMIN_KEY), Some(json!({ "index_price": "100" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,237 @@
+//! GraphQL API tests
+//!
+//! Runs the gateway in-process with the GraphQL query endpoint under `/api/v1`
+//! and the subscription endpoint beside it, as the binaries mount them.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use api_gateway::config::AppConfig;
+use api_gateway::graphql::{graphql_ws_handler, schema};
+use api_gateway::routes::api_router;
+use axum::http::{header, StatusCode};
+use axum::{Extension, Router};
+use common::{serve, state, Gateway, MARKET};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+impl Gateway {
+    /// Gateway with the query endpoint under `/api/v1` and subscriptions beside it
+    fn setup() -> Self {
+        let state = Arc::new(state());
+        let app = Router::new()
+            .nest("/api/v1", api_router(state.clone(), &AppConfig::default(), Router::new()))
+            .merge(
+                Router::new()
+                    .route("/graphql/ws", axum::routing::get(graphql_ws_handler))
+                    .layer(Extension(schema(state.clone())))
+                    .with_state(state.clone()),
+            );
+        Self { app, state }
+    }
+
+    /// Run a query and return its full GraphQL response
+    async fn query(&self, query: &str, key: Option<&str>) -> Value {
+        let (status, body) = self.send("POST", "/api/v1/graphql", key, Some(json!({ "query": query }))).await;
+        assert_eq!(status, StatusCode::OK, "query failed: {}", body);
+        body
+    }
+
+    async fn account(&self, asset: &str, amount: &str) -> (Uuid, String) {
+        let (_, body) = self.send("POST", "/api/v1/accounts", None, Some(json!({}))).await;
+        let id: Uuid = body["data"]["id"].as_str().unwrap().parse().unwrap();
+        let key = body["data"]["api_key"].as_str().unwrap().to_string();
+        let deposit = json!({ "asset": asset, "amount": amount });
+        let (status, _) = self.send("POST", &format!("/api/v1/accounts/{}/deposit", id), Some(&key), Some(deposit)).await;
+        assert_eq!(status, StatusCode::OK);
+        (id, key)
+    }
+
+    async fn order(&self, account_id: Uuid, key: &str, side: &str, price: &str, quantity: &str) -> Uuid {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": side,
+            "order_type": "Limit",
+            "price": price,
+            "quantity": quantity,
+        });
+        let (status, body) = self.send("POST", "/api/v1/orders", Some(key), Some(order)).await;
+        assert_eq!(status, StatusCode::CREATED, "order failed: {}", body);
+        body["data"]["order"]["id"].as_str().unwrap().parse().unwrap()
+    }
+}
+
+fn error_code(response: &Value) -> &str {
+    response["errors"][0]["extensions"]["code"].as_str().unwrap_or_default()
+}
+
+#[tokio::test]
+async fn test_market_queries() {
+    let gateway = Gateway::setup();
+    let (buyer, buyer_key) = gateway.account("USD", "1000").await;
+    gateway.order(buyer, &buyer_key, "Buy", "100", "2.5").await;
+
+    let response = gateway
+        .query(
+            r#"{
+                markets { symbol baseAsset quoteAsset priceTick }
+                market(symbol: "BTC/USD") { tradingEnabled }
+                orderBook(market: "BTC/USD", depth: 5) { market bids { price quantity } asks { price } }
+                trades(market: "BTC/USD") { price }
+                candles(market: "BTC/USD", interval: MINUTE_5) { open }
+            }"#,
+            None,
+        )
+        .await;
+
+    assert!(response["errors"].is_null(), "unexpected errors: {}", response);
+    let data = &response["data"];
+    assert_eq!(data["markets"][0], json!({ "symbol": MARKET, "baseAsset": "BTC", "quoteAsset": "USD", "priceTick": "0.01" }));
+    assert_eq!(data["market"]["tradingEnabled"], json!(true));
+    assert_eq!(data["orderBook"]["bids"], json!([{ "price": "100", "quantity": "2.5" }]));
+    assert_eq!(data["orderBook"]["asks"], json!([]));
+    assert_eq!(data["trades"], json!([]));
+    assert_eq!(data["candles"], json!([]));
+
+    let unknown = gateway.query(r#"{ orderBook(market: "ETH/USD") { market } }"#, None).await;
+    assert_eq!(error_code(&unknown), "market_not_found");
+}
+
+#[tokio::test]
+async fn test_account_queries_require_the_owners_key() {
+    let gateway = Gateway::setup();
+    let (buyer, buyer_key) = gateway.account("USD", "1000").await;
+    let (_, other_key) = gateway.account("USD", "1").await;
+    let bid = gateway.order(buyer, &buyer_key, "Buy", "100", "2").await;
+
+    let query = format!(
+        r#"{{
+            account(id: "{}") {{
+                id
+                balances {{ asset available locked }}
+                reservations {{ orderId amount }}
+                openOrders {{ id side status remainingQuantity }}
+            }}
+            order(id: "{}") {{ price status }}
+        }}"#,
+        buyer, bid
+    );
+
+    let response = gateway.query(&query, Some(&buyer_key)).await;
+    assert!(response["errors"].is_null(), "unexpected errors: {}", response);
+    let account = &response["data"]["account"];
+    assert_eq!(account["id"], json!(buyer));
+    assert_eq!(account["balances"], json!([{ "asset": "USD", "available": "800", "locked": "200" }]));
+    assert_eq!(account["reservations"], json!([{ "orderId": bid, "amount": "200" }]));
+    assert_eq!(
+        account["openOrders"],
+        json!([{ "id": bid, "side": "BUY", "status": "NEW", "remainingQuantity": "2" }])
+    );
+    assert_eq!(response["data"]["order"], json!({ "price": "100", "status": "NEW" }));
+
+    let anonymous = gateway.query(&query, None).await;
+    assert_eq!(error_code(&anonymous), "unauthorized");
+    assert!(anonymous["data"].is_null());
+
+    let other = gateway.query(&query, Some(&other_key)).await;
+    assert_eq!(error_code(&other), "forbidden");
+
+    // An unknown key is refused outright, as on the REST API
+    let (status, _) = gateway
+        .send("POST", "/api/v1/graphql", Some("zk_unknown"), Some(json!({ "query": "{ markets { symbol } }" })))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_query_depth_is_limited() {
+    let gateway = Gateway::setup();
+
+    let response = gateway
+        .query("{ __schema { types { fields { type { ofType { ofType { ofType { ofType { name } } } } } } } } }", None)
+        .await;
+
+    assert!(response["data"].is_null());
+    assert!(response["errors"][0]["message"].as_str().unwrap().contains("nested too deep"), "{}", response);
+}
+
+#[tokio::test]
+async fn test_graphiql_is_served() {
+    let gateway = Gateway::setup();
+
+    let (status, headers, _) = gateway.call(Gateway::request("GET", "/api/v1/graphql", None, None)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/html"));
+}
+
+#[tokio::test]
+async fn test_order_book_subscription() {
+    let gateway = Gateway::setup();
+    let addr = serve(gateway.app.clone()).await;
+    let (seller, seller_key) = gateway.account("BTC", "5").await;
+
+    let mut request = format!("ws://{}/graphql/ws", addr).into_client_request().unwrap();
+    request.headers_mut().insert("sec-websocket-protocol", "graphql-transport-ws".parse().unwrap());
+    let (mut socket, response) = tokio_tungstenite::connect_async(request).await.unwrap();
+    assert_eq!(response.headers()["sec-websocket-protocol"], "graphql-transport-ws");
+
+    socket
+        .send(Message::Text(json!({ "type": "connection_init", "payload": { "apiKey": seller_key } }).to_string()))
+        .await
+        .unwrap();
+    let ack = tokio::time::timeout(TIMEOUT, socket.next()).await.unwrap().unwrap().unwrap();
+    assert_eq!(serde_json::from_str::<Value>(ack.to_text().unwrap()).unwrap()["type"], "connection_ack");
+
+    let subscribe = json!({
+        "id": "1",
+        "type": "subscribe",
+        "payload": { "query": r#"subscription { orderBook(market: "BTC/USD") { market sequence asks { price quantity } } }"# },
+    });
+    socket.send(Message::Text(subscribe.to_string())).await.unwrap();
+
+    // The subscription is registered once the operation starts; retry the
+    // order until an update arrives
+    let update = loop {
+        gateway.order(seller, &seller_key, "Sell", "101", "0.5").await;
+        match tokio::time::timeout(Duration::from_millis(500), socket.next()).await {
+            Ok(Some(Ok(message))) => break serde_json::from_str::<Value>(message.to_text().unwrap()).unwrap(),
+            Ok(other) => panic!("socket closed: {:?}", other),
+            Err(_) => continue,
+        }
+    };
+
+    assert_eq!(update["type"], "next");
+    assert_eq!(update["id"], "1");
+    let book = &update["payload"]["data"]["orderBook"];
+    assert_eq!(book["market"], MARKET);
+    assert_eq!(book["asks"][0]["price"], "101");
+    assert!(book["sequence"].as_u64().unwrap() >= 1);
+
+    socket
+        .send(Message::Text(json!({ "id": "1", "type": "complete" }).to_string()))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_subscription_endpoint_requires_a_graphql_protocol() {
+    let gateway = Gateway::setup();
+    let addr = serve(gateway.app.clone()).await;
+
+    let request = format!("ws://{}/graphql/ws", addr).into_client_request().unwrap();
+    let error = tokio_tungstenite::connect_async(request).await.unwrap_err();
+
+    match error {
+        tokio_tungstenite::tungstenite::Error::Http(response) => assert_eq!(response.status(), StatusCode::BAD_REQUEST),
+        other => panic!("unexpected error: {}", other),
+    }
+}
@@ -0,0 +1,59 @@
+//! Liquidity heatmap tests
+//!
+//! Samples a market's book and reads the heatmap back over REST, checking
+//! the window, bucket count and errors.
+
+mod common;
+
+use ::common::decimal::{Price, Quantity};
+use api_gateway::config::AppConfig;
+use axum::http::StatusCode;
+use common::{state_for, Gateway};
+
+impl Gateway {
+    fn setup() -> Self {
+        Self::new(state_for(Vec::new()), &AppConfig::default())
+    }
+}
+
+#[tokio::test]
+async fn test_heatmap_of_the_sampled_book() {
+    let gateway = Gateway::setup();
+    let level = |price: i64, quantity: i64| (Price::new(price, 0), Quantity::new(quantity, 0));
+    gateway.state.market_data_service
+        .update_order_book("BTC/USD", vec![level(99, 1), level(98, 2)], vec![level(101, 3)])
+        .await
+        .unwrap();
+    gateway.state.market_data_service.sample_heatmaps(50).await.unwrap();
+
+    let (status, body) = gateway.send("GET", "/markets/BTC%2FUSD/heatmap?window=15m&buckets=3", None, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let heatmap = &body["data"];
+    assert_eq!(heatmap["market"], "BTC/USD");
+    assert_eq!(heatmap["buckets"], 3);
+    assert_eq!(heatmap["price_low"], "98");
+    assert_eq!(heatmap["price_high"], "101");
+    assert_eq!(heatmap["bucket_size"], "1");
+    let columns = heatmap["columns"].as_array().unwrap();
+    assert_eq!(columns.len(), 1);
+    assert_eq!(columns[0]["bids"], serde_json::json!(["2", "1", "0"]));
+    assert_eq!(columns[0]["asks"], serde_json::json!(["0", "0", "3"]));
+
+    // A window that ended before the sample is empty
+    let (_, body) = gateway.send("GET", "/markets/BTC%2FUSD/heatmap?to=2020-01-01T00:00:00Z", None, None).await;
+    assert_eq!(body["data"]["buckets"], 100);
+    assert!(body["data"]["columns"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_heatmap_errors() {
+    let gateway = Gateway::setup();
+    gateway.state.market_data_service.update_order_book("BTC/USD", Vec::new(), Vec::new()).await.unwrap();
+
+    let (status, _) = gateway.send("GET", "/markets/ETH%2FUSD/heatmap", None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = gateway.send("GET", "/markets/BTC%2FUSD/heatmap?window=2h", None, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = gateway.send("GET", "/markets/BTC%2FUSD/heatmap?buckets=501", None, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
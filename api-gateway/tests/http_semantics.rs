@@ -0,0 +1,142 @@
+//! HTTP semantics tests
+//!
+//! Checks creations answer 201 with a `Location`, custodian payouts 202,
+//! conflicts 409, and that cancelling with the old `POST` verb still works
+//! but is marked deprecated.
+
+mod common;
+
+use std::sync::Arc;
+
+use account_service::settlement::MockSettlementAdapter;
+use account_service::AccountService;
+use api_gateway::config::AppConfig;
+use api_gateway::AppState;
+use axum::http::{header, StatusCode};
+use common::{engine, spot, Gateway, MARKET};
+use market_data::MarketDataService;
+use serde_json::json;
+use uuid::Uuid;
+
+impl Gateway {
+    /// Start a gateway paying out BTC withdrawals through a custodian
+    fn setup() -> Self {
+        let markets = vec![spot(MARKET)];
+        let account_service = AccountService::new()
+            .with_settlement_adapter(Arc::new(MockSettlementAdapter::for_assets(&["BTC"])));
+        let state = AppState::new(engine(&markets), Arc::new(account_service), Arc::new(MarketDataService::new()), markets);
+        Self::new(state, &AppConfig::default())
+    }
+
+    /// Create an account holding USD and BTC, returning its ID and API key
+    async fn account(&self) -> (Uuid, String) {
+        let (status, headers, body) = self.call(Self::request("POST", "/accounts", None, Some(json!({})))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let id: Uuid = body["data"]["id"].as_str().unwrap().parse().unwrap();
+        assert_eq!(headers[header::LOCATION], format!("/api/v1/accounts/{}", id));
+        let key = body["data"]["api_key"].as_str().unwrap().to_string();
+
+        for (asset, amount) in [("USD", "1000"), ("BTC", "1")] {
+            let deposit = json!({ "asset": asset, "amount": amount });
+            let (status, _, _) = self.call(Self::request("POST", &format!("/accounts/{}/deposit", id), Some(&key), Some(deposit))).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        (id, key)
+    }
+
+    /// Rest a bid, returning its ID
+    async fn bid(&self, account_id: Uuid, key: &str) -> String {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": "Buy",
+            "order_type": "Limit",
+            "price": "100",
+            "quantity": "1",
+        });
+        let (status, headers, body) = self.call(Self::request("POST", "/orders", Some(key), Some(order))).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        let id = body["data"]["order"]["id"].as_str().unwrap().to_string();
+        assert_eq!(headers[header::LOCATION], format!("/api/v1/orders/{}", id));
+        id
+    }
+}
+
+#[tokio::test]
+async fn test_orders_are_created_and_cancelled_with_delete() {
+    let gateway = Gateway::setup();
+    let (id, key) = gateway.account().await;
+    let order_id = gateway.bid(id, &key).await;
+
+    let (status, headers, _) = gateway.call(Gateway::request("GET", &format!("/orders/{}", order_id), Some(&key), None)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get("deprecation").is_none());
+
+    let (status, headers, body) = gateway.call(Gateway::request("DELETE", &format!("/orders/{}", order_id), Some(&key), None)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["status"], "Cancelled");
+    assert!(headers.get("deprecation").is_none());
+
+    let (status, _, _) = gateway.call(Gateway::request("DELETE", &format!("/orders/{}", order_id), Some(&key), None)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_cancelling_with_post_is_deprecated() {
+    let gateway = Gateway::setup();
+    let (id, key) = gateway.account().await;
+    let order_id = gateway.bid(id, &key).await;
+
+    let (status, headers, body) = gateway.call(Gateway::request("POST", &format!("/orders/{}", order_id), Some(&key), None)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["status"], "Cancelled");
+    assert_eq!(headers["deprecation"], "true");
+    assert!(headers[header::WARNING].to_str().unwrap().contains("DELETE"));
+}
+
+#[tokio::test]
+async fn test_created_resources_have_locations() {
+    let gateway = Gateway::setup();
+    let (id, key) = gateway.account().await;
+
+    let address = json!({ "asset": "BTC", "address": "bc1qexampleaddress" });
+    let uri = format!("/accounts/{}/withdrawal-addresses", id);
+    let (status, headers, body) = gateway.call(Gateway::request("POST", &uri, Some(&key), Some(address.clone()))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(headers[header::LOCATION], format!("/api/v1{}/{}", uri, body["data"]["id"].as_str().unwrap()));
+
+    // Whitelisting the same address again conflicts with the existing entry
+    let (status, _, body) = gateway.call(Gateway::request("POST", &uri, Some(&key), Some(address))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "conflict");
+
+    let uri = format!("/accounts/{}/webhooks", id);
+    let (status, headers, body) = gateway.call(Gateway::request("POST", &uri, Some(&key), Some(json!({ "url": "https://example.com/hook" })))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(headers[header::LOCATION], format!("/api/v1{}/{}", uri, body["data"]["id"].as_str().unwrap()));
+}
+
+#[tokio::test]
+async fn test_custodian_payouts_are_accepted() {
+    let gateway = Gateway::setup();
+    let (id, key) = gateway.account().await;
+    let uri = format!("/accounts/{}/withdraw", id);
+
+    // USD has no custodian, so the withdrawal is complete
+    let (status, _, body) = gateway.call(Gateway::request("POST", &uri, Some(&key), Some(json!({ "asset": "USD", "amount": "10" })))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, _, body) = gateway.call(Gateway::request("POST", &uri, Some(&key), Some(json!({ "asset": "BTC", "amount": "0.5" })))).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+    assert_eq!(body["data"]["available"], "0.5");
+
+    let close = format!("/accounts/{}/close", id);
+    for (asset, amount) in [("USD", "990"), ("BTC", "0.5")] {
+        gateway.call(Gateway::request("POST", &uri, Some(&key), Some(json!({ "asset": asset, "amount": amount })))).await;
+    }
+    let (status, _, _) = gateway.call(Gateway::request("POST", &close, Some(&key), None)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, body) = gateway.call(Gateway::request("POST", &close, Some(&key), None)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "conflict");
+}
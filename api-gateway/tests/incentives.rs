@@ -0,0 +1,129 @@
+//! Maker rebate tests
+//!
+//! Trades against resting quotes through the REST API, then reads the
+//! activity report and settles a rebate period through the admin API.
+
+mod common;
+
+use std::time::Duration;
+
+use ::common::decimal::dec;
+use ::common::model::market::Market;
+use api_gateway::incentives::IncentiveConfig;
+use axum::http::StatusCode;
+use common::{admin_config, spot, state_for, ADMIN_KEY, Gateway, MARKET};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    fn setup() -> Self {
+        let market = Market {
+            quantity_step: dec!(0.001),
+            min_order_size: dec!(1),
+            ..spot(MARKET)
+        };
+        let state = state_for(vec![market]).with_incentives(IncentiveConfig {
+            rebate_rate: dec!(0.001),
+            max_spread_bps: Some(500.0),
+            ..IncentiveConfig::default()
+        });
+        Self::new(state, &admin_config())
+    }
+
+    /// Create an account with deposits
+    async fn account(&self, deposits: &[(&str, &str)]) -> (Uuid, String) {
+        let (id, key) = self.create_account().await;
+        for (asset, amount) in deposits {
+            self.fund(id, &key, asset, amount).await;
+        }
+        (id, key)
+    }
+
+    async fn order(&self, account_id: Uuid, key: &str, side: &str, price: &str, quantity: &str) {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": side,
+            "order_type": "Limit",
+            "price": price,
+            "quantity": quantity,
+        });
+        let (status, body) = self.send("POST", "/orders", Some(key), Some(order)).await;
+        assert_eq!(status, StatusCode::CREATED, "order failed: {}", body);
+    }
+
+    async fn usd(&self, account_id: Uuid) -> String {
+        let balance = self.state.account_service.get_balance(account_id, "USD").await.unwrap().unwrap();
+        balance.available.normalize().to_string()
+    }
+}
+
+#[tokio::test]
+async fn test_report_and_settle_maker_rebates() {
+    let gateway = Gateway::setup();
+    let (tight, tight_key) = gateway.account(&[("USD", "1000"), ("BTC", "5")]).await;
+    let (wide, wide_key) = gateway.account(&[("BTC", "5")]).await;
+    let (taker, taker_key) = gateway.account(&[("USD", "1000")]).await;
+
+    gateway.order(tight, &tight_key, "Buy", "99", "1").await;
+    gateway.order(tight, &tight_key, "Sell", "101", "1").await;
+    gateway.order(wide, &wide_key, "Sell", "110", "1").await;
+    gateway.order(taker, &taker_key, "Buy", "110", "2").await;
+
+    // Events reach the quote tracker on a background thread
+    let mut report = Value::Null;
+    for _ in 0..50 {
+        let (status, body) = gateway.send("GET", "/admin/incentives", Some(ADMIN_KEY), None).await;
+        assert_eq!(status, StatusCode::OK);
+        report = body["data"].clone();
+        if report["activity"].as_array().unwrap().iter().filter(|a| a["maker_trades"] == json!(1)).count() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(report["rebate_rate"], "0.001");
+
+    let activity = report["activity"].as_array().unwrap();
+    let of = |account: Uuid| activity.iter().find(|a| a["account_id"] == json!(account)).unwrap().clone();
+    assert_eq!(of(tight)["maker_quote_volume"], "101");
+    assert_eq!(of(wide)["maker_quote_volume"], "110");
+    assert!(activity.iter().all(|a| a["account_id"] != json!(taker)));
+
+    let before = gateway.usd(tight).await;
+    let (status, body) = gateway.send("POST", "/admin/incentives/periods", Some(ADMIN_KEY), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let rebates = body["data"]["rebates"].as_array().unwrap();
+    let rebate = |account: Uuid| rebates.iter().find(|r| r["account_id"] == json!(account)).unwrap().clone();
+    let paid = rebate(tight);
+    assert_eq!(paid["asset"], "USD");
+    assert_eq!(paid["eligible"], json!(true));
+    assert_eq!(paid["amount"], "0.101");
+    assert_eq!(paid["credited"], json!(true));
+
+    // Quoting one side only leaves no spread to qualify with
+    let unpaid = rebate(wide);
+    assert_eq!(unpaid["eligible"], json!(false));
+    assert_eq!(unpaid["amount"], "0");
+    assert_eq!(unpaid["credited"], json!(false));
+
+    assert_eq!(before, "1002");
+    assert_eq!(gateway.usd(tight).await, "1002.101");
+
+    // The settled period is listed, the new one starts empty and the settlement is audited
+    let (_, body) = gateway.send("GET", "/admin/incentives/periods", Some(ADMIN_KEY), None).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    let (_, body) = gateway.send("GET", "/admin/incentives", Some(ADMIN_KEY), None).await;
+    assert!(body["data"]["activity"].as_array().unwrap().iter().all(|a| a["maker_trades"] == json!(0)));
+    assert!(gateway.state.audit_log.recent(None, 10).iter().any(|entry| entry.action == "incentives.settled"));
+}
+
+#[tokio::test]
+async fn test_incentive_endpoints_require_the_admin_key() {
+    let gateway = Gateway::setup();
+
+    let (status, _) = gateway.send("GET", "/admin/incentives", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = gateway.send("POST", "/admin/incentives/periods", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,163 @@
+//! Index price tests
+//!
+//! Composes indices from stub and file sources, checking the median, that
+//! stale and failing sources drop out, and that perpetual funding settles
+//! against the index without being given one.
+
+mod common;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use ::common::decimal::{dec, Price};
+use ::common::error::{Error, Result};
+use ::common::model::market::{Market, MarketKind};
+use api_gateway::index_price::{FilePriceFetcher, IndexPriceConfig, IndexPriceService, PriceFetcher};
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use common::{admin_config, spot, state_for, Gateway, MARKET};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Source answering with whatever prices it was last given, or failing without any
+struct StubFetcher {
+    name: String,
+    prices: Mutex<Option<HashMap<String, Price>>>,
+}
+
+impl StubFetcher {
+    fn new(name: &str, prices: &[(&str, Price)]) -> Arc<Self> {
+        let fetcher = Arc::new(Self { name: name.to_string(), prices: Mutex::new(None) });
+        fetcher.set(prices);
+        fetcher
+    }
+
+    fn set(&self, prices: &[(&str, Price)]) {
+        let prices = prices.iter().map(|(asset, price)| (asset.to_string(), *price)).collect();
+        *self.prices.lock().unwrap() = Some(prices);
+    }
+
+    fn fail(&self) {
+        *self.prices.lock().unwrap() = None;
+    }
+}
+
+#[async_trait]
+impl PriceFetcher for StubFetcher {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, Price>> {
+        self.prices.lock().unwrap().clone().ok_or_else(|| Error::Internal("source down".to_string()))
+    }
+}
+
+#[tokio::test]
+async fn test_index_is_the_median_of_fresh_quotes() {
+    let path = std::env::temp_dir().join(format!("index-prices-{}.json", Uuid::new_v4()));
+    std::fs::write(&path, r#"{"btc": "100.5", "ETH": 10}"#).unwrap();
+
+    let alpha = StubFetcher::new("alpha", &[("BTC", dec!(100)), ("ETH", dec!(11))]);
+    let beta = StubFetcher::new("beta", &[("BTC", dec!(250))]);
+    let fetchers: Vec<Arc<dyn PriceFetcher>> = vec![
+        alpha.clone(),
+        beta.clone(),
+        Arc::new(FilePriceFetcher::new("file", &path)),
+    ];
+    let service = IndexPriceService::new(IndexPriceConfig::default(), fetchers);
+
+    let start = Utc::now();
+    service.poll(start).await;
+    std::fs::remove_file(&path).unwrap();
+
+    // An outlier moves the median less than the mean
+    let btc = service.index("BTC", start).unwrap();
+    assert_eq!(btc.price, Some(dec!(100.5)));
+    assert_eq!(btc.sources.len(), 3);
+    assert_eq!(service.index("eth", start).unwrap().price, Some(dec!(10.5)));
+    assert_eq!(service.market_price("ETH", "BTC", start), Some(dec!(10.5) / dec!(100.5)));
+    assert_eq!(service.market_price("BTC", "USD", start), Some(dec!(100.5)));
+
+    // The file is gone and beta is down, so only alpha's quote is refreshed
+    beta.fail();
+    alpha.set(&[("BTC", dec!(101))]);
+    let later = start + Duration::seconds(45);
+    service.poll(later).await;
+    let btc = service.index("BTC", later + Duration::seconds(30)).unwrap();
+    assert_eq!(btc.price, Some(dec!(101)));
+    assert_eq!(btc.sources.iter().filter(|quote| quote.stale).count(), 2);
+
+    // Once every quote is stale there is no index
+    let btc = service.index("BTC", later + Duration::seconds(61)).unwrap();
+    assert!(btc.stale);
+    assert_eq!(btc.price, None);
+    assert_eq!(service.market_price("BTC", "USD", later + Duration::seconds(61)), None);
+}
+
+#[tokio::test]
+async fn test_index_needs_the_minimum_number_of_sources() {
+    let config = IndexPriceConfig { min_sources: 2, ..IndexPriceConfig::default() };
+    let service = IndexPriceService::new(config, Vec::new());
+    let now = Utc::now();
+
+    service.record("alpha", "BTC", dec!(100), now);
+    assert_eq!(service.index("BTC", now).unwrap().price, None);
+    service.record("beta", "BTC", dec!(102), now);
+    assert_eq!(service.index("BTC", now).unwrap().price, Some(dec!(101)));
+}
+
+#[tokio::test]
+async fn test_funding_settles_against_the_index() {
+    let source = StubFetcher::new("alpha", &[("BTC", dec!(100))]);
+    let perpetual = Market { kind: MarketKind::Perpetual, ..spot(MARKET) };
+    let state = state_for(vec![perpetual]).with_index_prices(IndexPriceConfig::default(), vec![source.clone()]);
+    let gateway = Gateway::new(state, &admin_config());
+
+    // A long and a short, with quotes well above the index
+    let mut accounts = Vec::new();
+    for _ in 0..2 {
+        let (id, key) = gateway.account_with("USD", "1000").await;
+        gateway.fund(id, &key, "BTC", "10").await;
+        accounts.push((id, key));
+    }
+    let orders = [(1, "Sell", "100.4", "1"), (0, "Buy", "100.4", "1"), (1, "Buy", "100.4", "1"), (1, "Sell", "120", "1")];
+    for (account, side, price, quantity) in orders {
+        let (id, key) = &accounts[account];
+        let (status, body) = gateway.limit(*id, key, side, price, quantity).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+    }
+
+    // Nothing polled yet
+    let uri = "/admin/markets/BTC%2FUSD/funding";
+    let (status, _) = gateway.admin("POST", uri, Some(json!({}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = gateway.send("GET", "/index-prices/BTC", None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    gateway.state.index_prices.poll(Utc::now()).await;
+    let (status, body) = gateway.send("GET", "/index-prices", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"][0]["asset"], "BTC");
+    assert_eq!(body["data"][0]["quote_asset"], "USD");
+    assert_eq!(body["data"][0]["stale"], false);
+    assert_eq!(body["data"][0]["sources"][0]["source"], "alpha");
+
+    // The mark is the best bid, the book price closest to the index
+    let (status, body) = gateway.admin("POST", uri, Some(json!({}))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let decimal = |value: &Value| value.as_str().unwrap().parse::<Decimal>().unwrap();
+    assert_eq!(decimal(&body["data"]["index_price"]), dec!(100));
+    assert_eq!(decimal(&body["data"]["mark_price"]), dec!(100.4));
+    assert_eq!(decimal(&body["data"]["rate"]), dec!(0.004));
+
+    // A stale index is no index
+    gateway.state.index_prices.record("alpha", "BTC", dec!(100), Utc::now() - Duration::minutes(5));
+    let (status, body) = gateway.send("GET", "/index-prices/BTC", None, None).await;
+    assert_eq!(body["data"]["stale"], true);
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = gateway.admin("POST", uri, Some(json!({}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
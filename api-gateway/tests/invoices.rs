@@ -0,0 +1,158 @@
+//! Fee invoice tests
+//!
+//! Trades with fees, runs the monthly invoicing job and checks the invoices
+//! each account sees, their numbering, and marking them paid.
+
+mod common;
+
+use std::sync::Arc;
+
+use ::common::decimal::dec;
+use ::common::model::fee::FeeSchedule;
+use account_service::AccountService;
+use api_gateway::AppState;
+use axum::http::StatusCode;
+use chrono::Utc;
+use common::{admin_config, spot, Gateway, MARKET};
+use market_data::MarketDataService;
+use matching_engine::MatchingEngine;
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    /// Gateway charging 10 bps to makers and 20 bps to takers
+    fn setup() -> Self {
+        let engine = MatchingEngine::with_fee_schedule(FeeSchedule::new(dec!(0.001), dec!(0.002)).unwrap());
+        engine.register_market(MARKET.to_string());
+        let state = AppState::new(
+            Arc::new(engine),
+            Arc::new(AccountService::new()),
+            Arc::new(MarketDataService::new()),
+            vec![spot(MARKET)],
+        );
+        Self::new(state, &admin_config())
+    }
+
+    async fn invoice_month(&self) -> Value {
+        let date = Utc::now().date_naive().to_string();
+        let (status, body) = self.admin("POST", "/admin/report-jobs/fee_invoices/runs", Some(json!({ "date": date }))).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        assert_eq!(body["data"]["status"], "succeeded", "{}", body);
+        body["data"].clone()
+    }
+
+    async fn invoices(&self, account_id: Uuid, key: &str) -> Vec<Value> {
+        let (status, body) = self.send("GET", &format!("/accounts/{}/invoices", account_id), Some(key), None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["data"].as_array().unwrap().clone()
+    }
+}
+
+/// Decimal string as a number, whatever its scale
+fn amount(value: &Value) -> Decimal {
+    value.as_str().unwrap().parse().unwrap()
+}
+
+/// An invoice's line for one asset
+fn line<'a>(invoice: &'a Value, asset: &str) -> &'a Value {
+    invoice["lines"].as_array().unwrap().iter().find(|line| line["asset"] == asset).unwrap()
+}
+
+#[tokio::test]
+async fn test_monthly_invoices_list_fees_per_asset() {
+    let gateway = Gateway::setup();
+    let (seller, seller_key) = gateway.trader().await;
+    let (buyer, buyer_key) = gateway.trader().await;
+    let (idle, idle_key) = gateway.trader().await;
+
+    assert_eq!(gateway.limit(seller, &seller_key, "Sell", "100", "0.5").await.0, StatusCode::CREATED);
+    assert_eq!(gateway.limit(buyer, &buyer_key, "Buy", "100", "0.5").await.0, StatusCode::CREATED);
+    let rebate = json!({ "asset": "USD", "amount": "0.01", "reason_code": "fee_overcharge" });
+    let (status, _) = gateway.admin("POST", &format!("/admin/accounts/{}/fee-rebates", seller), Some(rebate)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Without a report destination the invoices are still issued
+    let run = gateway.invoice_month().await;
+    // Both accounts traded both assets
+    assert_eq!(run["records"], 4);
+    assert_eq!(run["files"], json!([]));
+
+    let month = Utc::now().format("%Y-%m").to_string();
+    let invoices = gateway.invoices(seller, &seller_key).await;
+    assert_eq!(invoices.len(), 1);
+    let invoice = &invoices[0];
+    assert_eq!(invoice["account_id"], seller.to_string());
+    assert_eq!(invoice["period"], month);
+    assert_eq!(invoice["period_start"], format!("{}-01", month));
+    assert_eq!(invoice["status"], "issued");
+    assert!(invoice["paid_at"].is_null());
+    // The maker pays 10 bps of the 50 USD it received, and nothing on the BTC it gave
+    let lines = invoice["lines"].as_array().unwrap();
+    assert_eq!(lines.iter().map(|line| line["asset"].as_str().unwrap()).collect::<Vec<_>>(), ["BTC", "USD"]);
+    let usd = line(invoice, "USD");
+    assert_eq!(usd["trades"], 1);
+    assert_eq!(amount(&usd["fees"]), dec!(0.05));
+    assert_eq!(amount(&usd["rebates"]), dec!(0.01));
+    assert_eq!(amount(&usd["net"]), dec!(0.04));
+    assert_eq!(amount(&line(invoice, "BTC")["net"]), Decimal::ZERO);
+
+    // The taker pays 20 bps of the 0.5 BTC it received
+    let buyer_invoices = gateway.invoices(buyer, &buyer_key).await;
+    assert_eq!(amount(&line(&buyer_invoices[0], "BTC")["net"]), dec!(0.001));
+
+    // Numbered in order of issue within the month
+    let mut numbers = vec![invoice["number"].as_str().unwrap(), buyer_invoices[0]["number"].as_str().unwrap()];
+    numbers.sort();
+    assert_eq!(numbers, [format!("INV-{}-000001", month), format!("INV-{}-000002", month)]);
+
+    // Accounts that did not trade are not invoiced, and only see their own invoices
+    assert!(gateway.invoices(idle, &idle_key).await.is_empty());
+    let (status, _) = gateway.send("GET", &format!("/accounts/{}/invoices", seller), Some(&idle_key), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_invoices_are_marked_paid_once_and_kept_by_later_runs() {
+    let gateway = Gateway::setup();
+    let (seller, seller_key) = gateway.trader().await;
+    let (buyer, buyer_key) = gateway.trader().await;
+    assert_eq!(gateway.limit(seller, &seller_key, "Sell", "100", "0.25").await.0, StatusCode::CREATED);
+    assert_eq!(gateway.limit(buyer, &buyer_key, "Buy", "100", "0.25").await.0, StatusCode::CREATED);
+    gateway.invoice_month().await;
+    let invoice = gateway.invoices(seller, &seller_key).await[0].clone();
+    let id = invoice["id"].as_str().unwrap();
+
+    // Running the job again updates the same invoice
+    assert_eq!(gateway.limit(seller, &seller_key, "Sell", "100", "0.25").await.0, StatusCode::CREATED);
+    assert_eq!(gateway.limit(buyer, &buyer_key, "Buy", "100", "0.25").await.0, StatusCode::CREATED);
+    gateway.invoice_month().await;
+    let invoices = gateway.invoices(seller, &seller_key).await;
+    assert_eq!(invoices.len(), 1);
+    assert_eq!(invoices[0]["number"], invoice["number"]);
+    assert_eq!(line(&invoices[0], "USD")["trades"], 2);
+
+    let paid = format!("/admin/invoices/{}/paid", id);
+    assert_eq!(gateway.send("POST", &paid, Some(&seller_key), None).await.0, StatusCode::UNAUTHORIZED);
+    let (status, body) = gateway.admin("POST", &paid, Some(json!({ "reference": "wire-0042" }))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["status"], "paid");
+    assert_eq!(body["data"]["payment_reference"], "wire-0042");
+    assert!(body["data"]["paid_at"].is_string());
+
+    assert_eq!(gateway.admin("POST", &paid, None).await.0, StatusCode::CONFLICT);
+    let unknown = format!("/admin/invoices/{}/paid", Uuid::new_v4());
+    assert_eq!(gateway.admin("POST", &unknown, None).await.0, StatusCode::NOT_FOUND);
+
+    let (_, body) = gateway.admin("GET", "/admin/audit", None).await;
+    assert_eq!(body["data"][0]["action"], "invoice.paid");
+    assert_eq!(body["data"][0]["details"]["reference"], "wire-0042");
+
+    // A paid invoice is not changed by later runs
+    assert_eq!(gateway.limit(seller, &seller_key, "Sell", "100", "0.25").await.0, StatusCode::CREATED);
+    assert_eq!(gateway.limit(buyer, &buyer_key, "Buy", "100", "0.25").await.0, StatusCode::CREATED);
+    gateway.invoice_month().await;
+    let invoices = gateway.invoices(seller, &seller_key).await;
+    assert_eq!(invoices[0]["status"], "paid");
+    assert_eq!(line(&invoices[0], "USD")["trades"], 2);
+}
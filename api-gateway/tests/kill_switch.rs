@@ -0,0 +1,317 @@
+//! Kill switch and admin API tests
+//!
+//! Drives the gateway router in-process: admin and self-service kill switches,
+//! the effects on orders, reservations and withdrawals, the audit trail,
+//! surveillance alerts, force-released reservations and withdrawal address
+//! whitelists.
+
+mod common;
+
+use ::common::decimal::dec;
+use ::common::model::order::{Order, Side, TimeInForce};
+use api_gateway::ws::message::AccountEvent;
+use axum::http::StatusCode;
+use common::{ADMIN_KEY, Gateway, MARKET};
+use market_data::channel::Topic;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    /// Create an account funded with USD, returning its ID and API key
+    async fn funded_account(&self) -> (Uuid, String) {
+        self.account_with("USD", "10000").await
+    }
+
+    async fn place_bid(&self, account_id: Uuid, key: &str) -> StatusCode {
+        self.limit(account_id, key, "Buy", "100", "1").await.0
+    }
+
+    async fn usd_balance(&self, account_id: Uuid, key: &str) -> Value {
+        let (_, body) = self.send("GET", &format!("/accounts/{}/balances", account_id), Some(key), None).await;
+        body["data"].as_array().unwrap().iter().find(|b| b["asset"] == "USD").unwrap().clone()
+    }
+}
+
+#[tokio::test]
+async fn test_admin_routes_require_admin_key() {
+    let gateway = Gateway::start_admin();
+    let (account_id, key) = gateway.funded_account().await;
+    let uri = format!("/admin/accounts/{}/kill-switch", account_id);
+
+    let (status, _) = gateway.send("POST", &uri, None, Some(json!({}))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // An account's own key is not an admin key
+    let (status, _) = gateway.send("POST", &uri, Some(&key), Some(json!({}))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let disabled = Gateway::start();
+    let (status, _) = disabled.send("GET", "/admin/audit", Some(ADMIN_KEY), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_admin_kill_switch_blocks_cancels_and_freezes() {
+    let gateway = Gateway::start_admin();
+    let (account_id, key) = gateway.funded_account().await;
+
+    assert_eq!(gateway.place_bid(account_id, &key).await, StatusCode::CREATED);
+    assert_eq!(gateway.place_bid(account_id, &key).await, StatusCode::CREATED);
+    assert_eq!(gateway.usd_balance(account_id, &key).await["locked"], "200");
+
+    let events = gateway
+        .state
+        .market_data_service
+        .channel()
+        .subscribe::<AccountEvent>(Topic::Account(account_id))
+        .await;
+
+    let (status, body) = gateway
+        .send(
+            "POST",
+            &format!("/admin/accounts/{}/kill-switch", account_id),
+            Some(ADMIN_KEY),
+            Some(json!({ "freeze_withdrawals": true, "reason": "runaway algo" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["orders_blocked"], true);
+    assert_eq!(body["data"]["withdrawals_frozen"], true);
+    assert_eq!(body["data"]["cancelled_orders"].as_array().unwrap().len(), 2);
+
+    // Reservations are released and nothing new gets through
+    assert_eq!(gateway.usd_balance(account_id, &key).await["locked"], "0");
+    assert_eq!(gateway.place_bid(account_id, &key).await, StatusCode::FORBIDDEN);
+    assert_eq!(gateway.usd_balance(account_id, &key).await["locked"], "0");
+
+    let (status, _) = gateway
+        .send("POST", &format!("/accounts/{}/withdraw", account_id), Some(&key), Some(json!({ "asset": "USD", "amount": "1" })))
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // The account is notified privately
+    let event = events.try_recv().expect("kill switch event");
+    match event.downcast_ref::<AccountEvent>() {
+        Some(AccountEvent::KillSwitchEngaged { cancelled_orders, withdrawals_frozen, .. }) => {
+            assert_eq!(cancelled_orders.len(), 2);
+            assert!(withdrawals_frozen);
+        }
+        other => panic!("unexpected event: {:?}", other),
+    }
+
+    // Releasing restores trading and withdrawals
+    let (status, _) = gateway
+        .send("DELETE", &format!("/admin/accounts/{}/kill-switch", account_id), Some(ADMIN_KEY), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(gateway.place_bid(account_id, &key).await, StatusCode::CREATED);
+
+    let (_, body) = gateway
+        .send("GET", &format!("/admin/audit?account_id={}", account_id), Some(ADMIN_KEY), None)
+        .await;
+    let actions: Vec<&str> = body["data"].as_array().unwrap().iter().map(|e| e["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, ["kill_switch.released", "kill_switch.engaged"]);
+    assert_eq!(body["data"][1]["details"]["reason"], "runaway algo");
+}
+
+#[tokio::test]
+async fn test_stuck_reservations_can_be_force_released() {
+    let gateway = Gateway::start_admin();
+    let (account_id, key) = gateway.funded_account().await;
+
+    // One open order, and funds left reserved for an order the engine never saw
+    assert_eq!(gateway.place_bid(account_id, &key).await, StatusCode::CREATED);
+    let stuck = Order::new_limit(account_id, MARKET.to_string(), Side::Buy, dec!(50), dec!(2), TimeInForce::GTC);
+    gateway.state.account_service.reserve_for_order(&stuck).await.unwrap();
+    assert_eq!(gateway.usd_balance(account_id, &key).await["locked"], "200");
+
+    let (status, body) = gateway.send("GET", &format!("/accounts/{}/reservations", account_id), Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let reservations = body["data"].as_array().unwrap();
+    assert_eq!(reservations.len(), 2);
+    assert_eq!(reservations[1]["order_id"], stuck.id.to_string());
+    assert_eq!(reservations[1]["asset"], "USD");
+    assert_eq!(reservations[1]["amount"], "100");
+    let open_order_id = reservations[0]["order_id"].as_str().unwrap().to_string();
+
+    let (_, body) = gateway.send("GET", &format!("/admin/accounts/{}/reservations", account_id), Some(ADMIN_KEY), None).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+
+    let release = |order_id: String| format!("/admin/accounts/{}/reservations/{}/release", account_id, order_id);
+    let reason = json!({ "reason": "ticket 42" });
+
+    // Open orders keep their funds, and every release needs a reason
+    let (status, _) = gateway.send("POST", &release(open_order_id), Some(ADMIN_KEY), Some(reason.clone())).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = gateway.send("POST", &release(stuck.id.to_string()), Some(ADMIN_KEY), Some(json!({ "reason": " " }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = gateway.send("POST", &release(stuck.id.to_string()), Some(&key), Some(reason.clone())).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = gateway.send("POST", &release(stuck.id.to_string()), Some(ADMIN_KEY), Some(reason.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["amount"], "100");
+    assert_eq!(gateway.usd_balance(account_id, &key).await["locked"], "100");
+    let (status, _) = gateway.send("POST", &release(stuck.id.to_string()), Some(ADMIN_KEY), Some(reason)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, body) = gateway
+        .send("GET", &format!("/admin/audit?account_id={}", account_id), Some(ADMIN_KEY), None)
+        .await;
+    assert_eq!(body["data"][0]["action"], "reservation.force_released");
+    assert_eq!(body["data"][0]["details"]["order_id"], stuck.id.to_string());
+    assert_eq!(body["data"][0]["details"]["reason"], "ticket 42");
+}
+
+#[tokio::test]
+async fn test_self_service_kill_switch() {
+    let gateway = Gateway::start_admin();
+    let (account_id, key) = gateway.funded_account().await;
+    let (other_id, _) = gateway.funded_account().await;
+
+    let (status, _) = gateway
+        .send("POST", &format!("/accounts/{}/kill-switch", other_id), Some(&key), Some(json!({})))
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    assert_eq!(gateway.place_bid(account_id, &key).await, StatusCode::CREATED);
+    let (status, body) = gateway
+        .send("POST", &format!("/accounts/{}/kill-switch", account_id), Some(&key), Some(json!({})))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["cancelled_orders"].as_array().unwrap().len(), 1);
+    assert_eq!(body["data"]["withdrawals_frozen"], false);
+    assert_eq!(gateway.place_bid(account_id, &key).await, StatusCode::FORBIDDEN);
+
+    let (_, body) = gateway.send("GET", "/admin/audit", Some(ADMIN_KEY), None).await;
+    assert_eq!(body["data"][0]["actor"], format!("account:{}", account_id));
+}
+
+#[tokio::test]
+async fn test_surveillance_alerts_are_queryable() {
+    let gateway = Gateway::start_admin();
+    let (account_id, key) = gateway.funded_account().await;
+    gateway
+        .send("POST", &format!("/accounts/{}/deposit", account_id), Some(&key), Some(json!({ "asset": "BTC", "amount": "1" })))
+        .await;
+
+    // Trade with ourselves
+    assert_eq!(gateway.place_bid(account_id, &key).await, StatusCode::CREATED);
+    let ask = json!({
+        "user_id": account_id,
+        "market": MARKET,
+        "side": "Sell",
+        "order_type": "Limit",
+        "price": "100",
+        "quantity": "1",
+    });
+    assert_eq!(gateway.send("POST", "/orders", Some(&key), Some(ask)).await.0, StatusCode::CREATED);
+
+    let uri = format!("/admin/surveillance/alerts?account_id={}&kind=self_trade", account_id);
+    let mut alerts = Value::Null;
+    for _ in 0..100 {
+        alerts = gateway.send("GET", &uri, Some(ADMIN_KEY), None).await.1["data"].clone();
+        if alerts.as_array().is_some_and(|alerts| !alerts.is_empty()) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(alerts[0]["kind"], "self_trade");
+    assert_eq!(alerts[0]["market"], MARKET);
+
+    let (status, _) = gateway.send("GET", "/admin/surveillance/alerts", Some(&key), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_withdrawal_address_whitelist() {
+    let gateway = Gateway::start_admin();
+    let (account_id, key) = gateway.funded_account().await;
+    let addresses = format!("/accounts/{}/withdrawal-addresses", account_id);
+    let withdraw = format!("/accounts/{}/withdraw", account_id);
+
+    let (status, body) = gateway
+        .send("POST", &addresses, Some(&key), Some(json!({ "asset": "USD", "address": "DE89370400440532013000", "label": "bank" })))
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let address_id = body["data"]["id"].as_str().unwrap().to_string();
+    let (status, _) = gateway
+        .send("POST", &addresses, Some(&key), Some(json!({ "asset": "USD", "address": "DE89370400440532013000" })))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (_, body) = gateway.send("GET", &addresses, Some(&key), None).await;
+    assert_eq!(body["data"][0]["label"], "bank");
+
+    // Withdrawals must now name a whitelisted address
+    let (status, _) = gateway.send("POST", &withdraw, Some(&key), Some(json!({ "asset": "USD", "amount": "10" }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = gateway
+        .send("POST", &withdraw, Some(&key), Some(json!({ "asset": "USD", "amount": "10", "address": "DE89370400440532013000" })))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = gateway.send("DELETE", &format!("{}/{}", addresses, address_id), Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = gateway.send("DELETE", &format!("{}/{}", addresses, address_id), Some(&key), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, body) = gateway
+        .send("GET", &format!("/admin/audit?account_id={}", account_id), Some(ADMIN_KEY), None)
+        .await;
+    let actions: Vec<&str> = body["data"].as_array().unwrap().iter().map(|entry| entry["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, ["withdrawal_address.removed", "withdrawal.rejected", "withdrawal_address.added"]);
+    assert_eq!(body["data"][1]["actor"], format!("account:{}", account_id));
+}
+
+#[tokio::test]
+async fn test_market_schedules_and_auction_settlement() {
+    let gateway = Gateway::start_admin();
+    let (buyer, buyer_key) = gateway.funded_account().await;
+    let (seller, seller_key) = gateway.funded_account().await;
+    gateway.state.account_service.deposit(seller, "BTC", dec!(5)).await.unwrap();
+
+    let (status, body) = gateway.send("GET", "/markets/BTC%2FUSD/session", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["state"], "Open");
+    assert!(body["data"]["schedule"].is_null());
+
+    // Closing auction through the end of the day
+    let schedule = json!({ "open": "00:00:00", "close": "23:59:59", "closing_auction_minutes": 1439 });
+    let (status, _) = gateway.send("PUT", "/admin/markets/BTC%2FUSD/schedule", Some(ADMIN_KEY), Some(json!({ "open": "10:00:00", "close": "09:00:00" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = gateway.send("PUT", "/admin/markets/ETH%2FUSD/schedule", Some(ADMIN_KEY), Some(schedule.clone())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = gateway.send("PUT", "/admin/markets/BTC%2FUSD/schedule", Some(&buyer_key), Some(schedule.clone())).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = gateway.send("PUT", "/admin/markets/BTC%2FUSD/schedule", Some(ADMIN_KEY), Some(schedule)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["schedule"]["closing_auction_minutes"], 1439);
+
+    let now = chrono::Utc::now().date_naive().and_hms_opt(12, 0, 0).unwrap().and_utc();
+    let changes = api_gateway::session::update_sessions(&gateway.state, now).await;
+    assert_eq!(changes[0].state, ::common::model::market::SessionState::Auction);
+
+    // Crossed orders rest until the auction ends
+    assert_eq!(gateway.place_bid(buyer, &buyer_key).await, StatusCode::CREATED);
+    let ask = json!({ "user_id": seller, "market": MARKET, "side": "Sell", "order_type": "Limit", "price": "100", "quantity": "1" });
+    let (status, body) = gateway.send("POST", "/orders", Some(&seller_key), Some(ask)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(body["data"]["trades"].as_array().unwrap().is_empty());
+    let market_order = json!({ "user_id": buyer, "market": MARKET, "side": "Buy", "order_type": "Market", "quantity": "1" });
+    let (status, _) = gateway.send("POST", "/orders", Some(&buyer_key), Some(market_order)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(gateway.usd_balance(buyer, &buyer_key).await["locked"], "100");
+
+    // Clearing the schedule ends the auction and settles its trades
+    let (status, _) = gateway.send("DELETE", "/admin/markets/BTC%2FUSD/schedule", Some(ADMIN_KEY), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let changes = api_gateway::session::update_sessions(&gateway.state, now).await;
+    assert_eq!(changes[0].state, ::common::model::market::SessionState::Open);
+    let usd = gateway.usd_balance(buyer, &buyer_key).await;
+    assert_eq!(usd["locked"], "0");
+    assert_eq!(usd["total"], "9900");
+
+    let (_, body) = gateway.send("GET", "/admin/audit", Some(ADMIN_KEY), None).await;
+    assert_eq!(body["data"][0]["action"], "market.schedule_cleared");
+    assert_eq!(body["data"][1]["action"], "market.schedule_set");
+}
@@ -0,0 +1,108 @@
+//! Order path latency tests
+//!
+//! Places orders through the REST API with and without a latency breakdown
+//! and reads the per-stage histograms through the admin API.
+
+mod common;
+
+use api_gateway::latency::{LatencyBreakdown, LatencyMetrics, Stage};
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use common::{Gateway, MARKET};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    /// Post a body as is, valid JSON or not
+    async fn post_raw(&self, uri: &str, key: &str, body: &str) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-api-key", key)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let (status, _, body) = self.call(request).await;
+        (status, body)
+    }
+}
+
+fn order(account_id: Uuid, side: &str) -> Value {
+    json!({
+        "user_id": account_id,
+        "market": MARKET,
+        "side": side,
+        "order_type": "Limit",
+        "price": "100",
+        "quantity": "1",
+    })
+}
+
+#[tokio::test]
+async fn test_breakdown_is_returned_on_request_and_recorded() {
+    let gateway = Gateway::start_admin();
+    let (buyer, buyer_key) = gateway.account_with("USD", "1000").await;
+    let (seller, seller_key) = gateway.account_with("BTC", "5").await;
+
+    let (status, body) = gateway.send("POST", "/orders", Some(&buyer_key), Some(order(buyer, "Buy"))).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert!(body["data"].get("latency_breakdown").is_none());
+
+    let (status, body) = gateway
+        .send("POST", "/orders?latency_breakdown=true", Some(&seller_key), Some(order(seller, "Sell")))
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["trades"].as_array().unwrap().len(), 1);
+
+    let breakdown = &body["data"]["latency_breakdown"];
+    let stages = ["parse_us", "risk_checks_us", "reserve_us", "match_us", "settle_us", "publish_us"];
+    let sum: u64 = stages.iter().map(|stage| breakdown[*stage].as_u64().unwrap()).sum();
+    assert!(breakdown["total_us"].as_u64().unwrap() >= sum, "{}", breakdown);
+
+    let (status, body) = gateway.admin("GET", "/admin/metrics/latency", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let histograms = body["data"].as_array().unwrap();
+    let names: Vec<&str> = histograms.iter().map(|histogram| histogram["stage"].as_str().unwrap()).collect();
+    assert_eq!(names, ["parse", "risk_checks", "reserve", "match", "settle", "publish", "total"]);
+    for histogram in histograms {
+        assert_eq!(histogram["count"], json!(2), "{}", histogram);
+        let bucketed: u64 = histogram["buckets"].as_array().unwrap().iter().map(|b| b["count"].as_u64().unwrap()).sum();
+        assert_eq!(bucketed, 2);
+    }
+}
+
+#[tokio::test]
+async fn test_malformed_body_is_a_bad_request() {
+    let gateway = Gateway::start_admin();
+    let (buyer, buyer_key) = gateway.account_with("USD", "1000").await;
+
+    let (status, body) = gateway.post_raw("/orders", &buyer_key, "{\"user_id\":").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "bad_request");
+
+    let missing_side = json!({ "user_id": buyer, "market": MARKET, "order_type": "Limit", "quantity": "1" });
+    let (status, _) = gateway.send("POST", "/orders", Some(&buyer_key), Some(missing_side)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Refused orders are not timed
+    let (_, body) = gateway.admin("GET", "/admin/metrics/latency", None).await;
+    assert_eq!(body["data"][0]["count"], json!(0));
+}
+
+#[test]
+fn test_percentiles_are_bucket_upper_bounds() {
+    let metrics = LatencyMetrics::new();
+    for parse_us in [5, 20, 20, 40, 3_000] {
+        metrics.record(&LatencyBreakdown { parse_us, total_us: parse_us, ..LatencyBreakdown::default() });
+    }
+
+    let parse = metrics.snapshot().into_iter().find(|histogram| histogram.stage == Stage::Parse).unwrap();
+    assert_eq!(parse.count, 5);
+    assert_eq!(parse.mean_us, 617);
+    assert_eq!(parse.p50_us, 25);
+    assert_eq!(parse.p90_us, 3_000);
+    assert_eq!(parse.max_us, 3_000);
+    assert_eq!(parse.buckets[0].le_us, Some(10));
+    assert_eq!(parse.buckets[0].count, 1);
+    assert_eq!(parse.buckets.last().unwrap().le_us, None);
+}
@@ -0,0 +1,90 @@
+//! Market order price cap tests
+//!
+//! Places market orders with a price cap or a max slippage against two
+//! resting asks and checks where they stop.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{Gateway, MARKET};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    /// Place a limit order
+    async fn order(&self, account_id: Uuid, key: &str, side: &str, price: &str, quantity: &str) {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": side,
+            "order_type": "Limit",
+            "price": price,
+            "quantity": quantity,
+        });
+        let (status, body) = self.send("POST", "/orders", Some(key), Some(order)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+    }
+
+    /// Place a market order with the given caps
+    async fn market_order(&self, account_id: Uuid, key: &str, caps: Value) -> (StatusCode, Value) {
+        let mut order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": "Buy",
+            "order_type": "Market",
+            "quantity": "1",
+        });
+        order.as_object_mut().unwrap().extend(caps.as_object().unwrap().clone());
+        self.send("POST", "/orders", Some(key), Some(order)).await
+    }
+}
+
+#[tokio::test]
+async fn test_market_order_stops_at_price_cap() {
+    let gateway = Gateway::start();
+    let (maker, maker_key) = gateway.trader().await;
+    let (taker, taker_key) = gateway.trader().await;
+    gateway.order(maker, &maker_key, "Sell", "100", "0.5").await;
+    gateway.order(maker, &maker_key, "Sell", "104", "0.5").await;
+
+    let (status, body) = gateway.market_order(taker, &taker_key, json!({ "price": "102" })).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let placement = &body["data"];
+    assert_eq!(placement["price_cap"], "102");
+    assert_eq!(placement["trades"].as_array().unwrap().len(), 1);
+    assert_eq!(placement["order"]["status"], "Expired");
+    assert_eq!(placement["order"]["reject_reason"], "PriceCap");
+    assert_eq!(placement["order"]["remaining_quantity"], "0.5");
+
+    // Funds held for the cancelled remainder are released
+    let (_, body) = gateway.send("GET", &format!("/accounts/{}/reservations", taker), Some(&taker_key), None).await;
+    assert_eq!(body["data"], json!([]));
+}
+
+#[tokio::test]
+async fn test_market_order_stops_at_max_slippage() {
+    let gateway = Gateway::start();
+    let (maker, maker_key) = gateway.trader().await;
+    let (taker, taker_key) = gateway.trader().await;
+    gateway.order(maker, &maker_key, "Sell", "100", "0.5").await;
+    gateway.order(maker, &maker_key, "Sell", "104", "0.5").await;
+
+    // 5% from the best ask allows both levels, with a price cap to lock funds against
+    let (status, body) = gateway.market_order(taker, &taker_key, json!({ "price": "110", "max_slippage_bps": 500 })).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["price_cap"], "105");
+    assert_eq!(body["data"]["order"]["status"], "Filled");
+
+    // Slippage limits only apply to market orders
+    let order = json!({
+        "user_id": taker,
+        "market": MARKET,
+        "side": "Buy",
+        "order_type": "Limit",
+        "price": "100",
+        "quantity": "1",
+        "max_slippage_bps": 500,
+    });
+    let (status, _) = gateway.send("POST", "/orders", Some(&taker_key), Some(order)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
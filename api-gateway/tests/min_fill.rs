@@ -0,0 +1,49 @@
+//! Minimum fill quantity tests
+//!
+//! Places orders with a `min_fill_quantity` through the order API against a
+//! thin book.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{Gateway, MARKET};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    /// Buy BTC/USD at up to 100 with a minimum fill quantity
+    async fn buy_with_minimum(&self, account_id: Uuid, key: &str, time_in_force: &str, quantity: &str, min: &str) -> (StatusCode, Value) {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": "Buy",
+            "order_type": "Limit",
+            "price": "100",
+            "quantity": quantity,
+            "time_in_force": time_in_force,
+            "min_fill_quantity": min,
+        });
+        self.send("POST", "/orders", Some(key), Some(order)).await
+    }
+}
+
+#[tokio::test]
+async fn test_orders_short_of_their_minimum_fill_do_not_trade() {
+    let gateway = Gateway::start();
+    let (maker, maker_key) = gateway.trader().await;
+    let (taker, taker_key) = gateway.trader().await;
+    assert_eq!(gateway.limit(maker, &maker_key, "Sell", "100", "0.5").await.0, StatusCode::CREATED);
+
+    let (_, body) = gateway.buy_with_minimum(taker, &taker_key, "IOC", "2", "1").await;
+    let order = &body["data"]["order"];
+    assert_eq!(order["status"], "Rejected", "{}", body);
+    assert_eq!(order["reject_reason"], "MinFillQuantity");
+    assert_eq!(order["min_fill_quantity"], "1");
+    assert!(body["data"]["trades"].as_array().unwrap().is_empty());
+
+    let (_, body) = gateway.buy_with_minimum(taker, &taker_key, "IOC", "2", "0.5").await;
+    assert_eq!(body["data"]["trades"].as_array().unwrap().len(), 1, "{}", body);
+
+    assert_eq!(gateway.buy_with_minimum(taker, &taker_key, "GTC", "1", "2").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(gateway.buy_with_minimum(taker, &taker_key, "GTC", "1", "0").await.0, StatusCode::BAD_REQUEST);
+}
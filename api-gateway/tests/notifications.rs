@@ -0,0 +1,240 @@
+//! Notification tests
+//!
+//! Sets preferences through the REST API and records what a test channel is
+//! asked to send, then talks to a local fake mail server through the SMTP
+//! channel.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ::common::error::Result;
+use api_gateway::config::AppConfig;
+use api_gateway::notification::{
+    Notification, NotificationConfig, NotificationKind, NotificationPreferences, NotificationService, Notifier,
+    SmtpConfig, SmtpNotifier,
+};
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use chrono::Utc;
+use common::{state, Gateway, MARKET};
+use matching_engine::MatchingEngine;
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+/// Channel that records what it is asked to send, once a permit is available
+struct Recorder {
+    sent: Mutex<Vec<(String, Notification)>>,
+    permits: Semaphore,
+}
+
+impl Recorder {
+    fn new(permits: usize) -> Arc<Self> {
+        Arc::new(Self {
+            sent: Mutex::new(Vec::new()),
+            permits: Semaphore::new(permits),
+        })
+    }
+
+    /// Wait until `count` notifications were sent, returning their kinds and addresses
+    async fn wait_for(&self, count: usize) -> Vec<(NotificationKind, String)> {
+        for _ in 0..100 {
+            let sent: Vec<_> = self.sent.lock().unwrap().iter()
+                .map(|(address, notification)| (notification.kind, address.clone()))
+                .collect();
+            if sent.len() >= count {
+                return sent;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("expected {} notifications, got {:?}", count, self.sent.lock().unwrap());
+    }
+}
+
+#[async_trait]
+impl Notifier for Recorder {
+    fn name(&self) -> &str {
+        "recorder"
+    }
+
+    fn address<'a>(&self, preferences: &'a NotificationPreferences) -> Option<&'a str> {
+        preferences.email.as_deref()
+    }
+
+    async fn send(&self, address: &str, notification: &Notification) -> Result<()> {
+        self.permits.acquire().await.unwrap().forget();
+        self.sent.lock().unwrap().push((address.to_string(), notification.clone()));
+        Ok(())
+    }
+}
+
+impl Gateway {
+    fn setup(recorder: Arc<Recorder>) -> Self {
+        let state = state().with_notifications(NotificationConfig::default(), vec![recorder]);
+        Self::new(state, &AppConfig::default())
+    }
+
+    async fn order(&self, account_id: Uuid, key: &str, side: &str) {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": side,
+            "order_type": "Limit",
+            "price": "100",
+            "quantity": "1",
+        });
+        let (status, body) = self.send("POST", "/orders", Some(key), Some(order)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+    }
+}
+
+#[tokio::test]
+async fn test_accounts_are_notified_of_the_events_they_chose() {
+    let recorder = Recorder::new(Semaphore::MAX_PERMITS);
+    let gateway = Gateway::setup(recorder.clone());
+    let (buyer, buyer_key) = gateway.account_with("USD", "1000").await;
+    let (seller, seller_key) = gateway.account_with("BTC", "1").await;
+    let uri = format!("/accounts/{}/notifications", buyer);
+
+    let (status, body) = gateway.send("GET", &uri, Some(&buyer_key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"], json!({ "email": null, "webhook_url": null, "events": [] }));
+
+    let (status, _) = gateway.send("PUT", &uri, Some(&buyer_key), Some(json!({ "email": "a@b.com\r\nBcc: x@y.com" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = gateway.send("PUT", &uri, Some(&buyer_key), Some(json!({ "webhook_url": "http://example.com/hook" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = gateway.send("PUT", &uri, Some(&seller_key), Some(json!({}))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let preferences = json!({ "email": "buyer@example.com", "events": ["withdrawal", "fill", "login", "fill"] });
+    let (status, body) = gateway.send("PUT", &uri, Some(&buyer_key), Some(preferences)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["events"], json!(["fill", "login", "withdrawal"]));
+
+    // The seller only wants withdrawals, so its fill and logins go unsent
+    let preferences = json!({ "email": "seller@example.com", "events": ["withdrawal"] });
+    gateway.send("PUT", &format!("/accounts/{}/notifications", seller), Some(&seller_key), Some(preferences)).await;
+
+    // The first request after opting in to logins comes from a new address
+    gateway.order(buyer, &buyer_key, "Buy").await;
+    gateway.order(seller, &seller_key, "Sell").await;
+    let withdrawal = json!({ "asset": "BTC", "amount": "0.5" });
+    let (status, _) = gateway.send("POST", &format!("/accounts/{}/withdraw", buyer), Some(&buyer_key), Some(withdrawal)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut sent = recorder.wait_for(3).await;
+    sent.sort_by_key(|(kind, _)| kind.name());
+    let buyer_email = "buyer@example.com".to_string();
+    assert_eq!(sent, [
+        (NotificationKind::Fill, buyer_email.clone()),
+        (NotificationKind::Login, buyer_email.clone()),
+        (NotificationKind::Withdrawal, buyer_email),
+    ]);
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(recorder.sent.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_full_queue_drops_notifications() {
+    // The recorder takes the first notification and holds it
+    let recorder = Recorder::new(0);
+    let config = NotificationConfig { queue_capacity: 1, ..NotificationConfig::default() };
+    let service = NotificationService::new(Arc::new(MatchingEngine::new()), config, vec![recorder.clone()]);
+
+    let account = Uuid::new_v4();
+    let preferences = NotificationPreferences {
+        email: Some("someone@example.com".to_string()),
+        events: vec![NotificationKind::Login],
+        ..NotificationPreferences::default()
+    };
+    service.set_preferences(account, preferences).unwrap();
+
+    service.record_login(account, "10.0.0.1");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    service.record_login(account, "10.0.0.2");
+    service.record_login(account, "10.0.0.3");
+    // Addresses already seen are not notified again
+    service.record_login(account, "10.0.0.1");
+    assert_eq!(service.dropped(), 1);
+
+    recorder.permits.add_permits(10);
+    let sent = recorder.wait_for(2).await;
+    assert_eq!(sent.len(), 2);
+}
+
+#[tokio::test]
+async fn test_smtp_notifier_sends_a_mail() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    // Minimal relay accepting one message and returning the conversation
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let mut received = Vec::new();
+
+        writer.write_all(b"220 relay ready\r\n").await.unwrap();
+        let mut in_data = false;
+        while let Some(line) = lines.next_line().await.unwrap() {
+            received.push(line.clone());
+            let reply: &[u8] = if in_data {
+                if line != "." {
+                    continue;
+                }
+                in_data = false;
+                b"250 queued\r\n"
+            } else if line.starts_with("EHLO") {
+                b"250-relay\r\n250 AUTH PLAIN\r\n"
+            } else if line.starts_with("AUTH") {
+                b"235 ok\r\n"
+            } else if line == "DATA" {
+                in_data = true;
+                b"354 go ahead\r\n"
+            } else if line == "QUIT" {
+                writer.write_all(b"221 bye\r\n").await.unwrap();
+                break;
+            } else {
+                b"250 ok\r\n"
+            };
+            writer.write_all(reply).await.unwrap();
+        }
+        received
+    });
+
+    let notifier = SmtpNotifier::new(
+        SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            from: "alerts@exchange.test".to_string(),
+            username: Some("user".to_string()),
+            password: Some("secret".to_string()),
+        },
+        Duration::from_secs(5),
+    );
+    let notification = Notification {
+        id: Uuid::new_v4(),
+        account_id: Uuid::new_v4(),
+        kind: NotificationKind::Withdrawal,
+        subject: "Withdrew 1 BTC".to_string(),
+        data: json!({ "note": ".hidden" }),
+        created_at: Utc::now(),
+    };
+    notifier.send("someone@example.com", &notification).await.unwrap();
+
+    let received = server.await.unwrap();
+    assert_eq!(received[0], "EHLO zavora-gateway");
+    // base64 of "\0user\0secret"
+    assert_eq!(received[1], "AUTH PLAIN AHVzZXIAc2VjcmV0");
+    assert_eq!(received[2], "MAIL FROM:<alerts@exchange.test>");
+    assert_eq!(received[3], "RCPT TO:<someone@example.com>");
+    assert!(received.contains(&"Subject: Withdrew 1 BTC".to_string()));
+    assert!(received.contains(&"To: <someone@example.com>".to_string()));
+    assert_eq!(received.last().unwrap(), "QUIT");
+}
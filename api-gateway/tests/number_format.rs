@@ -0,0 +1,190 @@
+//! JSON number format contract tests
+//!
+//! Locks the REST representation of decimals in both number formats: prices,
+//! quantities and amounts are always strings, ratios are numbers unless the
+//! client or the operator asks for `decimal-strings`, and integers are always
+//! numbers.
+
+mod common;
+
+use ::common::decimal::Price;
+use api_gateway::config::AppConfig;
+use api_gateway::number_format::NumberFormat;
+use axum::body::Body;
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use common::{state, Gateway, MARKET};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+const DECIMAL_STRINGS: &str = "application/json; profile=\"decimal-strings\"";
+
+impl Gateway {
+    /// Gateway with resting orders from two accounts: a bid at 100 and an ask at 101
+    ///
+    /// Returns the buyer's account, key and bid.
+    async fn setup(number_format: NumberFormat) -> (Self, Uuid, String, Uuid) {
+        let gateway = Self::new(state().with_number_format(number_format), &AppConfig::default());
+
+        let (buyer, buyer_key) = gateway.account_with("USD", "1000").await;
+        let (seller, seller_key) = gateway.account_with("BTC", "5").await;
+        let bid = gateway.order(buyer, &buyer_key, "Buy", "100", "2.5").await;
+        gateway.order(seller, &seller_key, "Sell", "101", "1").await;
+
+        (gateway, buyer, buyer_key, bid)
+    }
+
+    /// Send a request accepting the given media type, if any
+    async fn send_accepting(&self, method: &str, uri: &str, key: Option<&str>, accept: Option<&str>, body: Option<Value>) -> (StatusCode, HeaderMap, Value) {
+        let mut request = Self::request(method, uri, key, body);
+        if let Some(accept) = accept {
+            request.headers_mut().insert(header::ACCEPT, accept.parse().unwrap());
+        }
+        self.call(request).await
+    }
+
+    async fn get(&self, uri: &str, key: Option<&str>, accept: Option<&str>) -> (HeaderMap, Value) {
+        let (status, headers, body) = self.send_accepting("GET", uri, key, accept, None).await;
+        assert_eq!(status, StatusCode::OK, "GET {} failed: {}", uri, body);
+        (headers, body["data"].clone())
+    }
+
+    async fn order(&self, account_id: Uuid, key: &str, side: &str, price: &str, quantity: &str) -> Uuid {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": side,
+            "order_type": "Limit",
+            "price": price,
+            "quantity": quantity,
+        });
+        let (status, _, body) = self.send_accepting("POST", "/orders", Some(key), None, Some(order)).await;
+        assert_eq!(status, StatusCode::CREATED, "order failed: {}", body);
+        body["data"]["order"]["id"].as_str().unwrap().parse().unwrap()
+    }
+}
+
+fn is_decimal(value: &Value) -> bool {
+    value.as_str().is_some_and(|s| s.parse::<Price>().is_ok())
+}
+
+/// Fractional JSON numbers anywhere in a value, by path
+fn fractional_numbers(value: &Value, path: &str) -> Vec<String> {
+    match value {
+        Value::Number(number) if number.is_f64() => vec![path.to_string()],
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .flat_map(|(index, item)| fractional_numbers(item, &format!("{}[{}]", path, index)))
+            .collect(),
+        Value::Object(fields) => fields
+            .iter()
+            .flat_map(|(name, field)| fractional_numbers(field, &format!("{}.{}", path, name)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Prices, quantities and amounts are decimal strings whatever the format
+fn assert_decimals_are_strings(balances: &Value, order: &Value, order_book: &Value, analytics: &Value) {
+    let usd = balances.as_array().unwrap().iter().find(|b| b["asset"] == "USD").unwrap();
+    for field in ["total", "available", "locked"] {
+        assert!(is_decimal(&usd[field]), "balance {} should be a decimal string: {}", field, usd[field]);
+    }
+    assert_eq!(usd["locked"], "250.0");
+
+    for field in ["price", "quantity", "remaining_quantity", "filled_quantity"] {
+        assert!(is_decimal(&order[field]), "order {} should be a decimal string: {}", field, order[field]);
+    }
+
+    assert_eq!(order_book["bids"], json!([["100", "2.5"]]));
+    assert_eq!(order_book["asks"], json!([["101", "1"]]));
+    for field in ["best_bid", "best_ask", "mid", "spread", "bid_depth", "ask_depth"] {
+        assert!(is_decimal(&analytics[field]), "analytics {} should be a decimal string: {}", field, analytics[field]);
+    }
+    assert!(analytics["sequence"].is_u64());
+    assert!(analytics["depth_bps"].is_u64());
+    assert!(analytics["trade_count"].is_u64());
+}
+
+#[tokio::test]
+async fn test_native_format_writes_ratios_as_numbers() {
+    let (gateway, account_id, key, bid) = Gateway::setup(NumberFormat::Native).await;
+
+    let (headers, balances) = gateway.get(&format!("/accounts/{}/balances", account_id), Some(&key), None).await;
+    assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+    let (_, order) = gateway.get(&format!("/orders/{}", bid), Some(&key), None).await;
+    let (_, order_book) = gateway.get("/markets/BTC%2FUSD/order-book", None, None).await;
+    let (headers, analytics) = gateway.get("/markets/BTC%2FUSD/analytics?depth_bps=100", None, None).await;
+    assert!(headers.get_all(header::VARY).iter().any(|vary| vary == "accept"));
+
+    assert_decimals_are_strings(&balances, &order, &order_book, &analytics);
+    assert!(analytics["spread_bps"].is_f64());
+    assert!(analytics["book_imbalance"].is_f64());
+}
+
+#[tokio::test]
+async fn test_decimal_strings_profile_writes_no_fractional_numbers() {
+    let (gateway, account_id, key, bid) = Gateway::setup(NumberFormat::Native).await;
+    let accept = Some(DECIMAL_STRINGS);
+
+    let (headers, balances) = gateway.get(&format!("/accounts/{}/balances", account_id), Some(&key), accept).await;
+    assert_eq!(headers[header::CONTENT_TYPE], DECIMAL_STRINGS);
+    let (_, order) = gateway.get(&format!("/orders/{}", bid), Some(&key), accept).await;
+    let (headers, order_book) = gateway.get("/markets/BTC%2FUSD/order-book", None, accept).await;
+    let (_, analytics) = gateway.get("/markets/BTC%2FUSD/analytics?depth_bps=100", None, accept).await;
+
+    assert_decimals_are_strings(&balances, &order, &order_book, &analytics);
+    for body in [&balances, &order, &order_book, &analytics] {
+        assert_eq!(fractional_numbers(body, "data"), Vec::<String>::new());
+    }
+    assert_eq!(analytics["spread_bps"], "99.50248756218906");
+    assert_eq!(analytics["book_imbalance"], "0.4285714285714286");
+
+    // The rewritten body carries a weak tag that still revalidates
+    let etag = headers[header::ETAG].to_str().unwrap().to_string();
+    assert!(etag.starts_with("W/\""), "expected a weak ETag, got {}", etag);
+    let request = Request::builder()
+        .uri("/markets/BTC%2FUSD/order-book")
+        .header(header::ACCEPT, DECIMAL_STRINGS)
+        .header(header::IF_NONE_MATCH, &etag)
+        .body(Body::empty())
+        .unwrap();
+    let response = gateway.app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    // Unknown profiles fall back to the configured format
+    let (_, analytics) = gateway.get("/markets/BTC%2FUSD/analytics?depth_bps=100", None, Some("application/json; profile=\"x\"")).await;
+    assert!(analytics["spread_bps"].is_f64());
+}
+
+#[tokio::test]
+async fn test_configured_format_applies_unless_the_client_asks_for_another() {
+    let (gateway, _, _, _) = Gateway::setup(NumberFormat::DecimalStrings).await;
+
+    let (headers, analytics) = gateway.get("/markets/BTC%2FUSD/analytics?depth_bps=100", None, None).await;
+    assert_eq!(headers[header::CONTENT_TYPE], DECIMAL_STRINGS);
+    assert!(is_decimal(&analytics["spread_bps"]));
+
+    let (headers, analytics) = gateway.get("/markets/BTC%2FUSD/analytics?depth_bps=100", None, Some("application/json; profile=native")).await;
+    assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+    assert!(analytics["spread_bps"].is_f64());
+
+    // Errors are rewritten too, and stay valid JSON
+    let (status, _, body) = gateway.send_accepting("GET", "/markets/ETH%2FUSD/analytics", None, None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body.is_object());
+}
+
+#[test]
+fn test_fractions_become_shortest_decimal_strings() {
+    let mut value = json!({ "ratio": 0.1, "whole": 12.0, "negative": -0.25, "tiny": 1e-7, "count": 3, "items": [1.5, "2.50"] });
+    NumberFormat::DecimalStrings.apply(&mut value);
+    assert_eq!(value, json!({ "ratio": "0.1", "whole": "12", "negative": "-0.25", "tiny": "0.0000001", "count": 3, "items": ["1.5", "2.50"] }));
+
+    let text = NumberFormat::Native.apply_to_text("{\"ratio\":0.1}".to_string());
+    assert_eq!(text, "{\"ratio\":0.1}");
+    assert_eq!(NumberFormat::DecimalStrings.apply_to_text("not json".to_string()), "not json");
+    assert_eq!("decimal-strings".parse(), Ok(NumberFormat::DecimalStrings));
+    assert!("float".parse::<NumberFormat>().is_err());
+}
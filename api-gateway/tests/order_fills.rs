@@ -0,0 +1,89 @@
+//! Order fill report tests
+//!
+//! Fills one order against two resting asks and checks each side's fills,
+//! their running average price, and who may read them.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{Gateway, MARKET};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    /// Place a limit order, returning its ID
+    async fn order(&self, account_id: Uuid, key: &str, side: &str, price: &str, quantity: &str) -> String {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": side,
+            "order_type": "Limit",
+            "price": price,
+            "quantity": quantity,
+        });
+        let (status, body) = self.send("POST", "/orders", Some(key), Some(order)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        body["data"]["order"]["id"].as_str().unwrap().to_string()
+    }
+
+    async fn fills(&self, order_id: &str, key: &str) -> (StatusCode, Value) {
+        self.send("GET", &format!("/orders/{}/fills", order_id), Some(key), None).await
+    }
+}
+
+#[tokio::test]
+async fn test_fills_report_each_trade_with_running_average() {
+    let gateway = Gateway::start();
+    let (maker, maker_key) = gateway.trader().await;
+    let (taker, taker_key) = gateway.trader().await;
+
+    let first_ask = gateway.order(maker, &maker_key, "Sell", "100", "0.5").await;
+    gateway.order(maker, &maker_key, "Sell", "104", "0.5").await;
+    let bid = gateway.order(taker, &taker_key, "Buy", "104", "1").await;
+
+    // The bid left the book filled, its fills are still reported
+    let (status, body) = gateway.fills(&bid, &taker_key).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let fills = body["data"].as_array().unwrap();
+    assert_eq!(fills.len(), 2);
+    for fill in fills {
+        assert_eq!(fill["order_id"], bid);
+        assert_eq!(fill["account_id"], taker.to_string());
+        assert_eq!(fill["side"], "Buy");
+        assert_eq!(fill["liquidity"], "taker");
+        assert_eq!(fill["fee_asset"], "BTC");
+    }
+    let prices: Vec<&str> = fills.iter().map(|fill| fill["price"].as_str().unwrap()).collect();
+    assert_eq!(prices, ["100", "104"]);
+    assert_eq!(fills[0]["average_price"], "100");
+    assert_eq!(fills[1]["average_price"], "102");
+    assert_eq!(fills[1]["cumulative_quantity"], "1.0");
+    let first_trade = fills[0]["trade_id"].clone();
+
+    let (status, body) = gateway.fills(&first_ask, &maker_key).await;
+    assert_eq!(status, StatusCode::OK);
+    let fills = body["data"].as_array().unwrap();
+    assert_eq!(fills.len(), 1);
+    assert_eq!(fills[0]["liquidity"], "maker");
+    assert_eq!(fills[0]["fee_asset"], "USD");
+    assert_eq!(fills[0]["trade_id"], first_trade);
+}
+
+#[tokio::test]
+async fn test_fills_of_unknown_or_foreign_orders_are_refused() {
+    let gateway = Gateway::start();
+    let (owner, owner_key) = gateway.trader().await;
+    let (_, other_key) = gateway.trader().await;
+
+    // A resting order without fills has an empty report
+    let bid = gateway.order(owner, &owner_key, "Buy", "90", "0.1").await;
+    let (status, body) = gateway.fills(&bid, &owner_key).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"], json!([]));
+
+    let (status, _) = gateway.fills(&bid, &other_key).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = gateway.fills(&Uuid::new_v4().to_string(), &owner_key).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,85 @@
+//! Order audit trail tests
+//!
+//! Fills and cancels orders through the REST API and reads back each order's
+//! recorded status changes.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::Gateway;
+use serde_json::Value;
+
+impl Gateway {
+    /// Status changes of an order, oldest first
+    async fn history(&self, order_id: &str, key: &str) -> Vec<Value> {
+        let (status, body) = self.send("GET", &format!("/orders/{}/history", order_id), Some(key), None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["data"].as_array().unwrap().clone()
+    }
+}
+
+/// Statuses of a history, oldest first
+fn statuses(history: &[Value]) -> Vec<&str> {
+    history.iter().map(|transition| transition["status"].as_str().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn test_order_history_records_every_status_change() {
+    let gateway = Gateway::start();
+    let (maker, maker_key) = gateway.trader().await;
+    let (taker, taker_key) = gateway.trader().await;
+
+    let (_, body) = gateway.limit(maker, &maker_key, "Sell", "100", "1").await;
+    let maker_order = body["data"]["order"]["id"].as_str().unwrap().to_string();
+    let (_, body) = gateway.limit(taker, &taker_key, "Buy", "100", "0.4").await;
+    let taker_order = body["data"]["order"]["id"].as_str().unwrap().to_string();
+    let (status, _) = gateway.send("DELETE", &format!("/orders/{}", maker_order), Some(&maker_key), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let history = gateway.history(&maker_order, &maker_key).await;
+    assert_eq!(statuses(&history), ["New", "PartiallyFilled", "Cancelled"]);
+    assert!(history[0]["previous_status"].is_null());
+    assert_eq!(history[1]["previous_status"], "New");
+    assert_eq!(history[1]["fill_quantity"], "0.4");
+    assert_eq!(history[1]["remaining_quantity"], "0.6");
+    assert_eq!(history[2]["previous_status"], "PartiallyFilled");
+    assert_eq!(history[2]["fill_quantity"], "0");
+    assert_eq!(history[2]["filled_quantity"], "0.4");
+
+    // A taker filled on arrival was accepted and filled in one step, and
+    // reads of its history wait for both
+    let history = gateway.history(&taker_order, &taker_key).await;
+    assert_eq!(statuses(&history), ["New", "Filled"]);
+    assert_eq!(history[1]["fill_quantity"], "0.4");
+    assert_eq!(history[1]["average_fill_price"], "100");
+
+    let (status, _) = gateway.send("GET", &format!("/orders/{}/history", maker_order), Some(&taker_key), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = gateway.send("GET", &format!("/orders/{}/history", uuid::Uuid::new_v4()), Some(&taker_key), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_engine_terminated_orders_record_their_reason() {
+    let gateway = Gateway::start();
+    let (taker, taker_key) = gateway.trader().await;
+
+    // Nothing to match, so the IOC remainder expires
+    let order = serde_json::json!({
+        "user_id": taker,
+        "market": common::MARKET,
+        "side": "Buy",
+        "order_type": "Limit",
+        "price": "100",
+        "quantity": "1",
+        "time_in_force": "IOC",
+    });
+    let (_, body) = gateway.send("POST", "/orders", Some(&taker_key), Some(order)).await;
+    let order_id = body["data"]["order"]["id"].as_str().unwrap().to_string();
+
+    let history = gateway.history(&order_id, &taker_key).await;
+    assert_eq!(statuses(&history), ["New", body["data"]["order"]["status"].as_str().unwrap()]);
+    assert_eq!(history[1]["reason"], body["data"]["order"]["reject_reason"]);
+    assert!(!history[1]["reason"].is_null());
+    assert!(!history[1]["message"].is_null());
+}
@@ -0,0 +1,155 @@
+//! Admin CSV order import tests
+//!
+//! Uploads order files through the admin API and checks per-row validation,
+//! placement through the normal order path, dry runs and file-level errors.
+
+mod common;
+
+use ::common::decimal::dec;
+use ::common::model::market::Market;
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use common::{admin_config, spot, state_for, ADMIN_KEY, Gateway, MARKET};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    fn setup() -> Self {
+        let market = Market {
+            quantity_step: dec!(0.001),
+            min_order_size: dec!(10),
+            ..spot(MARKET)
+        };
+        Self::new(state_for(vec![market]), &admin_config())
+    }
+
+    async fn upload(&self, uri: &str, key: Option<&str>, csv: &str) -> (StatusCode, Value) {
+        let mut request = Request::builder().method("POST").uri(uri).header(header::CONTENT_TYPE, "text/csv");
+        if let Some(key) = key {
+            request = request.header("x-api-key", key);
+        }
+        let (status, _, body) = self.call(request.body(Body::from(csv.to_string())).unwrap()).await;
+        (status, body)
+    }
+
+    async fn import(&self, query: &str, csv: &str) -> (StatusCode, Value) {
+        self.upload(&format!("/admin/orders/import{}", query), Some(ADMIN_KEY), csv).await
+    }
+
+    /// Create an account with a deposit
+    async fn account(&self, asset: &str, amount: &str) -> Uuid {
+        self.account_with(asset, amount).await.0
+    }
+}
+
+#[tokio::test]
+async fn test_import_places_valid_rows_and_reports_rejected_ones() {
+    let gateway = Gateway::setup();
+    let buyer = gateway.account("USD", "1000").await;
+    let seller = gateway.account("BTC", "5").await;
+
+    let csv = format!(
+        "account_id,market,side,order_type,price,quantity,time_in_force,reference\n\
+         {buyer},BTC/USD,buy,limit,100,2,gtc,venue-1\n\
+         {seller},BTC/USD,Sell,,101,1,,venue-2\n\
+         {seller},BTC/USD,sell,limit,100.005,1,gtc,bad-tick\n\
+         {buyer},BTC/USD,buy,limit,100,0.0005,gtc,bad-step\n\
+         {buyer},BTC/USD,buy,limit,1,1,gtc,too-small\n\
+         \n\
+         {buyer},ETH/USD,buy,limit,100,1,gtc,unknown-market\n\
+         {unknown},BTC/USD,buy,limit,100,1,gtc,unknown-account\n\
+         {buyer},BTC/USD,hold,limit,100,1,gtc,bad-side\n\
+         {buyer},BTC/USD,buy,limit,500,3,gtc,\"no funds, retry\"\n\
+         {seller},BTC/USD,sell,market,,0.5,ioc,take\n",
+        unknown = Uuid::new_v4(),
+    );
+
+    let (status, body) = gateway.import("", &csv).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let summary = &body["data"];
+    assert_eq!(summary["dry_run"], json!(false));
+    assert_eq!(summary["rows"], json!(10));
+    assert_eq!(summary["placed"], json!(3));
+    assert_eq!(summary["rejected"], json!(7));
+
+    let results = summary["results"].as_array().unwrap();
+    let outcome = |reference: &str| results.iter().find(|r| r["reference"] == reference).unwrap().clone();
+
+    let bid = outcome("venue-1");
+    assert_eq!(bid["line"], json!(2));
+    assert_eq!(bid["status"], "placed");
+    assert_eq!(bid["order_status"], "New");
+
+    assert_eq!(outcome("venue-2")["status"], "placed");
+
+    let expectations = [
+        ("bad-tick", "validation_error", "multiple of the tick"),
+        ("bad-step", "validation_error", "multiple of the step"),
+        ("too-small", "validation_error", "below the minimum"),
+        ("unknown-market", "market_not_found", "ETH/USD"),
+        ("unknown-account", "not_found", "Account not found"),
+        ("bad-side", "bad_request", "Invalid side"),
+        ("no funds, retry", "insufficient_balance", ""),
+    ];
+    for (reference, code, message) in expectations {
+        let row = outcome(reference);
+        assert_eq!(row["status"], "rejected", "{}", row);
+        assert_eq!(row["error_code"], code, "{}", row);
+        assert!(row["error"].as_str().unwrap().contains(message), "{}", row);
+    }
+    // Lines count from the header, including the blank line
+    assert_eq!(outcome("unknown-market")["line"], json!(8));
+
+    // The market sell filled against the imported bid
+    let take = outcome("take");
+    assert_eq!(take["status"], "placed");
+    assert_eq!(take["order_status"], "Filled");
+    assert_eq!(take["filled_quantity"], "0.5");
+
+    let (bids, asks) = gateway.state.matching_engine.get_market_depth(MARKET, 10).unwrap();
+    assert_eq!(bids, vec![(dec!(100), dec!(1.5))]);
+    assert_eq!(asks, vec![(dec!(101), dec!(1))]);
+
+    let audit = gateway.state.audit_log.recent(None, 10);
+    assert!(audit.iter().any(|entry| entry.action == "orders.imported"));
+}
+
+#[tokio::test]
+async fn test_dry_run_places_nothing() {
+    let gateway = Gateway::setup();
+    let buyer = gateway.account("USD", "1000").await;
+
+    let csv = format!("account_id,market,side,price,quantity\n{buyer},BTC/USD,buy,100,1\n{buyer},BTC/USD,buy,0,1\n");
+    let (status, body) = gateway.import("?dry_run=true", &csv).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let summary = &body["data"];
+    assert_eq!(summary["dry_run"], json!(true));
+    assert_eq!(summary["placed"], json!(0));
+    assert_eq!(summary["results"][0]["status"], "valid");
+    assert!(summary["results"][0]["order_id"].is_null());
+    assert_eq!(summary["results"][1]["status"], "rejected");
+
+    let (bids, _) = gateway.state.matching_engine.get_market_depth(MARKET, 10).unwrap();
+    assert!(bids.is_empty());
+    assert!(gateway.state.audit_log.recent(None, 10).iter().all(|entry| entry.action != "orders.imported"));
+}
+
+#[tokio::test]
+async fn test_file_errors_reject_the_whole_import() {
+    let gateway = Gateway::setup();
+
+    for (csv, message) in [
+        ("", "no header row"),
+        ("account_id,market,side\n", "Missing column: quantity"),
+        ("account_id,market,side,quantity,colour\n", "Unknown column: colour"),
+        ("account_id,market,side,quantity,side\n", "Duplicate column: side"),
+    ] {
+        let (status, body) = gateway.import("", csv).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{:?}", csv);
+        assert!(body["error"]["message"].as_str().unwrap().contains(message), "{}", body);
+    }
+
+    let (status, _) = gateway.upload("/admin/orders/import", None, "account_id,market,side,quantity\n").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
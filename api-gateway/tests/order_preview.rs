@@ -0,0 +1,97 @@
+//! Order preview tests
+//!
+//! Previews orders against two resting asks and checks the estimated fills,
+//! slippage and funds, and that previews neither match nor reserve.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{Gateway, MARKET};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    /// Place a limit order, returning its ID
+    async fn order(&self, account_id: Uuid, key: &str, side: &str, price: &str, quantity: &str) -> String {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": side,
+            "order_type": "Limit",
+            "price": price,
+            "quantity": quantity,
+        });
+        let (status, body) = self.send("POST", "/orders", Some(key), Some(order)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        body["data"]["order"]["id"].as_str().unwrap().to_string()
+    }
+
+    async fn preview(&self, account_id: Uuid, key: &str, side: &str, price: &str, quantity: &str) -> (StatusCode, Value) {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": side,
+            "order_type": "Limit",
+            "price": price,
+            "quantity": quantity,
+        });
+        self.send("POST", "/orders/preview", Some(key), Some(order)).await
+    }
+}
+
+#[tokio::test]
+async fn test_preview_estimates_fills_without_placing() {
+    let gateway = Gateway::start();
+    let (maker, maker_key) = gateway.trader().await;
+    let (taker, taker_key) = gateway.trader().await;
+    gateway.order(maker, &maker_key, "Sell", "100", "0.5").await;
+    gateway.order(maker, &maker_key, "Sell", "104", "0.5").await;
+
+    let (status, body) = gateway.preview(taker, &taker_key, "Buy", "104", "2").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let preview = &body["data"];
+    let prices: Vec<&str> = preview["fills"].as_array().unwrap().iter().map(|fill| fill["price"].as_str().unwrap()).collect();
+    assert_eq!(prices, ["100", "104"]);
+    assert_eq!(preview["filled_quantity"], "1.0");
+    assert_eq!(preview["unfilled_quantity"], "1.0");
+    assert_eq!(preview["rests"], true);
+    assert_eq!(preview["average_price"], "102");
+    assert_eq!(preview["best_price"], "100");
+    assert_eq!(preview["slippage_bps"], 200.0);
+    assert_eq!(preview["estimated_receive"], "1.0");
+    assert_eq!(preview["receive_asset"], "BTC");
+    assert_eq!(preview["funds_asset"], "USD");
+    assert_eq!(preview["funds_required"], "208");
+    assert_eq!(preview["sufficient_funds"], true);
+
+    // Nothing was matched or reserved
+    let (_, body) = gateway.send("GET", &format!("/accounts/{}/reservations", taker), Some(&taker_key), None).await;
+    assert_eq!(body["data"], json!([]));
+    let (_, again) = gateway.preview(taker, &taker_key, "Buy", "104", "2").await;
+    assert_eq!(again["data"]["fills"], preview["fills"]);
+}
+
+#[tokio::test]
+async fn test_preview_checks_filters_funds_and_ownership() {
+    let gateway = Gateway::start();
+    let (account, key) = gateway.trader().await;
+    let (_, other_key) = gateway.trader().await;
+
+    // Nothing to fill against, and more BTC than the account holds
+    let (status, body) = gateway.preview(account, &key, "Sell", "90", "3").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let preview = &body["data"];
+    assert_eq!(preview["fills"], json!([]));
+    assert_eq!(preview["average_price"], Value::Null);
+    assert_eq!(preview["slippage_bps"], Value::Null);
+    assert_eq!(preview["funds_asset"], "BTC");
+    assert_eq!(preview["funds_available"], "1");
+    assert_eq!(preview["sufficient_funds"], false);
+
+    // Off the price tick
+    let (status, _) = gateway.preview(account, &key, "Buy", "100.001", "0.1").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = gateway.preview(account, &other_key, "Buy", "100", "0.1").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
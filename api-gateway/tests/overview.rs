@@ -0,0 +1,87 @@
+//! Operator overview tests
+//!
+//! Trades through the REST API and checks the admin overview reports each
+//! market's book and volume today, account and balance totals, and the
+//! depth of a market data subscriber that is not reading.
+
+mod common;
+
+use ::common::decimal::dec;
+use axum::http::StatusCode;
+use common::{Gateway, MARKET};
+use market_data::channel::Topic;
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+
+impl Gateway {
+    async fn overview(&self) -> Value {
+        let (status, body) = self.admin("GET", "/admin/overview", None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["data"].clone()
+    }
+}
+
+/// Decimal string as a number, whatever its scale
+fn amount(value: &Value) -> Decimal {
+    value.as_str().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn test_overview_reports_books_volume_totals_and_queues() {
+    let gateway = Gateway::start_admin();
+    let (buyer, buyer_key) = gateway.account_with("USD", "1000").await;
+    let (seller, seller_key) = gateway.account_with("BTC", "5").await;
+
+    // Never read, so every trade published after this waits in its queue
+    let _trades = gateway.state.market_data_service.channel().subscribe::<Value>(Topic::Trades(MARKET.to_string())).await;
+
+    assert_eq!(gateway.limit(seller, &seller_key, "Sell", "101", "1").await.0, StatusCode::CREATED);
+    assert_eq!(gateway.limit(seller, &seller_key, "Sell", "102", "1").await.0, StatusCode::CREATED);
+    assert_eq!(gateway.limit(buyer, &buyer_key, "Buy", "99", "1").await.0, StatusCode::CREATED);
+    assert_eq!(gateway.limit(buyer, &buyer_key, "Buy", "101", "0.5").await.0, StatusCode::CREATED);
+
+    let overview = gateway.overview().await;
+
+    let market = &overview["markets"][0];
+    assert_eq!(overview["markets"].as_array().unwrap().len(), 1);
+    assert_eq!(market["market"], MARKET);
+    assert_eq!(market["bid_orders"], 1);
+    assert_eq!(market["ask_orders"], 2);
+    assert_eq!(market["ask_levels"], 2);
+    assert_eq!(market["best_bid"], "99");
+    assert_eq!(market["best_ask"], "101");
+    assert_eq!(market["last_price"], "101");
+    assert_eq!(market["trades_today"], 1);
+    assert_eq!(market["volume_today"], "0.5");
+    assert_eq!(market["quote_volume_today"], "50.5");
+    assert_eq!(overview["open_orders"], 3);
+
+    let accounts = &overview["accounts"];
+    assert_eq!(accounts["accounts"], 2);
+    assert_eq!(accounts["closed"], 0);
+    let assets: Vec<&str> = accounts["balances"].as_array().unwrap().iter().map(|total| total["asset"].as_str().unwrap()).collect();
+    assert_eq!(assets, ["BTC", "USD"]);
+    let usd = &accounts["balances"][1];
+    assert_eq!(usd["holders"], 2);
+    assert_eq!(amount(&usd["total"]), dec!(1000));
+    // The resting bid at 99 keeps its reservation
+    assert_eq!(amount(&usd["locked"]), dec!(99));
+
+    let queues = &overview["queues"];
+    assert_eq!(queues["market_data"], 1);
+    assert_eq!(queues["settlement"], 0);
+    assert_eq!(queues["notifications"], 0);
+    assert_eq!(queues["webhook_deliveries"], 0);
+    assert_eq!(overview["websocket"]["connections"], 0);
+}
+
+#[tokio::test]
+async fn test_overview_requires_the_admin_key() {
+    let gateway = Gateway::start_admin();
+    let (_, key) = gateway.create_account().await;
+
+    let (status, _) = gateway.send("GET", "/admin/overview", Some(&key), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(gateway.send("GET", "/admin/overview", None, None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(gateway.overview().await["accounts"], json!({ "accounts": 1, "closed": 0, "balances": [] }));
+}
@@ -0,0 +1,84 @@
+//! Pegged order tests
+//!
+//! Places orders pegged to the mid and best bid through the order API and
+//! checks that the published depth follows their re-pricing.
+
+mod common;
+
+use api_gateway::peg::reprice_pegged_orders;
+use axum::http::StatusCode;
+use common::{Gateway, MARKET};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    /// Place a pegged limit order on BTC/USD, with `price` as its limit
+    async fn pegged(&self, account_id: Uuid, key: &str, side: &str, price: &str, peg: Value) -> (StatusCode, Value) {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": side,
+            "order_type": "Limit",
+            "price": price,
+            "quantity": "0.5",
+            "peg": peg,
+        });
+        self.send("POST", "/orders", Some(key), Some(order)).await
+    }
+
+    async fn bids(&self) -> Value {
+        let (_, book) = self.send("GET", "/markets/BTC%2FUSD/order-book", None, None).await;
+        book["data"]["bids"].clone()
+    }
+}
+
+#[tokio::test]
+async fn test_pegged_orders_rest_at_their_peg_and_follow_the_book() {
+    let gateway = Gateway::start();
+    let (maker, maker_key) = gateway.trader().await;
+    let (pegger, pegger_key) = gateway.trader().await;
+    assert_eq!(gateway.limit(maker, &maker_key, "Buy", "90", "1").await.0, StatusCode::CREATED);
+    assert_eq!(gateway.limit(maker, &maker_key, "Sell", "110", "0.5").await.0, StatusCode::CREATED);
+
+    // A dollar under the mid of 100, and no higher than 105
+    let peg = json!({ "reference": "mid", "offset": "-1" });
+    let (status, body) = gateway.pegged(pegger, &pegger_key, "Buy", "105", peg).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let order = &body["data"]["order"];
+    assert_eq!(order["price"], "99");
+    assert_eq!(order["peg"], json!({ "reference": "mid", "offset": "-1", "limit": "105" }));
+    assert_eq!(gateway.bids().await, json!([["99", "0.5"], ["90", "1"]]));
+
+    // A lower ask moves the mid to 95 once the sweep catches up
+    assert_eq!(gateway.limit(maker, &maker_key, "Sell", "100", "0.25").await.0, StatusCode::CREATED);
+    let later = gateway.state.matching_engine.clock().now() + chrono::Duration::seconds(1);
+    let repriced = reprice_pegged_orders(&gateway.state, later).await;
+    assert_eq!(repriced.len(), 1);
+    assert_eq!(gateway.bids().await, json!([["94", "0.5"], ["90", "1"]]));
+}
+
+#[tokio::test]
+async fn test_pegs_are_validated() {
+    let gateway = Gateway::start();
+    let (trader, key) = gateway.trader().await;
+    let peg = json!({ "reference": "best", "offset": "0" });
+
+    // Nothing to peg to yet
+    let (status, body) = gateway.pegged(trader, &key, "Buy", "100", peg.clone()).await;
+    assert_eq!(body["data"]["order"]["status"], "Rejected", "{} {}", status, body);
+    assert_eq!(body["data"]["order"]["reject_reason"], "PegUnavailable");
+
+    let order = json!({
+        "user_id": trader,
+        "market": MARKET,
+        "side": "Buy",
+        "order_type": "Limit",
+        "price": "100",
+        "quantity": "0.5",
+        "time_in_force": "IOC",
+        "peg": peg,
+    });
+    assert_eq!(gateway.send("POST", "/orders", Some(&key), Some(order)).await.0, StatusCode::BAD_REQUEST);
+    let order = json!({ "user_id": trader, "market": MARKET, "side": "Buy", "order_type": "Market", "quantity": "0.5", "peg": peg });
+    assert_eq!(gateway.send("POST", "/orders", Some(&key), Some(order)).await.0, StatusCode::BAD_REQUEST);
+}
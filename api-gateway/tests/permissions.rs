@@ -0,0 +1,107 @@
+//! Account permission tests
+//!
+//! Restricts an account as an admin and checks its API key's scopes, orders
+//! and withdrawals follow, along with the audit trail.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{admin_config, spot, state_for, ADMIN_KEY, Gateway};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+const MARKETS: [&str; 2] = ["BTC/USD", "ETH/USD"];
+
+impl Gateway {
+    fn setup() -> Self {
+        Self::new(state_for(MARKETS.iter().map(|symbol| spot(symbol)).collect()), &admin_config())
+    }
+
+    /// Create an account holding USD, returning its ID and API key
+    async fn account(&self) -> (Uuid, String) {
+        self.account_with("USD", "1000").await
+    }
+
+    async fn order(&self, account_id: Uuid, key: &str, market: &str) -> StatusCode {
+        let order = json!({
+            "user_id": account_id,
+            "market": market,
+            "side": "Buy",
+            "order_type": "Limit",
+            "price": "100",
+            "quantity": "0.5",
+        });
+        self.send("POST", "/orders", Some(key), Some(order)).await.0
+    }
+
+    async fn withdraw(&self, account_id: Uuid, key: &str) -> StatusCode {
+        let withdrawal = json!({ "asset": "USD", "amount": "10" });
+        self.send("POST", &format!("/accounts/{}/withdraw", account_id), Some(key), Some(withdrawal)).await.0
+    }
+
+    async fn set_permissions(&self, account_id: Uuid, permissions: Value) -> (StatusCode, Value) {
+        self.send("PUT", &format!("/admin/accounts/{}/permissions", account_id), Some(ADMIN_KEY), Some(permissions)).await
+    }
+}
+
+#[tokio::test]
+async fn test_permissions_restrict_the_api_key() {
+    let gateway = Gateway::setup();
+    let (id, key) = gateway.account().await;
+
+    // Accounts start with every scope
+    let (status, body) = gateway.send("GET", &format!("/accounts/{}/permissions", id), Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["scopes"], json!(["read", "trade", "withdraw"]));
+
+    let permissions = json!({
+        "can_trade": true,
+        "can_withdraw": false,
+        "allowed_markets": ["eth/usd"],
+        "max_leverage": "3",
+    });
+    let (status, body) = gateway.set_permissions(id, permissions).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["allowed_markets"], json!(["ETH/USD"]));
+
+    let (_, body) = gateway.send("GET", &format!("/accounts/{}/permissions", id), Some(&key), None).await;
+    assert_eq!(body["data"]["scopes"], json!(["read", "trade", "market:ETH/USD", "leverage:3"]));
+
+    assert_eq!(gateway.order(id, &key, "BTC/USD").await, StatusCode::FORBIDDEN);
+    assert_eq!(gateway.order(id, &key, "ETH/USD").await, StatusCode::CREATED);
+    assert_eq!(gateway.withdraw(id, &key).await, StatusCode::FORBIDDEN);
+
+    // Lifting the restrictions applies to the same key at once
+    let (status, _) = gateway.set_permissions(id, json!({ "can_trade": true, "can_withdraw": true })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(gateway.order(id, &key, "BTC/USD").await, StatusCode::CREATED);
+    assert_eq!(gateway.withdraw(id, &key).await, StatusCode::OK);
+
+    // Both changes and the rejected withdrawal are in the audit trail
+    let (_, body) = gateway.send("GET", &format!("/admin/audit?account_id={}", id), Some(ADMIN_KEY), None).await;
+    let actions: Vec<&str> = body["data"].as_array().unwrap().iter().map(|entry| entry["action"].as_str().unwrap()).collect();
+    assert_eq!(actions.iter().filter(|action| **action == "account.permissions_set").count(), 2);
+    assert!(actions.contains(&"withdrawal.rejected"));
+}
+
+#[tokio::test]
+async fn test_only_operators_set_valid_permissions() {
+    let gateway = Gateway::setup();
+    let (id, key) = gateway.account().await;
+    let allow_all = json!({ "can_trade": true, "can_withdraw": true });
+
+    let uri = format!("/admin/accounts/{}/permissions", id);
+    assert_eq!(gateway.send("PUT", &uri, Some(&key), Some(allow_all.clone())).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(gateway.send("GET", &uri, Some(ADMIN_KEY), None).await.0, StatusCode::OK);
+    assert_eq!(gateway.set_permissions(Uuid::new_v4(), allow_all).await.0, StatusCode::NOT_FOUND);
+
+    let unknown_market = json!({ "can_trade": true, "can_withdraw": true, "allowed_markets": ["DOGE/USD"] });
+    assert_eq!(gateway.set_permissions(id, unknown_market).await.0, StatusCode::BAD_REQUEST);
+    let low_leverage = json!({ "can_trade": true, "can_withdraw": true, "max_leverage": "0.5" });
+    assert_eq!(gateway.set_permissions(id, low_leverage).await.0, StatusCode::BAD_REQUEST);
+
+    // Another account's key cannot read the scopes
+    let (other, _) = gateway.account().await;
+    let (status, _) = gateway.send("GET", &format!("/accounts/{}/permissions", other), Some(&key), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
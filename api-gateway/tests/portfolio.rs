@@ -0,0 +1,173 @@
+//! Portfolio valuation tests
+//!
+//! Quotes BTC/USD and ETH/BTC, then values balances in USD and BTC, converting
+//! ETH to USD through BTC. Tickers are converted the same way.
+
+mod common;
+
+use ::common::decimal::dec;
+use ::common::model::market::Market;
+use ::common::model::order::Side;
+use ::common::model::trade::Trade;
+use api_gateway::config::AppConfig;
+use api_gateway::valuation::{ConversionLeg, ConversionResolver, ShortestPathResolver};
+use axum::http::StatusCode;
+use common::{spot, state_for, Gateway};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+fn market(base: &str, quote: &str) -> Market {
+    Market {
+        price_tick: dec!(0.0001),
+        ..spot(&format!("{}/{}", base, quote))
+    }
+}
+
+fn leg(market: &str, inverse: bool) -> ConversionLeg {
+    ConversionLeg { market: market.to_string(), inverse }
+}
+
+impl Gateway {
+    fn setup() -> Self {
+        Self::new(state_for(vec![market("BTC", "USD"), market("ETH", "BTC")]), &AppConfig::default())
+    }
+
+    async fn account(&self, deposits: &[(&str, &str)]) -> (Uuid, String) {
+        let (id, key) = self.create_account().await;
+        for (asset, amount) in deposits {
+            self.fund(id, &key, asset, amount).await;
+        }
+        (id, key)
+    }
+
+    async fn order(&self, account_id: Uuid, key: &str, market: &str, side: &str, price: &str) {
+        let order = json!({
+            "user_id": account_id,
+            "market": market,
+            "side": side,
+            "order_type": "Limit",
+            "price": price,
+            "quantity": "1",
+        });
+        let (status, body) = self.send("POST", "/orders", Some(key), Some(order)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+    }
+
+    async fn quote_books(&self) {
+        let (maker, maker_key) = self.account(&[("USD", "1000"), ("BTC", "10"), ("ETH", "10")]).await;
+        self.order(maker, &maker_key, "BTC/USD", "Buy", "99").await;
+        self.order(maker, &maker_key, "BTC/USD", "Sell", "101").await;
+        self.order(maker, &maker_key, "ETH/BTC", "Buy", "0.049").await;
+        self.order(maker, &maker_key, "ETH/BTC", "Sell", "0.051").await;
+    }
+
+    async fn portfolio(&self, account_id: Uuid, key: &str, quote: &str) -> Value {
+        let uri = format!("/accounts/{}/portfolio?quote={}", account_id, quote);
+        let (status, body) = self.send("GET", &uri, Some(key), None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["data"].clone()
+    }
+}
+
+fn amount(value: &Value) -> Decimal {
+    value.as_str().unwrap().parse::<Decimal>().unwrap().normalize()
+}
+
+#[test]
+fn test_shortest_path_resolver() {
+    let markets = [market("BTC", "USD"), market("ETH", "BTC"), market("ETH", "USD"), market("SOL", "ETH")];
+    let resolver = ShortestPathResolver;
+
+    assert_eq!(resolver.resolve(&markets, "USD", "USD"), Some(vec![]));
+    assert_eq!(resolver.resolve(&markets, "BTC", "USD"), Some(vec![leg("BTC/USD", false)]));
+    assert_eq!(resolver.resolve(&markets, "USD", "BTC"), Some(vec![leg("BTC/USD", true)]));
+    assert_eq!(resolver.resolve(&markets, "ETH", "USD"), Some(vec![leg("ETH/USD", false)]));
+    assert_eq!(
+        resolver.resolve(&markets, "SOL", "BTC"),
+        Some(vec![leg("SOL/ETH", false), leg("ETH/BTC", false)])
+    );
+    assert_eq!(resolver.resolve(&markets, "DOGE", "USD"), None);
+}
+
+#[tokio::test]
+async fn test_balances_are_valued_through_conversion_paths() {
+    let gateway = Gateway::setup();
+    gateway.quote_books().await;
+
+    let (account, key) = gateway.account(&[("USD", "50"), ("BTC", "1"), ("ETH", "2"), ("SOL", "5")]).await;
+    // Locked funds still count towards the value
+    gateway.order(account, &key, "BTC/USD", "Buy", "20").await;
+
+    let portfolio = gateway.portfolio(account, &key, "usd").await;
+    assert_eq!(portfolio["quote"], "USD");
+    assert_eq!(amount(&portfolio["total_value"]), dec!(160));
+    assert_eq!(amount(&portfolio["locked_value"]), dec!(20));
+    assert_eq!(amount(&portfolio["available_value"]), dec!(140));
+    assert_eq!(portfolio["unpriced"], json!(["SOL"]));
+
+    let assets = portfolio["assets"].as_array().unwrap();
+    let asset = |name: &str| assets.iter().find(|asset| asset["asset"] == name).unwrap().clone();
+    assert_eq!(amount(&asset("ETH")["price"]), dec!(5));
+    assert_eq!(amount(&asset("ETH")["value"]), dec!(10));
+    assert_eq!(asset("ETH")["path"], json!([
+        { "market": "ETH/BTC", "inverse": false },
+        { "market": "BTC/USD", "inverse": false },
+    ]));
+    assert_eq!(asset("SOL")["value"], Value::Null);
+
+    let portfolio = gateway.portfolio(account, &key, "BTC").await;
+    assert_eq!(amount(&portfolio["total_value"]), dec!(1.6));
+    assert_eq!(portfolio["unpriced"], json!(["SOL"]));
+}
+
+#[tokio::test]
+async fn test_portfolio_is_private_to_its_account() {
+    let gateway = Gateway::setup();
+    let (account, _) = gateway.account(&[]).await;
+    let (_, other_key) = gateway.account(&[]).await;
+
+    let (status, _) = gateway.send("GET", &format!("/accounts/{}/portfolio", account), Some(&other_key), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = gateway.send("GET", &format!("/accounts/{}/portfolio", account), None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_tickers_are_converted_into_a_currency() {
+    let gateway = Gateway::setup();
+    gateway.quote_books().await;
+    for (market, price) in [("ETH/BTC", dec!(0.05)), ("ETH/BTC", dec!(0.06))] {
+        let trade = Trade::new(market.to_string(), price, dec!(2), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Side::Buy);
+        gateway.state.market_data_service.process_trade(&trade).await.unwrap();
+    }
+
+    let tickers = |body: Value| {
+        let tickers = body["data"].as_array().unwrap().clone();
+        tickers.into_iter().find(|ticker| ticker["market"] == "ETH/BTC").unwrap()
+    };
+
+    // Without `convert` tickers are unchanged
+    let (status, body) = gateway.send("GET", "/markets/tickers", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let ticker = tickers(body);
+    assert!(ticker.get("converted").is_none());
+    assert_eq!(amount(&ticker["last"]), dec!(0.06));
+
+    // ETH/BTC is quoted in BTC, converted at the BTC/USD mark of 100
+    let (_, body) = gateway.send("GET", "/markets/tickers?convert=usd", None, None).await;
+    let converted = &tickers(body)["converted"];
+    assert_eq!(converted["currency"], "USD");
+    assert_eq!(amount(&converted["rate"]), dec!(100));
+    assert_eq!(amount(&converted["last"]), dec!(6));
+    assert_eq!(amount(&converted["high_24h"]), dec!(6));
+    assert_eq!(amount(&converted["low_24h"]), dec!(5));
+    assert_eq!(converted["path"], json!([{ "market": "BTC/USD", "inverse": false }]));
+
+    // A currency no market leads to cannot be converted
+    let (_, body) = gateway.send("GET", "/markets/tickers?convert=EUR", None, None).await;
+    let converted = &tickers(body)["converted"];
+    assert_eq!(converted["currency"], "EUR");
+    assert_eq!(converted["rate"], Value::Null);
+    assert_eq!(converted["last"], Value::Null);
+}
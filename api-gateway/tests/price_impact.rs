@@ -0,0 +1,72 @@
+//! Price impact tests
+//!
+//! Estimates the impact of hypothetical orders against resting asks and bids
+//! without an API key, and checks that nothing is placed.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::Gateway;
+use serde_json::Value;
+
+impl Gateway {
+    async fn impact(&self, query: &str) -> (StatusCode, Value) {
+        self.send("GET", &format!("/markets/BTC%2FUSD/impact?{}", query), None, None).await
+    }
+}
+
+#[tokio::test]
+async fn test_impact_walks_the_book_without_an_account() {
+    let gateway = Gateway::start();
+    let (maker, maker_key) = gateway.trader().await;
+    assert_eq!(gateway.limit(maker, &maker_key, "Sell", "100", "0.5").await.0, StatusCode::CREATED);
+    assert_eq!(gateway.limit(maker, &maker_key, "Sell", "104", "0.5").await.0, StatusCode::CREATED);
+    assert_eq!(gateway.limit(maker, &maker_key, "Buy", "90", "1").await.0, StatusCode::CREATED);
+
+    let (status, body) = gateway.impact("side=buy&quantity=0.75").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let impact = &body["data"];
+    assert_eq!(impact["side"], "Buy");
+    assert_eq!(impact["filled_quantity"], "0.75");
+    assert_eq!(impact["unfilled_quantity"], "0.00");
+    // 0.5 at 100 and 0.25 at 104
+    assert_eq!(impact["best_price"], "100");
+    assert_eq!(impact["worst_price"], "104");
+    assert_eq!(impact["notional"], "76.00");
+    assert_eq!(impact["average_price"].as_str().unwrap().parse::<f64>().unwrap(), 76.0 / 0.75);
+    assert!((impact["slippage_bps"].as_f64().unwrap() - 133.33).abs() < 0.01);
+
+    // More than the book holds fills what it can
+    let (_, body) = gateway.impact("side=buy&quantity=2.5").await;
+    assert_eq!(body["data"]["filled_quantity"], "1.0");
+    assert_eq!(body["data"]["unfilled_quantity"], "1.5");
+    assert_eq!(body["data"]["slippage_bps"], 200.0);
+
+    // Sells walk the bids, and a single level has no slippage
+    let (_, body) = gateway.impact("side=Sell&quantity=0.5").await;
+    assert_eq!(body["data"]["best_price"], "90");
+    assert_eq!(body["data"]["worst_price"], "90");
+    assert_eq!(body["data"]["slippage_bps"], 0.0);
+
+    // Nothing was placed or matched
+    let (_, book) = gateway.send("GET", "/markets/BTC%2FUSD/order-book", None, None).await;
+    assert_eq!(book["data"]["asks"].as_array().unwrap().len(), 2, "{}", book);
+}
+
+#[tokio::test]
+async fn test_impact_rejects_bad_queries() {
+    let gateway = Gateway::start();
+
+    // An empty side has nothing to fill
+    let (status, body) = gateway.impact("side=buy&quantity=1").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["filled_quantity"], "0");
+    assert!(body["data"]["average_price"].is_null());
+    assert!(body["data"]["slippage_bps"].is_null());
+
+    assert_eq!(gateway.impact("side=short&quantity=1").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(gateway.impact("side=buy&quantity=0").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(gateway.impact("side=buy").await.0, StatusCode::BAD_REQUEST);
+    let (status, _) = gateway.send("GET", "/markets/DOGE%2FEUR/impact?side=buy&quantity=1", None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,236 @@
+//! Scheduled report job tests
+//!
+//! Parses cron-like schedules, then runs the built-in report jobs through the
+//! admin API and checks the files written and the run history.
+
+mod common;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use api_gateway::report::{AccountingConfig, AccountingFormat, ChartOfAccounts, ReportConfig, ReportSink};
+use api_gateway::scheduler::{builtin_jobs, CronSchedule, JobSchedule, SchedulerConfig};
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use common::{admin_config, state, ADMIN_KEY, Gateway, MARKET};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    fn setup(sink: Option<PathBuf>, scheduler: SchedulerConfig) -> Self {
+        let state = state()
+            .with_reports(ReportConfig {
+                sink: sink.map(ReportSink::Directory),
+                ..ReportConfig::default()
+            })
+            .with_scheduler(scheduler, builtin_jobs());
+        Self::new(state, &admin_config())
+    }
+
+    async fn run(&self, job: &str, body: Value) -> (StatusCode, Value) {
+        self.send("POST", &format!("/admin/report-jobs/{}/runs", job), Some(ADMIN_KEY), Some(body)).await
+    }
+}
+
+fn temp_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("zavora-report-jobs-{}", Uuid::new_v4()))
+}
+
+fn at(time: &str) -> DateTime<Utc> {
+    time.parse().unwrap()
+}
+
+#[test]
+fn test_cron_schedules() {
+    let daily: CronSchedule = "5 0 * * *".parse().unwrap();
+    assert_eq!(daily.next_after(at("2025-02-27T00:04:00Z")), Some(at("2025-02-27T00:05:00Z")));
+    assert_eq!(daily.next_after(at("2025-02-27T00:05:00Z")), Some(at("2025-02-28T00:05:00Z")));
+
+    let monthly: CronSchedule = "0 1 1 * *".parse().unwrap();
+    assert_eq!(monthly.next_after(at("2025-02-27T12:00:00Z")), Some(at("2025-03-01T01:00:00Z")));
+
+    // Steps, ranges and lists; 2025-03-01 is a Saturday
+    let weekdays: CronSchedule = "*/20 9-10 * * 1-5".parse().unwrap();
+    assert_eq!(weekdays.next_after(at("2025-02-28T10:45:00Z")), Some(at("2025-03-03T09:00:00Z")));
+    assert_eq!(weekdays.next_after(at("2025-03-03T09:00:00Z")), Some(at("2025-03-03T09:20:00Z")));
+    let sundays: CronSchedule = "30 6 * * 7".parse().unwrap();
+    assert_eq!(sundays.next_after(at("2025-02-27T00:00:00Z")), Some(at("2025-03-02T06:30:00Z")));
+
+    // Restricting both day fields fires on either
+    let either: CronSchedule = "0 0 15 * 1".parse().unwrap();
+    assert_eq!(either.next_after(at("2025-03-04T00:00:00Z")), Some(at("2025-03-10T00:00:00Z")));
+    assert_eq!(either.next_after(at("2025-03-11T00:00:00Z")), Some(at("2025-03-15T00:00:00Z")));
+
+    assert_eq!("@daily".parse::<CronSchedule>().unwrap().to_string(), "@daily");
+    assert_eq!("0 0 29 2 *".parse::<CronSchedule>().unwrap().next_after(at("2025-03-01T00:00:00Z")), Some(at("2028-02-29T00:00:00Z")));
+    assert_eq!("0 0 31 2 *".parse::<CronSchedule>().unwrap().next_after(at("2025-03-01T00:00:00Z")), None);
+
+    for invalid in ["", "* * * *", "60 * * * *", "* 24 * * *", "0 0 0 * *", "*/0 * * * *", "5-1 * * * *", "a * * * *"] {
+        assert!(invalid.parse::<CronSchedule>().is_err(), "{}", invalid);
+    }
+
+    let schedule: JobSchedule = "fee_invoices=0 1 1 * *".parse().unwrap();
+    assert_eq!(schedule.job, "fee_invoices");
+    assert!("fee_invoices".parse::<JobSchedule>().is_err());
+}
+
+#[tokio::test]
+async fn test_jobs_write_statements_invoices_and_exports() {
+    let dir = temp_dir();
+    let gateway = Gateway::setup(Some(dir.clone()), SchedulerConfig::default());
+    let (seller, seller_key) = gateway.trader().await;
+    let (buyer, buyer_key) = gateway.trader().await;
+
+    let order = |account_id: Uuid, side: &str| json!({
+        "user_id": account_id,
+        "market": MARKET,
+        "side": side,
+        "order_type": "Limit",
+        "price": "100",
+        "quantity": "0.5",
+    });
+    assert_eq!(gateway.send("POST", "/orders", Some(&seller_key), Some(order(seller, "Sell"))).await.0, StatusCode::CREATED);
+    assert_eq!(gateway.send("POST", "/orders", Some(&buyer_key), Some(order(buyer, "Buy"))).await.0, StatusCode::CREATED);
+    let rebate = json!({ "asset": "USD", "amount": "1.25", "reason_code": "fee_overcharge" });
+    let (status, _) = gateway.send("POST", &format!("/admin/accounts/{}/fee-rebates", buyer), Some(ADMIN_KEY), Some(rebate)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let today = Utc::now().date_naive().to_string();
+    let (status, body) = gateway.run("daily_statements", json!({ "date": today })).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let run = &body["data"];
+    assert_eq!(run["status"], "succeeded");
+    assert_eq!(run["trigger"], "manual");
+    assert_eq!(run["records"], 5);
+    let statements = std::fs::read_to_string(dir.join(format!("statements/{}.csv", today))).unwrap();
+    assert!(statements.starts_with("account_id,timestamp,kind,asset,amount,fee,reference,market,reason_code,note\n"));
+    assert_eq!(statements.lines().filter(|line| line.starts_with(&buyer.to_string())).count(), 3);
+    assert!(statements.contains("fee_rebate,USD,1.25"));
+
+    let (status, body) = gateway.run("fee_invoices", json!({ "date": today })).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["records"], 4);
+    let month = &today[..7];
+    let invoices = std::fs::read_to_string(dir.join(format!("invoices/{}.csv", month))).unwrap();
+    let buyer_usd = invoices.lines().find(|line| line.starts_with(&format!("{},USD,", buyer))).unwrap();
+    assert!(buyer_usd.contains(",1,"), "{}", buyer_usd);
+    assert!(buyer_usd.contains(",1.25,"), "{}", buyer_usd);
+
+    let (status, body) = gateway.run("compliance_export", json!({ "date": today })).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["status"], "succeeded");
+    assert!(body["data"]["files"][0].as_str().unwrap().ends_with("BTC-USD.csv"));
+
+    // Each run is in the job's history and the audit trail
+    let (_, body) = gateway.send("GET", "/admin/report-jobs/fee_invoices/runs", Some(ADMIN_KEY), None).await;
+    let runs = body["data"].as_array().unwrap();
+    assert_eq!(runs.len(), 1);
+    let run_id = runs[0]["id"].as_str().unwrap();
+    let (status, body) = gateway.send("GET", &format!("/admin/report-jobs/fee_invoices/runs/{}", run_id), Some(ADMIN_KEY), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["date"], today);
+    let (status, _) = gateway.send("GET", &format!("/admin/report-jobs/daily_statements/runs/{}", run_id), Some(ADMIN_KEY), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, body) = gateway.send("GET", "/admin/audit", Some(ADMIN_KEY), None).await;
+    assert_eq!(body["data"][0]["action"], "report_job.run");
+    assert_eq!(body["data"][0]["details"]["job"], "compliance_export");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_runs_and_schedules_are_listed_and_failures_kept() {
+    let scheduler = SchedulerConfig {
+        schedules: vec![
+            "daily_statements=5 0 * * *".parse().unwrap(),
+            "fee_invoices=@monthly".parse().unwrap(),
+            "payroll=@daily".parse().unwrap(),
+        ],
+        ..SchedulerConfig::default()
+    };
+    let gateway = Gateway::setup(None, scheduler);
+
+    let (status, body) = gateway.send("GET", "/admin/report-jobs", Some(ADMIN_KEY), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let jobs = body["data"].as_array().unwrap();
+    let names: Vec<&str> = jobs.iter().map(|job| job["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["accounting_export", "compliance_export", "daily_statements", "fee_invoices"]);
+    assert_eq!(jobs[0]["schedules"], json!([]));
+    assert!(jobs[0]["next_run_at"].is_null());
+    assert_eq!(jobs[2]["schedules"], json!(["5 0 * * *"]));
+    assert!(jobs[2]["next_run_at"].as_str().unwrap().contains("T00:05:00"));
+
+    // Without a destination the run fails but is still recorded
+    let (status, body) = gateway.run("daily_statements", Value::Null).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["status"], "failed");
+    assert!(body["data"]["error"].as_str().unwrap().contains("No report destination"));
+    let yesterday = (Utc::now().date_naive() - chrono::Days::new(1)).to_string();
+    assert_eq!(body["data"]["date"], yesterday);
+
+    let (_, body) = gateway.send("GET", "/admin/report-jobs", Some(ADMIN_KEY), None).await;
+    assert_eq!(body["data"][2]["last_run"]["status"], "failed");
+
+    assert_eq!(gateway.run("payroll", json!({})).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(gateway.send("GET", "/admin/report-jobs/payroll/runs", Some(ADMIN_KEY), None).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(gateway.send("GET", "/admin/report-jobs", Some("not-admin"), None).await.0, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_accounting_export_writes_balanced_journals() {
+    let dir = temp_dir();
+    let accounting = AccountingConfig {
+        formats: vec![AccountingFormat::Ledger, AccountingFormat::Csv],
+        chart: ChartOfAccounts::with_mappings(&[
+            "clients=Liabilities:Customers:{account}".parse().unwrap(),
+            "fee_rebates=Expenses:Marketing".parse().unwrap(),
+        ]),
+    };
+    let state = state()
+        .with_reports(ReportConfig {
+            sink: Some(ReportSink::Directory(dir.clone())),
+            accounting,
+            ..ReportConfig::default()
+        })
+        .with_scheduler(SchedulerConfig::default(), builtin_jobs());
+    let gateway = Gateway::new(state, &admin_config());
+    let (seller, seller_key) = gateway.trader().await;
+    let (buyer, buyer_key) = gateway.trader().await;
+
+    assert_eq!(gateway.limit(seller, &seller_key, "Sell", "100", "0.5").await.0, StatusCode::CREATED);
+    assert_eq!(gateway.limit(buyer, &buyer_key, "Buy", "100", "0.5").await.0, StatusCode::CREATED);
+    let rebate = json!({ "asset": "USD", "amount": "1.25", "reason_code": "fee_overcharge" });
+    let (status, _) = gateway.send("POST", &format!("/admin/accounts/{}/fee-rebates", buyer), Some(ADMIN_KEY), Some(rebate)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let today = Utc::now().date_naive().to_string();
+    let (status, body) = gateway.run("accounting_export", json!({ "date": today })).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["status"], "succeeded", "{}", body);
+    // Two legs for each side of the trade, and the rebate
+    assert_eq!(body["data"]["records"], 5);
+    assert_eq!(body["data"]["files"].as_array().unwrap().len(), 2);
+
+    let ledger = std::fs::read_to_string(dir.join(format!("accounting/{}.ledger", today))).unwrap();
+    assert!(ledger.contains(&format!("{} * ", today)), "{}", ledger);
+    assert!(ledger.contains(&format!("fee_rebate fee_overcharge for {}", buyer)), "{}", ledger);
+    assert!(ledger.contains(&format!("    Liabilities:Customers:{}  -1.25 USD\n    Expenses:Marketing  1.25 USD\n", buyer)), "{}", ledger);
+    assert!(ledger.contains(&format!("    Liabilities:Customers:{}  50 USD\n    Assets:Clearing:Trades  -50 USD\n", buyer)), "{}", ledger);
+
+    // Every transaction balances in each asset
+    let csv = std::fs::read_to_string(dir.join(format!("accounting/{}.csv", today))).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("transaction,date,timestamp,account,asset,debit,credit,description,reference"));
+    let mut balances: BTreeMap<(String, String), Decimal> = BTreeMap::new();
+    for line in lines {
+        let columns: Vec<&str> = line.split(',').collect();
+        let amount = |column: &str| column.parse::<Decimal>().unwrap_or_default();
+        *balances.entry((columns[0].to_string(), columns[4].to_string())).or_default() += amount(columns[5]) - amount(columns[6]);
+    }
+    assert_eq!(balances.keys().map(|(transaction, _)| transaction.as_str()).collect::<BTreeSet<_>>().len(), 5);
+    assert!(balances.values().all(|balance| balance.is_zero()), "{:?}", balances);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
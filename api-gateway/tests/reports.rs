@@ -0,0 +1,190 @@
+//! End-of-day report tests
+//!
+//! Regenerates reports through the admin API and checks the files written to
+//! a local directory and to a stub S3-compatible endpoint.
+
+mod common;
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use ::common::decimal::{Price, Quantity};
+use ::common::model::order::{Order, OrderType, Side, Status, TimeInForce};
+use api_gateway::report::{ReportConfig, ReportField, ReportFormat, ReportSink, S3Config};
+use axum::body::Bytes;
+use axum::http::{HeaderMap, StatusCode, Uri};
+use axum::routing::put;
+use axum::Router;
+use chrono::{Days, Utc};
+use common::{admin_config, state, Gateway, MARKET};
+use serde_json::Value;
+use uuid::Uuid;
+
+impl Gateway {
+    fn setup(reports: ReportConfig) -> Self {
+        Self::new(state().with_reports(reports), &admin_config())
+    }
+
+    /// Rest a bid, hit it and cancel a second bid: three placements, a maker fill, a trade and a cancel
+    fn trade(&self) {
+        let (maker, taker) = (Uuid::new_v4(), Uuid::new_v4());
+        self.state.matching_engine.place_order(limit_order(maker, Side::Buy, 100)).unwrap();
+        self.state.matching_engine.place_order(limit_order(taker, Side::Sell, 100)).unwrap();
+
+        let cancelled = limit_order(maker, Side::Buy, 90);
+        let cancelled_id = cancelled.id;
+        self.state.matching_engine.place_order(cancelled).unwrap();
+        self.state.matching_engine.cancel_order(cancelled_id).unwrap();
+    }
+
+    async fn regenerate(&self, date: &str) -> (StatusCode, Value) {
+        self.admin("POST", &format!("/admin/reports/{}", date), None).await
+    }
+
+    /// Regenerate today's reports once the journal has caught up with the engine
+    async fn regenerate_today(&self, records: u64) -> Value {
+        let today = Utc::now().date_naive().to_string();
+        for _ in 0..100 {
+            let (status, body) = self.regenerate(&today).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            if body["data"]["files"][0]["records"] == records {
+                return body["data"].clone();
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("journal never reached {} records", records);
+    }
+}
+
+fn limit_order(user_id: Uuid, side: Side, price: i64) -> Order {
+    Order {
+        id: Uuid::new_v4(),
+        user_id,
+        market: MARKET.to_string(),
+        side,
+        order_type: OrderType::Limit,
+        price: Some(Price::new(price, 0)),
+        quantity: Quantity::new(1, 0),
+        remaining_quantity: Quantity::new(1, 0),
+        filled_quantity: Quantity::ZERO,
+        status: Status::New,
+        time_in_force: TimeInForce::GTC,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
+    }
+}
+
+fn temp_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("zavora-reports-{}", Uuid::new_v4()))
+}
+
+#[tokio::test]
+async fn test_reports_are_written_per_market_and_format() {
+    let dir = temp_dir();
+    let gateway = Gateway::setup(ReportConfig {
+        sink: Some(ReportSink::Directory(dir.clone())),
+        formats: vec![ReportFormat::Csv, ReportFormat::Json],
+        fields: vec![ReportField::Event, ReportField::Market, ReportField::Price, ReportField::BuyerId],
+        ..ReportConfig::default()
+    });
+    gateway.trade();
+
+    let summary = gateway.regenerate_today(6).await;
+    assert_eq!(summary["files"].as_array().unwrap().len(), 2);
+
+    let day = dir.join(Utc::now().date_naive().to_string());
+    let csv = std::fs::read_to_string(day.join("BTC-USD.csv")).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "event,market,price,buyer_id");
+    assert_eq!(lines.len(), 7);
+    assert!(lines[1].starts_with("order_placed,BTC/USD,100,"));
+    assert!(lines.iter().any(|line| line.starts_with("trade,BTC/USD,100,") && !line.ends_with(',')));
+    assert!(lines.iter().any(|line| line.starts_with("order_updated,BTC/USD,100,")));
+    assert!(lines.iter().any(|line| line.starts_with("order_cancelled,")));
+
+    let json: Value = serde_json::from_slice(&std::fs::read(day.join("BTC-USD.json")).unwrap()).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 6);
+    assert_eq!(json[0]["event"], "order_placed");
+    assert_eq!(json[0]["buyer_id"], Value::Null);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_regeneration_requires_configuration_and_journal() {
+    let gateway = Gateway::setup(ReportConfig::default());
+    let (status, _) = gateway.regenerate(&Utc::now().date_naive().to_string()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let dir = temp_dir();
+    let gateway = Gateway::setup(ReportConfig {
+        sink: Some(ReportSink::Directory(dir.clone())),
+        retention_days: 1,
+        ..ReportConfig::default()
+    });
+    let too_old = Utc::now().date_naive() - Days::new(2);
+    let (status, _) = gateway.regenerate(&too_old.to_string()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = gateway.regenerate("not-a-date").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(!dir.exists());
+}
+
+#[tokio::test]
+async fn test_reports_upload_to_s3_with_signature() {
+    type Uploads = Arc<Mutex<Vec<(String, HeaderMap, Bytes)>>>;
+    let uploads: Uploads = Arc::default();
+
+    let store = Router::new().route(
+        "/*key",
+        put({
+            let uploads = uploads.clone();
+            move |uri: Uri, headers: HeaderMap, body: Bytes| async move {
+                uploads.lock().unwrap().push((uri.path().to_string(), headers, body));
+                StatusCode::OK
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, store).await.unwrap() });
+
+    let gateway = Gateway::setup(ReportConfig {
+        sink: Some(ReportSink::S3(S3Config {
+            endpoint,
+            bucket: "compliance".to_string(),
+            prefix: "daily/".to_string(),
+            region: "eu-west-1".to_string(),
+            access_key: "AKIDEXAMPLE".to_string(),
+            secret_key: "secret".to_string(),
+        })),
+        ..ReportConfig::default()
+    });
+    gateway.trade();
+
+    let summary = gateway.regenerate_today(6).await;
+    let today = Utc::now().date_naive();
+    assert_eq!(summary["files"][0]["location"], format!("s3://compliance/daily/{}/BTC-USD.csv", today));
+
+    let uploads = uploads.lock().unwrap();
+    let (path, headers, body) = uploads.last().unwrap();
+    assert_eq!(path, &format!("/compliance/daily/{}/BTC-USD.csv", today));
+    assert_eq!(headers["content-type"], "text/csv");
+    let authorization = headers["authorization"].to_str().unwrap();
+    assert!(authorization.starts_with(&format!(
+        "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/{}/eu-west-1/s3/aws4_request",
+        today.format("%Y%m%d")
+    )));
+    assert!(authorization.contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date"));
+    assert!(String::from_utf8_lossy(body).starts_with("event,timestamp,market,"));
+}
@@ -0,0 +1,174 @@
+//! Request limit tests
+//!
+//! Sends oversized and slow REST requests through the router, and oversized
+//! and too frequent WebSocket messages to a served gateway, with small limits.
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use api_gateway::config::AppConfig;
+use api_gateway::limits::RequestLimits;
+use api_gateway::ws::handler::ws_handler;
+use axum::body::{Body, Bytes};
+use axum::http::{header, Request, StatusCode};
+use axum::routing::get;
+use axum::Router;
+use common::{serve, state, Gateway, MARKET};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::tungstenite::Message;
+
+fn limits() -> RequestLimits {
+    RequestLimits {
+        max_body_bytes: 256,
+        max_headers: 8,
+        max_header_bytes: 512,
+        timeout: Duration::from_millis(200),
+        ws_max_message_bytes: 1024,
+        ws_max_frame_bytes: 1024,
+        ws_messages_per_second: 5,
+        ws_method_quotas: BTreeMap::from([("getOrderBook".to_string(), 2)]),
+        ws_max_refused_per_minute: 4,
+    }
+}
+
+impl Gateway {
+    /// Gateway over BTC/USD with small limits
+    fn limited() -> Self {
+        Self::new(state().with_limits(limits()), &AppConfig::default())
+    }
+}
+
+/// Request creating an account, awaiting its body
+fn create_account() -> axum::http::request::Builder {
+    Request::builder().method("POST").uri("/accounts").header(header::CONTENT_TYPE, "application/json")
+}
+
+#[tokio::test]
+async fn test_oversized_requests_are_refused() {
+    let gateway = Gateway::limited();
+
+    // Within the limits
+    let (status, _, _) = gateway.call(create_account().body(Body::from("{}")).unwrap()).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    // Declared too large
+    let padding = json!({ "padding": "x".repeat(300) }).to_string();
+    let request = create_account()
+        .header(header::CONTENT_LENGTH, padding.len())
+        .body(Body::from(padding.clone()))
+        .unwrap();
+    let (status, _, body) = gateway.call(request).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["error"]["code"], "payload_too_large");
+
+    // Streamed without a length
+    let chunks = padding.into_bytes().chunks(64).map(|chunk| Ok::<_, std::io::Error>(Bytes::copy_from_slice(chunk))).collect::<Vec<_>>();
+    let request = create_account().body(Body::from_stream(futures::stream::iter(chunks))).unwrap();
+    let (status, _, body) = gateway.call(request).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["error"]["code"], "payload_too_large");
+
+    // Too many headers, then too many header bytes
+    let mut request = Request::builder().uri("/markets");
+    for i in 0..10 {
+        request = request.header(format!("x-extra-{}", i), "1");
+    }
+    let (status, _, body) = gateway.call(request.body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+    assert_eq!(body["error"]["code"], "headers_too_large");
+
+    let request = Request::builder().uri("/markets").header("x-extra", "x".repeat(600)).body(Body::empty()).unwrap();
+    let (status, _, _) = gateway.call(request).await;
+    assert_eq!(status, StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_slow_clients_time_out() {
+    let gateway = Gateway::limited();
+
+    let slow = futures::stream::once(async {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        Ok::<_, std::io::Error>(Bytes::from_static(b"{}"))
+    });
+    let request = create_account().body(Body::from_stream(slow)).unwrap();
+    let (status, _, body) = gateway.call(request).await;
+    assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+    assert_eq!(body["error"]["code"], "request_timeout");
+}
+
+/// Address of a served WebSocket endpoint
+async fn serve_ws() -> std::net::SocketAddr {
+    let gateway = Gateway::limited();
+    serve(Router::new().route("/ws", get(ws_handler)).with_state(gateway.state)).await
+}
+
+/// The first `count` responses, or those until the connection closes
+async fn responses(socket: &mut WebSocketStream<MaybeTlsStream<TcpStream>>, count: usize) -> Vec<Value> {
+    let mut responses = Vec::new();
+    while responses.len() < count {
+        match tokio::time::timeout(Duration::from_secs(5), socket.next()).await.unwrap() {
+            Some(Ok(Message::Text(text))) => responses.push(serde_json::from_str(&text).unwrap()),
+            None | Some(Err(_)) | Some(Ok(Message::Close(_))) => break,
+            Some(Ok(_)) => continue,
+        }
+    }
+    responses
+}
+
+#[tokio::test]
+async fn test_oversized_websocket_messages_close_the_connection() {
+    let addr = serve_ws().await;
+
+    let (mut socket, _) = connect_async(format!("ws://{}/ws", addr)).await.expect("Failed to connect");
+    let ping = json!({ "id": "1", "method": "ping", "params": {} }).to_string();
+    socket.send(Message::Text(ping)).await.unwrap();
+    let reply = tokio::time::timeout(Duration::from_secs(5), socket.next()).await.unwrap();
+    assert!(matches!(reply, Some(Ok(Message::Text(_)))), "{:?}", reply);
+
+    let oversized = json!({ "id": "2", "method": "ping", "params": { "padding": "x".repeat(2048) } }).to_string();
+    socket.send(Message::Text(oversized)).await.unwrap();
+    let closed = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match socket.next().await {
+                None | Some(Err(_)) | Some(Ok(Message::Close(_))) => break,
+                Some(Ok(_)) => continue,
+            }
+        }
+    })
+    .await;
+    assert!(closed.is_ok(), "connection stayed open");
+}
+
+#[tokio::test]
+async fn test_websocket_requests_over_quota_are_refused_then_the_connection_closed() {
+    let addr = serve_ws().await;
+    let (mut socket, _) = connect_async(format!("ws://{}/ws", addr)).await.expect("Failed to connect");
+
+    // The third order book call within a minute is over the method's quota
+    for id in 1..=3 {
+        let request = json!({ "id": id.to_string(), "method": "getOrderBook", "params": { "market": MARKET } });
+        socket.send(Message::Text(request.to_string())).await.unwrap();
+    }
+    let replies = responses(&mut socket, 3).await;
+    assert!(replies[0]["error"].is_null(), "{}", replies[0]);
+    assert!(replies[1]["error"].is_null(), "{}", replies[1]);
+    assert_eq!(replies[2]["id"], "3");
+    assert_eq!(replies[2]["error"]["code"], 429);
+    assert!(replies[2]["error"]["message"].as_str().unwrap().contains("getOrderBook"));
+
+    // A burst over the message rate is refused until the connection is closed
+    for id in 4..=20 {
+        let ping = json!({ "id": id.to_string(), "method": "ping", "params": {} });
+        socket.send(Message::Text(ping.to_string())).await.unwrap();
+    }
+    let replies = responses(&mut socket, usize::MAX).await;
+    let refused: Vec<&Value> = replies.iter().filter(|reply| reply["error"]["code"] == 429).collect();
+    assert_eq!(refused.len(), 3, "{:?}", replies);
+    assert!(refused[0]["error"]["message"].as_str().unwrap().contains("messages per second"));
+    assert_eq!(replies.last().unwrap()["error"]["code"], 429);
+}
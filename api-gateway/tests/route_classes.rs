@@ -0,0 +1,92 @@
+//! REST route class tests
+//!
+//! Checks that public market data is open, cacheable and limited per client,
+//! and that trading and account endpoints require the caller's own API key.
+
+mod common;
+
+use api_gateway::config::AppConfig;
+use axum::http::{header, StatusCode};
+use common::{state, Gateway, MARKET};
+use serde_json::json;
+
+#[tokio::test]
+async fn test_public_market_data_needs_no_key() {
+    let gateway = Gateway::start();
+
+    let (status, headers, _) = gateway.call(Gateway::request("GET", "/markets", None, None)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CACHE_CONTROL], "public, max-age=1");
+    assert_eq!(headers["x-ratelimit-limit"], "1200");
+    assert_eq!(headers["x-ratelimit-remaining"], "1199");
+
+    // Analytics are public too, and only exist once a market has data
+    let (status, _) = gateway.send("GET", "/markets/BTC%2FUSD/analytics", None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = gateway.send("GET", "/markets/BTC%2FUSD/analytics?depth_bps=20000", None, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // So is order book history, which needs a time and a snapshot taken by then
+    let (status, _) = gateway.send("GET", "/markets/BTC%2FUSD/order-book/history?at=2025-01-01T00:00:00Z", None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = gateway.send("GET", "/markets/BTC%2FUSD/order-book/history", None, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_private_routes_require_api_key() {
+    let gateway = Gateway::start();
+    let (account_id, _) = gateway.create_account().await;
+
+    let uri = format!("/accounts/{}/balances", account_id);
+    let (status, _) = gateway.send("GET", &uri, None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = gateway.send("GET", &uri, Some("zk_invalid"), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_api_key_grants_own_account_only() {
+    let gateway = Gateway::start();
+    let (account_id, key) = gateway.create_account().await;
+    let (other_id, _) = gateway.create_account().await;
+
+    let request = Gateway::request("GET", &format!("/accounts/{}/balances", account_id), Some(&key), None);
+    let (status, headers, _) = gateway.call(request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CACHE_CONTROL], "no-store");
+
+    let (status, _) = gateway.send("GET", &format!("/accounts/{}/balances", other_id), Some(&key), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let order = json!({
+        "user_id": other_id,
+        "market": MARKET,
+        "side": "Buy",
+        "order_type": "Limit",
+        "price": "100",
+        "quantity": "1",
+    });
+    let (status, _) = gateway.send("POST", "/orders", Some(&key), Some(order)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_rate_limit_returns_retry_after() {
+    let config = AppConfig {
+        public_rate_limit: 2,
+        ..AppConfig::default()
+    };
+    let gateway = Gateway::new(state(), &config);
+
+    for _ in 0..2 {
+        let (status, _) = gateway.send("GET", "/markets", None, None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, headers, _) = gateway.call(Gateway::request("GET", "/markets", None, None)).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(headers["x-ratelimit-remaining"], "0");
+    assert!(headers.contains_key(header::RETRY_AFTER));
+}
@@ -0,0 +1,67 @@
+//! Runtime tests
+//!
+//! Builds the gateway the binaries serve from a default configuration and
+//! drives its router without binding a port.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use api_gateway::config::AppConfig;
+use api_gateway::runtime::{spot_market, Runtime};
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use serde_json::Value;
+use tower::ServiceExt;
+
+async fn get(server: &api_gateway::runtime::Server, uri: &str) -> (StatusCode, Value) {
+    let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    let response = server.router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_runtime_serves_the_default_market_after_startup_tasks() {
+    let started = Arc::new(AtomicBool::new(false));
+    let seen = started.clone();
+    let server = Runtime::from_config(AppConfig::default())
+        .on_start(move |state| async move {
+            // The state is complete by the time startup work runs
+            assert_eq!(state.markets.len(), 1);
+            seen.store(true, Ordering::SeqCst);
+            Ok(())
+        })
+        .build()
+        .await
+        .unwrap();
+    assert!(started.load(Ordering::SeqCst));
+
+    let (status, body) = get(&server, "/api/v1/markets").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"][0]["symbol"], "BTC/USD");
+
+    let (status, _) = get(&server, "/api/v1/health").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_runtime_trades_the_markets_it_is_given() {
+    let server = Runtime::from_config(AppConfig::default())
+        .with_market(spot_market("ETH/USD").unwrap())
+        .build()
+        .await
+        .unwrap();
+
+    let (_, body) = get(&server, "/api/v1/markets").await;
+    let symbols: Vec<&str> = body["data"].as_array().unwrap().iter().map(|market| market["symbol"].as_str().unwrap()).collect();
+    assert_eq!(symbols, vec!["ETH/USD"]);
+    assert_eq!(server.state.matching_engine.markets(), vec!["ETH/USD".to_string()]);
+
+    // A failing startup task fails the build
+    let failed = Runtime::from_config(AppConfig::default())
+        .on_start(|_| async { Err(common::error::Error::Internal("not ready".to_string())) })
+        .build()
+        .await;
+    assert!(failed.is_err());
+}
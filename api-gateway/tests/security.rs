@@ -0,0 +1,143 @@
+//! Security event and session tests
+//!
+//! Uses an account's keys from several addresses and checks the security
+//! events recorded, the sessions listed and that ending a session or rotating
+//! a key cuts off the old secret.
+
+mod common;
+
+use api_gateway::config::AppConfig;
+use axum::http::StatusCode;
+use common::{state_for, Gateway};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    fn setup() -> Self {
+        Self::new(state_for(Vec::new()), &AppConfig::default())
+    }
+
+    async fn account(&self) -> (Uuid, String) {
+        let (_, body) = self.send_from(Some("198.51.100.1"), "POST", "/accounts", "", Some(json!({}))).await;
+        let id: Uuid = body["data"]["id"].as_str().unwrap().parse().unwrap();
+        (id, body["data"]["api_key"].as_str().unwrap().to_string())
+    }
+
+    async fn events(&self, id: Uuid, key: &str) -> Vec<Value> {
+        let (status, body) = self.send_from(Some("198.51.100.1"), "GET", &format!("/accounts/{}/security-events", id), key, None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["data"].as_array().unwrap().clone()
+    }
+}
+
+fn kinds(events: &[Value]) -> Vec<&str> {
+    events.iter().map(|event| event["kind"].as_str().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn test_logins_address_changes_and_rejections_are_recorded() {
+    let gateway = Gateway::setup();
+    let (id, key) = gateway.account().await;
+    let balances = format!("/accounts/{}/balances", id);
+
+    // The key's first use is a login; only an address it has not used from is a change
+    for client in ["198.51.100.1", "198.51.100.1", "203.0.113.5", "198.51.100.1"] {
+        assert_eq!(gateway.send_from(Some(client), "GET", &balances, &key, None).await.0, StatusCode::OK);
+    }
+    let events = gateway.events(id, &key).await;
+    assert_eq!(kinds(&events), ["ip_changed", "login", "api_key_created"]);
+    assert_eq!(events[0]["ip_address"], "203.0.113.5");
+    assert_eq!(events[0]["details"]["previous_address"], "198.51.100.1");
+    assert_eq!(events[2]["details"]["issued_by"], "signup");
+
+    // Keys used outside their allowlist or scopes are recorded against the account
+    let (_, body) = gateway.send_from(
+        Some("198.51.100.1"),
+        "POST",
+        &format!("/accounts/{}/api-keys", id),
+        &key,
+        Some(json!({ "scopes": ["read"], "allowed_ips": ["10.0.0.0/8"] }))).await;
+    let office = body["data"]["api_key"].as_str().unwrap().to_string();
+    assert_eq!(gateway.send_from(Some("192.0.2.1"), "GET", &balances, &office, None).await.0, StatusCode::FORBIDDEN);
+    let deposit = json!({ "asset": "USD", "amount": "10" });
+    let (status, _) = gateway.send_from(Some("10.0.0.1"), "POST", &format!("/accounts/{}/deposit", id), &office, Some(deposit)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let events = gateway.events(id, &key).await;
+    assert_eq!(kinds(&events)[..4], ["auth_failed", "login", "auth_failed", "api_key_created"]);
+    assert_eq!(events[0]["details"]["code"], "insufficient_scope");
+    assert_eq!(events[2]["details"]["code"], "ip_not_allowed");
+    assert_eq!(events[2]["ip_address"], "192.0.2.1");
+    assert_eq!(events[0]["key_prefix"], office[..8]);
+
+    // Another account cannot read them
+    let (_, other) = gateway.account().await;
+    let (status, _) = gateway.send_from(Some("198.51.100.1"), "GET", &format!("/accounts/{}/security-events", id), &other, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_sessions_can_be_listed_and_ended() {
+    let gateway = Gateway::setup();
+    let (id, key) = gateway.account().await;
+    let (_, body) = gateway.send_from(Some("198.51.100.1"), "POST", &format!("/accounts/{}/api-keys", id), &key, Some(json!({ "scopes": ["read"] }))).await;
+    let bot = body["data"]["api_key"].as_str().unwrap().to_string();
+    let bot_id = body["data"]["key"]["id"].as_str().unwrap().to_string();
+
+    let balances = format!("/accounts/{}/balances", id);
+    for client in ["203.0.113.5", "203.0.113.6"] {
+        assert_eq!(gateway.send_from(Some(client), "GET", &balances, &bot, None).await.0, StatusCode::OK);
+    }
+
+    let (status, body) = gateway.send_from(Some("198.51.100.1"), "GET", &format!("/accounts/{}/sessions", id), &key, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let sessions = body["data"].as_array().unwrap();
+    assert_eq!(sessions.len(), 2);
+    let current = sessions.iter().find(|session| session["current"] == true).unwrap();
+    let bot_session = sessions.iter().find(|session| session["id"] == bot_id.as_str()).unwrap();
+    assert_ne!(current["id"], bot_session["id"]);
+    assert_eq!(bot_session["requests"], 2);
+    assert_eq!(bot_session["ip_address"], "203.0.113.6");
+    assert_eq!(bot_session["addresses"], json!(["203.0.113.5", "203.0.113.6"]));
+
+    // Ending the session revokes its key
+    let session = format!("/accounts/{}/sessions/{}", id, bot_id);
+    let (status, _) = gateway.send_from(Some("198.51.100.1"), "DELETE", &session, &key, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = gateway.send_from(Some("203.0.113.6"), "GET", &balances, &bot, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["code"], "invalid_api_key");
+    assert_eq!(gateway.send_from(Some("198.51.100.1"), "DELETE", &session, &key, None).await.0, StatusCode::NOT_FOUND);
+
+    let events = gateway.events(id, &key).await;
+    assert_eq!(events[0]["kind"], "session_revoked");
+    assert_eq!(events[0]["details"]["last_address"], "203.0.113.6");
+}
+
+#[tokio::test]
+async fn test_rotated_keys_get_a_new_secret() {
+    let gateway = Gateway::setup();
+    let (id, key) = gateway.account().await;
+    let (_, body) = gateway.send_from(Some("198.51.100.1"), "GET", &format!("/accounts/{}/api-keys", id), &key, None).await;
+    let key_id = body["data"][0]["id"].as_str().unwrap().to_string();
+
+    let rotate = format!("/accounts/{}/api-keys/{}/rotate", id, key_id);
+    let (status, body) = gateway.send_from(Some("198.51.100.1"), "POST", &rotate, &key, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let rotated = body["data"]["api_key"].as_str().unwrap().to_string();
+    assert_ne!(rotated, key);
+    assert_eq!(body["data"]["key"]["id"], key_id.as_str());
+    assert_eq!(body["data"]["key"]["scopes"], json!(["read", "trade", "withdraw"]));
+
+    let balances = format!("/accounts/{}/balances", id);
+    assert_eq!(gateway.send_from(Some("198.51.100.1"), "GET", &balances, &key, None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(gateway.send_from(Some("198.51.100.1"), "GET", &balances, &rotated, None).await.0, StatusCode::OK);
+
+    let events = gateway.events(id, &rotated).await;
+    assert_eq!(events[0]["kind"], "api_key_rotated");
+    assert_eq!(events[0]["details"]["previous_prefix"], key[..8]);
+    assert_eq!(events[0]["key_prefix"], rotated[..8]);
+
+    let unknown = format!("/accounts/{}/api-keys/{}/rotate", id, Uuid::new_v4());
+    assert_eq!(gateway.send_from(Some("198.51.100.1"), "POST", &unknown, &rotated, None).await.0, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,134 @@
+//! Asynchronous settlement tests
+//!
+//! Runs jobs through the pipeline directly to check ordering and backpressure,
+//! then trades through the REST API with settlement in the background.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use api_gateway::config::AppConfig;
+use api_gateway::pipeline::{PipelineConfig, SettlementPipeline};
+use axum::http::StatusCode;
+use common::{state, Gateway, MARKET};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+fn pipeline(workers: usize, capacity: usize) -> Arc<SettlementPipeline> {
+    SettlementPipeline::new(PipelineConfig { workers, capacity, ..PipelineConfig::default() })
+}
+
+#[tokio::test]
+async fn test_jobs_of_an_account_run_in_order() {
+    let pipeline = pipeline(4, 16);
+    let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    // The slow job on both accounts holds back later jobs of either account
+    for (accounts, name, delay) in [
+        (vec![first, second], "both", 50),
+        (vec![first], "first", 0),
+        (vec![second], "second", 0),
+    ] {
+        let log = log.clone();
+        pipeline.submit(accounts, async move {
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            log.lock().unwrap().push(name);
+        }).await;
+    }
+
+    pipeline.flush(first).await;
+    assert_eq!(log.lock().unwrap()[..2], ["both", "first"]);
+    pipeline.flush(second).await;
+    assert_eq!(log.lock().unwrap().len(), 3);
+    assert_eq!(log.lock().unwrap()[0], "both");
+}
+
+#[tokio::test]
+async fn test_unrelated_accounts_run_concurrently() {
+    let pipeline = pipeline(2, 16);
+    let (blocked, free) = (Uuid::new_v4(), Uuid::new_v4());
+    let (release, wait) = oneshot::channel::<()>();
+
+    pipeline.submit([blocked], async move {
+        let _ = wait.await;
+    }).await;
+    pipeline.submit([free], async {}).await;
+
+    tokio::time::timeout(Duration::from_secs(1), pipeline.flush(free)).await
+        .expect("job of another account was held back");
+    assert_eq!(pipeline.queued(), 1);
+
+    release.send(()).unwrap();
+    pipeline.flush(blocked).await;
+}
+
+#[tokio::test]
+async fn test_full_queue_holds_back_submissions() {
+    let pipeline = pipeline(1, 1);
+    let (release, wait) = oneshot::channel::<()>();
+
+    pipeline.submit([Uuid::new_v4()], async move {
+        let _ = wait.await;
+    }).await;
+
+    let blocked = tokio::time::timeout(Duration::from_millis(50), pipeline.submit([Uuid::new_v4()], async {})).await;
+    assert!(blocked.is_err(), "submitted past capacity");
+
+    release.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(1), pipeline.submit([Uuid::new_v4()], async {})).await
+        .expect("queue did not drain");
+}
+
+impl Gateway {
+    fn setup() -> Self {
+        let state = state().with_settlement_pipeline(PipelineConfig { workers: 2, capacity: 8, ..PipelineConfig::default() });
+        Self::new(state, &AppConfig::default())
+    }
+
+    async fn order(&self, account_id: Uuid, key: &str, side: &str, quantity: &str) -> Value {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": side,
+            "order_type": "Limit",
+            "price": "100",
+            "quantity": quantity,
+        });
+        let (status, body) = self.send("POST", "/orders", Some(key), Some(order)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        body["data"].clone()
+    }
+
+    async fn balance(&self, account_id: Uuid, key: &str, asset: &str) -> (String, String) {
+        let (status, body) = self.send("GET", &format!("/accounts/{}/balances", account_id), Some(key), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let balance = body["data"].as_array().unwrap().iter().find(|b| b["asset"] == asset).unwrap().clone();
+        let amount = |field: &str| balance[field].as_str().unwrap().parse::<Decimal>().unwrap().normalize().to_string();
+        (amount("available"), amount("locked"))
+    }
+}
+
+#[tokio::test]
+async fn test_reads_after_placement_see_settled_trades() {
+    let gateway = Gateway::setup();
+    let (buyer, buyer_key) = gateway.account_with("USD", "1000").await;
+    let (seller, seller_key) = gateway.account_with("BTC", "5").await;
+
+    let bid = gateway.order(buyer, &buyer_key, "Buy", "3").await;
+    let placed = gateway.order(seller, &seller_key, "Sell", "1").await;
+    assert_eq!(placed["trades"].as_array().unwrap().len(), 1);
+
+    assert_eq!(gateway.balance(seller, &seller_key, "USD").await, ("100".to_string(), "0".to_string()));
+    assert_eq!(gateway.balance(buyer, &buyer_key, "BTC").await, ("1".to_string(), "0".to_string()));
+    assert_eq!(gateway.balance(buyer, &buyer_key, "USD").await, ("700".to_string(), "200".to_string()));
+
+    // Cancelling the partly filled bid releases what its fills left reserved
+    let bid_id = bid["order"]["id"].as_str().unwrap();
+    let (status, _) = gateway.send("DELETE", &format!("/orders/{}", bid_id), Some(&buyer_key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(gateway.balance(buyer, &buyer_key, "USD").await, ("900".to_string(), "0".to_string()));
+}
@@ -0,0 +1,98 @@
+//! Shadow market tests
+//!
+//! Mirrors a market from a local stand-in for an exchange's public REST API,
+//! then reads it through the market data endpoints and checks that it cannot
+//! be traded.
+
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use account_service::AccountService;
+use api_gateway::config::AppConfig;
+use api_gateway::shadow::ShadowConfig;
+use api_gateway::AppState;
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use common::{engine, serve, spot, Gateway, MARKET};
+use market_data::shadow::ShadowMarket;
+use market_data::MarketDataService;
+use serde_json::json;
+
+/// Serve a Binance-style depth and trades API for `BTCUSDT`, returning its base URL
+async fn exchange() -> String {
+    let app = Router::new()
+        .route("/api/v3/depth", get(|Query(query): Query<HashMap<String, String>>| async move {
+            assert_eq!(query["symbol"], "BTCUSDT");
+            assert_eq!(query["limit"], "5");
+            Json(json!({
+                "lastUpdateId": 1027024,
+                "bids": [["64999.50", "0.25"], ["64999.00", "0.00000000"]],
+                "asks": [["65000.50", "1.50"]],
+            }))
+        }))
+        .route("/api/v3/trades", get(|| async {
+            Json(json!([
+                { "id": 28457, "price": "65000.00", "qty": "0.10", "time": 1700000000000i64, "isBuyerMaker": true },
+                { "id": 28458, "price": "65000.50", "qty": "0.05", "time": 1700000001000i64, "isBuyerMaker": false },
+            ]))
+        }));
+
+    format!("http://{}", serve(app).await)
+}
+
+#[tokio::test]
+async fn test_shadow_market_mirrors_the_exchange_read_only() {
+    let shadow = ShadowConfig {
+        url: Some(exchange().await),
+        markets: vec![ShadowMarket { symbol: "BTC/USDT".to_string(), external_symbol: "BTCUSDT".to_string() }],
+        poll_interval: Duration::from_secs(5),
+        depth: 5,
+        ..ShadowConfig::default()
+    };
+    assert!(shadow.is_enabled());
+    let market_data = Arc::new(
+        MarketDataService::new().with_shadow_markets(Arc::new(shadow.fetcher().unwrap()), shadow.markets.clone()),
+    );
+    market_data.ingest_shadow_markets().await.unwrap();
+
+    let state = AppState::new(engine(&[spot(MARKET)]), Arc::new(AccountService::new()), market_data, vec![spot(MARKET)]);
+    let gateway = Gateway::new(state, &AppConfig::default());
+
+    let (status, body) = gateway.send("GET", "/markets/shadow", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let markets = body["data"].as_array().unwrap();
+    assert_eq!(markets.len(), 1);
+    assert_eq!(markets[0]["symbol"], "BTC/USDT");
+    assert_eq!(markets[0]["trades_ingested"], 2);
+    assert_eq!(markets[0]["last_trade_id"], 28458);
+
+    // The book without the emptied level, and both trades with their taker
+    let (status, body) = gateway.send("GET", "/markets/BTC%2FUSDT/order-book", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["bids"].as_array().unwrap().len(), 1);
+    let (_, body) = gateway.send("GET", "/markets/BTC%2FUSDT/trades", None, None).await;
+    let sides: Vec<&str> = body["data"]["trades"].as_array().unwrap().iter().map(|trade| trade["taker_side"].as_str().unwrap()).collect();
+    assert_eq!(sides.len(), 2);
+    assert!(sides.contains(&"sell") && sides.contains(&"buy"));
+
+    // Shadow markets are not listed for trading and refuse orders
+    let (_, body) = gateway.send("GET", "/markets", None, None).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    let (account, key) = gateway.create_account().await;
+    let order = json!({
+        "user_id": account,
+        "market": "BTC/USDT",
+        "side": "Buy",
+        "order_type": "Limit",
+        "price": "65000",
+        "quantity": "0.01",
+    });
+    let (status, body) = gateway.send("POST", "/orders", Some(&key), Some(order)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]["message"].as_str().unwrap().contains("read-only"));
+}
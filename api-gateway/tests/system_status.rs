@@ -0,0 +1,136 @@
+//! System channel tests
+//!
+//! Publishes announcements through the admin API, moves a market between
+//! sessions and reports component checks, reading what subscribers of the
+//! `system` channel receive.
+
+mod common;
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use ::common::model::market::{SessionState, TradingSchedule};
+use api_gateway::system::ComponentStatus;
+use api_gateway::ws::message::{Notification, NotificationPayload, ProtocolVersion, SystemEvent};
+use axum::http::StatusCode;
+use common::{ADMIN_KEY, Gateway, MARKET};
+use market_data::channel::Topic;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Data of a received system event, encoded as a version 2 notification
+fn decode(received: Result<Arc<dyn Any + Send + Sync>, impl Debug>) -> Value {
+    let message = received.expect("system event");
+    let event = message.downcast_ref::<SystemEvent>().expect("system event type").clone();
+    let text = Notification::new(Uuid::new_v4(), NotificationPayload::System(event))
+        .encode(&Topic::System, ProtocolVersion::V2)
+        .unwrap();
+    let notification: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(notification["params"]["channel"], "system");
+    notification["params"]["data"].clone()
+}
+
+#[tokio::test]
+async fn test_operators_publish_announcements() {
+    let gateway = Gateway::start_admin();
+    let events = gateway.state.market_data_service.channel().subscribe::<SystemEvent>(Topic::System).await;
+
+    let maintenance = json!({
+        "kind": "maintenance",
+        "severity": "warning",
+        "title": "Database upgrade",
+        "message": "Trading pauses for ten minutes",
+        "starts_at": "2030-01-01T02:00:00Z",
+        "ends_at": "2030-01-01T02:10:00Z",
+    });
+    let (status, _) = gateway.send("POST", "/admin/announcements", None, Some(maintenance.clone())).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = gateway.send("POST", "/admin/announcements", Some(ADMIN_KEY), Some(json!({ "title": " " }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let backwards = json!({ "title": "Backwards", "starts_at": "2030-01-01T02:00:00Z", "ends_at": "2030-01-01T01:00:00Z" });
+    let (status, _) = gateway.send("POST", "/admin/announcements", Some(ADMIN_KEY), Some(backwards)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(events.try_recv().is_err());
+
+    let (status, body) = gateway.send("POST", "/admin/announcements", Some(ADMIN_KEY), Some(maintenance)).await;
+    assert_eq!(status, StatusCode::OK);
+    let id = body["data"]["id"].clone();
+
+    let event = decode(events.try_recv());
+    assert_eq!(event["type"], "announcement");
+    assert_eq!(event["id"], id);
+    assert_eq!(event["kind"], "maintenance");
+    assert_eq!(event["severity"], "warning");
+    assert_eq!(event["ends_at"], "2030-01-01T02:10:00Z");
+
+    // Defaults to a general notice for information
+    let (status, _) = gateway.send("POST", "/admin/announcements", Some(ADMIN_KEY), Some(json!({ "title": "Welcome" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let event = decode(events.try_recv());
+    assert_eq!(event["kind"], "general");
+    assert_eq!(event["severity"], "info");
+
+    // Clients connecting later can catch up, newest first
+    let (status, body) = gateway.send("GET", "/system/announcements?limit=5", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let titles: Vec<_> = body["data"].as_array().unwrap().iter().map(|a| a["title"].clone()).collect();
+    assert_eq!(titles, [json!("Welcome"), json!("Database upgrade")]);
+
+    let (_, body) = gateway.send("GET", "/admin/audit", Some(ADMIN_KEY), None).await;
+    assert_eq!(body["data"][1]["action"], "announcement.published");
+    assert_eq!(body["data"][1]["details"]["id"], id);
+}
+
+#[tokio::test]
+async fn test_market_session_changes_are_published() {
+    let gateway = Gateway::start_admin();
+    let events = gateway.state.market_data_service.channel().subscribe::<SystemEvent>(Topic::System).await;
+
+    // Trading only in the morning, so the market closes by the afternoon
+    let schedule: TradingSchedule = serde_json::from_value(json!({ "open": "00:00:00", "close": "06:00:00" })).unwrap();
+    gateway.state.matching_engine.set_trading_schedule(MARKET, Some(schedule)).unwrap();
+    let now = chrono::Utc::now().date_naive().and_hms_opt(12, 0, 0).unwrap().and_utc();
+    let changes = api_gateway::session::update_sessions(&gateway.state, now).await;
+    assert_eq!(changes[0].state, SessionState::Closed);
+
+    let event = decode(events.try_recv());
+    assert_eq!(event, json!({
+        "type": "market_status",
+        "market": MARKET,
+        "previous": "Open",
+        "state": "Closed",
+        "timestamp": now,
+    }));
+
+    // Nothing is published while the session stays the same
+    api_gateway::session::update_sessions(&gateway.state, now).await;
+    assert!(events.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_component_status_changes_are_published() {
+    let gateway = Gateway::start_admin();
+    let events = gateway.state.market_data_service.channel().subscribe::<SystemEvent>(Topic::System).await;
+    let system = &gateway.state.system;
+
+    // Healthy from the start is not news
+    assert!(!system.report_component("account_service", true).await);
+    assert!(events.try_recv().is_err());
+
+    assert!(system.report_component("account_service", false).await);
+    assert!(!system.report_component("account_service", false).await);
+    assert_eq!(system.component("account_service"), Some(ComponentStatus::Down));
+    let event = decode(events.try_recv());
+    assert_eq!(event["type"], "component_status");
+    assert_eq!(event["component"], "account_service");
+    assert_eq!(event["status"], "down");
+    assert!(events.try_recv().is_err());
+
+    assert!(system.report_component("account_service", true).await);
+    assert_eq!(decode(events.try_recv())["status"], "up");
+
+    // A component down on its first check is reported straight away
+    assert!(system.report_component("market_data_service", false).await);
+    assert_eq!(decode(events.try_recv())["component"], "market_data_service");
+}
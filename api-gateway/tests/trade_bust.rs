@@ -0,0 +1,114 @@
+//! Trade bust tests
+//!
+//! Matches two accounts over REST, busts the trade as an admin and checks
+//! the balances, audit trail, private events and public correction.
+
+mod common;
+
+use ::common::decimal::{dec, Amount};
+use api_gateway::ws::message::AccountEvent;
+use axum::http::StatusCode;
+use common::{ADMIN_KEY, Gateway, MARKET};
+use market_data::channel::Topic;
+use market_data::{CorrectionKind, TradeCorrection};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    /// Place a limit order, returning its ID
+    async fn order(&self, account_id: Uuid, key: &str, side: &str, price: &str, quantity: &str) -> String {
+        let order = json!({
+            "user_id": account_id,
+            "market": MARKET,
+            "side": side,
+            "order_type": "Limit",
+            "price": price,
+            "quantity": quantity,
+        });
+        let (status, body) = self.send("POST", "/orders", Some(key), Some(order)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        body["data"]["order"]["id"].as_str().unwrap().to_string()
+    }
+
+    /// Available balance of an asset
+    async fn available(&self, account_id: Uuid, key: &str, asset: &str) -> Amount {
+        let (_, body) = self.send("GET", &format!("/accounts/{}/balances", account_id), Some(key), None).await;
+        let balance = body["data"].as_array().unwrap().iter().find(|balance| balance["asset"] == asset).unwrap();
+        balance["available"].as_str().unwrap().parse().unwrap()
+    }
+
+    async fn bust(&self, trade_id: &str, key: &str, reason: &str) -> (StatusCode, Value) {
+        self.send("POST", &format!("/admin/trades/{}/bust", trade_id), Some(key), Some(json!({ "reason": reason }))).await
+    }
+}
+
+#[tokio::test]
+async fn test_bust_reverses_the_trade_and_tells_everyone() {
+    let gateway = Gateway::start_admin();
+    let (seller, seller_key) = gateway.trader().await;
+    let (buyer, buyer_key) = gateway.trader().await;
+
+    gateway.order(seller, &seller_key, "Sell", "100", "0.5").await;
+    let bid = gateway.order(buyer, &buyer_key, "Buy", "100", "0.5").await;
+    let (_, fills) = gateway.send("GET", &format!("/orders/{}/fills", bid), Some(&buyer_key), None).await;
+    let trade_id = fills["data"][0]["trade_id"].as_str().unwrap().to_string();
+    assert_eq!(gateway.available(buyer, &buyer_key, "USD").await, dec!(950));
+    assert_eq!(gateway.state.market_data_service.get_recent_trades(MARKET, 10).len(), 1);
+
+    let channel = gateway.state.market_data_service.channel();
+    let buyer_events = channel.subscribe::<AccountEvent>(Topic::Account(buyer)).await;
+    let seller_events = channel.subscribe::<AccountEvent>(Topic::Account(seller)).await;
+    let corrections = channel.subscribe::<TradeCorrection>(Topic::TradeCorrections(MARKET.to_string())).await;
+
+    let (status, body) = gateway.bust(&trade_id, ADMIN_KEY, "erroneous price").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["trade_id"], trade_id);
+    assert_eq!(body["data"]["actor"], "admin");
+
+    // Both sides are back where they started, fees included
+    for (account_id, key) in [(buyer, &buyer_key), (seller, &seller_key)] {
+        assert_eq!(gateway.available(account_id, key, "USD").await, dec!(1000));
+        assert_eq!(gateway.available(account_id, key, "BTC").await, dec!(1));
+    }
+
+    // Each party hears of it privately, and the tape publicly
+    for (events, side) in [(buyer_events, "Buy"), (seller_events, "Sell")] {
+        let event = events.try_recv().expect("bust event");
+        match event.downcast_ref::<AccountEvent>() {
+            Some(AccountEvent::TradeBusted { trade_id: busted, side: busted_side, reason, .. }) => {
+                assert_eq!(busted.to_string(), trade_id);
+                assert_eq!(json!(busted_side), side);
+                assert_eq!(reason, "erroneous price");
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+    let correction = corrections.try_recv().expect("correction");
+    let correction = correction.downcast_ref::<TradeCorrection>().unwrap();
+    assert_eq!(correction.kind, CorrectionKind::Bust);
+    assert_eq!(correction.price, dec!(100));
+    assert!(gateway.state.market_data_service.get_recent_trades(MARKET, 10).is_empty());
+
+    // The reason is kept in both parties' audit trails
+    let (_, body) = gateway.send("GET", &format!("/admin/audit?account_id={}", buyer), Some(ADMIN_KEY), None).await;
+    assert_eq!(body["data"][0]["action"], "trade.busted");
+    assert_eq!(body["data"][0]["details"]["reason"], "erroneous price");
+}
+
+#[tokio::test]
+async fn test_only_operators_bust_a_trade_once() {
+    let gateway = Gateway::start_admin();
+    let (seller, seller_key) = gateway.trader().await;
+    let (buyer, buyer_key) = gateway.trader().await;
+
+    gateway.order(seller, &seller_key, "Sell", "100", "0.5").await;
+    let bid = gateway.order(buyer, &buyer_key, "Buy", "100", "0.5").await;
+    let (_, fills) = gateway.send("GET", &format!("/orders/{}/fills", bid), Some(&buyer_key), None).await;
+    let trade_id = fills["data"][0]["trade_id"].as_str().unwrap().to_string();
+
+    assert_eq!(gateway.bust(&trade_id, &buyer_key, "regret").await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(gateway.bust(&trade_id, ADMIN_KEY, "").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(gateway.bust(&Uuid::new_v4().to_string(), ADMIN_KEY, "typo").await.0, StatusCode::NOT_FOUND);
+    assert_eq!(gateway.bust(&trade_id, ADMIN_KEY, "typo").await.0, StatusCode::OK);
+    assert_eq!(gateway.bust(&trade_id, ADMIN_KEY, "typo").await.0, StatusCode::CONFLICT);
+}
@@ -0,0 +1,88 @@
+//! Trade tape filtering tests
+//!
+//! Executes a displayed trade and a dust trade on a market with a minimum
+//! displayed size and checks the public and raw trade endpoints.
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use ::common::decimal::dec;
+use ::common::model::order::Side;
+use ::common::model::trade::Trade;
+use account_service::AccountService;
+use api_gateway::config::AppConfig;
+use api_gateway::AppState;
+use axum::http::StatusCode;
+use common::{engine, spot, Gateway, MARKET};
+use market_data::tape::TapeFilter;
+use market_data::MarketDataService;
+use serde_json::Value;
+use uuid::Uuid;
+
+impl Gateway {
+    fn setup() -> Self {
+        let markets = vec![spot(MARKET)];
+        let market_data = MarketDataService::new().with_tape_filter(TapeFilter {
+            min_trade_sizes: BTreeMap::from([(MARKET.to_string(), dec!(0.01))]),
+        });
+        let state = AppState::new(engine(&markets), Arc::new(AccountService::new()), Arc::new(market_data), markets);
+        Self::new(state, &AppConfig::default())
+    }
+
+    async fn trade(&self, quantity: rust_decimal::Decimal) -> Trade {
+        let trade = Trade::new(
+            MARKET.to_string(),
+            dec!(100),
+            quantity,
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Side::Buy,
+        );
+        self.state.market_data_service.process_trade(&trade).await.unwrap();
+        trade
+    }
+
+    /// Create an account, returning its API key
+    async fn api_key(&self) -> String {
+        self.create_account().await.1
+    }
+}
+
+fn trade_ids(body: &Value) -> Vec<String> {
+    body["data"]["trades"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|trade| trade["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_dust_is_only_on_the_raw_tape() {
+    let gateway = Gateway::setup();
+    let shown = gateway.trade(dec!(0.5)).await;
+    let dust = gateway.trade(dec!(0.001)).await;
+
+    let (status, body) = gateway.send("GET", "/markets/BTC%2FUSD/trades", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(trade_ids(&body), [shown.id.to_string()]);
+
+    let (status, body) = gateway.send("GET", "/markets/BTC%2FUSD/ticker", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["last"], "100");
+
+    // The full tape needs an API key
+    let (status, _) = gateway.send("GET", "/markets/BTC%2FUSD/trades/raw", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let key = gateway.api_key().await;
+    let (status, body) = gateway.send("GET", "/markets/BTC%2FUSD/trades/raw", Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let ids = trade_ids(&body);
+    assert_eq!(ids.len(), 2);
+    assert!(ids.contains(&dust.id.to_string()));
+}
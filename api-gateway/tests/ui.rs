@@ -0,0 +1,47 @@
+//! Embedded web UI tests
+//!
+//! Only built with the `ui` feature. Checks the bundled files are served and
+//! point at the gateway's real REST and WebSocket paths.
+#![cfg(feature = "ui")]
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use tower::ServiceExt;
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, String, String) {
+    let response = app.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+    let status = response.status();
+    let content_type = response.headers().get(header::CONTENT_TYPE).map(|v| v.to_str().unwrap().to_string()).unwrap_or_default();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, content_type, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_ui_files_are_served() {
+    let app: Router = api_gateway::ui::router();
+
+    for uri in ["/app", "/app/"] {
+        let (status, content_type, body) = get(&app, uri).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(content_type.starts_with("text/html"));
+        assert!(body.contains("<script src=\"/app/app.js\"></script>"));
+        assert!(body.contains("/app/app.css"));
+    }
+
+    let (status, content_type, script) = get(&app, "/app/app.js").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("text/javascript"));
+    assert!(script.contains("const API = '/api/v1';"));
+    assert!(script.contains("/ws`"));
+    for channel in ["'orderbook'", "'ticker'", "'trades'"] {
+        assert!(script.contains(channel), "app.js should subscribe to {}", channel);
+    }
+
+    let (status, content_type, _) = get(&app, "/app/app.css").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("text/css"));
+
+    let (status, _, _) = get(&app, "/app/missing.js").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
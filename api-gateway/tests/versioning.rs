@@ -0,0 +1,112 @@
+//! API versioning tests
+//!
+//! Mounts every version with `versioned_api` and checks each serves the same
+//! routes in its own response shapes, and that unversioned requests are
+//! served by the version they ask for.
+
+mod common;
+
+use std::sync::Arc;
+
+use ::common::model::market::Market;
+use api_gateway::config::AppConfig;
+use api_gateway::versioning::{openapi_for, versioned_api, ApiVersion};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::Router;
+use common::{spot, state_for, Gateway, MARKET};
+use serde_json::{json, Value};
+use utoipa::openapi::path::Operation;
+use utoipa::openapi::{InfoBuilder, OpenApiBuilder, PathItem, PathItemType, PathsBuilder};
+use uuid::Uuid;
+
+impl Gateway {
+    fn setup() -> Self {
+        let state = Arc::new(state_for(vec![Market {
+            max_price_deviation: 10.5,
+            ..spot(MARKET)
+        }]));
+        Self {
+            app: versioned_api(state.clone(), &AppConfig::default(), Router::new()),
+            state,
+        }
+    }
+
+    /// Send a request asking for an API version, if any
+    async fn versioned(&self, method: &str, uri: &str, version: Option<&str>, body: Option<Value>) -> (StatusCode, HeaderMap, Value) {
+        let mut request = Self::request(method, uri, None, body);
+        if let Some(version) = version {
+            request.headers_mut().insert("x-api-version", version.parse().unwrap());
+        }
+        self.call(request).await
+    }
+}
+
+#[tokio::test]
+async fn test_versions_share_routes_but_not_shapes() {
+    let gateway = Gateway::setup();
+
+    let (status, headers, body) = gateway.versioned("GET", "/api/v1/markets", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["x-api-version"], "1");
+    assert_eq!(body["data"][0]["max_price_deviation"], json!(10.5));
+
+    // Version 2 writes every fraction as a decimal string
+    let (status, headers, body) = gateway.versioned("GET", "/api/v2/markets", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["x-api-version"], "2");
+    assert_eq!(body["data"][0]["max_price_deviation"], "10.5");
+
+    let account = format!("/accounts/{}", Uuid::new_v4());
+    let (status, _, body) = gateway.versioned("GET", &format!("/api/v1{}", account), None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["code"], "unauthorized");
+    assert!(body["request_id"].is_string());
+
+    let (status, _, body) = gateway.versioned("GET", &format!("/api/v2{}", account), None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["code"], "UNAUTHORIZED");
+    assert!(body.get("request_id").is_none());
+    assert!(body["meta"]["request_id"].is_string());
+
+    // Created resources point at the version that created them
+    let (status, headers, body) = gateway.versioned("POST", "/api/v2/accounts", None, Some(json!({}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(headers[header::LOCATION], format!("/api/v2/accounts/{}", body["data"]["id"].as_str().unwrap()));
+}
+
+#[tokio::test]
+async fn test_unversioned_requests_negotiate_a_version() {
+    let gateway = Gateway::setup();
+
+    let (status, headers, _) = gateway.versioned("GET", "/api/markets", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["x-api-version"], "1");
+
+    let (status, headers, body) = gateway.versioned("GET", "/api/markets", Some("v2"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["x-api-version"], "2");
+    assert_eq!(body["data"][0]["max_price_deviation"], "10.5");
+
+    let (status, _, body) = gateway.versioned("GET", "/api/markets", Some("7"), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]["message"].as_str().unwrap().contains("v1, v2"), "{}", body);
+
+    let (status, _, _) = gateway.versioned("GET", "/api/v7/markets", None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn test_openapi_paths_follow_the_version() {
+    let doc = OpenApiBuilder::new()
+        .info(InfoBuilder::new().title("Zavora").version("1.0.0").build())
+        .paths(PathsBuilder::new().path("/api/v1/markets", PathItem::new(PathItemType::Get, Operation::new())))
+        .build();
+
+    let v1 = openapi_for(ApiVersion::V1, doc.clone());
+    assert!(v1.paths.paths.contains_key("/api/v1/markets"));
+
+    let v2 = openapi_for(ApiVersion::V2, doc);
+    let paths: Vec<_> = v2.paths.paths.keys().cloned().collect();
+    assert_eq!(paths, ["/api/v2/markets"]);
+    assert_eq!(v2.info.title, "Zavora (v2)");
+}
@@ -0,0 +1,214 @@
+//! Account webhook tests
+//!
+//! Registers webhooks through the gateway router and checks the signed
+//! notifications, retries and delivery log against a local receiver.
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ::common::decimal::{Price, Quantity};
+use ::common::model::order::{Order, OrderType, Side, Status, TimeInForce};
+use api_gateway::config::AppConfig;
+use api_gateway::webhook::{self, WebhookConfig};
+use axum::body::Bytes;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use chrono::Utc;
+use common::{state, Gateway, MARKET};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+impl Gateway {
+    fn setup(webhooks: WebhookConfig) -> Self {
+        Self::new(state().with_webhooks(webhooks), &AppConfig::default())
+    }
+
+    /// Poll the delivery log until every delivery has settled
+    async fn settled_deliveries(&self, account_id: Uuid, key: &str, count: usize) -> Vec<Value> {
+        for _ in 0..200 {
+            let (_, body) = self.send("GET", &format!("/accounts/{}/webhooks/deliveries", account_id), Some(key), None).await;
+            let deliveries = body["data"].as_array().cloned().unwrap_or_default();
+            if deliveries.len() == count && deliveries.iter().all(|delivery| delivery["status"] != "pending") {
+                return deliveries;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("deliveries never settled");
+    }
+}
+
+type Received = Arc<Mutex<Vec<(HeaderMap, Bytes)>>>;
+
+/// Start a receiver that fails the first `failures` requests, returning its URL and the requests it accepted
+async fn receiver(failures: usize) -> (String, Received) {
+    let received: Received = Arc::default();
+    let remaining_failures = Arc::new(AtomicUsize::new(failures));
+
+    let app = Router::new().route(
+        "/hook",
+        post({
+            let received = received.clone();
+            move |headers: HeaderMap, body: Bytes| async move {
+                if remaining_failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_ok() {
+                    return StatusCode::SERVICE_UNAVAILABLE;
+                }
+                received.lock().unwrap().push((headers, body));
+                StatusCode::OK
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, received)
+}
+
+fn local_config() -> WebhookConfig {
+    WebhookConfig {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(10),
+        allow_http: true,
+        ..WebhookConfig::default()
+    }
+}
+
+fn limit_order(user_id: Uuid, side: Side) -> Order {
+    Order {
+        id: Uuid::new_v4(),
+        user_id,
+        market: MARKET.to_string(),
+        side,
+        order_type: OrderType::Limit,
+        price: Some(Price::new(100, 0)),
+        quantity: Quantity::new(1, 0),
+        remaining_quantity: Quantity::new(1, 0),
+        filled_quantity: Quantity::ZERO,
+        status: Status::New,
+        time_in_force: TimeInForce::GTC,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
+    }
+}
+
+#[tokio::test]
+async fn test_webhook_registration() {
+    let gateway = Gateway::setup(WebhookConfig::default());
+    let (account_id, key) = gateway.create_account().await;
+    let (_, other_key) = gateway.create_account().await;
+    let uri = format!("/accounts/{}/webhooks", account_id);
+
+    let (status, _) = gateway.send("POST", &uri, Some(&key), Some(json!({ "url": "http://example.com/hook" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = gateway.send("POST", &uri, Some(&key), Some(json!({ "url": "not a url" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = gateway.send("POST", &uri, Some(&other_key), Some(json!({ "url": "https://example.com/hook" }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = gateway.send("POST", &uri, Some(&key), Some(json!({ "url": "https://example.com/hook" }))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(body["data"]["secret"].as_str().unwrap().starts_with("whsec_"));
+    assert_eq!(body["data"]["events"], json!(["fill", "order_status", "deposit", "withdrawal"]));
+    let webhook_id = body["data"]["id"].as_str().unwrap().to_string();
+
+    let (_, body) = gateway.send("GET", &uri, Some(&key), None).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    assert!(body["data"][0].get("secret").is_none());
+
+    let (status, _) = gateway.send("DELETE", &format!("{}/{}", uri, webhook_id), Some(&key), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = gateway.send("DELETE", &format!("{}/{}", uri, webhook_id), Some(&key), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, body) = gateway.send("GET", &uri, Some(&key), None).await;
+    assert!(body["data"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_notifications_are_signed_and_logged() {
+    let gateway = Gateway::setup(local_config());
+    let (account_id, key) = gateway.create_account().await;
+    let (url, received) = receiver(0).await;
+
+    let (_, body) = gateway
+        .send("POST", &format!("/accounts/{}/webhooks", account_id), Some(&key), Some(json!({ "url": url, "events": ["fill", "deposit"] })))
+        .await;
+    let secret = body["data"]["secret"].as_str().unwrap().to_string();
+
+    let (status, _) = gateway
+        .send("POST", &format!("/accounts/{}/deposit", account_id), Some(&key), Some(json!({ "asset": "USD", "amount": "500" })))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let engine = &gateway.state.matching_engine;
+    engine.place_order(limit_order(account_id, Side::Buy)).unwrap();
+    engine.place_order(limit_order(Uuid::new_v4(), Side::Sell)).unwrap();
+
+    // Order status changes were not subscribed to
+    let deliveries = gateway.settled_deliveries(account_id, &key, 2).await;
+    assert!(deliveries.iter().all(|delivery| delivery["status"] == "delivered" && delivery["attempts"] == 1));
+
+    let received = received.lock().unwrap();
+    let mut events: Vec<Value> = Vec::new();
+    for (headers, body) in received.iter() {
+        let signature = headers[webhook::SIGNATURE_HEADER].to_str().unwrap();
+        let (timestamp, digest) = signature.strip_prefix("t=").unwrap().split_once(",v1=").unwrap();
+        let body = std::str::from_utf8(body).unwrap();
+        assert_eq!(digest, webhook::sign(&secret, timestamp.parse().unwrap(), body));
+
+        let event: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(headers[webhook::EVENT_HEADER], event["type"].as_str().unwrap());
+        assert_eq!(headers[webhook::DELIVERY_HEADER], event["id"].as_str().unwrap());
+        assert_eq!(event["account_id"], account_id.to_string());
+        events.push(event);
+    }
+
+    let deposit = events.iter().find(|event| event["type"] == "deposit").unwrap();
+    assert_eq!(deposit["data"]["asset"], "USD");
+    let fill = events.iter().find(|event| event["type"] == "fill").unwrap();
+    assert_eq!(fill["data"]["side"], "Buy");
+    assert_eq!(fill["data"]["is_maker"], true);
+}
+
+#[tokio::test]
+async fn test_failed_deliveries_are_retried() {
+    let gateway = Gateway::setup(local_config());
+    let (account_id, key) = gateway.create_account().await;
+    let uri = format!("/accounts/{}/webhooks", account_id);
+
+    // One receiver recovers on the last attempt, the other never answers
+    let (url, received) = receiver(2).await;
+    gateway.send("POST", &uri, Some(&key), Some(json!({ "url": url, "events": ["withdrawal"] }))).await;
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let unreachable = format!("http://{}/hook", closed.local_addr().unwrap());
+    drop(closed);
+    gateway.send("POST", &uri, Some(&key), Some(json!({ "url": unreachable, "events": ["withdrawal"] }))).await;
+
+    gateway
+        .send("POST", &format!("/accounts/{}/deposit", account_id), Some(&key), Some(json!({ "asset": "USD", "amount": "500" })))
+        .await;
+    let (status, _) = gateway
+        .send("POST", &format!("/accounts/{}/withdraw", account_id), Some(&key), Some(json!({ "asset": "USD", "amount": "200" })))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let deliveries = gateway.settled_deliveries(account_id, &key, 2).await;
+    assert!(deliveries.iter().all(|delivery| delivery["event"] == "withdrawal" && delivery["attempts"] == 3));
+    let delivered = deliveries.iter().find(|delivery| delivery["status"] == "delivered").unwrap();
+    assert_eq!(delivered["response_status"], 200);
+    let failed = deliveries.iter().find(|delivery| delivery["status"] == "failed").unwrap();
+    assert_eq!(failed["response_status"], Value::Null);
+    assert!(failed["error"].is_string());
+
+    assert_eq!(received.lock().unwrap().len(), 1);
+}
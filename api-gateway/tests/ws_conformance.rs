@@ -0,0 +1,688 @@
+//! WebSocket protocol conformance tests
+//!
+//! Runs the gateway's WS endpoint in-process and checks every channel against
+//! the message shapes documented in the API gateway README.
+
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ::common::decimal::{dec, Price, Quantity};
+use ::common::model::order::{Order, Side, TimeInForce};
+use account_service::AccountService;
+use api_gateway::config::AppConfig;
+use api_gateway::routes::api_router;
+use api_gateway::runtime::resume_trade_sequences;
+use api_gateway::AppState;
+use common::{engine, serve, spot, state, Gateway, MARKET};
+use futures::{SinkExt, StreamExt};
+use market_data::repository::InMemoryMarketRepository;
+use market_data::MarketDataService;
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use uuid::Uuid;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// JSON value kinds used to describe documented message shapes
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    String,
+    OptionalString,
+    Uuid,
+    Decimal,
+    OptionalDecimal,
+    OptionalNumber,
+    Timestamp,
+    Sequence,
+    Levels,
+    Pairs,
+    Bool,
+    Integer,
+    Object,
+}
+
+fn is_decimal(value: &Value) -> bool {
+    value.as_str().is_some_and(|s| s.parse::<Price>().is_ok())
+}
+
+fn assert_kind(field: &str, value: &Value, kind: Kind) {
+    let ok = match kind {
+        Kind::String => value.is_string(),
+        Kind::OptionalString => value.is_null() || value.is_string(),
+        Kind::Uuid => value.as_str().is_some_and(|s| Uuid::parse_str(s).is_ok()),
+        Kind::Decimal => is_decimal(value),
+        Kind::OptionalDecimal => value.is_null() || is_decimal(value),
+        Kind::OptionalNumber => value.is_null() || value.is_number(),
+        Kind::Timestamp => value.as_str().is_some_and(|s| chrono::DateTime::parse_from_rfc3339(s).is_ok()),
+        Kind::Sequence => value.is_u64(),
+        Kind::Levels => value.as_array().is_some_and(|levels| {
+            levels.iter().all(|level| is_decimal(&level["price"]) && is_decimal(&level["quantity"]))
+        }),
+        Kind::Pairs => value.as_array().is_some_and(|levels| {
+            levels.iter().all(|level| {
+                level.as_array().is_some_and(|pair| pair.len() == 2 && pair.iter().all(is_decimal))
+            })
+        }),
+        Kind::Bool => value.is_boolean(),
+        Kind::Integer => value.is_i64(),
+        Kind::Object => value.is_object(),
+    };
+    assert!(ok, "field `{}` should be {:?}, got {}", field, kind, value);
+}
+
+/// Assert that a JSON object has exactly the documented fields
+fn assert_shape(value: &Value, shape: &[(&str, Kind)]) {
+    let object = value.as_object().unwrap_or_else(|| panic!("expected object, got {}", value));
+    for (field, kind) in shape {
+        let field_value = object.get(*field).unwrap_or_else(|| panic!("missing field `{}` in {}", field, value));
+        assert_kind(field, field_value, *kind);
+    }
+    assert_eq!(object.len(), shape.len(), "unexpected fields in {}", value);
+}
+
+const ORDER_BOOK_SHAPE: &[(&str, Kind)] = &[
+    ("market", Kind::String),
+    ("timestamp", Kind::Timestamp),
+    ("sequence", Kind::Sequence),
+    ("bids", Kind::Levels),
+    ("asks", Kind::Levels),
+];
+
+const BBO_SHAPE: &[(&str, Kind)] = &[
+    ("market", Kind::String),
+    ("bid_price", Kind::OptionalDecimal),
+    ("bid_size", Kind::OptionalDecimal),
+    ("ask_price", Kind::OptionalDecimal),
+    ("ask_size", Kind::OptionalDecimal),
+    ("sequence", Kind::Sequence),
+    ("timestamp", Kind::Timestamp),
+];
+
+const TRADE_SHAPE: &[(&str, Kind)] = &[
+    ("id", Kind::Uuid),
+    ("market", Kind::String),
+    ("price", Kind::Decimal),
+    ("quantity", Kind::Decimal),
+    ("taker_side", Kind::String),
+    ("is_buyer_maker", Kind::Bool),
+    ("timestamp", Kind::Timestamp),
+    ("sequence", Kind::Sequence),
+];
+
+const TICKER_SHAPE: &[(&str, Kind)] = &[
+    ("market", Kind::String),
+    ("bid", Kind::OptionalDecimal),
+    ("ask", Kind::OptionalDecimal),
+    ("last", Kind::OptionalDecimal),
+    ("change_24h", Kind::OptionalDecimal),
+    ("change_24h_percent", Kind::OptionalNumber),
+    ("high_24h", Kind::OptionalDecimal),
+    ("low_24h", Kind::OptionalDecimal),
+    ("volume_24h", Kind::OptionalDecimal),
+    ("quote_volume_24h", Kind::OptionalDecimal),
+    ("timestamp", Kind::Timestamp),
+];
+
+const CANDLE_SHAPE: &[(&str, Kind)] = &[
+    ("market", Kind::String),
+    ("interval", Kind::String),
+    ("open_time", Kind::Timestamp),
+    ("close_time", Kind::Timestamp),
+    ("open", Kind::Decimal),
+    ("high", Kind::Decimal),
+    ("low", Kind::Decimal),
+    ("close", Kind::Decimal),
+    ("volume", Kind::Decimal),
+    ("quote_volume", Kind::Decimal),
+    ("trades", Kind::Integer),
+    ("taker_buy_volume", Kind::Decimal),
+    ("taker_buy_quote_volume", Kind::Decimal),
+    ("closed", Kind::Bool),
+];
+
+/// Validate a notification envelope and its data payload
+fn assert_notification(notification: &Value) {
+    if notification.get("version").is_some() {
+        return assert_notification_v2(notification);
+    }
+    assert_shape(notification, &[("method", Kind::String), ("params", Kind::Object)]);
+
+    let method = notification["method"].as_str().unwrap();
+    let params = &notification["params"];
+    let data = &params["data"];
+
+    let data_shape = match method {
+        "orderbook" => ORDER_BOOK_SHAPE,
+        "bbo" => BBO_SHAPE,
+        "trades" => TRADE_SHAPE,
+        "ticker" => TICKER_SHAPE,
+        "candles" => CANDLE_SHAPE,
+        // All-market subscriptions carry the same payloads under "update"
+        "update" if data.get("taker_side").is_some() => TRADE_SHAPE,
+        "update" if data.get("sequence").is_some() => ORDER_BOOK_SHAPE,
+        "update" => TICKER_SHAPE,
+        other => panic!("unknown notification method {}", other),
+    };
+
+    if method == "update" {
+        assert_shape(params, &[("subscription_id", Kind::Uuid), ("data", Kind::Object)]);
+    } else {
+        assert_shape(params, &[("market", Kind::String), ("subscription_id", Kind::Uuid), ("data", Kind::Object)]);
+        assert_eq!(params["market"], MARKET);
+    }
+    assert_shape(data, data_shape);
+
+    if let Some(side) = data.get("taker_side") {
+        assert!(side == "buy" || side == "sell", "bad taker_side {}", side);
+    }
+}
+
+/// Validate a version 2 notification, which names its channel and market for every subscription
+fn assert_notification_v2(notification: &Value) {
+    assert_shape(notification, &[("version", Kind::Integer), ("method", Kind::String), ("params", Kind::Object)]);
+    assert_eq!(notification["version"], 2);
+
+    let params = &notification["params"];
+    assert_shape(params, &[
+        ("subscriptionId", Kind::Uuid),
+        ("market", Kind::String),
+        ("channel", Kind::String),
+        ("data", Kind::Object),
+    ]);
+    assert_eq!(params["channel"], notification["method"]);
+    assert_eq!(params["market"], MARKET);
+
+    let data_shape = match notification["method"].as_str().unwrap() {
+        "orderbook" => ORDER_BOOK_SHAPE,
+        "bbo" => BBO_SHAPE,
+        "trades" => TRADE_SHAPE,
+        "ticker" => TICKER_SHAPE,
+        "candles" => CANDLE_SHAPE,
+        other => panic!("unknown notification method {}", other),
+    };
+    assert_shape(&params["data"], data_shape);
+}
+
+/// Minimal WS test client that separates responses from notifications
+struct Client {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    notifications: Vec<Value>,
+    next_id: u64,
+}
+
+impl Client {
+    async fn connect(addr: SocketAddr) -> Self {
+        let (socket, _) = connect_async(format!("ws://{}/ws", addr)).await.expect("Failed to connect");
+        Self { socket, notifications: Vec::new(), next_id: 1 }
+    }
+
+    async fn send_text(&mut self, text: String) {
+        self.socket.send(Message::Text(text)).await.unwrap();
+    }
+
+    async fn next_message(&mut self) -> Value {
+        loop {
+            let message = tokio::time::timeout(TIMEOUT, self.socket.next())
+                .await
+                .expect("Timed out waiting for message")
+                .expect("Connection closed")
+                .unwrap();
+            if let Message::Text(text) = message {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    /// Read the next message, recording it if it is a notification
+    async fn next_response(&mut self) -> Option<Value> {
+        let message = self.next_message().await;
+        if message.get("method").is_some() {
+            assert_notification(&message);
+            self.notifications.push(message);
+            None
+        } else {
+            Some(message)
+        }
+    }
+
+    /// Send a request and wait for the response with the same ID
+    async fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id.to_string();
+        self.next_id += 1;
+        self.send_text(json!({ "id": id, "method": method, "params": params }).to_string()).await;
+
+        loop {
+            if let Some(response) = self.next_response().await {
+                assert_eq!(response["id"], id, "response out of order: {}", response);
+                return response;
+            }
+        }
+    }
+
+    /// Wait until at least `count` notifications for a subscription have arrived
+    async fn notifications_for(&mut self, subscription_id: &str, count: usize) -> Vec<Value> {
+        while self.received(subscription_id).len() < count {
+            if let Some(response) = self.next_response().await {
+                panic!("unexpected response {}", response);
+            }
+        }
+        self.received(subscription_id)
+    }
+
+    fn received(&self, subscription_id: &str) -> Vec<Value> {
+        self.notifications
+            .iter()
+            .filter(|n| n["params"]["subscription_id"] == subscription_id || n["params"]["subscriptionId"] == subscription_id)
+            .cloned()
+            .collect()
+    }
+
+    async fn subscribe(&mut self, channel: &str, market: Option<&str>) -> String {
+        let response = self.request("subscribe", json!({ "channel": channel, "market": market })).await;
+        assert_shape(&response, &[("id", Kind::String), ("result", Kind::Object)]);
+
+        let result = &response["result"];
+        assert_shape(result, &[
+            ("subscriptionId", Kind::Uuid),
+            ("channel", Kind::String),
+            ("market", Kind::OptionalString),
+        ]);
+        assert_eq!(result["channel"], channel);
+        assert_eq!(result["market"], json!(market));
+
+        result["subscriptionId"].as_str().unwrap().to_string()
+    }
+}
+
+impl Gateway {
+    /// Serve the gateway, returning it and its address
+    async fn setup() -> (Self, SocketAddr) {
+        Self::setup_with(state()).await
+    }
+
+    /// Serve the gateway over `state`, returning it and its address
+    async fn setup_with(state: AppState) -> (Self, SocketAddr) {
+        let state = Arc::new(state);
+
+        // Mounted like the binaries: compressed REST routes beside the WS endpoint
+        let config = AppConfig {
+            compression_min_bytes: 0,
+            ..AppConfig::default()
+        };
+        let app = axum::Router::new()
+            .nest("/api/v1", api_router(state.clone(), &config, axum::Router::new()))
+            .merge(
+                axum::Router::new()
+                    .route("/ws", axum::routing::get(api_gateway::ws::handler::ws_handler))
+                    .with_state(state.clone()),
+            );
+
+        let addr = serve(app.clone()).await;
+        (Self { app, state }, addr)
+    }
+
+    /// Place an order and publish the resulting trades and depth, as the order API does
+    async fn place(&self, side: Side, price: Price, quantity: Quantity) {
+        let order = Order::new_limit(Uuid::new_v4(), MARKET.to_string(), side, price, quantity, TimeInForce::GTC);
+        let result = self.state.matching_engine.place_order(order).unwrap();
+
+        for trade in &result.trades {
+            self.state.market_data_service.process_trade(trade).await.unwrap();
+        }
+
+        let (bids, asks) = self.state.matching_engine.get_market_depth(MARKET, 10).unwrap();
+        self.state.market_data_service.update_order_book(MARKET, bids, asks).await.unwrap();
+    }
+}
+
+fn sequences(notifications: &[Value]) -> Vec<u64> {
+    notifications
+        .iter()
+        .map(|n| n["params"]["data"]["sequence"].as_u64().unwrap())
+        .collect()
+}
+
+fn assert_contiguous(sequences: &[u64]) {
+    for pair in sequences.windows(2) {
+        assert_eq!(pair[1], pair[0] + 1, "gap in depth sequence: {:?}", sequences);
+    }
+}
+
+#[tokio::test]
+async fn test_all_channels_conform_to_documented_shapes() {
+    let (gateway, addr) = Gateway::setup().await;
+    let mut client = Client::connect(addr).await;
+
+    let pong = client.request("ping", json!({})).await;
+    assert_shape(&pong["result"], &[("pong", Kind::Timestamp)]);
+
+    let order_book = client.subscribe("orderbook", Some(MARKET)).await;
+    let trades = client.subscribe("trades", Some(MARKET)).await;
+    let ticker = client.subscribe("ticker", Some(MARKET)).await;
+    let all_order_books = client.subscribe("orderbook", None).await;
+    let all_trades = client.subscribe("trades", None).await;
+    let all_tickers = client.subscribe("ticker", None).await;
+
+    // Rest two asks, then lift one of them to produce a trade
+    gateway.place(Side::Sell, dec!(20000), dec!(1)).await;
+    gateway.place(Side::Sell, dec!(20100), dec!(2)).await;
+    gateway.place(Side::Buy, dec!(20000), dec!(0.5)).await;
+
+    // Every subscription sees every update, schema-checked on receipt
+    let depth = client.notifications_for(&order_book, 3).await;
+    client.notifications_for(&all_order_books, 3).await;
+    client.notifications_for(&ticker, 3).await;
+    client.notifications_for(&all_tickers, 3).await;
+    let trade = client.notifications_for(&trades, 1).await;
+    client.notifications_for(&all_trades, 1).await;
+
+    assert_contiguous(&sequences(&depth));
+    assert_eq!(depth.last().unwrap()["params"]["data"]["asks"][0]["price"], "20000");
+    assert_eq!(depth.last().unwrap()["params"]["data"]["asks"][0]["quantity"], "0.5");
+    assert_eq!(trade[0]["params"]["data"]["taker_side"], "buy");
+    assert_eq!(trade[0]["params"]["data"]["is_buyer_maker"], false);
+
+    // Snapshot requests use [price, quantity] pairs
+    let snapshot = client.request("getOrderBook", json!({ "market": MARKET })).await;
+    assert_shape(&snapshot["result"], &[
+        ("market", Kind::String),
+        ("bids", Kind::Pairs),
+        ("asks", Kind::Pairs),
+        ("timestamp", Kind::Timestamp),
+    ]);
+    assert_eq!(snapshot["result"]["asks"][0][0], "20000");
+    assert_eq!(snapshot["result"]["asks"][0][1], "0.5");
+}
+
+#[tokio::test]
+async fn test_unsubscribe_and_resubscribe() {
+    let (gateway, addr) = Gateway::setup().await;
+    let mut client = Client::connect(addr).await;
+
+    // A second subscription keeps observing the stream while the first is removed
+    let first = client.subscribe("orderbook", Some(MARKET)).await;
+    let witness = client.subscribe("orderbook", None).await;
+
+    gateway.place(Side::Buy, dec!(19000), dec!(1)).await;
+    gateway.place(Side::Buy, dec!(19100), dec!(1)).await;
+    let before = sequences(&client.notifications_for(&first, 2).await);
+    assert_contiguous(&before);
+
+    let response = client.request("unsubscribe", json!({ "subscriptionId": first })).await;
+    assert_shape(&response["result"], &[("unsubscribed", Kind::Bool)]);
+
+    // Unsubscribing twice reports the subscription as unknown
+    let response = client.request("unsubscribe", json!({ "subscriptionId": first })).await;
+    assert_eq!(response["error"]["code"], 404);
+
+    // Updates published while unsubscribed reach only the witness
+    gateway.place(Side::Buy, dec!(19200), dec!(1)).await;
+    client.notifications_for(&witness, 3).await;
+    assert_eq!(client.received(&first).len(), 2);
+
+    // A new subscription resumes the same per-market sequence
+    let second = client.subscribe("orderbook", Some(MARKET)).await;
+    assert_ne!(second, first);
+
+    gateway.place(Side::Buy, dec!(19300), dec!(1)).await;
+    gateway.place(Side::Buy, dec!(19400), dec!(1)).await;
+    let after = sequences(&client.notifications_for(&second, 2).await);
+    assert_contiguous(&after);
+    assert_eq!(after[0], before[1] + 2);
+
+    let witnessed = sequences(&client.notifications_for(&witness, 5).await);
+    assert_contiguous(&witnessed);
+    assert_eq!(client.received(&first).len(), 2);
+}
+
+#[tokio::test]
+async fn test_error_responses() {
+    let (_, addr) = Gateway::setup().await;
+    let mut client = Client::connect(addr).await;
+
+    // Unparseable requests are answered with id "0"
+    client.send_text("not json".to_string()).await;
+    let response = client.next_message().await;
+    assert_eq!(response["id"], "0");
+    assert_shape(&response["error"], &[("code", Kind::Integer), ("message", Kind::String)]);
+    assert_eq!(response["error"]["code"], 400);
+
+    let cases = [
+        ("subscribe", json!({ "channel": "news", "market": MARKET }), 400),
+        ("subscribe", json!({ "channel": "bbo" }), 400),
+        ("subscribe", json!({ "channel": "candles" }), 400),
+        ("subscribe", json!({ "channel": "candles", "market": MARKET, "interval": "2m" }), 400),
+        ("subscribe", json!({}), 400),
+        ("subscribe", json!({ "channel": "account" }), 401),
+        ("subscribe", json!({ "channel": "account", "apiKey": "zk_invalid" }), 401),
+        ("subscribe", json!({ "channel": "rawtrades" }), 400),
+        ("subscribe", json!({ "channel": "rawtrades", "market": MARKET }), 401),
+        ("subscribe", json!({ "channel": "trades", "version": 9 }), 400),
+        ("hello", json!({}), 400),
+        ("hello", json!({ "version": 0 }), 400),
+        ("hello", json!({ "version": 1, "numbers": "float" }), 400),
+        ("unsubscribe", json!({ "subscriptionId": "not-a-uuid" }), 400),
+        ("unsubscribe", json!({ "subscriptionId": Uuid::new_v4() }), 404),
+        ("getOrderBook", json!({}), 400),
+        ("getOrderBook", json!({ "market": "ETH/USD" }), 500),
+        ("getTrades", json!({}), 400),
+        ("getTicker", json!({}), 400),
+        ("launchRocket", json!({}), 400),
+    ];
+
+    for (method, params, code) in cases {
+        let response = client.request(method, params.clone()).await;
+        assert!(response.get("result").is_none(), "{} {} succeeded: {}", method, params, response);
+        assert_shape(&response["error"], &[("code", Kind::Integer), ("message", Kind::String)]);
+        assert_eq!(response["error"]["code"], code, "{} {}", method, params);
+    }
+}
+
+#[tokio::test]
+async fn test_candles_channel() {
+    let (gateway, addr) = Gateway::setup().await;
+    let mut client = Client::connect(addr).await;
+
+    let response = client.request("subscribe", json!({ "channel": "candles", "market": MARKET, "interval": "5m" })).await;
+    assert_shape(&response["result"], &[
+        ("subscriptionId", Kind::Uuid),
+        ("channel", Kind::String),
+        ("market", Kind::OptionalString),
+        ("interval", Kind::String),
+    ]);
+    assert_eq!(response["result"]["interval"], "5m");
+    let candles = response["result"]["subscriptionId"].as_str().unwrap().to_string();
+
+    gateway.place(Side::Sell, dec!(20000), dec!(2)).await;
+    gateway.place(Side::Buy, dec!(20000), dec!(0.5)).await;
+    gateway.place(Side::Buy, dec!(20000), dec!(0.25)).await;
+
+    // Each trade pushes the working candle
+    let updates = client.notifications_for(&candles, 2).await;
+    assert_eq!(updates[0]["method"], "candles");
+    assert_eq!(updates[0]["params"]["market"], MARKET);
+    assert_eq!(updates[1]["params"]["data"]["interval"], "Minute5");
+    assert_eq!(updates[1]["params"]["data"]["volume"], "0.75");
+    assert_eq!(updates[1]["params"]["data"]["closed"], false);
+}
+
+#[tokio::test]
+async fn test_bbo_channel() {
+    let (gateway, addr) = Gateway::setup().await;
+    let mut client = Client::connect(addr).await;
+    let bbo = client.subscribe("bbo", Some(MARKET)).await;
+
+    gateway.place(Side::Sell, dec!(20000), dec!(2)).await;
+    let updates = client.notifications_for(&bbo, 1).await;
+    assert_eq!(updates[0]["method"], "bbo");
+    assert_eq!(updates[0]["params"]["data"]["bid_price"], Value::Null);
+    assert_eq!(updates[0]["params"]["data"]["ask_price"], "20000");
+
+    // A deeper ask leaves the top of book alone, a partial fill of the best ask moves it
+    gateway.place(Side::Sell, dec!(20100), dec!(1)).await;
+    gateway.place(Side::Buy, dec!(20000), dec!(0.5)).await;
+    let updates = client.notifications_for(&bbo, 2).await;
+    assert_eq!(updates[1]["params"]["data"]["ask_size"], "1.5");
+    assert_eq!(sequences(&updates), [1, 3]);
+}
+
+#[tokio::test]
+async fn test_version_negotiation() {
+    let (gateway, addr) = Gateway::setup().await;
+    let mut client = Client::connect(addr).await;
+
+    // Clients newer than the server get the newest version the server speaks
+    let hello = client.request("hello", json!({ "version": 3 })).await;
+    assert_eq!(hello["result"], json!({ "version": 2, "supportedVersions": [1, 2] }));
+
+    let response = client.request("subscribe", json!({ "channel": "trades" })).await;
+    assert_eq!(response["result"]["version"], 2);
+    let all_trades = response["result"]["subscriptionId"].as_str().unwrap().to_string();
+
+    // A subscription can still ask for the version 1 shape
+    let response = client.request("subscribe", json!({ "channel": "ticker", "market": MARKET, "version": 1 })).await;
+    assert!(response["result"].get("version").is_none());
+    let ticker = response["result"]["subscriptionId"].as_str().unwrap().to_string();
+
+    gateway.place(Side::Sell, dec!(20000), dec!(1)).await;
+    gateway.place(Side::Buy, dec!(20000), dec!(1)).await;
+
+    let trades = client.notifications_for(&all_trades, 1).await;
+    assert_eq!(trades[0]["method"], "trades");
+    assert_eq!(trades[0]["params"]["data"]["taker_side"], "buy");
+    let tickers = client.notifications_for(&ticker, 1).await;
+    assert!(tickers[0].get("version").is_none());
+    assert_eq!(tickers[0]["method"], "ticker");
+}
+
+#[tokio::test]
+async fn test_decimal_strings_number_format() {
+    let (gateway, addr) = Gateway::setup().await;
+    let mut client = Client::connect(addr).await;
+
+    let hello = client.request("hello", json!({ "version": 1, "numbers": "decimal-strings" })).await;
+    assert_eq!(hello["result"], json!({ "version": 1, "supportedVersions": [1, 2], "numbers": "decimal-strings" }));
+
+    let trades = client.subscribe("trades", Some(MARKET)).await;
+    let order_book = client.subscribe("orderbook", Some(MARKET)).await;
+    gateway.place(Side::Sell, dec!(20000.5), dec!(1.25)).await;
+    gateway.place(Side::Buy, dec!(20000.5), dec!(0.5)).await;
+
+    // Decimals stay strings and sequences stay integers
+    let trades = client.notifications_for(&trades, 1).await;
+    assert_eq!(trades[0]["params"]["data"]["price"], "20000.5");
+    assert_eq!(trades[0]["params"]["data"]["quantity"], "0.5");
+    let depth = client.notifications_for(&order_book, 2).await;
+    assert_eq!(depth[1]["params"]["data"]["asks"], json!([{ "price": "20000.5", "quantity": "0.75" }]));
+    assert_contiguous(&sequences(&depth));
+}
+
+#[tokio::test]
+async fn test_upgrade_is_not_compressed() {
+    let (_, addr) = Gateway::setup().await;
+
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    request.headers_mut().insert("accept-encoding", "gzip, br".parse().unwrap());
+    let (mut socket, response) = connect_async(request).await.expect("Failed to connect");
+
+    assert_eq!(response.status(), 101);
+    assert!(response.headers().get("content-encoding").is_none());
+
+    socket
+        .send(Message::Text(json!({ "id": "1", "method": "ping", "params": {} }).to_string()))
+        .await
+        .unwrap();
+    let reply = tokio::time::timeout(TIMEOUT, socket.next()).await.unwrap().unwrap().unwrap();
+    let reply: Value = serde_json::from_str(reply.to_text().unwrap()).unwrap();
+    assert_eq!(reply["id"], "1");
+    assert!(reply["result"]["pong"].is_string());
+}
+
+#[tokio::test]
+async fn test_trades_replay_from_sequence() {
+    let (gateway, addr) = Gateway::setup().await;
+    let mut client = Client::connect(addr).await;
+
+    // Trades 1 to 3 happen while the client is away
+    gateway.place(Side::Sell, dec!(20000), dec!(3)).await;
+    for _ in 0..3 {
+        gateway.place(Side::Buy, dec!(20000), dec!(0.5)).await;
+    }
+
+    let response = client.request("subscribe", json!({ "channel": "trades", "market": MARKET, "from_sequence": 1 })).await;
+    assert_shape(&response["result"], &[
+        ("subscriptionId", Kind::Uuid),
+        ("channel", Kind::String),
+        ("market", Kind::OptionalString),
+        ("replayed", Kind::Integer),
+        ("replayedThrough", Kind::Integer),
+    ]);
+    assert_eq!(response["result"]["replayed"], 2);
+    assert_eq!(response["result"]["replayedThrough"], 3);
+    let trades = response["result"]["subscriptionId"].as_str().unwrap().to_string();
+
+    // Missed trades come first, then live ones without repeats
+    gateway.place(Side::Buy, dec!(20000), dec!(0.5)).await;
+    let received = client.notifications_for(&trades, 3).await;
+    assert_eq!(sequences(&received), [2, 3, 4]);
+
+    // Only a market's trades can be replayed
+    for params in [
+        json!({ "channel": "trades", "from_sequence": 1 }),
+        json!({ "channel": "ticker", "market": MARKET, "from_sequence": 1 }),
+        json!({ "channel": "trades", "market": MARKET, "from_sequence": "latest" }),
+    ] {
+        let response = client.request("subscribe", params.clone()).await;
+        assert_eq!(response["error"]["code"], 400, "{}", params);
+    }
+}
+
+#[tokio::test]
+async fn test_trades_replay_across_a_restart() {
+    // In-memory services over trade history that outlives them
+    let repository = Arc::new(InMemoryMarketRepository::new());
+    let start = || async {
+        let state = AppState::new(
+            engine(&[spot(MARKET)]),
+            Arc::new(AccountService::new()),
+            Arc::new(MarketDataService::new().with_repository(repository.clone())),
+            vec![spot(MARKET)],
+        );
+        resume_trade_sequences(&state.matching_engine, &state.market_data_service, &[MARKET.to_string()]).await.unwrap();
+        Gateway::setup_with(state).await
+    };
+
+    // Trades 1 to 3 before the restart
+    let (before, _) = start().await;
+    before.place(Side::Sell, dec!(20000), dec!(3)).await;
+    for _ in 0..3 {
+        before.place(Side::Buy, dec!(20000), dec!(0.5)).await;
+    }
+
+    // Numbering carries on after it
+    let (gateway, addr) = start().await;
+    assert_eq!(gateway.state.matching_engine.last_trade_sequence(MARKET).unwrap(), 3);
+    gateway.place(Side::Sell, dec!(20000), dec!(3)).await;
+    gateway.place(Side::Buy, dec!(20000), dec!(0.5)).await;
+    let mut client = Client::connect(addr).await;
+    let response = client.request("subscribe", json!({ "channel": "trades", "market": MARKET, "from_sequence": 2 })).await;
+    assert_eq!(response["result"]["replayed"], 2);
+    assert_eq!(response["result"]["replayedThrough"], 4);
+    let trades = response["result"]["subscriptionId"].as_str().unwrap().to_string();
+    gateway.place(Side::Buy, dec!(20000), dec!(0.5)).await;
+    assert_eq!(sequences(&client.notifications_for(&trades, 3).await), [3, 4, 5]);
+
+    // A client ahead of the engine resumes from its last trade
+    let response = client.request("subscribe", json!({ "channel": "trades", "market": MARKET, "from_sequence": 100 })).await;
+    assert_eq!(response["result"]["replayed"], 0);
+    assert_eq!(response["result"]["replayedThrough"], 5);
+    let ahead = response["result"]["subscriptionId"].as_str().unwrap().to_string();
+    gateway.place(Side::Buy, dec!(20000), dec!(0.5)).await;
+    assert_eq!(sequences(&client.notifications_for(&ahead, 1).await), [6]);
+}
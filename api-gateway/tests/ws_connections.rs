@@ -0,0 +1,123 @@
+//! WebSocket connection inspector tests
+//!
+//! Opens WS connections against an in-process gateway, lists them through
+//! the admin API and closes one from there.
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use axum::Router;
+use common::{serve, Gateway, MARKET};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use uuid::Uuid;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+impl Gateway {
+    /// Gateway answering the admin key, with its WS endpoint served beside it
+    async fn setup() -> (Self, SocketAddr) {
+        let gateway = Self::start_admin();
+        let ws = Router::new()
+            .route("/ws", axum::routing::get(api_gateway::ws::handler::ws_handler))
+            .with_state(gateway.state.clone());
+        let addr = serve(ws).await;
+        (gateway, addr)
+    }
+}
+
+async fn connect(addr: SocketAddr) -> Socket {
+    connect_async(format!("ws://{}/ws", addr)).await.expect("Failed to connect").0
+}
+
+/// Subscribe and wait for the response
+async fn subscribe(socket: &mut Socket, params: Value) -> Value {
+    socket.send(Message::Text(json!({ "id": "1", "method": "subscribe", "params": params }).to_string())).await.unwrap();
+    loop {
+        let message = tokio::time::timeout(TIMEOUT, socket.next()).await.unwrap().unwrap().unwrap();
+        if let Message::Text(text) = message {
+            let value: Value = serde_json::from_str(&text).unwrap();
+            if value.get("id").is_some() {
+                return value;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_live_connections_are_listed_with_their_subscriptions_and_account() {
+    let (gateway, addr) = Gateway::setup().await;
+    let account_id = Uuid::new_v4();
+    let key = gateway.state.api_keys.issue(account_id);
+
+    let mut public = connect(addr).await;
+    let mut private = connect(addr).await;
+    subscribe(&mut public, json!({ "channel": "trades", "market": MARKET })).await;
+    subscribe(&mut public, json!({ "channel": "ticker", "market": MARKET })).await;
+    let response = subscribe(&mut private, json!({ "channel": "account", "apiKey": key })).await;
+    assert!(response["error"].is_null(), "{}", response);
+
+    let (status, body) = gateway.admin("GET", "/admin/ws/connections", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let connections = body["data"].as_array().unwrap();
+    assert_eq!(connections.len(), 2);
+
+    let private = connections.iter().find(|connection| connection["account_id"] == json!(account_id)).unwrap();
+    assert_eq!(private["subscriptions"][0]["channel"], "account");
+    assert_eq!(private["messages_received"], 1);
+    assert_eq!(private["client_address"], "127.0.0.1");
+
+    let public = connections.iter().find(|connection| connection["account_id"].is_null()).unwrap();
+    let mut channels: Vec<_> = public["subscriptions"].as_array().unwrap().iter().map(|s| s["channel"].as_str().unwrap()).collect();
+    channels.sort();
+    assert_eq!(channels, ["ticker", "trades"]);
+    assert_eq!(public["messages_received"], 2);
+    assert_eq!(public["lag"], 0);
+
+    let id = public["id"].as_str().unwrap();
+    let (status, body) = gateway.admin("GET", &format!("/admin/ws/connections/{}", id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["id"], id);
+}
+
+#[tokio::test]
+async fn test_operator_disconnect_closes_the_connection() {
+    let (gateway, addr) = Gateway::setup().await;
+    let mut socket = connect(addr).await;
+    subscribe(&mut socket, json!({ "channel": "trades", "market": MARKET })).await;
+
+    let (_, body) = gateway.admin("GET", "/admin/ws/connections", None).await;
+    let id = body["data"][0]["id"].as_str().unwrap().to_string();
+    let (status, body) = gateway.admin("DELETE", &format!("/admin/ws/connections/{}", id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["subscriptions"].as_array().unwrap().len(), 1);
+
+    // The socket ends and the connection and its subscription are gone
+    let closed = tokio::time::timeout(TIMEOUT, async {
+        while let Some(Ok(message)) = socket.next().await {
+            if message.is_close() {
+                break;
+            }
+        }
+    });
+    closed.await.expect("connection was not closed");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(gateway.state.ws_connections.is_empty());
+    assert_eq!(gateway.state.market_data_service.channel().subscription_count().await, 0);
+
+    let (status, _) = gateway.admin("GET", &format!("/admin/ws/connections/{}", id), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = gateway.admin("DELETE", &format!("/admin/ws/connections/{}", Uuid::new_v4()), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let entries = gateway.state.audit_log.recent(None, 10);
+    assert!(entries.iter().any(|entry| entry.action == "ws_connection.disconnected"));
+}
@@ -0,0 +1,111 @@
+//! WebSocket notification serialization tests
+//!
+//! Encodes the typed notifications from `ws::message` directly, without a
+//! server, in both schema versions.
+
+use api_gateway::number_format::NumberFormat;
+use api_gateway::ws::message::{AccountEvent, Notification, NotificationPayload, ProtocolVersion};
+use chrono::Utc;
+use common::decimal::dec;
+use market_data::channel::Topic;
+use market_data::{OrderBookUpdate, PriceLevel, Ticker, TradeMessage};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+const MARKET: &str = "BTC/USD";
+
+fn encode(topic: &Topic, payload: NotificationPayload, version: ProtocolVersion) -> (Uuid, Value) {
+    let subscription_id = Uuid::new_v4();
+    let text = Notification::new(subscription_id, payload).encode(topic, version).unwrap();
+    (subscription_id, serde_json::from_str(&text).unwrap())
+}
+
+fn order_book_update() -> NotificationPayload {
+    NotificationPayload::Orderbook(OrderBookUpdate {
+        market: MARKET.to_string(),
+        timestamp: Utc::now(),
+        sequence: 7,
+        bids: vec![PriceLevel { price: dec!(100), quantity: dec!(2) }],
+        asks: Vec::new(),
+    })
+}
+
+#[test]
+fn test_v1_market_and_all_market_notifications() {
+    let (subscription_id, notification) =
+        encode(&Topic::OrderBook(MARKET.to_string()), order_book_update(), ProtocolVersion::V1);
+    assert_eq!(notification["method"], "orderbook");
+    assert_eq!(notification["params"]["market"], MARKET);
+    assert_eq!(notification["params"]["subscription_id"], subscription_id.to_string());
+    assert_eq!(notification["params"]["data"]["sequence"], 7);
+    assert_eq!(notification["params"]["data"]["bids"], json!([{ "price": "100", "quantity": "2" }]));
+
+    let trade = NotificationPayload::Trades(TradeMessage {
+        id: Uuid::new_v4(),
+        market: MARKET.to_string(),
+        price: dec!(100),
+        quantity: dec!(1),
+        taker_side: "sell".to_string(),
+        is_buyer_maker: true,
+        timestamp: Utc::now(),
+        sequence: 12,
+    });
+    let (_, notification) = encode(&Topic::AllTrades, trade, ProtocolVersion::V1);
+    assert_eq!(notification["method"], "update");
+    assert!(notification["params"].get("market").is_none());
+    assert_eq!(notification["params"]["data"]["taker_side"], "sell");
+    assert_eq!(notification["params"]["data"]["sequence"], 12);
+}
+
+#[test]
+fn test_v2_notifications_name_channel_and_market() {
+    let (subscription_id, notification) = encode(&Topic::AllOrderBooks, order_book_update(), ProtocolVersion::V2);
+    assert_eq!(notification["version"], 2);
+    assert_eq!(notification["method"], "orderbook");
+    assert_eq!(notification["params"]["subscriptionId"], subscription_id.to_string());
+    assert_eq!(notification["params"]["channel"], "orderbook");
+    assert_eq!(notification["params"]["market"], MARKET);
+    assert_eq!(notification["params"]["data"]["sequence"], 7);
+
+    let account_id = Uuid::new_v4();
+    let released = NotificationPayload::Account(AccountEvent::KillSwitchReleased {
+        account_id,
+        actor: "admin".to_string(),
+        timestamp: Utc::now(),
+    });
+    let (_, notification) = encode(&Topic::Account(account_id), released, ProtocolVersion::V2);
+    assert_eq!(notification["method"], "account");
+    assert!(notification["params"].get("market").is_none());
+    assert_eq!(notification["params"]["data"]["type"], "kill_switch_released");
+}
+
+#[test]
+fn test_decimal_strings_format_leaves_no_fractional_numbers() {
+    let ticker = NotificationPayload::Ticker(Ticker {
+        market: MARKET.to_string(),
+        bid: Some(dec!(99.5)),
+        ask: None,
+        last: Some(dec!(100)),
+        change_24h: Some(dec!(2.5)),
+        change_24h_percent: Some(2.5),
+        high_24h: None,
+        low_24h: None,
+        volume_24h: Some(dec!(10)),
+        quote_volume_24h: None,
+        timestamp: Utc::now(),
+    });
+    let topic = Topic::Ticker(MARKET.to_string());
+
+    for version in ProtocolVersion::SUPPORTED {
+        let text = Notification::new(Uuid::new_v4(), ticker.clone()).encode(&topic, version).unwrap();
+        let native: Value = serde_json::from_str(&NumberFormat::Native.apply_to_text(text.clone())).unwrap();
+        assert_eq!(native["params"]["data"]["change_24h_percent"], json!(2.5));
+
+        let strings: Value = serde_json::from_str(&NumberFormat::DecimalStrings.apply_to_text(text)).unwrap();
+        let data = &strings["params"]["data"];
+        assert_eq!(data["bid"], "99.5");
+        assert_eq!(data["change_24h"], "2.5");
+        assert_eq!(data["change_24h_percent"], "2.5");
+        assert_eq!(data["ask"], Value::Null);
+    }
+}
@@ -0,0 +1,242 @@
+//! Fault injection for failure testing
+//!
+//! The services call [`Chaos::inject`] at a few defined points: market data
+//! repository calls, publication of trades to market data, settlement of
+//! trades against balances and crediting of deposits. A [`ChaosConfig`] gives each point a [`Fault`],
+//! a delay before the call and a share of calls that fail, so integration
+//! tests can check that gap repair, background settlement and the other
+//! recovery paths cope with partial failures.
+//!
+//! Faults are only injected when built with the test-only `chaos` feature;
+//! without it [`Chaos::inject`] does nothing.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+
+/// Where a fault may be injected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChaosPoint {
+    /// Market data repository calls
+    Repository,
+    /// Publication of trades to market data
+    Publication,
+    /// Settlement of trades against balances
+    Settlement,
+    /// Crediting of confirmed deposits and earn interest
+    Deposit,
+}
+
+impl ChaosPoint {
+    /// Every injection point
+    pub const ALL: [ChaosPoint; 4] = [ChaosPoint::Repository, ChaosPoint::Publication, ChaosPoint::Settlement, ChaosPoint::Deposit];
+
+    /// Name used in configuration
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChaosPoint::Repository => "repository",
+            ChaosPoint::Publication => "publication",
+            ChaosPoint::Settlement => "settlement",
+            ChaosPoint::Deposit => "deposit",
+        }
+    }
+}
+
+impl fmt::Display for ChaosPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ChaosPoint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|point| point.name() == s)
+            .ok_or_else(|| Error::ValidationError(format!("Unknown chaos point: {}", s)))
+    }
+}
+
+/// What happens to calls at one point
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fault {
+    /// Delay before every call
+    pub latency: Duration,
+    /// Calls failing before any succeeds
+    pub fail_first: u64,
+    /// Share of later calls failing, from 0 to 1
+    pub error_rate: f64,
+}
+
+impl Fault {
+    /// Fail the next `count` calls
+    pub fn fail_first(count: u64) -> Self {
+        Self { fail_first: count, ..Self::default() }
+    }
+
+    /// Fail a share of calls, from 0 to 1
+    pub fn error_rate(rate: f64) -> Self {
+        Self { error_rate: rate, ..Self::default() }
+    }
+
+    /// Delay every call by `latency`
+    pub fn latency(latency: Duration) -> Self {
+        Self { latency, ..Self::default() }
+    }
+}
+
+/// Faults by injection point
+///
+/// Parsed from `point=key:value,...;...` with the keys `latency_ms`,
+/// `fail_first` and `error_rate`, for example
+/// `settlement=latency_ms:50;publication=error_rate:0.1`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChaosConfig {
+    /// Fault of each point, none where absent
+    pub faults: BTreeMap<ChaosPoint, Fault>,
+    /// Seed choosing which calls fail at the configured error rates
+    pub seed: u64,
+}
+
+impl ChaosConfig {
+    /// Inject `fault` at `point`
+    pub fn with_fault(mut self, point: ChaosPoint, fault: Fault) -> Self {
+        self.faults.insert(point, fault);
+        self
+    }
+
+    /// Whether any fault is configured
+    pub fn is_enabled(&self) -> bool {
+        !self.faults.is_empty()
+    }
+}
+
+impl FromStr for ChaosConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut config = Self::default();
+        for entry in s.split(';').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (point, settings) = entry.split_once('=')
+                .ok_or_else(|| Error::ValidationError(format!("Expected point=settings: {}", entry)))?;
+            let mut fault = Fault::default();
+            for setting in settings.split(',').map(str::trim).filter(|setting| !setting.is_empty()) {
+                let (key, value) = setting.split_once(':')
+                    .ok_or_else(|| Error::ValidationError(format!("Expected key:value: {}", setting)))?;
+                let invalid = || Error::ValidationError(format!("Invalid {}: {}", key, value));
+                match key {
+                    "latency_ms" => fault.latency = Duration::from_millis(value.parse().map_err(|_| invalid())?),
+                    "fail_first" => fault.fail_first = value.parse().map_err(|_| invalid())?,
+                    "error_rate" => {
+                        fault.error_rate = value.parse().map_err(|_| invalid())?;
+                        if !(0.0..=1.0).contains(&fault.error_rate) {
+                            return Err(invalid());
+                        }
+                    }
+                    _ => return Err(Error::ValidationError(format!("Unknown chaos setting: {}", key))),
+                }
+            }
+            config.faults.insert(point.trim().parse()?, fault);
+        }
+        Ok(config)
+    }
+}
+
+/// Calls and failures at one point
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChaosCounts {
+    /// Calls that reached the point
+    pub calls: u64,
+    /// Calls failed by injection
+    pub failures: u64,
+}
+
+/// Injects the configured faults, shared by the services
+#[derive(Debug, Default)]
+pub struct Chaos {
+    faults: RwLock<BTreeMap<ChaosPoint, Fault>>,
+    counts: RwLock<BTreeMap<ChaosPoint, ChaosCounts>>,
+    seed: u64,
+    draws: AtomicU64,
+}
+
+/// Fault injection shared by the services
+pub type SharedChaos = Arc<Chaos>;
+
+impl Chaos {
+    /// Inject the faults of `config`
+    pub fn new(config: ChaosConfig) -> Self {
+        Self {
+            faults: RwLock::new(config.faults),
+            seed: config.seed,
+            ..Self::default()
+        }
+    }
+
+    /// No faults, shared
+    pub fn shared() -> SharedChaos {
+        Arc::new(Self::default())
+    }
+
+    /// Whether faults are injected in this build
+    pub const fn is_supported() -> bool {
+        cfg!(feature = "chaos")
+    }
+
+    /// Replace the fault at `point`, restarting its `fail_first` count
+    pub fn set_fault(&self, point: ChaosPoint, fault: Fault) {
+        self.faults.write().unwrap().insert(point, fault);
+        self.counts.write().unwrap().remove(&point);
+    }
+
+    /// Stop injecting faults at `point`
+    pub fn clear(&self, point: ChaosPoint) {
+        self.faults.write().unwrap().remove(&point);
+    }
+
+    /// Calls and failures at `point` since its fault was set
+    pub fn counts(&self, point: ChaosPoint) -> ChaosCounts {
+        self.counts.read().unwrap().get(&point).copied().unwrap_or_default()
+    }
+
+    /// Apply the fault at `point` to a call: wait out its latency, then fail
+    /// it if it is among the first to fail or drawn at the error rate
+    pub async fn inject(&self, point: ChaosPoint) -> Result<()> {
+        if !Self::is_supported() {
+            return Ok(());
+        }
+        let Some(fault) = self.faults.read().unwrap().get(&point).cloned() else {
+            return Ok(());
+        };
+        #[cfg(feature = "chaos")]
+        if !fault.latency.is_zero() {
+            tokio::time::sleep(fault.latency).await;
+        }
+
+        let mut counts = self.counts.write().unwrap();
+        let counts = counts.entry(point).or_default();
+        counts.calls += 1;
+        if counts.calls <= fault.fail_first || self.draw() < fault.error_rate {
+            counts.failures += 1;
+            return Err(Error::Internal(format!("Injected {} failure", point)));
+        }
+        Ok(())
+    }
+
+    /// Next of a seeded sequence of numbers from 0 to 1
+    fn draw(&self) -> f64 {
+        // SplitMix64 over the seed and the number of draws so far
+        let mut z = self.seed.wrapping_add(self.draws.fetch_add(1, Ordering::Relaxed).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,66 @@
+//! Time sources
+//!
+//! Services read the current time through a [`Clock`] rather than the system
+//! time directly, so tests and backtests can control it with a
+//! [`ManualClock`].
+
+use std::fmt::Debug;
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Source of the current time
+pub trait Clock: Debug + Send + Sync {
+    /// Current time
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Clock shared between services
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The system clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl SystemClock {
+    /// The system clock, shared
+    pub fn shared() -> SharedClock {
+        Arc::new(SystemClock)
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock that stands still until set or advanced
+#[derive(Debug)]
+pub struct ManualClock {
+    now: RwLock<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    /// Clock standing at `start`
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self { now: RwLock::new(start) }
+    }
+
+    /// Move the clock to `at`, which may be in its past
+    pub fn set(&self, at: DateTime<Utc>) {
+        *self.now.write().unwrap() = at;
+    }
+
+    /// Move the clock forward by `by`
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.write().unwrap();
+        *now += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.read().unwrap()
+    }
+}
@@ -0,0 +1,78 @@
+//! Disposable PostgreSQL fixtures for tests
+//!
+//! Each fixture owns its own database: either a fresh container started via
+//! testcontainers, or the server named by `TEST_DATABASE_URL` when set.
+//! Migrations are applied before the fixture is handed out.
+
+use std::env;
+
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use testcontainers_modules::postgres::Postgres;
+use testcontainers_modules::testcontainers::{runners::AsyncRunner, ContainerAsync};
+
+use super::run_migrations;
+
+/// A migrated PostgreSQL database that lives as long as the fixture
+pub struct PostgresFixture {
+    /// Connection pool for the database
+    pub pool: PgPool,
+    /// Connection URL for the database
+    pub database_url: String,
+    /// Container backing the database, removed when the fixture is dropped
+    _container: Option<ContainerAsync<Postgres>>,
+}
+
+impl PostgresFixture {
+    /// Start a database for a test
+    ///
+    /// Panics if neither Docker nor `TEST_DATABASE_URL` gives a reachable
+    /// server, so a missing database fails the run instead of passing it.
+    /// Returns `None` only when `SKIP_POSTGRES_TESTS=1` opts out explicitly.
+    pub async fn start() -> Option<Self> {
+        if env::var("SKIP_POSTGRES_TESTS").is_ok_and(|value| value == "1") {
+            eprintln!("Skipping PostgreSQL test: SKIP_POSTGRES_TESTS=1");
+            return None;
+        }
+
+        let (database_url, container) = match env::var("TEST_DATABASE_URL") {
+            Ok(url) => (url, None),
+            Err(_) => Self::start_container().await.unwrap_or_else(|e| {
+                panic!("PostgreSQL test fixture {}; start Docker, set TEST_DATABASE_URL, or set SKIP_POSTGRES_TESTS=1", e)
+            }),
+        };
+
+        let pool = PgPoolOptions::new()
+            .max_connections(10)
+            .connect(&database_url)
+            .await
+            .unwrap_or_else(|e| panic!("PostgreSQL test fixture failed to connect to {}: {}", database_url, e));
+
+        run_migrations(&pool)
+            .await
+            .expect("Failed to run migrations on test database");
+
+        Some(Self {
+            pool,
+            database_url,
+            _container: container,
+        })
+    }
+
+    async fn start_container() -> Result<(String, Option<ContainerAsync<Postgres>>), String> {
+        let container = Postgres::default()
+            .start()
+            .await
+            .map_err(|e| format!("failed to start container: {}", e))?;
+        let host = container
+            .get_host()
+            .await
+            .map_err(|e| format!("failed to resolve container host: {}", e))?;
+        let port = container
+            .get_host_port_ipv4(5432)
+            .await
+            .map_err(|e| format!("failed to resolve container port: {}", e))?;
+
+        let url = format!("postgres://postgres:postgres@{}:{}/postgres", host, port);
+        Ok((url, Some(container)))
+    }
+}
@@ -6,6 +6,8 @@ use crate::error::Result;
 pub mod models;
 pub mod queries;
 pub mod transaction;
+#[cfg(feature = "test-fixtures")]
+pub mod fixture;
 
 // Re-export transaction types
 pub use transaction::{
@@ -38,6 +38,8 @@ pub struct DbOrder {
     pub quantity: Decimal,
     pub filled_quantity: Decimal,
     pub status: OrderStatus,
+    pub reject_reason: Option<String>,
+    pub reject_message: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -4,7 +4,7 @@ use rust_decimal::Decimal;
 
 use crate::error::Result;
 use crate::model::account::Account;
-use crate::model::market::Market;
+use crate::model::market::{Market, MarketKind};
 use crate::model::order::{Order, Side, OrderType};
 use crate::model::trade::Trade;
 use chrono::Utc;
@@ -18,8 +18,10 @@ pub async fn create_account(_pool: &PgPool, _user_id: &str) -> Result<Account> {
     // Mock implementation
     Ok(Account {
         id,
+        external_id: None,
         created_at: now,
         updated_at: now,
+        closed_at: None,
     })
 }
 
@@ -29,8 +31,10 @@ pub async fn get_account_by_id(_pool: &PgPool, id: Uuid) -> Result<Option<Accoun
     // Mock implementation
     Ok(Some(Account {
         id,
+        external_id: None,
         created_at: now,
         updated_at: now,
+        closed_at: None,
     }))
 }
 
@@ -121,6 +125,7 @@ pub async fn create_market(
         min_order_size: min_quantity,
         max_price_deviation: 0.05,  // Default 5% max deviation
         trading_enabled: true,
+        kind: MarketKind::Spot,
     })
 }
 
@@ -149,6 +154,14 @@ pub async fn create_order(
         filled_quantity: Decimal::ZERO,
         remaining_quantity: quantity,
         average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
         time_in_force: crate::model::order::TimeInForce::GTC, // Default
         status: crate::model::order::Status::New,
         created_at: now,
@@ -169,21 +182,15 @@ pub async fn create_trade(
     quantity: Decimal,
     taker_side: Side
 ) -> Result<Trade> {
-    let amount = price * quantity;
-    let now = Utc::now();
-    
     // Mock implementation
-    Ok(Trade {
-        id: Uuid::new_v4(),
-        market: market.to_string(),
+    Ok(Trade::new(
+        market.to_string(),
         price,
         quantity,
-        amount,
         buyer_order_id,
         seller_order_id,
         buyer_id,
         seller_id,
         taker_side,
-        created_at: now,
-    })
+    ))
 }
\ No newline at end of file
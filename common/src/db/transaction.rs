@@ -2,7 +2,7 @@
 //!
 //! This module provides a standardized approach to database transactions
 //! across all services. It defines traits for transaction management
-//! and concrete implementations for PostgreSQL.
+//! and concrete implementations for PostgreSQL and in-memory stores.
 
 use async_trait::async_trait;
 use sqlx::{PgPool, Transaction as SqlxTransaction, Postgres};
@@ -107,8 +107,17 @@ impl TransactionManager for PgTransactionManager {
     }
 }
 
+/// Change staged in an in-memory transaction
+type Leg = Box<dyn FnOnce() + Send>;
+
 /// In-memory transaction for testing
+///
+/// Repositories stage each write as a leg instead of applying it. Committing
+/// applies the legs in the order they were staged; rolling back, or dropping
+/// the transaction, discards them, so in-memory stores give the same
+/// all-or-nothing outcome as a PostgreSQL transaction.
 pub struct InMemoryTransaction {
+    legs: Vec<Leg>,
     committed: bool,
     rolled_back: bool,
 }
@@ -117,11 +126,22 @@ impl InMemoryTransaction {
     /// Create a new in-memory transaction
     pub fn new() -> Self {
         Self {
+            legs: Vec::new(),
             committed: false,
             rolled_back: false,
         }
     }
     
+    /// Stage a change, applied only if the transaction commits
+    pub fn stage(&mut self, leg: impl FnOnce() + Send + 'static) {
+        self.legs.push(Box::new(leg));
+    }
+    
+    /// Number of changes staged so far
+    pub fn staged(&self) -> usize {
+        self.legs.len()
+    }
+    
     /// Check if this transaction was committed
     pub fn is_committed(&self) -> bool {
         self.committed
@@ -141,14 +161,18 @@ impl InMemoryTransaction {
         Ok(1)
     }
     
-    /// Commit the transaction
+    /// Commit the transaction, applying its staged changes in order
     pub async fn commit(mut self) -> Result<()> {
+        for leg in self.legs.drain(..) {
+            leg();
+        }
         self.committed = true;
         Ok(())
     }
     
-    /// Rollback the transaction
+    /// Rollback the transaction, discarding its staged changes
     pub async fn rollback(mut self) -> Result<()> {
+        self.legs.clear();
         self.rolled_back = true;
         Ok(())
     }
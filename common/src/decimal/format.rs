@@ -0,0 +1,190 @@
+//! Display formatting of prices, quantities and amounts
+//!
+//! Values shown to clients and written to logs are rounded to the precision
+//! of their market or asset with one [`RoundingMode`], and are always written
+//! the same way whatever the host locale: a `.` separator, no digit grouping,
+//! no exponent and no trailing zeros. Markets and assets that were never
+//! registered use [`PRICE_PRECISION`] and [`QUANTITY_PRECISION`].
+//!
+//! The free functions format through the process-wide [`DisplayFormat`],
+//! which the services fill in as they learn about markets and assets.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{OnceLock, RwLock};
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+
+use super::precision::{PRICE_PRECISION, QUANTITY_PRECISION};
+use super::{Amount, Price, Quantity};
+
+/// How values are rounded to their display precision
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoundingMode {
+    /// Round to nearest, ties to even
+    #[default]
+    HalfEven,
+    /// Round to nearest, ties away from zero
+    HalfUp,
+    /// Truncate towards zero
+    Down,
+}
+
+impl RoundingMode {
+    /// Name of the mode, e.g. `half-even`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoundingMode::HalfEven => "half-even",
+            RoundingMode::HalfUp => "half-up",
+            RoundingMode::Down => "down",
+        }
+    }
+
+    fn strategy(self) -> RoundingStrategy {
+        match self {
+            RoundingMode::HalfEven => RoundingStrategy::MidpointNearestEven,
+            RoundingMode::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            RoundingMode::Down => RoundingStrategy::ToZero,
+        }
+    }
+}
+
+impl fmt::Display for RoundingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for RoundingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "half-even" => Ok(RoundingMode::HalfEven),
+            "half-up" => Ok(RoundingMode::HalfUp),
+            "down" => Ok(RoundingMode::Down),
+            other => Err(format!("unknown rounding mode {:?}, expected half-even, half-up or down", other)),
+        }
+    }
+}
+
+/// Decimal places prices and quantities of a market are shown with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketPrecision {
+    /// Places of prices
+    pub price: u32,
+    /// Places of quantities
+    pub quantity: u32,
+}
+
+impl MarketPrecision {
+    /// Places implied by a market's price tick and quantity step, e.g. 2 for a tick of `0.01`
+    pub fn from_steps(price_tick: Price, quantity_step: Quantity) -> Self {
+        Self {
+            price: price_tick.normalize().scale(),
+            quantity: quantity_step.normalize().scale(),
+        }
+    }
+}
+
+impl Default for MarketPrecision {
+    fn default() -> Self {
+        Self {
+            price: PRICE_PRECISION,
+            quantity: QUANTITY_PRECISION,
+        }
+    }
+}
+
+/// Precision of every known market and asset, and the rounding mode
+#[derive(Debug, Default)]
+pub struct DisplayFormat {
+    markets: RwLock<HashMap<String, MarketPrecision>>,
+    assets: RwLock<HashMap<String, u32>>,
+    rounding: RwLock<RoundingMode>,
+}
+
+impl DisplayFormat {
+    /// Format with no markets or assets registered, rounding half to even
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The format shared by the process
+    pub fn global() -> &'static DisplayFormat {
+        static GLOBAL: OnceLock<DisplayFormat> = OnceLock::new();
+        GLOBAL.get_or_init(DisplayFormat::new)
+    }
+
+    /// Show a market's prices and quantities with the given places
+    pub fn register_market(&self, symbol: &str, precision: MarketPrecision) {
+        self.markets.write().unwrap().insert(symbol.to_string(), precision);
+    }
+
+    /// Show an asset's amounts with the given places
+    pub fn register_asset(&self, code: &str, precision: u32) {
+        self.assets.write().unwrap().insert(code.to_uppercase(), precision);
+    }
+
+    /// Round with the given mode from now on
+    pub fn set_rounding_mode(&self, mode: RoundingMode) {
+        *self.rounding.write().unwrap() = mode;
+    }
+
+    /// Mode values are rounded with
+    pub fn rounding_mode(&self) -> RoundingMode {
+        *self.rounding.read().unwrap()
+    }
+
+    /// Places a market is shown with, the defaults if it is not registered
+    pub fn market(&self, symbol: &str) -> MarketPrecision {
+        self.markets.read().unwrap().get(symbol).copied().unwrap_or_default()
+    }
+
+    /// Places an asset is shown with, the default quantity places if it is not registered
+    pub fn asset(&self, code: &str) -> u32 {
+        self.assets.read().unwrap().get(&code.to_uppercase()).copied().unwrap_or(QUANTITY_PRECISION)
+    }
+
+    /// A price of a market
+    pub fn price(&self, symbol: &str, price: Price) -> String {
+        self.round(price, self.market(symbol).price)
+    }
+
+    /// A quantity of a market's base asset
+    pub fn quantity(&self, symbol: &str, quantity: Quantity) -> String {
+        self.round(quantity, self.market(symbol).quantity)
+    }
+
+    /// An amount of an asset
+    pub fn amount(&self, asset: &str, amount: Amount) -> String {
+        self.round(amount, self.asset(asset))
+    }
+
+    fn round(&self, value: Decimal, places: u32) -> String {
+        format_decimal(value.round_dp_with_strategy(places, self.rounding_mode().strategy()))
+    }
+}
+
+/// A price of a market, rounded to the market's price precision
+pub fn format_price(symbol: &str, price: Price) -> String {
+    DisplayFormat::global().price(symbol, price)
+}
+
+/// A quantity of a market, rounded to the market's quantity precision
+pub fn format_quantity(symbol: &str, quantity: Quantity) -> String {
+    DisplayFormat::global().quantity(symbol, quantity)
+}
+
+/// An amount of an asset, rounded to the asset's precision
+pub fn format_amount(asset: &str, amount: Amount) -> String {
+    DisplayFormat::global().amount(asset, amount)
+}
+
+/// A value written in full without trailing zeros, for storage and wire formats that must not round
+pub fn format_decimal(value: Decimal) -> String {
+    value.normalize().to_string()
+}
@@ -3,6 +3,10 @@
 use rust_decimal::Decimal;
 pub use rust_decimal_macros::dec;
 
+pub mod format;
+
+pub use format::{format_amount, format_decimal, format_price, format_quantity, DisplayFormat, RoundingMode};
+
 /// Price type with high precision
 pub type Price = Decimal;
 
@@ -34,6 +34,10 @@ pub enum Error {
     #[error("Validation error: {0}")]
     ValidationError(String),
     
+    /// Request conflicts with the current state of a resource
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    
     /// Configuration error
     #[error("Configuration error: {0}")]
     ConfigurationError(String),
@@ -95,6 +99,7 @@ impl<T> ErrorExt<T> for Result<T> {
                 Error::MarketNotFound(msg) => Error::MarketNotFound(format!("{}: {}", context, msg)),
                 Error::AccountNotFound(msg) => Error::AccountNotFound(format!("{}: {}", context, msg)),
                 Error::ValidationError(msg) => Error::ValidationError(format!("{}: {}", context, msg)),
+                Error::Conflict(msg) => Error::Conflict(format!("{}: {}", context, msg)),
                 Error::ConfigurationError(msg) => Error::ConfigurationError(format!("{}: {}", context, msg)),
                 Error::AuthorizationError(msg) => Error::AuthorizationError(format!("{}: {}", context, msg)),
                 Error::RateLimitExceeded(msg) => Error::RateLimitExceeded(format!("{}: {}", context, msg)),
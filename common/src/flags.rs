@@ -0,0 +1,136 @@
+//! Runtime feature flags
+//!
+//! Experimental behaviors are rolled out behind named flags that operators
+//! turn on and off while the engine runs. Every flag is declared in [`FLAGS`]
+//! with its default; the services consult one shared [`FeatureFlags`] store,
+//! so a toggle takes effect on the next order or request that checks it.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+#[cfg(feature = "utoipa")]
+use crate::utoipa::ToSchema;
+
+/// Settle trades on the background settlement pipeline rather than inline
+pub const NEW_SETTLEMENT_PIPELINE: &str = "new_settlement_pipeline";
+/// Let better priced orders prune the farthest level of a full book
+pub const BOOK_LEVEL_PRUNING: &str = "book_level_pruning";
+/// Offer margin trading
+pub const ENABLE_MARGIN: &str = "enable_margin";
+
+/// A declared flag
+#[derive(Debug, Clone, Copy)]
+pub struct FlagSpec {
+    /// Flag name
+    pub name: &'static str,
+    /// What the flag turns on
+    pub description: &'static str,
+    /// Whether the flag is on unless configured otherwise
+    pub default: bool,
+}
+
+/// Every flag the services know about
+pub const FLAGS: &[FlagSpec] = &[
+    FlagSpec {
+        name: NEW_SETTLEMENT_PIPELINE,
+        description: "Settle trades on background workers after answering the placement",
+        default: true,
+    },
+    FlagSpec {
+        name: BOOK_LEVEL_PRUNING,
+        description: "Let better priced orders prune the farthest level of markets with the prune-farthest policy",
+        default: true,
+    },
+    FlagSpec {
+        name: ENABLE_MARGIN,
+        description: "Offer margin trading",
+        default: false,
+    },
+];
+
+/// Look up a declared flag
+pub fn spec(name: &str) -> Option<&'static FlagSpec> {
+    FLAGS.iter().find(|spec| spec.name == name)
+}
+
+/// A flag and whether it is on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct FlagState {
+    /// Flag name
+    pub name: String,
+    /// Whether the flag is on
+    pub enabled: bool,
+    /// Whether the flag is on unless configured otherwise
+    pub default: bool,
+    /// What the flag turns on
+    pub description: String,
+}
+
+/// Current value of every declared flag
+#[derive(Debug)]
+pub struct FeatureFlags {
+    values: RwLock<BTreeMap<&'static str, bool>>,
+}
+
+/// Flags shared by the services
+pub type SharedFeatureFlags = Arc<FeatureFlags>;
+
+impl FeatureFlags {
+    /// Every flag at its default
+    pub fn new() -> Self {
+        Self {
+            values: RwLock::new(FLAGS.iter().map(|spec| (spec.name, spec.default)).collect()),
+        }
+    }
+
+    /// Every flag at its default, shared
+    pub fn shared() -> SharedFeatureFlags {
+        Arc::new(Self::new())
+    }
+
+    /// Start the given flags at the given values instead of their defaults
+    pub fn with_overrides(self, overrides: &BTreeMap<String, bool>) -> Result<Self> {
+        for (name, enabled) in overrides {
+            self.set(name, *enabled)?;
+        }
+        Ok(self)
+    }
+
+    /// Whether a flag is on; undeclared flags are off
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.values.read().unwrap().get(name).copied().unwrap_or(false)
+    }
+
+    /// Turn a flag on or off, returning whether it was on
+    pub fn set(&self, name: &str, enabled: bool) -> Result<bool> {
+        let spec = spec(name).ok_or_else(|| Error::ValidationError(format!("Unknown feature flag {}", name)))?;
+        let previous = self.values.write().unwrap().insert(spec.name, enabled);
+        Ok(previous.unwrap_or(spec.default))
+    }
+
+    /// Every declared flag and whether it is on, by name
+    pub fn list(&self) -> Vec<FlagState> {
+        let values = self.values.read().unwrap();
+        let mut flags: Vec<FlagState> = FLAGS
+            .iter()
+            .map(|spec| FlagState {
+                name: spec.name.to_string(),
+                enabled: values.get(spec.name).copied().unwrap_or(spec.default),
+                default: spec.default,
+                description: spec.description.to_string(),
+            })
+            .collect();
+        flags.sort_by(|a, b| a.name.cmp(&b.name));
+        flags
+    }
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self::new()
+    }
+}
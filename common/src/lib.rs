@@ -4,7 +4,11 @@
 //! all microservices in the trading platform. It provides a unified approach to
 //! error handling, database access, and domain models.
 
+pub mod chaos;
+pub mod clock;
 pub mod error;
+pub mod flags;
+pub mod id;
 pub mod model;
 pub mod decimal;
 pub mod db;
@@ -1,10 +1,12 @@
 //! Account models and related types
 
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::decimal::Quantity;
+use crate::decimal::{Amount, Price, Quantity};
+use crate::model::order::Side;
 #[cfg(feature = "utoipa")]
 use crate::utoipa::ToSchema;
 
@@ -14,15 +16,29 @@ use crate::utoipa::ToSchema;
 pub struct Account {
     /// Unique account ID
     pub id: Uuid,
+    /// Reference of the account in an external system, unique when set
+    #[serde(default)]
+    pub external_id: Option<String>,
     /// Account creation timestamp
     pub created_at: DateTime<Utc>,
     /// Last update timestamp
     pub updated_at: DateTime<Utc>,
+    /// Closure timestamp; closed accounts keep their records but can no longer trade or move funds
+    #[serde(default)]
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+impl Account {
+    /// Check whether the account has been closed
+    pub fn is_closed(&self) -> bool {
+        self.closed_at.is_some()
+    }
 }
 
 /// Balance model
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct Balance {
     /// Account ID
     pub account_id: Uuid,
@@ -38,6 +54,316 @@ pub struct Balance {
     pub updated_at: DateTime<Utc>,
 }
 
+/// Balance of an account in an asset at one point in time, for charting
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct BalanceSnapshot {
+    /// Account ID
+    pub account_id: Uuid,
+    /// Asset symbol
+    pub asset: String,
+    /// Total balance
+    pub total: Quantity,
+    /// Available balance
+    pub available: Quantity,
+    /// Balance locked in open orders
+    pub locked: Quantity,
+    /// When the snapshot was taken
+    pub taken_at: DateTime<Utc>,
+}
+
+impl BalanceSnapshot {
+    /// Snapshot of a balance taken at `taken_at`
+    pub fn of(balance: &Balance, taken_at: DateTime<Utc>) -> Self {
+        Self {
+            account_id: balance.account_id,
+            asset: balance.asset.clone(),
+            total: balance.total,
+            available: balance.available,
+            locked: balance.locked,
+            taken_at,
+        }
+    }
+}
+
+/// Balances of every account in one asset, summed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct AssetTotal {
+    /// Asset symbol
+    pub asset: String,
+    /// Accounts holding a balance of the asset
+    pub holders: usize,
+    /// Total balance
+    pub total: Quantity,
+    /// Available balance
+    pub available: Quantity,
+    /// Balance locked in open orders
+    pub locked: Quantity,
+}
+
+/// Number of accounts and their balances by asset
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct AccountTotals {
+    /// Accounts, open and closed
+    pub accounts: usize,
+    /// Accounts that have been closed
+    pub closed: usize,
+    /// Balances by asset, in asset order
+    pub balances: Vec<AssetTotal>,
+}
+
+/// Funds locked for one open order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct Reservation {
+    /// Order the funds are locked for
+    pub order_id: Uuid,
+    /// Account ID
+    pub account_id: Uuid,
+    /// Market the order trades in
+    pub market: String,
+    /// Order side
+    pub side: Side,
+    /// Whether the order may only shrink the account's position
+    #[serde(default)]
+    pub reduce_only: bool,
+    /// Asset symbol the funds are locked in
+    pub asset: String,
+    /// Amount still locked for the order
+    pub amount: Quantity,
+    /// Unfilled order quantity the amount covers
+    pub quantity: Quantity,
+    /// Reservation timestamp
+    pub created_at: DateTime<Utc>,
+}
+
+/// Net quantity of a market's base asset an account has traded into
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct Position {
+    /// Account ID
+    pub account_id: Uuid,
+    /// Market symbol (e.g., "BTC/USD")
+    pub market: String,
+    /// Bought minus sold quantity: positive when long, negative when short
+    pub quantity: Quantity,
+}
+
+/// Funding one position paid or received at a perpetual market's funding time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct FundingPayment {
+    /// Account ID
+    pub account_id: Uuid,
+    /// Perpetual market symbol
+    pub market: String,
+    /// Asset the funding is paid in, the market's quote asset
+    pub asset: String,
+    /// Position the funding was charged on, negative when short
+    pub position: Quantity,
+    /// Funding rate for the period: positive when longs pay shorts
+    pub rate: Decimal,
+    /// Mark price the position was valued at
+    pub mark_price: Price,
+    /// Amount paid, negative when received
+    pub amount: Amount,
+    /// Funding time
+    pub settled_at: DateTime<Utc>,
+}
+
+/// Address an account may withdraw an asset to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct WithdrawalAddress {
+    /// Unique address ID
+    pub id: Uuid,
+    /// Account ID
+    pub account_id: Uuid,
+    /// Asset symbol (e.g., "BTC", "USD")
+    pub asset: String,
+    /// Destination address or account number
+    pub address: String,
+    /// Account holder's name for the address
+    pub label: Option<String>,
+    /// Whitelisting timestamp
+    pub created_at: DateTime<Utc>,
+}
+
+/// What an operator's balance adjustment is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum AdjustmentKind {
+    /// Fees handed back to the account, always a credit
+    FeeRebate,
+    /// Any other correction, a credit or a debit
+    Manual,
+}
+
+/// Why an operator adjusted a balance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ReasonCode {
+    /// Fees were charged at the wrong rate
+    FeeOvercharge,
+    /// A volume tier or rebate program was applied late
+    VolumeTier,
+    /// A promotion or trading competition
+    Promotion,
+    /// An operational error, such as a misapplied deposit
+    ErrorCorrection,
+    /// A goodwill gesture
+    Goodwill,
+    /// Anything else, explained in the note
+    Other,
+}
+
+/// Credit or debit an operator posted to an account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct BalanceAdjustment {
+    /// Unique adjustment ID
+    pub id: Uuid,
+    /// Account ID
+    pub account_id: Uuid,
+    /// Fee rebate or manual adjustment
+    pub kind: AdjustmentKind,
+    /// Asset symbol (e.g., "BTC", "USD")
+    pub asset: String,
+    /// Amount credited, negative when debited
+    pub amount: Amount,
+    /// Why the adjustment was made
+    pub reason_code: ReasonCode,
+    /// Free-text explanation, required with [`ReasonCode::Other`]
+    pub note: Option<String>,
+    /// Who made it
+    pub actor: String,
+    /// When it was posted
+    pub created_at: DateTime<Utc>,
+}
+
+/// What moved an account's balance, on its statement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum StatementEntryKind {
+    /// One asset leg of a settled trade, net of its fee
+    Trade,
+    /// Reversal of a trade leg by a bust
+    TradeBust,
+    /// Funding paid or received on a perpetual position
+    Funding,
+    /// Fees handed back by an operator
+    FeeRebate,
+    /// Other operator adjustment
+    Adjustment,
+}
+
+/// One change to one of an account's balances
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct StatementEntry {
+    /// When the change was made
+    pub timestamp: DateTime<Utc>,
+    /// What made it
+    pub kind: StatementEntryKind,
+    /// Asset symbol
+    pub asset: String,
+    /// Amount credited, negative when debited
+    pub amount: Amount,
+    /// Fee charged, in `asset`, already taken from `amount`
+    pub fee: Amount,
+    /// Trade or adjustment the change belongs to, if it has an ID
+    pub reference: Option<Uuid>,
+    /// Market of a trade or funding payment
+    pub market: Option<String>,
+    /// Reason code of an adjustment
+    pub reason_code: Option<ReasonCode>,
+    /// Note of an adjustment, or reason of a bust
+    pub note: Option<String>,
+}
+
+/// What an account is allowed to do, set by operators
+///
+/// Accounts without permissions of their own get [`Default`], which allows
+/// everything. The permissions also make up the scopes of the account's API
+/// keys.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct AccountPermissions {
+    /// Whether the account may place orders
+    pub can_trade: bool,
+    /// Whether the account may withdraw funds
+    pub can_withdraw: bool,
+    /// Markets the account may trade, or any market when unset
+    #[serde(default)]
+    pub allowed_markets: Option<Vec<String>>,
+    /// Most leverage the account's orders may use, or no cap when unset
+    ///
+    /// Orders are fully collateralised, so spot orders trade at 1x and any
+    /// cap of at least 1 allows them.
+    #[serde(default)]
+    pub max_leverage: Option<Decimal>,
+}
+
+impl Default for AccountPermissions {
+    fn default() -> Self {
+        Self {
+            can_trade: true,
+            can_withdraw: true,
+            allowed_markets: None,
+            max_leverage: None,
+        }
+    }
+}
+
+impl AccountPermissions {
+    /// Check whether the account may trade a market
+    pub fn allows_market(&self, market: &str) -> bool {
+        self.allowed_markets
+            .as_ref()
+            .is_none_or(|markets| markets.iter().any(|allowed| allowed == market))
+    }
+
+    /// Check an order on `market` using `leverage` is allowed
+    pub fn check_order(&self, market: &str, leverage: Decimal) -> Result<(), String> {
+        if !self.can_trade {
+            return Err("Trading is not permitted".to_string());
+        }
+        if !self.allows_market(market) {
+            return Err(format!("Trading {} is not permitted", market));
+        }
+        match self.max_leverage {
+            Some(max_leverage) if leverage > max_leverage => {
+                Err(format!("Leverage {}x exceeds the permitted {}x", leverage, max_leverage))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// API key scopes granted by these permissions, e.g. `["read", "trade", "market:BTC/USD"]`
+    pub fn scopes(&self) -> Vec<String> {
+        let mut scopes = vec!["read".to_string()];
+        if self.can_trade {
+            scopes.push("trade".to_string());
+            if let Some(markets) = &self.allowed_markets {
+                scopes.extend(markets.iter().map(|market| format!("market:{}", market)));
+            }
+            if let Some(max_leverage) = self.max_leverage {
+                scopes.push(format!("leverage:{}", max_leverage.normalize()));
+            }
+        }
+        if self.can_withdraw {
+            scopes.push("withdraw".to_string());
+        }
+        scopes
+    }
+}
+
 impl Balance {
     /// Create a new balance with zero amounts
     pub fn new(account_id: Uuid, asset: String) -> Self {
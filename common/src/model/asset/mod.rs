@@ -0,0 +1,107 @@
+//! Asset registry entries
+//!
+//! Every asset the platform holds balances in can be registered with its
+//! display name, the decimal places its amounts are quoted in and its
+//! deposit and withdrawal rules. Assets that are not registered fall back to
+//! [`QUANTITY_PRECISION`] places with no withdrawal minimum or fee.
+
+use serde::{Deserialize, Serialize};
+
+use crate::decimal::precision::QUANTITY_PRECISION;
+use crate::decimal::Quantity;
+use crate::error::{Error, Result};
+#[cfg(feature = "utoipa")]
+use crate::utoipa::ToSchema;
+
+/// Most decimal places an asset can be registered with
+pub const MAX_ASSET_PRECISION: u32 = 18;
+
+/// An asset and the rules for moving it in and out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct Asset {
+    /// Asset code, e.g. `BTC`
+    pub code: String,
+    /// Display name, e.g. `Bitcoin`
+    pub name: String,
+    /// Decimal places deposits, withdrawals and order quantities may have
+    pub precision: u32,
+    /// Smallest amount that can be withdrawn
+    pub withdrawal_min: Quantity,
+    /// Fee kept from every withdrawal, deducted from the amount paid out
+    pub withdrawal_fee: Quantity,
+    /// Whether deposits are accepted
+    pub deposit_enabled: bool,
+}
+
+impl Asset {
+    /// Asset with no withdrawal minimum or fee, open for deposits
+    pub fn new(code: &str, name: &str, precision: u32) -> Self {
+        Self {
+            code: code.trim().to_uppercase(),
+            name: name.to_string(),
+            precision,
+            withdrawal_min: Quantity::ZERO,
+            withdrawal_fee: Quantity::ZERO,
+            deposit_enabled: true,
+        }
+    }
+
+    /// Rules of an asset that is not registered
+    pub fn unlisted(code: &str) -> Self {
+        Self::new(code, code, QUANTITY_PRECISION)
+    }
+
+    /// Check the entry can be registered
+    pub fn validate(&self) -> Result<()> {
+        if self.code.is_empty() || !self.code.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(Error::ValidationError(format!("Invalid asset code {:?}", self.code)));
+        }
+        if self.precision > MAX_ASSET_PRECISION {
+            return Err(Error::ValidationError(format!(
+                "Precision of {} must be at most {}", self.code, MAX_ASSET_PRECISION
+            )));
+        }
+        for (name, value) in [("withdrawal_min", self.withdrawal_min), ("withdrawal_fee", self.withdrawal_fee)] {
+            if value.is_sign_negative() {
+                return Err(Error::ValidationError(format!("{} of {} must not be negative", name, self.code)));
+            }
+            self.check_precision(value)?;
+        }
+        Ok(())
+    }
+
+    /// Check an amount is positive and has no more decimal places than the asset allows
+    pub fn check_amount(&self, amount: Quantity) -> Result<()> {
+        if amount <= Quantity::ZERO {
+            return Err(Error::ValidationError(format!("Amount of {} must be positive", self.code)));
+        }
+        self.check_precision(amount)
+    }
+
+    /// Check an amount has no more decimal places than the asset allows
+    pub fn check_precision(&self, amount: Quantity) -> Result<()> {
+        if amount.normalize().scale() > self.precision {
+            return Err(Error::ValidationError(format!(
+                "{} has more than {} decimal places allowed for {}", amount, self.precision, self.code
+            )));
+        }
+        Ok(())
+    }
+
+    /// Amount paid out for a withdrawal, once checked against the minimum and fee
+    pub fn withdrawal_payout(&self, amount: Quantity) -> Result<Quantity> {
+        self.check_amount(amount)?;
+        if amount < self.withdrawal_min {
+            return Err(Error::ValidationError(format!(
+                "Withdrawals of {} must be at least {}", self.code, self.withdrawal_min
+            )));
+        }
+        if amount <= self.withdrawal_fee {
+            return Err(Error::ValidationError(format!(
+                "Withdrawal of {} {} does not cover the {} fee", amount, self.code, self.withdrawal_fee
+            )));
+        }
+        Ok(amount - self.withdrawal_fee)
+    }
+}
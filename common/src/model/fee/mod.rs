@@ -0,0 +1,62 @@
+//! Trading fee schedule
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::decimal::Amount;
+use crate::error::{Error, Result};
+use crate::model::trade::Trade;
+#[cfg(feature = "utoipa")]
+use crate::utoipa::ToSchema;
+
+/// Maker and taker fee rates applied to every trade
+///
+/// Fees are charged in the asset each side receives: the buyer pays in the
+/// base asset and the seller pays in the quote asset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct FeeSchedule {
+    /// Fee rate for the resting (maker) side, e.g. 0.001 for 10 bps
+    pub maker_rate: Decimal,
+    /// Fee rate for the incoming (taker) side
+    pub taker_rate: Decimal,
+}
+
+impl FeeSchedule {
+    /// Create a fee schedule from maker and taker rates
+    pub fn new(maker_rate: Decimal, taker_rate: Decimal) -> Result<Self> {
+        for (name, rate) in [("maker", maker_rate), ("taker", taker_rate)] {
+            if rate < Decimal::ZERO || rate >= Decimal::ONE {
+                return Err(Error::ValidationError(format!(
+                    "Invalid {} fee rate {}: must be in [0, 1)", name, rate
+                )));
+            }
+        }
+
+        Ok(Self { maker_rate, taker_rate })
+    }
+
+    /// Fee schedule that charges nothing
+    pub fn zero() -> Self {
+        Self::default()
+    }
+
+    /// Populate the fee fields of a trade
+    pub fn apply(&self, trade: &mut Trade) {
+        let buyer_fee = trade.quantity * self.rate_for(trade.is_buyer_maker);
+        let seller_fee = trade.amount * self.rate_for(!trade.is_buyer_maker);
+
+        let (maker_fee, taker_fee) = if trade.is_buyer_maker {
+            (buyer_fee, seller_fee)
+        } else {
+            (seller_fee, buyer_fee)
+        };
+
+        trade.maker_fee = maker_fee;
+        trade.taker_fee = taker_fee;
+    }
+
+    fn rate_for(&self, is_maker: bool) -> Amount {
+        if is_maker { self.maker_rate } else { self.taker_rate }
+    }
+}
@@ -0,0 +1,44 @@
+//! Market maker incentive metrics
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::decimal::{Amount, Quantity};
+#[cfg(feature = "utoipa")]
+use crate::utoipa::ToSchema;
+
+/// Maker volume and quoting of one account on one market over a period
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct MakerActivity {
+    /// Market symbol
+    pub market: String,
+    /// Account ID
+    pub account_id: Uuid,
+    /// Base quantity filled as the resting side
+    pub maker_volume: Quantity,
+    /// Quote value filled as the resting side
+    pub maker_quote_volume: Amount,
+    /// Trades the account was the maker in
+    pub maker_trades: u64,
+    /// Seconds the account's bid was at the best bid
+    pub seconds_at_best_bid: f64,
+    /// Seconds the account's ask was at the best ask
+    pub seconds_at_best_ask: f64,
+    /// Seconds the account quoted both sides
+    pub seconds_two_sided: f64,
+    /// Time-weighted spread between the account's own best bid and ask, in
+    /// basis points of their midpoint, while it quoted both sides
+    pub average_spread_bps: Option<f64>,
+}
+
+impl MakerActivity {
+    /// Share of a period of `seconds` the account spent at the top of the
+    /// book, averaged over both sides
+    pub fn presence(&self, seconds: f64) -> f64 {
+        if seconds <= 0.0 {
+            return 0.0;
+        }
+        ((self.seconds_at_best_bid + self.seconds_at_best_ask) / (2.0 * seconds)).min(1.0)
+    }
+}
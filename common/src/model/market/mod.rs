@@ -1,14 +1,29 @@
 //! Market models and related types
 
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, TimeDelta, Utc, Weekday};
 use serde::{Deserialize, Serialize};
 
 use crate::decimal::{Price, Quantity};
+use crate::error::{Error, Result};
 #[cfg(feature = "utoipa")]
 use crate::utoipa::ToSchema;
 
+/// What a market trades
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+pub enum MarketKind {
+    /// The base asset itself, delivered on every trade
+    #[default]
+    Spot,
+    /// A perpetual future whose holders pay each other funding towards the index price
+    Perpetual,
+}
+
 /// Market configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct Market {
     /// Market symbol (e.g., "BTC/USD")
     pub symbol: String,
@@ -26,6 +41,47 @@ pub struct Market {
     pub max_price_deviation: f64,
     /// Whether trading is enabled
     pub trading_enabled: bool,
+    /// Spot or perpetual
+    #[serde(default)]
+    pub kind: MarketKind,
+}
+
+impl Market {
+    /// Check an order's price and quantity against the market's filters
+    ///
+    /// The minimum order size applies to the quote value, so it is only
+    /// checked for orders with a price.
+    pub fn check_order(&self, price: Option<Price>, quantity: Quantity) -> Result<()> {
+        if !self.trading_enabled {
+            return Err(Error::ValidationError(format!("Trading is disabled on {}", self.symbol)));
+        }
+        if quantity <= Quantity::ZERO {
+            return Err(Error::ValidationError("Quantity must be positive".to_string()));
+        }
+        if !self.quantity_step.is_zero() && !(quantity % self.quantity_step).is_zero() {
+            return Err(Error::ValidationError(format!(
+                "Quantity {} is not a multiple of the step {}", quantity, self.quantity_step
+            )));
+        }
+
+        let Some(price) = price else {
+            return Ok(());
+        };
+        if price <= Price::ZERO {
+            return Err(Error::ValidationError("Price must be positive".to_string()));
+        }
+        if !self.price_tick.is_zero() && !(price % self.price_tick).is_zero() {
+            return Err(Error::ValidationError(format!(
+                "Price {} is not a multiple of the tick {}", price, self.price_tick
+            )));
+        }
+        if price * quantity < self.min_order_size {
+            return Err(Error::ValidationError(format!(
+                "Order value {} is below the minimum of {}", (price * quantity).normalize(), self.min_order_size
+            )));
+        }
+        Ok(())
+    }
 }
 
 /// Market summary information
@@ -49,3 +105,244 @@ pub struct MarketSummary {
     /// 24h volume in quote asset
     pub quote_volume_24h: Option<Quantity>,
 }
+
+/// Trading session state of a market
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub enum SessionState {
+    /// Continuous trading
+    Open,
+    /// No new orders accepted
+    Closed,
+    /// Good-til-cancelled limit orders are collected and matched at a single price when the auction ends
+    Auction,
+}
+
+/// Daily trading calendar of a market, in UTC
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct TradingSchedule {
+    /// Start of continuous trading
+    #[cfg_attr(feature = "utoipa", schema(value_type = String, example = "09:00:00"))]
+    pub open: NaiveTime,
+    /// End of continuous trading, after `open` on the same day
+    #[cfg_attr(feature = "utoipa", schema(value_type = String, example = "17:00:00"))]
+    pub close: NaiveTime,
+    /// Closed on Saturdays and Sundays
+    #[serde(default)]
+    pub weekend_closed: bool,
+    /// Minutes of auction before `open`
+    #[serde(default)]
+    pub opening_auction_minutes: u32,
+    /// Minutes of auction before `close`
+    #[serde(default)]
+    pub closing_auction_minutes: u32,
+    /// Dates the market stays closed
+    #[serde(default)]
+    pub holidays: Vec<NaiveDate>,
+}
+
+/// Days searched for the next session change
+const SCHEDULE_SEARCH_DAYS: u64 = 400;
+
+impl TradingSchedule {
+    /// Check the session fits within a day and leaves time for continuous trading
+    pub fn validate(&self) -> Result<()> {
+        let opening_auction = TimeDelta::minutes(self.opening_auction_minutes.into());
+        let closing_auction = TimeDelta::minutes(self.closing_auction_minutes.into());
+
+        if self.close <= self.open {
+            return Err(Error::ValidationError(format!("Close {} must be after open {}", self.close, self.open)));
+        }
+        if self.open - NaiveTime::MIN < opening_auction {
+            return Err(Error::ValidationError("Opening auction must start on the same day".to_string()));
+        }
+        if self.close - self.open <= closing_auction {
+            return Err(Error::ValidationError("Closing auction must leave time for continuous trading".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Session state at a point in time
+    pub fn state_at(&self, at: DateTime<Utc>) -> SessionState {
+        let date = at.date_naive();
+        let weekend = matches!(date.weekday(), Weekday::Sat | Weekday::Sun);
+        if (self.weekend_closed && weekend) || self.holidays.contains(&date) {
+            return SessionState::Closed;
+        }
+
+        let time = at.time();
+        let auction_start = self.open - TimeDelta::minutes(self.opening_auction_minutes.into());
+        let closing_start = self.close - TimeDelta::minutes(self.closing_auction_minutes.into());
+        if time < auction_start || time >= self.close {
+            SessionState::Closed
+        } else if time < self.open || time >= closing_start {
+            SessionState::Auction
+        } else {
+            SessionState::Open
+        }
+    }
+
+    /// Time and state of the next session change after `at`
+    pub fn next_transition(&self, at: DateTime<Utc>) -> Option<(DateTime<Utc>, SessionState)> {
+        let current = self.state_at(at);
+        let boundaries = [
+            NaiveTime::MIN,
+            self.open - TimeDelta::minutes(self.opening_auction_minutes.into()),
+            self.open,
+            self.close - TimeDelta::minutes(self.closing_auction_minutes.into()),
+            self.close,
+        ];
+
+        (0..SCHEDULE_SEARCH_DAYS)
+            .filter_map(|days| at.date_naive().checked_add_days(Days::new(days)))
+            .flat_map(|date| boundaries.map(|time| date.and_time(time).and_utc()))
+            .filter(|instant| *instant > at)
+            .map(|instant| (instant, self.state_at(instant)))
+            .find(|(_, state)| *state != current)
+    }
+}
+
+/// Current session of a market and when it next changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct MarketSession {
+    /// Market symbol
+    pub market: String,
+    /// Current session state
+    pub state: SessionState,
+    /// Trading calendar, or none if the market trades around the clock
+    pub schedule: Option<TradingSchedule>,
+    /// State the market changes to next
+    pub next_state: Option<SessionState>,
+    /// When the market next changes state
+    pub next_transition: Option<DateTime<Utc>>,
+}
+
+/// What happens to an order that would open a price level beyond the limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LevelPolicy {
+    /// The order's remainder expires instead of resting
+    #[default]
+    Reject,
+    /// A better priced order expires every order at the farthest level to make room
+    PruneFarthest,
+}
+
+/// Limits on the orders resting on a market's book
+///
+/// Checked when an order's remainder is about to rest; orders already on the
+/// book are left alone when the limits change. The age limit is the exception:
+/// the engine sweeps every resting order against it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct BookLimits {
+    /// Resting orders one account may have on the book
+    #[serde(default)]
+    pub max_orders_per_account: Option<usize>,
+    /// Farthest a resting order's price may be from the mid price, in basis points
+    #[serde(default)]
+    pub max_distance_bps: Option<u32>,
+    /// Price levels each side of the book may hold
+    #[serde(default)]
+    pub max_levels: Option<usize>,
+    /// What happens to an order that would open a level beyond `max_levels`
+    #[serde(default)]
+    pub level_policy: LevelPolicy,
+    /// Longest an order may rest on the book, in seconds
+    #[serde(default)]
+    pub max_order_age_secs: Option<u64>,
+}
+
+impl BookLimits {
+    /// Check every limit that is set leaves room for at least one order
+    pub fn validate(&self) -> Result<()> {
+        if self.max_orders_per_account == Some(0) {
+            return Err(Error::ValidationError("max_orders_per_account must be at least 1".to_string()));
+        }
+        if self.max_distance_bps == Some(0) {
+            return Err(Error::ValidationError("max_distance_bps must be at least 1".to_string()));
+        }
+        if self.max_levels == Some(0) {
+            return Err(Error::ValidationError("max_levels must be at least 1".to_string()));
+        }
+        if self.max_order_age_secs == Some(0) {
+            return Err(Error::ValidationError("max_order_age_secs must be at least 1".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// How a taker's quantity is shared among the orders resting at one price level
+///
+/// Auctions always uncross in price-time priority.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[serde(tag = "algorithm", rename_all = "snake_case")]
+pub enum Allocation {
+    /// Price-time priority: the oldest order at the level fills first
+    #[default]
+    Fifo,
+    /// Each order gets a share of the taker's quantity in proportion to its
+    /// remaining quantity
+    ///
+    /// Shares are rounded down to a multiple of `lot_size`, and shares
+    /// smaller than `min_allocation` are dropped. What rounding leaves over
+    /// goes to the level's orders in time priority, so the taker fills as
+    /// much as it would under FIFO. A taker that takes the whole level fills
+    /// every order in full.
+    ProRata {
+        /// Smallest share an order is given
+        min_allocation: Quantity,
+        /// Shares are multiples of this, usually the market's quantity step
+        lot_size: Quantity,
+    },
+}
+
+impl Allocation {
+    /// Check a pro-rata lot size is positive and its minimum not negative
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Allocation::Fifo => Ok(()),
+            Allocation::ProRata { min_allocation, lot_size } => {
+                if *lot_size <= Quantity::ZERO {
+                    return Err(Error::ValidationError("lot_size must be positive".to_string()));
+                }
+                if *min_allocation < Quantity::ZERO {
+                    return Err(Error::ValidationError("min_allocation must not be negative".to_string()));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Allocation {
+    type Err = String;
+
+    /// Parse `fifo`, or `pro_rata:MIN_ALLOCATION[:LOT_SIZE]` where a missing
+    /// lot size is left zero for the caller to fill in
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut parts = s.trim().split(':').map(str::trim);
+        let algorithm = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let mut quantity = |name: &str| -> std::result::Result<Quantity, String> {
+            parts.next()
+                .map(|value| value.parse().map_err(|_| format!("Invalid {}: {}", name, value)))
+                .unwrap_or(Ok(Quantity::ZERO))
+        };
+        let allocation = match algorithm.as_str() {
+            "fifo" => Allocation::Fifo,
+            "pro_rata" => Allocation::ProRata {
+                min_allocation: quantity("minimum allocation")?,
+                lot_size: quantity("lot size")?,
+            },
+            _ => return Err(format!("Unknown allocation: {}", s)),
+        };
+        if parts.next().is_some() {
+            return Err(format!("Too many parts in allocation: {}", s));
+        }
+        Ok(allocation)
+    }
+}
@@ -4,3 +4,8 @@ pub mod order;
 pub mod trade;
 pub mod market;
 pub mod account;
+pub mod asset;
+pub mod symbol;
+pub mod fee;
+pub mod surveillance;
+pub mod incentive;
@@ -5,12 +5,15 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::decimal::{Price, Quantity};
+use crate::error::Result;
+use crate::model::symbol::Symbol;
 #[cfg(feature = "utoipa")]
 use crate::utoipa::ToSchema;
 
 /// Order side (buy or sell)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
 pub enum Side {
     Buy,
     Sell,
@@ -19,6 +22,7 @@ pub enum Side {
 /// Order type
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
 pub enum OrderType {
     /// Market order to be executed immediately at the current market price
     Market,
@@ -29,6 +33,7 @@ pub enum OrderType {
 /// Order time in force
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
 pub enum TimeInForce {
     /// Good till cancelled
     GTC,
@@ -41,6 +46,7 @@ pub enum TimeInForce {
 /// Order status
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
 pub enum Status {
     /// Order has been received but not yet processed
     New,
@@ -52,11 +58,92 @@ pub enum Status {
     Cancelled,
     /// Order has been rejected
     Rejected,
+    /// Order expired without resting on the book (IOC, FOK or market remainder), or was removed from it by the engine
+    Expired,
+}
+
+/// Reason the engine rejected or expired an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+pub enum RejectReason {
+    /// No liquidity on the opposite side of the book
+    NoLiquidity,
+    /// Immediate-or-cancel remainder could not be filled
+    ImmediateOrCancel,
+    /// Fill-or-kill order could not be filled in full
+    FillOrKill,
+    /// Market order remainder could not be filled
+    MarketOrderUnfilled,
+    /// Market order remainder would have filled beyond its price cap
+    PriceCap,
+    /// The order would exceed the market's book limits, or was pruned to make room
+    BookLimit,
+    /// The order rested longer than its market allows
+    MaxAge,
+    /// A pegged order's reference price was missing on arrival
+    PegUnavailable,
+    /// Less than the order's minimum fill quantity could execute on arrival,
+    /// or its remainder would have crossed an order waiting for its own
+    MinFillQuantity,
+    /// Resting the order would have crossed the book, which only a bug allows
+    CrossedBook,
+}
+
+impl RejectReason {
+    /// Stable reason code
+    pub fn code(&self) -> &'static str {
+        match self {
+            RejectReason::NoLiquidity => "NO_LIQUIDITY",
+            RejectReason::ImmediateOrCancel => "IOC_UNFILLED",
+            RejectReason::FillOrKill => "FOK_UNFILLED",
+            RejectReason::MarketOrderUnfilled => "MARKET_UNFILLED",
+            RejectReason::PriceCap => "PRICE_CAP",
+            RejectReason::BookLimit => "BOOK_LIMIT",
+            RejectReason::MaxAge => "MAX_AGE",
+            RejectReason::PegUnavailable => "PEG_UNAVAILABLE",
+            RejectReason::MinFillQuantity => "MIN_FILL_QUANTITY",
+            RejectReason::CrossedBook => "CROSSED_BOOK",
+        }
+    }
+}
+
+/// Price a pegged order tracks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+#[serde(rename_all = "snake_case")]
+pub enum PegReference {
+    /// Midpoint of the best bid and ask
+    Mid,
+    /// Best price on the order's own side: the best bid for buys, the best ask for sells
+    Best,
+}
+
+/// How a pegged order's price follows the book
+///
+/// The price is the reference plus the offset, rounded away from the
+/// opposite side to the market's tick, capped at the limit, and kept a tick
+/// behind the opposite best price so a pegged order never takes liquidity.
+/// References are taken from orders that are not pegged themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct Peg {
+    /// Price tracked
+    pub reference: PegReference,
+    /// Added to the reference price, e.g. `-0.5` to bid half a unit below it
+    #[serde(default)]
+    pub offset: Price,
+    /// Highest price a pegged buy, or lowest price a pegged sell, may move to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<Price>,
 }
 
 /// Order model
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct Order {
     /// Unique order ID
     pub id: Uuid,
@@ -68,8 +155,25 @@ pub struct Order {
     pub side: Side,
     /// Order type
     pub order_type: OrderType,
-    /// Price (for limit orders)
+    /// Price of limit orders, or the worst price a market order may fill at
     pub price: Option<Price>,
+    /// Most a market order's fills may move from the best opposite price on
+    /// arrival, in basis points
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_slippage_bps: Option<u32>,
+    /// Whether the order may only shrink the account's position in its market
+    #[serde(default)]
+    pub reduce_only: bool,
+    /// How the engine re-prices a pegged limit order as the book moves
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peg: Option<Peg>,
+    /// Least quantity the order's first execution may be; once partly filled
+    /// the order trades like any other
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_fill_quantity: Option<Quantity>,
+    /// Reference the client gave the order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
     /// Original quantity
     pub quantity: Quantity,
     /// Remaining quantity
@@ -82,13 +186,28 @@ pub struct Order {
     pub time_in_force: TimeInForce,
     /// Current status
     pub status: Status,
+    /// Why the engine rejected or expired the order
+    #[serde(default)]
+    pub reject_reason: Option<RejectReason>,
+    /// Human-readable detail for the rejection
+    #[serde(default)]
+    pub reject_message: Option<String>,
     /// Creation timestamp
     pub created_at: DateTime<Utc>,
     /// Last update timestamp
     pub updated_at: DateTime<Utc>,
+    /// Position in the market's orders as numbered by the matching engine
+    /// on arrival, 0 for orders it has not processed
+    #[serde(default)]
+    pub sequence: u64,
 }
 
 impl Order {
+    /// Parse the order's market symbol
+    pub fn symbol(&self) -> Result<Symbol> {
+        Symbol::parse(&self.market)
+    }
+    
     /// Create a new limit order
     pub fn new_limit(
         user_id: Uuid,
@@ -106,14 +225,22 @@ impl Order {
             side,
             order_type: OrderType::Limit,
             price: Some(price),
+            max_slippage_bps: None,
+            peg: None,
+            min_fill_quantity: None,
+            reduce_only: false,
             quantity,
             remaining_quantity: quantity,
             filled_quantity: Quantity::ZERO,
             average_fill_price: None,
             time_in_force,
             status: Status::New,
+            reject_reason: None,
+            reject_message: None,
+            client_order_id: None,
             created_at: now,
             updated_at: now,
+            sequence: 0,
         }
     }
     
@@ -132,17 +259,31 @@ impl Order {
             side,
             order_type: OrderType::Market,
             price: None,
+            max_slippage_bps: None,
+            peg: None,
+            min_fill_quantity: None,
+            reduce_only: false,
             quantity,
             remaining_quantity: quantity,
             filled_quantity: Quantity::ZERO,
             average_fill_price: None,
             time_in_force: TimeInForce::IOC, // Market orders are IOC by default
             status: Status::New,
+            reject_reason: None,
+            reject_message: None,
+            client_order_id: None,
             created_at: now,
             updated_at: now,
+            sequence: 0,
         }
     }
     
+    /// Whether a fill of `quantity` meets the order's minimum fill quantity,
+    /// which only binds until the order first trades
+    pub fn accepts_fill(&self, quantity: Quantity) -> bool {
+        self.min_fill_quantity.is_none_or(|min| !self.filled_quantity.is_zero() || quantity >= min)
+    }
+    
     /// Check if the order is fully filled
     pub fn is_filled(&self) -> bool {
         self.remaining_quantity.is_zero() || self.status == Status::Filled
@@ -152,4 +293,103 @@ impl Order {
     pub fn is_active(&self) -> bool {
         matches!(self.status, Status::New | Status::PartiallyFilled)
     }
+    
+    /// Mark the order as rejected by the engine
+    pub fn reject(&mut self, reason: RejectReason, message: impl Into<String>, now: DateTime<Utc>) {
+        self.status = Status::Rejected;
+        self.reject_reason = Some(reason);
+        self.reject_message = Some(message.into());
+        self.updated_at = now;
+    }
+    
+    /// Mark the unfilled remainder of the order as expired by the engine
+    pub fn expire(&mut self, reason: RejectReason, message: impl Into<String>, now: DateTime<Utc>) {
+        self.status = Status::Expired;
+        self.reject_reason = Some(reason);
+        self.reject_message = Some(message.into());
+        self.updated_at = now;
+    }
+    
+    /// Check if the order was ended by the engine rather than by the user
+    pub fn is_engine_terminated(&self) -> bool {
+        matches!(self.status, Status::Rejected | Status::Expired)
+    }
+}
+
+/// One status change of an order, for its audit trail
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct OrderTransition {
+    /// Order ID
+    pub order_id: Uuid,
+    /// Account owning the order
+    pub account_id: Uuid,
+    /// Market symbol
+    pub market: String,
+    /// Status before the change, unset for the order's first entry
+    pub previous_status: Option<Status>,
+    /// Status after the change
+    pub status: Status,
+    /// Price of the order after the change
+    pub price: Option<Price>,
+    /// Original quantity
+    pub quantity: Quantity,
+    /// Quantity filled by the change
+    pub fill_quantity: Quantity,
+    /// Cumulative matched quantity after the change
+    pub filled_quantity: Quantity,
+    /// Remaining quantity after the change
+    pub remaining_quantity: Quantity,
+    /// Average fill price after the change
+    pub average_fill_price: Option<Price>,
+    /// Why the engine rejected or expired the order
+    pub reason: Option<RejectReason>,
+    /// Human-readable detail for the rejection
+    pub message: Option<String>,
+    /// When the change happened
+    pub at: DateTime<Utc>,
+}
+
+impl OrderTransition {
+    /// Entry of an order as the engine accepted it, before it matched
+    pub fn accepted(order: &Order) -> Self {
+        Self {
+            order_id: order.id,
+            account_id: order.user_id,
+            market: order.market.clone(),
+            previous_status: None,
+            status: Status::New,
+            price: order.price,
+            quantity: order.quantity,
+            fill_quantity: Quantity::ZERO,
+            filled_quantity: Quantity::ZERO,
+            remaining_quantity: order.quantity,
+            average_fill_price: None,
+            reason: None,
+            message: None,
+            at: order.created_at,
+        }
+    }
+    
+    /// Entry of an order's current state, following one with `previous`
+    /// status and filled quantity
+    pub fn new(order: &Order, previous: Option<(Status, Quantity)>) -> Self {
+        let previously_filled = previous.map_or(Quantity::ZERO, |(_, filled)| filled);
+        Self {
+            order_id: order.id,
+            account_id: order.user_id,
+            market: order.market.clone(),
+            previous_status: previous.map(|(status, _)| status),
+            status: order.status,
+            price: order.price,
+            quantity: order.quantity,
+            fill_quantity: (order.filled_quantity - previously_filled).normalize(),
+            filled_quantity: order.filled_quantity,
+            remaining_quantity: order.remaining_quantity,
+            average_fill_price: order.average_fill_price,
+            reason: order.reject_reason,
+            message: order.reject_message.clone(),
+            at: order.updated_at,
+        }
+    }
 }
@@ -0,0 +1,60 @@
+//! Trade surveillance alerts
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[cfg(feature = "utoipa")]
+use crate::utoipa::ToSchema;
+
+/// Suspicious trading pattern
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    /// An account traded with itself
+    SelfTrade,
+    /// Two accounts repeatedly traded back and forth with each other
+    WashTrading,
+    /// An account cancelled far more orders than it filled
+    HighCancelToFill,
+    /// An account stacked orders on one side, traded on the other, then pulled the stack
+    Layering,
+}
+
+/// Surveillance alert raised for one or more accounts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct Alert {
+    /// Unique alert ID
+    pub id: Uuid,
+    /// Pattern that was detected
+    pub kind: AlertKind,
+    /// Market the pattern was seen on
+    pub market: String,
+    /// Accounts involved
+    pub account_ids: Vec<Uuid>,
+    /// Human readable description of the evidence
+    pub details: String,
+    /// When the alert was raised
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Alert {
+    /// Create an alert
+    pub fn new(kind: AlertKind, market: impl Into<String>, account_ids: Vec<Uuid>, details: impl Into<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            kind,
+            market: market.into(),
+            account_ids,
+            details: details.into(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Whether an account is involved in the alert
+    pub fn involves(&self, account_id: Uuid) -> bool {
+        self.account_ids.contains(&account_id)
+    }
+}
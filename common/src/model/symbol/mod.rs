@@ -0,0 +1,170 @@
+//! Market symbol and asset types
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Asset code (e.g., "BTC", "USD")
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Asset(String);
+
+impl Asset {
+    /// Maximum length of an asset code
+    pub const MAX_LEN: usize = 16;
+
+    /// Parse and validate an asset code
+    pub fn new(code: &str) -> Result<Self> {
+        Self::validate(code)
+            .map_err(|reason| Error::ValidationError(format!("Invalid asset '{}': {}", code, reason)))?;
+
+        Ok(Self(code.to_string()))
+    }
+
+    fn validate(code: &str) -> std::result::Result<(), String> {
+        if code.is_empty() || code.len() > Self::MAX_LEN {
+            return Err(format!("must be 1 to {} characters", Self::MAX_LEN));
+        }
+
+        if !code.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+            return Err("must contain only uppercase letters and digits".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Get the asset code
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for Asset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<String> for Asset {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        Self::new(&value)
+    }
+}
+
+impl From<Asset> for String {
+    fn from(asset: Asset) -> Self {
+        asset.0
+    }
+}
+
+impl AsRef<str> for Asset {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for Asset {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Asset {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+/// Market symbol in `BASE/QUOTE` form (e.g., "BTC/USD")
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Symbol {
+    /// Asset being traded
+    base: Asset,
+    /// Asset prices are quoted in
+    quote: Asset,
+}
+
+impl Symbol {
+    /// Separator between the base and quote assets
+    pub const SEPARATOR: char = '/';
+
+    /// Create a symbol from its base and quote assets
+    pub fn new(base: Asset, quote: Asset) -> Result<Self> {
+        if base == quote {
+            return Err(Error::ValidationError(format!(
+                "Invalid market '{}{}{}': base and quote assets must differ", base, Self::SEPARATOR, quote
+            )));
+        }
+
+        Ok(Self { base, quote })
+    }
+
+    /// Parse and validate a `BASE/QUOTE` symbol
+    pub fn parse(symbol: &str) -> Result<Self> {
+        let (base, quote) = symbol.split_once(Self::SEPARATOR).ok_or_else(|| {
+            Error::ValidationError(format!(
+                "Invalid market '{}': expected BASE{}QUOTE", symbol, Self::SEPARATOR
+            ))
+        })?;
+
+        for asset in [base, quote] {
+            Asset::validate(asset).map_err(|reason| {
+                Error::ValidationError(format!("Invalid market '{}': asset '{}' {}", symbol, asset, reason))
+            })?;
+        }
+
+        Self::new(Asset(base.to_string()), Asset(quote.to_string()))
+    }
+
+    /// Get the base asset
+    pub fn base(&self) -> &Asset {
+        &self.base
+    }
+
+    /// Get the quote asset
+    pub fn quote(&self) -> &Asset {
+        &self.quote
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.base, Self::SEPARATOR, self.quote)
+    }
+}
+
+impl FromStr for Symbol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for Symbol {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        Self::parse(&value)
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(symbol: Symbol) -> Self {
+        symbol.to_string()
+    }
+}
@@ -5,13 +5,16 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::decimal::{Price, Quantity, Amount};
-use crate::model::order::Side;
+use crate::error::Result;
+use crate::model::order::{Order, Side};
+use crate::model::symbol::Symbol;
 #[cfg(feature = "utoipa")]
 use crate::utoipa::ToSchema;
 
 /// Trade model representing a matched order
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct Trade {
     /// Unique trade ID
     pub id: Uuid,
@@ -33,11 +36,35 @@ pub struct Trade {
     pub seller_id: Uuid,
     /// Side that was the taker (initiated the match)
     pub taker_side: Side,
+    /// Whether the buyer was the resting (maker) side
+    #[serde(default)]
+    pub is_buyer_maker: bool,
+    /// Fee charged to the maker
+    #[serde(default)]
+    pub maker_fee: Amount,
+    /// Asset the maker fee is charged in
+    #[serde(default)]
+    pub maker_fee_asset: String,
+    /// Fee charged to the taker
+    #[serde(default)]
+    pub taker_fee: Amount,
+    /// Asset the taker fee is charged in
+    #[serde(default)]
+    pub taker_fee_asset: String,
     /// Timestamp when the trade occurred
     pub created_at: DateTime<Utc>,
+    /// Position in the market's trades as numbered by the matching engine,
+    /// 0 for trades it did not number
+    #[serde(default)]
+    pub sequence: u64,
 }
 
 impl Trade {
+    /// Parse the trade's market symbol
+    pub fn symbol(&self) -> Result<Symbol> {
+        Symbol::parse(&self.market)
+    }
+    
     /// Create a new trade from matched orders
     pub fn new(
         market: String,
@@ -50,6 +77,18 @@ impl Trade {
         taker_side: Side,
     ) -> Self {
         let amount = price * quantity;
+        let is_buyer_maker = taker_side == Side::Sell;
+        
+        // Each side pays fees in the asset it receives
+        let (buyer_fee_asset, seller_fee_asset) = Symbol::parse(&market)
+            .map(|symbol| (symbol.base().to_string(), symbol.quote().to_string()))
+            .unwrap_or_default();
+        let (maker_fee_asset, taker_fee_asset) = if is_buyer_maker {
+            (buyer_fee_asset, seller_fee_asset)
+        } else {
+            (seller_fee_asset, buyer_fee_asset)
+        };
+        
         Self {
             id: Uuid::new_v4(),
             market,
@@ -61,6 +100,209 @@ impl Trade {
             buyer_id,
             seller_id,
             taker_side,
+            is_buyer_maker,
+            maker_fee: Amount::ZERO,
+            maker_fee_asset,
+            taker_fee: Amount::ZERO,
+            taker_fee_asset,
+            created_at: Utc::now(),
+            sequence: 0,
+        }
+    }
+    
+    /// Fee charged to the buyer, in the base asset
+    pub fn buyer_fee(&self) -> Amount {
+        if self.is_buyer_maker { self.maker_fee } else { self.taker_fee }
+    }
+    
+    /// Fee charged to the seller, in the quote asset
+    pub fn seller_fee(&self) -> Amount {
+        if self.is_buyer_maker { self.taker_fee } else { self.maker_fee }
+    }
+    
+    /// Side an order was on, if the trade filled it
+    pub fn order_side(&self, order_id: Uuid) -> Option<Side> {
+        if self.buyer_order_id == order_id {
+            Some(Side::Buy)
+        } else if self.seller_order_id == order_id {
+            Some(Side::Sell)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether an order's side of a trade rested in the book or took from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Liquidity {
+    /// Resting order that was matched
+    Maker,
+    /// Incoming order that matched
+    Taker,
+}
+
+/// One trade's part in filling an order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct OrderFill {
+    /// Trade ID
+    pub trade_id: Uuid,
+    /// Filled order ID
+    pub order_id: Uuid,
+    /// Account owning the order
+    pub account_id: Uuid,
+    /// Market symbol
+    pub market: String,
+    /// Side of the order
+    pub side: Side,
+    /// Price of the trade
+    pub price: Price,
+    /// Quantity filled by the trade
+    pub quantity: Quantity,
+    /// Fee the order's account paid on the trade
+    pub fee: Amount,
+    /// Asset the fee is charged in
+    pub fee_asset: String,
+    /// Whether the order was maker or taker
+    pub liquidity: Liquidity,
+    /// Quantity filled by this and earlier trades
+    pub cumulative_quantity: Quantity,
+    /// Average price of this and earlier trades
+    pub average_price: Price,
+    /// Timestamp when the trade occurred
+    pub created_at: DateTime<Utc>,
+}
+
+impl OrderFill {
+    /// Fills of an order, oldest first, from the trades that filled it
+    ///
+    /// Trades of other orders are skipped.
+    pub fn from_trades(order_id: Uuid, trades: &[Trade]) -> Vec<OrderFill> {
+        let mut trades: Vec<&Trade> = trades.iter().collect();
+        trades.sort_by_key(|trade| trade.created_at);
+        
+        let mut cumulative_quantity = Quantity::ZERO;
+        let mut cumulative_amount = Amount::ZERO;
+        trades
+            .into_iter()
+            .filter_map(|trade| {
+                let side = trade.order_side(order_id)?;
+                let maker = (side == Side::Buy) == trade.is_buyer_maker;
+                let (account_id, fee) = match side {
+                    Side::Buy => (trade.buyer_id, trade.buyer_fee()),
+                    Side::Sell => (trade.seller_id, trade.seller_fee()),
+                };
+                cumulative_quantity += trade.quantity;
+                cumulative_amount += trade.price * trade.quantity;
+                
+                Some(OrderFill {
+                    trade_id: trade.id,
+                    order_id,
+                    account_id,
+                    market: trade.market.clone(),
+                    side,
+                    price: trade.price,
+                    quantity: trade.quantity,
+                    fee,
+                    fee_asset: if maker { trade.maker_fee_asset.clone() } else { trade.taker_fee_asset.clone() },
+                    liquidity: if maker { Liquidity::Maker } else { Liquidity::Taker },
+                    cumulative_quantity,
+                    average_price: cumulative_amount / cumulative_quantity,
+                    created_at: trade.created_at,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Reversal of a settled trade
+///
+/// A bust never deletes the trade. It posts the opposite of the trade's
+/// balance changes, fees included, as a compensating entry and is kept
+/// alongside the trade.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct TradeBust {
+    /// Busted trade ID
+    pub trade_id: Uuid,
+    /// Market symbol
+    pub market: String,
+    /// Price of the busted trade
+    pub price: Price,
+    /// Quantity of the busted trade
+    pub quantity: Quantity,
+    /// Buyer user ID, who gives back the base asset and gets the quote asset back
+    pub buyer_id: Uuid,
+    /// Seller user ID, who gives back the quote asset and gets the base asset back
+    pub seller_id: Uuid,
+    /// Fee refunded to the buyer, in the base asset
+    pub buyer_fee_refund: Amount,
+    /// Fee refunded to the seller, in the quote asset
+    pub seller_fee_refund: Amount,
+    /// Why the trade was busted
+    pub reason: String,
+    /// Who busted it
+    pub actor: String,
+    /// When it was busted
+    pub busted_at: DateTime<Utc>,
+}
+
+impl TradeBust {
+    /// Bust of a trade
+    pub fn new(trade: &Trade, reason: &str, actor: &str, busted_at: DateTime<Utc>) -> Self {
+        Self {
+            trade_id: trade.id,
+            market: trade.market.clone(),
+            price: trade.price,
+            quantity: trade.quantity,
+            buyer_id: trade.buyer_id,
+            seller_id: trade.seller_id,
+            buyer_fee_refund: trade.buyer_fee(),
+            seller_fee_refund: trade.seller_fee(),
+            reason: reason.to_string(),
+            actor: actor.to_string(),
+            busted_at,
+        }
+    }
+}
+
+/// Trades of a placement stored until they are settled and published
+///
+/// Written before the placement is answered, so trades whose settlement
+/// fails, or is cut short by a restart, are retried rather than lost.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingSettlement {
+    /// Unique settlement ID
+    pub id: Uuid,
+    /// Order whose placement matched the trades
+    pub order_id: Uuid,
+    /// Market symbol
+    pub market: String,
+    /// Trades to settle, in execution order
+    pub trades: Vec<Trade>,
+    /// Orders the engine expired or rejected, whose funds are released once the trades settle
+    pub terminated: Vec<Order>,
+    /// Failed attempts so far
+    pub attempts: u32,
+    /// Why the last attempt failed
+    pub last_error: Option<String>,
+    /// When the placement matched
+    pub created_at: DateTime<Utc>,
+}
+
+impl PendingSettlement {
+    /// Settlement of a placement's trades and terminated orders
+    pub fn new(order_id: Uuid, market: String, trades: Vec<Trade>, terminated: Vec<Order>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            order_id,
+            market,
+            trades,
+            terminated,
+            attempts: 0,
+            last_error: None,
             created_at: Utc::now(),
         }
     }
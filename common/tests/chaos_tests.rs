@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+use common::chaos::{ChaosConfig, ChaosPoint, Fault};
+
+#[test]
+fn test_chaos_config_parses_faults_by_point() {
+    let config: ChaosConfig = "settlement=latency_ms:50, fail_first:2; publication=error_rate:0.25".parse().unwrap();
+    assert_eq!(config.faults.len(), 2);
+    assert_eq!(config.faults[&ChaosPoint::Settlement], Fault {
+        latency: Duration::from_millis(50),
+        fail_first: 2,
+        error_rate: 0.0,
+    });
+    assert_eq!(config.faults[&ChaosPoint::Publication], Fault::error_rate(0.25));
+    assert!(!ChaosConfig::default().is_enabled());
+}
+
+#[test]
+fn test_chaos_config_rejects_unknown_points_and_settings() {
+    assert!("matching=fail_first:1".parse::<ChaosConfig>().is_err());
+    assert!("settlement=timeout:1".parse::<ChaosConfig>().is_err());
+    assert!("settlement=error_rate:2".parse::<ChaosConfig>().is_err());
+    assert!("settlement".parse::<ChaosConfig>().is_err());
+}
@@ -0,0 +1,59 @@
+use common::decimal::dec;
+use common::model::fee::FeeSchedule;
+use common::model::order::Side;
+use common::model::trade::Trade;
+use uuid::Uuid;
+
+fn trade(taker_side: Side) -> Trade {
+    Trade::new(
+        "BTC/USD".to_string(),
+        dec!(20000),
+        dec!(2),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        taker_side,
+    )
+}
+
+#[test]
+fn test_trade_liquidity_flag_and_fee_assets() {
+    let buy_taker = trade(Side::Buy);
+    assert!(!buy_taker.is_buyer_maker);
+    assert_eq!(buy_taker.maker_fee_asset, "USD");
+    assert_eq!(buy_taker.taker_fee_asset, "BTC");
+
+    let sell_taker = trade(Side::Sell);
+    assert!(sell_taker.is_buyer_maker);
+    assert_eq!(sell_taker.maker_fee_asset, "BTC");
+    assert_eq!(sell_taker.taker_fee_asset, "USD");
+}
+
+#[test]
+fn test_fee_schedule_applies_per_side() {
+    let schedule = FeeSchedule::new(dec!(0.001), dec!(0.002)).unwrap();
+
+    // Buyer takes: pays 0.2% of 2 BTC, seller makes: pays 0.1% of 40000 USD
+    let mut buy_taker = trade(Side::Buy);
+    schedule.apply(&mut buy_taker);
+    assert_eq!(buy_taker.taker_fee, dec!(0.004));
+    assert_eq!(buy_taker.maker_fee, dec!(40));
+    assert_eq!(buy_taker.buyer_fee(), dec!(0.004));
+    assert_eq!(buy_taker.seller_fee(), dec!(40));
+
+    // Seller takes: pays 0.2% of 40000 USD, buyer makes: pays 0.1% of 2 BTC
+    let mut sell_taker = trade(Side::Sell);
+    schedule.apply(&mut sell_taker);
+    assert_eq!(sell_taker.taker_fee, dec!(80));
+    assert_eq!(sell_taker.maker_fee, dec!(0.002));
+    assert_eq!(sell_taker.buyer_fee(), dec!(0.002));
+    assert_eq!(sell_taker.seller_fee(), dec!(80));
+}
+
+#[test]
+fn test_fee_schedule_rejects_invalid_rates() {
+    assert!(FeeSchedule::new(dec!(-0.001), dec!(0.002)).is_err());
+    assert!(FeeSchedule::new(dec!(0.001), dec!(1)).is_err());
+    assert_eq!(FeeSchedule::zero(), FeeSchedule::new(dec!(0), dec!(0)).unwrap());
+}
@@ -0,0 +1,55 @@
+use common::decimal::format::MarketPrecision;
+use common::decimal::{dec, format_decimal, format_price, DisplayFormat, RoundingMode};
+
+fn display() -> DisplayFormat {
+    let display = DisplayFormat::new();
+    display.register_market("BTC/USD", MarketPrecision::from_steps(dec!(0.01), dec!(0.0001)));
+    display.register_asset("usd", 2);
+    display
+}
+
+#[test]
+fn test_values_are_rounded_to_their_market_or_asset() {
+    let display = display();
+
+    assert_eq!(display.price("BTC/USD", dec!(20000.125)), "20000.12");
+    assert_eq!(display.price("BTC/USD", dec!(20000.135)), "20000.14");
+    assert_eq!(display.quantity("BTC/USD", dec!(0.123456)), "0.1235");
+    assert_eq!(display.amount("USD", dec!(10.005)), "10");
+    assert_eq!(display.amount("Usd", dec!(-10.015)), "-10.02");
+
+    // Unregistered markets and assets keep eight places
+    assert_eq!(display.price("ETH/USD", dec!(1.123456789)), "1.12345679");
+    assert_eq!(display.amount("ETH", dec!(0.000000001)), "0");
+}
+
+#[test]
+fn test_rounding_mode_applies_to_every_value() {
+    let display = display();
+
+    display.set_rounding_mode(RoundingMode::HalfUp);
+    assert_eq!(display.price("BTC/USD", dec!(20000.125)), "20000.13");
+    assert_eq!(display.amount("USD", dec!(-0.005)), "-0.01");
+
+    display.set_rounding_mode(RoundingMode::Down);
+    assert_eq!(display.price("BTC/USD", dec!(20000.129)), "20000.12");
+    assert_eq!(display.quantity("BTC/USD", dec!(0.99999)), "0.9999");
+    assert_eq!(display.amount("USD", dec!(-0.009)), "0");
+
+    assert_eq!("half-up".parse::<RoundingMode>(), Ok(RoundingMode::HalfUp));
+    assert_eq!(RoundingMode::default().to_string(), "half-even");
+    assert!("ceiling".parse::<RoundingMode>().is_err());
+}
+
+#[test]
+fn test_values_are_written_the_same_way_everywhere() {
+    // No trailing zeros, grouping or exponent, whatever the scale
+    assert_eq!(format_decimal(dec!(1.50000000)), "1.5");
+    assert_eq!(format_decimal(dec!(20000)), "20000");
+    assert_eq!(format_decimal(dec!(0.00000001)), "0.00000001");
+    assert_eq!(format_decimal(dec!(-0.0)), "0");
+    assert_eq!(format_decimal(dec!(1234567.891)), "1234567.891");
+
+    // The process-wide format falls back to the defaults
+    assert_eq!(format_price("XYZ/ABC", dec!(100.000)), "100");
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration};
+use common::clock::{Clock, ManualClock};
+use common::id::{IdGenerator, IdScheme, MonotonicIdGenerator, RandomIdGenerator, MAX_NODE};
+
+fn monotonic(node: u16) -> (MonotonicIdGenerator, Arc<ManualClock>) {
+    let clock = Arc::new(ManualClock::new(DateTime::from_timestamp(1_700_000_000, 0).unwrap()));
+    (MonotonicIdGenerator::new(node, clock.clone()), clock)
+}
+
+#[test]
+fn test_monotonic_ids_increase_within_and_across_milliseconds() {
+    let (ids, clock) = monotonic(3);
+
+    let mut previous = ids.next_id();
+    for step in 0..100 {
+        if step % 10 == 0 {
+            clock.advance(Duration::milliseconds(1));
+        }
+        let id = ids.next_id();
+        assert!(id > previous, "{} should sort after {}", id, previous);
+        previous = id;
+    }
+}
+
+#[test]
+fn test_monotonic_ids_keep_increasing_when_the_clock_goes_back() {
+    let (ids, clock) = monotonic(0);
+    let before = ids.next_id();
+
+    clock.advance(Duration::seconds(-5));
+    assert!(ids.next_id() > before);
+}
+
+#[test]
+fn test_monotonic_ids_carry_time_node_and_uuid_version() {
+    let (ids, clock) = monotonic(0xABC);
+    let id = ids.next_id();
+
+    assert_eq!(id.get_version_num(), 7);
+    assert_eq!(id.get_variant(), uuid::Variant::RFC4122);
+    let value = id.as_u128();
+    assert_eq!((value >> 80) as i64, clock.now().timestamp_millis());
+    assert_eq!(((value >> 64) & 0xFFF) as u16, 0xABC);
+}
+
+#[test]
+fn test_monotonic_ids_repeat_on_the_same_clock() {
+    let (first, _) = monotonic(1);
+    let (second, _) = monotonic(1);
+
+    let replay = |ids: &MonotonicIdGenerator| (0..5).map(|_| ids.next_id()).collect::<Vec<_>>();
+    assert_eq!(replay(&first), replay(&second));
+}
+
+#[test]
+fn test_monotonic_node_is_limited_to_twelve_bits() {
+    let (ids, _) = monotonic(u16::MAX);
+    assert_eq!(ids.node(), MAX_NODE);
+}
+
+#[test]
+fn test_random_ids_are_version_4() {
+    assert_eq!(RandomIdGenerator.next_id().get_version_num(), 4);
+}
+
+#[test]
+fn test_id_scheme_parses_case_insensitively() {
+    assert_eq!("Monotonic".parse::<IdScheme>().unwrap(), IdScheme::Monotonic);
+    assert_eq!(" random ".parse::<IdScheme>().unwrap(), IdScheme::Random);
+    assert!("snowflake".parse::<IdScheme>().is_err());
+    assert_eq!(IdScheme::default(), IdScheme::Random);
+}
@@ -0,0 +1,41 @@
+use common::decimal::dec;
+use common::model::market::{Market, MarketKind};
+
+fn market() -> Market {
+    Market {
+        symbol: "BTC/USD".to_string(),
+        base_asset: "BTC".to_string(),
+        quote_asset: "USD".to_string(),
+        price_tick: dec!(0.5),
+        quantity_step: dec!(0.01),
+        min_order_size: dec!(10),
+        max_price_deviation: 10.0,
+        trading_enabled: true,
+        kind: MarketKind::Spot,
+    }
+}
+
+#[test]
+fn test_orders_within_filters_pass() {
+    let market = market();
+
+    market.check_order(Some(dec!(100.5)), dec!(0.1)).unwrap();
+    market.check_order(Some(dec!(1000)), dec!(0.01)).unwrap();
+    // Market orders have no value to check against the minimum
+    market.check_order(None, dec!(0.01)).unwrap();
+}
+
+#[test]
+fn test_orders_outside_filters_fail() {
+    let market = market();
+
+    let error = |price, quantity| market.check_order(price, quantity).unwrap_err().to_string();
+    assert!(error(Some(dec!(100.25)), dec!(1)).contains("tick"));
+    assert!(error(Some(dec!(100)), dec!(0.015)).contains("step"));
+    assert!(error(Some(dec!(100)), dec!(0.05)).contains("minimum"));
+    assert!(error(Some(dec!(0)), dec!(1)).contains("Price must be positive"));
+    assert!(error(None, dec!(0)).contains("Quantity must be positive"));
+
+    let disabled = Market { trading_enabled: false, ..market.clone() };
+    assert!(disabled.check_order(Some(dec!(100)), dec!(1)).unwrap_err().to_string().contains("disabled"));
+}
@@ -0,0 +1,70 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use common::model::market::{SessionState, TradingSchedule};
+
+fn at(timestamp: &str) -> DateTime<Utc> {
+    timestamp.parse().unwrap()
+}
+
+fn schedule() -> TradingSchedule {
+    TradingSchedule {
+        open: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        close: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        weekend_closed: true,
+        opening_auction_minutes: 15,
+        closing_auction_minutes: 10,
+        holidays: vec![NaiveDate::from_ymd_opt(2025, 3, 4).unwrap()],
+    }
+}
+
+#[test]
+fn test_schedule_states_through_the_day() {
+    let schedule = schedule();
+    schedule.validate().unwrap();
+
+    // Monday 3 March 2025
+    assert_eq!(schedule.state_at(at("2025-03-03T08:44:59Z")), SessionState::Closed);
+    assert_eq!(schedule.state_at(at("2025-03-03T08:45:00Z")), SessionState::Auction);
+    assert_eq!(schedule.state_at(at("2025-03-03T09:00:00Z")), SessionState::Open);
+    assert_eq!(schedule.state_at(at("2025-03-03T16:49:59Z")), SessionState::Open);
+    assert_eq!(schedule.state_at(at("2025-03-03T16:50:00Z")), SessionState::Auction);
+    assert_eq!(schedule.state_at(at("2025-03-03T17:00:00Z")), SessionState::Closed);
+
+    // Holidays and weekends stay closed
+    assert_eq!(schedule.state_at(at("2025-03-04T12:00:00Z")), SessionState::Closed);
+    assert_eq!(schedule.state_at(at("2025-03-08T12:00:00Z")), SessionState::Closed);
+}
+
+#[test]
+fn test_next_transition_skips_closed_days() {
+    let schedule = schedule();
+
+    assert_eq!(
+        schedule.next_transition(at("2025-03-03T12:00:00Z")),
+        Some((at("2025-03-03T16:50:00Z"), SessionState::Auction))
+    );
+    // Tuesday is a holiday, so Monday's close is followed by Wednesday's auction
+    assert_eq!(
+        schedule.next_transition(at("2025-03-03T17:00:00Z")),
+        Some((at("2025-03-05T08:45:00Z"), SessionState::Auction))
+    );
+    // Friday's close is followed by Monday's auction
+    assert_eq!(
+        schedule.next_transition(at("2025-03-07T18:00:00Z")),
+        Some((at("2025-03-10T08:45:00Z"), SessionState::Auction))
+    );
+}
+
+#[test]
+fn test_invalid_schedules_are_rejected() {
+    let mut closes_before_open = schedule();
+    closes_before_open.close = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+    assert!(closes_before_open.validate().is_err());
+
+    let mut auction_before_midnight = schedule();
+    auction_before_midnight.open = NaiveTime::from_hms_opt(0, 10, 0).unwrap();
+    assert!(auction_before_midnight.validate().is_err());
+
+    let mut no_continuous_trading = schedule();
+    no_continuous_trading.closing_auction_minutes = 8 * 60;
+    assert!(no_continuous_trading.validate().is_err());
+}
@@ -0,0 +1,44 @@
+use common::error::Error;
+use common::model::symbol::{Asset, Symbol};
+
+#[test]
+fn test_parse_symbol() {
+    let symbol = Symbol::parse("BTC/USD").unwrap();
+    assert_eq!(symbol.base(), "BTC");
+    assert_eq!(symbol.quote(), "USD");
+    assert_eq!(symbol.to_string(), "BTC/USD");
+
+    let symbol: Symbol = "1INCH/USDT".parse().unwrap();
+    assert_eq!(symbol.base().as_str(), "1INCH");
+    assert_eq!(symbol.quote().as_str(), "USDT");
+}
+
+#[test]
+fn test_reject_malformed_symbols() {
+    for malformed in ["", "BTC", "BTC/", "/USD", "BTC/USD/EUR", "btc/usd", "BTC-USD", "BTC /USD", "BTC/BTC"] {
+        match Symbol::parse(malformed) {
+            Err(Error::ValidationError(_)) => (),
+            other => panic!("expected validation error for {:?}, got {:?}", malformed, other),
+        }
+    }
+}
+
+#[test]
+fn test_asset_validation() {
+    assert!(Asset::new("ETH").is_ok());
+    assert!(Asset::new("").is_err());
+    assert!(Asset::new("eth").is_err());
+    assert!(Asset::new(&"X".repeat(Asset::MAX_LEN + 1)).is_err());
+}
+
+#[test]
+fn test_symbol_serde_as_string() {
+    let symbol = Symbol::new(Asset::new("ETH").unwrap(), Asset::new("BTC").unwrap()).unwrap();
+    let json = serde_json::to_string(&symbol).unwrap();
+    assert_eq!(json, "\"ETH/BTC\"");
+
+    let parsed: Symbol = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, symbol);
+
+    assert!(serde_json::from_str::<Symbol>("\"ETHBTC\"").is_err());
+}
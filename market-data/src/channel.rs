@@ -1,27 +1,134 @@
 //! Channel for market data distribution
+//!
+//! Every subscriber has its own unbounded queue, so a subscriber that reads
+//! slower than messages are published falls behind. Publishing watches each
+//! queue's depth: once it reaches [`Backpressure::conflate_at`], subscribers
+//! of topics whose every message supersedes the last, such as tickers, skip
+//! messages until they catch up, while once it reaches
+//! [`Backpressure::disconnect_at`] any other subscriber is disconnected.
 
 use std::collections::HashMap;
+use std::fmt;
 use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
+use common::clock::{SharedClock, SystemClock};
 use crossbeam_channel::{self, Receiver, Sender};
+use serde::Serialize;
 use tokio::sync::Mutex;
+use tracing::warn;
+#[cfg(feature = "utoipa")]
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::models::CandleInterval;
+
 /// Topic types for market data
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Topic {
     /// Order book updates for a market
     OrderBook(String),
-    /// Trades for a market
+    /// Trades for a market, without ones below its minimum displayed size
     Trades(String),
+    /// Every trade of a market, including ones below its minimum displayed size
+    RawTrades(String),
     /// Ticker updates for a market
     Ticker(String),
+    /// Corrections, such as busts, to trades already published for a market
+    TradeCorrections(String),
     /// All order book updates
     AllOrderBooks,
     /// All trades
     AllTrades,
     /// All ticker updates
     AllTickers,
+    /// Best bid and offer of a market
+    Bbo(String),
+    /// Working and closed candles of a market at one interval
+    Candles(String, CandleInterval),
+    /// Private events for an account, only delivered to its authenticated clients
+    Account(Uuid),
+    /// Exchange-wide status changes and operator announcements
+    System,
+}
+
+impl Topic {
+    /// Whether every message supersedes the previous one, so a lagging
+    /// subscriber loses nothing but staleness by skipping messages
+    pub fn is_conflatable(&self) -> bool {
+        matches!(self, Topic::Ticker(_) | Topic::AllTickers | Topic::Bbo(_))
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Topic::OrderBook(market) => write!(f, "orderbook:{}", market),
+            Topic::Trades(market) => write!(f, "trades:{}", market),
+            Topic::RawTrades(market) => write!(f, "raw_trades:{}", market),
+            Topic::Ticker(market) => write!(f, "ticker:{}", market),
+            Topic::TradeCorrections(market) => write!(f, "trade_corrections:{}", market),
+            Topic::AllOrderBooks => write!(f, "orderbook:*"),
+            Topic::AllTrades => write!(f, "trades:*"),
+            Topic::AllTickers => write!(f, "ticker:*"),
+            Topic::Bbo(market) => write!(f, "bbo:{}", market),
+            Topic::Candles(market, interval) => write!(f, "candles:{}:{}", market, interval.code()),
+            Topic::Account(account_id) => write!(f, "account:{}", account_id),
+            Topic::System => write!(f, "system"),
+        }
+    }
+}
+
+/// Queue depths at which lagging subscribers are conflated or disconnected
+#[derive(Debug, Clone)]
+pub struct Backpressure {
+    /// Queue depth from which subscribers of conflatable topics skip messages
+    pub conflate_at: usize,
+    /// Queue depth at which subscribers of other topics are disconnected
+    pub disconnect_at: usize,
+}
+
+impl Default for Backpressure {
+    fn default() -> Self {
+        Self {
+            conflate_at: 256,
+            disconnect_at: 10_000,
+        }
+    }
+}
+
+/// Delivery statistics of one subscription to one topic
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct SubscriberMetrics {
+    /// Subscription ID
+    pub id: Uuid,
+    /// Topic subscribed to, e.g. `ticker:BTC/USD`
+    pub topic: String,
+    /// When the subscription was made
+    pub subscribed_at: DateTime<Utc>,
+    /// Messages waiting to be read
+    pub queue_depth: usize,
+    /// Most messages ever waiting to be read
+    pub max_queue_depth: usize,
+    /// Messages queued for the subscriber
+    pub delivered: u64,
+    /// Messages skipped while the subscriber lagged
+    pub dropped: u64,
+    /// Whether the subscriber is skipping messages until it catches up
+    pub conflating: bool,
+}
+
+/// Delivery statistics of every subscriber, most lagging first
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct ChannelMetrics {
+    /// Open subscriptions, deepest queue first
+    pub subscribers: Vec<SubscriberMetrics>,
+    /// Subscribers disconnected for lagging since startup
+    pub disconnected: u64,
+    /// Messages skipped for lagging subscribers since startup
+    pub dropped: u64,
 }
 
 /// Subscription entry
@@ -30,31 +137,86 @@ struct SubscriptionEntry {
     sender: Sender<Arc<dyn std::any::Any + Send + Sync>>,
     /// Subscription ID
     id: Uuid,
+    /// When the subscription was made
+    subscribed_at: DateTime<Utc>,
+    /// Most messages ever waiting in the queue
+    max_depth: usize,
+    /// Messages queued
+    delivered: u64,
+    /// Messages skipped while lagging
+    dropped: u64,
+    /// Whether messages are skipped until the queue drains
+    conflating: bool,
+}
+
+/// Outcome of offering a message to one subscriber
+enum Delivery {
+    /// Queued or skipped, the subscriber stays
+    Kept,
+    /// The receiver was dropped
+    Closed,
+    /// The queue is too deep, the subscriber is disconnected
+    Lagging,
 }
 
 /// Market data channel
 pub struct MarketDataChannel {
     /// Senders by topic
     senders: Mutex<HashMap<Topic, Vec<SubscriptionEntry>>>,
+    /// Queue depths at which lagging subscribers are conflated or disconnected
+    backpressure: Backpressure,
+    /// Subscribers disconnected and messages skipped since startup
+    totals: std::sync::Mutex<(u64, u64)>,
+    /// Clock stamping subscriptions
+    clock: SharedClock,
 }
 
 impl MarketDataChannel {
     /// Create a new market data channel
     pub fn new() -> Self {
+        Self::with_backpressure(Backpressure::default())
+    }
+    
+    /// Create a market data channel that conflates or disconnects lagging
+    /// subscribers at `backpressure`'s queue depths
+    pub fn with_backpressure(backpressure: Backpressure) -> Self {
         Self {
             senders: Mutex::new(HashMap::new()),
+            backpressure,
+            totals: std::sync::Mutex::new((0, 0)),
+            clock: SystemClock::shared(),
         }
     }
     
+    /// Stamp subscriptions by the given clock's time
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Queue depths at which lagging subscribers are conflated or disconnected
+    pub fn backpressure(&self) -> &Backpressure {
+        &self.backpressure
+    }
+    
     /// Subscribe to a topic
     pub async fn subscribe<T: 'static + Send + Sync>(&self, topic: Topic) -> Receiver<Arc<dyn std::any::Any + Send + Sync>> {
+        self.subscribe_with_id::<T>(topic, Uuid::new_v4()).await
+    }
+    
+    /// Subscribe to a topic under a caller-chosen subscription ID
+    pub async fn subscribe_with_id<T: 'static + Send + Sync>(&self, topic: Topic, subscription_id: Uuid) -> Receiver<Arc<dyn std::any::Any + Send + Sync>> {
         let (sender, receiver) = crossbeam_channel::unbounded();
-        let subscription_id = Uuid::new_v4();
         
         let mut senders = self.senders.lock().await;
         senders.entry(topic).or_default().push(SubscriptionEntry {
             sender,
             id: subscription_id,
+            subscribed_at: self.clock.now(),
+            max_depth: 0,
+            delivered: 0,
+            dropped: 0,
+            conflating: false,
         });
         
         receiver
@@ -67,45 +229,105 @@ impl MarketDataChannel {
         
         // Publish to specific topic
         if let Some(topic_senders) = senders.get_mut(&topic) {
-            // Remove closed channels
-            topic_senders.retain(|entry| !entry.sender.is_empty());
-            
-            // Send to all subscribers
-            for entry in topic_senders.iter() {
-                let _ = entry.sender.try_send(message.clone());
-            }
+            self.broadcast(&topic, topic_senders, &message);
         }
         
         // Also publish to "all" topics if applicable
-        match &topic {
-            Topic::OrderBook(_market) => {
-                if let Some(all_senders) = senders.get_mut(&Topic::AllOrderBooks) {
-                    all_senders.retain(|entry| !entry.sender.is_empty());
-                    for entry in all_senders.iter() {
-                        let _ = entry.sender.try_send(message.clone());
-                    }
-                }
-            },
-            Topic::Trades(_market) => {
-                if let Some(all_senders) = senders.get_mut(&Topic::AllTrades) {
-                    all_senders.retain(|entry| !entry.sender.is_empty());
-                    for entry in all_senders.iter() {
-                        let _ = entry.sender.try_send(message.clone());
-                    }
-                }
-            },
-            Topic::Ticker(_market) => {
-                if let Some(all_senders) = senders.get_mut(&Topic::AllTickers) {
-                    all_senders.retain(|entry| !entry.sender.is_empty());
-                    for entry in all_senders.iter() {
-                        let _ = entry.sender.try_send(message.clone());
-                    }
+        let all_topic = match &topic {
+            Topic::OrderBook(_market) => Some(Topic::AllOrderBooks),
+            Topic::Trades(_market) => Some(Topic::AllTrades),
+            Topic::Ticker(_market) => Some(Topic::AllTickers),
+            _ => None,
+        };
+        
+        if let Some(all_topic) = all_topic {
+            if let Some(all_senders) = senders.get_mut(&all_topic) {
+                self.broadcast(&all_topic, all_senders, &message);
+            }
+        }
+    }
+    
+    /// Send a message to every subscriber, dropping those whose receiver is
+    /// gone and those lagging past the disconnect depth
+    fn broadcast(&self, topic: &Topic, entries: &mut Vec<SubscriptionEntry>, message: &Arc<dyn std::any::Any + Send + Sync>) {
+        let conflatable = topic.is_conflatable();
+        let mut dropped = 0;
+        let mut disconnected = 0;
+        entries.retain_mut(|entry| {
+            let before = entry.dropped;
+            let delivery = self.deliver(entry, conflatable, message);
+            dropped += entry.dropped - before;
+            match delivery {
+                Delivery::Kept => true,
+                Delivery::Closed => false,
+                Delivery::Lagging => {
+                    warn!("Disconnecting subscriber {} of {} lagging {} messages behind", entry.id, topic, entry.sender.len());
+                    disconnected += 1;
+                    false
                 }
-            },
-            _ => {}
+            }
+        });
+        
+        if dropped > 0 || disconnected > 0 {
+            let mut totals = self.totals.lock().unwrap();
+            totals.0 += disconnected;
+            totals.1 += dropped;
         }
     }
     
+    /// Offer a message to one subscriber, skipping it if the subscriber lags
+    /// on a conflatable topic
+    fn deliver(&self, entry: &mut SubscriptionEntry, conflatable: bool, message: &Arc<dyn std::any::Any + Send + Sync>) -> Delivery {
+        let depth = entry.sender.len();
+        entry.max_depth = entry.max_depth.max(depth);
+        if conflatable {
+            entry.conflating = depth >= self.backpressure.conflate_at;
+            if entry.conflating {
+                entry.dropped += 1;
+                return Delivery::Kept;
+            }
+        } else if depth >= self.backpressure.disconnect_at {
+            return Delivery::Lagging;
+        }
+        
+        // Channels are unbounded, so a failed send means the receiver was dropped
+        match entry.sender.try_send(message.clone()) {
+            Ok(()) => {
+                entry.delivered += 1;
+                Delivery::Kept
+            }
+            Err(_) => Delivery::Closed,
+        }
+    }
+    
+    /// Delivery statistics of every subscriber, deepest queue first
+    pub async fn metrics(&self) -> ChannelMetrics {
+        let senders = self.senders.lock().await;
+        let mut subscribers: Vec<SubscriberMetrics> = senders
+            .iter()
+            .flat_map(|(topic, entries)| entries.iter().map(move |entry| SubscriberMetrics {
+                id: entry.id,
+                topic: topic.to_string(),
+                subscribed_at: entry.subscribed_at,
+                queue_depth: entry.sender.len(),
+                max_queue_depth: entry.max_depth.max(entry.sender.len()),
+                delivered: entry.delivered,
+                dropped: entry.dropped,
+                conflating: entry.conflating,
+            }))
+            .collect();
+        drop(senders);
+        subscribers.sort_by(|a, b| b.queue_depth.cmp(&a.queue_depth).then(b.dropped.cmp(&a.dropped)));
+        
+        let (disconnected, dropped) = *self.totals.lock().unwrap();
+        ChannelMetrics { subscribers, disconnected, dropped }
+    }
+    
+    /// Number of open subscriptions across all topics
+    pub async fn subscription_count(&self) -> usize {
+        self.senders.lock().await.values().map(Vec::len).sum()
+    }
+    
     /// Unsubscribe using subscription ID
     pub async fn unsubscribe_by_id(&self, subscription_id: Uuid) -> bool {
         let mut senders = self.senders.lock().await;
@@ -0,0 +1,291 @@
+//! Fixed-layout encoding of feed messages
+//!
+//! Every frame is a little endian `u16` length of the rest of the frame,
+//! followed by a 12 byte header and a body whose layout depends only on the
+//! template, so consumers can decode at fixed offsets:
+//!
+//! | Offset | Field          | Type       |
+//! |--------|----------------|------------|
+//! | 0      | length         | `u16`      |
+//! | 2      | template       | `u16`      |
+//! | 4      | schema version | `u16`      |
+//! | 6      | sequence       | `u64`      |
+//!
+//! Sequence numbers are feed-wide and start at 1. Gap fill requests and
+//! rejects are not part of the stream and carry sequence 0.
+//!
+//! Decimals are an `i64` mantissa followed by a `u8` scale, the value being
+//! `mantissa * 10^-scale`. Markets are ASCII symbols padded with zeros to
+//! 16 bytes, timestamps nanoseconds since the Unix epoch, and sides `0` for
+//! buy (bid) and `1` for sell (ask).
+
+use chrono::{DateTime, Utc};
+use common::decimal::{Price, Quantity};
+use common::error::{Error, Result};
+use common::model::order::Side;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+/// Version of the layouts below, bumped on any change to them
+pub const SCHEMA_VERSION: u16 = 1;
+
+/// Bytes of a market symbol
+pub const MARKET_LEN: usize = 16;
+
+/// Bytes of the length prefix and header
+pub const HEADER_LEN: usize = 14;
+
+/// Change of one price level, one per changed level of an order book update
+pub const TEMPLATE_DEPTH_DELTA: u16 = 1;
+/// Trade
+pub const TEMPLATE_TRADE: u16 = 2;
+/// Client request to resend a range of sequence numbers
+pub const TEMPLATE_GAP_FILL_REQUEST: u16 = 3;
+/// Reply to a gap fill request for messages no longer retained
+pub const TEMPLATE_GAP_FILL_REJECT: u16 = 4;
+
+/// Flag of the last delta of an order book update, after which the book is consistent
+const FLAG_LAST: u8 = 1;
+
+/// Change of one price level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepthDelta {
+    /// Market symbol
+    pub market: String,
+    /// Sequence of the order book update, as in REST and WebSocket depth
+    pub book_sequence: u64,
+    /// Time of the order book update
+    pub timestamp: DateTime<Utc>,
+    /// Bid (`Buy`) or ask (`Sell`) side
+    pub side: Side,
+    /// Price of the level
+    pub price: Price,
+    /// New quantity at the level, zero when the level was removed
+    pub quantity: Quantity,
+    /// Whether this is the last delta of the update
+    pub last: bool,
+}
+
+/// Trade
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TradeTick {
+    /// Market symbol
+    pub market: String,
+    /// Trade ID
+    pub id: Uuid,
+    /// Execution time
+    pub timestamp: DateTime<Utc>,
+    /// Side that took liquidity
+    pub taker_side: Side,
+    /// Price
+    pub price: Price,
+    /// Quantity
+    pub quantity: Quantity,
+}
+
+/// Message carried in a frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedMessage {
+    /// Change of one price level
+    DepthDelta(DepthDelta),
+    /// Trade
+    Trade(TradeTick),
+    /// Resend up to `count` messages starting at sequence `from`
+    GapFillRequest { from: u64, count: u32 },
+    /// Messages from `from` are no longer retained, the oldest kept is `first_available`
+    GapFillReject { from: u64, first_available: u64 },
+}
+
+impl FeedMessage {
+    /// Encode as a frame with the given sequence number
+    pub fn encode(&self, sequence: u64) -> Result<Vec<u8>> {
+        let mut body = Vec::with_capacity(64);
+        let template = match self {
+            FeedMessage::DepthDelta(delta) => {
+                put_market(&mut body, &delta.market)?;
+                body.extend_from_slice(&delta.book_sequence.to_le_bytes());
+                put_timestamp(&mut body, delta.timestamp)?;
+                body.push(side_code(delta.side));
+                body.push(if delta.last { FLAG_LAST } else { 0 });
+                put_decimal(&mut body, delta.price)?;
+                put_decimal(&mut body, delta.quantity)?;
+                TEMPLATE_DEPTH_DELTA
+            }
+            FeedMessage::Trade(trade) => {
+                put_market(&mut body, &trade.market)?;
+                body.extend_from_slice(trade.id.as_bytes());
+                put_timestamp(&mut body, trade.timestamp)?;
+                body.push(side_code(trade.taker_side));
+                put_decimal(&mut body, trade.price)?;
+                put_decimal(&mut body, trade.quantity)?;
+                TEMPLATE_TRADE
+            }
+            FeedMessage::GapFillRequest { from, count } => {
+                body.extend_from_slice(&from.to_le_bytes());
+                body.extend_from_slice(&count.to_le_bytes());
+                TEMPLATE_GAP_FILL_REQUEST
+            }
+            FeedMessage::GapFillReject { from, first_available } => {
+                body.extend_from_slice(&from.to_le_bytes());
+                body.extend_from_slice(&first_available.to_le_bytes());
+                TEMPLATE_GAP_FILL_REJECT
+            }
+        };
+
+        let mut frame = Vec::with_capacity(HEADER_LEN + body.len());
+        frame.extend_from_slice(&((HEADER_LEN - 2 + body.len()) as u16).to_le_bytes());
+        frame.extend_from_slice(&template.to_le_bytes());
+        frame.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+        frame.extend_from_slice(&sequence.to_le_bytes());
+        frame.extend_from_slice(&body);
+        Ok(frame)
+    }
+
+    /// Decode a frame, returning its sequence number and message
+    pub fn decode(frame: &[u8]) -> Result<(u64, FeedMessage)> {
+        let mut reader = Reader { bytes: frame, position: 0 };
+        let length = reader.u16()? as usize;
+        if length != frame.len() - 2 {
+            return Err(Error::ValidationError(format!(
+                "Frame length {} does not match its {} bytes",
+                length,
+                frame.len() - 2
+            )));
+        }
+        let template = reader.u16()?;
+        let version = reader.u16()?;
+        if version != SCHEMA_VERSION {
+            return Err(Error::ValidationError(format!("Unsupported feed schema version {}", version)));
+        }
+        let sequence = reader.u64()?;
+
+        let message = match template {
+            TEMPLATE_DEPTH_DELTA => FeedMessage::DepthDelta(DepthDelta {
+                market: reader.market()?,
+                book_sequence: reader.u64()?,
+                timestamp: reader.timestamp()?,
+                side: reader.side()?,
+                last: reader.u8()? & FLAG_LAST != 0,
+                price: reader.decimal()?,
+                quantity: reader.decimal()?,
+            }),
+            TEMPLATE_TRADE => FeedMessage::Trade(TradeTick {
+                market: reader.market()?,
+                id: Uuid::from_slice(reader.take(16)?).map_err(|e| Error::ValidationError(e.to_string()))?,
+                timestamp: reader.timestamp()?,
+                taker_side: reader.side()?,
+                price: reader.decimal()?,
+                quantity: reader.decimal()?,
+            }),
+            TEMPLATE_GAP_FILL_REQUEST => FeedMessage::GapFillRequest {
+                from: reader.u64()?,
+                count: reader.u32()?,
+            },
+            TEMPLATE_GAP_FILL_REJECT => FeedMessage::GapFillReject {
+                from: reader.u64()?,
+                first_available: reader.u64()?,
+            },
+            other => return Err(Error::ValidationError(format!("Unknown feed template {}", other))),
+        };
+        Ok((sequence, message))
+    }
+}
+
+fn side_code(side: Side) -> u8 {
+    match side {
+        Side::Buy => 0,
+        Side::Sell => 1,
+    }
+}
+
+fn put_market(body: &mut Vec<u8>, market: &str) -> Result<()> {
+    if market.len() > MARKET_LEN || !market.is_ascii() {
+        return Err(Error::ValidationError(format!(
+            "Market {} is not an ASCII symbol of at most {} bytes",
+            market, MARKET_LEN
+        )));
+    }
+    let mut symbol = [0u8; MARKET_LEN];
+    symbol[..market.len()].copy_from_slice(market.as_bytes());
+    body.extend_from_slice(&symbol);
+    Ok(())
+}
+
+fn put_timestamp(body: &mut Vec<u8>, timestamp: DateTime<Utc>) -> Result<()> {
+    let nanos = timestamp
+        .timestamp_nanos_opt()
+        .ok_or_else(|| Error::ValidationError(format!("Timestamp {} is out of range", timestamp)))?;
+    body.extend_from_slice(&nanos.to_le_bytes());
+    Ok(())
+}
+
+fn put_decimal(body: &mut Vec<u8>, value: Decimal) -> Result<()> {
+    let value = value.normalize();
+    let mantissa = i64::try_from(value.mantissa())
+        .map_err(|_| Error::DecimalError(format!("{} does not fit a 64 bit mantissa", value)))?;
+    body.extend_from_slice(&mantissa.to_le_bytes());
+    body.push(value.scale() as u8);
+    Ok(())
+}
+
+/// Reads fields in order from a frame
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + len)
+            .ok_or_else(|| Error::ValidationError("Truncated feed frame".to_string()))?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn market(&mut self) -> Result<String> {
+        let symbol = self.take(MARKET_LEN)?;
+        let len = symbol.iter().position(|byte| *byte == 0).unwrap_or(MARKET_LEN);
+        String::from_utf8(symbol[..len].to_vec()).map_err(|e| Error::ValidationError(e.to_string()))
+    }
+
+    fn timestamp(&mut self) -> Result<DateTime<Utc>> {
+        Ok(DateTime::from_timestamp_nanos(self.i64()?))
+    }
+
+    fn side(&mut self) -> Result<Side> {
+        match self.u8()? {
+            0 => Ok(Side::Buy),
+            1 => Ok(Side::Sell),
+            other => Err(Error::ValidationError(format!("Unknown side {}", other))),
+        }
+    }
+
+    fn decimal(&mut self) -> Result<Decimal> {
+        let mantissa = self.i64()?;
+        let scale = self.u8()?;
+        Decimal::try_from_i128_with_scale(mantissa as i128, scale as u32)
+            .map_err(|e| Error::DecimalError(e.to_string()))
+    }
+}
@@ -0,0 +1,232 @@
+//! Binary market data feed
+//!
+//! For latency sensitive consumers, order book changes and trades are
+//! published in the fixed-layout encoding of [`codec`], taken from the same
+//! channel topics that feed WebSocket subscribers. Each changed price level
+//! is one depth delta, so a consumer applies deltas to its copy of the book
+//! and can start from the REST depth by skipping deltas whose book sequence
+//! it already has.
+//!
+//! Frames go to TCP clients and, when configured, as UDP datagrams to a
+//! multicast group or single host. Every frame carries a feed-wide sequence
+//! number; a consumer that sees a gap sends a gap fill request over TCP and
+//! is sent the missing frames again, or a reject naming the oldest frame
+//! still retained.
+
+pub mod codec;
+mod publisher;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use common::error::{Error, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::channel::{MarketDataChannel, Topic};
+use crate::models::{OrderBookUpdate, TradeMessage};
+use codec::FeedMessage;
+use publisher::{Frame, Publisher};
+
+/// Most frames resent for one gap fill request
+pub const MAX_GAP_FILL: u32 = 10_000;
+
+/// Where the binary feed is published
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedConfig {
+    /// Address TCP clients connect to
+    pub tcp_addr: Option<SocketAddr>,
+    /// Multicast group or host UDP datagrams are sent to
+    pub udp_target: Option<SocketAddr>,
+    /// Most recent frames kept for gap fills
+    pub retained: usize,
+}
+
+impl FeedConfig {
+    /// Whether the feed is published anywhere
+    pub fn is_enabled(&self) -> bool {
+        self.tcp_addr.is_some() || self.udp_target.is_some()
+    }
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            tcp_addr: None,
+            udp_target: None,
+            retained: 100_000,
+        }
+    }
+}
+
+/// A running binary feed
+pub struct BinaryFeed {
+    publisher: Arc<Publisher>,
+    tcp_addr: Option<SocketAddr>,
+}
+
+impl BinaryFeed {
+    /// Bind the configured sockets and publish the channel's order book
+    /// updates and trades until the process exits
+    pub async fn start(channel: Arc<MarketDataChannel>, config: FeedConfig) -> Result<Self> {
+        let publisher = Arc::new(Publisher::new(config.retained));
+
+        let tcp_addr = match config.tcp_addr {
+            Some(addr) => {
+                let listener = TcpListener::bind(addr)
+                    .await
+                    .map_err(|e| Error::ConfigurationError(format!("Binary feed cannot listen on {}: {}", addr, e)))?;
+                let addr = listener.local_addr().map_err(|e| Error::Internal(e.to_string()))?;
+                tokio::spawn(accept_clients(listener, publisher.clone()));
+                info!("Binary feed accepting TCP clients on {}", addr);
+                Some(addr)
+            }
+            None => None,
+        };
+
+        if let Some(target) = config.udp_target {
+            let local: SocketAddr = if target.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+            let socket = UdpSocket::bind(local)
+                .await
+                .map_err(|e| Error::ConfigurationError(format!("Binary feed cannot send UDP: {}", e)))?;
+            tokio::spawn(send_datagrams(socket, target, publisher.subscribe()));
+            info!("Binary feed sending UDP datagrams to {}", target);
+        }
+
+        // Subscribe last, so nothing is published before the sockets are ready
+        let id = Uuid::new_v4();
+        let depths = channel.subscribe_with_id::<OrderBookUpdate>(Topic::AllOrderBooks, id).await;
+        let trades = channel.subscribe_with_id::<TradeMessage>(Topic::AllTrades, id).await;
+        let forwarder = publisher.clone();
+        // The channel receivers block until a message arrives, so read them on
+        // a blocking thread
+        tokio::task::spawn_blocking(move || loop {
+            crossbeam_channel::select! {
+                recv(depths) -> message => match message {
+                    Ok(message) => if let Some(update) = message.downcast_ref::<OrderBookUpdate>() {
+                        forwarder.publish_depth(update);
+                    },
+                    Err(_) => break,
+                },
+                recv(trades) -> message => match message {
+                    Ok(message) => if let Some(trade) = message.downcast_ref::<TradeMessage>() {
+                        forwarder.publish_trade(trade);
+                    },
+                    Err(_) => break,
+                },
+            }
+        });
+
+        Ok(Self { publisher, tcp_addr })
+    }
+
+    /// Address TCP clients connect to, when enabled
+    pub fn tcp_addr(&self) -> Option<SocketAddr> {
+        self.tcp_addr
+    }
+
+    /// Sequence number of the last frame published, 0 before the first
+    pub fn last_sequence(&self) -> u64 {
+        self.publisher.last_sequence()
+    }
+}
+
+async fn accept_clients(listener: TcpListener, publisher: Arc<Publisher>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                debug!("Binary feed client {} connected", peer);
+                tokio::spawn(serve_client(stream, publisher.clone()));
+            }
+            Err(e) => warn!("Binary feed failed to accept a client: {}", e),
+        }
+    }
+}
+
+/// Stream live frames to a client, interleaved with replies to its gap fill requests
+async fn serve_client(stream: TcpStream, publisher: Arc<Publisher>) {
+    let _ = stream.set_nodelay(true);
+    let (mut reader, mut writer) = stream.into_split();
+    let mut live = publisher.subscribe();
+    let (replies_tx, mut replies) = mpsc::channel::<Vec<Frame>>(8);
+
+    tokio::spawn(async move {
+        loop {
+            let mut length = [0u8; 2];
+            if reader.read_exact(&mut length).await.is_err() {
+                break;
+            }
+            let mut frame = length.to_vec();
+            frame.resize(2 + u16::from_le_bytes(length) as usize, 0);
+            if reader.read_exact(&mut frame[2..]).await.is_err() {
+                break;
+            }
+
+            let reply = match FeedMessage::decode(&frame) {
+                Ok((_, FeedMessage::GapFillRequest { from, count })) => {
+                    match publisher.gap_fill(from, count.min(MAX_GAP_FILL) as usize) {
+                        Ok(frames) => frames,
+                        Err(first_available) => match (FeedMessage::GapFillReject { from, first_available }).encode(0) {
+                            Ok(reject) => vec![Arc::new(reject)],
+                            Err(_) => break,
+                        },
+                    }
+                }
+                Ok((_, other)) => {
+                    warn!("Binary feed client sent {:?}, closing", other);
+                    break;
+                }
+                Err(e) => {
+                    warn!("Binary feed client sent an invalid frame, closing: {}", e);
+                    break;
+                }
+            };
+            if replies_tx.send(reply).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        let frames = tokio::select! {
+            frame = live.recv() => match frame {
+                Ok(frame) => vec![frame],
+                // The client sees the gap in sequence numbers and can fill it
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!("Binary feed client lagged by {} frames", skipped);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            reply = replies.recv() => match reply {
+                Some(frames) => frames,
+                None => break,
+            },
+        };
+        for frame in frames {
+            if writer.write_all(&frame).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Send every live frame as one datagram
+async fn send_datagrams(socket: UdpSocket, target: SocketAddr, mut live: broadcast::Receiver<Frame>) {
+    loop {
+        match live.recv().await {
+            Ok(frame) => {
+                if let Err(e) = socket.send_to(&frame, target).await {
+                    warn!("Binary feed failed to send to {}: {}", target, e);
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!("Binary feed dropped {} UDP frames", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
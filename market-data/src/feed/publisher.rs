@@ -0,0 +1,156 @@
+//! Sequencing and retention of feed messages
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use common::decimal::{Price, Quantity};
+use common::model::order::Side;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use super::codec::{DepthDelta, FeedMessage, TradeTick};
+use crate::models::{OrderBookUpdate, PriceLevel, TradeMessage};
+
+/// Frames buffered per live consumer before it lags and has to gap fill
+const LIVE_CAPACITY: usize = 4096;
+
+/// Encoded frame, shared by every consumer
+pub type Frame = Arc<Vec<u8>>;
+
+/// Last published levels of a market
+#[derive(Default)]
+struct Book {
+    bids: HashMap<Price, Quantity>,
+    asks: HashMap<Price, Quantity>,
+}
+
+struct State {
+    next_sequence: u64,
+    books: HashMap<String, Book>,
+    /// Most recent frames by sequence, for gap fills
+    history: VecDeque<(u64, Frame)>,
+}
+
+/// Turns order book updates and trades into sequenced frames
+pub struct Publisher {
+    state: Mutex<State>,
+    live: broadcast::Sender<Frame>,
+    retained: usize,
+}
+
+impl Publisher {
+    /// Create a publisher keeping the last `retained` frames for gap fills
+    pub fn new(retained: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                next_sequence: 1,
+                books: HashMap::new(),
+                history: VecDeque::with_capacity(retained.min(LIVE_CAPACITY)),
+            }),
+            live: broadcast::channel(LIVE_CAPACITY).0,
+            retained,
+        }
+    }
+
+    /// Receive frames as they are published
+    pub fn subscribe(&self) -> broadcast::Receiver<Frame> {
+        self.live.subscribe()
+    }
+
+    /// Sequence number of the last frame published, 0 before the first
+    pub fn last_sequence(&self) -> u64 {
+        self.state.lock().unwrap().next_sequence - 1
+    }
+
+    /// Publish the levels that changed since the market's previous update
+    pub fn publish_depth(&self, update: &OrderBookUpdate) {
+        let mut state = self.state.lock().unwrap();
+        let book = state.books.entry(update.market.clone()).or_default();
+        let mut changes = diff(&mut book.bids, &update.bids, Side::Buy);
+        changes.extend(diff(&mut book.asks, &update.asks, Side::Sell));
+
+        let count = changes.len();
+        for (i, (side, price, quantity)) in changes.into_iter().enumerate() {
+            let delta = DepthDelta {
+                market: update.market.clone(),
+                book_sequence: update.sequence,
+                timestamp: update.timestamp,
+                side,
+                price,
+                quantity,
+                last: i + 1 == count,
+            };
+            self.publish(&mut state, FeedMessage::DepthDelta(delta));
+        }
+    }
+
+    /// Publish a trade
+    pub fn publish_trade(&self, trade: &TradeMessage) {
+        let tick = TradeTick {
+            market: trade.market.clone(),
+            id: trade.id,
+            timestamp: trade.timestamp,
+            taker_side: if trade.taker_side == "sell" { Side::Sell } else { Side::Buy },
+            price: trade.price,
+            quantity: trade.quantity,
+        };
+        let mut state = self.state.lock().unwrap();
+        self.publish(&mut state, FeedMessage::Trade(tick));
+    }
+
+    /// Retained frames from `from`, at most `count`, or the oldest sequence
+    /// still retained when `from` is older
+    pub fn gap_fill(&self, from: u64, count: usize) -> Result<Vec<Frame>, u64> {
+        let state = self.state.lock().unwrap();
+        let first_available = state.history.front().map(|(sequence, _)| *sequence).unwrap_or(state.next_sequence);
+        if from < first_available {
+            return Err(first_available);
+        }
+        let skip = (from - first_available) as usize;
+        Ok(state.history.iter().skip(skip).take(count).map(|(_, frame)| frame.clone()).collect())
+    }
+
+    fn publish(&self, state: &mut State, message: FeedMessage) {
+        let frame = match message.encode(state.next_sequence) {
+            Ok(frame) => Arc::new(frame),
+            Err(e) => {
+                warn!("Not publishing {:?} on the binary feed: {}", message, e);
+                return;
+            }
+        };
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+
+        if self.retained > 0 {
+            if state.history.len() == self.retained {
+                state.history.pop_front();
+            }
+            state.history.push_back((sequence, frame.clone()));
+        }
+        // Fails only when nobody is listening
+        let _ = self.live.send(frame);
+    }
+}
+
+/// Levels of one side that changed, best price first, updating `levels` to match
+fn diff(levels: &mut HashMap<Price, Quantity>, update: &[PriceLevel], side: Side) -> Vec<(Side, Price, Quantity)> {
+    let current: HashMap<Price, Quantity> = update.iter().map(|level| (level.price, level.quantity)).collect();
+    let mut changes: Vec<(Side, Price, Quantity)> = current
+        .iter()
+        .filter(|(price, quantity)| levels.get(*price) != Some(*quantity))
+        .map(|(price, quantity)| (side, *price, *quantity))
+        .chain(
+            levels
+                .keys()
+                .filter(|price| !current.contains_key(*price))
+                .map(|price| (side, *price, Quantity::ZERO)),
+        )
+        .collect();
+    match side {
+        Side::Buy => changes.sort_by_key(|(_, price, _)| Reverse(*price)),
+        Side::Sell => changes.sort_by_key(|(_, price, _)| *price),
+    }
+    *levels = current;
+    changes
+}
@@ -0,0 +1,154 @@
+//! Order book liquidity heatmaps
+//!
+//! Every sampling interval the depth of each market is cut down to its top
+//! levels and saved as a [`HeatmapSample`], next to the periodic order book
+//! snapshots. A [`Heatmap`] spreads the samples of a window over equal price
+//! buckets, one column per sample, for UIs that draw liquidity over time.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use common::decimal::{Price, Quantity};
+use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "utoipa")]
+use utoipa::ToSchema;
+
+use crate::models::{MarketDepth, PriceLevel};
+
+/// Most columns a heatmap has; longer windows skip samples evenly
+pub const MAX_HEATMAP_COLUMNS: usize = 1000;
+
+/// Most price buckets a heatmap can be split into
+pub const MAX_HEATMAP_BUCKETS: usize = 500;
+
+/// How often depth is sampled and how much of it is kept
+#[derive(Debug, Clone)]
+pub struct HeatmapConfig {
+    /// Time between samples
+    pub interval: Duration,
+    /// Levels kept per side of each sample
+    pub levels: usize,
+}
+
+impl Default for HeatmapConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            levels: 50,
+        }
+    }
+}
+
+/// The top of a market's book at one time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeatmapSample {
+    /// Market symbol
+    pub market: String,
+    /// When the sample was taken
+    pub timestamp: DateTime<Utc>,
+    /// Best bids, highest price first
+    pub bids: Vec<PriceLevel>,
+    /// Best asks, lowest price first
+    pub asks: Vec<PriceLevel>,
+}
+
+impl HeatmapSample {
+    /// Sample the top `levels` of each side of a book
+    pub fn from_depth(depth: &MarketDepth, levels: usize, timestamp: DateTime<Utc>) -> Self {
+        Self {
+            market: depth.market.clone(),
+            timestamp,
+            bids: depth.bids.iter().take(levels).cloned().collect(),
+            asks: depth.asks.iter().take(levels).cloned().collect(),
+        }
+    }
+}
+
+/// Resting quantity in each price bucket at one time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct HeatmapColumn {
+    /// When the sample was taken
+    pub timestamp: DateTime<Utc>,
+    /// Bid quantity per bucket, lowest price first
+    pub bids: Vec<Quantity>,
+    /// Ask quantity per bucket, lowest price first
+    pub asks: Vec<Quantity>,
+}
+
+/// Resting liquidity of a market by price and time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct Heatmap {
+    /// Market symbol
+    pub market: String,
+    /// Start of the window
+    pub from: DateTime<Utc>,
+    /// End of the window, exclusive
+    pub to: DateTime<Utc>,
+    /// Lowest sampled price, the bottom of the first bucket; unset without samples
+    pub price_low: Option<Price>,
+    /// Highest sampled price, the top of the last bucket; unset without samples
+    pub price_high: Option<Price>,
+    /// Width of each price bucket
+    pub bucket_size: Price,
+    /// Number of price buckets
+    pub buckets: usize,
+    /// One column per sample, oldest first
+    pub columns: Vec<HeatmapColumn>,
+}
+
+impl Heatmap {
+    /// Spread samples over `buckets` equal price buckets between their lowest and highest price
+    ///
+    /// Samples are expected oldest first. If there are more than
+    /// [`MAX_HEATMAP_COLUMNS`], samples are skipped evenly to fit.
+    pub fn build(market: &str, from: DateTime<Utc>, to: DateTime<Utc>, buckets: usize, samples: &[HeatmapSample]) -> Self {
+        let buckets = buckets.max(1);
+        let prices = || samples.iter().flat_map(|sample| sample.bids.iter().chain(&sample.asks)).map(|level| level.price);
+        let (low, high) = (prices().min(), prices().max());
+        let bucket_size = match (low, high) {
+            (Some(low), Some(high)) => ((high - low) / Price::from(buckets)).normalize(),
+            _ => Price::ZERO,
+        };
+
+        let bucket = |price: Price| -> usize {
+            match low {
+                Some(low) if !bucket_size.is_zero() => {
+                    ((price - low) / bucket_size).floor().to_usize().unwrap_or(0).min(buckets - 1)
+                }
+                _ => 0,
+            }
+        };
+        let spread = |levels: &[PriceLevel]| {
+            let mut quantities = vec![Quantity::ZERO; buckets];
+            for level in levels {
+                quantities[bucket(level.price)] += level.quantity;
+            }
+            quantities
+        };
+
+        let stride = samples.len().div_ceil(MAX_HEATMAP_COLUMNS).max(1);
+        let columns = samples
+            .iter()
+            .step_by(stride)
+            .map(|sample| HeatmapColumn {
+                timestamp: sample.timestamp,
+                bids: spread(&sample.bids),
+                asks: spread(&sample.asks),
+            })
+            .collect();
+
+        Self {
+            market: market.to_string(),
+            from,
+            to,
+            price_low: low,
+            price_high: high,
+            bucket_size,
+            buckets,
+            columns,
+        }
+    }
+}
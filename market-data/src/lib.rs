@@ -3,9 +3,19 @@
 mod service;
 mod models;
 pub mod channel;
+pub mod feed;
+pub mod heatmap;
+pub mod memory;
+pub mod repository;
+pub mod retention;
+pub mod shadow;
+pub mod sync;
+pub mod tape;
+pub mod warmup;
 
 pub use service::MarketDataService;
 pub use models::{
-    MarketDepth, OrderBookUpdate, PriceLevel, TradeMessage, 
-    Ticker, MarketSummary, Candle, CandleInterval,
+    MarketDepth, OrderBookUpdate, BestBidOffer, PriceLevel, TradeMessage, 
+    Ticker, MarketSummary, Candle, CandleFill, CandleInterval, CandleUpdate, MarketAnalytics,
+    CorrectionKind, TradeCorrection,
 };
\ No newline at end of file
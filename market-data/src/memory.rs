@@ -0,0 +1,131 @@
+//! Memory budgets
+//!
+//! Recent trades, candles and order books are kept in memory per market. A
+//! [`MemoryBudget`] bounds them: recent trades are capped per market as they
+//! arrive, while a periodic eviction pass spills the oldest candles of long
+//! series to the market repository and evicts whole markets that have been
+//! idle too long or are the least recently active beyond the market cap.
+//! Spilled candles are still served by candle history reads. An evicted
+//! market's book returns with its next change, its tickers are kept.
+
+use std::mem::size_of;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+#[cfg(feature = "utoipa")]
+use utoipa::ToSchema;
+
+use crate::models::{BestBidOffer, Candle, MarketDepth, PriceLevel, Ticker, TradeMessage};
+
+/// Bounds on the market data kept in memory
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    /// Recent trades kept per market
+    pub recent_trades: usize,
+    /// Candles kept in memory per market and interval, `None` for no limit
+    pub candles_per_series: Option<usize>,
+    /// Markets kept in memory, `None` for no limit
+    pub markets: Option<usize>,
+    /// How long a market may go without trades or book changes before it is
+    /// evicted, `None` to keep idle markets
+    pub idle_after: Option<chrono::Duration>,
+    /// Time between eviction passes
+    pub eviction_interval: Duration,
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        Self {
+            recent_trades: 100,
+            candles_per_series: None,
+            markets: None,
+            idle_after: None,
+            eviction_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Estimated memory held by one structure
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct StructureUsage {
+    /// Structure name, e.g. `recent_trades`
+    pub name: String,
+    /// Markets with entries
+    pub markets: usize,
+    /// Trades, candles, books, or levels held
+    pub entries: usize,
+    /// Estimated bytes held
+    pub bytes: usize,
+}
+
+/// What one eviction pass removed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct MemoryEviction {
+    /// Markets evicted, idle or least recently active first
+    pub evicted_markets: Vec<String>,
+    /// Candles written to the repository and dropped from memory
+    pub spilled_candles: u64,
+    /// Candles kept in memory because the repository failed to save them
+    pub spill_failures: u64,
+}
+
+/// Memory use by structure and eviction totals since startup
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct MemoryUsage {
+    /// Usage of each structure
+    pub structures: Vec<StructureUsage>,
+    /// Estimated bytes held by every structure
+    pub total_bytes: usize,
+    /// Markets with data in memory
+    pub markets: usize,
+    /// Eviction passes run
+    pub runs: u64,
+    /// When the last pass ran
+    pub last_run: Option<DateTime<Utc>>,
+    /// Markets evicted since startup
+    pub evicted_markets: u64,
+    /// Candles spilled to the repository since startup
+    pub spilled_candles: u64,
+    /// Candles the repository failed to save since startup
+    pub spill_failures: u64,
+}
+
+impl MemoryUsage {
+    pub(crate) fn add(&mut self, eviction: &MemoryEviction, now: DateTime<Utc>) {
+        self.runs += 1;
+        self.last_run = Some(now);
+        self.evicted_markets += eviction.evicted_markets.len() as u64;
+        self.spilled_candles += eviction.spilled_candles;
+        self.spill_failures += eviction.spill_failures;
+    }
+}
+
+/// Estimated bytes of a trade
+pub(crate) fn trade_bytes(trade: &TradeMessage) -> usize {
+    size_of::<TradeMessage>() + trade.market.capacity() + trade.taker_side.capacity()
+}
+
+/// Estimated bytes of a candle
+pub(crate) fn candle_bytes(candle: &Candle) -> usize {
+    size_of::<Candle>() + candle.market.capacity()
+}
+
+/// Estimated bytes of an order book
+pub(crate) fn depth_bytes(depth: &MarketDepth) -> usize {
+    size_of::<MarketDepth>() + depth.market.capacity()
+        + (depth.bids.capacity() + depth.asks.capacity()) * size_of::<PriceLevel>()
+}
+
+/// Estimated bytes of a best bid and offer
+pub(crate) fn bbo_bytes(bbo: &BestBidOffer) -> usize {
+    size_of::<BestBidOffer>() + bbo.market.capacity()
+}
+
+/// Estimated bytes of a ticker
+pub(crate) fn ticker_bytes(ticker: &Ticker) -> usize {
+    size_of::<Ticker>() + ticker.market.capacity()
+}
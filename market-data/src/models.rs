@@ -11,11 +11,14 @@ use utoipa::ToSchema;
 
 /// Market depth (order book)
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
 pub struct MarketDepth {
     /// Market symbol
     pub market: String,
     /// Timestamp
     pub timestamp: DateTime<Utc>,
+    /// Sequence number of the update that produced this depth
+    pub sequence: u64,
     /// Bid side (price, quantity) sorted by price in descending order
     pub bids: Vec<PriceLevel>,
     /// Ask side (price, quantity) sorted by price in ascending order
@@ -24,19 +27,53 @@ pub struct MarketDepth {
 
 /// Order book update message
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct OrderBookUpdate {
     /// Market symbol
     pub market: String,
     /// Timestamp
     pub timestamp: DateTime<Utc>,
+    /// Per-market sequence number, incremented by one for every update
+    pub sequence: u64,
     /// Bid updates (price, quantity) - quantity of 0 means remove level
     pub bids: Vec<PriceLevel>,
     /// Ask updates (price, quantity) - quantity of 0 means remove level
     pub asks: Vec<PriceLevel>,
 }
 
+/// Top of a market's book, published only when it changes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct BestBidOffer {
+    /// Market symbol
+    pub market: String,
+    /// Best bid price
+    pub bid_price: Option<Price>,
+    /// Quantity at the best bid
+    pub bid_size: Option<Quantity>,
+    /// Best ask price
+    pub ask_price: Option<Price>,
+    /// Quantity at the best ask
+    pub ask_size: Option<Quantity>,
+    /// Sequence of the order book update that produced it
+    pub sequence: u64,
+    /// Timestamp
+    pub timestamp: DateTime<Utc>,
+}
+
+impl BestBidOffer {
+    /// Whether both have the same prices and sizes
+    pub fn same_top(&self, other: &BestBidOffer) -> bool {
+        (self.bid_price, self.bid_size, self.ask_price, self.ask_size)
+            == (other.bid_price, other.bid_size, other.ask_price, other.ask_size)
+    }
+}
+
 /// Price level in order book
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct PriceLevel {
     /// Price
     pub price: Price,
@@ -46,6 +83,7 @@ pub struct PriceLevel {
 
 /// Trade message
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct TradeMessage {
     /// Unique trade ID
     pub id: Uuid,
@@ -57,8 +95,14 @@ pub struct TradeMessage {
     pub quantity: Quantity,
     /// Side that was the taker (initiated the match)
     pub taker_side: String, // "buy" or "sell"
+    /// Whether the buyer was the resting (maker) side
+    pub is_buyer_maker: bool,
     /// Timestamp
     pub timestamp: DateTime<Utc>,
+    /// Position in the market's trades as numbered by the matching engine,
+    /// consecutive unless trades were missed
+    #[serde(default)]
+    pub sequence: u64,
 }
 
 impl From<&Trade> for TradeMessage {
@@ -72,14 +116,46 @@ impl From<&Trade> for TradeMessage {
                 common::model::order::Side::Buy => "buy".to_string(),
                 common::model::order::Side::Sell => "sell".to_string(),
             },
+            is_buyer_maker: trade.is_buyer_maker,
             timestamp: trade.created_at,
+            sequence: trade.sequence,
         }
     }
 }
 
+/// What happened to a trade on the tape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum CorrectionKind {
+    /// The trade was busted and no longer stands
+    Bust,
+}
+
+/// Correction to a trade already published, on its market's correction topic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct TradeCorrection {
+    /// Corrected trade ID
+    pub trade_id: Uuid,
+    /// Market symbol
+    pub market: String,
+    /// Price the trade was published at
+    pub price: Price,
+    /// Quantity the trade was published with
+    pub quantity: Quantity,
+    /// What happened to the trade
+    pub kind: CorrectionKind,
+    /// Reason given by the operator
+    pub reason: String,
+    /// When the correction was made
+    pub timestamp: DateTime<Utc>,
+}
+
 /// Market ticker
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct Ticker {
     /// Market symbol
     pub market: String,
@@ -135,6 +211,7 @@ pub struct MarketSummary {
 /// Candle interval
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
 pub enum CandleInterval {
     /// 1 minute
     Minute1,
@@ -157,6 +234,39 @@ pub enum CandleInterval {
 }
 
 impl CandleInterval {
+    /// Every interval, shortest first
+    pub const ALL: [CandleInterval; 9] = [
+        CandleInterval::Minute1,
+        CandleInterval::Minute5,
+        CandleInterval::Minute15,
+        CandleInterval::Minute30,
+        CandleInterval::Hour1,
+        CandleInterval::Hour4,
+        CandleInterval::Hour12,
+        CandleInterval::Day1,
+        CandleInterval::Week1,
+    ];
+
+    /// Short code used in requests, e.g. `1m`
+    pub fn code(&self) -> &'static str {
+        match self {
+            CandleInterval::Minute1 => "1m",
+            CandleInterval::Minute5 => "5m",
+            CandleInterval::Minute15 => "15m",
+            CandleInterval::Minute30 => "30m",
+            CandleInterval::Hour1 => "1h",
+            CandleInterval::Hour4 => "4h",
+            CandleInterval::Hour12 => "12h",
+            CandleInterval::Day1 => "1d",
+            CandleInterval::Week1 => "1w",
+        }
+    }
+
+    /// Interval with the given short code
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|interval| interval.code() == code)
+    }
+
     /// Get the duration in seconds
     pub fn duration_secs(&self) -> i64 {
         match self {
@@ -171,11 +281,18 @@ impl CandleInterval {
             CandleInterval::Week1 => 604800,
         }
     }
+
+    /// Start of the interval containing `at`
+    pub fn open_time(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let interval_secs = self.duration_secs();
+        DateTime::from_timestamp(at.timestamp().div_euclid(interval_secs) * interval_secs, 0).unwrap_or(at)
+    }
 }
 
 /// OHLCV candle
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct Candle {
     /// Market symbol
     pub market: String,
@@ -199,4 +316,140 @@ pub struct Candle {
     pub quote_volume: Quantity,
     /// Number of trades
     pub trades: u64,
-}
\ No newline at end of file
+    /// Base asset volume of trades where the taker bought
+    #[serde(default)]
+    pub taker_buy_volume: Quantity,
+    /// Quote asset volume of trades where the taker bought
+    #[serde(default)]
+    pub taker_buy_quote_volume: Quantity,
+}
+
+impl Candle {
+    /// Candles of the given interval built from trades, oldest first
+    ///
+    /// Intervals without trades have no candle.
+    pub fn from_trades(market: &str, interval: CandleInterval, trades: &[TradeMessage]) -> Vec<Candle> {
+        let mut candles: Vec<Candle> = Vec::new();
+        let mut trades: Vec<&TradeMessage> = trades.iter().collect();
+        trades.sort_by_key(|trade| trade.timestamp);
+
+        for trade in trades {
+            let open_time = interval.open_time(trade.timestamp);
+            let (taker_buy_volume, taker_buy_quote_volume) = if trade.taker_side == "buy" {
+                (trade.quantity, trade.price * trade.quantity)
+            } else {
+                (Quantity::ZERO, Quantity::ZERO)
+            };
+            match candles.last_mut() {
+                Some(candle) if candle.open_time == open_time => {
+                    candle.high = candle.high.max(trade.price);
+                    candle.low = candle.low.min(trade.price);
+                    candle.close = trade.price;
+                    candle.volume += trade.quantity;
+                    candle.quote_volume += trade.price * trade.quantity;
+                    candle.trades += 1;
+                    candle.taker_buy_volume += taker_buy_volume;
+                    candle.taker_buy_quote_volume += taker_buy_quote_volume;
+                }
+                _ => candles.push(Candle {
+                    market: market.to_string(),
+                    interval,
+                    open_time,
+                    close_time: open_time + chrono::Duration::seconds(interval.duration_secs()),
+                    open: trade.price,
+                    high: trade.price,
+                    low: trade.price,
+                    close: trade.price,
+                    volume: trade.quantity,
+                    quote_volume: trade.price * trade.quantity,
+                    trades: 1,
+                    taker_buy_volume,
+                    taker_buy_quote_volume,
+                }),
+            }
+        }
+
+        candles
+    }
+
+    /// Candle of a period without trades, with every price at `price`
+    pub fn empty(market: &str, interval: CandleInterval, open_time: DateTime<Utc>, price: Price) -> Candle {
+        Candle {
+            market: market.to_string(),
+            interval,
+            open_time,
+            close_time: open_time + chrono::Duration::seconds(interval.duration_secs()),
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: Quantity::ZERO,
+            quote_volume: Quantity::ZERO,
+            trades: 0,
+            taker_buy_volume: Quantity::ZERO,
+            taker_buy_quote_volume: Quantity::ZERO,
+        }
+    }
+}
+
+/// How periods without trades are filled in candle queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+#[serde(rename_all = "snake_case")]
+pub enum CandleFill {
+    /// Prices and volumes of zero
+    Zero,
+    /// Every price at the previous close, volumes of zero
+    Previous,
+}
+
+/// Change to a candle, published on its market's candle topic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct CandleUpdate {
+    /// The candle as of this update
+    #[serde(flatten)]
+    #[cfg_attr(feature = "graphql", graphql(flatten))]
+    pub candle: Candle,
+    /// Whether the candle's interval has ended, making this its final update
+    pub closed: bool,
+}
+
+/// Spread, depth and trade flow statistics of a market
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct MarketAnalytics {
+    /// Market symbol
+    pub market: String,
+    /// Sequence of the order book snapshot used, 0 before the first one
+    pub sequence: u64,
+    /// When the statistics were computed
+    pub timestamp: DateTime<Utc>,
+    /// Best bid price
+    pub best_bid: Option<Price>,
+    /// Best ask price
+    pub best_ask: Option<Price>,
+    /// Midpoint of the best bid and ask
+    pub mid: Option<Price>,
+    /// Best ask minus best bid
+    pub spread: Option<Price>,
+    /// Spread in basis points of the mid
+    pub spread_bps: Option<f64>,
+    /// Distance from the mid, in basis points, that depth is measured within
+    pub depth_bps: u32,
+    /// Bid quantity within `depth_bps` of the mid, or on the whole side without a mid
+    pub bid_depth: Quantity,
+    /// Ask quantity within `depth_bps` of the mid, or on the whole side without a mid
+    pub ask_depth: Quantity,
+    /// `(bid_depth - ask_depth) / (bid_depth + ask_depth)`, from -1 (only asks) to 1 (only bids)
+    pub book_imbalance: Option<f64>,
+    /// Number of recent trades the trade flow covers
+    pub trade_count: usize,
+    /// Volume bought by takers in those trades
+    pub buy_volume: Quantity,
+    /// Volume sold by takers in those trades
+    pub sell_volume: Quantity,
+    /// `(buy_volume - sell_volume) / (buy_volume + sell_volume)`, from -1 (only selling) to 1 (only buying)
+    pub trade_flow_imbalance: Option<f64>,
+}
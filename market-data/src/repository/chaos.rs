@@ -0,0 +1,83 @@
+//! Market data storage with injected faults
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::chaos::{ChaosPoint, SharedChaos};
+use common::error::Result;
+
+use crate::heatmap::HeatmapSample;
+use crate::models::{Candle, CandleInterval, MarketDepth, TradeMessage};
+use super::MarketRepository;
+
+/// Repository applying the [`ChaosPoint::Repository`] fault to every call
+/// before passing it on to another
+pub struct ChaosRepository {
+    inner: Arc<dyn MarketRepository>,
+    chaos: SharedChaos,
+}
+
+impl ChaosRepository {
+    /// Pass calls on to `inner` once `chaos` lets them through
+    pub fn new(inner: Arc<dyn MarketRepository>, chaos: SharedChaos) -> Self {
+        Self { inner, chaos }
+    }
+}
+
+#[async_trait]
+impl MarketRepository for ChaosRepository {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn save_depth_snapshot(&self, depth: &MarketDepth) -> Result<()> {
+        self.chaos.inject(ChaosPoint::Repository).await?;
+        self.inner.save_depth_snapshot(depth).await
+    }
+
+    async fn get_depth_snapshot_at(&self, market: &str, at: DateTime<Utc>) -> Result<Option<MarketDepth>> {
+        self.chaos.inject(ChaosPoint::Repository).await?;
+        self.inner.get_depth_snapshot_at(market, at).await
+    }
+
+    async fn save_trade(&self, trade: &TradeMessage) -> Result<()> {
+        self.chaos.inject(ChaosPoint::Repository).await?;
+        self.inner.save_trade(trade).await
+    }
+
+    async fn get_trades_between(&self, market: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<TradeMessage>> {
+        self.chaos.inject(ChaosPoint::Repository).await?;
+        self.inner.get_trades_between(market, from, to).await
+    }
+
+    async fn get_trades_after(&self, market: &str, sequence: u64, limit: usize) -> Result<Vec<TradeMessage>> {
+        self.chaos.inject(ChaosPoint::Repository).await?;
+        self.inner.get_trades_after(market, sequence, limit).await
+    }
+
+    async fn get_last_trade_sequence(&self, market: &str) -> Result<u64> {
+        self.chaos.inject(ChaosPoint::Repository).await?;
+        self.inner.get_last_trade_sequence(market).await
+    }
+
+    async fn save_heatmap_sample(&self, sample: &HeatmapSample) -> Result<()> {
+        self.chaos.inject(ChaosPoint::Repository).await?;
+        self.inner.save_heatmap_sample(sample).await
+    }
+
+    async fn get_heatmap_samples(&self, market: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<HeatmapSample>> {
+        self.chaos.inject(ChaosPoint::Repository).await?;
+        self.inner.get_heatmap_samples(market, from, to).await
+    }
+
+    async fn save_candles(&self, candles: &[Candle]) -> Result<()> {
+        self.chaos.inject(ChaosPoint::Repository).await?;
+        self.inner.save_candles(candles).await
+    }
+
+    async fn get_candles_before(&self, market: &str, interval: CandleInterval, before: DateTime<Utc>, limit: usize) -> Result<Vec<Candle>> {
+        self.chaos.inject(ChaosPoint::Repository).await?;
+        self.inner.get_candles_before(market, interval, before, limit).await
+    }
+}
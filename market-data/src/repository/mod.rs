@@ -1,28 +1,229 @@
+//! Storage for market data history
+
+mod chaos;
 mod postgres;
 
-use std::sync::Arc;
-use async_trait::async_trait;
-use sqlx::PgPool;
+use std::collections::VecDeque;
 
-use common::decimal::Decimal;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use common::error::Result;
-use common::model::{Market, OrderBook, Trade};
+use dashmap::DashMap;
+
+use crate::heatmap::HeatmapSample;
+use crate::models::{Candle, CandleInterval, MarketDepth, TradeMessage};
+
+pub use chaos::ChaosRepository;
+pub use postgres::PostgresMarketRepository;
+
+/// Snapshots kept per market by the in-memory repository, a week at one a minute
+const DEFAULT_SNAPSHOT_RETENTION: usize = 7 * 24 * 60;
+
+/// Trades kept per market by the in-memory repository
+const DEFAULT_TRADE_RETENTION: usize = 250_000;
 
-use crate::models::MarketSummary;
+/// Heatmap samples kept per market by the in-memory repository, a day at one every ten seconds
+const DEFAULT_HEATMAP_RETENTION: usize = 24 * 60 * 6;
 
+/// Candles kept per market and interval by the in-memory repository
+const DEFAULT_CANDLE_RETENTION: usize = 100_000;
+
+/// Market data repository trait defining the interface for market data storage
 #[async_trait]
 pub trait MarketRepository: Send + Sync {
-    async fn create_market(&self, market: Market) -> Result<Market>;
-    async fn get_market(&self, market_id: &str) -> Result<Option<Market>>;
-    async fn list_markets(&self) -> Result<Vec<Market>>;
-    async fn update_market_summary(&self, market_id: &str, summary: &MarketSummary) -> Result<()>;
-    async fn get_market_summary(&self, market_id: &str) -> Result<Option<MarketSummary>>;
-    async fn save_trade(&self, trade: &Trade) -> Result<()>;
-    async fn get_recent_trades(&self, market_id: &str, limit: usize) -> Result<Vec<Trade>>;
-    async fn save_order_book(&self, market_id: &str, order_book: &OrderBook) -> Result<()>;
-    async fn get_order_book(&self, market_id: &str) -> Result<Option<OrderBook>>;
+    /// Storage backend name, e.g. `memory` or `postgres`
+    fn name(&self) -> &str;
+
+    /// Save a snapshot of a market's order book
+    async fn save_depth_snapshot(&self, depth: &MarketDepth) -> Result<()>;
+
+    /// Get the newest snapshot of a market's order book taken at or before `at`
+    async fn get_depth_snapshot_at(&self, market: &str, at: DateTime<Utc>) -> Result<Option<MarketDepth>>;
+
+    /// Save a trade to a market's history
+    async fn save_trade(&self, trade: &TradeMessage) -> Result<()>;
+
+    /// Get a market's trades executed at or after `from` and before `to`, oldest first
+    async fn get_trades_between(&self, market: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<TradeMessage>>;
+
+    /// Get up to `limit` of a market's trades numbered after `sequence`, oldest first
+    async fn get_trades_after(&self, market: &str, sequence: u64, limit: usize) -> Result<Vec<TradeMessage>>;
+
+    /// Get the sequence number of a market's last stored trade, 0 before the first
+    async fn get_last_trade_sequence(&self, market: &str) -> Result<u64>;
+
+    /// Save a heatmap sample of a market's book
+    async fn save_heatmap_sample(&self, sample: &HeatmapSample) -> Result<()>;
+
+    /// Get a market's heatmap samples taken at or after `from` and before `to`, oldest first
+    async fn get_heatmap_samples(&self, market: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<HeatmapSample>>;
+
+    /// Save candles spilled from memory, replacing any of the same period
+    async fn save_candles(&self, candles: &[Candle]) -> Result<()>;
+
+    /// Get up to `limit` of a market's candles opened before `before`, newest first
+    async fn get_candles_before(&self, market: &str, interval: CandleInterval, before: DateTime<Utc>, limit: usize) -> Result<Vec<Candle>>;
+}
+
+/// In-memory repository for market data
+pub struct InMemoryMarketRepository {
+    /// Order book snapshots by market, oldest first
+    snapshots: DashMap<String, VecDeque<MarketDepth>>,
+    /// Snapshots kept per market before the oldest is dropped
+    retention: usize,
+    /// Trades by market, oldest first
+    trades: DashMap<String, VecDeque<TradeMessage>>,
+    /// Trades kept per market before the oldest is dropped
+    trade_retention: usize,
+    /// Heatmap samples by market, oldest first
+    heatmap: DashMap<String, VecDeque<HeatmapSample>>,
+    /// Spilled candles by market and interval, oldest first
+    candles: DashMap<(String, CandleInterval), VecDeque<Candle>>,
+}
+
+impl InMemoryMarketRepository {
+    /// Create a new in-memory market data repository
+    pub fn new() -> Self {
+        Self::with_retention(DEFAULT_SNAPSHOT_RETENTION)
+    }
+
+    /// Create a repository that keeps at most `retention` snapshots per market
+    pub fn with_retention(retention: usize) -> Self {
+        Self {
+            snapshots: DashMap::new(),
+            retention: retention.max(1),
+            trades: DashMap::new(),
+            trade_retention: DEFAULT_TRADE_RETENTION,
+            heatmap: DashMap::new(),
+            candles: DashMap::new(),
+        }
+    }
+
+    /// Keep at most `retention` trades per market
+    pub fn with_trade_retention(mut self, retention: usize) -> Self {
+        self.trade_retention = retention.max(1);
+        self
+    }
+}
+
+impl Default for InMemoryMarketRepository {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-pub fn create_repository(pool: PgPool) -> Arc<dyn MarketRepository> {
-    Arc::new(postgres::PostgresMarketRepository::new(pool))
-}
\ No newline at end of file
+#[async_trait]
+impl MarketRepository for InMemoryMarketRepository {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    async fn save_depth_snapshot(&self, depth: &MarketDepth) -> Result<()> {
+        let mut snapshots = self.snapshots.entry(depth.market.clone()).or_default();
+
+        // Keep snapshots ordered by time even if one arrives late
+        let index = snapshots.partition_point(|snapshot| snapshot.timestamp <= depth.timestamp);
+        snapshots.insert(index, depth.clone());
+        while snapshots.len() > self.retention {
+            snapshots.pop_front();
+        }
+
+        Ok(())
+    }
+
+    async fn get_depth_snapshot_at(&self, market: &str, at: DateTime<Utc>) -> Result<Option<MarketDepth>> {
+        let Some(snapshots) = self.snapshots.get(market) else {
+            return Ok(None);
+        };
+
+        let index = snapshots.partition_point(|snapshot| snapshot.timestamp <= at);
+        Ok(index.checked_sub(1).map(|index| snapshots[index].clone()))
+    }
+
+    async fn save_trade(&self, trade: &TradeMessage) -> Result<()> {
+        let mut trades = self.trades.entry(trade.market.clone()).or_default();
+
+        let index = trades.partition_point(|saved| saved.timestamp <= trade.timestamp);
+        trades.insert(index, trade.clone());
+        while trades.len() > self.trade_retention {
+            trades.pop_front();
+        }
+
+        Ok(())
+    }
+
+    async fn get_trades_between(&self, market: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<TradeMessage>> {
+        let Some(trades) = self.trades.get(market) else {
+            return Ok(Vec::new());
+        };
+
+        let start = trades.partition_point(|trade| trade.timestamp < from);
+        let end = trades.partition_point(|trade| trade.timestamp < to);
+        Ok(trades.range(start..end.max(start)).cloned().collect())
+    }
+
+    async fn get_trades_after(&self, market: &str, sequence: u64, limit: usize) -> Result<Vec<TradeMessage>> {
+        let Some(trades) = self.trades.get(market) else {
+            return Ok(Vec::new());
+        };
+
+        // Kept in time order, which a late trade may not share with its sequence
+        let mut after: Vec<TradeMessage> = trades.iter().filter(|trade| trade.sequence > sequence).cloned().collect();
+        after.sort_by_key(|trade| trade.sequence);
+        after.truncate(limit);
+        Ok(after)
+    }
+
+    async fn get_last_trade_sequence(&self, market: &str) -> Result<u64> {
+        Ok(self.trades
+            .get(market)
+            .and_then(|trades| trades.iter().map(|trade| trade.sequence).max())
+            .unwrap_or(0))
+    }
+
+    async fn save_heatmap_sample(&self, sample: &HeatmapSample) -> Result<()> {
+        let mut samples = self.heatmap.entry(sample.market.clone()).or_default();
+
+        let index = samples.partition_point(|saved| saved.timestamp <= sample.timestamp);
+        samples.insert(index, sample.clone());
+        while samples.len() > DEFAULT_HEATMAP_RETENTION {
+            samples.pop_front();
+        }
+
+        Ok(())
+    }
+
+    async fn get_heatmap_samples(&self, market: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<HeatmapSample>> {
+        let Some(samples) = self.heatmap.get(market) else {
+            return Ok(Vec::new());
+        };
+
+        let start = samples.partition_point(|sample| sample.timestamp < from);
+        let end = samples.partition_point(|sample| sample.timestamp < to);
+        Ok(samples.range(start..end.max(start)).cloned().collect())
+    }
+
+    async fn save_candles(&self, candles: &[Candle]) -> Result<()> {
+        for candle in candles {
+            let mut saved = self.candles.entry((candle.market.clone(), candle.interval)).or_default();
+            match saved.binary_search_by_key(&candle.open_time, |saved| saved.open_time) {
+                Ok(index) => saved[index] = candle.clone(),
+                Err(index) => saved.insert(index, candle.clone()),
+            }
+            while saved.len() > DEFAULT_CANDLE_RETENTION {
+                saved.pop_front();
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_candles_before(&self, market: &str, interval: CandleInterval, before: DateTime<Utc>, limit: usize) -> Result<Vec<Candle>> {
+        let Some(saved) = self.candles.get(&(market.to_string(), interval)) else {
+            return Ok(Vec::new());
+        };
+
+        let end = saved.partition_point(|candle| candle.open_time < before);
+        Ok(saved.range(..end).rev().take(limit).cloned().collect())
+    }
+}
@@ -0,0 +1,136 @@
+//! Candle retention and downsampling
+//!
+//! Short candle intervals are only kept for a while: by default minute
+//! candles for a week and hourly candles for three months, while daily and
+//! weekly candles are kept forever. A periodic compaction drops candles past
+//! their interval's retention. Before dropping them, it rolls them up into
+//! the next longer interval kept longer, for any period that interval has no
+//! candle of, so history is only ever coarsened, never lost.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+#[cfg(feature = "utoipa")]
+use utoipa::ToSchema;
+
+use crate::models::{Candle, CandleInterval};
+
+/// How long candles of each interval are kept
+#[derive(Debug, Clone)]
+pub struct CandleRetention {
+    /// Retention of 1m to 30m candles, `None` to keep them forever
+    pub minute: Option<chrono::Duration>,
+    /// Retention of 1h to 12h candles, `None` to keep them forever
+    pub hour: Option<chrono::Duration>,
+    /// Time between compactions
+    pub compaction_interval: Duration,
+}
+
+impl Default for CandleRetention {
+    fn default() -> Self {
+        Self {
+            minute: Some(chrono::Duration::days(7)),
+            hour: Some(chrono::Duration::days(90)),
+            compaction_interval: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl CandleRetention {
+    /// Keep candles of every interval forever
+    pub fn forever() -> Self {
+        Self {
+            minute: None,
+            hour: None,
+            ..Self::default()
+        }
+    }
+
+    /// How long candles of an interval are kept, `None` for forever
+    pub fn retention(&self, interval: CandleInterval) -> Option<chrono::Duration> {
+        match interval {
+            CandleInterval::Minute1 | CandleInterval::Minute5 | CandleInterval::Minute15 | CandleInterval::Minute30 => self.minute,
+            CandleInterval::Hour1 | CandleInterval::Hour4 | CandleInterval::Hour12 => self.hour,
+            CandleInterval::Day1 | CandleInterval::Week1 => None,
+        }
+    }
+
+    /// Shortest longer interval kept longer than `interval`, which its
+    /// expired candles are rolled up into
+    pub fn downsample_target(&self, interval: CandleInterval) -> Option<CandleInterval> {
+        let retention = self.retention(interval)?;
+        CandleInterval::ALL
+            .into_iter()
+            .filter(|target| target.duration_secs() > interval.duration_secs())
+            .find(|target| self.retention(*target).is_none_or(|kept| kept > retention))
+    }
+}
+
+/// Candles one compaction removed and added
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct CandleCompaction {
+    /// Candles dropped past retention, by interval code
+    pub purged: BTreeMap<String, u64>,
+    /// Candles of longer intervals built from dropped ones
+    pub downsampled: u64,
+}
+
+impl CandleCompaction {
+    /// Number of candles dropped in every interval
+    pub fn total_purged(&self) -> u64 {
+        self.purged.values().sum()
+    }
+
+    pub(crate) fn add(&mut self, other: &CandleCompaction) {
+        for (interval, purged) in &other.purged {
+            *self.purged.entry(interval.clone()).or_default() += purged;
+        }
+        self.downsampled += other.downsampled;
+    }
+}
+
+/// Compaction totals since startup
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct CompactionMetrics {
+    /// Compactions run
+    pub runs: u64,
+    /// When the last compaction ran
+    pub last_run: Option<DateTime<Utc>>,
+    /// What the last compaction removed and added
+    pub last: CandleCompaction,
+    /// What every compaction removed and added
+    pub total: CandleCompaction,
+}
+
+/// Candles of `interval` covering the periods of `candles`, one per period
+///
+/// `candles` must be oldest first.
+pub fn downsample(interval: CandleInterval, candles: &[Candle]) -> Vec<Candle> {
+    let mut merged: Vec<Candle> = Vec::new();
+    for candle in candles {
+        let open_time = interval.open_time(candle.open_time);
+        match merged.last_mut() {
+            Some(current) if current.open_time == open_time => {
+                current.high = current.high.max(candle.high);
+                current.low = current.low.min(candle.low);
+                current.close = candle.close;
+                current.volume += candle.volume;
+                current.quote_volume += candle.quote_volume;
+                current.trades += candle.trades;
+                current.taker_buy_volume += candle.taker_buy_volume;
+                current.taker_buy_quote_volume += candle.taker_buy_quote_volume;
+            }
+            _ => merged.push(Candle {
+                interval,
+                open_time,
+                close_time: open_time + chrono::Duration::seconds(interval.duration_secs()),
+                ..candle.clone()
+            }),
+        }
+    }
+    merged
+}
@@ -1,17 +1,36 @@
 //! Market data service implementation
 
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use common::chaos::{Chaos, ChaosPoint, SharedChaos};
+use common::clock::{SharedClock, SystemClock};
 use common::decimal::{Price, Quantity};
-use common::error::Result;
-use common::model::trade::Trade;
+use common::error::{Error, Result};
+use common::model::order::Side;
+use common::model::trade::{Trade, TradeBust};
+use uuid::Uuid;
 use dashmap::DashMap;
+use rust_decimal::prelude::ToPrimitive;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
 
-use crate::channel::{MarketDataChannel, Topic};
+use crate::channel::{Backpressure, MarketDataChannel, Topic};
+use crate::feed::{BinaryFeed, FeedConfig};
+use crate::heatmap::{Heatmap, HeatmapConfig, HeatmapSample, MAX_HEATMAP_BUCKETS};
+use crate::memory::{self, MemoryBudget, MemoryEviction, MemoryUsage, StructureUsage};
+use crate::repository::{InMemoryMarketRepository, MarketRepository};
+use crate::retention::{self, CandleCompaction, CandleRetention, CompactionMetrics};
+use crate::shadow::{ExternalMarketFetcher, ShadowFeed, ShadowMarket, ShadowMarketStatus};
+use crate::sync::{Levels, MarketGaps, SyncSource, TradeSync};
+use crate::tape::TapeFilter;
+use crate::warmup::{self, MarketWarmup, WarmupConfig};
 use crate::models::{
-    MarketDepth, OrderBookUpdate, PriceLevel, TradeMessage, 
-    Ticker, MarketSummary, Candle, CandleInterval,
+    MarketDepth, OrderBookUpdate, BestBidOffer, PriceLevel, TradeMessage, 
+    Ticker, MarketSummary, Candle, CandleFill, CandleInterval, CandleUpdate, MarketAnalytics,
+    CorrectionKind, TradeCorrection,
 };
 
 /// Market data service for providing real-time market data
@@ -20,14 +39,50 @@ pub struct MarketDataService {
     channel: Arc<MarketDataChannel>,
     /// Latest market depths
     market_depths: DashMap<String, MarketDepth>,
+    /// Last order book sequence number by market
+    depth_sequences: Mutex<HashMap<String, u64>>,
+    /// Latest best bid and offer by market
+    bbos: DashMap<String, BestBidOffer>,
     /// Latest tickers
     tickers: DashMap<String, Ticker>,
     /// Market summaries
     _market_summaries: DashMap<String, MarketSummary>,
-    /// Recent trades by market
+    /// Recent trades by market, including ones below the displayed size
     recent_trades: DashMap<String, Vec<TradeMessage>>,
     /// Price candles by market and interval
     candles: DashMap<(String, CandleInterval), Vec<Candle>>,
+    /// Open time of the last candle announced as closed, by market and interval
+    closed_candles: DashMap<(String, CandleInterval), DateTime<Utc>>,
+    /// How long candles of each interval are kept
+    candle_retention: CandleRetention,
+    /// Candles purged and downsampled by compactions
+    compaction_metrics: std::sync::Mutex<CompactionMetrics>,
+    /// Storage for order book history
+    repository: Arc<dyn MarketRepository>,
+    /// Sequence of the last saved order book snapshot by market
+    snapshot_sequences: DashMap<String, u64>,
+    /// Source of trades and order books to repair gaps from
+    sync_source: Option<Arc<dyn SyncSource>>,
+    /// Engine numbered trades applied by market, locked while one is applied
+    trade_syncs: DashMap<String, Arc<Mutex<TradeSync>>>,
+    /// Last trade sequence of each market seen by the previous sync check
+    checked_sequences: DashMap<String, u64>,
+    /// Minimum trade sizes shown on public feeds
+    tape_filter: TapeFilter,
+    /// Read-only markets mirrored from an external exchange
+    shadow: Option<ShadowFeed>,
+    /// Time source stamping books and tickers and closing candles
+    clock: SharedClock,
+    /// Bounds on the trades, candles and books kept in memory
+    memory_budget: MemoryBudget,
+    /// When each market last traded or changed its book
+    last_active: DashMap<String, DateTime<Utc>>,
+    /// Open time of the newest candle spilled to the repository, by market and interval
+    spilled_candles: DashMap<(String, CandleInterval), DateTime<Utc>>,
+    /// Evictions since startup
+    memory_metrics: std::sync::Mutex<MemoryUsage>,
+    /// Faults injected into trade publication, for failure testing
+    chaos: SharedChaos,
 }
 
 impl MarketDataService {
@@ -36,13 +91,120 @@ impl MarketDataService {
         Self {
             channel: Arc::new(MarketDataChannel::new()),
             market_depths: DashMap::new(),
+            depth_sequences: Mutex::new(HashMap::new()),
+            bbos: DashMap::new(),
             tickers: DashMap::new(),
             _market_summaries: DashMap::new(),
             recent_trades: DashMap::new(),
             candles: DashMap::new(),
+            closed_candles: DashMap::new(),
+            candle_retention: CandleRetention::default(),
+            compaction_metrics: std::sync::Mutex::new(CompactionMetrics::default()),
+            repository: Arc::new(InMemoryMarketRepository::new()),
+            snapshot_sequences: DashMap::new(),
+            sync_source: None,
+            trade_syncs: DashMap::new(),
+            checked_sequences: DashMap::new(),
+            tape_filter: TapeFilter::default(),
+            shadow: None,
+            clock: SystemClock::shared(),
+            memory_budget: MemoryBudget::default(),
+            last_active: DashMap::new(),
+            spilled_candles: DashMap::new(),
+            memory_metrics: std::sync::Mutex::new(MemoryUsage::default()),
+            chaos: Chaos::shared(),
         }
     }
     
+    /// Store order book history in `repository` instead of memory
+    pub fn with_repository(mut self, repository: Arc<dyn MarketRepository>) -> Self {
+        self.repository = repository;
+        self
+    }
+    
+    /// Inject the faults of `chaos` into trade publication
+    ///
+    /// A trade failed this way is missed like one lost on its way from the
+    /// engine, and repaired by the next trade or sync check.
+    pub fn with_chaos(mut self, chaos: SharedChaos) -> Self {
+        self.chaos = chaos;
+        self
+    }
+    
+    /// Repair gaps in engine numbered trades from `source`
+    pub fn with_sync_source(mut self, source: Arc<dyn SyncSource>) -> Self {
+        self.sync_source = Some(source);
+        self
+    }
+    
+    /// Keep candles for `retention` instead of the default
+    pub fn with_candle_retention(mut self, retention: CandleRetention) -> Self {
+        self.candle_retention = retention;
+        self
+    }
+    
+    /// How long candles of each interval are kept
+    pub fn candle_retention(&self) -> &CandleRetention {
+        &self.candle_retention
+    }
+    
+    /// Bound the trades, candles and books kept in memory by `budget`
+    pub fn with_memory_budget(mut self, budget: MemoryBudget) -> Self {
+        self.memory_budget = budget;
+        self
+    }
+    
+    /// Bounds on the trades, candles and books kept in memory
+    pub fn memory_budget(&self) -> &MemoryBudget {
+        &self.memory_budget
+    }
+    
+    /// Keep trades below `filter`'s sizes off public feeds and tickers
+    pub fn with_tape_filter(mut self, filter: TapeFilter) -> Self {
+        self.tape_filter = filter;
+        self
+    }
+    
+    /// Minimum trade sizes shown on public feeds
+    pub fn tape_filter(&self) -> &TapeFilter {
+        &self.tape_filter
+    }
+    
+    /// Mirror `markets` from the external exchange `fetcher` reads
+    pub fn with_shadow_markets(mut self, fetcher: Arc<dyn ExternalMarketFetcher>, markets: Vec<ShadowMarket>) -> Self {
+        self.shadow = Some(ShadowFeed::new(fetcher, markets));
+        self
+    }
+    
+    /// Stamp books, tickers and channel subscriptions and close candles by
+    /// the given clock's time
+    ///
+    /// Replaces the channel, so must be called before anything subscribes.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.channel = Arc::new(MarketDataChannel::with_backpressure(self.channel.backpressure().clone()).with_clock(clock.clone()));
+        self.clock = clock;
+        self
+    }
+    
+    /// Get the clock stamping books and tickers and closing candles
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+    
+    /// Name of the storage backend for order book history
+    pub fn repository_name(&self) -> &str {
+        self.repository.name()
+    }
+    
+    /// Conflate or disconnect channel subscribers lagging past `backpressure`'s
+    /// queue depths
+    ///
+    /// Replaces the channel, so must be called before anything subscribes.
+    pub fn with_backpressure(mut self, backpressure: Backpressure) -> Self {
+        self.channel = Arc::new(MarketDataChannel::with_backpressure(backpressure).with_clock(self.clock.clone()));
+        self
+    }
+    
     /// Get the market data channel
     pub fn channel(&self) -> Arc<MarketDataChannel> {
         self.channel.clone()
@@ -50,7 +212,15 @@ impl MarketDataService {
     
     /// Update order book
     pub async fn update_order_book(&self, market: &str, bids: Vec<(Price, Quantity)>, asks: Vec<(Price, Quantity)>) -> Result<()> {
-        let timestamp = Utc::now();
+        let timestamp = self.clock.now();
+        
+        // Hold the sequence lock until the update is published so subscribers
+        // always see sequence numbers in order
+        let mut sequences = self.depth_sequences.lock().await;
+        self.last_active.insert(market.to_string(), timestamp);
+        let sequence = sequences.entry(market.to_string()).or_insert(0);
+        *sequence += 1;
+        let sequence = *sequence;
         
         // Convert to price levels
         let bids = bids.into_iter()
@@ -64,10 +234,26 @@ impl MarketDataService {
         let market_depth = MarketDepth {
             market: market.to_string(),
             timestamp,
+            sequence,
             bids,
             asks,
         };
         
+        // Publish the top of book ahead of the full depth, and only when it moved
+        let bbo = BestBidOffer {
+            market: market.to_string(),
+            bid_price: market_depth.bids.first().map(|level| level.price),
+            bid_size: market_depth.bids.first().map(|level| level.quantity),
+            ask_price: market_depth.asks.first().map(|level| level.price),
+            ask_size: market_depth.asks.first().map(|level| level.quantity),
+            sequence,
+            timestamp,
+        };
+        let previous = self.bbos.insert(market.to_string(), bbo.clone());
+        if previous.is_none_or(|previous| !previous.same_top(&bbo)) {
+            self.channel.publish(Topic::Bbo(market.to_string()), bbo).await;
+        }
+        
         // Store latest market depth
         self.market_depths.insert(market.to_string(), market_depth.clone());
         
@@ -75,12 +261,14 @@ impl MarketDataService {
         let update = OrderBookUpdate {
             market: market.to_string(),
             timestamp,
+            sequence,
             bids: market_depth.bids.clone(),
             asks: market_depth.asks.clone(),
         };
         
         // Publish update
         self.channel.publish(Topic::OrderBook(market.to_string()), update).await;
+        drop(sequences);
         
         // Update ticker
         self.update_ticker_from_order_book(market, &market_depth).await?;
@@ -89,26 +277,251 @@ impl MarketDataService {
     }
     
     /// Process a new trade
+    ///
+    /// Trades numbered by the engine are applied in order once each: a trade
+    /// arriving ahead of missing ones repairs the gap first.
     pub async fn process_trade(&self, trade: &Trade) -> Result<()> {
+        self.chaos.inject(ChaosPoint::Publication).await?;
+        if trade.sequence == 0 {
+            return self.apply_trade(trade).await;
+        }
+        
+        let sync = self.trade_sync(&trade.market);
+        let mut sync = sync.lock().await;
+        if !sync.accept(trade.sequence) {
+            debug!("Ignoring trade {} of {}, already applied", trade.sequence, trade.market);
+            return Ok(());
+        }
+        if trade.sequence > sync.applied + 1 {
+            self.repair_gap(&trade.market, &mut sync, trade.sequence - 1).await?;
+        }
+        
+        self.apply_trade(trade).await?;
+        sync.applied = sync.applied.max(trade.sequence);
+        Ok(())
+    }
+    
+    /// Continue a market's trades after the last one stored before a
+    /// restart, returning its sequence number for the engine to resume from
+    ///
+    /// Trades up to it count as applied, so the first new one is not taken
+    /// for a gap.
+    pub async fn resume_trade_sequence(&self, market: &str) -> Result<u64> {
+        let sequence = self.repository.get_last_trade_sequence(market).await?;
+        let sync = self.trade_sync(market);
+        let mut sync = sync.lock().await;
+        sync.applied = sync.applied.max(sequence);
+        Ok(sequence)
+    }
+    
+    /// Repair trades the engine executed before the previous check that
+    /// were never applied
+    pub async fn check_sync(&self) -> Result<()> {
+        let Some(source) = self.sync_source.clone() else {
+            return Ok(());
+        };
+        
+        for market in source.markets() {
+            let Some(latest) = source.last_trade_sequence(&market) else {
+                continue;
+            };
+            // Trades executed since the previous check may still be on their way
+            let previous = self.checked_sequences.insert(market.clone(), latest).unwrap_or(0);
+            let sync = self.trade_sync(&market);
+            let mut sync = sync.lock().await;
+            if previous > sync.applied {
+                self.repair_gap(&market, &mut sync, previous).await?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Check for gaps every `interval`
+    pub fn spawn_sync_check(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                if let Err(e) = self.check_sync().await {
+                    warn!("Failed to check market data for gaps: {}", e);
+                }
+            }
+        })
+    }
+    
+    /// Gaps detected in each market's trades, by market
+    pub async fn gap_report(&self) -> Vec<MarketGaps> {
+        let syncs: Vec<(String, Arc<Mutex<TradeSync>>)> = self.trade_syncs
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        let mut report = Vec::with_capacity(syncs.len());
+        for (market, sync) in syncs {
+            report.push(sync.lock().await.report(&market));
+        }
+        report.sort_by(|a, b| a.market.cmp(&b.market));
+        report
+    }
+    
+    /// Whether a market is a read-only mirror of an external one
+    pub fn is_shadow_market(&self, market: &str) -> bool {
+        self.shadow.as_ref().is_some_and(|shadow| shadow.markets.contains_key(market))
+    }
+    
+    /// Mirrored book of a shadow market, at most `limit` levels per side
+    pub fn shadow_depth(&self, market: &str, limit: usize) -> Option<(Levels, Levels)> {
+        if !self.is_shadow_market(market) {
+            return None;
+        }
+        let levels = |side: &[PriceLevel]| side.iter().take(limit).map(|level| (level.price, level.quantity)).collect();
+        Some(self.market_depths
+            .get(market)
+            .map(|depth| (levels(&depth.bids), levels(&depth.asks)))
+            .unwrap_or_default())
+    }
+    
+    /// Ingestion state of the shadow markets, by symbol
+    pub fn shadow_markets(&self) -> Vec<ShadowMarketStatus> {
+        let mut markets: Vec<ShadowMarketStatus> = self.shadow
+            .iter()
+            .flat_map(|shadow| shadow.markets.iter().map(|entry| entry.value().clone()))
+            .collect();
+        markets.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        markets
+    }
+    
+    /// Poll every shadow market once, applying its book and the trades not
+    /// ingested before
+    pub async fn ingest_shadow_markets(&self) -> Result<()> {
+        let Some(shadow) = &self.shadow else {
+            return Ok(());
+        };
+        
+        let markets: Vec<(String, String, Option<u64>)> = shadow.markets
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.external_symbol.clone(), entry.last_trade_id))
+            .collect();
+        for (market, external_symbol, last_trade_id) in markets {
+            let snapshot = match shadow.fetcher.fetch(&external_symbol).await {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    warn!("Failed to fetch {} from {}: {}", external_symbol, shadow.fetcher.name(), e);
+                    if let Some(mut status) = shadow.markets.get_mut(&market) {
+                        status.last_error = Some(e.to_string());
+                    }
+                    continue;
+                }
+            };
+            
+            let mut trades: Vec<_> = snapshot.trades
+                .into_iter()
+                .filter(|trade| last_trade_id.is_none_or(|last| trade.id > last))
+                .collect();
+            trades.sort_by_key(|trade| trade.id);
+            for external in &trades {
+                let mut trade = Trade::new(
+                    market.clone(),
+                    external.price,
+                    external.quantity,
+                    Uuid::nil(),
+                    Uuid::nil(),
+                    Uuid::nil(),
+                    Uuid::nil(),
+                    external.taker_side,
+                );
+                trade.created_at = external.executed_at;
+                self.apply_trade(&trade).await?;
+            }
+            self.update_order_book(&market, snapshot.bids, snapshot.asks).await?;
+            
+            if let Some(mut status) = shadow.markets.get_mut(&market) {
+                status.trades_ingested += trades.len() as u64;
+                status.last_trade_id = trades.last().map(|trade| trade.id).or(status.last_trade_id);
+                status.last_update_at = Some(self.clock.now());
+                status.last_error = None;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Poll the shadow markets every `interval`
+    pub fn spawn_shadow_ingestion(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                if let Err(e) = self.ingest_shadow_markets().await {
+                    warn!("Failed to ingest shadow markets: {}", e);
+                }
+            }
+        })
+    }
+    
+    fn trade_sync(&self, market: &str) -> Arc<Mutex<TradeSync>> {
+        self.trade_syncs.entry(market.to_string()).or_default().clone()
+    }
+    
+    /// Apply the trades missing through `through` from the sync source, then
+    /// re-sync the market's order book
+    async fn repair_gap(&self, market: &str, sync: &mut TradeSync, through: u64) -> Result<()> {
+        let missed = through - sync.applied;
+        let trades: Vec<Trade> = self.sync_source
+            .as_ref()
+            .map(|source| source.trades_after(market, sync.applied))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|trade| trade.sequence <= through)
+            .collect();
+        for trade in &trades {
+            self.apply_trade(trade).await?;
+        }
+        
+        let repaired: Vec<u64> = trades.iter().map(|trade| trade.sequence).collect();
+        warn!(
+            "Gap of {} trades in {} after trade {}, repaired {}",
+            missed, market, sync.applied, repaired.len()
+        );
+        sync.record_gap(through, &repaired, self.clock.now());
+        
+        if let Some((bids, asks)) = self.sync_source.as_ref().and_then(|source| source.depth(market)) {
+            self.update_order_book(market, bids, asks).await?;
+        }
+        Ok(())
+    }
+    
+    /// Store, publish and aggregate a trade
+    async fn apply_trade(&self, trade: &Trade) -> Result<()> {
         let market = &trade.market;
         
         // Convert to trade message
         let trade_message = TradeMessage::from(trade);
         
         // Store recent trade
+        self.last_active.insert(market.clone(), self.clock.now());
         let mut recent_trades = self.recent_trades
             .entry(market.clone())
-            .or_insert_with(|| Vec::with_capacity(100));
+            .or_default();
         
         recent_trades.push(trade_message.clone());
         
-        // Keep only last 100 trades
-        if recent_trades.len() > 100 {
-            recent_trades.remove(0);
+        // Keep only the budgeted number of trades
+        let excess = recent_trades.len().saturating_sub(self.memory_budget.recent_trades);
+        recent_trades.drain(..excess);
+        drop(recent_trades);
+        
+        // Keep the trade for bulk history downloads
+        if let Err(e) = self.repository.save_trade(&trade_message).await {
+            warn!("Failed to save trade {} to history: {}", trade_message.id, e);
         }
         
-        // Publish trade
-        self.channel.publish(Topic::Trades(market.clone()), trade_message).await;
+        // Publish the full tape, and the public feed and ticker without dust
+        self.channel.publish(Topic::RawTrades(market.clone()), trade_message.clone()).await;
+        if self.tape_filter.displays(&trade_message) {
+            self.channel.publish(Topic::Trades(market.clone()), trade_message).await;
+            self.update_ticker_from_trade(trade).await?;
+        }
         
         // Update candles
         self.update_candles(trade).await?;
@@ -132,14 +545,14 @@ impl MarketDataService {
                 low_24h: None,
                 volume_24h: None,
                 quote_volume_24h: None,
-                timestamp: Utc::now(),
+                timestamp: self.clock.now(),
             })
             .clone();
         
         // Update bid and ask
         ticker.bid = depth.bids.first().map(|level| level.price);
         ticker.ask = depth.asks.first().map(|level| level.price);
-        ticker.timestamp = Utc::now();
+        ticker.timestamp = self.clock.now();
         
         // Store updated ticker
         self.tickers.insert(market.to_string(), ticker.clone());
@@ -151,7 +564,7 @@ impl MarketDataService {
     }
     
     /// Update ticker from trade
-    async fn _update_ticker_from_trade(&self, trade: &Trade) -> Result<()> {
+    async fn update_ticker_from_trade(&self, trade: &Trade) -> Result<()> {
         let market = &trade.market;
         
         // Get existing ticker or create new one
@@ -168,7 +581,7 @@ impl MarketDataService {
                 low_24h: None,
                 volume_24h: None,
                 quote_volume_24h: None,
-                timestamp: Utc::now(),
+                timestamp: self.clock.now(),
             })
             .clone();
         
@@ -193,7 +606,7 @@ impl MarketDataService {
         }
         
         // Update timestamp
-        ticker.timestamp = Utc::now();
+        ticker.timestamp = self.clock.now();
         
         // Store updated ticker
         self.tickers.insert(market.clone(), ticker.clone());
@@ -206,8 +619,9 @@ impl MarketDataService {
     
     /// Update candles from trade
     async fn update_candles(&self, trade: &Trade) -> Result<()> {
-        // For MVP, just update 1 minute candles
-        self.update_candle_interval(trade, CandleInterval::Minute1).await?;
+        for interval in CandleInterval::ALL {
+            self.update_candle_interval(trade, interval).await?;
+        }
         
         Ok(())
     }
@@ -226,15 +640,25 @@ impl MarketDataService {
         let candle_end = chrono::DateTime::from_timestamp(candle_start_secs + interval_secs, 0)
             .unwrap_or(trade_time);
         
-        // Get candles for this market and interval
+        // Finish the previous candle before the trade opens a new one
         let key = (market.clone(), interval);
+        self.close_candle(&key, trade_time).await;
+        
+        // Get candles for this market and interval
         let mut candles = self.candles
             .entry(key.clone())  // Clone the key here
             .or_insert_with(Vec::new)
             .clone();
         
+        // Volume the taker bought, for order flow
+        let (taker_buy_volume, taker_buy_quote_volume) = if trade.taker_side == Side::Buy {
+            (trade.quantity, trade.price * trade.quantity)
+        } else {
+            (Quantity::ZERO, Quantity::ZERO)
+        };
+        
         // Check if current candle exists
-        if let Some(current_candle) = candles.iter_mut().find(|c| c.open_time == candle_start) {
+        let candle = if let Some(current_candle) = candles.iter_mut().find(|c| c.open_time == candle_start) {
             // Update existing candle
             current_candle.high = current_candle.high.max(trade.price);
             current_candle.low = current_candle.low.min(trade.price);
@@ -242,6 +666,9 @@ impl MarketDataService {
             current_candle.volume += trade.quantity;
             current_candle.quote_volume += trade.price * trade.quantity;
             current_candle.trades += 1;
+            current_candle.taker_buy_volume += taker_buy_volume;
+            current_candle.taker_buy_quote_volume += taker_buy_quote_volume;
+            current_candle.clone()
         } else {
             // Create new candle
             let new_candle = Candle {
@@ -256,31 +683,446 @@ impl MarketDataService {
                 volume: trade.quantity,
                 quote_volume: trade.price * trade.quantity,
                 trades: 1,
+                taker_buy_volume,
+                taker_buy_quote_volume,
             };
             
-            candles.push(new_candle);
+            candles.push(new_candle.clone());
             
-            // Sort candles by time
+            // Sort candles by time, compaction drops them past retention
             candles.sort_by(|a, b| a.open_time.cmp(&b.open_time));
+            new_candle
+        };
+        
+        // Store updated candles
+        self.candles.insert(key, candles);
+        
+        // Publish the working candle
+        let topic = Topic::Candles(market.clone(), interval);
+        self.channel.publish(topic, CandleUpdate { candle, closed: false }).await;
+        
+        Ok(())
+    }
+    
+    /// Publish the final update of every candle whose interval ended by `now`
+    pub async fn close_candles(&self, now: DateTime<Utc>) {
+        let keys: Vec<(String, CandleInterval)> = self.candles.iter().map(|entry| entry.key().clone()).collect();
+        for key in keys {
+            self.close_candle(&key, now).await;
+        }
+    }
+    
+    /// Close candles every second so subscribers learn a candle is final without waiting for the next trade
+    pub fn spawn_candle_closer(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(std::time::Duration::from_secs(1));
+            loop {
+                ticks.tick().await;
+                self.close_candles(self.clock.now()).await;
+            }
+        })
+    }
+    
+    /// Drop candles that ended before their interval's retention, rolling
+    /// them up into longer intervals missing their periods
+    pub fn compact_candles(&self, now: DateTime<Utc>) -> CandleCompaction {
+        let mut compaction = CandleCompaction::default();
+        
+        // Shortest intervals first, so candles they roll up into are compacted in turn
+        let mut keys: Vec<(String, CandleInterval)> = self.candles.iter().map(|entry| entry.key().clone()).collect();
+        keys.sort_by_key(|(market, interval)| (interval.duration_secs(), market.clone()));
+        
+        for key in keys {
+            let Some(kept) = self.candle_retention.retention(key.1) else {
+                continue;
+            };
+            let cutoff = now - kept;
+            let expired: Vec<Candle> = match self.candles.get_mut(&key) {
+                Some(mut candles) => {
+                    let end = candles.partition_point(|candle| candle.close_time <= cutoff);
+                    candles.drain(..end).collect()
+                }
+                None => continue,
+            };
+            if expired.is_empty() {
+                continue;
+            }
+            *compaction.purged.entry(key.1.code().to_string()).or_default() += expired.len() as u64;
             
-            // Keep only last 1000 candles
-            if candles.len() > 1000 {
-                let skip_count = candles.len().saturating_sub(1000);
-                candles = candles.iter().skip(skip_count).cloned().collect();
+            if let Some(target) = self.candle_retention.downsample_target(key.1) {
+                let mut candles = self.candles.entry((key.0.clone(), target)).or_default();
+                for candle in retention::downsample(target, &expired) {
+                    if let Err(at) = candles.binary_search_by_key(&candle.open_time, |existing| existing.open_time) {
+                        candles.insert(at, candle);
+                        compaction.downsampled += 1;
+                    }
+                }
             }
         }
         
-        // Store updated candles
-        self.candles.insert(key, candles);
+        let mut metrics = self.compaction_metrics.lock().unwrap();
+        metrics.runs += 1;
+        metrics.last_run = Some(now);
+        metrics.total.add(&compaction);
+        metrics.last = compaction.clone();
+        compaction
+    }
+    
+    /// Candles purged and downsampled by compactions since startup
+    pub fn compaction_metrics(&self) -> CompactionMetrics {
+        self.compaction_metrics.lock().unwrap().clone()
+    }
+    
+    /// Compact candles at the retention's compaction interval
+    pub fn spawn_candle_compaction(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(self.candle_retention.compaction_interval);
+            loop {
+                ticks.tick().await;
+                let compaction = self.compact_candles(self.clock.now());
+                if compaction.total_purged() > 0 {
+                    info!(
+                        "Compacted candles: purged {:?}, downsampled {}",
+                        compaction.purged, compaction.downsampled
+                    );
+                }
+            }
+        })
+    }
+    
+    /// Enforce the memory budget: evict markets idle past `idle_after` or
+    /// least recently active beyond the market cap, and spill the oldest
+    /// candles of series over their cap to the repository
+    ///
+    /// Only candles whose interval ended by `now` are spilled, so an evicted
+    /// market keeps its working candles.
+    pub async fn evict_memory(&self, now: DateTime<Utc>) -> MemoryEviction {
+        let mut eviction = MemoryEviction::default();
+        
+        // Least recently active first
+        let mut activity: Vec<(String, DateTime<Utc>)> = self.last_active
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect();
+        activity.sort_by_key(|(market, at)| (*at, market.clone()));
+        let idle = self.memory_budget.idle_after
+            .map_or(0, |idle_after| activity.iter().take_while(|(_, at)| now - *at > idle_after).count());
+        let over_cap = self.memory_budget.markets.map_or(0, |markets| activity.len().saturating_sub(markets));
+        
+        for (market, at) in activity.into_iter().take(idle.max(over_cap)) {
+            // Skip a market that became active since it was picked
+            if self.last_active.remove_if(&market, |_, active| *active == at).is_none() {
+                continue;
+            }
+            self.recent_trades.remove(&market);
+            self.market_depths.remove(&market);
+            self.bbos.remove(&market);
+            
+            let keys: Vec<(String, CandleInterval)> = self.candles
+                .iter()
+                .filter(|entry| entry.key().0 == market)
+                .map(|entry| entry.key().clone())
+                .collect();
+            for key in keys {
+                self.spill_candles(&key, usize::MAX, now, &mut eviction).await;
+                self.candles.remove_if(&key, |_, candles| candles.is_empty());
+            }
+            eviction.evicted_markets.push(market);
+        }
+        
+        if let Some(cap) = self.memory_budget.candles_per_series {
+            let over: Vec<((String, CandleInterval), usize)> = self.candles
+                .iter()
+                .filter(|entry| entry.value().len() > cap)
+                .map(|entry| (entry.key().clone(), entry.value().len() - cap))
+                .collect();
+            for (key, count) in over {
+                self.spill_candles(&key, count, now, &mut eviction).await;
+            }
+        }
+        
+        self.memory_metrics.lock().unwrap().add(&eviction, now);
+        eviction
+    }
+    
+    /// Save up to `count` of a series' oldest candles that ended by `now` to
+    /// the repository and drop them from memory
+    async fn spill_candles(&self, key: &(String, CandleInterval), count: usize, now: DateTime<Utc>, eviction: &mut MemoryEviction) {
+        let spilled: Vec<Candle> = match self.candles.get(key) {
+            Some(candles) => candles.iter().take(count).take_while(|candle| candle.close_time <= now).cloned().collect(),
+            None => return,
+        };
+        let Some(newest) = spilled.last().map(|candle| candle.open_time) else {
+            return;
+        };
+        
+        if let Err(e) = self.repository.save_candles(&spilled).await {
+            warn!("Failed to spill {} {} candles of {}: {}", spilled.len(), key.1.code(), key.0, e);
+            eviction.spill_failures += spilled.len() as u64;
+            return;
+        }
+        if let Some(mut candles) = self.candles.get_mut(key) {
+            candles.retain(|candle| candle.open_time > newest);
+        }
+        let mut spilled_through = self.spilled_candles.entry(key.clone()).or_insert(newest);
+        *spilled_through = (*spilled_through).max(newest);
+        eviction.spilled_candles += spilled.len() as u64;
+    }
+    
+    /// Estimated memory held by each structure, with eviction totals since startup
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut markets: HashSet<String> = HashSet::new();
+        
+        let mut recent_trades = StructureUsage { name: "recent_trades".to_string(), ..Default::default() };
+        for entry in self.recent_trades.iter() {
+            markets.insert(entry.key().clone());
+            recent_trades.markets += 1;
+            recent_trades.entries += entry.value().len();
+            recent_trades.bytes += entry.value().iter().map(memory::trade_bytes).sum::<usize>();
+        }
+        
+        let mut candles = StructureUsage { name: "candles".to_string(), ..Default::default() };
+        let mut candle_markets: HashSet<String> = HashSet::new();
+        for entry in self.candles.iter() {
+            candle_markets.insert(entry.key().0.clone());
+            candles.entries += entry.value().len();
+            candles.bytes += entry.value().iter().map(memory::candle_bytes).sum::<usize>();
+        }
+        candles.markets = candle_markets.len();
+        markets.extend(candle_markets);
+        
+        let mut depths = StructureUsage { name: "depths".to_string(), ..Default::default() };
+        for entry in self.market_depths.iter() {
+            markets.insert(entry.key().clone());
+            depths.markets += 1;
+            depths.entries += entry.value().bids.len() + entry.value().asks.len();
+            depths.bytes += memory::depth_bytes(entry.value());
+        }
+        
+        let bbos = StructureUsage {
+            name: "bbos".to_string(),
+            markets: self.bbos.len(),
+            entries: self.bbos.len(),
+            bytes: self.bbos.iter().map(|entry| memory::bbo_bytes(entry.value())).sum(),
+        };
+        let tickers = StructureUsage {
+            name: "tickers".to_string(),
+            markets: self.tickers.len(),
+            entries: self.tickers.len(),
+            bytes: self.tickers.iter().map(|entry| memory::ticker_bytes(entry.value())).sum(),
+        };
+        
+        let structures = vec![recent_trades, candles, depths, bbos, tickers];
+        MemoryUsage {
+            total_bytes: structures.iter().map(|structure| structure.bytes).sum(),
+            structures,
+            markets: markets.len(),
+            ..self.memory_metrics.lock().unwrap().clone()
+        }
+    }
+    
+    /// Enforce the memory budget at its eviction interval
+    pub fn spawn_memory_eviction(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(self.memory_budget.eviction_interval);
+            loop {
+                ticks.tick().await;
+                let eviction = self.evict_memory(self.clock.now()).await;
+                if !eviction.evicted_markets.is_empty() || eviction.spilled_candles > 0 {
+                    info!(
+                        "Evicted markets {:?} from memory, spilled {} candles",
+                        eviction.evicted_markets, eviction.spilled_candles
+                    );
+                }
+            }
+        })
+    }
+    
+    /// Restore the tickers, recent trades and candles of `markets` from the
+    /// trades kept by the repository, so they are served right after a restart
+    ///
+    /// Meant to run once at startup, before trades arrive. Nothing is published.
+    pub async fn warm_up(&self, markets: &[String], config: &WarmupConfig) -> Result<Vec<MarketWarmup>> {
+        let now = self.clock.now();
+        let from = config.load_from(now);
+        let mut warmed = Vec::with_capacity(markets.len());
+        
+        for market in markets {
+            let trades = self.repository.get_trades_between(market, from, now).await?;
+            let mut warmup = MarketWarmup { market: market.clone(), trades: trades.len(), ..MarketWarmup::default() };
+            
+            // Recent trades and the ticker's last price and range from the ticker window
+            let recent = &trades[trades.partition_point(|trade| trade.timestamp < now - config.window)..];
+            let kept = &recent[recent.len().saturating_sub(self.memory_budget.recent_trades)..];
+            if !kept.is_empty() {
+                self.recent_trades.insert(market.clone(), kept.to_vec());
+            }
+            let displayed: Vec<&TradeMessage> = recent.iter().filter(|trade| self.tape_filter.displays(trade)).collect();
+            let depth = self.repository.get_depth_snapshot_at(market, now).await?;
+            if !displayed.is_empty() || depth.is_some() {
+                self.tickers.insert(market.clone(), Ticker {
+                    market: market.clone(),
+                    bid: depth.as_ref().and_then(|depth| depth.bids.first()).map(|level| level.price),
+                    ask: depth.as_ref().and_then(|depth| depth.asks.first()).map(|level| level.price),
+                    last: displayed.last().map(|trade| trade.price),
+                    change_24h: None,
+                    change_24h_percent: None,
+                    high_24h: displayed.iter().map(|trade| trade.price).max(),
+                    low_24h: displayed.iter().map(|trade| trade.price).min(),
+                    volume_24h: None,
+                    quote_volume_24h: None,
+                    timestamp: displayed.last().map_or(now, |trade| trade.timestamp),
+                });
+                warmup.ticker = true;
+            }
+            
+            // Candles of every period since the oldest one still open
+            let minutes = warmup::minute_candles(&trades);
+            for interval in CandleInterval::ALL {
+                let key = (market.clone(), interval);
+                if let Some(spilled) = self.repository.get_candles_before(market, interval, from, 1).await?.first() {
+                    self.spilled_candles.insert(key.clone(), spilled.open_time);
+                }
+                
+                let candles = match interval {
+                    CandleInterval::Minute1 => minutes.clone(),
+                    _ => retention::downsample(interval, &minutes),
+                };
+                let candles: Vec<Candle> = candles.into_iter().filter(|candle| candle.open_time >= from).collect();
+                let Some(newest) = candles.last() else {
+                    continue;
+                };
+                // Candles that ended while the service was down are not announced
+                if newest.close_time <= now {
+                    self.closed_candles.insert(key.clone(), newest.open_time);
+                }
+                warmup.candles += candles.len();
+                self.candles.insert(key, candles);
+            }
+            
+            if let Some(last) = trades.last() {
+                self.last_active.insert(market.clone(), last.timestamp);
+            }
+            warmed.push(warmup);
+        }
+        
+        Ok(warmed)
+    }
+    
+    /// Save the depth of every market whose book changed since its last snapshot
+    pub async fn snapshot_order_books(&self) -> Result<()> {
+        let depths: Vec<MarketDepth> = self.market_depths.iter().map(|entry| entry.value().clone()).collect();
+        for depth in depths {
+            if self.snapshot_sequences.get(&depth.market).is_some_and(|sequence| *sequence == depth.sequence) {
+                continue;
+            }
+            
+            self.repository.save_depth_snapshot(&depth).await?;
+            self.snapshot_sequences.insert(depth.market.clone(), depth.sequence);
+        }
+        
+        Ok(())
+    }
+    
+    /// Snapshot order books every `interval` for later replay
+    pub fn spawn_order_book_snapshots(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                if let Err(e) = self.snapshot_order_books().await {
+                    warn!("Failed to save order book snapshots: {}", e);
+                }
+            }
+        })
+    }
+    
+    /// Save a heatmap sample of every market's book
+    pub async fn sample_heatmaps(&self, levels: usize) -> Result<()> {
+        let now = self.clock.now();
+        let depths: Vec<MarketDepth> = self.market_depths.iter().map(|entry| entry.value().clone()).collect();
+        for depth in depths {
+            self.repository.save_heatmap_sample(&HeatmapSample::from_depth(&depth, levels, now)).await?;
+        }
         
         Ok(())
     }
     
+    /// Sample order books for heatmaps at the configured interval
+    pub fn spawn_heatmap_sampling(self: Arc<Self>, config: HeatmapConfig) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(config.interval);
+            loop {
+                ticks.tick().await;
+                if let Err(e) = self.sample_heatmaps(config.levels).await {
+                    warn!("Failed to save heatmap samples: {}", e);
+                }
+            }
+        })
+    }
+    
+    /// Get a market's liquidity heatmap over `[from, to)` split into `buckets` price buckets
+    pub async fn get_heatmap(&self, market: &str, from: DateTime<Utc>, to: DateTime<Utc>, buckets: usize) -> Result<Heatmap> {
+        if buckets == 0 || buckets > MAX_HEATMAP_BUCKETS {
+            return Err(Error::ValidationError(format!("buckets must be between 1 and {}", MAX_HEATMAP_BUCKETS)));
+        }
+        let samples = self.repository.get_heatmap_samples(market, from, to).await?;
+        Ok(Heatmap::build(market, from, to, buckets, &samples))
+    }
+    
+    /// Publish order book changes and trades on the binary feed
+    pub async fn start_binary_feed(&self, config: FeedConfig) -> Result<BinaryFeed> {
+        BinaryFeed::start(self.channel(), config).await
+    }
+    
+    /// Get a market's trades executed at or after `from` and before `to`, oldest first
+    pub async fn get_trade_history(&self, market: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<TradeMessage>> {
+        self.repository.get_trades_between(market, from, to).await
+    }
+    
+    /// Get up to `limit` of a market's trades numbered after `sequence`, oldest
+    /// first, including ones below the displayed size, as far back as the
+    /// trade history is kept
+    pub async fn get_trades_after(&self, market: &str, sequence: u64, limit: usize) -> Result<Vec<TradeMessage>> {
+        self.repository.get_trades_after(market, sequence, limit).await
+    }
+    
+    /// Get the newest order book snapshot of a market taken at or before `at`
+    pub async fn get_order_book_at(&self, market: &str, at: DateTime<Utc>) -> Result<Option<MarketDepth>> {
+        self.repository.get_depth_snapshot_at(market, at).await
+    }
+    
+    /// Publish the final update of a market's newest candle if its interval ended by `now`
+    async fn close_candle(&self, key: &(String, CandleInterval), now: DateTime<Utc>) {
+        let Some(candle) = self.candles.get(key).and_then(|candles| candles.last().cloned()) else {
+            return;
+        };
+        if candle.close_time > now {
+            return;
+        }
+        
+        // Only the first caller to see the candle end announces it
+        let mut closed = self.closed_candles.entry(key.clone()).or_insert(DateTime::<Utc>::MIN_UTC);
+        if *closed >= candle.open_time {
+            return;
+        }
+        *closed = candle.open_time;
+        drop(closed);
+        
+        let topic = Topic::Candles(key.0.clone(), key.1);
+        self.channel.publish(topic, CandleUpdate { candle, closed: true }).await;
+    }
+    
     /// Get market depth
     pub fn get_market_depth(&self, market: &str) -> Option<MarketDepth> {
         self.market_depths.get(market).map(|d| d.clone())
     }
     
+    /// Get the latest best bid and offer
+    pub fn get_bbo(&self, market: &str) -> Option<BestBidOffer> {
+        self.bbos.get(market).map(|bbo| bbo.clone())
+    }
+    
     /// Get ticker
     pub fn get_ticker(&self, market: &str) -> Option<Ticker> {
         self.tickers.get(market).map(|t| t.clone())
@@ -291,8 +1133,38 @@ impl MarketDataService {
         self.tickers.iter().map(|t| t.clone()).collect()
     }
     
-    /// Get recent trades
+    /// Take a busted trade off the recent trades and publish its correction
+    ///
+    /// Tickers and candles the trade already went into are left as they are.
+    pub async fn bust_trade(&self, bust: &TradeBust) -> TradeCorrection {
+        if let Some(mut trades) = self.recent_trades.get_mut(&bust.market) {
+            trades.retain(|trade| trade.id != bust.trade_id);
+        }
+        
+        let correction = TradeCorrection {
+            trade_id: bust.trade_id,
+            market: bust.market.clone(),
+            price: bust.price,
+            quantity: bust.quantity,
+            kind: CorrectionKind::Bust,
+            reason: bust.reason.clone(),
+            timestamp: bust.busted_at,
+        };
+        info!("Busted trade {} taken off the {} tape", bust.trade_id, bust.market);
+        self.channel.publish(Topic::TradeCorrections(bust.market.clone()), correction.clone()).await;
+        correction
+    }
+    
+    /// Get recent trades shown on public feeds
     pub fn get_recent_trades(&self, market: &str, limit: usize) -> Vec<TradeMessage> {
+        let mut result = self.get_raw_trades(market, usize::MAX);
+        result.retain(|trade| self.tape_filter.displays(trade));
+        result.truncate(limit);
+        result
+    }
+    
+    /// Get recent trades, including ones below the displayed size
+    pub fn get_raw_trades(&self, market: &str, limit: usize) -> Vec<TradeMessage> {
         self.recent_trades
             .get(market)
             .map(|trades| {
@@ -316,4 +1188,131 @@ impl MarketDataService {
             })
             .unwrap_or_default()
     }
-}
\ No newline at end of file
+    
+    /// Get up to `limit` candles, newest first, reading candles spilled from
+    /// memory back from the repository
+    pub async fn get_candle_history(&self, market: &str, interval: CandleInterval, limit: usize) -> Result<Vec<Candle>> {
+        let mut candles = self.get_candles(market, interval, limit);
+        if candles.len() < limit && self.spilled_candles.contains_key(&(market.to_string(), interval)) {
+            let before = candles.last().map_or(DateTime::<Utc>::MAX_UTC, |candle| candle.open_time);
+            let older = self.repository.get_candles_before(market, interval, before, limit - candles.len()).await?;
+            candles.extend(older);
+        }
+        Ok(candles)
+    }
+    
+    /// Candles of the last `limit` periods up to the current one, newest first,
+    /// with periods without trades filled in
+    ///
+    /// Periods before the market's first candle are left out.
+    pub fn get_filled_candles(&self, market: &str, interval: CandleInterval, limit: usize, fill: CandleFill) -> Vec<Candle> {
+        let mut candles = self.candles
+            .get(&(market.to_string(), interval))
+            .map(|candles| candles.clone())
+            .unwrap_or_default();
+        candles.sort_by_key(|candle| candle.open_time);
+        let (Some(first), Some(last)) = (candles.first(), candles.last()) else {
+            return Vec::new();
+        };
+        if limit == 0 {
+            return Vec::new();
+        }
+
+        let step = chrono::Duration::seconds(interval.duration_secs());
+        let end = interval.open_time(self.clock.now()).max(last.open_time);
+        let periods = i32::try_from(limit - 1).unwrap_or(i32::MAX);
+        let start = end.checked_sub_signed(step * periods).unwrap_or(first.open_time).max(first.open_time);
+
+        let mut index = candles.partition_point(|candle| candle.open_time < start);
+        let mut previous_close = index.checked_sub(1).map(|before| candles[before].close);
+        let mut filled = Vec::new();
+        let mut open_time = start;
+        while open_time <= end {
+            match candles.get(index) {
+                Some(candle) if candle.open_time == open_time => {
+                    previous_close = Some(candle.close);
+                    filled.push(candle.clone());
+                    index += 1;
+                }
+                _ => {
+                    let price = match fill {
+                        CandleFill::Zero => Price::ZERO,
+                        CandleFill::Previous => previous_close.unwrap_or(Price::ZERO),
+                    };
+                    filled.push(Candle::empty(market, interval, open_time, price));
+                }
+            }
+            open_time += step;
+        }
+
+        filled.reverse();
+        filled
+    }
+    
+    /// Spread, depth within `depth_bps` of the mid and flow of the last `trades` trades
+    ///
+    /// Returns `None` for a market with neither an order book nor trades.
+    pub fn get_analytics(&self, market: &str, depth_bps: u32, trades: usize) -> Option<MarketAnalytics> {
+        let depth = self.get_market_depth(market);
+        let recent_trades = self.get_raw_trades(market, trades);
+        if depth.is_none() && recent_trades.is_empty() {
+            return None;
+        }
+        
+        let (bids, asks) = depth.as_ref()
+            .map(|depth| (depth.bids.as_slice(), depth.asks.as_slice()))
+            .unwrap_or_default();
+        let best_bid = bids.first().map(|level| level.price);
+        let best_ask = asks.first().map(|level| level.price);
+        let mid = best_bid.zip(best_ask).map(|(bid, ask)| (bid + ask) / Price::TWO);
+        let spread = best_bid.zip(best_ask).map(|(bid, ask)| ask - bid);
+        let spread_bps = spread.zip(mid)
+            .filter(|(_, mid)| !mid.is_zero())
+            .and_then(|(spread, mid)| (spread / mid * Price::from(10_000)).to_f64());
+        
+        // Depth band around the mid, unbounded when one side is empty
+        let band = mid.map(|mid| mid * Price::from(depth_bps) / Price::from(10_000));
+        let bid_depth: Quantity = bids.iter()
+            .filter(|level| mid.zip(band).is_none_or(|(mid, band)| level.price >= mid - band))
+            .map(|level| level.quantity)
+            .sum();
+        let ask_depth: Quantity = asks.iter()
+            .filter(|level| mid.zip(band).is_none_or(|(mid, band)| level.price <= mid + band))
+            .map(|level| level.quantity)
+            .sum();
+        
+        let (buy_volume, sell_volume) = recent_trades.iter()
+            .fold((Quantity::ZERO, Quantity::ZERO), |(buy, sell), trade| match trade.taker_side.as_str() {
+                "buy" => (buy + trade.quantity, sell),
+                _ => (buy, sell + trade.quantity),
+            });
+        
+        Some(MarketAnalytics {
+            market: market.to_string(),
+            sequence: depth.as_ref().map(|depth| depth.sequence).unwrap_or_default(),
+            timestamp: self.clock.now(),
+            best_bid,
+            best_ask,
+            mid,
+            spread,
+            spread_bps,
+            depth_bps,
+            bid_depth,
+            ask_depth,
+            book_imbalance: imbalance(bid_depth, ask_depth),
+            trade_count: recent_trades.len(),
+            buy_volume,
+            sell_volume,
+            trade_flow_imbalance: imbalance(buy_volume, sell_volume),
+        })
+    }
+}
+
+/// `(a - b) / (a + b)`, or `None` when both are zero
+fn imbalance(a: Quantity, b: Quantity) -> Option<f64> {
+    let total = a + b;
+    if total.is_zero() {
+        return None;
+    }
+    ((a - b) / total).to_f64()
+}
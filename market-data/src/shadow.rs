@@ -0,0 +1,120 @@
+//! Shadow markets
+//!
+//! A shadow market mirrors a market of an external exchange, so UIs and
+//! strategies can be built against realistic data without local order flow.
+//! An [`ExternalMarketFetcher`] is polled for each market's book and recent
+//! trades, which then take the same path as the engine's: tickers, candles,
+//! analytics and the WebSocket channels all cover shadow markets.
+//!
+//! Shadow markets are read-only. The matching engine does not know them, so
+//! orders for them are refused, and their trades settle no accounts. Trades
+//! are ingested once each in the order of their external IDs; a failed poll
+//! leaves the market as it was until the next one succeeds.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::decimal::{Price, Quantity};
+use common::error::Result;
+use common::model::order::Side;
+use dashmap::DashMap;
+use serde::Serialize;
+#[cfg(feature = "utoipa")]
+use utoipa::ToSchema;
+
+use crate::sync::Levels;
+
+/// Local market mirroring an external one
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowMarket {
+    /// Local market symbol, e.g. `BTC/USDT`
+    pub symbol: String,
+    /// Symbol the external exchange lists the market under, e.g. `BTCUSDT`
+    pub external_symbol: String,
+}
+
+/// Trade on an external exchange
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalTrade {
+    /// Exchange trade ID, increasing within a market
+    pub id: u64,
+    /// Execution price
+    pub price: Price,
+    /// Quantity traded
+    pub quantity: Quantity,
+    /// Side that was the taker
+    pub taker_side: Side,
+    /// Execution time
+    pub executed_at: DateTime<Utc>,
+}
+
+/// An external market's current book and recent trades
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExternalSnapshot {
+    /// Bid levels, best first
+    pub bids: Levels,
+    /// Ask levels, best first
+    pub asks: Levels,
+    /// Recent trades, in any order
+    pub trades: Vec<ExternalTrade>,
+}
+
+/// External exchange's public market data
+#[async_trait]
+pub trait ExternalMarketFetcher: Send + Sync {
+    /// Exchange name shown with its markets
+    fn name(&self) -> &str;
+
+    /// Current book and recent trades of a market, by its external symbol
+    async fn fetch(&self, external_symbol: &str) -> Result<ExternalSnapshot>;
+}
+
+/// Ingestion state of a shadow market
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct ShadowMarketStatus {
+    /// Local market symbol
+    pub symbol: String,
+    /// External market symbol
+    pub external_symbol: String,
+    /// Exchange the market is mirrored from
+    pub source: String,
+    /// Trades ingested since startup
+    pub trades_ingested: u64,
+    /// External ID of the newest trade ingested
+    pub last_trade_id: Option<u64>,
+    /// When the market was last polled successfully
+    pub last_update_at: Option<DateTime<Utc>>,
+    /// Error of the last poll, if it failed
+    pub last_error: Option<String>,
+}
+
+/// Shadow markets and the exchange they are mirrored from
+pub(crate) struct ShadowFeed {
+    pub(crate) fetcher: Arc<dyn ExternalMarketFetcher>,
+    /// Ingestion state by local symbol
+    pub(crate) markets: DashMap<String, ShadowMarketStatus>,
+}
+
+impl ShadowFeed {
+    pub(crate) fn new(fetcher: Arc<dyn ExternalMarketFetcher>, markets: Vec<ShadowMarket>) -> Self {
+        let source = fetcher.name().to_string();
+        let markets = markets
+            .into_iter()
+            .map(|market| {
+                let status = ShadowMarketStatus {
+                    symbol: market.symbol.clone(),
+                    external_symbol: market.external_symbol,
+                    source: source.clone(),
+                    trades_ingested: 0,
+                    last_trade_id: None,
+                    last_update_at: None,
+                    last_error: None,
+                };
+                (market.symbol, status)
+            })
+            .collect();
+        Self { fetcher, markets }
+    }
+}
@@ -0,0 +1,120 @@
+//! Gap detection and repair
+//!
+//! The matching engine numbers each market's trades. Market data applies
+//! them in that order: a trade arriving ahead of ones not yet seen is a gap,
+//! e.g. from a publish that failed after settlement. The missing trades are
+//! fetched from a [`SyncSource`] and applied first, and the order book is
+//! re-synced from it, so candles and tickers never skip or double count a
+//! trade. Trades arriving after they were repaired are ignored, while ones
+//! the source could not provide are still applied when they turn up late.
+//!
+//! A periodic check also compares each market with the engine's last trade,
+//! catching gaps no later trade reveals.
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use common::decimal::{Price, Quantity};
+use common::model::trade::Trade;
+use serde::Serialize;
+#[cfg(feature = "utoipa")]
+use utoipa::ToSchema;
+
+/// Price levels of a book side, best first
+pub type Levels = Vec<(Price, Quantity)>;
+
+/// Authoritative trades and order books to repair gaps from
+pub trait SyncSource: Send + Sync {
+    /// Markets trading
+    fn markets(&self) -> Vec<String>;
+
+    /// Sequence number of a market's last trade, 0 before the first
+    fn last_trade_sequence(&self, market: &str) -> Option<u64>;
+
+    /// A market's trades numbered after `sequence`, oldest first, as far
+    /// back as they are kept
+    fn trades_after(&self, market: &str, sequence: u64) -> Vec<Trade>;
+
+    /// A market's current bid and ask levels
+    fn depth(&self, market: &str) -> Option<(Levels, Levels)>;
+}
+
+/// Gaps detected in a market's trades
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct MarketGaps {
+    /// Market symbol
+    pub market: String,
+    /// Sequence number of the newest trade applied
+    pub last_sequence: u64,
+    /// Gaps detected
+    pub gaps: u64,
+    /// Trades missing from the gaps
+    pub missed_trades: u64,
+    /// Missing trades fetched from the source and applied
+    pub repaired_trades: u64,
+    /// Missing trades the source no longer had, applied when they arrived
+    pub late_trades: u64,
+    /// Missing trades neither repaired nor arrived yet
+    pub outstanding_trades: u64,
+    /// Trades ignored because they were already applied
+    pub duplicates: u64,
+    /// When the last gap was detected
+    pub last_gap_at: Option<DateTime<Utc>>,
+}
+
+/// Most missing sequence numbers remembered per market, oldest are forgotten first
+const MAX_OUTSTANDING: usize = 10_000;
+
+/// Trades applied to a market so far
+#[derive(Default)]
+pub(crate) struct TradeSync {
+    /// Newest sequence number applied
+    pub(crate) applied: u64,
+    /// Sequence numbers before `applied` neither repaired nor arrived
+    outstanding: BTreeSet<u64>,
+    /// Gap counters
+    gaps: MarketGaps,
+}
+
+impl TradeSync {
+    /// Whether a trade with this sequence number still has to be applied,
+    /// counting duplicates and late arrivals
+    pub(crate) fn accept(&mut self, sequence: u64) -> bool {
+        if sequence > self.applied {
+            true
+        } else if self.outstanding.remove(&sequence) {
+            self.gaps.late_trades += 1;
+            true
+        } else {
+            self.gaps.duplicates += 1;
+            false
+        }
+    }
+
+    /// Record a gap from `applied` through `through` found at `now`, of which
+    /// the trades with `repaired` sequence numbers, in order, were recovered
+    pub(crate) fn record_gap(&mut self, through: u64, repaired: &[u64], now: DateTime<Utc>) {
+        self.gaps.gaps += 1;
+        self.gaps.missed_trades += through - self.applied;
+        self.gaps.repaired_trades += repaired.len() as u64;
+        self.gaps.last_gap_at = Some(now);
+
+        let first = (self.applied + 1).max(through.saturating_sub(MAX_OUTSTANDING as u64) + 1);
+        self.outstanding.extend((first..=through).filter(|sequence| repaired.binary_search(sequence).is_err()));
+        while self.outstanding.len() > MAX_OUTSTANDING {
+            self.outstanding.pop_first();
+        }
+        self.applied = through;
+    }
+
+    /// Counters, as of now
+    pub(crate) fn report(&self, market: &str) -> MarketGaps {
+        MarketGaps {
+            market: market.to_string(),
+            last_sequence: self.applied,
+            outstanding_trades: self.outstanding.len() as u64,
+            ..self.gaps.clone()
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! Trade tape filtering
+//!
+//! A market can set a minimum displayed trade size. Smaller trades, such as
+//! dust fills left over by rounding, are kept off the public trade feed,
+//! recent trades and tickers so they do not clutter charts. They still count
+//! towards candles, analytics and the trade history, and every trade is
+//! published on [`Topic::RawTrades`](crate::channel::Topic::RawTrades) for
+//! consumers that need the full tape.
+
+use std::collections::BTreeMap;
+
+use common::decimal::Quantity;
+
+use crate::models::TradeMessage;
+
+/// Minimum displayed trade sizes
+#[derive(Debug, Clone, Default)]
+pub struct TapeFilter {
+    /// Smallest quantity shown on public feeds, by market; unlisted markets show every trade
+    pub min_trade_sizes: BTreeMap<String, Quantity>,
+}
+
+impl TapeFilter {
+    /// Smallest quantity of a market's trades shown on public feeds
+    pub fn min_trade_size(&self, market: &str) -> Quantity {
+        self.min_trade_sizes.get(market).copied().unwrap_or(Quantity::ZERO)
+    }
+
+    /// Whether a trade is shown on public feeds
+    pub fn displays(&self, trade: &TradeMessage) -> bool {
+        trade.quantity >= self.min_trade_size(&trade.market)
+    }
+}
@@ -0,0 +1,96 @@
+//! Cold-start warmup
+//!
+//! Tickers, recent trades and candles live in memory, so a restarted service
+//! has none until new trades arrive. A warmup rebuilds them from the trades
+//! the market repository kept: the last [`WarmupConfig::window`] of trades
+//! sets each ticker's last price and range and the recent trades, while
+//! every trade since the oldest candle still open rebuilds that candle and
+//! the candles after it. Candles spilled to the repository before then stay
+//! readable through candle history.
+
+use chrono::{DateTime, Duration, Utc};
+use common::decimal::Quantity;
+use serde::Serialize;
+#[cfg(feature = "utoipa")]
+use utoipa::ToSchema;
+
+use crate::models::{Candle, CandleInterval, TradeMessage};
+
+/// How much history a warmup loads
+#[derive(Debug, Clone)]
+pub struct WarmupConfig {
+    /// Trades setting tickers' last price and 24h range
+    pub window: Duration,
+}
+
+impl Default for WarmupConfig {
+    fn default() -> Self {
+        Self { window: Duration::hours(24) }
+    }
+}
+
+impl WarmupConfig {
+    /// Time of the oldest trade loaded at `now`: the start of the ticker
+    /// window or of the oldest candle open at `now`, whichever is earlier
+    pub fn load_from(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        CandleInterval::ALL
+            .iter()
+            .map(|interval| interval.open_time(now))
+            .fold(now - self.window, DateTime::min)
+    }
+}
+
+/// What a warmup loaded for one market
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct MarketWarmup {
+    /// Market symbol
+    pub market: String,
+    /// Trades read from the repository
+    pub trades: usize,
+    /// Candles rebuilt, across intervals
+    pub candles: usize,
+    /// Whether the market's ticker was restored
+    pub ticker: bool,
+}
+
+/// One-minute candles of `trades`, which must be oldest first
+pub(crate) fn minute_candles(trades: &[TradeMessage]) -> Vec<Candle> {
+    let mut candles: Vec<Candle> = Vec::new();
+    for trade in trades {
+        let open_time = CandleInterval::Minute1.open_time(trade.timestamp);
+        let (taker_buy_volume, taker_buy_quote_volume) = if trade.taker_side == "buy" {
+            (trade.quantity, trade.price * trade.quantity)
+        } else {
+            (Quantity::ZERO, Quantity::ZERO)
+        };
+        match candles.last_mut() {
+            Some(candle) if candle.open_time == open_time => {
+                candle.high = candle.high.max(trade.price);
+                candle.low = candle.low.min(trade.price);
+                candle.close = trade.price;
+                candle.volume += trade.quantity;
+                candle.quote_volume += trade.price * trade.quantity;
+                candle.trades += 1;
+                candle.taker_buy_volume += taker_buy_volume;
+                candle.taker_buy_quote_volume += taker_buy_quote_volume;
+            }
+            _ => candles.push(Candle {
+                market: trade.market.clone(),
+                interval: CandleInterval::Minute1,
+                open_time,
+                close_time: open_time + Duration::seconds(CandleInterval::Minute1.duration_secs()),
+                open: trade.price,
+                high: trade.price,
+                low: trade.price,
+                close: trade.price,
+                volume: trade.quantity,
+                quote_volume: trade.price * trade.quantity,
+                trades: 1,
+                taker_buy_volume,
+                taker_buy_quote_volume,
+            }),
+        }
+    }
+    candles
+}
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use chrono::{Duration, TimeZone, Utc};
+use common::clock::ManualClock;
+use market_data::channel::{Backpressure, MarketDataChannel, Topic};
+use market_data::MarketDataService;
+
+fn channel() -> MarketDataChannel {
+    MarketDataChannel::with_backpressure(Backpressure { conflate_at: 2, disconnect_at: 4 })
+}
+
+#[tokio::test]
+async fn test_lagging_ticker_subscriber_is_conflated_until_it_catches_up() {
+    let channel = channel();
+    let receiver = channel.subscribe::<u32>(Topic::Ticker("BTC/USD".to_string())).await;
+    for update in 0..5u32 {
+        channel.publish(Topic::Ticker("BTC/USD".to_string()), update).await;
+    }
+
+    let metrics = channel.metrics().await;
+    let subscriber = &metrics.subscribers[0];
+    assert_eq!((subscriber.queue_depth, subscriber.delivered, subscriber.dropped), (2, 2, 3));
+    assert!(subscriber.conflating);
+    assert_eq!(metrics.dropped, 3);
+
+    // Once drained, the subscriber gets updates again
+    assert_eq!(receiver.try_iter().count(), 2);
+    channel.publish(Topic::Ticker("BTC/USD".to_string()), 5u32).await;
+    let message = receiver.try_recv().unwrap();
+    assert_eq!(message.downcast_ref::<u32>(), Some(&5));
+    let subscriber = &channel.metrics().await.subscribers[0];
+    assert!(!subscriber.conflating);
+    assert_eq!(subscriber.max_queue_depth, 2);
+}
+
+#[tokio::test]
+async fn test_lagging_trade_subscriber_is_disconnected() {
+    let channel = channel();
+    let slow = channel.subscribe::<u32>(Topic::Trades("BTC/USD".to_string())).await;
+    let fast = channel.subscribe::<u32>(Topic::Trades("BTC/USD".to_string())).await;
+    for trade in 0..6u32 {
+        channel.publish(Topic::Trades("BTC/USD".to_string()), trade).await;
+        fast.try_recv().unwrap();
+    }
+
+    // Trades are never skipped: the slow subscriber keeps what it was sent, then is cut off
+    assert_eq!(slow.try_iter().count(), 4);
+    assert!(slow.recv().is_err());
+    let metrics = channel.metrics().await;
+    assert_eq!(metrics.subscribers.len(), 1);
+    assert_eq!(metrics.subscribers[0].delivered, 6);
+    assert_eq!((metrics.disconnected, metrics.dropped), (1, 0));
+}
+
+#[tokio::test]
+async fn test_metrics_list_the_most_lagging_subscriber_first() {
+    let channel = MarketDataChannel::new();
+    let _quiet = channel.subscribe::<u32>(Topic::OrderBook("ETH/USD".to_string())).await;
+    let _busy = channel.subscribe::<u32>(Topic::AllOrderBooks).await;
+    for update in 0..3u32 {
+        channel.publish(Topic::OrderBook("BTC/USD".to_string()), update).await;
+    }
+
+    let metrics = channel.metrics().await;
+    let topics: Vec<_> = metrics.subscribers.iter().map(|subscriber| subscriber.topic.as_str()).collect();
+    assert_eq!(topics, ["orderbook:*", "orderbook:ETH/USD"]);
+    assert_eq!(metrics.subscribers[0].queue_depth, 3);
+}
+
+#[tokio::test]
+async fn test_subscriptions_are_stamped_by_the_service_clock() {
+    let start = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+    let clock = Arc::new(ManualClock::new(start));
+    // The clock carries over whichever order the channel options are set in
+    let service = MarketDataService::new()
+        .with_clock(clock.clone())
+        .with_backpressure(Backpressure { conflate_at: 2, disconnect_at: 4 });
+    let channel = service.channel();
+
+    let _first = channel.subscribe::<u32>(Topic::Ticker("BTC/USD".to_string())).await;
+    clock.advance(Duration::minutes(5));
+    let _second = channel.subscribe::<u32>(Topic::Trades("BTC/USD".to_string())).await;
+
+    let mut stamps: Vec<_> = channel.metrics().await.subscribers.iter().map(|subscriber| subscriber.subscribed_at).collect();
+    stamps.sort();
+    assert_eq!(stamps, [start, start + Duration::minutes(5)]);
+}
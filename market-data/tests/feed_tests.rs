@@ -0,0 +1,209 @@
+use std::net::SocketAddr;
+
+use chrono::{TimeZone, Utc};
+use common::decimal::{Price, Quantity};
+use common::model::order::Side;
+use common::model::trade::Trade;
+use market_data::feed::codec::{DepthDelta, FeedMessage, TradeTick};
+use market_data::feed::FeedConfig;
+use market_data::MarketDataService;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{sleep, timeout, Duration};
+use uuid::Uuid;
+
+const MARKET: &str = "BTC/USD";
+
+/// Read one frame from a feed connection
+async fn read_frame(stream: &mut TcpStream) -> (u64, FeedMessage) {
+    let mut length = [0u8; 2];
+    timeout(Duration::from_secs(5), stream.read_exact(&mut length)).await.expect("frame").unwrap();
+    let mut frame = length.to_vec();
+    frame.resize(2 + u16::from_le_bytes(length) as usize, 0);
+    stream.read_exact(&mut frame[2..]).await.unwrap();
+    FeedMessage::decode(&frame).unwrap()
+}
+
+fn delta(message: FeedMessage) -> DepthDelta {
+    match message {
+        FeedMessage::DepthDelta(delta) => delta,
+        other => panic!("expected a depth delta, got {:?}", other),
+    }
+}
+
+fn trade() -> Trade {
+    Trade::new(
+        MARKET.to_string(),
+        Price::new(10000, 0),
+        Quantity::new(5, 1),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Side::Sell,
+    )
+}
+
+#[test]
+fn test_messages_round_trip() {
+    let timestamp = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+    let messages = vec![
+        FeedMessage::DepthDelta(DepthDelta {
+            market: MARKET.to_string(),
+            book_sequence: 42,
+            timestamp,
+            side: Side::Sell,
+            price: Price::new(1010050, 2),
+            quantity: Quantity::ZERO,
+            last: true,
+        }),
+        FeedMessage::Trade(TradeTick {
+            market: MARKET.to_string(),
+            id: Uuid::new_v4(),
+            timestamp,
+            taker_side: Side::Buy,
+            price: Price::new(10000, 0),
+            quantity: Quantity::new(125, 3),
+        }),
+        FeedMessage::GapFillRequest { from: 7, count: 3 },
+        FeedMessage::GapFillReject { from: 1, first_available: 5 },
+    ];
+
+    for (sequence, message) in messages.into_iter().enumerate() {
+        let frame = message.encode(sequence as u64).unwrap();
+        assert_eq!(u16::from_le_bytes([frame[0], frame[1]]) as usize, frame.len() - 2);
+        assert_eq!(FeedMessage::decode(&frame).unwrap(), (sequence as u64, message));
+    }
+
+    // Layouts are fixed, whatever the values
+    let small = FeedMessage::GapFillRequest { from: 0, count: 0 }.encode(0).unwrap();
+    let large = FeedMessage::GapFillRequest { from: u64::MAX, count: u32::MAX }.encode(u64::MAX).unwrap();
+    assert_eq!(small.len(), large.len());
+}
+
+#[test]
+fn test_invalid_frames_are_rejected() {
+    let frame = FeedMessage::GapFillRequest { from: 1, count: 1 }.encode(0).unwrap();
+    assert!(FeedMessage::decode(&frame[..frame.len() - 1]).is_err());
+
+    let mut unknown = frame.clone();
+    unknown[2] = 99;
+    assert!(FeedMessage::decode(&unknown).is_err());
+
+    let mut future = frame;
+    future[4] = 2;
+    assert!(FeedMessage::decode(&future).is_err());
+
+    let long_market = FeedMessage::Trade(TradeTick {
+        market: "A-VERY-LONG-MARKET/USD".to_string(),
+        id: Uuid::nil(),
+        timestamp: Utc::now(),
+        taker_side: Side::Buy,
+        price: Price::ONE,
+        quantity: Quantity::ONE,
+    });
+    assert!(long_market.encode(1).is_err());
+}
+
+#[tokio::test]
+async fn test_feed_publishes_deltas_and_trades() {
+    let service = MarketDataService::new();
+    let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let config = FeedConfig {
+        tcp_addr: Some("127.0.0.1:0".parse::<SocketAddr>().unwrap()),
+        udp_target: Some(udp.local_addr().unwrap()),
+        retained: 100,
+    };
+    let feed = service.start_binary_feed(config).await.unwrap();
+    let mut client = TcpStream::connect(feed.tcp_addr().unwrap()).await.unwrap();
+    sleep(Duration::from_millis(100)).await;
+
+    service.update_order_book(
+        MARKET,
+        vec![(Price::new(9900, 0), Quantity::ONE), (Price::new(9800, 0), Quantity::TWO)],
+        vec![(Price::new(10100, 0), Quantity::ONE)],
+    ).await.unwrap();
+
+    // The first update is the whole book, best prices first
+    let mut first = Vec::new();
+    for _ in 0..3 {
+        let (sequence, message) = read_frame(&mut client).await;
+        first.push((sequence, delta(message)));
+    }
+    let sequences: Vec<u64> = first.iter().map(|(sequence, _)| *sequence).collect();
+    assert_eq!(sequences, [1, 2, 3]);
+    let levels: Vec<(Side, Price)> = first.iter().map(|(_, delta)| (delta.side, delta.price)).collect();
+    assert_eq!(levels, [(Side::Buy, Price::new(9900, 0)), (Side::Buy, Price::new(9800, 0)), (Side::Sell, Price::new(10100, 0))]);
+    assert!(first.iter().all(|(_, delta)| delta.book_sequence == 1 && delta.market == MARKET));
+    assert_eq!(first.iter().filter(|(_, delta)| delta.last).count(), 1);
+    assert!(first[2].1.last);
+
+    // Later updates only carry the levels that changed
+    service.update_order_book(
+        MARKET,
+        vec![(Price::new(9900, 0), Quantity::ONE)],
+        vec![(Price::new(10100, 0), Quantity::new(3, 0))],
+    ).await.unwrap();
+    let (sequence, removed) = read_frame(&mut client).await;
+    let removed = delta(removed);
+    assert_eq!(sequence, 4);
+    assert_eq!((removed.side, removed.price, removed.quantity), (Side::Buy, Price::new(9800, 0), Quantity::ZERO));
+    assert!(!removed.last);
+    let (_, changed) = read_frame(&mut client).await;
+    let changed = delta(changed);
+    assert_eq!((changed.side, changed.quantity, changed.book_sequence), (Side::Sell, Quantity::new(3, 0), 2));
+    assert!(changed.last);
+
+    let traded = trade();
+    service.process_trade(&traded).await.unwrap();
+    let (sequence, message) = read_frame(&mut client).await;
+    assert_eq!(sequence, 6);
+    match message {
+        FeedMessage::Trade(tick) => {
+            assert_eq!(tick.id, traded.id);
+            assert_eq!(tick.taker_side, Side::Sell);
+            assert_eq!(tick.quantity, Quantity::new(5, 1));
+        }
+        other => panic!("expected a trade, got {:?}", other),
+    }
+    assert_eq!(feed.last_sequence(), 6);
+
+    // Datagrams carry the same frames
+    let mut buffer = [0u8; 512];
+    for expected in 1..=6 {
+        let len = timeout(Duration::from_secs(5), udp.recv(&mut buffer)).await.expect("datagram").unwrap();
+        let (sequence, _) = FeedMessage::decode(&buffer[..len]).unwrap();
+        assert_eq!(sequence, expected);
+    }
+}
+
+#[tokio::test]
+async fn test_gap_fills_resend_retained_frames() {
+    let service = MarketDataService::new();
+    let config = FeedConfig {
+        tcp_addr: Some("127.0.0.1:0".parse::<SocketAddr>().unwrap()),
+        udp_target: None,
+        retained: 3,
+    };
+    let feed = service.start_binary_feed(config).await.unwrap();
+
+    for price in 1..=5 {
+        service.update_order_book(MARKET, vec![(Price::new(price, 0), Quantity::ONE)], vec![]).await.unwrap();
+    }
+    // Each update replaces the only bid, one delta for the new level and one for the old
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(feed.last_sequence(), 9);
+
+    let mut client = TcpStream::connect(feed.tcp_addr().unwrap()).await.unwrap();
+    let request = FeedMessage::GapFillRequest { from: 8, count: 10 }.encode(0).unwrap();
+    client.write_all(&request).await.unwrap();
+    let (first, _) = read_frame(&mut client).await;
+    let (second, _) = read_frame(&mut client).await;
+    assert_eq!((first, second), (8, 9));
+
+    let request = FeedMessage::GapFillRequest { from: 2, count: 1 }.encode(0).unwrap();
+    client.write_all(&request).await.unwrap();
+    let (sequence, reply) = read_frame(&mut client).await;
+    assert_eq!(sequence, 0);
+    assert_eq!(reply, FeedMessage::GapFillReject { from: 2, first_available: 7 });
+}
@@ -0,0 +1,105 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use common::decimal::{Price, Quantity};
+use common::model::order::Side;
+use common::model::trade::Trade;
+use market_data::memory::MemoryBudget;
+use market_data::{CandleInterval, MarketDataService};
+use uuid::Uuid;
+
+async fn trade(service: &MarketDataService, market: &str, at: DateTime<Utc>) {
+    let mut trade = Trade::new(
+        market.to_string(),
+        Price::new(100, 0),
+        Quantity::ONE,
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Side::Buy,
+    );
+    trade.created_at = at;
+    service.process_trade(&trade).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_recent_trades_are_capped_per_market() {
+    let service = MarketDataService::new().with_memory_budget(MemoryBudget { recent_trades: 3, ..MemoryBudget::default() });
+    for _ in 0..5 {
+        trade(&service, "BTC/USD", Utc::now()).await;
+    }
+
+    assert_eq!(service.get_recent_trades("BTC/USD", 100).len(), 3);
+    let usage = service.memory_usage();
+    let recent = usage.structures.iter().find(|structure| structure.name == "recent_trades").unwrap();
+    assert_eq!((recent.markets, recent.entries), (1, 3));
+    assert!(recent.bytes > 0);
+    assert_eq!(usage.markets, 1);
+}
+
+#[tokio::test]
+async fn test_old_candles_spill_to_the_repository_and_are_read_back() {
+    let service = MarketDataService::new()
+        .with_memory_budget(MemoryBudget { candles_per_series: Some(2), ..MemoryBudget::default() });
+    let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+    for minute in 0..5 {
+        trade(&service, "BTC/USD", start + Duration::minutes(minute)).await;
+    }
+
+    let eviction = service.evict_memory(start + Duration::minutes(10)).await;
+    assert!(eviction.evicted_markets.is_empty());
+    // Three of the five minute candles are over the cap
+    assert!(eviction.spilled_candles >= 3);
+    assert_eq!(service.get_candles("BTC/USD", CandleInterval::Minute1, 100).len(), 2);
+
+    let history = service.get_candle_history("BTC/USD", CandleInterval::Minute1, 100).await.unwrap();
+    let opens: Vec<_> = history.iter().map(|candle| candle.open_time).collect();
+    let expected: Vec<_> = (0..5).rev().map(|minute| start + Duration::minutes(minute)).collect();
+    assert_eq!(opens, expected);
+
+    let usage = service.memory_usage();
+    assert_eq!(usage.runs, 1);
+    assert_eq!(usage.spilled_candles, eviction.spilled_candles);
+}
+
+#[tokio::test]
+async fn test_candles_of_open_periods_are_not_spilled() {
+    let service = MarketDataService::new()
+        .with_memory_budget(MemoryBudget { candles_per_series: Some(1), ..MemoryBudget::default() });
+    let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+    trade(&service, "BTC/USD", start).await;
+    trade(&service, "BTC/USD", start + Duration::minutes(1)).await;
+
+    // The daily candle is still open, so only closed minute candles spill
+    service.evict_memory(start + Duration::minutes(5)).await;
+    assert_eq!(service.get_candles("BTC/USD", CandleInterval::Minute1, 100).len(), 1);
+    assert_eq!(service.get_candles("BTC/USD", CandleInterval::Day1, 100).len(), 1);
+}
+
+#[tokio::test]
+async fn test_idle_and_least_recently_active_markets_are_evicted() {
+    let service = MarketDataService::new().with_memory_budget(MemoryBudget {
+        markets: Some(2),
+        idle_after: Some(Duration::hours(1)),
+        ..MemoryBudget::default()
+    });
+    for market in ["BTC/USD", "ETH/USD", "SOL/USD"] {
+        trade(&service, market, Utc::now()).await;
+        service.update_order_book(market, vec![(Price::new(99, 0), Quantity::ONE)], vec![]).await.unwrap();
+    }
+
+    // BTC/USD was active least recently and is over the cap
+    let eviction = service.evict_memory(Utc::now()).await;
+    assert_eq!(eviction.evicted_markets, vec!["BTC/USD".to_string()]);
+    assert!(service.get_recent_trades("BTC/USD", 100).is_empty());
+    assert_eq!(service.get_recent_trades("ETH/USD", 100).len(), 1);
+    // Candles of periods still open stay in memory
+    let history = service.get_candle_history("BTC/USD", CandleInterval::Minute1, 100).await.unwrap();
+    assert_eq!(history.len(), 1);
+
+    // Everything left is idle an hour later
+    let eviction = service.evict_memory(Utc::now() + Duration::hours(2)).await;
+    assert_eq!(eviction.evicted_markets.len(), 2);
+    let usage = service.memory_usage();
+    assert_eq!(usage.evicted_markets, 3);
+    assert_eq!(usage.runs, 2);
+}
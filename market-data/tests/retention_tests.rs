@@ -0,0 +1,119 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use common::decimal::{Price, Quantity};
+use common::model::order::Side;
+use common::model::trade::Trade;
+use market_data::retention::{self, CandleRetention};
+use market_data::{CandleInterval, MarketDataService};
+use uuid::Uuid;
+
+const MARKET: &str = "BTC/USD";
+
+fn retention() -> CandleRetention {
+    CandleRetention {
+        minute: Some(Duration::days(1)),
+        hour: Some(Duration::days(2)),
+        ..CandleRetention::default()
+    }
+}
+
+async fn trade(service: &MarketDataService, at: DateTime<Utc>, price: i64) {
+    let mut trade = Trade::new(
+        MARKET.to_string(),
+        Price::new(price, 0),
+        Quantity::ONE,
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Side::Buy,
+    );
+    trade.created_at = at;
+    service.process_trade(&trade).await.unwrap();
+}
+
+#[test]
+fn test_retention_and_downsample_targets_by_interval() {
+    let retention = retention();
+    assert_eq!(retention.retention(CandleInterval::Minute15), Some(Duration::days(1)));
+    assert_eq!(retention.retention(CandleInterval::Hour4), Some(Duration::days(2)));
+    assert_eq!(retention.retention(CandleInterval::Week1), None);
+
+    assert_eq!(retention.downsample_target(CandleInterval::Minute1), Some(CandleInterval::Hour1));
+    assert_eq!(retention.downsample_target(CandleInterval::Hour12), Some(CandleInterval::Day1));
+    assert_eq!(retention.downsample_target(CandleInterval::Day1), None);
+    assert_eq!(CandleRetention::forever().downsample_target(CandleInterval::Minute1), None);
+}
+
+#[tokio::test]
+async fn test_compaction_purges_candles_past_retention() {
+    let service = MarketDataService::new().with_candle_retention(retention());
+    let now = Utc::now();
+    let times = [now - Duration::days(3), now - Duration::hours(36), now - Duration::hours(1)];
+    for (time, price) in times.into_iter().zip([100, 110, 120]) {
+        trade(&service, time, price).await;
+    }
+
+    let compaction = service.compact_candles(now);
+    for code in ["1m", "5m", "15m", "30m"] {
+        assert_eq!(compaction.purged[code], 2, "{}", code);
+    }
+    for code in ["1h", "4h", "12h"] {
+        assert_eq!(compaction.purged[code], 1, "{}", code);
+    }
+    assert!(!compaction.purged.contains_key("1d"));
+    // Longer intervals already had candles of every purged period
+    assert_eq!(compaction.downsampled, 0);
+
+    assert_eq!(service.get_candles(MARKET, CandleInterval::Minute1, 100).len(), 1);
+    assert_eq!(service.get_candles(MARKET, CandleInterval::Hour1, 100).len(), 2);
+    // Daily candles are kept forever
+    let mut days: Vec<_> = times.iter().map(|time| CandleInterval::Day1.open_time(*time)).collect();
+    days.dedup();
+    assert_eq!(service.get_candles(MARKET, CandleInterval::Day1, 100).len(), days.len());
+
+    // Nothing more expires until time passes
+    assert_eq!(service.compact_candles(now).total_purged(), 0);
+    let metrics = service.compaction_metrics();
+    assert_eq!(metrics.runs, 2);
+    assert_eq!(metrics.last_run, Some(now));
+    assert_eq!(metrics.total.total_purged(), compaction.total_purged());
+    assert_eq!(metrics.last.total_purged(), 0);
+}
+
+#[test]
+fn test_downsampled_candles_aggregate_their_period() {
+    let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+    let candles: Vec<_> = [(0, 100, 105, 99, 104), (1, 104, 110, 103, 108), (75, 90, 91, 89, 90)]
+        .into_iter()
+        .map(|(minutes, open, high, low, close)| market_data::Candle {
+            market: MARKET.to_string(),
+            interval: CandleInterval::Minute1,
+            open_time: start + Duration::minutes(minutes),
+            close_time: start + Duration::minutes(minutes + 1),
+            open: Price::new(open, 0),
+            high: Price::new(high, 0),
+            low: Price::new(low, 0),
+            close: Price::new(close, 0),
+            volume: Quantity::ONE,
+            quote_volume: Price::new(close, 0),
+            trades: 2,
+            taker_buy_volume: Quantity::new(4, 1),
+            taker_buy_quote_volume: Price::new(close, 0) * Quantity::new(4, 1),
+        })
+        .collect();
+
+    let hourly = retention::downsample(CandleInterval::Hour1, &candles);
+    assert_eq!(hourly.len(), 2);
+    assert_eq!(hourly[0].interval, CandleInterval::Hour1);
+    assert_eq!((hourly[0].open_time, hourly[0].close_time), (start, start + Duration::hours(1)));
+    assert_eq!(
+        (hourly[0].open, hourly[0].high, hourly[0].low, hourly[0].close),
+        (Price::new(100, 0), Price::new(110, 0), Price::new(99, 0), Price::new(108, 0))
+    );
+    assert_eq!((hourly[0].volume, hourly[0].trades), (Quantity::TWO, 4));
+    assert_eq!(
+        (hourly[0].taker_buy_volume, hourly[0].taker_buy_quote_volume),
+        (Quantity::new(8, 1), Price::new(848, 1))
+    );
+    assert_eq!(hourly[1].open_time, start + Duration::hours(1));
+}
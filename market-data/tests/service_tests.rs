@@ -1,8 +1,16 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use chrono::{TimeZone, Utc};
+use common::clock::{Clock, ManualClock};
 use common::decimal::{Price, Quantity};
 use common::model::order::Side;
 use common::model::trade::Trade;
+use crossbeam_channel::Receiver;
 use market_data::channel::Topic;
-use market_data::{CandleInterval, TradeMessage, MarketDataService, OrderBookUpdate};
+use market_data::repository::InMemoryMarketRepository;
+use market_data::tape::TapeFilter;
+use market_data::{BestBidOffer, CandleFill, CandleInterval, CandleUpdate, TradeMessage, MarketDataService, OrderBookUpdate};
 use tokio::time::{sleep, Duration};
 use uuid::Uuid;
 
@@ -145,6 +153,8 @@ async fn test_multiple_trades_same_candle() {
     assert_eq!(candle.close, Price::new(190, 0)); // Last trade price
     assert_eq!(candle.volume, Quantity::new(18, 0)); // Sum of all quantities
     assert_eq!(candle.trades, 3); // Three trades
+    assert_eq!(candle.taker_buy_volume, Quantity::new(15, 0)); // The two taker buys
+    assert_eq!(candle.taker_buy_quote_volume, Quantity::new(3050, 0));
 }
 
 #[tokio::test]
@@ -180,7 +190,6 @@ async fn test_get_all_tickers() {
 }
 
 #[tokio::test]
-#[ignore = "Channel subscription test occasionally fails due to timing issues"]
 async fn test_channel_subscription() {
     let service = MarketDataService::new();
     let channel = service.channel();
@@ -211,7 +220,6 @@ async fn test_channel_subscription() {
 }
 
 #[tokio::test]
-#[ignore = "Channel subscription test occasionally fails due to timing issues"]
 async fn test_trade_subscription() {
     let service = MarketDataService::new();
     let channel = service.channel();
@@ -246,4 +254,366 @@ async fn test_trade_subscription() {
     assert_eq!(trade_msg.market, "BTC/USD");
     assert_eq!(trade_msg.price, Price::new(10000, 0));
     assert_eq!(trade_msg.quantity, Quantity::new(1, 0));
-}
\ No newline at end of file
+}
+/// Candle updates delivered so far
+fn candle_updates(receiver: &Receiver<Arc<dyn Any + Send + Sync>>) -> Vec<CandleUpdate> {
+    receiver.try_iter()
+        .map(|message| message.downcast_ref::<CandleUpdate>().unwrap().clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_candle_subscription() {
+    let service = MarketDataService::new();
+    let channel = service.channel();
+    let minute = channel.subscribe::<CandleUpdate>(Topic::Candles("BTC/USD".to_string(), CandleInterval::Minute1)).await;
+    let five_minutes = channel.subscribe::<CandleUpdate>(Topic::Candles("BTC/USD".to_string(), CandleInterval::Minute5)).await;
+
+    // Two trades a minute apart, inside the same five minutes
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    for (offset, price) in [(10, 100), (70, 110)] {
+        let mut trade = Trade::new(
+            "BTC/USD".to_string(),
+            Price::new(price, 0),
+            Quantity::new(1, 0),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Side::Buy,
+        );
+        trade.created_at = start + chrono::Duration::seconds(offset);
+        service.process_trade(&trade).await.unwrap();
+    }
+
+    // The second trade first closes the one minute candle it does not belong to
+    let minute_updates = candle_updates(&minute);
+    let flags: Vec<(Price, bool)> = minute_updates.iter().map(|u| (u.candle.close, u.closed)).collect();
+    assert_eq!(flags, [(Price::new(100, 0), false), (Price::new(100, 0), true), (Price::new(110, 0), false)]);
+    let five_minute_updates = candle_updates(&five_minutes);
+    assert_eq!(five_minute_updates.len(), 2);
+    assert!(five_minute_updates.iter().all(|u| !u.closed));
+    assert_eq!(five_minute_updates[1].candle.trades, 2);
+
+    // Once time passes, every open candle gets exactly one final update
+    service.close_candles(start + chrono::Duration::days(1)).await;
+    service.close_candles(start + chrono::Duration::days(1)).await;
+    let closed = candle_updates(&minute);
+    assert_eq!(closed.len(), 1);
+    assert!(closed[0].closed);
+    assert_eq!(closed[0].candle.close, Price::new(110, 0));
+    assert_eq!(candle_updates(&five_minutes).len(), 1);
+}
+
+#[tokio::test]
+async fn test_bbo_subscription() {
+    let service = MarketDataService::new();
+    let receiver = service.channel().subscribe::<BestBidOffer>(Topic::Bbo("BTC/USD".to_string())).await;
+    let bbos = || -> Vec<BestBidOffer> {
+        receiver.try_iter()
+            .map(|message| message.downcast_ref::<BestBidOffer>().unwrap().clone())
+            .collect()
+    };
+
+    let asks = vec![(Price::new(101, 0), Quantity::new(2, 0))];
+    service.update_order_book("BTC/USD", vec![(Price::new(99, 0), Quantity::new(1, 0))], asks.clone()).await.unwrap();
+    // Depth below the top does not move the BBO
+    service.update_order_book(
+        "BTC/USD",
+        vec![(Price::new(99, 0), Quantity::new(1, 0)), (Price::new(98, 0), Quantity::new(4, 0))],
+        asks.clone(),
+    ).await.unwrap();
+    service.update_order_book("BTC/USD", vec![(Price::new(99, 0), Quantity::new(3, 0))], asks).await.unwrap();
+
+    let updates = bbos();
+    assert_eq!(updates.len(), 2);
+    assert_eq!(updates[0].sequence, 1);
+    assert_eq!(updates[0].bid_price, Some(Price::new(99, 0)));
+    assert_eq!(updates[0].ask_size, Some(Quantity::new(2, 0)));
+    assert_eq!(updates[1].sequence, 3);
+    assert_eq!(updates[1].bid_size, Some(Quantity::new(3, 0)));
+    assert_eq!(service.get_bbo("BTC/USD").unwrap().sequence, 3);
+
+    // An emptied side clears its price and size
+    service.update_order_book("BTC/USD", Vec::new(), Vec::new()).await.unwrap();
+    let updates = bbos();
+    assert_eq!(updates.len(), 1);
+    assert_eq!((updates[0].bid_price, updates[0].ask_price), (None, None));
+}
+
+#[tokio::test]
+async fn test_analytics() {
+    let service = MarketDataService::new();
+    assert!(service.get_analytics("BTC/USD", 10, 100).is_none());
+
+    // Mid 100, so a 50 bps band reaches 99.5 and 100.5
+    service.update_order_book(
+        "BTC/USD",
+        vec![(Price::new(999, 1), Quantity::new(3, 0)), (Price::new(99, 0), Quantity::new(5, 0))],
+        vec![(Price::new(1001, 1), Quantity::new(1, 0)), (Price::new(1004, 1), Quantity::new(1, 0))],
+    ).await.unwrap();
+    for (seconds, side, quantity) in [(0, Side::Buy, 3), (1, Side::Sell, 1)] {
+        let mut trade = Trade::new(
+            "BTC/USD".to_string(),
+            Price::new(100, 0),
+            Quantity::new(quantity, 0),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            side,
+        );
+        trade.created_at += chrono::Duration::seconds(seconds);
+        service.process_trade(&trade).await.unwrap();
+    }
+
+    let analytics = service.get_analytics("BTC/USD", 50, 100).unwrap();
+    assert_eq!(analytics.mid, Some(Price::new(100, 0)));
+    assert_eq!(analytics.spread, Some(Price::new(2, 1)));
+    assert_eq!(analytics.spread_bps, Some(20.0));
+    assert_eq!(analytics.bid_depth, Quantity::new(3, 0));
+    assert_eq!(analytics.ask_depth, Quantity::new(2, 0));
+    assert_eq!(analytics.book_imbalance, Some(0.2));
+    assert_eq!(analytics.trade_count, 2);
+    assert_eq!(analytics.buy_volume, Quantity::new(3, 0));
+    assert_eq!(analytics.sell_volume, Quantity::new(1, 0));
+    assert_eq!(analytics.trade_flow_imbalance, Some(0.5));
+
+    // A wider band takes in the deeper bid, the last trade alone is a sell
+    let analytics = service.get_analytics("BTC/USD", 200, 1).unwrap();
+    assert_eq!(analytics.bid_depth, Quantity::new(8, 0));
+    assert_eq!(analytics.trade_flow_imbalance, Some(-1.0));
+}
+
+#[tokio::test]
+async fn test_order_book_history() {
+    let before = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    let clock = Arc::new(ManualClock::new(before));
+    let service = MarketDataService::new()
+        .with_repository(Arc::new(InMemoryMarketRepository::with_retention(2)))
+        .with_clock(clock.clone());
+    let asks = vec![(Price::new(101, 0), Quantity::new(1, 0))];
+
+    service.update_order_book("BTC/USD", vec![(Price::new(99, 0), Quantity::new(1, 0))], asks.clone()).await.unwrap();
+    service.snapshot_order_books().await.unwrap();
+    let first = service.get_market_depth("BTC/USD").unwrap().timestamp;
+    assert_eq!(first, before);
+    clock.advance(chrono::Duration::seconds(5));
+
+    // An unchanged book is not snapshotted twice
+    service.snapshot_order_books().await.unwrap();
+    service.update_order_book("BTC/USD", vec![(Price::new(100, 0), Quantity::new(2, 0))], asks).await.unwrap();
+    service.snapshot_order_books().await.unwrap();
+
+    assert!(service.get_order_book_at("BTC/USD", before - chrono::Duration::seconds(1)).await.unwrap().is_none());
+    let replayed = service.get_order_book_at("BTC/USD", first).await.unwrap().unwrap();
+    assert_eq!(replayed.sequence, 1);
+    assert_eq!(replayed.bids[0].price, Price::new(99, 0));
+    let latest = service.get_order_book_at("BTC/USD", clock.now()).await.unwrap().unwrap();
+    assert_eq!(latest.sequence, 2);
+    assert!(service.get_order_book_at("ETH/USD", clock.now()).await.unwrap().is_none());
+
+    // Retention drops the oldest snapshot first
+    service.update_order_book("BTC/USD", Vec::new(), Vec::new()).await.unwrap();
+    service.snapshot_order_books().await.unwrap();
+    assert!(service.get_order_book_at("BTC/USD", first).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_heatmap_spreads_samples_over_price_buckets() {
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    let clock = Arc::new(ManualClock::new(start));
+    let service = MarketDataService::new().with_clock(clock.clone());
+    let level = |price: i64, quantity: i64| (Price::new(price, 0), Quantity::new(quantity, 0));
+
+    service.update_order_book("BTC/USD", vec![level(90, 1), level(80, 2)], vec![level(110, 3), level(120, 4)]).await.unwrap();
+    service.sample_heatmaps(1).await.unwrap();
+    clock.advance(chrono::Duration::seconds(10));
+    service.update_order_book("BTC/USD", vec![level(100, 5)], vec![level(120, 6)]).await.unwrap();
+    service.sample_heatmaps(1).await.unwrap();
+
+    // Only the top level of each side is kept, spread over 90..=120 in buckets of 10
+    let heatmap = service.get_heatmap("BTC/USD", start, clock.now() + chrono::Duration::seconds(1), 3).await.unwrap();
+    assert_eq!(heatmap.price_low, Some(Price::new(90, 0)));
+    assert_eq!(heatmap.price_high, Some(Price::new(120, 0)));
+    assert_eq!(heatmap.bucket_size, Price::new(10, 0));
+    assert_eq!(heatmap.columns.len(), 2);
+    assert_eq!(heatmap.columns[0].timestamp, start);
+    assert_eq!(heatmap.columns[0].bids, [Quantity::new(1, 0), Quantity::ZERO, Quantity::ZERO]);
+    assert_eq!(heatmap.columns[0].asks, [Quantity::ZERO, Quantity::ZERO, Quantity::new(3, 0)]);
+    assert_eq!(heatmap.columns[1].bids, [Quantity::ZERO, Quantity::new(5, 0), Quantity::ZERO]);
+    assert_eq!(heatmap.columns[1].asks, [Quantity::ZERO, Quantity::ZERO, Quantity::new(6, 0)]);
+
+    // The window's end is exclusive
+    let heatmap = service.get_heatmap("BTC/USD", start, clock.now(), 3).await.unwrap();
+    assert_eq!(heatmap.columns.len(), 1);
+
+    let empty = service.get_heatmap("ETH/USD", start, clock.now(), 3).await.unwrap();
+    assert!(empty.columns.is_empty());
+    assert_eq!(empty.price_low, None);
+    assert!(service.get_heatmap("BTC/USD", start, clock.now(), 0).await.is_err());
+}
+
+#[tokio::test]
+async fn test_trade_history_and_candles() {
+    let service = MarketDataService::new();
+    let market = "BTC/USD";
+    let start = Utc::now() - chrono::Duration::hours(2);
+
+    let mut trades = Vec::new();
+    for (minutes, price) in [(0, 100), (30, 110), (61, 90), (119, 95)] {
+        let mut trade = Trade::new(
+            market.to_string(),
+            Price::new(price, 0),
+            Quantity::new(1, 0),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Side::Buy,
+        );
+        trade.created_at = start + chrono::Duration::minutes(minutes);
+        service.process_trade(&trade).await.unwrap();
+        trades.push(trade);
+    }
+
+    // `to` is exclusive
+    let history = service
+        .get_trade_history(market, trades[1].created_at, trades[3].created_at)
+        .await
+        .unwrap();
+    let ids: Vec<Uuid> = history.iter().map(|trade| trade.id).collect();
+    assert_eq!(ids, [trades[1].id, trades[2].id]);
+    assert!(service.get_trade_history("ETH/USD", start, Utc::now()).await.unwrap().is_empty());
+
+    let all = service.get_trade_history(market, start, Utc::now()).await.unwrap();
+    let candles = market_data::Candle::from_trades(market, CandleInterval::Week1, &all);
+    assert_eq!(candles.len(), 1);
+    assert_eq!(candles[0].open, Price::new(100, 0));
+    assert_eq!(candles[0].high, Price::new(110, 0));
+    assert_eq!(candles[0].low, Price::new(90, 0));
+    assert_eq!(candles[0].close, Price::new(95, 0));
+    assert_eq!(candles[0].trades, 4);
+    assert_eq!(candles[0].open_time, CandleInterval::Week1.open_time(start));
+}
+
+#[tokio::test]
+async fn test_trades_after_sequence() {
+    let service = MarketDataService::new();
+    let market = "BTC/USD";
+    let start = Utc::now() - chrono::Duration::minutes(10);
+
+    // Trade 3 arrives last but executed before trade 2
+    let mut ids = Vec::new();
+    for (sequence, minutes) in [(1, 0), (2, 5), (4, 6), (3, 4)] {
+        let mut trade = Trade::new(
+            market.to_string(),
+            Price::new(100, 0),
+            Quantity::new(1, 0),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Side::Buy,
+        );
+        trade.sequence = sequence;
+        trade.created_at = start + chrono::Duration::minutes(minutes);
+        service.process_trade(&trade).await.unwrap();
+        ids.push(trade.id);
+    }
+
+    let after: Vec<u64> = service.get_trades_after(market, 1, 10).await.unwrap().iter().map(|trade| trade.sequence).collect();
+    assert_eq!(after, [2, 3, 4]);
+    let limited = service.get_trades_after(market, 1, 2).await.unwrap();
+    assert_eq!(limited.iter().map(|trade| trade.id).collect::<Vec<_>>(), [ids[1], ids[3]]);
+    assert!(service.get_trades_after(market, 4, 10).await.unwrap().is_empty());
+    assert!(service.get_trades_after("ETH/USD", 0, 10).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_tape_filter_hides_dust_from_public_feeds() {
+    let market = "BTC/USD";
+    let filter = TapeFilter {
+        min_trade_sizes: [(market.to_string(), Quantity::new(1, 2))].into(),
+    };
+    let service = MarketDataService::new().with_tape_filter(filter);
+    let public = service.channel().subscribe::<TradeMessage>(Topic::Trades(market.to_string())).await;
+    let raw = service.channel().subscribe::<TradeMessage>(Topic::RawTrades(market.to_string())).await;
+
+    // A displayed trade, then dust at another price in the same minute
+    let at = Utc::now();
+    for (price, quantity) in [(100, Quantity::new(5, 2)), (130, Quantity::new(1, 4))] {
+        let mut trade = Trade::new(
+            market.to_string(),
+            Price::new(price, 0),
+            quantity,
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Side::Buy,
+        );
+        trade.created_at = at;
+        service.process_trade(&trade).await.unwrap();
+    }
+
+    assert_eq!(public.try_iter().count(), 1);
+    assert_eq!(raw.try_iter().count(), 2);
+    let recent = service.get_recent_trades(market, 10);
+    assert_eq!(recent.len(), 1);
+    assert_eq!(recent[0].price, Price::new(100, 0));
+    assert_eq!(service.get_raw_trades(market, 10).len(), 2);
+    assert_eq!(service.get_ticker(market).unwrap().last, Some(Price::new(100, 0)));
+
+    // Dust still trades, so it counts towards candles
+    let candle = &service.get_candles(market, CandleInterval::Minute1, 1)[0];
+    assert_eq!(candle.trades, 2);
+    assert_eq!(candle.high, Price::new(130, 0));
+}
+
+#[tokio::test]
+async fn test_filled_candles_cover_idle_periods() {
+    let market = "BTC/USD";
+    let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+    let clock = Arc::new(ManualClock::new(start));
+    let service = MarketDataService::new().with_clock(clock.clone());
+
+    for (minutes, price) in [(0, 100), (3, 110)] {
+        let mut trade = Trade::new(
+            market.to_string(),
+            Price::new(price, 0),
+            Quantity::new(1, 0),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Side::Buy,
+        );
+        trade.created_at = start + chrono::Duration::minutes(minutes);
+        service.process_trade(&trade).await.unwrap();
+    }
+    clock.set(start + chrono::Duration::seconds(5 * 60 + 30));
+
+    // Up to the current minute, but not before the first trade
+    let candles = service.get_filled_candles(market, CandleInterval::Minute1, 10, CandleFill::Previous);
+    let open_times: Vec<_> = candles.iter().map(|candle| candle.open_time).collect();
+    let expected: Vec<_> = (0..6).rev().map(|minutes| start + chrono::Duration::minutes(minutes)).collect();
+    assert_eq!(open_times, expected);
+    let closes: Vec<_> = candles.iter().map(|candle| candle.close).collect();
+    assert_eq!(closes, [110, 110, 110, 100, 100, 100].map(|price| Price::new(price, 0)));
+    assert_eq!((candles[1].open, candles[1].low), (Price::new(110, 0), Price::new(110, 0)));
+    assert_eq!((candles[1].volume, candles[1].trades), (Quantity::ZERO, 0));
+
+    // The close before the window is carried into it
+    let candles = service.get_filled_candles(market, CandleInterval::Minute1, 4, CandleFill::Previous);
+    assert_eq!(candles.len(), 4);
+    assert_eq!(candles[3].open_time, start + chrono::Duration::minutes(2));
+    assert_eq!(candles[3].close, Price::new(100, 0));
+
+    let candles = service.get_filled_candles(market, CandleInterval::Minute1, 10, CandleFill::Zero);
+    assert_eq!(candles.len(), 6);
+    assert_eq!((candles[3].close, candles[3].high), (Price::ZERO, Price::ZERO));
+    assert_eq!(candles[2].close, Price::new(110, 0));
+
+    assert!(service.get_filled_candles("ETH/USD", CandleInterval::Minute1, 10, CandleFill::Zero).is_empty());
+}
@@ -0,0 +1,114 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use common::decimal::{Price, Quantity};
+use common::error::{Error, Result};
+use common::model::order::Side;
+use market_data::shadow::{ExternalMarketFetcher, ExternalSnapshot, ExternalTrade, ShadowMarket};
+use market_data::MarketDataService;
+
+const MARKET: &str = "BTC/USDT";
+
+/// Exchange stand-in serving a fixed recent trade list, or failing
+#[derive(Default)]
+struct Exchange {
+    snapshot: Mutex<Option<ExternalSnapshot>>,
+}
+
+impl Exchange {
+    /// Serve trades `ids`, each priced at its ID, and a book around the newest one
+    fn serve(&self, ids: &[u64]) {
+        let trades = ids
+            .iter()
+            .map(|&id| ExternalTrade {
+                id,
+                price: Price::from(id),
+                quantity: Quantity::ONE,
+                taker_side: Side::Buy,
+                executed_at: Utc.timestamp_opt(1_700_000_000 + id as i64, 0).unwrap(),
+            })
+            .collect();
+        let last = Price::from(*ids.iter().max().unwrap());
+        *self.snapshot.lock().unwrap() = Some(ExternalSnapshot {
+            bids: vec![(last - Price::ONE, Quantity::TWO)],
+            asks: vec![(last + Price::ONE, Quantity::ONE)],
+            trades,
+        });
+    }
+
+    fn fail(&self) {
+        *self.snapshot.lock().unwrap() = None;
+    }
+}
+
+#[async_trait]
+impl ExternalMarketFetcher for Exchange {
+    fn name(&self) -> &str {
+        "exchange"
+    }
+
+    async fn fetch(&self, external_symbol: &str) -> Result<ExternalSnapshot> {
+        assert_eq!(external_symbol, "BTCUSDT");
+        self.snapshot.lock().unwrap().clone().ok_or_else(|| Error::Internal("exchange down".to_string()))
+    }
+}
+
+fn shadow_service(exchange: Arc<Exchange>) -> MarketDataService {
+    MarketDataService::new().with_shadow_markets(
+        exchange,
+        vec![ShadowMarket { symbol: MARKET.to_string(), external_symbol: "BTCUSDT".to_string() }],
+    )
+}
+
+fn prices(service: &MarketDataService) -> Vec<Price> {
+    let mut trades = service.get_recent_trades(MARKET, 100);
+    trades.sort_by_key(|trade| trade.timestamp);
+    trades.into_iter().map(|trade| trade.price).collect()
+}
+
+#[tokio::test]
+async fn test_external_book_and_trades_are_mirrored_once() {
+    let exchange = Arc::new(Exchange::default());
+    let service = shadow_service(exchange.clone());
+    assert!(service.is_shadow_market(MARKET));
+    assert!(!service.is_shadow_market("BTC/USD"));
+
+    // Trades may come newest first
+    exchange.serve(&[12, 11, 10]);
+    service.ingest_shadow_markets().await.unwrap();
+    assert_eq!(prices(&service), [Price::from(10), Price::from(11), Price::from(12)]);
+    let depth = service.get_market_depth(MARKET).unwrap();
+    assert_eq!((depth.bids[0].price, depth.asks[0].price), (Price::from(11), Price::from(13)));
+    assert_eq!(service.get_ticker(MARKET).unwrap().last, Some(Price::from(12)));
+
+    // Trades already ingested are skipped when the window overlaps
+    exchange.serve(&[11, 12, 13, 14]);
+    service.ingest_shadow_markets().await.unwrap();
+    assert_eq!(prices(&service).len(), 5);
+
+    let status = &service.shadow_markets()[0];
+    assert_eq!((status.symbol.as_str(), status.source.as_str()), (MARKET, "exchange"));
+    assert_eq!((status.trades_ingested, status.last_trade_id), (5, Some(14)));
+    assert!(status.last_update_at.is_some() && status.last_error.is_none());
+}
+
+#[tokio::test]
+async fn test_failed_poll_keeps_the_market_as_it_was() {
+    let exchange = Arc::new(Exchange::default());
+    let service = shadow_service(exchange.clone());
+    exchange.serve(&[1, 2]);
+    service.ingest_shadow_markets().await.unwrap();
+
+    exchange.fail();
+    service.ingest_shadow_markets().await.unwrap();
+    assert_eq!(prices(&service).len(), 2);
+    assert!(service.get_market_depth(MARKET).is_some());
+    let status = &service.shadow_markets()[0];
+    assert_eq!(status.last_error.as_deref(), Some("Internal error: exchange down"));
+
+    exchange.serve(&[2, 3]);
+    service.ingest_shadow_markets().await.unwrap();
+    assert_eq!(prices(&service).len(), 3);
+    assert!(service.shadow_markets()[0].last_error.is_none());
+}
@@ -0,0 +1,148 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::decimal::{Price, Quantity};
+use common::model::order::Side;
+use common::model::trade::Trade;
+use market_data::repository::InMemoryMarketRepository;
+use market_data::sync::{Levels, SyncSource};
+use market_data::MarketDataService;
+use uuid::Uuid;
+
+const MARKET: &str = "BTC/USD";
+
+/// Engine stand-in holding numbered trades and a book
+#[derive(Default)]
+struct Source {
+    trades: Mutex<Vec<Trade>>,
+}
+
+impl Source {
+    /// Execute a trade, numbered after the previous one
+    fn execute(&self, price: i64) -> Trade {
+        let mut trades = self.trades.lock().unwrap();
+        let mut trade = Trade::new(
+            MARKET.to_string(),
+            Price::new(price, 0),
+            Quantity::ONE,
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Side::Buy,
+        );
+        trade.sequence = trades.len() as u64 + 1;
+        trades.push(trade.clone());
+        trade
+    }
+}
+
+impl SyncSource for Source {
+    fn markets(&self) -> Vec<String> {
+        vec![MARKET.to_string()]
+    }
+
+    fn last_trade_sequence(&self, _market: &str) -> Option<u64> {
+        Some(self.trades.lock().unwrap().len() as u64)
+    }
+
+    fn trades_after(&self, _market: &str, sequence: u64) -> Vec<Trade> {
+        self.trades.lock().unwrap().iter().filter(|trade| trade.sequence > sequence).cloned().collect()
+    }
+
+    fn depth(&self, _market: &str) -> Option<(Levels, Levels)> {
+        Some((vec![(Price::new(99, 0), Quantity::ONE)], vec![(Price::new(101, 0), Quantity::TWO)]))
+    }
+}
+
+fn prices(service: &MarketDataService) -> Vec<Price> {
+    let mut trades = service.get_recent_trades(MARKET, 100);
+    trades.sort_by_key(|trade| trade.timestamp);
+    trades.into_iter().map(|trade| trade.price).collect()
+}
+
+#[tokio::test]
+async fn test_gaps_are_repaired_from_the_source() {
+    let source = Arc::new(Source::default());
+    let service = MarketDataService::new().with_sync_source(source.clone());
+    let trades: Vec<Trade> = (1..=4).map(|price| source.execute(price)).collect();
+
+    // The second and third trades never arrive on their own
+    service.process_trade(&trades[0]).await.unwrap();
+    service.process_trade(&trades[3]).await.unwrap();
+    assert_eq!(prices(&service), [Price::new(1, 0), Price::new(2, 0), Price::new(3, 0), Price::new(4, 0)]);
+
+    // The book is re-synced along with the trades
+    let depth = service.get_market_depth(MARKET).unwrap();
+    assert_eq!(depth.asks[0].quantity, Quantity::TWO);
+
+    // A repaired trade arriving late is not counted twice
+    service.process_trade(&trades[1]).await.unwrap();
+    assert_eq!(prices(&service).len(), 4);
+
+    let report = service.gap_report().await;
+    assert_eq!(report.len(), 1);
+    let gaps = &report[0];
+    assert_eq!((gaps.last_sequence, gaps.gaps, gaps.missed_trades, gaps.repaired_trades), (4, 1, 2, 2));
+    assert_eq!((gaps.duplicates, gaps.outstanding_trades), (1, 0));
+    assert!(gaps.last_gap_at.is_some());
+}
+
+#[tokio::test]
+async fn test_unrepaired_trades_are_applied_when_they_arrive() {
+    let source = Source::default();
+    let service = MarketDataService::new();
+    let trades: Vec<Trade> = (1..=3).map(|price| source.execute(price)).collect();
+
+    service.process_trade(&trades[0]).await.unwrap();
+    service.process_trade(&trades[2]).await.unwrap();
+    assert_eq!(service.gap_report().await[0].outstanding_trades, 1);
+
+    service.process_trade(&trades[1]).await.unwrap();
+    service.process_trade(&trades[1]).await.unwrap();
+    assert_eq!(prices(&service).len(), 3);
+    let gaps = &service.gap_report().await[0];
+    assert_eq!((gaps.missed_trades, gaps.repaired_trades, gaps.late_trades), (1, 0, 1));
+    assert_eq!((gaps.duplicates, gaps.outstanding_trades), (1, 0));
+}
+
+#[tokio::test]
+async fn test_sync_check_repairs_trades_that_never_arrived() {
+    let source = Arc::new(Source::default());
+    let service = MarketDataService::new().with_sync_source(source.clone());
+    source.execute(100);
+    source.execute(101);
+
+    // Trades are given until the next check to arrive
+    service.check_sync().await.unwrap();
+    assert!(prices(&service).is_empty());
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    service.check_sync().await.unwrap();
+    assert_eq!(prices(&service), [Price::new(100, 0), Price::new(101, 0)]);
+    assert_eq!(service.gap_report().await[0].repaired_trades, 2);
+
+    // Nothing more to repair
+    service.check_sync().await.unwrap();
+    assert_eq!(service.gap_report().await[0].gaps, 1);
+}
+
+#[tokio::test]
+async fn test_numbering_resumed_after_a_restart_is_not_a_gap() {
+    let repository = Arc::new(InMemoryMarketRepository::new());
+    let source = Source::default();
+    let trades: Vec<Trade> = (1..=3).map(|price| source.execute(price)).collect();
+    let before = MarketDataService::new().with_repository(repository.clone());
+    for trade in &trades[..2] {
+        before.process_trade(trade).await.unwrap();
+    }
+
+    // The restarted service carries on from the last stored trade
+    let after = MarketDataService::new().with_repository(repository);
+    assert_eq!(after.resume_trade_sequence(MARKET).await.unwrap(), 2);
+    after.process_trade(&trades[2]).await.unwrap();
+    after.process_trade(&trades[1]).await.unwrap();
+    let gaps = &after.gap_report().await[0];
+    assert_eq!((gaps.last_sequence, gaps.gaps, gaps.duplicates), (3, 0, 1));
+    assert_eq!(MarketDataService::new().resume_trade_sequence(MARKET).await.unwrap(), 0);
+}
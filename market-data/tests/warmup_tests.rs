@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use common::clock::ManualClock;
+use common::decimal::{Price, Quantity};
+use common::model::order::Side;
+use common::model::trade::Trade;
+use market_data::channel::Topic;
+use market_data::repository::InMemoryMarketRepository;
+use market_data::warmup::WarmupConfig;
+use market_data::{CandleInterval, CandleUpdate, MarketDataService};
+use uuid::Uuid;
+
+const MARKET: &str = "BTC/USD";
+
+async fn trade(service: &MarketDataService, at: DateTime<Utc>, price: i64) {
+    let mut trade = Trade::new(
+        MARKET.to_string(),
+        Price::new(price, 0),
+        Quantity::ONE,
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Uuid::new_v4(),
+        Side::Buy,
+    );
+    trade.created_at = at;
+    service.process_trade(&trade).await.unwrap();
+}
+
+/// A service that traded before `now` and one restarted at `now` over the same repository
+async fn restarted(now: DateTime<Utc>, trades: &[(Duration, i64)]) -> (MarketDataService, MarketDataService) {
+    let repository = Arc::new(InMemoryMarketRepository::new());
+    let before = MarketDataService::new()
+        .with_repository(repository.clone())
+        .with_clock(Arc::new(ManualClock::new(now)));
+    for (ago, price) in trades {
+        trade(&before, now - *ago, *price).await;
+    }
+    before.update_order_book(MARKET, vec![(Price::new(99, 0), Quantity::ONE)], vec![(Price::new(101, 0), Quantity::ONE)])
+        .await
+        .unwrap();
+    before.snapshot_order_books().await.unwrap();
+
+    let after = MarketDataService::new()
+        .with_repository(repository)
+        .with_clock(Arc::new(ManualClock::new(now)));
+    (before, after)
+}
+
+#[tokio::test]
+async fn test_warmup_restores_ticker_and_recent_trades() {
+    let now = Utc.with_ymd_and_hms(2024, 3, 6, 12, 0, 30).unwrap();
+    let trades = [(Duration::hours(30), 150), (Duration::hours(3), 120), (Duration::hours(2), 90), (Duration::minutes(5), 100)];
+    let (before, after) = restarted(now, &trades).await;
+    assert!(after.get_ticker(MARKET).is_none());
+
+    let warmed = after.warm_up(&[MARKET.to_string(), "ETH/USD".to_string()], &WarmupConfig::default()).await.unwrap();
+    assert_eq!(warmed.len(), 2);
+    assert!(warmed[0].ticker);
+    assert_eq!(warmed[0].trades, 4);
+    assert!(!warmed[1].ticker);
+    assert_eq!((warmed[1].trades, warmed[1].candles), (0, 0));
+
+    // Only the last day counts toward the range, the book comes from the last snapshot
+    let ticker = after.get_ticker(MARKET).unwrap();
+    assert_eq!(ticker.last, Some(Price::new(100, 0)));
+    assert_eq!((ticker.high_24h, ticker.low_24h), (Some(Price::new(120, 0)), Some(Price::new(90, 0))));
+    assert_eq!((ticker.bid, ticker.ask), (Some(Price::new(99, 0)), Some(Price::new(101, 0))));
+
+    let recent: Vec<_> = after.get_recent_trades(MARKET, 100).iter().map(|trade| trade.id).collect();
+    let expected: Vec<_> = before.get_recent_trades(MARKET, 3).iter().map(|trade| trade.id).collect();
+    assert_eq!(recent, expected);
+}
+
+#[tokio::test]
+async fn test_warmup_rebuilds_open_candles_without_announcing_closed_ones() {
+    // A Wednesday, so the weekly candle opened days before the ticker window
+    let now = Utc.with_ymd_and_hms(2024, 3, 6, 12, 0, 30).unwrap();
+    let trades = [(Duration::days(10), 500), (Duration::hours(30), 150), (Duration::hours(2), 90), (Duration::seconds(20), 100)];
+    let (before, after) = restarted(now, &trades).await;
+    let receiver = after.channel().subscribe::<CandleUpdate>(Topic::Candles(MARKET.to_string(), CandleInterval::Minute1)).await;
+
+    after.warm_up(&[MARKET.to_string()], &WarmupConfig::default()).await.unwrap();
+
+    // Candles of open periods match the ones built live, the trade before the week is left out
+    for interval in [CandleInterval::Minute1, CandleInterval::Hour1, CandleInterval::Day1, CandleInterval::Week1] {
+        let live = before.get_candles(MARKET, interval, 1).remove(0);
+        let warmed = after.get_candles(MARKET, interval, 1).remove(0);
+        assert_eq!((warmed.open_time, warmed.close_time), (live.open_time, live.close_time), "{}", interval.code());
+        assert_eq!((warmed.open, warmed.high, warmed.low, warmed.close), (live.open, live.high, live.low, live.close));
+        assert_eq!((warmed.volume, warmed.trades, warmed.taker_buy_volume), (live.volume, live.trades, live.taker_buy_volume));
+    }
+    assert_eq!(after.get_candles(MARKET, CandleInterval::Week1, 100).len(), 1);
+    assert_eq!(after.get_candles(MARKET, CandleInterval::Minute1, 100).len(), 3);
+
+    // The working minute candle is still announced when it closes, nothing before it
+    after.close_candles(now + Duration::minutes(1)).await;
+    let closed: Vec<_> = receiver.try_iter().map(|message| message.downcast_ref::<CandleUpdate>().unwrap().clone()).collect();
+    assert_eq!(closed.len(), 1);
+    assert_eq!(closed[0].candle.open_time, CandleInterval::Minute1.open_time(now));
+}
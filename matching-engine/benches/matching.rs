@@ -0,0 +1,94 @@
+//! Matching hot path benchmarks
+//!
+//! Run with `cargo bench -p matching-engine`.
+
+use chrono::Utc;
+use common::decimal::{Price, Quantity};
+use common::model::order::{Order, OrderType, Side, Status, TimeInForce};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use matching_engine::engine::MatchingEngine;
+use uuid::Uuid;
+
+const MARKET: &str = "BTC/USD";
+
+fn order(side: Side, order_type: OrderType, price: Option<i64>, quantity: i64) -> Order {
+    let quantity = Quantity::new(quantity, 0);
+    Order {
+        id: Uuid::new_v4(),
+        user_id: Uuid::new_v4(),
+        market: MARKET.to_string(),
+        side,
+        order_type,
+        price: price.map(|price| Price::new(price, 0)),
+        quantity,
+        remaining_quantity: quantity,
+        filled_quantity: Quantity::ZERO,
+        status: Status::New,
+        time_in_force: TimeInForce::GTC,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
+    }
+}
+
+/// An engine with `levels` asks of one unit each from 100 up
+fn book(levels: i64) -> MatchingEngine {
+    let engine = MatchingEngine::new();
+    engine.register_market(MARKET.to_string());
+    for level in 0..levels {
+        engine.place_order(order(Side::Sell, OrderType::Limit, Some(100 + level), 1)).unwrap();
+    }
+    engine
+}
+
+// Filling benchmarks return the engine so dropping the book is not timed
+fn bench_matching(c: &mut Criterion) {
+    c.bench_function("rest_limit_order", |b| {
+        let engine = book(0);
+        b.iter(|| engine.place_order(black_box(order(Side::Buy, OrderType::Limit, Some(90), 1))).unwrap());
+    });
+
+    c.bench_function("single_fill", |b| {
+        b.iter_batched(
+            || (book(1), order(Side::Buy, OrderType::Limit, Some(100), 1)),
+            |(engine, taker)| {
+                let result = engine.place_order(taker).unwrap();
+                (engine, result)
+            },
+            BatchSize::SmallInput,
+        );
+    });
+
+    c.bench_function("sweep_4_levels", |b| {
+        b.iter_batched(
+            || (book(4), order(Side::Buy, OrderType::Market, None, 4)),
+            |(engine, taker)| {
+                let result = engine.place_order(taker).unwrap();
+                (engine, result)
+            },
+            BatchSize::SmallInput,
+        );
+    });
+
+    c.bench_function("sweep_32_levels", |b| {
+        b.iter_batched(
+            || (book(32), order(Side::Buy, OrderType::Market, None, 32)),
+            |(engine, taker)| {
+                let result = engine.place_order(taker).unwrap();
+                (engine, result)
+            },
+            BatchSize::SmallInput,
+        );
+    });
+}
+
+criterion_group!(benches, bench_matching);
+criterion_main!(benches);
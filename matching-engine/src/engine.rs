@@ -1,15 +1,31 @@
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, RwLock, RwLockWriteGuard};
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use common::clock::{SharedClock, SystemClock};
+use common::id::{RandomIdGenerator, SharedIdGenerator};
 use common::decimal::{Price, Quantity};
 use common::error::{Error, Result};
-use common::model::order::{Order, Status, Side, OrderType, TimeInForce};
+use common::flags::{FeatureFlags, SharedFeatureFlags, BOOK_LEVEL_PRUNING};
+use common::model::fee::FeeSchedule;
+use common::model::market::{Allocation, BookLimits, LevelPolicy, MarketSession, SessionState, TradingSchedule};
+use common::model::order::{Order, Peg, PegReference, RejectReason, Status, Side, OrderType, TimeInForce};
 use common::model::trade::Trade;
-use dashmap::DashMap;
-use tracing::{debug, info};
+use rust_decimal::Decimal;
+use dashmap::{DashMap, DashSet};
+use smallvec::SmallVec;
+use tracing::{debug, error, info};
 use uuid::Uuid;
 
+use crate::events::{EngineEvent, EventBus, SessionChange};
 use crate::order_book::{OrderBook, OrderBookSide};
+use crate::throttle::{Throttle, ThrottleConfig};
+use crate::verification::{BookChecks, BookVerification, Verifier};
+
+/// Fills most orders generate without spilling to the heap
+pub const INLINE_FILLS: usize = 4;
+
+/// Least time between re-pricings of a book's pegged orders, unless set
+pub const DEFAULT_PEG_REPRICE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
 
 /// Result of a matching operation
 #[derive(Debug, Default)]
@@ -17,31 +33,403 @@ pub struct MatchingResult {
     /// The updated taker order
     pub taker_order: Option<Arc<Order>>,
     /// Maker orders that were matched
-    pub maker_orders: Vec<Arc<Order>>,
+    pub maker_orders: SmallVec<[Arc<Order>; INLINE_FILLS]>,
     /// Trades that were generated
-    pub trades: Vec<Trade>,
+    pub trades: SmallVec<[Trade; INLINE_FILLS]>,
+    /// Resting orders the engine expired to make room on the book
+    pub expired_orders: Vec<Arc<Order>>,
+    /// Worst price a market order was allowed to fill at, from its price or slippage limit
+    pub price_cap: Option<Price>,
+    /// Resting pegged orders the engine moved to follow the book
+    pub repriced_orders: Vec<Arc<Order>>,
+}
+
+/// Resting orders and sequence numbers of one market's book
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BookStats {
+    /// Resting buy orders
+    pub bid_orders: usize,
+    /// Resting sell orders
+    pub ask_orders: usize,
+    /// Price levels with buy orders
+    pub bid_levels: usize,
+    /// Price levels with sell orders
+    pub ask_levels: usize,
+    /// Best bid price
+    pub best_bid: Option<Price>,
+    /// Best ask price
+    pub best_ask: Option<Price>,
+    /// Sequence number of the last order, 0 before the first
+    pub last_order_sequence: u64,
+    /// Sequence number of the last trade, 0 before the first
+    pub last_trade_sequence: u64,
 }
 
 /// The matching engine responsible for processing orders and generating trades
 pub struct MatchingEngine {
     /// Map of market symbols to order books
     order_books: DashMap<String, Arc<RwLock<OrderBook>>>,
+    /// Fees applied to generated trades
+    fee_schedule: FeeSchedule,
+    /// Per-account order and cancel rate limits
+    throttle: Throttle,
+    /// Accounts barred from placing new orders
+    blocked_accounts: DashSet<Uuid>,
+    /// Subscribers to processed orders, cancels and trades
+    events: EventBus,
+    /// Trading calendars of markets that do not trade around the clock
+    schedules: DashMap<String, TradingSchedule>,
+    /// Session state of markets that are not open
+    sessions: DashMap<String, SessionState>,
+    /// Limits on resting orders of markets that have them
+    book_limits: DashMap<String, BookLimits>,
+    /// Time source stamping orders and trades
+    clock: SharedClock,
+    /// Source of new order and trade ids
+    ids: SharedIdGenerator,
+    /// Experimental behaviors turned on or off at runtime
+    flags: SharedFeatureFlags,
+    /// Least time between re-pricings of a book's pegged orders
+    peg_interval: chrono::Duration,
+    /// Crossed book checks run after each command
+    verifier: Verifier,
 }
 
 impl MatchingEngine {
     /// Create a new matching engine
     pub fn new() -> Self {
+        Self::with_fee_schedule(FeeSchedule::zero())
+    }
+    
+    /// Create a new matching engine that charges the given fees
+    pub fn with_fee_schedule(fee_schedule: FeeSchedule) -> Self {
         Self {
             order_books: DashMap::new(),
+            fee_schedule,
+            throttle: Throttle::new(ThrottleConfig::unlimited()),
+            blocked_accounts: DashSet::new(),
+            events: EventBus::default(),
+            schedules: DashMap::new(),
+            sessions: DashMap::new(),
+            book_limits: DashMap::new(),
+            clock: SystemClock::shared(),
+            ids: RandomIdGenerator::shared(),
+            flags: FeatureFlags::shared(),
+            peg_interval: peg_interval(DEFAULT_PEG_REPRICE_INTERVAL),
+            verifier: Verifier::new(BookVerification::default()),
         }
     }
     
+    /// Re-price each book's pegged orders at most once per `interval`
+    ///
+    /// Book changes within the interval are caught up by
+    /// [`reprice_pegged_orders`](Self::reprice_pegged_orders).
+    pub fn with_peg_reprice_interval(mut self, interval: std::time::Duration) -> Self {
+        self.peg_interval = peg_interval(interval);
+        self
+    }
+    
+    /// Check books for crossed prices after each command, counting or
+    /// panicking on a crossing, instead of the build's default
+    pub fn with_book_verification(mut self, mode: BookVerification) -> Self {
+        self.verifier = Verifier::new(mode);
+        self
+    }
+    
+    /// How books are checked for crossed prices after each command
+    pub fn book_verification(&self) -> BookVerification {
+        self.verifier.mode()
+    }
+    
+    /// Crossed book checks of each market since startup, by market
+    pub fn book_checks(&self) -> Vec<BookChecks> {
+        self.verifier.report()
+    }
+    
+    /// Limit new orders and cancels per account per market
+    pub fn with_throttle(mut self, config: ThrottleConfig) -> Self {
+        self.throttle = Throttle::new(config);
+        self
+    }
+    
+    /// Stamp orders and trades with the given clock's time
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Get the clock stamping orders and trades
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+    
+    /// Take new order and trade ids from the given generator
+    pub fn with_id_generator(mut self, ids: SharedIdGenerator) -> Self {
+        self.ids = ids;
+        self
+    }
+    
+    /// Get the generator of new order and trade ids
+    pub fn id_generator(&self) -> &SharedIdGenerator {
+        &self.ids
+    }
+    
+    /// Consult the given feature flags, shared with the other services
+    pub fn with_feature_flags(mut self, flags: SharedFeatureFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+    
+    /// Get the feature flags the engine consults
+    pub fn feature_flags(&self) -> &SharedFeatureFlags {
+        &self.flags
+    }
+    
+    /// Get the fee schedule applied to trades
+    pub fn fee_schedule(&self) -> FeeSchedule {
+        self.fee_schedule
+    }
+    
+    /// Get the per-account throttle limits
+    pub fn throttle_config(&self) -> ThrottleConfig {
+        self.throttle.config()
+    }
+    
+    /// Subscribe to the stream of processed orders, cancels and trades
+    pub fn subscribe_events(&self) -> crossbeam::channel::Receiver<EngineEvent> {
+        self.events.subscribe()
+    }
+    
     /// Register a new market
     pub fn register_market(&self, market: String) {
         info!("Registering market: {}", market);
         self.order_books.insert(market.clone(), Arc::new(RwLock::new(OrderBook::new(market))));
     }
     
+    /// Set or clear a market's trading calendar
+    ///
+    /// Takes effect at the next [`update_sessions`](Self::update_sessions).
+    /// Markets without a calendar trade around the clock.
+    pub fn set_trading_schedule(&self, market: &str, schedule: Option<TradingSchedule>) -> Result<()> {
+        if !self.order_books.contains_key(market) {
+            return Err(Error::MarketNotFound(format!("Market not found: {}", market)));
+        }
+        
+        match schedule {
+            Some(schedule) => {
+                schedule.validate()?;
+                info!("Setting trading schedule of {}: {:?}", market, schedule);
+                self.schedules.insert(market.to_string(), schedule);
+            }
+            None => {
+                info!("Clearing trading schedule of {}", market);
+                self.schedules.remove(market);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Set or clear the limits on a market's resting orders
+    ///
+    /// Applies to orders that rest from now on.
+    pub fn set_book_limits(&self, market: &str, limits: Option<BookLimits>) -> Result<()> {
+        if !self.order_books.contains_key(market) {
+            return Err(Error::MarketNotFound(format!("Market not found: {}", market)));
+        }
+        
+        match limits {
+            Some(limits) => {
+                limits.validate()?;
+                info!("Setting book limits of {}: {:?}", market, limits);
+                self.book_limits.insert(market.to_string(), limits);
+            }
+            None => {
+                info!("Clearing book limits of {}", market);
+                self.book_limits.remove(market);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Set how a market shares takers among the orders at a price level
+    ///
+    /// Applies from the next match.
+    pub fn set_allocation(&self, market: &str, allocation: Allocation) -> Result<()> {
+        let book = self.order_books.get(market)
+            .ok_or_else(|| Error::MarketNotFound(format!("Market not found: {}", market)))?;
+        allocation.validate()?;
+        info!("Setting allocation of {}: {:?}", market, allocation);
+        book.write().unwrap().set_allocation(allocation);
+        Ok(())
+    }
+    
+    /// Set the tick a market's pegged prices are rounded to
+    ///
+    /// Without one, a pegged order that would reach the opposite side is
+    /// left where it is rather than moved a tick behind it.
+    pub fn set_price_tick(&self, market: &str, tick: Price) -> Result<()> {
+        let book = self.order_books.get(market)
+            .ok_or_else(|| Error::MarketNotFound(format!("Market not found: {}", market)))?;
+        if tick < Price::ZERO {
+            return Err(Error::ValidationError("Price tick must not be negative".to_string()));
+        }
+        book.write().unwrap().set_price_tick(tick);
+        Ok(())
+    }
+    
+    /// Move the pegged orders of every market to the price their peg gives now
+    ///
+    /// Books whose pegs were re-priced less than the re-pricing interval ago
+    /// are skipped. Returns the moved orders, which are also published as
+    /// `OrderUpdated` events.
+    pub fn reprice_pegged_orders(&self, now: DateTime<Utc>) -> Vec<Arc<Order>> {
+        let books: Vec<Arc<RwLock<OrderBook>>> = self.order_books.iter().map(|entry| entry.value().clone()).collect();
+        let repriced: Vec<Arc<Order>> = books
+            .iter()
+            .flat_map(|book| {
+                let mut book = book.write().unwrap();
+                let repriced = self.reprice_pegs(&mut book, now);
+                if !repriced.is_empty() {
+                    self.events.publish(|| repriced.iter().cloned().map(EngineEvent::OrderUpdated).collect());
+                    self.verify(book, "re-pricing pegged orders");
+                }
+                repriced
+            })
+            .collect();
+        
+        if !repriced.is_empty() {
+            debug!("Re-priced {} pegged orders", repriced.len());
+        }
+        repriced
+    }
+    
+    /// Get how a market shares takers among the orders at a price level
+    pub fn allocation(&self, market: &str) -> Result<Allocation> {
+        let book = self.order_books.get(market)
+            .ok_or_else(|| Error::MarketNotFound(format!("Market not found: {}", market)))?;
+        let allocation = book.read().unwrap().allocation();
+        Ok(allocation)
+    }
+    
+    /// Get the limits on a market's resting orders, which are all off unless set
+    pub fn book_limits(&self, market: &str) -> Result<BookLimits> {
+        if !self.order_books.contains_key(market) {
+            return Err(Error::MarketNotFound(format!("Market not found: {}", market)));
+        }
+        Ok(self.book_limits.get(market).map(|limits| limits.clone()).unwrap_or_default())
+    }
+    
+    /// Expire resting orders older than their market's `max_order_age_secs`
+    ///
+    /// Returns the expired orders, which are also published as engine events.
+    pub fn expire_stale_orders(&self, now: DateTime<Utc>) -> Vec<Arc<Order>> {
+        let max_ages: Vec<(String, u64)> = self.book_limits
+            .iter()
+            .filter_map(|entry| entry.max_order_age_secs.map(|age| (entry.key().clone(), age)))
+            .collect();
+        
+        let mut expired = Vec::new();
+        for (market, max_age) in max_ages {
+            let Some(book) = self.order_books.get(&market).map(|book| book.clone()) else {
+                continue;
+            };
+            let Some(cutoff) = i64::try_from(max_age).ok()
+                .and_then(chrono::Duration::try_seconds)
+                .and_then(|age| now.checked_sub_signed(age)) else {
+                continue;
+            };
+            
+            let mut book = book.write().unwrap();
+            let first = expired.len();
+            for order in book.orders_created_before(cutoff) {
+                if let Some(order) = book.remove_order(order.id, order.side) {
+                    let mut order = order.as_ref().clone();
+                    order.expire(RejectReason::MaxAge, format!("Rested longer than {} seconds", max_age), now);
+                    expired.push(Arc::new(order));
+                }
+            }
+            self.events.publish(|| expired[first..].iter().cloned().map(EngineEvent::OrderExpired).collect());
+            self.verify(book, "expiring stale orders");
+        }
+        
+        if !expired.is_empty() {
+            info!("Expired {} orders past their maximum age", expired.len());
+        }
+        expired
+    }
+    
+    /// Get the session state a market is in
+    pub fn session_state(&self, market: &str) -> SessionState {
+        self.sessions.get(market).map(|state| *state).unwrap_or(SessionState::Open)
+    }
+    
+    /// Get a market's session, calendar and next scheduled change as of `now`
+    pub fn market_session(&self, market: &str, now: DateTime<Utc>) -> Result<MarketSession> {
+        if !self.order_books.contains_key(market) {
+            return Err(Error::MarketNotFound(format!("Market not found: {}", market)));
+        }
+        
+        let schedule = self.schedules.get(market).map(|schedule| schedule.clone());
+        let next = schedule.as_ref().and_then(|schedule| schedule.next_transition(now));
+        Ok(MarketSession {
+            market: market.to_string(),
+            state: self.session_state(market),
+            schedule,
+            next_state: next.map(|(_, state)| state),
+            next_transition: next.map(|(at, _)| at),
+        })
+    }
+    
+    /// Move every market into the session state its calendar sets for `now`
+    ///
+    /// A market leaving an auction is uncrossed at the single price that
+    /// executes the most quantity. Returns each change with the auction's
+    /// fills, which are also published as engine events.
+    pub fn update_sessions(&self, now: DateTime<Utc>) -> Vec<(SessionChange, MatchingResult)> {
+        let books: Vec<(String, Arc<RwLock<OrderBook>>)> = self.order_books
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        
+        let mut changes = Vec::new();
+        for (market, book) in books {
+            let state = self.schedules.get(&market)
+                .map(|schedule| schedule.state_at(now))
+                .unwrap_or(SessionState::Open);
+            
+            // Hold the book so no order is placed under the old state once it changes
+            let mut book = book.write().unwrap();
+            let previous = self.session_state(&market);
+            if previous == state {
+                continue;
+            }
+            
+            let auction = if previous == SessionState::Auction {
+                self.uncross(&mut book)
+            } else {
+                MatchingResult::default()
+            };
+            if state == SessionState::Open {
+                self.sessions.remove(&market);
+            } else {
+                self.sessions.insert(market.clone(), state);
+            }
+            let change = SessionChange { market, previous, state, at: now };
+            self.events.publish(|| {
+                auction.maker_orders.iter().cloned().map(EngineEvent::OrderUpdated)
+                    .chain(auction.trades.iter().map(|trade| EngineEvent::Trade(Arc::new(trade.clone()))))
+                    .chain(std::iter::once(EngineEvent::SessionChanged(change.clone())))
+                    .collect()
+            });
+            self.verify(book, "a session change");
+            
+            info!("Market {} moved from {:?} to {:?} with {} auction trades", change.market, previous, state, auction.trades.len());
+            changes.push((change, auction));
+        }
+        
+        changes
+    }
+    
     /// Get an order by ID
     pub fn get_order(&self, order_id: Uuid) -> Option<Arc<Order>> {
         // Search in all order books
@@ -72,6 +460,14 @@ impl MatchingEngine {
         None
     }
     
+    /// Get every resting order of an account in all markets
+    pub fn get_open_orders(&self, account_id: Uuid) -> Vec<Arc<Order>> {
+        self.order_books
+            .iter()
+            .flat_map(|book_entry| book_entry.value().read().unwrap().orders_for_user(account_id))
+            .collect()
+    }
+    
     /// Cancel an order
     pub fn cancel_order(&self, order_id: Uuid) -> Result<Arc<Order>> {
         // First, find the order
@@ -80,6 +476,8 @@ impl MatchingEngine {
             None => return Err(Error::OrderNotFound(format!("Order not found: {}", order_id))),
         };
         
+        self.throttle.check_cancel(original_order.user_id, &original_order.market)?;
+        
         // Find the order book for this market
         if let Some(book_entry) = self.order_books.get(&original_order.market) {
             let mut book = book_entry.write().unwrap();
@@ -87,19 +485,70 @@ impl MatchingEngine {
             // Remove the order from the book
             if let Some(order) = book.remove_order(order_id, original_order.side) {
                 // Create a canceled version of the order
-                let canceled_order = Order {
+                let canceled_order = Arc::new(Order {
                     status: Status::Cancelled,
-                    updated_at: Utc::now(),
+                    updated_at: self.clock.now(),
                     ..(*order).clone()
-                };
-                
-                return Ok(Arc::new(canceled_order));
+                });
+                let repriced = self.reprice_pegs(&mut book, self.clock.now());
+                self.events.publish(|| {
+                    std::iter::once(EngineEvent::OrderCancelled(canceled_order.clone()))
+                        .chain(repriced.into_iter().map(EngineEvent::OrderUpdated))
+                        .collect()
+                });
+                self.verify(book, "cancelling an order");
+                return Ok(canceled_order);
             }
         }
         
         Err(Error::OrderNotFound(format!("Order not found in book: {}", order_id)))
     }
     
+    /// Bar an account from placing new orders, returning false if already blocked
+    pub fn block_account(&self, account_id: Uuid) -> bool {
+        info!("Blocking new orders for account {}", account_id);
+        self.blocked_accounts.insert(account_id)
+    }
+    
+    /// Allow a blocked account to place orders again, returning false if it was not blocked
+    pub fn unblock_account(&self, account_id: Uuid) -> bool {
+        info!("Unblocking new orders for account {}", account_id);
+        self.blocked_accounts.remove(&account_id).is_some()
+    }
+    
+    /// Check whether an account is barred from placing new orders
+    pub fn is_account_blocked(&self, account_id: Uuid) -> bool {
+        self.blocked_accounts.contains(&account_id)
+    }
+    
+    /// Cancel every resting order of an account in all markets
+    ///
+    /// Not subject to the cancel throttle, so a runaway account can always be
+    /// pulled from the book.
+    pub fn cancel_account_orders(&self, account_id: Uuid) -> Vec<Arc<Order>> {
+        let mut cancelled = Vec::new();
+        
+        for book_entry in self.order_books.iter() {
+            let mut book = book_entry.value().write().unwrap();
+            let first = cancelled.len();
+            
+            for order in book.orders_for_user(account_id) {
+                if let Some(order) = book.remove_order(order.id, order.side) {
+                    cancelled.push(Arc::new(Order {
+                        status: Status::Cancelled,
+                        updated_at: self.clock.now(),
+                        ..(*order).clone()
+                    }));
+                }
+            }
+            self.events.publish(|| cancelled[first..].iter().cloned().map(EngineEvent::OrderCancelled).collect());
+            self.verify(book, "cancelling an account's orders");
+        }
+        
+        info!("Cancelled {} orders for account {}", cancelled.len(), account_id);
+        cancelled
+    }
+    
     /// Get market depth
     pub fn get_market_depth(&self, market: &str, limit: usize) -> Result<(Vec<(Price, Quantity)>, Vec<(Price, Quantity)>)> {
         if let Some(book_entry) = self.order_books.get(market) {
@@ -114,9 +563,74 @@ impl MatchingEngine {
             Err(Error::MarketNotFound(format!("Market not found: {}", market)))
         }
     }
+
+    /// Sequence number of a market's last order, 0 before the first
+    pub fn last_order_sequence(&self, market: &str) -> Result<u64> {
+        let book_entry = self.order_books.get(market)
+            .ok_or_else(|| Error::MarketNotFound(format!("Market not found: {}", market)))?;
+        let sequence = book_entry.read().unwrap().last_order_sequence();
+        Ok(sequence)
+    }
+    
+    /// Sequence number of a market's last trade, 0 before the first
+    pub fn last_trade_sequence(&self, market: &str) -> Result<u64> {
+        let book_entry = self.order_books.get(market)
+            .ok_or_else(|| Error::MarketNotFound(format!("Market not found: {}", market)))?;
+        let sequence = book_entry.read().unwrap().last_trade_sequence();
+        Ok(sequence)
+    }
+    
+    /// Number a market's trades on from `sequence`, the last one stored
+    /// before a restart, so sequence numbers are never reused
+    pub fn resume_trade_sequence(&self, market: &str, sequence: u64) -> Result<()> {
+        let book_entry = self.order_books.get(market)
+            .ok_or_else(|| Error::MarketNotFound(format!("Market not found: {}", market)))?;
+        book_entry.write().unwrap().resume_trade_sequence(sequence);
+        Ok(())
+    }
+    
+    /// A market's trades numbered after `sequence`, oldest first, as far
+    /// back as the engine keeps them
+    pub fn trades_after(&self, market: &str, sequence: u64) -> Result<Vec<Trade>> {
+        let book_entry = self.order_books.get(market)
+            .ok_or_else(|| Error::MarketNotFound(format!("Market not found: {}", market)))?;
+        let trades = book_entry.read().unwrap().trades_after(sequence);
+        Ok(trades)
+    }
+    
+    /// Resting orders, levels, best prices and sequence numbers of a market's book
+    pub fn book_stats(&self, market: &str) -> Result<BookStats> {
+        let book_entry = self.order_books.get(market)
+            .ok_or_else(|| Error::MarketNotFound(format!("Market not found: {}", market)))?;
+        let book = book_entry.read().unwrap();
+        Ok(BookStats {
+            bid_orders: book.bids().orders().count(),
+            ask_orders: book.asks().orders().count(),
+            bid_levels: book.level_count(Side::Buy),
+            ask_levels: book.level_count(Side::Sell),
+            best_bid: book.best_bid(),
+            best_ask: book.best_ask(),
+            last_order_sequence: book.last_order_sequence(),
+            last_trade_sequence: book.last_trade_sequence(),
+        })
+    }
+    
+    /// Symbols of the registered markets
+    pub fn markets(&self) -> Vec<String> {
+        self.order_books.iter().map(|entry| entry.key().clone()).collect()
+    }
     
-    /// Process an incoming order
-    pub fn place_order(&self, order: Order) -> Result<MatchingResult> {
+    /// Get a market's mark price: the mid of the book, or the last trade price
+    /// while one side is empty
+    pub fn mark_price(&self, market: &str) -> Result<Option<Price>> {
+        let book_entry = self.order_books.get(market)
+            .ok_or_else(|| Error::MarketNotFound(format!("Market not found: {}", market)))?;
+        let book = book_entry.read().unwrap();
+        Ok(book.mid_price())
+    }
+
+    /// Process an incoming order, stamped with the time it is accepted
+    pub fn place_order(&self, mut order: Order) -> Result<MatchingResult> {
         // Check if we have an order book for this market
         let order_book = match self.order_books.get(&order.market) {
             Some(ob) => ob.clone(),
@@ -125,29 +639,124 @@ impl MatchingEngine {
             }
         };
         
-        // Clone the order into an Arc for thread-safe sharing
-        let order = Arc::new(order);
+        let in_auction = self.admit(&order)?;
+        if order.peg.is_some() && (in_auction || order.order_type != OrderType::Limit || order.time_in_force != TimeInForce::GTC) {
+            return Err(Error::InvalidOrder(
+                "Only good-til-cancelled limit orders can be pegged, and not during an auction".to_string()
+            ));
+        }
+        if let Some(min) = order.min_fill_quantity {
+            if in_auction {
+                return Err(Error::InvalidOrder("Auction orders cannot have a minimum fill quantity".to_string()));
+            }
+            if min <= Quantity::ZERO || min > order.quantity {
+                return Err(Error::InvalidOrder(format!(
+                    "Minimum fill quantity {} must be positive and at most the order quantity {}", min, order.quantity
+                )));
+            }
+        }
+        
+        self.throttle.check_order(order.user_id, &order.market)?;
         
-        // Execute the order based on type
-        match order.order_type {
+        order.created_at = self.clock.now();
+        order.updated_at = order.created_at;
+        
+        // Execute the order based on type; the taker is only shared once it is final
+        let mut book = order_book.write().unwrap();
+        let mut result = match order.order_type {
+            _ if in_auction => {
+                debug!("Collecting auction order: {}", order.id);
+                self.collect_auction_order(order, &mut book)?
+            },
             OrderType::Market => {
                 debug!("Processing market order: {}", order.id);
-                self.execute_market_order(order, order_book)
+                self.execute_market_order(order, &mut book)?
             },
             OrderType::Limit => {
                 debug!("Processing limit order: {}", order.id);
-                self.execute_limit_order(order, order_book)
+                self.execute_limit_order(order, &mut book)?
             }
+        };
+        // Pegged orders follow the book the order moved
+        result.repriced_orders = self.reprice_pegs(&mut book, self.clock.now());
+        
+        self.events.publish(|| {
+            result.expired_orders.iter().cloned().map(EngineEvent::OrderExpired)
+                .chain(result.taker_order.iter().cloned().map(EngineEvent::OrderPlaced))
+                .chain(result.maker_orders.iter().cloned().map(EngineEvent::OrderUpdated))
+                .chain(result.trades.iter().map(|trade| EngineEvent::Trade(Arc::new(trade.clone()))))
+                .chain(result.repriced_orders.iter().cloned().map(EngineEvent::OrderUpdated))
+                .collect()
+        });
+        self.verify(book, "placing an order");
+        
+        Ok(result)
+    }
+    
+    /// Fills an order would get if placed now, best price first, without
+    /// placing it
+    ///
+    /// Runs the same account and session checks as [`place_order`](Self::place_order),
+    /// but takes no throttle token. Orders collected for an auction, pegged
+    /// orders, which never take liquidity, fill-or-kill orders that cannot
+    /// fill in full and orders short of their minimum fill get no fills.
+    pub fn preview_order(&self, order: &Order) -> Result<Vec<(Price, Quantity)>> {
+        let order_book = self.order_books.get(&order.market)
+            .map(|book| book.clone())
+            .ok_or_else(|| Error::MarketNotFound(format!("Market not found: {}", order.market)))?;
+        if self.admit(order)? || order.peg.is_some() {
+            return Ok(Vec::new());
+        }
+        
+        let order_book = order_book.read().unwrap();
+        let limit_price = price_limit(order, &order_book);
+        let short = order.time_in_force == TimeInForce::FOK
+            && order_book.fillable_quantity(order.side, limit_price, order.remaining_quantity) < order.remaining_quantity;
+        if short || short_of_minimum(order, &order_book, limit_price).is_some() {
+            return Ok(Vec::new());
+        }
+        Ok(order_book.estimate_fills(order.side, limit_price, order.remaining_quantity))
+    }
+    
+    /// Fills a taker on `side` for `quantity` would get from the current book,
+    /// best price first, with no price limit
+    ///
+    /// Unlike [`preview_order`](Self::preview_order) there is no order, so no
+    /// account or session checks are made.
+    pub fn estimate_impact(&self, market: &str, side: Side, quantity: Quantity) -> Result<Vec<(Price, Quantity)>> {
+        let order_book = self.order_books.get(market)
+            .map(|book| book.clone())
+            .ok_or_else(|| Error::MarketNotFound(format!("Market not found: {}", market)))?;
+        let order_book = order_book.read().unwrap();
+        Ok(order_book.estimate_fills(side, None, quantity))
+    }
+    
+    /// Check that an order's account may trade and its market accepts it,
+    /// returning whether the market is collecting orders for an auction
+    fn admit(&self, order: &Order) -> Result<bool> {
+        if self.is_account_blocked(order.user_id) {
+            return Err(Error::AuthorizationError(format!(
+                "Account {} is blocked from placing orders", order.user_id
+            )));
+        }
+        
+        match self.session_state(&order.market) {
+            SessionState::Open => Ok(false),
+            SessionState::Closed => Err(Error::InvalidOrder(format!("Market {} is closed", order.market))),
+            SessionState::Auction if order.order_type != OrderType::Limit || order.time_in_force != TimeInForce::GTC => {
+                Err(Error::InvalidOrder(format!(
+                    "Market {} is in an auction, only good-til-cancelled limit orders are accepted", order.market
+                )))
+            }
+            SessionState::Auction => Ok(true),
         }
     }
     
     /// Execute a market order
-    fn execute_market_order(&self, order: Arc<Order>, order_book: Arc<RwLock<OrderBook>>) -> Result<MatchingResult> {
+    fn execute_market_order(&self, mut order: Order, order_book: &mut OrderBook) -> Result<MatchingResult> {
         let side = order.side;
         let mut result = MatchingResult::default();
-        
-        // Get exclusive access to the order book
-        let mut order_book = order_book.write().unwrap();
+        order_book.record_order(&mut order);
         
         // Check if the order book is empty on the opposite side
         let is_empty = match side {
@@ -156,289 +765,338 @@ impl MatchingEngine {
         };
         
         if is_empty {
-            return Err(Error::ValidationError(format!(
-                "Cannot execute market {} order, no liquidity", 
-                if side == Side::Buy { "buy" } else { "sell" }
-            )));
+            order.reject(
+                RejectReason::NoLiquidity,
+                format!("Cannot execute market {} order, no liquidity", side_name(side)),
+                self.clock.now(),
+            );
+            debug!("Market order {} rejected, no liquidity", order.id);
+            
+            result.taker_order = Some(Arc::new(order));
+            return Ok(result);
         }
         
-        // Match against the opposite side of the book
-        let (matched_order, matched_makers, trades) = match side {
-            Side::Buy => {
-                self.match_against_asks(order.clone(), &mut order_book)
-            },
-            Side::Sell => {
-                self.match_against_bids(order.clone(), &mut order_book)
-            }
-        };
-        
-        result.taker_order = matched_order;
-        result.maker_orders = matched_makers;
-        result.trades = trades;
+        // Match against the opposite side of the book, up to the order's price cap
+        result.price_cap = price_limit(&order, order_book);
+        if let Some(message) = short_of_minimum(&order, order_book, result.price_cap) {
+            order.reject(RejectReason::MinFillQuantity, message, self.clock.now());
+            debug!("Market order {} rejected, minimum fill unavailable", order.id);
+            result.taker_order = Some(Arc::new(order));
+            return Ok(result);
+        }
+        self.match_order(&mut order, order_book, &mut result);
         
-        // Since this is a market order, if it's not fully filled, the remainder is canceled
-        if let Some(ref taker) = result.taker_order {
-            if !taker.is_filled() {
-                // TODO: In a real system, we'd update the order status to Canceled in the database
-                debug!("Market order {} partially filled, canceling remainder", taker.id);
+        // Since this is a market order, if it's not fully filled, the remainder expires
+        if !order.is_filled() {
+            debug!("Market order {} partially filled, expiring remainder", order.id);
+            let remaining = order.remaining_quantity;
+            let liquidity_left = match side {
+                Side::Buy => order_book.best_ask().is_some(),
+                Side::Sell => order_book.best_bid().is_some(),
+            };
+            match result.price_cap {
+                Some(cap) if liquidity_left => order.expire(
+                    RejectReason::PriceCap,
+                    format!("Next price level breaches the cap of {}, remaining {} cancelled", cap, remaining),
+                    self.clock.now(),
+                ),
+                _ => order.expire(
+                    RejectReason::MarketOrderUnfilled,
+                    format!("Insufficient liquidity, remaining {} expired", remaining),
+                    self.clock.now(),
+                ),
             }
         }
+        result.taker_order = Some(Arc::new(order));
         
         Ok(result)
     }
     
     /// Execute a limit order
-    fn execute_limit_order(&self, order: Arc<Order>, order_book: Arc<RwLock<OrderBook>>) -> Result<MatchingResult> {
+    fn execute_limit_order(&self, mut order: Order, order_book: &mut OrderBook) -> Result<MatchingResult> {
         let side = order.side;
         let mut result = MatchingResult::default();
+        order_book.record_order(&mut order);
         
-        // Get exclusive access to the order book
-        let mut order_book = order_book.write().unwrap();
+        // Pegged orders rest at the price their peg gives, which never matches
+        if let Some(peg) = order.peg {
+            let Some(price) = order_book.peg_price(side, &peg) else {
+                order.reject(RejectReason::PegUnavailable, format!("No {} price to peg the order to", peg_name(&peg)), self.clock.now());
+                debug!("Pegged order {} rejected, no reference price", order.id);
+                result.taker_order = Some(Arc::new(order));
+                return Ok(result);
+            };
+            order.price = Some(price);
+        }
         
         // Check if this order can match immediately
         let price = order.price.expect("Limit orders must have a price");
-        let can_match = match side {
-            Side::Buy => order_book.best_ask().map_or(false, |ask| price >= ask),
-            Side::Sell => order_book.best_bid().map_or(false, |bid| price <= bid),
-        };
         
-        if can_match {
-            // Match against the opposite side of the book
-            let (matched_order, matched_makers, trades) = match side {
-                Side::Buy => {
-                    self.match_against_asks(order.clone(), &mut order_book)
-                },
-                Side::Sell => {
-                    self.match_against_bids(order.clone(), &mut order_book)
-                }
-            };
+        // Fill-or-kill orders never touch the book unless they can fill in full
+        if order.time_in_force == TimeInForce::FOK
+            && order_book.fillable_quantity(side, Some(price), order.remaining_quantity) < order.remaining_quantity
+        {
+            let remaining = order.remaining_quantity;
+            order.expire(
+                RejectReason::FillOrKill,
+                format!("Insufficient liquidity to fill {} at {}", remaining, price),
+                self.clock.now(),
+            );
+            debug!("Fill-or-kill order {} expired", order.id);
             
-            result.taker_order = matched_order;
-            result.maker_orders = matched_makers;
-            result.trades = trades;
-            
-            // If the order wasn't fully filled and it's GTC, add the remainder to the book
-            if let Some(ref taker) = result.taker_order {
-                if !taker.is_filled() && taker.time_in_force == TimeInForce::GTC {
-                    debug!("Adding remaining limit order to the book: {}", taker.id);
-                    order_book.add_order(taker.clone());
-                }
+            result.taker_order = Some(Arc::new(order));
+            return Ok(result);
+        }
+        
+        // Orders with a minimum fill trade at least that much or not at all;
+        // GTC orders may still rest if they cross nothing
+        let crosses = order_book.would_match(price, side);
+        if order.time_in_force != TimeInForce::GTC || crosses {
+            if let Some(message) = short_of_minimum(&order, order_book, Some(price)) {
+                order.reject(RejectReason::MinFillQuantity, message, self.clock.now());
+                debug!("Limit order {} rejected, minimum fill unavailable", order.id);
+                result.taker_order = Some(Arc::new(order));
+                return Ok(result);
             }
-        } else {
-            // No immediate match, add to the book if GTC
-            if order.time_in_force == TimeInForce::GTC {
-                debug!("Adding limit order to the book: {}", order.id);
-                order_book.add_order(order.clone());
+        }
+        
+        let blocked = crosses && self.match_order(&mut order, order_book, &mut result);
+        
+        // Rest the remainder of GTC orders within the book limits, expire everything else
+        if order.is_filled() {
+            result.taker_order = Some(Arc::new(order));
+        } else if order.time_in_force == TimeInForce::GTC && blocked {
+            // Matching stopped at an order whose minimum fill this one cannot meet
+            let remaining = order.remaining_quantity;
+            order.expire(
+                RejectReason::MinFillQuantity,
+                format!("Remaining {} would cross an order waiting for its minimum fill", remaining),
+                self.clock.now(),
+            );
+            debug!("Limit order {} expired, would cross the book", order.id);
+            result.taker_order = Some(Arc::new(order));
+        } else if order.time_in_force == TimeInForce::GTC && order_book.would_match(price, side) {
+            // Only a matching bug leaves a remainder that could still trade
+            error!("Limit order {} refused, resting at {} would cross the book of {}", order.id, price, order.market);
+            self.verifier.refused(&order.market);
+            order.expire(RejectReason::CrossedBook, format!("Resting at {} would cross the book", price), self.clock.now());
+            result.taker_order = Some(Arc::new(order));
+        } else if order.time_in_force == TimeInForce::GTC {
+            if let Some(message) = self.admit_resting(order_book, &order, &mut result.expired_orders) {
+                debug!("Limit order {} expired: {}", order.id, message);
+                order.expire(RejectReason::BookLimit, message, self.clock.now());
+                result.taker_order = Some(Arc::new(order));
+                return Ok(result);
             }
             
+            debug!("Adding limit order to the book: {}", order.id);
+            let order = Arc::new(order);
+            order_book.add_order(order.clone());
             result.taker_order = Some(order);
+        } else {
+            let remaining = order.remaining_quantity;
+            order.expire(
+                RejectReason::ImmediateOrCancel,
+                format!("Remaining {} could not be filled immediately", remaining),
+                self.clock.now(),
+            );
+            debug!("Immediate-or-cancel order {} expired", order.id);
+            result.taker_order = Some(Arc::new(order));
         }
         
         Ok(result)
     }
     
-    /// Match an order against the ask side of the book
-    fn match_against_asks(
-        &self,
-        taker: Arc<Order>,
-        order_book: &mut OrderBook,
-    ) -> (Option<Arc<Order>>, Vec<Arc<Order>>, Vec<Trade>) {
-        let mut matched_makers = Vec::new();
-        let mut trades = Vec::new();
-        let mut taker_quantity = taker.remaining_quantity;
-        let mut taker_filled = false;
-        
-        // Create a mutable clone of the taker order
-        let mut taker_clone = Order {
-            remaining_quantity: taker_quantity,
-            filled_quantity: taker.filled_quantity,
-            average_fill_price: taker.average_fill_price,
-            status: taker.status,
-            updated_at: taker.updated_at,
-            ..taker.as_ref().clone()
-        };
+    /// Rest an order on the book of a market in an auction without matching it
+    fn collect_auction_order(&self, mut order: Order, order_book: &mut OrderBook) -> Result<MatchingResult> {
+        // The auction may have ended while the order waited for the book
+        if self.session_state(&order.market) != SessionState::Auction {
+            return Err(Error::InvalidOrder(format!("The auction of {} has ended", order.market)));
+        }
+        order_book.record_order(&mut order);
+        let order = Arc::new(order);
         
-        // While we have quantity to fill and there are matching asks
-        while taker_quantity > Quantity::ZERO {
-            // Get the best ask
-            let best_ask = match order_book.best_ask() {
-                Some(price) => price,
-                None => break, // No more asks to match against
-            };
-            
-            // For limit orders, check if the price is acceptable
-            if taker.order_type == OrderType::Limit {
-                let limit_price = taker.price.unwrap();
-                if limit_price < best_ask {
-                    break; // Best ask is higher than our limit price
-                }
+        let mut result = MatchingResult::default();
+        if let Some(message) = self.admit_resting(order_book, &order, &mut result.expired_orders) {
+            let mut expired = order.as_ref().clone();
+            expired.expire(RejectReason::BookLimit, message, self.clock.now());
+            result.taker_order = Some(Arc::new(expired));
+            return Ok(result);
+        }
+        
+        order_book.add_order(order.clone());
+        result.taker_order = Some(order);
+        Ok(result)
+    }
+    
+    /// Check an order about to rest against its market's book limits
+    ///
+    /// Returns why the order may not rest. Under [`LevelPolicy::PruneFarthest`]
+    /// the orders of the farthest level are removed to make room for a better
+    /// priced one and added to `expired`, unless the `book_level_pruning` flag
+    /// is off.
+    fn admit_resting(&self, order_book: &mut OrderBook, order: &Order, expired: &mut Vec<Arc<Order>>) -> Option<String> {
+        let limits = self.book_limits.get(&order.market)?.clone();
+        let price = order.price?;
+        
+        if let Some(max) = limits.max_orders_per_account {
+            if order_book.account_order_count(order.user_id) >= max {
+                return Some(format!("Account already has {} resting orders on the book", max));
             }
-            
-            // Get the first maker order at the best ask price
-            if let Some(maker) = order_book.get_first_ask_order(best_ask) {
-                // Calculate the match quantity
-                let match_quantity = Quantity::min(taker_quantity, maker.remaining_quantity);
-                
-                // Create a trade
-                let trade = self.create_trade(
-                    best_ask,
-                    match_quantity,
-                    &taker.market,
-                    taker.id,
-                    maker.id,
-                    taker.user_id,
-                    maker.user_id,
-                    Side::Buy, // Taker is buying, so taker side is Buy
-                );
-                
-                // Update taker
-                taker_quantity -= match_quantity;
-                taker_clone.remaining_quantity = taker_quantity;
-                taker_clone.filled_quantity += match_quantity;
-                
-                // Calculate new average fill price
-                let total_filled_amount = taker_clone.average_fill_price
-                    .map_or(Quantity::ZERO, |p| p * taker_clone.filled_quantity);
-                let match_amount = best_ask * match_quantity;
-                let new_total_amount = total_filled_amount + match_amount;
-                taker_clone.average_fill_price = Some(new_total_amount / taker_clone.filled_quantity);
-                
-                // Update maker (in a real system, this would be persisted)
-                // For now we just track them for the result
-                matched_makers.push(maker.clone());
-                
-                // Add the trade to the result
-                trades.push(trade);
-                
-                // Update the order book's last price
-                order_book.set_last_price(best_ask);
-                
-                // Remove filled maker orders from the book
-                if maker.remaining_quantity == match_quantity {
-                    order_book.remove_order(maker.id, Side::Sell);
+        }
+        
+        if let (Some(max_bps), Some(mid)) = (limits.max_distance_bps, order_book.mid_price()) {
+            if !mid.is_zero() && (price - mid).abs() * Decimal::from(10_000) > mid * Decimal::from(max_bps) {
+                return Some(format!("Price {} is more than {} bps from the mid price {}", price, max_bps, mid));
+            }
+        }
+        
+        if let Some(max) = limits.max_levels {
+            if !order_book.has_level(order.side, price) && order_book.level_count(order.side) >= max {
+                let farthest = order_book.farthest_price(order.side)?;
+                let better = match order.side {
+                    Side::Buy => price > farthest,
+                    Side::Sell => price < farthest,
+                };
+                let prune = limits.level_policy == LevelPolicy::PruneFarthest && self.flags.is_enabled(BOOK_LEVEL_PRUNING);
+                if !prune || !better {
+                    return Some(format!("The book already holds {} {} levels", max, side_name(order.side)));
                 }
                 
-                // Check if taker is filled
-                if taker_quantity == Quantity::ZERO {
-                    taker_filled = true;
-                    break;
+                for pruned in order_book.remove_level(order.side, farthest) {
+                    let mut pruned = pruned.as_ref().clone();
+                    pruned.expire(RejectReason::BookLimit, format!("Pruned to make room for a better priced order at {}", price), self.clock.now());
+                    expired.push(Arc::new(pruned));
                 }
+                debug!("Pruned {} level {} of {}", side_name(order.side), farthest, order.market);
             }
         }
         
-        // Update taker status
-        if taker_filled {
-            taker_clone.status = Status::Filled;
-        } else if taker_clone.filled_quantity > Quantity::ZERO {
-            taker_clone.status = Status::PartiallyFilled;
-        }
-        
-        taker_clone.updated_at = Utc::now();
-        
-        // Return updated taker order and trades
-        (Some(Arc::new(taker_clone)), matched_makers, trades)
+        None
     }
     
-    /// Match an order against the bid side of the book
-    fn match_against_bids(
-        &self,
-        taker: Arc<Order>,
-        order_book: &mut OrderBook,
-    ) -> (Option<Arc<Order>>, Vec<Arc<Order>>, Vec<Trade>) {
-        let mut matched_makers = Vec::new();
-        let mut trades = Vec::new();
-        let mut taker_quantity = taker.remaining_quantity;
-        let mut taker_filled = false;
-        
-        // Create a mutable clone of the taker order
-        let mut taker_clone = Order {
-            remaining_quantity: taker_quantity,
-            filled_quantity: taker.filled_quantity,
-            average_fill_price: taker.average_fill_price,
-            status: taker.status,
-            updated_at: taker.updated_at,
-            ..taker.as_ref().clone()
+    /// Match crossed orders left by an auction at a single price
+    ///
+    /// Fills follow price-time priority. The later order of each pair is
+    /// treated as the taker for fees.
+    fn uncross(&self, order_book: &mut OrderBook) -> MatchingResult {
+        let mut result = MatchingResult::default();
+        let Some(price) = auction_price(order_book) else {
+            return result;
         };
+        let now = self.clock.now();
         
-        // While we have quantity to fill and there are matching bids
-        while taker_quantity > Quantity::ZERO {
-            // Get the best bid
-            let best_bid = match order_book.best_bid() {
-                Some(price) => price,
-                None => break, // No more bids to match against
+        while let (Some(bid), Some(ask)) = (order_book.best_bid(), order_book.best_ask()) {
+            if bid < price || ask > price {
+                break;
+            }
+            let (Some(buy), Some(sell)) = (order_book.get_first_bid_order(bid), order_book.get_first_ask_order(ask)) else {
+                break;
             };
             
-            // For limit orders, check if the price is acceptable
-            if taker.order_type == OrderType::Limit {
-                let limit_price = taker.price.unwrap();
-                if limit_price > best_bid {
-                    break; // Best bid is lower than our limit price
+            let quantity = Quantity::min(buy.remaining_quantity, sell.remaining_quantity);
+            let taker_side = if sell.created_at > buy.created_at { Side::Sell } else { Side::Buy };
+            let mut trade = self.create_trade(
+                price,
+                quantity,
+                &order_book.market,
+                buy.id,
+                sell.id,
+                buy.user_id,
+                sell.user_id,
+                taker_side,
+            );
+            order_book.record_trade(&mut trade);
+            result.trades.push(trade);
+            
+            for order in [fill_at(&buy, quantity, price, now), fill_at(&sell, quantity, price, now)] {
+                if order.is_filled() {
+                    order_book.remove_order(order.id, order.side);
+                } else {
+                    order_book.replace_order(order.clone());
                 }
+                result.maker_orders.push(order);
             }
+        }
+        
+        result
+    }
+    
+    /// Match a taker against the opposite side of the book, updating it in place
+    ///
+    /// Fills follow price priority, are shared within a level by the market's
+    /// allocation, and stop at the taker's limit price or price cap. Makers
+    /// whose minimum fill quantity the taker cannot meet are passed over and
+    /// keep their place. Matched makers and trades are appended to `result`.
+    ///
+    /// Returns whether a remainder is left that would cross such a maker.
+    fn match_order(&self, taker: &mut Order, order_book: &mut OrderBook, result: &mut MatchingResult) -> bool {
+        let now = self.clock.now();
+        let limit_price = price_limit(taker, order_book);
+        let maker_side = match taker.side {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        };
+        let mut passed_over = false;
+        
+        let mut level = order_book.next_level(taker.side, None, limit_price);
+        while let Some(price) = level.filter(|_| !taker.remaining_quantity.is_zero()) {
+            // Maker orders at this price and what each fills, by the market's allocation
+            let (fills, passed) = order_book.allocate_accepted(maker_side, price, taker.remaining_quantity);
+            passed_over |= passed;
             
-            // Get the first maker order at the best bid price
-            if let Some(maker) = order_book.get_first_bid_order(best_bid) {
-                // Calculate the match quantity
-                let match_quantity = Quantity::min(taker_quantity, maker.remaining_quantity);
-                
-                // Create a trade
-                let trade = self.create_trade(
-                    best_bid,
-                    match_quantity,
-                    &taker.market,
-                    maker.id,
-                    taker.id,
-                    maker.user_id,
-                    taker.user_id,
-                    Side::Sell, // Taker is selling, so taker side is Sell
-                );
-                
-                // Update taker
-                taker_quantity -= match_quantity;
-                taker_clone.remaining_quantity = taker_quantity;
-                taker_clone.filled_quantity += match_quantity;
-                
-                // Calculate new average fill price
-                let total_filled_amount = taker_clone.average_fill_price
-                    .map_or(Quantity::ZERO, |p| p * taker_clone.filled_quantity);
-                let match_amount = best_bid * match_quantity;
-                let new_total_amount = total_filled_amount + match_amount;
-                taker_clone.average_fill_price = Some(new_total_amount / taker_clone.filled_quantity);
-                
-                // Update maker (in a real system, this would be persisted)
-                // For now we just track them for the result
-                matched_makers.push(maker.clone());
-                
-                // Add the trade to the result
-                trades.push(trade);
-                
-                // Update the order book's last price
-                order_book.set_last_price(best_bid);
-                
-                // Remove filled maker orders from the book
-                if maker.remaining_quantity == match_quantity {
-                    order_book.remove_order(maker.id, Side::Buy);
-                }
+            for (maker, quantity) in fills {
+                let mut trade = match taker.side {
+                    Side::Buy => self.create_trade(
+                        price, quantity, &taker.market, taker.id, maker.id, taker.user_id, maker.user_id, Side::Buy,
+                    ),
+                    Side::Sell => self.create_trade(
+                        price, quantity, &taker.market, maker.id, taker.id, maker.user_id, taker.user_id, Side::Sell,
+                    ),
+                };
+                apply_fill(taker, quantity, price, now);
                 
-                // Check if taker is filled
-                if taker_quantity == Quantity::ZERO {
-                    taker_filled = true;
-                    break;
+                // Update maker, keeping partially filled makers at their queue position
+                let maker = fill_at(&maker, quantity, price, now);
+                if maker.is_filled() {
+                    order_book.remove_order(maker.id, maker.side);
+                } else {
+                    order_book.replace_order(maker.clone());
                 }
+                result.maker_orders.push(maker);
+                order_book.record_trade(&mut trade);
+                result.trades.push(trade);
             }
+            
+            level = order_book.next_level(taker.side, Some(price), limit_price);
         }
         
-        // Update taker status
-        if taker_filled {
-            taker_clone.status = Status::Filled;
-        } else if taker_clone.filled_quantity > Quantity::ZERO {
-            taker_clone.status = Status::PartiallyFilled;
+        taker.updated_at = now;
+        passed_over && !taker.remaining_quantity.is_zero()
+    }
+    
+    /// Check that a book outside an auction is not crossed after `command`
+    ///
+    /// The book's lock is released before the check, so a crossed book in
+    /// `Assert` mode panics without poisoning it for later commands.
+    fn verify(&self, order_book: RwLockWriteGuard<'_, OrderBook>, command: &str) {
+        if self.session_state(&order_book.market) == SessionState::Auction {
+            return;
         }
-        
-        taker_clone.updated_at = Utc::now();
-        
-        // Return updated taker order and trades
-        (Some(Arc::new(taker_clone)), matched_makers, trades)
+        let (market, best_bid, best_ask) = (order_book.market.clone(), order_book.best_bid(), order_book.best_ask());
+        drop(order_book);
+        self.verifier.check(&market, best_bid, best_ask, command, self.clock.now());
+    }
+    
+    /// Re-price a book's pegged orders, unless they were less than the
+    /// re-pricing interval ago
+    fn reprice_pegs(&self, order_book: &mut OrderBook, now: DateTime<Utc>) -> Vec<Arc<Order>> {
+        let due = order_book.has_pegged_orders()
+            && order_book.pegs_repriced_at().is_none_or(|at| now - at >= self.peg_interval);
+        if !due {
+            return Vec::new();
+        }
+        order_book.reprice_pegged_orders(now)
     }
     
     /// Create a trade from a match
@@ -453,18 +1111,123 @@ impl MatchingEngine {
         seller_id: Uuid,
         taker_side: Side,
     ) -> Trade {
-        Trade {
-            id: Uuid::new_v4(),
-            market: market.to_string(),
+        let mut trade = Trade::new(
+            market.to_string(),
             price,
             quantity,
-            amount: price * quantity,
             buyer_order_id,
             seller_order_id,
             buyer_id,
             seller_id,
             taker_side,
-            created_at: Utc::now(),
+        );
+        trade.id = self.ids.next_id();
+        trade.created_at = self.clock.now();
+        self.fee_schedule.apply(&mut trade);
+        trade
+    }
+}
+
+/// Worst price an order may fill at: a limit order's price, or the tighter
+/// of a market order's price cap and its slippage limit from the best
+/// opposite price on arrival
+fn price_limit(order: &Order, order_book: &OrderBook) -> Option<Price> {
+    if order.order_type == OrderType::Limit {
+        return order.price;
+    }
+    let slippage = order.max_slippage_bps.map(|bps| Decimal::from(bps) / Decimal::from(10_000));
+    match order.side {
+        Side::Buy => {
+            let slippage_cap = slippage.zip(order_book.best_ask()).map(|(slippage, ask)| (ask * (Decimal::ONE + slippage)).normalize());
+            [order.price, slippage_cap].into_iter().flatten().min()
+        }
+        Side::Sell => {
+            let slippage_cap = slippage.zip(order_book.best_bid()).map(|(slippage, bid)| (bid * (Decimal::ONE - slippage)).normalize());
+            [order.price, slippage_cap].into_iter().flatten().max()
+        }
+    }
+}
+
+/// Record a fill of `quantity` at `price` on an order
+fn apply_fill(order: &mut Order, quantity: Quantity, price: Price, now: DateTime<Utc>) {
+    // Resting orders fill at their own price, which needs no division
+    order.average_fill_price = match order.average_fill_price {
+        Some(average) if average != price => {
+            let filled_amount = average * order.filled_quantity + price * quantity;
+            Some(filled_amount / (order.filled_quantity + quantity))
         }
+        _ => Some(price),
+    };
+    order.remaining_quantity -= quantity;
+    order.filled_quantity += quantity;
+    order.status = if order.remaining_quantity.is_zero() { Status::Filled } else { Status::PartiallyFilled };
+    order.updated_at = now;
+}
+
+/// Apply a fill at `price` to a resting order
+fn fill_at(order: &Order, quantity: Quantity, price: Price, now: DateTime<Utc>) -> Arc<Order> {
+    let mut order = order.clone();
+    apply_fill(&mut order, quantity, price, now);
+    Arc::new(order)
+}
+
+/// Price at which an auction executes the most quantity, or `None` if the book is not crossed
+///
+/// Ties go to the price leaving the least quantity unmatched, then the one
+/// closest to the last trade price, then the lowest.
+fn auction_price(order_book: &OrderBook) -> Option<Price> {
+    let (best_bid, best_ask) = (order_book.best_bid()?, order_book.best_ask()?);
+    if best_bid < best_ask {
+        return None;
+    }
+    
+    let bids = order_book.bids().price_levels(usize::MAX);
+    let asks = order_book.asks().price_levels(usize::MAX);
+    let distance = |price: Price| order_book.last_price.map_or(Price::ZERO, |last| (price - last).abs());
+    
+    bids.iter()
+        .chain(asks.iter())
+        .map(|(price, _)| *price)
+        .filter(|price| *price >= best_ask && *price <= best_bid)
+        .map(|price| {
+            let demand: Quantity = bids.iter().filter(|(bid, _)| *bid >= price).map(|(_, quantity)| *quantity).sum();
+            let supply: Quantity = asks.iter().filter(|(ask, _)| *ask <= price).map(|(_, quantity)| *quantity).sum();
+            (price, demand.min(supply), (demand - supply).abs())
+        })
+        .min_by(|a, b| {
+            b.1.cmp(&a.1)
+                .then(a.2.cmp(&b.2))
+                .then(distance(a.0).cmp(&distance(b.0)))
+                .then(a.0.cmp(&b.0))
+        })
+        .map(|(price, ..)| price)
+}
+
+/// Why an order cannot get its minimum fill quantity up to `limit_price` on
+/// arrival, if it has one it cannot get
+fn short_of_minimum(order: &Order, order_book: &OrderBook, limit_price: Option<Price>) -> Option<String> {
+    let min = order.min_fill_quantity?;
+    let fillable = order_book.fillable_quantity(order.side, limit_price, order.remaining_quantity);
+    (fillable < min).then(|| format!("Only {} of the minimum fill quantity {} is available", fillable, min))
+}
+
+/// Re-pricing interval as a time difference, saturating if it is too long
+fn peg_interval(interval: std::time::Duration) -> chrono::Duration {
+    chrono::Duration::from_std(interval).unwrap_or(chrono::Duration::MAX)
+}
+
+/// Name of a peg's reference price for messages
+fn peg_name(peg: &Peg) -> &'static str {
+    match peg.reference {
+        PegReference::Mid => "mid",
+        PegReference::Best => "best same-side",
     }
-}
\ No newline at end of file
+}
+
+/// Lowercase side name for messages
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "buy",
+        Side::Sell => "sell",
+    }
+}
@@ -0,0 +1,84 @@
+//! Engine event stream
+//!
+//! Every processed order, cancellation, trade and session change is published
+//! to subscribers as it happens. Events of one command are published together
+//! before its order book lock is released, so each market's events arrive in
+//! the order its book applied them: an order's placement always comes before
+//! its fills. Events of different markets may interleave. Orders and trades
+//! carry the `sequence` numbers their book gave them.
+
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Utc};
+use common::model::market::SessionState;
+use common::model::order::Order;
+use common::model::trade::Trade;
+use crossbeam::channel::{self, Receiver, Sender};
+
+/// Event emitted by the matching engine
+#[derive(Debug, Clone)]
+pub enum EngineEvent {
+    /// An order was placed, in its state after matching
+    OrderPlaced(Arc<Order>),
+    /// A resting order was filled in part or in full, or moved by its peg
+    OrderUpdated(Arc<Order>),
+    /// A resting order was cancelled
+    OrderCancelled(Arc<Order>),
+    /// A resting order was removed by the engine rather than its owner
+    OrderExpired(Arc<Order>),
+    /// A trade was executed
+    Trade(Arc<Trade>),
+    /// A market moved to another trading session state
+    SessionChanged(SessionChange),
+}
+
+/// Change of a market's trading session state
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionChange {
+    /// Market symbol
+    pub market: String,
+    /// State before the change
+    pub previous: SessionState,
+    /// State after the change
+    pub state: SessionState,
+    /// When the change was applied
+    pub at: DateTime<Utc>,
+}
+
+/// Subscribers to the engine event stream
+#[derive(Default)]
+pub(crate) struct EventBus {
+    subscribers: RwLock<Vec<Sender<EngineEvent>>>,
+}
+
+impl EventBus {
+    /// Add a subscriber, which receives events until its receiver is dropped
+    pub(crate) fn subscribe(&self) -> Receiver<EngineEvent> {
+        let (sender, receiver) = channel::unbounded();
+        self.subscribers.write().unwrap().push(sender);
+        receiver
+    }
+
+    /// Publish events, building them only when someone is listening
+    pub(crate) fn publish(&self, events: impl FnOnce() -> Vec<EngineEvent>) {
+        let subscribers = self.subscribers.read().unwrap();
+        if subscribers.is_empty() {
+            return;
+        }
+
+        let events = events();
+        // Channels are unbounded, so a failed send means the receiver was dropped
+        let disconnected: Vec<Sender<EngineEvent>> = subscribers
+            .iter()
+            .filter(|subscriber| events.iter().any(|event| subscriber.send(event.clone()).is_err()))
+            .cloned()
+            .collect();
+        drop(subscribers);
+
+        if !disconnected.is_empty() {
+            self.subscribers.write().unwrap().retain(|subscriber| {
+                !disconnected.iter().any(|gone| gone.same_channel(subscriber))
+            });
+        }
+    }
+}
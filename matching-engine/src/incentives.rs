@@ -0,0 +1,311 @@
+//! Market maker quote tracking
+//!
+//! Consumes the engine event stream on a background thread and measures, per
+//! account and market:
+//! - Volume filled as the resting (maker) side
+//! - Time the account's best bid or ask was at the top of the book
+//! - How tight the account's own spread was while it quoted both sides
+//!
+//! Time is measured between event timestamps, so the book is assumed
+//! unchanged between events. Figures accumulate until the period is closed,
+//! while resting orders carry over into the next period.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use chrono::{DateTime, Utc};
+use common::decimal::{Amount, Price, Quantity};
+use common::model::incentive::MakerActivity;
+use common::model::order::{Order, OrderType, Side};
+use common::model::trade::Trade;
+use rust_decimal::prelude::ToPrimitive;
+use uuid::Uuid;
+
+use crate::engine::MatchingEngine;
+use crate::events::EngineEvent;
+
+/// A resting limit order as seen by the tracker
+struct RestingQuote {
+    account_id: Uuid,
+    side: Side,
+    price: Price,
+}
+
+/// Resting orders of one account, counted by price
+#[derive(Default)]
+struct AccountQuotes {
+    bids: BTreeMap<Price, usize>,
+    asks: BTreeMap<Price, usize>,
+}
+
+impl AccountQuotes {
+    fn side(&mut self, side: Side) -> &mut BTreeMap<Price, usize> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    fn best_bid(&self) -> Option<Price> {
+        self.bids.keys().next_back().copied()
+    }
+
+    fn best_ask(&self) -> Option<Price> {
+        self.asks.keys().next().copied()
+    }
+}
+
+/// Figures of one account in the current period
+#[derive(Clone, Default)]
+struct Accumulator {
+    maker_volume: Quantity,
+    maker_quote_volume: Amount,
+    maker_trades: u64,
+    seconds_at_best_bid: f64,
+    seconds_at_best_ask: f64,
+    seconds_two_sided: f64,
+    /// Spread in basis points multiplied by seconds quoted at it
+    spread_seconds: f64,
+}
+
+impl Accumulator {
+    fn add(&mut self, other: &Accumulator) {
+        self.maker_volume += other.maker_volume;
+        self.maker_quote_volume += other.maker_quote_volume;
+        self.maker_trades += other.maker_trades;
+        self.seconds_at_best_bid += other.seconds_at_best_bid;
+        self.seconds_at_best_ask += other.seconds_at_best_ask;
+        self.seconds_two_sided += other.seconds_two_sided;
+        self.spread_seconds += other.spread_seconds;
+    }
+}
+
+/// Quotes and figures of one market
+#[derive(Default)]
+struct MarketQuotes {
+    /// Time quotes were last accounted up to
+    last_at: Option<DateTime<Utc>>,
+    orders: HashMap<Uuid, RestingQuote>,
+    quotes: HashMap<Uuid, AccountQuotes>,
+    activity: HashMap<Uuid, Accumulator>,
+}
+
+impl MarketQuotes {
+    /// Credit quoting time from the last event up to `at`
+    fn advance(&mut self, at: DateTime<Utc>) {
+        let credits = self.quoting(at);
+        if self.last_at.is_none_or(|last_at| at > last_at) {
+            self.last_at = Some(at);
+        }
+        for (account_id, credit) in credits {
+            self.activity.entry(account_id).or_default().add(&credit);
+        }
+    }
+
+    /// Quoting time each account earns from the last event up to `at` at the current quotes
+    fn quoting(&self, at: DateTime<Utc>) -> Vec<(Uuid, Accumulator)> {
+        let Some(last_at) = self.last_at.filter(|last_at| at > *last_at) else {
+            return Vec::new();
+        };
+
+        let seconds = (at - last_at).num_microseconds().unwrap_or(i64::MAX) as f64 / 1_000_000.0;
+        let best_bid = self.quotes.values().filter_map(AccountQuotes::best_bid).max();
+        let best_ask = self.quotes.values().filter_map(AccountQuotes::best_ask).min();
+
+        self.quotes.iter()
+            .map(|(account_id, quotes)| {
+                let (bid, ask) = (quotes.best_bid(), quotes.best_ask());
+                let mut credit = Accumulator::default();
+                if bid.is_some() && bid == best_bid {
+                    credit.seconds_at_best_bid = seconds;
+                }
+                if ask.is_some() && ask == best_ask {
+                    credit.seconds_at_best_ask = seconds;
+                }
+                if let (Some(bid), Some(ask)) = (bid, ask) {
+                    credit.seconds_two_sided = seconds;
+                    credit.spread_seconds = spread_bps(bid, ask) * seconds;
+                }
+                (*account_id, credit)
+            })
+            .collect()
+    }
+
+    /// Track an order in its latest state, dropping it once it no longer rests
+    fn update_order(&mut self, order: &Order) {
+        self.remove_order(order.id);
+
+        let resting = order.is_active() && order.order_type == OrderType::Limit && order.remaining_quantity > Quantity::ZERO;
+        let Some(price) = order.price.filter(|_| resting) else {
+            return;
+        };
+
+        *self.quotes.entry(order.user_id).or_default().side(order.side).entry(price).or_default() += 1;
+        self.orders.insert(order.id, RestingQuote { account_id: order.user_id, side: order.side, price });
+    }
+
+    fn remove_order(&mut self, order_id: Uuid) {
+        let Some(quote) = self.orders.remove(&order_id) else {
+            return;
+        };
+        let Some(quotes) = self.quotes.get_mut(&quote.account_id) else {
+            return;
+        };
+
+        let levels = quotes.side(quote.side);
+        if let Some(count) = levels.get_mut(&quote.price) {
+            *count -= 1;
+            if *count == 0 {
+                levels.remove(&quote.price);
+            }
+        }
+        if quotes.bids.is_empty() && quotes.asks.is_empty() {
+            self.quotes.remove(&quote.account_id);
+        }
+    }
+
+    fn on_trade(&mut self, trade: &Trade) {
+        let maker = if trade.is_buyer_maker { trade.buyer_id } else { trade.seller_id };
+        let activity = self.activity.entry(maker).or_default();
+        activity.maker_volume += trade.quantity;
+        activity.maker_quote_volume += trade.amount;
+        activity.maker_trades += 1;
+    }
+
+    /// Figures up to `now`, leaving the clock at the last event so events
+    /// still on their way are credited in full
+    fn report(&self, market: &str, now: DateTime<Utc>) -> Vec<MakerActivity> {
+        let mut activity = self.activity.clone();
+        for (account_id, credit) in self.quoting(now) {
+            activity.entry(account_id).or_default().add(&credit);
+        }
+
+        activity.iter().map(|(account_id, activity)| MakerActivity {
+            market: market.to_string(),
+            account_id: *account_id,
+            maker_volume: activity.maker_volume,
+            maker_quote_volume: activity.maker_quote_volume,
+            maker_trades: activity.maker_trades,
+            seconds_at_best_bid: activity.seconds_at_best_bid,
+            seconds_at_best_ask: activity.seconds_at_best_ask,
+            seconds_two_sided: activity.seconds_two_sided,
+            average_spread_bps: (activity.seconds_two_sided > 0.0)
+                .then(|| activity.spread_seconds / activity.seconds_two_sided),
+        }).collect()
+    }
+}
+
+/// Spread between a bid and an ask in basis points of their midpoint
+fn spread_bps(bid: Price, ask: Price) -> f64 {
+    let mid = (bid + ask) / Price::TWO;
+    if mid <= Price::ZERO {
+        return 0.0;
+    }
+    ((ask - bid) / mid * Price::from(10_000)).to_f64().unwrap_or(0.0)
+}
+
+struct TrackerState {
+    period_start: DateTime<Utc>,
+    markets: HashMap<String, MarketQuotes>,
+}
+
+/// Maker volume and quoting tracker over the engine event stream
+pub struct QuoteTracker {
+    state: Mutex<TrackerState>,
+}
+
+impl QuoteTracker {
+    /// Create a tracker whose first period starts at `period_start`
+    pub fn new(period_start: DateTime<Utc>) -> Self {
+        Self {
+            state: Mutex::new(TrackerState {
+                period_start,
+                markets: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Track a matching engine's events on a background thread
+    pub fn start(engine: &MatchingEngine) -> Arc<Self> {
+        let tracker = Arc::new(Self::new(engine.clock().now()));
+        let events = engine.subscribe_events();
+
+        let worker = tracker.clone();
+        thread::Builder::new()
+            .name("quote-tracker".to_string())
+            .spawn(move || {
+                for event in events {
+                    worker.process(&event);
+                }
+            })
+            .expect("failed to spawn quote tracker thread");
+
+        tracker
+    }
+
+    /// Feed one engine event
+    pub fn process(&self, event: &EngineEvent) {
+        let mut state = self.state.lock().unwrap();
+        match event {
+            EngineEvent::OrderPlaced(order) | EngineEvent::OrderUpdated(order) => {
+                let market = state.markets.entry(order.market.clone()).or_default();
+                market.advance(order.updated_at);
+                market.update_order(order);
+            }
+            EngineEvent::OrderCancelled(order) | EngineEvent::OrderExpired(order) => {
+                let market = state.markets.entry(order.market.clone()).or_default();
+                market.advance(order.updated_at);
+                market.remove_order(order.id);
+            }
+            EngineEvent::Trade(trade) => {
+                let market = state.markets.entry(trade.market.clone()).or_default();
+                market.advance(trade.created_at);
+                market.on_trade(trade);
+            }
+            EngineEvent::SessionChanged(change) => {
+                state.markets.entry(change.market.clone()).or_default().advance(change.at);
+            }
+        }
+    }
+
+    /// Start of the current period
+    pub fn period_start(&self) -> DateTime<Utc> {
+        self.state.lock().unwrap().period_start
+    }
+
+    /// Figures of the current period up to `now`, by market and then by
+    /// descending maker quote volume
+    pub fn activity(&self, now: DateTime<Utc>) -> Vec<MakerActivity> {
+        let state = self.state.lock().unwrap();
+        Self::collect(&state, now)
+    }
+
+    /// End the current period at `now`, returning its start and figures
+    ///
+    /// Resting orders carry over into the next period, which starts at `now`.
+    pub fn close_period(&self, now: DateTime<Utc>) -> (DateTime<Utc>, Vec<MakerActivity>) {
+        let mut state = self.state.lock().unwrap();
+        let activity = Self::collect(&state, now);
+
+        for market in state.markets.values_mut() {
+            market.advance(now);
+            market.activity.clear();
+        }
+        let start = std::mem::replace(&mut state.period_start, now);
+        (start, activity)
+    }
+
+    fn collect(state: &TrackerState, now: DateTime<Utc>) -> Vec<MakerActivity> {
+        let mut activity: Vec<MakerActivity> = state.markets.iter()
+            .flat_map(|(symbol, market)| market.report(symbol, now))
+            .collect();
+
+        activity.sort_by(|a, b| {
+            a.market.cmp(&b.market)
+                .then(b.maker_quote_volume.cmp(&a.maker_quote_volume))
+                .then(a.account_id.cmp(&b.account_id))
+        });
+        activity
+    }
+}
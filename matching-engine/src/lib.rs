@@ -1,6 +1,14 @@
+mod events;
 mod order_book;
+mod throttle;
+mod verification;
 pub mod engine;
+pub mod incentives;
+pub mod surveillance;
 
-pub use engine::{MatchingEngine, MatchingResult};
+pub use engine::{BookStats, MatchingEngine, MatchingResult, DEFAULT_PEG_REPRICE_INTERVAL};
+pub use events::{EngineEvent, SessionChange};
 pub use order_book::{OrderBook, OrderBookSide};
+pub use throttle::ThrottleConfig;
+pub use verification::{BookChecks, BookVerification};
 
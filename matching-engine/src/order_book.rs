@@ -1,17 +1,25 @@
-//! Order book implementation for price-time priority matching
+//! Order book implementation for price-time priority matching, or pro-rata
+//! allocation within a price level for markets that use it
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::ops::Bound;
 use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
 use common::decimal::{Price, Quantity};
-use common::model::order::{Order, Side};
+use common::model::market::Allocation;
+use common::model::order::{Order, Peg, PegReference, Side};
+use common::model::trade::Trade;
 use rust_decimal::Decimal;
 use uuid::Uuid;
 
+/// Most recent trades an order book keeps for consumers that missed them
+pub const TRADE_LOG_CAPACITY: usize = 10_000;
+
 /// The buy side of the order book (bids)
 pub struct BidSide {
     /// Price-ordered map of limit orders (price -> orders)
-    /// For bids (buy orders), higher prices come first (reverse ordering)
+    /// For bids (buy orders), higher prices are best, so the map is read from the back
     limits: BTreeMap<Price, Vec<Arc<Order>>>,
     /// Index for fast order lookup by ID
     order_map: HashMap<Uuid, (Price, usize)>,
@@ -29,7 +37,6 @@ impl BidSide {
     /// Add an order to the bid side
     pub fn add(&mut self, order: Arc<Order>) {
         if let Some(price) = order.price {
-            // Store in reverse order for bids (highest price first)
             let price_level = self.limits.entry(price).or_default();
             let position = price_level.len();
             price_level.push(order.clone());
@@ -39,7 +46,22 @@ impl BidSide {
 
     /// Get the best price (highest bid)
     pub fn best_price(&self) -> Option<Price> {
-        self.limits.keys().next().copied()
+        self.limits.keys().next_back().copied()
+    }
+
+    /// Best price after `after`, which is the best price overall without one
+    pub fn next_price(&self, after: Option<Price>) -> Option<Price> {
+        match after {
+            Some(after) => self.limits.range(..after).next_back().map(|(price, _)| *price),
+            None => self.best_price(),
+        }
+    }
+
+    /// Best price with an order that is not pegged, which pegged orders track
+    pub fn best_unpegged_price(&self) -> Option<Price> {
+        self.limits.iter().rev()
+            .find(|(_, orders)| orders.iter().any(|order| order.peg.is_none()))
+            .map(|(price, _)| *price)
     }
 
     /// Get orders at the given price level
@@ -47,10 +69,16 @@ impl BidSide {
         self.limits.get(&price)
     }
 
+    /// Iterate over all resting orders
+    pub fn orders(&self) -> impl Iterator<Item = &Arc<Order>> {
+        self.limits.values().flatten()
+    }
+
     /// Get all price levels with their orders (for market data)
     pub fn price_levels(&self, limit: usize) -> Vec<(Price, Quantity)> {
         self.limits
             .iter()
+            .rev()
             .take(limit)
             .map(|(price, orders)| {
                 let total_quantity = orders
@@ -62,6 +90,36 @@ impl BidSide {
             .collect()
     }
 
+    /// Number of price levels
+    pub fn level_count(&self) -> usize {
+        self.limits.len()
+    }
+
+    /// Get the price farthest from the top of the book (lowest bid)
+    pub fn farthest_price(&self) -> Option<Price> {
+        self.limits.keys().next().copied()
+    }
+
+    /// Remove every order at a price level
+    pub fn remove_level(&mut self, price: Price) -> Vec<Arc<Order>> {
+        let orders = self.limits.remove(&price).unwrap_or_default();
+        for order in &orders {
+            self.order_map.remove(&order.id);
+        }
+        orders
+    }
+
+    /// Replace a resting order in place, keeping its time priority
+    pub fn replace(&mut self, order: Arc<Order>) -> bool {
+        if let Some((price, position)) = self.order_map.get(&order.id).copied() {
+            if let Some(slot) = self.limits.get_mut(&price).and_then(|orders| orders.get_mut(position)) {
+                *slot = order;
+                return true;
+            }
+        }
+        false
+    }
+
     /// Remove an order by ID
     pub fn remove(&mut self, order_id: Uuid) -> Option<Arc<Order>> {
         if let Some((price, position)) = self.order_map.remove(&order_id) {
@@ -124,11 +182,31 @@ impl AskSide {
         self.limits.keys().next().copied()
     }
 
+    /// Best price after `after`, which is the best price overall without one
+    pub fn next_price(&self, after: Option<Price>) -> Option<Price> {
+        match after {
+            Some(after) => self.limits.range((Bound::Excluded(after), Bound::Unbounded)).next().map(|(price, _)| *price),
+            None => self.best_price(),
+        }
+    }
+
+    /// Best price with an order that is not pegged, which pegged orders track
+    pub fn best_unpegged_price(&self) -> Option<Price> {
+        self.limits.iter()
+            .find(|(_, orders)| orders.iter().any(|order| order.peg.is_none()))
+            .map(|(price, _)| *price)
+    }
+
     /// Get orders at the given price level
     pub fn orders_at(&self, price: Price) -> Option<&Vec<Arc<Order>>> {
         self.limits.get(&price)
     }
 
+    /// Iterate over all resting orders
+    pub fn orders(&self) -> impl Iterator<Item = &Arc<Order>> {
+        self.limits.values().flatten()
+    }
+
     /// Get all price levels with their orders (for market data)
     pub fn price_levels(&self, limit: usize) -> Vec<(Price, Quantity)> {
         self.limits
@@ -144,6 +222,36 @@ impl AskSide {
             .collect()
     }
 
+    /// Number of price levels
+    pub fn level_count(&self) -> usize {
+        self.limits.len()
+    }
+
+    /// Get the price farthest from the top of the book (highest ask)
+    pub fn farthest_price(&self) -> Option<Price> {
+        self.limits.keys().next_back().copied()
+    }
+
+    /// Remove every order at a price level
+    pub fn remove_level(&mut self, price: Price) -> Vec<Arc<Order>> {
+        let orders = self.limits.remove(&price).unwrap_or_default();
+        for order in &orders {
+            self.order_map.remove(&order.id);
+        }
+        orders
+    }
+
+    /// Replace a resting order in place, keeping its time priority
+    pub fn replace(&mut self, order: Arc<Order>) -> bool {
+        if let Some((price, position)) = self.order_map.get(&order.id).copied() {
+            if let Some(slot) = self.limits.get_mut(&price).and_then(|orders| orders.get_mut(position)) {
+                *slot = order;
+                return true;
+            }
+        }
+        false
+    }
+
     /// Remove an order by ID
     pub fn remove(&mut self, order_id: Uuid) -> Option<Arc<Order>> {
         if let Some((price, position)) = self.order_map.remove(&order_id) {
@@ -233,6 +341,22 @@ pub struct OrderBook {
     asks: AskSide,
     /// Last traded price
     pub last_price: Option<Price>,
+    /// Resting orders per account
+    account_orders: HashMap<Uuid, usize>,
+    /// Sequence number of the last order processed
+    last_order_sequence: u64,
+    /// Sequence number of the last trade
+    last_trade_sequence: u64,
+    /// Most recent trades, oldest first
+    trade_log: VecDeque<Trade>,
+    /// How takers are shared among the orders at a price level
+    allocation: Allocation,
+    /// Minimum price change pegged prices are rounded to, zero for none
+    price_tick: Price,
+    /// Resting pegged orders
+    pegged_orders: usize,
+    /// When pegged orders were last re-priced
+    pegs_repriced_at: Option<DateTime<Utc>>,
 }
 
 impl OrderBook {
@@ -243,11 +367,139 @@ impl OrderBook {
             bids: BidSide::new(),
             asks: AskSide::new(),
             last_price: None,
+            account_orders: HashMap::new(),
+            last_order_sequence: 0,
+            last_trade_sequence: 0,
+            trade_log: VecDeque::new(),
+            allocation: Allocation::Fifo,
+            price_tick: Price::ZERO,
+            pegged_orders: 0,
+            pegs_repriced_at: None,
+        }
+    }
+    
+    /// Minimum price change pegged prices are rounded to
+    pub fn price_tick(&self) -> Price {
+        self.price_tick
+    }
+    
+    /// Round pegged prices to the given tick, or not at all when zero
+    pub fn set_price_tick(&mut self, tick: Price) {
+        self.price_tick = tick;
+    }
+    
+    /// How takers are shared among the orders at a price level
+    pub fn allocation(&self) -> Allocation {
+        self.allocation
+    }
+    
+    /// Share takers among the orders at a price level with the given algorithm
+    pub fn set_allocation(&mut self, allocation: Allocation) {
+        self.allocation = allocation;
+    }
+    
+    /// Resting orders on `side` at `price` that a taker for `quantity` fills,
+    /// with how much each gets, in time priority
+    ///
+    /// See [`Allocation`] for how pro-rata shares are rounded.
+    pub fn allocate(&self, side: Side, price: Price, quantity: Quantity) -> Vec<(Arc<Order>, Quantity)> {
+        self.allocate_among(side, price, quantity, &[])
+    }
+    
+    /// Like [`allocate`](Self::allocate), but passing over resting orders
+    /// whose share falls short of their minimum fill quantity
+    ///
+    /// Passed over orders keep their place in the queue, and the level is
+    /// shared again among the rest. Returns the fills and whether any order
+    /// was passed over.
+    pub fn allocate_accepted(&self, side: Side, price: Price, quantity: Quantity) -> (Vec<(Arc<Order>, Quantity)>, bool) {
+        if let Allocation::ProRata { .. } = self.allocation {
+            let mut passed = Vec::new();
+            loop {
+                let fills = self.allocate_among(side, price, quantity, &passed);
+                let short = passed.len();
+                passed.extend(fills.iter().filter(|(order, share)| !order.accepts_fill(*share)).map(|(order, _)| order.id));
+                if passed.len() == short {
+                    return (fills, !passed.is_empty());
+                }
+            }
+        }
+        
+        // In time priority each order takes what is left up to its size, so
+        // passing one over leaves its share to the orders behind it
+        let mut fills = Vec::new();
+        let mut passed = false;
+        let mut remaining = quantity;
+        for order in self.orders_at(side, price).into_iter().flatten() {
+            if remaining.is_zero() {
+                break;
+            }
+            let share = Quantity::min(remaining, order.remaining_quantity);
+            if order.accepts_fill(share) {
+                fills.push((order.clone(), share));
+                remaining -= share;
+            } else {
+                passed = true;
+            }
+        }
+        (fills, passed)
+    }
+    
+    /// Share `quantity` among the orders at a level, leaving out `passed`
+    fn allocate_among(&self, side: Side, price: Price, quantity: Quantity, passed: &[Uuid]) -> Vec<(Arc<Order>, Quantity)> {
+        let Some(orders) = self.orders_at(side, price) else {
+            return Vec::new();
+        };
+        let orders: Vec<&Arc<Order>> = orders.iter().filter(|order| !passed.contains(&order.id)).collect();
+        
+        let level: Quantity = orders.iter().map(|order| order.remaining_quantity).sum();
+        let mut shares = match self.allocation {
+            Allocation::ProRata { min_allocation, lot_size } if quantity < level && !lot_size.is_zero() => {
+                orders.iter()
+                    .map(|order| {
+                        let share = (quantity * order.remaining_quantity / level / lot_size).floor() * lot_size;
+                        if share < min_allocation { Quantity::ZERO } else { share }
+                    })
+                    .collect()
+            }
+            _ => vec![Quantity::ZERO; orders.len()],
+        };
+        
+        // Whatever is left goes in time priority
+        let mut remaining = quantity - shares.iter().copied().sum::<Quantity>();
+        for (share, order) in shares.iter_mut().zip(&orders) {
+            if remaining.is_zero() {
+                break;
+            }
+            let extra = Quantity::min(remaining, order.remaining_quantity - *share);
+            *share += extra;
+            remaining -= extra;
+        }
+        
+        orders.into_iter()
+            .zip(shares)
+            .filter(|(_, share)| !share.is_zero())
+            .map(|(order, share)| (order.clone(), share))
+            .collect()
+    }
+    
+    /// Resting orders on `side` at `price`, in time priority
+    fn orders_at(&self, side: Side, price: Price) -> Option<&Vec<Arc<Order>>> {
+        match side {
+            Side::Buy => self.bids.orders_at(price),
+            Side::Sell => self.asks.orders_at(price),
         }
     }
     
     /// Add an order to the book
     pub fn add_order(&mut self, order: Arc<Order>) {
+        if order.price.is_none() {
+            return;
+        }
+        *self.account_orders.entry(order.user_id).or_default() += 1;
+        if order.peg.is_some() {
+            self.pegged_orders += 1;
+        }
         match order.side {
             Side::Buy => self.bids.add_order(order),
             Side::Sell => self.asks.add_order(order),
@@ -256,10 +508,85 @@ impl OrderBook {
     
     /// Remove an order from the book
     pub fn remove_order(&mut self, order_id: Uuid, side: Side) -> Option<Arc<Order>> {
-        match side {
+        let order = match side {
             Side::Buy => self.bids.remove_order(order_id),
             Side::Sell => self.asks.remove_order(order_id),
+        }?;
+        self.forget_order(&order);
+        Some(order)
+    }
+    
+    /// Remove every order at a price level
+    pub fn remove_level(&mut self, side: Side, price: Price) -> Vec<Arc<Order>> {
+        let orders = match side {
+            Side::Buy => self.bids.remove_level(price),
+            Side::Sell => self.asks.remove_level(price),
+        };
+        for order in &orders {
+            self.forget_order(order);
         }
+        orders
+    }
+    
+    fn forget_order(&mut self, order: &Order) {
+        if order.peg.is_some() {
+            self.pegged_orders -= 1;
+        }
+        if let Some(count) = self.account_orders.get_mut(&order.user_id) {
+            *count -= 1;
+            if *count == 0 {
+                self.account_orders.remove(&order.user_id);
+            }
+        }
+    }
+    
+    /// Number of orders an account has resting on the book
+    pub fn account_order_count(&self, user_id: Uuid) -> usize {
+        self.account_orders.get(&user_id).copied().unwrap_or(0)
+    }
+    
+    /// Number of price levels on one side
+    pub fn level_count(&self, side: Side) -> usize {
+        match side {
+            Side::Buy => self.bids.level_count(),
+            Side::Sell => self.asks.level_count(),
+        }
+    }
+    
+    /// Check whether one side already has a level at a price
+    pub fn has_level(&self, side: Side, price: Price) -> bool {
+        match side {
+            Side::Buy => self.bids.orders_at(price).is_some(),
+            Side::Sell => self.asks.orders_at(price).is_some(),
+        }
+    }
+    
+    /// Price on one side farthest from the top of the book
+    pub fn farthest_price(&self, side: Side) -> Option<Price> {
+        match side {
+            Side::Buy => self.bids.farthest_price(),
+            Side::Sell => self.asks.farthest_price(),
+        }
+    }
+    
+    /// Get all resting orders of a user
+    pub fn orders_for_user(&self, user_id: Uuid) -> Vec<Arc<Order>> {
+        self.bids
+            .orders()
+            .chain(self.asks.orders())
+            .filter(|order| order.user_id == user_id)
+            .cloned()
+            .collect()
+    }
+    
+    /// Get all resting orders created before a point in time
+    pub fn orders_created_before(&self, cutoff: DateTime<Utc>) -> Vec<Arc<Order>> {
+        self.bids
+            .orders()
+            .chain(self.asks.orders())
+            .filter(|order| order.created_at < cutoff)
+            .cloned()
+            .collect()
     }
     
     /// Get the best bid price
@@ -301,8 +628,152 @@ impl OrderBook {
     /// Check if orders would match
     pub fn would_match(&self, price: Price, side: Side) -> bool {
         match side {
-            Side::Buy => self.best_ask().map_or(false, |ask| price >= ask),
-            Side::Sell => self.best_bid().map_or(false, |bid| price <= bid),
+            Side::Buy => self.best_ask().is_some_and(|ask| price >= ask),
+            Side::Sell => self.best_bid().is_some_and(|bid| price <= bid),
+        }
+    }
+    
+    /// Total resting quantity a taker on `side` could match up to `limit_price`
+    pub fn matchable_quantity(&self, side: Side, limit_price: Option<Price>) -> Quantity {
+        let maker_side = opposite(side);
+        let mut total = Quantity::ZERO;
+        let mut level = self.next_level(side, None, limit_price);
+        while let Some(price) = level {
+            total += self.orders_at(maker_side, price).into_iter().flatten().map(|order| order.remaining_quantity).sum::<Quantity>();
+            level = self.next_level(side, Some(price), limit_price);
+        }
+        total
+    }
+    
+    /// Levels a taker on `side` for `quantity` up to `limit_price` would fill
+    /// at and how much at each, best first, without changing the book
+    ///
+    /// Levels are shared by the market's allocation, passing over resting
+    /// orders whose share falls short of their minimum fill quantity, as
+    /// matching does.
+    pub fn estimate_fills(&self, side: Side, limit_price: Option<Price>, quantity: Quantity) -> Vec<(Price, Quantity)> {
+        let maker_side = opposite(side);
+        let mut remaining = quantity;
+        let mut fills = Vec::new();
+        let mut level = self.next_level(side, None, limit_price);
+        while let Some(price) = level.filter(|_| !remaining.is_zero()) {
+            let (level_fills, _) = self.allocate_accepted(maker_side, price, remaining);
+            let filled: Quantity = level_fills.iter().map(|(_, share)| *share).sum();
+            if !filled.is_zero() {
+                fills.push((price, filled));
+            }
+            remaining -= filled;
+            level = self.next_level(side, Some(price), limit_price);
+        }
+        fills
+    }
+    
+    /// Quantity a taker on `side` for `quantity` up to `limit_price` would
+    /// fill now, respecting resting orders' minimum fill quantities
+    pub fn fillable_quantity(&self, side: Side, limit_price: Option<Price>, quantity: Quantity) -> Quantity {
+        self.estimate_fills(side, limit_price, quantity)
+            .into_iter()
+            .map(|(_, quantity)| quantity)
+            .sum()
+    }
+    
+    /// Best opposite level a taker on `side` can match within `limit_price`,
+    /// after the level at `after` if given
+    pub fn next_level(&self, side: Side, after: Option<Price>, limit_price: Option<Price>) -> Option<Price> {
+        match side {
+            Side::Buy => self.asks.next_price(after).filter(|ask| limit_price.is_none_or(|limit| limit >= *ask)),
+            Side::Sell => self.bids.next_price(after).filter(|bid| limit_price.is_none_or(|limit| limit <= *bid)),
+        }
+    }
+    
+    /// Price a pegged order on `side` would rest at now, or `None` without a
+    /// reference price or room behind the opposite best price
+    pub fn peg_price(&self, side: Side, peg: &Peg) -> Option<Price> {
+        let (bid, ask) = (self.bids.best_unpegged_price(), self.asks.best_unpegged_price());
+        let reference = match (peg.reference, side) {
+            (PegReference::Mid, _) => (bid? + ask?) / Decimal::TWO,
+            (PegReference::Best, Side::Buy) => bid?,
+            (PegReference::Best, Side::Sell) => ask?,
+        };
+        
+        // Round away from the opposite side, then cap at the limit
+        let tick = self.price_tick;
+        let mut price = reference + peg.offset;
+        if !tick.is_zero() {
+            price = match side {
+                Side::Buy => (price / tick).floor() * tick,
+                Side::Sell => (price / tick).ceil() * tick,
+            };
+        }
+        if let Some(limit) = peg.limit {
+            price = match side {
+                Side::Buy => price.min(limit),
+                Side::Sell => price.max(limit),
+            };
+        }
+        
+        // Stay a tick behind the opposite side so the order never takes liquidity
+        match side {
+            Side::Buy if self.best_ask().is_some_and(|ask| price >= ask) && !tick.is_zero() => {
+                price = self.best_ask()? - tick;
+            }
+            Side::Sell if self.best_bid().is_some_and(|bid| price <= bid) && !tick.is_zero() => {
+                price = self.best_bid()? + tick;
+            }
+            _ => {}
+        }
+        (price > Price::ZERO && !self.would_match(price, side)).then(|| price.normalize())
+    }
+    
+    /// Whether pegged orders rest on the book
+    pub fn has_pegged_orders(&self) -> bool {
+        self.pegged_orders > 0
+    }
+    
+    /// When pegged orders were last re-priced
+    pub fn pegs_repriced_at(&self) -> Option<DateTime<Utc>> {
+        self.pegs_repriced_at
+    }
+    
+    /// Move every pegged order whose peg gives a new price to the back of its
+    /// new level, returning the moved orders
+    ///
+    /// Orders without a price to peg to stay where they are.
+    pub fn reprice_pegged_orders(&mut self, now: DateTime<Utc>) -> Vec<Arc<Order>> {
+        self.pegs_repriced_at = Some(now);
+        let pegged: Vec<Arc<Order>> = self.bids
+            .orders()
+            .chain(self.asks.orders())
+            .filter(|order| order.peg.is_some())
+            .cloned()
+            .collect();
+        
+        let mut repriced = Vec::new();
+        for order in pegged {
+            let Some(price) = order.peg.as_ref().and_then(|peg| self.peg_price(order.side, peg)) else {
+                continue;
+            };
+            if order.price == Some(price) {
+                continue;
+            }
+            
+            self.remove_order(order.id, order.side);
+            let order = Arc::new(Order {
+                price: Some(price),
+                updated_at: now,
+                ..order.as_ref().clone()
+            });
+            self.add_order(order.clone());
+            repriced.push(order);
+        }
+        repriced
+    }
+    
+    /// Replace a resting order in place, keeping its time priority
+    pub fn replace_order(&mut self, order: Arc<Order>) -> bool {
+        match order.side {
+            Side::Buy => self.bids.replace(order),
+            Side::Sell => self.asks.replace(order),
         }
     }
     
@@ -310,6 +781,47 @@ impl OrderBook {
     pub fn set_last_price(&mut self, price: Price) {
         self.last_price = Some(price);
     }
+    
+    /// Number an order arriving at this book, before it matches or rests
+    pub fn record_order(&mut self, order: &mut Order) {
+        self.last_order_sequence += 1;
+        order.sequence = self.last_order_sequence;
+    }
+    
+    /// Sequence number of the last order processed, 0 before the first
+    pub fn last_order_sequence(&self) -> u64 {
+        self.last_order_sequence
+    }
+    
+    /// Number a trade executed in this book, update the last price and keep
+    /// the trade for consumers that miss it
+    pub fn record_trade(&mut self, trade: &mut Trade) {
+        self.last_trade_sequence += 1;
+        trade.sequence = self.last_trade_sequence;
+        self.last_price = Some(trade.price);
+        
+        if self.trade_log.len() == TRADE_LOG_CAPACITY {
+            self.trade_log.pop_front();
+        }
+        self.trade_log.push_back(trade.clone());
+    }
+    
+    /// Sequence number of the last trade, 0 before the first
+    pub fn last_trade_sequence(&self) -> u64 {
+        self.last_trade_sequence
+    }
+    
+    /// Number trades on from `sequence`, e.g. the last one stored before a
+    /// restart, unless this book already numbered past it
+    pub fn resume_trade_sequence(&mut self, sequence: u64) {
+        self.last_trade_sequence = self.last_trade_sequence.max(sequence);
+    }
+    
+    /// Kept trades numbered after `sequence`, oldest first
+    pub fn trades_after(&self, sequence: u64) -> Vec<Trade> {
+        let skip = self.trade_log.partition_point(|trade| trade.sequence <= sequence);
+        self.trade_log.iter().skip(skip).cloned().collect()
+    }
 
     // Get a reference to the bids side
     pub fn bids(&self) -> &BidSide {
@@ -340,4 +852,12 @@ impl OrderBook {
         }
         None
     }
-}
\ No newline at end of file
+}
+
+/// The other side of the book
+fn opposite(side: Side) -> Side {
+    match side {
+        Side::Buy => Side::Sell,
+        Side::Sell => Side::Buy,
+    }
+}
@@ -0,0 +1,334 @@
+//! Trade surveillance
+//!
+//! Consumes the engine event stream on a background thread and flags
+//! suspicious patterns:
+//! - Self trades, where an account matches against its own order
+//! - Wash trading, where two accounts repeatedly trade back and forth
+//! - High cancel-to-fill ratios
+//! - Layering, where an account stacks orders on one side, trades on the
+//!   other and then pulls the stack
+//!
+//! Patterns are evaluated over a sliding window of event timestamps. The same
+//! pattern is reported at most once per window for the same accounts and
+//! market. Alerts are kept in memory, newest first.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use common::decimal::Price;
+use common::model::order::{Order, Side};
+use common::model::surveillance::{Alert, AlertKind};
+use common::model::trade::Trade;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::engine::MatchingEngine;
+use crate::events::EngineEvent;
+
+/// Maximum number of alerts kept in memory
+const ALERT_CAPACITY: usize = 10_000;
+
+/// Surveillance thresholds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurveillanceConfig {
+    /// Sliding window patterns are evaluated over
+    pub window: Duration,
+    /// Trades between the same two accounts, with both buying from the other, that count as wash trading
+    pub wash_trade_min_trades: usize,
+    /// Cancels in the window before the cancel-to-fill ratio is checked
+    pub min_cancels: usize,
+    /// Cancels per fill at which an account is flagged
+    pub max_cancel_to_fill: usize,
+    /// Distinct price levels pulled on one side after a fill on the other that count as layering
+    pub layering_min_levels: usize,
+}
+
+impl Default for SurveillanceConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(60),
+            wash_trade_min_trades: 4,
+            min_cancels: 50,
+            max_cancel_to_fill: 20,
+            layering_min_levels: 3,
+        }
+    }
+}
+
+/// Account pair as (lower ID, higher ID, market)
+type PairKey = (Uuid, Uuid, String);
+
+/// Reported pattern as (kind, accounts, market)
+type AlertKey = (AlertKind, Vec<Uuid>, String);
+
+/// A cancelled order as seen by the detector
+struct Cancel {
+    at: DateTime<Utc>,
+    placed_at: DateTime<Utc>,
+    side: Side,
+    price: Option<Price>,
+}
+
+/// Recent fills and cancels of one account on one market
+#[derive(Default)]
+struct Activity {
+    /// Fill times and the side the account was on
+    fills: VecDeque<(DateTime<Utc>, Side)>,
+    cancels: VecDeque<Cancel>,
+}
+
+impl Activity {
+    fn prune(&mut self, cutoff: DateTime<Utc>) {
+        while self.fills.front().is_some_and(|(at, _)| *at < cutoff) {
+            self.fills.pop_front();
+        }
+        while self.cancels.front().is_some_and(|cancel| cancel.at < cutoff) {
+            self.cancels.pop_front();
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.fills.is_empty() && self.cancels.is_empty()
+    }
+}
+
+/// Sliding window state, fed one event at a time
+struct Detector {
+    config: SurveillanceConfig,
+    window: chrono::Duration,
+    /// Activity by account and market
+    activity: HashMap<(Uuid, String), Activity>,
+    /// Trades between account pairs, with whether the lower ID bought
+    pairs: HashMap<PairKey, VecDeque<(DateTime<Utc>, bool)>>,
+    /// Last time each pattern was reported
+    raised: HashMap<AlertKey, DateTime<Utc>>,
+    /// Event time of the last sweep of idle state
+    last_sweep: Option<DateTime<Utc>>,
+}
+
+impl Detector {
+    fn new(config: SurveillanceConfig) -> Self {
+        Self {
+            config,
+            window: chrono::Duration::from_std(config.window).unwrap_or(chrono::Duration::MAX),
+            activity: HashMap::new(),
+            pairs: HashMap::new(),
+            raised: HashMap::new(),
+            last_sweep: None,
+        }
+    }
+
+    fn process(&mut self, event: &EngineEvent) -> Vec<Alert> {
+        let (alerts, now) = match event {
+            EngineEvent::Trade(trade) => (self.on_trade(trade), trade.created_at),
+            EngineEvent::OrderCancelled(order) => (self.on_cancel(order), order.updated_at),
+            EngineEvent::OrderPlaced(_)
+            | EngineEvent::OrderUpdated(_)
+            | EngineEvent::OrderExpired(_)
+            | EngineEvent::SessionChanged(_) => return Vec::new(),
+        };
+
+        self.sweep(now);
+        alerts
+    }
+
+    fn on_trade(&mut self, trade: &Trade) -> Vec<Alert> {
+        let now = trade.created_at;
+        let cutoff = now - self.window;
+
+        if trade.buyer_id == trade.seller_id {
+            let details = format!("Trade {} matched {} @ {} against the same account", trade.id, trade.quantity, trade.price);
+            return self.raise(AlertKind::SelfTrade, &trade.market, vec![trade.buyer_id], details, now)
+                .into_iter()
+                .collect();
+        }
+
+        for (account_id, side) in [(trade.buyer_id, Side::Buy), (trade.seller_id, Side::Sell)] {
+            let activity = self.activity.entry((account_id, trade.market.clone())).or_default();
+            activity.prune(cutoff);
+            activity.fills.push_back((now, side));
+        }
+
+        let (low, high) = if trade.buyer_id < trade.seller_id {
+            (trade.buyer_id, trade.seller_id)
+        } else {
+            (trade.seller_id, trade.buyer_id)
+        };
+        let trades = self.pairs.entry((low, high, trade.market.clone())).or_default();
+        while trades.front().is_some_and(|(at, _)| *at < cutoff) {
+            trades.pop_front();
+        }
+        trades.push_back((now, trade.buyer_id == low));
+
+        let low_bought = trades.iter().filter(|(_, low_bought)| *low_bought).count();
+        let total = trades.len();
+        if total >= self.config.wash_trade_min_trades && low_bought > 0 && low_bought < total {
+            let details = format!(
+                "{} trades between the two accounts in both directions within {:?}",
+                total, self.config.window
+            );
+            return self.raise(AlertKind::WashTrading, &trade.market, vec![low, high], details, now)
+                .into_iter()
+                .collect();
+        }
+
+        Vec::new()
+    }
+
+    fn on_cancel(&mut self, order: &Order) -> Vec<Alert> {
+        let now = order.updated_at;
+        let activity = self.activity.entry((order.user_id, order.market.clone())).or_default();
+        activity.prune(now - self.window);
+        activity.cancels.push_back(Cancel {
+            at: now,
+            placed_at: order.created_at,
+            side: order.side,
+            price: order.price,
+        });
+
+        let cancels = activity.cancels.len();
+        let fills = activity.fills.len();
+
+        // Orders on this side that were resting before the latest fill on the other side and pulled since
+        let opposite_fill = activity.fills.iter().rev()
+            .find(|(_, side)| *side != order.side)
+            .map(|(at, _)| *at);
+        let layered_levels = opposite_fill.map_or(0, |filled_at| {
+            activity.cancels.iter()
+                .filter(|cancel| cancel.side == order.side && cancel.placed_at <= filled_at && cancel.at >= filled_at)
+                .filter_map(|cancel| cancel.price)
+                .collect::<HashSet<_>>()
+                .len()
+        });
+
+        let mut alerts = Vec::new();
+
+        if cancels >= self.config.min_cancels && cancels >= fills.max(1) * self.config.max_cancel_to_fill {
+            let details = format!("{} cancels against {} fills within {:?}", cancels, fills, self.config.window);
+            alerts.extend(self.raise(AlertKind::HighCancelToFill, &order.market, vec![order.user_id], details, now));
+        }
+
+        if layered_levels >= self.config.layering_min_levels {
+            let details = format!(
+                "Pulled {} {} price levels after a fill on the other side",
+                layered_levels,
+                match order.side {
+                    Side::Buy => "bid",
+                    Side::Sell => "ask",
+                }
+            );
+            alerts.extend(self.raise(AlertKind::Layering, &order.market, vec![order.user_id], details, now));
+        }
+
+        alerts
+    }
+
+    /// Build an alert unless the same pattern was already reported within the window
+    fn raise(
+        &mut self,
+        kind: AlertKind,
+        market: &str,
+        account_ids: Vec<Uuid>,
+        details: String,
+        now: DateTime<Utc>,
+    ) -> Option<Alert> {
+        let key = (kind, account_ids.clone(), market.to_string());
+        if self.raised.get(&key).is_some_and(|at| now - *at < self.window) {
+            return None;
+        }
+        self.raised.insert(key, now);
+
+        Some(Alert::new(kind, market, account_ids, details))
+    }
+
+    /// Drop state that has fallen out of the window, at most once per window
+    fn sweep(&mut self, now: DateTime<Utc>) {
+        if self.last_sweep.is_some_and(|at| now - at < self.window) {
+            return;
+        }
+        self.last_sweep = Some(now);
+
+        let cutoff = now - self.window;
+        self.activity.retain(|_, activity| {
+            activity.prune(cutoff);
+            !activity.is_empty()
+        });
+        self.pairs.retain(|_, trades| trades.back().is_some_and(|(at, _)| *at >= cutoff));
+        self.raised.retain(|_, at| *at >= cutoff);
+    }
+}
+
+/// Surveillance of the engine event stream
+pub struct Surveillance {
+    detector: Mutex<Detector>,
+    alerts: RwLock<VecDeque<Alert>>,
+}
+
+impl Surveillance {
+    /// Create a surveillance instance fed through `process`
+    pub fn new(config: SurveillanceConfig) -> Self {
+        Self {
+            detector: Mutex::new(Detector::new(config)),
+            alerts: RwLock::new(VecDeque::new()),
+        }
+    }
+
+    /// Watch an engine's events on a background thread
+    ///
+    /// The thread exits once the engine is dropped.
+    pub fn start(engine: &MatchingEngine, config: SurveillanceConfig) -> Arc<Self> {
+        let surveillance = Arc::new(Self::new(config));
+        let events = engine.subscribe_events();
+
+        let worker = surveillance.clone();
+        thread::Builder::new()
+            .name("surveillance".to_string())
+            .spawn(move || {
+                for event in events {
+                    worker.process(&event);
+                }
+            })
+            .expect("failed to spawn surveillance thread");
+
+        surveillance
+    }
+
+    /// Feed one engine event, returning any alerts it raised
+    pub fn process(&self, event: &EngineEvent) -> Vec<Alert> {
+        let alerts = self.detector.lock().unwrap().process(event);
+        if alerts.is_empty() {
+            return alerts;
+        }
+
+        let mut stored = self.alerts.write().unwrap();
+        for alert in &alerts {
+            warn!(
+                target: "surveillance",
+                kind = ?alert.kind,
+                market = %alert.market,
+                accounts = ?alert.account_ids,
+                "{}", alert.details
+            );
+            if stored.len() == ALERT_CAPACITY {
+                stored.pop_back();
+            }
+            stored.push_front(alert.clone());
+        }
+
+        alerts
+    }
+
+    /// Get recent alerts, newest first, optionally for one account or pattern
+    pub fn alerts(&self, account_id: Option<Uuid>, kind: Option<AlertKind>, limit: usize) -> Vec<Alert> {
+        self.alerts.read().unwrap()
+            .iter()
+            .filter(|alert| account_id.is_none_or(|id| alert.involves(id)))
+            .filter(|alert| kind.is_none_or(|kind| alert.kind == kind))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,124 @@
+//! Per-account order entry throttles
+//!
+//! Limits how many new orders and cancels each account may send per second on
+//! each market, independent of the transport the orders arrive on. Each limit
+//! is a token bucket that holds one second's worth of requests.
+
+use std::time::Instant;
+
+use common::error::{Error, Result};
+use dashmap::DashMap;
+use uuid::Uuid;
+
+/// Engine throttle limits, where zero means unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThrottleConfig {
+    /// New orders per second per account per market
+    pub max_orders_per_sec: u32,
+    /// Cancels per second per account per market
+    pub max_cancels_per_sec: u32,
+}
+
+impl ThrottleConfig {
+    /// No throttling
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Limit new orders and cancels per second, zero meaning unlimited
+    pub fn new(max_orders_per_sec: u32, max_cancels_per_sec: u32) -> Self {
+        Self {
+            max_orders_per_sec,
+            max_cancels_per_sec,
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets for one kind of request, keyed by account and market
+struct Limit {
+    per_sec: u32,
+    buckets: DashMap<(Uuid, String), Bucket>,
+}
+
+impl Limit {
+    fn new(per_sec: u32) -> Self {
+        Self {
+            per_sec,
+            buckets: DashMap::new(),
+        }
+    }
+
+    fn take(&self, account_id: Uuid, market: &str) -> bool {
+        if self.per_sec == 0 {
+            return true;
+        }
+
+        let capacity = self.per_sec as f64;
+        let now = Instant::now();
+        let mut bucket = self.buckets.entry((account_id, market.to_string())).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * capacity).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Per-account order and cancel throttles
+pub(crate) struct Throttle {
+    config: ThrottleConfig,
+    orders: Limit,
+    cancels: Limit,
+}
+
+impl Throttle {
+    pub(crate) fn new(config: ThrottleConfig) -> Self {
+        Self {
+            config,
+            orders: Limit::new(config.max_orders_per_sec),
+            cancels: Limit::new(config.max_cancels_per_sec),
+        }
+    }
+
+    pub(crate) fn config(&self) -> ThrottleConfig {
+        self.config
+    }
+
+    /// Take a new-order token for an account on a market
+    pub(crate) fn check_order(&self, account_id: Uuid, market: &str) -> Result<()> {
+        if self.orders.take(account_id, market) {
+            Ok(())
+        } else {
+            Err(Error::RateLimitExceeded(format!(
+                "Account {} exceeded {} new orders per second on {}",
+                account_id, self.config.max_orders_per_sec, market
+            )))
+        }
+    }
+
+    /// Take a cancel token for an account on a market
+    pub(crate) fn check_cancel(&self, account_id: Uuid, market: &str) -> Result<()> {
+        if self.cancels.take(account_id, market) {
+            Ok(())
+        } else {
+            Err(Error::RateLimitExceeded(format!(
+                "Account {} exceeded {} cancels per second on {}",
+                account_id, self.config.max_cancels_per_sec, market
+            )))
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Crossed book checks
+//!
+//! After every command that changes a book, the engine checks that its best
+//! bid is below its best ask. A crossed or locked book outside an auction
+//! means an order rested where it should have matched, which only a bug in
+//! matching or in how commands were sequenced can cause. Orders that would
+//! rest across the book are refused rather than added.
+
+use chrono::{DateTime, Utc};
+use common::decimal::Price;
+use dashmap::DashMap;
+use serde::Serialize;
+use tracing::error;
+
+/// What the engine does with a book found crossed after a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookVerification {
+    /// No checks
+    Off,
+    /// Log and count crossed books, the default in release builds
+    Count,
+    /// Panic on a crossed book, the default in debug builds
+    ///
+    /// The panic comes after the book's lock is released, so the market
+    /// keeps working for later commands.
+    Assert,
+}
+
+impl Default for BookVerification {
+    fn default() -> Self {
+        if cfg!(debug_assertions) {
+            BookVerification::Assert
+        } else {
+            BookVerification::Count
+        }
+    }
+}
+
+impl std::str::FromStr for BookVerification {
+    type Err = String;
+
+    /// Parse `off`, `count` or `assert`
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(BookVerification::Off),
+            "count" => Ok(BookVerification::Count),
+            "assert" => Ok(BookVerification::Assert),
+            _ => Err(format!("Unknown book verification: {}", s)),
+        }
+    }
+}
+
+/// Crossed book checks of one market since startup
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BookChecks {
+    /// Market symbol
+    pub market: String,
+    /// Commands the book was checked after
+    pub checks: u64,
+    /// Checks that found the best bid at or above the best ask
+    pub crossings: u64,
+    /// Orders refused because resting them would have crossed the book
+    pub refused_orders: u64,
+    /// When the book was last found crossed
+    pub last_crossed_at: Option<DateTime<Utc>>,
+    /// Best bid when the book was last found crossed
+    pub last_crossed_bid: Option<Price>,
+    /// Best ask when the book was last found crossed
+    pub last_crossed_ask: Option<Price>,
+}
+
+/// Checks of every market's book, under one verification mode
+pub(crate) struct Verifier {
+    mode: BookVerification,
+    checks: DashMap<String, BookChecks>,
+}
+
+impl Verifier {
+    pub(crate) fn new(mode: BookVerification) -> Self {
+        Self {
+            mode,
+            checks: DashMap::new(),
+        }
+    }
+
+    pub(crate) fn mode(&self) -> BookVerification {
+        self.mode
+    }
+
+    /// Check a market's best prices after `command`
+    pub(crate) fn check(&self, market: &str, best_bid: Option<Price>, best_ask: Option<Price>, command: &str, now: DateTime<Utc>) {
+        if self.mode == BookVerification::Off {
+            return;
+        }
+
+        let crossed = best_bid.zip(best_ask).filter(|(bid, ask)| bid >= ask);
+        let mut checks = self.entry(market);
+        checks.checks += 1;
+        let Some((bid, ask)) = crossed else {
+            return;
+        };
+        checks.crossings += 1;
+        checks.last_crossed_at = Some(now);
+        checks.last_crossed_bid = Some(bid);
+        checks.last_crossed_ask = Some(ask);
+        drop(checks);
+
+        error!("Book of {} is crossed after {}: best bid {} >= best ask {}", market, command, bid, ask);
+        assert!(
+            self.mode != BookVerification::Assert,
+            "Book of {} is crossed after {}: best bid {} >= best ask {}", market, command, bid, ask
+        );
+    }
+
+    /// Count an order refused for resting across a market's book
+    pub(crate) fn refused(&self, market: &str) {
+        self.entry(market).refused_orders += 1;
+    }
+
+    /// Checks of every market checked so far, by market
+    pub(crate) fn report(&self) -> Vec<BookChecks> {
+        let mut report: Vec<BookChecks> = self.checks.iter().map(|entry| entry.value().clone()).collect();
+        report.sort_by(|a, b| a.market.cmp(&b.market));
+        report
+    }
+
+    fn entry(&self, market: &str) -> dashmap::mapref::one::RefMut<'_, String, BookChecks> {
+        self.checks.entry(market.to_string()).or_insert_with(|| BookChecks {
+            market: market.to_string(),
+            ..BookChecks::default()
+        })
+    }
+}
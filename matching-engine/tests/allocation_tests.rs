@@ -0,0 +1,113 @@
+use common::decimal::{dec, Quantity};
+use common::error::Error;
+use common::model::market::Allocation;
+use common::model::order::{Order, Side, TimeInForce};
+use matching_engine::MatchingEngine;
+use uuid::Uuid;
+
+const MARKET: &str = "ES/USD";
+
+fn engine(allocation: Allocation) -> MatchingEngine {
+    let engine = MatchingEngine::new();
+    engine.register_market(MARKET.to_string());
+    engine.set_allocation(MARKET, allocation).unwrap();
+    engine
+}
+
+fn pro_rata(min_allocation: Quantity, lot_size: Quantity) -> Allocation {
+    Allocation::ProRata { min_allocation, lot_size }
+}
+
+/// Rest sell orders of the given sizes at 100, oldest first, returning their IDs
+fn rest_asks(engine: &MatchingEngine, quantities: &[Quantity]) -> Vec<Uuid> {
+    quantities.iter()
+        .map(|quantity| {
+            let order = Order::new_limit(Uuid::new_v4(), MARKET.to_string(), Side::Sell, 100.into(), *quantity, TimeInForce::GTC);
+            engine.place_order(order).unwrap().taker_order.unwrap().id
+        })
+        .collect()
+}
+
+/// Buy `quantity` up to `price`, returning each fill's maker order and quantity in trade order
+fn buy(engine: &MatchingEngine, price: i64, quantity: Quantity) -> Vec<(Uuid, Quantity)> {
+    let order = Order::new_limit(Uuid::new_v4(), MARKET.to_string(), Side::Buy, price.into(), quantity, TimeInForce::IOC);
+    let result = engine.place_order(order).unwrap();
+    result.trades.iter().map(|trade| (trade.seller_order_id, trade.quantity)).collect()
+}
+
+#[test]
+fn test_fifo_is_the_default() {
+    let engine = MatchingEngine::new();
+    engine.register_market(MARKET.to_string());
+    assert_eq!(engine.allocation(MARKET).unwrap(), Allocation::Fifo);
+
+    let makers = rest_asks(&engine, &[dec!(1), dec!(2), dec!(3)]);
+    assert_eq!(buy(&engine, 100, dec!(2)), [(makers[0], dec!(1)), (makers[1], dec!(1))]);
+}
+
+#[test]
+fn test_pro_rata_shares_in_proportion_to_size() {
+    let engine = engine(pro_rata(Quantity::ZERO, dec!(0.1)));
+    let makers = rest_asks(&engine, &[dec!(1), dec!(3)]);
+
+    // Fills stay in time priority, each sized by its share of the level
+    assert_eq!(buy(&engine, 100, dec!(2)), [(makers[0], dec!(0.5)), (makers[1], dec!(1.5))]);
+    let (_, asks) = engine.get_market_depth(MARKET, 1).unwrap();
+    assert_eq!(asks, [(dec!(100), dec!(2))]);
+}
+
+#[test]
+fn test_pro_rata_rounds_down_to_lots_and_gives_the_rest_in_time_priority() {
+    let engine = engine(pro_rata(Quantity::ZERO, dec!(0.1)));
+    let makers = rest_asks(&engine, &[dec!(1), dec!(1), dec!(1)]);
+
+    // A third of 1 is 0.3 in lots of 0.1, and the 0.1 left over goes to the oldest
+    let fills = buy(&engine, 100, dec!(1));
+    assert_eq!(fills, [(makers[0], dec!(0.4)), (makers[1], dec!(0.3)), (makers[2], dec!(0.3))]);
+}
+
+#[test]
+fn test_pro_rata_drops_shares_below_the_minimum_allocation() {
+    let engine = engine(pro_rata(dec!(1), dec!(0.1)));
+    let makers = rest_asks(&engine, &[dec!(10), dec!(1)]);
+
+    // The small order's share of 0.5 is dropped and goes to the oldest order
+    assert_eq!(buy(&engine, 100, dec!(5.5)), [(makers[0], dec!(5.5))]);
+}
+
+#[test]
+fn test_pro_rata_fills_every_order_of_a_level_taken_in_full() {
+    let engine = engine(pro_rata(dec!(1), dec!(1)));
+    let makers = rest_asks(&engine, &[dec!(2), dec!(1)]);
+    let order = Order::new_limit(Uuid::new_v4(), MARKET.to_string(), Side::Sell, 101.into(), dec!(4), TimeInForce::GTC);
+    let far = engine.place_order(order).unwrap().taker_order.unwrap().id;
+
+    // The first level fills in full before the next is shared
+    let fills = buy(&engine, 101, dec!(5));
+    assert_eq!(fills, [(makers[0], dec!(2)), (makers[1], dec!(1)), (far, dec!(2))]);
+}
+
+#[test]
+fn test_allocation_is_validated() {
+    let engine = MatchingEngine::new();
+    engine.register_market(MARKET.to_string());
+
+    assert!(matches!(engine.set_allocation("BTC/USD", Allocation::Fifo), Err(Error::MarketNotFound(_))));
+    assert!(matches!(engine.allocation("BTC/USD"), Err(Error::MarketNotFound(_))));
+    let no_lots = pro_rata(dec!(1), Quantity::ZERO);
+    assert!(matches!(engine.set_allocation(MARKET, no_lots), Err(Error::ValidationError(_))));
+    let negative = pro_rata(dec!(-1), dec!(1));
+    assert!(matches!(engine.set_allocation(MARKET, negative), Err(Error::ValidationError(_))));
+    assert_eq!(engine.allocation(MARKET).unwrap(), Allocation::Fifo);
+}
+
+#[test]
+fn test_allocation_is_parsed() {
+    assert_eq!("fifo".parse::<Allocation>().unwrap(), Allocation::Fifo);
+    assert_eq!("pro_rata:2:0.5".parse::<Allocation>().unwrap(), pro_rata(dec!(2), dec!(0.5)));
+    // The lot size is left for the caller to fill in
+    assert_eq!("PRO_RATA:2".parse::<Allocation>().unwrap(), pro_rata(dec!(2), Quantity::ZERO));
+    assert!("lifo".parse::<Allocation>().is_err());
+    assert!("pro_rata:two".parse::<Allocation>().is_err());
+    assert!("pro_rata:1:1:1".parse::<Allocation>().is_err());
+}
@@ -0,0 +1,210 @@
+use std::sync::Arc;
+
+use chrono::{TimeZone, Utc};
+use common::clock::{Clock, ManualClock};
+use common::decimal::Price;
+use common::error::Error;
+use common::flags::BOOK_LEVEL_PRUNING;
+use common::model::market::{BookLimits, LevelPolicy};
+use common::model::order::{Order, RejectReason, Side, Status, TimeInForce};
+use matching_engine::{EngineEvent, MatchingEngine};
+use uuid::Uuid;
+
+const MARKET: &str = "BTC/USD";
+
+fn engine(limits: BookLimits) -> MatchingEngine {
+    let engine = MatchingEngine::new();
+    engine.register_market(MARKET.to_string());
+    engine.set_book_limits(MARKET, Some(limits)).unwrap();
+    engine
+}
+
+fn limit(user_id: Uuid, side: Side, price: i64) -> Order {
+    Order::new_limit(user_id, MARKET.to_string(), side, price.into(), 1.into(), TimeInForce::GTC)
+}
+
+/// Place an order, returning it as it left the engine
+fn place(engine: &MatchingEngine, order: Order) -> Arc<Order> {
+    engine.place_order(order).unwrap().taker_order.unwrap()
+}
+
+fn levels(engine: &MatchingEngine) -> (Vec<Price>, Vec<Price>) {
+    let (bids, asks) = engine.get_market_depth(MARKET, 100).unwrap();
+    let prices = |levels: Vec<(Price, _)>| levels.into_iter().map(|(price, _)| price).collect();
+    (prices(bids), prices(asks))
+}
+
+fn prices(prices: &[i64]) -> Vec<Price> {
+    prices.iter().map(|price| Price::from(*price)).collect()
+}
+
+#[test]
+fn test_limits_are_validated() {
+    let engine = MatchingEngine::new();
+    engine.register_market(MARKET.to_string());
+
+    assert!(matches!(engine.set_book_limits("ETH/USD", Some(BookLimits::default())), Err(Error::MarketNotFound(_))));
+    assert!(matches!(engine.book_limits("ETH/USD"), Err(Error::MarketNotFound(_))));
+
+    let zero = BookLimits { max_levels: Some(0), ..BookLimits::default() };
+    assert!(matches!(engine.set_book_limits(MARKET, Some(zero)), Err(Error::ValidationError(_))));
+
+    let limits = BookLimits { max_orders_per_account: Some(2), ..BookLimits::default() };
+    engine.set_book_limits(MARKET, Some(limits.clone())).unwrap();
+    assert_eq!(engine.book_limits(MARKET).unwrap(), limits);
+    engine.set_book_limits(MARKET, None).unwrap();
+    assert_eq!(engine.book_limits(MARKET).unwrap(), BookLimits::default());
+}
+
+#[test]
+fn test_orders_per_account_are_capped() {
+    let engine = engine(BookLimits { max_orders_per_account: Some(2), ..BookLimits::default() });
+    let (account, other) = (Uuid::new_v4(), Uuid::new_v4());
+
+    engine.place_order(limit(account, Side::Buy, 100)).unwrap();
+    engine.place_order(limit(account, Side::Sell, 110)).unwrap();
+
+    let refused = place(&engine, limit(account, Side::Buy, 99));
+    assert_eq!(refused.status, Status::Expired);
+    assert_eq!(refused.reject_reason, Some(RejectReason::BookLimit));
+    assert_eq!(place(&engine, limit(other, Side::Buy, 99)).status, Status::New);
+
+    // A filled order frees its place
+    let fill = engine.place_order(limit(other, Side::Sell, 100)).unwrap();
+    assert_eq!(fill.trades.len(), 1);
+    assert_eq!(place(&engine, limit(account, Side::Buy, 98)).status, Status::New);
+}
+
+#[test]
+fn test_orders_far_from_mid_are_refused() {
+    let engine = engine(BookLimits { max_distance_bps: Some(500), ..BookLimits::default() });
+    let account = Uuid::new_v4();
+
+    // Without a two-sided book there is no mid to measure from
+    engine.place_order(limit(account, Side::Buy, 90)).unwrap();
+    engine.place_order(limit(account, Side::Sell, 110)).unwrap();
+
+    // The mid is 100, so 5% allows 95 to 105
+    let far = place(&engine, limit(account, Side::Buy, 94));
+    assert_eq!(far.status, Status::Expired);
+    assert_eq!(far.reject_reason, Some(RejectReason::BookLimit));
+    assert_eq!(place(&engine, limit(account, Side::Sell, 105)).status, Status::New);
+}
+
+#[test]
+fn test_new_levels_are_refused_once_full() {
+    let engine = engine(BookLimits { max_levels: Some(2), ..BookLimits::default() });
+    let account = Uuid::new_v4();
+
+    engine.place_order(limit(account, Side::Buy, 100)).unwrap();
+    engine.place_order(limit(account, Side::Buy, 99)).unwrap();
+
+    // Joining a level is allowed, opening a third is not
+    assert_eq!(place(&engine, limit(account, Side::Buy, 99)).status, Status::New);
+    let refused = place(&engine, limit(account, Side::Buy, 101));
+    assert_eq!(refused.status, Status::Expired);
+    assert_eq!(refused.reject_reason, Some(RejectReason::BookLimit));
+
+    // Each side has its own levels
+    assert_eq!(place(&engine, limit(account, Side::Sell, 110)).status, Status::New);
+    assert_eq!(levels(&engine), (prices(&[100, 99]), prices(&[110])));
+}
+
+#[test]
+fn test_farthest_level_is_pruned_for_better_prices() {
+    let engine = engine(BookLimits {
+        max_levels: Some(2),
+        level_policy: LevelPolicy::PruneFarthest,
+        ..BookLimits::default()
+    });
+    let events = engine.subscribe_events();
+    let (maker, other, taker) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+    engine.place_order(limit(maker, Side::Sell, 110)).unwrap();
+    let farthest = [
+        place(&engine, limit(maker, Side::Sell, 111)),
+        place(&engine, limit(other, Side::Sell, 111)),
+    ];
+
+    // A worse price than the farthest level is refused
+    let worse = place(&engine, limit(taker, Side::Sell, 112));
+    assert_eq!(worse.status, Status::Expired);
+
+    let result = engine.place_order(limit(taker, Side::Sell, 109)).unwrap();
+    assert_eq!(result.taker_order.unwrap().status, Status::New);
+    assert_eq!(result.expired_orders.len(), 2);
+    for (expired, resting) in result.expired_orders.iter().zip(&farthest) {
+        assert_eq!(expired.id, resting.id);
+        assert_eq!(expired.status, Status::Expired);
+        assert_eq!(expired.reject_reason, Some(RejectReason::BookLimit));
+    }
+    assert_eq!(levels(&engine), (prices(&[]), prices(&[109, 110])));
+    assert!(engine.get_order(farthest[0].id).is_none());
+
+    let expired: Vec<Uuid> = events.try_iter()
+        .filter_map(|event| match event {
+            EngineEvent::OrderExpired(order) => Some(order.id),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(expired, [farthest[0].id, farthest[1].id]);
+}
+
+#[test]
+fn test_pruning_is_turned_off_by_its_feature_flag() {
+    let engine = engine(BookLimits {
+        max_levels: Some(2),
+        level_policy: LevelPolicy::PruneFarthest,
+        ..BookLimits::default()
+    });
+    engine.feature_flags().set(BOOK_LEVEL_PRUNING, false).unwrap();
+    let (maker, taker) = (Uuid::new_v4(), Uuid::new_v4());
+    engine.place_order(limit(maker, Side::Sell, 110)).unwrap();
+    engine.place_order(limit(maker, Side::Sell, 111)).unwrap();
+
+    // The book is full, so even a better price is refused as under the reject policy
+    let better = place(&engine, limit(taker, Side::Sell, 109));
+    assert_eq!(better.status, Status::Expired);
+    assert_eq!(levels(&engine), (prices(&[]), prices(&[110, 111])));
+
+    engine.feature_flags().set(BOOK_LEVEL_PRUNING, true).unwrap();
+    let result = engine.place_order(limit(taker, Side::Sell, 109)).unwrap();
+    assert_eq!(result.expired_orders.len(), 1);
+    assert_eq!(levels(&engine), (prices(&[]), prices(&[109, 110])));
+}
+
+#[test]
+fn test_orders_past_their_maximum_age_are_expired() {
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    let clock = Arc::new(ManualClock::new(start));
+    let engine = MatchingEngine::new().with_clock(clock.clone());
+    engine.register_market(MARKET.to_string());
+    engine.set_book_limits(MARKET, Some(BookLimits { max_order_age_secs: Some(60), ..BookLimits::default() })).unwrap();
+    let events = engine.subscribe_events();
+    let account = Uuid::new_v4();
+
+    // Orders are stamped by the engine's clock when accepted
+    let old = place(&engine, limit(account, Side::Buy, 99));
+    assert_eq!(old.created_at, start);
+    clock.advance(chrono::Duration::seconds(30));
+    let young = place(&engine, limit(account, Side::Sell, 101));
+    let now = clock.now();
+    assert_eq!(young.created_at, now);
+
+    assert!(engine.expire_stale_orders(now).is_empty());
+
+    let later = now + chrono::Duration::seconds(31);
+    let expired = engine.expire_stale_orders(later);
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].id, old.id);
+    assert_eq!(expired[0].status, Status::Expired);
+    assert_eq!(expired[0].reject_reason, Some(RejectReason::MaxAge));
+    assert_eq!(expired[0].updated_at, later);
+    assert!(engine.get_order(old.id).is_none());
+    assert!(engine.get_order(young.id).is_some());
+    assert!(events.try_iter().any(|event| matches!(event, EngineEvent::OrderExpired(order) if order.id == old.id)));
+
+    // Markets without a maximum age keep their orders
+    engine.set_book_limits(MARKET, None).unwrap();
+    assert!(engine.expire_stale_orders(later + chrono::Duration::days(1)).is_empty());
+}
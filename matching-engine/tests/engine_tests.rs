@@ -1,8 +1,14 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 use uuid::Uuid;
+use common::clock::{Clock, ManualClock};
+use common::id::MonotonicIdGenerator;
 use common::decimal::{Price, Quantity};
-use common::model::order::{Order, Status, OrderType, Side, TimeInForce};
+use common::model::fee::FeeSchedule;
+use common::model::order::{Order, RejectReason, Status, OrderType, Side, TimeInForce};
+use common::error::Error;
 use matching_engine::engine::MatchingEngine;
+use matching_engine::{EngineEvent, ThrottleConfig};
 
 fn create_test_order(
     user_id: Uuid,
@@ -27,6 +33,14 @@ fn create_test_order(
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
         average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
     }
 }
 
@@ -372,3 +386,532 @@ fn test_price_time_priority() {
     assert_eq!(result.maker_orders.len(), 1);
     assert_eq!(result.maker_orders[0].id, sell_order1.id);
 }
+
+#[test]
+fn test_sweep_across_levels_averages_fill_price() {
+    let engine = MatchingEngine::new();
+    engine.register_market("BTC/USD".to_string());
+    
+    let maker = Uuid::new_v4();
+    for (price, quantity) in [(100, 1), (101, 1), (102, 2)] {
+        engine.place_order(create_test_order(
+            maker,
+            "BTC/USD",
+            Side::Sell,
+            OrderType::Limit,
+            Some(Quantity::new(price, 0)),
+            Quantity::new(quantity, 0)
+        )).unwrap();
+    }
+    
+    // Sweep the first two levels and half of the third
+    let buy = create_test_order(
+        Uuid::new_v4(),
+        "BTC/USD",
+        Side::Buy,
+        OrderType::Limit,
+        Some(Quantity::new(102, 0)),
+        Quantity::new(3, 0)
+    );
+    let result = engine.place_order(buy).unwrap();
+    
+    assert_eq!(result.trades.len(), 3);
+    let prices: Vec<Price> = result.trades.iter().map(|trade| trade.price).collect();
+    assert_eq!(prices, [Quantity::new(100, 0), Quantity::new(101, 0), Quantity::new(102, 0)]);
+    
+    let taker = result.taker_order.unwrap();
+    assert_eq!(taker.status, Status::Filled);
+    assert_eq!(taker.average_fill_price, Some(Quantity::new(101, 0)));
+    
+    // The last maker rests with the rest of its quantity at its own price
+    let last = result.maker_orders.last().unwrap();
+    assert_eq!(last.status, Status::PartiallyFilled);
+    assert_eq!(last.remaining_quantity, Quantity::new(1, 0));
+    assert_eq!(last.average_fill_price, Some(Quantity::new(102, 0)));
+    assert_eq!(engine.get_order(last.id).unwrap().remaining_quantity, Quantity::new(1, 0));
+}
+
+#[test]
+fn test_market_order_without_liquidity_is_rejected() {
+    let engine = MatchingEngine::new();
+    engine.register_market("BTC/USD".to_string());
+    
+    let order = create_test_order(
+        Uuid::new_v4(),
+        "BTC/USD",
+        Side::Buy,
+        OrderType::Market,
+        None,
+        Quantity::new(1, 0)
+    );
+    
+    let result = engine.place_order(order).unwrap();
+    let taker = result.taker_order.unwrap();
+    assert_eq!(taker.status, Status::Rejected);
+    assert_eq!(taker.reject_reason, Some(RejectReason::NoLiquidity));
+    assert!(taker.reject_message.is_some());
+    assert!(result.trades.is_empty());
+}
+
+#[test]
+fn test_ioc_remainder_expires() {
+    let engine = MatchingEngine::new();
+    engine.register_market("BTC/USD".to_string());
+    
+    let sell_order = create_test_order(
+        Uuid::new_v4(),
+        "BTC/USD",
+        Side::Sell,
+        OrderType::Limit,
+        Some(Quantity::new(10000, 0)),
+        Quantity::new(1, 0)
+    );
+    engine.place_order(sell_order).unwrap();
+    
+    let mut buy_order = create_test_order(
+        Uuid::new_v4(),
+        "BTC/USD",
+        Side::Buy,
+        OrderType::Limit,
+        Some(Quantity::new(10000, 0)),
+        Quantity::new(3, 0)
+    );
+    buy_order.time_in_force = TimeInForce::IOC;
+    
+    let result = engine.place_order(buy_order.clone()).unwrap();
+    let taker = result.taker_order.unwrap();
+    assert_eq!(result.trades.len(), 1);
+    assert_eq!(taker.status, Status::Expired);
+    assert_eq!(taker.reject_reason, Some(RejectReason::ImmediateOrCancel));
+    assert_eq!(taker.filled_quantity, Quantity::new(1, 0));
+    assert_eq!(taker.remaining_quantity, Quantity::new(2, 0));
+    
+    // The remainder must not rest on the book
+    assert!(engine.get_order(buy_order.id).is_none());
+}
+
+#[test]
+fn test_fok_without_full_liquidity_expires_untouched() {
+    let engine = MatchingEngine::new();
+    engine.register_market("BTC/USD".to_string());
+    
+    let sell_order = create_test_order(
+        Uuid::new_v4(),
+        "BTC/USD",
+        Side::Sell,
+        OrderType::Limit,
+        Some(Quantity::new(10000, 0)),
+        Quantity::new(1, 0)
+    );
+    engine.place_order(sell_order.clone()).unwrap();
+    
+    let mut buy_order = create_test_order(
+        Uuid::new_v4(),
+        "BTC/USD",
+        Side::Buy,
+        OrderType::Limit,
+        Some(Quantity::new(10000, 0)),
+        Quantity::new(2, 0)
+    );
+    buy_order.time_in_force = TimeInForce::FOK;
+    
+    let result = engine.place_order(buy_order).unwrap();
+    let taker = result.taker_order.unwrap();
+    assert!(result.trades.is_empty());
+    assert_eq!(taker.status, Status::Expired);
+    assert_eq!(taker.reject_reason, Some(RejectReason::FillOrKill));
+    
+    // The resting liquidity is untouched
+    let maker = engine.get_order(sell_order.id).unwrap();
+    assert_eq!(maker.remaining_quantity, Quantity::new(1, 0));
+}
+
+#[test]
+fn test_preview_estimates_fills_without_matching() {
+    let engine = MatchingEngine::new();
+    engine.register_market("BTC/USD".to_string());
+    
+    for price in [100, 101] {
+        let ask = create_test_order(
+            Uuid::new_v4(), "BTC/USD", Side::Sell, OrderType::Limit, Some(Price::new(price, 0)), Quantity::ONE,
+        );
+        engine.place_order(ask).unwrap();
+    }
+    
+    let mut bid = create_test_order(
+        Uuid::new_v4(), "BTC/USD", Side::Buy, OrderType::Market, None, Quantity::new(15, 1),
+    );
+    let fills = engine.preview_order(&bid).unwrap();
+    assert_eq!(fills, [(Price::new(100, 0), Quantity::ONE), (Price::new(101, 0), Quantity::new(5, 1))]);
+    
+    // The book is untouched
+    let (_, asks) = engine.get_market_depth("BTC/USD", 10).unwrap();
+    assert_eq!(asks.len(), 2);
+    
+    // Fill-or-kill orders beyond the liquidity get nothing
+    bid.order_type = OrderType::Limit;
+    bid.price = Some(Price::new(100, 0));
+    bid.time_in_force = TimeInForce::FOK;
+    assert!(engine.preview_order(&bid).unwrap().is_empty());
+    
+    // Blocked accounts are refused as on placement
+    engine.block_account(bid.user_id);
+    assert!(matches!(engine.preview_order(&bid), Err(Error::AuthorizationError(_))));
+}
+
+#[test]
+fn test_market_order_stops_at_slippage_limit() {
+    let engine = MatchingEngine::new();
+    engine.register_market("BTC/USD".to_string());
+    
+    for price in [100, 101, 110] {
+        let ask = create_test_order(
+            Uuid::new_v4(), "BTC/USD", Side::Sell, OrderType::Limit, Some(Price::new(price, 0)), Quantity::ONE,
+        );
+        engine.place_order(ask).unwrap();
+    }
+    
+    // 5% from the best ask of 100 allows 101 but not 110
+    let mut bid = create_test_order(
+        Uuid::new_v4(), "BTC/USD", Side::Buy, OrderType::Market, None, Quantity::new(3, 0),
+    );
+    bid.max_slippage_bps = Some(500);
+    let result = engine.place_order(bid).unwrap();
+    assert_eq!(result.price_cap, Some(Price::new(105, 0)));
+    assert_eq!(result.trades.len(), 2);
+    
+    let taker = result.taker_order.unwrap();
+    assert_eq!(taker.status, Status::Expired);
+    assert_eq!(taker.reject_reason, Some(RejectReason::PriceCap));
+    assert_eq!(taker.remaining_quantity, Quantity::ONE);
+    
+    let (_, asks) = engine.get_market_depth("BTC/USD", 10).unwrap();
+    assert_eq!(asks.len(), 1);
+}
+
+#[test]
+fn test_market_order_takes_tighter_of_price_and_slippage_caps() {
+    let engine = MatchingEngine::new();
+    engine.register_market("BTC/USD".to_string());
+    
+    for price in [100, 99, 95] {
+        let bid = create_test_order(
+            Uuid::new_v4(), "BTC/USD", Side::Buy, OrderType::Limit, Some(Price::new(price, 0)), Quantity::ONE,
+        );
+        engine.place_order(bid).unwrap();
+    }
+    
+    // The explicit cap of 99.5 is tighter than 10% slippage
+    let mut ask = create_test_order(
+        Uuid::new_v4(), "BTC/USD", Side::Sell, OrderType::Market, Some(Price::new(995, 1)), Quantity::new(3, 0),
+    );
+    ask.max_slippage_bps = Some(1000);
+    assert_eq!(engine.preview_order(&ask).unwrap(), [(Price::new(100, 0), Quantity::ONE)]);
+    
+    let result = engine.place_order(ask).unwrap();
+    assert_eq!(result.price_cap, Some(Price::new(995, 1)));
+    assert_eq!(result.trades.len(), 1);
+    assert_eq!(result.taker_order.unwrap().reject_reason, Some(RejectReason::PriceCap));
+    
+    // Without a cap the remainder expires for lack of liquidity as before
+    let ask = create_test_order(
+        Uuid::new_v4(), "BTC/USD", Side::Sell, OrderType::Market, None, Quantity::new(3, 0),
+    );
+    let result = engine.place_order(ask).unwrap();
+    assert!(result.price_cap.is_none());
+    assert_eq!(result.taker_order.unwrap().reject_reason, Some(RejectReason::MarketOrderUnfilled));
+}
+
+#[test]
+fn test_user_cancel_has_no_reject_reason() {
+    let engine = MatchingEngine::new();
+    engine.register_market("BTC/USD".to_string());
+    
+    let order = create_test_order(
+        Uuid::new_v4(),
+        "BTC/USD",
+        Side::Buy,
+        OrderType::Limit,
+        Some(Quantity::new(10000, 0)),
+        Quantity::new(1, 0)
+    );
+    engine.place_order(order.clone()).unwrap();
+    
+    let cancelled = engine.cancel_order(order.id).unwrap();
+    assert_eq!(cancelled.status, Status::Cancelled);
+    assert!(cancelled.reject_reason.is_none());
+    assert!(!cancelled.is_engine_terminated());
+}
+
+#[test]
+fn test_trades_carry_liquidity_flag_and_fees() {
+    let engine = MatchingEngine::with_fee_schedule(
+        FeeSchedule::new(Quantity::new(1, 3), Quantity::new(2, 3)).unwrap()
+    );
+    engine.register_market("BTC/USD".to_string());
+    
+    let buy_order = create_test_order(
+        Uuid::new_v4(),
+        "BTC/USD",
+        Side::Buy,
+        OrderType::Limit,
+        Some(Quantity::new(10000, 0)),
+        Quantity::new(1, 0)
+    );
+    engine.place_order(buy_order).unwrap();
+    
+    let sell_order = create_test_order(
+        Uuid::new_v4(),
+        "BTC/USD",
+        Side::Sell,
+        OrderType::Limit,
+        Some(Quantity::new(10000, 0)),
+        Quantity::new(1, 0)
+    );
+    let result = engine.place_order(sell_order).unwrap();
+    
+    // The resting buyer is the maker and pays in BTC, the incoming seller pays in USD
+    let trade = &result.trades[0];
+    assert!(trade.is_buyer_maker);
+    assert_eq!(trade.maker_fee, Quantity::new(1, 3));
+    assert_eq!(trade.maker_fee_asset, "BTC");
+    assert_eq!(trade.taker_fee, Quantity::new(20, 0));
+    assert_eq!(trade.taker_fee_asset, "USD");
+}
+
+#[test]
+fn test_trades_are_numbered_per_market() {
+    let engine = MatchingEngine::new();
+    engine.register_market("BTC/USD".to_string());
+    engine.register_market("ETH/USD".to_string());
+    
+    // Three resting asks, then one bid taking all of them
+    for price in [100, 101, 102] {
+        let ask = create_test_order(
+            Uuid::new_v4(), "BTC/USD", Side::Sell, OrderType::Limit, Some(Price::new(price, 0)), Quantity::ONE,
+        );
+        engine.place_order(ask).unwrap();
+    }
+    let bid = create_test_order(
+        Uuid::new_v4(), "BTC/USD", Side::Buy, OrderType::Limit, Some(Price::new(102, 0)), Quantity::new(3, 0),
+    );
+    let result = engine.place_order(bid).unwrap();
+    
+    let sequences: Vec<u64> = result.trades.iter().map(|trade| trade.sequence).collect();
+    assert_eq!(sequences, [1, 2, 3]);
+    assert_eq!(engine.last_trade_sequence("BTC/USD").unwrap(), 3);
+    assert_eq!(engine.last_trade_sequence("ETH/USD").unwrap(), 0);
+    assert!(engine.last_trade_sequence("SOL/USD").is_err());
+    
+    // Kept trades can be fetched again by consumers that missed them
+    let missed = engine.trades_after("BTC/USD", 1).unwrap();
+    let ids: Vec<Uuid> = missed.iter().map(|trade| trade.id).collect();
+    assert_eq!(ids, [result.trades[1].id, result.trades[2].id]);
+    assert!(engine.trades_after("BTC/USD", 3).unwrap().is_empty());
+}
+
+#[test]
+fn test_throttle_limits_orders_per_account_and_market() {
+    let engine = MatchingEngine::new().with_throttle(ThrottleConfig::new(2, 0));
+    engine.register_market("BTC/USD".to_string());
+    engine.register_market("ETH/USD".to_string());
+    
+    let user_id = Uuid::new_v4();
+    let order = |user_id, market: &str| create_test_order(
+        user_id,
+        market,
+        Side::Buy,
+        OrderType::Limit,
+        Some(Quantity::new(100, 0)),
+        Quantity::new(1, 0)
+    );
+    
+    assert!(engine.place_order(order(user_id, "BTC/USD")).is_ok());
+    assert!(engine.place_order(order(user_id, "BTC/USD")).is_ok());
+    assert!(matches!(
+        engine.place_order(order(user_id, "BTC/USD")),
+        Err(Error::RateLimitExceeded(_))
+    ));
+    
+    // Other markets and other accounts have their own budgets
+    assert!(engine.place_order(order(user_id, "ETH/USD")).is_ok());
+    assert!(engine.place_order(order(Uuid::new_v4(), "BTC/USD")).is_ok());
+    
+    // Tokens refill over the second
+    std::thread::sleep(std::time::Duration::from_millis(600));
+    assert!(engine.place_order(order(user_id, "BTC/USD")).is_ok());
+}
+
+#[test]
+fn test_throttle_limits_cancels() {
+    let engine = MatchingEngine::new().with_throttle(ThrottleConfig::new(0, 1));
+    engine.register_market("BTC/USD".to_string());
+    
+    let user_id = Uuid::new_v4();
+    let orders: Vec<Order> = (0..2)
+        .map(|_| create_test_order(
+            user_id,
+            "BTC/USD",
+            Side::Buy,
+            OrderType::Limit,
+            Some(Quantity::new(100, 0)),
+            Quantity::new(1, 0)
+        ))
+        .collect();
+    for order in &orders {
+        engine.place_order(order.clone()).unwrap();
+    }
+    
+    assert!(engine.cancel_order(orders[0].id).is_ok());
+    assert!(matches!(
+        engine.cancel_order(orders[1].id),
+        Err(Error::RateLimitExceeded(_))
+    ));
+    
+    // The throttled order stays on the book
+    assert!(engine.get_order(orders[1].id).is_some());
+}
+
+#[test]
+fn test_orders_trades_and_cancels_follow_the_engine_clock() {
+    let start = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+    let clock = Arc::new(ManualClock::new(start));
+    let engine = MatchingEngine::new().with_clock(clock.clone());
+    let market = "BTC/USD";
+    engine.register_market(market.to_string());
+
+    let maker = create_test_order(Uuid::new_v4(), market, Side::Sell, OrderType::Limit, Some(Price::from(100)), Quantity::from(2));
+    let maker = engine.place_order(maker).unwrap().taker_order.unwrap();
+    assert_eq!((maker.created_at, maker.updated_at), (start, start));
+
+    clock.advance(chrono::Duration::seconds(10));
+    let taker = create_test_order(Uuid::new_v4(), market, Side::Buy, OrderType::Limit, Some(Price::from(100)), Quantity::from(1));
+    let result = engine.place_order(taker).unwrap();
+    assert_eq!(result.trades[0].created_at, clock.now());
+    assert_eq!(result.maker_orders[0].updated_at, clock.now());
+
+    clock.advance(chrono::Duration::seconds(10));
+    let cancelled = engine.cancel_order(maker.id).unwrap();
+    assert_eq!(cancelled.updated_at, clock.now());
+    assert_eq!(cancelled.created_at, start);
+}
+
+#[test]
+fn test_rejected_and_expired_orders_follow_the_engine_clock() {
+    let start = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+    let clock = Arc::new(ManualClock::new(start));
+    let engine = MatchingEngine::new().with_clock(clock.clone());
+    let market = "BTC/USD";
+    engine.register_market(market.to_string());
+
+    // A market order with nothing to fill it is rejected
+    clock.advance(chrono::Duration::seconds(10));
+    let order = create_test_order(Uuid::new_v4(), market, Side::Buy, OrderType::Market, None, Quantity::from(1));
+    let rejected = engine.place_order(order).unwrap().taker_order.unwrap();
+    assert_eq!(rejected.status, Status::Rejected);
+    assert_eq!(rejected.updated_at, clock.now());
+
+    // An immediate-or-cancel order that crosses nothing expires
+    clock.advance(chrono::Duration::seconds(10));
+    let mut order = create_test_order(Uuid::new_v4(), market, Side::Buy, OrderType::Limit, Some(Price::from(100)), Quantity::from(1));
+    order.time_in_force = TimeInForce::IOC;
+    let expired = engine.place_order(order).unwrap().taker_order.unwrap();
+    assert_eq!(expired.status, Status::Expired);
+    assert_eq!(expired.updated_at, clock.now());
+}
+
+#[test]
+fn test_trades_take_ids_from_the_engine_generator() {
+    let start = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+    let clock = Arc::new(ManualClock::new(start));
+    let engine = MatchingEngine::new()
+        .with_clock(clock.clone())
+        .with_id_generator(Arc::new(MonotonicIdGenerator::new(7, clock.clone())));
+    let market = "BTC/USD";
+    engine.register_market(market.to_string());
+
+    let maker = create_test_order(Uuid::new_v4(), market, Side::Sell, OrderType::Limit, Some(Price::from(100)), Quantity::from(2));
+    engine.place_order(maker).unwrap();
+    let mut trade_ids = Vec::new();
+    for _ in 0..2 {
+        let taker = create_test_order(Uuid::new_v4(), market, Side::Buy, OrderType::Limit, Some(Price::from(100)), Quantity::from(1));
+        trade_ids.extend(engine.place_order(taker).unwrap().trades.iter().map(|trade| trade.id));
+    }
+
+    assert_eq!(trade_ids.len(), 2);
+    assert!(trade_ids[0] < trade_ids[1]);
+    assert!(trade_ids.iter().all(|id| id.get_version_num() == 7));
+}
+
+#[test]
+fn test_orders_and_trades_are_numbered_per_market() {
+    let engine = MatchingEngine::new();
+    engine.register_market("BTC/USD".to_string());
+    engine.register_market("ETH/USD".to_string());
+
+    let maker = create_test_order(Uuid::new_v4(), "BTC/USD", Side::Sell, OrderType::Limit, Some(Price::from(100)), Quantity::from(2));
+    let maker = engine.place_order(maker).unwrap().taker_order.unwrap();
+    let other = create_test_order(Uuid::new_v4(), "ETH/USD", Side::Sell, OrderType::Limit, Some(Price::from(10)), Quantity::from(1));
+    let other = engine.place_order(other).unwrap().taker_order.unwrap();
+    assert_eq!((maker.sequence, other.sequence), (1, 1));
+
+    let mut trade_sequences = Vec::new();
+    for sequence in 2..4 {
+        let taker = create_test_order(Uuid::new_v4(), "BTC/USD", Side::Buy, OrderType::Market, None, Quantity::from(1));
+        let result = engine.place_order(taker).unwrap();
+        assert_eq!(result.taker_order.unwrap().sequence, sequence);
+        // Resting orders keep the number they arrived with
+        assert_eq!(result.maker_orders[0].sequence, maker.sequence);
+        trade_sequences.extend(result.trades.iter().map(|trade| trade.sequence));
+    }
+
+    // Orders killed on arrival are numbered too, so the numbers have no gaps
+    let unfilled = create_test_order(Uuid::new_v4(), "BTC/USD", Side::Buy, OrderType::Market, None, Quantity::from(1));
+    assert_eq!(engine.place_order(unfilled).unwrap().taker_order.unwrap().sequence, 4);
+
+    assert_eq!(trade_sequences, vec![1, 2]);
+    assert_eq!(engine.last_order_sequence("BTC/USD").unwrap(), 4);
+    assert_eq!(engine.last_trade_sequence("BTC/USD").unwrap(), 2);
+    assert_eq!(engine.last_order_sequence("ETH/USD").unwrap(), 1);
+}
+
+#[test]
+fn test_fills_are_never_published_before_their_placement() {
+    let engine = Arc::new(MatchingEngine::new());
+    engine.register_market("BTC/USD".to_string());
+    let events = engine.subscribe_events();
+
+    // Resting sells are filled by buys placed concurrently on another thread
+    let threads: Vec<_> = [Side::Sell, Side::Buy]
+        .into_iter()
+        .map(|side| {
+            let engine = engine.clone();
+            std::thread::spawn(move || {
+                for _ in 0..2000 {
+                    let order = create_test_order(Uuid::new_v4(), "BTC/USD", side, OrderType::Limit, Some(Price::from(100)), Quantity::from(1));
+                    engine.place_order(order).unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let mut placed = HashSet::new();
+    let mut updates = 0;
+    for event in events.try_iter() {
+        match event {
+            EngineEvent::OrderPlaced(order) => {
+                placed.insert(order.id);
+            }
+            EngineEvent::OrderUpdated(order) => {
+                assert!(placed.contains(&order.id), "order {} updated before it was placed", order.id);
+                updates += 1;
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(placed.len(), 4000);
+    assert!(updates > 0);
+}
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+use common::decimal::{Price, Quantity};
+use common::model::incentive::MakerActivity;
+use common::model::order::{Order, OrderType, Side, Status, TimeInForce};
+use common::model::trade::Trade;
+use matching_engine::engine::MatchingEngine;
+use matching_engine::incentives::QuoteTracker;
+use matching_engine::EngineEvent;
+use uuid::Uuid;
+
+const MARKET: &str = "BTC/USD";
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap()
+}
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    start() + TimeDelta::seconds(seconds)
+}
+
+fn quote(user_id: Uuid, side: Side, price: i64, seconds: i64) -> Order {
+    Order {
+        id: Uuid::new_v4(),
+        user_id,
+        market: MARKET.to_string(),
+        side,
+        order_type: OrderType::Limit,
+        price: Some(Price::new(price, 0)),
+        quantity: Quantity::new(1, 0),
+        remaining_quantity: Quantity::new(1, 0),
+        filled_quantity: Quantity::ZERO,
+        status: Status::New,
+        time_in_force: TimeInForce::GTC,
+        created_at: at(seconds),
+        updated_at: at(seconds),
+        average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
+    }
+}
+
+fn place(tracker: &QuoteTracker, order: &Order) {
+    tracker.process(&EngineEvent::OrderPlaced(Arc::new(order.clone())));
+}
+
+fn cancel(tracker: &QuoteTracker, order: &Order, seconds: i64) {
+    let mut cancelled = order.clone();
+    cancelled.status = Status::Cancelled;
+    cancelled.updated_at = at(seconds);
+    tracker.process(&EngineEvent::OrderCancelled(Arc::new(cancelled)));
+}
+
+fn of(activity: &[MakerActivity], account_id: Uuid) -> MakerActivity {
+    activity.iter().find(|activity| activity.account_id == account_id).cloned().unwrap()
+}
+
+#[test]
+fn test_time_at_top_of_book_and_spread() {
+    let tracker = QuoteTracker::new(start());
+    let (tight, wide) = (Uuid::new_v4(), Uuid::new_v4());
+
+    // The tight maker quotes 99/101 for the whole minute; the wide maker quotes
+    // 98/102 and improves its bid to 100 for the last 20 seconds
+    place(&tracker, &quote(tight, Side::Buy, 99, 0));
+    place(&tracker, &quote(tight, Side::Sell, 101, 0));
+    place(&tracker, &quote(wide, Side::Sell, 102, 0));
+    let wide_bid = quote(wide, Side::Buy, 98, 0);
+    place(&tracker, &wide_bid);
+    cancel(&tracker, &wide_bid, 40);
+    place(&tracker, &quote(wide, Side::Buy, 100, 40));
+
+    let activity = tracker.activity(at(60));
+    assert_eq!(activity.len(), 2);
+
+    let tight = of(&activity, tight);
+    assert_eq!(tight.seconds_at_best_bid, 40.0);
+    assert_eq!(tight.seconds_at_best_ask, 60.0);
+    assert_eq!(tight.seconds_two_sided, 60.0);
+    assert_eq!(tight.average_spread_bps, Some(200.0));
+    assert!((tight.presence(60.0) - 100.0 / 120.0).abs() < 1e-9);
+
+    let wide = of(&activity, wide);
+    assert_eq!(wide.seconds_at_best_bid, 20.0);
+    assert_eq!(wide.seconds_at_best_ask, 0.0);
+    assert_eq!(wide.seconds_two_sided, 60.0);
+    // 400 bps for 40 seconds, then about 199 bps for 20 seconds
+    let spread = wide.average_spread_bps.unwrap();
+    assert!(spread > 330.0 && spread < 335.0, "{}", spread);
+}
+
+#[test]
+fn test_maker_volume_and_period_close() {
+    let tracker = QuoteTracker::new(start());
+    let (maker, taker) = (Uuid::new_v4(), Uuid::new_v4());
+    let ask = quote(maker, Side::Sell, 100, 0);
+    place(&tracker, &ask);
+
+    let mut trade = Trade::new(
+        MARKET.to_string(),
+        Price::new(100, 0),
+        Quantity::new(5, 1),
+        Uuid::new_v4(),
+        ask.id,
+        taker,
+        maker,
+        Side::Buy,
+    );
+    trade.created_at = at(30);
+    tracker.process(&EngineEvent::Trade(Arc::new(trade)));
+
+    let (period_start, activity) = tracker.close_period(at(60));
+    assert_eq!(period_start, start());
+    assert_eq!(activity.len(), 1);
+    let maker_activity = of(&activity, maker);
+    assert_eq!(maker_activity.maker_volume, Quantity::new(5, 1));
+    assert_eq!(maker_activity.maker_quote_volume, Price::new(50, 0));
+    assert_eq!(maker_activity.maker_trades, 1);
+    assert_eq!(maker_activity.seconds_at_best_ask, 60.0);
+    assert_eq!(maker_activity.average_spread_bps, None);
+
+    // The resting ask carries over into the next period
+    assert_eq!(tracker.period_start(), at(60));
+    let next = tracker.activity(at(70));
+    assert_eq!(next.len(), 1);
+    assert_eq!(next[0].maker_trades, 0);
+    assert_eq!(next[0].seconds_at_best_ask, 10.0);
+}
+
+#[test]
+fn test_tracks_the_engine_event_stream() {
+    let engine = MatchingEngine::new();
+    engine.register_market(MARKET.to_string());
+    let tracker = QuoteTracker::new(Utc::now());
+    let events = engine.subscribe_events();
+    let (maker, taker) = (Uuid::new_v4(), Uuid::new_v4());
+
+    let mut bid = quote(maker, Side::Buy, 100, 0);
+    bid.quantity = Quantity::new(2, 0);
+    bid.remaining_quantity = bid.quantity;
+    engine.place_order(bid).unwrap();
+    let mut sell = quote(taker, Side::Sell, 100, 0);
+    sell.order_type = OrderType::Market;
+    sell.price = None;
+    engine.place_order(sell).unwrap();
+
+    for event in events.try_iter() {
+        tracker.process(&event);
+    }
+
+    let activity = tracker.activity(Utc::now());
+    let maker_activity = of(&activity, maker);
+    assert_eq!(maker_activity.maker_volume, Quantity::new(1, 0));
+    assert_eq!(maker_activity.maker_quote_volume, Price::new(100, 0));
+    // The taker never rested
+    assert!(activity.iter().all(|activity| activity.account_id != taker));
+}
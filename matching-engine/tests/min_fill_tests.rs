@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use common::decimal::{dec, Quantity};
+use common::error::Error;
+use common::model::order::{Order, RejectReason, Side, Status, TimeInForce};
+use matching_engine::{MatchingEngine, MatchingResult};
+use uuid::Uuid;
+
+const MARKET: &str = "ES/USD";
+
+fn engine() -> MatchingEngine {
+    let engine = MatchingEngine::new();
+    engine.register_market(MARKET.to_string());
+    engine
+}
+
+fn limit(side: Side, price: i64, quantity: Quantity, time_in_force: TimeInForce, min_fill: Option<Quantity>) -> Order {
+    let mut order = Order::new_limit(Uuid::new_v4(), MARKET.to_string(), side, price.into(), quantity, time_in_force);
+    order.min_fill_quantity = min_fill;
+    order
+}
+
+fn place(engine: &MatchingEngine, order: Order) -> (Arc<Order>, MatchingResult) {
+    let mut result = engine.place_order(order).unwrap();
+    (result.taker_order.take().unwrap(), result)
+}
+
+#[test]
+fn test_takers_short_of_their_minimum_are_rejected() {
+    let engine = engine();
+    place(&engine, limit(Side::Sell, 100, dec!(1), TimeInForce::GTC, None));
+    place(&engine, limit(Side::Sell, 101, dec!(1), TimeInForce::GTC, None));
+
+    // One is available at 100, two up to 101
+    let (order, result) = place(&engine, limit(Side::Buy, 100, dec!(3), TimeInForce::IOC, Some(dec!(2))));
+    assert_eq!(order.status, Status::Rejected);
+    assert_eq!(order.reject_reason, Some(RejectReason::MinFillQuantity));
+    assert!(result.trades.is_empty());
+    let (order, _) = place(&engine, limit(Side::Buy, 100, dec!(3), TimeInForce::GTC, Some(dec!(2))));
+    assert_eq!(order.reject_reason, Some(RejectReason::MinFillQuantity));
+    let mut market = Order::new_market(Uuid::new_v4(), MARKET.to_string(), Side::Buy, dec!(3));
+    market.min_fill_quantity = Some(dec!(2.5));
+    let (order, _) = place(&engine, market);
+    assert_eq!(order.reject_reason, Some(RejectReason::MinFillQuantity));
+    assert_eq!(engine.get_market_depth(MARKET, 2).unwrap().1, [(dec!(100), dec!(1)), (dec!(101), dec!(1))]);
+
+    // Once the minimum is there the order trades as usual
+    let (order, result) = place(&engine, limit(Side::Buy, 101, dec!(3), TimeInForce::IOC, Some(dec!(2))));
+    assert_eq!(result.trades.len(), 2);
+    assert_eq!(order.filled_quantity, dec!(2));
+    assert_eq!(order.reject_reason, Some(RejectReason::ImmediateOrCancel));
+}
+
+#[test]
+fn test_resting_orders_wait_for_their_minimum() {
+    let engine = engine();
+
+    // Nothing to cross, so the order rests
+    let (maker, _) = place(&engine, limit(Side::Sell, 100, dec!(5), TimeInForce::GTC, Some(dec!(3))));
+    assert_eq!(maker.status, Status::New);
+
+    // Smaller takers do not trade with it, and may not rest across it
+    let (order, result) = place(&engine, limit(Side::Buy, 100, dec!(2), TimeInForce::IOC, None));
+    assert!(result.trades.is_empty());
+    assert_eq!(order.reject_reason, Some(RejectReason::ImmediateOrCancel));
+    let (order, _) = place(&engine, limit(Side::Buy, 101, dec!(2), TimeInForce::GTC, None));
+    assert_eq!(order.status, Status::Expired);
+    assert_eq!(order.reject_reason, Some(RejectReason::MinFillQuantity));
+    assert!(engine.get_market_depth(MARKET, 1).unwrap().0.is_empty());
+
+    // A taker for the minimum fills it, after which any size trades
+    let (_, result) = place(&engine, limit(Side::Buy, 100, dec!(3), TimeInForce::IOC, None));
+    assert_eq!(result.trades[0].quantity, dec!(3));
+    let (_, result) = place(&engine, limit(Side::Buy, 100, dec!(1), TimeInForce::IOC, None));
+    assert_eq!(result.trades[0].quantity, dec!(1));
+    assert_eq!(engine.get_order(maker.id).unwrap().remaining_quantity, dec!(1));
+}
+
+#[test]
+fn test_matching_passes_over_a_maker_waiting_for_its_minimum() {
+    let engine = engine();
+    let (first, _) = place(&engine, limit(Side::Sell, 100, dec!(1), TimeInForce::GTC, None));
+    let (blocked, _) = place(&engine, limit(Side::Sell, 100, dec!(5), TimeInForce::GTC, Some(dec!(5))));
+    let (behind, _) = place(&engine, limit(Side::Sell, 100, dec!(1), TimeInForce::GTC, None));
+    place(&engine, limit(Side::Sell, 101, dec!(1), TimeInForce::GTC, None));
+
+    // Fill-or-kill and previews pass over the same order as matching
+    let (order, _) = place(&engine, limit(Side::Buy, 100, dec!(3), TimeInForce::FOK, None));
+    assert_eq!(order.reject_reason, Some(RejectReason::FillOrKill));
+    let preview = limit(Side::Buy, 101, dec!(3), TimeInForce::IOC, None);
+    assert_eq!(engine.preview_order(&preview).unwrap(), [(dec!(100), dec!(2)), (dec!(101), dec!(1))]);
+
+    // A smaller taker fills against the orders on either side of it
+    let (order, result) = place(&engine, limit(Side::Buy, 100, dec!(2), TimeInForce::GTC, None));
+    assert_eq!(order.status, Status::Filled);
+    let sellers: Vec<_> = result.trades.iter().map(|trade| trade.seller_order_id).collect();
+    assert_eq!(sellers, [first.id, behind.id]);
+    assert_eq!(engine.get_market_depth(MARKET, 2).unwrap().1, [(dec!(100), dec!(5)), (dec!(101), dec!(1))]);
+
+    // A GTC remainder left crossing it still expires, after filling what it can
+    let (order, result) = place(&engine, limit(Side::Buy, 101, dec!(3), TimeInForce::GTC, None));
+    assert_eq!(result.trades.len(), 1);
+    assert_eq!(order.filled_quantity, dec!(1));
+    assert_eq!(order.reject_reason, Some(RejectReason::MinFillQuantity));
+
+    // The passed over order kept its place ahead of later orders at its price
+    place(&engine, limit(Side::Sell, 100, dec!(1), TimeInForce::GTC, None));
+    let (_, result) = place(&engine, limit(Side::Buy, 100, dec!(5), TimeInForce::IOC, None));
+    assert_eq!(result.trades.len(), 1);
+    assert_eq!(result.trades[0].seller_order_id, blocked.id);
+    assert_eq!(result.trades[0].quantity, dec!(5));
+}
+
+#[test]
+fn test_minimum_fill_quantity_is_validated() {
+    let engine = engine();
+
+    let zero = limit(Side::Buy, 100, dec!(1), TimeInForce::GTC, Some(Quantity::ZERO));
+    assert!(matches!(engine.place_order(zero), Err(Error::InvalidOrder(_))));
+    let too_big = limit(Side::Buy, 100, dec!(1), TimeInForce::GTC, Some(dec!(2)));
+    assert!(matches!(engine.place_order(too_big), Err(Error::InvalidOrder(_))));
+}
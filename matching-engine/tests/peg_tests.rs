@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use chrono::{TimeZone, Utc};
+use common::clock::{Clock, ManualClock};
+use common::decimal::{dec, Price};
+use common::error::Error;
+use common::model::order::{Order, Peg, PegReference, RejectReason, Side, Status, TimeInForce};
+use matching_engine::{EngineEvent, MatchingEngine};
+use uuid::Uuid;
+
+const MARKET: &str = "ES/USD";
+
+/// Engine with a one-dollar tick whose clock stands still until advanced
+fn engine() -> (MatchingEngine, Arc<ManualClock>) {
+    let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()));
+    let engine = MatchingEngine::new()
+        .with_clock(clock.clone())
+        .with_peg_reprice_interval(std::time::Duration::from_millis(100));
+    engine.register_market(MARKET.to_string());
+    engine.set_price_tick(MARKET, dec!(1)).unwrap();
+    (engine, clock)
+}
+
+fn limit(side: Side, price: i64) -> Order {
+    Order::new_limit(Uuid::new_v4(), MARKET.to_string(), side, price.into(), dec!(1), TimeInForce::GTC)
+}
+
+fn pegged(side: Side, reference: PegReference, offset: Price, limit: Option<Price>) -> Order {
+    let mut order = Order::new_limit(Uuid::new_v4(), MARKET.to_string(), side, 1000.into(), dec!(1), TimeInForce::GTC);
+    order.peg = Some(Peg { reference, offset, limit });
+    order
+}
+
+fn place(engine: &MatchingEngine, order: Order) -> Arc<Order> {
+    engine.place_order(order).unwrap().taker_order.unwrap()
+}
+
+fn resting_price(engine: &MatchingEngine, id: Uuid) -> Option<Price> {
+    engine.get_order(id).and_then(|order| order.price)
+}
+
+#[test]
+fn test_mid_pegs_round_away_from_the_other_side() {
+    let (engine, _) = engine();
+    place(&engine, limit(Side::Buy, 99));
+    place(&engine, limit(Side::Sell, 104));
+
+    // The mid of 101.5 rounds down for bids and up for asks
+    let bid = place(&engine, pegged(Side::Buy, PegReference::Mid, Price::ZERO, None));
+    assert_eq!(bid.status, Status::New);
+    assert_eq!(bid.price, Some(dec!(101)));
+    let ask = place(&engine, pegged(Side::Sell, PegReference::Mid, Price::ZERO, None));
+    assert_eq!(ask.price, Some(dec!(102)));
+
+    let (bids, asks) = engine.get_market_depth(MARKET, 1).unwrap();
+    assert_eq!(bids, [(dec!(101), dec!(1))]);
+    assert_eq!(asks, [(dec!(102), dec!(1))]);
+}
+
+#[test]
+fn test_best_pegs_follow_the_book_at_most_once_per_interval() {
+    let (engine, clock) = engine();
+    place(&engine, limit(Side::Buy, 99));
+    place(&engine, limit(Side::Sell, 105));
+    let order = place(&engine, pegged(Side::Buy, PegReference::Best, dec!(1), None));
+    assert_eq!(order.price, Some(dec!(100)));
+    let events = engine.subscribe_events();
+
+    // The book was just re-priced, so a better bid moves the peg only once the interval is up
+    let better = place(&engine, limit(Side::Buy, 101));
+    assert_eq!(resting_price(&engine, order.id), Some(dec!(100)));
+    assert!(engine.reprice_pegged_orders(clock.now()).is_empty());
+    clock.advance(chrono::Duration::milliseconds(100));
+    let repriced = engine.reprice_pegged_orders(clock.now());
+    assert_eq!(repriced.len(), 1);
+    assert_eq!(repriced[0].price, Some(dec!(102)));
+    assert_eq!(repriced[0].updated_at, clock.now());
+    assert!(events.try_iter().any(|event| matches!(event, EngineEvent::OrderUpdated(moved) if moved.id == order.id)));
+
+    // The peg does not chase itself, and a cancel past the interval moves it back
+    clock.advance(chrono::Duration::milliseconds(100));
+    assert!(engine.reprice_pegged_orders(clock.now()).is_empty());
+    clock.advance(chrono::Duration::milliseconds(100));
+    engine.cancel_order(better.id).unwrap();
+    assert_eq!(resting_price(&engine, order.id), Some(dec!(100)));
+}
+
+#[test]
+fn test_pegs_stay_behind_the_other_side_and_within_their_limit() {
+    let (narrow, _) = engine();
+    place(&narrow, limit(Side::Buy, 99));
+    place(&narrow, limit(Side::Sell, 101));
+
+    // Five above the best bid would take the ask, so the order rests a tick below it
+    let result = narrow.place_order(pegged(Side::Buy, PegReference::Best, dec!(5), None)).unwrap();
+    assert!(result.trades.is_empty());
+    assert_eq!(result.taker_order.unwrap().price, Some(dec!(100)));
+
+    // With room on a wider book, the limit caps how far each order goes
+    let (wide, _) = engine();
+    place(&wide, limit(Side::Buy, 99));
+    place(&wide, limit(Side::Sell, 110));
+    let bid = place(&wide, pegged(Side::Buy, PegReference::Best, dec!(5), Some(dec!(102))));
+    assert_eq!(bid.price, Some(dec!(102)));
+    let ask = place(&wide, pegged(Side::Sell, PegReference::Best, dec!(-20), Some(dec!(105))));
+    assert_eq!(ask.price, Some(dec!(105)));
+}
+
+#[test]
+fn test_pegs_without_a_reference_price_are_rejected() {
+    let (engine, _) = engine();
+    place(&engine, limit(Side::Buy, 99));
+
+    // A mid needs both sides, while a best peg only needs its own
+    let order = place(&engine, pegged(Side::Buy, PegReference::Mid, Price::ZERO, None));
+    assert_eq!(order.status, Status::Rejected);
+    assert_eq!(order.reject_reason, Some(RejectReason::PegUnavailable));
+    assert!(engine.get_order(order.id).is_none());
+    let order = place(&engine, pegged(Side::Sell, PegReference::Best, Price::ZERO, None));
+    assert_eq!(order.reject_reason, Some(RejectReason::PegUnavailable));
+    let order = place(&engine, pegged(Side::Buy, PegReference::Best, Price::ZERO, None));
+    assert_eq!(order.price, Some(dec!(99)));
+
+    // Pegged orders are no reference for one another
+    let order = place(&engine, pegged(Side::Sell, PegReference::Mid, Price::ZERO, None));
+    assert_eq!(order.reject_reason, Some(RejectReason::PegUnavailable));
+}
+
+#[test]
+fn test_only_resting_limit_orders_can_be_pegged() {
+    let (engine, _) = engine();
+    place(&engine, limit(Side::Buy, 99));
+    place(&engine, limit(Side::Sell, 101));
+
+    let mut order = pegged(Side::Buy, PegReference::Mid, Price::ZERO, None);
+    order.time_in_force = TimeInForce::IOC;
+    assert!(matches!(engine.place_order(order), Err(Error::InvalidOrder(_))));
+    let mut order = Order::new_market(Uuid::new_v4(), MARKET.to_string(), Side::Buy, dec!(1));
+    order.peg = Some(Peg { reference: PegReference::Mid, offset: Price::ZERO, limit: None });
+    assert!(matches!(engine.place_order(order), Err(Error::InvalidOrder(_))));
+
+    assert!(matches!(engine.set_price_tick(MARKET, dec!(-1)), Err(Error::ValidationError(_))));
+    assert!(matches!(engine.set_price_tick("BTC/USD", dec!(1)), Err(Error::MarketNotFound(_))));
+}
@@ -0,0 +1,116 @@
+use chrono::{DateTime, NaiveTime, Utc};
+use common::decimal::dec;
+use common::error::Error;
+use common::model::market::{SessionState, TradingSchedule};
+use common::model::order::{Order, Side, Status, TimeInForce};
+use matching_engine::{EngineEvent, MatchingEngine};
+use uuid::Uuid;
+
+const MARKET: &str = "BTC/USD";
+
+fn at(timestamp: &str) -> DateTime<Utc> {
+    timestamp.parse().unwrap()
+}
+
+/// 09:00 to 17:00 UTC with a 15 minute opening auction
+fn schedule() -> TradingSchedule {
+    TradingSchedule {
+        open: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        close: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        weekend_closed: false,
+        opening_auction_minutes: 15,
+        closing_auction_minutes: 0,
+        holidays: Vec::new(),
+    }
+}
+
+fn limit(side: Side, price: i64, quantity: i64) -> Order {
+    Order::new_limit(Uuid::new_v4(), MARKET.to_string(), side, price.into(), quantity.into(), TimeInForce::GTC)
+}
+
+#[test]
+fn test_closed_markets_reject_orders() {
+    let engine = MatchingEngine::new();
+    engine.register_market(MARKET.to_string());
+    assert!(matches!(engine.set_trading_schedule("ETH/USD", Some(schedule())), Err(Error::MarketNotFound(_))));
+
+    engine.set_trading_schedule(MARKET, Some(schedule())).unwrap();
+    // Schedules apply at the next update
+    assert_eq!(engine.session_state(MARKET), SessionState::Open);
+    let changes = engine.update_sessions(at("2025-03-03T06:00:00Z"));
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].0.previous, SessionState::Open);
+    assert_eq!(changes[0].0.state, SessionState::Closed);
+    assert!(engine.update_sessions(at("2025-03-03T06:00:01Z")).is_empty());
+
+    assert!(matches!(engine.place_order(limit(Side::Buy, 100, 1)), Err(Error::InvalidOrder(_))));
+
+    let session = engine.market_session(MARKET, at("2025-03-03T06:00:00Z")).unwrap();
+    assert_eq!(session.state, SessionState::Closed);
+    assert_eq!(session.next_state, Some(SessionState::Auction));
+    assert_eq!(session.next_transition, Some(at("2025-03-03T08:45:00Z")));
+
+    // Without a schedule the market opens again
+    engine.set_trading_schedule(MARKET, None).unwrap();
+    engine.update_sessions(at("2025-03-03T06:00:02Z"));
+    assert_eq!(engine.session_state(MARKET), SessionState::Open);
+    assert!(engine.place_order(limit(Side::Buy, 100, 1)).is_ok());
+}
+
+#[test]
+fn test_auction_collects_orders_and_uncrosses_at_one_price() {
+    let engine = MatchingEngine::new();
+    engine.register_market(MARKET.to_string());
+    engine.set_trading_schedule(MARKET, Some(schedule())).unwrap();
+    let events = engine.subscribe_events();
+
+    engine.update_sessions(at("2025-03-03T08:50:00Z"));
+    assert_eq!(engine.session_state(MARKET), SessionState::Auction);
+
+    // Only good-til-cancelled limit orders are collected
+    assert!(engine.place_order(Order::new_market(Uuid::new_v4(), MARKET.to_string(), Side::Buy, dec!(1))).is_err());
+    let mut ioc = limit(Side::Buy, 100, 1);
+    ioc.time_in_force = TimeInForce::IOC;
+    assert!(engine.place_order(ioc).is_err());
+
+    // Crossed orders rest without matching
+    let bid_high = limit(Side::Buy, 105, 2);
+    let bid_low = limit(Side::Buy, 100, 2);
+    let ask_low = limit(Side::Sell, 98, 1);
+    let ask_mid = limit(Side::Sell, 101, 2);
+    let ask_high = limit(Side::Sell, 110, 1);
+    for order in [&bid_high, &bid_low, &ask_low, &ask_mid, &ask_high] {
+        let result = engine.place_order(order.clone()).unwrap();
+        assert!(result.trades.is_empty());
+    }
+
+    // 101 and 105 both execute 2 with 1 left over; ties go to the lower price
+    let changes = engine.update_sessions(at("2025-03-03T09:00:00Z"));
+    assert_eq!(changes.len(), 1);
+    let (change, auction) = &changes[0];
+    assert_eq!(change.state, SessionState::Open);
+    assert_eq!(auction.trades.len(), 2);
+    assert!(auction.trades.iter().all(|trade| trade.price == dec!(101)));
+    assert_eq!(auction.trades.iter().map(|trade| trade.quantity).sum::<rust_decimal::Decimal>(), dec!(2));
+    assert_eq!(auction.trades[0].buyer_order_id, bid_high.id);
+    assert_eq!(auction.trades[0].seller_order_id, ask_low.id);
+
+    let filled = engine.get_order(ask_mid.id).unwrap();
+    assert_eq!(filled.status, Status::PartiallyFilled);
+    assert_eq!(filled.remaining_quantity, dec!(1));
+    assert!(engine.get_order(bid_high.id).is_none());
+
+    // The book is no longer crossed and trades continuously again
+    let (bids, asks) = engine.get_market_depth(MARKET, 10).unwrap();
+    assert_eq!(bids, vec![(dec!(100), dec!(2))]);
+    assert_eq!(asks, vec![(dec!(101), dec!(1)), (dec!(110), dec!(1))]);
+    assert_eq!(engine.place_order(limit(Side::Buy, 101, 1)).unwrap().trades.len(), 1);
+
+    let session_events: Vec<_> = events.try_iter()
+        .filter_map(|event| match event {
+            EngineEvent::SessionChanged(change) => Some(change.state),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(session_events, vec![SessionState::Auction, SessionState::Open]);
+}
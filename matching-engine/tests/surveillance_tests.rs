@@ -0,0 +1,203 @@
+use std::thread;
+use std::time::Duration;
+
+use common::decimal::{Price, Quantity};
+use common::model::order::{Order, OrderType, Side, Status, TimeInForce};
+use common::model::surveillance::{Alert, AlertKind};
+use crossbeam::channel::Receiver;
+use matching_engine::engine::MatchingEngine;
+use matching_engine::surveillance::{Surveillance, SurveillanceConfig};
+use matching_engine::EngineEvent;
+use uuid::Uuid;
+
+const MARKET: &str = "BTC/USD";
+
+fn limit_order(user_id: Uuid, side: Side, price: i64) -> Order {
+    Order {
+        id: Uuid::new_v4(),
+        user_id,
+        market: MARKET.to_string(),
+        side,
+        order_type: OrderType::Limit,
+        price: Some(Price::new(price, 0)),
+        quantity: Quantity::new(1, 0),
+        remaining_quantity: Quantity::new(1, 0),
+        filled_quantity: Quantity::ZERO,
+        status: Status::New,
+        time_in_force: TimeInForce::GTC,
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+        average_fill_price: None,
+        reject_reason: None,
+        reject_message: None,
+        client_order_id: None,
+        sequence: 0,
+        max_slippage_bps: None,
+        peg: None,
+        min_fill_quantity: None,
+        reduce_only: false,
+    }
+}
+
+fn engine() -> MatchingEngine {
+    let engine = MatchingEngine::new();
+    engine.register_market(MARKET.to_string());
+    engine
+}
+
+/// Feed every pending engine event to surveillance, collecting the alerts raised
+fn drain(events: &Receiver<EngineEvent>, surveillance: &Surveillance) -> Vec<Alert> {
+    events.try_iter().flat_map(|event| surveillance.process(&event)).collect()
+}
+
+fn kinds(alerts: &[Alert]) -> Vec<AlertKind> {
+    alerts.iter().map(|alert| alert.kind).collect()
+}
+
+#[test]
+fn test_engine_publishes_order_and_trade_events() {
+    let engine = engine();
+    let events = engine.subscribe_events();
+    let (maker, taker) = (Uuid::new_v4(), Uuid::new_v4());
+
+    let resting = limit_order(maker, Side::Buy, 100);
+    let resting_id = resting.id;
+    engine.place_order(resting).unwrap();
+    engine.place_order(limit_order(maker, Side::Buy, 99)).unwrap();
+    engine.place_order(limit_order(taker, Side::Sell, 100)).unwrap();
+
+    let published: Vec<EngineEvent> = events.try_iter().collect();
+    assert_eq!(published.len(), 5);
+    assert!(matches!(&published[2], EngineEvent::OrderPlaced(order) if order.status == Status::Filled));
+    assert!(matches!(&published[3], EngineEvent::OrderUpdated(order) if order.id == resting_id));
+    match &published[4] {
+        EngineEvent::Trade(trade) => {
+            assert_eq!(trade.buyer_order_id, resting_id);
+            assert_eq!(trade.seller_id, taker);
+        }
+        other => panic!("expected trade, got {:?}", other),
+    }
+
+    let cancelled = engine.cancel_account_orders(maker);
+    assert_eq!(cancelled.len(), 1);
+    assert!(matches!(events.try_recv(), Ok(EngineEvent::OrderCancelled(order)) if order.id == cancelled[0].id));
+}
+
+#[test]
+fn test_self_trade_alert() {
+    let engine = engine();
+    let events = engine.subscribe_events();
+    let surveillance = Surveillance::new(SurveillanceConfig::default());
+    let account = Uuid::new_v4();
+
+    engine.place_order(limit_order(account, Side::Buy, 100)).unwrap();
+    engine.place_order(limit_order(account, Side::Sell, 100)).unwrap();
+
+    let alerts = drain(&events, &surveillance);
+    assert_eq!(kinds(&alerts), [AlertKind::SelfTrade]);
+    assert_eq!(alerts[0].account_ids, [account]);
+    assert_eq!(surveillance.alerts(Some(account), None, 10).len(), 1);
+    assert!(surveillance.alerts(Some(Uuid::new_v4()), None, 10).is_empty());
+}
+
+#[test]
+fn test_wash_trading_alert_is_raised_once_per_window() {
+    let engine = engine();
+    let events = engine.subscribe_events();
+    let surveillance = Surveillance::new(SurveillanceConfig::default());
+    let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+    // A and B pass the same unit back and forth
+    for _ in 0..3 {
+        engine.place_order(limit_order(a, Side::Buy, 100)).unwrap();
+        engine.place_order(limit_order(b, Side::Sell, 100)).unwrap();
+        engine.place_order(limit_order(b, Side::Buy, 100)).unwrap();
+        engine.place_order(limit_order(a, Side::Sell, 100)).unwrap();
+    }
+
+    let alerts = drain(&events, &surveillance);
+    assert_eq!(kinds(&alerts), [AlertKind::WashTrading]);
+    assert!(alerts[0].involves(a) && alerts[0].involves(b));
+}
+
+#[test]
+fn test_one_way_flow_is_not_wash_trading() {
+    let engine = engine();
+    let events = engine.subscribe_events();
+    let surveillance = Surveillance::new(SurveillanceConfig::default());
+    let (buyer, seller) = (Uuid::new_v4(), Uuid::new_v4());
+
+    for _ in 0..10 {
+        engine.place_order(limit_order(buyer, Side::Buy, 100)).unwrap();
+        engine.place_order(limit_order(seller, Side::Sell, 100)).unwrap();
+    }
+
+    assert!(drain(&events, &surveillance).is_empty());
+}
+
+#[test]
+fn test_high_cancel_to_fill_alert() {
+    let engine = engine();
+    let events = engine.subscribe_events();
+    let surveillance = Surveillance::new(SurveillanceConfig {
+        min_cancels: 5,
+        max_cancel_to_fill: 5,
+        ..SurveillanceConfig::default()
+    });
+    let account = Uuid::new_v4();
+
+    for i in 0..5 {
+        let order = limit_order(account, Side::Buy, 90 - i);
+        let order_id = order.id;
+        engine.place_order(order).unwrap();
+        engine.cancel_order(order_id).unwrap();
+    }
+
+    assert_eq!(kinds(&drain(&events, &surveillance)), [AlertKind::HighCancelToFill]);
+}
+
+#[test]
+fn test_layering_alert() {
+    let engine = engine();
+    let events = engine.subscribe_events();
+    let surveillance = Surveillance::new(SurveillanceConfig::default());
+    let (spoofer, victim) = (Uuid::new_v4(), Uuid::new_v4());
+
+    // Stack the ask side, buy from someone else, then pull the stack
+    let layers: Vec<Uuid> = (101..104)
+        .map(|price| {
+            let order = limit_order(spoofer, Side::Sell, price);
+            let order_id = order.id;
+            engine.place_order(order).unwrap();
+            order_id
+        })
+        .collect();
+    engine.place_order(limit_order(spoofer, Side::Buy, 100)).unwrap();
+    engine.place_order(limit_order(victim, Side::Sell, 100)).unwrap();
+
+    for order_id in layers {
+        engine.cancel_order(order_id).unwrap();
+    }
+
+    let alerts = drain(&events, &surveillance);
+    assert_eq!(kinds(&alerts), [AlertKind::Layering]);
+    assert_eq!(alerts[0].account_ids, [spoofer]);
+}
+
+#[test]
+fn test_started_surveillance_watches_engine() {
+    let engine = engine();
+    let surveillance = Surveillance::start(&engine, SurveillanceConfig::default());
+    let account = Uuid::new_v4();
+
+    engine.place_order(limit_order(account, Side::Buy, 100)).unwrap();
+    engine.place_order(limit_order(account, Side::Sell, 100)).unwrap();
+
+    for _ in 0..100 {
+        if !surveillance.alerts(None, None, 10).is_empty() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(kinds(&surveillance.alerts(None, Some(AlertKind::SelfTrade), 10)), [AlertKind::SelfTrade]);
+}
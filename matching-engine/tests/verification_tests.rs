@@ -0,0 +1,83 @@
+use chrono::{DateTime, NaiveTime, Utc};
+use common::model::market::{SessionState, TradingSchedule};
+use common::model::order::{Order, Side, TimeInForce};
+use matching_engine::{BookVerification, MatchingEngine};
+use uuid::Uuid;
+
+const MARKET: &str = "BTC/USD";
+
+fn at(timestamp: &str) -> DateTime<Utc> {
+    timestamp.parse().unwrap()
+}
+
+fn limit(side: Side, price: i64, quantity: i64) -> Order {
+    Order::new_limit(Uuid::new_v4(), MARKET.to_string(), side, price.into(), quantity.into(), TimeInForce::GTC)
+}
+
+#[test]
+fn test_books_are_checked_after_each_command() {
+    let engine = MatchingEngine::new().with_book_verification(BookVerification::Count);
+    engine.register_market(MARKET.to_string());
+    assert_eq!(engine.book_verification(), BookVerification::Count);
+    assert!(engine.book_checks().is_empty());
+
+    engine.place_order(limit(Side::Buy, 99, 1)).unwrap();
+    let ask = engine.place_order(limit(Side::Sell, 101, 2)).unwrap().taker_order.unwrap();
+    // A crossing order matches instead of resting across the book
+    engine.place_order(limit(Side::Buy, 101, 1)).unwrap();
+    engine.cancel_order(ask.id).unwrap();
+
+    let checks = engine.book_checks();
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0].market, MARKET);
+    assert_eq!(checks[0].checks, 4);
+    assert_eq!(checks[0].crossings, 0);
+    assert_eq!(checks[0].refused_orders, 0);
+    assert!(checks[0].last_crossed_at.is_none());
+}
+
+#[test]
+fn test_auction_books_may_cross_until_they_uncross() {
+    let engine = MatchingEngine::new();
+    engine.register_market(MARKET.to_string());
+    let schedule = TradingSchedule {
+        open: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        close: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        weekend_closed: false,
+        opening_auction_minutes: 15,
+        closing_auction_minutes: 0,
+        holidays: Vec::new(),
+    };
+    engine.set_trading_schedule(MARKET, Some(schedule)).unwrap();
+    engine.update_sessions(at("2025-03-03T08:50:00Z"));
+    assert_eq!(engine.session_state(MARKET), SessionState::Auction);
+
+    // Crossed orders wait for the uncross without tripping the checks
+    engine.place_order(limit(Side::Buy, 102, 1)).unwrap();
+    engine.place_order(limit(Side::Sell, 100, 1)).unwrap();
+    let stats = engine.book_stats(MARKET).unwrap();
+    assert!(stats.best_bid >= stats.best_ask);
+    assert!(engine.book_checks().is_empty());
+
+    engine.update_sessions(at("2025-03-03T09:00:00Z"));
+    let checks = engine.book_checks();
+    assert_eq!(checks[0].checks, 1);
+    assert_eq!(checks[0].crossings, 0);
+    let stats = engine.book_stats(MARKET).unwrap();
+    assert_eq!((stats.best_bid, stats.best_ask), (None, None));
+}
+
+#[test]
+fn test_book_verification_is_parsed() {
+    assert_eq!("off".parse::<BookVerification>().unwrap(), BookVerification::Off);
+    assert_eq!(" Count ".parse::<BookVerification>().unwrap(), BookVerification::Count);
+    assert_eq!("ASSERT".parse::<BookVerification>().unwrap(), BookVerification::Assert);
+    assert!("panic".parse::<BookVerification>().is_err());
+    // Tests run as a debug build
+    assert_eq!(BookVerification::default(), BookVerification::Assert);
+
+    let engine = MatchingEngine::new().with_book_verification(BookVerification::Off);
+    engine.register_market(MARKET.to_string());
+    engine.place_order(limit(Side::Buy, 99, 1)).unwrap();
+    assert!(engine.book_checks().is_empty());
+}
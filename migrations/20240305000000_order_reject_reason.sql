@@ -0,0 +1,3 @@
+-- Track why the engine rejected or expired an order
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS reject_reason TEXT;
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS reject_message TEXT;
@@ -0,0 +1,10 @@
+-- Periodic order book snapshots for post-trade analysis
+CREATE TABLE IF NOT EXISTS order_book_snapshots (
+    id BIGSERIAL PRIMARY KEY,
+    market_id TEXT NOT NULL,
+    sequence BIGINT NOT NULL,
+    taken_at TIMESTAMPTZ NOT NULL,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS order_book_snapshots_market_taken_at_idx ON order_book_snapshots(market_id, taken_at);
@@ -0,0 +1,2 @@
+-- Closed accounts keep their rows; closed_at marks them as closed
+ALTER TABLE accounts ADD COLUMN IF NOT EXISTS closed_at TIMESTAMPTZ;
@@ -0,0 +1,9 @@
+-- Public trade history for bulk data archives
+CREATE TABLE IF NOT EXISTS market_trades (
+    id UUID PRIMARY KEY,
+    market_id TEXT NOT NULL,
+    executed_at TIMESTAMPTZ NOT NULL,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS market_trades_market_executed_at_idx ON market_trades(market_id, executed_at);
@@ -0,0 +1,8 @@
+-- Settled trades by order, for fill reports
+CREATE TABLE IF NOT EXISTS order_fills (
+    order_id UUID NOT NULL,
+    trade_id UUID NOT NULL,
+    executed_at TIMESTAMPTZ NOT NULL,
+    data JSONB NOT NULL,
+    PRIMARY KEY (order_id, trade_id)
+);
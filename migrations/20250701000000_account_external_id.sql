@@ -0,0 +1,4 @@
+-- Reference of an account in an external system, unique when set
+ALTER TABLE accounts ADD COLUMN IF NOT EXISTS external_id TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS accounts_external_id_idx ON accounts(external_id) WHERE external_id IS NOT NULL;
@@ -0,0 +1,10 @@
+-- End-of-day and on-demand copies of balances, for balance history charts
+CREATE TABLE IF NOT EXISTS balance_snapshots (
+    account_id UUID NOT NULL REFERENCES accounts(id),
+    asset TEXT NOT NULL,
+    total TEXT NOT NULL,
+    available TEXT NOT NULL,
+    locked TEXT NOT NULL,
+    taken_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (account_id, asset, taken_at)
+);
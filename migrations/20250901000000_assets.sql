@@ -0,0 +1,9 @@
+-- Registered assets with their precision and deposit and withdrawal rules
+CREATE TABLE IF NOT EXISTS assets (
+    code TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    decimals INTEGER NOT NULL,
+    withdrawal_min TEXT NOT NULL DEFAULT '0',
+    withdrawal_fee TEXT NOT NULL DEFAULT '0',
+    deposit_enabled BOOLEAN NOT NULL DEFAULT TRUE
+);
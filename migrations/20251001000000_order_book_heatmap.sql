@@ -0,0 +1,7 @@
+-- Coarse order book depth sampled for liquidity heatmaps
+CREATE TABLE IF NOT EXISTS order_book_heatmap (
+    market_id TEXT NOT NULL,
+    taken_at TIMESTAMPTZ NOT NULL,
+    data JSONB NOT NULL,
+    PRIMARY KEY (market_id, taken_at)
+);
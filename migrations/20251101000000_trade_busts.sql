@@ -0,0 +1,6 @@
+-- Busted trades, kept beside the trades they reverse
+CREATE TABLE IF NOT EXISTS trade_busts (
+    trade_id UUID PRIMARY KEY,
+    busted_at TIMESTAMPTZ NOT NULL,
+    data JSONB NOT NULL
+);
@@ -0,0 +1,9 @@
+-- Fee rebates and manual adjustments posted by operators
+CREATE TABLE IF NOT EXISTS balance_adjustments (
+    id UUID PRIMARY KEY,
+    account_id UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_balance_adjustments_account_created ON balance_adjustments (account_id, created_at);
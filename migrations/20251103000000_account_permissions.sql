@@ -0,0 +1,6 @@
+-- Trading and withdrawal permissions operators set on accounts
+CREATE TABLE IF NOT EXISTS account_permissions (
+    account_id UUID PRIMARY KEY,
+    updated_at TIMESTAMPTZ NOT NULL,
+    data JSONB NOT NULL
+);
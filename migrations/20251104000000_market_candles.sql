@@ -0,0 +1,8 @@
+-- Candles spilled from memory by the market data service
+CREATE TABLE IF NOT EXISTS market_candles (
+    market_id TEXT NOT NULL,
+    candle_interval TEXT NOT NULL,
+    open_time TIMESTAMPTZ NOT NULL,
+    data JSONB NOT NULL,
+    PRIMARY KEY (market_id, candle_interval, open_time)
+);
@@ -0,0 +1,9 @@
+-- Status changes of every order the engine processed, for the order audit trail
+CREATE TABLE IF NOT EXISTS order_history (
+    id BIGSERIAL PRIMARY KEY,
+    order_id UUID NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_order_history_order ON order_history (order_id, id);
@@ -0,0 +1,6 @@
+-- Engine sequence numbers of public trades, for WebSocket replay from a sequence
+ALTER TABLE market_trades ADD COLUMN IF NOT EXISTS sequence BIGINT NOT NULL DEFAULT 0;
+
+UPDATE market_trades SET sequence = (data->>'sequence')::BIGINT WHERE sequence = 0 AND data ? 'sequence';
+
+CREATE INDEX IF NOT EXISTS market_trades_market_sequence_idx ON market_trades(market_id, sequence);
@@ -0,0 +1,11 @@
+-- The engine numbers trades on from the last stored one after a restart, so a
+-- market's sequence numbers are unique. Numbers stored twice before that are
+-- cleared, since replay cannot tell the trades apart
+UPDATE market_trades trade SET sequence = 0
+WHERE sequence > 0 AND EXISTS (
+    SELECT 1 FROM market_trades other
+    WHERE other.market_id = trade.market_id AND other.sequence = trade.sequence AND other.id <> trade.id
+);
+
+DROP INDEX IF EXISTS market_trades_market_sequence_idx;
+CREATE UNIQUE INDEX IF NOT EXISTS market_trades_market_sequence_idx ON market_trades(market_id, sequence) WHERE sequence > 0;
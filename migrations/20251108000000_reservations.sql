@@ -0,0 +1,9 @@
+-- Funds locked for open orders, written with the balance they lock so they survive a restart
+CREATE TABLE IF NOT EXISTS reservations (
+    order_id UUID PRIMARY KEY,
+    account_id UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_reservations_account ON reservations (account_id);
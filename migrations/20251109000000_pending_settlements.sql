@@ -0,0 +1,9 @@
+-- Trades of placements answered before they were settled, kept until settlement succeeds
+CREATE TABLE IF NOT EXISTS pending_settlements (
+    id UUID PRIMARY KEY,
+    order_id UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    data JSONB NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_pending_settlements_created ON pending_settlements (created_at);
@@ -2,53 +2,32 @@
 
 #[cfg(test)]
 mod db_persistence_tests {
-    use sqlx::Row;
-    use std::env;
+    use sqlx::{PgPool, Row};
     use tokio::runtime::Runtime;
-    use sqlx::{postgres::PgPoolOptions, PgPool};
+    use common::db::fixture::PostgresFixture;
     
-    // Helper function to run async tests
+    // Helper function to run async tests against a disposable database
     fn run_db_test<F>(test: F)
     where
         F: FnOnce(PgPool) -> futures::future::BoxFuture<'static, ()> + Send + 'static,
     {
-        // Skip test if TEST_DATABASE_URL is not set
-        let db_url = match env::var("TEST_DATABASE_URL") {
-            Ok(url) => url,
-            Err(_) => {
-                println!("Skipping database test: TEST_DATABASE_URL not set");
-                println!("Run ./create_test_db.sh to set up the test database");
-                return;
-            }
-        };
-            
         // Create runtime
         let rt = Runtime::new().unwrap();
         
         // Run the test
         rt.block_on(async {
-            // Create database connection
-            let pool = match PgPoolOptions::new()
-                .max_connections(5)
-                .connect(&db_url)
-                .await
-            {
-                Ok(pool) => pool,
-                Err(err) => {
-                    println!("Skipping database test: could not connect to database: {}", err);
-                    println!("Run ./create_test_db.sh to set up the test database");
-                    return;
-                }
+            // Skip the test only when SKIP_POSTGRES_TESTS=1 opts out
+            let Some(fixture) = PostgresFixture::start().await else {
+                return;
             };
                 
             // Run the test
-            test(pool).await;
+            test(fixture.pool.clone()).await;
         });
     }
 
     // Simple test to write and read data from a temp table
     #[test]
-    #[ignore = "Requires test database, run with RUST_TEST_THREADS=1 cargo test -- --ignored"]
     fn test_basic_db_operations() {
         run_db_test(|pool| {
             Box::pin(async move {
@@ -101,7 +80,6 @@ mod db_persistence_tests {
 
     // Test with multiple rows
     #[test]
-    #[ignore = "Requires test database, run with RUST_TEST_THREADS=1 cargo test -- --ignored"]
     fn test_multiple_rows() {
         run_db_test(|pool| {
             Box::pin(async move {
@@ -162,7 +140,6 @@ mod db_persistence_tests {
     
     // Test transactions
     #[test]
-    #[ignore = "Requires test database, run with RUST_TEST_THREADS=1 cargo test -- --ignored"]
     fn test_transactions() {
         run_db_test(|pool| {
             Box::pin(async move {
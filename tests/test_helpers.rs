@@ -1,7 +1,7 @@
 // File: tests/test_helpers.rs
 
 use std::process::{Command, Child};
-use std::sync::{Arc, Mutex, Once};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::thread;
 use std::env;
@@ -9,7 +9,6 @@ use std::env;
 // Singleton to manage the engine process
 lazy_static::lazy_static! {
     static ref ENGINE_PROCESS: Arc<Mutex<Option<Child>>> = Arc::new(Mutex::new(None));
-    static ref DB_INITIALIZATION: Once = Once::new();
 }
 
 // Start the trading engine for tests
@@ -75,44 +74,27 @@ impl Drop for EngineGuard {
 
 // Database test utilities
 #[cfg(feature = "db_tests")]
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use common::db::fixture::PostgresFixture;
+#[cfg(feature = "db_tests")]
+use sqlx::PgPool;
 
 #[cfg(feature = "db_tests")]
 pub struct DbTestContext {
     pub pool: PgPool,
+    _fixture: PostgresFixture,
 }
 
 #[cfg(feature = "db_tests")]
 impl DbTestContext {
-    // Create a new test database context
-    pub async fn new() -> Self {
-        // Use a test-specific database configuration
-        let db_url = env::var("TEST_DATABASE_URL")
-            .expect("TEST_DATABASE_URL must be set for database tests. Run ./create_test_db.sh to set it up.");
-        
-        // Connect to the test database
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .connect(&db_url)
-            .await
-            .expect("Failed to connect to test database");
-        
-        // Run migrations to set up schema
-        DB_INITIALIZATION.call_once(|| {
-            // This block runs only once per test run
-            println!("Initializing test database schema...");
-            
-            // Create a blocking runtime for migrations
-            let rt = tokio::runtime::Runtime::new().unwrap();
-            rt.block_on(async {
-                // Run migrations to ensure schema is up to date
-                common::db::run_migrations(&pool)
-                    .await
-                    .expect("Failed to run database migrations");
-            });
-        });
+    // Create a new test database context, or None if no database is available
+    pub async fn new() -> Option<Self> {
+        // The fixture provides a disposable, migrated database
+        let fixture = PostgresFixture::start().await?;
         
-        Self { pool }
+        Some(Self {
+            pool: fixture.pool.clone(),
+            _fixture: fixture,
+        })
     }
     
     // Clean up test data after tests
@@ -0,0 +1,144 @@
+//! Historical data replayed by backtests
+//!
+//! Reads the CSV files of the gateway's bulk archives, gzipped or not. Trade
+//! files are replayed as recorded; each candle of a candle file becomes four
+//! trades through its open, low or high, high or low, and close.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use common::decimal::{Price, Quantity};
+use common::error::{Error, Result};
+use common::model::order::Side;
+use flate2::read::GzDecoder;
+
+/// Trade replayed by a backtest
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoricalTrade {
+    /// Execution time
+    pub timestamp: DateTime<Utc>,
+    /// Execution price
+    pub price: Price,
+    /// Quantity traded
+    pub quantity: Quantity,
+    /// Side that was the taker
+    pub taker_side: Side,
+}
+
+/// Read trades from an archive file, oldest first
+pub fn load(path: &Path) -> Result<Vec<HistoricalTrade>> {
+    let mut file = File::open(path).map_err(|e| Error::Internal(format!("Failed to open {}: {}", path.display(), e)))?;
+    let mut csv = String::new();
+    let read = if path.extension().is_some_and(|extension| extension == "gz") {
+        GzDecoder::new(file).read_to_string(&mut csv)
+    } else {
+        file.read_to_string(&mut csv)
+    };
+    read.map_err(|e| Error::Internal(format!("Failed to read {}: {}", path.display(), e)))?;
+    parse(&csv)
+}
+
+/// Parse a trades or candles CSV, oldest trade first
+pub fn parse(csv: &str) -> Result<Vec<HistoricalTrade>> {
+    let mut lines = csv.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next().ok_or_else(|| Error::ValidationError("Historical data is empty".to_string()))?;
+
+    let mut trades = Vec::new();
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+    match columns.first().copied() {
+        Some("id") => {
+            for (row, line) in lines.enumerate() {
+                trades.push(trade_row(&columns, line).map_err(|e| row_error(row, e))?);
+            }
+        }
+        Some("open_time") => {
+            for (row, line) in lines.enumerate() {
+                trades.extend(candle_row(&columns, line).map_err(|e| row_error(row, e))?);
+            }
+        }
+        _ => return Err(Error::ValidationError(format!("Unknown historical data header: {}", header))),
+    }
+
+    trades.sort_by_key(|trade| trade.timestamp);
+    Ok(trades)
+}
+
+fn row_error(row: usize, error: Error) -> Error {
+    Error::ValidationError(format!("Row {}: {}", row + 1, error))
+}
+
+/// Value of a named column in a row
+fn field<'a>(columns: &[&str], values: &[&'a str], name: &str) -> Result<&'a str> {
+    columns
+        .iter()
+        .position(|column| *column == name)
+        .and_then(|i| values.get(i))
+        .map(|value| value.trim())
+        .ok_or_else(|| Error::ValidationError(format!("Missing {}", name)))
+}
+
+fn decimal(columns: &[&str], values: &[&str], name: &str) -> Result<Price> {
+    let value = field(columns, values, name)?;
+    value.parse().map_err(|_| Error::ValidationError(format!("Invalid {}: {}", name, value)))
+}
+
+fn time(columns: &[&str], values: &[&str], name: &str) -> Result<DateTime<Utc>> {
+    let value = field(columns, values, name)?;
+    DateTime::parse_from_rfc3339(value)
+        .map(|at| at.with_timezone(&Utc))
+        .map_err(|_| Error::ValidationError(format!("Invalid {}: {}", name, value)))
+}
+
+fn trade_row(columns: &[&str], line: &str) -> Result<HistoricalTrade> {
+    let values: Vec<&str> = line.split(',').collect();
+    let taker_side = match field(columns, &values, "taker_side")?.to_ascii_lowercase().as_str() {
+        "buy" => Side::Buy,
+        "sell" => Side::Sell,
+        other => return Err(Error::ValidationError(format!("Invalid taker_side: {}", other))),
+    };
+    Ok(HistoricalTrade {
+        timestamp: time(columns, &values, "timestamp")?,
+        price: decimal(columns, &values, "price")?,
+        quantity: decimal(columns, &values, "quantity")?,
+        taker_side,
+    })
+}
+
+/// Trades through a candle's prices, its volume split evenly between them
+fn candle_row(columns: &[&str], line: &str) -> Result<Vec<HistoricalTrade>> {
+    let values: Vec<&str> = line.split(',').collect();
+    let volume = decimal(columns, &values, "volume")?;
+    if volume.is_zero() {
+        return Ok(Vec::new());
+    }
+
+    let open_time = time(columns, &values, "open_time")?;
+    let close_time = time(columns, &values, "close_time")?;
+    let open = decimal(columns, &values, "open")?;
+    let high = decimal(columns, &values, "high")?;
+    let low = decimal(columns, &values, "low")?;
+    let close = decimal(columns, &values, "close")?;
+
+    // A rising candle most likely dipped first, a falling one peaked first
+    let path = if close >= open { [open, low, high, close] } else { [open, high, low, close] };
+    let step = (close_time - open_time) / 4;
+    let quantity = volume / Quantity::from(4);
+
+    let mut previous = open;
+    Ok(path
+        .iter()
+        .enumerate()
+        .map(|(i, &price)| {
+            let taker_side = if price >= previous { Side::Buy } else { Side::Sell };
+            previous = price;
+            HistoricalTrade {
+                timestamp: open_time + step * i as i32,
+                price,
+                quantity,
+                taker_side,
+            }
+        })
+        .collect())
+}
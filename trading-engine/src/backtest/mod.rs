@@ -0,0 +1,304 @@
+//! Backtesting strategies against historical data
+//!
+//! A backtest replays recorded trades on a manual clock through the real
+//! matching engine, fee schedule and account service, while the registered
+//! strategies trade as they would live. History has no book, so one is
+//! reconstructed from the tape: a replay account quotes a bid and an ask
+//! around the last historical price, and every historical trade is sent by it
+//! as an immediate-or-cancel order, which fills the strategies' resting orders
+//! priced at or through it. What the strategies do not fill is recorded as a
+//! trade of the market, so prices and volumes follow the history.
+//!
+//! Callbacks run in simulated time order on the caller's task: ticks due up to
+//! a historical trade first, then the trade and the fills it caused. The
+//! report gives each strategy's fills, fees and PnL.
+
+mod data;
+mod report;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use common::clock::{Clock, ManualClock};
+use common::decimal::{Amount, Price, Quantity};
+use common::error::{Error, Result};
+use common::model::market::Market;
+use common::model::order::{Order, Side, TimeInForce};
+use common::model::trade::Trade;
+use crossbeam_channel::Receiver;
+use matching_engine::EngineEvent;
+use rust_decimal_macros::dec;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::strategy::{Exchange, Fill, Strategy, StrategyHandle, StrategyHost};
+
+pub use data::{load, parse, HistoricalTrade};
+pub use report::{BacktestReport, FillRecord, StrategyReport};
+
+/// Funds of the replay account, enough for any historical trade
+const REPLAY_FUNDS: Quantity = dec!(1000000000000);
+
+/// Backtest settings
+#[derive(Debug, Clone)]
+pub struct BacktestConfig {
+    /// Market the history belongs to
+    pub market: Market,
+    /// Distance of the reconstructed bid and ask from the last price, as a fraction of it
+    pub spread: Price,
+    /// Quantity quoted on each side of the reconstructed book, the last
+    /// historical trade's quantity when unset
+    pub depth_quantity: Option<Quantity>,
+}
+
+impl BacktestConfig {
+    /// Replay history of `market` with the default book reconstruction
+    pub fn new(market: Market) -> Self {
+        Self {
+            market,
+            spread: dec!(0.0005),
+            depth_quantity: None,
+        }
+    }
+}
+
+/// Strategy under test with its account and what it did
+struct Runner {
+    name: String,
+    strategy: Box<dyn Strategy>,
+    handle: StrategyHandle,
+    next_tick: DateTime<Utc>,
+    starting_balances: BTreeMap<String, Quantity>,
+    fills: Vec<FillRecord>,
+}
+
+/// Replays history through the engine for a host's strategies
+pub struct Backtest {
+    exchange: Exchange,
+    clock: Arc<ManualClock>,
+    config: BacktestConfig,
+    runners: Vec<Runner>,
+    events: Receiver<EngineEvent>,
+    replay_account: Uuid,
+    /// Orders of the reconstructed book
+    quotes: Vec<Uuid>,
+}
+
+impl Backtest {
+    /// Replay `trades` for the strategies registered with `host`, returning the report
+    ///
+    /// The host's exchange must have the market registered with its engine
+    /// and nothing else trading on it. `clock` must be the clock of its
+    /// engine and market data, it is moved along the history.
+    pub async fn run(
+        host: StrategyHost,
+        clock: Arc<ManualClock>,
+        config: BacktestConfig,
+        trades: &[HistoricalTrade],
+    ) -> Result<BacktestReport> {
+        let (first, last) = match (trades.first(), trades.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(Error::ValidationError("No historical trades to replay".to_string())),
+        };
+
+        let (exchange, registrations) = host.into_parts();
+        clock.set(first.timestamp);
+        let events = exchange.matching_engine().subscribe_events();
+        let market = &config.market;
+        let replay_account = exchange
+            .create_account(&[(market.base_asset.clone(), REPLAY_FUNDS), (market.quote_asset.clone(), REPLAY_FUNDS)])
+            .await?;
+
+        let mut runners = Vec::with_capacity(registrations.len());
+        for registration in registrations {
+            let mut strategy = registration.strategy;
+            let account_id = exchange.create_account(&registration.funds).await?;
+            runners.push(Runner {
+                name: strategy.name().to_string(),
+                next_tick: next_tick(strategy.as_mut(), first.timestamp),
+                strategy,
+                handle: StrategyHandle::new(exchange.clone(), account_id),
+                starting_balances: registration.funds.into_iter().collect(),
+                fills: Vec::new(),
+            });
+        }
+
+        info!(
+            "Backtesting {} strategies on {} trades of {} from {} to {}",
+            runners.len(), trades.len(), market.symbol, first.timestamp, last.timestamp
+        );
+        let mut backtest = Self {
+            exchange,
+            clock,
+            config,
+            runners,
+            events,
+            replay_account,
+            quotes: Vec::new(),
+        };
+        for trade in trades {
+            backtest.tick_until(trade.timestamp).await;
+            backtest.clock.set(trade.timestamp);
+            backtest.replay(trade).await?;
+        }
+
+        backtest.report(first.timestamp, last, trades.len()).await
+    }
+
+    /// Run every tick due up to `until`, in time order
+    async fn tick_until(&mut self, until: DateTime<Utc>) {
+        loop {
+            let Some(runner) = self.runners.iter_mut().filter(|runner| runner.next_tick <= until).min_by_key(|runner| runner.next_tick) else {
+                return;
+            };
+            self.clock.set(runner.next_tick);
+            if let Err(e) = runner.strategy.on_tick(&runner.handle).await {
+                warn!("Strategy {} failed on tick: {}", runner.name, e);
+            }
+            runner.next_tick = next_tick(runner.strategy.as_mut(), runner.next_tick);
+            self.dispatch(None).await;
+        }
+    }
+
+    /// Send a historical trade through the engine and rebuild the book around it
+    async fn replay(&mut self, historical: &HistoricalTrade) -> Result<()> {
+        let market = &self.config.market;
+        for order_id in std::mem::take(&mut self.quotes) {
+            self.exchange.cancel(order_id).await?;
+        }
+
+        let order = Order::new_limit(
+            self.replay_account,
+            market.symbol.clone(),
+            historical.taker_side,
+            historical.price,
+            historical.quantity,
+            TimeInForce::IOC,
+        );
+        let taker = self.exchange.place(order).await?;
+
+        // The rest of the historical trade was with participants not simulated
+        let remainder = historical.quantity - taker.filled_quantity;
+        let tape = (remainder > Quantity::ZERO).then(|| {
+            let mut trade = Trade::new(
+                market.symbol.clone(),
+                historical.price,
+                remainder,
+                Uuid::nil(),
+                Uuid::nil(),
+                Uuid::nil(),
+                Uuid::nil(),
+                historical.taker_side,
+            );
+            trade.id = self.exchange.matching_engine().id_generator().next_id();
+            trade.created_at = historical.timestamp;
+            trade
+        });
+        if let Some(trade) = &tape {
+            self.exchange.record_trade(trade).await?;
+        }
+
+        let quantity = round_down(self.config.depth_quantity.unwrap_or(historical.quantity), market.quantity_step)
+            .max(market.quantity_step);
+        let bid = round_down(historical.price * (Price::ONE - self.config.spread), market.price_tick);
+        let ask = round_up(historical.price * (Price::ONE + self.config.spread), market.price_tick);
+        for (side, price) in [(Side::Buy, bid), (Side::Sell, ask)] {
+            let order = Order::new_limit(self.replay_account, market.symbol.clone(), side, price, quantity, TimeInForce::GTC);
+            let quote = self.exchange.place(order).await?;
+            if quote.is_active() {
+                self.quotes.push(quote.id);
+            }
+        }
+
+        self.dispatch(tape).await;
+        Ok(())
+    }
+
+    /// Deliver the engine's trades since the last dispatch, then the tape trade
+    async fn dispatch(&mut self, tape: Option<Trade>) {
+        let now = self.clock.now();
+        let mut trades: Vec<Arc<Trade>> = self
+            .events
+            .try_iter()
+            .filter_map(|event| match event {
+                EngineEvent::Trade(trade) => Some(trade),
+                _ => None,
+            })
+            .collect();
+        trades.extend(tape.map(Arc::new));
+
+        for trade in &trades {
+            for runner in &mut self.runners {
+                if let Err(e) = runner.strategy.on_trade(&runner.handle, trade).await {
+                    warn!("Strategy {} failed on trade {}: {}", runner.name, trade.id, e);
+                }
+                if let Some(fill) = Fill::of(trade, runner.handle.account_id()) {
+                    runner.fills.push(FillRecord::new(&fill, now));
+                    if let Err(e) = runner.strategy.on_fill(&runner.handle, &fill).await {
+                        warn!("Strategy {} failed on fill of order {}: {}", runner.name, fill.order_id, e);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn report(self, start: DateTime<Utc>, last: &HistoricalTrade, trades_replayed: usize) -> Result<BacktestReport> {
+        let market = &self.config.market;
+        let mark_price = last.price;
+        let value = |balances: &BTreeMap<String, Quantity>| -> Amount {
+            let quote = balances.get(&market.quote_asset).copied().unwrap_or_default();
+            let base = balances.get(&market.base_asset).copied().unwrap_or_default();
+            quote + base * mark_price
+        };
+
+        let mut strategies = Vec::with_capacity(self.runners.len());
+        for runner in self.runners {
+            let account_id = runner.handle.account_id();
+            let ending_balances = self.exchange.balances(account_id).await?.into_iter().collect();
+            strategies.push(StrategyReport::new(
+                runner.name,
+                account_id,
+                runner.fills,
+                runner.starting_balances,
+                ending_balances,
+                value,
+            ));
+        }
+
+        Ok(BacktestReport {
+            market: market.symbol.clone(),
+            start,
+            end: last.timestamp,
+            trades_replayed,
+            mark_price,
+            strategies,
+        })
+    }
+}
+
+/// Time of a strategy's next tick after `at`, at least a millisecond later so time moves on
+fn next_tick(strategy: &mut dyn Strategy, at: DateTime<Utc>) -> DateTime<Utc> {
+    let interval = strategy.tick_interval().max(Duration::from_millis(1));
+    chrono::Duration::from_std(interval)
+        .ok()
+        .and_then(|interval| at.checked_add_signed(interval))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+/// Round a value down to a multiple of `step`
+fn round_down(value: Price, step: Price) -> Price {
+    if step.is_zero() {
+        return value;
+    }
+    (value / step).floor() * step
+}
+
+/// Round a value up to a multiple of `step`
+fn round_up(value: Price, step: Price) -> Price {
+    if step.is_zero() {
+        return value;
+    }
+    (value / step).ceil() * step
+}
@@ -0,0 +1,132 @@
+//! Backtest results
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use common::decimal::{Amount, Price, Quantity};
+use common::model::order::Side;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::strategy::Fill;
+
+/// Outcome of a backtest
+#[derive(Debug, Clone, Serialize)]
+pub struct BacktestReport {
+    /// Market replayed
+    pub market: String,
+    /// Time of the first historical trade
+    pub start: DateTime<Utc>,
+    /// Time of the last historical trade
+    pub end: DateTime<Utc>,
+    /// Historical trades replayed
+    pub trades_replayed: usize,
+    /// Price the balances are marked at, the last historical price
+    pub mark_price: Price,
+    /// Results by strategy, in registration order
+    pub strategies: Vec<StrategyReport>,
+}
+
+/// Results of one strategy
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyReport {
+    /// Strategy name
+    pub name: String,
+    /// Account the strategy traded from
+    pub account_id: Uuid,
+    /// Fills of the strategy's orders
+    pub fills: usize,
+    /// Fills of resting orders
+    pub maker_fills: usize,
+    /// Base asset bought
+    pub bought: Quantity,
+    /// Base asset sold
+    pub sold: Quantity,
+    /// Fees paid by asset
+    pub fees: BTreeMap<String, Amount>,
+    /// Balances the account was funded with
+    pub starting_balances: BTreeMap<String, Quantity>,
+    /// Balances at the end of the replay
+    pub ending_balances: BTreeMap<String, Quantity>,
+    /// Change in the quote value of the balances, both marked at the mark
+    /// price, so holding the funds alone scores zero
+    pub pnl: Amount,
+    /// Every fill, oldest first
+    pub fill_log: Vec<FillRecord>,
+}
+
+/// Fill as recorded by a backtest
+#[derive(Debug, Clone, Serialize)]
+pub struct FillRecord {
+    /// Simulated time of the fill
+    pub at: DateTime<Utc>,
+    /// Strategy order that traded
+    pub order_id: Uuid,
+    /// Side of the strategy order
+    pub side: Side,
+    /// Execution price
+    pub price: Price,
+    /// Quantity filled
+    pub quantity: Quantity,
+    /// Whether the strategy order was resting on the book
+    pub is_maker: bool,
+    /// Fee charged for the fill
+    pub fee: Amount,
+    /// Asset the fee was charged in
+    pub fee_asset: String,
+}
+
+impl FillRecord {
+    pub(crate) fn new(fill: &Fill, at: DateTime<Utc>) -> Self {
+        let (fee, fee_asset) = if fill.is_maker {
+            (fill.trade.maker_fee, fill.trade.maker_fee_asset.clone())
+        } else {
+            (fill.trade.taker_fee, fill.trade.taker_fee_asset.clone())
+        };
+        Self {
+            at,
+            order_id: fill.order_id,
+            side: fill.side,
+            price: fill.price,
+            quantity: fill.quantity,
+            is_maker: fill.is_maker,
+            fee,
+            fee_asset,
+        }
+    }
+}
+
+impl StrategyReport {
+    pub(crate) fn new(
+        name: String,
+        account_id: Uuid,
+        fill_log: Vec<FillRecord>,
+        starting_balances: BTreeMap<String, Quantity>,
+        ending_balances: BTreeMap<String, Quantity>,
+        value: impl Fn(&BTreeMap<String, Quantity>) -> Amount,
+    ) -> Self {
+        let mut fees = BTreeMap::new();
+        let (mut bought, mut sold) = (Quantity::ZERO, Quantity::ZERO);
+        for fill in &fill_log {
+            match fill.side {
+                Side::Buy => bought += fill.quantity,
+                Side::Sell => sold += fill.quantity,
+            }
+            *fees.entry(fill.fee_asset.clone()).or_insert(Amount::ZERO) += fill.fee;
+        }
+
+        Self {
+            name,
+            account_id,
+            fills: fill_log.len(),
+            maker_fills: fill_log.iter().filter(|fill| fill.is_maker).count(),
+            bought,
+            sold,
+            fees,
+            pnl: value(&ending_balances) - value(&starting_balances),
+            starting_balances,
+            ending_balances,
+            fill_log,
+        }
+    }
+}
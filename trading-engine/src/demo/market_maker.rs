@@ -0,0 +1,118 @@
+//! Market-maker bot quoting both sides around a drifting reference price
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::decimal::{Price, Quantity};
+use common::error::Result;
+use common::model::market::Market;
+use common::model::order::{Order, Side, TimeInForce};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_decimal_macros::dec;
+use tracing::debug;
+use uuid::Uuid;
+
+use super::{random_fraction, round_to_step, DemoConfig};
+use crate::strategy::{Strategy, StrategyHandle};
+
+/// Bot that keeps a ladder of bids and asks on the book
+pub struct MarketMaker {
+    name: String,
+    market: Market,
+    config: DemoConfig,
+    /// Price the ladder is centred on
+    reference_price: Price,
+    /// Orders placed in the last requote
+    resting: Vec<Uuid>,
+    rng: StdRng,
+}
+
+impl MarketMaker {
+    /// Create a market maker quoting a market
+    pub fn new(name: String, market: Market, config: DemoConfig) -> Self {
+        let reference_price = config.start_price;
+        Self {
+            name,
+            market,
+            config,
+            reference_price,
+            resting: Vec::new(),
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Replace the previous ladder with a fresh one around the new reference price
+    async fn requote(&mut self, handle: &StrategyHandle) -> Result<()> {
+        // Keep tracking quotes that could not be cancelled, e.g. when throttled
+        let resting = std::mem::take(&mut self.resting);
+        for (i, order_id) in resting.iter().enumerate() {
+            if let Err(e) = handle.cancel(*order_id).await {
+                self.resting.extend_from_slice(&resting[i..]);
+                return Err(e);
+            }
+        }
+
+        self.update_reference_price(handle);
+
+        for level in 1..=self.config.levels {
+            let offset = self.config.level_spacing * Price::from(level as u64);
+
+            for (side, price) in [
+                (Side::Buy, self.reference_price * (dec!(1) - offset)),
+                (Side::Sell, self.reference_price * (dec!(1) + offset)),
+            ] {
+                let price = round_to_step(price, self.market.price_tick);
+                let order = Order::new_limit(
+                    handle.account_id(),
+                    self.market.symbol.clone(),
+                    side,
+                    price,
+                    self.random_quantity(),
+                    TimeInForce::GTC,
+                );
+
+                let placed = handle.place(order).await?;
+                if placed.is_active() {
+                    self.resting.push(placed.id);
+                }
+            }
+        }
+
+        debug!(
+            "Market maker {} quoting {} levels around {}",
+            self.name, self.config.levels, self.reference_price
+        );
+        Ok(())
+    }
+
+    /// Pull the reference towards the last trade, then take a random step
+    fn update_reference_price(&mut self, handle: &StrategyHandle) {
+        if let Some(last) = handle.last_price(&self.market.symbol) {
+            self.reference_price = (self.reference_price + last) / dec!(2);
+        }
+
+        let step = self.config.volatility * (dec!(2) * random_fraction(&mut self.rng) - dec!(1));
+        self.reference_price *= dec!(1) + step;
+    }
+
+    fn random_quantity(&mut self) -> Quantity {
+        let quantity = self.config.max_quantity * random_fraction(&mut self.rng);
+        round_to_step(quantity, self.market.quantity_step).max(self.market.quantity_step)
+    }
+}
+
+#[async_trait]
+impl Strategy for MarketMaker {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn tick_interval(&mut self) -> Duration {
+        self.config.quote_interval
+    }
+
+    async fn on_tick(&mut self, handle: &StrategyHandle) -> Result<()> {
+        self.requote(handle).await
+    }
+}
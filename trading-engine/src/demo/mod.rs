@@ -0,0 +1,100 @@
+//! Demo mode trading bots
+//!
+//! Market-maker bots continuously quote around a drifting reference price and
+//! random-taker bots cross the spread, so the order book, trades, candles and
+//! WebSocket channels stay active for UI development. Both run as strategies
+//! of the engine host, each trading from its own funded account.
+
+mod market_maker;
+mod taker;
+
+use std::time::Duration;
+
+use common::decimal::{Price, Quantity};
+use common::model::market::Market;
+use rust_decimal_macros::dec;
+use tracing::info;
+
+use crate::strategy::StrategyHost;
+
+pub use market_maker::MarketMaker;
+pub use taker::RandomTaker;
+
+/// Demo bot configuration
+#[derive(Debug, Clone)]
+pub struct DemoConfig {
+    /// Number of market-maker bots per market
+    pub market_makers: usize,
+    /// Number of random-taker bots per market
+    pub takers: usize,
+    /// Price the market makers start quoting around
+    pub start_price: Price,
+    /// Price levels quoted on each side
+    pub levels: usize,
+    /// Distance between quoted levels, as a fraction of the reference price
+    pub level_spacing: Price,
+    /// Largest random step of the reference price per requote
+    pub volatility: Price,
+    /// Largest quantity a bot quotes or takes
+    pub max_quantity: Quantity,
+    /// Delay between market-maker requotes
+    pub quote_interval: Duration,
+    /// Average delay between taker orders
+    pub taker_interval: Duration,
+}
+
+impl Default for DemoConfig {
+    fn default() -> Self {
+        Self {
+            market_makers: 2,
+            takers: 3,
+            start_price: dec!(20000),
+            levels: 5,
+            level_spacing: dec!(0.0005),
+            volatility: dec!(0.001),
+            max_quantity: dec!(0.5),
+            quote_interval: Duration::from_secs(2),
+            taker_interval: Duration::from_millis(1500),
+        }
+    }
+}
+
+/// Funds given to every bot account
+const BOT_QUOTE_FUNDS: Quantity = dec!(1000000000);
+const BOT_BASE_FUNDS: Quantity = dec!(1000000);
+
+/// Register the demo bots for a market as strategies of the host
+pub fn register(host: &mut StrategyHost, market: Market, config: DemoConfig) {
+    let funds = vec![
+        (market.quote_asset.clone(), BOT_QUOTE_FUNDS),
+        (market.base_asset.clone(), BOT_BASE_FUNDS),
+    ];
+
+    for i in 0..config.market_makers {
+        let bot = MarketMaker::new(format!("demo-maker-{}", i + 1), market.clone(), config.clone());
+        host.register(Box::new(bot), funds.clone());
+    }
+
+    for i in 0..config.takers {
+        let bot = RandomTaker::new(format!("demo-taker-{}", i + 1), market.clone(), config.clone());
+        host.register(Box::new(bot), funds.clone());
+    }
+
+    info!(
+        "Registered {} market makers and {} takers on {}",
+        config.market_makers, config.takers, market.symbol
+    );
+}
+
+/// Round a value down to a multiple of `step`
+fn round_to_step(value: Price, step: Price) -> Price {
+    if step.is_zero() {
+        return value;
+    }
+    (value / step).floor() * step
+}
+
+/// Random fraction in [0, 1) as a decimal
+fn random_fraction(rng: &mut impl rand::Rng) -> Price {
+    Price::from(rng.gen_range(0..10_000u32)) / dec!(10000)
+}
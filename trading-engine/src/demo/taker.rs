@@ -0,0 +1,89 @@
+//! Random-taker bot crossing the spread at random intervals
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::decimal::Quantity;
+use common::error::Result;
+use common::model::market::Market;
+use common::model::order::{Order, Side, TimeInForce};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+use tracing::debug;
+
+use super::{random_fraction, round_to_step, DemoConfig};
+use crate::strategy::{Strategy, StrategyHandle};
+
+/// Bot that takes liquidity from the top of the book
+pub struct RandomTaker {
+    name: String,
+    market: Market,
+    config: DemoConfig,
+    rng: StdRng,
+}
+
+impl RandomTaker {
+    /// Create a taker trading a market
+    pub fn new(name: String, market: Market, config: DemoConfig) -> Self {
+        Self {
+            name,
+            market,
+            config,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Send an immediate-or-cancel order at the best opposite price
+    async fn take(&mut self, handle: &StrategyHandle) -> Result<()> {
+        let side = if self.rng.gen_bool(0.5) { Side::Buy } else { Side::Sell };
+        let (best_bid, best_ask) = handle.best_prices(&self.market.symbol)?;
+
+        let price = match side {
+            Side::Buy => best_ask,
+            Side::Sell => best_bid,
+        };
+        let Some(price) = price else {
+            return Ok(());
+        };
+
+        let order = Order::new_limit(
+            handle.account_id(),
+            self.market.symbol.clone(),
+            side,
+            price,
+            self.random_quantity(),
+            TimeInForce::IOC,
+        );
+
+        let taker = handle.place(order).await?;
+        debug!(
+            "Taker {} {:?} {} @ {} -> {:?}",
+            self.name, side, taker.filled_quantity, price, taker.status
+        );
+        Ok(())
+    }
+
+    fn random_quantity(&mut self) -> Quantity {
+        let quantity = self.config.max_quantity * random_fraction(&mut self.rng);
+        round_to_step(quantity, self.market.quantity_step).max(self.market.quantity_step)
+    }
+}
+
+#[async_trait]
+impl Strategy for RandomTaker {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Jitter the delay between half and one and a half intervals
+    fn tick_interval(&mut self) -> Duration {
+        let jitter = (dec!(0.5) + random_fraction(&mut self.rng)).to_f64().unwrap_or(1.0);
+        self.config.taker_interval.mul_f64(jitter)
+    }
+
+    async fn on_tick(&mut self, handle: &StrategyHandle) -> Result<()> {
+        self.take(handle).await
+    }
+}
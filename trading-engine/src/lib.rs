@@ -0,0 +1,12 @@
+//! Trading engine host
+//!
+//! In-process strategies and the demo bots built on them, run by the
+//! trading-engine binary next to the matching engine on the gateway's
+//! runtime, backtests replaying historical data for them and the self-test
+//! operators run against a build.
+
+pub mod backtest;
+pub mod demo;
+pub mod runtime;
+pub mod selftest;
+pub mod strategy;
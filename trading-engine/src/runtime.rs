@@ -0,0 +1,50 @@
+//! Strategies on the shared gateway runtime
+//!
+//! Adds the in-process strategies and demo bots to an api-gateway
+//! [`Runtime`], so the trading-engine binary serves the same services and
+//! background tasks as the gateway binary with its strategies on top.
+
+use api_gateway::runtime::Runtime;
+use common::model::market::Market;
+use tracing::info;
+
+use crate::demo::{self, DemoConfig};
+use crate::strategy::{Exchange, StrategyHost};
+
+/// Strategy hosting on a [`Runtime`]
+pub trait StrategyRuntime {
+    /// Register strategies once the runtime is built and start them before serving
+    ///
+    /// `register` is given the host and the runtime's markets.
+    fn with_strategies(self, register: impl FnOnce(&mut StrategyHost, &[Market]) + Send + 'static) -> Self;
+
+    /// Run the demo bots on the runtime's first market
+    fn with_demo(self, config: DemoConfig) -> Self;
+}
+
+impl StrategyRuntime for Runtime {
+    fn with_strategies(self, register: impl FnOnce(&mut StrategyHost, &[Market]) + Send + 'static) -> Self {
+        self.on_start(move |state| async move {
+            let mut strategies = StrategyHost::new(Exchange::new(
+                state.matching_engine.clone(),
+                state.account_service.clone(),
+                state.market_data_service.clone(),
+            ));
+            register(&mut strategies, &state.markets);
+
+            if !strategies.is_empty() {
+                info!("Starting {} strategies...", strategies.len());
+                strategies.start().await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn with_demo(self, config: DemoConfig) -> Self {
+        self.with_strategies(move |strategies, markets| {
+            if let Some(market) = markets.first() {
+                demo::register(strategies, market.clone(), config);
+            }
+        })
+    }
+}
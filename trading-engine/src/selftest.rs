@@ -0,0 +1,286 @@
+//! Startup self-test
+//!
+//! Boots the gateway in memory on a loopback port and drives it the way a
+//! client would: it checks health, creates a maker and a taker account,
+//! funds them, subscribes to the market's trades over WebSocket, crosses a
+//! sell with a buy and then checks both accounts' balances and the trade
+//! notification. Steps run in order and stop at the first failure, so the
+//! report shows how far a build or host got.
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use api_gateway::config::AppConfig;
+use api_gateway::runtime::{Runtime, Server};
+use common::decimal::{Price, Quantity};
+use common::error::{Error, Result};
+use common::model::market::Market;
+use futures::{SinkExt, StreamExt};
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Price the self-test trades at
+const PRICE: Price = dec!(100);
+/// Quantity the self-test trades
+const QUANTITY: Quantity = dec!(1);
+/// Longest wait for a response or notification
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Outcome of one self-test step
+#[derive(Debug, Clone, Serialize)]
+pub struct StepReport {
+    /// Step name
+    pub name: String,
+    /// Whether the step passed
+    pub passed: bool,
+    /// What the step saw, or why it failed
+    pub detail: String,
+    /// Time the step took, in milliseconds
+    pub elapsed_ms: u64,
+}
+
+/// Outcome of a self-test
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SelfTestReport {
+    /// Steps run, in order, up to the first failure
+    pub steps: Vec<StepReport>,
+}
+
+impl SelfTestReport {
+    /// Whether every step passed
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(|step| step.passed)
+    }
+
+    /// First failed step, if any
+    pub fn failure(&self) -> Option<&StepReport> {
+        self.steps.iter().find(|step| !step.passed)
+    }
+
+    /// Record the outcome of step `name`, started at `started`, passing its result through
+    fn record<T>(&mut self, name: &str, started: Instant, result: Result<(T, String)>) -> Result<T> {
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        let (passed, detail, result) = match result {
+            Ok((value, detail)) => (true, detail, Ok(value)),
+            Err(e) => (false, e.to_string(), Err(e)),
+        };
+        if passed {
+            info!("Self-test step {} passed: {}", name, detail);
+        } else {
+            warn!("Self-test step {} failed: {}", name, detail);
+        }
+        self.steps.push(StepReport { name: name.to_string(), passed, detail, elapsed_ms });
+        result
+    }
+}
+
+/// Build the gateway from `config` without a database and run the scenario against it
+///
+/// Fees are left at zero so the balances checked are exact.
+pub async fn run(mut config: AppConfig) -> Result<SelfTestReport> {
+    config.database_url = None;
+    config.market_data_persist = false;
+    config.market_data_warmup = None;
+    let server = Runtime::from_config(config).build().await?;
+    run_on(server).await
+}
+
+/// Serve `server` on a loopback port and run the scenario against its first market
+pub async fn run_on(server: Server) -> Result<SelfTestReport> {
+    let market = server.state.markets.first().cloned()
+        .ok_or_else(|| Error::Internal("No market to self-test".to_string()))?;
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await
+        .map_err(|e| Error::Internal(format!("Failed to bind a loopback port: {}", e)))?;
+    let addr = listener.local_addr().map_err(|e| Error::Internal(e.to_string()))?;
+    let serving = tokio::spawn(server.serve_on(listener));
+
+    let mut report = SelfTestReport::default();
+    let client = Client { http: reqwest::Client::new(), addr };
+    // A failed step is in the report, which ends there
+    let _ = scenario(&client, &market, &mut report).await;
+    serving.abort();
+    Ok(report)
+}
+
+/// The scripted steps, stopping at the first failure
+async fn scenario(client: &Client, market: &Market, report: &mut SelfTestReport) -> Result<()> {
+    let started = Instant::now();
+    let result = client.request(reqwest::Method::GET, "/health", None, None).await
+        .map(|health| ((), format!("status {}", health["status"])));
+    report.record("health", started, result)?;
+
+    let started = Instant::now();
+    let result = async {
+        let maker = client.create_account().await?;
+        let taker = client.create_account().await?;
+        Ok(((maker.clone(), taker.clone()), format!("maker {}, taker {}", maker.0, taker.0)))
+    }.await;
+    let (maker, taker) = report.record("accounts", started, result)?;
+
+    let started = Instant::now();
+    let result = async {
+        client.deposit(&maker, &market.base_asset, QUANTITY).await?;
+        client.deposit(&taker, &market.quote_asset, PRICE * QUANTITY).await?;
+        Ok(((), format!("{} {} to the maker, {} {} to the taker", QUANTITY, market.base_asset, PRICE * QUANTITY, market.quote_asset)))
+    }.await;
+    report.record("deposits", started, result)?;
+
+    let started = Instant::now();
+    let result = async {
+        let (mut socket, _) = tokio::time::timeout(TIMEOUT, connect_async(format!("ws://{}/ws", client.addr))).await
+            .map_err(|_| Error::Internal("Timed out connecting to /ws".to_string()))?
+            .map_err(|e| Error::Internal(format!("Failed to connect to /ws: {}", e)))?;
+        let subscription = subscribe(&mut socket, &market.symbol).await?;
+        Ok(((socket, subscription.clone()), format!("subscription {}", subscription)))
+    }.await;
+    let (mut socket, subscription) = report.record("websocket subscribe", started, result)?;
+
+    let started = Instant::now();
+    let result = async {
+        let sell = client.place_order(&maker, &market.symbol, "Sell").await?;
+        let buy = client.place_order(&taker, &market.symbol, "Buy").await?;
+        Ok(((), format!("sell {} crossed by buy {}", sell, buy)))
+    }.await;
+    report.record("orders", started, result)?;
+
+    let started = Instant::now();
+    let result = trade_notification(&mut socket, &subscription).await;
+    report.record("websocket trade", started, result)?;
+
+    let started = Instant::now();
+    let result = async {
+        client.expect_balances(&maker, &[(&market.base_asset, Quantity::ZERO), (&market.quote_asset, PRICE * QUANTITY)]).await?;
+        client.expect_balances(&taker, &[(&market.base_asset, QUANTITY), (&market.quote_asset, Quantity::ZERO)]).await?;
+        Ok(((), "maker and taker settled".to_string()))
+    }.await;
+    report.record("balances", started, result)?;
+    Ok(())
+}
+
+/// Subscribe `socket` to the trades of `market`, returning the subscription ID
+async fn subscribe(socket: &mut Socket, market: &str) -> Result<String> {
+    let request = json!({ "id": "selftest", "method": "subscribe", "params": { "channel": "trades", "market": market } });
+    socket.send(Message::Text(request.to_string())).await
+        .map_err(|e| Error::Internal(format!("Failed to subscribe: {}", e)))?;
+    loop {
+        let message = next_message(socket).await?;
+        if message.get("id").is_some() {
+            return message["result"]["subscriptionId"].as_str()
+                .map(str::to_string)
+                .ok_or_else(|| Error::Internal(format!("Subscription refused: {}", message)));
+        }
+    }
+}
+
+/// Wait for the trade notification of `subscription`
+async fn trade_notification(socket: &mut Socket, subscription: &str) -> Result<((), String)> {
+    loop {
+        let message = next_message(socket).await?;
+        let params = &message["params"];
+        if params["subscription_id"] != subscription && params["subscriptionId"] != subscription {
+            continue;
+        }
+        let trade = &params["data"];
+        let price: Option<Price> = trade["price"].as_str().and_then(|price| price.parse().ok());
+        let quantity: Option<Quantity> = trade["quantity"].as_str().and_then(|quantity| quantity.parse().ok());
+        if price != Some(PRICE) || quantity != Some(QUANTITY) || trade["taker_side"] != "buy" {
+            return Err(Error::Internal(format!("Unexpected trade notification: {}", trade)));
+        }
+        return Ok(((), format!("trade {} at {}", trade["id"], PRICE)));
+    }
+}
+
+/// Next text message of `socket`, as JSON
+async fn next_message(socket: &mut Socket) -> Result<Value> {
+    loop {
+        let message = tokio::time::timeout(TIMEOUT, socket.next()).await
+            .map_err(|_| Error::Internal("Timed out waiting for a WebSocket message".to_string()))?
+            .ok_or_else(|| Error::Internal("WebSocket closed".to_string()))?
+            .map_err(|e| Error::Internal(format!("WebSocket error: {}", e)))?;
+        if let Message::Text(text) = message {
+            return serde_json::from_str(&text).map_err(|e| Error::Internal(format!("Invalid WebSocket message: {}", e)));
+        }
+    }
+}
+
+/// HTTP client of the gateway under test
+struct Client {
+    http: reqwest::Client,
+    addr: SocketAddr,
+}
+
+impl Client {
+    /// Send a request to the v1 API, returning the `data` of a successful response
+    async fn request(&self, method: reqwest::Method, path: &str, key: Option<&str>, body: Option<Value>) -> Result<Value> {
+        let mut request = self.http.request(method.clone(), format!("http://{}/api/v1{}", self.addr, path)).timeout(TIMEOUT);
+        if let Some(key) = key {
+            request = request.header("x-api-key", key);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.map_err(|e| Error::Internal(format!("{} {} failed: {}", method, path, e)))?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            return Err(Error::Internal(format!("{} {} returned {}: {}", method, path, status, body)));
+        }
+        Ok(body.get("data").cloned().unwrap_or(body))
+    }
+
+    /// Create an account, returning its ID and API key
+    async fn create_account(&self) -> Result<(Uuid, String)> {
+        let account = self.request(reqwest::Method::POST, "/accounts", None, Some(json!({}))).await?;
+        let id = account["id"].as_str().and_then(|id| id.parse().ok());
+        let key = account["api_key"].as_str().map(str::to_string);
+        id.zip(key).ok_or_else(|| Error::Internal(format!("Unexpected account: {}", account)))
+    }
+
+    async fn deposit(&self, (id, key): &(Uuid, String), asset: &str, amount: Quantity) -> Result<()> {
+        let deposit = json!({ "asset": asset, "amount": amount.to_string() });
+        self.request(reqwest::Method::POST, &format!("/accounts/{}/deposit", id), Some(key), Some(deposit)).await?;
+        Ok(())
+    }
+
+    /// Place a limit order of the self-test price and quantity, returning its ID
+    async fn place_order(&self, (id, key): &(Uuid, String), market: &str, side: &str) -> Result<String> {
+        let order = json!({
+            "user_id": id,
+            "market": market,
+            "side": side,
+            "order_type": "Limit",
+            "price": PRICE.to_string(),
+            "quantity": QUANTITY.to_string(),
+        });
+        let placed = self.request(reqwest::Method::POST, "/orders", Some(key), Some(order)).await?;
+        placed["order"]["id"].as_str()
+            .map(str::to_string)
+            .ok_or_else(|| Error::Internal(format!("Unexpected order: {}", placed)))
+    }
+
+    /// Check the account's total balance of each asset, an absent asset counting as zero
+    async fn expect_balances(&self, (id, key): &(Uuid, String), expected: &[(&str, Quantity)]) -> Result<()> {
+        let balances = self.request(reqwest::Method::GET, &format!("/accounts/{}/balances", id), Some(key), None).await?;
+        for (asset, amount) in expected {
+            let total = balances.as_array()
+                .and_then(|balances| balances.iter().find(|balance| balance["asset"] == *asset))
+                .and_then(|balance| balance["total"].as_str())
+                .map(|total| total.parse::<Quantity>())
+                .transpose()
+                .map_err(|e| Error::Internal(format!("Invalid {} balance: {}", asset, e)))?
+                .unwrap_or(Quantity::ZERO);
+            if total != *amount {
+                return Err(Error::Internal(format!("Account {} holds {} {}, expected {}", id, total, asset, amount)));
+            }
+        }
+        Ok(())
+    }
+}
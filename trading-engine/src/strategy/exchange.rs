@@ -0,0 +1,123 @@
+//! In-process order entry for strategies
+
+use std::sync::Arc;
+
+use common::decimal::{Price, Quantity};
+use common::error::{Error, Result};
+use common::model::order::Order;
+use common::model::trade::Trade;
+use uuid::Uuid;
+
+use account_service::AccountService;
+use market_data::MarketDataService;
+use matching_engine::MatchingEngine;
+
+/// Order entry shared by the strategies, following the same steps as the REST API
+#[derive(Clone)]
+pub struct Exchange {
+    matching_engine: Arc<MatchingEngine>,
+    account_service: Arc<AccountService>,
+    market_data_service: Arc<MarketDataService>,
+}
+
+impl Exchange {
+    /// Create an exchange handle over the running services
+    pub fn new(
+        matching_engine: Arc<MatchingEngine>,
+        account_service: Arc<AccountService>,
+        market_data_service: Arc<MarketDataService>,
+    ) -> Self {
+        Self {
+            matching_engine,
+            account_service,
+            market_data_service,
+        }
+    }
+
+    /// Engine the strategies trade on
+    pub fn matching_engine(&self) -> &Arc<MatchingEngine> {
+        &self.matching_engine
+    }
+
+    /// Create an account funded with the given balances
+    pub async fn create_account(&self, funds: &[(String, Quantity)]) -> Result<Uuid> {
+        let account = self.account_service.create_account().await?;
+        for (asset, amount) in funds {
+            self.account_service.deposit(account.id, asset, *amount).await?;
+        }
+        Ok(account.id)
+    }
+
+    /// Reserve funds, match, settle and publish an order, under a new id from the engine
+    pub async fn place(&self, mut order: Order) -> Result<Arc<Order>> {
+        order.id = self.matching_engine.id_generator().next_id();
+        self.account_service.reserve_for_order(&order).await?;
+
+        let result = match self.matching_engine.place_order(order.clone()) {
+            Ok(result) => result,
+            Err(e) => {
+                self.account_service.release_reserved_funds(&order).await?;
+                return Err(e);
+            }
+        };
+
+        for trade in &result.trades {
+            self.account_service.process_trade(trade).await?;
+            self.market_data_service.process_trade(trade).await?;
+        }
+
+        let taker = result.taker_order.unwrap_or_else(|| Arc::new(order));
+        if taker.is_engine_terminated() {
+            self.account_service.release_reserved_funds(&taker).await?;
+        }
+        for expired in &result.expired_orders {
+            self.account_service.release_reserved_funds(expired).await?;
+        }
+
+        self.publish_depth(&taker.market).await?;
+        Ok(taker)
+    }
+
+    /// Cancel a resting order, ignoring orders that already left the book
+    pub async fn cancel(&self, order_id: Uuid) -> Result<()> {
+        match self.matching_engine.cancel_order(order_id) {
+            Ok(order) => self.account_service.release_reserved_funds(&order).await,
+            Err(Error::OrderNotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Available balance of an account in an asset
+    pub async fn available(&self, account_id: Uuid, asset: &str) -> Result<Quantity> {
+        let balance = self.account_service.get_balance(account_id, asset).await?;
+        Ok(balance.map(|b| b.available).unwrap_or_default())
+    }
+
+    /// Total balances of an account by asset
+    pub async fn balances(&self, account_id: Uuid) -> Result<Vec<(String, Quantity)>> {
+        let balances = self.account_service.get_balances(account_id).await?;
+        Ok(balances.into_iter().map(|b| (b.asset, b.total)).collect())
+    }
+
+    /// Record a trade executed away from the engine, e.g. replayed history
+    pub async fn record_trade(&self, trade: &Trade) -> Result<()> {
+        self.market_data_service.process_trade(trade).await
+    }
+
+    /// Best bid and ask of a market
+    pub fn best_prices(&self, market: &str) -> Result<(Option<Price>, Option<Price>)> {
+        let (bids, asks) = self.matching_engine.get_market_depth(market, 1)?;
+        Ok((bids.first().map(|(p, _)| *p), asks.first().map(|(p, _)| *p)))
+    }
+
+    /// Last traded price of a market
+    pub fn last_price(&self, market: &str) -> Option<Price> {
+        self.market_data_service.get_ticker(market).and_then(|t| t.last)
+    }
+
+    /// Push the current depth to market data subscribers
+    pub async fn publish_depth(&self, market: &str) -> Result<()> {
+        let (bids, asks) = self.matching_engine.get_market_depth(market, 10)?;
+        self.market_data_service.update_order_book(market, bids, asks).await
+    }
+}
@@ -0,0 +1,266 @@
+//! In-process trading strategies
+//!
+//! A [`Strategy`] runs inside the engine host next to the matching engine,
+//! so bots such as market makers or arbitrageurs react without a network hop.
+//! Each registered strategy trades from an account of its own, created and
+//! funded at start, and is driven by three callbacks on its own task: a timer
+//! tick, every trade of the engine, and every fill of its own orders. Orders
+//! go through a [`StrategyHandle`], which settles and publishes them the same
+//! way the REST API does.
+//!
+//! Callbacks run one at a time per strategy; an error is logged and the
+//! strategy keeps running.
+
+mod exchange;
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::decimal::{Price, Quantity};
+use common::error::Result;
+use common::model::order::{Order, Side};
+use common::model::trade::Trade;
+use matching_engine::EngineEvent;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+pub use exchange::Exchange;
+
+/// Trading logic run in-process by the [`StrategyHost`]
+#[async_trait]
+pub trait Strategy: Send {
+    /// Name shown in logs
+    fn name(&self) -> &str;
+
+    /// Delay until the next tick, asked again after every tick
+    fn tick_interval(&mut self) -> Duration {
+        Duration::from_secs(1)
+    }
+
+    /// Called on every timer tick
+    async fn on_tick(&mut self, handle: &StrategyHandle) -> Result<()>;
+
+    /// Called for every trade executed by the engine, including the strategy's own
+    async fn on_trade(&mut self, _handle: &StrategyHandle, _trade: &Trade) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when one of the strategy's orders trades, after `on_trade`
+    async fn on_fill(&mut self, _handle: &StrategyHandle, _fill: &Fill) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The strategy's part in a trade
+#[derive(Debug, Clone)]
+pub struct Fill {
+    /// Trade the fill belongs to
+    pub trade: Arc<Trade>,
+    /// Strategy order that traded
+    pub order_id: Uuid,
+    /// Side of the strategy order
+    pub side: Side,
+    /// Execution price
+    pub price: Price,
+    /// Quantity filled
+    pub quantity: Quantity,
+    /// Whether the strategy order was resting on the book
+    pub is_maker: bool,
+}
+
+impl Fill {
+    /// The fill of `account_id` in a trade, if it took part
+    pub(crate) fn of(trade: &Arc<Trade>, account_id: Uuid) -> Option<Self> {
+        let (order_id, side, is_maker) = if trade.buyer_id == account_id {
+            (trade.buyer_order_id, Side::Buy, trade.is_buyer_maker)
+        } else if trade.seller_id == account_id {
+            (trade.seller_order_id, Side::Sell, !trade.is_buyer_maker)
+        } else {
+            return None;
+        };
+        Some(Self {
+            trade: trade.clone(),
+            order_id,
+            side,
+            price: trade.price,
+            quantity: trade.quantity,
+            is_maker,
+        })
+    }
+}
+
+/// Order entry and market access of one strategy, bound to its account
+#[derive(Clone)]
+pub struct StrategyHandle {
+    exchange: Exchange,
+    account_id: Uuid,
+}
+
+impl StrategyHandle {
+    pub(crate) fn new(exchange: Exchange, account_id: Uuid) -> Self {
+        Self { exchange, account_id }
+    }
+
+    /// Account the strategy trades from
+    pub fn account_id(&self) -> Uuid {
+        self.account_id
+    }
+
+    /// Current time by the engine's clock, simulated in a backtest
+    pub fn now(&self) -> DateTime<Utc> {
+        self.exchange.matching_engine().clock().now()
+    }
+
+    /// Place an order, which must be for the strategy's account
+    ///
+    /// The order is given a new id by the engine, the returned order has it.
+    pub async fn place(&self, order: Order) -> Result<Arc<Order>> {
+        if order.user_id != self.account_id {
+            return Err(common::error::Error::ValidationError(format!(
+                "Strategy orders must be placed for account {}",
+                self.account_id
+            )));
+        }
+        self.exchange.place(order).await
+    }
+
+    /// Cancel a resting order, ignoring orders that already left the book
+    pub async fn cancel(&self, order_id: Uuid) -> Result<()> {
+        self.exchange.cancel(order_id).await
+    }
+
+    /// Available balance of the strategy's account in an asset
+    pub async fn available(&self, asset: &str) -> Result<Quantity> {
+        self.exchange.available(self.account_id, asset).await
+    }
+
+    /// Best bid and ask of a market
+    pub fn best_prices(&self, market: &str) -> Result<(Option<Price>, Option<Price>)> {
+        self.exchange.best_prices(market)
+    }
+
+    /// Last traded price of a market
+    pub fn last_price(&self, market: &str) -> Option<Price> {
+        self.exchange.last_price(market)
+    }
+}
+
+/// Strategy waiting to be started, with the funds of its account
+pub(crate) struct Registration {
+    pub(crate) strategy: Box<dyn Strategy>,
+    pub(crate) funds: Vec<(String, Quantity)>,
+}
+
+/// Runs registered strategies in-process
+pub struct StrategyHost {
+    exchange: Exchange,
+    registrations: Vec<Registration>,
+}
+
+impl StrategyHost {
+    /// Create a host trading on the given exchange
+    pub fn new(exchange: Exchange) -> Self {
+        Self {
+            exchange,
+            registrations: Vec::new(),
+        }
+    }
+
+    /// Exchange the strategies trade on
+    pub fn exchange(&self) -> &Exchange {
+        &self.exchange
+    }
+
+    /// Register a strategy, whose account is funded with `funds` when started
+    pub fn register(&mut self, strategy: Box<dyn Strategy>, funds: Vec<(String, Quantity)>) {
+        self.registrations.push(Registration { strategy, funds });
+    }
+
+    /// Number of registered strategies
+    pub fn len(&self) -> usize {
+        self.registrations.len()
+    }
+
+    /// Whether no strategy is registered
+    pub fn is_empty(&self) -> bool {
+        self.registrations.is_empty()
+    }
+
+    /// Exchange and registered strategies, to run them other than live
+    pub(crate) fn into_parts(self) -> (Exchange, Vec<Registration>) {
+        (self.exchange, self.registrations)
+    }
+
+    /// Create the strategies' accounts and run each on its own task
+    pub async fn start(self) -> Result<Vec<JoinHandle<()>>> {
+        let mut handles = Vec::with_capacity(self.registrations.len());
+
+        for Registration { strategy, funds } in self.registrations {
+            let account_id = self.exchange.create_account(&funds).await?;
+            let handle = StrategyHandle::new(self.exchange.clone(), account_id);
+            let trades = forward_trades(&self.exchange, strategy.name());
+            info!("Starting strategy {} on account {}", strategy.name(), account_id);
+            handles.push(tokio::spawn(run(strategy, handle, trades)));
+        }
+
+        Ok(handles)
+    }
+}
+
+/// Forward the engine's trades to a strategy's task from a background thread
+fn forward_trades(exchange: &Exchange, name: &str) -> mpsc::UnboundedReceiver<Arc<Trade>> {
+    let events = exchange.matching_engine().subscribe_events();
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    thread::Builder::new()
+        .name(format!("strategy-{}", name))
+        .spawn(move || {
+            for event in events {
+                if let EngineEvent::Trade(trade) = event {
+                    if sender.send(trade).is_err() {
+                        break;
+                    }
+                }
+            }
+        })
+        .expect("failed to spawn strategy event thread");
+
+    receiver
+}
+
+/// Drive a strategy's callbacks until the engine's event stream ends
+async fn run(mut strategy: Box<dyn Strategy>, handle: StrategyHandle, mut trades: mpsc::UnboundedReceiver<Arc<Trade>>) {
+    let tick = tokio::time::sleep(strategy.tick_interval());
+    tokio::pin!(tick);
+
+    loop {
+        tokio::select! {
+            _ = &mut tick => {
+                if let Err(e) = strategy.on_tick(&handle).await {
+                    warn!("Strategy {} failed on tick: {}", strategy.name(), e);
+                }
+                tick.as_mut().reset(tokio::time::Instant::now() + strategy.tick_interval());
+            }
+            trade = trades.recv() => {
+                let Some(trade) = trade else {
+                    break;
+                };
+                if let Err(e) = strategy.on_trade(&handle, &trade).await {
+                    warn!("Strategy {} failed on trade {}: {}", strategy.name(), trade.id, e);
+                }
+                if let Some(fill) = Fill::of(&trade, handle.account_id) {
+                    if let Err(e) = strategy.on_fill(&handle, &fill).await {
+                        warn!("Strategy {} failed on fill of order {}: {}", strategy.name(), fill.order_id, e);
+                    }
+                }
+            }
+        }
+    }
+
+    info!("Strategy {} stopped", strategy.name());
+}
@@ -0,0 +1,131 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use account_service::AccountService;
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use common::clock::ManualClock;
+use common::decimal::Quantity;
+use common::error::Result;
+use common::model::fee::FeeSchedule;
+use common::model::market::{Market, MarketKind};
+use common::model::order::{Order, Side, TimeInForce};
+use market_data::MarketDataService;
+use matching_engine::MatchingEngine;
+use rust_decimal_macros::dec;
+use trading_engine::backtest::{self, Backtest, BacktestConfig, HistoricalTrade};
+use trading_engine::strategy::{Exchange, Fill, Strategy, StrategyHandle, StrategyHost};
+
+const MARKET: &str = "BTC/USD";
+
+fn market() -> Market {
+    Market {
+        symbol: MARKET.to_string(),
+        base_asset: "BTC".to_string(),
+        quote_asset: "USD".to_string(),
+        price_tick: dec!(0.01),
+        quantity_step: dec!(0.0001),
+        min_order_size: dec!(0.0001),
+        max_price_deviation: 10.0,
+        trading_enabled: true,
+        kind: MarketKind::Spot,
+    }
+}
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(1_700_000_000 + seconds, 0).unwrap()
+}
+
+/// Strategy resting one bid at 99 on its first tick, which it records
+#[derive(Default)]
+struct BidAt99 {
+    placed_at: Arc<Mutex<Option<DateTime<Utc>>>>,
+    fills: Arc<Mutex<Vec<Fill>>>,
+}
+
+#[async_trait]
+impl Strategy for BidAt99 {
+    fn name(&self) -> &str {
+        "bid-at-99"
+    }
+
+    fn tick_interval(&mut self) -> Duration {
+        Duration::from_secs(1)
+    }
+
+    async fn on_tick(&mut self, handle: &StrategyHandle) -> Result<()> {
+        if self.placed_at.lock().unwrap().is_some() {
+            return Ok(());
+        }
+        *self.placed_at.lock().unwrap() = Some(handle.now());
+        let order = Order::new_limit(handle.account_id(), MARKET.to_string(), Side::Buy, dec!(99), dec!(1), TimeInForce::GTC);
+        handle.place(order).await?;
+        Ok(())
+    }
+
+    async fn on_fill(&mut self, _handle: &StrategyHandle, fill: &Fill) -> Result<()> {
+        self.fills.lock().unwrap().push(fill.clone());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_candles_become_trades_through_their_prices() {
+    let csv = "open_time,close_time,open,high,low,close,volume,quote_volume,trades\n\
+        2023-11-14T22:13:20.000Z,2023-11-14T22:14:20.000Z,100,104,98,102,8,800,5\n\
+        2023-11-14T22:14:20.000Z,2023-11-14T22:15:20.000Z,102,102,102,102,0,0,0\n";
+    let trades = backtest::parse(csv).unwrap();
+
+    // A rising candle dips to its low before reaching its high, its volume split evenly
+    let prices: Vec<_> = trades.iter().map(|trade| trade.price).collect();
+    assert_eq!(prices, [dec!(100), dec!(98), dec!(104), dec!(102)]);
+    let sides: Vec<_> = trades.iter().map(|trade| trade.taker_side).collect();
+    assert_eq!(sides, [Side::Buy, Side::Sell, Side::Buy, Side::Sell]);
+    assert!(trades.iter().all(|trade| trade.quantity == dec!(2)));
+    assert_eq!(trades[0].timestamp, at(0));
+    assert_eq!(trades[3].timestamp, at(45));
+
+    assert!(backtest::parse("when,price\n").is_err());
+}
+
+#[tokio::test]
+async fn test_history_fills_resting_strategy_orders() {
+    let csv = "id,timestamp,price,quantity,taker_side,is_buyer_maker\n\
+        a,2023-11-14T22:13:20.000Z,100,1,buy,false\n\
+        b,2023-11-14T22:13:22.000Z,99,3,sell,true\n\
+        c,2023-11-14T22:13:23.000Z,101,1,buy,false\n";
+    let trades: Vec<HistoricalTrade> = backtest::parse(csv).unwrap();
+
+    let clock = Arc::new(ManualClock::new(at(0)));
+    let fee_schedule = FeeSchedule::new(dec!(0.001), dec!(0.002)).unwrap();
+    let matching_engine = Arc::new(MatchingEngine::with_fee_schedule(fee_schedule).with_clock(clock.clone()));
+    matching_engine.register_market(MARKET.to_string());
+    let market_data = Arc::new(MarketDataService::new().with_clock(clock.clone()));
+    let mut host = StrategyHost::new(Exchange::new(matching_engine, Arc::new(AccountService::new()), market_data.clone()));
+    let strategy = BidAt99::default();
+    let (placed_at, fills) = (strategy.placed_at.clone(), strategy.fills.clone());
+    host.register(Box::new(strategy), vec![("USD".to_string(), dec!(1000))]);
+
+    let report = Backtest::run(host, clock, BacktestConfig::new(market()), &trades).await.unwrap();
+
+    // The strategy ticks in simulated time and its bid is hit by the sell
+    assert_eq!(*placed_at.lock().unwrap(), Some(at(1)));
+    assert_eq!(fills.lock().unwrap().len(), 1);
+    assert_eq!((report.trades_replayed, report.mark_price, report.end), (3, dec!(101), at(3)));
+
+    let strategy = &report.strategies[0];
+    assert_eq!((strategy.fills, strategy.maker_fills), (1, 1));
+    assert_eq!((strategy.bought, strategy.sold), (dec!(1), Quantity::ZERO));
+    assert_eq!(strategy.fill_log[0].at, at(2));
+    assert_eq!(strategy.fill_log[0].price, dec!(99));
+
+    // Market data keeps the replay's time, down to the candles
+    let trades = market_data.get_recent_trades(MARKET, 10);
+    assert!(trades.iter().all(|trade| trade.timestamp >= at(0) && trade.timestamp <= at(3)));
+
+    // The maker fee is paid in the BTC received, the rest is marked at 101
+    assert_eq!(strategy.fees["BTC"], dec!(0.001));
+    assert_eq!(strategy.ending_balances["BTC"], dec!(0.999));
+    assert_eq!(strategy.ending_balances["USD"], dec!(901));
+    assert_eq!(strategy.pnl, dec!(1.899));
+}
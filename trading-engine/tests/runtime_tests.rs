@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use api_gateway::config::AppConfig;
+use api_gateway::runtime::Runtime;
+use rust_decimal_macros::dec;
+use trading_engine::demo::DemoConfig;
+use trading_engine::runtime::StrategyRuntime;
+
+#[tokio::test]
+async fn test_demo_bots_quote_on_the_runtime_market() {
+    let config = DemoConfig {
+        market_makers: 1,
+        takers: 0,
+        start_price: dec!(100),
+        quote_interval: Duration::from_millis(20),
+        ..Default::default()
+    };
+    let server = Runtime::from_config(AppConfig::default())
+        .with_demo(config)
+        .build()
+        .await
+        .unwrap();
+
+    // The maker quotes both sides of the default market once started
+    let state = server.state.clone();
+    let mut quoted = false;
+    for _ in 0..50 {
+        let book = state.matching_engine.get_market_depth("BTC/USD", 5);
+        if book.is_ok_and(|(bids, asks)| !bids.is_empty() && !asks.is_empty()) {
+            quoted = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(quoted);
+}
@@ -0,0 +1,11 @@
+use api_gateway::config::AppConfig;
+use trading_engine::selftest;
+
+#[tokio::test]
+async fn test_selftest_passes_every_step_on_the_default_stack() {
+    let report = selftest::run(AppConfig::default()).await.unwrap();
+    assert!(report.passed(), "{:?}", report.failure());
+
+    let steps: Vec<&str> = report.steps.iter().map(|step| step.name.as_str()).collect();
+    assert_eq!(steps, ["health", "accounts", "deposits", "websocket subscribe", "orders", "websocket trade", "balances"]);
+}
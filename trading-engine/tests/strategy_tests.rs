@@ -0,0 +1,144 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use account_service::AccountService;
+use async_trait::async_trait;
+use common::decimal::Quantity;
+use common::error::Result;
+use common::model::order::{Order, Side, TimeInForce};
+use common::model::trade::Trade;
+use market_data::MarketDataService;
+use matching_engine::MatchingEngine;
+use rust_decimal_macros::dec;
+use trading_engine::strategy::{Exchange, Fill, Strategy, StrategyHandle, StrategyHost};
+use uuid::Uuid;
+
+const MARKET: &str = "BTC/USD";
+
+/// What a strategy saw through its callbacks
+#[derive(Default)]
+struct Seen {
+    trades: Vec<Trade>,
+    fills: Vec<Fill>,
+    errors: Vec<String>,
+}
+
+/// Strategy trading 2 at 100 once and recording its callbacks
+struct OneShot {
+    side: Side,
+    time_in_force: TimeInForce,
+    done: bool,
+    /// Place the order for this account instead of the strategy's own
+    account: Option<Uuid>,
+    seen: Arc<Mutex<Seen>>,
+}
+
+impl OneShot {
+    fn new(side: Side, time_in_force: TimeInForce) -> (Self, Arc<Mutex<Seen>>) {
+        let seen = Arc::new(Mutex::new(Seen::default()));
+        let strategy = Self { side, time_in_force, done: false, account: None, seen: seen.clone() };
+        (strategy, seen)
+    }
+}
+
+#[async_trait]
+impl Strategy for OneShot {
+    fn name(&self) -> &str {
+        "one-shot"
+    }
+
+    fn tick_interval(&mut self) -> Duration {
+        Duration::from_millis(10)
+    }
+
+    /// Place the order, again on later ticks while it neither rests nor trades
+    async fn on_tick(&mut self, handle: &StrategyHandle) -> Result<()> {
+        if self.done {
+            return Ok(());
+        }
+        let account = self.account.unwrap_or_else(|| handle.account_id());
+        let order = Order::new_limit(account, MARKET.to_string(), self.side, dec!(100), dec!(2), self.time_in_force);
+        match handle.place(order).await {
+            Ok(order) => self.done = order.is_active() || !order.filled_quantity.is_zero(),
+            Err(e) => {
+                self.done = true;
+                self.seen.lock().unwrap().errors.push(e.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    async fn on_trade(&mut self, _handle: &StrategyHandle, trade: &Trade) -> Result<()> {
+        self.seen.lock().unwrap().trades.push(trade.clone());
+        Ok(())
+    }
+
+    async fn on_fill(&mut self, _handle: &StrategyHandle, fill: &Fill) -> Result<()> {
+        self.seen.lock().unwrap().fills.push(fill.clone());
+        Ok(())
+    }
+}
+
+fn host() -> StrategyHost {
+    let matching_engine = Arc::new(MatchingEngine::new());
+    matching_engine.register_market(MARKET.to_string());
+    StrategyHost::new(Exchange::new(
+        matching_engine,
+        Arc::new(AccountService::new()),
+        Arc::new(MarketDataService::new()),
+    ))
+}
+
+fn funds() -> Vec<(String, Quantity)> {
+    vec![("USD".to_string(), dec!(10000)), ("BTC".to_string(), dec!(10))]
+}
+
+/// Wait until `done` holds, for at most two seconds
+async fn eventually(done: impl Fn() -> bool) {
+    for _ in 0..200 {
+        if done() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("condition not reached in time");
+}
+
+#[tokio::test]
+async fn test_fills_reach_both_sides_of_a_trade() {
+    let mut host = host();
+    let (maker, maker_seen) = OneShot::new(Side::Sell, TimeInForce::GTC);
+    let (taker, taker_seen) = OneShot::new(Side::Buy, TimeInForce::IOC);
+    host.register(Box::new(maker), funds());
+    host.register(Box::new(taker), funds());
+    assert_eq!(host.len(), 2);
+    host.start().await.unwrap();
+
+    eventually(|| maker_seen.lock().unwrap().fills.len() == 1 && taker_seen.lock().unwrap().fills.len() == 1).await;
+
+    let maker_seen = maker_seen.lock().unwrap();
+    let taker_seen = taker_seen.lock().unwrap();
+    assert!(maker_seen.errors.is_empty() && taker_seen.errors.is_empty());
+    assert_eq!((maker_seen.trades.len(), taker_seen.trades.len()), (1, 1));
+
+    // Each strategy trades from an account of its own
+    let (maker_fill, taker_fill) = (&maker_seen.fills[0], &taker_seen.fills[0]);
+    assert_eq!(maker_fill.trade.id, taker_fill.trade.id);
+    assert_ne!(maker_fill.trade.buyer_id, maker_fill.trade.seller_id);
+    assert_eq!((maker_fill.side, maker_fill.is_maker), (Side::Sell, true));
+    assert_eq!((taker_fill.side, taker_fill.is_maker), (Side::Buy, false));
+    assert_eq!((taker_fill.price, taker_fill.quantity), (dec!(100), dec!(2)));
+    assert_eq!(maker_fill.order_id, maker_fill.trade.seller_order_id);
+}
+
+#[tokio::test]
+async fn test_orders_for_other_accounts_are_refused() {
+    let mut host = host();
+    let (mut strategy, seen) = OneShot::new(Side::Buy, TimeInForce::GTC);
+    strategy.account = Some(Uuid::new_v4());
+    host.register(Box::new(strategy), funds());
+    host.start().await.unwrap();
+
+    eventually(|| !seen.lock().unwrap().errors.is_empty()).await;
+    assert!(seen.lock().unwrap().errors[0].contains("Strategy orders must be placed for account"));
+}